@@ -0,0 +1,452 @@
+//! Micro-benchmarks of hot-path building blocks, plus end-to-end request
+//! latency on a local in-process cluster, for catching performance
+//! regressions across commits.
+
+use std::fs;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use summerset::{
+    ApiReply, ApiRequest, Bitmap, Command, GenericEndpoint, LogAction,
+    LogResult, RSCodeword, RequestId, SmrProtocol, StorageHub, SummersetError,
+};
+
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+use reed_solomon_erasure::galois_8::ReedSolomon;
+
+use tokio::runtime::{Builder, Runtime};
+use tokio::sync::watch;
+use tokio::time;
+
+use criterion::{
+    black_box, criterion_group, criterion_main, BenchmarkId, Criterion,
+};
+
+use lazy_static::lazy_static;
+
+static VALUE_SIZES: [usize; 3] = [64, 1024, 16 * 1024];
+static BITMAP_SIZES: [u16; 2] = [5, 64];
+static RS_SCHEME: (u16, u16) = (3, 2);
+
+static WAL_PATH: &str = "/tmp/summerset.bench.wal";
+static WAL_SIZE_CAP: usize = 64 * 1024 * 1024;
+
+static CLUSTER_PROTOCOL: SmrProtocol = SmrProtocol::MultiPaxos;
+static CLUSTER_POPULATION: u16 = 3;
+static MANAGER_SRV_PORT: u16 = 53600;
+static MANAGER_CLI_PORT: u16 = 53601;
+static SERVER_API_PORT_BASE: u16 = 53700;
+static SERVER_P2P_PORT_BASE: u16 = 53800;
+
+lazy_static!(
+    /// A long pre-generated value string to get values from.
+    static ref MOM_VALUE: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(16 * 1024)
+        .map(char::from)
+        .collect();
+
+    /// Reed-Solomon coder.
+    static ref RS_CODER: ReedSolomon =
+        ReedSolomon::new(RS_SCHEME.0 as usize, RS_SCHEME.1 as usize).unwrap();
+);
+
+fn put_cmd(size: usize) -> Command {
+    Command::Put {
+        key: "k42".into(),
+        value: MOM_VALUE[..size].to_string(),
+    }
+}
+
+fn serialization_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialization");
+    for size in VALUE_SIZES {
+        let req = ApiRequest::Req {
+            id: 7,
+            cmd: put_cmd(size),
+            checksum: None,
+        };
+        group.bench_with_input(
+            BenchmarkId::new("encode", size),
+            &req,
+            |b, req| b.iter(|| bincode::serialize(black_box(req)).unwrap()),
+        );
+
+        let bytes = bincode::serialize(&req).unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("decode", size),
+            &bytes,
+            |b, bytes| {
+                b.iter(|| {
+                    bincode::deserialize::<ApiRequest>(black_box(bytes))
+                        .unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bitmap_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bitmap");
+    for size in BITMAP_SIZES {
+        group.bench_with_input(
+            BenchmarkId::new("set_count", size),
+            &size,
+            |b, &size| {
+                b.iter(|| {
+                    let mut map = Bitmap::new(size, false);
+                    for idx in (0..size).step_by(2) {
+                        map.set(idx, true).unwrap();
+                    }
+                    black_box(map.count())
+                })
+            },
+        );
+
+        let odds =
+            Bitmap::from((size, (1..size).step_by(2).collect::<Vec<_>>()));
+        group.bench_with_input(
+            BenchmarkId::new("union_iter", size),
+            &odds,
+            |b, odds| {
+                b.iter(|| {
+                    let mut map = Bitmap::new(odds.size(), false);
+                    map.union(odds).unwrap();
+                    black_box(map.iter().filter(|(_, flag)| *flag).count())
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn rscoding_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rscoding");
+    for size in VALUE_SIZES {
+        let value = MOM_VALUE[..size].to_string();
+        group.bench_with_input(
+            BenchmarkId::new("encode", size),
+            &value,
+            |b, value| {
+                b.iter(|| {
+                    let mut cw = RSCodeword::<String>::from_data(
+                        value.clone(),
+                        RS_SCHEME.0,
+                        RS_SCHEME.1,
+                    )
+                    .unwrap();
+                    cw.compute_parity(Some(&RS_CODER)).unwrap();
+                    black_box(cw)
+                })
+            },
+        );
+
+        // drop the first `num_parity_shards` shards so that decoding is needed
+        let mut full =
+            RSCodeword::<String>::from_data(value, RS_SCHEME.0, RS_SCHEME.1)
+                .unwrap();
+        full.compute_parity(Some(&RS_CODER)).unwrap();
+        let num_shards = RS_SCHEME.0 + RS_SCHEME.1;
+        let subset = Bitmap::from((num_shards, RS_SCHEME.1..num_shards));
+        let partial = full.subset_copy(&subset, false).unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("reconstruct", size),
+            &partial,
+            |b, partial| {
+                b.iter(|| {
+                    let mut cw = partial.clone();
+                    cw.reconstruct_data(Some(&RS_CODER)).unwrap();
+                    black_box(cw.get_data().unwrap().len())
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn wal_append_bench(c: &mut Criterion) {
+    let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+    let _ = fs::remove_file(WAL_PATH);
+    let mut storage_hub = runtime
+        .block_on(StorageHub::<Command>::new_and_setup(0, Path::new(WAL_PATH)))
+        .unwrap();
+    let mut next_action = 0;
+
+    let mut group = c.benchmark_group("wal_append");
+    for sync in [false, true] {
+        let cmd = put_cmd(1024);
+        group.bench_with_input(
+            BenchmarkId::new(if sync { "sync" } else { "nosync" }, 1024),
+            &cmd,
+            |b, cmd| {
+                b.iter_custom(|iters| {
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iters {
+                        let start = Instant::now();
+                        let (_, result) = runtime
+                            .block_on(storage_hub.do_sync_action(
+                                next_action,
+                                LogAction::Append {
+                                    entry: cmd.clone(),
+                                    sync,
+                                },
+                            ))
+                            .unwrap();
+                        elapsed += start.elapsed();
+                        next_action += 1;
+
+                        // keep the backing file from growing unboundedly;
+                        // truncation time is not counted
+                        if let LogResult::Append { now_size } = result {
+                            if now_size > WAL_SIZE_CAP {
+                                runtime
+                                    .block_on(storage_hub.do_sync_action(
+                                        next_action,
+                                        LogAction::Truncate { offset: 0 },
+                                    ))
+                                    .unwrap();
+                                next_action += 1;
+                            }
+                        }
+                    }
+                    elapsed
+                })
+            },
+        );
+    }
+    group.finish();
+
+    drop(storage_hub);
+    let _ = fs::remove_file(WAL_PATH);
+}
+
+/// A local cluster of the manager and replicas, each running on a separate
+/// thread with its own tokio runtime, as if launched as separate processes.
+struct LocalCluster {
+    tx_term: watch::Sender<bool>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl LocalCluster {
+    fn backer_path(id: u16) -> String {
+        format!("/tmp/summerset.bench.{}.wal", id)
+    }
+
+    fn snapshot_path(id: u16) -> String {
+        format!("/tmp/summerset.bench.{}.snap", id)
+    }
+
+    fn remove_files() {
+        for id in 0..CLUSTER_POPULATION {
+            let _ = fs::remove_file(Self::backer_path(id));
+            let _ = fs::remove_file(Self::snapshot_path(id));
+        }
+    }
+
+    fn node_runtime() -> Result<Runtime, SummersetError> {
+        Ok(Builder::new_multi_thread()
+            .enable_all()
+            .worker_threads(2)
+            .build()?)
+    }
+
+    fn launch() -> Self {
+        Self::remove_files();
+        let (tx_term, rx_term) = watch::channel(false);
+        let srv_addr: SocketAddr =
+            format!("127.0.0.1:{}", MANAGER_SRV_PORT).parse().unwrap();
+        let cli_addr: SocketAddr =
+            format!("127.0.0.1:{}", MANAGER_CLI_PORT).parse().unwrap();
+        let mut handles = vec![];
+
+        let rx_term_manager = rx_term.clone();
+        handles.push(thread::spawn(move || {
+            let result = Self::node_runtime().and_then(|runtime| {
+                runtime.block_on(async move {
+                    let mut manager = CLUSTER_PROTOCOL
+                        .new_cluster_manager_setup(
+                            srv_addr,
+                            cli_addr,
+                            None,
+                            None,
+                            None,
+                            None,
+                            None,
+                            CLUSTER_POPULATION,
+                            Duration::ZERO,
+                        )
+                        .await?;
+                    manager.run(rx_term_manager).await
+                })
+            });
+            if let Err(e) = result {
+                eprintln!("manager exited: {}", e);
+            }
+        }));
+        thread::sleep(Duration::from_millis(500));
+
+        for id in 0..CLUSTER_POPULATION {
+            let rx_term_replica = rx_term.clone();
+            let api_addr: SocketAddr =
+                format!("127.0.0.1:{}", SERVER_API_PORT_BASE + id)
+                    .parse()
+                    .unwrap();
+            let p2p_addr: SocketAddr =
+                format!("127.0.0.1:{}", SERVER_P2P_PORT_BASE + id)
+                    .parse()
+                    .unwrap();
+            let config = format!(
+                "backer_path = '{}'\nsnapshot_path = '{}'",
+                Self::backer_path(id),
+                Self::snapshot_path(id)
+            );
+            handles.push(thread::spawn(move || {
+                let result = Self::node_runtime().and_then(|runtime| {
+                    runtime.block_on(async move {
+                        let mut replica = CLUSTER_PROTOCOL
+                            .new_server_replica_setup(
+                                api_addr,
+                                p2p_addr,
+                                srv_addr,
+                                Some(&config),
+                            )
+                            .await?;
+                        replica.run(rx_term_replica).await?;
+                        Ok::<(), SummersetError>(())
+                    })
+                });
+                if let Err(e) = result {
+                    eprintln!("replica {} exited: {}", id, e);
+                }
+            }));
+        }
+
+        LocalCluster { tx_term, handles }
+    }
+
+    /// Creates a client endpoint and waits until the cluster is able to
+    /// commit its requests.
+    async fn connect_client() -> Result<Box<dyn GenericEndpoint>, SummersetError>
+    {
+        let cli_addr: SocketAddr =
+            format!("127.0.0.1:{}", MANAGER_CLI_PORT).parse()?;
+        for _ in 0..30 {
+            time::sleep(Duration::from_millis(500)).await;
+            let Ok(mut endpoint) =
+                CLUSTER_PROTOCOL.new_client_endpoint(cli_addr, None).await
+            else {
+                continue;
+            };
+            if endpoint.connect().await.is_err() {
+                continue;
+            }
+            for id in 0..10 {
+                if let Ok(Ok(())) = time::timeout(
+                    Duration::from_secs(1),
+                    issue_put(endpoint.as_mut(), id, 64),
+                )
+                .await
+                {
+                    return Ok(endpoint);
+                }
+            }
+            endpoint.leave(true).await?;
+        }
+        Err(SummersetError::msg("local cluster failed to become ready"))
+    }
+
+    fn shutdown(self) {
+        let _ = self.tx_term.send(true);
+        for handle in self.handles {
+            let _ = handle.join();
+        }
+        Self::remove_files();
+    }
+}
+
+/// Issues a Put request and waits until it is committed, resending it if
+/// the service replies with a failure or a redirection.
+async fn issue_put(
+    endpoint: &mut dyn GenericEndpoint,
+    id: RequestId,
+    size: usize,
+) -> Result<(), SummersetError> {
+    let req = ApiRequest::Req {
+        id,
+        cmd: put_cmd(size),
+        checksum: None,
+    };
+    loop {
+        let mut sent = endpoint.send_req(Some(&req))?;
+        while !sent {
+            sent = endpoint.send_req(None)?;
+        }
+
+        loop {
+            match endpoint.recv_reply().await? {
+                ApiReply::Reply {
+                    id: reply_id,
+                    result,
+                    ..
+                } if reply_id == id => {
+                    if result.is_some() {
+                        return Ok(());
+                    }
+                    break;
+                }
+                ApiReply::LeaderChange { .. } => break,
+                _ => {} // ignore stale replies
+            }
+        }
+    }
+}
+
+fn e2e_latency_bench(c: &mut Criterion) {
+    let cluster = LocalCluster::launch();
+    let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+    let mut endpoint =
+        runtime.block_on(LocalCluster::connect_client()).unwrap();
+    let mut next_req: RequestId = 1000;
+
+    let mut group = c.benchmark_group("e2e_latency");
+    group
+        .sample_size(50)
+        .measurement_time(Duration::from_secs(10));
+    for size in VALUE_SIZES {
+        group.bench_with_input(
+            BenchmarkId::new(CLUSTER_PROTOCOL.to_string(), size),
+            &size,
+            |b, &size| {
+                b.iter(|| {
+                    runtime
+                        .block_on(issue_put(endpoint.as_mut(), next_req, size))
+                        .unwrap();
+                    next_req += 1;
+                })
+            },
+        );
+    }
+    group.finish();
+
+    let _ = runtime.block_on(endpoint.leave(true));
+    cluster.shutdown();
+}
+
+criterion_group! {
+    name = micro;
+    config = Criterion::default().without_plots();
+    targets = serialization_bench, bitmap_bench, rscoding_bench,
+              wal_append_bench,
+}
+criterion_group! {
+    name = e2e;
+    config = Criterion::default().without_plots();
+    targets = e2e_latency_bench,
+}
+
+criterion_main!(micro, e2e);
@@ -22,8 +22,8 @@ use memory_stats::{memory_stats, MemoryStats};
 
 use lazy_static::lazy_static;
 
-// static SCHEMES: [(u8, u8); 4] = [(3, 2), (6, 4), (9, 6), (12, 8)];
-static SCHEMES: [(u8, u8); 1] = [(3, 2)];
+// static SCHEMES: [(u16, u16); 4] = [(3, 2), (6, 4), (9, 6), (12, 8)];
+static SCHEMES: [(u16, u16); 1] = [(3, 2)];
 static SIZES: [usize; 6] = [
     4096,
     16 * 1024,
@@ -33,7 +33,7 @@ static SIZES: [usize; 6] = [
     4096 * 1024,
 ];
 
-struct BenchId(usize, (u8, u8));
+struct BenchId(usize, (u16, u16));
 
 impl fmt::Display for BenchId {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -50,7 +50,7 @@ lazy_static!(
         .collect();
 
     /// Reed-Solomon coder.
-    static ref RS_CODER: HashMap<(u8, u8), ReedSolomon> = SCHEMES
+    static ref RS_CODER: HashMap<(u16, u16), ReedSolomon> = SCHEMES
         .iter()
         .map(|&s| (s, ReedSolomon::new(s.0 as usize, s.1 as usize).unwrap()))
         .collect();
@@ -173,7 +173,7 @@ impl Measurement for MemUsage {
 
 fn compute_codeword(
     size: usize,
-    scheme: (u8, u8),
+    scheme: (u16, u16),
 ) -> Result<(), SummersetError> {
     let value = MOM_VALUE[..size].to_string();
     let mut cw = RSCodeword::<String>::from_data(value, scheme.0, scheme.1)?;
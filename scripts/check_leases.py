@@ -0,0 +1,66 @@
+import sys
+import json
+import argparse
+
+
+def load_lease_intervals(event_log):
+    intervals = []
+    with open(event_log, "r") as f:
+        for line in f:
+            line = line.strip()
+            if len(line) == 0:
+                continue
+            record = json.loads(line)
+            event = record["event"]
+            if isinstance(event, dict) and "LeaseHeld" in event:
+                held = event["LeaseHeld"]
+                intervals.append(
+                    (
+                        held["start_ms"],
+                        held["end_ms"],
+                        held["server"],
+                        held["lease_num"],
+                    )
+                )
+    intervals.sort()
+    return intervals
+
+
+def find_overlaps(intervals):
+    overlaps = []
+    active = []  # intervals that may still overlap with later ones
+    for start, end, server, lease_num in intervals:
+        active = [iv for iv in active if iv[1] > start]
+        for a_start, a_end, a_server, a_lease_num in active:
+            if a_server != server:
+                overlaps.append(
+                    (
+                        (a_server, a_lease_num),
+                        (server, lease_num),
+                        start,
+                        min(end, a_end),
+                    )
+                )
+        active.append((start, end, server, lease_num))
+    return overlaps
+
+
+if __name__ == "__main__":
+    parser = argparse.ArgumentParser(allow_abbrev=False)
+    parser.add_argument(
+        "event_log", type=str, help="manager event log file (JSON lines)"
+    )
+    args = parser.parse_args()
+
+    intervals = load_lease_intervals(args.event_log)
+    overlaps = find_overlaps(intervals)
+    print(f"Checked {len(intervals)} lease intervals")
+    for (s0, n0), (s1, n1), start, end in overlaps:
+        print(
+            f"  OVERLAP: server {s0} @ {n0} and server {s1} @ {n1} "
+            f"during {start}-{end}"
+        )
+    if len(overlaps) > 0:
+        print(f"Found {len(overlaps)} overlaps!")
+        sys.exit(1)
+    print("No overlaps found")
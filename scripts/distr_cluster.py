@@ -39,6 +39,7 @@ PROTOCOL_FEATURES = {
     "RepNothing": ProtoFeats(False, False, None),
     "SimplePush": ProtoFeats(False, False, None),
     "ChainRep": ProtoFeats(False, False, None),
+    "CRAQ": ProtoFeats(False, False, None),
     "MultiPaxos": ProtoFeats(True, True, None),
     "EPaxos": ProtoFeats(True, False, lambda n, _: f"optimized_quorum=true"),
     "RSPaxos": ProtoFeats(True, True, lambda n, _: f"fault_tolerance={(n//2)//2}"),
@@ -47,6 +48,12 @@ PROTOCOL_FEATURES = {
     "Crossword": ProtoFeats(True, True, lambda n, _: f"fault_tolerance={n//2}"),
     "QuorumLeases": ProtoFeats(True, True, lambda n, _: f"sim_read_lease=false"),
     "Bodega": ProtoFeats(True, True, lambda n, _: f"sim_read_lease=false"),
+    "Mencius": ProtoFeats(True, False, None),
+    "FastPaxos": ProtoFeats(False, False, None),
+    "CURP": ProtoFeats(False, False, None),
+    "VR": ProtoFeats(False, False, None),
+    "Zab": ProtoFeats(False, False, None),
+    "Hermes": ProtoFeats(False, False, None),
 }
 
 
@@ -113,7 +120,7 @@ def config_with_defaults(
         if fresh_files:
             utils.proc.run_process_over_ssh(
                 remote,
-                ["sudo", "rm", "-f", snapshot_path],
+                ["sudo", "rm", "-f", snapshot_path, f"{snapshot_path}.seed"],
                 print_cmd=False,
             ).wait()
 
@@ -133,7 +140,9 @@ def config_with_defaults(
     return config_dict_to_str(config_dict)
 
 
-def compose_manager_cmd(protocol, srv_port, cli_port, num_replicas, release):
+def compose_manager_cmd(
+    protocol, srv_port, cli_port, num_replicas, release, seed_image=None
+):
     cmd = [f"./target/{'release' if release else 'debug'}/summerset_manager"]
     cmd += [
         "-p",
@@ -147,16 +156,19 @@ def compose_manager_cmd(protocol, srv_port, cli_port, num_replicas, release):
         "-n",
         str(num_replicas),
     ]
+    if seed_image is not None:
+        cmd += ["--seed-image", seed_image]
     return cmd
 
 
-def launch_manager(protocol, partition, num_replicas, release):
+def launch_manager(protocol, partition, num_replicas, release, seed_image):
     cmd = compose_manager_cmd(
         protocol,
         MANAGER_SRV_PORT(partition),
         MANAGER_CLI_PORT(partition),
         num_replicas,
         release,
+        seed_image,
     )
     return run_process_pinned(cmd, capture_stderr=True)
 
@@ -328,6 +340,9 @@ if __name__ == "__main__":
     parser.add_argument(
         "--skip_build", action="store_true", help="if set, skip cargo build"
     )
+    parser.add_argument(
+        "--seed_image", type=str, help="if given, seed servers with this image"
+    )
     args = parser.parse_args()
 
     # parse hosts config file
@@ -399,7 +414,7 @@ if __name__ == "__main__":
 
     # launch cluster manager oracle first
     manager_proc = launch_manager(
-        args.protocol, partition, args.num_replicas, args.release
+        args.protocol, partition, args.num_replicas, args.release, args.seed_image
     )
     wait_manager_setup(manager_proc)
 
@@ -32,6 +32,7 @@ UTILITY_PARAM_NAMES = {
         "norm_stdev_ratio",
         "unif_interval_ms",
         "unif_upper_bound",
+        "watch_resets",
     ],
     "tester": [
         "test_name",
@@ -242,6 +243,11 @@ if __name__ == "__main__":
     parser_bench.add_argument(
         "--unif_upper_bound", type=int, help="uniform dist upper bound"
     )
+    parser_bench.add_argument(
+        "--watch_resets",
+        action="store_true",
+        help="if set, pause through cluster resets instead of erroring out",
+    )
     parser_bench.add_argument(
         "--output_prefix",
         type=str,
@@ -1,6 +1,7 @@
 import sys
 import os
 import signal
+import shutil
 import argparse
 
 sys.path.append(os.path.dirname(os.path.realpath(__file__)))
@@ -24,6 +25,9 @@ PROTOCOL_BACKER_PATH = (
 PROTOCOL_SNAPSHOT_PATH = (
     lambda protocol, prefix, midfix, r: f"{prefix}/{protocol}{midfix}.{r}.snap"
 )
+PROTOCOL_BLOB_DIR = (
+    lambda protocol, prefix, midfix, r: f"{prefix}/{protocol}{midfix}.{r}.blobs"
+)
 
 
 class ProtoFeats:
@@ -37,6 +41,7 @@ PROTOCOL_FEATURES = {
     "RepNothing": ProtoFeats(False, False, None),
     "SimplePush": ProtoFeats(False, False, None),
     "ChainRep": ProtoFeats(False, False, None),
+    "CRAQ": ProtoFeats(False, False, None),
     "MultiPaxos": ProtoFeats(True, True, None),
     "EPaxos": ProtoFeats(True, False, lambda n, _: f"optimized_quorum=true"),
     "RSPaxos": ProtoFeats(True, True, lambda n, _: f"fault_tolerance={(n//2)//2}"),
@@ -45,6 +50,12 @@ PROTOCOL_FEATURES = {
     "Crossword": ProtoFeats(True, True, lambda n, _: f"fault_tolerance={n//2}"),
     "QuorumLeases": ProtoFeats(True, True, lambda n, _: f"sim_read_lease=false"),
     "Bodega": ProtoFeats(True, True, lambda n, _: f"sim_read_lease=false"),
+    "Mencius": ProtoFeats(True, False, None),
+    "FastPaxos": ProtoFeats(False, False, None),
+    "CURP": ProtoFeats(False, False, None),
+    "VR": ProtoFeats(False, False, None),
+    "Zab": ProtoFeats(False, False, None),
+    "Hermes": ProtoFeats(False, False, None),
 }
 
 
@@ -100,6 +111,19 @@ def config_with_defaults(
         if fresh_files and os.path.isfile(snapshot_path):
             print(f"Delete: {snapshot_path}")
             os.remove(snapshot_path)
+        seed_path = f"{snapshot_path}.seed"
+        if fresh_files and os.path.isfile(seed_path):
+            print(f"Delete: {seed_path}")
+            os.remove(seed_path)
+
+    if protocol == "MultiPaxos":
+        blob_dir = PROTOCOL_BLOB_DIR(
+            protocol, states_prefix, states_midfix, replica_id
+        )
+        config_dict["blob_dir"] = f"'{blob_dir}'"
+        if fresh_files and os.path.isdir(blob_dir):
+            print(f"Delete: {blob_dir}")
+            shutil.rmtree(blob_dir)
 
     if PROTOCOL_FEATURES[protocol].extra_defaults is not None:
         config_dict.update(
@@ -117,7 +141,9 @@ def config_with_defaults(
     return config_dict_to_str(config_dict)
 
 
-def compose_manager_cmd(protocol, bind_ip, srv_port, cli_port, num_replicas, release):
+def compose_manager_cmd(
+    protocol, bind_ip, srv_port, cli_port, num_replicas, release, seed_image=None
+):
     cmd = [f"./target/{'release' if release else 'debug'}/summerset_manager"]
     cmd += [
         "-p",
@@ -131,10 +157,12 @@ def compose_manager_cmd(protocol, bind_ip, srv_port, cli_port, num_replicas, rel
         "-n",
         str(num_replicas),
     ]
+    if seed_image is not None:
+        cmd += ["--seed-image", seed_image]
     return cmd
 
 
-def launch_manager(protocol, num_replicas, release, use_veth):
+def launch_manager(protocol, num_replicas, release, use_veth, seed_image):
     bind_ip = MANAGER_LOOP_IP
     if use_veth:
         bind_ip = MANAGER_VETH_IP
@@ -145,6 +173,7 @@ def launch_manager(protocol, num_replicas, release, use_veth):
         MANAGER_CLI_PORT,
         num_replicas,
         release,
+        seed_image,
     )
     return run_process_pinned(-1, cmd, capture_stderr=True)
 
@@ -283,6 +312,9 @@ if __name__ == "__main__":
     parser.add_argument(
         "--skip_build", action="store_true", help="if set, skip cargo build"
     )
+    parser.add_argument(
+        "--seed_image", type=str, help="if given, seed servers with this image"
+    )
     args = parser.parse_args()
 
     # kill all existing server and manager processes
@@ -309,7 +341,7 @@ if __name__ == "__main__":
 
     # launch cluster manager oracle first
     manager_proc = launch_manager(
-        args.protocol, args.num_replicas, args.release, args.use_veth
+        args.protocol, args.num_replicas, args.release, args.use_veth, args.seed_image
     )
     wait_manager_setup(manager_proc)
 
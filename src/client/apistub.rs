@@ -5,7 +5,8 @@ use std::net::SocketAddr;
 use crate::client::ClientId;
 use crate::server::{ApiReply, ApiRequest};
 use crate::utils::{
-    safe_tcp_read, safe_tcp_write, tcp_connect_with_retry, SummersetError,
+    exchange_codec, safe_tcp_read, safe_tcp_write, tcp_connect_with_retry,
+    SummersetError,
 };
 
 use bytes::BytesMut;
@@ -14,7 +15,7 @@ use tokio::io::AsyncWriteExt;
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 
 /// Client API connection stub.
-pub(crate) struct ClientApiStub {
+pub struct ClientApiStub {
     /// My client ID.
     _id: ClientId,
 
@@ -36,12 +37,13 @@ pub(crate) struct ClientApiStub {
 
 impl ClientApiStub {
     /// Creates a new API connection stub by connecting to the given server.
-    pub(crate) async fn new_by_connect(
+    pub async fn new_by_connect(
         id: ClientId,
         server: SocketAddr,
     ) -> Result<Self, SummersetError> {
         let mut stream = tcp_connect_with_retry(server, 15).await?;
         stream.write_u64(id).await?; // send my client ID
+        exchange_codec(&mut stream).await?;
         let (read_half, write_half) = stream.into_split();
 
         Ok(ClientApiStub {
@@ -62,7 +64,7 @@ impl ClientApiStub {
     ///                 (typically after doing a few `recv_reply()`s to free
     ///                 up some buffer space)
     ///   - `Err(err)` if any unexpected error occurs
-    pub(crate) fn send_req(
+    pub fn send_req(
         &mut self,
         req: Option<&ApiRequest>,
     ) -> Result<bool, SummersetError> {
@@ -84,9 +86,7 @@ impl ClientApiStub {
     }
 
     /// Receives a reply from established server connection.
-    pub(crate) async fn recv_reply(
-        &mut self,
-    ) -> Result<ApiReply, SummersetError> {
+    pub async fn recv_reply(&mut self) -> Result<ApiReply, SummersetError> {
         let reply =
             safe_tcp_read(&mut self.reply_buf, &mut self.conn_read).await?;
 
@@ -1,23 +1,65 @@
 //! Summerset client -> manager oracle control API stub implementation.
 
+use std::collections::HashMap;
+use std::fs;
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
 
 use crate::client::ClientId;
-use crate::manager::{CtrlReply, CtrlRequest};
+use crate::manager::{CtrlEventRecord, CtrlReply, CtrlRequest};
+use crate::server::ReplicaId;
 use crate::utils::{
-    safe_tcp_read, safe_tcp_write, tcp_connect_with_retry, SummersetError, ME,
+    decode, exchange_codec, safe_tcp_read, safe_tcp_write, set_me,
+    tcp_connect_with_retry, SummersetError,
 };
 
 use bytes::BytesMut;
 
-use tokio::io::AsyncReadExt;
+use serde::{Deserialize, Serialize};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 
+/// Special client ID sent to a server's API port to ask for the manager's
+/// address and the server map instead of joining as a client.
+pub(crate) const DISCOVERY_CLIENT_ID: ClientId = ClientId::MAX;
+
+/// Reply of a server to a discovery probe.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct DiscoveryReply {
+    /// The manager's client-facing address, if known to the server.
+    pub manager: Option<SocketAddr>,
+
+    /// Client-facing API addresses of all servers, as last told to the
+    /// server by the manager.
+    pub servers: HashMap<ReplicaId, SocketAddr>,
+}
+
+/// Path of the file persisting this process's client identity, if enabled.
+static IDENTITY_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Has the persisted identity been claimed by a control stub already?
+static IDENTITY_CLAIMED: AtomicBool = AtomicBool::new(false);
+
+/// Enables persisting the client identity issued by the manager to `path`,
+/// so that a restarted client reclaims the same client ID and the servers'
+/// per-client state (e.g., dedup tables) carries over. Only the first
+/// control stub created in the process uses it. Should be called once at
+/// startup.
+pub fn set_client_identity_path(path: PathBuf) {
+    let _ = IDENTITY_PATH.set(path);
+}
+
 /// Client -> manager oracle control API stub.
 pub struct ClientCtrlStub {
     /// My client ID.
     pub id: ClientId,
 
+    /// Address of the manager.
+    manager: SocketAddr,
+
     /// Write-half split of the TCP connection stream.
     conn_write: OwnedWriteHalf,
 
@@ -35,18 +77,37 @@ pub struct ClientCtrlStub {
 }
 
 impl ClientCtrlStub {
-    /// Creates a new control API stub and connects to the manager.
+    /// Creates a new control API stub and connects to the manager. Presents
+    /// the reconnection token of a persisted identity if there is one.
     pub(crate) async fn new_by_connect(
         manager: SocketAddr,
     ) -> Result<Self, SummersetError> {
+        let identity = IDENTITY_PATH
+            .get()
+            .filter(|_| !IDENTITY_CLAIMED.swap(true, Ordering::AcqRel));
+        let token = identity
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|s| s.split_whitespace().nth(1)?.parse().ok())
+            .unwrap_or(0);
+
         let mut stream = tcp_connect_with_retry(manager, 15).await?;
+        stream.write_u64(token).await?; // 0 if asking for a new identity
         let id = stream.read_u64().await?; // receive my client ID
+        let token = stream.read_u64().await?; // and its reconnection token
+        exchange_codec(&mut stream).await?;
         let (read_half, write_half) = stream.into_split();
 
-        ME.get_or_init(|| id.to_string());
+        if let Some(path) = identity {
+            if let Err(e) = fs::write(path, format!("{} {}\n", id, token)) {
+                pf_warn!("error persisting identity to {:?}: {}", path, e);
+            }
+        }
+
+        set_me(id.to_string());
 
         Ok(ClientCtrlStub {
             id,
+            manager,
             conn_write: write_half,
             req_buf: BytesMut::with_capacity(8 + 1024),
             req_buf_cursor: 0,
@@ -55,6 +116,46 @@ impl ClientCtrlStub {
         })
     }
 
+    /// Learns the manager's client-facing address and the client-facing API
+    /// addresses of all servers through any server's API address
+    /// `bootstrap`, so that clients only need one known address.
+    pub async fn discover_cluster(
+        bootstrap: SocketAddr,
+    ) -> Result<(SocketAddr, HashMap<ReplicaId, SocketAddr>), SummersetError>
+    {
+        let mut stream = tcp_connect_with_retry(bootstrap, 15).await?;
+        stream.write_u64(DISCOVERY_CLIENT_ID).await?;
+        exchange_codec(&mut stream).await?;
+        let len = stream.read_u32().await? as usize;
+        let mut buf = vec![0; len];
+        stream.read_exact(&mut buf).await?;
+        let reply: DiscoveryReply = decode(&buf)?;
+
+        let Some(mut manager) = reply.manager else {
+            return logged_err!(
+                "server '{}' does not know the manager address",
+                bootstrap
+            );
+        };
+        // servers may know the manager by a local address only
+        if manager.ip().is_loopback() || manager.ip().is_unspecified() {
+            manager.set_ip(bootstrap.ip());
+        }
+        let mut servers = reply.servers;
+        for addr in servers.values_mut() {
+            if addr.ip().is_loopback() || addr.ip().is_unspecified() {
+                addr.set_ip(bootstrap.ip());
+            }
+        }
+        pf_debug!(
+            "discovered manager '{}' servers {:?} via '{}'",
+            manager,
+            servers,
+            bootstrap
+        );
+        Ok((manager, servers))
+    }
+
     /// Sends a request to established manager connection. Returns:
     ///   - `Ok(true)` if successful
     ///   - `Ok(false)` if socket full and may block; in this case, the input
@@ -107,6 +208,87 @@ impl ClientCtrlStub {
         // pf_trace!("recv reply {:?}", reply);
         Ok(reply)
     }
+
+    /// Subscribes to the manager's control-plane event stream, returning
+    /// past events with sequence number >= `since`. Later events are then
+    /// received through `recv_events()`; the stub should not be used for
+    /// other requests afterwards.
+    pub async fn subscribe_events(
+        &mut self,
+        since: u64,
+    ) -> Result<Vec<CtrlEventRecord>, SummersetError> {
+        self.send_req_insist(&CtrlRequest::Subscribe { since })?;
+        match self.recv_reply().await? {
+            CtrlReply::Subscribe { events } => Ok(events),
+            reply => {
+                logged_err!(Protocol; "unexpected subscribe reply: {:?}", reply)
+            }
+        }
+    }
+
+    /// Opens a dedicated connection to the manager that watches for cluster
+    /// resets. Whoever holds the returned watcher must answer each
+    /// `CtrlReply::ResetBegin` notice with `ResetWatcher::quiesced()` after
+    /// leaving the service, and must not itself request a reset through
+    /// another connection while waiting for notices.
+    pub async fn watch_resets(&self) -> Result<ResetWatcher, SummersetError> {
+        let mut stub = Self::new_by_connect(self.manager).await?;
+        stub.send_req_insist(&CtrlRequest::WatchResets)?;
+        match stub.recv_reply().await? {
+            CtrlReply::WatchResets => Ok(ResetWatcher { stub }),
+            reply => {
+                logged_err!(Protocol; "unexpected watch reply: {:?}", reply)
+            }
+        }
+    }
+
+    /// Opens another connection to the manager, getting a new client ID of
+    /// its own, for talking to servers alongside this client's connections.
+    pub async fn open_sibling(&self) -> Result<ClientCtrlStub, SummersetError> {
+        Self::new_by_connect(self.manager).await
+    }
+
+    /// Waits for the next batch of events pushed after subscribing.
+    pub async fn recv_events(
+        &mut self,
+    ) -> Result<Vec<CtrlEventRecord>, SummersetError> {
+        match self.recv_reply().await? {
+            CtrlReply::Events { events } => Ok(events),
+            reply => {
+                logged_err!(
+                    Protocol; "unexpected reply while subscribed: {:?}", reply
+                )
+            }
+        }
+    }
+}
+
+/// Dedicated manager connection receiving cluster reset notices.
+pub struct ResetWatcher {
+    /// Control stub of the dedicated connection.
+    stub: ClientCtrlStub,
+}
+
+impl ResetWatcher {
+    /// Waits for the next reset notice, either a `CtrlReply::ResetBegin` or a
+    /// `CtrlReply::ResetDone`. Cancellation-safe.
+    pub async fn recv_notice(&mut self) -> Result<CtrlReply, SummersetError> {
+        match self.stub.recv_reply().await? {
+            notice @ (CtrlReply::ResetBegin { .. }
+            | CtrlReply::ResetDone { .. }) => Ok(notice),
+            reply => {
+                logged_err!(
+                    Protocol; "unexpected reply while watching: {:?}", reply
+                )
+            }
+        }
+    }
+
+    /// Tells the manager that I have stopped talking to the servers, so that
+    /// the announced reset may go ahead.
+    pub fn quiesced(&mut self) -> Result<(), SummersetError> {
+        self.stub.send_req_insist(&CtrlRequest::ResetQuiesced)
+    }
 }
 
 // Unit tests are done together with `manager::reactor`.
@@ -39,14 +39,40 @@ pub trait GenericEndpoint {
         req: Option<&ApiRequest>,
     ) -> Result<bool, SummersetError>;
 
+    /// Sends multiple requests framed together in one message, so that they
+    /// cost a single write and get taken by the server into the same batch.
+    /// The batch is routed as a whole like a normal consensus command.
+    /// Returns the same as `send_req()`; on `Ok(false)`, the whole batch is
+    /// to be retried through `send_req(None)`.
+    fn send_req_batch(
+        &mut self,
+        reqs: &[ApiRequest],
+    ) -> Result<bool, SummersetError> {
+        self.send_req(Some(&ApiRequest::Batch {
+            reqs: reqs.to_vec(),
+        }))
+    }
+
     /// Receives a reply from the service according to protocol-specific logic.
     async fn recv_reply(&mut self) -> Result<ApiReply, SummersetError>;
 
+    /// Sends a duplicate of an already-sent, still outstanding read-only
+    /// request to a different server, if the protocol can safely serve it
+    /// there. Returns `Ok(true)` if the hedge was sent, or `Ok(false)` if
+    /// hedging is not applicable. Both copies carry the same request ID, so
+    /// the caller simply takes whichever reply comes first.
+    fn send_hedge(
+        &mut self,
+        _req: &ApiRequest,
+    ) -> Result<bool, SummersetError> {
+        Ok(false)
+    }
+
     /// Gets my client ID.
     fn id(&self) -> ClientId;
 
     /// Gets current cluster size. 0 means population unknown.
-    fn population(&self) -> u8;
+    fn population(&self) -> u16;
 
     /// Gets a mutable reference to the control stub for sending control
     /// requests and receiving control replies for testing purposes.
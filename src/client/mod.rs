@@ -4,8 +4,13 @@ mod endpoint;
 
 mod apistub;
 mod ctrlstub;
+mod wsstub;
 
-pub use ctrlstub::ClientCtrlStub;
+pub use apistub::ClientApiStub;
+pub use ctrlstub::{
+    set_client_identity_path, ClientCtrlStub, DiscoveryReply, ResetWatcher,
+};
 pub use endpoint::{ClientId, GenericEndpoint};
+pub use wsstub::ClientWsStub;
 
-pub(crate) use apistub::ClientApiStub;
+pub(crate) use ctrlstub::DISCOVERY_CLIENT_ID;
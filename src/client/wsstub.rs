@@ -0,0 +1,58 @@
+//! Summerset client WebSocket stub implementation, for browser-based clients.
+//!
+//! Only the JSON framing of a server's WebSocket bridge is provided here: the
+//! stub encodes requests and decodes replies, but does no I/O itself, and
+//! the caller moves the text frames over whatever WebSocket it holds. This
+//! is not a wasm32 client endpoint. The crate, this stub included, does not
+//! build for wasm32, so a browser front-end has to carry the framing over
+//! along with the `ApiRequest` / `ApiReply` types, or hand-write the same
+//! JSON in JS.
+
+use crate::server::{ApiReply, ApiRequest, Command, RequestId};
+use crate::utils::SummersetError;
+
+/// Client WebSocket bridge stub.
+#[derive(Debug, Default)]
+pub struct ClientWsStub {
+    /// ID of the next request to send.
+    next_req: RequestId,
+}
+
+impl ClientWsStub {
+    /// Creates a new WebSocket stub.
+    pub fn new() -> Self {
+        ClientWsStub { next_req: 0 }
+    }
+
+    /// Encodes an API request into a text frame payload.
+    pub fn encode_req(req: &ApiRequest) -> Result<String, SummersetError> {
+        Ok(serde_json::to_string(req)?)
+    }
+
+    /// Decodes a text frame payload into an API reply.
+    pub fn decode_reply(text: &str) -> Result<ApiReply, SummersetError> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    /// Wraps a command into a request with the next request ID and encodes
+    /// it into a text frame payload. Returns the request ID along with it.
+    pub fn encode_cmd(
+        &mut self,
+        cmd: Command,
+    ) -> Result<(RequestId, String), SummersetError> {
+        let id = self.next_req;
+        let text = Self::encode_req(&ApiRequest::Req {
+            id,
+            cmd,
+            checksum: None,
+        })?;
+        self.next_req += 1;
+        Ok((id, text))
+    }
+
+    /// Encodes a `Leave` request into a text frame payload; the bridge
+    /// closes the WebSocket once the server has replied to it.
+    pub fn encode_leave() -> Result<String, SummersetError> {
+        Self::encode_req(&ApiRequest::Leave)
+    }
+}
@@ -15,23 +15,47 @@ mod protocols;
 
 #[doc(inline)]
 pub use crate::utils::{
-    logger_init, Bitmap, RSCodeword, Stopwatch, SummersetError, Timer, ME,
+    logger_init, logger_max_level, set_codec, set_log_filters,
+    set_max_msg_bytes, set_rng_seed, Bitmap, Codec, ConfNum, ErrorKind,
+    HlcStamp, HybridClock, RSCodeword, Stopwatch, SummersetError, Timer, ME,
 };
 
+// Used by the exported logging macros only.
+#[doc(hidden)]
+pub use crate::utils::LogPrefix;
+
 #[doc(inline)]
-pub use crate::manager::{ClusterManager, CtrlReply, CtrlRequest, ServerInfo};
+pub use crate::manager::{
+    ChaosAction, ChaosParams, ChaosPlan, ChaosStep, ClusterManager, CtrlEvent,
+    CtrlEventRecord, CtrlReply, CtrlRequest, ServerInfo, ServerStatus,
+};
 
 #[doc(inline)]
 pub use crate::server::{
-    ApiReply, ApiRequest, Command, CommandResult, ConfChange, GenericReplica,
-    ReplicaId, RequestId,
+    read_audit_segment, set_app_state_machine, set_conflict_spec,
+    set_debug_api, set_mirror_target, set_msg_trace_path, set_panic_dump_path,
+    set_peer_tls_dir, ApiReply, ApiRequest, AppStateMachine, AuditEntry,
+    Command, CommandResult, CommitCert, ConfChange, DebugState, Footprint,
+    GenericReplica, IdemKey, KeysReport, MsgTrace, ReadConsistency, ReplicaId,
+    ReplyError, RequestId, ResourceUsage, WsBridge,
 };
 
+#[cfg(feature = "byzantine-hooks")]
 #[doc(inline)]
-pub use crate::client::{ClientCtrlStub, ClientId, GenericEndpoint};
+pub use crate::server::set_byzantine_hooks;
+
+// durable logging module exposed only for the benchmarking suite
+#[doc(hidden)]
+pub use crate::server::{LogAction, LogActionId, LogResult, StorageHub};
 
 #[doc(inline)]
-pub use crate::protocols::SmrProtocol;
+pub use crate::client::{
+    set_client_identity_path, ClientApiStub, ClientCtrlStub, ClientId,
+    ClientWsStub, DiscoveryReply, GenericEndpoint, ResetWatcher,
+};
+
+#[doc(inline)]
+pub use crate::protocols::{FsckReport, SmrProtocol};
 
 // below are config structs exposed for users to know how to write TOML-format
 // config strings
@@ -42,6 +66,8 @@ pub use crate::protocols::{ClientConfigSimplePush, ReplicaConfigSimplePush};
 #[rustfmt::skip]
 pub use crate::protocols::{ClientConfigChainRep, ReplicaConfigChainRep};
 #[rustfmt::skip]
+pub use crate::protocols::{ClientConfigCRAQ, ReplicaConfigCRAQ};
+#[rustfmt::skip]
 pub use crate::protocols::{ClientConfigMultiPaxos, ReplicaConfigMultiPaxos};
 #[rustfmt::skip]
 pub use crate::protocols::{ClientConfigEPaxos, ReplicaConfigEPaxos};
@@ -57,3 +83,15 @@ pub use crate::protocols::{ClientConfigCrossword, ReplicaConfigCrossword};
 pub use crate::protocols::{ClientConfigQuorumLeases, ReplicaConfigQuorumLeases};
 #[rustfmt::skip]
 pub use crate::protocols::{ClientConfigBodega, ReplicaConfigBodega};
+#[rustfmt::skip]
+pub use crate::protocols::{ClientConfigMencius, ReplicaConfigMencius};
+#[rustfmt::skip]
+pub use crate::protocols::{ClientConfigFastPaxos, ReplicaConfigFastPaxos};
+#[rustfmt::skip]
+pub use crate::protocols::{ClientConfigCURP, ReplicaConfigCURP};
+#[rustfmt::skip]
+pub use crate::protocols::{ClientConfigVR, ReplicaConfigVR};
+#[rustfmt::skip]
+pub use crate::protocols::{ClientConfigZab, ReplicaConfigZab};
+#[rustfmt::skip]
+pub use crate::protocols::{ClientConfigHermes, ReplicaConfigHermes};
@@ -0,0 +1,303 @@
+//! Cluster manager chaos mode: fault schedules drawn randomly from a seed,
+//! recorded step by step so that a failing run can be replayed exactly.
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::server::ReplicaId;
+use crate::utils::SummersetError;
+
+use rand::rngs::StdRng;
+use rand::{seq, Rng, SeedableRng};
+
+use serde::{Deserialize, Serialize};
+
+use tokio::time::{Duration, Instant};
+
+/// Chaos mode parameters. Each fault kind arrives as a Poisson process with
+/// the given mean gap between arrivals; a gap of 0 disables the kind.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct ChaosParams {
+    /// Seed of the random fault schedule.
+    pub seed: u64,
+
+    /// Length of the chaos period in millisecs.
+    pub duration_ms: u64,
+
+    /// Mean gap between server crash-restarts in millisecs.
+    pub crash_gap_ms: u64,
+
+    /// Mean gap between network partitions in millisecs.
+    pub partition_gap_ms: u64,
+
+    /// Mean gap between network delays in millisecs.
+    pub delay_gap_ms: u64,
+
+    /// Mean gap between leadership transfers in millisecs.
+    pub transfer_gap_ms: u64,
+
+    /// How long a partition or delay lasts before healing, in millisecs.
+    pub net_fault_ms: u64,
+
+    /// Extra delay of messages received by a delayed server in millisecs.
+    pub delay_ms: u64,
+}
+
+impl Default for ChaosParams {
+    fn default() -> Self {
+        ChaosParams {
+            seed: 0,
+            duration_ms: 60000,
+            crash_gap_ms: 20000,
+            partition_gap_ms: 15000,
+            delay_gap_ms: 15000,
+            transfer_gap_ms: 0,
+            net_fault_ms: 3000,
+            delay_ms: 100,
+        }
+    }
+}
+
+/// A fault injected by chaos mode.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum ChaosAction {
+    /// Crash-restart a server, keeping its durable state.
+    Crash { server: ReplicaId },
+
+    /// Cut the servers in `side` off from the rest of the cluster.
+    Partition { side: HashSet<ReplicaId> },
+
+    /// Delay all messages a server receives from its peers.
+    Delay { server: ReplicaId, delay_ms: u64 },
+
+    /// Heal all partitions and delays.
+    Heal,
+
+    /// Hand leadership over to a server. (only used by relevant protocols)
+    TransferLeader { target: ReplicaId },
+}
+
+/// A step of a chaos fault schedule.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct ChaosStep {
+    /// Time since chaos mode started in millisecs.
+    pub at_ms: u64,
+
+    /// The fault injected.
+    pub action: ChaosAction,
+}
+
+/// How a chaos run gets its fault schedule.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum ChaosPlan {
+    /// Draw a random schedule from the parameters.
+    Random(ChaosParams),
+
+    /// Replay a recorded schedule.
+    Replay(Vec<ChaosStep>),
+}
+
+impl ChaosPlan {
+    /// Turns the plan into a concrete fault schedule for a cluster of the
+    /// given population.
+    pub(crate) fn schedule(
+        self,
+        population: u16,
+    ) -> Result<Vec<ChaosStep>, SummersetError> {
+        match self {
+            ChaosPlan::Random(params) => random_schedule(&params, population),
+            ChaosPlan::Replay(mut steps) => {
+                steps.sort_by_key(|step| step.at_ms);
+                Ok(steps)
+            }
+        }
+    }
+}
+
+/// Arrival times in millisecs of a Poisson process with the given mean gap
+/// over the chaos period. Gaps are drawn from the exponential distribution
+/// by inverse transform sampling.
+fn arrivals(rng: &mut StdRng, gap_ms: u64, duration_ms: u64) -> Vec<u64> {
+    if gap_ms == 0 {
+        return vec![];
+    }
+    let mut exp_gap = || -(1.0 - rng.gen::<f64>()).ln() * gap_ms as f64;
+    let mut times = vec![];
+    let mut t = exp_gap();
+    while t < duration_ms as f64 {
+        times.push(t as u64);
+        t += exp_gap();
+    }
+    times
+}
+
+/// Draws a random fault schedule from the parameters. The same parameters
+/// and population always give the same schedule. Partitions and delays do
+/// not overlap: one arriving before the last has healed is skipped.
+fn random_schedule(
+    params: &ChaosParams,
+    population: u16,
+) -> Result<Vec<ChaosStep>, SummersetError> {
+    if population == 0 {
+        return Err(SummersetError::msg("no servers to inject faults to"));
+    }
+    let mut rng = StdRng::seed_from_u64(params.seed);
+
+    // arrivals of each fault kind, tagged by kind: 0 crash, 1 partition,
+    // 2 delay, 3 transfer
+    let mut faults: Vec<(u64, u8)> = vec![];
+    for (kind, gap_ms) in [
+        params.crash_gap_ms,
+        params.partition_gap_ms,
+        params.delay_gap_ms,
+        params.transfer_gap_ms,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        for at_ms in arrivals(&mut rng, gap_ms, params.duration_ms) {
+            faults.push((at_ms, kind as u8));
+        }
+    }
+    faults.sort();
+
+    let mut steps = vec![];
+    let mut net_healed_ms = 0;
+    for (at_ms, kind) in faults {
+        let action = match kind {
+            0 => ChaosAction::Crash {
+                server: rng.gen_range(0..population),
+            },
+            1 | 2 if at_ms < net_healed_ms => continue,
+            1 => {
+                // cut off a random minority
+                if population < 2 {
+                    continue;
+                }
+                let size = rng.gen_range(1..=(population / 2).max(1));
+                let side = seq::index::sample(
+                    &mut rng,
+                    population as usize,
+                    size as usize,
+                )
+                .into_iter()
+                .map(|s| s as ReplicaId)
+                .collect();
+                ChaosAction::Partition { side }
+            }
+            2 => ChaosAction::Delay {
+                server: rng.gen_range(0..population),
+                delay_ms: params.delay_ms,
+            },
+            _ => ChaosAction::TransferLeader {
+                target: rng.gen_range(0..population),
+            },
+        };
+        steps.push(ChaosStep { at_ms, action });
+        if kind == 1 || kind == 2 {
+            net_healed_ms =
+                (at_ms + params.net_fault_ms).min(params.duration_ms);
+            steps.push(ChaosStep {
+                at_ms: net_healed_ms,
+                action: ChaosAction::Heal,
+            });
+        }
+    }
+    steps.sort_by_key(|step| step.at_ms);
+    Ok(steps)
+}
+
+/// An ongoing chaos run.
+pub(crate) struct ChaosRun {
+    /// Time the run started.
+    start: Instant,
+
+    /// Steps not yet taken, in time order.
+    pending: VecDeque<ChaosStep>,
+
+    /// Time the run ends, after which all network faults are healed.
+    end: Instant,
+}
+
+impl ChaosRun {
+    /// Starts a chaos run of the given schedule and length in millisecs;
+    /// the run lasts at least until its last step.
+    pub(crate) fn new(steps: Vec<ChaosStep>, duration_ms: u64) -> Self {
+        let start = Instant::now();
+        let last_ms = steps.last().map_or(0, |step| step.at_ms);
+        ChaosRun {
+            start,
+            pending: steps.into(),
+            end: start + Duration::from_millis(duration_ms.max(last_ms)),
+        }
+    }
+
+    /// Time the next step is due, or the run's end if no steps are left.
+    pub(crate) fn next_due(&self) -> Instant {
+        match self.pending.front() {
+            Some(step) => self.start + Duration::from_millis(step.at_ms),
+            None => self.end,
+        }
+    }
+
+    /// Takes the next step if it is due.
+    pub(crate) fn pop_due(&mut self) -> Option<ChaosStep> {
+        if self.pending.is_empty() || self.next_due() > Instant::now() {
+            return None;
+        }
+        self.pending.pop_front()
+    }
+
+    /// Returns true if all steps are taken and the run's end has passed.
+    pub(crate) fn finished(&self) -> bool {
+        self.pending.is_empty() && self.end <= Instant::now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedule_reproducible() -> Result<(), SummersetError> {
+        let params = ChaosParams {
+            seed: 7,
+            transfer_gap_ms: 20000,
+            ..Default::default()
+        };
+        let steps = ChaosPlan::Random(params.clone()).schedule(5)?;
+        assert!(!steps.is_empty());
+        assert_eq!(ChaosPlan::Random(params.clone()).schedule(5)?, steps);
+        assert!(steps.windows(2).all(|w| w[0].at_ms <= w[1].at_ms));
+        assert!(steps.iter().all(|step| step.at_ms <= params.duration_ms));
+        assert_eq!(ChaosPlan::Replay(steps.clone()).schedule(5)?, steps);
+        Ok(())
+    }
+
+    #[test]
+    fn net_faults_heal() -> Result<(), SummersetError> {
+        let params = ChaosParams {
+            seed: 3,
+            crash_gap_ms: 0,
+            partition_gap_ms: 1000,
+            delay_gap_ms: 1000,
+            ..Default::default()
+        };
+        let mut faulty = false;
+        for step in ChaosPlan::Random(params).schedule(5)? {
+            match step.action {
+                ChaosAction::Partition { ref side } => {
+                    assert!(!faulty);
+                    assert!(!side.is_empty() && side.len() <= 2);
+                    faulty = true;
+                }
+                ChaosAction::Delay { .. } => {
+                    assert!(!faulty);
+                    faulty = true;
+                }
+                ChaosAction::Heal => faulty = false,
+                _ => panic!("unexpected action {:?}", step.action),
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,20 +1,45 @@
 //! Summerset cluster manager oracle implementation.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
 use std::net::SocketAddr;
+use std::path::Path;
 
 use crate::client::ClientId;
 use crate::manager::{
-    ClientReactor, CtrlMsg, CtrlReply, CtrlRequest, ServerReigner,
+    ChaosAction, ChaosPlan, ChaosRun, ClientEvent, ClientReactor,
+    ClusterSnapshot, CtrlEvent, CtrlMsg, CtrlReply, CtrlRequest, Dashboard,
+    EventLog, LeaseChecker, LeaseInterval, ServerReigner,
 };
 use crate::protocols::SmrProtocol;
-use crate::server::ReplicaId;
-use crate::utils::{ConfNum, RespondersConf, SummersetError, ME};
+use crate::server::{ReplicaId, ResourceUsage};
+use crate::utils::{set_me, Bitmap, ConfNum, RespondersConf, SummersetError};
 
 use serde::{Deserialize, Serialize};
 
 use tokio::sync::{mpsc, watch};
-use tokio::time::{self, Duration};
+use tokio::time::{self, Duration, Instant};
+
+/// Number of most recent control events shown on the dashboard.
+const DASHBOARD_EVENTS_SHOWN: usize = 50;
+
+/// How long to wait for reset watchers to quiesce before resetting anyway.
+const RESET_QUIESCE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long to wait for a leader to be known after a reset before replying
+/// anyway.
+const RESET_LEADER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Size in bytes of the chunks a seed image is handed out to servers in.
+const SEED_CHUNK_BYTES: usize = 1024 * 1024;
+
+/// How long to wait for servers to report their coordinated snapshots before
+/// replying with those reported so far.
+const COORD_SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait for the leader to carry out a control request sent to it
+/// before replying with failure.
+const LEADER_CTRL_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// Information about an active server.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
@@ -35,6 +60,66 @@ pub struct ServerInfo {
     pub start_slot: usize,
 }
 
+/// Latest protocol status periodically reported by an active server.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct ServerStatus {
+    /// Highest ballot/term number seen, in the protocol's own encoding.
+    pub ballot: u64,
+
+    /// Commit progress index, in the protocol's own convention (e.g., first
+    /// non-committed slot for Paxos variants, last committed slot for Raft).
+    pub commit_bar: usize,
+
+    /// Execution progress index, in the same convention as `commit_bar`.
+    pub exec_bar: usize,
+
+    /// Peers (including myself) that my heartbeater considers alive.
+    pub peers_alive: Bitmap,
+
+    /// Current per-peer perf model coefficients rendered as
+    /// `(slope ms/MiB,delay+jitter ms)`, for protocols that maintain such
+    /// models; empty otherwise.
+    pub perf_models: HashMap<ReplicaId, String>,
+
+    /// Most frequently accessed keys executed by my state machine with their
+    /// approximate access counts, hottest first.
+    pub hot_keys: Vec<(String, u64)>,
+
+    /// Depths of my internal pipeline queues as `(stage, current depth, high
+    /// watermark since the previous report)` tuples.
+    pub queue_depths: Vec<(String, usize, usize)>,
+
+    /// Resource usage of my server process, sampled by the control hub when
+    /// sending the report.
+    pub resources: ResourceUsage,
+
+    /// Number of my log entries found to have diverged from what got
+    /// committed and been repaired, for protocols that track it; 0 otherwise.
+    pub divergence_repaired: u64,
+}
+
+/// Ongoing coordinated snapshot, waiting for servers to report.
+#[derive(Debug)]
+struct CoordSnapWait {
+    /// Client that requested the snapshot.
+    client: ClientId,
+
+    /// Leader that proposed the marker entry.
+    leader: ReplicaId,
+
+    /// Servers active when the snapshot was requested.
+    servers: Vec<ReplicaId>,
+
+    /// Slot of the marker entry, once first reported.
+    mark_slot: Option<usize>,
+
+    /// Map from server ID -> path of its snapshot file reported so far.
+    paths: HashMap<ReplicaId, String>,
+
+    /// Deadline after which to reply with the snapshots reported so far.
+    deadline: Instant,
+}
+
 /// Standalone cluster manager oracle.
 pub struct ClusterManager {
     /// SMR Protocol in use.
@@ -47,17 +132,23 @@ pub struct ClusterManager {
     _cli_addr: SocketAddr,
 
     /// Total number of server replicas in cluster.
-    population: u8,
+    population: u16,
 
     /// Receiver side of the server ID assignment channel.
     rx_id_assign: mpsc::UnboundedReceiver<()>,
 
     /// Sender side of the server ID assignment result channel.
-    tx_id_result: mpsc::UnboundedSender<(ReplicaId, u8)>,
+    tx_id_result: mpsc::UnboundedSender<(ReplicaId, u16)>,
 
     /// Information of current active servers.
     servers_info: HashMap<ReplicaId, ServerInfo>,
 
+    /// Latest status reported by current active servers.
+    servers_status: HashMap<ReplicaId, ServerStatus>,
+
+    /// Time when each active server's latest status was received.
+    report_times: HashMap<ReplicaId, Instant>,
+
     /// Currently assigned server IDs.
     assigned_ids: HashSet<ReplicaId>,
 
@@ -67,36 +158,114 @@ pub struct ClusterManager {
     /// Latest known responders config number.
     latest_conf_num: ConfNum,
 
+    /// Latest ballot epoch granted as Vertical Paxos configuration master.
+    master_epoch: u64,
+
+    /// Voting members of the latest configuration known as master.
+    master_members: Bitmap,
+
     /// ServerReigner module.
     server_reigner: ServerReigner,
 
     /// ClientReactor module.
     client_reactor: ClientReactor,
+
+    /// Optional HTTP dashboard module.
+    dashboard: Option<Dashboard>,
+
+    /// Timeline of control-plane events.
+    event_log: EventLog,
+
+    /// Optional state machine image to seed fresh servers with.
+    seed_image: Option<Vec<u8>>,
+
+    /// Cross-checker of majority lease intervals reported by servers.
+    lease_checker: LeaseChecker,
+
+    /// Map from subscribed observer client ID -> sequence number of the next
+    /// event to push to it.
+    subscribers: HashMap<ClientId, u64>,
+
+    /// Clients watching for cluster resets.
+    reset_watchers: HashSet<ClientId>,
+
+    /// Client requests received while waiting for reset watchers to quiesce,
+    /// to be handled after the reset.
+    deferred_reqs: VecDeque<(ClientId, CtrlRequest)>,
+
+    /// How long a dropped client's per-client state is kept on servers,
+    /// waiting for it to reconnect under the same identity.
+    client_grace: Duration,
+
+    /// Map from dropped client ID -> when its per-client state expires.
+    expiring_clients: HashMap<ClientId, Instant>,
+
+    /// Ongoing chaos mode run, if any.
+    chaos: Option<ChaosRun>,
+
+    /// Ongoing coordinated snapshot, if any.
+    coord_snap: Option<CoordSnapWait>,
 }
 
 impl ClusterManager {
     /// Creates a new standalone cluster manager and sets up required
     /// functionality modules.
+    #[allow(clippy::too_many_arguments)]
     pub async fn new_and_setup(
         protocol: SmrProtocol,
         srv_addr: SocketAddr,
         cli_addr: SocketAddr,
-        population: u8,
+        http_addr: Option<SocketAddr>,
+        event_log_path: Option<&Path>,
+        cluster_ca_path: Option<&Path>,
+        seed_image_path: Option<&Path>,
+        port_range: Option<(u16, u16)>,
+        population: u16,
+        client_grace: Duration,
     ) -> Result<Self, SummersetError> {
         if population == 0 {
             return logged_err!("invalid population {}", population);
         }
+        let cluster_ca = match cluster_ca_path {
+            Some(path) => Some(fs::read_to_string(path)?),
+            None => None,
+        };
+        let seed_image = match seed_image_path {
+            Some(path) => {
+                let image = fs::read(path)?;
+                pf_info!(
+                    "seeding servers with image '{}' of {} bytes",
+                    path.display(),
+                    image.len()
+                );
+                Some(image)
+            }
+            None => None,
+        };
 
-        ME.get_or_init(|| "m".into());
+        set_me("m".into());
 
         let (tx_id_assign, rx_id_assign) = mpsc::unbounded_channel();
         let (tx_id_result, rx_id_result) = mpsc::unbounded_channel();
-        let server_reigner =
-            ServerReigner::new_and_setup(srv_addr, tx_id_assign, rx_id_result)
-                .await?;
+        let server_reigner = ServerReigner::new_and_setup(
+            srv_addr,
+            cli_addr.port(),
+            cluster_ca,
+            port_range,
+            tx_id_assign,
+            rx_id_result,
+        )
+        .await?;
 
         let client_reactor = ClientReactor::new_and_setup(cli_addr).await?;
 
+        let dashboard = match http_addr {
+            Some(http_addr) => Some(Dashboard::new_and_setup(http_addr).await?),
+            None => None,
+        };
+
+        let event_log = EventLog::new(event_log_path)?;
+
         Ok(ClusterManager {
             protocol,
             _srv_addr: srv_addr,
@@ -105,11 +274,26 @@ impl ClusterManager {
             rx_id_assign,
             tx_id_result,
             servers_info: HashMap::new(),
+            servers_status: HashMap::new(),
+            report_times: HashMap::new(),
             assigned_ids: HashSet::new(),
             responders_conf: RespondersConf::empty(population),
             latest_conf_num: 0,
+            master_epoch: 0,
+            master_members: Bitmap::new(population, true),
             server_reigner,
             client_reactor,
+            dashboard,
+            event_log,
+            seed_image,
+            lease_checker: LeaseChecker::new(),
+            subscribers: HashMap::new(),
+            reset_watchers: HashSet::new(),
+            deferred_reqs: VecDeque::new(),
+            client_grace,
+            expiring_clients: HashMap::new(),
+            chaos: None,
+            coord_snap: None,
         })
     }
 
@@ -126,6 +310,44 @@ impl ClusterManager {
         logged_err!("no server ID < population left available")
     }
 
+    /// Publishes the latest cluster state to the dashboard, if enabled.
+    fn publish_to_dashboard(&mut self) -> Result<(), SummersetError> {
+        if let Some(dashboard) = self.dashboard.as_mut() {
+            dashboard.publish(ClusterSnapshot {
+                population: self.population,
+                servers_info: self.servers_info.clone(),
+                servers_status: self.servers_status.clone(),
+                report_times: self.report_times.clone(),
+                recent_events: self.event_log.latest(DASHBOARD_EVENTS_SHOWN),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Pushes events recorded since the last push to subscribed observers.
+    fn publish_to_subscribers(&mut self) -> Result<(), SummersetError> {
+        let next_seq = self.event_log.next_seq();
+        let mut gone = vec![];
+        for (&client, cursor) in &mut self.subscribers {
+            if *cursor >= next_seq {
+                continue;
+            }
+            let events = self.event_log.since(*cursor);
+            *cursor = next_seq;
+            if self
+                .client_reactor
+                .send_reply(CtrlReply::Events { events }, client)
+                .is_err()
+            {
+                gone.push(client);
+            }
+        }
+        for client in gone {
+            self.subscribers.remove(&client);
+        }
+        Ok(())
+    }
+
     /// Main event loop logic of the cluster manager. Breaks out of the loop
     /// only upon catching termination signals to the process.
     pub async fn run(
@@ -133,6 +355,9 @@ impl ClusterManager {
         mut rx_term: watch::Receiver<bool>,
     ) -> Result<(), SummersetError> {
         loop {
+            let client_expiry = self.next_client_expiry();
+            let chaos_due = self.next_chaos_due();
+            let coord_snap_due = self.next_coord_snap_due();
             tokio::select! {
                 // receiving server ID assignment request
                 _ = self.rx_id_assign.recv() => {
@@ -154,21 +379,77 @@ impl ClusterManager {
                         pf_error!("error handling ctrl msg <- {}: {}",
                                        server, e);
                     }
+                    if let Err(e) = self.publish_to_dashboard() {
+                        pf_error!("error publishing to dashboard: {}", e);
+                    }
+                    if let Err(e) = self.publish_to_subscribers() {
+                        pf_error!("error publishing to subscribers: {}", e);
+                    }
                 },
 
                 // receiving client control request
-                ctrl_req = self.client_reactor.recv_req() => {
-                    if let Err(_e) = ctrl_req {
+                event = self.client_reactor.recv_event() => {
+                    if let Err(_e) = event {
                         // NOTE: commented out to prevent console lags
                         //       during benchmarking
-                        // pf_error!("error receiving ctrl req: {}", e);
+                        // pf_error!("error receiving client event: {}", e);
                         continue;
                     }
-                    let (client, req) = ctrl_req.unwrap();
+                    let (client, req) = match event.unwrap() {
+                        ClientEvent::Req(client, req) => (client, req),
+                        ClientEvent::Left(client, graceful) => {
+                            // a client's control connection has ended
+                            if let Err(e) = self.handle_client_left(client, graceful) {
+                                pf_error!("error handling client {} left: {}",
+                                               client, e);
+                            }
+                            continue;
+                        }
+                    };
                     if let Err(e) = self.handle_ctrl_req(client, req).await {
                         pf_error!("error handling ctrl req <- {}: {}",
                                        client, e);
                     }
+                    while let Some((client, req)) = self.deferred_reqs.pop_front() {
+                        if let Err(e) = self.handle_ctrl_req(client, req).await {
+                            pf_error!("error handling ctrl req <- {}: {}",
+                                           client, e);
+                        }
+                    }
+                    if let Err(e) = self.publish_to_dashboard() {
+                        pf_error!("error publishing to dashboard: {}", e);
+                    }
+                    if let Err(e) = self.publish_to_subscribers() {
+                        pf_error!("error publishing to subscribers: {}", e);
+                    }
+                },
+
+                // grace period of some dropped client(s) has passed
+                _ = time::sleep_until(client_expiry), if !self.expiring_clients.is_empty() => {
+                    if let Err(e) = self.expire_dropped_clients() {
+                        pf_error!("error expiring dropped clients: {}", e);
+                    }
+                },
+
+                // next step of the ongoing chaos run is due
+                _ = time::sleep_until(chaos_due), if self.chaos.is_some() => {
+                    if let Err(e) = self.step_chaos().await {
+                        pf_error!("error taking chaos step: {}", e);
+                    }
+                    if let Err(e) = self.publish_to_dashboard() {
+                        pf_error!("error publishing to dashboard: {}", e);
+                    }
+                    if let Err(e) = self.publish_to_subscribers() {
+                        pf_error!("error publishing to subscribers: {}", e);
+                    }
+                },
+
+                // ongoing coordinated snapshot has timed out
+                _ = time::sleep_until(coord_snap_due), if self.coord_snap.is_some() => {
+                    pf_warn!("coordinated snapshot timed out");
+                    if let Err(e) = self.finish_coord_snapshot(false) {
+                        pf_error!("error finishing coordinated snapshot: {}", e);
+                    }
                 },
 
                 // receiving termination signal
@@ -221,6 +502,25 @@ impl ClusterManager {
                 start_slot: 0,
             },
         );
+        self.event_log.record(CtrlEvent::ServerJoined { server })?;
+
+        // hand it the seed image to start from, if any, in chunks so that a
+        // large image does not make one huge control message
+        if let Some(image) = self.seed_image.as_ref() {
+            let num_chunks = image.len().div_ceil(SEED_CHUNK_BYTES).max(1);
+            for idx in 0..num_chunks {
+                let offset = idx * SEED_CHUNK_BYTES;
+                let end = image.len().min(offset + SEED_CHUNK_BYTES);
+                self.server_reigner.send_ctrl(
+                    CtrlMsg::SeedState {
+                        offset,
+                        chunk: image[offset..end].to_vec(),
+                        last: idx + 1 == num_chunks,
+                    },
+                    server,
+                )?;
+            }
+        }
 
         // tell it to connect to all other existing known servers
         self.server_reigner.send_ctrl(
@@ -230,9 +530,33 @@ impl ClusterManager {
             },
             server,
         )?;
+
+        // let every server know the new map of client-facing addresses
+        self.bcast_servers_map();
         Ok(())
     }
 
+    /// Sends the current map of servers' client-facing API addresses to all
+    /// known servers. Failures to reach a server are only logged, as it will
+    /// get a fresh map when it (re)joins.
+    fn bcast_servers_map(&mut self) {
+        let api_addrs: HashMap<ReplicaId, SocketAddr> = self
+            .servers_info
+            .iter()
+            .map(|(&server, info)| (server, info.api_addr))
+            .collect();
+        for &server in api_addrs.keys() {
+            if let Err(e) = self.server_reigner.send_ctrl(
+                CtrlMsg::ServersMap {
+                    api_addrs: api_addrs.clone(),
+                },
+                server,
+            ) {
+                pf_warn!("error sending servers map to {}: {}", server, e);
+            }
+        }
+    }
+
     /// Handler of LeaderStatus message.
     fn handle_leader_status(
         &mut self,
@@ -251,7 +575,11 @@ impl ClusterManager {
             logged_err!("server {} is already marked as non-leader", server)
         } else {
             info.is_leader = step_up;
-            Ok(())
+            self.event_log.record(if step_up {
+                CtrlEvent::LeaderStepUp { server }
+            } else {
+                CtrlEvent::LeaderStepDown { server }
+            })
         }
     }
 
@@ -270,6 +598,8 @@ impl ClusterManager {
         if conf_num >= self.latest_conf_num {
             self.responders_conf = new_conf;
             self.latest_conf_num = conf_num;
+            self.event_log
+                .record(CtrlEvent::RespondersConf { server, conf_num })?;
         } else {
             pf_warn!("outdated responders config number {} ignored", conf_num);
         }
@@ -297,7 +627,191 @@ impl ClusterManager {
             )
         } else {
             info.start_slot = new_start;
-            Ok(())
+            self.event_log
+                .record(CtrlEvent::SnapshotTaken { server, new_start })
+        }
+    }
+
+    /// Handler of SnapshotAt message.
+    fn handle_snapshot_at(
+        &mut self,
+        server: ReplicaId,
+        slot: usize,
+        path: &str,
+    ) -> Result<(), SummersetError> {
+        if !self.servers_info.contains_key(&server) {
+            return logged_err!("manager got unknown server ID: {}", server);
+        }
+        pf_info!("server {} snapshot at slot {} in '{}'", server, slot, path);
+        self.event_log
+            .record(CtrlEvent::CoordSnapshotTaken { server, slot })
+    }
+
+    /// Handler of MembersConf message.
+    fn handle_members_conf(
+        &mut self,
+        server: ReplicaId,
+        members: Bitmap,
+        slot: usize,
+    ) -> Result<(), SummersetError> {
+        if !self.servers_info.contains_key(&server) {
+            return logged_err!("manager got unknown server ID: {}", server);
+        }
+
+        pf_info!("members changed to {:?} at slot {}", members, slot);
+        self.master_members = members.clone();
+        // the stop-sign's slot numbers the new config, which clients fence
+        // their requests with
+        self.latest_conf_num = self.latest_conf_num.max(slot as ConfNum);
+        self.event_log.record(CtrlEvent::MembersChanged {
+            server,
+            members,
+            slot,
+        })
+    }
+
+    /// Handler of LeaseInterval message. Records the interval in the event
+    /// log and cross-checks it against other servers' recent intervals.
+    fn handle_lease_interval(
+        &mut self,
+        server: ReplicaId,
+        lease_num: u64,
+        start_ms: u64,
+        end_ms: u64,
+    ) -> Result<(), SummersetError> {
+        if !self.servers_info.contains_key(&server) {
+            return logged_err!("manager got unknown server ID: {}", server);
+        }
+
+        self.event_log.record(CtrlEvent::LeaseHeld {
+            server,
+            lease_num,
+            start_ms,
+            end_ms,
+        })?;
+        for (other, start_ms, end_ms) in self
+            .lease_checker
+            .add(server, LeaseInterval { start_ms, end_ms })
+        {
+            pf_error!(
+                "lease intervals of {} and {} overlap in {}-{}",
+                server,
+                other,
+                start_ms,
+                end_ms
+            );
+            self.event_log.record(CtrlEvent::LeaseOverlap {
+                server,
+                other,
+                start_ms,
+                end_ms,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Handler of BallotRequest message. Acting as the Vertical Paxos
+    /// configuration master, grants a new ballot epoch to the requester with
+    /// all currently unpaused servers as the new voting members, unless the
+    /// requester has not yet seen the latest granted epoch.
+    fn handle_ballot_request(
+        &mut self,
+        server: ReplicaId,
+        seen_epoch: u64,
+    ) -> Result<(), SummersetError> {
+        if !self.servers_info.contains_key(&server) {
+            return logged_err!("manager got unknown server ID: {}", server);
+        }
+        if seen_epoch < self.master_epoch
+            || self.servers_info[&server].is_paused
+        {
+            pf_debug!("ballot request from {} ignored", server);
+            return Ok(());
+        }
+
+        let epoch = seen_epoch + 1;
+        let members = Bitmap::from((
+            self.population,
+            self.servers_info
+                .iter()
+                .filter_map(|(&s, info)| (!info.is_paused).then_some(s))
+                .collect::<Vec<_>>(),
+        ));
+        let prev_members =
+            std::mem::replace(&mut self.master_members, members.clone());
+        self.master_epoch = epoch;
+        pf_info!(
+            "granted epoch {} to {} with members {:?}",
+            epoch,
+            server,
+            members
+        );
+
+        // every server adopts the new members; only the requester steps up
+        for &s in self.servers_info.keys() {
+            self.server_reigner.send_ctrl(
+                CtrlMsg::BallotGrant {
+                    leader: server,
+                    epoch,
+                    members: members.clone(),
+                    prev_members: prev_members.clone(),
+                },
+                s,
+            )?;
+        }
+        self.event_log.record(CtrlEvent::BallotGranted {
+            server,
+            epoch,
+            members,
+        })
+    }
+
+    /// Handler of BallotActive message.
+    fn handle_ballot_active(
+        &mut self,
+        server: ReplicaId,
+        epoch: u64,
+    ) -> Result<(), SummersetError> {
+        if !self.servers_info.contains_key(&server) {
+            return logged_err!("manager got unknown server ID: {}", server);
+        }
+
+        self.event_log
+            .record(CtrlEvent::BallotActivated { server, epoch })
+    }
+
+    /// Handler of periodic StatusReport message.
+    fn handle_status_report(
+        &mut self,
+        server: ReplicaId,
+        status: ServerStatus,
+    ) -> Result<(), SummersetError> {
+        if !self.servers_info.contains_key(&server) {
+            return logged_err!("manager got unknown server ID: {}", server);
+        }
+
+        self.servers_status.insert(server, status);
+        self.report_times.insert(server, Instant::now());
+        Ok(())
+    }
+
+    /// Handler of a server's control connection going away, either with a
+    /// graceful Leave message or ungracefully.
+    fn handle_server_gone(
+        &mut self,
+        server: ReplicaId,
+        graceful: bool,
+    ) -> Result<(), SummersetError> {
+        if !self.servers_info.contains_key(&server) {
+            // server already forgotten, e.g., leaving due to a reset
+            return Ok(());
+        }
+
+        if graceful {
+            self.event_log.record(CtrlEvent::ServerLeft { server })
+        } else {
+            pf_warn!("server {} control connection dropped", server);
+            self.event_log.record(CtrlEvent::ServerCrashed { server })
         }
     }
 
@@ -338,9 +852,72 @@ impl ClusterManager {
                 self.handle_snapshot_up_to(server, new_start)?;
             }
 
+            CtrlMsg::SnapshotAt { slot, path } => {
+                self.handle_snapshot_at(server, slot, &path)?;
+                if let Some(wait) = self.coord_snap.as_mut() {
+                    if *wait.mark_slot.get_or_insert(slot) == slot {
+                        wait.paths.insert(server, path);
+                    }
+                }
+            }
+
+            CtrlMsg::SnapshotMarkReject
+                if self
+                    .coord_snap
+                    .as_ref()
+                    .is_some_and(|wait| wait.leader == server) =>
+            {
+                pf_warn!("leader {} rejected snapshot marker", server);
+                self.finish_coord_snapshot(true)?;
+            }
+
+            CtrlMsg::MembersConf { members, slot } => {
+                self.handle_members_conf(server, members, slot)?;
+            }
+
+            CtrlMsg::BallotRequest { seen_epoch } => {
+                self.handle_ballot_request(server, seen_epoch)?;
+            }
+
+            CtrlMsg::BallotActive { epoch } => {
+                self.handle_ballot_active(server, epoch)?;
+            }
+
+            CtrlMsg::StatusReport { status } => {
+                self.handle_status_report(server, status)?;
+            }
+
+            CtrlMsg::LeaseInterval {
+                lease_num,
+                start_ms,
+                end_ms,
+            } => {
+                self.handle_lease_interval(
+                    server, lease_num, start_ms, end_ms,
+                )?;
+            }
+
+            CtrlMsg::Leave => {
+                self.handle_server_gone(server, true)?;
+            }
+
+            CtrlMsg::Disconnected => {
+                self.handle_server_gone(server, false)?;
+            }
+
             _ => {} // ignore all other types
         }
 
+        // an ongoing coordinated snapshot may be complete now, as the last
+        // server reported or left
+        if self.coord_snap.as_ref().is_some_and(|wait| {
+            wait.servers.iter().all(|s| {
+                wait.paths.contains_key(s) || !self.servers_info.contains_key(s)
+            })
+        }) {
+            self.finish_coord_snapshot(false)?;
+        }
+
         Ok(())
     }
 }
@@ -375,71 +952,309 @@ impl ClusterManager {
         )
     }
 
-    /// Handler of client ResetServers request.
-    async fn handle_client_reset_servers(
+    /// Handler of client QueryStatus request.
+    fn handle_client_query_status(
         &mut self,
         client: ClientId,
-        servers: HashSet<ReplicaId>,
-        durable: bool,
     ) -> Result<(), SummersetError> {
-        let num_replicas = self.servers_info.len();
-        let mut servers: Vec<ReplicaId> = if servers.is_empty() {
-            // all active servers
-            self.servers_info.keys().copied().collect()
-        } else {
-            servers.into_iter().collect()
-        };
-
-        // reset specified server(s)
-        let mut reset_done = HashSet::new();
-        while let Some(s) = servers.pop() {
-            // send reset server control message to server
-            self.server_reigner
-                .send_ctrl(CtrlMsg::ResetState { durable }, s)?;
-
-            // remove information about this server
-            debug_assert!(self.assigned_ids.contains(&s));
-            debug_assert!(self.servers_info.contains_key(&s));
-            self.assigned_ids.remove(&s);
-            self.servers_info.remove(&s);
-
-            // wait for the new server ID assignment request from it
-            self.rx_id_assign.recv().await;
-            if let Err(e) = self.assign_server_id() {
-                return logged_err!("error assigning new server ID: {}", e);
-            }
-
-            // wait a while to ensure the server's transport hub is setup
-            time::sleep(Duration::from_millis(500)).await;
-
-            reset_done.insert(s);
-        }
-
-        // now the reset servers should be sending NewServerJoin messages to
-        // me. Process them until all servers joined
-        while self.servers_info.len() < num_replicas {
-            let (s, msg) = self.server_reigner.recv_ctrl().await?;
-            if let Err(e) = self.handle_ctrl_msg(s, msg).await {
-                pf_error!("error handling ctrl msg <- {}: {}", s, e);
-            }
-        }
+        self.client_reactor.send_reply(
+            CtrlReply::QueryStatus {
+                servers_status: self.servers_status.clone(),
+            },
+            client,
+        )
+    }
 
+    /// Handler of client QueryEvents request.
+    fn handle_client_query_events(
+        &mut self,
+        client: ClientId,
+        since: u64,
+    ) -> Result<(), SummersetError> {
         self.client_reactor.send_reply(
-            CtrlReply::ResetServers {
-                servers: reset_done,
+            CtrlReply::QueryEvents {
+                events: self.event_log.since(since),
             },
             client,
         )
     }
 
-    /// Handler of client PauseServers request.
-    async fn handle_client_pause_servers(
+    /// Handler of client Subscribe request. Replies with events since
+    /// `since` right away; later events get pushed as they are recorded.
+    fn handle_client_subscribe(
         &mut self,
         client: ClientId,
-        servers: HashSet<ReplicaId>,
+        since: u64,
     ) -> Result<(), SummersetError> {
-        let mut servers: Vec<ReplicaId> = if servers.is_empty() {
-            // all active servers
+        pf_debug!("client {} subscribed to events since {}", client, since);
+        self.subscribers.insert(client, self.event_log.next_seq());
+        self.client_reactor.send_reply(
+            CtrlReply::Subscribe {
+                events: self.event_log.since(since),
+            },
+            client,
+        )
+    }
+
+    /// Handler of client WatchResets request.
+    fn handle_client_watch_resets(
+        &mut self,
+        client: ClientId,
+    ) -> Result<(), SummersetError> {
+        pf_debug!("client {} watching for resets", client);
+        self.reset_watchers.insert(client);
+        self.client_reactor
+            .send_reply(CtrlReply::WatchResets, client)
+    }
+
+    /// Tells reset watchers that `servers` are about to be reset, and waits
+    /// for them to quiesce, up to a timeout. Other client requests received
+    /// meanwhile are deferred.
+    async fn quiesce_reset_watchers(
+        &mut self,
+        servers: &HashSet<ReplicaId>,
+    ) -> Result<(), SummersetError> {
+        let mut waiting = HashSet::new();
+        for &watcher in &self.reset_watchers {
+            let notice = CtrlReply::ResetBegin {
+                servers: servers.clone(),
+            };
+            match self.client_reactor.send_reply(notice, watcher) {
+                Ok(()) => {
+                    waiting.insert(watcher);
+                }
+                Err(e) => {
+                    pf_warn!("dropping reset watcher {}: {}", watcher, e);
+                }
+            }
+        }
+        self.reset_watchers
+            .retain(|watcher| waiting.contains(watcher));
+
+        let timeout = time::sleep(RESET_QUIESCE_TIMEOUT);
+        tokio::pin!(timeout);
+        while !waiting.is_empty() {
+            tokio::select! {
+                event = self.client_reactor.recv_event() => match event {
+                    Ok(ClientEvent::Req(client, req)) => {
+                        if req == CtrlRequest::ResetQuiesced {
+                            waiting.remove(&client);
+                        } else {
+                            self.deferred_reqs.push_back((client, req));
+                        }
+                    }
+                    Ok(ClientEvent::Left(client, graceful)) => {
+                        if waiting.remove(&client) {
+                            pf_warn!("dropping reset watcher {}: left", client);
+                        }
+                        if let Err(e) = self.handle_client_left(client, graceful) {
+                            pf_error!("error handling client {} left: {}",
+                                           client, e);
+                        }
+                    }
+                    Err(e) => {
+                        pf_error!("error waiting for reset watchers: {}", e);
+                        break;
+                    }
+                },
+
+                _ = &mut timeout => {
+                    pf_warn!("reset watchers {:?} not quiesced in time", waiting);
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Waits until some server is known as leader, for protocols that elect
+    /// one, up to a timeout. Handles server control messages meanwhile.
+    async fn wait_for_leader(&mut self) -> Result<(), SummersetError> {
+        if !self.protocol.elects_leader() {
+            return Ok(());
+        }
+
+        let timeout = time::sleep(RESET_LEADER_TIMEOUT);
+        tokio::pin!(timeout);
+        while !self.servers_info.values().any(|info| info.is_leader) {
+            tokio::select! {
+                ctrl_msg = self.server_reigner.recv_ctrl() => {
+                    let (s, msg) = ctrl_msg?;
+                    if let Err(e) = self.handle_ctrl_msg(s, msg).await {
+                        pf_error!("error handling ctrl msg <- {}: {}", s, e);
+                    }
+                },
+
+                _ = &mut timeout => {
+                    pf_warn!("no leader known after reset");
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Resets the given server(s), or all active servers if empty, and
+    /// returns the set reset. Reset watchers are quiesced before the reset
+    /// and told when it is done.
+    async fn reset_servers(
+        &mut self,
+        servers: HashSet<ReplicaId>,
+        durable: bool,
+    ) -> Result<HashSet<ReplicaId>, SummersetError> {
+        let num_replicas = self.servers_info.len();
+        let mut servers: Vec<ReplicaId> = if servers.is_empty() {
+            // all active servers
+            self.servers_info.keys().copied().collect()
+        } else {
+            servers.into_iter().collect()
+        };
+        let to_reset: HashSet<ReplicaId> = servers.iter().copied().collect();
+        self.quiesce_reset_watchers(&to_reset).await?;
+
+        // reset specified server(s)
+        let mut reset_done = HashSet::new();
+        while let Some(s) = servers.pop() {
+            // send reset server control message to server
+            self.server_reigner
+                .send_ctrl(CtrlMsg::ResetState { durable }, s)?;
+
+            // remove information about this server
+            debug_assert!(self.assigned_ids.contains(&s));
+            debug_assert!(self.servers_info.contains_key(&s));
+            self.assigned_ids.remove(&s);
+            self.servers_info.remove(&s);
+            self.servers_status.remove(&s);
+            self.report_times.remove(&s);
+            self.event_log
+                .record(CtrlEvent::ServerReset { server: s, durable })?;
+
+            // wait for the new server ID assignment request from it
+            self.rx_id_assign.recv().await;
+            if let Err(e) = self.assign_server_id() {
+                return logged_err!("error assigning new server ID: {}", e);
+            }
+
+            // wait a while to ensure the server's transport hub is setup
+            time::sleep(Duration::from_millis(500)).await;
+
+            reset_done.insert(s);
+        }
+
+        // now the reset servers should be sending NewServerJoin messages to
+        // me. Process them until all servers joined
+        while self.servers_info.len() < num_replicas {
+            let (s, msg) = self.server_reigner.recv_ctrl().await?;
+            if let Err(e) = self.handle_ctrl_msg(s, msg).await {
+                pf_error!("error handling ctrl msg <- {}: {}", s, e);
+            }
+        }
+        self.wait_for_leader().await?;
+
+        for &watcher in &self.reset_watchers {
+            let notice = CtrlReply::ResetDone {
+                servers: reset_done.clone(),
+            };
+            if let Err(e) = self.client_reactor.send_reply(notice, watcher) {
+                pf_warn!("error notifying reset watcher {}: {}", watcher, e);
+            }
+        }
+        Ok(reset_done)
+    }
+
+    /// Handler of client ResetServers request.
+    async fn handle_client_reset_servers(
+        &mut self,
+        client: ClientId,
+        servers: HashSet<ReplicaId>,
+        durable: bool,
+    ) -> Result<(), SummersetError> {
+        let reset_done = self.reset_servers(servers, durable).await?;
+        self.client_reactor.send_reply(
+            CtrlReply::ResetServers {
+                servers: reset_done,
+            },
+            client,
+        )
+    }
+
+    /// Tells all active servers to drop given clients' session and dedup
+    /// state.
+    fn expire_clients(
+        &mut self,
+        clients: Vec<ClientId>,
+    ) -> Result<(), SummersetError> {
+        pf_debug!("expiring sessions of clients {:?}", clients);
+        for &s in self.servers_info.keys() {
+            self.server_reigner.send_ctrl(
+                CtrlMsg::ExpireClients {
+                    clients: clients.clone(),
+                },
+                s,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Handler of a client's control connection ending. If it left
+    /// gracefully or there is no grace period, its per-client state on
+    /// servers is expired right away; otherwise, that is deferred to give it
+    /// a chance to reconnect under the same identity.
+    fn handle_client_left(
+        &mut self,
+        client: ClientId,
+        graceful: bool,
+    ) -> Result<(), SummersetError> {
+        self.subscribers.remove(&client);
+        self.reset_watchers.remove(&client);
+        if graceful || self.client_grace.is_zero() {
+            self.expiring_clients.remove(&client);
+            self.expire_clients(vec![client])
+        } else {
+            pf_debug!("client {} dropped, in grace period", client);
+            self.expiring_clients
+                .insert(client, Instant::now() + self.client_grace);
+            Ok(())
+        }
+    }
+
+    /// Earliest time some dropped client's grace period passes.
+    fn next_client_expiry(&self) -> Instant {
+        self.expiring_clients
+            .values()
+            .min()
+            .copied()
+            .unwrap_or_else(Instant::now)
+    }
+
+    /// Expires dropped clients whose grace period has passed without them
+    /// reconnecting.
+    fn expire_dropped_clients(&mut self) -> Result<(), SummersetError> {
+        let now = Instant::now();
+        let mut expired = vec![];
+        self.expiring_clients.retain(|&client, &mut deadline| {
+            if deadline > now {
+                true
+            } else {
+                expired.push(client);
+                false
+            }
+        });
+        expired.retain(|&client| !self.client_reactor.has_client(client));
+        if expired.is_empty() {
+            return Ok(());
+        }
+        self.expire_clients(expired)
+    }
+
+    /// Handler of client PauseServers request.
+    async fn handle_client_pause_servers(
+        &mut self,
+        client: ClientId,
+        servers: HashSet<ReplicaId>,
+    ) -> Result<(), SummersetError> {
+        let mut servers: Vec<ReplicaId> = if servers.is_empty() {
+            // all active servers
             self.servers_info.keys().copied().collect()
         } else {
             servers.into_iter().collect()
@@ -454,6 +1269,8 @@ impl ClusterManager {
             // set the is_paused flag
             debug_assert!(self.servers_info.contains_key(&s));
             self.servers_info.get_mut(&s).unwrap().is_paused = true;
+            self.event_log
+                .record(CtrlEvent::ServerPaused { server: s })?;
 
             // wait for dummy reply
             loop {
@@ -508,6 +1325,8 @@ impl ClusterManager {
             // clear the is_paused flag
             debug_assert!(self.servers_info.contains_key(&s));
             self.servers_info.get_mut(&s).unwrap().is_paused = false;
+            self.event_log
+                .record(CtrlEvent::ServerResumed { server: s })?;
 
             resume_done.insert(s);
         }
@@ -520,6 +1339,92 @@ impl ClusterManager {
         )
     }
 
+    /// Handler of client SetLogFilters request.
+    async fn handle_client_set_log_filters(
+        &mut self,
+        client: ClientId,
+        servers: HashSet<ReplicaId>,
+        filters: String,
+    ) -> Result<(), SummersetError> {
+        let mut servers: Vec<ReplicaId> = if servers.is_empty() {
+            // all active servers
+            self.servers_info.keys().copied().collect()
+        } else {
+            servers.into_iter().collect()
+        };
+
+        // tell specified server(s)
+        let mut switch_done = HashSet::new();
+        while let Some(s) = servers.pop() {
+            // send log filters control message to server
+            self.server_reigner.send_ctrl(
+                CtrlMsg::SetLogFilters {
+                    filters: filters.clone(),
+                },
+                s,
+            )?;
+
+            // wait for reply
+            loop {
+                let (server, reply) = self.server_reigner.recv_ctrl().await?;
+                match reply {
+                    CtrlMsg::LogFiltersReply { ok } if server == s => {
+                        if ok {
+                            switch_done.insert(s);
+                        }
+                        break;
+                    }
+                    _ => self.handle_ctrl_msg(server, reply).await?,
+                }
+            }
+        }
+
+        self.client_reactor.send_reply(
+            CtrlReply::SetLogFilters {
+                servers: switch_done,
+            },
+            client,
+        )
+    }
+
+    /// Handler of client QueryKeys request.
+    async fn handle_client_query_keys(
+        &mut self,
+        client: ClientId,
+        server: ReplicaId,
+        cursor: String,
+        limit: usize,
+    ) -> Result<(), SummersetError> {
+        if !self.servers_info.contains_key(&server) {
+            return self
+                .client_reactor
+                .send_reply(CtrlReply::QueryKeys { report: None }, client);
+        }
+
+        // send list keys control message to server
+        self.server_reigner
+            .send_ctrl(CtrlMsg::ListKeys { cursor, limit }, server)?;
+
+        // wait for reply
+        let report = loop {
+            let (s, reply) = self.server_reigner.recv_ctrl().await?;
+            match reply {
+                CtrlMsg::ListKeysReply { report } if s == server => {
+                    break report;
+                }
+                CtrlMsg::Leave | CtrlMsg::Disconnected if s == server => {
+                    // server gone before replying
+                    self.handle_ctrl_msg(s, reply).await?;
+                    break None;
+                }
+                _ => self.handle_ctrl_msg(s, reply).await?,
+            }
+        };
+
+        self.client_reactor
+            .send_reply(CtrlReply::QueryKeys { report }, client)
+    }
+
     /// Handler of client TakeSnapshot request.
     async fn handle_client_take_snapshot(
         &mut self,
@@ -557,6 +1462,10 @@ impl ClusterManager {
                             self.servers_info.get_mut(&s).unwrap().start_slot =
                                 new_start;
                         }
+                        self.event_log.record(CtrlEvent::SnapshotTaken {
+                            server: s,
+                            new_start,
+                        })?;
 
                         snapshot_up_to.insert(s, new_start);
                         break;
@@ -571,6 +1480,479 @@ impl ClusterManager {
             .send_reply(CtrlReply::TakeSnapshot { snapshot_up_to }, client)
     }
 
+    /// Handler of client CoordSnapshot request. The reply is sent once
+    /// every active server has reported its snapshot at the marker (or left),
+    /// or with the snapshots reported so far once `COORD_SNAPSHOT_TIMEOUT`
+    /// has passed.
+    fn handle_client_coord_snapshot(
+        &mut self,
+        client: ClientId,
+    ) -> Result<(), SummersetError> {
+        // the commit index is agreed on through a marker entry proposed by
+        // the current leader
+        let leader = self
+            .servers_info
+            .iter()
+            .find(|(_, info)| info.is_leader)
+            .map(|(&s, _)| s);
+        if self.protocol != SmrProtocol::MultiPaxos
+            || leader.is_none()
+            || self.coord_snap.is_some()
+        {
+            pf_warn!("cannot take coordinated snapshot now");
+            return self
+                .client_reactor
+                .send_reply(CtrlReply::CoordSnapshot { result: None }, client);
+        }
+        let leader = leader.unwrap();
+
+        self.server_reigner
+            .send_ctrl(CtrlMsg::SnapshotMark, leader)?;
+        self.coord_snap = Some(CoordSnapWait {
+            client,
+            leader,
+            servers: self.servers_info.keys().copied().collect(),
+            mark_slot: None,
+            paths: HashMap::new(),
+            deadline: Instant::now() + COORD_SNAPSHOT_TIMEOUT,
+        });
+        Ok(())
+    }
+
+    /// Replies to the client of the ongoing coordinated snapshot with the
+    /// snapshots reported so far, or with `None` if rejected.
+    fn finish_coord_snapshot(
+        &mut self,
+        rejected: bool,
+    ) -> Result<(), SummersetError> {
+        let Some(wait) = self.coord_snap.take() else {
+            return Ok(());
+        };
+        let result = if rejected {
+            None
+        } else {
+            wait.mark_slot.map(|slot| (slot, wait.paths))
+        };
+        self.client_reactor
+            .send_reply(CtrlReply::CoordSnapshot { result }, wait.client)
+    }
+
+    /// Returns when the ongoing coordinated snapshot times out, or now if
+    /// none.
+    fn next_coord_snap_due(&self) -> Instant {
+        self.coord_snap
+            .as_ref()
+            .map_or_else(Instant::now, |wait| wait.deadline)
+    }
+
+    /// Handler of client ChangeMembers request.
+    async fn handle_client_change_members(
+        &mut self,
+        client: ClientId,
+        members: HashSet<ReplicaId>,
+    ) -> Result<(), SummersetError> {
+        // only protocols with stop-sign support can change membership, and
+        // the stop-sign must be proposed by the current leader
+        let leader = self
+            .servers_info
+            .iter()
+            .find(|(_, info)| info.is_leader)
+            .map(|(&s, _)| s);
+        let valid = self.protocol == SmrProtocol::MultiPaxos
+            && !members.is_empty()
+            && members.iter().all(|&s| s < self.population);
+        if !valid || leader.is_none() {
+            pf_warn!("cannot change members to {:?}", members);
+            return self
+                .client_reactor
+                .send_reply(CtrlReply::ChangeMembers { result: None }, client);
+        }
+        let leader = leader.unwrap();
+
+        // send stop-sign control message to leader
+        self.server_reigner.send_ctrl(
+            CtrlMsg::StopSign {
+                members: Bitmap::from((self.population, &members)),
+            },
+            leader,
+        )?;
+
+        // wait for the stop-sign to be committed or rejected, up to a timeout
+        // or until the leader is gone
+        let timeout = time::sleep(LEADER_CTRL_TIMEOUT);
+        tokio::pin!(timeout);
+        let result = loop {
+            tokio::select! {
+                ctrl_msg = self.server_reigner.recv_ctrl() => {
+                    let (server, reply) = ctrl_msg?;
+                    match reply {
+                        CtrlMsg::StopSignReject if server == leader => {
+                            break None;
+                        }
+
+                        CtrlMsg::MembersConf { members, slot } => {
+                            self.handle_members_conf(
+                                server,
+                                members.clone(),
+                                slot,
+                            )?;
+                            break Some((
+                                members
+                                    .iter()
+                                    .filter_map(|(s, flag)| flag.then_some(s))
+                                    .collect(),
+                                slot,
+                            ));
+                        }
+
+                        CtrlMsg::Leave | CtrlMsg::Disconnected
+                            if server == leader =>
+                        {
+                            // leader gone before the stop-sign committed
+                            self.handle_ctrl_msg(server, reply).await?;
+                            break None;
+                        }
+
+                        _ => self.handle_ctrl_msg(server, reply).await?,
+                    }
+                },
+
+                _ = &mut timeout => {
+                    pf_warn!("change of members to {:?} timed out", members);
+                    break None;
+                }
+            }
+        };
+
+        self.client_reactor
+            .send_reply(CtrlReply::ChangeMembers { result }, client)
+    }
+
+    /// Handler of client Reconfigure request.
+    async fn handle_client_reconfigure(
+        &mut self,
+        client: ClientId,
+        add: HashSet<ReplicaId>,
+        remove: HashSet<ReplicaId>,
+    ) -> Result<(), SummersetError> {
+        // the change is carried out by the current leader through the
+        // protocol's own reconfiguration entries
+        let leader = self
+            .servers_info
+            .iter()
+            .find(|(_, info)| info.is_leader)
+            .map(|(&s, _)| s);
+        // members are drawn from the population the cluster was started with;
+        // servers beyond it cannot be added, as the transport, heartbeats, and
+        // clients all work with a fixed population
+        if let Some(s) = add
+            .iter()
+            .chain(remove.iter())
+            .find(|&&s| s >= self.population)
+        {
+            pf_warn!(
+                "cannot reconfigure with server {} outside population {}",
+                s,
+                self.population
+            );
+            return self
+                .client_reactor
+                .send_reply(CtrlReply::Reconfigure { result: None }, client);
+        }
+        let valid = matches!(
+            self.protocol,
+            SmrProtocol::MultiPaxos | SmrProtocol::Raft
+        ) && (!add.is_empty() || !remove.is_empty())
+            && add.is_disjoint(&remove);
+        if !valid || leader.is_none() {
+            pf_warn!("cannot reconfigure +{:?} -{:?}", add, remove);
+            return self
+                .client_reactor
+                .send_reply(CtrlReply::Reconfigure { result: None }, client);
+        }
+        let leader = leader.unwrap();
+
+        self.server_reigner.send_ctrl(
+            CtrlMsg::Reconfigure {
+                add: Bitmap::from((self.population, &add)),
+                remove: Bitmap::from((self.population, &remove)),
+            },
+            leader,
+        )?;
+
+        // wait for the new configuration to take effect or be rejected, up to
+        // a timeout or until the leader is gone
+        let timeout = time::sleep(LEADER_CTRL_TIMEOUT);
+        tokio::pin!(timeout);
+        let result = loop {
+            tokio::select! {
+                ctrl_msg = self.server_reigner.recv_ctrl() => {
+                    let (server, reply) = ctrl_msg?;
+                    match reply {
+                        CtrlMsg::ReconfigureReject if server == leader => {
+                            break None;
+                        }
+
+                        CtrlMsg::MembersConf { members, slot } => {
+                            self.handle_members_conf(
+                                server,
+                                members.clone(),
+                                slot,
+                            )?;
+                            break Some((members.into(), slot));
+                        }
+
+                        CtrlMsg::Leave | CtrlMsg::Disconnected
+                            if server == leader =>
+                        {
+                            // leader gone before the change took effect
+                            self.handle_ctrl_msg(server, reply).await?;
+                            break None;
+                        }
+
+                        _ => self.handle_ctrl_msg(server, reply).await?,
+                    }
+                },
+
+                _ = &mut timeout => {
+                    pf_warn!("reconfigure +{:?} -{:?} timed out", add, remove);
+                    break None;
+                }
+            }
+        };
+
+        self.client_reactor
+            .send_reply(CtrlReply::Reconfigure { result }, client)
+    }
+
+    /// Handler of client TransferLeader request.
+    async fn handle_client_transfer_leader(
+        &mut self,
+        client: ClientId,
+        target: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        // only protocols with planned transfer support can do this, and the
+        // transfer must be initiated by the current leader
+        let leader = self
+            .servers_info
+            .iter()
+            .find(|(_, info)| info.is_leader)
+            .map(|(&s, _)| s);
+        let valid = matches!(
+            self.protocol,
+            SmrProtocol::MultiPaxos | SmrProtocol::Raft
+        ) && self.servers_info.contains_key(&target)
+            && leader.is_some_and(|l| l != target);
+        if !valid {
+            pf_warn!("cannot transfer leadership to {}", target);
+            return self.client_reactor.send_reply(
+                CtrlReply::TransferLeader { success: false },
+                client,
+            );
+        }
+        let leader = leader.unwrap();
+
+        // send transfer control message to leader
+        self.server_reigner
+            .send_ctrl(CtrlMsg::TransferLeader { target }, leader)?;
+
+        // wait for the target to step up or the leader to reject, up to a
+        // timeout
+        let timeout = time::sleep(LEADER_CTRL_TIMEOUT);
+        tokio::pin!(timeout);
+        let success = loop {
+            tokio::select! {
+                ctrl_msg = self.server_reigner.recv_ctrl() => {
+                    let (server, reply) = ctrl_msg?;
+                    match reply {
+                        CtrlMsg::TransferReject if server == leader => {
+                            break false;
+                        }
+
+                        CtrlMsg::LeaderStatus { step_up: true }
+                            if server == target =>
+                        {
+                            self.handle_ctrl_msg(server, reply).await?;
+                            break true;
+                        }
+
+                        _ => self.handle_ctrl_msg(server, reply).await?,
+                    }
+                },
+
+                _ = &mut timeout => {
+                    pf_warn!("leadership transfer to {} timed out", target);
+                    break false;
+                }
+            }
+        };
+
+        self.client_reactor
+            .send_reply(CtrlReply::TransferLeader { success }, client)
+    }
+
+    /// Handler of client StartChaos request. Rejected if a chaos run is
+    /// already ongoing.
+    fn handle_client_start_chaos(
+        &mut self,
+        client: ClientId,
+        plan: ChaosPlan,
+    ) -> Result<(), SummersetError> {
+        if self.chaos.is_some() {
+            pf_warn!("chaos mode is already on");
+            return self
+                .client_reactor
+                .send_reply(CtrlReply::StartChaos { schedule: None }, client);
+        }
+
+        let duration_ms = match plan {
+            ChaosPlan::Random(ref params) => params.duration_ms,
+            ChaosPlan::Replay(_) => 0,
+        };
+        let schedule = plan.schedule(self.population)?;
+        pf_warn!("chaos mode on: {} steps", schedule.len());
+        self.event_log.record(CtrlEvent::ChaosStarted {
+            steps: schedule.len(),
+        })?;
+        self.chaos = Some(ChaosRun::new(schedule.clone(), duration_ms));
+
+        self.client_reactor.send_reply(
+            CtrlReply::StartChaos {
+                schedule: Some(schedule),
+            },
+            client,
+        )
+    }
+
+    /// Handler of client StopChaos request.
+    fn handle_client_stop_chaos(
+        &mut self,
+        client: ClientId,
+    ) -> Result<(), SummersetError> {
+        if self.chaos.is_some() {
+            self.end_chaos()?;
+        }
+        self.client_reactor.send_reply(CtrlReply::StopChaos, client)
+    }
+
+    /// Handler of client SplitRange and MergeRange requests. The change is
+    /// checked against my view of the responders config, then sent to the
+    /// leader tagged with that view's config number as epoch. The leader
+    /// rejects it if its config has moved on since, so a change computed
+    /// against a stale layout never takes effect; the leader announcing the
+    /// new config revokes old config leases, fencing reads in flight.
+    async fn handle_client_range_change(
+        &mut self,
+        client: ClientId,
+        req: CtrlRequest,
+    ) -> Result<(), SummersetError> {
+        let is_split = matches!(req, CtrlRequest::SplitRange { .. });
+        let make_reply = |conf_num| {
+            if is_split {
+                CtrlReply::SplitRange { conf_num }
+            } else {
+                CtrlReply::MergeRange { conf_num }
+            }
+        };
+
+        // only protocols with keys-ranged responders can do this, and the
+        // change must be applied by the current leader
+        let leader = self
+            .servers_info
+            .iter()
+            .find(|(_, info)| info.is_leader)
+            .map(|(&s, _)| s);
+        let epoch = self.latest_conf_num;
+        let mut check_conf = self.responders_conf.clone();
+        let (checked, msg) = match req {
+            CtrlRequest::SplitRange {
+                range,
+                at,
+                responders,
+            } => (
+                responders.size() == self.population
+                    && check_conf
+                        .split_range(&range, &at, responders.clone())
+                        .is_ok(),
+                CtrlMsg::SplitRange {
+                    epoch,
+                    range,
+                    at,
+                    responders,
+                },
+            ),
+            CtrlRequest::MergeRange {
+                left,
+                right,
+                responders,
+            } => (
+                responders
+                    .as_ref()
+                    .is_none_or(|r| r.size() == self.population)
+                    && check_conf
+                        .merge_ranges(&left, &right, responders.clone())
+                        .is_ok(),
+                CtrlMsg::MergeRange {
+                    epoch,
+                    left,
+                    right,
+                    responders,
+                },
+            ),
+            _ => return logged_err!("unexpected range change req {:?}", req),
+        };
+        if self.protocol != SmrProtocol::Bodega || !checked || leader.is_none()
+        {
+            pf_warn!("cannot apply range change {:?}", msg);
+            return self.client_reactor.send_reply(make_reply(None), client);
+        }
+        let leader = leader.unwrap();
+
+        // send range change control message to leader
+        self.server_reigner.send_ctrl(msg, leader)?;
+
+        // wait for the new config to be announced or the leader to reject, up
+        // to a timeout or until the leader is gone
+        let timeout = time::sleep(LEADER_CTRL_TIMEOUT);
+        tokio::pin!(timeout);
+        let conf_num = loop {
+            tokio::select! {
+                ctrl_msg = self.server_reigner.recv_ctrl() => {
+                    let (server, reply) = ctrl_msg?;
+                    match reply {
+                        CtrlMsg::RangeReject if server == leader => break None,
+
+                        CtrlMsg::RespondersConf { conf_num, new_conf }
+                            if server == leader =>
+                        {
+                            self.handle_responders_conf(
+                                server, conf_num, new_conf,
+                            )?;
+                            break Some(conf_num);
+                        }
+
+                        CtrlMsg::Leave | CtrlMsg::Disconnected
+                            if server == leader =>
+                        {
+                            // leader gone before announcing the new config
+                            self.handle_ctrl_msg(server, reply).await?;
+                            break None;
+                        }
+
+                        _ => self.handle_ctrl_msg(server, reply).await?,
+                    }
+                },
+
+                _ = &mut timeout => {
+                    pf_warn!("range change at epoch {} timed out", epoch);
+                    break None;
+                }
+            }
+        };
+
+        self.client_reactor.send_reply(make_reply(conf_num), client)
+    }
+
     /// Synthesized handler of client-initiated control requests.
     async fn handle_ctrl_req(
         &mut self,
@@ -586,6 +1968,22 @@ impl ClusterManager {
                 self.handle_client_query_conf(client)?;
             }
 
+            CtrlRequest::QueryStatus => {
+                self.handle_client_query_status(client)?;
+            }
+
+            CtrlRequest::QueryEvents { since } => {
+                self.handle_client_query_events(client, since)?;
+            }
+
+            CtrlRequest::Subscribe { since } => {
+                self.handle_client_subscribe(client, since)?;
+            }
+
+            CtrlRequest::WatchResets => {
+                self.handle_client_watch_resets(client)?;
+            }
+
             CtrlRequest::ResetServers { servers, durable } => {
                 self.handle_client_reset_servers(client, servers, durable)
                     .await?;
@@ -603,9 +2001,181 @@ impl ClusterManager {
                 self.handle_client_take_snapshot(client, servers).await?;
             }
 
+            CtrlRequest::CoordSnapshot => {
+                self.handle_client_coord_snapshot(client)?;
+            }
+
+            CtrlRequest::SetLogFilters { servers, filters } => {
+                self.handle_client_set_log_filters(client, servers, filters)
+                    .await?;
+            }
+
+            CtrlRequest::QueryKeys {
+                server,
+                cursor,
+                limit,
+            } => {
+                self.handle_client_query_keys(client, server, cursor, limit)
+                    .await?;
+            }
+
+            CtrlRequest::ChangeMembers { members } => {
+                self.handle_client_change_members(client, members).await?;
+            }
+
+            CtrlRequest::Reconfigure { add, remove } => {
+                self.handle_client_reconfigure(client, add, remove).await?;
+            }
+
+            CtrlRequest::TransferLeader { target } => {
+                self.handle_client_transfer_leader(client, target).await?;
+            }
+
+            CtrlRequest::StartChaos { plan } => {
+                self.handle_client_start_chaos(client, plan)?;
+            }
+
+            CtrlRequest::StopChaos => {
+                self.handle_client_stop_chaos(client)?;
+            }
+
+            CtrlRequest::SplitRange { .. } | CtrlRequest::MergeRange { .. } => {
+                self.handle_client_range_change(client, req).await?;
+            }
+
             _ => {} // ignore all other types
         }
 
         Ok(())
     }
 }
+
+// ClusterManager chaos mode actions
+impl ClusterManager {
+    /// Time the ongoing chaos run next needs attention.
+    fn next_chaos_due(&self) -> Instant {
+        self.chaos
+            .as_ref()
+            .map_or_else(Instant::now, ChaosRun::next_due)
+    }
+
+    /// Sets the network faults of all active servers, each blocking its
+    /// peers in the given map.
+    fn set_net_faults(
+        &mut self,
+        mut blocked: HashMap<ReplicaId, HashSet<ReplicaId>>,
+        delayed: Option<(ReplicaId, u64)>,
+    ) -> Result<(), SummersetError> {
+        let servers: Vec<ReplicaId> =
+            self.servers_info.keys().copied().collect();
+        for s in servers {
+            let delay_ms = match delayed {
+                Some((server, delay_ms)) if server == s => delay_ms,
+                _ => 0,
+            };
+            self.server_reigner.send_ctrl(
+                CtrlMsg::NetFaults {
+                    blocked: blocked.remove(&s).unwrap_or_default(),
+                    delay_ms,
+                },
+                s,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Heals all network faults and ends the ongoing chaos run.
+    fn end_chaos(&mut self) -> Result<(), SummersetError> {
+        self.chaos = None;
+        self.set_net_faults(HashMap::new(), None)?;
+        pf_warn!("chaos mode off");
+        self.event_log.record(CtrlEvent::ChaosEnded)
+    }
+
+    /// Takes all due steps of the ongoing chaos run, ending it if finished.
+    /// Steps that do not apply to the cluster at the time are skipped.
+    async fn step_chaos(&mut self) -> Result<(), SummersetError> {
+        while let Some(step) = self.chaos.as_mut().and_then(ChaosRun::pop_due) {
+            let taken = match step.action {
+                ChaosAction::Crash { server } => {
+                    if self.servers_info.contains_key(&server) {
+                        self.reset_servers(HashSet::from([server]), true)
+                            .await?;
+                        true
+                    } else {
+                        false
+                    }
+                }
+
+                ChaosAction::Partition { ref side } => {
+                    // each server blocks everyone on the other side
+                    let blocked = self
+                        .servers_info
+                        .keys()
+                        .map(|&s| {
+                            let in_side = side.contains(&s);
+                            let others = (0..self.population)
+                                .filter(|p| side.contains(p) != in_side)
+                                .collect();
+                            (s, others)
+                        })
+                        .collect();
+                    self.set_net_faults(blocked, None)?;
+                    true
+                }
+
+                ChaosAction::Delay { server, delay_ms } => {
+                    if self.servers_info.contains_key(&server) {
+                        self.set_net_faults(
+                            HashMap::new(),
+                            Some((server, delay_ms)),
+                        )?;
+                        true
+                    } else {
+                        false
+                    }
+                }
+
+                ChaosAction::Heal => {
+                    self.set_net_faults(HashMap::new(), None)?;
+                    true
+                }
+
+                ChaosAction::TransferLeader { target } => {
+                    // fire and forget; replies are handled by the main loop
+                    let leader = self
+                        .servers_info
+                        .iter()
+                        .find(|(_, info)| info.is_leader)
+                        .map(|(&s, _)| s);
+                    match leader {
+                        Some(leader)
+                            if matches!(
+                                self.protocol,
+                                SmrProtocol::MultiPaxos | SmrProtocol::Raft
+                            ) && leader != target
+                                && self.servers_info.contains_key(&target) =>
+                        {
+                            self.server_reigner.send_ctrl(
+                                CtrlMsg::TransferLeader { target },
+                                leader,
+                            )?;
+                            true
+                        }
+                        _ => false,
+                    }
+                }
+            };
+
+            if taken {
+                pf_info!("chaos step @ {} ms: {:?}", step.at_ms, step.action);
+                self.event_log.record(CtrlEvent::ChaosStepTaken { step })?;
+            }
+        }
+
+        if self.chaos.as_ref().is_some_and(ChaosRun::finished) {
+            self.end_chaos()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,370 @@
+//! Cluster manager optional HTTP dashboard module implementation.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+
+use crate::manager::{CtrlEventRecord, ServerInfo, ServerStatus};
+use crate::server::ReplicaId;
+use crate::utils::{tcp_bind_with_retry, SummersetError};
+
+use serde::Serialize;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{self, Duration, Instant};
+
+/// Static dashboard page, which polls the `/status` endpoint for updates.
+const DASHBOARD_HTML: &str = include_str!("dashboard.html");
+
+/// Number of throughput samples kept per server for the sparkline graphs.
+const TPUT_HISTORY_LEN: usize = 60;
+
+/// Snapshot of cluster state published by the manager to the dashboard.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ClusterSnapshot {
+    /// Total number of server replicas in cluster.
+    pub(crate) population: u16,
+
+    /// Information of current active servers.
+    pub(crate) servers_info: HashMap<ReplicaId, ServerInfo>,
+
+    /// Latest status reported by each active server.
+    pub(crate) servers_status: HashMap<ReplicaId, ServerStatus>,
+
+    /// Time when each active server's latest status was received.
+    pub(crate) report_times: HashMap<ReplicaId, Instant>,
+
+    /// Most recent control-plane events, oldest first.
+    pub(crate) recent_events: Vec<CtrlEventRecord>,
+}
+
+/// Per-server entry of the dashboard `/status` JSON response.
+#[derive(Debug, Serialize)]
+struct ServerView<'s> {
+    id: ReplicaId,
+    info: &'s ServerInfo,
+    status: Option<&'s ServerStatus>,
+    /// Peers this server considers alive, flattened for easy rendering.
+    peers_alive: Vec<ReplicaId>,
+    /// Milliseconds since the last status report, if any.
+    report_age_ms: Option<u64>,
+    /// Recent executed slots per second, oldest first.
+    tput_history: Vec<f64>,
+}
+
+/// Dashboard `/status` JSON response.
+#[derive(Debug, Serialize)]
+struct ClusterView<'s> {
+    population: u16,
+    servers: Vec<ServerView<'s>>,
+    events: &'s [CtrlEventRecord],
+}
+
+/// The manager HTTP dashboard module.
+pub(crate) struct Dashboard {
+    /// Sender side of the cluster snapshot watch channel.
+    tx_snapshot: watch::Sender<ClusterSnapshot>,
+
+    /// Join handle of the dashboard server task.
+    _server_handle: JoinHandle<()>,
+}
+
+// Dashboard public API implementation
+impl Dashboard {
+    /// Creates a new dashboard module serving HTTP on `http_addr`. Spawns the
+    /// dashboard server task.
+    pub(crate) async fn new_and_setup(
+        http_addr: SocketAddr,
+    ) -> Result<Self, SummersetError> {
+        let (tx_snapshot, rx_snapshot) =
+            watch::channel(ClusterSnapshot::default());
+
+        let http_listener = tcp_bind_with_retry(http_addr, 15).await?;
+        let mut server = DashboardServerTask::new(http_listener, rx_snapshot);
+        let server_handle = tokio::spawn(async move { server.run().await });
+
+        Ok(Dashboard {
+            tx_snapshot,
+            _server_handle: server_handle,
+        })
+    }
+
+    /// Publishes the latest cluster state to the dashboard.
+    pub(crate) fn publish(
+        &mut self,
+        snapshot: ClusterSnapshot,
+    ) -> Result<(), SummersetError> {
+        self.tx_snapshot.send(snapshot)?;
+        Ok(())
+    }
+}
+
+/// Dashboard HTTP server task.
+struct DashboardServerTask {
+    http_listener: TcpListener,
+    rx_snapshot: watch::Receiver<ClusterSnapshot>,
+
+    /// Map from server ID -> recent (time, exec_bar) samples.
+    exec_samples: HashMap<ReplicaId, VecDeque<(Instant, usize)>>,
+}
+
+impl DashboardServerTask {
+    /// Creates the dashboard server task.
+    fn new(
+        http_listener: TcpListener,
+        rx_snapshot: watch::Receiver<ClusterSnapshot>,
+    ) -> Self {
+        DashboardServerTask {
+            http_listener,
+            rx_snapshot,
+            exec_samples: HashMap::new(),
+        }
+    }
+
+    /// Records throughput samples from a newly published snapshot.
+    fn record_samples(&mut self) {
+        let snapshot = self.rx_snapshot.borrow_and_update();
+
+        // forget servers that are no longer active
+        self.exec_samples
+            .retain(|s, _| snapshot.servers_info.contains_key(s));
+
+        for (&server, status) in &snapshot.servers_status {
+            let Some(&reported) = snapshot.report_times.get(&server) else {
+                continue;
+            };
+            let samples = self.exec_samples.entry(server).or_default();
+            if samples.back().is_some_and(|&(t, _)| t == reported) {
+                continue; // nothing new from this server
+            }
+            samples.push_back((reported, status.exec_bar));
+            while samples.len() > TPUT_HISTORY_LEN + 1 {
+                samples.pop_front();
+            }
+        }
+    }
+
+    /// Renders the current cluster view as a JSON string.
+    fn render_status(&self) -> Result<String, SummersetError> {
+        let now = Instant::now();
+        let snapshot = self.rx_snapshot.borrow();
+
+        let mut servers: Vec<ServerView> = snapshot
+            .servers_info
+            .iter()
+            .map(|(&id, info)| {
+                let tput_history =
+                    self.exec_samples.get(&id).map_or(vec![], |samples| {
+                        samples
+                            .iter()
+                            .zip(samples.iter().skip(1))
+                            .map(|(&(t0, e0), &(t1, e1))| {
+                                let secs = t1.duration_since(t0).as_secs_f64();
+                                if secs > 0.0 {
+                                    e1.saturating_sub(e0) as f64 / secs
+                                } else {
+                                    0.0
+                                }
+                            })
+                            .collect()
+                    });
+                let status = snapshot.servers_status.get(&id);
+                ServerView {
+                    id,
+                    info,
+                    status,
+                    peers_alive: status.map_or(vec![], |st| {
+                        st.peers_alive
+                            .iter()
+                            .filter_map(|(p, alive)| alive.then_some(p))
+                            .collect()
+                    }),
+                    report_age_ms: snapshot
+                        .report_times
+                        .get(&id)
+                        .map(|t| now.duration_since(*t).as_millis() as u64),
+                    tput_history,
+                }
+            })
+            .collect();
+        servers.sort_by_key(|v| v.id);
+
+        Ok(serde_json::to_string(&ClusterView {
+            population: snapshot.population,
+            servers,
+            events: &snapshot.recent_events,
+        })?)
+    }
+
+    /// Serves one HTTP request on an accepted connection. Only `GET /` and
+    /// `GET /status` are supported.
+    async fn serve_request(
+        mut stream: TcpStream,
+        status_json: String,
+    ) -> Result<(), SummersetError> {
+        // read until end of request headers; bodies are never expected
+        let mut buf = Vec::with_capacity(1024);
+        let mut chunk = [0u8; 1024];
+        while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+            let n = stream.read(&mut chunk).await?;
+            if n == 0 || buf.len() > 16 * 1024 {
+                return logged_err!("malformed HTTP request");
+            }
+            buf.extend_from_slice(&chunk[..n]);
+        }
+
+        let request = String::from_utf8_lossy(&buf);
+        let path = request
+            .lines()
+            .next()
+            .and_then(|line| {
+                let mut parts = line.split_whitespace();
+                match (parts.next(), parts.next()) {
+                    (Some("GET"), Some(path)) => Some(path),
+                    _ => None,
+                }
+            })
+            .unwrap_or("");
+
+        let (code, content_type, body) = match path {
+            "/" | "/index.html" => {
+                ("200 OK", "text/html", DASHBOARD_HTML.to_string())
+            }
+            "/status" => ("200 OK", "application/json", status_json),
+            _ => ("404 Not Found", "text/plain", "not found".into()),
+        };
+
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}; charset=utf-8\r\n\
+             Content-Length: {}\r\nCache-Control: no-store\r\n\
+             Connection: close\r\n\r\n{}",
+            code,
+            content_type,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await?;
+        Ok(())
+    }
+
+    /// Starts the dashboard server task loop.
+    async fn run(&mut self) {
+        pf_debug!("dashboard_server task spawned");
+
+        let local_addr = self.http_listener.local_addr().unwrap();
+        pf_info!("serving dashboard on 'http://{}'", local_addr);
+
+        loop {
+            tokio::select! {
+                // new cluster state published by the manager
+                changed = self.rx_snapshot.changed() => {
+                    if changed.is_err() {
+                        break; // manager is gone
+                    }
+                    self.record_samples();
+                },
+
+                // new HTTP connection
+                accepted = self.http_listener.accept() => {
+                    if let Err(e) = accepted {
+                        pf_warn!("error accepting dashboard connection: {}", e);
+                        continue;
+                    }
+                    let (stream, addr) = accepted.unwrap();
+                    let status_json = match self.render_status() {
+                        Ok(json) => json,
+                        Err(e) => {
+                            pf_error!("error rendering cluster status: {}", e);
+                            continue;
+                        }
+                    };
+                    tokio::spawn(async move {
+                        match time::timeout(
+                            Duration::from_secs(5),
+                            Self::serve_request(stream, status_json),
+                        )
+                        .await
+                        {
+                            Ok(Err(e)) => pf_debug!(
+                                "error serving dashboard '{}': {}", addr, e
+                            ),
+                            Err(_) => pf_debug!(
+                                "timed out serving dashboard '{}'", addr
+                            ),
+                            Ok(Ok(())) => {}
+                        }
+                    });
+                },
+            }
+        }
+
+        pf_debug!("dashboard_server task exited");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::CtrlEvent;
+    use crate::server::ResourceUsage;
+    use crate::utils::Bitmap;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn dashboard_status() -> Result<(), SummersetError> {
+        let mut dashboard =
+            Dashboard::new_and_setup("127.0.0.1:30319".parse()?).await?;
+        dashboard.publish(ClusterSnapshot {
+            population: 1,
+            servers_info: HashMap::from([(
+                0,
+                ServerInfo {
+                    api_addr: "127.0.0.1:31100".parse()?,
+                    p2p_addr: "127.0.0.1:31200".parse()?,
+                    is_leader: true,
+                    is_paused: false,
+                    start_slot: 0,
+                },
+            )]),
+            servers_status: HashMap::from([(
+                0,
+                ServerStatus {
+                    ballot: 7,
+                    commit_bar: 3,
+                    exec_bar: 2,
+                    peers_alive: Bitmap::new(1, true),
+                    perf_models: HashMap::new(),
+                    hot_keys: vec![],
+                    queue_depths: vec![],
+                    resources: ResourceUsage::default(),
+                    divergence_repaired: 0,
+                },
+            )]),
+            report_times: HashMap::from([(0, Instant::now())]),
+            recent_events: vec![CtrlEventRecord {
+                seq: 0,
+                unix_ms: 0,
+                event: CtrlEvent::LeaderStepUp { server: 0 },
+            }],
+        })?;
+        time::sleep(Duration::from_millis(100)).await;
+
+        let mut stream = TcpStream::connect("127.0.0.1:30319").await?;
+        stream.write_all(b"GET /status HTTP/1.1\r\n\r\n").await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("\"ballot\":7"));
+        assert!(response.contains("\"is_leader\":true"));
+        assert!(response.contains("\"LeaderStepUp\":{\"server\":0}"));
+
+        let mut stream = TcpStream::connect("127.0.0.1:30319").await?;
+        stream.write_all(b"GET /nothing HTTP/1.1\r\n\r\n").await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        assert!(response.starts_with("HTTP/1.1 404"));
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+//! Cluster manager control-plane event log implementation.
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::manager::ChaosStep;
+use crate::server::ReplicaId;
+use crate::utils::{Bitmap, ConfNum, SummersetError};
+
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of most recent events kept in memory for queries.
+const MAX_EVENTS_IN_MEM: usize = 10000;
+
+/// Control-plane event observed by the manager.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum CtrlEvent {
+    /// A server joined the cluster.
+    ServerJoined { server: ReplicaId },
+
+    /// A server left the cluster gracefully.
+    ServerLeft { server: ReplicaId },
+
+    /// A server's control connection dropped ungracefully (likely crashed).
+    ServerCrashed { server: ReplicaId },
+
+    /// A server stepped up as leader.
+    LeaderStepUp { server: ReplicaId },
+
+    /// A server stepped down from leader.
+    LeaderStepDown { server: ReplicaId },
+
+    /// A server reported a new responders configuration.
+    RespondersConf {
+        server: ReplicaId,
+        conf_num: ConfNum,
+    },
+
+    /// A server was told to reset to initial state.
+    ServerReset { server: ReplicaId, durable: bool },
+
+    /// A server was paused.
+    ServerPaused { server: ReplicaId },
+
+    /// A server was resumed.
+    ServerResumed { server: ReplicaId },
+
+    /// A server took a snapshot up to a new log start index.
+    SnapshotTaken { server: ReplicaId, new_start: usize },
+
+    /// A server took a coordinated snapshot at the marker entry at slot.
+    CoordSnapshotTaken { server: ReplicaId, slot: usize },
+
+    /// The manager as Vertical Paxos configuration master granted a ballot
+    /// epoch to a new leader with a new set of voting members.
+    BallotGranted {
+        server: ReplicaId,
+        epoch: u64,
+        members: Bitmap,
+    },
+
+    /// A server's granted Vertical Paxos ballot epoch became active.
+    BallotActivated { server: ReplicaId, epoch: u64 },
+
+    /// A server committed a stop-sign entry at slot, starting a new
+    /// configuration of voting members.
+    MembersChanged {
+        server: ReplicaId,
+        members: Bitmap,
+        slot: usize,
+    },
+
+    /// A server reported an interval during which it believed it held a
+    /// majority lease, in millisecs since UNIX epoch.
+    LeaseHeld {
+        server: ReplicaId,
+        lease_num: u64,
+        start_ms: u64,
+        end_ms: u64,
+    },
+
+    /// Two servers' majority lease intervals overlapped in time, which
+    /// indicates a lease safety violation (or badly skewed clocks).
+    LeaseOverlap {
+        server: ReplicaId,
+        other: ReplicaId,
+        start_ms: u64,
+        end_ms: u64,
+    },
+
+    /// Chaos mode started with a fault schedule of the given length.
+    ChaosStarted { steps: usize },
+
+    /// Chaos mode took a step of its fault schedule. Steps skipped for not
+    /// applying to the cluster at the time, e.g., a crash of a server that
+    /// is not up, are not recorded.
+    ChaosStepTaken { step: ChaosStep },
+
+    /// Chaos mode ended and all network faults were healed.
+    ChaosEnded,
+}
+
+/// A timestamped entry of the control-plane event log.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct CtrlEventRecord {
+    /// Sequence number of this event, starting from 0.
+    pub seq: u64,
+
+    /// Wall-clock time of this event in millisecs since UNIX epoch.
+    pub unix_ms: u64,
+
+    /// The event itself.
+    pub event: CtrlEvent,
+}
+
+/// The manager's control-plane event log. Keeps the most recent events in
+/// memory for queries, and optionally appends every event as a JSON line to
+/// a persistent file for post-hoc analysis.
+pub(crate) struct EventLog {
+    /// Sequence number to assign to the next event.
+    next_seq: u64,
+
+    /// Most recent events, oldest first.
+    records: VecDeque<CtrlEventRecord>,
+
+    /// Optional persistent log file writer.
+    file: Option<BufWriter<File>>,
+}
+
+impl EventLog {
+    /// Creates a new event log, appending to the file at `path` if given.
+    pub(crate) fn new(path: Option<&Path>) -> Result<Self, SummersetError> {
+        let file = match path {
+            Some(path) => {
+                let file =
+                    OpenOptions::new().create(true).append(true).open(path)?;
+                pf_info!("persisting control events to '{}'", path.display());
+                Some(BufWriter::new(file))
+            }
+            None => None,
+        };
+
+        Ok(EventLog {
+            next_seq: 0,
+            records: VecDeque::new(),
+            file,
+        })
+    }
+
+    /// Records a new event.
+    pub(crate) fn record(
+        &mut self,
+        event: CtrlEvent,
+    ) -> Result<(), SummersetError> {
+        let unix_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(SummersetError::msg)?
+            .as_millis() as u64;
+        let record = CtrlEventRecord {
+            seq: self.next_seq,
+            unix_ms,
+            event,
+        };
+        self.next_seq += 1;
+        pf_debug!("control event {:?}", record.event);
+
+        if let Some(file) = self.file.as_mut() {
+            serde_json::to_writer(&mut *file, &record)?;
+            file.write_all(b"\n")?;
+            file.flush()?;
+        }
+
+        self.records.push_back(record);
+        while self.records.len() > MAX_EVENTS_IN_MEM {
+            self.records.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Returns the sequence number to be assigned to the next event.
+    #[inline]
+    pub(crate) fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Returns in-memory events with sequence number >= `since`.
+    pub(crate) fn since(&self, since: u64) -> Vec<CtrlEventRecord> {
+        self.records
+            .iter()
+            .filter(|r| r.seq >= since)
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the last `n` in-memory events.
+    pub(crate) fn latest(&self, n: usize) -> Vec<CtrlEventRecord> {
+        self.records
+            .iter()
+            .skip(self.records.len().saturating_sub(n))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn record_and_query() -> Result<(), SummersetError> {
+        let mut log = EventLog::new(None)?;
+        log.record(CtrlEvent::ServerJoined { server: 0 })?;
+        log.record(CtrlEvent::LeaderStepUp { server: 0 })?;
+        log.record(CtrlEvent::ServerCrashed { server: 0 })?;
+        assert_eq!(log.since(0).len(), 3);
+        let events = log.since(1);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].seq, 1);
+        assert_eq!(events[0].event, CtrlEvent::LeaderStepUp { server: 0 });
+        assert!(log.since(3).is_empty());
+        assert_eq!(log.latest(1)[0].seq, 2);
+        assert_eq!(log.latest(5).len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn persist_to_file() -> Result<(), SummersetError> {
+        let path = Path::new("/tmp/test-manager-events.log");
+        let _ = fs::remove_file(path);
+        {
+            let mut log = EventLog::new(Some(path))?;
+            log.record(CtrlEvent::ServerReset {
+                server: 1,
+                durable: true,
+            })?;
+            log.record(CtrlEvent::SnapshotTaken {
+                server: 1,
+                new_start: 7,
+            })?;
+        }
+        let content = fs::read_to_string(path)?;
+        let records: Vec<CtrlEventRecord> = content
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[1].event,
+            CtrlEvent::SnapshotTaken {
+                server: 1,
+                new_start: 7
+            }
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+//! Cluster manager cross-checking of lease holding intervals reported by
+//! servers in lease diagnostics mode.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::server::ReplicaId;
+
+/// Maximum number of most recent intervals kept per server for checking.
+const MAX_INTERVALS_PER_SERVER: usize = 100;
+
+/// An interval during which a server believed it held a majority lease.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) struct LeaseInterval {
+    /// Start wall-clock time in millisecs since UNIX epoch (inclusive).
+    pub(crate) start_ms: u64,
+
+    /// End wall-clock time in millisecs since UNIX epoch (exclusive).
+    pub(crate) end_ms: u64,
+}
+
+/// Online checker that no two servers' reported majority lease intervals
+/// overlap. Only the most recent intervals of each server are remembered;
+/// the full history can be checked offline from the persisted event log.
+#[derive(Debug, Default)]
+pub(crate) struct LeaseChecker {
+    /// Map from server ID -> its most recent reported intervals.
+    intervals: HashMap<ReplicaId, VecDeque<LeaseInterval>>,
+}
+
+impl LeaseChecker {
+    /// Creates a new empty checker.
+    pub(crate) fn new() -> Self {
+        LeaseChecker::default()
+    }
+
+    /// Adds a new interval reported by `server`, returning the list of
+    /// other servers whose remembered intervals overlap with it, each with
+    /// the overlapping part as `(start_ms, end_ms)`.
+    pub(crate) fn add(
+        &mut self,
+        server: ReplicaId,
+        interval: LeaseInterval,
+    ) -> Vec<(ReplicaId, u64, u64)> {
+        let mut overlaps = vec![];
+        for (&other, others) in &self.intervals {
+            if other == server {
+                continue;
+            }
+            for prev in others {
+                let start_ms = interval.start_ms.max(prev.start_ms);
+                let end_ms = interval.end_ms.min(prev.end_ms);
+                if start_ms < end_ms {
+                    overlaps.push((other, start_ms, end_ms));
+                }
+            }
+        }
+        overlaps.sort_unstable();
+
+        let mine = self.intervals.entry(server).or_default();
+        mine.push_back(interval);
+        while mine.len() > MAX_INTERVALS_PER_SERVER {
+            mine.pop_front();
+        }
+        overlaps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interval(start_ms: u64, end_ms: u64) -> LeaseInterval {
+        LeaseInterval { start_ms, end_ms }
+    }
+
+    #[test]
+    fn disjoint_intervals() {
+        let mut checker = LeaseChecker::new();
+        assert!(checker.add(0, interval(100, 200)).is_empty());
+        assert!(checker.add(0, interval(150, 300)).is_empty());
+        assert!(checker.add(1, interval(300, 400)).is_empty());
+        assert!(checker.add(2, interval(50, 100)).is_empty());
+    }
+
+    #[test]
+    fn overlapping_intervals() {
+        let mut checker = LeaseChecker::new();
+        assert!(checker.add(0, interval(100, 200)).is_empty());
+        assert!(checker.add(1, interval(250, 300)).is_empty());
+        assert_eq!(
+            checker.add(2, interval(180, 260)),
+            vec![(0, 180, 200), (1, 250, 260)]
+        );
+        assert_eq!(
+            checker.add(0, interval(255, 270)),
+            vec![(1, 255, 270), (2, 255, 260)]
+        );
+    }
+}
@@ -2,11 +2,21 @@
 
 mod clusman;
 
+mod chaos;
+mod dashboard;
+mod eventlog;
+mod leasecheck;
 mod reactor;
 mod reigner;
 
-pub use clusman::{ClusterManager, ServerInfo};
+pub use chaos::{ChaosAction, ChaosParams, ChaosPlan, ChaosStep};
+pub use clusman::{ClusterManager, ServerInfo, ServerStatus};
+pub use eventlog::{CtrlEvent, CtrlEventRecord};
 pub use reactor::{CtrlReply, CtrlRequest};
 
-pub(crate) use reactor::ClientReactor;
+pub(crate) use chaos::ChaosRun;
+pub(crate) use dashboard::{ClusterSnapshot, Dashboard};
+pub(crate) use eventlog::EventLog;
+pub(crate) use leasecheck::{LeaseChecker, LeaseInterval};
+pub(crate) use reactor::{ClientEvent, ClientReactor};
 pub(crate) use reigner::{CtrlMsg, ServerReigner};
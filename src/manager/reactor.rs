@@ -4,22 +4,25 @@ use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
 
 use crate::client::ClientId;
-use crate::manager::ServerInfo;
-use crate::server::ReplicaId;
+use crate::manager::{
+    ChaosPlan, ChaosStep, CtrlEventRecord, ServerInfo, ServerStatus,
+};
+use crate::server::{KeysReport, ReplicaId};
 use crate::utils::{
-    safe_tcp_read, safe_tcp_write, tcp_bind_with_retry, ConfNum,
-    RespondersConf, SummersetError,
+    exchange_codec, safe_tcp_read, safe_tcp_write, tcp_bind_with_retry, Bitmap,
+    ConfNum, RespondersConf, SummersetError,
 };
 
 use bytes::BytesMut;
 
 use serde::{Deserialize, Serialize};
 
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
+use tokio::time::{self, Duration};
 
 /// Control event request from client.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
@@ -30,7 +33,38 @@ pub enum CtrlRequest {
     /// Query the approximate current responders configuration.
     QueryConf,
 
-    /// Reset the specified server(s) to initial state.
+    /// Query the latest status reported by each active server.
+    QueryStatus,
+
+    /// Query the control-plane event log.
+    QueryEvents {
+        /// Only return events with sequence number >= this.
+        since: u64,
+    },
+
+    /// Subscribe to the control-plane event stream. Events since `since`
+    /// are replied right away, and every later event is pushed as a
+    /// `CtrlReply::Events` as it happens, until the connection ends. A
+    /// subscribed connection should be dedicated to receiving events.
+    Subscribe {
+        /// Only return past events with sequence number >= this.
+        since: u64,
+    },
+
+    /// Watch for cluster resets. The manager then pushes a
+    /// `CtrlReply::ResetBegin` before resetting any server and waits for a
+    /// `ResetQuiesced` back, then pushes a `CtrlReply::ResetDone` once the
+    /// cluster is ready to serve again. A watching connection should be
+    /// dedicated to reset notices.
+    WatchResets,
+
+    /// Acknowledgement of a `CtrlReply::ResetBegin`, telling that the client
+    /// has stopped talking to the servers.
+    ResetQuiesced,
+
+    /// Reset the specified server(s) to initial state. Replied once the
+    /// servers have rejoined and, for leader-based protocols, a leader is
+    /// known.
     ResetServers {
         /// IDs of servers to reset. If empty, resets all active servers.
         servers: HashSet<ReplicaId>,
@@ -56,6 +90,87 @@ pub enum CtrlRequest {
         servers: HashSet<ReplicaId>,
     },
 
+    /// Tell all servers to snapshot their state at the same commit index,
+    /// agreed on through a marker entry in the replicated log. (only used by
+    /// relevant protocols)
+    CoordSnapshot,
+
+    /// Change the set of voting members through a stop-sign entry in the
+    /// replicated log. (only used by relevant protocols)
+    ChangeMembers {
+        /// IDs of servers in the new configuration; must be non-empty.
+        members: HashSet<ReplicaId>,
+    },
+
+    /// Add and remove voting members of a live cluster, through joint
+    /// consensus in Raft or a stop-sign entry in MultiPaxos. Servers not in
+    /// the configuration keep learning the log. All servers must be within
+    /// the population the cluster was started with; provisioning new server
+    /// IDs at runtime is not supported. (only used by relevant protocols)
+    Reconfigure {
+        /// IDs of servers to become voting members.
+        add: HashSet<ReplicaId>,
+        /// IDs of servers to stop being voting members.
+        remove: HashSet<ReplicaId>,
+    },
+
+    /// Hand leadership over from the current leader to the target server in
+    /// a planned way. (only used by relevant protocols)
+    TransferLeader {
+        /// ID of the server to become the new leader.
+        target: ReplicaId,
+    },
+
+    /// Split an existing keys range of the responders config into two live.
+    /// (only used by relevant protocols)
+    SplitRange {
+        /// Existing keys range to split.
+        range: (String, String),
+        /// First key of the second part.
+        at: String,
+        /// Responders of the second part.
+        responders: Bitmap,
+    },
+
+    /// Merge two adjacent keys ranges of the responders config into one
+    /// live. (only used by relevant protocols)
+    MergeRange {
+        /// Existing keys range on the left.
+        left: (String, String),
+        /// Existing keys range on the right.
+        right: (String, String),
+        /// Responders of the merged range; if `None`, keeps the left one's.
+        responders: Option<Bitmap>,
+    },
+
+    /// Start chaos mode with a random or recorded fault schedule.
+    StartChaos {
+        /// Where the fault schedule comes from.
+        plan: ChaosPlan,
+    },
+
+    /// Stop chaos mode early, healing all network faults.
+    StopChaos,
+
+    /// Switch the log filters of the specified server(s) at runtime.
+    SetLogFilters {
+        /// IDs of servers to switch. If empty, switches all active servers.
+        servers: HashSet<ReplicaId>,
+        /// New filters in the syntax of `RUST_LOG`.
+        filters: String,
+    },
+
+    /// Query the number and total size of keys in a server's state machine,
+    /// listing one page of its keys in key order.
+    QueryKeys {
+        /// ID of the server to query.
+        server: ReplicaId,
+        /// List keys after this one; if empty, from the first key.
+        cursor: String,
+        /// Maximum number of keys to list; 0 for counting only.
+        limit: usize,
+    },
+
     /// Client leave notification.
     Leave,
 }
@@ -66,7 +181,7 @@ pub enum CtrlReply {
     /// Reply to server info query.
     QueryInfo {
         /// Number of replicas in cluster.
-        population: u8,
+        population: u16,
         /// Map from replica ID -> (addr, is_leader).
         servers_info: HashMap<ReplicaId, ServerInfo>,
     },
@@ -80,6 +195,40 @@ pub enum CtrlReply {
         now_conf: RespondersConf,
     },
 
+    /// Reply to server status query.
+    QueryStatus {
+        /// Map from replica ID -> its latest reported status.
+        servers_status: HashMap<ReplicaId, ServerStatus>,
+    },
+
+    /// Reply to event log query.
+    QueryEvents {
+        /// Matching events kept in the manager's memory, oldest first.
+        events: Vec<CtrlEventRecord>,
+    },
+
+    /// Reply to event stream subscription.
+    Subscribe {
+        /// Matching past events kept in the manager's memory, oldest first.
+        events: Vec<CtrlEventRecord>,
+    },
+
+    /// Events pushed to a subscribed client as they happen.
+    Events {
+        /// New events, oldest first.
+        events: Vec<CtrlEventRecord>,
+    },
+
+    /// Reply to reset watch request.
+    WatchResets,
+
+    /// Notice pushed to reset watchers that the servers are about to be
+    /// reset.
+    ResetBegin { servers: HashSet<ReplicaId> },
+
+    /// Notice pushed to reset watchers that the reset has finished.
+    ResetDone { servers: HashSet<ReplicaId> },
+
     /// Reply to server reset request.
     ResetServers { servers: HashSet<ReplicaId> },
 
@@ -95,15 +244,85 @@ pub enum CtrlReply {
         snapshot_up_to: HashMap<ReplicaId, usize>,
     },
 
+    /// Reply to coordinated snapshot request.
+    CoordSnapshot {
+        /// Slot of the marker entry and the map from replica ID -> path of
+        /// its snapshot file, or `None` if the request was rejected. The map
+        /// covers only the servers that reported in time if some did not.
+        result: Option<(usize, HashMap<ReplicaId, String>)>,
+    },
+
+    /// Reply to membership change request.
+    ChangeMembers {
+        /// New set of voting members and the slot of the stop-sign entry
+        /// that started it, or `None` if the change was rejected.
+        result: Option<(HashSet<ReplicaId>, usize)>,
+    },
+
+    /// Reply to reconfiguration request.
+    Reconfigure {
+        /// New set of voting members and the slot of the entry that started
+        /// it, or `None` if the change was rejected.
+        result: Option<(HashSet<ReplicaId>, usize)>,
+    },
+
+    /// Reply to leadership transfer request.
+    TransferLeader {
+        /// Whether the target server has stepped up as the new leader.
+        success: bool,
+    },
+
+    /// Reply to keys range split request.
+    SplitRange {
+        /// Config number the split took effect in, or `None` if rejected.
+        conf_num: Option<ConfNum>,
+    },
+
+    /// Reply to keys range merge request.
+    MergeRange {
+        /// Config number the merge took effect in, or `None` if rejected.
+        conf_num: Option<ConfNum>,
+    },
+
+    /// Reply to chaos mode start request, with the full fault schedule to
+    /// record for replay; `None` if chaos mode is already on.
+    StartChaos { schedule: Option<Vec<ChaosStep>> },
+
+    /// Reply to chaos mode stop request.
+    StopChaos,
+
+    /// Reply to log filters switch request, listing the servers where it
+    /// took effect.
+    SetLogFilters { servers: HashSet<ReplicaId> },
+
+    /// Reply to keys query, or `None` if the server is not active or its
+    /// state machine does not hold key-value pairs.
+    QueryKeys { report: Option<KeysReport> },
+
     /// Reply to client leave notification.
     Leave,
 }
 
+/// Event coming from the client-facing side.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) enum ClientEvent {
+    /// A control event request from some client.
+    Req(ClientId, CtrlRequest),
+
+    /// A client whose connection to me has ended, and whether it left
+    /// gracefully.
+    Left(ClientId, bool),
+}
+
 /// The client-facing reactor API module.
 pub(crate) struct ClientReactor {
     /// Receiver side of the req channel.
     rx_req: mpsc::UnboundedReceiver<(ClientId, CtrlRequest)>,
 
+    /// Receiver side of the left channel, notified of clients whose
+    /// connection to me has ended and whether they left gracefully.
+    rx_left: mpsc::UnboundedReceiver<(ClientId, bool)>,
+
     /// Map from client ID -> sender side of the reply channel, shared with
     /// the client acceptor task.
     tx_replies:
@@ -126,6 +345,7 @@ impl ClientReactor {
         cli_addr: SocketAddr,
     ) -> Result<Self, SummersetError> {
         let (tx_req, rx_req) = mpsc::unbounded_channel();
+        let (tx_left, rx_left) = mpsc::unbounded_channel();
 
         let (tx_replies_write, tx_replies_read) =
             flashmap::new::<ClientId, mpsc::UnboundedSender<CtrlReply>>();
@@ -136,6 +356,7 @@ impl ClientReactor {
         let client_listener = tcp_bind_with_retry(cli_addr, 15).await?;
         let mut acceptor = ClientReactorAcceptorTask::new(
             tx_req,
+            tx_left,
             tx_replies_write,
             client_listener,
             client_responder_handles_write,
@@ -145,6 +366,7 @@ impl ClientReactor {
 
         Ok(ClientReactor {
             rx_req,
+            rx_left,
             tx_replies: tx_replies_read,
             _client_acceptor_handle: client_acceptor_handle,
             _client_responder_handles: client_responder_handles_read,
@@ -158,13 +380,21 @@ impl ClientReactor {
         tx_replies_guard.contains_key(&client)
     }
 
-    /// Waits for the next control event request from some client.
-    pub(crate) async fn recv_req(
+    /// Waits for the next event from the client side: either a control event
+    /// request, or a client whose connection to me has ended by leaving or
+    /// by dropping.
+    pub(crate) async fn recv_event(
         &mut self,
-    ) -> Result<(ClientId, CtrlRequest), SummersetError> {
-        match self.rx_req.recv().await {
-            Some((id, req)) => Ok((id, req)),
-            None => logged_err!("req channel has been closed"),
+    ) -> Result<ClientEvent, SummersetError> {
+        tokio::select! {
+            req = self.rx_req.recv() => match req {
+                Some((id, req)) => Ok(ClientEvent::Req(id, req)),
+                None => logged_err!("req channel has been closed"),
+            },
+            left = self.rx_left.recv() => match left {
+                Some((id, graceful)) => Ok(ClientEvent::Left(id, graceful)),
+                None => logged_err!("left channel has been closed"),
+            },
         }
     }
 
@@ -193,6 +423,7 @@ impl ClientReactor {
 /// ClientReactor client acceptor task.
 struct ClientReactorAcceptorTask {
     tx_req: mpsc::UnboundedSender<(ClientId, CtrlRequest)>,
+    tx_left: mpsc::UnboundedSender<(ClientId, bool)>,
     tx_replies:
         flashmap::WriteHandle<ClientId, mpsc::UnboundedSender<CtrlReply>>,
 
@@ -201,14 +432,18 @@ struct ClientReactorAcceptorTask {
 
     next_client_id: ClientId,
 
-    tx_exit: mpsc::UnboundedSender<ClientId>,
-    rx_exit: mpsc::UnboundedReceiver<ClientId>,
+    /// Map from reconnection token -> the client identity it reclaims.
+    identities: HashMap<u64, ClientId>,
+
+    tx_exit: mpsc::UnboundedSender<(ClientId, bool)>,
+    rx_exit: mpsc::UnboundedReceiver<(ClientId, bool)>,
 }
 
 impl ClientReactorAcceptorTask {
     /// Creates the client acceptor task.
     fn new(
         tx_req: mpsc::UnboundedSender<(ClientId, CtrlRequest)>,
+        tx_left: mpsc::UnboundedSender<(ClientId, bool)>,
         tx_replies: flashmap::WriteHandle<
             ClientId,
             mpsc::UnboundedSender<CtrlReply>,
@@ -230,26 +465,70 @@ impl ClientReactorAcceptorTask {
 
         ClientReactorAcceptorTask {
             tx_req,
+            tx_left,
             tx_replies,
             client_listener,
             client_responder_handles,
             next_client_id,
+            identities: HashMap::new(),
             tx_exit,
             rx_exit,
         }
     }
 
-    /// Accepts a new client connection.
+    /// Decides the client ID for a new client connection presenting given
+    /// reconnection token. A known token reclaims its identity, unless that
+    /// identity's old connection has not been cleaned up yet; otherwise, a
+    /// new identity is issued with a new token. Returns the client ID and
+    /// its token.
+    fn decide_identity(&mut self, token: u64) -> (ClientId, u64) {
+        if let Some(&id) = self.identities.get(&token) {
+            let tx_replies_guard = self.tx_replies.guard();
+            if !tx_replies_guard.contains_key(&id) {
+                pf_info!("client {} reclaimed its identity", id);
+                return (id, token);
+            }
+        }
+
+        let id = self.next_client_id;
+        self.next_client_id += 1;
+        let mut token = 0;
+        while token == 0 || self.identities.contains_key(&token) {
+            token = rand::random();
+        }
+        self.identities.insert(token, id);
+        (id, token)
+    }
+
+    /// Accepts a new client connection. The client first sends its
+    /// reconnection token, 0 if it has none, and I reply with its assigned
+    /// client ID and token.
     async fn accept_new_client(
         &mut self,
         mut stream: TcpStream,
         addr: SocketAddr,
-        id: ClientId,
     ) -> Result<(), SummersetError> {
+        let token = match time::timeout(
+            Duration::from_secs(1),
+            stream.read_u64(),
+        )
+        .await
+        {
+            Ok(Ok(token)) => token,
+            _ => return logged_err!("no token from new client '{}'", addr),
+        };
+        let (id, token) = self.decide_identity(token);
+
         // send ID assignment
         if let Err(e) = stream.write_u64(id).await {
             return logged_err!("error assigning new client ID: {}", e);
         }
+        if let Err(e) = stream.write_u64(token).await {
+            return logged_err!("error sending client token: {}", e);
+        }
+        if let Err(e) = exchange_codec(&mut stream).await {
+            return logged_err!("error negotiating codec with client: {}", e);
+        }
 
         let mut tx_replies_guard = self.tx_replies.guard();
         if let Some(sender) = tx_replies_guard.get(&id) {
@@ -291,6 +570,7 @@ impl ClientReactorAcceptorTask {
     fn remove_left_client(
         &mut self,
         id: ClientId,
+        graceful: bool,
     ) -> Result<(), SummersetError> {
         let mut tx_replies_guard = self.tx_replies.guard();
         if !tx_replies_guard.contains_key(&id) {
@@ -302,6 +582,9 @@ impl ClientReactorAcceptorTask {
             self.client_responder_handles.guard();
         client_responder_handles_guard.remove(id);
 
+        self.tx_left
+            .send((id, graceful))
+            .map_err(SummersetError::msg)?;
         Ok(())
     }
 
@@ -324,19 +607,17 @@ impl ClientReactorAcceptorTask {
                     if let Err(e) = self.accept_new_client(
                         stream,
                         addr,
-                        self.next_client_id
                     ).await {
                         pf_error!("error accepting new client: {}", e);
-                    } else {
-                        self.next_client_id += 1;
                     }
                 },
 
                 // a client responder task exits
-                id = self.rx_exit.recv() => {
-                    let id = id.unwrap();
+                exit = self.rx_exit.recv() => {
+                    let (id, graceful) = exit.unwrap();
                     if let Err(e) = self.remove_left_client(
                         id,
+                        graceful,
                     ) {
                         pf_error!("error removing left client {}: {}", id, e);
                     }
@@ -364,7 +645,7 @@ struct ClientReactorResponderTask {
     reply_buf_cursor: usize,
     retrying: bool,
 
-    tx_exit: mpsc::UnboundedSender<ClientId>,
+    tx_exit: mpsc::UnboundedSender<(ClientId, bool)>,
 }
 
 impl ClientReactorResponderTask {
@@ -375,7 +656,7 @@ impl ClientReactorResponderTask {
         conn: TcpStream,
         tx_req: mpsc::UnboundedSender<(ClientId, CtrlRequest)>,
         rx_reply: mpsc::UnboundedReceiver<CtrlReply>,
-        tx_exit: mpsc::UnboundedSender<ClientId>,
+        tx_exit: mpsc::UnboundedSender<(ClientId, bool)>,
     ) -> Self {
         let (conn_read, conn_write) = conn.into_split();
 
@@ -429,6 +710,7 @@ impl ClientReactorResponderTask {
             self.addr
         );
 
+        let mut graceful = false;
         loop {
             tokio::select! {
                 // gets a reply to send to client
@@ -500,6 +782,7 @@ impl ClientReactorResponderTask {
                             } else { // NOTE: skips `WouldBlock` error check here
                                 pf_debug!("client {} has left", self.id);
                             }
+                            graceful = true;
                             break;
                         },
 
@@ -521,7 +804,7 @@ impl ClientReactorResponderTask {
             }
         }
 
-        if let Err(e) = self.tx_exit.send(self.id) {
+        if let Err(e) = self.tx_exit.send((self.id, graceful)) {
             pf_error!("error sending exit signal for {}: {}", self.id, e);
         }
         pf_debug!(
@@ -541,6 +824,17 @@ mod tests {
     use tokio::sync::Barrier;
     use tokio::time::{self, Duration};
 
+    /// Waits for the next control event request, skipping clients leaving.
+    async fn recv_req(
+        reactor: &mut ClientReactor,
+    ) -> Result<(ClientId, CtrlRequest), SummersetError> {
+        loop {
+            if let ClientEvent::Req(client, req) = reactor.recv_event().await? {
+                return Ok((client, req));
+            }
+        }
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn api_req_reply() -> Result<(), SummersetError> {
         let barrier = Arc::new(Barrier::new(2));
@@ -552,7 +846,7 @@ mod tests {
                     .await?;
             barrier2.wait().await;
             // recv request from client
-            let (client, req) = reactor.recv_req().await?;
+            let (client, req) = recv_req(&mut reactor).await?;
             debug_assert!(reactor.has_client(client));
             assert_eq!(req, CtrlRequest::QueryInfo);
             // send reply to client
@@ -715,7 +1009,7 @@ mod tests {
             ClientReactor::new_and_setup("127.0.0.1:30109".parse()?).await?;
         barrier.wait().await;
         // recv request from client
-        let (client, req) = reactor.recv_req().await?;
+        let (client, req) = recv_req(&mut reactor).await?;
         debug_assert!(reactor.has_client(client));
         assert_eq!(req, CtrlRequest::QueryInfo);
         // send reply to client
@@ -748,7 +1042,7 @@ mod tests {
             client,
         )?;
         // recv request from new client
-        let (client2, req2) = reactor.recv_req().await?;
+        let (client2, req2) = recv_req(&mut reactor).await?;
         debug_assert!(reactor.has_client(client2));
         debug_assert!(!reactor.has_client(client));
         assert_eq!(req2, CtrlRequest::QueryInfo);
@@ -783,4 +1077,104 @@ mod tests {
         )?;
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn api_watch_resets() -> Result<(), SummersetError> {
+        let barrier = Arc::new(Barrier::new(2));
+        let barrier2 = barrier.clone();
+        tokio::spawn(async move {
+            // manager-side
+            let mut reactor =
+                ClientReactor::new_and_setup("127.0.0.1:30309".parse()?)
+                    .await?;
+            barrier2.wait().await;
+            // recv watch request from client's dedicated connection
+            let (watcher, req) = recv_req(&mut reactor).await?;
+            assert_eq!(req, CtrlRequest::WatchResets);
+            reactor.send_reply(CtrlReply::WatchResets, watcher)?;
+            // announce a reset and wait for the client to quiesce
+            reactor.send_reply(
+                CtrlReply::ResetBegin {
+                    servers: HashSet::from([1]),
+                },
+                watcher,
+            )?;
+            let (client, req) = recv_req(&mut reactor).await?;
+            assert_eq!(client, watcher);
+            assert_eq!(req, CtrlRequest::ResetQuiesced);
+            reactor.send_reply(
+                CtrlReply::ResetDone {
+                    servers: HashSet::from([1]),
+                },
+                watcher,
+            )?;
+            Ok::<(), SummersetError>(())
+        });
+        // client-side
+        barrier.wait().await;
+        let ctrl_stub =
+            ClientCtrlStub::new_by_connect("127.0.0.1:30309".parse()?).await?;
+        let mut watcher = ctrl_stub.watch_resets().await?;
+        assert_eq!(
+            watcher.recv_notice().await?,
+            CtrlReply::ResetBegin {
+                servers: HashSet::from([1]),
+            }
+        );
+        watcher.quiesced()?;
+        assert_eq!(
+            watcher.recv_notice().await?,
+            CtrlReply::ResetDone {
+                servers: HashSet::from([1]),
+            }
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn api_client_reclaim() -> Result<(), SummersetError> {
+        let barrier = Arc::new(Barrier::new(2));
+        let barrier2 = barrier.clone();
+        tokio::spawn(async move {
+            // client-side
+            barrier2.wait().await;
+            let mut stream = TcpStream::connect("127.0.0.1:30420").await?;
+            stream.write_u64(0).await?;
+            let id = stream.read_u64().await?;
+            let token = stream.read_u64().await?;
+            assert_ne!(token, 0);
+            stream.write_u8(0).await?; // bincode codec
+            assert_eq!(stream.read_u8().await?, 0);
+            drop(stream);
+            time::sleep(Duration::from_millis(100)).await;
+            // come back with the token, reclaiming the same identity
+            let mut stream = TcpStream::connect("127.0.0.1:30420").await?;
+            stream.write_u64(token).await?;
+            assert_eq!(stream.read_u64().await?, id);
+            assert_eq!(stream.read_u64().await?, token);
+            stream.write_u8(0).await?;
+            assert_eq!(stream.read_u8().await?, 0);
+            // a concurrent connection with the same token gets a new one
+            let mut stream2 = TcpStream::connect("127.0.0.1:30420").await?;
+            stream2.write_u64(token).await?;
+            assert_ne!(stream2.read_u64().await?, id);
+            assert_ne!(stream2.read_u64().await?, token);
+            stream2.write_u8(0).await?;
+            assert_eq!(stream2.read_u8().await?, 0);
+            time::sleep(Duration::from_millis(500)).await;
+            Ok::<(), SummersetError>(())
+        });
+        // manager-side
+        let mut reactor =
+            ClientReactor::new_and_setup("127.0.0.1:30420".parse()?).await?;
+        barrier.wait().await;
+        let ClientEvent::Left(client, graceful) = reactor.recv_event().await?
+        else {
+            panic!("expected a client left event");
+        };
+        assert!(!graceful);
+        time::sleep(Duration::from_millis(300)).await;
+        assert!(reactor.has_client(client));
+        Ok(())
+    }
 }
@@ -1,13 +1,15 @@
 //! Cluster manager server-facing controller module implementation.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
 
+use crate::client::ClientId;
+use crate::manager::ServerStatus;
 use crate::protocols::SmrProtocol;
-use crate::server::ReplicaId;
+use crate::server::{KeysReport, ReplicaId};
 use crate::utils::{
-    safe_tcp_read, safe_tcp_write, tcp_bind_with_retry, ConfNum,
-    RespondersConf, SummersetError,
+    exchange_codec, safe_tcp_read, safe_tcp_write, tcp_bind_with_retry, Bitmap,
+    ConfNum, RespondersConf, SummersetError,
 };
 
 use bytes::BytesMut;
@@ -22,7 +24,7 @@ use tokio::task::JoinHandle;
 
 /// Control message from/to servers. Control traffic could be bidirectional:
 /// some initiated by the manager and some by servers.
-// TODO: later add basic lease, membership/view change, link drop, etc.
+// TODO: later add basic lease, link drop, etc.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub(crate) enum CtrlMsg {
     /// Server -> Manager: new server up, requesting a list of peers' addresses
@@ -36,10 +38,26 @@ pub(crate) enum CtrlMsg {
 
     /// Manager -> Server: assign a list of peers to proactively connect to.
     ConnectToPeers {
-        population: u8,
+        population: u16,
         to_peers: HashMap<ReplicaId, SocketAddr>,
     },
 
+    /// Manager -> Server: client-facing API addresses of all currently known
+    /// servers, re-sent to everyone whenever a server joins, for answering
+    /// clients that bootstrap through any replica.
+    ServersMap {
+        api_addrs: HashMap<ReplicaId, SocketAddr>,
+    },
+
+    /// Manager -> Server: chunk at byte offset of the initial state machine
+    /// image to start from if my durable state is fresh, sent in order right
+    /// before `ConnectToPeers` when the cluster is seeded.
+    SeedState {
+        offset: usize,
+        chunk: Vec<u8>,
+        last: bool,
+    },
+
     /// Server -> Manager: tell the manager that I steped-up/down as leader.
     LeaderStatus { step_up: bool },
 
@@ -72,11 +90,133 @@ pub(crate) enum CtrlMsg {
     /// Server -> Manager: server took snapshot up to log index.
     SnapshotUpTo { new_start: usize },
 
+    /// Manager -> Server: propose a snapshot marker entry; every server
+    /// snapshots its state exactly as of the marker's slot once committed.
+    SnapshotMark,
+
+    /// Server -> Manager: I took the snapshot of the marker entry at slot,
+    /// saved as the file at path.
+    SnapshotAt { slot: usize, path: String },
+
+    /// Server -> Manager: the requested snapshot marker cannot be proposed
+    /// by me.
+    SnapshotMarkReject,
+
+    /// Manager -> Server: propose a stop-sign entry that freezes the current
+    /// configuration and starts a new one with the given voting members.
+    StopSign { members: Bitmap },
+
+    /// Server -> Manager: a stop-sign entry at slot got committed, and the
+    /// new configuration with the given voting members is now in effect.
+    MembersConf { members: Bitmap, slot: usize },
+
+    /// Server -> Manager: the requested stop-sign cannot be proposed by me.
+    StopSignReject,
+
+    /// Manager -> Server: change the voting members to the current ones plus
+    /// `add` minus `remove`, through the protocol's reconfiguration entries.
+    /// Completion is reported as `MembersConf`.
+    Reconfigure { add: Bitmap, remove: Bitmap },
+
+    /// Server -> Manager: the requested reconfiguration cannot be started by
+    /// me, e.g. as another one is still in progress.
+    ReconfigureReject,
+
+    /// Manager -> Server: hand leadership over to the target server in a
+    /// planned way.
+    TransferLeader { target: ReplicaId },
+
+    /// Server -> Manager: the requested leadership transfer cannot be done
+    /// by me.
+    TransferReject,
+
+    /// Manager -> Server: split an existing keys range of the responders
+    /// config at key `at`, giving the second part the given responders.
+    /// Fenced by `epoch`, the config number the manager based it on.
+    SplitRange {
+        epoch: ConfNum,
+        range: (String, String),
+        at: String,
+        responders: Bitmap,
+    },
+
+    /// Manager -> Server: merge two adjacent keys ranges of the responders
+    /// config into one. Fenced by `epoch` as above.
+    MergeRange {
+        epoch: ConfNum,
+        left: (String, String),
+        right: (String, String),
+        responders: Option<Bitmap>,
+    },
+
+    /// Server -> Manager: the requested keys range split/merge cannot be
+    /// applied by me, e.g. because the config changed since `epoch`.
+    RangeReject,
+
+    /// Server -> Manager: in Vertical Paxos mode, request a new ballot epoch
+    /// from the configuration master after timing out on the leader.
+    BallotRequest { seen_epoch: u64 },
+
+    /// Manager -> Server: in Vertical Paxos mode, the configuration master
+    /// granted a ballot epoch to a new leader with a new set of members.
+    BallotGrant {
+        leader: ReplicaId,
+        epoch: u64,
+        members: Bitmap,
+        prev_members: Bitmap,
+    },
+
+    /// Server -> Manager: in Vertical Paxos mode, my granted ballot epoch
+    /// finished its Prepare phase and is now active.
+    BallotActive { epoch: u64 },
+
+    /// Server -> Manager: in lease diagnostics mode, an interval (in millisecs
+    /// since UNIX epoch) during which I believed I held a majority lease.
+    LeaseInterval {
+        lease_num: u64,
+        start_ms: u64,
+        end_ms: u64,
+    },
+
+    /// Server -> Manager: periodic report of my latest protocol status.
+    StatusReport { status: ServerStatus },
+
+    /// Manager -> Server: forcefully close the sessions of given clients,
+    /// e.g., because they have left the manager, and reclaim their state.
+    ExpireClients { clients: Vec<ClientId> },
+
+    /// Manager -> Server: switch my log filters at runtime, given in the
+    /// syntax of `RUST_LOG`.
+    SetLogFilters { filters: String },
+
+    /// Server -> Manager: whether the log filters switch took effect.
+    LogFiltersReply { ok: bool },
+
+    /// Manager -> Server: report the number and size of keys in my state
+    /// machine, listing up to `limit` of them after `cursor`.
+    ListKeys { cursor: String, limit: usize },
+
+    /// Server -> Manager: reply to `ListKeys`, or `None` if my state machine
+    /// does not hold key-value pairs.
+    ListKeysReply { report: Option<KeysReport> },
+
+    /// Manager -> Server: in chaos mode, replace my simulated network faults
+    /// with dropping messages from `blocked` peers and delaying the rest by
+    /// `delay_ms`. Empty `blocked` and zero `delay_ms` heal them.
+    NetFaults {
+        blocked: HashSet<ReplicaId>,
+        delay_ms: u64,
+    },
+
     /// Server -> Manager: leave notification.
     Leave,
 
     /// Manager -> Server: dummy leave reply.
     LeaveReply,
+
+    /// Manager-internal: the server's control connection dropped without
+    /// a `Leave` notification. Never sent over the wire.
+    Disconnected,
 }
 
 /// The server-facing controller API module.
@@ -100,11 +240,20 @@ pub(crate) struct ServerReigner {
 impl ServerReigner {
     /// Creates a new server-facing controller module. Spawns the server
     /// acceptor task. Creates a pair of ID assignment channels. Creates
-    /// a recv channel for buffering incoming control messages.
+    /// a recv channel for buffering incoming control messages. `cli_port` is
+    /// the manager's client-facing port, told to servers upon joining so
+    /// that they can point clients to the manager. `cluster_ca`, if given,
+    /// is the PEM content of the cluster CA certificate handed to servers,
+    /// which then require mutual TLS between peers. `port_range`, if given,
+    /// is the inclusive range of ports to assign servers' API and p2p ports
+    /// from, two per server ID in order.
     pub(crate) async fn new_and_setup(
         srv_addr: SocketAddr,
+        cli_port: u16,
+        cluster_ca: Option<String>,
+        port_range: Option<(u16, u16)>,
         tx_id_assign: mpsc::UnboundedSender<()>,
-        rx_id_result: mpsc::UnboundedReceiver<(ReplicaId, u8)>,
+        rx_id_result: mpsc::UnboundedReceiver<(ReplicaId, u16)>,
     ) -> Result<Self, SummersetError> {
         let (tx_recv, rx_recv) = mpsc::unbounded_channel();
 
@@ -116,6 +265,9 @@ impl ServerReigner {
 
         let server_listener = tcp_bind_with_retry(srv_addr, 15).await?;
         let mut acceptor = ServerReignerAcceptorTask::new(
+            cli_port,
+            cluster_ca,
+            port_range,
             tx_id_assign,
             rx_id_result,
             tx_recv,
@@ -175,8 +327,12 @@ impl ServerReigner {
 
 /// ServerReigner server acceptor task.
 struct ServerReignerAcceptorTask {
+    cli_port: u16,
+    cluster_ca: Option<String>,
+    port_range: Option<(u16, u16)>,
+
     tx_id_assign: mpsc::UnboundedSender<()>,
-    rx_id_result: mpsc::UnboundedReceiver<(ReplicaId, u8)>,
+    rx_id_result: mpsc::UnboundedReceiver<(ReplicaId, u16)>,
 
     tx_recv: mpsc::UnboundedSender<(ReplicaId, CtrlMsg)>,
     tx_sends: flashmap::WriteHandle<ReplicaId, mpsc::UnboundedSender<CtrlMsg>>,
@@ -190,9 +346,14 @@ struct ServerReignerAcceptorTask {
 
 impl ServerReignerAcceptorTask {
     /// Creates the server acceptor task.
+    #[allow(clippy::too_many_arguments)]
     fn new(
+        cli_port: u16,
+        cluster_ca: Option<String>,
+        port_range: Option<(u16, u16)>,
+
         tx_id_assign: mpsc::UnboundedSender<()>,
-        rx_id_result: mpsc::UnboundedReceiver<(ReplicaId, u8)>,
+        rx_id_result: mpsc::UnboundedReceiver<(ReplicaId, u16)>,
 
         tx_recv: mpsc::UnboundedSender<(ReplicaId, CtrlMsg)>,
         tx_sends: flashmap::WriteHandle<
@@ -211,6 +372,9 @@ impl ServerReignerAcceptorTask {
         let (tx_exit, rx_exit) = mpsc::unbounded_channel();
 
         ServerReignerAcceptorTask {
+            cli_port,
+            cluster_ca,
+            port_range,
             tx_id_assign,
             rx_id_result,
             tx_recv,
@@ -222,6 +386,28 @@ impl ServerReignerAcceptorTask {
         }
     }
 
+    /// Computes the API and p2p ports assigned to a server ID, which are the
+    /// pair at offset `2 * id` into the port range, so that a server
+    /// rejoining under the same ID gets the same ports.
+    fn assigned_ports(
+        &self,
+        id: ReplicaId,
+    ) -> Result<(u16, u16), SummersetError> {
+        let Some((start, end)) = self.port_range else {
+            return Ok((0, 0));
+        };
+        let api_port = start as usize + 2 * id as usize;
+        if api_port + 1 > end as usize {
+            return logged_err!(
+                "port range {}-{} exhausted at server {}",
+                start,
+                end,
+                id
+            );
+        }
+        Ok((api_port as u16, api_port as u16 + 1))
+    }
+
     /// Accepts a new server connection.
     async fn accept_new_server(
         &mut self,
@@ -236,15 +422,43 @@ impl ServerReignerAcceptorTask {
             ))?;
 
         // first send server ID assignment
-        if let Err(e) = stream.write_u8(id).await {
+        if let Err(e) = stream.write_u16(id).await {
             return logged_err!("error assigning new server ID: {}", e);
         }
 
         // then send population
-        if let Err(e) = stream.write_u8(population).await {
+        if let Err(e) = stream.write_u16(population).await {
             return logged_err!("error sending population: {}", e);
         }
 
+        // then send my client-facing port
+        if let Err(e) = stream.write_u16(self.cli_port).await {
+            return logged_err!("error sending client-facing port: {}", e);
+        }
+
+        // then send the cluster CA certificate, empty if not using TLS
+        let ca_pem = self.cluster_ca.as_deref().unwrap_or("").as_bytes();
+        if let Err(e) = stream.write_u32(ca_pem.len() as u32).await {
+            return logged_err!("error sending cluster CA: {}", e);
+        }
+        if let Err(e) = stream.write_all(ca_pem).await {
+            return logged_err!("error sending cluster CA: {}", e);
+        }
+
+        // then send the assigned API and p2p ports, zeros if not assigning
+        let (api_port, p2p_port) = self.assigned_ports(id)?;
+        if let Err(e) = stream.write_u16(api_port).await {
+            return logged_err!("error sending assigned ports: {}", e);
+        }
+        if let Err(e) = stream.write_u16(p2p_port).await {
+            return logged_err!("error sending assigned ports: {}", e);
+        }
+
+        // lastly make sure we agree on the serialization codec
+        if let Err(e) = exchange_codec(&mut stream).await {
+            return logged_err!("error negotiating codec: {}", e);
+        }
+
         let mut tx_sends_guard = self.tx_sends.guard();
         if let Some(sender) = tx_sends_guard.get(&id) {
             if sender.is_closed() {
@@ -489,6 +703,11 @@ impl ServerReignerControllerTask {
                             } else { // NOTE: skips `WouldBlock` error check here
                                 pf_debug!("server {} has left", self.id);
                             }
+                            // also let the manager know about it
+                            if let Err(e) = self.tx_recv.send((self.id, CtrlMsg::Leave)) {
+                                pf_error!("error sending to tx_recv for {}: {}",
+                                          self.id, e);
+                            }
                             break;
                         },
 
@@ -503,8 +722,14 @@ impl ServerReignerControllerTask {
                             // for api_addr and p2p_addr fields. Fill them with
                             // the server's remote IP address known at the
                             // time of accepting connection to make them valid
-                            // remote addresses
-                            let conn_ip = self.conn_write.peer_addr().unwrap().ip();
+                            // remote addresses; IPv4 peers accepted by a
+                            // dual-stack listener are unmapped back to IPv4
+                            let conn_ip = self
+                                .conn_write
+                                .peer_addr()
+                                .unwrap()
+                                .ip()
+                                .to_canonical();
                             api_addr.set_ip(conn_ip);
                             p2p_addr.set_ip(conn_ip);
 
@@ -533,7 +758,12 @@ impl ServerReignerControllerTask {
                             // NOTE: commented out to prevent console lags
                             //       during benchmarking
                             // pf_error!("error reading ctrl <- {}: {}", id, e);
-                            break; // probably the server exited ungracefully
+                            // probably the server exited ungracefully
+                            if let Err(e) = self.tx_recv.send((self.id, CtrlMsg::Disconnected)) {
+                                pf_error!("error sending to tx_recv for {}: {}",
+                                          self.id, e);
+                            }
+                            break;
                         }
                     }
                 }
@@ -566,6 +796,8 @@ mod tests {
         let setup_bar1 = setup_bar.clone();
         let server1_bar = Arc::new(Barrier::new(2));
         let server1_bar1 = server1_bar.clone();
+        let done_bar = Arc::new(Barrier::new(2));
+        let done_bar0 = done_bar.clone();
         tokio::spawn(async move {
             // replica 0
             setup_bar0.wait().await;
@@ -588,6 +820,8 @@ mod tests {
                 }
             );
             server1_bar.wait().await;
+            // stay connected, or the manager would see me gone
+            done_bar0.wait().await;
             Ok::<(), SummersetError>(())
         });
         tokio::spawn(async move {
@@ -597,6 +831,14 @@ mod tests {
             let mut hub =
                 ControlHub::new_and_setup("127.0.0.1:30019".parse()?).await?;
             assert_eq!(hub.me, 1);
+            // explicitly given ports are kept, while 0 gets assigned
+            assert_eq!(
+                hub.resolve_addrs(
+                    "127.0.0.1:30001".parse()?,
+                    "127.0.0.1:0".parse()?
+                )?,
+                ("127.0.0.1:30001".parse()?, "127.0.0.1:30503".parse()?)
+            );
             // send a message to manager
             hub.send_ctrl(CtrlMsg::NewServerJoin {
                 id: hub.me,
@@ -619,6 +861,9 @@ mod tests {
         let (tx_id_result, rx_id_result) = mpsc::unbounded_channel();
         let mut reigner = ServerReigner::new_and_setup(
             "127.0.0.1:30019".parse()?,
+            30009,
+            None,
+            Some((30500, 30503)),
             tx_id_assign,
             rx_id_result,
         )
@@ -668,6 +913,7 @@ mod tests {
             },
             id,
         )?;
+        done_bar.wait().await;
         Ok(())
     }
 
@@ -703,6 +949,9 @@ mod tests {
         let (tx_id_result, rx_id_result) = mpsc::unbounded_channel();
         let mut reigner = ServerReigner::new_and_setup(
             "127.0.0.1:30119".parse()?,
+            30009,
+            None,
+            None,
             tx_id_assign,
             rx_id_result,
         )
@@ -791,6 +1040,9 @@ mod tests {
         let (tx_id_result, rx_id_result) = mpsc::unbounded_channel();
         let mut reigner = ServerReigner::new_and_setup(
             "127.0.0.1:30219".parse()?,
+            30009,
+            None,
+            None,
             tx_id_assign,
             rx_id_result,
         )
@@ -818,6 +1070,8 @@ mod tests {
             },
             id,
         )?;
+        // recv forwarded leave notification of server 0
+        assert_eq!(reigner.recv_ctrl().await?, (0, CtrlMsg::Leave));
         rx_id_assign.recv().await;
         tx_id_result.send((0, 1))?;
         // recv message from server 0
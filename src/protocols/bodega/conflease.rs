@@ -26,6 +26,10 @@ impl BodegaReplica {
         // update my highest seen ballot and config
         self.bal_max_seen = new_bal;
         self.bodega_conf = new_conf;
+        self.external_api.set_conf_epoch(new_bal);
+        if let Some(leader) = self.bodega_conf.leader {
+            self.external_api.notify_leader_change(leader)?;
+        }
 
         // clear peers' accept_bar information
         for (&peer, bar) in self.peer_accept_bar.iter_mut() {
@@ -83,6 +83,54 @@ impl BodegaReplica {
         Ok(())
     }
 
+    /// Handler of SplitRange and MergeRange control messages. Rejected unless
+    /// I am a prepared leader whose config is still the one numbered `epoch`
+    /// that the manager based the change on; otherwise, applies the change
+    /// and announces the new config through the same path as user-initiated
+    /// conf changes.
+    async fn handle_ctrl_range_change(
+        &mut self,
+        msg: CtrlMsg,
+    ) -> Result<(), SummersetError> {
+        pf_warn!("server got range change req");
+        let mut new_conf = self.bodega_conf.clone();
+        let (epoch, applied) = match msg {
+            CtrlMsg::SplitRange {
+                epoch,
+                range,
+                at,
+                responders,
+            } => (epoch, new_conf.split_range(&range, &at, responders).is_ok()),
+            CtrlMsg::MergeRange {
+                epoch,
+                left,
+                right,
+                responders,
+            } => (
+                epoch,
+                new_conf.merge_ranges(&left, &right, responders).is_ok(),
+            ),
+            _ => (0, false),
+        };
+        if !self.is_leader()
+            || self.bal_prepared == 0
+            || epoch != self.bal_max_seen
+            || !applied
+            || new_conf == self.bodega_conf
+        {
+            pf_debug!("rejecting range change at epoch {}", epoch);
+            self.control_hub.send_ctrl(CtrlMsg::RangeReject)?;
+            return Ok(());
+        }
+
+        self.announce_new_conf(new_conf.clone()).await?;
+        self.control_hub.send_ctrl(CtrlMsg::RespondersConf {
+            conf_num: self.bal_max_seen,
+            new_conf,
+        })?;
+        Ok(())
+    }
+
     /// Synthesized handler of manager control messages. If ok, returns
     /// `Some(true)` if decides to terminate and reboot, `Some(false)` if
     /// decides to shutdown completely, and `None` if not terminating.
@@ -112,6 +160,16 @@ impl BodegaReplica {
                 Ok(None)
             }
 
+            CtrlMsg::SplitRange { .. } | CtrlMsg::MergeRange { .. } => {
+                self.handle_ctrl_range_change(msg).await?;
+                Ok(None)
+            }
+
+            CtrlMsg::ExpireClients { clients } => {
+                self.external_api.expire_clients(&clients)?;
+                Ok(None)
+            }
+
             _ => Ok(None), // ignore all other types
         }
     }
@@ -211,10 +211,11 @@ impl BodegaReplica {
                     inst.status = Status::Executed;
                 } else if inst.status == Status::Committed {
                     for (cmd_idx, (_, req)) in inst.reqs.iter().enumerate() {
-                        if let ApiRequest::Req { cmd, .. } = req {
-                            self.state_machine.submit_cmd(
+                        if let ApiRequest::Req { cmd, checksum, .. } = req {
+                            self.state_machine.submit_checked_cmd(
                                 Self::make_command_id(self.commit_bar, cmd_idx),
                                 cmd.clone(),
+                                *checksum,
                             )?;
                         }
                     }
@@ -280,7 +281,9 @@ impl BodegaReplica {
             // then update self.wal_offset
             self.wal_offset = now_size;
         } else {
-            return logged_err!("unexpected log result type: {:?}", log_result);
+            return logged_err!(
+                Protocol; "unexpected log result type: {:?}", log_result
+            );
         }
 
         match entry_type {
@@ -288,7 +291,9 @@ impl BodegaReplica {
             Status::Accepting => self.handle_logged_accept_data(slot),
             Status::Committed => self.handle_logged_commit_slot(slot),
             _ => {
-                logged_err!("unexpected log entry type: {:?}", entry_type)
+                logged_err!(
+                    Protocol; "unexpected log entry type: {:?}", entry_type
+                )
             }
         }
     }
@@ -6,8 +6,10 @@ use super::*;
 
 use rand::prelude::*;
 
-use crate::manager::CtrlMsg;
-use crate::server::{LeaseMsg, LogAction, ReplicaId};
+use crate::manager::{CtrlMsg, ServerStatus};
+use crate::server::{
+    DebugState, LeaseMsg, LogAction, ReplicaId, ResourceUsage,
+};
 use crate::utils::SummersetError;
 
 // BodegaReplica heartbeats related logic
@@ -43,12 +45,12 @@ impl BodegaReplica {
     ) -> Result<(), SummersetError> {
         self.volunteer_timer.cancel()?;
 
-        let timeout_ms = thread_rng().gen_range(
+        let timeout_ms = self.volunteer_rng.gen_range(
             self.config.hb_hear_timeout_min..=self.config.hb_hear_timeout_max,
         );
         // pf_trace!("kickoff volunteer_timer @ {} ms", timeout_ms);
         self.volunteer_timer
-            .kickoff(Duration::from_millis(timeout_ms as u64))
+            .kickoff(Duration::from_millis(timeout_ms))
     }
 
     /// Refreshes the heartbeat timer for given peer, and if current config has
@@ -158,13 +160,14 @@ impl BodegaReplica {
         // check and send lease refreshers to all peers
         let to_refresh = self.lease_manager.attempt_refresh(None)?;
         if to_refresh.count() > 0 {
-            self.transport_hub.bcast_lease_msg(
+            self.transport_hub.queue_lease_msg(
                 0, // only one lease purpose exists in Bodega
                 self.bal_max_seen,
                 LeaseMsg::Promise,
                 Some(to_refresh),
             )?;
         }
+        self.transport_hub.flush_lease_msgs()?;
 
         // broadcast heartbeat to all peers
         self.transport_hub.bcast_msg(
@@ -193,6 +196,36 @@ impl BodegaReplica {
         )
         .await?;
 
+        // expose my protocol state to debug queries
+        self.external_api.update_debug_state(DebugState {
+            is_leader: self.is_leader(),
+            leader: self.bodega_conf.leader,
+            ballot: self.bal_max_seen,
+            commit_bar: self.commit_bar,
+            exec_bar: self.exec_bar,
+            start_slot: self.start_slot,
+            log_len: self.insts.len(),
+            peers_alive: self.heartbeater.peer_alive().clone(),
+        });
+
+        // periodically report my status to the manager
+        self.control_hub.report_status(ServerStatus {
+            ballot: self.bal_max_seen,
+            commit_bar: self.commit_bar,
+            exec_bar: self.exec_bar,
+            peers_alive: self.heartbeater.peer_alive().clone(),
+            perf_models: HashMap::new(),
+            hot_keys: self.state_machine.hot_keys(),
+            queue_depths: vec![
+                self.external_api.queue_depth_report(),
+                self.storage_hub.queue_depth_report(),
+                self.transport_hub.queue_depth_report(),
+                self.state_machine.queue_depth_report(),
+            ],
+            resources: ResourceUsage::default(),
+            divergence_repaired: 0,
+        })?;
+
         // pf_trace!("broadcast heartbeats bal {}", self.bal_prep_sent);
         Ok(())
     }
@@ -266,7 +299,7 @@ impl BodegaReplica {
                     .peer_exec_bar
                     .values()
                     .filter(|&&e| e >= exec_bar)
-                    .count() as u8;
+                    .count() as u16;
                 if passed_cnt == self.population {
                     // all servers have executed up to exec_bar
                     self.snap_bar = exec_bar;
@@ -32,7 +32,7 @@ impl BodegaReplica {
     pub(super) fn commit_condition(
         leader_bk: &LeaderBookkeeping,
         req_batch: &ReqBatch,
-        quorum_cnt: u8,
+        quorum_cnt: u16,
         bodega_conf: &RespondersConf,
     ) -> Result<bool, SummersetError> {
         if leader_bk.accept_acks.count() < quorum_cnt {
@@ -65,7 +65,7 @@ impl BodegaReplica {
     ) {
         for (_, req) in reqs {
             if let ApiRequest::Req {
-                cmd: Command::Put { key, .. },
+                cmd: Command::Put { key, .. } | Command::Cas { key, .. },
                 ..
             } = req
             {
@@ -112,14 +112,22 @@ impl BodegaReplica {
                     // instance committed, return the latest value for the key
                     // in batch
                     for (_, req) in inst.reqs.iter().rev() {
-                        if let ApiRequest::Req {
-                            cmd: Command::Put { key: k, value },
-                            ..
-                        } = req
-                        {
-                            if k == key {
+                        match req {
+                            ApiRequest::Req {
+                                cmd: Command::Put { key: k, value },
+                                ..
+                            } if k == key => {
                                 return Ok(Some((slot, Some(value.clone()))));
                             }
+                            ApiRequest::Req {
+                                cmd: Command::Cas { key: k, .. },
+                                ..
+                            } if k == key => {
+                                // outcome of a compare-and-swap is only known
+                                // after execution; play safe
+                                return Ok(Some((slot, None)));
+                            }
+                            _ => {}
                         }
                     }
                     logged_err!(
@@ -159,20 +167,17 @@ impl BodegaReplica {
         Ok(())
     }
 
-    /// Read only req on key: I am the stable leader.
+    /// Read only req, a Get or a Scan: I am the stable leader.
     pub(super) async fn read_on_stable_leader(
         &mut self,
         client: ClientId,
         req_id: RequestId,
-        key: String,
+        cmd: Command,
     ) -> Result<(), SummersetError> {
         // has to use the `do_sync_cmd()` API
         let (old_results, cmd_result) = self
             .state_machine
-            .do_sync_cmd(
-                Self::make_ro_command_id(client, req_id),
-                Command::Get { key },
-            )
+            .do_sync_cmd(Self::make_ro_command_id(client, req_id), cmd)
             .await?;
         for (old_id, old_result) in old_results {
             self.handle_cmd_result(old_id, old_result).await?;
@@ -230,6 +235,7 @@ impl BodegaReplica {
                         ApiRequest::Req {
                             id: req_id,
                             cmd: Command::Get { key },
+                            checksum: None,
                         },
                     ));
                     None
@@ -284,6 +290,7 @@ impl BodegaReplica {
                 if let ApiRequest::Req {
                     id: req_id,
                     cmd: Command::Get { key },
+                    ..
                 } = req
                 {
                     self.external_api.send_reply(
@@ -309,10 +316,22 @@ impl BodegaReplica {
             if let ApiRequest::Req {
                 id: req_id,
                 cmd: Command::Get { key },
+                ..
             } = req
             {
                 // locate the last write in batch for the key
                 for (_, req) in inst.reqs.iter().rev() {
+                    if let ApiRequest::Req {
+                        cmd: Command::Cas { key: k, .. },
+                        ..
+                    } = req
+                    {
+                        if k == &key {
+                            // outcome of a compare-and-swap is only known
+                            // after execution; reject below
+                            break;
+                        }
+                    }
                     if let ApiRequest::Req {
                         cmd: Command::Put { key: k, value },
                         ..
@@ -343,7 +362,7 @@ impl BodegaReplica {
                         }
                     }
                 }
-                // although should not reach here, playing safe...
+                // last write is a compare-and-swap, or playing safe...
                 self.external_api.send_reply(
                     ApiReply::rq_retry(
                         req_id,
@@ -544,6 +544,10 @@ impl BodegaReplica {
             PeerMsg::CommitNotice { ballot, commit_bar } => {
                 self.heard_commit_notice(peer, ballot, commit_bar)
             }
+            PeerMsg::AccessReport { counts } => {
+                self.handle_msg_access_report(peer, counts);
+                Ok(())
+            }
         }
     }
 }
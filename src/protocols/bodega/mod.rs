@@ -15,6 +15,7 @@ mod localread;
 mod messages;
 mod recovery;
 mod request;
+mod skewness;
 mod snapshot;
 
 use std::collections::HashMap;
@@ -25,14 +26,19 @@ use crate::client::{ClientApiStub, ClientCtrlStub, ClientId, GenericEndpoint};
 use crate::manager::{CtrlMsg, CtrlReply, CtrlRequest};
 use crate::protocols::SmrProtocol;
 use crate::server::{
-    ApiReply, ApiRequest, Command, CommandId, CommandResult, ControlHub,
-    ExternalApi, GenericReplica, HeartbeatEvent, Heartbeater, LeaseManager,
-    LogActionId, ReplicaId, RequestId, StateMachine, StorageHub, TransportHub,
+    AccessTracker, ApiReply, ApiRequest, Command, CommandId, CommandResult,
+    ControlHub, ExternalApi, GenericReplica, HeartbeatEvent, Heartbeater,
+    LeaseManager, LogActionId, ReplicaId, RequestId, StateMachine, StorageHub,
+    TransportHub,
+};
+use crate::utils::{
+    new_rng, Bitmap, IntyKey, RespondersConf, SummersetError, Timer,
 };
-use crate::utils::{Bitmap, RespondersConf, SummersetError, Timer};
 
 use atomic_refcell::AtomicRefCell;
 
+use rand::rngs::StdRng;
+
 use async_trait::async_trait;
 
 use get_size::GetSize;
@@ -51,6 +57,19 @@ pub struct ReplicaConfigBodega {
     /// Client request batching maximum batch size.
     pub max_batch_size: usize,
 
+    /// Number of completed replies cached per client for collapsing retried
+    /// requests by idempotency key; 0 means collapsing disabled.
+    pub idem_cache_size: usize,
+
+    /// Idle timeout in secs after which a client session that sent no request
+    /// is closed and its state reclaimed; 0 means never.
+    pub session_idle_s: u64,
+
+    /// Quota in KiB of approximate key-value bytes stored per namespace (the
+    /// key prefix before the first '/'), beyond which Puts are rejected; 0
+    /// means unlimited.
+    pub ns_quota_kb: usize,
+
     /// Path to backing log file.
     pub backer_path: String,
 
@@ -92,6 +111,23 @@ pub struct ReplicaConfigBodega {
     /// Maximum chunk size (in slots) of any bulk messages.
     pub msg_chunk_size: usize,
 
+    /// Interval of skewness-aware responders reassignment in millisecs. The
+    /// leader gathers per-key-range read counts from all replicas and moves
+    /// responder roles toward replicas that receive the reads. 0 means
+    /// never reassigning responders autonomously.
+    pub responders_adapt_ms: u64,
+
+    /// Number of consecutive integer keys per tracked key range.
+    pub responders_range_width: u64,
+
+    /// Minimum number of reads to a key range within the tracking window for
+    /// it to be considered for reassignment.
+    pub responders_min_reads: u64,
+
+    /// Minimum share of a key range's reads a replica must receive to become
+    /// one of its responders.
+    pub responders_min_share: f64,
+
     // [for perf breakdown only]
     /// Recording performance breakdown statistics?
     pub record_breakdown: bool,
@@ -112,6 +148,9 @@ impl Default for ReplicaConfigBodega {
         ReplicaConfigBodega {
             batch_interval_ms: 1,
             max_batch_size: 5000,
+            idem_cache_size: 0,
+            session_idle_s: 0,
+            ns_quota_kb: 0,
             backer_path: "/tmp/summerset.bodega.wal".into(),
             logger_sync: false,
             hb_hear_timeout_min: 1200,
@@ -125,6 +164,10 @@ impl Default for ReplicaConfigBodega {
             snapshot_path: "/tmp/summerset.bodega.snap".into(),
             snapshot_interval_s: 0,
             msg_chunk_size: 10,
+            responders_adapt_ms: 0,
+            responders_range_width: 1000,
+            responders_min_reads: 100,
+            responders_min_share: 0.2,
             record_breakdown: false,
             record_node_cnts: false,
             sim_read_lease: false,
@@ -242,9 +285,9 @@ enum WalEntry {
 
 /// Snapshot file entry type.
 //
-// NOTE: the current implementation simply appends a squashed log at the
-//       end of the snapshot file for simplicity. In production, the snapshot
-//       file should be a bounded-sized backend, e.g., an LSM-tree.
+// NOTE: the current implementation rewrites a whole image of the state
+//       machine into the snapshot file for simplicity. In production, the
+//       snapshot file should be a bounded-sized backend, e.g., an LSM-tree.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, GetSize)]
 enum SnapEntry {
     /// Necessary slot indices to remember.
@@ -254,8 +297,9 @@ enum SnapEntry {
         start_slot: usize,
     },
 
-    /// Set of key-value pairs to apply to the state.
-    KVPairSet { pairs: HashMap<String, String> },
+    /// Image of the state machine taken by `take_snapshot()`, reflecting
+    /// exactly the commands of all slots before start_slot.
+    StateImage { image: Vec<u8> },
 }
 
 /// Peer-peer message type.
@@ -319,6 +363,10 @@ enum PeerMsg {
 
     /// Early Accept notice from replica to peer follower.
     AcceptNotice { slot: usize, ballot: Ballot },
+
+    /// Periodic report of local read counts from replica to leader, as a list
+    /// of (key range index, count) pairs.
+    AccessReport { counts: Vec<(IntyKey, u64)> },
 }
 
 /// Bodega server replica module.
@@ -327,10 +375,10 @@ pub(crate) struct BodegaReplica {
     id: ReplicaId,
 
     /// Total number of replicas in cluster.
-    population: u8,
+    population: u16,
 
     /// Majority quorum size.
-    quorum_cnt: u8,
+    quorum_cnt: u16,
 
     /// Configuration parameters struct.
     // NOTE: this is not the "config" of leader & responders; see `bodega_conf`.
@@ -368,6 +416,9 @@ pub(crate) struct BodegaReplica {
     /// has no leader.
     volunteer_timer: Timer,
 
+    /// Random number generator for volunteer step-up timeouts.
+    volunteer_rng: StdRng,
+
     /// LeaseManager module.
     lease_manager: LeaseManager,
 
@@ -435,6 +486,12 @@ pub(crate) struct BodegaReplica {
     /// Current durable snapshot file offset.
     snap_offset: usize,
 
+    /// Per-key-range read access frequency tracker.
+    access_tracker: AccessTracker,
+
+    /// Timer for skewness-aware responders reassignment.
+    responders_adapt_interval: Interval,
+
     // [for perf breakdown only]
     /// Performance breakdown printing interval.
     bd_print_interval: Interval,
@@ -481,13 +538,13 @@ impl BodegaReplica {
     /// Compose a unique ballot number from base.
     #[inline]
     fn make_unique_ballot(&self, base: u64) -> Ballot {
-        ((base << 8) | ((self.id + 1) as u64)) as Ballot
+        ((base << 16) | ((self.id + 1) as u64)) as Ballot
     }
 
     /// Compose a unique ballot number greater than the given one.
     #[inline]
     fn make_greater_ballot(&self, bal: Ballot) -> Ballot {
-        self.make_unique_ballot((bal >> 8) + 1)
+        self.make_unique_ballot((bal >> 16) + 1)
     }
 
     /// Compose LogActionId from slot index & entry type.
@@ -553,18 +610,25 @@ impl GenericReplica for BodegaReplica {
         // connect to the cluster manager and get assigned a server ID
         let mut control_hub = ControlHub::new_and_setup(manager).await?;
         let id = control_hub.me;
+        let (api_addr, p2p_addr) =
+            control_hub.resolve_addrs(api_addr, p2p_addr)?;
         let population = control_hub.population;
 
         // parse protocol-specific configs
         let config = parsed_config!(config_str => ReplicaConfigBodega;
                                     batch_interval_ms, max_batch_size,
+                                    idem_cache_size, session_idle_s,
+                                    ns_quota_kb,
                                     backer_path, logger_sync,
                                     hb_hear_timeout_min, hb_hear_timeout_max,
                                     hb_send_interval_ms, disable_hb_timer,
                                     disallow_step_up, lease_expire_ms,
                                     urgent_commit_notice, urgent_accept_notice,
                                     snapshot_path, snapshot_interval_s,
-                                    msg_chunk_size, record_breakdown,
+                                    msg_chunk_size, responders_adapt_ms,
+                                    responders_range_width,
+                                    responders_min_reads,
+                                    responders_min_share, record_breakdown,
                                     record_node_cnts, sim_read_lease)?;
         if config.batch_interval_ms == 0 {
             return logged_err!(
@@ -602,14 +666,29 @@ impl GenericReplica for BodegaReplica {
                 config.msg_chunk_size
             );
         }
+        if config.responders_min_share <= 0.0
+            || config.responders_min_share > 1.0
+        {
+            return logged_err!(
+                "invalid config.responders_min_share '{}'",
+                config.responders_min_share
+            );
+        }
+
+        // setup per-key-range access tracker; fails if range width invalid
+        let access_tracker =
+            AccessTracker::new(population, config.responders_range_width)?;
 
         // setup state machine module
-        let state_machine = StateMachine::new_and_setup(id).await?;
+        let state_machine =
+            StateMachine::new_and_setup(id, config.ns_quota_kb * 1024).await?;
+        control_hub.inspect_keys(&state_machine);
 
         // setup storage hub module
         let storage_hub =
             StorageHub::new_and_setup(id, Path::new(&config.backer_path))
                 .await?;
+        control_hub.monitor_wal(Path::new(&config.backer_path));
 
         // setup heartbeat management module
         let mut heartbeater = Heartbeater::new_and_setup(
@@ -633,7 +712,9 @@ impl GenericReplica for BodegaReplica {
         let mut transport_hub = TransportHub::new_and_setup(
             id,
             population,
+            SmrProtocol::Bodega,
             p2p_addr,
+            false,
             HashMap::from([(
                 0, // only one lease purpose exists in Bodega
                 tx_lease_msg,
@@ -655,7 +736,7 @@ impl GenericReplica for BodegaReplica {
         {
             to_peers
         } else {
-            return logged_err!("unexpected ctrl msg type received");
+            return logged_err!(Protocol; "unexpected ctrl msg type received");
         };
 
         // proactively connect to some peers, then wait for all population
@@ -671,13 +752,16 @@ impl GenericReplica for BodegaReplica {
                 .await?;
 
         // setup external API module, ready to take in client requests
-        let external_api = ExternalApi::new_and_setup(
+        let mut external_api = ExternalApi::new_and_setup(
             id,
             api_addr,
             Duration::from_millis(config.batch_interval_ms),
             config.max_batch_size,
+            config.idem_cache_size,
+            Duration::from_secs(config.session_idle_s),
         )
         .await?;
+        external_api.stamp_replies(&state_machine);
 
         let mut snapshot_interval = time::interval(Duration::from_secs(
             if config.snapshot_interval_s > 0 {
@@ -688,6 +772,16 @@ impl GenericReplica for BodegaReplica {
         ));
         snapshot_interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
+        let mut responders_adapt_interval = time::interval(
+            Duration::from_millis(if config.responders_adapt_ms > 0 {
+                config.responders_adapt_ms
+            } else {
+                1000 // dummy non-zero value to make `time::interval` happy
+            }),
+        );
+        responders_adapt_interval
+            .set_missed_tick_behavior(MissedTickBehavior::Skip);
+
         // [for perf breakdown only]
         let mut bd_print_interval = time::interval(Duration::from_secs(5));
         bd_print_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
@@ -707,6 +801,7 @@ impl GenericReplica for BodegaReplica {
             transport_hub,
             heartbeater,
             volunteer_timer: Timer::new::<fn()>(true, None, false),
+            volunteer_rng: new_rng(id, "volunteer"),
             lease_manager,
             bodega_conf: RespondersConf::empty(population),
             insts: vec![],
@@ -727,6 +822,8 @@ impl GenericReplica for BodegaReplica {
             highest_slot: HashMap::new(),
             wal_offset: 0,
             snap_offset: 0,
+            access_tracker,
+            responders_adapt_interval,
             bd_print_interval,
             node_cnts_stats: (0..population).map(|s| (s, 0)).collect(),
         })
@@ -852,6 +949,14 @@ impl GenericReplica for BodegaReplica {
                     }
                 },
 
+                // skewness-aware responders reassignment timeout
+                _ = self.responders_adapt_interval.tick(), if !paused
+                                                              && self.config.responders_adapt_ms > 0 => {
+                    if let Err(e) = self.adapt_responders().await {
+                        pf_error!("error adapting responders: {}", e);
+                    }
+                },
+
                 // [for perf breakdown only]
                 // performance breakdown stats printing
                 _ = self.bd_print_interval.tick(), if !paused && self.config.record_breakdown => {
@@ -900,7 +1005,7 @@ impl GenericReplica for BodegaReplica {
         self.id
     }
 
-    fn population(&self) -> u8 {
+    fn population(&self) -> u16 {
         self.population
     }
 }
@@ -938,7 +1043,7 @@ pub(crate) struct BodegaClient {
     id: ClientId,
 
     /// Number of servers in the cluster.
-    population: u8,
+    population: u16,
 
     /// Configuration parameters struct.
     config: ClientConfigBodega,
@@ -1072,7 +1177,7 @@ impl GenericEndpoint for BodegaClient {
                 }
                 Ok(())
             }
-            _ => logged_err!("unexpected reply type received"),
+            _ => logged_err!(Protocol; "unexpected reply type received"),
         }
     }
 
@@ -1148,7 +1253,8 @@ impl GenericEndpoint for BodegaClient {
                 if let Some(ApiRequest::Req {
                     id: req_id,
                     cmd: Command::Get { key },
-                }) = req
+                    ..
+                }) = req.map(ApiRequest::unfenced)
                 {
                     // NOTE: for closed-loop clients we can make the timer once
                     //       and reuse it for requests, but we are here creating
@@ -1184,6 +1290,31 @@ impl GenericEndpoint for BodegaClient {
         }
     }
 
+    fn send_hedge(&mut self, req: &ApiRequest) -> Result<bool, SummersetError> {
+        // only a read sent to a near server other than the believed leader
+        // can be hedged, by duplicating it to the leader, which can always
+        // serve it safely
+        if req.read_only().is_none()
+            || self.last_server_id.is_some()
+            || self.near_server_id.is_none_or(|s| s == self.curr_server_id)
+        {
+            return Ok(false);
+        }
+
+        match self.api_stubs.get(&self.curr_server_id) {
+            Some(api_stub) => {
+                let mut api_stub = api_stub.borrow_mut();
+                let mut success = api_stub.send_req(Some(req))?;
+                while !success {
+                    success = api_stub.send_req(None)?;
+                }
+                pf_debug!("hedged read to replica {}", self.curr_server_id);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
     async fn recv_reply(&mut self) -> Result<ApiReply, SummersetError> {
         if !self.api_stubs.contains_key(&self.curr_server_id) {
             return Err(SummersetError::msg(format!(
@@ -1265,12 +1396,18 @@ impl GenericEndpoint for BodegaClient {
                                         result: None,
                                         redirect: None,
                                         rq_retry: None,
+                                        cert: None,
+                                        error: None,
+                                        read_path: None,
+                                        conf_epoch: None,
+                                        hlc: None,
                                     }
                                 }
 
                                 curr_stub.send_req(Some(&ApiRequest::Req {
                                     id: req_id,
-                                    cmd: Command::Get { key: key.clone() }
+                                    cmd: Command::Get { key: key.clone() },
+                                    checksum: None,
                                 }))?;
 
                             }
@@ -1280,11 +1417,28 @@ impl GenericEndpoint for BodegaClient {
             }
         };
 
+        if let ApiReply::LeaderChange { leader } = reply {
+            // notified of a new leader; switch over to it, or keep waiting if
+            // already talking to it
+            if leader == self.curr_server_id
+                || !self.servers.contains_key(&leader)
+            {
+                return self.recv_reply().await;
+            }
+            self.curr_server_id = leader;
+            pf_debug!(
+                "notified of new leader {} '{}'",
+                leader,
+                self.servers[&leader]
+            );
+        }
+
         if let ApiReply::Reply {
             id: req_id,
             ref result,
             ref redirect,
             ref mut rq_retry,
+            ..
         } = reply
         {
             // if the current server redirects me to a different server
@@ -1327,6 +1481,7 @@ impl GenericEndpoint for BodegaClient {
                     .send_req(Some(&ApiRequest::Req {
                         id: req_id,
                         cmd: read_cmd,
+                        checksum: None,
                     }))?;
                 return self.recv_reply().await;
             }
@@ -1339,7 +1494,7 @@ impl GenericEndpoint for BodegaClient {
         self.id
     }
 
-    fn population(&self) -> u8 {
+    fn population(&self) -> u16 {
         self.population
     }
 
@@ -146,7 +146,7 @@ impl BodegaReplica {
                     break;
                 }
                 _ => {
-                    return logged_err!("unexpected log result type");
+                    return logged_err!(Protocol; "unexpected log result type");
                 }
             }
         }
@@ -174,7 +174,9 @@ impl BodegaReplica {
             }
             Ok(())
         } else {
-            logged_err!("unexpected log result type or failed truncate")
+            logged_err!(
+                Protocol; "unexpected log result type or failed truncate"
+            )
         }
     }
 }
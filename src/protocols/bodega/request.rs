@@ -102,17 +102,31 @@ impl BodegaReplica {
     ) -> Result<ReqBatch, SummersetError> {
         let mut filtered = vec![];
 
+        // track where reads of each key range arrive at
+        if self.config.responders_adapt_ms > 0 {
+            for (_, req) in &req_batch {
+                if let ApiRequest::Req {
+                    cmd: Command::Get { key },
+                    ..
+                } = req
+                {
+                    self.access_tracker.record(key, self.id);
+                }
+            }
+        }
+
         if self.is_stable_leader() {
             // conditions of majority-leased stable leader met, can reply
-            // read-only commands directly back to clients by simply using
-            // the last committed value
+            // read-only commands, including scans, directly back to clients
+            // by simply using the last committed value
             for (client, req) in req_batch {
                 if let ApiRequest::Req {
                     id: req_id,
-                    cmd: Command::Get { key },
+                    cmd: cmd @ (Command::Get { .. } | Command::Scan { .. }),
+                    ..
                 } = req
                 {
-                    self.read_on_stable_leader(client, req_id, key).await?;
+                    self.read_on_stable_leader(client, req_id, cmd).await?;
                     continue;
                 }
                 filtered.push((client, req));
@@ -126,6 +140,7 @@ impl BodegaReplica {
                 if let ApiRequest::Req {
                     id: req_id,
                     cmd: Command::Get { key },
+                    ..
                 } = req
                 {
                     if self.is_responder_for(&key) {
@@ -150,6 +165,7 @@ impl BodegaReplica {
                 if let ApiRequest::Req {
                     id: req_id,
                     cmd: Command::Get { key },
+                    ..
                 } = req
                 {
                     self.read_on_improper_node(client, req_id, key)?;
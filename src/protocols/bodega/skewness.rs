@@ -0,0 +1,73 @@
+//! Bodega -- skewness-aware responders reassignment.
+
+use super::*;
+
+use crate::utils::SummersetError;
+
+// BodegaReplica skewness-aware responders reassignment logic
+impl BodegaReplica {
+    /// Handler of AccessReport message from a peer.
+    pub(super) fn handle_msg_access_report(
+        &mut self,
+        peer: ReplicaId,
+        counts: Vec<(IntyKey, u64)>,
+    ) {
+        if !self.is_leader() {
+            return; // only the leader aggregates access counts
+        }
+        pf_trace!("received AccessReport <- {} ranges {}", peer, counts.len());
+        self.access_tracker.merge_report(peer, counts);
+    }
+
+    /// Periodic responders reassignment tick. Non-leaders report their local
+    /// read counts to the leader; the leader proposes responders for hot key
+    /// ranges from the aggregated counts and, if that changes the current
+    /// config, announces the new config through the same path as
+    /// user-initiated conf changes.
+    pub(super) async fn adapt_responders(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        if !self.is_leader() {
+            let counts = self.access_tracker.drain_report(self.id);
+            if let Some(leader) = self.bodega_conf.leader {
+                if !counts.is_empty() {
+                    self.transport_hub
+                        .send_msg(PeerMsg::AccessReport { counts }, leader)?;
+                }
+            }
+            return Ok(());
+        }
+        if self.bal_prepared == 0 {
+            return Ok(()); // not yet a prepared leader
+        }
+
+        let proposals = self.access_tracker.propose(
+            &self.bodega_conf,
+            self.config.responders_min_reads,
+            self.config.responders_min_share,
+        )?;
+        if proposals.is_empty() {
+            return Ok(());
+        }
+
+        let mut new_conf = self.bodega_conf.clone();
+        for (range, responders) in proposals {
+            pf_debug!(
+                "reassigning responders {}-{} => {:?}",
+                range.0,
+                range.1,
+                responders
+            );
+            new_conf.set_responders(Some(&range), responders, None)?;
+        }
+
+        if new_conf != self.bodega_conf {
+            self.announce_new_conf(new_conf.clone()).await?;
+            self.control_hub.send_ctrl(CtrlMsg::RespondersConf {
+                conf_num: self.bal_max_seen,
+                new_conf,
+            })?;
+        }
+        Ok(())
+    }
+}
@@ -91,6 +91,11 @@ impl ChainRepReplica {
                 Ok(None)
             }
 
+            CtrlMsg::ExpireClients { clients } => {
+                self.external_api.expire_clients(&clients)?;
+                Ok(None)
+            }
+
             _ => Ok(None), // ignore all other types
         }
     }
@@ -31,7 +31,9 @@ impl ChainRepReplica {
             // then update self.wal_offset
             self.wal_offset = now_size;
         } else {
-            return logged_err!("unexpected log result type: {:?}", log_result);
+            return logged_err!(
+                Protocol; "unexpected log result type: {:?}", log_result
+            );
         }
         pf_trace!("finished durable logging for slot {}", slot);
 
@@ -54,14 +56,15 @@ impl ChainRepReplica {
                         for (cmd_idx, (_, req)) in
                             self.log[self.prop_bar].reqs.iter().enumerate()
                         {
-                            if let ApiRequest::Req { cmd, .. } = req {
-                                self.state_machine.submit_cmd(
+                            if let ApiRequest::Req { cmd, checksum, .. } = req {
+                                self.state_machine.submit_checked_cmd(
                                     Self::make_command_id(
                                         self.prop_bar,
                                         cmd_idx,
                                         false,
                                     ),
                                     cmd.clone(),
+                                    *checksum,
                                 )?;
                             }
                         }
@@ -69,7 +69,7 @@ impl ChainRepReplica {
                         );
                     }
                 } else {
-                    return logged_err!("unexpected API request type");
+                    return logged_err!(Protocol; "unexpected API request type");
                 }
             }
 
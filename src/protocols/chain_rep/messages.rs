@@ -81,14 +81,15 @@ impl ChainRepReplica {
                     for (cmd_idx, (_, req)) in
                         self.log[self.prop_bar].reqs.iter().enumerate()
                     {
-                        if let ApiRequest::Req { cmd, .. } = req {
-                            self.state_machine.submit_cmd(
+                        if let ApiRequest::Req { cmd, checksum, .. } = req {
+                            self.state_machine.submit_checked_cmd(
                                 Self::make_command_id(
                                     self.prop_bar,
                                     cmd_idx,
                                     false,
                                 ),
                                 cmd.clone(),
+                                *checksum,
                             )?;
                         }
                     }
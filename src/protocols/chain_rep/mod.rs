@@ -43,6 +43,19 @@ pub struct ReplicaConfigChainRep {
     /// Client request batching maximum batch size.
     pub max_batch_size: usize,
 
+    /// Number of completed replies cached per client for collapsing retried
+    /// requests by idempotency key; 0 means collapsing disabled.
+    pub idem_cache_size: usize,
+
+    /// Idle timeout in secs after which a client session that sent no request
+    /// is closed and its state reclaimed; 0 means never.
+    pub session_idle_s: u64,
+
+    /// Quota in KiB of approximate key-value bytes stored per namespace (the
+    /// key prefix before the first '/'), beyond which Puts are rejected; 0
+    /// means unlimited.
+    pub ns_quota_kb: usize,
+
     /// Path to backing log file.
     pub backer_path: String,
 
@@ -56,6 +69,9 @@ impl Default for ReplicaConfigChainRep {
         ReplicaConfigChainRep {
             batch_interval_ms: 1,
             max_batch_size: 5000,
+            idem_cache_size: 0,
+            session_idle_s: 0,
+            ns_quota_kb: 0,
             backer_path: "/tmp/summerset.chain_rep.wal".into(),
             logger_sync: false,
         }
@@ -76,6 +92,13 @@ enum Status {
 /// Request batch type.
 type ReqBatch = Vec<(ClientId, ApiRequest)>;
 
+/// Is the request served at the tail? Reads, including scans, are; writes
+/// enter the chain at the head.
+#[inline]
+fn served_at_tail(req: &ApiRequest) -> bool {
+    req.read_only().is_some() || req.scan_range().is_some()
+}
+
 /// In-memory log entry containing a commands batch.
 struct LogEntry {
     /// Log entry status.
@@ -112,7 +135,7 @@ pub(crate) struct ChainRepReplica {
     id: ReplicaId,
 
     /// Total number of replicas in cluster.
-    population: u8,
+    population: u16,
 
     /// Configuration parameters struct.
     config: ReplicaConfigChainRep,
@@ -249,11 +272,15 @@ impl GenericReplica for ChainRepReplica {
         // connect to the cluster manager and get assigned a server ID
         let mut control_hub = ControlHub::new_and_setup(manager).await?;
         let id = control_hub.me;
+        let (api_addr, p2p_addr) =
+            control_hub.resolve_addrs(api_addr, p2p_addr)?;
         let population = control_hub.population;
 
         // parse protocol-specific configs
         let config = parsed_config!(config_str => ReplicaConfigChainRep;
                                     batch_interval_ms, max_batch_size,
+                                    idem_cache_size, session_idle_s,
+                                    ns_quota_kb,
                                     backer_path, logger_sync)?;
         if config.batch_interval_ms == 0 {
             return logged_err!(
@@ -263,7 +290,9 @@ impl GenericReplica for ChainRepReplica {
         }
 
         // setup state machine module
-        let state_machine = StateMachine::new_and_setup(id).await?;
+        let state_machine =
+            StateMachine::new_and_setup(id, config.ns_quota_kb * 1024).await?;
+        control_hub.inspect_keys(&state_machine);
 
         // setup storage hub module
         let storage_hub =
@@ -274,7 +303,9 @@ impl GenericReplica for ChainRepReplica {
         let mut transport_hub = TransportHub::new_and_setup(
             id,
             population,
+            SmrProtocol::ChainRep,
             p2p_addr,
+            false,
             HashMap::new(),
         )
         .await?;
@@ -293,7 +324,7 @@ impl GenericReplica for ChainRepReplica {
         {
             to_peers
         } else {
-            return logged_err!("unexpected ctrl msg type received");
+            return logged_err!(Protocol; "unexpected ctrl msg type received");
         };
 
         // proactively connect to some peers, then wait for all population
@@ -304,13 +335,16 @@ impl GenericReplica for ChainRepReplica {
         transport_hub.wait_for_group(population).await?;
 
         // setup external API module, ready to take in client requests
-        let external_api = ExternalApi::new_and_setup(
+        let mut external_api = ExternalApi::new_and_setup(
             id,
             api_addr,
             Duration::from_millis(config.batch_interval_ms),
             config.max_batch_size,
+            config.idem_cache_size,
+            Duration::from_secs(config.session_idle_s),
         )
         .await?;
+        external_api.stamp_replies(&state_machine);
 
         Ok(ChainRepReplica {
             id,
@@ -424,7 +458,7 @@ impl GenericReplica for ChainRepReplica {
         self.id
     }
 
-    fn population(&self) -> u8 {
+    fn population(&self) -> u16 {
         self.population
     }
 }
@@ -455,7 +489,7 @@ pub(crate) struct ChainRepClient {
     id: ClientId,
 
     /// Number of servers in the cluster.
-    population: u8,
+    population: u16,
 
     /// Configuration parameters struct.
     _config: ClientConfigChainRep,
@@ -588,7 +622,7 @@ impl GenericEndpoint for ChainRepClient {
                 Ok(())
             }
 
-            _ => logged_err!("unexpected reply type received"),
+            _ => logged_err!(Protocol; "unexpected reply type received"),
         }
     }
 
@@ -630,7 +664,7 @@ impl GenericEndpoint for ChainRepClient {
         req: Option<&ApiRequest>,
     ) -> Result<bool, SummersetError> {
         let read_only = match req {
-            Some(req) if req.read_only().is_some() => true,
+            Some(req) if served_at_tail(req) => true,
             None if self.last_read_only => true,
             _ => false,
         };
@@ -663,7 +697,7 @@ impl GenericEndpoint for ChainRepClient {
         self.id
     }
 
-    fn population(&self) -> u8 {
+    fn population(&self) -> u16 {
         self.population
     }
 
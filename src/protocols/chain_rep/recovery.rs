@@ -78,7 +78,7 @@ impl ChainRepReplica {
                     break;
                 }
                 _ => {
-                    return logged_err!("unexpected log result type");
+                    return logged_err!(Protocol; "unexpected log result type");
                 }
             }
         }
@@ -106,7 +106,9 @@ impl ChainRepReplica {
             }
             Ok(())
         } else {
-            logged_err!("unexpected log result type or failed truncate")
+            logged_err!(
+                Protocol; "unexpected log result type or failed truncate"
+            )
         }
     }
 }
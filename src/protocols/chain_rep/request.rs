@@ -18,7 +18,7 @@ impl ChainRepReplica {
 
         // if I'm not the head for Put requests or not the tail for Get
         // requests, ignore the request batch; NOTE: only checking req 0
-        let read_only = req_batch[0].1.read_only().is_some();
+        let read_only = served_at_tail(&req_batch[0].1);
         if !((self.is_head() && !read_only) || (self.is_tail() && read_only)) {
             pf_warn!(
                 "ignoring request batch: head? {} tail? {} read-only? {}",
@@ -34,18 +34,24 @@ impl ChainRepReplica {
             // tail receives Get requests; submit to state machine right away
             let num_reqs = req_batch.len();
             for (client, req) in req_batch {
-                if req.read_only().is_none() {
+                if !served_at_tail(&req) {
                     return logged_err!("non read-only command seen at tail");
                 }
 
-                if let ApiRequest::Req { id: req_id, cmd } = req {
-                    self.state_machine.submit_cmd(
+                if let ApiRequest::Req {
+                    id: req_id,
+                    cmd,
+                    checksum,
+                } = req
+                {
+                    self.state_machine.submit_checked_cmd(
                         Self::make_command_id(
                             req_id as usize,
                             client as usize,
                             true,
                         ),
                         cmd,
+                        checksum,
                     )?;
                 }
             }
@@ -53,7 +59,7 @@ impl ChainRepReplica {
         } else {
             // head receives Put requests; record a new log entry durably
             for (_, req) in &req_batch {
-                if req.read_only().is_some() {
+                if served_at_tail(req) {
                     return logged_err!("read-only command seen at head");
                 }
             }
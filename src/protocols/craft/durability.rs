@@ -9,6 +9,31 @@ use crate::utils::{Bitmap, SummersetError};
 
 // CRaftReplica durable logging
 impl CRaftReplica {
+    /// Collects followers whose outgoing queues are congested, if skipping
+    /// them still leaves enough followers to commit with me, which takes
+    /// `majority + fault_tolerance` replicas when not in full-copy mode.
+    /// Otherwise returns an empty set.
+    fn congested_followers(&mut self) -> Result<Bitmap, SummersetError> {
+        let mut congested = Bitmap::new(self.population, false);
+        if self.config.peer_queue_high == 0 {
+            return Ok(congested);
+        }
+        for peer in 0..self.population {
+            if peer != self.id && self.transport_hub.peer_congested(peer)? {
+                congested.set(peer, true)?;
+            }
+        }
+        let commit_quorum = if self.full_copy_mode {
+            self.majority
+        } else {
+            self.majority + self.config.fault_tolerance
+        };
+        if self.population - congested.count() < commit_quorum {
+            return Ok(Bitmap::new(self.population, false));
+        }
+        Ok(congested)
+    }
+
     /// Handler of leader append logging result chan recv.
     async fn handle_logged_leader_append(
         &mut self,
@@ -26,9 +51,14 @@ impl CRaftReplica {
         debug_assert_eq!(slot, slot_e);
 
         // broadcast AppendEntries messages to followers, each containing just
-        // the one shard of each entry for that follower
+        // the one shard of each entry for that follower, holding off the
+        // congested ones; their entries go out on a later trigger
+        let skipped = self.congested_followers()?;
         for peer in 0..self.population {
-            if peer == self.id || self.try_next_slot[&peer] < 1 {
+            if peer == self.id
+                || self.try_next_slot[&peer] < 1
+                || skipped.get(peer)?
+            {
                 continue;
             }
 
@@ -186,7 +216,9 @@ impl CRaftReplica {
             debug_assert!(now_size > self.log_offset);
             self.log_offset = now_size;
         } else {
-            return logged_err!("unexpected log result type: {:?}", log_result);
+            return logged_err!(
+                Protocol; "unexpected log result type: {:?}", log_result
+            );
         }
 
         match entry_type {
@@ -195,7 +227,9 @@ impl CRaftReplica {
                 self.handle_logged_leader_append(slot, slot_e).await
             }
             _ => {
-                logged_err!("unexpected log entry type: {:?}", entry_type)
+                logged_err!(
+                    Protocol; "unexpected log entry type: {:?}", entry_type
+                )
             }
         }
     }
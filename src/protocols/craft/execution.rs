@@ -40,7 +40,7 @@ impl CRaftReplica {
                 );
             }
         } else {
-            return logged_err!("unexpected API request type");
+            return logged_err!(Protocol; "unexpected API request type");
         }
 
         // if all commands in this entry have been executed, update last_exec
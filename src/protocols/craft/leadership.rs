@@ -5,8 +5,10 @@ use std::collections::HashSet;
 
 use super::*;
 
-use crate::manager::CtrlMsg;
-use crate::server::{LogAction, LogResult, ReplicaId};
+use crate::manager::{CtrlMsg, ServerStatus};
+use crate::server::{
+    DebugState, LogAction, LogResult, ReplicaId, ResourceUsage,
+};
 use crate::utils::SummersetError;
 
 // CRaftReplica leader election timeout logic
@@ -55,7 +57,7 @@ impl CRaftReplica {
             {
             } else {
                 return logged_err!(
-                    "unexpected log result type or failed write"
+                    Protocol; "unexpected log result type or failed write"
                 );
             }
 
@@ -202,7 +204,9 @@ impl CRaftReplica {
         } = result
         {
         } else {
-            return logged_err!("unexpected log result type or failed write");
+            return logged_err!(
+                Protocol; "unexpected log result type or failed write"
+            );
         }
 
         Ok(())
@@ -217,6 +221,7 @@ impl CRaftReplica {
         self.heartbeater.set_sending(true);
         self.control_hub
             .send_ctrl(CtrlMsg::LeaderStatus { step_up: true })?;
+        self.external_api.notify_leader_change(self.id)?;
 
         // clear peers' heartbeat reply counters, and broadcast a heartbeat now
         self.heartbeater.clear_reply_cnts(None)?;
@@ -314,6 +319,36 @@ impl CRaftReplica {
             self.heartbeater.kickoff_hear_timer(Some(peer))?;
         }
 
+        // expose my protocol state to debug queries
+        self.external_api.update_debug_state(DebugState {
+            is_leader: self.role == Role::Leader,
+            leader: self.leader,
+            ballot: self.curr_term,
+            commit_bar: self.last_commit,
+            exec_bar: self.last_exec,
+            start_slot: self.start_slot,
+            log_len: self.log.len(),
+            peers_alive: self.heartbeater.peer_alive().clone(),
+        });
+
+        // periodically report my status to the manager
+        self.control_hub.report_status(ServerStatus {
+            ballot: self.curr_term,
+            commit_bar: self.last_commit,
+            exec_bar: self.last_exec,
+            peers_alive: self.heartbeater.peer_alive().clone(),
+            perf_models: HashMap::new(),
+            hot_keys: self.state_machine.hot_keys(),
+            queue_depths: vec![
+                self.external_api.queue_depth_report(),
+                self.storage_hub.queue_depth_report(),
+                self.transport_hub.queue_depth_report(),
+                self.state_machine.queue_depth_report(),
+            ],
+            resources: ResourceUsage::default(),
+            divergence_repaired: 0,
+        })?;
+
         // pf_trace!("heard heartbeat <- {} term {}", peer, term);
         Ok(())
     }
@@ -88,6 +88,7 @@ impl CRaftReplica {
                 // also refresh heartbeat timer here since the "decrementing"
                 // procedure for a lagging follower might take long
                 self.leader = Some(leader);
+                self.external_api.notify_leader_change(leader)?;
                 self.heard_heartbeat(leader, term).await?;
             }
             return Ok(());
@@ -96,6 +97,7 @@ impl CRaftReplica {
         // update my knowledge of who's the current leader, and reset election
         // timeout timer
         self.leader = Some(leader);
+        self.external_api.notify_leader_change(leader)?;
         self.heard_heartbeat(leader, term).await?;
 
         // check if any existing entry conflicts with a new one in `entries`.
@@ -132,6 +134,7 @@ impl CRaftReplica {
                     self.log_offset = cut_offset;
                 } else {
                     return logged_err!(
+                        Protocol;
                         "unexpected log result type or failed truncate"
                     );
                 }
@@ -227,10 +230,11 @@ impl CRaftReplica {
                 let reqs = entry.reqs_cw.get_data()?;
 
                 for (cmd_idx, (_, req)) in reqs.iter().enumerate() {
-                    if let ApiRequest::Req { cmd, .. } = req {
-                        self.state_machine.submit_cmd(
+                    if let ApiRequest::Req { cmd, checksum, .. } = req {
+                        self.state_machine.submit_checked_cmd(
                             Self::make_command_id(slot, cmd_idx),
                             cmd.clone(),
+                            *checksum,
                         )?;
                     }
                 }
@@ -304,7 +308,7 @@ impl CRaftReplica {
                     .match_slot
                     .values()
                     .filter(|&&s| s >= slot)
-                    .count() as u8;
+                    .count() as u16;
                 if (!self.full_copy_mode
                     && match_cnt >= self.majority + self.config.fault_tolerance)
                     || (self.full_copy_mode && match_cnt >= self.majority)
@@ -340,10 +344,11 @@ impl CRaftReplica {
                 if can_execute {
                     let reqs = entry.reqs_cw.get_data()?;
                     for (cmd_idx, (_, req)) in reqs.iter().enumerate() {
-                        if let ApiRequest::Req { cmd, .. } = req {
-                            self.state_machine.submit_cmd(
+                        if let ApiRequest::Req { cmd, checksum, .. } = req {
+                            self.state_machine.submit_checked_cmd(
                                 Self::make_command_id(slot, cmd_idx),
                                 cmd.clone(),
+                                *checksum,
                             )?;
                         }
                     }
@@ -377,7 +382,7 @@ impl CRaftReplica {
                     .match_slot
                     .values()
                     .filter(|&&s| s >= slot)
-                    .count() as u8;
+                    .count() as u16;
                 if match_cnt == self.population {
                     // all servers have durably stored this entry
                     self.last_snap = slot;
@@ -581,7 +586,7 @@ impl CRaftReplica {
                 {
                 } else {
                     return logged_err!(
-                        "unexpected log result type or failed write"
+                        Protocol; "unexpected log result type or failed write"
                     );
                 }
             }
@@ -611,7 +616,7 @@ impl CRaftReplica {
         self.votes_granted.insert(peer);
 
         // if a majority of servers have voted for me, become the leader
-        if self.votes_granted.len() as u8 >= self.majority {
+        if self.votes_granted.len() as u16 >= self.majority {
             self.become_the_leader().await?;
         }
 
@@ -719,13 +724,14 @@ impl CRaftReplica {
                     // submit commands in committed instance to the state machine
                     // for execution
                     for (cmd_idx, (_, req)) in reqs.iter().enumerate() {
-                        if let ApiRequest::Req { cmd, .. } = req {
-                            self.state_machine.submit_cmd(
+                        if let ApiRequest::Req { cmd, checksum, .. } = req {
+                            self.state_machine.submit_checked_cmd(
                                 Self::make_command_id(
                                     self.last_commit + 1,
                                     cmd_idx,
                                 ),
                                 cmd.clone(),
+                                *checksum,
                             )?;
                         }
                     }
@@ -46,6 +46,19 @@ pub struct ReplicaConfigCRaft {
     /// Client request batching maximum batch size.
     pub max_batch_size: usize,
 
+    /// Number of completed replies cached per client for collapsing retried
+    /// requests by idempotency key; 0 means collapsing disabled.
+    pub idem_cache_size: usize,
+
+    /// Idle timeout in secs after which a client session that sent no request
+    /// is closed and its state reclaimed; 0 means never.
+    pub session_idle_s: u64,
+
+    /// Quota in KiB of approximate key-value bytes stored per namespace (the
+    /// key prefix before the first '/'), beyond which Puts are rejected; 0
+    /// means unlimited.
+    pub ns_quota_kb: usize,
+
     /// Path to backing log file.
     pub backer_path: String,
 
@@ -74,11 +87,19 @@ pub struct ReplicaConfigCRaft {
     pub snapshot_interval_s: u64,
 
     /// Fault-tolerance level.
-    pub fault_tolerance: u8,
+    pub fault_tolerance: u16,
 
     /// Maximum chunk size of any bulk of messages.
     pub msg_chunk_size: usize,
 
+    /// Outgoing queue depth (in messages) at which a follower is considered
+    /// congested and skipped for new entries, as long as the others can
+    /// still form a commit quorum. 0 means never skipping.
+    pub peer_queue_high: usize,
+    /// Outgoing queue depth at which a congested follower is considered
+    /// drained again.
+    pub peer_queue_low: usize,
+
     // [for benchmarking purposes only]
     /// Simulate local read lease implementation?
     pub sim_read_lease: bool,
@@ -90,6 +111,9 @@ impl Default for ReplicaConfigCRaft {
         ReplicaConfigCRaft {
             batch_interval_ms: 1,
             max_batch_size: 5000,
+            idem_cache_size: 0,
+            session_idle_s: 0,
+            ns_quota_kb: 0,
             backer_path: "/tmp/summerset.craft.wal".into(),
             logger_sync: false,
             hb_hear_timeout_min: 1200,
@@ -101,6 +125,8 @@ impl Default for ReplicaConfigCRaft {
             snapshot_interval_s: 0,
             fault_tolerance: 0,
             msg_chunk_size: 10,
+            peer_queue_high: 0,
+            peer_queue_low: 0,
             sim_read_lease: false,
         }
     }
@@ -192,8 +218,10 @@ enum SnapEntry {
         start_slot: usize,
     },
 
-    /// Set of key-value pairs to apply to the state.
-    KVPairSet { pairs: HashMap<String, String> },
+    /// Image of the state machine taken by `take_snapshot()`, reflecting
+    /// exactly the commands of all entries up to last_exec, which may be
+    /// beyond the start slot.
+    StateImage { last_exec: usize, image: Vec<u8> },
 }
 
 /// Peer-peer message type.
@@ -256,10 +284,10 @@ pub(crate) struct CRaftReplica {
     id: ReplicaId,
 
     /// Total number of replicas in cluster.
-    population: u8,
+    population: u16,
 
     /// Majority quorum size.
-    majority: u8,
+    majority: u16,
 
     /// True if we are in full-copy replication fallback mode.
     // NOTE: works that follow CRaft proposed more gradual fallback mechanisms,
@@ -421,17 +449,23 @@ impl GenericReplica for CRaftReplica {
         // connect to the cluster manager and get assigned a server ID
         let mut control_hub = ControlHub::new_and_setup(manager).await?;
         let id = control_hub.me;
+        let (api_addr, p2p_addr) =
+            control_hub.resolve_addrs(api_addr, p2p_addr)?;
         let population = control_hub.population;
 
         // parse protocol-specific configs
         let config = parsed_config!(config_str => ReplicaConfigCRaft;
                                     batch_interval_ms, max_batch_size,
+                                    idem_cache_size, session_idle_s,
+                                    ns_quota_kb,
                                     backer_path, logger_sync,
                                     hb_hear_timeout_min, hb_hear_timeout_max,
                                     hb_send_interval_ms, disable_hb_timer,
                                     disallow_step_up, snapshot_path,
                                     snapshot_interval_s, fault_tolerance,
-                                    msg_chunk_size, sim_read_lease)?;
+                                    msg_chunk_size,
+                                    peer_queue_high, peer_queue_low,
+                                    sim_read_lease)?;
         if config.batch_interval_ms == 0 {
             return logged_err!(
                 "invalid config.batch_interval_ms '{}'",
@@ -464,12 +498,15 @@ impl GenericReplica for CRaftReplica {
         }
 
         // setup state machine module
-        let state_machine = StateMachine::new_and_setup(id).await?;
+        let state_machine =
+            StateMachine::new_and_setup(id, config.ns_quota_kb * 1024).await?;
+        control_hub.inspect_keys(&state_machine);
 
         // setup storage hub module
         let storage_hub =
             StorageHub::new_and_setup(id, Path::new(&config.backer_path))
                 .await?;
+        control_hub.monitor_wal(Path::new(&config.backer_path));
 
         // setup heartbeat management module
         let heartbeater = Heartbeater::new_and_setup(
@@ -484,10 +521,18 @@ impl GenericReplica for CRaftReplica {
         let mut transport_hub = TransportHub::new_and_setup(
             id,
             population,
+            SmrProtocol::CRaft,
             p2p_addr,
+            false,
             HashMap::new(),
         )
         .await?;
+        if config.peer_queue_high > 0 {
+            transport_hub.set_queue_watermarks(
+                config.peer_queue_high,
+                config.peer_queue_low,
+            )?;
+        }
 
         // ask for the list of peers to proactively connect to. Do this after
         // transport hub has been set up, so that I will be able to accept
@@ -503,7 +548,7 @@ impl GenericReplica for CRaftReplica {
         {
             to_peers
         } else {
-            return logged_err!("unexpected ctrl msg type received");
+            return logged_err!(Protocol; "unexpected ctrl msg type received");
         };
 
         // create a Reed-Solomon coder with num_data_shards == quorum size and
@@ -533,13 +578,16 @@ impl GenericReplica for CRaftReplica {
                 .await?;
 
         // setup external API module, ready to take in client requests
-        let external_api = ExternalApi::new_and_setup(
+        let mut external_api = ExternalApi::new_and_setup(
             id,
             api_addr,
             Duration::from_millis(config.batch_interval_ms),
             config.max_batch_size,
+            config.idem_cache_size,
+            Duration::from_secs(config.session_idle_s),
         )
         .await?;
+        external_api.stamp_replies(&state_machine);
 
         let mut snapshot_interval = time::interval(Duration::from_secs(
             if config.snapshot_interval_s > 0 {
@@ -727,7 +775,7 @@ impl GenericReplica for CRaftReplica {
         self.id
     }
 
-    fn population(&self) -> u8 {
+    fn population(&self) -> u16 {
         self.population
     }
 }
@@ -752,7 +800,7 @@ pub(crate) struct CRaftClient {
     id: ClientId,
 
     /// Number of servers in the cluster.
-    population: u8,
+    population: u16,
 
     /// Configuration parameters struct.
     _config: ClientConfigCRaft,
@@ -838,7 +886,7 @@ impl GenericEndpoint for CRaftClient {
                 }
                 Ok(())
             }
-            _ => logged_err!("unexpected reply type received"),
+            _ => logged_err!(Protocol; "unexpected reply type received"),
         }
     }
 
@@ -897,6 +945,22 @@ impl GenericEndpoint for CRaftClient {
                 .recv_reply()
                 .await?;
 
+            if let ApiReply::LeaderChange { leader } = reply {
+                // notified of a new leader; switch over to it, or keep waiting if
+                // already talking to it
+                if leader == self.server_id
+                    || !self.servers.contains_key(&leader)
+                {
+                    return self.recv_reply().await;
+                }
+                self.server_id = leader;
+                pf_debug!(
+                    "notified of new leader {} '{}'",
+                    leader,
+                    self.servers[&leader]
+                );
+            }
+
             if let ApiReply::Reply {
                 ref result,
                 ref redirect,
@@ -929,7 +993,7 @@ impl GenericEndpoint for CRaftClient {
         self.id
     }
 
-    fn population(&self) -> u8 {
+    fn population(&self) -> u16 {
         self.population
     }
 
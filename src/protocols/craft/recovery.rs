@@ -61,7 +61,9 @@ impl CRaftReplica {
                             break;
                         }
                         _ => {
-                            return logged_err!("unexpected log result type");
+                            return logged_err!(
+                                Protocol; "unexpected log result type"
+                            );
                         }
                     }
                 }
@@ -92,7 +94,7 @@ impl CRaftReplica {
                     self.log_meta_end = now_size;
                 } else {
                     return logged_err!(
-                        "unexpected log result type or failed write"
+                        Protocol; "unexpected log result type or failed write"
                     );
                 }
                 // ... and push a 0-th dummy entry into in-mem log
@@ -127,12 +129,12 @@ impl CRaftReplica {
                     self.log_offset = now_size;
                 } else {
                     return logged_err!(
-                        "unexpected log result type or failed write"
+                        Protocol; "unexpected log result type or failed write"
                     );
                 }
             }
 
-            _ => return logged_err!("unexpected log result type"),
+            _ => return logged_err!(Protocol; "unexpected log result type"),
         }
 
         // do an extra Truncate to remove partial entry at the end if any
@@ -160,7 +162,9 @@ impl CRaftReplica {
             }
             Ok(())
         } else {
-            logged_err!("unexpected log result type or failed truncate")
+            logged_err!(
+                Protocol; "unexpected log result type or failed truncate"
+            )
         }
     }
 }
@@ -49,6 +49,7 @@ impl CRaftReplica {
                 if let ApiRequest::Req {
                     id: req_id,
                     cmd: Command::Get { .. },
+                    ..
                 } = req
                 {
                     self.external_api.send_reply(
@@ -0,0 +1,292 @@
+//! CRAQ -- peer-peer messaging.
+
+use super::*;
+
+use crate::server::{ApiReply, LogAction, ReplicaId};
+use crate::utils::SummersetError;
+
+// CRAQReplica peer-peer messages handling
+impl CRAQReplica {
+    /// Handler of Propagate message from predecessor.
+    fn handle_msg_propagate(
+        &mut self,
+        peer: ReplicaId,
+        slot: usize,
+        reqs: ReqBatch,
+    ) -> Result<(), SummersetError> {
+        pf_debug!(
+            "received Propagate <- {} slot {} num_reqs {}",
+            peer,
+            slot,
+            reqs.len()
+        );
+
+        // ignore if Propagate message not from my predecessor
+        if self.is_head() || self.predecessor().unwrap() != peer {
+            return Ok(());
+        }
+
+        // locate log entry in memory, filling in null entries if needed
+        while self.log.len() <= slot {
+            self.log.push(Self::null_log_entry());
+        }
+
+        self.log[slot].status = Status::Streaming;
+        self.log[slot].reqs.clone_from(&reqs);
+        self.note_writes(slot, &reqs);
+
+        // record the new log entry durably
+        self.storage_hub.submit_action(
+            slot as LogActionId,
+            LogAction::Append {
+                entry: WalEntry { slot, reqs },
+                sync: self.config.logger_sync,
+            },
+        )?;
+        pf_trace!("submitted durable log action for slot {}", slot);
+
+        Ok(())
+    }
+
+    /// Handler of Propagate reply from successor.
+    fn handle_msg_propagate_reply(
+        &mut self,
+        peer: ReplicaId,
+        slot: usize,
+    ) -> Result<(), SummersetError> {
+        pf_trace!("received PropagateReply <- {} slot {}", peer, slot);
+
+        // ignore if Propagate reply not from my successor
+        if self.is_tail() || self.successor().unwrap() != peer {
+            return Ok(());
+        }
+
+        // update log entry status
+        debug_assert!(slot < self.log.len());
+        if self.log[slot].status != Status::Streaming {
+            return Ok(());
+        }
+        self.log[slot].status = Status::Propagated;
+
+        // update index of the first non-propagated entry
+        if slot == self.prop_bar {
+            while self.prop_bar < self.log.len() {
+                if self.log[self.prop_bar].status < Status::Propagated {
+                    break;
+                }
+
+                // submit commands in propagated entry to the state machine
+                if self.log[self.prop_bar].reqs.is_empty() {
+                    self.log[self.prop_bar].status = Status::Executed;
+                } else {
+                    for (cmd_idx, (_, req)) in
+                        self.log[self.prop_bar].reqs.iter().enumerate()
+                    {
+                        if let ApiRequest::Req { cmd, checksum, .. } = req {
+                            self.state_machine.submit_checked_cmd(
+                                Self::make_command_id(
+                                    self.prop_bar,
+                                    cmd_idx,
+                                    false,
+                                ),
+                                cmd.clone(),
+                                *checksum,
+                            )?;
+                        }
+                    }
+                    pf_trace!(
+                        "submitted {} exec commands for slot {}",
+                        self.log[self.prop_bar].reqs.len(),
+                        self.prop_bar
+                    );
+                }
+
+                self.prop_bar += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves `clean_bar` forward to given index, telling my predecessor about
+    /// it.
+    pub(super) fn advance_clean_bar(
+        &mut self,
+        clean_bar: usize,
+    ) -> Result<(), SummersetError> {
+        if clean_bar <= self.clean_bar {
+            return Ok(());
+        }
+        self.clean_bar = clean_bar;
+
+        if let Some(predecessor) = self.predecessor() {
+            self.transport_hub
+                .send_msg(PeerMsg::Commit { clean_bar }, predecessor)?;
+            pf_trace!(
+                "sent Commit -> {} for clean_bar {}",
+                predecessor,
+                clean_bar
+            );
+        }
+        Ok(())
+    }
+
+    /// Handler of Commit message from successor.
+    fn handle_msg_commit(
+        &mut self,
+        peer: ReplicaId,
+        clean_bar: usize,
+    ) -> Result<(), SummersetError> {
+        pf_trace!("received Commit <- {} clean_bar {}", peer, clean_bar);
+
+        // ignore if Commit message not from my successor
+        if self.is_tail() || self.successor().unwrap() != peer {
+            return Ok(());
+        }
+
+        self.advance_clean_bar(clean_bar)
+    }
+
+    /// Handler of VersionQuery message from a node holding a dirty key.
+    fn handle_msg_version_query(
+        &mut self,
+        peer: ReplicaId,
+        key: String,
+    ) -> Result<(), SummersetError> {
+        pf_trace!("received VersionQuery <- {} key '{}'", peer, key);
+        if !self.is_tail() {
+            return Ok(());
+        }
+
+        // every entry in my log before prop_bar has been committed, later
+        // ones are still being logged; if the key's latest write is among
+        // the latter, look back for its latest committed one
+        let version = match self.last_write.get(&key) {
+            Some(&slot) if slot >= self.prop_bar => {
+                (0..self.prop_bar).rev().find(|&s| {
+                    self.log[s]
+                        .reqs
+                        .iter()
+                        .any(|(_, req)| req.write_key() == Some(&key))
+                })
+            }
+            last => last.copied(),
+        };
+        self.transport_hub
+            .send_msg(PeerMsg::VersionReply { key, version }, peer)?;
+        pf_trace!("sent VersionReply -> {} version {:?}", peer, version);
+        Ok(())
+    }
+
+    /// Handler of VersionReply message from the tail.
+    fn handle_msg_version_reply(
+        &mut self,
+        peer: ReplicaId,
+        key: String,
+        version: Option<usize>,
+    ) -> Result<(), SummersetError> {
+        pf_trace!(
+            "received VersionReply <- {} key '{}' version {:?}",
+            peer,
+            key,
+            version
+        );
+        let Some((reads, waiting)) = self.dirty_reads.remove(&key) else {
+            return Ok(());
+        };
+        if !waiting.is_empty() {
+            self.dirty_reads.insert(key.clone(), (waiting, vec![]));
+            self.send_version_query(key.clone())?;
+        }
+
+        // if my latest version is the committed one and my state machine
+        // has it, serve the held-back reads locally; otherwise I hold only a
+        // newer dirty version, so relay them to the tail
+        //
+        // NOTE: the original paper keeps multiple versions per key so that
+        //       the committed one can always be served locally; the state
+        //       machine here keeps only the latest, hence the relaying
+        let latest = self.last_write.get(&key).copied();
+        if latest == version && latest.is_none_or(|s| s < self.prop_bar) {
+            for (client, req) in reads {
+                self.serve_read_locally(client, req)?;
+            }
+        } else {
+            let tail = self.population - 1;
+            pf_trace!(
+                "sent ReadRelay -> {} for key '{}' num_reqs {}",
+                tail,
+                key,
+                reads.len()
+            );
+            self.transport_hub
+                .send_msg(PeerMsg::ReadRelay { reqs: reads }, tail)?;
+        }
+        Ok(())
+    }
+
+    /// Handler of ReadRelay message from a node holding a dirty key.
+    fn handle_msg_read_relay(
+        &mut self,
+        peer: ReplicaId,
+        reqs: ReqBatch,
+    ) -> Result<(), SummersetError> {
+        pf_trace!("received ReadRelay <- {} num_reqs {}", peer, reqs.len());
+        if !self.is_tail() {
+            return Ok(());
+        }
+
+        for (client, req) in reqs {
+            if let ApiRequest::Req { id: req_id, .. } = req {
+                self.relayed_reads.insert((client, req_id), peer);
+                self.serve_read_locally(client, req)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handler of RelayedReply message from the tail.
+    fn handle_msg_relayed_reply(
+        &mut self,
+        peer: ReplicaId,
+        client: ClientId,
+        reply: ApiReply,
+    ) -> Result<(), SummersetError> {
+        pf_trace!("received RelayedReply <- {} for client {}", peer, client);
+        if self.external_api.has_client(client) {
+            self.external_api.send_reply(reply, client)?;
+        }
+        Ok(())
+    }
+
+    /// Synthesized handler of receiving message from peer.
+    pub(super) async fn handle_msg_recv(
+        &mut self,
+        peer: ReplicaId,
+        msg: PeerMsg,
+    ) -> Result<(), SummersetError> {
+        match msg {
+            PeerMsg::Propagate { slot, reqs } => {
+                self.handle_msg_propagate(peer, slot, reqs)
+            }
+            PeerMsg::PropagateReply { slot } => {
+                self.handle_msg_propagate_reply(peer, slot)
+            }
+            PeerMsg::Commit { clean_bar } => {
+                self.handle_msg_commit(peer, clean_bar)
+            }
+            PeerMsg::VersionQuery { key } => {
+                self.handle_msg_version_query(peer, key)
+            }
+            PeerMsg::VersionReply { key, version } => {
+                self.handle_msg_version_reply(peer, key, version)
+            }
+            PeerMsg::ReadRelay { reqs } => {
+                self.handle_msg_read_relay(peer, reqs)
+            }
+            PeerMsg::RelayedReply { client, reply } => {
+                self.handle_msg_relayed_reply(peer, client, reply)
+            }
+        }
+    }
+}
@@ -0,0 +1,840 @@
+//! Replication protocol: CRAQ (Chain Replication with Apportioned Queries).
+//!
+//! Chain replication where every node serves reads of keys it holds a clean
+//! (tail-committed) version of, and asks the tail for the committed version
+//! of dirty ones. A partial implementation without proper fault-tolerance,
+//! like ChainRep. References:
+//!   - <https://www.usenix.org/legacy/event/usenix09/tech/full_papers/terrace/terrace.pdf>
+//!   - <https://www.cs.cornell.edu/home/rvr/papers/OSDI04.pdf>
+
+mod control;
+mod durability;
+mod execution;
+mod messages;
+mod recovery;
+mod request;
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::Path;
+
+use crate::client::{ClientApiStub, ClientCtrlStub, ClientId, GenericEndpoint};
+use crate::manager::{CtrlMsg, CtrlReply, CtrlRequest};
+use crate::protocols::SmrProtocol;
+use crate::server::{
+    ApiReply, ApiRequest, CommandId, CommandResult, ControlHub, ExternalApi,
+    GenericReplica, LogActionId, ReplicaId, RequestId, StateMachine,
+    StorageHub, TransportHub,
+};
+use crate::utils::SummersetError;
+
+use async_trait::async_trait;
+
+use get_size::GetSize;
+
+use serde::{Deserialize, Serialize};
+
+use tokio::sync::watch;
+use tokio::time::Duration;
+
+/// Configuration parameters struct.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReplicaConfigCRAQ {
+    /// Client request batching interval in millisecs.
+    pub batch_interval_ms: u64,
+
+    /// Client request batching maximum batch size.
+    pub max_batch_size: usize,
+
+    /// Number of completed replies cached per client for collapsing retried
+    /// requests by idempotency key; 0 means collapsing disabled.
+    pub idem_cache_size: usize,
+
+    /// Idle timeout in secs after which a client session that sent no request
+    /// is closed and its state reclaimed; 0 means never.
+    pub session_idle_s: u64,
+
+    /// Quota in KiB of approximate key-value bytes stored per namespace (the
+    /// key prefix before the first '/'), beyond which Puts are rejected; 0
+    /// means unlimited.
+    pub ns_quota_kb: usize,
+
+    /// Path to backing log file.
+    pub backer_path: String,
+
+    /// Whether to call `fsync()`/`fdatasync()` on logger.
+    pub logger_sync: bool,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for ReplicaConfigCRAQ {
+    fn default() -> Self {
+        ReplicaConfigCRAQ {
+            batch_interval_ms: 1,
+            max_batch_size: 5000,
+            idem_cache_size: 0,
+            session_idle_s: 0,
+            ns_quota_kb: 0,
+            backer_path: "/tmp/summerset.craq.wal".into(),
+            logger_sync: false,
+        }
+    }
+}
+
+/// Log entry status enum.
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize,
+)]
+enum Status {
+    Null = 0,
+    Streaming = 1,
+    Propagated = 2,
+    Executed = 3,
+}
+
+/// Request batch type.
+type ReqBatch = Vec<(ClientId, ApiRequest)>;
+
+/// Is the request a read? Single-key reads can be served at any node, scans
+/// only at the tail; writes enter the chain at the head.
+#[inline]
+fn is_read(req: &ApiRequest) -> bool {
+    req.read_only().is_some() || req.scan_range().is_some()
+}
+
+/// In-memory log entry containing a commands batch.
+struct LogEntry {
+    /// Log entry status.
+    status: Status,
+
+    /// Batch of client requests.
+    reqs: ReqBatch,
+
+    /// Offset of first durable WAL log entry related to this entry.
+    wal_offset: usize,
+}
+
+/// Stable storage WAL log entry type.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, GetSize)]
+struct WalEntry {
+    slot: usize,
+    reqs: ReqBatch,
+}
+
+/// Peer-peer message type.
+#[derive(Debug, Clone, Serialize, Deserialize, GetSize)]
+enum PeerMsg {
+    /// Propagate message from predecessor to successor.
+    Propagate { slot: usize, reqs: ReqBatch },
+
+    /// Propagate reply from successor to predecessor (mimics tracking of
+    /// message delivery status).
+    PropagateReply { slot: usize },
+
+    /// Commit notification flowing from the tail back up the chain: all log
+    /// entries before `clean_bar` have been committed at the tail.
+    Commit { clean_bar: usize },
+
+    /// Version query from a node holding a dirty version of key to the tail.
+    VersionQuery { key: String },
+
+    /// Reply to a version query, carrying the slot of the latest committed
+    /// write to key, if any.
+    VersionReply { key: String, version: Option<usize> },
+
+    /// Reads of a key that the querying node cannot serve locally, relayed
+    /// to the tail.
+    ReadRelay { reqs: ReqBatch },
+
+    /// Reply to a relayed read, to be passed on to the client.
+    RelayedReply { client: ClientId, reply: ApiReply },
+}
+
+/// CRAQ server replica module.
+pub(crate) struct CRAQReplica {
+    /// Replica ID in cluster.
+    id: ReplicaId,
+
+    /// Total number of replicas in cluster.
+    population: u16,
+
+    /// Configuration parameters struct.
+    config: ReplicaConfigCRAQ,
+
+    /// Address string for client requests API.
+    _api_addr: SocketAddr,
+
+    /// Address string for internal peer-peer communication.
+    _p2p_addr: SocketAddr,
+
+    /// ControlHub module.
+    control_hub: ControlHub,
+
+    /// ExternalApi module.
+    external_api: ExternalApi,
+
+    /// StateMachine module.
+    state_machine: StateMachine,
+
+    /// StorageHub module.
+    storage_hub: StorageHub<WalEntry>,
+
+    /// TransportHub module.
+    transport_hub: TransportHub<PeerMsg>,
+
+    /// In-memory log of entries.
+    log: Vec<LogEntry>,
+
+    /// Index of the first non-propagated log entry.
+    prop_bar: usize,
+
+    /// Index of the first non-executed log entry.
+    /// It is always true that exec_bar <= prop_bar <= log.len()
+    exec_bar: usize,
+
+    /// Index of the first log entry not known to be committed at the tail.
+    clean_bar: usize,
+
+    /// Slot of the latest write to each key in my log, used to tell whether
+    /// I hold a clean version of the key.
+    last_write: HashMap<String, usize>,
+
+    /// Reads of dirty keys held back for version queries to the tail, per
+    /// key: those covered by the one in-flight query, and those arrived
+    /// after it was sent that need a fresh query.
+    dirty_reads: HashMap<String, (ReqBatch, ReqBatch)>,
+
+    /// Reads of dirty keys that the tail is serving on behalf of a node,
+    /// mapping to that node.
+    relayed_reads: HashMap<(ClientId, RequestId), ReplicaId>,
+
+    /// Current durable WAL log file offset.
+    wal_offset: usize,
+}
+
+// CRAQReplica common helpers
+impl CRAQReplica {
+    /// Am I the head node?
+    #[inline]
+    fn is_head(&self) -> bool {
+        self.id == 0
+    }
+
+    /// Am I the tail node?
+    #[inline]
+    fn is_tail(&self) -> bool {
+        self.id == self.population - 1
+    }
+
+    /// Who's my predecessor?
+    #[inline]
+    fn predecessor(&self) -> Option<ReplicaId> {
+        if self.is_head() {
+            None
+        } else {
+            Some(self.id - 1)
+        }
+    }
+
+    /// Who's my successor?
+    #[inline]
+    fn successor(&self) -> Option<ReplicaId> {
+        if self.is_tail() {
+            None
+        } else {
+            Some(self.id + 1)
+        }
+    }
+
+    /// Create an empty null log entry.
+    #[inline]
+    fn null_log_entry() -> LogEntry {
+        LogEntry {
+            status: Status::Null,
+            reqs: vec![],
+            wal_offset: 0,
+        }
+    }
+
+    /// Is my latest version of key clean, i.e., committed at the tail and
+    /// submitted to my state machine?
+    #[inline]
+    fn key_is_clean(&self, key: &String) -> bool {
+        self.last_write
+            .get(key)
+            .is_none_or(|&slot| slot < self.clean_bar.min(self.prop_bar))
+    }
+
+    /// Records the writes of a batch newly placed in my log at slot.
+    fn note_writes(&mut self, slot: usize, reqs: &ReqBatch) {
+        for (_, req) in reqs {
+            if let Some(key) = req.write_key() {
+                self.last_write.insert(key.clone(), slot);
+            }
+        }
+    }
+
+    /// Locate the first null log entry or append one if no holes exist.
+    fn first_null_slot(&mut self) -> usize {
+        for s in self.exec_bar..self.log.len() {
+            if self.log[s].status == Status::Null {
+                return s;
+            }
+        }
+        self.log.push(Self::null_log_entry());
+        self.log.len() - 1
+    }
+
+    /// Compose CommandId from:
+    ///   - slot index & command index within if non read-only
+    ///   - request ID & client ID if read-only
+    #[inline]
+    fn make_command_id(
+        slot_or_req_id: usize,
+        cmd_idx_or_client: usize,
+        read_only: bool,
+    ) -> CommandId {
+        debug_assert!(slot_or_req_id <= (u32::MAX as usize));
+        debug_assert!(cmd_idx_or_client <= ((u32::MAX >> 1) as usize));
+        let mut cmd_id =
+            ((slot_or_req_id << 32) | (cmd_idx_or_client << 1)) as CommandId;
+        if read_only {
+            cmd_id += 1;
+        }
+        cmd_id
+    }
+
+    /// Decompose CommandId into:
+    ///   - slot index & command index within if non read-only
+    ///   - request ID & client ID if read-only
+    #[inline]
+    fn split_command_id(command_id: CommandId) -> (usize, usize, bool) {
+        let slot_or_req_id = (command_id >> 32) as usize;
+        let cmd_idx_or_client = ((command_id & ((1 << 32) - 1)) >> 1) as usize;
+        let read_only = (command_id % 2) == 1;
+        (slot_or_req_id, cmd_idx_or_client, read_only)
+    }
+}
+
+#[async_trait]
+impl GenericReplica for CRAQReplica {
+    async fn new_and_setup(
+        api_addr: SocketAddr,
+        p2p_addr: SocketAddr,
+        manager: SocketAddr,
+        config_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        // connect to the cluster manager and get assigned a server ID
+        let mut control_hub = ControlHub::new_and_setup(manager).await?;
+        let id = control_hub.me;
+        let (api_addr, p2p_addr) =
+            control_hub.resolve_addrs(api_addr, p2p_addr)?;
+        let population = control_hub.population;
+
+        // parse protocol-specific configs
+        let config = parsed_config!(config_str => ReplicaConfigCRAQ;
+                                    batch_interval_ms, max_batch_size,
+                                    idem_cache_size, session_idle_s,
+                                    ns_quota_kb,
+                                    backer_path, logger_sync)?;
+        if config.batch_interval_ms == 0 {
+            return logged_err!(
+                "invalid config.batch_interval_ms '{}'",
+                config.batch_interval_ms
+            );
+        }
+
+        // setup state machine module
+        let state_machine =
+            StateMachine::new_and_setup(id, config.ns_quota_kb * 1024).await?;
+        control_hub.inspect_keys(&state_machine);
+
+        // setup storage hub module
+        let storage_hub =
+            StorageHub::new_and_setup(id, Path::new(&config.backer_path))
+                .await?;
+
+        // setup transport hub module
+        let mut transport_hub = TransportHub::new_and_setup(
+            id,
+            population,
+            SmrProtocol::CRAQ,
+            p2p_addr,
+            false,
+            HashMap::new(),
+        )
+        .await?;
+
+        // ask for the list of peers to proactively connect to. Do this after
+        // transport hub has been set up, so that I will be able to accept
+        // later peer connections
+        control_hub.send_ctrl(CtrlMsg::NewServerJoin {
+            id,
+            protocol: SmrProtocol::CRAQ,
+            api_addr,
+            p2p_addr,
+        })?;
+        let to_peers = if let CtrlMsg::ConnectToPeers { to_peers, .. } =
+            control_hub.recv_ctrl().await?
+        {
+            to_peers
+        } else {
+            return logged_err!(Protocol; "unexpected ctrl msg type received");
+        };
+
+        // proactively connect to some peers, then wait for all population
+        // have been connected with me
+        for (peer, conn_addr) in to_peers {
+            transport_hub.connect_to_peer(peer, conn_addr).await?;
+        }
+        transport_hub.wait_for_group(population).await?;
+
+        // setup external API module, ready to take in client requests
+        let mut external_api = ExternalApi::new_and_setup(
+            id,
+            api_addr,
+            Duration::from_millis(config.batch_interval_ms),
+            config.max_batch_size,
+            config.idem_cache_size,
+            Duration::from_secs(config.session_idle_s),
+        )
+        .await?;
+        external_api.stamp_replies(&state_machine);
+
+        Ok(CRAQReplica {
+            id,
+            population,
+            config,
+            _api_addr: api_addr,
+            _p2p_addr: p2p_addr,
+            control_hub,
+            external_api,
+            state_machine,
+            storage_hub,
+            transport_hub,
+            log: vec![],
+            prop_bar: 0,
+            exec_bar: 0,
+            clean_bar: 0,
+            last_write: HashMap::new(),
+            dirty_reads: HashMap::new(),
+            relayed_reads: HashMap::new(),
+            wal_offset: 0,
+        })
+    }
+
+    async fn run(
+        &mut self,
+        mut rx_term: watch::Receiver<bool>,
+    ) -> Result<bool, SummersetError> {
+        // recover the log & state from durable WAL log
+        self.recover_from_wal().await?;
+
+        // main event loop
+        let mut paused = false;
+        loop {
+            tokio::select! {
+                // client request batch
+                req_batch = self.external_api.get_req_batch(), if !paused => {
+                    if let Err(e) = req_batch {
+                        pf_error!("error getting req batch: {}", e);
+                        continue;
+                    }
+                    let req_batch = req_batch.unwrap();
+                    if let Err(e) = self.handle_req_batch(req_batch).await {
+                        pf_error!("error handling req batch: {}", e);
+                    }
+                },
+
+                // durable logging result
+                log_result = self.storage_hub.get_result(), if !paused => {
+                    if let Err(e) = log_result {
+                        pf_error!("error getting log result: {}", e);
+                        continue;
+                    }
+                    let (action_id, log_result) = log_result.unwrap();
+                    if let Err(e) = self.handle_log_result(action_id, log_result).await {
+                        pf_error!("error handling log result {}: {}",
+                                           action_id, e);
+                    }
+                },
+
+                // message from peer
+                msg = self.transport_hub.recv_msg(), if !paused => {
+                    if let Err(_e) = msg {
+                        // NOTE: commented out to prevent console lags
+                        //       during benchmarking
+                        // pf_error!("error receiving peer msg: {}", e);
+                        continue;
+                    }
+                    let (peer, msg) = msg.unwrap();
+                    if let Err(e) = self.handle_msg_recv(peer, msg).await {
+                        pf_error!("error handling msg recv <- {}: {}", peer, e);
+                    }
+                },
+
+                // state machine execution result
+                cmd_result = self.state_machine.get_result(), if !paused => {
+                    if let Err(e) = cmd_result {
+                        pf_error!("error getting cmd result: {}", e);
+                        continue;
+                    }
+                    let (cmd_id, cmd_result) = cmd_result.unwrap();
+                    if let Err(e) = self.handle_cmd_result(cmd_id, cmd_result).await {
+                        pf_error!("error handling cmd result {}: {}", cmd_id, e);
+                    }
+                },
+
+                // manager control message
+                ctrl_msg = self.control_hub.recv_ctrl() => {
+                    if let Err(e) = ctrl_msg {
+                        pf_error!("error getting ctrl msg: {}", e);
+                        continue;
+                    }
+                    let ctrl_msg = ctrl_msg.unwrap();
+                    match self.handle_ctrl_msg(ctrl_msg, &mut paused).await {
+                        Ok(terminate) => {
+                            if let Some(restart) = terminate {
+                                return Ok(restart);
+                            }
+                        },
+                        Err(e) => {
+                            pf_error!("error handling ctrl msg: {}", e);
+                        }
+                    }
+                },
+
+                // receiving termination signal
+                _ = rx_term.changed() => {
+                    pf_warn!("server caught termination signal");
+                    return Ok(false);
+                }
+            }
+        }
+    }
+
+    fn id(&self) -> ReplicaId {
+        self.id
+    }
+
+    fn population(&self) -> u16 {
+        self.population
+    }
+}
+
+/// Configuration parameters struct.
+#[derive(Debug, Deserialize)]
+pub struct ClientConfigCRAQ {
+    /// Which server to consider as head initially.
+    pub init_head_id: ReplicaId,
+
+    /// Which server to consider as tail initially.
+    pub init_tail_id: ReplicaId,
+
+    /// App-designated nearest server ID to send single-key reads to.
+    /// Any number that's larger than cluster size means picking one by my
+    /// client ID, so that clients spread their reads over the chain.
+    pub near_server_id: ReplicaId,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for ClientConfigCRAQ {
+    fn default() -> Self {
+        ClientConfigCRAQ {
+            init_head_id: 0,
+            init_tail_id: 9, // will cap to the last server
+            near_server_id: ReplicaId::MAX,
+        }
+    }
+}
+
+/// Which server a request is sent to.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum SendTarget {
+    Head,
+    Tail,
+    Near,
+}
+
+/// CRAQ client-side module.
+pub(crate) struct CRAQClient {
+    /// Client ID.
+    id: ClientId,
+
+    /// Number of servers in the cluster.
+    population: u16,
+
+    /// Configuration parameters struct.
+    config: ClientConfigCRAQ,
+
+    /// List of active servers information.
+    servers: HashMap<ReplicaId, SocketAddr>,
+
+    /// Current head server ID.
+    head_id: ReplicaId,
+
+    /// Current tail server ID.
+    tail_id: ReplicaId,
+
+    /// Current server ID to send single-key reads to.
+    near_id: ReplicaId,
+
+    /// Which server was the last request sent to?
+    last_target: SendTarget,
+
+    /// Control API stub to the cluster manager.
+    ctrl_stub: ClientCtrlStub,
+
+    /// API stubs for communicating with the head server.
+    /// Separated out to make `tokio::select!` happy.
+    head_api_stub: Option<ClientApiStub>,
+
+    /// API stubs for communicating with the tail server.
+    /// Separated out to make `tokio::select!` happy.
+    tail_api_stub: Option<ClientApiStub>,
+
+    /// API stubs for communicating with middle servers.
+    mid_api_stubs: HashMap<ReplicaId, ClientApiStub>,
+}
+
+impl CRAQClient {
+    /// Which server should the request go to?
+    #[inline]
+    fn target_of(&self, req: &ApiRequest) -> SendTarget {
+        if req.read_only().is_some() {
+            if self.near_id == self.head_id {
+                SendTarget::Head
+            } else if self.near_id == self.tail_id {
+                SendTarget::Tail
+            } else {
+                SendTarget::Near
+            }
+        } else if req.scan_range().is_some() {
+            SendTarget::Tail
+        } else {
+            SendTarget::Head
+        }
+    }
+}
+
+#[async_trait]
+impl GenericEndpoint for CRAQClient {
+    async fn new_and_setup(
+        manager: SocketAddr,
+        config_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        // connect to the cluster manager and get assigned a client ID
+        pf_debug!("connecting to manager '{}'...", manager);
+        let ctrl_stub = ClientCtrlStub::new_by_connect(manager).await?;
+        let id = ctrl_stub.id;
+
+        // parse protocol-specific configs
+        let config = parsed_config!(config_str => ClientConfigCRAQ;
+                                    init_head_id, init_tail_id,
+                                    near_server_id)?;
+        let init_head_id = config.init_head_id;
+        let init_tail_id = config.init_tail_id;
+
+        Ok(CRAQClient {
+            id,
+            population: 0,
+            config,
+            servers: HashMap::new(),
+            head_id: init_head_id,
+            tail_id: init_tail_id,
+            near_id: init_tail_id,
+            last_target: SendTarget::Head,
+            ctrl_stub,
+            head_api_stub: None,
+            tail_api_stub: None,
+            mid_api_stubs: HashMap::new(),
+        })
+    }
+
+    async fn connect(&mut self) -> Result<(), SummersetError> {
+        // disallow reconnection without leaving
+        if self.head_api_stub.is_some() || self.tail_api_stub.is_some() {
+            return logged_err!("reconnecting without leaving");
+        }
+
+        // ask the manager about the list of active servers
+        let mut sent =
+            self.ctrl_stub.send_req(Some(&CtrlRequest::QueryInfo))?;
+        while !sent {
+            sent = self.ctrl_stub.send_req(None)?;
+        }
+
+        let reply = self.ctrl_stub.recv_reply().await?;
+        match reply {
+            CtrlReply::QueryInfo {
+                population,
+                servers_info,
+            } => {
+                self.population = population;
+
+                // shift to a new head_id/tail_id if current one not active
+                debug_assert!(!servers_info.is_empty());
+                while !servers_info.contains_key(&self.head_id)
+                    || servers_info[&self.head_id].is_paused
+                {
+                    self.head_id = (self.head_id + 1) % population;
+                }
+                while !servers_info.contains_key(&self.tail_id)
+                    || servers_info[&self.tail_id].is_paused
+                {
+                    self.tail_id =
+                        if self.tail_id == 0 || self.tail_id >= population {
+                            population - 1
+                        } else {
+                            self.tail_id - 1
+                        };
+                }
+                if self.head_id == self.tail_id {
+                    return logged_err!(
+                        "head & tail resolve to the same server {}",
+                        self.head_id
+                    );
+                }
+
+                // pick the server to send single-key reads to
+                self.near_id = if self.config.near_server_id < population {
+                    self.config.near_server_id
+                } else {
+                    (self.id % population as ClientId) as ReplicaId
+                };
+                if !servers_info.contains_key(&self.near_id)
+                    || servers_info[&self.near_id].is_paused
+                {
+                    pf_warn!(
+                        "near server {} inactive, using {} instead...",
+                        self.near_id,
+                        self.tail_id
+                    );
+                    self.near_id = self.tail_id;
+                }
+
+                // establish connection to all servers
+                self.servers = servers_info
+                    .into_iter()
+                    .map(|(id, info)| (id, info.api_addr))
+                    .collect();
+                for (&id, &server) in &self.servers {
+                    pf_debug!("connecting to server {} '{}'...", id, server);
+                    let api_stub =
+                        ClientApiStub::new_by_connect(self.id, server).await?;
+
+                    if id == self.head_id {
+                        self.head_api_stub = Some(api_stub);
+                    } else if id == self.tail_id {
+                        self.tail_api_stub = Some(api_stub);
+                    } else {
+                        self.mid_api_stubs.insert(id, api_stub);
+                    }
+                }
+
+                debug_assert!(self.head_api_stub.is_some());
+                debug_assert!(self.tail_api_stub.is_some());
+                Ok(())
+            }
+
+            _ => logged_err!(Protocol; "unexpected reply type received"),
+        }
+    }
+
+    async fn leave(&mut self, permanent: bool) -> Result<(), SummersetError> {
+        // send leave notification to all servers
+        self.mid_api_stubs
+            .insert(self.head_id, self.head_api_stub.take().unwrap());
+        self.mid_api_stubs
+            .insert(self.tail_id, self.tail_api_stub.take().unwrap());
+        for (id, mut api_stub) in self.mid_api_stubs.drain() {
+            let mut sent = api_stub.send_req(Some(&ApiRequest::Leave))?;
+            while !sent {
+                sent = api_stub.send_req(None)?;
+            }
+
+            // NOTE: commented out the following wait to avoid accidental
+            //       hanging upon leaving
+            // while api_stub.recv_reply().await? != ApiReply::Leave {}
+            pf_debug!("left server connection {}", id);
+        }
+
+        // if permanently leaving, send leave notification to the manager
+        if permanent {
+            let mut sent =
+                self.ctrl_stub.send_req(Some(&CtrlRequest::Leave))?;
+            while !sent {
+                sent = self.ctrl_stub.send_req(None)?;
+            }
+
+            while self.ctrl_stub.recv_reply().await? != CtrlReply::Leave {}
+            pf_debug!("left manager connection");
+        }
+
+        Ok(())
+    }
+
+    fn send_req(
+        &mut self,
+        req: Option<&ApiRequest>,
+    ) -> Result<bool, SummersetError> {
+        let target = match req {
+            Some(req) => self.target_of(req),
+            None => self.last_target,
+        };
+        self.last_target = target;
+
+        debug_assert!(self.head_api_stub.is_some());
+        debug_assert!(self.tail_api_stub.is_some());
+        match target {
+            SendTarget::Head => {
+                self.head_api_stub.as_mut().unwrap().send_req(req)
+            }
+            SendTarget::Tail => {
+                self.tail_api_stub.as_mut().unwrap().send_req(req)
+            }
+            SendTarget::Near => self
+                .mid_api_stubs
+                .get_mut(&self.near_id)
+                .unwrap()
+                .send_req(req),
+        }
+    }
+
+    async fn recv_reply(&mut self) -> Result<ApiReply, SummersetError> {
+        debug_assert!(self.head_api_stub.is_some());
+        debug_assert!(self.tail_api_stub.is_some());
+        let head_stub = self.head_api_stub.as_mut().unwrap();
+        let tail_stub = self.tail_api_stub.as_mut().unwrap();
+
+        let reply = if let Some(near_stub) =
+            self.mid_api_stubs.get_mut(&self.near_id)
+        {
+            tokio::select! {
+                reply = head_stub.recv_reply() => { reply? },
+                reply = tail_stub.recv_reply() => { reply? },
+                reply = near_stub.recv_reply() => { reply? },
+            }
+        } else {
+            tokio::select! {
+                reply = head_stub.recv_reply() => { reply? },
+                reply = tail_stub.recv_reply() => { reply? },
+            }
+        };
+
+        // ignoring the `redirect` field here...
+        Ok(reply)
+    }
+
+    fn id(&self) -> ClientId {
+        self.id
+    }
+
+    fn population(&self) -> u16 {
+        self.population
+    }
+
+    fn ctrl_stub(&mut self) -> &mut ClientCtrlStub {
+        &mut self.ctrl_stub
+    }
+}
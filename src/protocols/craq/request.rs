@@ -0,0 +1,134 @@
+//! CRAQ -- client request entrance.
+
+use super::*;
+
+use crate::server::{ApiRequest, LogAction};
+use crate::utils::SummersetError;
+
+// CRAQReplica client requests entrance
+impl CRAQReplica {
+    /// Submits a read request to my state machine to be served locally.
+    pub(super) fn serve_read_locally(
+        &mut self,
+        client: ClientId,
+        req: ApiRequest,
+    ) -> Result<(), SummersetError> {
+        if let ApiRequest::Req {
+            id: req_id,
+            cmd,
+            checksum,
+        } = req
+        {
+            self.state_machine.submit_checked_cmd(
+                Self::make_command_id(req_id as usize, client as usize, true),
+                cmd,
+                checksum,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Handles a single-key read at a non-tail node: serves it right away if
+    /// I hold a clean version of the key, otherwise holds it back and asks
+    /// the tail for the committed version.
+    fn handle_apportioned_read(
+        &mut self,
+        client: ClientId,
+        req: ApiRequest,
+    ) -> Result<(), SummersetError> {
+        let key = req.read_only().unwrap().clone();
+        if self.key_is_clean(&key) {
+            return self.serve_read_locally(client, req);
+        }
+
+        if let Some((_, waiting)) = self.dirty_reads.get_mut(&key) {
+            // a version query for the key is in flight, but its answer may
+            // predate this read; wait for the next one
+            waiting.push((client, req));
+            Ok(())
+        } else {
+            self.dirty_reads
+                .insert(key.clone(), (vec![(client, req)], vec![]));
+            self.send_version_query(key)
+        }
+    }
+
+    /// Sends a version query for key to the tail.
+    pub(super) fn send_version_query(
+        &mut self,
+        key: String,
+    ) -> Result<(), SummersetError> {
+        let tail = self.population - 1;
+        pf_trace!("sent VersionQuery -> {} for key '{}'", tail, key);
+        self.transport_hub
+            .send_msg(PeerMsg::VersionQuery { key }, tail)?;
+        Ok(())
+    }
+
+    /// Handler of client request batch chan recv.
+    pub(super) async fn handle_req_batch(
+        &mut self,
+        req_batch: ReqBatch,
+    ) -> Result<(), SummersetError> {
+        let batch_size = req_batch.len();
+        debug_assert!(batch_size > 0);
+        pf_debug!("got request batch of size {}", batch_size);
+
+        // reads may arrive at any node, writes only at the head; a batch may
+        // mix both if the head is also some client's read node
+        let mut write_batch = ReqBatch::new();
+        let (mut num_reads, mut num_ignored) = (0, 0);
+        for (client, req) in req_batch {
+            if !is_read(&req) {
+                if self.is_head() {
+                    write_batch.push((client, req));
+                } else {
+                    num_ignored += 1;
+                }
+            } else if self.is_tail() {
+                // tail holds only committed versions; serve right away
+                self.serve_read_locally(client, req)?;
+                num_reads += 1;
+            } else if req.read_only().is_some() {
+                self.handle_apportioned_read(client, req)?;
+                num_reads += 1;
+            } else {
+                // scans span a range of keys and are served at the tail only
+                num_ignored += 1;
+            }
+        }
+        if num_ignored > 0 {
+            pf_warn!(
+                "ignoring {} requests: head? {} tail? {}",
+                num_ignored,
+                self.is_head(),
+                self.is_tail()
+            );
+        }
+        if num_reads > 0 {
+            pf_trace!("handled {} read-only commands", num_reads);
+        }
+
+        // head records writes in a new log entry durably
+        if !write_batch.is_empty() {
+            let slot = self.first_null_slot();
+            self.log[slot].status = Status::Streaming;
+            self.log[slot].reqs.clone_from(&write_batch);
+            self.note_writes(slot, &write_batch);
+
+            self.storage_hub.submit_action(
+                slot as LogActionId,
+                LogAction::Append {
+                    entry: WalEntry {
+                        slot,
+                        reqs: write_batch,
+                    },
+                    sync: self.config.logger_sync,
+                },
+            )?;
+            pf_trace!("submitted durable log action for slot {}", slot);
+        }
+
+        Ok(())
+    }
+}
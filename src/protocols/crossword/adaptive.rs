@@ -33,9 +33,9 @@ impl CrosswordReplica {
 
     /// Parse config string into initial shards assignment policy.
     pub(super) fn parse_init_assignment(
-        population: u8,
-        rs_total_shards: u8,
-        rs_data_shards: u8,
+        population: u16,
+        rs_total_shards: u16,
+        rs_data_shards: u16,
         s: &str,
     ) -> Result<Vec<Bitmap>, SummersetError> {
         debug_assert_eq!(rs_total_shards % population, 0);
@@ -49,7 +49,7 @@ impl CrosswordReplica {
                     (r * dj_spr)..((r + 1) * dj_spr),
                 )));
             }
-        } else if let Ok(spr) = s.parse::<u8>() {
+        } else if let Ok(spr) = s.parse::<u16>() {
             // a single number: the same #shards per replica round-robinly
             if spr < dj_spr || spr > rs_data_shards {
                 return Err(SummersetError::msg(format!(
@@ -62,7 +62,7 @@ impl CrosswordReplica {
                     rs_total_shards,
                     ((r * dj_spr)..(r * dj_spr + spr))
                         .map(|i| i % rs_total_shards)
-                        .collect::<Vec<u8>>(),
+                        .collect::<Vec<u16>>(),
                 )));
             }
         } else {
@@ -97,15 +97,45 @@ impl CrosswordReplica {
     /// assignment) that is be responsive for a given peer_alive cnt.
     #[inline]
     pub(super) fn min_shards_per_replica(
-        rs_data_shards: u8,
-        majority: u8,
-        fault_tolerance: u8,
-        alive_cnt: u8,
-    ) -> u8 {
+        rs_data_shards: u16,
+        majority: u16,
+        fault_tolerance: u16,
+        alive_cnt: u16,
+    ) -> u16 {
         (majority + fault_tolerance + 1 - alive_cnt)
             * (rs_data_shards / majority)
     }
 
+    /// Predict the time to reach a quorum for each balanced config of
+    /// (#shards_per_replica, quorum_size) pair along the constraint boundary
+    /// line, given data size. Returns a list of (#shards_per_replica, time).
+    pub(super) fn predict_config_times(
+        rs_data_shards: u16,
+        majority: u16,
+        fault_tolerance: u16,
+        data_size: usize,
+        linreg_model: &HashMap<ReplicaId, PerfModel>,
+    ) -> Vec<(u16, f64)> {
+        let dj_spr = rs_data_shards / majority;
+        let mut config_times =
+            Vec::<(u16, f64)>::with_capacity(majority as usize);
+        for (spr, q) in (dj_spr..=rs_data_shards)
+            .step_by(dj_spr as usize)
+            .enumerate()
+            .map(|(i, spr)| (spr, majority + fault_tolerance - i as u16))
+        {
+            let load_size =
+                ((data_size / rs_data_shards as usize) + 1) * spr as usize;
+            let mut peer_times: Vec<f64> = linreg_model
+                .iter()
+                .map(|(_, model)| model.predict(load_size))
+                .collect();
+            peer_times.sort_by(|x, y| x.partial_cmp(y).unwrap());
+            config_times.push((spr, peer_times[q as usize - 2]));
+        }
+        config_times
+    }
+
     /// Get the proper assignment policy given data size and peer_alive count.
     // NOTE: if data_size == exactly `usize::MAX` this will fail; won't bother
     //       to account for this rare case right now
@@ -115,10 +145,10 @@ impl CrosswordReplica {
         assignment_adaptive: bool,
         assignment_balanced: bool,
         init_assignment: &'a Vec<Bitmap>,
-        brr_assignments: &'a HashMap<u8, Vec<Bitmap>>,
-        rs_data_shards: u8,
-        majority: u8,
-        fault_tolerance: u8,
+        brr_assignments: &'a HashMap<u16, Vec<Bitmap>>,
+        rs_data_shards: u16,
+        majority: u16,
+        fault_tolerance: u16,
         data_size: usize,
         linreg_model: &HashMap<ReplicaId, PerfModel>,
         b_to_d_threshold: f64,
@@ -154,27 +184,17 @@ impl CrosswordReplica {
             // query the linear regression models and pick the best config of
             // (#shards_per_replica, quorum_size) pair along the constraint
             // boundary line if doing adaptive config choosing
-            let mut config_times =
-                Vec::<(u8, f64)>::with_capacity(majority as usize);
-            for (spr, q) in (dj_spr..=rs_data_shards)
-                .step_by(dj_spr as usize)
-                .enumerate()
-                .map(|(i, spr)| (spr, majority + fault_tolerance - i as u8))
-            {
-                let load_size =
-                    ((data_size / rs_data_shards as usize) + 1) * spr as usize;
-                let mut peer_times: Vec<f64> = linreg_model
-                    .iter()
-                    .map(|(_, model)| model.predict(load_size))
-                    .collect();
-                peer_times.sort_by(|x, y| x.partial_cmp(y).unwrap());
-                config_times.push((spr, peer_times[q as usize - 2]));
-            }
-            config_times
-                .iter()
-                .min_by(|x, y| x.1.partial_cmp(&y.1).unwrap())
-                .unwrap()
-                .0
+            Self::predict_config_times(
+                rs_data_shards,
+                majority,
+                fault_tolerance,
+                data_size,
+                linreg_model,
+            )
+            .iter()
+            .min_by(|x, y| x.1.partial_cmp(&y.1).unwrap())
+            .unwrap()
+            .0
         } else {
             init_assignment[0].count()
         };
@@ -306,6 +326,90 @@ impl CrosswordReplica {
         Ok(())
     }
 
+    /// Folds the per-request data size of a newly proposed batch into the
+    /// moving average used by adaptive batching.
+    pub(super) fn record_req_size(&mut self, num_reqs: usize, data_len: usize) {
+        if num_reqs == 0 {
+            return;
+        }
+        let size = data_len as f64 / num_reqs as f64;
+        self.avg_req_size = if self.avg_req_size > 0.0 {
+            0.9 * self.avg_req_size + 0.1 * size
+        } else {
+            size
+        };
+    }
+
+    /// Picks the batch size cap that minimizes the predicted commit latency
+    /// of currently pending requests, and applies it to the external API.
+    /// A batch of `b` requests is predicted to take the best quorum time of
+    /// its data size under the perf models, plus half a batch interval of
+    /// queueing for each further batch the pending requests get split into.
+    /// Ties favor larger caps.
+    pub(super) fn adapt_batch_size(&mut self) {
+        if self.avg_req_size <= 0.0 {
+            return; // no batch seen yet
+        }
+        let pending = self.external_api.pending_reqs().max(1);
+        let max_cap = if self.config.max_batch_size == 0 {
+            pending
+        } else {
+            self.config.max_batch_size
+        };
+        let wait_ms = self.config.batch_interval_ms as f64 / 2.0;
+        let init_spr = self.init_assignment[0].count();
+
+        let (mut best_cap, mut best_ms) = (max_cap, f64::MAX);
+        let mut cap = 1;
+        loop {
+            let cap_now = cmp::min(cap, max_cap);
+            let data_size = (cmp::min(cap_now, pending) as f64
+                * self.avg_req_size) as usize;
+            let config_times = Self::predict_config_times(
+                self.rs_data_shards,
+                self.majority,
+                self.config.fault_tolerance,
+                data_size,
+                &self.linreg_model,
+            );
+            let quorum_ms = if self.assignment_adaptive {
+                config_times
+                    .iter()
+                    .map(|&(_, t)| t)
+                    .min_by(|x, y| x.partial_cmp(y).unwrap())
+            } else {
+                config_times
+                    .iter()
+                    .find(|&&(spr, _)| spr == init_spr)
+                    .map(|&(_, t)| t)
+            }
+            .unwrap_or(0.0);
+            let latency_ms =
+                quorum_ms + (pending.div_ceil(cap_now) - 1) as f64 * wait_ms;
+            if latency_ms <= best_ms {
+                (best_cap, best_ms) = (cap_now, latency_ms);
+            }
+            if cap_now == max_cap {
+                break;
+            }
+            cap *= 2;
+        }
+
+        if self.config.max_batch_size == 0 && best_cap == max_cap {
+            best_cap = 0; // keep unlimited
+        }
+        if best_cap != self.external_api.max_batch_size() {
+            pf_debug!(
+                "batch size cap {} -> {} (pending {} pred {:.2}ms)",
+                self.external_api.max_batch_size(),
+                best_cap,
+                pending,
+                best_ms
+            );
+            self.external_api.set_max_batch_size(best_cap);
+        }
+    }
+
     /// Updates `tc qdisc` netem information.
     pub(super) fn update_qdisc_info(&mut self) -> Result<(), SummersetError> {
         if let Some(qdisc_info) = self.qdisc_info.as_mut() {
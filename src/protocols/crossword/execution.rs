@@ -40,7 +40,7 @@ impl CrosswordReplica {
                 );
             }
         } else {
-            return logged_err!("unexpected API request type");
+            return logged_err!(Protocol; "unexpected API request type");
         }
 
         // if all commands in this instance have been executed, set status to
@@ -21,7 +21,7 @@ impl CrosswordReplica {
     ) -> Result<(), SummersetError> {
         self.gossip_timer.cancel()?;
 
-        let timeout_ms = thread_rng().gen_range(
+        let timeout_ms = self.gossip_rng.gen_range(
             self.config.gossip_timeout_min..=self.config.gossip_timeout_max,
         );
         // pf_trace!("kickoff gossip_timer @ {} ms", timeout_ms);
@@ -36,8 +36,8 @@ impl CrosswordReplica {
     #[allow(clippy::too_many_arguments, clippy::ptr_arg)]
     fn gossip_targets_excl(
         me: ReplicaId,
-        population: u8,
-        rs_data_shards: u8,
+        population: u16,
+        rs_data_shards: u16,
         replica_bk: &Option<ReplicaBookkeeping>,
         mut avail_shards_map: Bitmap,
         assignment: &Vec<Bitmap>,
@@ -4,8 +4,8 @@ use std::collections::{HashMap, VecDeque};
 
 use super::*;
 
-use crate::manager::CtrlMsg;
-use crate::server::{LogAction, ReplicaId};
+use crate::manager::{CtrlMsg, ServerStatus};
+use crate::server::{DebugState, LogAction, ReplicaId, ResourceUsage};
 use crate::utils::{Bitmap, SummersetError};
 
 // CrosswordReplica leadership related logic
@@ -35,6 +35,7 @@ impl CrosswordReplica {
             // set this peer to be the believed leader
             debug_assert_ne!(peer, self.id);
             self.leader = Some(peer);
+            self.external_api.notify_leader_change(peer)?;
             self.heartbeater.set_sending(false);
         }
 
@@ -58,6 +59,7 @@ impl CrosswordReplica {
         self.heartbeater.set_sending(true);
         self.control_hub
             .send_ctrl(CtrlMsg::LeaderStatus { step_up: true })?;
+        self.external_api.notify_leader_change(self.id)?;
         pf_info!("becoming a leader...");
 
         // clear peers' heartbeat reply counters, and broadcast a heartbeat now
@@ -368,7 +370,7 @@ impl CrosswordReplica {
                     .peer_exec_bar
                     .values()
                     .filter(|&&e| e >= exec_bar)
-                    .count() as u8;
+                    .count() as u16;
                 if passed_cnt == self.population {
                     // all servers have executed up to exec_bar
                     self.snap_bar = exec_bar;
@@ -381,6 +383,40 @@ impl CrosswordReplica {
             }
         }
 
+        // expose my protocol state to debug queries
+        self.external_api.update_debug_state(DebugState {
+            is_leader: self.is_leader(),
+            leader: self.leader,
+            ballot: self.bal_max_seen,
+            commit_bar: self.commit_bar,
+            exec_bar: self.exec_bar,
+            start_slot: self.start_slot,
+            log_len: self.insts.len(),
+            peers_alive: self.heartbeater.peer_alive().clone(),
+        });
+
+        // periodically report my status to the manager
+        self.control_hub.report_status(ServerStatus {
+            ballot: self.bal_max_seen,
+            commit_bar: self.commit_bar,
+            exec_bar: self.exec_bar,
+            peers_alive: self.heartbeater.peer_alive().clone(),
+            perf_models: self
+                .linreg_model
+                .iter()
+                .map(|(&p, m)| (p, m.to_string()))
+                .collect(),
+            hot_keys: self.state_machine.hot_keys(),
+            queue_depths: vec![
+                self.external_api.queue_depth_report(),
+                self.storage_hub.queue_depth_report(),
+                self.transport_hub.queue_depth_report(),
+                self.state_machine.queue_depth_report(),
+            ],
+            resources: ResourceUsage::default(),
+            divergence_repaired: 0,
+        })?;
+
         // pf_trace!("heard heartbeat <- {} bal {}", peer, ballot);
         Ok(())
     }
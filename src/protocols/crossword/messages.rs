@@ -14,12 +14,12 @@ impl CrosswordReplica {
     // considering at most `fault_tolerance` failures.
     #[inline]
     fn coverage_under_faults(
-        rs_total_shards: u8,
-        population: u8,
+        rs_total_shards: u16,
+        population: u16,
         acks: &HashMap<ReplicaId, Bitmap>,
-        fault_tolerance: u8,
+        fault_tolerance: u16,
         assignment_balanced: bool,
-    ) -> u8 {
+    ) -> u16 {
         if acks.len() <= fault_tolerance as usize {
             return 0;
         }
@@ -29,7 +29,7 @@ impl CrosswordReplica {
         if assignment_balanced {
             let spr = acks.values().next().unwrap().count();
             let dj_spr = rs_total_shards / population;
-            return (acks.len() as u8 - fault_tolerance - 1) * dj_spr + spr;
+            return (acks.len() as u16 - fault_tolerance - 1) * dj_spr + spr;
         }
 
         // enumerate all subsets of acks excluding fault number of replicas
@@ -531,7 +531,7 @@ impl CrosswordReplica {
 
             // if quorum size reached AND enough number of shards are
             // remembered, mark this instance as committed
-            if leader_bk.accept_acks.len() as u8 >= self.majority
+            if leader_bk.accept_acks.len() as u16 >= self.majority
                 && Self::coverage_under_faults(
                     self.rs_total_shards,
                     self.population,
@@ -694,13 +694,17 @@ impl CrosswordReplica {
                             inst.status = Status::Executed;
                         } else {
                             for (cmd_idx, (_, req)) in reqs.iter().enumerate() {
-                                if let ApiRequest::Req { cmd, .. } = req {
-                                    self.state_machine.submit_cmd(
+                                if let ApiRequest::Req {
+                                    cmd, checksum, ..
+                                } = req
+                                {
+                                    self.state_machine.submit_checked_cmd(
                                         Self::make_command_id(
                                             self.commit_bar,
                                             cmd_idx,
                                         ),
                                         cmd.clone(),
+                                        *checksum,
                                     )?;
                                 }
                             }
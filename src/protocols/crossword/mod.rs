@@ -30,12 +30,14 @@ use crate::server::{
     StorageHub, TransportHub,
 };
 use crate::utils::{
-    Bitmap, LinearRegressor, PerfModel, QdiscInfo, RSCodeword, Stopwatch,
-    SummersetError, Timer,
+    new_rng, Bitmap, LinearRegressor, PerfModel, QdiscInfo, RSCodeword,
+    Stopwatch, SummersetError, Timer,
 };
 
 use async_trait::async_trait;
 
+use rand::rngs::StdRng;
+
 use get_size::GetSize;
 
 use serde::{Deserialize, Serialize};
@@ -54,6 +56,19 @@ pub struct ReplicaConfigCrossword {
     /// Client request batching maximum batch size.
     pub max_batch_size: usize,
 
+    /// Number of completed replies cached per client for collapsing retried
+    /// requests by idempotency key; 0 means collapsing disabled.
+    pub idem_cache_size: usize,
+
+    /// Idle timeout in secs after which a client session that sent no request
+    /// is closed and its state reclaimed; 0 means never.
+    pub session_idle_s: u64,
+
+    /// Quota in KiB of approximate key-value bytes stored per namespace (the
+    /// key prefix before the first '/'), beyond which Puts are rejected; 0
+    /// means unlimited.
+    pub ns_quota_kb: usize,
+
     /// Path to backing log file.
     pub backer_path: String,
 
@@ -96,16 +111,16 @@ pub struct ReplicaConfigCrossword {
     pub disable_gossip_timer: bool,
 
     /// Fault-tolerance level.
-    pub fault_tolerance: u8,
+    pub fault_tolerance: u16,
 
     /// Maximum chunk size (in slots) of any bulk messages.
     pub msg_chunk_size: usize,
 
     /// Total number of possible shards in a codeword (i.e., codeword width).
     /// If zero, sets this to == population.
-    pub rs_total_shards: u8,
+    pub rs_total_shards: u16,
     /// Number of data shards. If zero, sets this to == majority.
-    pub rs_data_shards: u8,
+    pub rs_data_shards: u16,
 
     /// If non-empty, use this initial shards assignment policy.
     pub init_assignment: String,
@@ -125,6 +140,10 @@ pub struct ReplicaConfigCrossword {
     /// Knob that controls choosing the best config with perf model values.
     pub b_to_d_threshold: f64,
 
+    /// Adapt the batch size cap online to minimize the commit latency
+    /// predicted by the perf models? `max_batch_size` stays the upper bound.
+    pub adaptive_batching: bool,
+
     // [for perf breakdown only]
     /// Recording performance breakdown statistics?
     pub record_breakdown: bool,
@@ -150,6 +169,9 @@ impl Default for ReplicaConfigCrossword {
         ReplicaConfigCrossword {
             batch_interval_ms: 1,
             max_batch_size: 5000,
+            idem_cache_size: 0,
+            session_idle_s: 0,
+            ns_quota_kb: 0,
             backer_path: "/tmp/summerset.crossword.wal".into(),
             logger_sync: false,
             hb_hear_timeout_min: 1200,
@@ -175,6 +197,7 @@ impl Default for ReplicaConfigCrossword {
             linreg_init_b: 10.0,
             linreg_outlier_ratio: 0.5,
             b_to_d_threshold: 0.0,
+            adaptive_batching: false,
             record_breakdown: false,
             record_value_ver: false,
             record_size_recv: false,
@@ -288,9 +311,9 @@ enum WalEntry {
 
 /// Snapshot file entry type.
 //
-// NOTE: the current implementation simply appends a squashed log at the
-//       end of the snapshot file for simplicity. In production, the snapshot
-//       file should be a bounded-sized backend, e.g., an LSM-tree.
+// NOTE: the current implementation rewrites a whole image of the state
+//       machine into the snapshot file for simplicity. In production, the
+//       snapshot file should be a bounded-sized backend, e.g., an LSM-tree.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, GetSize)]
 enum SnapEntry {
     /// Necessary slot indices to remember.
@@ -300,8 +323,9 @@ enum SnapEntry {
         start_slot: usize,
     },
 
-    /// Set of key-value pairs to apply to the state.
-    KVPairSet { pairs: HashMap<String, String> },
+    /// Image of the state machine taken by `take_snapshot()`, reflecting
+    /// exactly the commands of all slots before start_slot.
+    StateImage { image: Vec<u8> },
 }
 
 /// Heartbeat messages monotonically incrementing ID.
@@ -391,16 +415,16 @@ pub(crate) struct CrosswordReplica {
     id: ReplicaId,
 
     /// Total number of replicas in cluster.
-    population: u8,
+    population: u16,
 
     /// Majority quorum size.
-    majority: u8,
+    majority: u16,
 
     /// Reed-Solomon total number of shards.
-    rs_total_shards: u8,
+    rs_total_shards: u16,
 
     /// Reed-Solomon number of data shards.
-    rs_data_shards: u8,
+    rs_data_shards: u16,
 
     /// Doing dynamically adaptive config choosing?
     // NOTE: currently, adaptability is only enabled when an initial assignment
@@ -417,7 +441,7 @@ pub(crate) struct CrosswordReplica {
 
     /// Pre-filled good balanced round-robin assignment policies for quicker
     /// access when peer_alive count is low.
-    brr_assignments: HashMap<u8, Vec<Bitmap>>,
+    brr_assignments: HashMap<u16, Vec<Bitmap>>,
 
     /// Configuration parameters struct.
     config: ReplicaConfigCrossword,
@@ -467,6 +491,9 @@ pub(crate) struct CrosswordReplica {
     /// Timer for triggering follower gossiping.
     gossip_timer: Timer,
 
+    /// Random number generator for gossiping timeouts.
+    gossip_rng: StdRng,
+
     /// Largest ballot number that a leader has sent Prepare messages in.
     bal_prep_sent: Ballot,
 
@@ -530,6 +557,10 @@ pub(crate) struct CrosswordReplica {
     /// Map from peer ID -> current saved linear regression perf model.
     linreg_model: HashMap<ReplicaId, PerfModel>,
 
+    /// Moving average of batch data bytes per request, used by adaptive
+    /// batching; zero before any batch is seen.
+    avg_req_size: f64,
+
     /// Queueing discipline information tracker.
     qdisc_info: Option<QdiscInfo>,
 
@@ -593,13 +624,13 @@ impl CrosswordReplica {
     /// Compose a unique ballot number from base.
     #[inline]
     fn make_unique_ballot(&self, base: u64) -> Ballot {
-        ((base << 8) | ((self.id + 1) as u64)) as Ballot
+        ((base << 16) | ((self.id + 1) as u64)) as Ballot
     }
 
     /// Compose a unique ballot number greater than the given one.
     #[inline]
     fn make_greater_ballot(&self, bal: Ballot) -> Ballot {
-        self.make_unique_ballot((bal >> 8) + 1)
+        self.make_unique_ballot((bal >> 16) + 1)
     }
 
     /// Compose LogActionId from slot index & entry type.
@@ -657,11 +688,15 @@ impl GenericReplica for CrosswordReplica {
         // connect to the cluster manager and get assigned a server ID
         let mut control_hub = ControlHub::new_and_setup(manager).await?;
         let id = control_hub.me;
+        let (api_addr, p2p_addr) =
+            control_hub.resolve_addrs(api_addr, p2p_addr)?;
         let population = control_hub.population;
 
         // parse protocol-specific configs
         let config = parsed_config!(config_str => ReplicaConfigCrossword;
                                     batch_interval_ms, max_batch_size,
+                                    idem_cache_size, session_idle_s,
+                                    ns_quota_kb,
                                     backer_path, logger_sync,
                                     hb_hear_timeout_min, hb_hear_timeout_max,
                                     hb_send_interval_ms, disable_hb_timer,
@@ -674,9 +709,9 @@ impl GenericReplica for CrosswordReplica {
                                     init_assignment, linreg_interval_ms,
                                     linreg_keep_ms, linreg_outlier_ratio,
                                     linreg_init_a, linreg_init_b,
-                                    b_to_d_threshold, record_breakdown,
-                                    record_value_ver, record_size_recv,
-                                    sim_read_lease)?;
+                                    b_to_d_threshold, adaptive_batching,
+                                    record_breakdown, record_value_ver,
+                                    record_size_recv, sim_read_lease)?;
         if config.batch_interval_ms == 0 {
             return logged_err!(
                 "invalid config.batch_interval_ms '{}'",
@@ -739,12 +774,15 @@ impl GenericReplica for CrosswordReplica {
         }
 
         // setup state machine module
-        let state_machine = StateMachine::new_and_setup(id).await?;
+        let state_machine =
+            StateMachine::new_and_setup(id, config.ns_quota_kb * 1024).await?;
+        control_hub.inspect_keys(&state_machine);
 
         // setup storage hub module
         let storage_hub =
             StorageHub::new_and_setup(id, Path::new(&config.backer_path))
                 .await?;
+        control_hub.monitor_wal(Path::new(&config.backer_path));
 
         // setup heartbeat management module
         let heartbeater = Heartbeater::new_and_setup(
@@ -759,7 +797,9 @@ impl GenericReplica for CrosswordReplica {
         let mut transport_hub = TransportHub::new_and_setup(
             id,
             population,
+            SmrProtocol::Crossword,
             p2p_addr,
+            false,
             HashMap::new(),
         )
         .await?;
@@ -778,7 +818,7 @@ impl GenericReplica for CrosswordReplica {
         {
             to_peers
         } else {
-            return logged_err!("unexpected ctrl msg type received");
+            return logged_err!(Protocol; "unexpected ctrl msg type received");
         };
 
         // compute majority and set fault_tolerance level
@@ -826,7 +866,7 @@ impl GenericReplica for CrosswordReplica {
             &config.init_assignment,
         )?;
         let mut max_coverage = Bitmap::new(rs_total_shards, false);
-        let mut nums_assigned: HashSet<u8> = HashSet::new();
+        let mut nums_assigned: HashSet<u16> = HashSet::new();
         for shards in &init_assignment {
             nums_assigned.insert(shards.count());
             for (shard, flag) in shards.iter() {
@@ -865,7 +905,7 @@ impl GenericReplica for CrosswordReplica {
                                     rs_total_shards,
                                     ((r * dj_spr)..(r * dj_spr + spr))
                                         .map(|i| i % rs_total_shards)
-                                        .collect::<Vec<u8>>(),
+                                        .collect::<Vec<u16>>(),
                                 ))
                             })
                             .collect(),
@@ -889,13 +929,16 @@ impl GenericReplica for CrosswordReplica {
                 .await?;
 
         // setup external API module, ready to take in client requests
-        let external_api = ExternalApi::new_and_setup(
+        let mut external_api = ExternalApi::new_and_setup(
             id,
             api_addr,
             Duration::from_millis(config.batch_interval_ms),
             config.max_batch_size,
+            config.idem_cache_size,
+            Duration::from_secs(config.session_idle_s),
         )
         .await?;
+        external_api.stamp_replies(&state_machine);
 
         let mut snapshot_interval = time::interval(Duration::from_secs(
             if config.snapshot_interval_s > 0 {
@@ -945,6 +988,7 @@ impl GenericReplica for CrosswordReplica {
             snapshot_interval,
             linreg_interval,
             gossip_timer: Timer::default(),
+            gossip_rng: new_rng(id, "gossip"),
             bal_prep_sent: 0,
             bal_prepared: 0,
             bal_max_seen: 0,
@@ -1004,6 +1048,7 @@ impl GenericReplica for CrosswordReplica {
                     }
                 })
                 .collect(),
+            avg_req_size: 0.0,
             qdisc_info: if config.b_to_d_threshold > 0.0 {
                 Some(QdiscInfo::new()?)
             } else {
@@ -1129,6 +1174,9 @@ impl GenericReplica for CrosswordReplica {
                     if let Err(e) = self.update_linreg_model(self.config.linreg_keep_ms) {
                         pf_error!("error updating linear regression model: {}", e);
                     }
+                    if self.config.adaptive_batching {
+                        self.adapt_batch_size();
+                    }
                     if let Err(e) = self.update_qdisc_info() {
                         pf_error!("error updating tc qdisc info: {}", e);
                     }
@@ -1206,7 +1254,7 @@ impl GenericReplica for CrosswordReplica {
         self.id
     }
 
-    fn population(&self) -> u8 {
+    fn population(&self) -> u16 {
         self.population
     }
 }
@@ -1231,7 +1279,7 @@ pub(crate) struct CrosswordClient {
     id: ClientId,
 
     /// Number of servers in the cluster.
-    population: u8,
+    population: u16,
 
     /// Configuration parameters struct.
     _config: ClientConfigCrossword,
@@ -1317,7 +1365,7 @@ impl GenericEndpoint for CrosswordClient {
                 }
                 Ok(())
             }
-            _ => logged_err!("unexpected reply type received"),
+            _ => logged_err!(Protocol; "unexpected reply type received"),
         }
     }
 
@@ -1376,6 +1424,22 @@ impl GenericEndpoint for CrosswordClient {
                 .recv_reply()
                 .await?;
 
+            if let ApiReply::LeaderChange { leader } = reply {
+                // notified of a new leader; switch over to it, or keep waiting if
+                // already talking to it
+                if leader == self.server_id
+                    || !self.servers.contains_key(&leader)
+                {
+                    return self.recv_reply().await;
+                }
+                self.server_id = leader;
+                pf_debug!(
+                    "notified of new leader {} '{}'",
+                    leader,
+                    self.servers[&leader]
+                );
+            }
+
             if let ApiReply::Reply {
                 ref result,
                 ref redirect,
@@ -1408,7 +1472,7 @@ impl GenericEndpoint for CrosswordClient {
         self.id
     }
 
-    fn population(&self) -> u8 {
+    fn population(&self) -> u16 {
         self.population
     }
 
@@ -154,7 +154,7 @@ impl CrosswordReplica {
                     break;
                 }
                 _ => {
-                    return logged_err!("unexpected log result type");
+                    return logged_err!(Protocol; "unexpected log result type");
                 }
             }
         }
@@ -182,7 +182,9 @@ impl CrosswordReplica {
             }
             Ok(())
         } else {
-            logged_err!("unexpected log result type or failed truncate")
+            logged_err!(
+                Protocol; "unexpected log result type or failed truncate"
+            )
         }
     }
 }
@@ -51,6 +51,7 @@ impl CrosswordReplica {
                 if let ApiRequest::Req {
                     id: req_id,
                     cmd: Command::Get { .. },
+                    ..
                 } = req
                 {
                     self.external_api.send_reply(
@@ -79,12 +80,16 @@ impl CrosswordReplica {
         }
 
         // compute the complete Reed-Solomon codeword for the batch data
+        let num_reqs = req_batch.len();
         let mut reqs_cw = RSCodeword::from_data(
             req_batch,
             self.rs_data_shards,
             self.rs_total_shards - self.rs_data_shards,
         )?;
         reqs_cw.compute_parity(Some(&self.rs_coder))?;
+        if self.config.adaptive_batching {
+            self.record_req_size(num_reqs, reqs_cw.data_len());
+        }
 
         // create a new instance in the first null slot (or append a new one
         // at the end if no holes exist); fill it up with incoming data
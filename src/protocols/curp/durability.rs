@@ -0,0 +1,160 @@
+//! CURP -- durable logging.
+
+use super::*;
+
+use crate::server::{ApiReply, ApiRequest, LogActionId, LogResult};
+use crate::utils::SummersetError;
+
+// CURPReplica durable WAL logging
+impl CURPReplica {
+    /// Handler of AcceptSlot logging result chan recv.
+    fn handle_logged_accept_slot(
+        &mut self,
+        slot: usize,
+    ) -> Result<(), SummersetError> {
+        pf_trace!("finished AcceptSlot logging for slot {}", slot);
+        self.insts[slot].logged = true;
+        if self.insts[slot].status != Status::Accepting {
+            return Ok(());
+        }
+
+        if self.is_leader() {
+            // speculative results of this slot may be released now
+            for (cmd_idx, cmd_result) in
+                std::mem::take(&mut self.insts[slot].held_replies)
+            {
+                self.reply_executed(slot, cmd_idx, cmd_result)?;
+            }
+
+            // on leader, finishing the logging of an AcceptSlot entry is
+            // equivalent to receiving an Accept reply from myself
+            self.handle_msg_accept_reply(self.id, slot)?;
+        } else {
+            self.transport_hub.send_msg(
+                PeerMsg::AcceptReply { slot },
+                self.config.leader_id,
+            )?;
+            pf_trace!(
+                "sent AcceptReply -> {} for slot {}",
+                self.config.leader_id,
+                slot
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Handles the updates of a synced slot: on the leader, executes the ones
+    /// not speculated and notifies clients of the sync; on a backup, executes
+    /// them all and drops their witness records.
+    fn handle_synced_slot(
+        &mut self,
+        slot: usize,
+    ) -> Result<(), SummersetError> {
+        let is_leader = self.is_leader();
+        let inst = &self.insts[slot];
+        for (cmd_idx, (client, req)) in inst.reqs.iter().enumerate() {
+            let ApiRequest::Req {
+                id: req_id,
+                cmd,
+                checksum,
+            } = req
+            else {
+                continue;
+            };
+
+            let speculated =
+                inst.speculated.get(cmd_idx).copied().unwrap_or(false);
+            if !is_leader || !speculated {
+                self.state_machine.submit_checked_cmd(
+                    Self::make_command_id(slot, cmd_idx),
+                    cmd.clone(),
+                    *checksum,
+                )?;
+            }
+
+            if is_leader {
+                self.unsynced.remove(&(*client, *req_id));
+                if self.external_api.has_client(*client) {
+                    self.external_api.send_reply(
+                        ApiReply::Committed { id: *req_id },
+                        *client,
+                    )?;
+                }
+            } else if !self.witnessed.remove(&(*client, *req_id)) {
+                self.synced_early.insert((*client, *req_id), slot);
+            }
+        }
+        pf_trace!("submitted synced commands in slot {}", slot);
+
+        Ok(())
+    }
+
+    /// Handler of CommitSlot logging result chan recv.
+    fn handle_logged_commit_slot(
+        &mut self,
+        slot: usize,
+    ) -> Result<(), SummersetError> {
+        pf_trace!("finished CommitSlot logging for slot {}", slot);
+
+        // update index of the first non-committed instance
+        if slot == self.commit_bar {
+            while self.commit_bar < self.insts.len() {
+                if self.insts[self.commit_bar].status < Status::Committed {
+                    break;
+                }
+                self.handle_synced_slot(self.commit_bar)?;
+                self.commit_bar += 1;
+            }
+
+            // reads held back by the updates just synced may proceed now
+            if self.is_leader() {
+                self.serve_held_reads()?;
+            } else {
+                // witness requests of clients that never arrived by now are
+                // not coming
+                let commit_bar = self.commit_bar;
+                self.synced_early
+                    .retain(|_, s| *s + SYNCED_EARLY_SLOTS >= commit_bar);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Synthesized handler of durable logging result chan recv.
+    pub(super) async fn handle_log_result(
+        &mut self,
+        action_id: LogActionId,
+        log_result: LogResult<WalEntry>,
+    ) -> Result<(), SummersetError> {
+        let (slot, entry_type) = Self::split_log_action_id(action_id);
+        debug_assert!(slot < self.insts.len());
+
+        if let LogResult::Append { now_size } = log_result {
+            debug_assert!(now_size >= self.wal_offset);
+            // update first wal_offset of slot
+            let inst = &mut self.insts[slot];
+            if inst.wal_offset == 0 || inst.wal_offset > self.wal_offset {
+                inst.wal_offset = self.wal_offset;
+            }
+            debug_assert!(inst.wal_offset <= self.wal_offset);
+            // then update self.wal_offset
+            self.wal_offset = now_size;
+        } else {
+            return logged_err!(
+                Protocol; "unexpected log result type: {:?}", log_result
+            );
+        }
+
+        match entry_type {
+            Status::Accepting => self.handle_logged_accept_slot(slot),
+            Status::Committed => self.handle_logged_commit_slot(slot),
+            _ => {
+                logged_err!(
+                    Protocol; "unexpected log entry type: {:?}", entry_type
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! CURP -- command execution.
+
+use super::*;
+
+use crate::server::{ApiReply, ApiRequest};
+use crate::utils::SummersetError;
+
+// CURPReplica state machine execution
+impl CURPReplica {
+    /// Handler of state machine exec result chan recv.
+    pub(super) async fn handle_cmd_result(
+        &mut self,
+        cmd_id: CommandId,
+        cmd_result: CommandResult,
+    ) -> Result<(), SummersetError> {
+        // only the leader replies to clients
+        if !self.is_leader() {
+            return Ok(());
+        }
+
+        // reads served outside the log carry client & request IDs
+        if let Some((client, req_id)) = Self::split_ro_command_id(cmd_id) {
+            pf_trace!("executed read {} of client {}", req_id, client);
+            if self.external_api.has_client(client) {
+                self.external_api.send_reply(
+                    ApiReply::normal(req_id, Some(cmd_result)),
+                    client,
+                )?;
+            }
+            return Ok(());
+        }
+
+        let (slot, cmd_idx) = Self::split_command_id(cmd_id);
+        debug_assert!(slot < self.insts.len());
+        pf_trace!("executed cmd in instance at slot {} idx {}", slot, cmd_idx);
+
+        // a speculative result is released only once the update is durable
+        // in my own log, so that it survives my crash
+        let inst = &mut self.insts[slot];
+        if !inst.logged {
+            inst.held_replies.push((cmd_idx, cmd_result));
+            return Ok(());
+        }
+        self.reply_executed(slot, cmd_idx, cmd_result)
+    }
+
+    /// Replies to the client of the request at index in slot with its
+    /// execution result.
+    pub(super) fn reply_executed(
+        &mut self,
+        slot: usize,
+        cmd_idx: usize,
+        cmd_result: CommandResult,
+    ) -> Result<(), SummersetError> {
+        let inst = &self.insts[slot];
+        debug_assert!(cmd_idx < inst.reqs.len());
+        let (client, ref req) = inst.reqs[cmd_idx];
+        if let ApiRequest::Req { id: req_id, .. } = req {
+            if self.external_api.has_client(client) {
+                self.external_api.send_reply(
+                    ApiReply::normal(*req_id, Some(cmd_result)),
+                    client,
+                )?;
+                pf_trace!(
+                    "replied -> client {} for slot {} idx {}",
+                    client,
+                    slot,
+                    cmd_idx
+                );
+            }
+        } else {
+            return logged_err!(Protocol; "unexpected API request type");
+        }
+
+        Ok(())
+    }
+}
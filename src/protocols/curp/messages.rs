@@ -0,0 +1,207 @@
+//! CURP -- peer-peer messaging.
+
+use super::*;
+
+use crate::server::LogAction;
+use crate::utils::SummersetError;
+
+// CURPReplica peer-peer messages handling
+impl CURPReplica {
+    /// Handler of Accept message from leader.
+    fn handle_msg_accept(
+        &mut self,
+        peer: ReplicaId,
+        slot: usize,
+        reqs: ReqBatch,
+    ) -> Result<(), SummersetError> {
+        if peer != self.config.leader_id {
+            return Ok(());
+        }
+        pf_trace!(
+            "received Accept <- {} for slot {} of size {}",
+            peer,
+            slot,
+            reqs.len()
+        );
+
+        // locate instance in memory, filling in null instances if needed
+        self.fill_null_insts(slot);
+        let inst = &mut self.insts[slot];
+        if inst.status >= Status::Committed {
+            return Ok(());
+        }
+        inst.status = Status::Accepting;
+        inst.reqs.clone_from(&reqs);
+
+        // record update to the slot durably
+        self.storage_hub.submit_action(
+            Self::make_log_action_id(slot, Status::Accepting),
+            LogAction::Append {
+                entry: WalEntry::AcceptSlot { slot, reqs },
+                sync: self.config.logger_sync,
+            },
+        )?;
+        pf_trace!("submitted AcceptSlot log action for slot {}", slot);
+
+        Ok(())
+    }
+
+    /// Handler of Accept reply from a backup (or myself).
+    pub(super) fn handle_msg_accept_reply(
+        &mut self,
+        peer: ReplicaId,
+        slot: usize,
+    ) -> Result<(), SummersetError> {
+        if !self.is_leader() || slot >= self.insts.len() {
+            return Ok(());
+        }
+        pf_trace!("received AcceptReply <- {} for slot {}", peer, slot);
+
+        // ignore spurious duplications
+        let inst = &mut self.insts[slot];
+        if inst.status != Status::Accepting || inst.accept_acks.get(peer)? {
+            return Ok(());
+        }
+
+        // bookkeep this Accept reply
+        inst.accept_acks.set(peer, true)?;
+
+        // if quorum size reached, the slot is synced; mark it as committed
+        if inst.accept_acks.count() >= self.quorum_cnt {
+            inst.status = Status::Committed;
+            pf_debug!("committed instance at slot {}", slot);
+
+            // record commit event
+            self.storage_hub.submit_action(
+                Self::make_log_action_id(slot, Status::Committed),
+                LogAction::Append {
+                    entry: WalEntry::CommitSlot { slot },
+                    sync: self.config.logger_sync,
+                },
+            )?;
+            pf_trace!("submitted CommitSlot log action for slot {}", slot);
+
+            // broadcast CommitNotice messages to all backups
+            self.transport_hub
+                .bcast_msg(PeerMsg::CommitNotice { slot }, None)?;
+            pf_trace!("broadcast CommitNotice messages for slot {}", slot);
+        }
+
+        Ok(())
+    }
+
+    /// Handler of CommitNotice message from leader.
+    fn handle_msg_commit_notice(
+        &mut self,
+        peer: ReplicaId,
+        slot: usize,
+    ) -> Result<(), SummersetError> {
+        if peer != self.config.leader_id {
+            return Ok(());
+        }
+        pf_trace!("received CommitNotice <- {} for slot {}", peer, slot);
+
+        // Accepts and CommitNotices travel the same link in order, so the
+        // slot is normally known here unless I missed it while down; ask the
+        // leader to send the slots I missed again if so
+        if slot >= self.insts.len() || self.insts[slot].status == Status::Null {
+            return self.ask_catch_up(slot);
+        }
+        if self.insts[slot].status != Status::Accepting {
+            return Ok(());
+        }
+        self.insts[slot].status = Status::Committed;
+
+        // record commit event
+        self.storage_hub.submit_action(
+            Self::make_log_action_id(slot, Status::Committed),
+            LogAction::Append {
+                entry: WalEntry::CommitSlot { slot },
+                sync: self.config.logger_sync,
+            },
+        )?;
+        pf_trace!("submitted CommitSlot log action for slot {}", slot);
+
+        Ok(())
+    }
+
+    /// Asks the leader to send the slots from my `commit_bar` on again, as
+    /// I missed the one at `slot`, unless already asked to cover it.
+    pub(super) fn ask_catch_up(
+        &mut self,
+        slot: usize,
+    ) -> Result<(), SummersetError> {
+        if slot < self.catch_up_bar {
+            return Ok(());
+        }
+        self.transport_hub.send_msg(
+            PeerMsg::CatchUp {
+                from_slot: self.commit_bar,
+            },
+            self.config.leader_id,
+        )?;
+        pf_debug!(
+            "sent CatchUp -> {} from slot {}",
+            self.config.leader_id,
+            self.commit_bar
+        );
+        self.catch_up_bar = slot + 1;
+        Ok(())
+    }
+
+    /// Handler of CatchUp message from a backup. Sends it the Accept of each
+    /// slot from `from_slot` on again, followed by the CommitNotice if
+    /// committed; both travel the same link in order.
+    fn handle_msg_catch_up(
+        &mut self,
+        peer: ReplicaId,
+        from_slot: usize,
+    ) -> Result<(), SummersetError> {
+        if !self.is_leader() {
+            return Ok(());
+        }
+        pf_debug!("received CatchUp <- {} from slot {}", peer, from_slot);
+
+        for slot in from_slot..self.insts.len() {
+            let inst = &self.insts[slot];
+            if inst.status == Status::Null {
+                continue;
+            }
+            self.transport_hub.send_msg(
+                PeerMsg::Accept {
+                    slot,
+                    reqs: inst.reqs.clone(),
+                },
+                peer,
+            )?;
+            if inst.status >= Status::Committed {
+                self.transport_hub
+                    .send_msg(PeerMsg::CommitNotice { slot }, peer)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Synthesized handler of receiving message from peer.
+    pub(super) async fn handle_msg_recv(
+        &mut self,
+        peer: ReplicaId,
+        msg: PeerMsg,
+    ) -> Result<(), SummersetError> {
+        match msg {
+            PeerMsg::Accept { slot, reqs } => {
+                self.handle_msg_accept(peer, slot, reqs)
+            }
+            PeerMsg::AcceptReply { slot } => {
+                self.handle_msg_accept_reply(peer, slot)
+            }
+            PeerMsg::CommitNotice { slot } => {
+                self.handle_msg_commit_notice(peer, slot)
+            }
+            PeerMsg::CatchUp { from_slot } => {
+                self.handle_msg_catch_up(peer, from_slot)
+            }
+        }
+    }
+}
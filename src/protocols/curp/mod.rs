@@ -0,0 +1,878 @@
+//! Replication protocol: CURP (Consistent Unordered Replication Protocol).
+//!
+//! Clients send each update to the leader (the master in CURP terms) and, in
+//! parallel, to the witnesses co-located at all other replicas. The leader
+//! executes an update speculatively and replies once it is durable in the
+//! leader's own log if it commutes with all updates not yet synced to a
+//! quorum, while replicating it to the backups in the background; a witness
+//! records an update if it commutes with all updates it holds. A client
+//! completes an update in one round trip once it has the leader's result and
+//! acceptances from all witnesses; otherwise, i.e., upon a conflict on either
+//! side, it waits for the leader's notice that the update has been synced.
+//! Conflicts are decided by command footprints, so that a custom conflict
+//! spec applies. References:
+//!   - <https://www.usenix.org/conference/nsdi19/presentation/park>
+//!
+//! A backup that missed slots, e.g., while down, asks the leader to send
+//! them again. The leader is statically configured and not replaced upon
+//! failure, so recovery from witnesses is not implemented: the witnesses
+//! only make updates complete in one round trip, and the leader's log is
+//! not recovered if its disk is lost.
+
+mod control;
+mod durability;
+mod execution;
+mod messages;
+mod recovery;
+mod request;
+mod witness;
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::Path;
+
+use crate::client::{ClientApiStub, ClientCtrlStub, ClientId, GenericEndpoint};
+use crate::manager::{CtrlMsg, CtrlReply, CtrlRequest};
+use crate::protocols::SmrProtocol;
+use crate::server::{
+    ApiReply, ApiRequest, CommandId, CommandResult, ConflictIndex, ControlHub,
+    ExternalApi, GenericReplica, LogActionId, ReplicaId, RequestId,
+    StateMachine, StorageHub, TransportHub,
+};
+use crate::utils::{Bitmap, SummersetError};
+
+use async_trait::async_trait;
+
+use futures::future;
+
+use get_size::GetSize;
+
+use serde::{Deserialize, Serialize};
+
+use tokio::sync::watch;
+use tokio::time::Duration;
+
+/// Configuration parameters struct.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReplicaConfigCURP {
+    /// Client request batching interval in millisecs.
+    pub batch_interval_ms: u64,
+
+    /// Client request batching maximum batch size.
+    pub max_batch_size: usize,
+
+    /// Number of completed replies cached per client for collapsing retried
+    /// requests by idempotency key; 0 means collapsing disabled.
+    pub idem_cache_size: usize,
+
+    /// Idle timeout in secs after which a client session that sent no request
+    /// is closed and its state reclaimed; 0 means never.
+    pub session_idle_s: u64,
+
+    /// Quota in KiB of approximate key-value bytes stored per namespace (the
+    /// key prefix before the first '/'), beyond which Puts are rejected; 0
+    /// means unlimited.
+    pub ns_quota_kb: usize,
+
+    /// Path to backing log file.
+    pub backer_path: String,
+
+    /// Whether to call `fsync()`/`fdatasync()` on logger.
+    pub logger_sync: bool,
+
+    /// ID of the leader that executes commands speculatively and replicates
+    /// them to the backups.
+    pub leader_id: ReplicaId,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for ReplicaConfigCURP {
+    fn default() -> Self {
+        ReplicaConfigCURP {
+            batch_interval_ms: 1,
+            max_batch_size: 5000,
+            idem_cache_size: 0,
+            session_idle_s: 0,
+            ns_quota_kb: 0,
+            backer_path: "/tmp/summerset.curp.wal".into(),
+            logger_sync: false,
+            leader_id: 0,
+        }
+    }
+}
+
+/// Instance status enum.
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Clone,
+    Copy,
+    Serialize,
+    Deserialize,
+    GetSize,
+)]
+enum Status {
+    Null = 0,
+    Accepting = 1,
+    Committed = 2,
+}
+
+/// Number of slots a witness keeps remembering an update synced before the
+/// client's request to record it arrived.
+const SYNCED_EARLY_SLOTS: usize = 4096;
+
+/// Request batch type (i.e., the "value" in Paxos).
+type ReqBatch = Vec<(ClientId, ApiRequest)>;
+
+/// In-memory instance containing a commands batch.
+#[derive(Debug, Clone)]
+struct Instance {
+    /// Instance status.
+    status: Status,
+
+    /// Batch of client requests.
+    reqs: ReqBatch,
+
+    /// Which requests in the batch the leader has executed speculatively,
+    /// ahead of syncing them.
+    speculated: Vec<bool>,
+
+    /// Whether the AcceptSlot entry of this instance is durable in my WAL.
+    logged: bool,
+
+    /// On the leader: results of speculatively executed requests, as
+    /// (index in batch, result), held back until the instance is logged.
+    held_replies: Vec<(usize, CommandResult)>,
+
+    /// Replicas from which the leader has received Accept confirmations.
+    accept_acks: Bitmap,
+
+    /// Offset of first durable WAL log entry related to this instance.
+    wal_offset: usize,
+}
+
+/// Stable storage WAL log entry type.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, GetSize)]
+#[allow(clippy::enum_variant_names)]
+enum WalEntry {
+    /// Records an update to the batch of requests in the instance at index.
+    AcceptSlot { slot: usize, reqs: ReqBatch },
+
+    /// Records an event of committing the instance at index.
+    CommitSlot { slot: usize },
+}
+
+/// Peer-peer message type.
+#[derive(Debug, Clone, Serialize, Deserialize, GetSize)]
+enum PeerMsg {
+    /// Accept message from leader to backups.
+    Accept { slot: usize, reqs: ReqBatch },
+
+    /// Accept reply from backup to leader.
+    AcceptReply { slot: usize },
+
+    /// Notification of commit from leader to backups.
+    CommitNotice { slot: usize },
+
+    /// Request from a backup that missed slots for the leader to send them
+    /// again from `from_slot` on.
+    CatchUp { from_slot: usize },
+}
+
+/// CURP server replica module.
+pub(crate) struct CURPReplica {
+    /// Replica ID in cluster.
+    id: ReplicaId,
+
+    /// Total number of replicas in cluster.
+    population: u16,
+
+    /// Majority quorum size.
+    quorum_cnt: u16,
+
+    /// Configuration parameters struct.
+    config: ReplicaConfigCURP,
+
+    /// Address string for client requests API.
+    _api_addr: SocketAddr,
+
+    /// Address string for internal peer-peer communication.
+    _p2p_addr: SocketAddr,
+
+    /// ControlHub module.
+    control_hub: ControlHub,
+
+    /// ExternalApi module.
+    external_api: ExternalApi,
+
+    /// StateMachine module.
+    state_machine: StateMachine,
+
+    /// StorageHub module.
+    storage_hub: StorageHub<WalEntry>,
+
+    /// TransportHub module.
+    transport_hub: TransportHub<PeerMsg>,
+
+    /// In-memory log of instances.
+    insts: Vec<Instance>,
+
+    /// Slot index of the first non-committed instance.
+    commit_bar: usize,
+
+    /// On a backup: slot index up to which I have asked the leader to send
+    /// missed slots again.
+    catch_up_bar: usize,
+
+    /// On the leader: updates accepted into the log but not yet synced to a
+    /// quorum, keyed by (client ID, request ID).
+    unsynced: ConflictIndex<(ClientId, RequestId)>,
+
+    /// On the leader: reads held back until the updates they conflict with
+    /// get synced.
+    held_reads: Vec<(ClientId, ApiRequest)>,
+
+    /// On a witness: updates recorded and not yet known to be synced.
+    witnessed: ConflictIndex<(ClientId, RequestId)>,
+
+    /// On a witness: updates known to be synced before the client's request
+    /// to record them arrived, so that they need no record, mapped to the
+    /// slot they got synced in. Forgotten after `SYNCED_EARLY_SLOTS` slots.
+    synced_early: HashMap<(ClientId, RequestId), usize>,
+
+    /// Current durable WAL log file offset.
+    wal_offset: usize,
+}
+
+// CURPReplica common helpers
+impl CURPReplica {
+    /// Am I the leader?
+    #[inline]
+    fn is_leader(&self) -> bool {
+        self.id == self.config.leader_id
+    }
+
+    /// Create an empty null instance.
+    #[inline]
+    fn null_instance(&self) -> Instance {
+        Instance {
+            status: Status::Null,
+            reqs: ReqBatch::new(),
+            speculated: vec![],
+            logged: false,
+            held_replies: vec![],
+            accept_acks: Bitmap::new(self.population, false),
+            wal_offset: 0,
+        }
+    }
+
+    /// Fill in null instances so that the in-mem log covers the given slot.
+    fn fill_null_insts(&mut self, slot: usize) {
+        while self.insts.len() <= slot {
+            self.insts.push(self.null_instance());
+        }
+    }
+
+    /// Compose LogActionId from slot index & entry type.
+    #[inline]
+    fn make_log_action_id(slot: usize, entry_type: Status) -> LogActionId {
+        let type_num = match entry_type {
+            Status::Accepting => 1,
+            Status::Committed => 2,
+            _ => panic!("unknown log entry type {:?}", entry_type),
+        };
+        ((slot << 2) | type_num) as LogActionId
+    }
+
+    /// Decompose LogActionId into slot index & entry type.
+    #[inline]
+    fn split_log_action_id(log_action_id: LogActionId) -> (usize, Status) {
+        let slot = (log_action_id >> 2) as usize;
+        let type_num = log_action_id & ((1 << 2) - 1);
+        let entry_type = match type_num {
+            1 => Status::Accepting,
+            2 => Status::Committed,
+            _ => panic!("unknown log entry type num {}", type_num),
+        };
+        (slot, entry_type)
+    }
+
+    /// Compose CommandId from slot index & command index within.
+    #[inline]
+    fn make_command_id(slot: usize, cmd_idx: usize) -> CommandId {
+        debug_assert!(slot <= (u32::MAX as usize));
+        debug_assert!(cmd_idx <= (u32::MAX as usize) / 2);
+        ((slot << 32) | cmd_idx) as CommandId
+    }
+
+    /// Decompose CommandId into slot index & command index within.
+    #[inline]
+    fn split_command_id(command_id: CommandId) -> (usize, usize) {
+        let slot = (command_id >> 32) as usize;
+        let cmd_idx = (command_id & ((1 << 32) - 1)) as usize;
+        (slot, cmd_idx)
+    }
+
+    /// Special composition of a command ID used for reads served by the
+    /// leader outside the log.
+    #[inline]
+    fn make_ro_command_id(client: ClientId, req_id: RequestId) -> CommandId {
+        debug_assert!(client <= (u32::MAX as ClientId));
+        debug_assert!(req_id <= (u32::MAX as RequestId) / 2);
+        ((client << 32) | (1 << 31) | req_id) as CommandId
+    }
+
+    /// Decompose a read CommandId into client ID & request ID; returns `None`
+    /// if it is a normal CommandId.
+    #[inline]
+    fn split_ro_command_id(
+        command_id: CommandId,
+    ) -> Option<(ClientId, RequestId)> {
+        if command_id & (1 << 31) == 0 {
+            return None;
+        }
+        let client = (command_id >> 32) as ClientId;
+        let req_id = (command_id & ((1 << 31) - 1)) as RequestId;
+        Some((client, req_id))
+    }
+}
+
+#[async_trait]
+impl GenericReplica for CURPReplica {
+    async fn new_and_setup(
+        api_addr: SocketAddr,
+        p2p_addr: SocketAddr,
+        manager: SocketAddr,
+        config_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        // connect to the cluster manager and get assigned a server ID
+        let mut control_hub = ControlHub::new_and_setup(manager).await?;
+        let id = control_hub.me;
+        let (api_addr, p2p_addr) =
+            control_hub.resolve_addrs(api_addr, p2p_addr)?;
+        let population = control_hub.population;
+
+        // parse protocol-specific configs
+        let config = parsed_config!(config_str => ReplicaConfigCURP;
+                                    batch_interval_ms, max_batch_size,
+                                    idem_cache_size, session_idle_s,
+                                    ns_quota_kb,
+                                    backer_path, logger_sync,
+                                    leader_id)?;
+        if config.batch_interval_ms == 0 {
+            return logged_err!(
+                "invalid config.batch_interval_ms '{}'",
+                config.batch_interval_ms
+            );
+        }
+        if config.leader_id >= population {
+            return logged_err!(
+                "invalid config.leader_id '{}'",
+                config.leader_id
+            );
+        }
+
+        // setup state machine module
+        let state_machine =
+            StateMachine::new_and_setup(id, config.ns_quota_kb * 1024).await?;
+        control_hub.inspect_keys(&state_machine);
+
+        // setup storage hub module
+        let storage_hub =
+            StorageHub::new_and_setup(id, Path::new(&config.backer_path))
+                .await?;
+
+        // setup transport hub module
+        let mut transport_hub = TransportHub::new_and_setup(
+            id,
+            population,
+            SmrProtocol::CURP,
+            p2p_addr,
+            false,
+            HashMap::new(), // no leases
+        )
+        .await?;
+
+        // ask for the list of peers to proactively connect to. Do this after
+        // transport hub has been set up, so that I will be able to accept
+        // later peer connections
+        control_hub.send_ctrl(CtrlMsg::NewServerJoin {
+            id,
+            protocol: SmrProtocol::CURP,
+            api_addr,
+            p2p_addr,
+        })?;
+        let to_peers = if let CtrlMsg::ConnectToPeers { to_peers, .. } =
+            control_hub.recv_ctrl().await?
+        {
+            to_peers
+        } else {
+            return logged_err!(Protocol; "unexpected ctrl msg type received");
+        };
+
+        // proactively connect to some peers, then wait for all population
+        // have been connected with me
+        for (peer, conn_addr) in to_peers {
+            transport_hub.connect_to_peer(peer, conn_addr).await?;
+        }
+        transport_hub.wait_for_group(population).await?;
+
+        // setup external API module, ready to take in client requests
+        let mut external_api = ExternalApi::new_and_setup(
+            id,
+            api_addr,
+            Duration::from_millis(config.batch_interval_ms),
+            config.max_batch_size,
+            config.idem_cache_size,
+            Duration::from_secs(config.session_idle_s),
+        )
+        .await?;
+        external_api.stamp_replies(&state_machine);
+
+        Ok(CURPReplica {
+            id,
+            population,
+            quorum_cnt: (population / 2) + 1,
+            config,
+            _api_addr: api_addr,
+            _p2p_addr: p2p_addr,
+            control_hub,
+            external_api,
+            state_machine,
+            storage_hub,
+            transport_hub,
+            insts: vec![],
+            commit_bar: 0,
+            catch_up_bar: 0,
+            unsynced: ConflictIndex::new(),
+            held_reads: vec![],
+            witnessed: ConflictIndex::new(),
+            synced_early: HashMap::new(),
+            wal_offset: 0,
+        })
+    }
+
+    async fn run(
+        &mut self,
+        mut rx_term: watch::Receiver<bool>,
+    ) -> Result<bool, SummersetError> {
+        // recover the log & state from durable WAL log, then pick up
+        // replication of the slots not yet committed
+        self.recover_from_wal().await?;
+        self.resume_replication()?;
+
+        // main event loop
+        let mut paused = false;
+        loop {
+            tokio::select! {
+                // client request batch
+                req_batch = self.external_api.get_req_batch(), if !paused => {
+                    if let Err(e) = req_batch {
+                        pf_error!("error getting req batch: {}", e);
+                        continue;
+                    }
+                    let req_batch = req_batch.unwrap();
+                    if let Err(e) = self.handle_req_batch(req_batch).await {
+                        pf_error!("error handling req batch: {}", e);
+                    }
+                },
+
+                // durable logging result
+                log_result = self.storage_hub.get_result(), if !paused => {
+                    if let Err(e) = log_result {
+                        pf_error!("error getting log result: {}", e);
+                        continue;
+                    }
+                    let (action_id, log_result) = log_result.unwrap();
+                    if let Err(e) = self.handle_log_result(action_id, log_result).await {
+                        pf_error!("error handling log result {}: {}",
+                                           action_id, e);
+                    }
+                },
+
+                // message from peer
+                msg = self.transport_hub.recv_msg(), if !paused => {
+                    if let Err(_e) = msg {
+                        // NOTE: commented out to prevent console lags
+                        //       during benchmarking
+                        // pf_error!("error receiving peer msg: {}", e);
+                        continue;
+                    }
+                    let (peer, msg) = msg.unwrap();
+                    if let Err(e) = self.handle_msg_recv(peer, msg).await {
+                        pf_error!("error handling msg recv <- {}: {}", peer, e);
+                    }
+                },
+
+                // state machine execution result
+                cmd_result = self.state_machine.get_result(), if !paused => {
+                    if let Err(e) = cmd_result {
+                        pf_error!("error getting cmd result: {}", e);
+                        continue;
+                    }
+                    let (cmd_id, cmd_result) = cmd_result.unwrap();
+                    if let Err(e) = self.handle_cmd_result(cmd_id, cmd_result).await {
+                        pf_error!("error handling cmd result {}: {}", cmd_id, e);
+                    }
+                },
+
+                // manager control message
+                ctrl_msg = self.control_hub.recv_ctrl() => {
+                    if let Err(e) = ctrl_msg {
+                        pf_error!("error getting ctrl msg: {}", e);
+                        continue;
+                    }
+                    let ctrl_msg = ctrl_msg.unwrap();
+                    match self.handle_ctrl_msg(ctrl_msg, &mut paused).await {
+                        Ok(terminate) => {
+                            if let Some(restart) = terminate {
+                                return Ok(restart);
+                            }
+                        },
+                        Err(e) => {
+                            pf_error!("error handling ctrl msg: {}", e);
+                        }
+                    }
+                },
+
+                // receiving termination signal
+                _ = rx_term.changed() => {
+                    pf_warn!("server caught termination signal");
+                    return Ok(false);
+                }
+            }
+        }
+    }
+
+    fn id(&self) -> ReplicaId {
+        self.id
+    }
+
+    fn population(&self) -> u16 {
+        self.population
+    }
+}
+
+/// Configuration parameters struct.
+#[derive(Debug, Deserialize)]
+pub struct ClientConfigCURP {
+    /// ID of the leader, which executes requests and replies to them.
+    pub leader_id: ReplicaId,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for ClientConfigCURP {
+    fn default() -> Self {
+        ClientConfigCURP { leader_id: 0 }
+    }
+}
+
+/// Progress of an update sent on the fast path.
+#[derive(Debug, Default)]
+struct PendingUpdate {
+    /// Leader's reply carrying the result, until handed out.
+    reply: Option<ApiReply>,
+
+    /// Whether the reply has been handed out already.
+    returned: bool,
+
+    /// Whether the leader has told that the update is synced.
+    synced: bool,
+
+    /// Number of witnesses that recorded the update.
+    accepts: usize,
+}
+
+/// CURP client-side module. Knows the addresses of all replicas, sends
+/// updates to the leader and all witnesses, and collects witness acks.
+pub(crate) struct CURPClient {
+    /// Client ID.
+    id: ClientId,
+
+    /// Number of servers in the cluster.
+    population: u16,
+
+    /// Configuration parameters struct.
+    config: ClientConfigCURP,
+
+    /// List of active servers information.
+    servers: HashMap<ReplicaId, SocketAddr>,
+
+    /// Control API stub to the cluster manager.
+    ctrl_stub: ClientCtrlStub,
+
+    /// API stubs for communicating with servers.
+    api_stubs: HashMap<ReplicaId, ClientApiStub>,
+
+    /// Servers to which the last request has not been fully sent yet.
+    unsent: Vec<ReplicaId>,
+
+    /// Map from request ID -> updates sent on the fast path and not yet
+    /// known to be synced.
+    pending: HashMap<RequestId, PendingUpdate>,
+}
+
+impl CURPClient {
+    /// If the request is an update to be sent to witnesses as well, returns
+    /// its request ID. Other requests go to the leader only.
+    #[inline]
+    fn fast_path_update(req: &ApiRequest) -> Option<RequestId> {
+        match req {
+            ApiRequest::Req { id, cmd, .. }
+                if !cmd.footprint().writes.is_empty() =>
+            {
+                Some(*id)
+            }
+            _ => None,
+        }
+    }
+
+    /// Receives the next reply from any server.
+    async fn recv_any_reply(
+        &mut self,
+    ) -> Result<(ReplicaId, ApiReply), SummersetError> {
+        if self.api_stubs.is_empty() {
+            return logged_err!("no server connection to receive from");
+        }
+        // `recv_reply()` is cancellation-safe, so dropping the others is ok
+        let recvs = self.api_stubs.iter_mut().map(|(&server, api_stub)| {
+            Box::pin(async move { (server, api_stub.recv_reply().await) })
+        });
+        let ((server, reply), _, _) = future::select_all(recvs).await;
+        Ok((server, reply?))
+    }
+
+    /// Hands out the result of the update if it has completed, either on the
+    /// fast path with acks from all witnesses or by being synced.
+    fn take_completed(&mut self, req_id: RequestId) -> Option<ApiReply> {
+        let num_witnesses = self.api_stubs.len().saturating_sub(1);
+        let update = self.pending.get_mut(&req_id)?;
+        if update.returned
+            || update.reply.is_none()
+            || !(update.synced || update.accepts >= num_witnesses)
+        {
+            return None;
+        }
+        update.returned = true;
+        let reply = update.reply.take();
+        if update.synced {
+            self.pending.remove(&req_id);
+        }
+        reply
+    }
+}
+
+#[async_trait]
+impl GenericEndpoint for CURPClient {
+    async fn new_and_setup(
+        manager: SocketAddr,
+        config_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        // connect to the cluster manager and get assigned a client ID
+        pf_debug!("connecting to manager '{}'...", manager);
+        let ctrl_stub = ClientCtrlStub::new_by_connect(manager).await?;
+        let id = ctrl_stub.id;
+
+        // parse protocol-specific configs
+        let config = parsed_config!(config_str => ClientConfigCURP;
+                                    leader_id)?;
+
+        Ok(CURPClient {
+            id,
+            population: 0,
+            config,
+            servers: HashMap::new(),
+            ctrl_stub,
+            api_stubs: HashMap::new(),
+            unsent: vec![],
+            pending: HashMap::new(),
+        })
+    }
+
+    async fn connect(&mut self) -> Result<(), SummersetError> {
+        // disallow reconnection without leaving
+        if !self.api_stubs.is_empty() {
+            return logged_err!("reconnecting without leaving");
+        }
+
+        // ask the manager about the list of active servers
+        let mut sent =
+            self.ctrl_stub.send_req(Some(&CtrlRequest::QueryInfo))?;
+        while !sent {
+            sent = self.ctrl_stub.send_req(None)?;
+        }
+
+        let reply = self.ctrl_stub.recv_reply().await?;
+        match reply {
+            CtrlReply::QueryInfo {
+                population,
+                servers_info,
+            } => {
+                self.population = population;
+
+                // the leader is fixed and must be active
+                debug_assert!(!servers_info.is_empty());
+                if !servers_info.contains_key(&self.config.leader_id)
+                    || servers_info[&self.config.leader_id].is_paused
+                {
+                    return logged_err!(
+                        "leader {} not active",
+                        self.config.leader_id
+                    );
+                }
+
+                // establish connection to all servers
+                self.servers = servers_info
+                    .into_iter()
+                    .map(|(id, info)| (id, info.api_addr))
+                    .collect();
+                for (&id, &server) in &self.servers {
+                    pf_debug!("connecting to server {} '{}'...", id, server);
+                    let api_stub =
+                        ClientApiStub::new_by_connect(self.id, server).await?;
+                    self.api_stubs.insert(id, api_stub);
+                }
+                Ok(())
+            }
+            _ => logged_err!(Protocol; "unexpected reply type received"),
+        }
+    }
+
+    async fn leave(&mut self, permanent: bool) -> Result<(), SummersetError> {
+        // send leave notification to all servers
+        self.unsent.clear();
+        self.pending.clear();
+        for (id, mut api_stub) in self.api_stubs.drain() {
+            let mut sent = api_stub.send_req(Some(&ApiRequest::Leave))?;
+            while !sent {
+                sent = api_stub.send_req(None)?;
+            }
+
+            // NOTE: commented out the following wait to avoid accidental
+            //       hanging upon leaving
+            // while api_stub.recv_reply().await? != ApiReply::Leave {}
+            pf_debug!("left server connection {}", id);
+        }
+
+        // if permanently leaving, send leave notification to the manager
+        if permanent {
+            let mut sent =
+                self.ctrl_stub.send_req(Some(&CtrlRequest::Leave))?;
+            while !sent {
+                sent = self.ctrl_stub.send_req(None)?;
+            }
+
+            while self.ctrl_stub.recv_reply().await? != CtrlReply::Leave {}
+            pf_debug!("left manager connection");
+        }
+
+        Ok(())
+    }
+
+    fn send_req(
+        &mut self,
+        req: Option<&ApiRequest>,
+    ) -> Result<bool, SummersetError> {
+        match req {
+            Some(req) => {
+                let targets: Vec<ReplicaId> =
+                    if let Some(req_id) = Self::fast_path_update(req) {
+                        self.pending.insert(req_id, PendingUpdate::default());
+                        self.api_stubs.keys().copied().collect()
+                    } else {
+                        vec![self.config.leader_id]
+                    };
+                self.unsent.clear();
+                for server in targets {
+                    let Some(api_stub) = self.api_stubs.get_mut(&server) else {
+                        return Err(SummersetError::msg(format!(
+                            "server {} not in api_stubs",
+                            server
+                        )));
+                    };
+                    if !api_stub.send_req(Some(req))? {
+                        self.unsent.push(server);
+                    }
+                }
+            }
+
+            None => {
+                // retry the servers that were blocked last time
+                let mut unsent = vec![];
+                for server in self.unsent.drain(..) {
+                    if let Some(api_stub) = self.api_stubs.get_mut(&server) {
+                        if !api_stub.send_req(None)? {
+                            unsent.push(server);
+                        }
+                    }
+                }
+                self.unsent = unsent;
+            }
+        }
+
+        Ok(self.unsent.is_empty())
+    }
+
+    async fn recv_reply(&mut self) -> Result<ApiReply, SummersetError> {
+        loop {
+            let (server, reply) = self.recv_any_reply().await?;
+            let from_leader = server == self.config.leader_id;
+            let req_id = match reply {
+                ApiReply::WitnessAck { id, accepted } => {
+                    if let Some(update) = self.pending.get_mut(&id) {
+                        if accepted {
+                            update.accepts += 1;
+                        }
+                    }
+                    id
+                }
+
+                ApiReply::Committed { id }
+                    if from_leader && self.pending.contains_key(&id) =>
+                {
+                    let update = self.pending.get_mut(&id).unwrap();
+                    update.synced = true;
+                    if update.returned {
+                        self.pending.remove(&id);
+                    }
+                    id
+                }
+
+                ApiReply::Reply { id, ref result, .. }
+                    if from_leader && self.pending.contains_key(&id) =>
+                {
+                    if result.is_none() {
+                        // not taken in by the leader, won't get synced
+                        self.pending.remove(&id);
+                        return Ok(reply);
+                    }
+                    self.pending.get_mut(&id).unwrap().reply = Some(reply);
+                    id
+                }
+
+                // replies to other requests come from the leader only
+                _ if from_leader => return Ok(reply),
+                _ => continue,
+            };
+
+            if let Some(reply) = self.take_completed(req_id) {
+                return Ok(reply);
+            }
+        }
+    }
+
+    fn id(&self) -> ClientId {
+        self.id
+    }
+
+    fn population(&self) -> u16 {
+        self.population
+    }
+
+    fn ctrl_stub(&mut self) -> &mut ClientCtrlStub {
+        &mut self.ctrl_stub
+    }
+}
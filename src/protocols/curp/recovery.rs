@@ -0,0 +1,146 @@
+//! CURP -- recovery from WAL.
+
+use super::*;
+
+use crate::server::{ApiRequest, LogAction, LogResult};
+use crate::utils::SummersetError;
+
+// CURPReplica recovery from WAL log
+impl CURPReplica {
+    /// Apply a durable storage log entry for recovery.
+    async fn recover_apply_entry(
+        &mut self,
+        entry: WalEntry,
+    ) -> Result<(), SummersetError> {
+        match entry {
+            WalEntry::AcceptSlot { slot, reqs } => {
+                // locate instance in memory, filling in null instances if needed
+                self.fill_null_insts(slot);
+                // update instance state
+                let inst = &mut self.insts[slot];
+                inst.logged = true;
+                if inst.status < Status::Committed {
+                    inst.status = Status::Accepting;
+                    inst.reqs = reqs;
+                }
+            }
+
+            WalEntry::CommitSlot { slot } => {
+                if slot >= self.insts.len() {
+                    return logged_err!(
+                        "commit for slot {} without accepted batch",
+                        slot
+                    );
+                }
+                self.insts[slot].status = Status::Committed;
+                // execute commands in contiguously committed instances on the
+                // state machine synchronously
+                while self.commit_bar < self.insts.len() {
+                    let inst = &self.insts[self.commit_bar];
+                    if inst.status < Status::Committed {
+                        break;
+                    }
+                    for (_, req) in &inst.reqs {
+                        if let ApiRequest::Req { cmd, .. } = req {
+                            // using 0 as a special command ID
+                            self.state_machine
+                                .do_sync_cmd(0, cmd.clone())
+                                .await?;
+                        }
+                    }
+                    self.commit_bar += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Picks up replication of the slots not yet committed after recovering
+    /// from WAL: the leader sends their Accepts to the backups again and
+    /// counts its own durable ones, while a backup asks the leader for the
+    /// slots it may have missed while down.
+    pub(super) fn resume_replication(&mut self) -> Result<(), SummersetError> {
+        if !self.is_leader() {
+            return self.ask_catch_up(self.commit_bar);
+        }
+
+        for slot in self.commit_bar..self.insts.len() {
+            let inst = &self.insts[slot];
+            if inst.status != Status::Accepting {
+                continue;
+            }
+            self.transport_hub.bcast_msg(
+                PeerMsg::Accept {
+                    slot,
+                    reqs: inst.reqs.clone(),
+                },
+                None,
+            )?;
+            if inst.logged {
+                self.handle_msg_accept_reply(self.id, slot)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Recover state from durable storage WAL log.
+    pub(super) async fn recover_from_wal(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        debug_assert_eq!(self.wal_offset, 0);
+        loop {
+            match self
+                .storage_hub
+                .do_sync_action(
+                    0, // using 0 as dummy log action ID
+                    LogAction::Read {
+                        offset: self.wal_offset,
+                    },
+                )
+                .await?
+                .1
+            {
+                LogResult::Read {
+                    entry: Some(entry),
+                    end_offset,
+                } => {
+                    self.recover_apply_entry(entry).await?;
+                    // update log offset
+                    self.wal_offset = end_offset;
+                }
+                LogResult::Read { entry: None, .. } => {
+                    // end of log reached
+                    break;
+                }
+                _ => {
+                    return logged_err!(Protocol; "unexpected log result type");
+                }
+            }
+        }
+
+        // do an extra Truncate to remove partial entry at the end if any
+        if let LogResult::Truncate {
+            offset_ok: true, ..
+        } = self
+            .storage_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Truncate {
+                    offset: self.wal_offset,
+                },
+            )
+            .await?
+            .1
+        {
+            if self.wal_offset > 0 {
+                pf_info!("recovered from wal log: commit {}", self.commit_bar);
+            }
+            Ok(())
+        } else {
+            logged_err!(
+                Protocol; "unexpected log result type or failed truncate"
+            )
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! CURP -- client request entrance.
+
+use std::mem;
+
+use super::*;
+
+use crate::server::{ApiRequest, LogAction};
+use crate::utils::SummersetError;
+
+// CURPReplica client requests entrance
+impl CURPReplica {
+    /// Serves a read on the leader outside the log: right away if it commutes
+    /// with all unsynced updates, otherwise once they get synced.
+    fn serve_read(
+        &mut self,
+        client: ClientId,
+        req: ApiRequest,
+    ) -> Result<(), SummersetError> {
+        if let ApiRequest::Req {
+            id: req_id,
+            ref cmd,
+            checksum,
+        } = req
+        {
+            if self.unsynced.conflicts(&cmd.footprint()) {
+                self.held_reads.push((client, req));
+                return Ok(());
+            }
+            self.state_machine.submit_checked_cmd(
+                Self::make_ro_command_id(client, req_id),
+                cmd.clone(),
+                checksum,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Serves the held reads that no longer conflict with unsynced updates.
+    pub(super) fn serve_held_reads(&mut self) -> Result<(), SummersetError> {
+        for (client, req) in mem::take(&mut self.held_reads) {
+            self.serve_read(client, req)?;
+        }
+        Ok(())
+    }
+
+    /// Puts a batch of updates into a new slot, executing speculatively the
+    /// ones that commute with all unsynced updates, and starts replicating
+    /// the slot to the backups.
+    fn accept_updates(
+        &mut self,
+        batch: ReqBatch,
+    ) -> Result<(), SummersetError> {
+        let slot = self.insts.len();
+        let mut speculated = Vec::with_capacity(batch.len());
+        for (cmd_idx, (client, req)) in batch.iter().enumerate() {
+            if let ApiRequest::Req {
+                id: req_id,
+                cmd,
+                checksum,
+            } = req
+            {
+                // later updates conflicting with this one in the same batch
+                // are caught, as it gets indexed right away
+                let footprint = cmd.footprint();
+                let speculate = !self.unsynced.conflicts(&footprint);
+                self.unsynced.insert((*client, *req_id), footprint);
+                if speculate {
+                    self.state_machine.submit_checked_cmd(
+                        Self::make_command_id(slot, cmd_idx),
+                        cmd.clone(),
+                        *checksum,
+                    )?;
+                }
+                speculated.push(speculate);
+            } else {
+                speculated.push(false);
+            }
+        }
+        pf_trace!(
+            "speculated {}/{} updates in slot {}, {} unsynced",
+            speculated.iter().filter(|&&s| s).count(),
+            batch.len(),
+            slot,
+            self.unsynced.len()
+        );
+
+        let mut inst = self.null_instance();
+        inst.status = Status::Accepting;
+        inst.reqs.clone_from(&batch);
+        inst.speculated = speculated;
+        self.insts.push(inst);
+
+        // record update to the slot durably
+        self.storage_hub.submit_action(
+            Self::make_log_action_id(slot, Status::Accepting),
+            LogAction::Append {
+                entry: WalEntry::AcceptSlot {
+                    slot,
+                    reqs: batch.clone(),
+                },
+                sync: self.config.logger_sync,
+            },
+        )?;
+        pf_trace!("submitted AcceptSlot log action for slot {}", slot);
+
+        // send Accept messages to all backups
+        self.transport_hub
+            .bcast_msg(PeerMsg::Accept { slot, reqs: batch }, None)?;
+        pf_trace!("broadcast Accept messages for slot {}", slot);
+
+        Ok(())
+    }
+
+    /// Handler of client request batch chan recv.
+    pub(super) async fn handle_req_batch(
+        &mut self,
+        req_batch: ReqBatch,
+    ) -> Result<(), SummersetError> {
+        let batch_size = req_batch.len();
+        debug_assert!(batch_size > 0);
+        pf_debug!("got request batch of size {}", batch_size);
+
+        // on backups, requests are updates sent to the witness
+        if !self.is_leader() {
+            return self.witness_record_reqs(req_batch);
+        }
+
+        // reads are served outside the log
+        let mut update_batch = ReqBatch::new();
+        for (client, req) in req_batch {
+            if let ApiRequest::Req { cmd, .. } = &req {
+                if cmd.footprint().writes.is_empty() {
+                    self.serve_read(client, req)?;
+                    continue;
+                }
+            }
+            update_batch.push((client, req));
+        }
+
+        if !update_batch.is_empty() {
+            self.accept_updates(update_batch)?;
+        }
+        Ok(())
+    }
+}
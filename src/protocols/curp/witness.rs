@@ -0,0 +1,61 @@
+//! CURP -- witness records of unsynced updates.
+
+use super::*;
+
+use crate::server::{ApiReply, ApiRequest};
+use crate::utils::SummersetError;
+
+// CURPReplica witness-side logic
+impl CURPReplica {
+    /// Records each update in the batch if it commutes with all updates the
+    /// witness holds, and tells the client whether it got recorded.
+    pub(super) fn witness_record_reqs(
+        &mut self,
+        req_batch: ReqBatch,
+    ) -> Result<(), SummersetError> {
+        for (client, req) in req_batch {
+            let ApiRequest::Req {
+                id: req_id, cmd, ..
+            } = req
+            else {
+                continue; // only updates are sent to witnesses
+            };
+
+            // an update synced already needs no record, and a retried one is
+            // recorded already
+            let key = (client, req_id);
+            let accepted = if self.synced_early.remove(&key).is_some()
+                || self.witnessed.contains(&key)
+            {
+                true
+            } else {
+                let footprint = cmd.footprint();
+                if self.witnessed.conflicts(&footprint) {
+                    false
+                } else {
+                    self.witnessed.insert(key, footprint);
+                    true
+                }
+            };
+            pf_trace!(
+                "witness {} update {} of client {}, {} recorded",
+                if accepted { "accepted" } else { "rejected" },
+                req_id,
+                client,
+                self.witnessed.len()
+            );
+
+            if self.external_api.has_client(client) {
+                self.external_api.send_reply(
+                    ApiReply::WitnessAck {
+                        id: req_id,
+                        accepted,
+                    },
+                    client,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -30,7 +30,7 @@ impl From<Vec<usize>> for DepSet {
     }
 }
 
-// Allow indexing using u8 ReplicaId:
+// Allow indexing using u16 ReplicaId:
 impl ops::Index<ReplicaId> for DepSet {
     type Output = Option<usize>;
 
@@ -63,14 +63,14 @@ impl ops::IndexMut<usize> for DepSet {
 impl DepSet {
     /// Creates an empty dependency set given population.
     #[inline]
-    pub(super) fn empty(population: u8) -> Self {
+    pub(super) fn empty(population: u16) -> Self {
         DepSet(vec![None; population as usize])
     }
 
     /// Dummy wrapper of the `.len()` method of inner `Vec`.
     #[inline]
-    pub(super) fn len(&self) -> u8 {
-        self.0.len() as u8
+    pub(super) fn len(&self) -> u16 {
+        self.0.len() as u16
     }
 
     /// Dummy wrapper of the `.iter()` method of inner `Vec`.
@@ -108,32 +108,31 @@ impl EPaxosReplica {
     }
 
     /// Identifies the dependencies set of a request batch based my current
-    /// instance space state.
+    /// instance space state. A command depends on the latest writes to every
+    /// key in its conflict footprint.
     pub(super) fn identify_deps(
         reqs: &ReqBatch,
-        population: u8,
+        population: u16,
         highest_cols: &HashMap<String, DepSet>,
     ) -> DepSet {
         let mut deps = DepSet::empty(population);
         for (_, req) in reqs {
-            match req {
-                ApiRequest::Req {
-                    cmd: Command::Put { key, .. },
-                    ..
-                } => {
+            if let ApiRequest::Req { cmd, .. } = req {
+                let footprint = cmd.footprint();
+                for key in footprint.reads.iter().chain(&footprint.writes) {
                     if let Some(cols) = highest_cols.get(key) {
                         deps.union(cols);
                     }
                 }
-                ApiRequest::Req {
-                    cmd: Command::Get { key },
-                    ..
-                } => {
-                    if let Some(cols) = highest_cols.get(key) {
-                        deps.union(cols);
+                if !footprint.read_ranges.is_empty() {
+                    // a ranged read depends on the writes to every key in its
+                    // ranges; these are rare, so just go over all keys tracked
+                    for (key, cols) in highest_cols {
+                        if footprint.reads_key(key) {
+                            deps.union(cols);
+                        }
                     }
                 }
-                _ => {}
             }
         }
         deps
@@ -144,17 +143,16 @@ impl EPaxosReplica {
     pub(super) fn refresh_highest_cols(
         slot: SlotIdx,
         reqs: &ReqBatch,
-        population: u8,
+        population: u16,
         highest_cols: &mut HashMap<String, DepSet>,
     ) {
         let (row, col) = slot.unpack();
         for (_, req) in reqs {
-            if let ApiRequest::Req {
-                cmd: Command::Put { key, .. },
-                ..
-            } = req
-            {
-                if let Some(highest_cols) = highest_cols.get_mut(key) {
+            let ApiRequest::Req { cmd, .. } = req else {
+                continue;
+            };
+            for key in cmd.footprint().writes {
+                if let Some(highest_cols) = highest_cols.get_mut(&key) {
                     let highest_col = &mut highest_cols[row];
                     if let Some(hc) = highest_col {
                         *hc = (*hc).max(col);
@@ -164,7 +162,7 @@ impl EPaxosReplica {
                 } else {
                     let mut cols = DepSet::empty(population);
                     cols[row] = Some(col);
-                    highest_cols.insert(key.clone(), cols);
+                    highest_cols.insert(key, cols);
                 }
             }
         }
@@ -181,9 +179,9 @@ impl EPaxosReplica {
         avoid_fast_path: bool,
         leader_bk: &LeaderBookkeeping,
         hear_timers: &HashMap<ReplicaId, Timer>,
-        population: u8,
-        simple_quorum_cnt: u8,
-        super_quorum_cnt: u8,
+        population: u16,
+        simple_quorum_cnt: u16,
+        super_quorum_cnt: u16,
     ) -> Option<(Status, SeqNum, DepSet)> {
         debug_assert_ne!(simple_quorum_cnt, 0);
         debug_assert!(simple_quorum_cnt <= super_quorum_cnt);
@@ -211,7 +209,7 @@ impl EPaxosReplica {
                         && *r != me
                         && hear_timers[r].exploded()
                 })
-                .count() as u8;
+                .count() as u16;
             debug_assert!(all_cnt + bad_cnt <= population);
 
             // will consider fast path if eligible
@@ -252,8 +250,8 @@ impl EPaxosReplica {
     pub(super) fn exp_prepare_next_step(
         slot_row: ReplicaId,
         leader_bk: &LeaderBookkeeping,
-        population: u8,
-        simple_quorum_cnt: u8,
+        population: u16,
+        simple_quorum_cnt: u16,
     ) -> Option<(Status, SeqNum, DepSet, ReqBatch)> {
         debug_assert_ne!(simple_quorum_cnt, 0);
         debug_assert!(simple_quorum_cnt <= population);
@@ -335,8 +333,8 @@ impl EPaxosReplica {
     /// an element.
     fn get_enough_identical<T: Eq>(
         mut v: Vec<T>,
-        thresh: u8,
-    ) -> (Option<T>, u8) {
+        thresh: u16,
+    ) -> (Option<T>, u16) {
         debug_assert_ne!(thresh, 0);
 
         let mut first = 0;
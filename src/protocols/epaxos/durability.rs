@@ -182,7 +182,9 @@ impl EPaxosReplica {
             // then update self.wal_offset
             self.wal_offset = now_size;
         } else {
-            return logged_err!("unexpected log result type: {:?}", log_result);
+            return logged_err!(
+                Protocol; "unexpected log result type: {:?}", log_result
+            );
         }
 
         match entry_type {
@@ -190,7 +192,9 @@ impl EPaxosReplica {
             Status::Accepting => self.handle_logged_accept_slot(slot),
             Status::Committed => self.handle_logged_commit_slot(slot).await,
             _ => {
-                logged_err!("unexpected log entry type: {:?}", entry_type)
+                logged_err!(
+                    Protocol; "unexpected log entry type: {:?}", entry_type
+                )
             }
         }
     }
@@ -102,12 +102,15 @@ impl EPaxosReplica {
                     .iter()
                     .enumerate()
                 {
-                    if let ApiRequest::Req { cmd, .. } = req {
+                    if let ApiRequest::Req { cmd, checksum, .. } = req {
                         let cmd_id =
                             Self::make_command_id(dep_graph[n], cmd_idx);
                         if !sync_exec {
-                            self.state_machine
-                                .submit_cmd(cmd_id, cmd.clone())?;
+                            self.state_machine.submit_checked_cmd(
+                                cmd_id,
+                                cmd.clone(),
+                                *checksum,
+                            )?;
                         } else {
                             self.state_machine
                                 .do_sync_cmd(cmd_id, cmd.clone())
@@ -178,7 +181,7 @@ impl EPaxosReplica {
                 );
             }
         } else {
-            return logged_err!("unexpected API request type");
+            return logged_err!(Protocol; "unexpected API request type");
         }
 
         // if all commands in this instance have been executed, set status to
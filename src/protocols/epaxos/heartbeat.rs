@@ -201,7 +201,7 @@ impl EPaxosReplica {
                     .peer_exec_min
                     .values()
                     .filter(|&&e| e >= exec_min)
-                    .count() as u8;
+                    .count() as u16;
                 if passed_cnt == self.population {
                     // all servers have executed up to exec_min
                     self.snap_bar = exec_min;
@@ -27,9 +27,9 @@ use crate::client::{ClientApiStub, ClientCtrlStub, ClientId, GenericEndpoint};
 use crate::manager::{CtrlMsg, CtrlReply, CtrlRequest};
 use crate::protocols::SmrProtocol;
 use crate::server::{
-    ApiReply, ApiRequest, Command, CommandId, CommandResult, ControlHub,
-    ExternalApi, GenericReplica, HeartbeatEvent, Heartbeater, LogActionId,
-    ReplicaId, StateMachine, StorageHub, TransportHub,
+    ApiReply, ApiRequest, CommandId, CommandResult, ControlHub, ExternalApi,
+    GenericReplica, HeartbeatEvent, Heartbeater, LogActionId, ReplicaId,
+    StateMachine, StorageHub, TransportHub,
 };
 use crate::utils::{Bitmap, SummersetError};
 
@@ -51,6 +51,19 @@ pub struct ReplicaConfigEPaxos {
     /// Client request batching maximum batch size.
     pub max_batch_size: usize,
 
+    /// Number of completed replies cached per client for collapsing retried
+    /// requests by idempotency key; 0 means collapsing disabled.
+    pub idem_cache_size: usize,
+
+    /// Idle timeout in secs after which a client session that sent no request
+    /// is closed and its state reclaimed; 0 means never.
+    pub session_idle_s: u64,
+
+    /// Quota in KiB of approximate key-value bytes stored per namespace (the
+    /// key prefix before the first '/'), beyond which Puts are rejected; 0
+    /// means unlimited.
+    pub ns_quota_kb: usize,
+
     /// Path to backing log file.
     pub backer_path: String,
 
@@ -90,6 +103,9 @@ impl Default for ReplicaConfigEPaxos {
         ReplicaConfigEPaxos {
             batch_interval_ms: 1,
             max_batch_size: 5000,
+            idem_cache_size: 0,
+            session_idle_s: 0,
+            ns_quota_kb: 0,
             backer_path: "/tmp/summerset.epaxos.wal".into(),
             logger_sync: false,
             optimized_quorum: true,
@@ -271,9 +287,9 @@ enum WalEntry {
 
 /// Snapshot file entry type.
 //
-// NOTE: the current implementation simply appends a squashed log at the
-//       end of the snapshot file for simplicity. In production, the snapshot
-//       file should be a bounded-sized backend, e.g., an LSM-tree.
+// NOTE: the current implementation rewrites a whole image of the state
+//       machine into the snapshot file for simplicity. In production, the
+//       snapshot file should be a bounded-sized backend, e.g., an LSM-tree.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, GetSize)]
 enum SnapEntry {
     /// Necessary slot indices to remember.
@@ -284,8 +300,12 @@ enum SnapEntry {
         start_col: usize,
     },
 
-    /// Set of key-value pairs to apply to the state.
-    KVPairSet { pairs: HashMap<String, String> },
+    /// Image of the state machine taken by `take_snapshot()`, reflecting
+    /// exactly the commands of all instances before exec_bars in each row.
+    StateImage {
+        exec_bars: Vec<usize>,
+        image: Vec<u8>,
+    },
 }
 
 /// Peer-peer message type.
@@ -369,13 +389,13 @@ pub(crate) struct EPaxosReplica {
     id: ReplicaId,
 
     /// Total number of replicas in cluster.
-    population: u8,
+    population: u16,
 
     /// Simple majority quorum size.
-    simple_quorum_cnt: u8,
+    simple_quorum_cnt: u16,
 
     /// Super majority (fast) quorum size.
-    super_quorum_cnt: u8,
+    super_quorum_cnt: u16,
 
     /// Configuration parameters struct.
     config: ReplicaConfigEPaxos,
@@ -484,13 +504,13 @@ impl EPaxosReplica {
     /// Compose a unique ballot number from base.
     #[inline]
     fn make_unique_ballot(id: ReplicaId, base: u64) -> Ballot {
-        ((base << 8) | ((id + 1) as u64)) as Ballot
+        ((base << 16) | ((id + 1) as u64)) as Ballot
     }
 
     /// Compose a unique ballot number greater than the given one.
     #[inline]
     fn make_greater_ballot(id: ReplicaId, bal: Ballot) -> Ballot {
-        Self::make_unique_ballot(id, (bal >> 8) + 1)
+        Self::make_unique_ballot(id, (bal >> 16) + 1)
     }
 
     /// Returns the default ballot number for replica ID
@@ -560,16 +580,28 @@ impl GenericReplica for EPaxosReplica {
         // connect to the cluster manager and get assigned a server ID
         let mut control_hub = ControlHub::new_and_setup(manager).await?;
         let id = control_hub.me;
+        let (api_addr, p2p_addr) =
+            control_hub.resolve_addrs(api_addr, p2p_addr)?;
         let population = control_hub.population;
 
         // parse protocol-specific configs
         let config = parsed_config!(config_str => ReplicaConfigEPaxos;
                                     batch_interval_ms, max_batch_size,
+                                    idem_cache_size, session_idle_s,
+                                    ns_quota_kb,
                                     backer_path, logger_sync,
                                     optimized_quorum, hb_hear_timeout_min,
                                     hb_hear_timeout_max, hb_send_interval_ms,
                                     disable_hb_timer, snapshot_path,
                                     snapshot_interval_s, msg_chunk_size)?;
+        if population > (1 << 6) {
+            // row index takes 6 bits in log action & command IDs
+            return logged_err!(
+                "population {} too large for EPaxos, max is {}",
+                population,
+                1 << 6
+            );
+        }
         if config.batch_interval_ms == 0 {
             return logged_err!(
                 "invalid config.batch_interval_ms '{}'",
@@ -602,7 +634,9 @@ impl GenericReplica for EPaxosReplica {
         }
 
         // setup state machine module
-        let state_machine = StateMachine::new_and_setup(id).await?;
+        let state_machine =
+            StateMachine::new_and_setup(id, config.ns_quota_kb * 1024).await?;
+        control_hub.inspect_keys(&state_machine);
 
         // setup storage hub module
         let storage_hub =
@@ -623,7 +657,9 @@ impl GenericReplica for EPaxosReplica {
         let mut transport_hub = TransportHub::new_and_setup(
             id,
             population,
+            SmrProtocol::EPaxos,
             p2p_addr,
+            false,
             HashMap::new(), // no leases
         )
         .await?;
@@ -642,7 +678,7 @@ impl GenericReplica for EPaxosReplica {
         {
             to_peers
         } else {
-            return logged_err!("unexpected ctrl msg type received");
+            return logged_err!(Protocol; "unexpected ctrl msg type received");
         };
 
         // proactively connect to some peers, then wait for all population
@@ -658,13 +694,16 @@ impl GenericReplica for EPaxosReplica {
                 .await?;
 
         // setup external API module, ready to take in client requests
-        let external_api = ExternalApi::new_and_setup(
+        let mut external_api = ExternalApi::new_and_setup(
             id,
             api_addr,
             Duration::from_millis(config.batch_interval_ms),
             config.max_batch_size,
+            config.idem_cache_size,
+            Duration::from_secs(config.session_idle_s),
         )
         .await?;
+        external_api.stamp_replies(&state_machine);
 
         let mut snapshot_interval = time::interval(Duration::from_secs(
             if config.snapshot_interval_s > 0 {
@@ -843,7 +882,7 @@ impl GenericReplica for EPaxosReplica {
         self.id
     }
 
-    fn population(&self) -> u8 {
+    fn population(&self) -> u16 {
         self.population
     }
 }
@@ -868,7 +907,7 @@ pub(crate) struct EPaxosClient {
     id: ClientId,
 
     /// Number of servers in the cluster.
-    population: u8,
+    population: u16,
 
     /// Configuration parameters struct.
     config: ClientConfigEPaxos,
@@ -962,7 +1001,7 @@ impl GenericEndpoint for EPaxosClient {
                 }
                 Ok(())
             }
-            _ => logged_err!("unexpected reply type received"),
+            _ => logged_err!(Protocol; "unexpected reply type received"),
         }
     }
 
@@ -1053,7 +1092,7 @@ impl GenericEndpoint for EPaxosClient {
         self.id
     }
 
-    fn population(&self) -> u8 {
+    fn population(&self) -> u16 {
         self.population
     }
 
@@ -104,6 +104,11 @@ impl EPaxosReplica {
                     self.population,
                     &mut self.highest_cols,
                 );
+                if col < self.exec_bars[row] {
+                    // already reflected by the state image from snapshot
+                    inst.status = Status::Executed;
+                    return Ok(());
+                }
                 // submit commands in contiguously committed instance to the
                 // state machine
                 if col == self.commit_bars[row] {
@@ -186,7 +191,7 @@ impl EPaxosReplica {
                     break;
                 }
                 _ => {
-                    return logged_err!("unexpected log result type");
+                    return logged_err!(Protocol; "unexpected log result type");
                 }
             }
         }
@@ -214,7 +219,9 @@ impl EPaxosReplica {
             }
             Ok(())
         } else {
-            logged_err!("unexpected log result type or failed truncate")
+            logged_err!(
+                Protocol; "unexpected log result type or failed truncate"
+            )
         }
     }
 }
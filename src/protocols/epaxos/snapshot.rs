@@ -1,7 +1,6 @@
 //! EPaxos -- snapshotting & GC.
 
 use std::cmp;
-use std::collections::HashMap;
 
 use super::*;
 
@@ -11,34 +10,105 @@ use crate::utils::SummersetError;
 
 // EPaxosReplica snapshotting & GC logic
 impl EPaxosReplica {
-    /// Dump new key-value pairs to snapshot file.
-    // TODO: the current snapshot dumping mechanism is not fully functional as
-    //       we need to take care of the case when there's a dependency from
-    //       the left side of new_start_col to the right side of it.
-    async fn snapshot_dump_kv_pairs(
+    /// Dump an image of the state machine to the end of snapshot file.
+    /// Returns the exec_bars it is taken at: the image reflects exactly the
+    /// commands of all instances before them in each row. Returns `None`
+    /// without dumping if some instance beyond them has been executed
+    /// already, as such an image could not be told apart on recovery.
+    async fn snapshot_dump_state(
         &mut self,
-        _new_start_col: usize,
-    ) -> Result<(), SummersetError> {
-        // collect all key-value pairs put up to exec_bar
-        let pairs = HashMap::new(); // dummy for now
+    ) -> Result<Option<Vec<usize>>, SummersetError> {
+        // handle results of all commands submitted before taking the image,
+        // so that instance statuses then reflect exactly what it holds
+        let unacked = self.state_machine.num_unacked();
+        let image = self.state_machine.take_snapshot().await?;
+        for _ in 0..unacked {
+            let (cmd_id, cmd_result) = self.state_machine.get_result().await?;
+            self.handle_cmd_result(cmd_id, cmd_result).await?;
+        }
 
-        // write the collection to snapshot file
+        // instances are executed in dependency order rather than column
+        // order, so the executed ones may not make up a prefix of each row
+        for (row, insts) in self.insts.iter().enumerate() {
+            if insts[(self.exec_bars[row] - self.start_col)..].iter().any(
+                |inst| inst.status >= Status::Executed && !inst.reqs.is_empty(),
+            ) {
+                pf_debug!("executed instances beyond exec_bars, deferred");
+                return Ok(None);
+            }
+        }
+
+        // write the image to snapshot file
         if let LogResult::Append { now_size } = self
             .snapshot_hub
             .do_sync_action(
                 0, // using 0 as dummy log action ID
                 LogAction::Append {
-                    entry: SnapEntry::KVPairSet { pairs },
+                    entry: SnapEntry::StateImage {
+                        exec_bars: self.exec_bars.clone(),
+                        image: image.to_vec(),
+                    },
                     sync: self.config.logger_sync,
                 },
             )
             .await?
             .1
+        {
+            self.snap_offset = now_size;
+            Ok(Some(self.exec_bars.clone()))
+        } else {
+            logged_err!(Protocol; "unexpected log result type")
+        }
+    }
+
+    /// Discard the entries between the slot info head and given offset in
+    /// snapshot file, i.e., those of the previous image.
+    async fn snapshot_discard_old(
+        &mut self,
+        offset: usize,
+    ) -> Result<(), SummersetError> {
+        let head_len = match self
+            .snapshot_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Read { offset: 0 },
+            )
+            .await?
+            .1
+        {
+            LogResult::Read {
+                entry: Some(SnapEntry::SlotInfo { .. }),
+                end_offset,
+            } => end_offset,
+            _ => {
+                return logged_err!(Protocol; "unexpected log result type");
+            }
+        };
+        if offset <= head_len {
+            return Ok(()); // nothing to discard
+        }
+
+        if let LogResult::Discard {
+            offset_ok: true,
+            now_size,
+        } = self
+            .snapshot_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Discard {
+                    offset,
+                    keep: head_len,
+                },
+            )
+            .await?
+            .1
         {
             self.snap_offset = now_size;
             Ok(())
         } else {
-            logged_err!("unexpected log result type")
+            logged_err!(
+                Protocol; "unexpected log result type or failed discard"
+            )
         }
     }
 
@@ -89,7 +159,7 @@ impl EPaxosReplica {
                 self.wal_offset = now_size;
             } else {
                 return logged_err!(
-                    "unexpected log result type or failed discard"
+                    Protocol; "unexpected log result type or failed discard"
                 );
             }
         }
@@ -119,6 +189,10 @@ impl EPaxosReplica {
     //       which all other peers have snapshotted); we assume here that failed
     //       Accept messages will be retried indefinitely until success before
     //       its associated data gets discarded from peer's memory.
+    //
+    // TODO: the current log discarding mechanism is not fully functional as
+    //       we need to take care of the case when there's a dependency from
+    //       the left side of new_start_col to the right side of it.
     pub(super) async fn take_new_snapshot(
         &mut self,
     ) -> Result<(), SummersetError> {
@@ -131,18 +205,23 @@ impl EPaxosReplica {
         );
         debug_assert!(exec_min >= self.start_col);
 
-        let new_start_col = cmp::min(self.snap_bar, exec_min);
-        if new_start_col == self.start_col {
+        if cmp::min(self.snap_bar, exec_min) == self.start_col {
             return Ok(());
         }
 
         // NOTE: broadcast heartbeats here to appease peers
         self.bcast_heartbeats().await?;
 
-        // collect and dump all Puts in executed instances
-        self.snapshot_dump_kv_pairs(new_start_col).await?;
+        // dump an image of the state machine after the previous one
+        let old_offset = self.snap_offset;
+        let Some(snap_bars) = self.snapshot_dump_state().await? else {
+            return Ok(());
+        };
+        let new_start_col =
+            cmp::min(self.snap_bar, *snap_bars.iter().min().unwrap());
 
-        // write new slot info entry to the head of snapshot
+        // write new slot info entry to the head of snapshot, then discard the
+        // previous image
         match self
             .snapshot_hub
             .do_sync_action(
@@ -163,10 +242,11 @@ impl EPaxosReplica {
             } => {}
             _ => {
                 return logged_err!(
-                    "unexpected log result type or failed write"
+                    Protocol; "unexpected log result type or failed write"
                 );
             }
         }
+        self.snapshot_discard_old(old_offset).await?;
 
         // update start_slot and discard all in-memory log instances up to exec_bar
         for insts in &mut self.insts {
@@ -218,7 +298,7 @@ impl EPaxosReplica {
                 self.exec_bars = vec![start_col; self.population as usize];
                 self.snap_bar = start_col;
 
-                // repeatedly apply key-value pairs
+                // restore the state image recorded
                 loop {
                     match self
                         .snapshot_hub
@@ -232,18 +312,17 @@ impl EPaxosReplica {
                         .1
                     {
                         LogResult::Read {
-                            entry: Some(SnapEntry::KVPairSet { pairs }),
+                            entry:
+                                Some(SnapEntry::StateImage { exec_bars, image }),
                             end_offset,
                         } => {
-                            // execute Put commands on state machine
-                            for (key, value) in pairs {
-                                self.state_machine
-                                    .do_sync_cmd(
-                                        0, // using 0 as dummy command ID
-                                        Command::Put { key, value },
-                                    )
-                                    .await?;
-                            }
+                            self.state_machine
+                                .restore_snapshot(image.into())
+                                .await?;
+                            // instances before exec_bars must not be
+                            // executed again on top of the image
+                            self.commit_bars.clone_from(&exec_bars);
+                            self.exec_bars = exec_bars;
                             // update snapshot file offset
                             self.snap_offset = end_offset;
                         }
@@ -252,7 +331,9 @@ impl EPaxosReplica {
                             break;
                         }
                         _ => {
-                            return logged_err!("unexpected log result type");
+                            return logged_err!(
+                                Protocol; "unexpected log result type"
+                            );
                         }
                     }
                 }
@@ -294,12 +375,14 @@ impl EPaxosReplica {
                     self.snap_offset = now_size;
                     Ok(())
                 } else {
-                    logged_err!("unexpected log result type or failed write")
+                    logged_err!(
+                        Protocol; "unexpected log result type or failed write"
+                    )
                 }
             }
 
             _ => {
-                logged_err!("unexpected log result type")
+                logged_err!(Protocol; "unexpected log result type")
             }
         }
     }
@@ -0,0 +1,302 @@
+//! FastPaxos -- fast-round vote counting & collision recovery.
+
+use super::*;
+
+use crate::server::LogAction;
+use crate::utils::SummersetError;
+
+// FastPaxosReplica leader-side fast round & collision recovery logic
+impl FastPaxosReplica {
+    /// Tallies fast-round votes by value, in order of first appearance.
+    fn tally_votes(
+        fast_votes: &HashMap<ReplicaId, ReqBatch>,
+    ) -> Vec<(&ReqBatch, u16)> {
+        let mut tally: Vec<(&ReqBatch, u16)> = vec![];
+        for reqs in fast_votes.values() {
+            if let Some(entry) = tally.iter_mut().find(|(v, _)| *v == reqs) {
+                entry.1 += 1;
+            } else {
+                tally.push((reqs, 1));
+            }
+        }
+        tally
+    }
+
+    /// Marks the instance at slot as committed with the given value, records
+    /// the commit, and notifies all peers.
+    pub(super) fn commit_slot(
+        &mut self,
+        slot: usize,
+        ballot: Ballot,
+        reqs: ReqBatch,
+    ) -> Result<(), SummersetError> {
+        let inst = &mut self.insts[slot];
+        inst.bal = inst.bal.max(ballot);
+        inst.status = Status::Committed;
+        inst.reqs.clone_from(&reqs);
+        pf_debug!("committed instance at slot {} bal {}", slot, ballot);
+
+        // record commit event
+        self.storage_hub.submit_action(
+            Self::make_log_action_id(slot, Status::Committed, ballot),
+            LogAction::Append {
+                entry: WalEntry::CommitSlot {
+                    slot,
+                    ballot,
+                    reqs: reqs.clone(),
+                },
+                sync: self.config.logger_sync,
+            },
+        )?;
+        pf_trace!(
+            "submitted CommitSlot log action for slot {} bal {}",
+            slot,
+            ballot
+        );
+
+        // broadcast CommitNotice messages to all peers
+        self.transport_hub
+            .bcast_msg(PeerMsg::CommitNotice { slot, ballot, reqs }, None)?;
+        pf_trace!(
+            "broadcast CommitNotice messages for slot {} bal {}",
+            slot,
+            ballot
+        );
+        Ok(())
+    }
+
+    /// Checks the fast-round votes received for slot: commits it if a fast
+    /// quorum voted for the same value, or starts recovering it if that can
+    /// no longer happen (or if `timed_out`) and a classic quorum has voted.
+    fn check_fast_votes(
+        &mut self,
+        slot: usize,
+        timed_out: bool,
+    ) -> Result<(), SummersetError> {
+        let inst = &self.insts[slot];
+        if inst.status >= Status::Committed || inst.bal > FAST_BALLOT {
+            return Ok(());
+        }
+        let Some(leader_bk) = inst.leader_bk.as_ref() else {
+            return Ok(());
+        };
+
+        let num_votes = leader_bk.fast_votes.len() as u16;
+        let tally = Self::tally_votes(&leader_bk.fast_votes);
+        let (top_reqs, top_cnt) = tally
+            .iter()
+            .max_by_key(|(_, cnt)| *cnt)
+            .map(|(reqs, cnt)| ((*reqs).clone(), *cnt))
+            .unwrap_or_default();
+
+        if top_cnt >= self.fast_quorum_cnt {
+            // fast quorum formed, value chosen in the fast round
+            return self.commit_slot(slot, FAST_BALLOT, top_reqs);
+        }
+        if num_votes >= self.quorum_cnt
+            && (timed_out
+                || top_cnt + (self.population - num_votes)
+                    < self.fast_quorum_cnt)
+        {
+            pf_debug!(
+                "collision at slot {} with {} votes, top {}",
+                slot,
+                num_votes,
+                top_cnt
+            );
+            return self.start_recovery(slot);
+        }
+        Ok(())
+    }
+
+    /// Recovers the slot through a classic round at `RECOVERY_BALLOT`. As I
+    /// coordinate the fast round as well, the fast-round votes from a classic
+    /// quorum serve as the Prepare replies of the recovery round.
+    fn start_recovery(&mut self, slot: usize) -> Result<(), SummersetError> {
+        let (reqs, losers) = {
+            let leader_bk = self.insts[slot].leader_bk.as_ref().unwrap();
+            let num_votes = leader_bk.fast_votes.len() as u16;
+            let tally = Self::tally_votes(&leader_bk.fast_votes);
+
+            // a value voted by at least this many in the quorum might have
+            // been chosen in the fast round, and there can be at most one
+            // such value; pick it if any, otherwise the most voted one
+            let threshold = num_votes + self.fast_quorum_cnt - self.population;
+            let chosen = tally
+                .iter()
+                .find(|(_, cnt)| *cnt >= threshold)
+                .or_else(|| tally.iter().max_by_key(|(_, cnt)| *cnt))
+                .map(|(reqs, _)| (*reqs).clone())
+                .unwrap_or_default();
+
+            // requests voted for in this slot but not chosen have to be
+            // proposed again
+            let losers: ReqBatch = tally
+                .iter()
+                .filter(|(reqs, _)| **reqs != chosen)
+                .flat_map(|(reqs, _)| reqs.iter().cloned())
+                .collect();
+            (chosen, losers)
+        };
+
+        let inst = &mut self.insts[slot];
+        inst.bal = RECOVERY_BALLOT;
+        inst.status = Status::Accepting;
+        inst.reqs.clone_from(&reqs);
+        inst.durable = false;
+        if let Some(leader_bk) = inst.leader_bk.as_mut() {
+            leader_bk.accept_acks = Bitmap::new(self.population, false);
+        }
+
+        // record update to accepted ballot and corresponding data
+        self.storage_hub.submit_action(
+            Self::make_log_action_id(slot, Status::Accepting, RECOVERY_BALLOT),
+            LogAction::Append {
+                entry: WalEntry::AcceptSlot {
+                    slot,
+                    ballot: RECOVERY_BALLOT,
+                    reqs: reqs.clone(),
+                },
+                sync: self.config.logger_sync,
+            },
+        )?;
+        pf_trace!(
+            "submitted AcceptSlot log action for recovered slot {}",
+            slot
+        );
+
+        // Accept messages are broadcast once my own accept is durable, so
+        // that a value accepted by anyone in the recovery round survives my
+        // restart; the round's ballot is fixed and cannot be bumped past it
+
+        // re-propose the losing requests to all acceptors, including myself
+        if !losers.is_empty() {
+            self.transport_hub.bcast_msg(
+                PeerMsg::FastPropose {
+                    reqs: losers.clone(),
+                },
+                None,
+            )?;
+            pf_debug!(
+                "re-proposed {} requests lost at slot {}",
+                losers.len(),
+                slot
+            );
+            self.fast_vote_reqs(losers)?;
+        }
+        Ok(())
+    }
+
+    /// Broadcasts Accept messages of the recovery round in slot to all
+    /// peers.
+    pub(super) fn bcast_recovery_accept(
+        &mut self,
+        slot: usize,
+    ) -> Result<(), SummersetError> {
+        self.transport_hub.bcast_msg(
+            PeerMsg::Accept {
+                slot,
+                ballot: RECOVERY_BALLOT,
+                reqs: self.insts[slot].reqs.clone(),
+            },
+            None,
+        )?;
+        pf_trace!("broadcast Accept messages for recovered slot {}", slot);
+        Ok(())
+    }
+
+    /// Handler of FastVote message from an acceptor (or myself).
+    pub(super) fn handle_msg_fast_vote(
+        &mut self,
+        peer: ReplicaId,
+        slot: usize,
+        reqs: ReqBatch,
+    ) -> Result<(), SummersetError> {
+        if !self.is_leader() {
+            return Ok(());
+        }
+        pf_trace!("received FastVote <- {} for slot {}", peer, slot);
+
+        // locate instance in memory, filling in null instances if needed
+        self.fill_null_insts(slot);
+        let population = self.population;
+        let inst = &mut self.insts[slot];
+        if inst.status >= Status::Committed || inst.bal > FAST_BALLOT {
+            return Ok(()); // already decided or being recovered
+        }
+
+        // bookkeep this vote
+        inst.leader_bk
+            .get_or_insert_with(|| LeaderBookkeeping {
+                fast_votes: HashMap::new(),
+                since: Instant::now(),
+                accept_acks: Bitmap::new(population, false),
+            })
+            .fast_votes
+            .insert(peer, reqs);
+
+        self.check_fast_votes(slot, false)
+    }
+
+    /// Handler of Accept reply from an acceptor (or myself) in a recovery
+    /// round.
+    pub(super) fn handle_msg_accept_reply(
+        &mut self,
+        peer: ReplicaId,
+        slot: usize,
+        ballot: Ballot,
+    ) -> Result<(), SummersetError> {
+        if !self.is_leader() || slot >= self.insts.len() {
+            return Ok(());
+        }
+        pf_trace!(
+            "received AcceptReply <- {} for slot {} bal {}",
+            peer,
+            slot,
+            ballot
+        );
+
+        // ignore spurious duplications and outdated replies
+        let inst = &mut self.insts[slot];
+        if inst.status != Status::Accepting || ballot != inst.bal {
+            return Ok(());
+        }
+        let Some(leader_bk) = inst.leader_bk.as_mut() else {
+            return Ok(());
+        };
+        if leader_bk.accept_acks.get(peer)? {
+            return Ok(());
+        }
+
+        // bookkeep this Accept reply
+        leader_bk.accept_acks.set(peer, true)?;
+
+        // if quorum size reached, mark this instance as committed
+        if leader_bk.accept_acks.count() >= self.quorum_cnt {
+            let reqs = inst.reqs.clone();
+            self.commit_slot(slot, ballot, reqs)?;
+        }
+        Ok(())
+    }
+
+    /// Recovers slots that have been waiting in the fast round for longer
+    /// than the timeout, e.g., because some acceptor has not voted.
+    pub(super) fn recover_stuck_slots(&mut self) -> Result<(), SummersetError> {
+        let timeout = Duration::from_millis(self.config.fast_timeout_ms);
+        let stuck: Vec<usize> = (self.commit_bar..self.insts.len())
+            .filter(|&slot| {
+                let inst = &self.insts[slot];
+                inst.status < Status::Committed
+                    && inst.bal <= FAST_BALLOT
+                    && inst
+                        .leader_bk
+                        .as_ref()
+                        .is_some_and(|bk| bk.since.elapsed() >= timeout)
+            })
+            .collect();
+        for slot in stuck {
+            self.check_fast_votes(slot, true)?;
+        }
+        Ok(())
+    }
+}
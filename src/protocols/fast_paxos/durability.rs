@@ -0,0 +1,156 @@
+//! FastPaxos -- durable logging.
+
+use super::*;
+
+use crate::server::{LogActionId, LogResult};
+use crate::utils::SummersetError;
+
+// FastPaxosReplica durable WAL logging
+impl FastPaxosReplica {
+    /// Handler of AcceptSlot logging result chan recv.
+    fn handle_logged_accept_slot(
+        &mut self,
+        slot: usize,
+        fast: bool,
+    ) -> Result<(), SummersetError> {
+        let inst = &mut self.insts[slot];
+        pf_trace!(
+            "finished AcceptSlot logging for slot {} bal {}",
+            slot,
+            inst.bal
+        );
+        let ballot = if fast { FAST_BALLOT } else { RECOVERY_BALLOT };
+        if inst.status != Status::Accepting || inst.bal != ballot {
+            // superseded by a higher ballot or committed in the meantime
+            return Ok(());
+        }
+        inst.durable = true;
+        let reqs = inst.reqs.clone();
+
+        if fast {
+            // a durable fast-round vote goes to the leader
+            if self.is_leader() {
+                self.handle_msg_fast_vote(self.id, slot, reqs)?;
+            } else {
+                self.transport_hub.send_msg(
+                    PeerMsg::FastVote { slot, reqs },
+                    self.config.leader_id,
+                )?;
+                pf_trace!(
+                    "sent FastVote -> {} for slot {}",
+                    self.config.leader_id,
+                    slot
+                );
+            }
+        } else if self.is_leader() {
+            // on leader, my recovery-round accept is durable now, so send
+            // out the Accepts; finishing the logging of an AcceptSlot entry
+            // is equivalent to receiving an Accept reply from myself
+            self.bcast_recovery_accept(slot)?;
+            self.handle_msg_accept_reply(self.id, slot, ballot)?;
+        } else {
+            self.transport_hub.send_msg(
+                PeerMsg::AcceptReply { slot, ballot },
+                self.config.leader_id,
+            )?;
+            pf_trace!(
+                "sent AcceptReply -> {} for slot {} bal {}",
+                self.config.leader_id,
+                slot,
+                ballot
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Handler of CommitSlot logging result chan recv.
+    fn handle_logged_commit_slot(
+        &mut self,
+        slot: usize,
+    ) -> Result<(), SummersetError> {
+        pf_trace!(
+            "finished CommitSlot logging for slot {} bal {}",
+            slot,
+            self.insts[slot].bal
+        );
+
+        // update index of the first non-committed instance
+        if slot == self.commit_bar {
+            while self.commit_bar < self.insts.len() {
+                let inst = &mut self.insts[self.commit_bar];
+                if inst.status < Status::Committed {
+                    break;
+                }
+
+                // submit the command in committed instance to the state
+                // machine for execution, unless the same request has been
+                // committed in an earlier slot as well
+                let fresh = match inst.reqs.first() {
+                    Some((client, ApiRequest::Req { id, .. })) => {
+                        self.decided_reqs.insert((*client, *id))
+                    }
+                    _ => false,
+                };
+                if !fresh {
+                    inst.status = Status::Executed;
+                } else if let Some((_, ApiRequest::Req { cmd, checksum, .. })) =
+                    inst.reqs.first()
+                {
+                    self.state_machine.submit_checked_cmd(
+                        Self::make_command_id(self.commit_bar),
+                        cmd.clone(),
+                        *checksum,
+                    )?;
+                    pf_trace!(
+                        "submitted exec command for slot {}",
+                        self.commit_bar
+                    );
+                }
+
+                self.commit_bar += 1;
+            }
+
+            // duplicate slots right at the exec_bar count as executed now
+            self.advance_exec_bar();
+        }
+
+        Ok(())
+    }
+
+    /// Synthesized handler of durable logging result chan recv.
+    pub(super) async fn handle_log_result(
+        &mut self,
+        action_id: LogActionId,
+        log_result: LogResult<WalEntry>,
+    ) -> Result<(), SummersetError> {
+        let (slot, entry_type, fast) = Self::split_log_action_id(action_id);
+        debug_assert!(slot < self.insts.len());
+
+        if let LogResult::Append { now_size } = log_result {
+            debug_assert!(now_size >= self.wal_offset);
+            // update first wal_offset of slot
+            let inst = &mut self.insts[slot];
+            if inst.wal_offset == 0 || inst.wal_offset > self.wal_offset {
+                inst.wal_offset = self.wal_offset;
+            }
+            debug_assert!(inst.wal_offset <= self.wal_offset);
+            // then update self.wal_offset
+            self.wal_offset = now_size;
+        } else {
+            return logged_err!(
+                Protocol; "unexpected log result type: {:?}", log_result
+            );
+        }
+
+        match entry_type {
+            Status::Accepting => self.handle_logged_accept_slot(slot, fast),
+            Status::Committed => self.handle_logged_commit_slot(slot),
+            _ => {
+                logged_err!(
+                    Protocol; "unexpected log entry type: {:?}", entry_type
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! FastPaxos -- command execution.
+
+use super::*;
+
+use crate::server::{ApiReply, ApiRequest};
+use crate::utils::SummersetError;
+
+// FastPaxosReplica state machine execution
+impl FastPaxosReplica {
+    /// Moves `exec_bar` past all contiguously executed instances.
+    pub(super) fn advance_exec_bar(&mut self) {
+        while self.exec_bar < self.commit_bar {
+            if self.insts[self.exec_bar].status < Status::Executed {
+                break;
+            }
+            self.exec_bar += 1;
+        }
+    }
+
+    /// Handler of state machine exec result chan recv.
+    pub(super) async fn handle_cmd_result(
+        &mut self,
+        cmd_id: CommandId,
+        cmd_result: CommandResult,
+    ) -> Result<(), SummersetError> {
+        let slot = Self::split_command_id(cmd_id);
+        debug_assert!(slot < self.insts.len());
+        pf_trace!("executed cmd in instance at slot {}", slot);
+
+        let is_leader = self.is_leader();
+        let inst = &mut self.insts[slot];
+        debug_assert_eq!(inst.reqs.len(), 1);
+        let (client, ref req) = inst.reqs[0];
+
+        // the leader replies command result back to client
+        if let ApiRequest::Req { id: req_id, .. } = req {
+            if is_leader && self.external_api.has_client(client) {
+                self.external_api.send_reply(
+                    ApiReply::normal(*req_id, Some(cmd_result)),
+                    client,
+                )?;
+                pf_trace!("replied -> client {} for slot {}", client, slot);
+            }
+        } else {
+            return logged_err!(Protocol; "unexpected API request type");
+        }
+
+        inst.status = Status::Executed;
+        pf_debug!("executed instance at slot {}", slot);
+        self.advance_exec_bar();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+//! FastPaxos -- peer-peer messaging.
+
+use super::*;
+
+use crate::server::LogAction;
+use crate::utils::SummersetError;
+
+// FastPaxosReplica peer-peer messages handling
+impl FastPaxosReplica {
+    /// Handler of Accept message from leader in a recovery round.
+    fn handle_msg_accept(
+        &mut self,
+        peer: ReplicaId,
+        slot: usize,
+        ballot: Ballot,
+        reqs: ReqBatch,
+    ) -> Result<(), SummersetError> {
+        pf_trace!(
+            "received Accept <- {} for slot {} bal {}",
+            peer,
+            slot,
+            ballot
+        );
+
+        // locate instance in memory, filling in null instances if needed
+        self.fill_null_insts(slot);
+        let inst = &mut self.insts[slot];
+
+        // if ballot is not smaller than what I have accepted in:
+        if ballot >= inst.bal && inst.status < Status::Committed {
+            inst.bal = ballot;
+            inst.status = Status::Accepting;
+            inst.reqs.clone_from(&reqs);
+            inst.durable = false;
+
+            // record update to accepted ballot and corresponding data
+            self.storage_hub.submit_action(
+                Self::make_log_action_id(slot, Status::Accepting, ballot),
+                LogAction::Append {
+                    entry: WalEntry::AcceptSlot { slot, ballot, reqs },
+                    sync: self.config.logger_sync,
+                },
+            )?;
+            pf_trace!(
+                "submitted AcceptSlot log action for slot {} bal {}",
+                slot,
+                ballot
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Handler of CommitNotice message from leader.
+    fn handle_msg_commit_notice(
+        &mut self,
+        peer: ReplicaId,
+        slot: usize,
+        ballot: Ballot,
+        reqs: ReqBatch,
+    ) -> Result<(), SummersetError> {
+        pf_trace!(
+            "received CommitNotice <- {} for slot {} bal {}",
+            peer,
+            slot,
+            ballot
+        );
+
+        // locate instance in memory, filling in null instances if needed
+        self.fill_null_insts(slot);
+        let inst = &mut self.insts[slot];
+
+        // the value chosen in a slot is unique, so take it in regardless of
+        // what I have voted for
+        if inst.status < Status::Committed {
+            inst.bal = inst.bal.max(ballot);
+            inst.status = Status::Committed;
+            inst.reqs.clone_from(&reqs);
+
+            // record commit event
+            self.storage_hub.submit_action(
+                Self::make_log_action_id(slot, Status::Committed, ballot),
+                LogAction::Append {
+                    entry: WalEntry::CommitSlot { slot, ballot, reqs },
+                    sync: self.config.logger_sync,
+                },
+            )?;
+            pf_trace!(
+                "submitted CommitSlot log action for slot {} bal {}",
+                slot,
+                ballot
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Handler of VoteQuery message from a restarted leader. Sends again
+    /// what I hold in each slot at or after `from_slot`: the chosen value if
+    /// committed, otherwise my durable fast-round vote. Votes not durable yet
+    /// go out once logged as usual, and recovery-round accepts are collected
+    /// by the leader sending its Accepts again.
+    fn handle_msg_vote_query(
+        &mut self,
+        peer: ReplicaId,
+        from_slot: usize,
+    ) -> Result<(), SummersetError> {
+        pf_debug!("received VoteQuery <- {} from slot {}", peer, from_slot);
+
+        for slot in from_slot..self.insts.len() {
+            let inst = &self.insts[slot];
+            let msg = if inst.status >= Status::Committed {
+                PeerMsg::CommitNotice {
+                    slot,
+                    ballot: inst.bal,
+                    reqs: inst.reqs.clone(),
+                }
+            } else if inst.status == Status::Accepting
+                && inst.bal == FAST_BALLOT
+                && inst.durable
+            {
+                PeerMsg::FastVote {
+                    slot,
+                    reqs: inst.reqs.clone(),
+                }
+            } else {
+                continue;
+            };
+            self.transport_hub.send_msg(msg, peer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Synthesized handler of receiving message from peer.
+    pub(super) async fn handle_msg_recv(
+        &mut self,
+        peer: ReplicaId,
+        msg: PeerMsg,
+    ) -> Result<(), SummersetError> {
+        match msg {
+            PeerMsg::FastPropose { reqs } => {
+                pf_trace!(
+                    "received FastPropose <- {} of size {}",
+                    peer,
+                    reqs.len()
+                );
+                self.fast_vote_reqs(reqs)
+            }
+            PeerMsg::FastVote { slot, reqs } => {
+                self.handle_msg_fast_vote(peer, slot, reqs)
+            }
+            PeerMsg::Accept { slot, ballot, reqs } => {
+                self.handle_msg_accept(peer, slot, ballot, reqs)
+            }
+            PeerMsg::AcceptReply { slot, ballot } => {
+                self.handle_msg_accept_reply(peer, slot, ballot)
+            }
+            PeerMsg::CommitNotice { slot, ballot, reqs } => {
+                self.handle_msg_commit_notice(peer, slot, ballot, reqs)
+            }
+            PeerMsg::VoteQuery { from_slot } => {
+                self.handle_msg_vote_query(peer, from_slot)
+            }
+        }
+    }
+}
@@ -0,0 +1,846 @@
+//! Replication protocol: Fast Paxos.
+//!
+//! Clients broadcast their requests straight to all acceptors, which vote
+//! for each request in their next free slot in a fast round. A slot commits
+//! in one round trip from the client when a fast quorum of acceptors votes
+//! for the same request in it. When concurrent requests land in a slot in
+//! different orders (a collision), the leader recovers the slot through a
+//! classic round, using the fast-round votes as its Prepare replies, and
+//! re-proposes the requests that lost. References:
+//!   - <https://www.microsoft.com/en-us/research/publication/fast-paxos/>
+//!
+//! The leader is statically configured and not replaced upon failure. As
+//! fast-round tallies only live in the leader's memory, a restarted leader
+//! queries acceptors for what they hold in slots not yet committed, and they
+//! send their votes, accepts, and commits there again.
+
+mod collision;
+mod control;
+mod durability;
+mod execution;
+mod messages;
+mod recovery;
+mod request;
+
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::path::Path;
+
+use crate::client::{ClientApiStub, ClientCtrlStub, ClientId, GenericEndpoint};
+use crate::manager::{CtrlMsg, CtrlReply, CtrlRequest};
+use crate::protocols::SmrProtocol;
+use crate::server::{
+    ApiReply, ApiRequest, CommandId, CommandResult, ControlHub, ExternalApi,
+    GenericReplica, LogActionId, ReplicaId, RequestId, StateMachine,
+    StorageHub, TransportHub,
+};
+use crate::utils::{Bitmap, SummersetError};
+
+use async_trait::async_trait;
+
+use get_size::GetSize;
+
+use serde::{Deserialize, Serialize};
+
+use tokio::sync::watch;
+use tokio::time::{self, Duration, Instant, Interval, MissedTickBehavior};
+
+/// Configuration parameters struct.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReplicaConfigFastPaxos {
+    /// Client request batching interval in millisecs.
+    pub batch_interval_ms: u64,
+
+    /// Client request batching maximum batch size.
+    pub max_batch_size: usize,
+
+    /// Number of completed replies cached per client for collapsing retried
+    /// requests by idempotency key; 0 means collapsing disabled.
+    pub idem_cache_size: usize,
+
+    /// Idle timeout in secs after which a client session that sent no request
+    /// is closed and its state reclaimed; 0 means never.
+    pub session_idle_s: u64,
+
+    /// Quota in KiB of approximate key-value bytes stored per namespace (the
+    /// key prefix before the first '/'), beyond which Puts are rejected; 0
+    /// means unlimited.
+    pub ns_quota_kb: usize,
+
+    /// Path to backing log file.
+    pub backer_path: String,
+
+    /// Whether to call `fsync()`/`fdatasync()` on logger.
+    pub logger_sync: bool,
+
+    /// ID of the leader that coordinates collision recovery and replies to
+    /// clients.
+    pub leader_id: ReplicaId,
+
+    /// Timeout in millisecs after which the leader recovers a slot that has
+    /// votes from a classic quorum but no fast quorum yet, e.g., because
+    /// some acceptor is down.
+    pub fast_timeout_ms: u64,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for ReplicaConfigFastPaxos {
+    fn default() -> Self {
+        ReplicaConfigFastPaxos {
+            batch_interval_ms: 1,
+            max_batch_size: 5000,
+            idem_cache_size: 0,
+            session_idle_s: 0,
+            ns_quota_kb: 0,
+            backer_path: "/tmp/summerset.fast_paxos.wal".into(),
+            logger_sync: false,
+            leader_id: 0,
+            fast_timeout_ms: 100,
+        }
+    }
+}
+
+/// Ballot number type. Use 0 as a null ballot number.
+type Ballot = u64;
+
+/// Ballot of the fast round that every slot starts in, where acceptors vote
+/// for requests coming straight from clients.
+const FAST_BALLOT: Ballot = 1;
+
+/// Ballot of the classic round the leader runs in a slot to recover it from
+/// a collision.
+const RECOVERY_BALLOT: Ballot = 2;
+
+/// Instance status enum.
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Clone,
+    Copy,
+    Serialize,
+    Deserialize,
+    GetSize,
+)]
+enum Status {
+    Null = 0,
+    Accepting = 1,
+    Committed = 2,
+    Executed = 3,
+}
+
+/// Request batch type (i.e., the "value" in Paxos). Votes in the fast round
+/// are cast per request, so a batch in a slot holds a single request.
+type ReqBatch = Vec<(ClientId, ApiRequest)>;
+
+/// Leader-side bookkeeping info for each instance.
+#[derive(Debug, Clone)]
+struct LeaderBookkeeping {
+    /// Fast-round votes received so far from each acceptor.
+    fast_votes: HashMap<ReplicaId, ReqBatch>,
+
+    /// When the first fast-round vote was received.
+    since: Instant,
+
+    /// Replicas from which I have received Accept confirmations in the
+    /// recovery round.
+    accept_acks: Bitmap,
+}
+
+/// In-memory instance containing a commands batch.
+#[derive(Debug, Clone)]
+struct Instance {
+    /// Highest ballot number accepted in.
+    bal: Ballot,
+
+    /// Instance status.
+    status: Status,
+
+    /// Batch of client requests.
+    reqs: ReqBatch,
+
+    /// Whether my vote or accept at `bal` has been durably logged.
+    durable: bool,
+
+    /// Leader-side bookkeeping info.
+    leader_bk: Option<LeaderBookkeeping>,
+
+    /// Offset of first durable WAL log entry related to this instance.
+    wal_offset: usize,
+}
+
+/// Stable storage WAL log entry type.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, GetSize)]
+#[allow(clippy::enum_variant_names)]
+enum WalEntry {
+    /// Records a vote in the fast round or an accept in the recovery round.
+    AcceptSlot {
+        slot: usize,
+        ballot: Ballot,
+        reqs: ReqBatch,
+    },
+
+    /// Records an event of committing the instance at index.
+    CommitSlot {
+        slot: usize,
+        ballot: Ballot,
+        reqs: ReqBatch,
+    },
+}
+
+/// Peer-peer message type.
+#[derive(Debug, Clone, Serialize, Deserialize, GetSize)]
+enum PeerMsg {
+    /// Requests that lost a collision, re-proposed by the leader to all
+    /// acceptors to be voted for again as if coming from clients.
+    FastPropose { reqs: ReqBatch },
+
+    /// Fast-round vote from acceptor to leader.
+    FastVote { slot: usize, reqs: ReqBatch },
+
+    /// Accept message of the recovery round from leader to acceptors.
+    Accept {
+        slot: usize,
+        ballot: Ballot,
+        reqs: ReqBatch,
+    },
+
+    /// Accept reply from acceptor to leader.
+    AcceptReply { slot: usize, ballot: Ballot },
+
+    /// Query from a restarted leader to acceptors for what they hold in
+    /// slots at or after `from_slot`, to rebuild its vote tallies.
+    VoteQuery { from_slot: usize },
+
+    /// Notification of commit from leader to acceptors.
+    // NOTE: think of these async CommitNotices as being indefinitely
+    //       retransmitted until all peers learn about them
+    CommitNotice {
+        slot: usize,
+        ballot: Ballot,
+        reqs: ReqBatch,
+    },
+}
+
+/// FastPaxos server replica module.
+pub(crate) struct FastPaxosReplica {
+    /// Replica ID in cluster.
+    id: ReplicaId,
+
+    /// Total number of replicas in cluster.
+    population: u16,
+
+    /// Majority (classic) quorum size.
+    quorum_cnt: u16,
+
+    /// Fast quorum size, such that any two fast quorums and one classic
+    /// quorum intersect.
+    fast_quorum_cnt: u16,
+
+    /// Configuration parameters struct.
+    config: ReplicaConfigFastPaxos,
+
+    /// Address string for client requests API.
+    _api_addr: SocketAddr,
+
+    /// Address string for internal peer-peer communication.
+    _p2p_addr: SocketAddr,
+
+    /// ControlHub module.
+    control_hub: ControlHub,
+
+    /// ExternalApi module.
+    external_api: ExternalApi,
+
+    /// StateMachine module.
+    state_machine: StateMachine,
+
+    /// StorageHub module.
+    storage_hub: StorageHub<WalEntry>,
+
+    /// TransportHub module.
+    transport_hub: TransportHub<PeerMsg>,
+
+    /// In-memory log of instances.
+    insts: Vec<Instance>,
+
+    /// Slot index from which to look for the next slot to vote in.
+    fast_bar: usize,
+
+    /// Slot index of the first non-committed instance.
+    commit_bar: usize,
+
+    /// Slot index of the first non-executed instance.
+    /// It is always true that
+    ///   exec_bar <= commit_bar <= insts.len()
+    exec_bar: usize,
+
+    /// Set of (client ID, request ID) of requests submitted for execution,
+    /// as a request may get committed in more than one slot.
+    // NOTE: may add garbage collection by per-client request ID watermarks
+    decided_reqs: HashSet<(ClientId, RequestId)>,
+
+    /// Timer for checking slots stuck in the fast round.
+    fast_check_interval: Interval,
+
+    /// Current durable WAL log file offset.
+    wal_offset: usize,
+}
+
+// FastPaxosReplica common helpers
+impl FastPaxosReplica {
+    /// Am I the leader?
+    #[inline]
+    fn is_leader(&self) -> bool {
+        self.id == self.config.leader_id
+    }
+
+    /// Create an empty null instance.
+    #[inline]
+    fn null_instance() -> Instance {
+        Instance {
+            bal: 0,
+            status: Status::Null,
+            reqs: ReqBatch::new(),
+            durable: false,
+            leader_bk: None,
+            wal_offset: 0,
+        }
+    }
+
+    /// Fill in null instances so that the in-mem log covers the given slot.
+    fn fill_null_insts(&mut self, slot: usize) {
+        while self.insts.len() <= slot {
+            self.insts.push(Self::null_instance());
+        }
+    }
+
+    /// Locate the next slot I have not voted or accepted anything in yet,
+    /// and move `fast_bar` past it.
+    fn next_fast_slot(&mut self) -> usize {
+        let mut slot = self.fast_bar;
+        loop {
+            self.fill_null_insts(slot);
+            if self.insts[slot].bal == 0 {
+                break;
+            }
+            slot += 1;
+        }
+        self.fast_bar = slot + 1;
+        slot
+    }
+
+    /// Compose LogActionId from slot index & entry type, where accepts are
+    /// told apart by ballot.
+    #[inline]
+    fn make_log_action_id(
+        slot: usize,
+        entry_type: Status,
+        ballot: Ballot,
+    ) -> LogActionId {
+        let type_num = match (entry_type, ballot) {
+            (Status::Accepting, FAST_BALLOT) => 1,
+            (Status::Accepting, _) => 2,
+            (Status::Committed, _) => 3,
+            _ => panic!("unknown log entry type {:?}", entry_type),
+        };
+        ((slot << 2) | type_num) as LogActionId
+    }
+
+    /// Decompose LogActionId into slot index, entry type & whether it is a
+    /// fast-round vote.
+    #[inline]
+    fn split_log_action_id(
+        log_action_id: LogActionId,
+    ) -> (usize, Status, bool) {
+        let slot = (log_action_id >> 2) as usize;
+        let type_num = log_action_id & ((1 << 2) - 1);
+        match type_num {
+            1 => (slot, Status::Accepting, true),
+            2 => (slot, Status::Accepting, false),
+            3 => (slot, Status::Committed, false),
+            _ => panic!("unknown log entry type num {}", type_num),
+        }
+    }
+
+    /// Compose CommandId from slot index.
+    #[inline]
+    fn make_command_id(slot: usize) -> CommandId {
+        slot as CommandId
+    }
+
+    /// Decompose CommandId into slot index.
+    #[inline]
+    fn split_command_id(command_id: CommandId) -> usize {
+        command_id as usize
+    }
+}
+
+#[async_trait]
+impl GenericReplica for FastPaxosReplica {
+    async fn new_and_setup(
+        api_addr: SocketAddr,
+        p2p_addr: SocketAddr,
+        manager: SocketAddr,
+        config_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        // connect to the cluster manager and get assigned a server ID
+        let mut control_hub = ControlHub::new_and_setup(manager).await?;
+        let id = control_hub.me;
+        let (api_addr, p2p_addr) =
+            control_hub.resolve_addrs(api_addr, p2p_addr)?;
+        let population = control_hub.population;
+
+        // parse protocol-specific configs
+        let config = parsed_config!(config_str => ReplicaConfigFastPaxos;
+                                    batch_interval_ms, max_batch_size,
+                                    idem_cache_size, session_idle_s,
+                                    ns_quota_kb,
+                                    backer_path, logger_sync,
+                                    leader_id, fast_timeout_ms)?;
+        if config.batch_interval_ms == 0 {
+            return logged_err!(
+                "invalid config.batch_interval_ms '{}'",
+                config.batch_interval_ms
+            );
+        }
+        if config.leader_id >= population {
+            return logged_err!(
+                "invalid config.leader_id '{}'",
+                config.leader_id
+            );
+        }
+        if config.fast_timeout_ms == 0 {
+            return logged_err!(
+                "invalid config.fast_timeout_ms '{}'",
+                config.fast_timeout_ms
+            );
+        }
+
+        // setup state machine module
+        let state_machine =
+            StateMachine::new_and_setup(id, config.ns_quota_kb * 1024).await?;
+        control_hub.inspect_keys(&state_machine);
+
+        // setup storage hub module
+        let storage_hub =
+            StorageHub::new_and_setup(id, Path::new(&config.backer_path))
+                .await?;
+
+        // setup transport hub module
+        let mut transport_hub = TransportHub::new_and_setup(
+            id,
+            population,
+            SmrProtocol::FastPaxos,
+            p2p_addr,
+            false,
+            HashMap::new(), // no leases
+        )
+        .await?;
+
+        // ask for the list of peers to proactively connect to. Do this after
+        // transport hub has been set up, so that I will be able to accept
+        // later peer connections
+        control_hub.send_ctrl(CtrlMsg::NewServerJoin {
+            id,
+            protocol: SmrProtocol::FastPaxos,
+            api_addr,
+            p2p_addr,
+        })?;
+        let to_peers = if let CtrlMsg::ConnectToPeers { to_peers, .. } =
+            control_hub.recv_ctrl().await?
+        {
+            to_peers
+        } else {
+            return logged_err!(Protocol; "unexpected ctrl msg type received");
+        };
+
+        // proactively connect to some peers, then wait for all population
+        // have been connected with me
+        for (peer, conn_addr) in to_peers {
+            transport_hub.connect_to_peer(peer, conn_addr).await?;
+        }
+        transport_hub.wait_for_group(population).await?;
+
+        // setup external API module, ready to take in client requests
+        let mut external_api = ExternalApi::new_and_setup(
+            id,
+            api_addr,
+            Duration::from_millis(config.batch_interval_ms),
+            config.max_batch_size,
+            config.idem_cache_size,
+            Duration::from_secs(config.session_idle_s),
+        )
+        .await?;
+        external_api.stamp_replies(&state_machine);
+
+        let mut fast_check_interval =
+            time::interval(Duration::from_millis(config.fast_timeout_ms));
+        fast_check_interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        // fast quorums must be large enough that any two of them and any
+        // classic quorum have a common member
+        let quorum_cnt = (population / 2) + 1;
+        let fast_quorum_cnt = (2 * population - quorum_cnt) / 2 + 1;
+
+        Ok(FastPaxosReplica {
+            id,
+            population,
+            quorum_cnt,
+            fast_quorum_cnt,
+            config,
+            _api_addr: api_addr,
+            _p2p_addr: p2p_addr,
+            control_hub,
+            external_api,
+            state_machine,
+            storage_hub,
+            transport_hub,
+            insts: vec![],
+            fast_bar: 0,
+            commit_bar: 0,
+            exec_bar: 0,
+            decided_reqs: HashSet::new(),
+            fast_check_interval,
+            wal_offset: 0,
+        })
+    }
+
+    async fn run(
+        &mut self,
+        mut rx_term: watch::Receiver<bool>,
+    ) -> Result<bool, SummersetError> {
+        // recover the log & state from durable WAL log
+        self.recover_from_wal().await?;
+
+        // if leader, rebuild the bookkeeping of slots not yet committed
+        if self.is_leader() {
+            self.resume_as_leader()?;
+        }
+
+        // main event loop
+        let mut paused = false;
+        loop {
+            tokio::select! {
+                // client request batch
+                req_batch = self.external_api.get_req_batch(), if !paused => {
+                    if let Err(e) = req_batch {
+                        pf_error!("error getting req batch: {}", e);
+                        continue;
+                    }
+                    let req_batch = req_batch.unwrap();
+                    if let Err(e) = self.handle_req_batch(req_batch).await {
+                        pf_error!("error handling req batch: {}", e);
+                    }
+                },
+
+                // durable logging result
+                log_result = self.storage_hub.get_result(), if !paused => {
+                    if let Err(e) = log_result {
+                        pf_error!("error getting log result: {}", e);
+                        continue;
+                    }
+                    let (action_id, log_result) = log_result.unwrap();
+                    if let Err(e) = self.handle_log_result(action_id, log_result).await {
+                        pf_error!("error handling log result {}: {}",
+                                           action_id, e);
+                    }
+                },
+
+                // message from peer
+                msg = self.transport_hub.recv_msg(), if !paused => {
+                    if let Err(_e) = msg {
+                        // NOTE: commented out to prevent console lags
+                        //       during benchmarking
+                        // pf_error!("error receiving peer msg: {}", e);
+                        continue;
+                    }
+                    let (peer, msg) = msg.unwrap();
+                    if let Err(e) = self.handle_msg_recv(peer, msg).await {
+                        pf_error!("error handling msg recv <- {}: {}", peer, e);
+                    }
+                },
+
+                // state machine execution result
+                cmd_result = self.state_machine.get_result(), if !paused => {
+                    if let Err(e) = cmd_result {
+                        pf_error!("error getting cmd result: {}", e);
+                        continue;
+                    }
+                    let (cmd_id, cmd_result) = cmd_result.unwrap();
+                    if let Err(e) = self.handle_cmd_result(cmd_id, cmd_result).await {
+                        pf_error!("error handling cmd result {}: {}", cmd_id, e);
+                    }
+                },
+
+                // leader checking for slots stuck in the fast round
+                _ = self.fast_check_interval.tick(), if !paused && self.is_leader() => {
+                    if let Err(e) = self.recover_stuck_slots() {
+                        pf_error!("error recovering stuck slots: {}", e);
+                    }
+                },
+
+                // manager control message
+                ctrl_msg = self.control_hub.recv_ctrl() => {
+                    if let Err(e) = ctrl_msg {
+                        pf_error!("error getting ctrl msg: {}", e);
+                        continue;
+                    }
+                    let ctrl_msg = ctrl_msg.unwrap();
+                    match self.handle_ctrl_msg(ctrl_msg, &mut paused).await {
+                        Ok(terminate) => {
+                            if let Some(restart) = terminate {
+                                return Ok(restart);
+                            }
+                        },
+                        Err(e) => {
+                            pf_error!("error handling ctrl msg: {}", e);
+                        }
+                    }
+                },
+
+                // receiving termination signal
+                _ = rx_term.changed() => {
+                    pf_warn!("server caught termination signal");
+                    return Ok(false);
+                }
+            }
+        }
+    }
+
+    fn id(&self) -> ReplicaId {
+        self.id
+    }
+
+    fn population(&self) -> u16 {
+        self.population
+    }
+}
+
+/// Configuration parameters struct.
+#[derive(Debug, Deserialize)]
+pub struct ClientConfigFastPaxos {
+    /// ID of the leader, which replies to requests.
+    pub leader_id: ReplicaId,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for ClientConfigFastPaxos {
+    fn default() -> Self {
+        ClientConfigFastPaxos { leader_id: 0 }
+    }
+}
+
+/// FastPaxos client-side module. Knows the addresses of all replicas and
+/// broadcasts requests carrying commands to all of them.
+pub(crate) struct FastPaxosClient {
+    /// Client ID.
+    id: ClientId,
+
+    /// Number of servers in the cluster.
+    population: u16,
+
+    /// Configuration parameters struct.
+    config: ClientConfigFastPaxos,
+
+    /// List of active servers information.
+    servers: HashMap<ReplicaId, SocketAddr>,
+
+    /// Control API stub to the cluster manager.
+    ctrl_stub: ClientCtrlStub,
+
+    /// API stubs for communicating with servers.
+    api_stubs: HashMap<ReplicaId, ClientApiStub>,
+
+    /// Servers to which the last request has not been fully sent yet.
+    unsent: Vec<ReplicaId>,
+}
+
+impl FastPaxosClient {
+    /// Should the request be broadcast to all acceptors, i.e., does it carry
+    /// commands to be voted for? Other requests go to the leader only.
+    #[inline]
+    fn is_proposal(req: &ApiRequest) -> bool {
+        matches!(
+            req.unfenced(),
+            ApiRequest::Req { .. }
+                | ApiRequest::IdemReq { .. }
+                | ApiRequest::AckedReq { .. }
+                | ApiRequest::Batch { .. }
+        )
+    }
+}
+
+#[async_trait]
+impl GenericEndpoint for FastPaxosClient {
+    async fn new_and_setup(
+        manager: SocketAddr,
+        config_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        // connect to the cluster manager and get assigned a client ID
+        pf_debug!("connecting to manager '{}'...", manager);
+        let ctrl_stub = ClientCtrlStub::new_by_connect(manager).await?;
+        let id = ctrl_stub.id;
+
+        // parse protocol-specific configs
+        let config = parsed_config!(config_str => ClientConfigFastPaxos;
+                                    leader_id)?;
+
+        Ok(FastPaxosClient {
+            id,
+            population: 0,
+            config,
+            servers: HashMap::new(),
+            ctrl_stub,
+            api_stubs: HashMap::new(),
+            unsent: vec![],
+        })
+    }
+
+    async fn connect(&mut self) -> Result<(), SummersetError> {
+        // disallow reconnection without leaving
+        if !self.api_stubs.is_empty() {
+            return logged_err!("reconnecting without leaving");
+        }
+
+        // ask the manager about the list of active servers
+        let mut sent =
+            self.ctrl_stub.send_req(Some(&CtrlRequest::QueryInfo))?;
+        while !sent {
+            sent = self.ctrl_stub.send_req(None)?;
+        }
+
+        let reply = self.ctrl_stub.recv_reply().await?;
+        match reply {
+            CtrlReply::QueryInfo {
+                population,
+                servers_info,
+            } => {
+                self.population = population;
+
+                // the leader is fixed and must be active
+                debug_assert!(!servers_info.is_empty());
+                if !servers_info.contains_key(&self.config.leader_id)
+                    || servers_info[&self.config.leader_id].is_paused
+                {
+                    return logged_err!(
+                        "leader {} not active",
+                        self.config.leader_id
+                    );
+                }
+
+                // establish connection to all servers
+                self.servers = servers_info
+                    .into_iter()
+                    .map(|(id, info)| (id, info.api_addr))
+                    .collect();
+                for (&id, &server) in &self.servers {
+                    pf_debug!("connecting to server {} '{}'...", id, server);
+                    let api_stub =
+                        ClientApiStub::new_by_connect(self.id, server).await?;
+                    self.api_stubs.insert(id, api_stub);
+                }
+                Ok(())
+            }
+            _ => logged_err!(Protocol; "unexpected reply type received"),
+        }
+    }
+
+    async fn leave(&mut self, permanent: bool) -> Result<(), SummersetError> {
+        // send leave notification to all servers
+        self.unsent.clear();
+        for (id, mut api_stub) in self.api_stubs.drain() {
+            let mut sent = api_stub.send_req(Some(&ApiRequest::Leave))?;
+            while !sent {
+                sent = api_stub.send_req(None)?;
+            }
+
+            // NOTE: commented out the following wait to avoid accidental
+            //       hanging upon leaving
+            // while api_stub.recv_reply().await? != ApiReply::Leave {}
+            pf_debug!("left server connection {}", id);
+        }
+
+        // if permanently leaving, send leave notification to the manager
+        if permanent {
+            let mut sent =
+                self.ctrl_stub.send_req(Some(&CtrlRequest::Leave))?;
+            while !sent {
+                sent = self.ctrl_stub.send_req(None)?;
+            }
+
+            while self.ctrl_stub.recv_reply().await? != CtrlReply::Leave {}
+            pf_debug!("left manager connection");
+        }
+
+        Ok(())
+    }
+
+    fn send_req(
+        &mut self,
+        req: Option<&ApiRequest>,
+    ) -> Result<bool, SummersetError> {
+        match req {
+            Some(req) => {
+                let targets: Vec<ReplicaId> = if Self::is_proposal(req) {
+                    self.api_stubs.keys().copied().collect()
+                } else {
+                    vec![self.config.leader_id]
+                };
+                self.unsent.clear();
+                for server in targets {
+                    let Some(api_stub) = self.api_stubs.get_mut(&server) else {
+                        return Err(SummersetError::msg(format!(
+                            "server {} not in api_stubs",
+                            server
+                        )));
+                    };
+                    if !api_stub.send_req(Some(req))? {
+                        self.unsent.push(server);
+                    }
+                }
+            }
+
+            None => {
+                // retry the servers that were blocked last time
+                let mut unsent = vec![];
+                for server in self.unsent.drain(..) {
+                    if let Some(api_stub) = self.api_stubs.get_mut(&server) {
+                        if !api_stub.send_req(None)? {
+                            unsent.push(server);
+                        }
+                    }
+                }
+                self.unsent = unsent;
+            }
+        }
+
+        Ok(self.unsent.is_empty())
+    }
+
+    async fn recv_reply(&mut self) -> Result<ApiReply, SummersetError> {
+        // only the leader replies to requests
+        if let Some(api_stub) = self.api_stubs.get_mut(&self.config.leader_id) {
+            api_stub.recv_reply().await
+        } else {
+            Err(SummersetError::msg(format!(
+                "leader {} not in api_stubs",
+                self.config.leader_id
+            )))
+        }
+    }
+
+    fn id(&self) -> ClientId {
+        self.id
+    }
+
+    fn population(&self) -> u16 {
+        self.population
+    }
+
+    fn ctrl_stub(&mut self) -> &mut ClientCtrlStub {
+        &mut self.ctrl_stub
+    }
+}
@@ -0,0 +1,167 @@
+//! FastPaxos -- recovery from WAL.
+
+use super::*;
+
+use crate::server::{LogAction, LogResult};
+use crate::utils::SummersetError;
+
+// FastPaxosReplica recovery from WAL log
+impl FastPaxosReplica {
+    /// Apply a durable storage log entry for recovery.
+    async fn recover_apply_entry(
+        &mut self,
+        entry: WalEntry,
+    ) -> Result<(), SummersetError> {
+        match entry {
+            WalEntry::AcceptSlot { slot, ballot, reqs } => {
+                // locate instance in memory, filling in null instances if needed
+                self.fill_null_insts(slot);
+                // update instance state
+                let inst = &mut self.insts[slot];
+                if inst.status < Status::Committed && ballot >= inst.bal {
+                    inst.bal = ballot;
+                    inst.status = Status::Accepting;
+                    inst.reqs = reqs;
+                    inst.durable = true;
+                }
+            }
+
+            WalEntry::CommitSlot { slot, ballot, reqs } => {
+                // locate instance in memory, filling in null instances if needed
+                self.fill_null_insts(slot);
+                // update instance state
+                let inst = &mut self.insts[slot];
+                inst.bal = inst.bal.max(ballot);
+                inst.status = Status::Committed;
+                inst.reqs = reqs;
+                // execute commands in contiguously committed instances on the
+                // state machine synchronously, skipping duplicate requests
+                if slot == self.commit_bar {
+                    while self.commit_bar < self.insts.len() {
+                        let inst = &self.insts[self.commit_bar];
+                        if inst.status < Status::Committed {
+                            break;
+                        }
+                        if let Some((client, ApiRequest::Req { id, cmd, .. })) =
+                            inst.reqs.first()
+                        {
+                            if self.decided_reqs.insert((*client, *id)) {
+                                // using 0 as a special command ID
+                                self.state_machine
+                                    .do_sync_cmd(0, cmd.clone())
+                                    .await?;
+                            }
+                        }
+                        // update instance status, commit_bar and exec_bar
+                        self.insts[self.commit_bar].status = Status::Executed;
+                        self.commit_bar += 1;
+                        self.exec_bar += 1;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Rebuilds the leader-side bookkeeping of slots not yet committed after
+    /// recovering from WAL, as fast-round tallies are not logged: counts my
+    /// own votes again, sends the Accepts of recovery rounds I had started
+    /// again, and queries acceptors for what they hold from `commit_bar` on.
+    pub(super) fn resume_as_leader(&mut self) -> Result<(), SummersetError> {
+        debug_assert!(self.is_leader());
+        for slot in self.commit_bar..self.insts.len() {
+            let inst = &mut self.insts[slot];
+            if inst.status != Status::Accepting {
+                continue;
+            }
+
+            if inst.bal == FAST_BALLOT {
+                let reqs = inst.reqs.clone();
+                self.handle_msg_fast_vote(self.id, slot, reqs)?;
+            } else {
+                let ballot = inst.bal;
+                inst.leader_bk = Some(LeaderBookkeeping {
+                    fast_votes: HashMap::new(),
+                    since: Instant::now(),
+                    accept_acks: Bitmap::new(self.population, false),
+                });
+                self.bcast_recovery_accept(slot)?;
+                self.handle_msg_accept_reply(self.id, slot, ballot)?;
+            }
+        }
+
+        self.transport_hub.bcast_msg(
+            PeerMsg::VoteQuery {
+                from_slot: self.commit_bar,
+            },
+            None,
+        )?;
+        pf_debug!("broadcast VoteQuery from slot {}", self.commit_bar);
+        Ok(())
+    }
+
+    /// Recover state from durable storage WAL log.
+    pub(super) async fn recover_from_wal(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        debug_assert_eq!(self.wal_offset, 0);
+        loop {
+            match self
+                .storage_hub
+                .do_sync_action(
+                    0, // using 0 as dummy log action ID
+                    LogAction::Read {
+                        offset: self.wal_offset,
+                    },
+                )
+                .await?
+                .1
+            {
+                LogResult::Read {
+                    entry: Some(entry),
+                    end_offset,
+                } => {
+                    self.recover_apply_entry(entry).await?;
+                    // update log offset
+                    self.wal_offset = end_offset;
+                }
+                LogResult::Read { entry: None, .. } => {
+                    // end of log reached
+                    break;
+                }
+                _ => {
+                    return logged_err!(Protocol; "unexpected log result type");
+                }
+            }
+        }
+
+        // do an extra Truncate to remove partial entry at the end if any
+        if let LogResult::Truncate {
+            offset_ok: true, ..
+        } = self
+            .storage_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Truncate {
+                    offset: self.wal_offset,
+                },
+            )
+            .await?
+            .1
+        {
+            if self.wal_offset > 0 {
+                pf_info!(
+                    "recovered from wal log: commit {} exec {}",
+                    self.commit_bar,
+                    self.exec_bar
+                );
+            }
+            Ok(())
+        } else {
+            logged_err!(
+                Protocol; "unexpected log result type or failed truncate"
+            )
+        }
+    }
+}
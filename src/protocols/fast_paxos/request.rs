@@ -0,0 +1,54 @@
+//! FastPaxos -- client request entrance.
+
+use super::*;
+
+use crate::server::LogAction;
+use crate::utils::SummersetError;
+
+// FastPaxosReplica client requests entrance
+impl FastPaxosReplica {
+    /// Votes for each request in the batch in the fast round of my next free
+    /// slot, one request per slot.
+    pub(super) fn fast_vote_reqs(
+        &mut self,
+        reqs: ReqBatch,
+    ) -> Result<(), SummersetError> {
+        for (client, req) in reqs {
+            let slot = self.next_fast_slot();
+            let inst = &mut self.insts[slot];
+            debug_assert_eq!(inst.status, Status::Null);
+            inst.bal = FAST_BALLOT;
+            inst.status = Status::Accepting;
+            inst.reqs = vec![(client, req)];
+            inst.durable = false;
+
+            // record vote durably before sending it to the leader
+            self.storage_hub.submit_action(
+                Self::make_log_action_id(slot, Status::Accepting, FAST_BALLOT),
+                LogAction::Append {
+                    entry: WalEntry::AcceptSlot {
+                        slot,
+                        ballot: FAST_BALLOT,
+                        reqs: inst.reqs.clone(),
+                    },
+                    sync: self.config.logger_sync,
+                },
+            )?;
+            pf_trace!("submitted fast vote log action for slot {}", slot);
+        }
+
+        Ok(())
+    }
+
+    /// Handler of client request batch chan recv.
+    pub(super) async fn handle_req_batch(
+        &mut self,
+        req_batch: ReqBatch,
+    ) -> Result<(), SummersetError> {
+        let batch_size = req_batch.len();
+        debug_assert!(batch_size > 0);
+        pf_debug!("got request batch of size {}", batch_size);
+
+        self.fast_vote_reqs(req_batch)
+    }
+}
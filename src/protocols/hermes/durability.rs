@@ -0,0 +1,47 @@
+//! Hermes -- durable logging.
+
+use super::*;
+
+use crate::server::{LogActionId, LogResult};
+use crate::utils::SummersetError;
+
+// HermesReplica durable WAL logging
+impl HermesReplica {
+    /// Handler of durable logging result chan recv.
+    pub(super) async fn handle_log_result(
+        &mut self,
+        action_id: LogActionId,
+        log_result: LogResult<WalEntry>,
+    ) -> Result<(), SummersetError> {
+        let Some((key, ts, coordinator)) = self.log_owners.remove(&action_id)
+        else {
+            return logged_err!("log action {} not found", action_id);
+        };
+
+        if let LogResult::Append { now_size } = log_result {
+            debug_assert!(now_size >= self.wal_offset);
+            self.wal_offset = now_size;
+        } else {
+            return logged_err!(
+                Protocol; "unexpected log result type: {:?}", log_result
+            );
+        }
+        pf_trace!("finished durable logging for key '{}' ts {:?}", key, ts);
+
+        // acknowledge the invalidation to its coordinator, which may be me
+        if coordinator == self.id {
+            if let Some(bk) = self.writes.get_mut(&key) {
+                if bk.ts == ts && bk.result.is_some() {
+                    bk.acks.set(self.id, true)?;
+                    self.try_finish_write(&key)?;
+                }
+            }
+        } else {
+            self.transport_hub
+                .send_msg(PeerMsg::Ack { key, ts }, coordinator)?;
+            pf_trace!("sent Ack -> {} for ts {:?}", coordinator, ts);
+        }
+
+        Ok(())
+    }
+}
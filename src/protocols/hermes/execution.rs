@@ -0,0 +1,100 @@
+//! Hermes -- command execution.
+
+use super::*;
+
+use crate::server::{ApiReply, ApiRequest, CommandResult};
+use crate::utils::SummersetError;
+
+// HermesReplica state machine execution
+impl HermesReplica {
+    /// Handles the execution result of my write or RMW to key: starts its
+    /// invalidation phase if it takes effect, otherwise replies right away.
+    fn handle_write_result(
+        &mut self,
+        key: String,
+        cmd_result: CommandResult,
+    ) -> Result<(), SummersetError> {
+        let Some(bk) = self.writes.get_mut(&key) else {
+            return logged_err!("write to key '{}' not found", key);
+        };
+        debug_assert!(bk.result.is_none());
+        let meta_ts = self.keys.get(&key).map(|m| m.ts).unwrap_or_default();
+
+        let takes_effect = match cmd_result {
+            CommandResult::Put { .. } => true,
+            CommandResult::Cas { success, .. } => {
+                if meta_ts != bk.base {
+                    // a higher-timestamp write got applied in the meantime
+                    return self.abort_rmw(&key);
+                }
+                success
+            }
+            _ => false,
+        };
+        if bk.rmw && takes_effect {
+            bk.ts = bk.base.next_rmw(self.id);
+            self.keys.get_mut(&key).unwrap().ts = bk.ts;
+        }
+        bk.result = Some(cmd_result);
+        pf_trace!(
+            "executed write to key '{}' ts {:?} effective? {}",
+            key,
+            bk.ts,
+            takes_effect
+        );
+
+        if takes_effect {
+            return self.start_invalidation(key);
+        }
+
+        // failed CAS or rejected Put, which peers need not know about
+        let bk = self.writes.remove(&key).unwrap();
+        if let ApiRequest::Req { id: req_id, .. } = bk.req {
+            if self.external_api.has_client(bk.client) {
+                self.external_api.send_reply(
+                    ApiReply::normal(req_id, bk.result),
+                    bk.client,
+                )?;
+            }
+        }
+        if meta_ts == bk.ts {
+            self.set_key_state(&key, KeyState::Valid);
+        }
+        self.release_stalled(&key)
+    }
+
+    /// Handler of state machine exec result chan recv.
+    pub(super) async fn handle_cmd_result(
+        &mut self,
+        cmd_id: CommandId,
+        cmd_result: CommandResult,
+    ) -> Result<(), SummersetError> {
+        match self.cmd_owners.remove(&cmd_id) {
+            Some(CmdOwner::Read(client, req_id)) => {
+                pf_trace!(
+                    "executed read cmd for client {} req_id {}",
+                    client,
+                    req_id
+                );
+                if self.external_api.has_client(client) {
+                    self.external_api.send_reply(
+                        ApiReply::normal(req_id, Some(cmd_result)),
+                        client,
+                    )?;
+                    pf_trace!(
+                        "replied -> client {} for read req {}",
+                        client,
+                        req_id
+                    );
+                }
+                Ok(())
+            }
+
+            Some(CmdOwner::Write(key)) => {
+                self.handle_write_result(key, cmd_result)
+            }
+
+            None => Ok(()), // write applied on behalf of a peer coordinator
+        }
+    }
+}
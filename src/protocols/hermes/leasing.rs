@@ -0,0 +1,236 @@
+//! Hermes -- membership leases and heartbeats.
+
+use super::*;
+
+use crate::server::{LeaseAction, LeaseMsg, LeaseNotice, LeaseNum, ReplicaId};
+use crate::utils::{Bitmap, SummersetError};
+
+// HermesReplica membership leases and heartbeats related logic
+impl HermesReplica {
+    /// Initiates granting membership leases of the current epoch to peers,
+    /// or to all other members if `peers` is `None`.
+    pub(super) fn grant_membership(
+        &mut self,
+        peers: Option<Bitmap>,
+    ) -> Result<(), SummersetError> {
+        self.lease_manager.add_notice(
+            self.epoch,
+            LeaseNotice::NewGrants {
+                peers: Some(peers.unwrap_or(self.members.clone())),
+                accept_bar: None,
+            },
+        )
+    }
+
+    /// Moves on to a new membership epoch with the given members, granting
+    /// membership leases of the new epoch to them. My writes waiting only on
+    /// dropped replicas may complete once I hold the new leases.
+    fn adopt_membership(
+        &mut self,
+        epoch: LeaseNum,
+        members: Bitmap,
+    ) -> Result<(), SummersetError> {
+        debug_assert!(epoch >= self.epoch);
+        pf_warn!("membership epoch {} members {:?}", epoch, members);
+        self.epoch = epoch;
+        self.members = members;
+        self.grant_membership(None)?;
+        self.retry_pending_writes()
+    }
+
+    /// Drops a peer whose membership lease has expired from the membership,
+    /// bumping the epoch and telling the remaining members about it. If the
+    /// peer is still deemed alive by heartbeats, or if dropping it would
+    /// leave no majority of replicas in the membership, keeps the peer and
+    /// tries granting to it again instead, so that it gets the lease again
+    /// once it comes back.
+    fn drop_member(&mut self, peer: ReplicaId) -> Result<(), SummersetError> {
+        if self.heartbeater.peer_alive().get(peer)?
+            || self.members.count() - 1 <= self.population / 2
+        {
+            pf_warn!("keeping peer {} as member @ {}", peer, self.epoch);
+            return self.grant_membership(Some(Bitmap::from((
+                self.population,
+                vec![peer],
+            ))));
+        }
+
+        let mut members = self.members.clone();
+        members.set(peer, false)?;
+        let epoch = self.epoch + 1;
+
+        // sent on the control lane, which the Guards of the new epoch also
+        // take, so that the update normally reaches members before them
+        let mut peers = members.clone();
+        peers.set(self.id, false)?;
+        self.transport_hub.bcast_ctrl_msg(
+            PeerMsg::Membership {
+                epoch,
+                members: members.clone(),
+            },
+            Some(peers),
+        )?;
+        pf_debug!("broadcast Membership messages for epoch {}", epoch);
+
+        self.adopt_membership(epoch, members)
+    }
+
+    /// Handler of Membership message from a peer that has dropped some
+    /// replica. Adopted if of a newer epoch, or if it shrinks my membership
+    /// of the same epoch, which happens when the peer's Guards of that epoch
+    /// got here first.
+    pub(super) fn handle_msg_membership(
+        &mut self,
+        peer: ReplicaId,
+        epoch: LeaseNum,
+        members: Bitmap,
+    ) -> Result<(), SummersetError> {
+        pf_debug!(
+            "received Membership <- {} for epoch {} members {:?}",
+            peer,
+            epoch,
+            members
+        );
+        let shrinks = members.count() < self.members.count()
+            && members
+                .iter()
+                .all(|(r, member)| !member || self.members.get(r).unwrap());
+        if epoch < self.epoch || (epoch == self.epoch && !shrinks) {
+            return Ok(());
+        }
+        if !members.get(self.id)? {
+            // nobody grants me membership leases anymore, so I stay unable
+            // to serve reads or complete writes
+            pf_warn!("dropped from membership by peer {} @ {}", peer, epoch);
+            return Ok(());
+        }
+        self.adopt_membership(epoch, members)
+    }
+
+    /// Synthesized handler of membership lease-related actions.
+    pub(super) fn handle_lease_action(
+        &mut self,
+        lease_num: LeaseNum,
+        lease_action: LeaseAction,
+    ) -> Result<(), SummersetError> {
+        match lease_action {
+            LeaseAction::SendLeaseMsg { peer, msg } => {
+                self.transport_hub.send_lease_msg(
+                    0, // only one lease purpose exists in the system
+                    lease_num, msg, peer,
+                )?;
+            }
+            LeaseAction::BcastLeaseMsgs { peers, msg } => {
+                self.transport_hub.bcast_lease_msg(
+                    0, // only one lease purpose exists in the system
+                    lease_num,
+                    msg,
+                    Some(peers),
+                )?;
+            }
+
+            LeaseAction::GrantTimeout { peer }
+                if lease_num == self.epoch && self.members.get(peer)? =>
+            {
+                // the lease I granted to the unresponsive peer has expired,
+                // so it can no longer serve reads without hearing of my writes
+                self.drop_member(peer)?;
+            }
+
+            LeaseAction::GrantRemoved { peer, held: false }
+                if lease_num == self.epoch && self.members.get(peer)? =>
+            {
+                // the peer no longer holds the lease I granted, e.g., having
+                // missed its refreshes for a while; grant it afresh
+                self.grant_membership(Some(Bitmap::from((
+                    self.population,
+                    vec![peer],
+                ))))?;
+            }
+
+            LeaseAction::LeaseTimeout { peer } => {
+                pf_debug!("lost membership lease from peer {}", peer);
+            }
+
+            LeaseAction::HigherNumber if lease_num > self.epoch => {
+                // a peer's Guards of a newer epoch got here before its
+                // Membership message; move on to the epoch right away so as
+                // to grant leases of it, with members to be updated then
+                self.adopt_membership(lease_num, self.members.clone())?;
+            }
+
+            _ => {
+                // nothing special protocol-specific to do for other actions
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reacts to a heartbeat timeout with a peer, suspecting that the peer
+    /// has failed. It gets dropped from the membership once the membership
+    /// lease I granted to it expires.
+    pub(super) fn handle_hear_timeout(
+        &mut self,
+        peer: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        pf_warn!("heartbeat timeout with peer {}", peer);
+        self.heartbeater.clear_reply_cnts(Some(peer))?;
+        Ok(())
+    }
+
+    /// Broadcasts heartbeats to all replicas, refreshing my membership
+    /// leases granted along the way. Also retries held-back reads and
+    /// pending writes, and resends stale invalidations.
+    pub(super) async fn bcast_heartbeats(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        // refresh leases granted to peers
+        let to_refresh = self.lease_manager.attempt_refresh(None)?;
+        if to_refresh.count() > 0 {
+            self.transport_hub.queue_lease_msg(
+                0, // only one lease purpose exists in the system
+                self.epoch,
+                LeaseMsg::Promise,
+                Some(to_refresh),
+            )?;
+        }
+        self.transport_hub.flush_lease_msgs()?;
+
+        // broadcast heartbeat to all peers
+        self.transport_hub.bcast_msg(PeerMsg::Heartbeat, None)?;
+
+        // update max heartbeat reply counters and their repetitions seen,
+        // and peers' liveness status accordingly
+        self.heartbeater.update_bcast_cnts()?;
+
+        // I also heard this heartbeat from myself
+        self.heard_heartbeat(self.id)?;
+
+        self.retry_held_back()?;
+        self.retry_pending_writes()?;
+        self.resend_stale_invs()?;
+
+        // pf_trace!("broadcast heartbeats");
+        Ok(())
+    }
+
+    /// Heard a heartbeat from some replica; refreshes my hearing timer.
+    pub(super) fn heard_heartbeat(
+        &mut self,
+        peer: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        if peer != self.id {
+            // update the peer's reply cnt and its liveness status accordingly
+            self.heartbeater.update_heard_cnt(peer)?;
+        }
+
+        // reset hearing timer
+        if !self.config.disable_hb_timer {
+            self.heartbeater.kickoff_hear_timer(Some(peer))?;
+        }
+
+        // pf_trace!("heard heartbeat <- {}", peer);
+        Ok(())
+    }
+}
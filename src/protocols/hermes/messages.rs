@@ -0,0 +1,271 @@
+//! Hermes -- peer-peer messaging.
+
+use super::*;
+
+use crate::server::{ApiReply, ApiRequest, Command, ReplicaId};
+use crate::utils::{Bitmap, SummersetError};
+
+// HermesReplica peer-peer messages handling
+impl HermesReplica {
+    /// Aborts my RMW to key upon seeing a higher timestamp for it, putting
+    /// the request back in front of the ones stalled on key to be retried.
+    pub(super) fn abort_rmw(
+        &mut self,
+        key: &str,
+    ) -> Result<(), SummersetError> {
+        if let Some(bk) = self.writes.remove(key) {
+            debug_assert!(bk.rmw);
+            pf_debug!("aborted RMW to key '{}' at ts {:?}", key, bk.ts);
+            self.stalled
+                .entry(key.into())
+                .or_default()
+                .insert(0, (bk.client, bk.req));
+            self.release_stalled(key)?;
+        }
+        Ok(())
+    }
+
+    /// Starts the invalidation phase of my write to key once executed,
+    /// logging it durably and broadcasting invalidations to all peers.
+    pub(super) fn start_invalidation(
+        &mut self,
+        key: String,
+    ) -> Result<(), SummersetError> {
+        let bk = self.writes.get_mut(&key).unwrap();
+        debug_assert!(bk.result.is_some());
+        let (ts, value, rmw) = (bk.ts, bk.value.clone(), bk.rmw);
+        bk.acks = Bitmap::new(self.population, false);
+        bk.sent_at = Instant::now();
+
+        self.log_write(key.clone(), ts, value.clone(), self.id)?;
+        self.transport_hub.bcast_msg(
+            PeerMsg::Inv {
+                key: key.clone(),
+                ts,
+                value,
+                rmw,
+            },
+            None,
+        )?;
+        pf_trace!("broadcast Inv messages for key '{}' ts {:?}", key, ts);
+        Ok(())
+    }
+
+    /// Resends invalidations of my writes that have been waiting on some
+    /// members' acknowledgements for too long to those members.
+    pub(super) fn resend_stale_invs(&mut self) -> Result<(), SummersetError> {
+        let timeout = Duration::from_millis(self.config.inv_resend_ms);
+        let now = Instant::now();
+        for (key, bk) in self.writes.iter_mut() {
+            if bk.result.is_none() || now - bk.sent_at < timeout {
+                continue;
+            }
+            let mut peers = self.members.clone();
+            for (peer, acked) in bk.acks.iter() {
+                if acked || peer == self.id {
+                    peers.set(peer, false)?;
+                }
+            }
+            if peers.count() == 0 {
+                continue; // only waiting on my own durable logging
+            }
+
+            self.transport_hub.bcast_msg(
+                PeerMsg::Inv {
+                    key: key.clone(),
+                    ts: bk.ts,
+                    value: bk.value.clone(),
+                    rmw: bk.rmw,
+                },
+                Some(peers.clone()),
+            )?;
+            bk.sent_at = now;
+            pf_debug!(
+                "resent Inv messages -> {:?} for key '{}' ts {:?}",
+                peers,
+                key,
+                bk.ts
+            );
+        }
+        Ok(())
+    }
+
+    /// Completes my write to key if it has been executed and acknowledged by
+    /// all members, and I still hold their membership leases: replies to the
+    /// client and validates the key everywhere.
+    pub(super) fn try_finish_write(
+        &mut self,
+        key: &str,
+    ) -> Result<(), SummersetError> {
+        match self.writes.get(key) {
+            Some(bk)
+                if bk.result.is_some()
+                    && self.acked_by_members(&bk.acks)
+                    && self.is_operational() => {}
+            _ => return Ok(()),
+        }
+        let bk = self.writes.remove(key).unwrap();
+
+        if let ApiRequest::Req { id: req_id, .. } = bk.req {
+            if self.external_api.has_client(bk.client) {
+                self.external_api.send_reply(
+                    ApiReply::normal(req_id, bk.result),
+                    bk.client,
+                )?;
+                pf_trace!(
+                    "replied -> client {} for req {} to key '{}'",
+                    bk.client,
+                    req_id,
+                    key
+                );
+            }
+        }
+
+        // peers that have since seen a higher timestamp ignore the validation
+        self.transport_hub.bcast_msg(
+            PeerMsg::Val {
+                key: key.into(),
+                ts: bk.ts,
+            },
+            None,
+        )?;
+        pf_trace!("broadcast Val messages for key '{}' ts {:?}", key, bk.ts);
+
+        if self.key_meta(key).ts == bk.ts {
+            self.set_key_state(key, KeyState::Valid);
+        }
+        self.release_stalled(key)
+    }
+
+    /// Retries completing all my writes, which may have been waiting on
+    /// dropped replicas or on my membership leases.
+    pub(super) fn retry_pending_writes(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        let keys: Vec<String> = self.writes.keys().cloned().collect();
+        for key in keys {
+            self.try_finish_write(&key)?;
+        }
+        Ok(())
+    }
+
+    /// Handler of Inv message from a coordinator.
+    fn handle_msg_inv(
+        &mut self,
+        peer: ReplicaId,
+        key: String,
+        ts: Ts,
+        value: String,
+        rmw: bool,
+    ) -> Result<(), SummersetError> {
+        pf_trace!("received Inv <- {} for key '{}' ts {:?}", peer, key, ts);
+        let meta = self.key_meta(&key);
+
+        if ts > meta.ts {
+            // adopt the higher timestamp, invalidating the key; acknowledged
+            // once durably logged
+            self.keys.entry(key.clone()).or_default().ts = ts;
+            self.set_key_state(&key, KeyState::Invalid);
+
+            // an RMW of mine already invalidating with a lower timestamp can
+            // no longer take effect; one still executing is aborted once its
+            // result comes back
+            if self
+                .writes
+                .get(&key)
+                .is_some_and(|bk| bk.rmw && bk.result.is_some())
+            {
+                self.abort_rmw(&key)?;
+            }
+
+            self.submit_to_state_machine(
+                None,
+                Command::Put {
+                    key: key.clone(),
+                    value: value.clone(),
+                },
+                None,
+            )?;
+            self.log_write(key, ts, value, peer)?;
+        } else if ts == meta.ts {
+            // duplicate of the latest write; acknowledge again unless still
+            // being logged, in which case the acknowledgement follows
+            if !self
+                .log_owners
+                .values()
+                .any(|(k, t, _)| *t == ts && *k == key)
+            {
+                self.transport_hub
+                    .send_msg(PeerMsg::Ack { key, ts }, peer)?;
+                pf_trace!("sent Ack -> {} for ts {:?}", peer, ts);
+            }
+        } else if !rmw {
+            // an older write is overwritten by the latest one right away; it
+            // may complete without being applied
+            self.transport_hub
+                .send_msg(PeerMsg::Ack { key, ts }, peer)?;
+            pf_trace!("sent Ack -> {} for ts {:?}", peer, ts);
+        } else {
+            // an older RMW must not complete; its coordinator aborts it upon
+            // the invalidation with the higher timestamp
+            pf_debug!("ignored stale RMW Inv <- {} ts {:?}", peer, ts);
+        }
+
+        Ok(())
+    }
+
+    /// Handler of Ack message from a replica.
+    fn handle_msg_ack(
+        &mut self,
+        peer: ReplicaId,
+        key: String,
+        ts: Ts,
+    ) -> Result<(), SummersetError> {
+        pf_trace!("received Ack <- {} for key '{}' ts {:?}", peer, key, ts);
+        if let Some(bk) = self.writes.get_mut(&key) {
+            if bk.ts == ts && bk.result.is_some() {
+                bk.acks.set(peer, true)?;
+                self.try_finish_write(&key)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handler of Val message from a coordinator.
+    fn handle_msg_val(
+        &mut self,
+        peer: ReplicaId,
+        key: String,
+        ts: Ts,
+    ) -> Result<(), SummersetError> {
+        pf_trace!("received Val <- {} for key '{}' ts {:?}", peer, key, ts);
+        let meta = self.key_meta(&key);
+        if meta.ts == ts && meta.state == KeyState::Invalid {
+            self.set_key_state(&key, KeyState::Valid);
+            self.release_stalled(&key)?;
+        }
+        Ok(())
+    }
+
+    /// Synthesized handler of receiving message from peer.
+    pub(super) async fn handle_msg_recv(
+        &mut self,
+        peer: ReplicaId,
+        msg: PeerMsg,
+    ) -> Result<(), SummersetError> {
+        match msg {
+            PeerMsg::Inv {
+                key,
+                ts,
+                value,
+                rmw,
+            } => self.handle_msg_inv(peer, key, ts, value, rmw),
+            PeerMsg::Ack { key, ts } => self.handle_msg_ack(peer, key, ts),
+            PeerMsg::Val { key, ts } => self.handle_msg_val(peer, key, ts),
+            PeerMsg::Membership { epoch, members } => {
+                self.handle_msg_membership(peer, epoch, members)
+            }
+            PeerMsg::Heartbeat => self.heard_heartbeat(peer),
+        }
+    }
+}
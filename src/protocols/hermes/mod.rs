@@ -0,0 +1,923 @@
+//! Replication protocol: Hermes.
+//!
+//! Broadcast-based replication where any replica coordinates writes, which
+//! invalidate the key at all replicas and validate it once all members have
+//! acked, ordered per key by logical timestamps (version, coordinator ID).
+//! Reads of valid keys are served locally at every replica holding membership
+//! leases from all other members. Writes advance the version by 2 and RMWs (CASes) by 1,
+//! so that RMWs abort on conflicts while writes never do. A replica whose
+//! membership lease expires gets dropped from the membership under a new
+//! epoch, as long as a majority of replicas remain, after which writes only
+//! wait on the remaining members. A partial implementation: membership
+//! updates are not agreed on through a consensus-backed membership service,
+//! dropped replicas do not rejoin, and failed coordinators' writes are not
+//! replayed. Lock commands are not supported. References:
+//!   - <https://dl.acm.org/doi/10.1145/3373376.3378496>
+//!   - <https://hermes-protocol.com/>
+
+mod control;
+mod durability;
+mod execution;
+mod leasing;
+mod messages;
+mod recovery;
+mod request;
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::Path;
+
+use crate::client::{ClientApiStub, ClientCtrlStub, ClientId, GenericEndpoint};
+use crate::manager::{CtrlMsg, CtrlReply, CtrlRequest};
+use crate::protocols::SmrProtocol;
+use crate::server::{
+    ApiReply, ApiRequest, Command, CommandId, CommandResult, ControlHub,
+    ExternalApi, GenericReplica, HeartbeatEvent, Heartbeater, LeaseManager,
+    LeaseNum, LogAction, LogActionId, ReplicaId, RequestId, StateMachine,
+    StorageHub, TransportHub,
+};
+use crate::utils::{Bitmap, SummersetError};
+
+use async_trait::async_trait;
+
+use get_size::GetSize;
+
+use serde::{Deserialize, Serialize};
+
+use tokio::sync::watch;
+use tokio::time::{Duration, Instant};
+
+/// Configuration parameters struct.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReplicaConfigHermes {
+    /// Client request batching interval in millisecs.
+    pub batch_interval_ms: u64,
+
+    /// Client request batching maximum batch size.
+    pub max_batch_size: usize,
+
+    /// Number of completed replies cached per client for collapsing retried
+    /// requests by idempotency key; 0 means collapsing disabled.
+    pub idem_cache_size: usize,
+
+    /// Idle timeout in secs after which a client session that sent no request
+    /// is closed and its state reclaimed; 0 means never.
+    pub session_idle_s: u64,
+
+    /// Quota in KiB of approximate key-value bytes stored per namespace (the
+    /// key prefix before the first '/'), beyond which Puts are rejected; 0
+    /// means unlimited.
+    pub ns_quota_kb: usize,
+
+    /// Path to backing log file.
+    pub backer_path: String,
+
+    /// Whether to call `fsync()`/`fdatasync()` on logger.
+    pub logger_sync: bool,
+
+    /// Min timeout of not hearing any heartbeat from peer in millisecs.
+    pub hb_hear_timeout_min: u64,
+    /// Max timeout of not hearing any heartbeat from peer in millisecs.
+    pub hb_hear_timeout_max: u64,
+
+    /// Interval of sending heartbeats to peers in millisecs.
+    pub hb_send_interval_ms: u64,
+
+    /// Disable heartbeat timer (to avoid noisy timeouts during tests).
+    pub disable_hb_timer: bool,
+
+    /// Membership lease timeout duration in millisecs.
+    pub lease_expire_ms: u64,
+
+    /// Time in millisecs after which invalidations not yet acknowledged by
+    /// some replicas are resent to them.
+    pub inv_resend_ms: u64,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for ReplicaConfigHermes {
+    fn default() -> Self {
+        ReplicaConfigHermes {
+            batch_interval_ms: 1,
+            max_batch_size: 5000,
+            idem_cache_size: 0,
+            session_idle_s: 0,
+            ns_quota_kb: 0,
+            backer_path: "/tmp/summerset.hermes.wal".into(),
+            logger_sync: false,
+            hb_hear_timeout_min: 1200,
+            hb_hear_timeout_max: 2000,
+            hb_send_interval_ms: 20,
+            disable_hb_timer: false,
+            lease_expire_ms: 2000,
+            inv_resend_ms: 500,
+        }
+    }
+}
+
+/// Per-key logical timestamp: a version number, then the ID of the writing
+/// coordinator breaking ties. Ordered lexicographically.
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Clone,
+    Copy,
+    Default,
+    Serialize,
+    Deserialize,
+    GetSize,
+)]
+struct Ts {
+    version: u64,
+    cid: ReplicaId,
+}
+
+impl Ts {
+    /// Timestamp of a write following this one, coordinated by `cid`.
+    #[inline]
+    fn next_write(&self, cid: ReplicaId) -> Ts {
+        Ts {
+            version: self.version + 2,
+            cid,
+        }
+    }
+
+    /// Timestamp of an RMW following this one, coordinated by `cid`.
+    #[inline]
+    fn next_rmw(&self, cid: ReplicaId) -> Ts {
+        Ts {
+            version: self.version + 1,
+            cid,
+        }
+    }
+}
+
+/// Key state enum.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum KeyState {
+    /// Latest write known to be acknowledged by all members.
+    Valid,
+    /// Invalidated by another coordinator's write in progress.
+    Invalid,
+    /// Being written by myself as the coordinator.
+    Write,
+}
+
+/// Per-key metadata.
+#[derive(Debug, Clone, Copy)]
+struct KeyMeta {
+    /// Timestamp of the latest write to the key applied to my state machine.
+    ts: Ts,
+
+    /// Key state.
+    state: KeyState,
+}
+
+impl Default for KeyMeta {
+    fn default() -> Self {
+        KeyMeta {
+            ts: Ts::default(),
+            state: KeyState::Valid,
+        }
+    }
+}
+
+/// Request batch type.
+type ReqBatch = Vec<(ClientId, ApiRequest)>;
+
+/// Bookkeeping of a write (or RMW) I coordinate, at most one per key.
+struct WriteBk {
+    /// Client that issued the write and its request.
+    client: ClientId,
+    req: ApiRequest,
+
+    /// Is the write an RMW?
+    rmw: bool,
+
+    /// Timestamp of the key when the write started.
+    base: Ts,
+
+    /// Timestamp of the write. For an RMW, only decided once it has been
+    /// executed and has swapped in its value.
+    ts: Ts,
+
+    /// Value written.
+    value: String,
+
+    /// Result of executing the write on my state machine; `None` while
+    /// executing, after which the invalidation phase starts.
+    result: Option<CommandResult>,
+
+    /// Replicas that have acknowledged the invalidation, including myself
+    /// once durably logged.
+    acks: Bitmap,
+
+    /// When the invalidation was last sent.
+    sent_at: Instant,
+}
+
+/// Owner of a command submitted to my state machine.
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum CmdOwner {
+    /// A read served locally for a client.
+    Read(ClientId, RequestId),
+
+    /// A write or RMW I coordinate to a key.
+    Write(String),
+}
+
+/// Stable storage WAL log entry type: a write applied to a key.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, GetSize)]
+struct WalEntry {
+    key: String,
+    ts: Ts,
+    value: String,
+}
+
+/// Peer-peer message type.
+#[derive(Debug, Clone, Serialize, Deserialize, GetSize)]
+enum PeerMsg {
+    /// Invalidation of key by a write with given timestamp and value.
+    Inv {
+        key: String,
+        ts: Ts,
+        value: String,
+        rmw: bool,
+    },
+
+    /// Acknowledgement of an invalidation.
+    Ack { key: String, ts: Ts },
+
+    /// Validation of key by the write with given timestamp, once
+    /// acknowledged by all members.
+    Val { key: String, ts: Ts },
+
+    /// Membership update: the members of a new membership epoch.
+    Membership { epoch: LeaseNum, members: Bitmap },
+
+    /// Peer-to-peer periodic heartbeat.
+    Heartbeat,
+}
+
+/// Hermes server replica module.
+pub(crate) struct HermesReplica {
+    /// Replica ID in cluster.
+    id: ReplicaId,
+
+    /// Total number of replicas in cluster.
+    population: u16,
+
+    /// Configuration parameters struct.
+    config: ReplicaConfigHermes,
+
+    /// Address string for client requests API.
+    _api_addr: SocketAddr,
+
+    /// Address string for internal peer-peer communication.
+    _p2p_addr: SocketAddr,
+
+    /// ControlHub module.
+    control_hub: ControlHub,
+
+    /// ExternalApi module.
+    external_api: ExternalApi,
+
+    /// StateMachine module.
+    state_machine: StateMachine,
+
+    /// StorageHub module.
+    storage_hub: StorageHub<WalEntry>,
+
+    /// TransportHub module.
+    transport_hub: TransportHub<PeerMsg>,
+
+    /// Heartbeater module.
+    heartbeater: Heartbeater,
+
+    /// LeaseManager module for the all-to-all membership leases.
+    lease_manager: LeaseManager,
+
+    /// Membership epoch, also used as the lease number of the membership
+    /// leases, so that leases of older memberships get invalidated.
+    epoch: LeaseNum,
+
+    /// Replicas in the membership of the current epoch, whose
+    /// acknowledgements writes wait on.
+    members: Bitmap,
+
+    /// Metadata of every key written so far.
+    keys: HashMap<String, KeyMeta>,
+
+    /// Writes I coordinate, by key.
+    writes: HashMap<String, WriteBk>,
+
+    /// Requests to keys not valid yet, or with a write of mine in progress,
+    /// held back per key in arrival order.
+    stalled: HashMap<String, ReqBatch>,
+
+    /// Reads held back until I hold all membership leases, and scans held
+    /// back until no key is being written; retried on every heartbeat tick.
+    held_back: ReqBatch,
+
+    /// Number of keys currently not valid.
+    num_unstable: usize,
+
+    /// Owners of commands submitted to my state machine.
+    cmd_owners: HashMap<CommandId, CmdOwner>,
+
+    /// Next command ID to submit with.
+    next_cmd_id: CommandId,
+
+    /// Durable logging actions in progress, mapping to the write logged and
+    /// the coordinator to acknowledge it to (myself for my own writes).
+    log_owners: HashMap<LogActionId, (String, Ts, ReplicaId)>,
+
+    /// Next log action ID to submit with.
+    next_action_id: LogActionId,
+
+    /// Current durable WAL log file offset.
+    wal_offset: usize,
+}
+
+// HermesReplica common helpers
+impl HermesReplica {
+    /// Gets the metadata of key, which is valid with the default timestamp
+    /// if never written.
+    #[inline]
+    fn key_meta(&self, key: &str) -> KeyMeta {
+        self.keys.get(key).copied().unwrap_or_default()
+    }
+
+    /// Sets the state of key, keeping count of keys not valid.
+    fn set_key_state(&mut self, key: &str, state: KeyState) {
+        let meta = self.keys.entry(key.into()).or_default();
+        match (meta.state == KeyState::Valid, state == KeyState::Valid) {
+            (true, false) => self.num_unstable += 1,
+            (false, true) => self.num_unstable -= 1,
+            _ => {}
+        }
+        meta.state = state;
+    }
+
+    /// Is the key valid with no write of mine to it in progress?
+    #[inline]
+    fn key_is_free(&self, key: &str) -> bool {
+        self.key_meta(key).state == KeyState::Valid
+            && !self.writes.contains_key(key)
+    }
+
+    /// Am I holding membership leases from all other members, thus allowed
+    /// to serve reads locally and complete writes?
+    fn is_operational(&self) -> bool {
+        let leases = self.lease_manager.lease_set();
+        self.members.iter().all(|(r, member)| {
+            !member || r == self.id || leases.get(r).unwrap_or(false)
+        })
+    }
+
+    /// Have all members acknowledged a write?
+    #[inline]
+    fn acked_by_members(&self, acks: &Bitmap) -> bool {
+        self.members
+            .iter()
+            .all(|(r, member)| !member || acks.get(r).unwrap_or(false))
+    }
+
+    /// Submits a command to my state machine on behalf of an owner, if any.
+    fn submit_to_state_machine(
+        &mut self,
+        owner: Option<CmdOwner>,
+        cmd: Command,
+        checksum: Option<u64>,
+    ) -> Result<(), SummersetError> {
+        let cmd_id = self.next_cmd_id;
+        self.next_cmd_id += 1;
+        if let Some(owner) = owner {
+            self.cmd_owners.insert(cmd_id, owner);
+        }
+        self.state_machine.submit_checked_cmd(cmd_id, cmd, checksum)
+    }
+
+    /// Durably logs a write applied to key, to be acknowledged to the
+    /// coordinator once done.
+    fn log_write(
+        &mut self,
+        key: String,
+        ts: Ts,
+        value: String,
+        coordinator: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        let action_id = self.next_action_id;
+        self.next_action_id += 1;
+        self.log_owners
+            .insert(action_id, (key.clone(), ts, coordinator));
+        self.storage_hub.submit_action(
+            action_id,
+            LogAction::Append {
+                entry: WalEntry { key, ts, value },
+                sync: self.config.logger_sync,
+            },
+        )?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl GenericReplica for HermesReplica {
+    async fn new_and_setup(
+        api_addr: SocketAddr,
+        p2p_addr: SocketAddr,
+        manager: SocketAddr,
+        config_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        // connect to the cluster manager and get assigned a server ID
+        let mut control_hub = ControlHub::new_and_setup(manager).await?;
+        let id = control_hub.me;
+        let (api_addr, p2p_addr) =
+            control_hub.resolve_addrs(api_addr, p2p_addr)?;
+        let population = control_hub.population;
+
+        // parse protocol-specific configs
+        let config = parsed_config!(config_str => ReplicaConfigHermes;
+                                    batch_interval_ms, max_batch_size,
+                                    idem_cache_size, session_idle_s,
+                                    ns_quota_kb,
+                                    backer_path, logger_sync,
+                                    hb_hear_timeout_min, hb_hear_timeout_max,
+                                    hb_send_interval_ms, disable_hb_timer,
+                                    lease_expire_ms, inv_resend_ms)?;
+        if config.batch_interval_ms == 0 {
+            return logged_err!(
+                "invalid config.batch_interval_ms '{}'",
+                config.batch_interval_ms
+            );
+        }
+        if config.hb_hear_timeout_min < 100 {
+            return logged_err!(
+                "invalid config.hb_hear_timeout_min '{}'",
+                config.hb_hear_timeout_min
+            );
+        }
+        if config.hb_hear_timeout_max < config.hb_hear_timeout_min + 100 {
+            return logged_err!(
+                "invalid config.hb_hear_timeout_max '{}'",
+                config.hb_hear_timeout_max
+            );
+        }
+        if config.hb_send_interval_ms == 0 {
+            return logged_err!(
+                "invalid config.hb_send_interval_ms '{}'",
+                config.hb_send_interval_ms
+            );
+        }
+        if config.inv_resend_ms == 0 {
+            return logged_err!(
+                "invalid config.inv_resend_ms '{}'",
+                config.inv_resend_ms
+            );
+        }
+
+        // setup state machine module
+        let state_machine =
+            StateMachine::new_and_setup(id, config.ns_quota_kb * 1024).await?;
+        control_hub.inspect_keys(&state_machine);
+
+        // setup storage hub module
+        let storage_hub =
+            StorageHub::new_and_setup(id, Path::new(&config.backer_path))
+                .await?;
+
+        // setup heartbeat management module
+        let mut heartbeater = Heartbeater::new_and_setup(
+            id,
+            population,
+            Duration::from_millis(config.hb_hear_timeout_min),
+            Duration::from_millis(config.hb_hear_timeout_max),
+            Duration::from_millis(config.hb_send_interval_ms),
+        )?;
+        heartbeater.set_sending(true); // doing all-to-all heartbeating
+
+        // setup membership leases management module
+        let (lease_manager, tx_lease_msg) = LeaseManager::new_and_setup(
+            id,
+            population,
+            Duration::from_millis(config.lease_expire_ms),
+            Duration::from_millis(config.hb_send_interval_ms),
+        )?;
+
+        // setup transport hub module
+        let mut transport_hub = TransportHub::new_and_setup(
+            id,
+            population,
+            SmrProtocol::Hermes,
+            p2p_addr,
+            false,
+            HashMap::from([(
+                0, // only one lease purpose exists in the system
+                tx_lease_msg,
+            )]),
+        )
+        .await?;
+
+        // ask for the list of peers to proactively connect to. Do this after
+        // transport hub has been set up, so that I will be able to accept
+        // later peer connections
+        control_hub.send_ctrl(CtrlMsg::NewServerJoin {
+            id,
+            protocol: SmrProtocol::Hermes,
+            api_addr,
+            p2p_addr,
+        })?;
+        let to_peers = if let CtrlMsg::ConnectToPeers { to_peers, .. } =
+            control_hub.recv_ctrl().await?
+        {
+            to_peers
+        } else {
+            return logged_err!(Protocol; "unexpected ctrl msg type received");
+        };
+
+        // proactively connect to some peers, then wait for all population
+        // have been connected with me
+        for (peer, conn_addr) in to_peers {
+            transport_hub.connect_to_peer(peer, conn_addr).await?;
+        }
+        transport_hub.wait_for_group(population).await?;
+
+        // setup external API module, ready to take in client requests
+        let mut external_api = ExternalApi::new_and_setup(
+            id,
+            api_addr,
+            Duration::from_millis(config.batch_interval_ms),
+            config.max_batch_size,
+            config.idem_cache_size,
+            Duration::from_secs(config.session_idle_s),
+        )
+        .await?;
+        external_api.stamp_replies(&state_machine);
+
+        Ok(HermesReplica {
+            id,
+            population,
+            config,
+            _api_addr: api_addr,
+            _p2p_addr: p2p_addr,
+            control_hub,
+            external_api,
+            state_machine,
+            storage_hub,
+            transport_hub,
+            heartbeater,
+            lease_manager,
+            epoch: 0,
+            members: Bitmap::new(population, true),
+            keys: HashMap::new(),
+            writes: HashMap::new(),
+            stalled: HashMap::new(),
+            held_back: vec![],
+            num_unstable: 0,
+            cmd_owners: HashMap::new(),
+            next_cmd_id: 0,
+            log_owners: HashMap::new(),
+            next_action_id: 0,
+            wal_offset: 0,
+        })
+    }
+
+    async fn run(
+        &mut self,
+        mut rx_term: watch::Receiver<bool>,
+    ) -> Result<bool, SummersetError> {
+        // recover keys from durable WAL log
+        self.recover_from_wal().await?;
+
+        // kick off peer heartbeats hearing timer, and grant membership
+        // leases to all peers
+        if !self.config.disable_hb_timer {
+            self.heartbeater.kickoff_hear_timer(None)?;
+        }
+        self.grant_membership(None)?;
+
+        // main event loop
+        let mut paused = false;
+        loop {
+            tokio::select! {
+                // client request batch
+                req_batch = self.external_api.get_req_batch(), if !paused => {
+                    if let Err(e) = req_batch {
+                        pf_error!("error getting req batch: {}", e);
+                        continue;
+                    }
+                    let req_batch = req_batch.unwrap();
+                    if let Err(e) = self.handle_req_batch(req_batch).await {
+                        pf_error!("error handling req batch: {}", e);
+                    }
+                },
+
+                // durable logging result
+                log_result = self.storage_hub.get_result(), if !paused => {
+                    if let Err(e) = log_result {
+                        pf_error!("error getting log result: {}", e);
+                        continue;
+                    }
+                    let (action_id, log_result) = log_result.unwrap();
+                    if let Err(e) = self.handle_log_result(action_id, log_result).await {
+                        pf_error!("error handling log result {}: {}",
+                                           action_id, e);
+                    }
+                },
+
+                // message from peer
+                msg = self.transport_hub.recv_msg(), if !paused => {
+                    if let Err(_e) = msg {
+                        // NOTE: commented out to prevent console lags
+                        //       during benchmarking
+                        // pf_error!("error receiving peer msg: {}", e);
+                        continue;
+                    }
+                    let (peer, msg) = msg.unwrap();
+                    if let Err(e) = self.handle_msg_recv(peer, msg).await {
+                        pf_error!("error handling msg recv <- {}: {}", peer, e);
+                    }
+                },
+
+                // state machine execution result
+                cmd_result = self.state_machine.get_result(), if !paused => {
+                    if let Err(e) = cmd_result {
+                        pf_error!("error getting cmd result: {}", e);
+                        continue;
+                    }
+                    let (cmd_id, cmd_result) = cmd_result.unwrap();
+                    if let Err(e) = self.handle_cmd_result(cmd_id, cmd_result).await {
+                        pf_error!("error handling cmd result {}: {}", cmd_id, e);
+                    }
+                },
+
+                // heartbeat-related event
+                hb_event = self.heartbeater.get_event(), if !paused => {
+                    if let Err(e) = hb_event {
+                        pf_error!("error getting heartbeat event: {}", e);
+                        continue;
+                    }
+                    match hb_event.unwrap() {
+                        HeartbeatEvent::HearTimeout { peer } => {
+                            if let Err(e) = self.handle_hear_timeout(peer) {
+                                pf_error!("error handling hear timeout: {}", e);
+                            }
+                        }
+                        HeartbeatEvent::SendTicked => {
+                            if let Err(e) = self.bcast_heartbeats().await {
+                                pf_error!("error broadcasting heartbeats: {}", e);
+                            }
+                        }
+                    }
+                },
+
+                // membership lease-related action
+                lease_action = self.lease_manager.get_action(), if !paused => {
+                    if let Err(e) = lease_action {
+                        pf_error!("error getting lease action: {}", e);
+                        continue;
+                    }
+                    let (lease_num, lease_action) = lease_action.unwrap();
+                    if let Err(e) = self.handle_lease_action(lease_num, lease_action) {
+                        pf_error!("error handling lease action @ {}: {}", lease_num, e);
+                    }
+                },
+
+                // manager control message
+                ctrl_msg = self.control_hub.recv_ctrl() => {
+                    if let Err(e) = ctrl_msg {
+                        pf_error!("error getting ctrl msg: {}", e);
+                        continue;
+                    }
+                    let ctrl_msg = ctrl_msg.unwrap();
+                    match self.handle_ctrl_msg(ctrl_msg, &mut paused).await {
+                        Ok(terminate) => {
+                            if let Some(restart) = terminate {
+                                return Ok(restart);
+                            }
+                        },
+                        Err(e) => {
+                            pf_error!("error handling ctrl msg: {}", e);
+                        }
+                    }
+                },
+
+                // receiving termination signal
+                _ = rx_term.changed() => {
+                    pf_warn!("server caught termination signal");
+                    return Ok(false);
+                }
+            }
+        }
+    }
+
+    fn id(&self) -> ReplicaId {
+        self.id
+    }
+
+    fn population(&self) -> u16 {
+        self.population
+    }
+}
+
+/// Configuration parameters struct.
+#[derive(Debug, Deserialize)]
+pub struct ClientConfigHermes {
+    /// App-designated nearest server ID to send all requests to. Any number
+    /// that's larger than cluster size means picking one by my client ID, so
+    /// that clients spread their requests over the replicas.
+    pub near_server_id: ReplicaId,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for ClientConfigHermes {
+    fn default() -> Self {
+        ClientConfigHermes {
+            near_server_id: ReplicaId::MAX,
+        }
+    }
+}
+
+/// Hermes client-side module.
+pub(crate) struct HermesClient {
+    /// Client ID.
+    id: ClientId,
+
+    /// Number of servers in the cluster.
+    population: u16,
+
+    /// Configuration parameters struct.
+    config: ClientConfigHermes,
+
+    /// List of active servers information.
+    servers: HashMap<ReplicaId, SocketAddr>,
+
+    /// Server ID to send requests to. Could become different from
+    /// `config.near_server_id` if the latter is deemed inactive.
+    server_id: ReplicaId,
+
+    /// Control API stub to the cluster manager.
+    ctrl_stub: ClientCtrlStub,
+
+    /// API stubs for communicating with servers.
+    api_stubs: HashMap<ReplicaId, ClientApiStub>,
+}
+
+#[async_trait]
+impl GenericEndpoint for HermesClient {
+    async fn new_and_setup(
+        manager: SocketAddr,
+        config_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        // connect to the cluster manager and get assigned a client ID
+        pf_debug!("connecting to manager '{}'...", manager);
+        let ctrl_stub = ClientCtrlStub::new_by_connect(manager).await?;
+        let id = ctrl_stub.id;
+
+        // parse protocol-specific configs
+        let config = parsed_config!(config_str => ClientConfigHermes;
+                                    near_server_id)?;
+        let server_id = config.near_server_id;
+
+        Ok(HermesClient {
+            id,
+            population: 0,
+            config,
+            servers: HashMap::new(),
+            server_id,
+            ctrl_stub,
+            api_stubs: HashMap::new(),
+        })
+    }
+
+    async fn connect(&mut self) -> Result<(), SummersetError> {
+        // disallow reconnection without leaving
+        if !self.api_stubs.is_empty() {
+            return logged_err!("reconnecting without leaving");
+        }
+
+        // ask the manager about the list of active servers
+        let mut sent =
+            self.ctrl_stub.send_req(Some(&CtrlRequest::QueryInfo))?;
+        while !sent {
+            sent = self.ctrl_stub.send_req(None)?;
+        }
+
+        let reply = self.ctrl_stub.recv_reply().await?;
+        match reply {
+            CtrlReply::QueryInfo {
+                population,
+                servers_info,
+            } => {
+                self.population = population;
+
+                // pick the server to send requests to, shifting to a new one
+                // if it is not active
+                debug_assert!(!servers_info.is_empty());
+                self.server_id = if self.config.near_server_id < population {
+                    self.config.near_server_id
+                } else {
+                    (self.id % population as ClientId) as ReplicaId
+                };
+                let picked = self.server_id;
+                while !servers_info.contains_key(&self.server_id)
+                    || servers_info[&self.server_id].is_paused
+                {
+                    self.server_id = (self.server_id + 1) % population;
+                }
+                if self.server_id != picked {
+                    pf_warn!(
+                        "near server {} inactive, using {} instead...",
+                        picked,
+                        self.server_id
+                    );
+                }
+
+                // establish connection to all servers
+                self.servers = servers_info
+                    .into_iter()
+                    .map(|(id, info)| (id, info.api_addr))
+                    .collect();
+                for (&id, &server) in &self.servers {
+                    pf_debug!("connecting to server {} '{}'...", id, server);
+                    let api_stub =
+                        ClientApiStub::new_by_connect(self.id, server).await?;
+                    self.api_stubs.insert(id, api_stub);
+                }
+                Ok(())
+            }
+            _ => logged_err!(Protocol; "unexpected reply type received"),
+        }
+    }
+
+    async fn leave(&mut self, permanent: bool) -> Result<(), SummersetError> {
+        // send leave notification to all servers
+        for (id, mut api_stub) in self.api_stubs.drain() {
+            let mut sent = api_stub.send_req(Some(&ApiRequest::Leave))?;
+            while !sent {
+                sent = api_stub.send_req(None)?;
+            }
+
+            // NOTE: commented out the following wait to avoid accidental
+            //       hanging upon leaving
+            // while api_stub.recv_reply().await? != ApiReply::Leave {}
+            pf_debug!("left server connection {}", id);
+        }
+
+        // if permanently leaving, send leave notification to the manager
+        if permanent {
+            let mut sent =
+                self.ctrl_stub.send_req(Some(&CtrlRequest::Leave))?;
+            while !sent {
+                sent = self.ctrl_stub.send_req(None)?;
+            }
+
+            while self.ctrl_stub.recv_reply().await? != CtrlReply::Leave {}
+            pf_debug!("left manager connection");
+        }
+
+        Ok(())
+    }
+
+    fn send_req(
+        &mut self,
+        req: Option<&ApiRequest>,
+    ) -> Result<bool, SummersetError> {
+        if let Some(api_stub) = self.api_stubs.get_mut(&self.server_id) {
+            api_stub.send_req(req)
+        } else {
+            Err(SummersetError::msg(format!(
+                "server_id {} not in api_stubs",
+                self.server_id
+            )))
+        }
+    }
+
+    async fn recv_reply(&mut self) -> Result<ApiReply, SummersetError> {
+        if let Some(api_stub) = self.api_stubs.get_mut(&self.server_id) {
+            // every replica serves all requests, so no redirection
+            api_stub.recv_reply().await
+        } else {
+            Err(SummersetError::msg(format!(
+                "server_id {} not in api_stubs",
+                self.server_id
+            )))
+        }
+    }
+
+    fn id(&self) -> ClientId {
+        self.id
+    }
+
+    fn population(&self) -> u16 {
+        self.population
+    }
+
+    fn ctrl_stub(&mut self) -> &mut ClientCtrlStub {
+        &mut self.ctrl_stub
+    }
+}
@@ -0,0 +1,98 @@
+//! Hermes -- recovery from WAL.
+
+use super::*;
+
+use crate::server::{Command, LogAction, LogResult};
+use crate::utils::SummersetError;
+
+// HermesReplica recovery from WAL log
+impl HermesReplica {
+    /// Apply a durable storage log entry for recovery.
+    async fn recover_apply_entry(
+        &mut self,
+        entry: WalEntry,
+    ) -> Result<(), SummersetError> {
+        // writes to a key may have been logged out of timestamp order; only
+        // apply ones newer than what has been recovered so far
+        let meta = self.keys.entry(entry.key.clone()).or_default();
+        if entry.ts <= meta.ts {
+            return Ok(());
+        }
+        meta.ts = entry.ts;
+
+        self.state_machine
+            .do_sync_cmd(
+                0, // using 0 as dummy command ID
+                Command::Put {
+                    key: entry.key,
+                    value: entry.value,
+                },
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Recover state from durable storage WAL log.
+    // NOTE: recovered keys are all deemed valid, although the latest write
+    //       to some of them may not have been acknowledged by all replicas
+    //       when I went down; its coordinator keeps resending invalidations
+    //       to me until I acknowledge them, though
+    pub(super) async fn recover_from_wal(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        debug_assert_eq!(self.wal_offset, 0);
+        loop {
+            match self
+                .storage_hub
+                .do_sync_action(
+                    0, // using 0 as dummy log action ID
+                    LogAction::Read {
+                        offset: self.wal_offset,
+                    },
+                )
+                .await?
+                .1
+            {
+                LogResult::Read {
+                    entry: Some(entry),
+                    end_offset,
+                } => {
+                    self.recover_apply_entry(entry).await?;
+                    // update log offset
+                    self.wal_offset = end_offset;
+                }
+                LogResult::Read { entry: None, .. } => {
+                    // end of log reached
+                    break;
+                }
+                _ => {
+                    return logged_err!(Protocol; "unexpected log result type");
+                }
+            }
+        }
+
+        // do an extra Truncate to remove partial entry at the end if any
+        if let LogResult::Truncate {
+            offset_ok: true, ..
+        } = self
+            .storage_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Truncate {
+                    offset: self.wal_offset,
+                },
+            )
+            .await?
+            .1
+        {
+            if self.wal_offset > 0 {
+                pf_info!("recovered from wal log: {} keys", self.keys.len());
+            }
+            Ok(())
+        } else {
+            logged_err!(
+                Protocol; "unexpected log result type or failed truncate"
+            )
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! Hermes -- client request entrance.
+
+use super::*;
+
+use crate::server::{ApiReply, ApiRequest, Command, CommandResult, ReplyError};
+use crate::utils::{Bitmap, SummersetError};
+
+// HermesReplica client requests entrance
+impl HermesReplica {
+    /// Submits a read request to my state machine to be served locally.
+    fn serve_read_locally(
+        &mut self,
+        client: ClientId,
+        req: ApiRequest,
+    ) -> Result<(), SummersetError> {
+        if let ApiRequest::Req {
+            id: req_id,
+            cmd,
+            checksum,
+        } = req
+        {
+            self.submit_to_state_machine(
+                Some(CmdOwner::Read(client, req_id)),
+                cmd,
+                checksum,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Starts coordinating a write or RMW to a valid key with no write of
+    /// mine in progress, by executing it on my state machine first.
+    fn start_write(
+        &mut self,
+        client: ClientId,
+        req: ApiRequest,
+    ) -> Result<(), SummersetError> {
+        let ApiRequest::Req {
+            id: req_id,
+            ref cmd,
+            checksum,
+        } = req
+        else {
+            return logged_err!(Protocol; "unexpected API request type");
+        };
+
+        // reject corrupted commands right away, as they must not reach peers
+        if checksum.is_some_and(|sum| sum != cmd.checksum()) {
+            pf_warn!("checksum mismatch of req {} from {}", req_id, client);
+            if self.external_api.has_client(client) {
+                self.external_api.send_reply(
+                    ApiReply::normal(
+                        req_id,
+                        Some(CommandResult::ChecksumMismatch),
+                    ),
+                    client,
+                )?;
+            }
+            return Ok(());
+        }
+
+        let (key, value, rmw) = match cmd {
+            Command::Put { key, value } => (key.clone(), value.clone(), false),
+            Command::Cas { key, value, .. } => {
+                (key.clone(), value.clone(), true)
+            }
+            _ => return logged_err!(Protocol; "unexpected command type"),
+        };
+        let base = self.key_meta(&key).ts;
+
+        // a write takes its timestamp right away, so that invalidations from
+        // concurrent writes get ordered against it; an RMW only takes one if
+        // it turns out to swap in its value on an unchanged key
+        let ts = if rmw {
+            base
+        } else {
+            let ts = base.next_write(self.id);
+            self.keys.entry(key.clone()).or_default().ts = ts;
+            ts
+        };
+        self.set_key_state(&key, KeyState::Write);
+
+        let cmd = cmd.clone();
+        self.writes.insert(
+            key.clone(),
+            WriteBk {
+                client,
+                req,
+                rmw,
+                base,
+                ts,
+                value,
+                result: None,
+                acks: Bitmap::new(self.population, false),
+                sent_at: Instant::now(),
+            },
+        );
+        self.submit_to_state_machine(Some(CmdOwner::Write(key)), cmd, None)?;
+        Ok(())
+    }
+
+    /// Handles a single client request, serving or starting it if possible,
+    /// and holding it back otherwise.
+    pub(super) fn handle_req(
+        &mut self,
+        client: ClientId,
+        req: ApiRequest,
+    ) -> Result<(), SummersetError> {
+        let ApiRequest::Req {
+            id: req_id, cmd, ..
+        } = &req
+        else {
+            return Ok(()); // ignore other types of requests
+        };
+
+        if cmd.lock_key().is_some() {
+            if self.external_api.has_client(client) {
+                self.external_api.send_reply(
+                    ApiReply::error(*req_id, ReplyError::Unsupported),
+                    client,
+                )?;
+            }
+        } else if let Some(key) = cmd.read_only() {
+            if !self.is_operational() {
+                self.held_back.push((client, req));
+            } else if !self.key_is_free(key) {
+                let key = key.clone();
+                self.stalled.entry(key).or_default().push((client, req));
+            } else {
+                self.serve_read_locally(client, req)?;
+            }
+        } else if cmd.scan_range().is_some() {
+            // scans span many keys; only served when none is being written
+            if !self.is_operational() || self.num_unstable > 0 {
+                self.held_back.push((client, req));
+            } else {
+                self.serve_read_locally(client, req)?;
+            }
+        } else if let Some(key) = cmd.write_key() {
+            if !self.key_is_free(key) {
+                let key = key.clone();
+                self.stalled.entry(key).or_default().push((client, req));
+            } else {
+                self.start_write(client, req)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Re-handles requests stalled on key, in arrival order, if the key has
+    /// become valid with no write of mine in progress.
+    pub(super) fn release_stalled(
+        &mut self,
+        key: &str,
+    ) -> Result<(), SummersetError> {
+        if !self.key_is_free(key) {
+            return Ok(());
+        }
+        if let Some(reqs) = self.stalled.remove(key) {
+            pf_trace!("releasing {} stalled reqs on key '{}'", reqs.len(), key);
+            for (client, req) in reqs {
+                self.handle_req(client, req)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Re-handles requests held back for being not operational or for keys
+    /// being written.
+    pub(super) fn retry_held_back(&mut self) -> Result<(), SummersetError> {
+        if self.held_back.is_empty() {
+            return Ok(());
+        }
+        for (client, req) in std::mem::take(&mut self.held_back) {
+            self.handle_req(client, req)?;
+        }
+        Ok(())
+    }
+
+    /// Handler of client request batch chan recv.
+    pub(super) async fn handle_req_batch(
+        &mut self,
+        req_batch: ReqBatch,
+    ) -> Result<(), SummersetError> {
+        let batch_size = req_batch.len();
+        debug_assert!(batch_size > 0);
+        pf_debug!("got request batch of size {}", batch_size);
+
+        for (client, req) in req_batch {
+            self.handle_req(client, req)?;
+        }
+        Ok(())
+    }
+}
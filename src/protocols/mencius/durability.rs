@@ -0,0 +1,146 @@
+//! Mencius -- durable logging.
+
+use super::*;
+
+use crate::server::{LogActionId, LogResult};
+use crate::utils::SummersetError;
+
+// MenciusReplica durable WAL logging
+impl MenciusReplica {
+    /// Handler of AcceptSlot logging result chan recv.
+    fn handle_logged_accept_slot(
+        &mut self,
+        slot: usize,
+    ) -> Result<(), SummersetError> {
+        if slot < self.start_slot {
+            return Ok(()); // ignore if slot index outdated
+        }
+        pf_trace!(
+            "finished AcceptSlot logging for slot {} bal {}",
+            slot,
+            self.insts[slot - self.start_slot].bal
+        );
+        let inst = &self.insts[slot - self.start_slot];
+        if inst.status != Status::Accepting || inst.voted.0 != inst.bal {
+            // superseded by a higher ballot in the meantime
+            return Ok(());
+        }
+
+        if let Some(LeaderBookkeeping { .. }) = inst.leader_bk {
+            // on proposer, finishing the logging of an AcceptSlot entry is
+            // equivalent to receiving an Accept reply from myself (as an
+            // acceptor role)
+            self.handle_msg_accept_reply(self.id, slot, inst.bal)?;
+        } else if let Some(ReplicaBookkeeping { source }) = inst.replica_bk {
+            // on follower replica, finishing the logging of an AcceptSlot
+            // entry leads to sending back an Accept reply
+            self.transport_hub.send_msg(
+                PeerMsg::AcceptReply {
+                    slot,
+                    ballot: inst.bal,
+                },
+                source,
+            )?;
+            pf_trace!(
+                "sent AcceptReply -> {} for slot {} bal {}",
+                source,
+                slot,
+                inst.bal
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Handler of CommitSlot logging result chan recv.
+    fn handle_logged_commit_slot(
+        &mut self,
+        slot: usize,
+    ) -> Result<(), SummersetError> {
+        if slot < self.start_slot {
+            return Ok(()); // ignore if slot index outdated
+        }
+        pf_trace!(
+            "finished CommitSlot logging for slot {} bal {}",
+            slot,
+            self.insts[slot - self.start_slot].bal
+        );
+
+        // update index of the first non-committed instance
+        if slot == self.commit_bar {
+            while self.commit_bar < self.start_slot + self.insts.len() {
+                let inst = &mut self.insts[self.commit_bar - self.start_slot];
+                if inst.status < Status::Committed {
+                    break;
+                }
+
+                // submit commands in committed instance to the state machine
+                // for execution; skipped slots have nothing to execute
+                if inst.reqs.is_empty() {
+                    inst.status = Status::Executed;
+                } else if inst.status == Status::Committed {
+                    for (cmd_idx, (_, req)) in inst.reqs.iter().enumerate() {
+                        if let ApiRequest::Req { cmd, checksum, .. } = req {
+                            self.state_machine.submit_checked_cmd(
+                                Self::make_command_id(self.commit_bar, cmd_idx),
+                                cmd.clone(),
+                                *checksum,
+                            )?;
+                        }
+                    }
+                    pf_trace!(
+                        "submitted {} exec commands for slot {}",
+                        inst.reqs.len(),
+                        self.commit_bar
+                    );
+                }
+
+                self.commit_bar += 1;
+            }
+
+            // skipped slots right at the exec_bar count as executed now
+            self.advance_exec_bar();
+        }
+
+        Ok(())
+    }
+
+    /// Synthesized handler of durable logging result chan recv.
+    pub(super) async fn handle_log_result(
+        &mut self,
+        action_id: LogActionId,
+        log_result: LogResult<WalEntry>,
+    ) -> Result<(), SummersetError> {
+        let (slot, entry_type) = Self::split_log_action_id(action_id);
+        if slot < self.start_slot {
+            return Ok(()); // ignore if slot index outdated
+        }
+        debug_assert!(slot < self.start_slot + self.insts.len());
+
+        if let LogResult::Append { now_size } = log_result {
+            debug_assert!(now_size >= self.wal_offset);
+            // update first wal_offset of slot
+            let inst = &mut self.insts[slot - self.start_slot];
+            if inst.wal_offset == 0 || inst.wal_offset > self.wal_offset {
+                inst.wal_offset = self.wal_offset;
+            }
+            debug_assert!(inst.wal_offset <= self.wal_offset);
+            // then update self.wal_offset
+            self.wal_offset = now_size;
+        } else {
+            return logged_err!(
+                Protocol; "unexpected log result type: {:?}", log_result
+            );
+        }
+
+        match entry_type {
+            Status::Accepting => self.handle_logged_accept_slot(slot),
+            Status::Committed => self.handle_logged_commit_slot(slot),
+            _ => {
+                logged_err!(
+                    Protocol; "unexpected log entry type: {:?}", entry_type
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Mencius -- heartbeats & slot revocation.
+
+use std::cmp;
+
+use super::*;
+
+use crate::server::ReplicaId;
+use crate::utils::SummersetError;
+
+// MenciusReplica heartbeats & revocation related logic
+impl MenciusReplica {
+    /// Reacts to a heartbeat timeout with a peer, suspecting that the peer
+    /// has failed. Revokes all its unfinished slots in my log by running the
+    /// Prepare phase on them with a higher ballot, which ends up committing
+    /// either a value it may have gotten accepted or a no-op.
+    //
+    // NOTE: the original paper revokes a long range of slots ahead of the
+    //       log tail at once; here we re-arm the timer instead, so that slots
+    //       of a silent peer that newly block execution get revoked after
+    //       every further timeout until hearing from it again
+    pub(super) async fn heartbeat_timeout(
+        &mut self,
+        timeout_source: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        // clear peer's heartbeat reply counters, and broadcast a heartbeat now
+        self.heartbeater.clear_reply_cnts(Some(timeout_source))?;
+        self.bcast_heartbeats().await?;
+
+        // re-initialize peer_exec_bar information
+        if let Some(slot) = self.peer_exec_bar.get_mut(&timeout_source) {
+            *slot = 0;
+        } else {
+            return logged_err!(
+                "peer {} not found in peer_exec_bar",
+                timeout_source
+            );
+        }
+
+        // start the Prepare phase for all unfinished slots owned by that peer
+        let mut prepares = vec![];
+        pf_info!("try revoking slots of replica {}...", timeout_source);
+
+        let mut slot = self.owned_slot_from(timeout_source, self.commit_bar);
+        while slot < self.start_slot + self.insts.len() {
+            let inst = &mut self.insts[slot - self.start_slot];
+            if inst.status < Status::Committed {
+                let ballot = Self::make_greater_ballot(self.id, inst.bal);
+                inst.bal = ballot;
+                inst.leader_bk = Some(LeaderBookkeeping {
+                    prepare_acks: Bitmap::new(self.population, false),
+                    prepare_max_bal: 0,
+                    prepare_voted: None,
+                    accept_acks: Bitmap::new(self.population, false),
+                });
+                inst.replica_bk = None;
+
+                // broadcast Prepare messages to all peers
+                self.transport_hub
+                    .bcast_msg(PeerMsg::Prepare { slot, ballot }, None)?;
+                pf_trace!(
+                    "broadcast Prepare messages for slot {} bal {}",
+                    slot,
+                    ballot
+                );
+
+                let voted = if inst.voted.0 > 0 {
+                    Some(inst.voted.clone())
+                } else {
+                    None
+                };
+                prepares.push((slot, ballot, voted));
+            }
+            slot += self.population as usize;
+        }
+
+        // also "reply" to Prepares to myself
+        for (slot, ballot, voted) in prepares {
+            self.handle_msg_prepare_reply(self.id, slot, ballot, voted)?;
+        }
+
+        // re-arm the timer for that peer
+        if !self.config.disable_hb_timer {
+            self.heartbeater.kickoff_hear_timer(Some(timeout_source))?;
+        }
+        Ok(())
+    }
+
+    /// Broadcasts heartbeats to all replicas.
+    pub(super) async fn bcast_heartbeats(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        // broadcast heartbeat to all peers
+        self.transport_hub.bcast_msg(
+            PeerMsg::Heartbeat {
+                exec_bar: self.exec_bar,
+                snap_bar: self.snap_bar,
+            },
+            None,
+        )?;
+
+        // update max heartbeat reply counters and their repetitions seen,
+        // and peers' liveness status accordingly
+        self.heartbeater.update_bcast_cnts()?;
+
+        // I also heard this heartbeat from myself
+        self.heard_heartbeat(self.id, self.exec_bar, self.snap_bar)?;
+
+        Ok(())
+    }
+
+    /// Heard a heartbeat from some replica. Refreshes my hearing timer for
+    /// it and updates the snapshotting barrier.
+    pub(super) fn heard_heartbeat(
+        &mut self,
+        peer: ReplicaId,
+        exec_bar: usize,
+        snap_bar: usize,
+    ) -> Result<(), SummersetError> {
+        if peer != self.id {
+            // update the peer's reply cnt and its liveness status accordingly
+            self.heartbeater.update_heard_cnt(peer)?;
+        }
+
+        // reset hearing timer
+        if !self.config.disable_hb_timer {
+            self.heartbeater.kickoff_hear_timer(Some(peer))?;
+        }
+
+        if peer != self.id {
+            // update peer_exec_bar if larger then known; if all servers'
+            // exec_bar (including myself) have passed a slot, that slot
+            // is (probably but not completely) safe to be snapshotted
+            if exec_bar > self.peer_exec_bar[&peer] {
+                *self.peer_exec_bar.get_mut(&peer).unwrap() = exec_bar;
+                let exec_min = self
+                    .peer_exec_bar
+                    .values()
+                    .fold(self.exec_bar, |min, &e| cmp::min(min, e));
+                if exec_min > self.snap_bar {
+                    self.snap_bar = exec_min;
+                }
+            }
+
+            // if snap_bar is larger than mine, update snap_bar
+            if snap_bar > self.snap_bar {
+                self.snap_bar = snap_bar;
+            }
+        }
+
+        Ok(())
+    }
+}
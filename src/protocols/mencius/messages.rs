@@ -0,0 +1,458 @@
+//! Mencius -- peer-peer messaging.
+
+use super::*;
+
+use crate::server::LogAction;
+use crate::utils::SummersetError;
+
+// MenciusReplica peer-peer messages handling
+impl MenciusReplica {
+    /// Commits the instance at slot as a no-op if not committed yet. Only
+    /// called on slots whose owner has never proposed in them, where any
+    /// value chosen must be a no-op anyway.
+    fn commit_skipped_slot(
+        &mut self,
+        slot: usize,
+    ) -> Result<(), SummersetError> {
+        self.fill_null_insts(slot);
+        let inst = &mut self.insts[slot - self.start_slot];
+        if inst.status >= Status::Committed {
+            return Ok(());
+        }
+        inst.status = Status::Committed;
+        inst.reqs = ReqBatch::new();
+
+        // record commit event
+        self.storage_hub.submit_action(
+            Self::make_log_action_id(slot, Status::Committed),
+            LogAction::Append {
+                entry: WalEntry::CommitSlot {
+                    slot,
+                    ballot: inst.bal,
+                    reqs: ReqBatch::new(),
+                },
+                sync: self.config.logger_sync,
+            },
+        )?;
+        pf_trace!("submitted CommitSlot log action for skipped slot {}", slot);
+        Ok(())
+    }
+
+    /// Some peer has proposed in the given slot, so skips all my unused
+    /// owned slots below it, and notifies peers about the skipped range. If
+    /// the slot itself is mine, it is being revoked, and I will never propose
+    /// in it either.
+    pub(super) fn skip_slots_until(
+        &mut self,
+        slot: usize,
+    ) -> Result<(), SummersetError> {
+        let from = self.next_slot;
+        if slot < from {
+            return Ok(());
+        }
+
+        let mut own_slot = from;
+        while own_slot < slot {
+            self.commit_skipped_slot(own_slot)?;
+            own_slot += self.population as usize;
+        }
+        self.next_slot = if own_slot == slot {
+            own_slot + self.population as usize
+        } else {
+            own_slot
+        };
+
+        if from < slot {
+            self.transport_hub
+                .bcast_msg(PeerMsg::Skip { from, to: slot }, None)?;
+            pf_trace!("broadcast Skip messages for slots {} - {}", from, slot);
+        }
+        Ok(())
+    }
+
+    /// Handler of Accept message from the proposer of a slot.
+    fn handle_msg_accept(
+        &mut self,
+        peer: ReplicaId,
+        slot: usize,
+        ballot: Ballot,
+        reqs: ReqBatch,
+    ) -> Result<(), SummersetError> {
+        if slot < self.start_slot {
+            return Ok(()); // ignore if slot index outdated
+        }
+        pf_trace!(
+            "received Accept <- {} for slot {} bal {} size {}",
+            peer,
+            slot,
+            ballot,
+            reqs.len()
+        );
+
+        // locate instance in memory, filling in null instances if needed
+        self.fill_null_insts(slot);
+        let inst = &mut self.insts[slot - self.start_slot];
+
+        // if ballot is not smaller than what I have seen:
+        if ballot >= inst.bal && inst.status < Status::Committed {
+            inst.bal = ballot;
+            inst.status = Status::Accepting;
+            inst.reqs.clone_from(&reqs);
+            inst.voted = (ballot, reqs.clone());
+            inst.leader_bk = None;
+            inst.replica_bk = Some(ReplicaBookkeeping { source: peer });
+
+            // record update to largest accepted ballot and corresponding data
+            self.storage_hub.submit_action(
+                Self::make_log_action_id(slot, Status::Accepting),
+                LogAction::Append {
+                    entry: WalEntry::AcceptSlot { slot, ballot, reqs },
+                    sync: self.config.logger_sync,
+                },
+            )?;
+            pf_trace!(
+                "submitted AcceptSlot log action for slot {} bal {}",
+                slot,
+                ballot
+            );
+        }
+
+        // my unused slots below this one will never be proposed in
+        self.skip_slots_until(slot)
+    }
+
+    /// Handler of Accept reply from replica.
+    pub(super) fn handle_msg_accept_reply(
+        &mut self,
+        peer: ReplicaId,
+        slot: usize,
+        ballot: Ballot,
+    ) -> Result<(), SummersetError> {
+        if slot < self.start_slot {
+            return Ok(()); // ignore if slot index outdated
+        }
+        pf_trace!(
+            "received AcceptReply <- {} for slot {} bal {}",
+            peer,
+            slot,
+            ballot
+        );
+
+        // ignore spurious duplications and outdated replies
+        if slot >= self.start_slot + self.insts.len() {
+            return Ok(());
+        }
+        let inst = &mut self.insts[slot - self.start_slot];
+        if inst.status != Status::Accepting || ballot != inst.bal {
+            return Ok(());
+        }
+        let Some(leader_bk) = inst.leader_bk.as_mut() else {
+            return Ok(());
+        };
+        if leader_bk.accept_acks.get(peer)? {
+            return Ok(());
+        }
+
+        // bookkeep this Accept reply
+        leader_bk.accept_acks.set(peer, true)?;
+
+        // if quorum size reached, mark this instance as committed
+        if leader_bk.accept_acks.count() >= self.quorum_cnt {
+            inst.status = Status::Committed;
+            pf_debug!("committed instance at slot {} bal {}", slot, inst.bal);
+
+            // record commit event
+            self.storage_hub.submit_action(
+                Self::make_log_action_id(slot, Status::Committed),
+                LogAction::Append {
+                    entry: WalEntry::CommitSlot {
+                        slot,
+                        ballot: inst.bal,
+                        reqs: inst.reqs.clone(),
+                    },
+                    sync: self.config.logger_sync,
+                },
+            )?;
+            pf_trace!(
+                "submitted CommitSlot log action for slot {} bal {}",
+                slot,
+                inst.bal
+            );
+
+            // broadcast CommitNotice messages to all peers
+            self.transport_hub.bcast_msg(
+                PeerMsg::CommitNotice {
+                    slot,
+                    ballot: inst.bal,
+                    reqs: inst.reqs.clone(),
+                },
+                None,
+            )?;
+            pf_trace!(
+                "broadcast CommitNotice messages for slot {} bal {}",
+                slot,
+                inst.bal
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Handler of CommitNotice message from proposer.
+    fn handle_msg_commit_notice(
+        &mut self,
+        peer: ReplicaId,
+        slot: usize,
+        ballot: Ballot,
+        reqs: ReqBatch,
+    ) -> Result<(), SummersetError> {
+        if slot < self.start_slot {
+            return Ok(()); // ignore if slot index outdated
+        }
+        pf_trace!(
+            "received CommitNotice <- {} for slot {} bal {}",
+            peer,
+            slot,
+            ballot
+        );
+
+        // locate instance in memory, filling in null instances if needed
+        self.fill_null_insts(slot);
+        let inst = &mut self.insts[slot - self.start_slot];
+
+        // the value chosen in a slot is unique, so take it in regardless of
+        // what I have seen
+        if inst.status < Status::Committed {
+            if ballot > inst.bal {
+                inst.bal = ballot;
+            }
+            inst.status = Status::Committed;
+            inst.reqs.clone_from(&reqs);
+
+            // record commit event
+            self.storage_hub.submit_action(
+                Self::make_log_action_id(slot, Status::Committed),
+                LogAction::Append {
+                    entry: WalEntry::CommitSlot {
+                        slot,
+                        ballot: inst.bal,
+                        reqs,
+                    },
+                    sync: self.config.logger_sync,
+                },
+            )?;
+            pf_trace!(
+                "submitted CommitSlot log action for slot {} bal {}",
+                slot,
+                inst.bal
+            );
+        }
+
+        // my unused slots below this one will never be proposed in
+        self.skip_slots_until(slot)
+    }
+
+    /// Handler of Skip message from the owner of skipped slots.
+    fn handle_msg_skip(
+        &mut self,
+        peer: ReplicaId,
+        from: usize,
+        to: usize,
+    ) -> Result<(), SummersetError> {
+        pf_trace!("received Skip <- {} for slots {} - {}", peer, from, to);
+
+        let mut slot = self.owned_slot_from(peer, from.max(self.start_slot));
+        while slot < to {
+            debug_assert_eq!(self.slot_owner(slot), peer);
+            self.commit_skipped_slot(slot)?;
+            slot += self.population as usize;
+        }
+        Ok(())
+    }
+
+    /// Handler of Prepare message from replica revoking the slot.
+    fn handle_msg_prepare(
+        &mut self,
+        peer: ReplicaId,
+        slot: usize,
+        ballot: Ballot,
+    ) -> Result<(), SummersetError> {
+        if slot < self.start_slot {
+            return Ok(()); // ignore if slot index outdated
+        }
+        pf_trace!(
+            "received Prepare <- {} for slot {} bal {}",
+            peer,
+            slot,
+            ballot
+        );
+
+        // locate instance in memory, filling in null instances if needed
+        self.fill_null_insts(slot);
+        let inst = &mut self.insts[slot - self.start_slot];
+
+        if inst.status >= Status::Committed {
+            // already committed, just let the revoker know
+            self.transport_hub.send_msg(
+                PeerMsg::CommitNotice {
+                    slot,
+                    ballot: inst.bal,
+                    reqs: inst.reqs.clone(),
+                },
+                peer,
+            )?;
+            pf_trace!(
+                "sent CommitNotice -> {} for slot {} bal {}",
+                peer,
+                slot,
+                inst.bal
+            );
+        } else if ballot > inst.bal {
+            // if ballot is larger than what I've ever seen for this instance
+            inst.bal = ballot;
+            inst.leader_bk = None;
+            inst.replica_bk = Some(ReplicaBookkeeping { source: peer });
+
+            // send back Prepare reply
+            self.transport_hub.send_msg(
+                PeerMsg::PrepareReply {
+                    slot,
+                    ballot,
+                    voted: if inst.voted.0 > 0 {
+                        Some(inst.voted.clone())
+                    } else {
+                        None
+                    },
+                },
+                peer,
+            )?;
+            pf_trace!(
+                "sent PrepareReply -> {} for slot {} bal {}",
+                peer,
+                slot,
+                ballot
+            );
+        }
+
+        // my unused slots up to this one will never be proposed in
+        self.skip_slots_until(slot)
+    }
+
+    /// Handler of Prepare reply from replica.
+    pub(super) fn handle_msg_prepare_reply(
+        &mut self,
+        peer: ReplicaId,
+        slot: usize,
+        ballot: Ballot,
+        voted: Option<(Ballot, ReqBatch)>,
+    ) -> Result<(), SummersetError> {
+        if slot < self.start_slot {
+            return Ok(()); // ignore if slot index outdated
+        }
+        pf_trace!(
+            "received PrepareReply <- {} for slot {} bal {}",
+            peer,
+            slot,
+            ballot
+        );
+
+        // ignore spurious duplications and outdated replies
+        if slot >= self.start_slot + self.insts.len() {
+            return Ok(());
+        }
+        let inst = &mut self.insts[slot - self.start_slot];
+        if inst.status >= Status::Committed || ballot != inst.bal {
+            return Ok(());
+        }
+        let Some(leader_bk) = inst.leader_bk.as_mut() else {
+            return Ok(());
+        };
+        if leader_bk.prepare_acks.get(peer)? {
+            return Ok(());
+        }
+
+        // bookkeep this Prepare reply
+        if let Some((voted_bal, voted_reqs)) = voted {
+            if voted_bal > leader_bk.prepare_max_bal {
+                leader_bk.prepare_max_bal = voted_bal;
+                leader_bk.prepare_voted = Some(voted_reqs);
+            }
+        }
+        leader_bk.prepare_acks.set(peer, true)?;
+
+        // if quorum size just reached, enter Accept phase with the value
+        // voted with the highest ballot, or a no-op if none
+        if leader_bk.prepare_acks.count() == self.quorum_cnt {
+            let reqs = leader_bk.prepare_voted.take().unwrap_or_default();
+            inst.status = Status::Accepting;
+            inst.reqs.clone_from(&reqs);
+            inst.voted = (ballot, reqs.clone());
+            pf_debug!(
+                "enter Accept phase for revoked slot {} bal {} size {}",
+                slot,
+                ballot,
+                reqs.len()
+            );
+
+            // record update to largest accepted ballot and corresponding data
+            self.storage_hub.submit_action(
+                Self::make_log_action_id(slot, Status::Accepting),
+                LogAction::Append {
+                    entry: WalEntry::AcceptSlot {
+                        slot,
+                        ballot,
+                        reqs: reqs.clone(),
+                    },
+                    sync: self.config.logger_sync,
+                },
+            )?;
+            pf_trace!(
+                "submitted AcceptSlot log action for slot {} bal {}",
+                slot,
+                ballot
+            );
+
+            // broadcast Accept messages to all peers
+            self.transport_hub
+                .bcast_msg(PeerMsg::Accept { slot, ballot, reqs }, None)?;
+            pf_trace!(
+                "broadcast Accept messages for slot {} bal {}",
+                slot,
+                ballot
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Synthesized handler of receiving message from peer.
+    pub(super) async fn handle_msg_recv(
+        &mut self,
+        peer: ReplicaId,
+        msg: PeerMsg,
+    ) -> Result<(), SummersetError> {
+        match msg {
+            PeerMsg::Accept { slot, ballot, reqs } => {
+                self.handle_msg_accept(peer, slot, ballot, reqs)
+            }
+            PeerMsg::AcceptReply { slot, ballot } => {
+                self.handle_msg_accept_reply(peer, slot, ballot)
+            }
+            PeerMsg::CommitNotice { slot, ballot, reqs } => {
+                self.handle_msg_commit_notice(peer, slot, ballot, reqs)
+            }
+            PeerMsg::Skip { from, to } => self.handle_msg_skip(peer, from, to),
+            PeerMsg::Prepare { slot, ballot } => {
+                self.handle_msg_prepare(peer, slot, ballot)
+            }
+            PeerMsg::PrepareReply {
+                slot,
+                ballot,
+                voted,
+            } => self.handle_msg_prepare_reply(peer, slot, ballot, voted),
+            PeerMsg::Heartbeat { exec_bar, snap_bar } => {
+                self.heard_heartbeat(peer, exec_bar, snap_bar)
+            }
+        }
+    }
+}
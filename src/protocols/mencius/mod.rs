@@ -0,0 +1,965 @@
+//! Replication protocol: Mencius.
+//!
+//! Multi-leader Paxos where log slots are pre-partitioned round-robin among
+//! replicas, so that every replica acts as the default leader of its own
+//! slots. An owner with nothing to propose skips its slots by committing
+//! no-ops in them, and slots of a suspected failed owner get revoked by
+//! peers through a regular Paxos Prepare phase. References:
+//!   - <https://www.usenix.org/legacy/event/osdi08/tech/full_papers/mao/mao.pdf>
+
+mod control;
+mod durability;
+mod execution;
+mod heartbeat;
+mod messages;
+mod recovery;
+mod request;
+mod snapshot;
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::Path;
+
+use crate::client::{ClientApiStub, ClientCtrlStub, ClientId, GenericEndpoint};
+use crate::manager::{CtrlMsg, CtrlReply, CtrlRequest};
+use crate::protocols::SmrProtocol;
+use crate::server::{
+    ApiReply, ApiRequest, CommandId, CommandResult, ControlHub, ExternalApi,
+    GenericReplica, HeartbeatEvent, Heartbeater, LogActionId, ReplicaId,
+    StateMachine, StorageHub, TransportHub,
+};
+use crate::utils::{Bitmap, SummersetError};
+
+use async_trait::async_trait;
+
+use get_size::GetSize;
+
+use serde::{Deserialize, Serialize};
+
+use tokio::sync::watch;
+use tokio::time::{self, Duration, Interval, MissedTickBehavior};
+
+/// Configuration parameters struct.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReplicaConfigMencius {
+    /// Client request batching interval in millisecs.
+    pub batch_interval_ms: u64,
+
+    /// Client request batching maximum batch size.
+    pub max_batch_size: usize,
+
+    /// Number of completed replies cached per client for collapsing retried
+    /// requests by idempotency key; 0 means collapsing disabled.
+    pub idem_cache_size: usize,
+
+    /// Idle timeout in secs after which a client session that sent no request
+    /// is closed and its state reclaimed; 0 means never.
+    pub session_idle_s: u64,
+
+    /// Quota in KiB of approximate key-value bytes stored per namespace (the
+    /// key prefix before the first '/'), beyond which Puts are rejected; 0
+    /// means unlimited.
+    pub ns_quota_kb: usize,
+
+    /// Path to backing log file.
+    pub backer_path: String,
+
+    /// Whether to call `fsync()`/`fdatasync()` on logger.
+    pub logger_sync: bool,
+
+    /// Min timeout of not hearing any heartbeat from peer in millisecs.
+    pub hb_hear_timeout_min: u64,
+    /// Max timeout of not hearing any heartbeat from peer in millisecs.
+    pub hb_hear_timeout_max: u64,
+
+    /// Interval of sending heartbeats to peers.
+    pub hb_send_interval_ms: u64,
+
+    /// Disable heartbeat timer (to force a deterministic leader during tests).
+    pub disable_hb_timer: bool,
+
+    /// Path to snapshot file.
+    pub snapshot_path: String,
+
+    /// Snapshot self-triggering interval in secs. 0 means never trigger
+    /// snapshotting autonomously.
+    pub snapshot_interval_s: u64,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for ReplicaConfigMencius {
+    fn default() -> Self {
+        ReplicaConfigMencius {
+            batch_interval_ms: 1,
+            max_batch_size: 5000,
+            idem_cache_size: 0,
+            session_idle_s: 0,
+            ns_quota_kb: 0,
+            backer_path: "/tmp/summerset.mencius.wal".into(),
+            logger_sync: false,
+            hb_hear_timeout_min: 1200,
+            hb_hear_timeout_max: 2000,
+            hb_send_interval_ms: 20,
+            disable_hb_timer: false,
+            snapshot_path: "/tmp/summerset.mencius.snap".into(),
+            snapshot_interval_s: 0,
+        }
+    }
+}
+
+/// Ballot number type. Use 0 as a null ballot number.
+type Ballot = u64;
+
+/// Instance status enum.
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Clone,
+    Copy,
+    Serialize,
+    Deserialize,
+    GetSize,
+)]
+enum Status {
+    Null = 0,
+    // no Preparing status; revocation prepares are by messages only
+    Accepting = 1,
+    Committed = 2,
+    Executed = 3,
+}
+
+/// Request batch type (i.e., the "value" in Paxos). An empty batch is a
+/// no-op, which is what skipped slots get committed with.
+type ReqBatch = Vec<(ClientId, ApiRequest)>;
+
+/// Proposer-side bookkeeping info for each instance I propose in, either as
+/// its owner or as a revoker.
+#[derive(Debug, Clone)]
+struct LeaderBookkeeping {
+    /// Replicas from which I have received Prepare confirmations.
+    prepare_acks: Bitmap,
+
+    /// Max ballot among received Prepare replies.
+    prepare_max_bal: Ballot,
+
+    /// Value voted with the max ballot among received Prepare replies.
+    prepare_voted: Option<ReqBatch>,
+
+    /// Replicas from which I have received Accept confirmations.
+    accept_acks: Bitmap,
+}
+
+/// Follower-side bookkeeping info for each instance received.
+#[derive(Debug, Clone)]
+struct ReplicaBookkeeping {
+    /// Source proposer replica ID for replying to messages.
+    source: ReplicaId,
+}
+
+/// In-memory instance containing a commands batch.
+#[derive(Debug, Clone)]
+struct Instance {
+    /// Highest ballot number seen.
+    bal: Ballot,
+
+    /// Instance status.
+    status: Status,
+
+    /// Batch of client requests.
+    reqs: ReqBatch,
+
+    /// Highest ballot and associated value I have accepted; this field is
+    /// required to support correct Prepare phase replies.
+    voted: (Ballot, ReqBatch),
+
+    /// Proposer-side bookkeeping info.
+    leader_bk: Option<LeaderBookkeeping>,
+
+    /// Follower-side bookkeeping info.
+    replica_bk: Option<ReplicaBookkeeping>,
+
+    /// True if from external client, else false.
+    external: bool,
+
+    /// Offset of first durable WAL log entry related to this instance.
+    wal_offset: usize,
+}
+
+/// Stable storage WAL log entry type.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, GetSize)]
+#[allow(clippy::enum_variant_names)]
+enum WalEntry {
+    /// Records an update to the largest accepted ballot and its value.
+    AcceptSlot {
+        slot: usize,
+        ballot: Ballot,
+        reqs: ReqBatch,
+    },
+
+    /// Records an event of committing the instance at index, including
+    /// committing a skipped slot as a no-op.
+    CommitSlot {
+        slot: usize,
+        ballot: Ballot,
+        reqs: ReqBatch,
+    },
+}
+
+/// Snapshot file entry type.
+//
+// NOTE: the current implementation rewrites a whole image of the state
+//       machine into the snapshot file for simplicity. In production, the
+//       snapshot file should be a bounded-sized backend, e.g., an LSM-tree.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, GetSize)]
+enum SnapEntry {
+    /// Necessary slot indices to remember.
+    SlotInfo {
+        /// First entry at the start of file: number of log instances covered
+        /// by this snapshot file == the start slot index of in-mem log.
+        start_slot: usize,
+    },
+
+    /// Image of the state machine taken by `take_snapshot()`, reflecting
+    /// exactly the commands of all slots before start_slot.
+    StateImage { image: Vec<u8> },
+}
+
+/// Peer-peer message type.
+#[derive(Debug, Clone, Serialize, Deserialize, GetSize)]
+enum PeerMsg {
+    /// Accept message from the owner (or a revoker) of a slot to replicas.
+    Accept {
+        slot: usize,
+        ballot: Ballot,
+        reqs: ReqBatch,
+    },
+
+    /// Accept reply from replica to proposer.
+    AcceptReply { slot: usize, ballot: Ballot },
+
+    /// Notification of commit from proposer to replicas.
+    // NOTE: think of these async CommitNotices as being indefinitely
+    //       retransmitted until all peers learn about them
+    CommitNotice {
+        slot: usize,
+        ballot: Ballot,
+        reqs: ReqBatch,
+    },
+
+    /// Notification from an owner that its slots in range [from, to) are
+    /// skipped, i.e., committed as no-ops.
+    Skip { from: usize, to: usize },
+
+    /// Prepare message from replica that suspects the owner of the slot to
+    /// have failed and tries to revoke it.
+    Prepare { slot: usize, ballot: Ballot },
+
+    /// Prepare reply from replica to revoker.
+    PrepareReply {
+        slot: usize,
+        ballot: Ballot,
+        /// Highest ballot *accepted* before the one in Prepare and its value.
+        voted: Option<(Ballot, ReqBatch)>,
+    },
+
+    /// Peer-to-peer periodic heartbeat.
+    Heartbeat {
+        /// For conservative snapshotting purpose.
+        exec_bar: usize,
+        /// For conservative snapshotting purpose.
+        snap_bar: usize,
+    },
+}
+
+/// Mencius server replica module.
+pub(crate) struct MenciusReplica {
+    /// Replica ID in cluster.
+    id: ReplicaId,
+
+    /// Total number of replicas in cluster.
+    population: u16,
+
+    /// Majority quorum size.
+    quorum_cnt: u16,
+
+    /// Configuration parameters struct.
+    config: ReplicaConfigMencius,
+
+    /// Address string for client requests API.
+    _api_addr: SocketAddr,
+
+    /// Address string for internal peer-peer communication.
+    _p2p_addr: SocketAddr,
+
+    /// ControlHub module.
+    control_hub: ControlHub,
+
+    /// ExternalApi module.
+    external_api: ExternalApi,
+
+    /// StateMachine module.
+    state_machine: StateMachine,
+
+    /// StorageHub module.
+    storage_hub: StorageHub<WalEntry>,
+
+    /// StorageHub module for the snapshot file.
+    snapshot_hub: StorageHub<SnapEntry>,
+
+    /// TransportHub module.
+    transport_hub: TransportHub<PeerMsg>,
+
+    /// Heartbeater module.
+    heartbeater: Heartbeater,
+
+    /// In-memory log of instances.
+    insts: Vec<Instance>,
+
+    /// Start slot index of in-mem log after latest snapshot.
+    start_slot: usize,
+
+    /// Timer for taking a new autonomous snapshot.
+    snapshot_interval: Interval,
+
+    /// Slot index of the first non-committed instance.
+    commit_bar: usize,
+
+    /// Slot index of the first non-executed instance.
+    /// It is always true that
+    ///   exec_bar <= commit_bar <= start_slot + insts.len()
+    exec_bar: usize,
+
+    /// First slot owned by me that I have neither proposed in nor skipped.
+    next_slot: usize,
+
+    /// Map from peer ID -> its latest exec_bar I know; this is for conservative
+    /// snapshotting purpose.
+    peer_exec_bar: HashMap<ReplicaId, usize>,
+
+    /// Slot index before which it is safe to take snapshot.
+    // NOTE: we are taking a conservative approach here that a snapshot
+    //       covering an entry can be taken only when all servers have durably
+    //       committed (and executed) that entry.
+    snap_bar: usize,
+
+    /// Current durable WAL log file offset.
+    wal_offset: usize,
+
+    /// Current durable snapshot file offset.
+    snap_offset: usize,
+}
+
+// MenciusReplica common helpers
+impl MenciusReplica {
+    /// Create an empty null instance.
+    #[inline]
+    fn null_instance(&self) -> Instance {
+        Instance {
+            bal: 0,
+            status: Status::Null,
+            reqs: ReqBatch::new(),
+            voted: (0, ReqBatch::new()),
+            leader_bk: None,
+            replica_bk: None,
+            external: false,
+            wal_offset: 0,
+        }
+    }
+
+    /// Fill in null instances so that the in-mem log covers the given slot.
+    fn fill_null_insts(&mut self, slot: usize) {
+        while self.start_slot + self.insts.len() <= slot {
+            let inst = self.null_instance();
+            self.insts.push(inst);
+        }
+    }
+
+    /// Replica that owns the given slot.
+    #[inline]
+    fn slot_owner(&self, slot: usize) -> ReplicaId {
+        (slot % self.population as usize) as ReplicaId
+    }
+
+    /// Smallest slot index >= `slot` owned by the given replica.
+    #[inline]
+    fn owned_slot_from(&self, owner: ReplicaId, slot: usize) -> usize {
+        let population = self.population as usize;
+        let owner = owner as usize;
+        slot + (population + owner - slot % population) % population
+    }
+
+    /// Compose a unique ballot number from base.
+    #[inline]
+    fn make_unique_ballot(id: ReplicaId, base: u64) -> Ballot {
+        ((base << 16) | ((id + 1) as u64)) as Ballot
+    }
+
+    /// Compose a unique ballot number greater than the given one.
+    #[inline]
+    fn make_greater_ballot(id: ReplicaId, bal: Ballot) -> Ballot {
+        Self::make_unique_ballot(id, (bal >> 16) + 1)
+    }
+
+    /// Returns the default ballot number for replica ID, used by owners in
+    /// their own slots.
+    #[inline]
+    fn make_default_ballot(id: ReplicaId) -> Ballot {
+        Self::make_unique_ballot(id, 0)
+    }
+
+    /// Compose LogActionId from slot index & entry type.
+    /// Uses the `Status` enum type to represent different entry types.
+    #[inline]
+    fn make_log_action_id(slot: usize, entry_type: Status) -> LogActionId {
+        let type_num = match entry_type {
+            Status::Accepting => 1,
+            Status::Committed => 2,
+            _ => panic!("unknown log entry type {:?}", entry_type),
+        };
+        ((slot << 2) | type_num) as LogActionId
+    }
+
+    /// Decompose LogActionId into slot index & entry type.
+    #[inline]
+    fn split_log_action_id(log_action_id: LogActionId) -> (usize, Status) {
+        let slot = (log_action_id >> 2) as usize;
+        let type_num = log_action_id & ((1 << 2) - 1);
+        let entry_type = match type_num {
+            1 => Status::Accepting,
+            2 => Status::Committed,
+            _ => panic!("unknown log entry type num {}", type_num),
+        };
+        (slot, entry_type)
+    }
+
+    /// Compose CommandId from slot index & command index within.
+    #[inline]
+    fn make_command_id(slot: usize, cmd_idx: usize) -> CommandId {
+        debug_assert!(slot <= (u32::MAX as usize));
+        debug_assert!(cmd_idx <= (u32::MAX as usize));
+        ((slot << 32) | cmd_idx) as CommandId
+    }
+
+    /// Decompose CommandId into slot index & command index within.
+    #[inline]
+    fn split_command_id(command_id: CommandId) -> (usize, usize) {
+        let slot = (command_id >> 32) as usize;
+        let cmd_idx = (command_id & ((1 << 32) - 1)) as usize;
+        (slot, cmd_idx)
+    }
+}
+
+#[async_trait]
+impl GenericReplica for MenciusReplica {
+    async fn new_and_setup(
+        api_addr: SocketAddr,
+        p2p_addr: SocketAddr,
+        manager: SocketAddr,
+        config_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        // connect to the cluster manager and get assigned a server ID
+        let mut control_hub = ControlHub::new_and_setup(manager).await?;
+        let id = control_hub.me;
+        let (api_addr, p2p_addr) =
+            control_hub.resolve_addrs(api_addr, p2p_addr)?;
+        let population = control_hub.population;
+
+        // parse protocol-specific configs
+        let config = parsed_config!(config_str => ReplicaConfigMencius;
+                                    batch_interval_ms, max_batch_size,
+                                    idem_cache_size, session_idle_s,
+                                    ns_quota_kb,
+                                    backer_path, logger_sync,
+                                    hb_hear_timeout_min, hb_hear_timeout_max,
+                                    hb_send_interval_ms, disable_hb_timer,
+                                    snapshot_path, snapshot_interval_s)?;
+        if config.batch_interval_ms == 0 {
+            return logged_err!(
+                "invalid config.batch_interval_ms '{}'",
+                config.batch_interval_ms
+            );
+        }
+        if config.hb_hear_timeout_min == 0 {
+            return logged_err!(
+                "invalid config.hb_hear_timeout_min '{}'",
+                config.hb_hear_timeout_min
+            );
+        }
+        if config.hb_hear_timeout_max < config.hb_hear_timeout_min {
+            return logged_err!(
+                "invalid config.hb_hear_timeout_max '{}'",
+                config.hb_hear_timeout_max
+            );
+        }
+        if config.hb_send_interval_ms == 0 {
+            return logged_err!(
+                "invalid config.hb_send_interval_ms '{}'",
+                config.hb_send_interval_ms
+            );
+        }
+
+        // setup state machine module
+        let state_machine =
+            StateMachine::new_and_setup(id, config.ns_quota_kb * 1024).await?;
+        control_hub.inspect_keys(&state_machine);
+
+        // setup storage hub module
+        let storage_hub =
+            StorageHub::new_and_setup(id, Path::new(&config.backer_path))
+                .await?;
+
+        // setup heartbeat management module
+        let mut heartbeater = Heartbeater::new_and_setup(
+            id,
+            population,
+            Duration::from_millis(config.hb_hear_timeout_min),
+            Duration::from_millis(config.hb_hear_timeout_max),
+            Duration::from_millis(config.hb_send_interval_ms),
+        )?;
+        heartbeater.set_sending(true); // doing all-to-all heartbeating
+
+        // setup transport hub module
+        let mut transport_hub = TransportHub::new_and_setup(
+            id,
+            population,
+            SmrProtocol::Mencius,
+            p2p_addr,
+            false,
+            HashMap::new(), // no leases
+        )
+        .await?;
+
+        // ask for the list of peers to proactively connect to. Do this after
+        // transport hub has been set up, so that I will be able to accept
+        // later peer connections
+        control_hub.send_ctrl(CtrlMsg::NewServerJoin {
+            id,
+            protocol: SmrProtocol::Mencius,
+            api_addr,
+            p2p_addr,
+        })?;
+        let to_peers = if let CtrlMsg::ConnectToPeers { to_peers, .. } =
+            control_hub.recv_ctrl().await?
+        {
+            to_peers
+        } else {
+            return logged_err!(Protocol; "unexpected ctrl msg type received");
+        };
+
+        // proactively connect to some peers, then wait for all population
+        // have been connected with me
+        for (peer, conn_addr) in to_peers {
+            transport_hub.connect_to_peer(peer, conn_addr).await?;
+        }
+        transport_hub.wait_for_group(population).await?;
+
+        // setup snapshot hub module
+        let snapshot_hub =
+            StorageHub::new_and_setup(id, Path::new(&config.snapshot_path))
+                .await?;
+
+        // setup external API module, ready to take in client requests
+        let mut external_api = ExternalApi::new_and_setup(
+            id,
+            api_addr,
+            Duration::from_millis(config.batch_interval_ms),
+            config.max_batch_size,
+            config.idem_cache_size,
+            Duration::from_secs(config.session_idle_s),
+        )
+        .await?;
+        external_api.stamp_replies(&state_machine);
+
+        let mut snapshot_interval = time::interval(Duration::from_secs(
+            if config.snapshot_interval_s > 0 {
+                config.snapshot_interval_s
+            } else {
+                60 // dummy non-zero value to make `time::interval` happy
+            },
+        ));
+        snapshot_interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        Ok(MenciusReplica {
+            id,
+            population,
+            quorum_cnt: (population / 2) + 1,
+            config,
+            _api_addr: api_addr,
+            _p2p_addr: p2p_addr,
+            control_hub,
+            external_api,
+            state_machine,
+            storage_hub,
+            snapshot_hub,
+            transport_hub,
+            heartbeater,
+            insts: vec![],
+            start_slot: 0,
+            snapshot_interval,
+            commit_bar: 0,
+            exec_bar: 0,
+            next_slot: id as usize,
+            peer_exec_bar: (0..population)
+                .filter_map(|s| if s == id { None } else { Some((s, 0)) })
+                .collect(),
+            snap_bar: 0,
+            wal_offset: 0,
+            snap_offset: 0,
+        })
+    }
+
+    async fn run(
+        &mut self,
+        mut rx_term: watch::Receiver<bool>,
+    ) -> Result<bool, SummersetError> {
+        // recover state from durable snapshot file
+        self.recover_from_snapshot().await?;
+
+        // recover the tail-piece memory log & state from durable WAL log
+        self.recover_from_wal().await?;
+
+        // kick off peer heartbeats hearing timer
+        if !self.config.disable_hb_timer {
+            self.heartbeater.kickoff_hear_timer(None)?;
+        }
+
+        // main event loop
+        let mut paused = false;
+        loop {
+            tokio::select! {
+                // client request batch
+                req_batch = self.external_api.get_req_batch(), if !paused => {
+                    if let Err(e) = req_batch {
+                        pf_error!("error getting req batch: {}", e);
+                        continue;
+                    }
+                    let req_batch = req_batch.unwrap();
+                    if let Err(e) = self.handle_req_batch(req_batch).await {
+                        pf_error!("error handling req batch: {}", e);
+                    }
+                },
+
+                // durable logging result
+                log_result = self.storage_hub.get_result(), if !paused => {
+                    if let Err(e) = log_result {
+                        pf_error!("error getting log result: {}", e);
+                        continue;
+                    }
+                    let (action_id, log_result) = log_result.unwrap();
+                    if let Err(e) = self.handle_log_result(action_id, log_result).await {
+                        pf_error!("error handling log result {}: {}",
+                                           action_id, e);
+                    }
+                },
+
+                // message from peer
+                msg = self.transport_hub.recv_msg(), if !paused => {
+                    if let Err(_e) = msg {
+                        // NOTE: commented out to prevent console lags
+                        //       during benchmarking
+                        // pf_error!("error receiving peer msg: {}", e);
+                        continue;
+                    }
+                    let (peer, msg) = msg.unwrap();
+                    if let Err(e) = self.handle_msg_recv(peer, msg).await {
+                        pf_error!("error handling msg recv <- {}: {}", peer, e);
+                    }
+                },
+
+                // state machine execution result
+                cmd_result = self.state_machine.get_result(), if !paused => {
+                    if let Err(e) = cmd_result {
+                        pf_error!("error getting cmd result: {}", e);
+                        continue;
+                    }
+                    let (cmd_id, cmd_result) = cmd_result.unwrap();
+                    if let Err(e) = self.handle_cmd_result(cmd_id, cmd_result).await {
+                        pf_error!("error handling cmd result {}: {}", cmd_id, e);
+                    }
+                },
+
+                // heartbeat-related event
+                hb_event = self.heartbeater.get_event(), if !paused => {
+                    if let Err(e) = hb_event {
+                        pf_error!("error getting heartbeat event: {}", e);
+                        continue;
+                    }
+                    match hb_event.unwrap() {
+                        HeartbeatEvent::HearTimeout { peer } => {
+                            if let Err(e) = self.heartbeat_timeout(peer).await {
+                                pf_error!("error taking care of hb_hear timeout: {}", e);
+                            }
+                        }
+                        HeartbeatEvent::SendTicked => {
+                            if let Err(e) = self.bcast_heartbeats().await {
+                                pf_error!("error broadcasting heartbeats: {}", e);
+                            }
+                        }
+                    }
+                },
+
+                // autonomous snapshot taking timeout
+                _ = self.snapshot_interval.tick(), if !paused
+                                                      && self.config.snapshot_interval_s > 0 => {
+                    if let Err(e) = self.take_new_snapshot().await {
+                        pf_error!("error taking a new snapshot: {}", e);
+                    } else {
+                        self.control_hub.send_ctrl(
+                            CtrlMsg::SnapshotUpTo { new_start: self.start_slot }
+                        )?;
+                    }
+                },
+
+                // manager control message
+                ctrl_msg = self.control_hub.recv_ctrl() => {
+                    if let Err(e) = ctrl_msg {
+                        pf_error!("error getting ctrl msg: {}", e);
+                        continue;
+                    }
+                    let ctrl_msg = ctrl_msg.unwrap();
+                    match self.handle_ctrl_msg(ctrl_msg, &mut paused).await {
+                        Ok(terminate) => {
+                            if let Some(restart) = terminate {
+                                return Ok(restart);
+                            }
+                        },
+                        Err(e) => {
+                            pf_error!("error handling ctrl msg: {}", e);
+                        }
+                    }
+                },
+
+                // receiving termination signal
+                _ = rx_term.changed() => {
+                    pf_warn!("server caught termination signal");
+                    return Ok(false);
+                }
+            }
+        }
+    }
+
+    fn id(&self) -> ReplicaId {
+        self.id
+    }
+
+    fn population(&self) -> u16 {
+        self.population
+    }
+}
+
+/// Configuration parameters struct.
+#[derive(Debug, Deserialize)]
+pub struct ClientConfigMencius {
+    /// App-designated nearest server ID to send requests to.
+    pub near_server_id: ReplicaId,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for ClientConfigMencius {
+    fn default() -> Self {
+        ClientConfigMencius { near_server_id: 0 }
+    }
+}
+
+/// Mencius client-side module.
+pub(crate) struct MenciusClient {
+    /// Client ID.
+    id: ClientId,
+
+    /// Number of servers in the cluster.
+    population: u16,
+
+    /// Configuration parameters struct.
+    config: ClientConfigMencius,
+
+    /// List of active servers information.
+    servers: HashMap<ReplicaId, SocketAddr>,
+
+    /// App-designated nearest server ID to send requests to. Could become
+    /// different from `config.near_server_id` if the latter is deemed inactive.
+    server_id: ReplicaId,
+
+    /// Control API stub to the cluster manager.
+    ctrl_stub: ClientCtrlStub,
+
+    /// API stubs for communicating with servers.
+    api_stubs: HashMap<ReplicaId, ClientApiStub>,
+}
+
+#[async_trait]
+impl GenericEndpoint for MenciusClient {
+    async fn new_and_setup(
+        manager: SocketAddr,
+        config_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        // connect to the cluster manager and get assigned a client ID
+        pf_debug!("connecting to manager '{}'...", manager);
+        let ctrl_stub = ClientCtrlStub::new_by_connect(manager).await?;
+        let id = ctrl_stub.id;
+
+        // parse protocol-specific configs
+        let config = parsed_config!(config_str => ClientConfigMencius;
+                                    near_server_id)?;
+        let server_id = config.near_server_id;
+
+        Ok(MenciusClient {
+            id,
+            population: 0,
+            config,
+            servers: HashMap::new(),
+            server_id,
+            ctrl_stub,
+            api_stubs: HashMap::new(),
+        })
+    }
+
+    async fn connect(&mut self) -> Result<(), SummersetError> {
+        // disallow reconnection without leaving
+        if !self.api_stubs.is_empty() {
+            return logged_err!("reconnecting without leaving");
+        }
+
+        // ask the manager about the list of active servers
+        let mut sent =
+            self.ctrl_stub.send_req(Some(&CtrlRequest::QueryInfo))?;
+        while !sent {
+            sent = self.ctrl_stub.send_req(None)?;
+        }
+
+        let reply = self.ctrl_stub.recv_reply().await?;
+        match reply {
+            CtrlReply::QueryInfo {
+                population,
+                servers_info,
+            } => {
+                self.population = population;
+
+                // shift to a new server_id if nearest one not active
+                debug_assert!(!servers_info.is_empty());
+                while !servers_info.contains_key(&self.server_id)
+                    || servers_info[&self.server_id].is_paused
+                {
+                    self.server_id = (self.server_id + 1) % population;
+                }
+                if self.server_id != self.config.near_server_id {
+                    pf_warn!(
+                        "near server {} inactive, using {} instead...",
+                        self.config.near_server_id,
+                        self.server_id
+                    );
+                }
+                // establish connection to all servers
+                self.servers = servers_info
+                    .into_iter()
+                    .map(|(id, info)| (id, info.api_addr))
+                    .collect();
+                for (&id, &server) in &self.servers {
+                    pf_debug!("connecting to server {} '{}'...", id, server);
+                    let api_stub =
+                        ClientApiStub::new_by_connect(self.id, server).await?;
+                    self.api_stubs.insert(id, api_stub);
+                }
+                Ok(())
+            }
+            _ => logged_err!(Protocol; "unexpected reply type received"),
+        }
+    }
+
+    async fn leave(&mut self, permanent: bool) -> Result<(), SummersetError> {
+        // send leave notification to all servers
+        for (id, mut api_stub) in self.api_stubs.drain() {
+            let mut sent = api_stub.send_req(Some(&ApiRequest::Leave))?;
+            while !sent {
+                sent = api_stub.send_req(None)?;
+            }
+
+            // NOTE: commented out the following wait to avoid accidental
+            //       hanging upon leaving
+            // while api_stub.recv_reply().await? != ApiReply::Leave {}
+            pf_debug!("left server connection {}", id);
+        }
+
+        // if permanently leaving, send leave notification to the manager
+        if permanent {
+            let mut sent =
+                self.ctrl_stub.send_req(Some(&CtrlRequest::Leave))?;
+            while !sent {
+                sent = self.ctrl_stub.send_req(None)?;
+            }
+
+            while self.ctrl_stub.recv_reply().await? != CtrlReply::Leave {}
+            pf_debug!("left manager connection");
+        }
+
+        Ok(())
+    }
+
+    fn send_req(
+        &mut self,
+        req: Option<&ApiRequest>,
+    ) -> Result<bool, SummersetError> {
+        if self.api_stubs.contains_key(&self.server_id) {
+            self.api_stubs
+                .get_mut(&self.server_id)
+                .unwrap()
+                .send_req(req)
+        } else {
+            Err(SummersetError::msg(format!(
+                "server_id {} not in api_stubs",
+                self.server_id
+            )))
+        }
+    }
+
+    async fn recv_reply(&mut self) -> Result<ApiReply, SummersetError> {
+        if self.api_stubs.contains_key(&self.server_id) {
+            let reply = self
+                .api_stubs
+                .get_mut(&self.server_id)
+                .unwrap()
+                .recv_reply()
+                .await?;
+
+            if let ApiReply::Reply {
+                ref result,
+                ref redirect,
+                ..
+            } = reply
+            {
+                // if the current server redirects me to a different server
+                if result.is_none() && redirect.is_some() {
+                    let redirect_id = redirect.unwrap();
+                    debug_assert!(self.servers.contains_key(&redirect_id));
+                    self.server_id = redirect_id;
+                    pf_debug!(
+                        "redirected to replica {} '{}'",
+                        redirect_id,
+                        self.servers[&redirect_id]
+                    );
+                }
+            }
+
+            Ok(reply)
+        } else {
+            Err(SummersetError::msg(format!(
+                "server_id {} not in api_stubs",
+                self.server_id
+            )))
+        }
+    }
+
+    fn id(&self) -> ClientId {
+        self.id
+    }
+
+    fn population(&self) -> u16 {
+        self.population
+    }
+
+    fn ctrl_stub(&mut self) -> &mut ClientCtrlStub {
+        &mut self.ctrl_stub
+    }
+}
@@ -0,0 +1,138 @@
+//! Mencius -- recovery from WAL.
+
+use super::*;
+
+use crate::server::{LogAction, LogResult};
+use crate::utils::SummersetError;
+
+// MenciusReplica recovery from WAL log
+impl MenciusReplica {
+    /// Apply a durable storage log entry for recovery.
+    async fn recover_apply_entry(
+        &mut self,
+        entry: WalEntry,
+    ) -> Result<(), SummersetError> {
+        match entry {
+            WalEntry::AcceptSlot { slot, ballot, reqs } => {
+                if slot < self.start_slot {
+                    return Ok(()); // ignore if slot index outdated
+                }
+                // locate instance in memory, filling in null instances if needed
+                self.fill_null_insts(slot);
+                // update instance state
+                let inst = &mut self.insts[slot - self.start_slot];
+                inst.bal = ballot;
+                inst.status = Status::Accepting;
+                inst.reqs.clone_from(&reqs);
+                inst.voted = (ballot, reqs);
+            }
+
+            WalEntry::CommitSlot { slot, ballot, reqs } => {
+                if slot < self.start_slot {
+                    return Ok(()); // ignore if slot index outdated
+                }
+                // locate instance in memory, filling in null instances if needed
+                self.fill_null_insts(slot);
+                // update instance state
+                let inst = &mut self.insts[slot - self.start_slot];
+                inst.bal = ballot;
+                inst.status = Status::Committed;
+                inst.reqs = reqs;
+                // submit commands in contiguously committed instances to the
+                // state machine
+                if slot == self.commit_bar {
+                    while self.commit_bar < self.start_slot + self.insts.len() {
+                        let inst =
+                            &self.insts[self.commit_bar - self.start_slot];
+                        if inst.status < Status::Committed {
+                            break;
+                        }
+                        // execute all commands in this instance on state
+                        // machine synchronously
+                        for (_, req) in inst.reqs.clone() {
+                            if let ApiRequest::Req { cmd, .. } = req {
+                                // using 0 as a special command ID
+                                self.state_machine.do_sync_cmd(0, cmd).await?;
+                            }
+                        }
+                        // update commit_bar and exec_bar
+                        self.insts[self.commit_bar - self.start_slot].status =
+                            Status::Executed;
+                        self.commit_bar += 1;
+                        self.exec_bar += 1;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Recover state from durable storage WAL log.
+    pub(super) async fn recover_from_wal(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        debug_assert_eq!(self.wal_offset, 0);
+        loop {
+            match self
+                .storage_hub
+                .do_sync_action(
+                    0, // using 0 as dummy log action ID
+                    LogAction::Read {
+                        offset: self.wal_offset,
+                    },
+                )
+                .await?
+                .1
+            {
+                LogResult::Read {
+                    entry: Some(entry),
+                    end_offset,
+                } => {
+                    self.recover_apply_entry(entry).await?;
+                    // update log offset
+                    self.wal_offset = end_offset;
+                }
+                LogResult::Read { entry: None, .. } => {
+                    // end of log reached
+                    break;
+                }
+                _ => {
+                    return logged_err!(Protocol; "unexpected log result type");
+                }
+            }
+        }
+
+        // never propose in any slot of mine that is already in the log
+        self.next_slot =
+            self.owned_slot_from(self.id, self.start_slot + self.insts.len());
+
+        // do an extra Truncate to remove partial entry at the end if any
+        if let LogResult::Truncate {
+            offset_ok: true, ..
+        } = self
+            .storage_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Truncate {
+                    offset: self.wal_offset,
+                },
+            )
+            .await?
+            .1
+        {
+            if self.wal_offset > 0 {
+                pf_info!(
+                    "recovered from wal log: commit {} exec {}",
+                    self.commit_bar,
+                    self.exec_bar
+                );
+            }
+            Ok(())
+        } else {
+            logged_err!(
+                Protocol; "unexpected log result type or failed truncate"
+            )
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Mencius -- client request entrance.
+
+use super::*;
+
+use crate::server::LogAction;
+use crate::utils::{Bitmap, SummersetError};
+
+// MenciusReplica client requests entrance
+impl MenciusReplica {
+    /// Handler of client request batch chan recv.
+    pub(super) async fn handle_req_batch(
+        &mut self,
+        req_batch: ReqBatch,
+    ) -> Result<(), SummersetError> {
+        let batch_size = req_batch.len();
+        debug_assert!(batch_size > 0);
+
+        // propose in the next slot owned by me, as its default leader,
+        // stepping over any that a revoker has already started preparing
+        let mut slot = self.next_slot;
+        loop {
+            self.fill_null_insts(slot);
+            if self.insts[slot - self.start_slot].bal == 0 {
+                break;
+            }
+            slot += self.population as usize;
+        }
+        self.next_slot = slot + self.population as usize;
+        pf_debug!("got request batch of size {} for slot {}", batch_size, slot);
+
+        let ballot = Self::make_default_ballot(self.id);
+        let inst = &mut self.insts[slot - self.start_slot];
+        debug_assert_eq!(inst.status, Status::Null);
+        inst.bal = ballot;
+        inst.status = Status::Accepting;
+        inst.reqs.clone_from(&req_batch);
+        inst.voted = (ballot, req_batch.clone());
+        inst.leader_bk = Some(LeaderBookkeeping {
+            prepare_acks: Bitmap::new(self.population, false),
+            prepare_max_bal: 0,
+            prepare_voted: None,
+            accept_acks: Bitmap::new(self.population, false),
+        });
+        inst.external = true;
+
+        // record update to instance status & data
+        self.storage_hub.submit_action(
+            Self::make_log_action_id(slot, Status::Accepting),
+            LogAction::Append {
+                entry: WalEntry::AcceptSlot {
+                    slot,
+                    ballot,
+                    reqs: req_batch.clone(),
+                },
+                sync: self.config.logger_sync,
+            },
+        )?;
+        pf_trace!(
+            "submitted AcceptSlot log action for slot {} bal {}",
+            slot,
+            ballot
+        );
+
+        // broadcast Accept messages to all peers
+        self.transport_hub.bcast_msg(
+            PeerMsg::Accept {
+                slot,
+                ballot,
+                reqs: req_batch,
+            },
+            None,
+        )?;
+        pf_trace!("broadcast Accept messages for slot {} bal {}", slot, ballot);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,350 @@
+//! Mencius -- snapshotting & GC.
+
+use std::cmp;
+
+use super::*;
+
+use crate::manager::CtrlMsg;
+use crate::server::{LogAction, LogResult};
+use crate::utils::SummersetError;
+
+// MenciusReplica snapshotting & GC logic
+impl MenciusReplica {
+    /// Dump an image of the state machine to the end of snapshot file.
+    /// Returns the slot it is taken at: the image reflects exactly the
+    /// commands of all slots before it.
+    async fn snapshot_dump_state(&mut self) -> Result<usize, SummersetError> {
+        // handle results of all commands submitted before taking the image,
+        // so that exec_bar then stands exactly where the image is taken
+        let unacked = self.state_machine.num_unacked();
+        let image = self.state_machine.take_snapshot().await?;
+        for _ in 0..unacked {
+            let (cmd_id, cmd_result) = self.state_machine.get_result().await?;
+            self.handle_cmd_result(cmd_id, cmd_result).await?;
+        }
+
+        // write the image to snapshot file
+        if let LogResult::Append { now_size } = self
+            .snapshot_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Append {
+                    entry: SnapEntry::StateImage {
+                        image: image.to_vec(),
+                    },
+                    sync: self.config.logger_sync,
+                },
+            )
+            .await?
+            .1
+        {
+            self.snap_offset = now_size;
+            Ok(self.exec_bar)
+        } else {
+            logged_err!(Protocol; "unexpected log result type")
+        }
+    }
+
+    /// Discard the entries between the slot info head and given offset in
+    /// snapshot file, i.e., those of the previous image.
+    async fn snapshot_discard_old(
+        &mut self,
+        offset: usize,
+    ) -> Result<(), SummersetError> {
+        let head_len = match self
+            .snapshot_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Read { offset: 0 },
+            )
+            .await?
+            .1
+        {
+            LogResult::Read {
+                entry: Some(SnapEntry::SlotInfo { .. }),
+                end_offset,
+            } => end_offset,
+            _ => {
+                return logged_err!(Protocol; "unexpected log result type");
+            }
+        };
+        if offset <= head_len {
+            return Ok(()); // nothing to discard
+        }
+
+        if let LogResult::Discard {
+            offset_ok: true,
+            now_size,
+        } = self
+            .snapshot_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Discard {
+                    offset,
+                    keep: head_len,
+                },
+            )
+            .await?
+            .1
+        {
+            self.snap_offset = now_size;
+            Ok(())
+        } else {
+            logged_err!(
+                Protocol; "unexpected log result type or failed discard"
+            )
+        }
+    }
+
+    /// Discard everything older than start_slot in durable WAL log.
+    async fn snapshot_discard_log(&mut self) -> Result<(), SummersetError> {
+        // do a dummy sync read to force all previously submitted log actions
+        // to be processed
+        let (old_results, _) = self
+            .storage_hub
+            .do_sync_action(0, LogAction::Read { offset: 0 })
+            .await?;
+        for (old_id, old_result) in old_results {
+            self.handle_log_result(old_id, old_result).await?;
+        }
+
+        // get offset to cut the WAL at
+        let cut_offset = if !self.insts.is_empty() {
+            self.insts[0].wal_offset
+        } else {
+            self.wal_offset
+        };
+
+        // discard the log before cut_offset
+        if cut_offset > 0 {
+            if let LogResult::Discard {
+                offset_ok: true,
+                now_size,
+            } = self
+                .storage_hub
+                .do_sync_action(
+                    0, // using 0 as dummy log action ID
+                    LogAction::Discard {
+                        offset: cut_offset,
+                        keep: 0,
+                    },
+                )
+                .await?
+                .1
+            {
+                debug_assert_eq!(self.wal_offset - cut_offset, now_size);
+                self.wal_offset = now_size;
+            } else {
+                return logged_err!(
+                    Protocol; "unexpected log result type or failed discard"
+                );
+            }
+        }
+
+        // update inst.wal_offset for all remaining in-mem instances
+        for inst in &mut self.insts {
+            if inst.wal_offset > 0 {
+                debug_assert!(inst.wal_offset >= cut_offset);
+                inst.wal_offset -= cut_offset;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Take a snapshot up to current exec_bar, then discard the in-mem log up
+    /// to that index as well as outdate entries in the durable WAL log file.
+    //
+    // NOTE: the current implementation does not guard against crashes in the
+    //       middle of taking a snapshot. Production quality implementations
+    //       should make the snapshotting action "atomic".
+    //
+    // NOTE: the current implementation does not take care of InstallSnapshot
+    //       messages (which is needed when some lagging follower has some slot
+    //       which all other peers have snapshotted); we assume here that failed
+    //       Accept messages will be retried indefinitely until success before
+    //       its associated data gets discarded from peer's memory.
+    pub(super) async fn take_new_snapshot(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        pf_debug!(
+            "taking new snapshot: start {} exec {} snap {}",
+            self.start_slot,
+            self.exec_bar,
+            self.snap_bar
+        );
+        debug_assert!(self.exec_bar >= self.start_slot);
+
+        if cmp::min(self.snap_bar, self.exec_bar) == self.start_slot {
+            return Ok(());
+        }
+
+        // NOTE: broadcast heartbeats here to appease peers
+        self.bcast_heartbeats().await?;
+
+        // dump an image of the state machine after the previous one
+        let old_offset = self.snap_offset;
+        let snap_start = self.snapshot_dump_state().await?;
+        let new_start_slot = cmp::min(self.snap_bar, snap_start);
+
+        // write new slot info entry to the head of snapshot, then discard the
+        // previous image
+        match self
+            .snapshot_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Write {
+                    entry: SnapEntry::SlotInfo {
+                        start_slot: snap_start,
+                    },
+                    offset: 0,
+                    sync: self.config.logger_sync,
+                },
+            )
+            .await?
+            .1
+        {
+            LogResult::Write {
+                offset_ok: true, ..
+            } => {}
+            _ => {
+                return logged_err!(
+                    Protocol; "unexpected log result type or failed write"
+                );
+            }
+        }
+        self.snapshot_discard_old(old_offset).await?;
+
+        // update start_slot and discard all in-memory log instances up to exec_bar
+        self.insts.drain(0..(new_start_slot - self.start_slot));
+        self.start_slot = new_start_slot;
+
+        // NOTE: broadcast heartbeats here to appease peers
+        self.bcast_heartbeats().await?;
+
+        // discarding everything older than start_slot in WAL log
+        self.snapshot_discard_log().await?;
+
+        // reset the heartbeat hearing timer
+        if !self.config.disable_hb_timer {
+            self.heartbeater.kickoff_hear_timer(None)?;
+        }
+
+        pf_info!("took snapshot up to: start {}", self.start_slot);
+        Ok(())
+    }
+
+    /// Recover initial state from durable storage snapshot file.
+    pub(super) async fn recover_from_snapshot(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        debug_assert_eq!(self.snap_offset, 0);
+
+        // first, try to read the first several bytes, which should record the
+        // start_slot index
+        match self
+            .snapshot_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Read { offset: 0 },
+            )
+            .await?
+            .1
+        {
+            LogResult::Read {
+                entry: Some(SnapEntry::SlotInfo { start_slot }),
+                end_offset,
+            } => {
+                self.snap_offset = end_offset;
+
+                // recover necessary slot indices info
+                self.start_slot = start_slot;
+                self.commit_bar = start_slot;
+                self.exec_bar = start_slot;
+                self.next_slot = self.owned_slot_from(self.id, start_slot);
+                self.snap_bar = start_slot;
+
+                // restore the state image recorded
+                loop {
+                    match self
+                        .snapshot_hub
+                        .do_sync_action(
+                            0, // using 0 as dummy log action ID
+                            LogAction::Read {
+                                offset: self.snap_offset,
+                            },
+                        )
+                        .await?
+                        .1
+                    {
+                        LogResult::Read {
+                            entry: Some(SnapEntry::StateImage { image }),
+                            end_offset,
+                        } => {
+                            self.state_machine
+                                .restore_snapshot(image.into())
+                                .await?;
+                            // update snapshot file offset
+                            self.snap_offset = end_offset;
+                        }
+                        LogResult::Read { entry: None, .. } => {
+                            // end of log reached
+                            break;
+                        }
+                        _ => {
+                            return logged_err!(
+                                Protocol; "unexpected log result type"
+                            );
+                        }
+                    }
+                }
+
+                // tell manager about my start_slot index
+                self.control_hub.send_ctrl(CtrlMsg::SnapshotUpTo {
+                    new_start: self.start_slot,
+                })?;
+
+                if self.start_slot > 0 {
+                    pf_info!(
+                        "recovered from snapshot: start {} commit {} exec {}",
+                        self.start_slot,
+                        self.commit_bar,
+                        self.exec_bar
+                    );
+                }
+                Ok(())
+            }
+
+            LogResult::Read { entry: None, .. } => {
+                // snapshot file is empty. Write a 0 as start_slot and return
+                if let LogResult::Write {
+                    offset_ok: true,
+                    now_size,
+                } = self
+                    .snapshot_hub
+                    .do_sync_action(
+                        0, // using 0 as dummy log action ID
+                        LogAction::Write {
+                            entry: SnapEntry::SlotInfo { start_slot: 0 },
+                            offset: 0,
+                            sync: self.config.logger_sync,
+                        },
+                    )
+                    .await?
+                    .1
+                {
+                    self.snap_offset = now_size;
+                    Ok(())
+                } else {
+                    logged_err!(
+                        Protocol; "unexpected log result type or failed write"
+                    )
+                }
+            }
+
+            _ => {
+                logged_err!(Protocol; "unexpected log result type")
+            }
+        }
+    }
+}
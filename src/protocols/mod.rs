@@ -2,14 +2,20 @@
 
 use std::fmt;
 use std::net::SocketAddr;
+use std::path::Path;
 
 use crate::client::GenericEndpoint;
 use crate::manager::ClusterManager;
 use crate::server::GenericReplica;
-use crate::utils::SummersetError;
+use crate::utils::{with_node_scope, SummersetError};
+
+use futures::stream::{FuturesUnordered, StreamExt};
 
 use serde::{Deserialize, Serialize};
 
+use tokio::sync::watch;
+use tokio::time::Duration;
+
 mod rep_nothing;
 pub use rep_nothing::{ClientConfigRepNothing, ReplicaConfigRepNothing};
 use rep_nothing::{RepNothingClient, RepNothingReplica};
@@ -22,6 +28,10 @@ mod chain_rep;
 use chain_rep::{ChainRepClient, ChainRepReplica};
 pub use chain_rep::{ClientConfigChainRep, ReplicaConfigChainRep};
 
+mod craq;
+use craq::{CRAQClient, CRAQReplica};
+pub use craq::{ClientConfigCRAQ, ReplicaConfigCRAQ};
+
 mod multipaxos;
 pub use multipaxos::{ClientConfigMultiPaxos, ReplicaConfigMultiPaxos};
 use multipaxos::{MultiPaxosClient, MultiPaxosReplica};
@@ -54,12 +64,37 @@ mod bodega;
 use bodega::{BodegaClient, BodegaReplica};
 pub use bodega::{ClientConfigBodega, ReplicaConfigBodega};
 
+mod mencius;
+pub use mencius::{ClientConfigMencius, ReplicaConfigMencius};
+use mencius::{MenciusClient, MenciusReplica};
+
+mod fast_paxos;
+pub use fast_paxos::{ClientConfigFastPaxos, ReplicaConfigFastPaxos};
+use fast_paxos::{FastPaxosClient, FastPaxosReplica};
+
+mod curp;
+use curp::{CURPClient, CURPReplica};
+pub use curp::{ClientConfigCURP, ReplicaConfigCURP};
+
+mod vr;
+pub use vr::{ClientConfigVR, ReplicaConfigVR};
+use vr::{VRClient, VRReplica};
+
+mod zab;
+pub use zab::{ClientConfigZab, ReplicaConfigZab};
+use zab::{ZabClient, ZabReplica};
+
+mod hermes;
+pub use hermes::{ClientConfigHermes, ReplicaConfigHermes};
+use hermes::{HermesClient, HermesReplica};
+
 /// Enum of supported replication protocol types.
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub enum SmrProtocol {
     RepNothing,
     SimplePush,
     ChainRep,
+    CRAQ,
     MultiPaxos,
     EPaxos,
     RSPaxos,
@@ -68,6 +103,72 @@ pub enum SmrProtocol {
     Crossword,
     QuorumLeases,
     Bodega,
+    Mencius,
+    FastPaxos,
+    CURP,
+    VR,
+    Zab,
+    Hermes,
+}
+
+/// Report of an offline consistency check of a replica's durable files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FsckReport {
+    /// Informational findings.
+    pub notes: Vec<String>,
+
+    /// Integrity problems found.
+    pub problems: Vec<String>,
+
+    /// Repairs applied.
+    pub repairs: Vec<String>,
+
+    /// Number of problems not yet repaired.
+    unrepaired: usize,
+}
+
+impl FsckReport {
+    /// Records an informational finding.
+    pub(crate) fn note(&mut self, msg: String) {
+        self.notes.push(msg);
+    }
+
+    /// Records an integrity problem.
+    pub(crate) fn problem(&mut self, msg: String) {
+        self.problems.push(msg);
+        self.unrepaired += 1;
+    }
+
+    /// Records an applied repair of the latest problem.
+    pub(crate) fn repair(&mut self, msg: String) {
+        self.repairs.push(msg);
+        self.unrepaired -= 1;
+    }
+
+    /// Are the files clean, or have all problems been repaired?
+    pub fn is_clean(&self) -> bool {
+        self.unrepaired == 0
+    }
+}
+
+impl fmt::Display for FsckReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for note in &self.notes {
+            writeln!(f, "  note:    {}", note)?;
+        }
+        for problem in &self.problems {
+            writeln!(f, "  PROBLEM: {}", problem)?;
+        }
+        for repair in &self.repairs {
+            writeln!(f, "  repair:  {}", repair)?;
+        }
+        write!(
+            f,
+            "{} problem(s) found, {} repaired",
+            self.problems.len(),
+            self.repairs.len()
+        )
+    }
 }
 
 /// Helper macro for saving boilder-plate `Box<dyn ..>` mapping in
@@ -86,6 +187,7 @@ impl SmrProtocol {
             "RepNothing" => Some(Self::RepNothing),
             "SimplePush" => Some(Self::SimplePush),
             "ChainRep" => Some(Self::ChainRep),
+            "CRAQ" => Some(Self::CRAQ),
             "MultiPaxos" => Some(Self::MultiPaxos),
             "EPaxos" => Some(Self::EPaxos),
             "RSPaxos" => Some(Self::RSPaxos),
@@ -94,19 +196,100 @@ impl SmrProtocol {
             "Crossword" => Some(Self::Crossword),
             "QuorumLeases" => Some(Self::QuorumLeases),
             "Bodega" => Some(Self::Bodega),
+            "Mencius" => Some(Self::Mencius),
+            "FastPaxos" => Some(Self::FastPaxos),
+            "CURP" => Some(Self::CURP),
+            "VR" => Some(Self::VR),
+            "Zab" => Some(Self::Zab),
+            "Hermes" => Some(Self::Hermes),
             _ => None,
         }
     }
 
+    /// Current version of this protocol's peer-peer message format. Bump it
+    /// whenever the protocol's `PeerMsg` type changes incompatibly.
+    pub fn wire_version(&self) -> u16 {
+        match self {
+            Self::RepNothing
+            | Self::SimplePush
+            | Self::ChainRep
+            | Self::CRAQ
+            | Self::EPaxos
+            | Self::RSPaxos
+            | Self::Raft
+            | Self::CRaft
+            | Self::Crossword
+            | Self::QuorumLeases
+            | Self::Bodega
+            | Self::Mencius
+            | Self::FastPaxos
+            | Self::CURP
+            | Self::VR
+            | Self::Zab
+            | Self::Hermes => 1,
+            Self::MultiPaxos => 2,
+        }
+    }
+
+    /// Oldest peer-peer message format version of this protocol that a
+    /// replica of the current version can still talk to. Raise it when
+    /// dropping support for speaking an older format.
+    pub fn min_wire_version(&self) -> u16 {
+        self.wire_version()
+    }
+
+    /// Does this protocol elect a leader that clients need to find before
+    /// the service is usable?
+    pub fn elects_leader(&self) -> bool {
+        match self {
+            Self::RepNothing
+            | Self::SimplePush
+            | Self::ChainRep
+            | Self::CRAQ
+            | Self::EPaxos
+            | Self::Mencius
+            | Self::FastPaxos
+            | Self::CURP
+            | Self::Hermes => false,
+            Self::MultiPaxos
+            | Self::RSPaxos
+            | Self::Raft
+            | Self::CRaft
+            | Self::Crossword
+            | Self::QuorumLeases
+            | Self::Bodega
+            | Self::VR
+            | Self::Zab => true,
+        }
+    }
+
     /// Create the cluster manager for this protocol.
+    #[allow(clippy::too_many_arguments)]
     pub async fn new_cluster_manager_setup(
         &self,
         srv_addr: SocketAddr,
         cli_addr: SocketAddr,
-        population: u8,
+        http_addr: Option<SocketAddr>,
+        event_log_path: Option<&Path>,
+        cluster_ca_path: Option<&Path>,
+        seed_image_path: Option<&Path>,
+        port_range: Option<(u16, u16)>,
+        population: u16,
+        client_grace: Duration,
     ) -> Result<ClusterManager, SummersetError> {
-        ClusterManager::new_and_setup(*self, srv_addr, cli_addr, population)
-            .await
+        ClusterManager::new_and_setup(
+            *self,
+            srv_addr,
+            cli_addr,
+            http_addr,
+            event_log_path,
+            cluster_ca_path,
+            seed_image_path,
+            port_range,
+            population,
+            client_grace,
+        )
+        .await
     }
 
     /// Create a server replica instance of this protocol on heap.
@@ -142,6 +325,14 @@ impl SmrProtocol {
                     .await
                 )
             }
+            Self::CRAQ => {
+                box_if_ok!(
+                    CRAQReplica::new_and_setup(
+                        api_addr, p2p_addr, manager, config_str
+                    )
+                    .await
+                )
+            }
             Self::MultiPaxos => {
                 box_if_ok!(
                     MultiPaxosReplica::new_and_setup(
@@ -206,6 +397,153 @@ impl SmrProtocol {
                     .await
                 )
             }
+            Self::Mencius => {
+                box_if_ok!(
+                    MenciusReplica::new_and_setup(
+                        api_addr, p2p_addr, manager, config_str
+                    )
+                    .await
+                )
+            }
+            Self::FastPaxos => {
+                box_if_ok!(
+                    FastPaxosReplica::new_and_setup(
+                        api_addr, p2p_addr, manager, config_str
+                    )
+                    .await
+                )
+            }
+            Self::CURP => {
+                box_if_ok!(
+                    CURPReplica::new_and_setup(
+                        api_addr, p2p_addr, manager, config_str
+                    )
+                    .await
+                )
+            }
+            Self::VR => {
+                box_if_ok!(
+                    VRReplica::new_and_setup(
+                        api_addr, p2p_addr, manager, config_str
+                    )
+                    .await
+                )
+            }
+            Self::Zab => {
+                box_if_ok!(
+                    ZabReplica::new_and_setup(
+                        api_addr, p2p_addr, manager, config_str
+                    )
+                    .await
+                )
+            }
+            Self::Hermes => {
+                box_if_ok!(
+                    HermesReplica::new_and_setup(
+                        api_addr, p2p_addr, manager, config_str
+                    )
+                    .await
+                )
+            }
+        }
+    }
+
+    /// Run several server replicas of this protocol concurrently in the
+    /// current runtime, e.g., to host a whole cluster in one process for
+    /// low-overhead experiments. Each replica is given as its API address,
+    /// p2p address, and optional config string, and logs under its own
+    /// identity. Returns `Ok(true)` as soon as any replica wants to restart,
+    /// in which case the caller should drop the runtime and start the group
+    /// anew, or `Ok(false)` once all replicas terminated without wanting to.
+    pub async fn run_colocated_replicas(
+        &self,
+        replicas: Vec<(SocketAddr, SocketAddr, Option<String>)>,
+        manager: SocketAddr,
+        rx_term: watch::Receiver<bool>,
+    ) -> Result<bool, SummersetError> {
+        let mut running: FuturesUnordered<_> = replicas
+            .into_iter()
+            .map(|(api_addr, p2p_addr, config_str)| {
+                let rx_term = rx_term.clone();
+                with_node_scope(async move {
+                    let mut replica = self
+                        .new_server_replica_setup(
+                            api_addr,
+                            p2p_addr,
+                            manager,
+                            config_str.as_deref(),
+                        )
+                        .await?;
+                    replica.run(rx_term).await
+                })
+            })
+            .collect();
+
+        while let Some(result) = running.next().await {
+            if result? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Check (and optionally repair) the durable files of a server replica of
+    /// this protocol offline, without joining the cluster.
+    pub async fn fsck_server_files(
+        &self,
+        config_str: Option<&str>,
+        repair: bool,
+    ) -> Result<FsckReport, SummersetError> {
+        match self {
+            Self::MultiPaxos => {
+                MultiPaxosReplica::fsck_files(config_str, repair).await
+            }
+            _ => logged_err!("fsck not supported for protocol {}", self),
+        }
+    }
+
+    /// Export the durable files of a server replica of this protocol as a
+    /// human-readable JSON string.
+    pub async fn export_server_files(
+        &self,
+        config_str: Option<&str>,
+    ) -> Result<String, SummersetError> {
+        match self {
+            Self::MultiPaxos => {
+                MultiPaxosReplica::export_files(config_str).await
+            }
+            _ => logged_err!("export not supported for protocol {}", self),
+        }
+    }
+
+    /// Import a JSON string produced by `export_server_files()`, overwriting
+    /// the durable files of a server replica of this protocol.
+    pub async fn import_server_files(
+        &self,
+        config_str: Option<&str>,
+        json: &str,
+    ) -> Result<(), SummersetError> {
+        match self {
+            Self::MultiPaxos => {
+                MultiPaxosReplica::import_files(config_str, json).await
+            }
+            _ => logged_err!("import not supported for protocol {}", self),
+        }
+    }
+
+    /// Replay the durable files of a server replica of this protocol offline,
+    /// logging every protocol decision and executed command, optionally
+    /// stopping after executing slot `until`.
+    pub async fn replay_server_files(
+        &self,
+        config_str: Option<&str>,
+        until: Option<usize>,
+    ) -> Result<(), SummersetError> {
+        match self {
+            Self::MultiPaxos => {
+                MultiPaxosReplica::replay_files(config_str, until).await
+            }
+            _ => logged_err!("replay not supported for protocol {}", self),
         }
     }
 
@@ -231,6 +569,9 @@ impl SmrProtocol {
                     ChainRepClient::new_and_setup(manager, config_str).await
                 )
             }
+            Self::CRAQ => {
+                box_if_ok!(CRAQClient::new_and_setup(manager, config_str).await)
+            }
             Self::MultiPaxos => {
                 box_if_ok!(
                     MultiPaxosClient::new_and_setup(manager, config_str).await
@@ -270,6 +611,30 @@ impl SmrProtocol {
                     BodegaClient::new_and_setup(manager, config_str).await
                 )
             }
+            Self::Mencius => {
+                box_if_ok!(
+                    MenciusClient::new_and_setup(manager, config_str).await
+                )
+            }
+            Self::FastPaxos => {
+                box_if_ok!(
+                    FastPaxosClient::new_and_setup(manager, config_str).await
+                )
+            }
+            Self::CURP => {
+                box_if_ok!(CURPClient::new_and_setup(manager, config_str).await)
+            }
+            Self::VR => {
+                box_if_ok!(VRClient::new_and_setup(manager, config_str).await)
+            }
+            Self::Zab => {
+                box_if_ok!(ZabClient::new_and_setup(manager, config_str).await)
+            }
+            Self::Hermes => {
+                box_if_ok!(
+                    HermesClient::new_and_setup(manager, config_str).await
+                )
+            }
         }
     }
 }
@@ -298,6 +663,7 @@ mod name_tests {
         valid_name_test!(RepNothing);
         valid_name_test!(SimplePush);
         valid_name_test!(ChainRep);
+        valid_name_test!(CRAQ);
         valid_name_test!(MultiPaxos);
         valid_name_test!(EPaxos);
         valid_name_test!(RSPaxos);
@@ -306,6 +672,12 @@ mod name_tests {
         valid_name_test!(Crossword);
         valid_name_test!(QuorumLeases);
         valid_name_test!(Bodega);
+        valid_name_test!(Mencius);
+        valid_name_test!(FastPaxos);
+        valid_name_test!(CURP);
+        valid_name_test!(VR);
+        valid_name_test!(Zab);
+        valid_name_test!(Hermes);
     }
 
     #[test]
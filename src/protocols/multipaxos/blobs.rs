@@ -0,0 +1,307 @@
+//! MultiPaxos -- blob store mode for large values.
+//!
+//! In blob store mode, the leader keeps the value of any large Put in its
+//! replica-local blob store and proposes the Put with only a reference to
+//! the blob in place of the value, so Accept messages and WAL entries stay
+//! small. Commands are resolved back into full values right before getting
+//! submitted to the state machine; if a blob is missing locally, execution
+//! stalls (in order) until the blob gets fetched from peers.
+//!
+//! The leader pushes each new blob to all peers ahead of the Accept carrying
+//! its reference, and a follower holds back its vote on an Accept until all
+//! blobs it references are durable locally (fetching them if needed), so a
+//! committed reference always has its blob on a quorum. Client values that
+//! would be mistaken for a reference are stashed as blobs as well. Blobs
+//! referenced only by slots covered by a snapshot get garbage collected.
+
+use super::*;
+
+use crate::server::{ApiRequest, BlobRef, Command};
+use crate::utils::SummersetError;
+
+// MultiPaxosReplica blob store mode logic
+impl MultiPaxosReplica {
+    /// Moves the values of large Puts in batch into my blob store, replacing
+    /// them with blob references, and pushes the new blobs to all peers.
+    /// Values that look like a reference are stashed regardless of size, so
+    /// that they resolve back into themselves.
+    pub(super) async fn stash_large_values(
+        &mut self,
+        req_batch: &mut ReqBatch,
+    ) -> Result<(), SummersetError> {
+        let Some(blob_store) = self.blob_store.as_mut() else {
+            return Ok(());
+        };
+        let threshold = self.config.blob_threshold_kb * 1024;
+        for (_, req) in req_batch.iter_mut() {
+            if let ApiRequest::Req {
+                cmd: Command::Put { value, .. },
+                ..
+            } = req
+            {
+                if value.len() > threshold || BlobRef::looks_like_ref(value) {
+                    let blob = blob_store.put(value).await?;
+                    let value = std::mem::replace(value, blob.encode());
+                    // ordered ahead of the Accept on each peer's channel
+                    self.transport_hub
+                        .bcast_msg(PeerMsg::BlobReply { blob, value }, None)?;
+                    pf_trace!("broadcast BlobReply messages for blob {}", blob);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Records the blobs referenced by the batch at slot as in use.
+    pub(super) fn note_blob_uses(
+        slot: usize,
+        reqs: &ReqBatch,
+        blob_store: &mut Option<BlobStore>,
+    ) {
+        let Some(blob_store) = blob_store.as_mut() else {
+            return;
+        };
+        for (_, req) in reqs {
+            if let ApiRequest::Req {
+                cmd: Command::Put { value, .. },
+                ..
+            } = req
+            {
+                if let Some(blob) = BlobRef::parse(value) {
+                    blob_store.note_use(blob, slot);
+                }
+            }
+        }
+    }
+
+    /// Returns the blobs referenced by the batch that are missing locally.
+    pub(super) fn missing_blobs_of(&self, reqs: &ReqBatch) -> Vec<BlobRef> {
+        let mut blobs = vec![];
+        for (_, req) in reqs {
+            if let ApiRequest::Req { cmd, .. } = req {
+                if let Some(blob) = self.missing_blob(cmd) {
+                    if !blobs.contains(&blob) {
+                        blobs.push(blob);
+                    }
+                }
+            }
+        }
+        blobs
+    }
+
+    /// Holds back my vote on an Accept whose batch references blobs missing
+    /// locally, requesting them from the peer that sent it. Returns `false`
+    /// if nothing is missing.
+    pub(super) fn hold_accept_for_blobs(
+        &mut self,
+        peer: ReplicaId,
+        slot: usize,
+        ballot: Ballot,
+        reqs: &ReqBatch,
+        hlc: HlcStamp,
+    ) -> Result<bool, SummersetError> {
+        let blobs = self.missing_blobs_of(reqs);
+        if blobs.is_empty() {
+            return Ok(false);
+        }
+        pf_debug!(
+            "holding Accept for slot {} bal {} on {} missing blobs",
+            slot,
+            ballot,
+            blobs.len()
+        );
+        self.accepts_held
+            .retain(|(_, s, b, ..)| *s != slot && *b >= self.bal_max_seen);
+        self.accepts_held
+            .push((peer, slot, ballot, reqs.clone(), hlc));
+        self.transport_hub
+            .send_msg(PeerMsg::BlobFetch { blobs }, peer)?;
+        pf_trace!("sent BlobFetch -> {}", peer);
+        Ok(true)
+    }
+
+    /// Collects blobs referenced only by slots before the given bar, which
+    /// the state machine image of my snapshot now covers.
+    pub(super) async fn collect_blob_garbage(
+        &mut self,
+        bar: usize,
+    ) -> Result<(), SummersetError> {
+        if let Some(blob_store) = self.blob_store.as_mut() {
+            let deleted = blob_store.collect_garbage(bar).await?;
+            if deleted > 0 {
+                pf_debug!("deleted {} blobs before slot {}", deleted, bar);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the blob referenced by command if it is missing locally.
+    fn missing_blob(&self, cmd: &Command) -> Option<BlobRef> {
+        let blob_store = self.blob_store.as_ref()?;
+        if let Command::Put { value, .. } = cmd {
+            BlobRef::parse(value).filter(|blob| !blob_store.contains(blob))
+        } else {
+            None
+        }
+    }
+
+    /// Resolves the blob reference in command, if any, into the full value
+    /// from my blob store. Returns `None` if the blob is missing locally.
+    pub(super) async fn resolve_blob(
+        &mut self,
+        cmd: Command,
+    ) -> Result<Option<Command>, SummersetError> {
+        let Some(blob_store) = self.blob_store.as_mut() else {
+            return Ok(Some(cmd));
+        };
+        match cmd {
+            Command::Put { key, value } => match BlobRef::parse(&value) {
+                Some(blob) => Ok(blob_store
+                    .get(&blob)
+                    .await?
+                    .map(|value| Command::Put { key, value })),
+                None => Ok(Some(Command::Put { key, value })),
+            },
+            cmd => Ok(Some(cmd)),
+        }
+    }
+
+    /// Submits queued commands to the state machine in order, resolving
+    /// their blob references, until one whose blob is missing locally. If
+    /// stalled, requests the missing blobs from peers.
+    pub(super) async fn drain_exec_pending(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        while let Some((cmd_id, cmd, checksum, hlc)) =
+            self.exec_pending.pop_front()
+        {
+            match self.resolve_blob(cmd.clone()).await? {
+                Some(resolved) => {
+                    // client checksums cover the resolved value
+                    self.state_machine
+                        .submit_stamped_cmd(cmd_id, resolved, checksum, hlc)?;
+                }
+                None => {
+                    self.exec_pending.push_front((cmd_id, cmd, checksum, hlc));
+                    break;
+                }
+            }
+        }
+
+        // request missing blobs from peers if stalled on a new one
+        let stalled_on = self
+            .exec_pending
+            .front()
+            .and_then(|(_, cmd, ..)| self.missing_blob(cmd));
+        match stalled_on {
+            Some(blob) if self.blob_fetching.map(|(b, _)| b) != stalled_on => {
+                pf_debug!("execution stalled on missing blob {}", blob);
+                self.fetch_missing_blobs(None)?;
+                self.blob_fetching = Some((blob, Instant::now()));
+            }
+            Some(_) => {}
+            None => self.blob_fetching = None,
+        }
+        Ok(())
+    }
+
+    /// Requests all blobs missing for queued commands from a peer, or from
+    /// all peers if `None`.
+    fn fetch_missing_blobs(
+        &mut self,
+        peer: Option<ReplicaId>,
+    ) -> Result<(), SummersetError> {
+        let mut blobs = vec![];
+        for (_, cmd, ..) in &self.exec_pending {
+            if let Some(blob) = self.missing_blob(cmd) {
+                if !blobs.contains(&blob) {
+                    blobs.push(blob);
+                }
+            }
+        }
+        if blobs.is_empty() {
+            return Ok(());
+        }
+
+        match peer {
+            Some(peer) => {
+                self.transport_hub
+                    .send_msg(PeerMsg::BlobFetch { blobs }, peer)?;
+                pf_trace!("sent BlobFetch -> {}", peer);
+            }
+            None => {
+                self.transport_hub
+                    .bcast_msg(PeerMsg::BlobFetch { blobs }, None)?;
+                pf_trace!("broadcast BlobFetch messages");
+            }
+        }
+        Ok(())
+    }
+
+    /// Re-requests missing blobs from the leader if execution has been
+    /// stalled on the same blob for longer than the max heartbeat hearing
+    /// timeout, e.g., because the peers first asked had no copy.
+    pub(super) fn refetch_stalled_blobs(
+        &mut self,
+        leader: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        if let Some((blob, ref mut since)) = self.blob_fetching {
+            let timeout =
+                Duration::from_millis(self.config.hb_hear_timeout_max);
+            if since.elapsed() > timeout {
+                pf_debug!("re-requesting stalled blob {} <- {}", blob, leader);
+                *since = Instant::now();
+                self.fetch_missing_blobs(Some(leader))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handler of BlobFetch message from a peer missing blobs.
+    pub(super) async fn handle_msg_blob_fetch(
+        &mut self,
+        peer: ReplicaId,
+        blobs: Vec<BlobRef>,
+    ) -> Result<(), SummersetError> {
+        let Some(blob_store) = self.blob_store.as_mut() else {
+            return Ok(());
+        };
+        for blob in blobs {
+            if let Some(value) = blob_store.get(&blob).await? {
+                self.transport_hub
+                    .send_msg(PeerMsg::BlobReply { blob, value }, peer)?;
+                pf_trace!("sent BlobReply -> {} for blob {}", peer, blob);
+            }
+        }
+        Ok(())
+    }
+
+    /// Handler of BlobReply message from a peer.
+    pub(super) async fn handle_msg_blob_reply(
+        &mut self,
+        peer: ReplicaId,
+        blob: BlobRef,
+        value: String,
+    ) -> Result<(), SummersetError> {
+        let Some(blob_store) = self.blob_store.as_mut() else {
+            return Ok(());
+        };
+        if blob_store.contains(&blob) {
+            return Ok(()); // already got it from someone else
+        }
+        pf_trace!("received BlobReply <- {} for blob {}", peer, blob);
+        blob_store.put_fetched(blob, &value).await?;
+
+        // vote on held Accepts that no longer miss any blob
+        let held = std::mem::take(&mut self.accepts_held);
+        for (src, slot, ballot, reqs, hlc) in held {
+            if self.missing_blobs_of(&reqs).is_empty() {
+                self.handle_msg_accept(src, slot, ballot, reqs, hlc).await?;
+            } else {
+                self.accepts_held.push((src, slot, ballot, reqs, hlc));
+            }
+        }
+
+        self.drain_exec_pending().await
+    }
+}
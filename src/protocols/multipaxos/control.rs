@@ -114,6 +114,47 @@ impl MultiPaxosReplica {
                 Ok(None)
             }
 
+            CtrlMsg::SnapshotMark => {
+                self.handle_ctrl_snapshot_mark()?;
+                Ok(None)
+            }
+
+            CtrlMsg::StopSign { members } => {
+                self.handle_ctrl_stop_sign(members)?;
+                Ok(None)
+            }
+
+            CtrlMsg::Reconfigure { add, remove } => {
+                self.handle_ctrl_reconfigure(add, remove)?;
+                Ok(None)
+            }
+
+            CtrlMsg::TransferLeader { target } => {
+                self.handle_ctrl_transfer_leader(target)?;
+                Ok(None)
+            }
+
+            CtrlMsg::BallotGrant {
+                leader,
+                epoch,
+                members,
+                prev_members,
+            } => {
+                self.handle_ctrl_ballot_grant(
+                    leader,
+                    epoch,
+                    members,
+                    prev_members,
+                )
+                .await?;
+                Ok(None)
+            }
+
+            CtrlMsg::ExpireClients { clients } => {
+                self.external_api.expire_clients(&clients)?;
+                Ok(None)
+            }
+
             _ => Ok(None), // ignore all other types
         }
     }
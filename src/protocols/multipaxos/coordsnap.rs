@@ -0,0 +1,174 @@
+//! MultiPaxos -- cluster-wide coordinated snapshots at marker entries.
+//!
+//! A snapshot marker is a special log entry proposed by the leader on the
+//! manager's request. Every replica that commits it snapshots its state
+//! machine exactly as of the marker's slot, before submitting any command
+//! after it, so the snapshot files of all replicas are mutually consistent
+//! and together make a cluster-wide backup or migration image.
+//!
+//! Such a snapshot file can in turn seed a fresh cluster: the manager hands
+//! it out to joining servers, which keep it next to their snapshot file and
+//! load it into the state machine before recovering anything else.
+
+use super::*;
+
+use crate::manager::CtrlMsg;
+use crate::server::{ApiRequest, ConfChange};
+use crate::utils::SummersetError;
+
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+/// Client ID used as the source of snapshot marker entries in request
+/// batches.
+const SNAPSHOT_MARK_CLIENT: ClientId = ClientId::MAX - 1;
+
+// MultiPaxosReplica coordinated snapshots logic
+impl MultiPaxosReplica {
+    /// Compose the request batch value of a snapshot marker entry.
+    fn make_snapshot_mark() -> ReqBatch {
+        vec![(
+            SNAPSHOT_MARK_CLIENT,
+            ApiRequest::Conf {
+                id: 0,
+                delta: ConfChange::default(),
+            },
+        )]
+    }
+
+    /// Is the request batch a snapshot marker entry?
+    pub(super) fn is_snapshot_mark(reqs: &ReqBatch) -> bool {
+        matches!(
+            reqs.as_slice(),
+            [(SNAPSHOT_MARK_CLIENT, ApiRequest::Conf { .. })]
+        )
+    }
+
+    /// Path of the seed image file kept next to the snapshot file.
+    fn seed_image_path(config: &ReplicaConfigMultiPaxos) -> String {
+        format!("{}.seed", config.snapshot_path)
+    }
+
+    /// Saves a chunk of the seed image handed out by the manager, unless I
+    /// already have one. Chunks are staged in a partial file that becomes
+    /// the seed image once the last one is saved. Only taken on fresh durable
+    /// state, as the image is the state before slot 0 of my log.
+    pub(super) async fn save_seed_chunk(
+        config: &ReplicaConfigMultiPaxos,
+        offset: usize,
+        chunk: &[u8],
+        last: bool,
+    ) -> Result<(), SummersetError> {
+        let path = Self::seed_image_path(config);
+        let part_path = format!("{}.part", path);
+        if offset == 0 {
+            if fs::try_exists(&path).await? {
+                return Ok(());
+            }
+            let fresh = |meta: Option<std::fs::Metadata>| {
+                meta.is_none_or(|meta| meta.len() == 0)
+            };
+            if !fresh(fs::metadata(&config.backer_path).await.ok())
+                || !fresh(fs::metadata(&config.snapshot_path).await.ok())
+            {
+                pf_warn!("durable state not fresh, seed image ignored");
+                return Ok(());
+            }
+            fs::write(&part_path, b"").await?;
+        }
+
+        // a missing partial file means the image is being ignored
+        let Ok(meta) = fs::metadata(&part_path).await else {
+            return Ok(());
+        };
+        if meta.len() != offset as u64 {
+            return logged_err!(
+                "seed image chunk at {} != staged {} bytes",
+                offset,
+                meta.len()
+            );
+        }
+        let mut file =
+            fs::OpenOptions::new().append(true).open(&part_path).await?;
+        file.write_all(chunk).await?;
+        file.sync_all().await?;
+
+        if last {
+            fs::rename(&part_path, &path).await?;
+            pf_info!(
+                "saved seed image of {} bytes in '{}'",
+                offset + chunk.len(),
+                path
+            );
+        }
+        Ok(())
+    }
+
+    /// Loads the saved seed image, if any, into the state machine. Skipped if
+    /// a persistent state machine has applied commands already.
+    pub(super) async fn restore_seed_image(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        let path = Self::seed_image_path(&self.config);
+        if !fs::try_exists(&path).await?
+            || self.state_machine.durable_applied().is_some()
+        {
+            return Ok(());
+        }
+        let image = fs::read(&path).await?;
+        self.state_machine.restore_snapshot(image.into()).await?;
+        pf_info!("restored state from seed image '{}'", path);
+        Ok(())
+    }
+
+    /// Path of the snapshot file taken at the marker entry at slot.
+    fn snapshot_mark_path(&self, slot: usize) -> String {
+        format!("{}.at{}", self.config.snapshot_path, slot)
+    }
+
+    /// Takes the due snapshot of a committed marker entry, if any, reports
+    /// it to the manager, and then resumes submitting the commands held back
+    /// behind the marker.
+    pub(super) async fn take_marked_snapshots(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        while let Some(slot) = self.snapshot_mark_due.take() {
+            // reflects exactly the commands of slots before the marker, as
+            // none after it has been submitted yet
+            let snap = self.state_machine.take_snapshot().await?;
+            let path = self.snapshot_mark_path(slot);
+            fs::write(&path, &snap).await?;
+            pf_info!("took snapshot at marker slot {} in '{}'", slot, path);
+            self.control_hub
+                .send_ctrl(CtrlMsg::SnapshotAt { slot, path })?;
+
+            if self.commit_bar < self.accept_bar {
+                self.handle_logged_commit_slot(self.commit_bar)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handler of SnapshotMark control message.
+    pub(super) fn handle_ctrl_snapshot_mark(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        pf_warn!("server told to propose snapshot marker");
+        // commands in blob store mode may be submitted well after commit,
+        // so a marker would not cut the state machine at its slot
+        if !self.is_leader()
+            || self.bal_prepared == 0
+            || self.blob_store.is_some()
+            || self.pending_stop_sign()
+        {
+            pf_warn!("cannot propose snapshot marker now, rejected");
+            self.control_hub.send_ctrl(CtrlMsg::SnapshotMarkReject)?;
+            return Ok(());
+        }
+
+        self.insts.push(self.null_instance());
+        let slot = self.start_slot + self.insts.len() - 1;
+        self.propose_batch(slot, Self::make_snapshot_mark(), false)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+//! MultiPaxos -- offline export/import of durable files as JSON.
+
+use super::*;
+
+use crate::server::{scan_log_file, write_log_file};
+use crate::utils::SummersetError;
+
+use serde::de::DeserializeOwned;
+
+use tokio::fs;
+
+/// Human-readable dump of a replica's durable files.
+#[derive(Debug, Serialize, Deserialize)]
+struct FilesDump {
+    /// Entries of the snapshot file, in file order.
+    snapshot: Vec<SnapEntry>,
+
+    /// Entries of the WAL log, in file order.
+    wal: Vec<WalEntry>,
+}
+
+// MultiPaxosReplica offline export/import
+impl MultiPaxosReplica {
+    /// Reads out all decodable entries of a durable file, or nothing if the
+    /// file does not exist.
+    pub(super) async fn dump_read_file<Ent>(
+        path: &Path,
+    ) -> Result<Vec<Ent>, SummersetError>
+    where
+        Ent: DeserializeOwned,
+    {
+        if !fs::try_exists(path).await? {
+            return Ok(vec![]);
+        }
+        let (entries, valid_end, file_size) = scan_log_file(path).await?;
+        if valid_end < file_size {
+            pf_warn!(
+                "'{}': skipped {} bytes of corrupted tail",
+                path.display(),
+                file_size - valid_end
+            );
+        }
+        Ok(entries.into_iter().map(|(_, e)| e).collect())
+    }
+
+    /// Exports the snapshot file and WAL log named in the config as a pretty
+    /// JSON string.
+    pub(crate) async fn export_files(
+        config_str: Option<&str>,
+    ) -> Result<String, SummersetError> {
+        let config = Self::parse_config(config_str)?;
+        let dump = FilesDump {
+            snapshot: Self::dump_read_file(Path::new(&config.snapshot_path))
+                .await?,
+            wal: Self::dump_read_file(Path::new(&config.backer_path)).await?,
+        };
+        Ok(serde_json::to_string_pretty(&dump)?)
+    }
+
+    /// Imports a JSON string produced by `export_files()`, overwriting the
+    /// snapshot file and WAL log named in the config.
+    pub(crate) async fn import_files(
+        config_str: Option<&str>,
+        json: &str,
+    ) -> Result<(), SummersetError> {
+        let config = Self::parse_config(config_str)?;
+        let dump: FilesDump = serde_json::from_str(json)?;
+        write_log_file(Path::new(&config.snapshot_path), &dump.snapshot)
+            .await?;
+        write_log_file(Path::new(&config.backer_path), &dump.wal).await?;
+        pf_info!(
+            "imported {} snapshot entries and {} wal entries",
+            dump.snapshot.len(),
+            dump.wal.len()
+        );
+        Ok(())
+    }
+}
@@ -2,7 +2,7 @@
 
 use super::*;
 
-use crate::server::{ApiRequest, LogActionId, LogResult};
+use crate::server::{panic_dump_event, ApiRequest, LogActionId, LogResult};
 use crate::utils::SummersetError;
 
 // MultiPaxosReplica durable WAL logging
@@ -147,7 +147,7 @@ impl MultiPaxosReplica {
     }
 
     /// Handler of CommitSlot logging result chan recv.
-    fn handle_logged_commit_slot(
+    pub(super) fn handle_logged_commit_slot(
         &mut self,
         slot: usize,
     ) -> Result<(), SummersetError> {
@@ -169,16 +169,59 @@ impl MultiPaxosReplica {
                 }
 
                 // submit commands in committed instance to the state machine
-                // for execution
-                if inst.reqs.is_empty() {
+                // for execution; a committed stop-sign takes effect right
+                // away and has nothing to execute
+                if let Some(members) = Self::parse_stop_sign(&inst.reqs) {
+                    if inst.status == Status::Committed {
+                        let members = members.clone();
+                        inst.status = Status::Executed;
+                        if self.exec_bar == self.commit_bar {
+                            self.exec_bar += 1;
+                        }
+                        self.apply_stop_sign(self.commit_bar, members)?;
+                    }
+                } else if Self::is_snapshot_mark(&inst.reqs) {
+                    // a committed snapshot marker holds back later commands
+                    // until its snapshot is taken
+                    if inst.status == Status::Committed {
+                        inst.status = Status::Executed;
+                        if self.exec_bar == self.commit_bar {
+                            self.exec_bar += 1;
+                        }
+                        self.snapshot_mark_due = Some(self.commit_bar);
+                    }
+                } else if inst.reqs.is_empty() {
                     inst.status = Status::Executed;
                 } else if inst.status == Status::Committed {
-                    for (cmd_idx, (_, req)) in inst.reqs.iter().enumerate() {
-                        if let ApiRequest::Req { cmd, .. } = req {
-                            self.state_machine.submit_cmd(
-                                Self::make_command_id(self.commit_bar, cmd_idx),
-                                cmd.clone(),
-                            )?;
+                    for (cmd_idx, (client, req)) in inst.reqs.iter().enumerate()
+                    {
+                        if let ApiRequest::Req {
+                            id, cmd, checksum, ..
+                        } = req
+                        {
+                            if inst.external {
+                                // early acknowledgement if client asked
+                                self.external_api
+                                    .ack_committed(*client, *id)?;
+                            }
+                            let cmd_id =
+                                Self::make_command_id(self.commit_bar, cmd_idx);
+                            if self.blob_store.is_some() {
+                                // queued up until blobs are resolved
+                                self.exec_pending.push_back((
+                                    cmd_id,
+                                    cmd.clone(),
+                                    *checksum,
+                                    inst.hlc,
+                                ));
+                            } else {
+                                self.state_machine.submit_stamped_cmd(
+                                    cmd_id,
+                                    cmd.clone(),
+                                    *checksum,
+                                    inst.hlc,
+                                )?;
+                            }
                         }
                     }
                     pf_trace!(
@@ -213,6 +256,10 @@ impl MultiPaxosReplica {
                         );
                     }
                 }
+
+                if self.snapshot_mark_due.is_some() {
+                    break;
+                }
             }
         }
 
@@ -241,16 +288,45 @@ impl MultiPaxosReplica {
             debug_assert!(inst.wal_offset <= self.wal_offset);
             // then update self.wal_offset
             self.wal_offset = now_size;
+            panic_dump_event(|| {
+                format!(
+                    "logged {:?} slot {} bal {} batch of {}",
+                    entry_type,
+                    slot,
+                    inst.bal,
+                    inst.reqs.len()
+                )
+            });
         } else {
-            return logged_err!("unexpected log result type: {:?}", log_result);
+            return logged_err!(
+                Protocol; "unexpected log result type: {:?}", log_result
+            );
         }
 
         match entry_type {
-            Status::Preparing => self.handle_logged_prepare_bal(slot),
-            Status::Accepting => self.handle_logged_accept_data(slot),
-            Status::Committed => self.handle_logged_commit_slot(slot),
+            Status::Preparing => {
+                // a Prepare reply carries what I voted
+                self.page_in_inst(slot).await?;
+                self.handle_logged_prepare_bal(slot)
+            }
+            Status::Accepting => {
+                self.handle_logged_accept_data(slot)?;
+                self.page_out_insts();
+                Ok(())
+            }
+            Status::Committed => {
+                self.page_in_committed().await?;
+                self.handle_logged_commit_slot(slot)?;
+                self.take_marked_snapshots().await?;
+                if self.blob_store.is_some() {
+                    self.drain_exec_pending().await?;
+                }
+                Ok(())
+            }
             _ => {
-                logged_err!("unexpected log entry type: {:?}", entry_type)
+                logged_err!(
+                    Protocol; "unexpected log entry type: {:?}", entry_type
+                )
             }
         }
     }
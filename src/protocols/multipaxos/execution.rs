@@ -2,7 +2,7 @@
 
 use super::*;
 
-use crate::server::{ApiReply, ApiRequest};
+use crate::server::{ApiReply, ApiRequest, AuditEntry, CommitCert};
 use crate::utils::SummersetError;
 
 // MultiPaxosReplica state machine execution
@@ -13,6 +13,18 @@ impl MultiPaxosReplica {
         cmd_id: CommandId,
         cmd_result: CommandResult,
     ) -> Result<(), SummersetError> {
+        if let Some((client, req_id)) = Self::split_ro_command_id(cmd_id) {
+            // result of a rinsed near quorum read
+            if self.external_api.has_client(client) {
+                self.external_api.send_reply(
+                    ApiReply::normal(req_id, Some(cmd_result)),
+                    client,
+                )?;
+                pf_trace!("replied -> client {} for near-read rinse", client);
+            }
+            return Ok(());
+        }
+
         let (slot, cmd_idx) = Self::split_command_id(cmd_id);
         if slot < self.start_slot {
             return Ok(()); // ignore if slot index outdated
@@ -25,11 +37,36 @@ impl MultiPaxosReplica {
         let (client, ref req) = inst.reqs[cmd_idx];
 
         // reply command result back to client
-        if let ApiRequest::Req { id: req_id, .. } = req {
+        if let ApiRequest::Req {
+            id: req_id, cmd, ..
+        } = req
+        {
             let read_only = cmd_result.read_only();
             if inst.external && self.external_api.has_client(client) {
+                // attach the accepting quorum as commit certificate if I
+                // committed this write as leader
+                let cert = match inst.leader_bk.as_ref() {
+                    Some(LeaderBookkeeping {
+                        accept_acks,
+                        commit_members: Some(members),
+                        ..
+                    }) if self.config.attach_commit_certs && !read_only => {
+                        Some(CommitCert {
+                            slot,
+                            ballot: inst.bal,
+                            quorum: accept_acks.clone(),
+                            members: members.clone(),
+                        })
+                    }
+                    _ => None,
+                };
                 self.external_api.send_reply(
-                    ApiReply::normal(*req_id, Some(cmd_result)),
+                    match cert {
+                        Some(cert) => {
+                            ApiReply::certified(*req_id, Some(cmd_result), cert)
+                        }
+                        None => ApiReply::normal(*req_id, Some(cmd_result)),
+                    },
                     client,
                 )?;
                 pf_trace!(
@@ -52,8 +89,21 @@ impl MultiPaxosReplica {
                     }
                 }
             }
+
+            // record to audit log on every replica, including commands of
+            // clients not connected to me
+            if let Some(audit_log) = self.audit_log.as_mut() {
+                audit_log
+                    .record(AuditEntry {
+                        client,
+                        req_id: *req_id,
+                        slot,
+                        cmd: cmd.clone(),
+                    })
+                    .await?;
+            }
         } else {
-            return logged_err!("unexpected API request type");
+            return logged_err!(Protocol; "unexpected API request type");
         }
 
         // if all commands in this instance have been executed, set status to
@@ -78,6 +128,11 @@ impl MultiPaxosReplica {
                     }
                     self.exec_bar += 1;
                 }
+                self.serve_rinsed_reads()?;
+            }
+
+            if slot < self.replay_bar && !self.replaying() {
+                pf_info!("finished background replay of wal log");
             }
         }
 
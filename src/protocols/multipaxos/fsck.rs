@@ -0,0 +1,164 @@
+//! MultiPaxos -- offline consistency check and repair of durable files.
+
+use std::collections::HashSet;
+
+use super::*;
+
+use crate::protocols::FsckReport;
+use crate::server::{scan_log_file, truncate_log_file};
+use crate::utils::SummersetError;
+
+use serde::de::DeserializeOwned;
+
+use tokio::fs;
+
+// MultiPaxosReplica offline file checking
+impl MultiPaxosReplica {
+    /// Checks one durable file for trailing content that cannot be decoded,
+    /// truncating it away if `repair` is set. Returns the decoded entries.
+    async fn fsck_scan_file<Ent>(
+        report: &mut FsckReport,
+        path: &Path,
+        repair: bool,
+    ) -> Result<Vec<Ent>, SummersetError>
+    where
+        Ent: DeserializeOwned,
+    {
+        if !fs::try_exists(path).await? {
+            report.note(format!("'{}' does not exist", path.display()));
+            return Ok(vec![]);
+        }
+
+        let (entries, valid_end, file_size) = scan_log_file(path).await?;
+        report.note(format!(
+            "'{}': {} entries in {} bytes",
+            path.display(),
+            entries.len(),
+            valid_end
+        ));
+        if valid_end < file_size {
+            report.problem(format!(
+                "'{}': {} bytes of corrupted tail at offset {}",
+                path.display(),
+                file_size - valid_end,
+                valid_end
+            ));
+            if repair {
+                truncate_log_file(path, valid_end).await?;
+                report.repair(format!(
+                    "'{}': truncated to {} bytes",
+                    path.display(),
+                    valid_end
+                ));
+            }
+        }
+        Ok(entries.into_iter().map(|(_, e)| e).collect())
+    }
+
+    /// Validates the snapshot file and WAL log named in the config without
+    /// joining the cluster: every entry must decode, the snapshot must begin
+    /// with its slot info, and every committed slot must have been accepted
+    /// before. Trailing corruption is truncated if `repair` is set.
+    pub(crate) async fn fsck_files(
+        config_str: Option<&str>,
+        repair: bool,
+    ) -> Result<FsckReport, SummersetError> {
+        let config = Self::parse_config(config_str)?;
+        let mut report = FsckReport::default();
+
+        // snapshot file
+        let snap_entries: Vec<SnapEntry> = Self::fsck_scan_file(
+            &mut report,
+            Path::new(&config.snapshot_path),
+            repair,
+        )
+        .await?;
+        let start_slot = match snap_entries.first() {
+            Some(SnapEntry::SlotInfo { start_slot }) => *start_slot,
+            Some(_) => {
+                report.problem("snapshot does not begin with slot info".into());
+                0
+            }
+            None => 0,
+        };
+        if snap_entries
+            .iter()
+            .skip(1)
+            .any(|e| matches!(e, SnapEntry::SlotInfo { .. }))
+        {
+            report.problem("snapshot has more than one slot info".into());
+        }
+        report.note(format!("snapshot covers slots before {}", start_slot));
+
+        // WAL log
+        let wal_entries: Vec<WalEntry> = Self::fsck_scan_file(
+            &mut report,
+            Path::new(&config.backer_path),
+            repair,
+        )
+        .await?;
+        let mut accepted = HashSet::new();
+        let mut committed = HashSet::new();
+        let mut stale_cnt = 0;
+        for entry in wal_entries {
+            match entry {
+                WalEntry::PrepareBal { slot, .. } if slot < start_slot => {
+                    stale_cnt += 1;
+                }
+                WalEntry::AcceptData { slot, .. } => {
+                    if slot < start_slot {
+                        stale_cnt += 1;
+                    } else {
+                        accepted.insert(slot);
+                    }
+                }
+                WalEntry::CommitSlot { slot } => {
+                    if slot < start_slot {
+                        stale_cnt += 1;
+                    } else if !accepted.contains(&slot) {
+                        report.problem(format!(
+                            "slot {} committed without accepted data",
+                            slot
+                        ));
+                    } else {
+                        committed.insert(slot);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if stale_cnt > 0 {
+            report.note(format!(
+                "{} wal entries already covered by snapshot",
+                stale_cnt
+            ));
+        }
+
+        // slot continuity: accepted slots should leave no holes, and the
+        // committed ones a contiguous prefix
+        let accept_end = start_slot
+            + (start_slot..).take_while(|s| accepted.contains(s)).count();
+        let commit_end = start_slot
+            + (start_slot..).take_while(|s| committed.contains(s)).count();
+        if let Some(&max_slot) = accepted.iter().max() {
+            if max_slot >= accept_end {
+                report.note(format!(
+                    "accepted slots have holes from {} up to {}",
+                    accept_end, max_slot
+                ));
+            }
+        }
+        if committed.len() > commit_end - start_slot {
+            report.note(format!(
+                "{} slots committed beyond the contiguous prefix",
+                committed.len() - (commit_end - start_slot)
+            ));
+        }
+        report.note(format!(
+            "wal accepts up to {} and commits up to {}",
+            accept_end, commit_end
+        ));
+
+        Ok(report)
+    }
+}
@@ -0,0 +1,488 @@
+//! MultiPaxos -- snapshot installation on lagging followers.
+//!
+//! A follower whose exec_bar lags behind the leader's snapshot, e.g., after
+//! losing its state, can no longer catch up through the log. The leader
+//! then offers its snapshot, and the follower pulls the leader's snapshot
+//! file one entry (chunk) at a time by offset. Received chunks are staged
+//! durably in a separate install file together with the offset to resume
+//! from, so an install interrupted by a crash or a flaky link picks up where
+//! it left off instead of starting over. Once the end of the leader's file
+//! is reached, the staged chunks are applied to my state and snapshot file
+//! all at once.
+
+use super::*;
+
+use crate::manager::CtrlMsg;
+use crate::server::{LogAction, LogResult};
+use crate::utils::SummersetError;
+
+// MultiPaxosReplica snapshot installation logic
+impl MultiPaxosReplica {
+    /// Is the outstanding fetch of an install (if any) considered lost?
+    #[inline]
+    fn install_fetch_lost(&self, fetched_at: Option<Instant>) -> bool {
+        fetched_at.is_none_or(|at| {
+            at.elapsed()
+                > Duration::from_millis(self.config.hb_hear_timeout_min)
+        })
+    }
+
+    /// Offers my snapshot to a peer lagging behind it, unless I have been
+    /// serving it chunks recently.
+    pub(super) fn offer_snapshot(
+        &mut self,
+        peer: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        let serving = self.install_serving.get(&peer).map(|&(_, at)| at);
+        if !self.install_fetch_lost(serving) {
+            return Ok(());
+        }
+        self.transport_hub.send_msg(
+            PeerMsg::SnapshotOffer {
+                start_slot: self.snap_start,
+            },
+            peer,
+        )?;
+        self.install_serving
+            .entry(peer)
+            .and_modify(|(_, at)| *at = Instant::now())
+            .or_insert((0, Instant::now()));
+        pf_trace!("sent SnapshotOffer -> {} start {}", peer, self.snap_start);
+        Ok(())
+    }
+
+    /// Sends the fetch of the next chunk of the ongoing install.
+    fn fetch_install_chunk(&mut self) -> Result<(), SummersetError> {
+        let Some(install) = self.installing.as_mut() else {
+            return Ok(());
+        };
+        install.fetched_at = Some(Instant::now());
+        self.transport_hub.send_msg(
+            PeerMsg::SnapshotFetch {
+                offset: install.next_offset,
+            },
+            install.source,
+        )?;
+        pf_trace!(
+            "sent SnapshotFetch -> {} offset {}",
+            install.source,
+            install.next_offset
+        );
+        Ok(())
+    }
+
+    /// Truncates the install staging file at given offset.
+    async fn truncate_install_file(
+        &mut self,
+        offset: usize,
+    ) -> Result<(), SummersetError> {
+        if let LogResult::Truncate {
+            offset_ok: true,
+            now_size,
+        } = self
+            .install_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Truncate { offset },
+            )
+            .await?
+            .1
+        {
+            self.install_offset = now_size;
+            Ok(())
+        } else {
+            logged_err!(
+                Protocol; "unexpected log result type or failed truncate"
+            )
+        }
+    }
+
+    /// Clears the install staging file and starts installing the snapshot
+    /// of peer starting at start_slot from scratch.
+    async fn begin_install(
+        &mut self,
+        peer: ReplicaId,
+        start_slot: usize,
+    ) -> Result<(), SummersetError> {
+        self.truncate_install_file(0).await?;
+        self.append_install_entry(InstallEntry::Source { peer, start_slot })
+            .await?;
+
+        self.installing = Some(SnapInstall {
+            source: peer,
+            start_slot,
+            next_offset: 0,
+            fetched_at: None,
+        });
+        pf_info!("installing snapshot from {}", peer);
+        self.fetch_install_chunk()
+    }
+
+    /// Appends an entry to the install staging file.
+    async fn append_install_entry(
+        &mut self,
+        entry: InstallEntry,
+    ) -> Result<(), SummersetError> {
+        if let LogResult::Append { now_size } = self
+            .install_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Append {
+                    entry,
+                    sync: self.config.logger_sync,
+                },
+            )
+            .await?
+            .1
+        {
+            self.install_offset = now_size;
+            Ok(())
+        } else {
+            logged_err!(Protocol; "unexpected log result type")
+        }
+    }
+
+    /// Handler of SnapshotOffer message from leader.
+    pub(super) async fn handle_msg_snapshot_offer(
+        &mut self,
+        peer: ReplicaId,
+        start_slot: usize,
+    ) -> Result<(), SummersetError> {
+        if start_slot <= self.exec_bar {
+            return Ok(()); // not lagging behind it (anymore)
+        }
+        pf_trace!("received SnapshotOffer <- {} start {}", peer, start_slot);
+
+        match self.installing {
+            Some(ref install)
+                if install.source == peer
+                    && install.start_slot == start_slot =>
+            {
+                // resume from where I left off if the last fetch got lost
+                if self.install_fetch_lost(install.fetched_at) {
+                    pf_debug!(
+                        "resuming snapshot install from {} at offset {}",
+                        peer,
+                        install.next_offset
+                    );
+                    self.fetch_install_chunk()?;
+                }
+                Ok(())
+            }
+            _ => self.begin_install(peer, start_slot).await,
+        }
+    }
+
+    /// Handler of SnapshotFetch message from a lagging peer.
+    pub(super) async fn handle_msg_snapshot_fetch(
+        &mut self,
+        peer: ReplicaId,
+        offset: usize,
+    ) -> Result<(), SummersetError> {
+        pf_trace!("received SnapshotFetch <- {} offset {}", peer, offset);
+        let (entry, next_offset) = if offset > self.snap_offset {
+            // not a valid offset into my snapshot file; have it start over
+            (None, 0)
+        } else {
+            // the slot info at the head is not a chunk; skip it
+            let mut read_at = offset;
+            loop {
+                match self
+                    .snapshot_hub
+                    .do_sync_action(
+                        0, // using 0 as dummy log action ID
+                        LogAction::Read { offset: read_at },
+                    )
+                    .await?
+                    .1
+                {
+                    LogResult::Read {
+                        entry: Some(SnapEntry::SlotInfo { .. }),
+                        end_offset,
+                    } => read_at = end_offset,
+                    LogResult::Read {
+                        entry: Some(entry),
+                        end_offset,
+                    } => break (Some(entry), end_offset),
+                    LogResult::Read { entry: None, .. } => {
+                        break (None, offset)
+                    }
+                    _ => {
+                        return logged_err!(
+                            Protocol; "unexpected log result type"
+                        );
+                    }
+                }
+            }
+        };
+
+        if entry.is_some() {
+            self.install_serving
+                .insert(peer, (next_offset, Instant::now()));
+        } else {
+            self.install_serving.remove(&peer);
+        }
+        self.transport_hub.send_msg(
+            PeerMsg::SnapshotChunk {
+                start_slot: self.snap_start,
+                offset,
+                next_offset,
+                entry,
+            },
+            peer,
+        )?;
+        pf_trace!(
+            "sent SnapshotChunk -> {} offset {} next {}",
+            peer,
+            offset,
+            next_offset
+        );
+        Ok(())
+    }
+
+    /// Handler of SnapshotChunk message from leader.
+    pub(super) async fn handle_msg_snapshot_chunk(
+        &mut self,
+        peer: ReplicaId,
+        start_slot: usize,
+        offset: usize,
+        next_offset: usize,
+        entry: Option<SnapEntry>,
+    ) -> Result<(), SummersetError> {
+        if !self.installing.as_ref().is_some_and(|install| {
+            install.source == peer && install.next_offset == offset
+        }) {
+            return Ok(()); // stale or duplicate chunk
+        }
+        pf_trace!(
+            "received SnapshotChunk <- {} offset {} next {}",
+            peer,
+            offset,
+            next_offset
+        );
+
+        // the source rewrites its snapshot file whenever taking a new one
+        let changed = self
+            .installing
+            .as_ref()
+            .is_some_and(|install| install.start_slot != start_slot);
+        match entry {
+            _ if changed || (entry.is_none() && next_offset != offset) => {
+                pf_warn!("snapshot of {} changed under install", peer);
+                self.begin_install(peer, start_slot).await
+            }
+
+            Some(entry) => {
+                // stage the chunk durably along with where to resume from
+                self.append_install_entry(InstallEntry::Chunk {
+                    entry,
+                    next_offset,
+                })
+                .await?;
+                let install = self.installing.as_mut().unwrap();
+                install.next_offset = next_offset;
+                self.fetch_install_chunk()
+            }
+
+            None if start_slot <= self.exec_bar => {
+                self.installing = None;
+                Ok(())
+            }
+
+            None => self.finish_install(start_slot).await,
+        }
+    }
+
+    /// Applies all staged chunks to my state and snapshot file, then moves
+    /// my log forward to the installed snapshot's start_slot.
+    //
+    // NOTE: like taking a snapshot, finishing an install is not atomic
+    //       against crashes in the middle of it.
+    async fn finish_install(
+        &mut self,
+        start_slot: usize,
+    ) -> Result<(), SummersetError> {
+        debug_assert!(start_slot > self.exec_bar);
+
+        // apply staged chunks in order, appending them to my snapshot file
+        let old_offset = self.snap_offset;
+        let mut read_at = 0;
+        let mut num_chunks = 0;
+        loop {
+            let (entry, end_offset) = match self
+                .install_hub
+                .do_sync_action(
+                    0, // using 0 as dummy log action ID
+                    LogAction::Read { offset: read_at },
+                )
+                .await?
+                .1
+            {
+                LogResult::Read {
+                    entry: Some(InstallEntry::Source { .. }),
+                    end_offset,
+                } => {
+                    read_at = end_offset;
+                    continue;
+                }
+                LogResult::Read {
+                    entry: Some(InstallEntry::Chunk { entry, .. }),
+                    end_offset,
+                } => (entry, end_offset),
+                LogResult::Read { entry: None, .. } => break,
+                _ => {
+                    return logged_err!(Protocol; "unexpected log result type");
+                }
+            };
+            read_at = end_offset;
+            num_chunks += 1;
+
+            match entry {
+                SnapEntry::StateImage { ref image } => {
+                    self.state_machine
+                        .restore_snapshot(image.clone().into())
+                        .await?;
+                }
+                SnapEntry::Members { ref members } => {
+                    self.set_members(members.clone())?;
+                    self.snap_members = Some(members.clone());
+                }
+                SnapEntry::SlotInfo { .. } => continue,
+            }
+
+            if let LogResult::Append { now_size } = self
+                .snapshot_hub
+                .do_sync_action(
+                    0, // using 0 as dummy log action ID
+                    LogAction::Append {
+                        entry,
+                        sync: self.config.logger_sync,
+                    },
+                )
+                .await?
+                .1
+            {
+                self.snap_offset = now_size;
+            } else {
+                return logged_err!(Protocol; "unexpected log result type");
+            }
+        }
+
+        // write new slot info entry to the head of snapshot
+        match self
+            .snapshot_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Write {
+                    entry: SnapEntry::SlotInfo { start_slot },
+                    offset: 0,
+                    sync: self.config.logger_sync,
+                },
+            )
+            .await?
+            .1
+        {
+            LogResult::Write {
+                offset_ok: true, ..
+            } => {}
+            _ => {
+                return logged_err!(
+                    Protocol; "unexpected log result type or failed write"
+                );
+            }
+        }
+        self.snap_start = start_slot;
+        self.snapshot_discard_old(old_offset).await?;
+        self.collect_blob_garbage(start_slot).await?;
+
+        // move the in-memory log forward, keeping instances beyond start_slot
+        if self.start_slot + self.insts.len() > start_slot {
+            self.insts.drain(0..(start_slot - self.start_slot));
+        } else {
+            self.insts.clear();
+        }
+        self.start_slot = start_slot;
+        self.accept_bar = self.accept_bar.max(start_slot);
+        self.commit_bar = self.commit_bar.max(start_slot);
+        self.exec_bar = start_slot;
+        self.snap_bar = self.snap_bar.max(start_slot);
+        self.serve_rinsed_reads()?;
+
+        // discard everything older than start_slot in WAL log
+        self.snapshot_discard_log().await?;
+
+        // the staged chunks are no longer needed
+        self.installing = None;
+        self.truncate_install_file(0).await?;
+
+        self.control_hub.send_ctrl(CtrlMsg::SnapshotUpTo {
+            new_start: self.start_slot,
+        })?;
+        pf_info!(
+            "installed snapshot of {} chunks: start {}",
+            num_chunks,
+            self.start_slot
+        );
+        Ok(())
+    }
+
+    /// Recovers the progress of an interrupted snapshot install from the
+    /// install staging file, if any.
+    pub(super) async fn recover_install(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        let mut install: Option<SnapInstall> = None;
+        let mut read_at = 0;
+        loop {
+            match self
+                .install_hub
+                .do_sync_action(
+                    0, // using 0 as dummy log action ID
+                    LogAction::Read { offset: read_at },
+                )
+                .await?
+                .1
+            {
+                LogResult::Read {
+                    entry: Some(InstallEntry::Source { peer, start_slot }),
+                    end_offset,
+                } => {
+                    install = Some(SnapInstall {
+                        source: peer,
+                        start_slot,
+                        next_offset: 0,
+                        fetched_at: None,
+                    });
+                    read_at = end_offset;
+                }
+                LogResult::Read {
+                    entry: Some(InstallEntry::Chunk { next_offset, .. }),
+                    end_offset,
+                } => {
+                    if let Some(ref mut install) = install {
+                        install.next_offset = next_offset;
+                    }
+                    read_at = end_offset;
+                }
+                LogResult::Read { entry: None, .. } => break,
+                _ => {
+                    return logged_err!(Protocol; "unexpected log result type");
+                }
+            }
+        }
+
+        // cut off any trailing partial entry so that appends go after the
+        // last complete one
+        self.truncate_install_file(read_at).await?;
+
+        if let Some(ref install) = install {
+            pf_info!(
+                "resuming snapshot install from {} at offset {}",
+                install.source,
+                install.next_offset
+            );
+        }
+        self.installing = install;
+        Ok(())
+    }
+}
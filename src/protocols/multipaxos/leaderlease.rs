@@ -2,6 +2,9 @@
 
 use super::*;
 
+use std::time::UNIX_EPOCH;
+
+use crate::manager::CtrlMsg;
 use crate::server::LeaseAction;
 
 // MultiPaxosReplica lease-related actions logic
@@ -37,7 +40,7 @@ impl MultiPaxosReplica {
                 // starving due to possibly having to wait on lease expirations
                 // NOTE: a nicer implementation could make the heartbeat bcast
                 //       action a separate background periodic task
-                self.transport_hub.bcast_msg(
+                self.transport_hub.bcast_msg_unreliable(
                     PeerMsg::Heartbeat {
                         ballot: self.bal_max_seen,
                         commit_bar: self.commit_bar,
@@ -54,6 +57,46 @@ impl MultiPaxosReplica {
         Ok(())
     }
 
+    /// If doing lease diagnostics, samples whether I currently believe I hold
+    /// a majority lease, and reports the interval of such belief to the
+    /// manager once it ends or moves on to a different lease number. Sampled
+    /// at every lease action, heartbeat, and before serving any lease-based
+    /// reads, so that reported intervals cover all such reads.
+    pub(super) fn observe_lease_held(&mut self) -> Result<(), SummersetError> {
+        if !self.config.enable_leader_leases || !self.config.lease_diagnostics {
+            return Ok(());
+        }
+
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(SummersetError::msg)?
+            .as_millis() as u64;
+        let held = self.is_leader()
+            && self.lease_manager.lease_cnt() >= self.quorum_cnt;
+
+        if let Some((lease_num, start_ms)) = self.lease_held_since {
+            if held && lease_num == self.bal_max_seen {
+                return Ok(()); // still holding the same lease
+            }
+            self.control_hub.send_ctrl(CtrlMsg::LeaseInterval {
+                lease_num,
+                start_ms,
+                end_ms: now_ms + 1, // end is exclusive
+            })?;
+            pf_debug!(
+                "lease held @ {} during {}-{}",
+                lease_num,
+                start_ms,
+                now_ms
+            );
+            self.lease_held_since = None;
+        }
+        if held {
+            self.lease_held_since = Some((self.bal_max_seen, now_ms));
+        }
+        Ok(())
+    }
+
     /// Synthesized handler of lease-related actions from LeaseManager.
     /// Returns true if this action is a possible indicator that the grant_set
     /// shrunk; otherwise returns false.
@@ -82,6 +125,7 @@ impl MultiPaxosReplica {
             | LeaseAction::GrantTimeout { .. }
             | LeaseAction::HigherNumber => {
                 // tell revoker that it might want to double check grant_set
+                self.observe_lease_held()?;
                 return Ok(true);
             }
 
@@ -90,6 +134,7 @@ impl MultiPaxosReplica {
             }
         }
 
+        self.observe_lease_held()?;
         Ok(false)
     }
 }
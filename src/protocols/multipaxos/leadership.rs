@@ -2,8 +2,11 @@
 
 use super::*;
 
-use crate::manager::CtrlMsg;
-use crate::server::{LeaseNotice, LogAction, ReplicaId};
+use crate::manager::{CtrlMsg, ServerStatus};
+use crate::server::{
+    entry_digest, panic_dump_event, DebugState, LeaseNotice, LogAction,
+    ReplicaId, ResourceUsage,
+};
 use crate::utils::{Bitmap, SummersetError};
 
 // MultiPaxosReplica heartbeats related logic
@@ -58,10 +61,17 @@ impl MultiPaxosReplica {
                 )?;
             }
 
-            // set this peer to be the believed leader
+            // set this peer to be the believed leader; any transfer of mine
+            // is over, having either completed or been overtaken
             debug_assert_ne!(peer, self.id);
             self.leader = Some(peer);
+            self.transfer = None;
+            self.external_api.notify_leader_change(peer)?;
             self.bal_max_seen = ballot;
+            panic_dump_event(|| {
+                format!("following leader {} bal {}", peer, ballot)
+            });
+            self.observe_lease_held()?;
         }
 
         Ok(())
@@ -69,17 +79,44 @@ impl MultiPaxosReplica {
 
     /// If current leader is not me but times out, steps up as leader, and
     /// sends self-initiated Prepare messages to followers for all in-progress
-    /// instances.
+    /// instances. In Vertical Paxos mode, the new ballot must be `granted` by
+    /// the manager; a timeout only requests one from it.
     pub(super) async fn become_a_leader(
         &mut self,
         timeout_source: ReplicaId,
+        granted: Option<Ballot>,
     ) -> Result<(), SummersetError> {
-        if self.leader.as_ref().is_some_and(|&l| l != timeout_source)
-            || self.config.disallow_step_up
+        if granted.is_none()
+            && (self.leader.as_ref().is_some_and(|&l| l != timeout_source)
+                || self.config.disallow_step_up
+                || self.is_witness(self.id)
+                || !self.is_member(self.id))
         {
             return Ok(());
         }
 
+        if self.replaying() {
+            // not stepping up until recovered commands are all executed;
+            // keep the timer going so that I retry after replay finishes
+            pf_debug!("still replaying wal log, not stepping up");
+            if !self.config.disable_hb_timer {
+                self.heartbeater.kickoff_hear_timer(Some(timeout_source))?;
+            }
+            return Ok(());
+        }
+
+        if self.config.vertical_paxos && granted.is_none() {
+            self.control_hub.send_ctrl(CtrlMsg::BallotRequest {
+                seen_epoch: self.bal_max_seen >> 16,
+            })?;
+            pf_debug!("requested new ballot from manager");
+            // keep the timer going in case no one gets granted
+            if !self.config.disable_hb_timer {
+                self.heartbeater.kickoff_hear_timer(Some(timeout_source))?;
+            }
+            return Ok(());
+        }
+
         // if leasing enabled, start to revoke old lease
         let mut old_leader = None;
         if self.config.enable_leader_leases {
@@ -102,20 +139,25 @@ impl MultiPaxosReplica {
         self.leader = Some(self.id);
         self.control_hub
             .send_ctrl(CtrlMsg::LeaderStatus { step_up: true })?;
+        self.external_api.notify_leader_change(self.id)?;
         pf_info!("becoming a leader...");
+        panic_dump_event(|| "stepping up as leader".into());
 
         // clear peers' heartbeat reply counters, and broadcast a heartbeat now
         self.heartbeater.clear_reply_cnts(None)?;
-        self.bcast_heartbeats().await?;
+        self.bcast_heartbeats(true).await?;
 
         // re-initialize peer_exec_bar information
         for slot in self.peer_exec_bar.values_mut() {
             *slot = 0;
         }
 
-        // make a greater ballot number and invalidate all in-progress instances
+        // make a greater ballot number (or use the granted one) and invalidate
+        // all in-progress instances
         self.bal_prepared = 0;
-        self.bal_prep_sent = self.make_greater_ballot(self.bal_max_seen);
+        self.prep_stop_sign = None;
+        self.bal_prep_sent = granted
+            .unwrap_or_else(|| self.make_greater_ballot(self.bal_max_seen));
         self.bal_max_seen = self.bal_prep_sent;
 
         // find the first and last slot index for which to redo Prepare phase
@@ -142,6 +184,12 @@ impl MultiPaxosReplica {
             self.bal_prep_sent
         );
 
+        // bring back in the batches of in-progress instances paged out, as
+        // Prepare replies may overwrite them
+        for slot in self.exec_bar..(self.start_slot + self.insts.len()) {
+            self.page_in_inst(slot).await?;
+        }
+
         // redo Prepare phase for all in-progress instances
         for (slot, inst) in self
             .insts
@@ -165,7 +213,9 @@ impl MultiPaxosReplica {
                 endprep_slot,
                 prepare_acks: Bitmap::new(self.population, false),
                 prepare_max_bal: 0,
+                witness_max_bal: 0,
                 accept_acks: Bitmap::new(self.population, false),
+                commit_members: None,
             });
 
             // record update to largest prepare ballot
@@ -192,8 +242,9 @@ impl MultiPaxosReplica {
         }
         self.peer_accept_max = usize::MAX;
 
-        // send Prepare message to all peers
-        self.transport_hub.bcast_msg(
+        // send Prepare message to all peers on the control lane, so that it
+        // does not wait behind any large Accept batches
+        self.transport_hub.bcast_ctrl_msg(
             PeerMsg::Prepare {
                 trigger_slot,
                 ballot: self.bal_prep_sent,
@@ -217,9 +268,101 @@ impl MultiPaxosReplica {
         Ok(())
     }
 
-    /// Broadcasts heartbeats to all replicas.
+    /// Handler of TransferLeader control message. Hands leadership over to
+    /// the target by giving it a ballot to step up with directly. If leasing
+    /// enabled, my lease is handed off in two phases: I drop my lease first,
+    /// then grantors start granting to the target under its new ballot right
+    /// away, instead of waiting for my lease to expire. The transfer is
+    /// aborted if the target has not stepped up within a max heartbeat
+    /// hearing timeout.
+    pub(super) fn handle_ctrl_transfer_leader(
+        &mut self,
+        target: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        pf_warn!("server told to transfer leadership to {}", target);
+
+        if !self.is_leader()
+            || self.bal_prepared == 0
+            || self.transfer.is_some()
+            || self.config.vertical_paxos
+            || target == self.id
+            || target >= self.population
+            || !self.is_member(target)
+            || self.is_witness(target)
+        {
+            pf_warn!("cannot transfer leadership now, rejected");
+            self.control_hub.send_ctrl(CtrlMsg::TransferReject)?;
+            return Ok(());
+        }
+
+        // the ballot the target would have made for itself
+        let ballot =
+            (((self.bal_max_seen >> 16) + 1) << 16) | ((target + 1) as u64);
+
+        if self.config.enable_leader_leases {
+            self.lease_manager.add_notice(
+                self.bal_max_seen,
+                LeaseNotice::DoHandoff {
+                    to: target,
+                    new_num: ballot,
+                },
+            )?;
+        }
+
+        self.transport_hub
+            .send_ctrl_msg(PeerMsg::TransferLeader { ballot }, target)?;
+        let deadline = Instant::now()
+            + Duration::from_millis(self.config.hb_hear_timeout_max);
+        self.transfer = Some((target, ballot, deadline));
+        pf_info!("transferring leadership to {} bal {}", target, ballot);
+        Ok(())
+    }
+
+    /// Aborts my ongoing leadership transfer if the target has not stepped
+    /// up by the deadline, e.g., because the TransferLeader message was lost
+    /// or ignored. If leasing enabled, my lease has been handed off already,
+    /// so I take it back by re-preparing with a ballot higher than the one
+    /// the target was given; this also fences off a late step-up of the
+    /// target.
+    pub(super) async fn check_transfer_timeout(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        let Some((target, ballot, deadline)) = self.transfer else {
+            return Ok(());
+        };
+        if Instant::now() < deadline {
+            return Ok(());
+        }
+
+        pf_warn!("leadership transfer to {} timed out", target);
+        self.transfer = None;
+        self.control_hub.send_ctrl(CtrlMsg::TransferReject)?;
+
+        if self.config.enable_leader_leases && self.is_leader() {
+            let reclaim = self.make_greater_ballot(ballot);
+            self.become_a_leader(self.id, Some(reclaim)).await?;
+        }
+        Ok(())
+    }
+
+    /// Handler of TransferLeader message from the current leader.
+    pub(super) async fn handle_msg_transfer_leader(
+        &mut self,
+        peer: ReplicaId,
+        ballot: Ballot,
+    ) -> Result<(), SummersetError> {
+        pf_debug!("received TransferLeader <- {} bal {}", peer, ballot);
+        if self.leader != Some(peer) || ballot <= self.bal_max_seen {
+            return Ok(()); // outdated or not from my believed leader
+        }
+        self.become_a_leader(peer, Some(ballot)).await
+    }
+
+    /// Broadcasts heartbeats to replicas due for one, or to all replicas if
+    /// `all` is true.
     pub(super) async fn bcast_heartbeats(
         &mut self,
+        all: bool,
     ) -> Result<(), SummersetError> {
         // check and send lease promise refresh to leader
         if self.config.enable_leader_leases {
@@ -229,7 +372,7 @@ impl MultiPaxosReplica {
                         Some(&Bitmap::from((self.population, vec![leader]))),
                     )?;
                     if to_refresh.count() > 0 {
-                        self.transport_hub.bcast_lease_msg(
+                        self.transport_hub.queue_lease_msg(
                             0, // only one lease purpose exists in the system
                             self.bal_max_seen,
                             LeaseMsg::Promise,
@@ -239,18 +382,23 @@ impl MultiPaxosReplica {
                 }
             }
         }
+        self.transport_hub.flush_lease_msgs()?;
 
-        // broadcast heartbeat to all peers
-        self.transport_hub.bcast_msg(
+        // broadcast heartbeat to peers due for one
+        let targets = self.heartbeater.send_targets(all)?;
+        self.transport_hub.bcast_msg_unreliable(
             PeerMsg::Heartbeat {
                 ballot: self.bal_max_seen,
                 commit_bar: self.commit_bar,
                 exec_bar: self.exec_bar,
                 snap_bar: self.snap_bar,
             },
-            None,
+            targets,
         )?;
 
+        // sample my lease holding status if doing lease diagnostics
+        self.observe_lease_held()?;
+
         // update max heartbeat reply counters and their repetitions seen,
         // and peers' liveness status accordingly
         self.heartbeater.update_bcast_cnts()?;
@@ -265,6 +413,45 @@ impl MultiPaxosReplica {
         )
         .await?;
 
+        // expose my protocol state to debug queries
+        self.external_api.update_debug_state(DebugState {
+            is_leader: self.is_leader(),
+            leader: self.leader,
+            ballot: self.bal_max_seen,
+            commit_bar: self.commit_bar,
+            exec_bar: self.exec_bar,
+            start_slot: self.start_slot,
+            log_len: self.insts.len(),
+            peers_alive: self.heartbeater.peer_alive().clone(),
+        });
+
+        // verify overwritten instances whose slots have committed since
+        let (start_slot, insts) = (self.start_slot, &self.insts);
+        self.divergence.verify(self.commit_bar, |slot| {
+            insts
+                .get(slot.checked_sub(start_slot)?)
+                .filter(|inst| !inst.paged_out)
+                .and_then(|inst| entry_digest(&inst.reqs).ok())
+        });
+
+        // periodically report my status to the manager
+        self.control_hub.report_status(ServerStatus {
+            ballot: self.bal_max_seen,
+            commit_bar: self.commit_bar,
+            exec_bar: self.exec_bar,
+            peers_alive: self.heartbeater.peer_alive().clone(),
+            perf_models: HashMap::new(),
+            hot_keys: self.state_machine.hot_keys(),
+            queue_depths: vec![
+                self.external_api.queue_depth_report(),
+                self.storage_hub.queue_depth_report(),
+                self.transport_hub.queue_depth_report(),
+                self.state_machine.queue_depth_report(),
+            ],
+            resources: ResourceUsage::default(),
+            divergence_repaired: self.divergence.repaired(),
+        })?;
+
         // pf_trace!("broadcast heartbeats bal {}", self.bal_prep_sent);
         Ok(())
     }
@@ -288,6 +475,30 @@ impl MultiPaxosReplica {
             // a new leader
             self.check_leader(peer, ballot).await?;
 
+            // keep asking the leader for blobs my execution is stalled on
+            if self.leader == Some(peer) {
+                self.refetch_stalled_blobs(peer)?;
+            }
+
+            // offer my snapshot to a peer lagging behind it
+            if self.is_leader() && exec_bar < self.start_slot {
+                self.offer_snapshot(peer)?;
+            }
+
+            // a peer still on a lower ballot may be a deposed leader that
+            // missed my latest heartbeats; nudge it with one right away
+            if ballot < self.bal_max_seen {
+                self.transport_hub.send_msg_unreliable(
+                    PeerMsg::Heartbeat {
+                        ballot: self.bal_max_seen,
+                        commit_bar: self.commit_bar,
+                        exec_bar: self.exec_bar,
+                        snap_bar: self.snap_bar,
+                    },
+                    peer,
+                )?;
+            }
+
             // reply back with a Heartbeat message
             // NOTE: commented out to favor the new all-to-all heartbeats
             //       pattern; performance-wise should have little impact
@@ -311,6 +522,7 @@ impl MultiPaxosReplica {
         if !self.config.disable_hb_timer
             && self.leader == Some(peer)
             && self.bal_max_seen == ballot
+            && self.is_member(peer)
         {
             self.heartbeater.kickoff_hear_timer(Some(peer))?;
         }
@@ -323,18 +535,19 @@ impl MultiPaxosReplica {
         self.advance_commit_bar(peer, ballot, commit_bar)?;
 
         if peer != self.id {
-            // update peer_exec_bar if larger then known; if all servers'
+            // update peer_exec_bar if larger then known; if all members'
             // exec_bar (including myself) have passed a slot, that slot
             // is definitely safe to be snapshotted
             if exec_bar > self.peer_exec_bar[&peer] {
                 *self.peer_exec_bar.get_mut(&peer).unwrap() = exec_bar;
-                let passed_cnt = 1 + self
-                    .peer_exec_bar
-                    .values()
-                    .filter(|&&e| e >= exec_bar)
-                    .count() as u8;
-                if passed_cnt == self.population {
-                    // all servers have executed up to exec_bar
+                let passed_cnt = self.is_member(self.id) as u16
+                    + self
+                        .peer_exec_bar
+                        .iter()
+                        .filter(|&(&p, &e)| e >= exec_bar && self.is_member(p))
+                        .count() as u16;
+                if passed_cnt >= self.members.count() {
+                    // all members have executed up to exec_bar
                     self.snap_bar = exec_bar;
                 }
             }
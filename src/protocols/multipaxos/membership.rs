@@ -0,0 +1,202 @@
+//! MultiPaxos -- membership reconfiguration through stop-sign entries.
+//!
+//! A stop-sign is a special log entry that freezes the current configuration
+//! of voting members: the leader proposes nothing after it until it commits,
+//! and every instance after it is decided by quorums of the new members.
+//! Replicas outside the new configuration keep learning the log, but neither
+//! count towards quorums nor step up as leader. Reference:
+//!   - <https://dl.acm.org/doi/10.1145/3552326.3587441>
+
+use super::*;
+
+use crate::manager::CtrlMsg;
+use crate::server::{ApiRequest, ConfChange};
+use crate::utils::{Bitmap, ConfNum, SummersetError};
+
+/// Client ID used as the source of stop-sign entries in request batches.
+const STOP_SIGN_CLIENT: ClientId = ClientId::MAX;
+
+// MultiPaxosReplica membership reconfiguration logic
+impl MultiPaxosReplica {
+    /// Compose the request batch value of a stop-sign entry. The new set of
+    /// voting members is carried as the responders bitmap of a conf change.
+    fn make_stop_sign(members: Bitmap) -> ReqBatch {
+        vec![(
+            STOP_SIGN_CLIENT,
+            ApiRequest::Conf {
+                id: 0,
+                delta: ConfChange {
+                    responders: Some(members),
+                    ..Default::default()
+                },
+            },
+        )]
+    }
+
+    /// If the request batch is a stop-sign entry, returns its new members.
+    pub(super) fn parse_stop_sign(reqs: &ReqBatch) -> Option<&Bitmap> {
+        match reqs.as_slice() {
+            [(
+                STOP_SIGN_CLIENT,
+                ApiRequest::Conf {
+                    delta:
+                        ConfChange {
+                            responders: Some(members),
+                            ..
+                        },
+                    ..
+                },
+            )] => Some(members),
+            _ => None,
+        }
+    }
+
+    /// Is the given replica a voting member of the current configuration?
+    #[inline]
+    pub(super) fn is_member(&self, peer: ReplicaId) -> bool {
+        self.members.get(peer).unwrap_or(false)
+    }
+
+    /// May the given peer send me Prepares and Accepts? It must be a voting
+    /// member of my latest committed configuration, or of a stop-sign I have
+    /// accepted but not yet seen committed. In Vertical Paxos mode, ballots
+    /// are fenced by the configuration master instead.
+    pub(super) fn may_lead(&self, peer: ReplicaId) -> bool {
+        self.config.vertical_paxos
+            || self.is_member(peer)
+            || self.insts[(self.commit_bar - self.start_slot)..]
+                .iter()
+                .any(|inst| {
+                    inst.status >= Status::Accepting
+                        && Self::parse_stop_sign(&inst.reqs)
+                            .is_some_and(|m| m.get(peer).unwrap_or(false))
+                })
+    }
+
+    /// Count the voting members among the replicas set in `acks`.
+    #[inline]
+    pub(super) fn member_cnt(members: &Bitmap, acks: &Bitmap) -> u16 {
+        acks.iter()
+            .filter(|&(p, flag)| flag && members.get(p).unwrap_or(false))
+            .count() as u16
+    }
+
+    /// Is there a stop-sign entry in my log that is not yet committed?
+    pub(super) fn pending_stop_sign(&self) -> bool {
+        self.insts[(self.commit_bar - self.start_slot)..]
+            .iter()
+            .any(|inst| {
+                inst.status >= Status::Preparing
+                    && Self::parse_stop_sign(&inst.reqs).is_some()
+            })
+    }
+
+    /// Switch to a new configuration of voting members.
+    pub(super) fn set_members(
+        &mut self,
+        members: Bitmap,
+    ) -> Result<(), SummersetError> {
+        if members.size() != self.population || members.count() == 0 {
+            return logged_err!("invalid members bitmap {:?}", members);
+        }
+        self.quorum_cnt = (members.count() / 2) + 1;
+        self.members = members;
+        Ok(())
+    }
+
+    /// Apply the new configuration of the stop-sign entry committed at slot,
+    /// which also becomes the config epoch fencing client requests. If I'm
+    /// the leader, report it to the manager, and step down if I'm no longer
+    /// a voting member.
+    pub(super) fn apply_stop_sign(
+        &mut self,
+        slot: usize,
+        members: Bitmap,
+    ) -> Result<(), SummersetError> {
+        self.set_members(members)?;
+        self.external_api.set_conf_epoch(slot as ConfNum);
+        pf_info!("members now {:?} after slot {}", self.members, slot);
+
+        if self.is_leader() {
+            self.control_hub.send_ctrl(CtrlMsg::MembersConf {
+                members: self.members.clone(),
+                slot,
+            })?;
+
+            if !self.is_member(self.id) {
+                // peers ignore heartbeats from non-members and will time out
+                // on me, electing a leader among the new members
+                self.leader = None;
+                self.control_hub
+                    .send_ctrl(CtrlMsg::LeaderStatus { step_up: false })?;
+                pf_info!("no longer a leader...");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Proposes a stop-sign entry starting a new configuration with the
+    /// given voting members, if I can right now. Returns false if not.
+    fn propose_stop_sign(
+        &mut self,
+        members: Bitmap,
+    ) -> Result<bool, SummersetError> {
+        // leader leases are granted among the full population and thus do not
+        // support membership changes
+        if !self.is_leader()
+            || self.bal_prepared == 0
+            || self.config.enable_leader_leases
+            || members.size() != self.population
+            || members.count() == 0
+            || self.pending_stop_sign()
+        {
+            return Ok(false);
+        }
+
+        // the stop-sign must be the last entry of the old configuration, so
+        // always append it at the end of the log instead of filling holes
+        self.insts.push(self.null_instance());
+        let slot = self.start_slot + self.insts.len() - 1;
+        self.propose_batch(slot, Self::make_stop_sign(members), false)?;
+        Ok(true)
+    }
+
+    /// Handler of StopSign control message.
+    pub(super) fn handle_ctrl_stop_sign(
+        &mut self,
+        members: Bitmap,
+    ) -> Result<(), SummersetError> {
+        pf_warn!("server told to propose stop-sign {:?}", members);
+        if !self.propose_stop_sign(members)? {
+            pf_warn!("cannot propose stop-sign now, rejected");
+            self.control_hub.send_ctrl(CtrlMsg::StopSignReject)?;
+        }
+        Ok(())
+    }
+
+    /// Handler of Reconfigure control message. Proposes a stop-sign with the
+    /// current members plus `add` minus `remove`.
+    pub(super) fn handle_ctrl_reconfigure(
+        &mut self,
+        add: Bitmap,
+        remove: Bitmap,
+    ) -> Result<(), SummersetError> {
+        pf_warn!("server told to reconfigure +{:?} -{:?}", add, remove);
+        let mut members = self.members.clone();
+        if add.size() == self.population && remove.size() == self.population {
+            for p in 0..self.population {
+                if remove.get(p)? {
+                    members.set(p, false)?;
+                } else if add.get(p)? {
+                    members.set(p, true)?;
+                }
+            }
+        }
+        if members == self.members || !self.propose_stop_sign(members)? {
+            pf_warn!("cannot reconfigure now, rejected");
+            self.control_hub.send_ctrl(CtrlMsg::ReconfigureReject)?;
+        }
+        Ok(())
+    }
+}
@@ -4,7 +4,7 @@ use std::cmp;
 
 use super::*;
 
-use crate::server::{LogAction, ReplicaId};
+use crate::server::{entry_digest, LogAction, ReplicaId};
 use crate::utils::SummersetError;
 
 // MultiPaxosReplica peer-peer messages handling
@@ -25,6 +25,10 @@ impl MultiPaxosReplica {
             trigger_slot,
             ballot
         );
+        if !self.may_lead(peer) {
+            pf_warn!("ignored Prepare <- non-member {} bal {}", peer, ballot);
+            return Ok(());
+        }
 
         // if ballot is not smaller than what I have seen:
         if ballot >= self.bal_max_seen {
@@ -92,7 +96,7 @@ impl MultiPaxosReplica {
         trigger_slot: usize,
         endprep_slot: usize,
         ballot: Ballot,
-        voted: Option<(Ballot, ReqBatch)>,
+        voted: Option<(Ballot, ReqBatch, HlcStamp)>,
         accept_bar: usize,
     ) -> Result<(), SummersetError> {
         if slot < self.start_slot {
@@ -168,7 +172,9 @@ impl MultiPaxosReplica {
                     endprep_slot: my_endprep_slot,
                     prepare_acks: Bitmap::new(self.population, false),
                     prepare_max_bal: 0,
+                    witness_max_bal: 0,
                     accept_acks: Bitmap::new(self.population, false),
+                    commit_members: None,
                 });
 
                 // record update to largest prepare ballot
@@ -190,6 +196,7 @@ impl MultiPaxosReplica {
             }
 
             {
+                let from_witness = self.is_witness(peer);
                 let inst = &mut self.insts[slot - self.start_slot];
 
                 // ignore spurious duplications and outdated replies
@@ -200,17 +207,33 @@ impl MultiPaxosReplica {
                 debug_assert!(self.bal_max_seen >= ballot);
 
                 // bookkeep this Prepare reply
-                if let Some((bal, val)) = voted {
+                if let Some((bal, val, hlc)) = voted {
                     debug_assert!(inst.leader_bk.is_some());
                     let leader_bk = inst.leader_bk.as_mut().unwrap();
-                    if bal > leader_bk.prepare_max_bal {
+                    if from_witness && Self::parse_stop_sign(&val).is_none() {
+                        // a witness only knows the ballot, not the value
+                        leader_bk.witness_max_bal =
+                            cmp::max(leader_bk.witness_max_bal, bal);
+                    } else if bal > leader_bk.prepare_max_bal {
                         leader_bk.prepare_max_bal = bal;
+                        if let Some(members) = Self::parse_stop_sign(&val) {
+                            // later slots may have been decided by the new
+                            // members, so my Prepare must reach them as well
+                            self.prep_stop_sign = Some(members.clone());
+                        }
                         inst.reqs = val;
+                        inst.hlc = hlc;
+                        self.clock.observe(hlc);
                         Self::refresh_highest_slot(
                             slot,
                             &inst.reqs,
                             &mut self.highest_slot,
                         );
+                        Self::note_blob_uses(
+                            slot,
+                            &inst.reqs,
+                            &mut self.blob_store,
+                        );
                     }
                 }
             }
@@ -219,6 +242,8 @@ impl MultiPaxosReplica {
             // include the sender peer into the quorum (by updating the
             // prepare_acks field in the trigger_slot entry)
             if slot == endprep_slot {
+                let witness_resolved =
+                    self.witness_votes_resolved(trigger_slot);
                 let trigger_inst =
                     &mut self.insts[trigger_slot - self.start_slot];
                 debug_assert!(trigger_inst.leader_bk.is_some());
@@ -228,12 +253,38 @@ impl MultiPaxosReplica {
 
                 // if quorum size reached, enter Accept phase for all instances
                 // at and after trigger_slot; for each entry, use the request
-                // batch value with the highest ballot number in quorum
-                if trigger_leader_bk.prepare_acks.count() >= self.quorum_cnt {
+                // batch value with the highest ballot number in quorum. In
+                // Vertical Paxos mode, the quorum is taken from the previous
+                // configuration until the new ballot becomes active. If some
+                // slot's value is so far known only to witnesses, wait for
+                // more replies from full replicas
+                let prep_members =
+                    self.prep_members.as_ref().unwrap_or(&self.members);
+                if witness_resolved
+                    && Self::member_cnt(
+                        prep_members,
+                        &trigger_leader_bk.prepare_acks,
+                    ) > prep_members.count() / 2
+                    && self.prep_stop_sign.as_ref().is_none_or(|members| {
+                        Self::member_cnt(
+                            members,
+                            &trigger_leader_bk.prepare_acks,
+                        ) > members.count() / 2
+                    })
+                {
                     // update bal_prepared
                     debug_assert!(self.bal_prepared <= ballot);
                     self.bal_prepared = ballot;
 
+                    // tell the configuration master that my ballot is active
+                    if self.config.vertical_paxos {
+                        self.prep_members = None;
+                        self.control_hub.send_ctrl(CtrlMsg::BallotActive {
+                            epoch: ballot >> 16,
+                        })?;
+                    }
+
+                    let mut accepting = vec![];
                     for (this_slot, inst) in self
                         .insts
                         .iter_mut()
@@ -248,6 +299,11 @@ impl MultiPaxosReplica {
                             this_slot,
                             inst.bal
                         );
+                        if inst.hlc == HlcStamp::default() {
+                            // a slot no one has proposed into before gets
+                            // stamped by me now
+                            inst.hlc = self.clock.now();
+                        }
 
                         // record update to largest accepted ballot and its
                         // corresponding data
@@ -261,6 +317,7 @@ impl MultiPaxosReplica {
                                     slot: this_slot,
                                     ballot,
                                     reqs: inst.reqs.clone(),
+                                    hlc: inst.hlc,
                                 },
                                 sync: self.config.logger_sync,
                             },
@@ -270,15 +327,16 @@ impl MultiPaxosReplica {
                             this_slot, ballot
                         );
 
-                        // send Accept messages to all peers
-                        self.transport_hub.bcast_msg(
-                            PeerMsg::Accept {
-                                slot: this_slot,
-                                ballot,
-                                reqs: inst.reqs.clone(),
-                            },
-                            None,
-                        )?;
+                        accepting.push((
+                            this_slot,
+                            inst.reqs.clone(),
+                            inst.hlc,
+                        ));
+                    }
+
+                    // send Accept messages to all peers
+                    for (this_slot, reqs, hlc) in accepting {
+                        self.bcast_accept(this_slot, ballot, reqs, hlc)?;
                         pf_trace!(
                             "broadcast Accept messages for slot {} bal {}",
                             this_slot,
@@ -293,12 +351,13 @@ impl MultiPaxosReplica {
     }
 
     /// Handler of Accept message from leader.
-    async fn handle_msg_accept(
+    pub(super) async fn handle_msg_accept(
         &mut self,
         peer: ReplicaId,
         slot: usize,
         ballot: Ballot,
         reqs: ReqBatch,
+        hlc: HlcStamp,
     ) -> Result<(), SummersetError> {
         if slot < self.start_slot {
             return Ok(()); // ignore if slot index outdated
@@ -309,6 +368,10 @@ impl MultiPaxosReplica {
             slot,
             ballot
         );
+        if !self.may_lead(peer) {
+            pf_warn!("ignored Accept <- non-member {} bal {}", peer, ballot);
+            return Ok(());
+        }
 
         // if ballot is not smaller than what I have made promises for:
         if ballot >= self.bal_max_seen {
@@ -318,17 +381,45 @@ impl MultiPaxosReplica {
                 self.heartbeater.kickoff_hear_timer(Some(peer))?;
             }
 
+            // if my WAL disk is critically short of space, leave the Accept
+            // unvoted rather than risk failing mid-append; the leader gathers
+            // its quorum from others
+            if let Some(free_bytes) = self.wal_disk_critical {
+                pf_warn!(
+                    "WAL disk {} MiB free, not voting for slot {} bal {}",
+                    free_bytes / (1024 * 1024),
+                    slot,
+                    ballot
+                );
+                return Ok(());
+            }
+
+            // in blob store mode, vote only once all blobs referenced are
+            // durable locally
+            if self.hold_accept_for_blobs(peer, slot, ballot, &reqs, hlc)? {
+                return Ok(());
+            }
+
             // locate instance in memory, filling in null instances if needed
             while self.start_slot + self.insts.len() <= slot {
                 self.insts.push(self.null_instance());
             }
+            self.page_in_inst(slot).await?;
             let inst = &mut self.insts[slot - self.start_slot];
             debug_assert!(inst.bal <= ballot);
+            if inst.voted.0 > 0 && inst.voted.0 < ballot {
+                // record what I had accepted before, for verification once
+                // the slot commits
+                self.divergence.record(slot, entry_digest(&inst.voted.1)?);
+            }
 
             inst.bal = ballot;
             inst.status = Status::Accepting;
             inst.reqs.clone_from(&reqs);
+            inst.hlc = hlc;
+            self.clock.observe(hlc);
             Self::refresh_highest_slot(slot, &reqs, &mut self.highest_slot);
+            Self::note_blob_uses(slot, &reqs, &mut self.blob_store);
             if let Some(replica_bk) = inst.replica_bk.as_mut() {
                 replica_bk.source = peer;
             } else {
@@ -349,11 +440,16 @@ impl MultiPaxosReplica {
             }
 
             // record update to instance ballot & data
-            inst.voted = (ballot, reqs.clone());
+            inst.voted = (ballot, reqs.clone(), hlc);
             self.storage_hub.submit_action(
                 Self::make_log_action_id(slot, Status::Accepting),
                 LogAction::Append {
-                    entry: WalEntry::AcceptData { slot, ballot, reqs },
+                    entry: WalEntry::AcceptData {
+                        slot,
+                        ballot,
+                        reqs,
+                        hlc,
+                    },
                     sync: self.config.logger_sync,
                 },
             )?;
@@ -410,8 +506,11 @@ impl MultiPaxosReplica {
             leader_bk.accept_acks.set(peer, true)?;
 
             // if quorum size reached, mark this instance as committed
-            if leader_bk.accept_acks.count() >= self.quorum_cnt {
+            if Self::member_cnt(&self.members, &leader_bk.accept_acks)
+                >= self.quorum_cnt
+            {
                 inst.status = Status::Committed;
+                leader_bk.commit_members = Some(self.members.clone());
                 pf_debug!(
                     "committed instance at slot {} bal {}",
                     slot,
@@ -470,9 +569,12 @@ impl MultiPaxosReplica {
                 voted,
                 accept_bar,
             ),
-            PeerMsg::Accept { slot, ballot, reqs } => {
-                self.handle_msg_accept(peer, slot, ballot, reqs).await
-            }
+            PeerMsg::Accept {
+                slot,
+                ballot,
+                reqs,
+                hlc,
+            } => self.handle_msg_accept(peer, slot, ballot, reqs, hlc).await,
             PeerMsg::AcceptReply {
                 slot,
                 ballot,
@@ -505,6 +607,36 @@ impl MultiPaxosReplica {
             PeerMsg::CommitNotice { ballot, commit_bar } => {
                 self.heard_commit_notice(peer, ballot, commit_bar)
             }
+            PeerMsg::TransferLeader { ballot } => {
+                self.handle_msg_transfer_leader(peer, ballot).await
+            }
+            PeerMsg::BlobFetch { blobs } => {
+                self.handle_msg_blob_fetch(peer, blobs).await
+            }
+            PeerMsg::BlobReply { blob, value } => {
+                self.handle_msg_blob_reply(peer, blob, value).await
+            }
+            PeerMsg::SnapshotOffer { start_slot } => {
+                self.handle_msg_snapshot_offer(peer, start_slot).await
+            }
+            PeerMsg::SnapshotFetch { offset } => {
+                self.handle_msg_snapshot_fetch(peer, offset).await
+            }
+            PeerMsg::SnapshotChunk {
+                start_slot,
+                offset,
+                next_offset,
+                entry,
+            } => {
+                self.handle_msg_snapshot_chunk(
+                    peer,
+                    start_slot,
+                    offset,
+                    next_offset,
+                    entry,
+                )
+                .await
+            }
         }
     }
 }
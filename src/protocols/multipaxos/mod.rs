@@ -7,19 +7,30 @@
 //!   - <https://github.com/josehu07/learn-tla/tree/main/Dr.-TLA%2B-selected/multipaxos_practical>
 //!   - <https://github.com/efficient/epaxos/blob/master/src/paxos/paxos.go>
 //!   - <https://www.usenix.org/system/files/hotstorage19-paper-charapko.pdf>
+//!   - <https://lamport.azurewebsites.net/pubs/vertical-paxos.pdf>
 
+mod blobs;
 mod control;
+mod coordsnap;
+mod dumping;
 mod durability;
 mod execution;
+mod fsck;
+mod install;
 mod leaderlease;
 mod leadership;
+mod membership;
 mod messages;
+mod paging;
 mod quorumread;
 mod recovery;
+mod replay;
 mod request;
 mod snapshot;
+mod vertical;
+mod witness;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::net::SocketAddr;
 use std::path::Path;
 use std::time::SystemTime;
@@ -28,12 +39,13 @@ use crate::client::{ClientApiStub, ClientCtrlStub, ClientId, GenericEndpoint};
 use crate::manager::{CtrlMsg, CtrlReply, CtrlRequest};
 use crate::protocols::SmrProtocol;
 use crate::server::{
-    ApiReply, ApiRequest, Command, CommandId, CommandResult, ControlHub,
-    ExternalApi, GenericReplica, HeartbeatEvent, Heartbeater, LeaseManager,
-    LeaseMsg, LeaseNum, LogActionId, ReplicaId, RequestId, StateMachine,
-    StorageHub, TransportHub,
+    ApiReply, ApiRequest, AuditLog, BlobRef, BlobStore, Command, CommandId,
+    CommandResult, ControlHub, DivergenceTracker, ExternalApi, GenericReplica,
+    HeartbeatEvent, Heartbeater, LeaseManager, LeaseMsg, LeaseNum, LogActionId,
+    ReadConsistency, ReplicaId, RequestId, StateMachine, StorageHub,
+    TransportHub,
 };
-use crate::utils::{Bitmap, Stopwatch, SummersetError};
+use crate::utils::{Bitmap, HlcStamp, HybridClock, Stopwatch, SummersetError};
 
 use atomic_refcell::AtomicRefCell;
 
@@ -43,6 +55,7 @@ use get_size::GetSize;
 
 use serde::{Deserialize, Serialize};
 
+use tokio::fs::File;
 use tokio::sync::watch;
 use tokio::time::{self, Duration, Instant, Interval, MissedTickBehavior};
 
@@ -55,6 +68,25 @@ pub struct ReplicaConfigMultiPaxos {
     /// Client request batching maximum batch size.
     pub max_batch_size: usize,
 
+    /// Number of completed replies cached per client for collapsing retried
+    /// requests by idempotency key; 0 means collapsing disabled.
+    pub idem_cache_size: usize,
+
+    /// Idle timeout in secs after which a client session that sent no request
+    /// is closed and its state reclaimed; 0 means never.
+    pub session_idle_s: u64,
+
+    /// Quota in KiB of approximate key-value bytes stored per namespace (the
+    /// key prefix before the first '/'), beyond which Puts are rejected; 0
+    /// means unlimited.
+    pub ns_quota_kb: usize,
+
+    /// Path to a database persisting the state machine's key-value state,
+    /// so that recovery only re-executes commands it had not applied yet
+    /// (requires the `persistent-state` feature); empty string means the
+    /// state is kept in memory only.
+    pub state_path: String,
+
     /// Path to backing log file.
     pub backer_path: String,
 
@@ -72,9 +104,22 @@ pub struct ReplicaConfigMultiPaxos {
     /// Disable heartbeat timer (to e.g., force a known leader during tests).
     pub disable_hb_timer: bool,
 
+    /// Per-peer overrides of heartbeat timing, e.g., longer ones for peers
+    /// across a WAN link, in format of `peer:send_ms,min_ms,max_ms` entries
+    /// separated by `/`. Empty string means no overrides.
+    pub hb_peer_timings: String,
+
+    /// Send heartbeats through a UDP side-channel at the same address as the
+    /// p2p TCP listener, so they do not queue up behind bulky messages.
+    pub udp_heartbeats: bool,
+
     /// Disallow me to ever attempt stepping up as leader?
     pub disallow_step_up: bool,
 
+    /// Comma-separated IDs of witness replicas, which store only ballots and
+    /// not values, and never lead. Empty string means no witnesses.
+    pub witnesses: String,
+
     /// Lease-related timeout duration in millisecs.
     pub lease_expire_ms: u64,
 
@@ -84,19 +129,62 @@ pub struct ReplicaConfigMultiPaxos {
     /// Enable nearest majority quorum read optimization?
     pub enable_quorum_reads: bool,
 
+    /// Report intervals during which I believe I hold a majority lease to
+    /// the manager, which cross-checks that no two replicas' intervals
+    /// overlap? Only effective if enable_leader_leases is set to true.
+    pub lease_diagnostics: bool,
+
+    /// Run in Vertical Paxos mode, where the manager acts as configuration
+    /// master issuing new ballots and voting members on leader changes?
+    pub vertical_paxos: bool,
+
+    /// Attach commit certificates to replies of writes?
+    pub attach_commit_certs: bool,
+
     /// Enable promptive CommitNotice sending for committed instances?
     pub urgent_commit_notice: bool,
 
     /// Path to snapshot file.
     pub snapshot_path: String,
 
+    /// Values of Puts larger than this many KiB are kept in a replica-local
+    /// blob store, with the log carrying only references to them; 0 means
+    /// blob store mode disabled.
+    pub blob_threshold_kb: usize,
+
+    /// Directory of the replica-local blob store.
+    pub blob_dir: String,
+
+    /// Path prefix of audit log segment files recording executed commands.
+    /// Empty string means audit logging disabled.
+    pub audit_path: String,
+
+    /// Audit log segment size in MiB at which to rotate to a new segment.
+    /// 0 means never rotate.
+    pub audit_rotate_mb: usize,
+
     /// Snapshot self-triggering interval in secs. 0 means never trigger
     /// snapshotting autonomously.
     pub snapshot_interval_s: u64,
 
+    /// Minimum free space in MiB to keep on the WAL's filesystem. Below it,
+    /// a snapshot is forced to compact the WAL, and if space is still short,
+    /// writes are rejected until it frees up. 0 means no guard.
+    pub wal_min_free_mb: u64,
+
     /// Maximum chunk size (in slots) of any bulk messages.
     pub msg_chunk_size: usize,
 
+    /// Number of most recent log instances whose request batches are kept
+    /// in memory; batches of older instances not yet snapshotted are paged
+    /// back in from the WAL when needed. 0 means keeping all in memory.
+    pub mem_log_window: usize,
+
+    /// Execute commands recovered from the WAL in the background, so that
+    /// peer messages get served while the state machine is still catching
+    /// up after restart?
+    pub parallel_recovery: bool,
+
     // [for perf breakdown only]
     /// Recording performance breakdown statistics?
     pub record_breakdown: bool,
@@ -127,20 +215,37 @@ impl Default for ReplicaConfigMultiPaxos {
         ReplicaConfigMultiPaxos {
             batch_interval_ms: 1,
             max_batch_size: 5000,
+            idem_cache_size: 0,
+            session_idle_s: 0,
+            ns_quota_kb: 0,
+            state_path: "".into(),
             backer_path: "/tmp/summerset.multipaxos.wal".into(),
             logger_sync: false,
             hb_hear_timeout_min: 1200,
             hb_hear_timeout_max: 2000,
             hb_send_interval_ms: 20,
             disable_hb_timer: false,
+            hb_peer_timings: "".into(),
+            udp_heartbeats: false,
             disallow_step_up: false,
+            witnesses: "".into(),
             lease_expire_ms: 2000, // need proper hb settings if leasing
             enable_leader_leases: false,
             enable_quorum_reads: false,
+            lease_diagnostics: false,
+            vertical_paxos: false,
+            attach_commit_certs: false,
             urgent_commit_notice: false,
             snapshot_path: "/tmp/summerset.multipaxos.snap".into(),
+            blob_threshold_kb: 0,
+            blob_dir: "/tmp/summerset.multipaxos.blobs".into(),
             snapshot_interval_s: 0,
+            wal_min_free_mb: 0,
+            audit_path: "".into(),
+            audit_rotate_mb: 64,
             msg_chunk_size: 10,
+            mem_log_window: 0,
+            parallel_recovery: false,
             record_breakdown: false,
             record_value_ver: false,
             record_size_recv: false,
@@ -150,6 +255,9 @@ impl Default for ReplicaConfigMultiPaxos {
     }
 }
 
+/// Interval of checking free space on the WAL's filesystem, if guarded.
+const DISK_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
 /// Ballot number type. Use 0 as a null ballot number.
 type Ballot = u64;
 
@@ -184,8 +292,16 @@ struct LeaderBookkeeping {
     /// Max ballot among received Prepare replies.
     prepare_max_bal: Ballot,
 
+    /// Max ballot among received Prepare replies from witnesses, which carry
+    /// no value.
+    witness_max_bal: Ballot,
+
     /// Replicas from which I have received Accept confirmations.
     accept_acks: Bitmap,
+
+    /// Voting members whose quorum committed this instance, kept for its
+    /// commit certificate.
+    commit_members: Option<Bitmap>,
 }
 
 /// Follower-side bookkeeping info for each instance received.
@@ -213,6 +329,10 @@ struct ReadQueryBookkeeping {
 
     /// The reply with the highest slot number found for each key.
     max_replies: Vec<Option<(usize, Option<String>)>>,
+
+    /// If true, reads whose highest slot is not yet committed wait for it to
+    /// commit on me (a rinse) instead of falling back to the leader.
+    rinse: bool,
 }
 
 /// In-memory instance containing a commands batch.
@@ -229,9 +349,14 @@ struct Instance {
     /// to avoid storing the full set of replies in `LeaderBookkeeping`.
     reqs: ReqBatch,
 
-    /// Highest ballot and associated value I have accepted; this field is
-    /// required to support correct Prepare phase replies.
-    voted: (Ballot, ReqBatch),
+    /// Timestamp the leader stamped `reqs` with when first proposing it.
+    /// The stamp travels with the batch and all its commands execute at it
+    /// on every replica.
+    hlc: HlcStamp,
+
+    /// Highest ballot and associated value (with its stamp) I have accepted;
+    /// this field is required to support correct Prepare phase replies.
+    voted: (Ballot, ReqBatch, HlcStamp),
 
     /// Leader-side bookkeeping info.
     leader_bk: Option<LeaderBookkeeping>,
@@ -244,6 +369,10 @@ struct Instance {
 
     /// Offset of first durable WAL log entry related to this instance.
     wal_offset: usize,
+
+    /// True if the request batch has been dropped from memory, to be paged
+    /// back in from the WAL when needed.
+    paged_out: bool,
 }
 
 /// Stable storage WAL log entry type.
@@ -257,6 +386,7 @@ enum WalEntry {
         slot: usize,
         ballot: Ballot,
         reqs: ReqBatch,
+        hlc: HlcStamp,
     },
 
     /// Records an event of committing the instance at index.
@@ -265,9 +395,9 @@ enum WalEntry {
 
 /// Snapshot file entry type.
 //
-// NOTE: the current implementation simply appends a squashed log at the
-//       end of the snapshot file for simplicity. In production, the snapshot
-//       file should be a bounded-sized backend, e.g., an LSM-tree.
+// NOTE: the current implementation rewrites a whole image of the state
+//       machine into the snapshot file for simplicity. In production, the
+//       snapshot file should be a bounded-sized backend, e.g., an LSM-tree.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, GetSize)]
 enum SnapEntry {
     /// Necessary slot indices to remember.
@@ -277,8 +407,43 @@ enum SnapEntry {
         start_slot: usize,
     },
 
-    /// Set of key-value pairs to apply to the state.
-    KVPairSet { pairs: HashMap<String, String> },
+    /// Image of the state machine taken by `take_snapshot()`, reflecting
+    /// exactly the commands of all slots before start_slot.
+    StateImage { image: Vec<u8> },
+
+    /// Voting members set by the latest stop-sign covered by the snapshot.
+    Members { members: Bitmap },
+}
+
+/// Snapshot install staging file entry type.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, GetSize)]
+enum InstallEntry {
+    /// First entry at the start of file: the peer whose snapshot file is
+    /// being installed, and the start slot of that snapshot.
+    Source { peer: ReplicaId, start_slot: usize },
+
+    /// A snapshot entry received, with the offset in the source's snapshot
+    /// file right after it, i.e., where to resume from.
+    Chunk {
+        entry: SnapEntry,
+        next_offset: usize,
+    },
+}
+
+/// Follower-side progress of installing a peer's snapshot.
+#[derive(Debug, Clone)]
+struct SnapInstall {
+    /// Peer whose snapshot file is being installed.
+    source: ReplicaId,
+
+    /// Start slot of the snapshot being installed.
+    start_slot: usize,
+
+    /// Offset in the source's snapshot file to fetch the next chunk from.
+    next_offset: usize,
+
+    /// When the outstanding fetch was sent, if any.
+    fetched_at: Option<Instant>,
 }
 
 /// Peer-peer message type.
@@ -310,8 +475,8 @@ enum PeerMsg {
         endprep_slot: usize,
         ballot: Ballot,
         /// Map from slot index -> the accepted ballot number for that
-        /// instance and the corresponding request batch value.
-        voted: Option<(Ballot, ReqBatch)>,
+        /// instance and the corresponding request batch value and stamp.
+        voted: Option<(Ballot, ReqBatch, HlcStamp)>,
         /// For stable majority-leased leader to determined when it is safe to
         /// start serving reads single-handedly. A better implementation now
         /// would be to use the `Guard` message to carry this information, but
@@ -324,6 +489,7 @@ enum PeerMsg {
         slot: usize,
         ballot: Ballot,
         reqs: ReqBatch,
+        hlc: HlcStamp,
     },
 
     /// Accept reply from replica to leader.
@@ -369,6 +535,33 @@ enum PeerMsg {
 
     /// Promptive notification of commits from leader to replicas.
     CommitNotice { ballot: Ballot, commit_bar: usize },
+
+    /// Planned leadership transfer from the current leader to the target,
+    /// carrying the ballot for the target to step up with.
+    TransferLeader { ballot: Ballot },
+
+    /// Request for the content of blobs missing on the sender, in blob store
+    /// mode.
+    BlobFetch { blobs: Vec<BlobRef> },
+
+    /// Reply carrying the content of a blob, in blob store mode.
+    BlobReply { blob: BlobRef, value: String },
+
+    /// Offer from leader to a peer lagging behind my snapshot to install it.
+    SnapshotOffer { start_slot: usize },
+
+    /// Request for the chunk of the leader's snapshot file at offset.
+    SnapshotFetch { offset: usize },
+
+    /// Reply carrying the snapshot entry at offset and the offset right
+    /// after it. `None` entry means end of file reached, at which point
+    /// the entries cover all slots before `start_slot`.
+    SnapshotChunk {
+        start_slot: usize,
+        offset: usize,
+        next_offset: usize,
+        entry: Option<SnapEntry>,
+    },
 }
 
 /// MultiPaxos server replica module.
@@ -377,10 +570,26 @@ pub(crate) struct MultiPaxosReplica {
     id: ReplicaId,
 
     /// Total number of replicas in cluster.
-    population: u8,
+    population: u16,
 
-    /// Majority quorum size.
-    quorum_cnt: u8,
+    /// Majority quorum size among current voting members.
+    quorum_cnt: u16,
+
+    /// Voting members of the current configuration, changed only by
+    /// committed stop-sign entries.
+    members: Bitmap,
+
+    /// In Vertical Paxos mode, voting members of the previous configuration
+    /// whose majority my Prepare phase must reach before my granted ballot
+    /// becomes active.
+    prep_members: Option<Bitmap>,
+
+    /// Voting members of the latest stop-sign found voted in Prepare replies
+    /// to my current Prepare phase, whose majority it must also reach.
+    prep_stop_sign: Option<Bitmap>,
+
+    /// Witness replicas, which store only ballots and never lead.
+    witnesses: Bitmap,
 
     /// Configuration parameters struct.
     config: ReplicaConfigMultiPaxos,
@@ -406,6 +615,15 @@ pub(crate) struct MultiPaxosReplica {
     /// StorageHub module for the snapshot file.
     snapshot_hub: StorageHub<SnapEntry>,
 
+    /// StorageHub module for the staging file of snapshot installs.
+    install_hub: StorageHub<InstallEntry>,
+
+    /// AuditLog module, if audit logging enabled.
+    audit_log: Option<AuditLog>,
+
+    /// BlobStore module, if blob store mode enabled.
+    blob_store: Option<BlobStore>,
+
     /// TransportHub module.
     transport_hub: TransportHub<PeerMsg>,
 
@@ -415,9 +633,17 @@ pub(crate) struct MultiPaxosReplica {
     /// LeaseManager module.
     lease_manager: LeaseManager,
 
+    /// Lease number and start wall-clock time in millisecs of the majority
+    /// lease I currently believe I hold, if doing lease diagnostics.
+    lease_held_since: Option<(Ballot, u64)>,
+
     /// Who do I think is the effective leader of the cluster right now?
     leader: Option<ReplicaId>,
 
+    /// Target of my ongoing leadership transfer, the ballot it was given,
+    /// and the deadline to abort it by.
+    transfer: Option<(ReplicaId, Ballot, Instant)>,
+
     /// In-memory log of instances.
     insts: Vec<Instance>,
 
@@ -427,6 +653,13 @@ pub(crate) struct MultiPaxosReplica {
     /// Timer for taking a new autonomous snapshot.
     snapshot_interval: Interval,
 
+    /// Timer for checking free space on the WAL's filesystem.
+    disk_check_interval: Interval,
+
+    /// Free bytes on the WAL's filesystem if found below `wal_min_free_mb`
+    /// even after forcing a snapshot; writes are rejected while set.
+    wal_disk_critical: Option<u64>,
+
     /// Largest ballot number that a leader has sent Prepare messages in.
     bal_prep_sent: Ballot,
 
@@ -455,6 +688,11 @@ pub(crate) struct MultiPaxosReplica {
     ///   exec_bar <= commit_bar <= accept_bar <= start_slot + insts.len()
     exec_bar: usize,
 
+    /// Index up to which recovered commands are being executed in the
+    /// background after restart; I'm still replaying while exec_bar is
+    /// below it.
+    replay_bar: usize,
+
     /// Map from peer ID -> its latest exec_bar I know; this is for conservative
     /// snapshotting purpose.
     peer_exec_bar: HashMap<ReplicaId, usize>,
@@ -475,12 +713,64 @@ pub(crate) struct MultiPaxosReplica {
     // NOTE: may add (easy) garbage collection for outdated stuck attempts.
     quorum_reads: HashMap<(ClientId, RequestId), ReadQueryBookkeeping>,
 
+    /// Quorum reads waiting for a rinse, as (slot, client ID, request ID,
+    /// key), where slot is the highest slot seen for the key in the quorum.
+    rinsing_reads: Vec<(usize, ClientId, RequestId, String)>,
+
+    /// In blob store mode, commands of committed instances waiting to be
+    /// submitted to the state machine in order.
+    exec_pending: VecDeque<(CommandId, Command, Option<u64>, HlcStamp)>,
+
+    /// In blob store mode, the missing blob at the head of `exec_pending`
+    /// that I have requested from peers and when, if stalled.
+    blob_fetching: Option<(BlobRef, Instant)>,
+
+    /// In blob store mode, Accepts as (peer, slot, ballot, batch, stamp)
+    /// that I hold back my vote on until the blobs they reference arrive.
+    accepts_held: Vec<(ReplicaId, usize, Ballot, ReqBatch, HlcStamp)>,
+
+    /// Hybrid logical clock stamping the batches I propose as leader, and
+    /// observing the stamps of batches proposed by others.
+    clock: HybridClock,
+
     /// Current durable WAL log file offset.
     wal_offset: usize,
 
+    /// Separate read handle of the WAL log file for paging instances back
+    /// in, if keeping a bounded window of instances in memory.
+    wal_reader: Option<File>,
+
+    /// Slot index before which instances have been considered for paging
+    /// out.
+    page_bar: usize,
+
     /// Current durable snapshot file offset.
     snap_offset: usize,
 
+    /// Start slot recorded at the head of my snapshot file. May run ahead of
+    /// start_slot, as the in-mem log is only discarded up to snap_bar.
+    snap_start: usize,
+
+    /// Voting members recorded in my snapshot file, if any.
+    snap_members: Option<Bitmap>,
+
+    /// Slot of a committed snapshot marker entry whose snapshot is due;
+    /// commands after it are held back from the state machine until taken.
+    snapshot_mark_due: Option<usize>,
+
+    /// Ongoing install of a peer's snapshot, if I lag behind it.
+    installing: Option<SnapInstall>,
+
+    /// Current durable install staging file offset.
+    install_offset: usize,
+
+    /// Map from lagging peer ID -> offset of the last chunk of my snapshot
+    /// I served it and when.
+    install_serving: HashMap<ReplicaId, (usize, Instant)>,
+
+    /// Tracker of my accepted instances overwritten by a higher ballot.
+    divergence: DivergenceTracker,
+
     /// Base time instant at startup, used as a reference zero timestamp.
     startup_time: Instant,
 
@@ -516,11 +806,13 @@ impl MultiPaxosReplica {
             bal: 0,
             status: Status::Null,
             reqs: ReqBatch::new(),
-            voted: (0, ReqBatch::new()),
+            hlc: HlcStamp::default(),
+            voted: (0, ReqBatch::new(), HlcStamp::default()),
             leader_bk: None,
             replica_bk: None,
             external: false,
             wal_offset: 0,
+            paged_out: false,
         }
     }
 
@@ -538,13 +830,13 @@ impl MultiPaxosReplica {
     /// Compose a unique ballot number from base.
     #[inline]
     fn make_unique_ballot(&self, base: u64) -> Ballot {
-        ((base << 8) | ((self.id + 1) as u64)) as Ballot
+        ((base << 16) | ((self.id + 1) as u64)) as Ballot
     }
 
     /// Compose a unique ballot number greater than the given one.
     #[inline]
     fn make_greater_ballot(&self, bal: Ballot) -> Ballot {
-        self.make_unique_ballot((bal >> 8) + 1)
+        self.make_unique_ballot((bal >> 16) + 1)
     }
 
     /// Compose LogActionId from slot index & entry type.
@@ -574,6 +866,35 @@ impl MultiPaxosReplica {
         (slot, entry_type)
     }
 
+    /// Parse protocol-specific configs from the given TOML string.
+    fn parse_config(
+        config_str: Option<&str>,
+    ) -> Result<ReplicaConfigMultiPaxos, SummersetError> {
+        parsed_config!(config_str => ReplicaConfigMultiPaxos;
+                                    batch_interval_ms, max_batch_size,
+                                    idem_cache_size, session_idle_s,
+                                    ns_quota_kb, state_path,
+                                    backer_path, logger_sync,
+                                    hb_hear_timeout_min, hb_hear_timeout_max,
+                                    hb_send_interval_ms, disable_hb_timer,
+                                    hb_peer_timings,
+                                    udp_heartbeats, disallow_step_up,
+                                    witnesses,
+                                    lease_expire_ms,
+                                    enable_leader_leases, enable_quorum_reads,
+                                    lease_diagnostics, vertical_paxos,
+                                    attach_commit_certs,
+                                    urgent_commit_notice, snapshot_path,
+                                    blob_threshold_kb, blob_dir,
+                                    snapshot_interval_s, wal_min_free_mb,
+                                    audit_path, audit_rotate_mb, msg_chunk_size,
+                                    mem_log_window,
+                                    parallel_recovery,
+                                    record_breakdown, record_value_ver,
+                                    record_size_recv, record_node_cnts,
+                                    sim_read_lease)
+    }
+
     /// Compose CommandId from slot index & command index within.
     #[inline]
     fn make_command_id(slot: usize, cmd_idx: usize) -> CommandId {
@@ -597,6 +918,20 @@ impl MultiPaxosReplica {
         debug_assert!(req_id <= (u32::MAX as RequestId) / 2);
         ((client << 32) | (1 << 31) | req_id) as CommandId
     }
+
+    /// Decompose a read-only shortcut CommandId into client ID & request ID;
+    /// returns `None` if it is a normal CommandId.
+    #[inline]
+    fn split_ro_command_id(
+        command_id: CommandId,
+    ) -> Option<(ClientId, RequestId)> {
+        if command_id & (1 << 31) == 0 {
+            return None;
+        }
+        let client = (command_id >> 32) as ClientId;
+        let req_id = (command_id & ((1 << 31) - 1)) as RequestId;
+        Some((client, req_id))
+    }
 }
 
 #[async_trait]
@@ -610,21 +945,12 @@ impl GenericReplica for MultiPaxosReplica {
         // connect to the cluster manager and get assigned a server ID
         let mut control_hub = ControlHub::new_and_setup(manager).await?;
         let id = control_hub.me;
+        let (api_addr, p2p_addr) =
+            control_hub.resolve_addrs(api_addr, p2p_addr)?;
         let population = control_hub.population;
 
         // parse protocol-specific configs
-        let config = parsed_config!(config_str => ReplicaConfigMultiPaxos;
-                                    batch_interval_ms, max_batch_size,
-                                    backer_path, logger_sync,
-                                    hb_hear_timeout_min, hb_hear_timeout_max,
-                                    hb_send_interval_ms, disable_hb_timer,
-                                    disallow_step_up, lease_expire_ms,
-                                    enable_leader_leases, enable_quorum_reads,
-                                    urgent_commit_notice, snapshot_path,
-                                    snapshot_interval_s, msg_chunk_size,
-                                    record_breakdown, record_value_ver,
-                                    record_size_recv, record_node_cnts,
-                                    sim_read_lease)?;
+        let config = Self::parse_config(config_str)?;
         if config.batch_interval_ms == 0 {
             return logged_err!(
                 "invalid config.batch_interval_ms '{}'",
@@ -661,14 +987,39 @@ impl GenericReplica for MultiPaxosReplica {
                 config.msg_chunk_size
             );
         }
+        let witnesses = Self::parse_witnesses(population, &config.witnesses)?;
+        if witnesses.count() > 0
+            && (config.enable_quorum_reads || config.vertical_paxos)
+        {
+            return logged_err!(
+                Config;
+                "witnesses cannot be used with quorum reads or vertical paxos"
+            );
+        }
 
         // setup state machine module
-        let state_machine = StateMachine::new_and_setup(id).await?;
+        let state_machine = if config.state_path.is_empty() {
+            StateMachine::new_and_setup(id, config.ns_quota_kb * 1024).await?
+        } else {
+            StateMachine::new_persistent(
+                id,
+                config.ns_quota_kb * 1024,
+                Path::new(&config.state_path),
+            )
+            .await?
+        };
+        control_hub.inspect_keys(&state_machine);
 
         // setup storage hub module
         let storage_hub =
             StorageHub::new_and_setup(id, Path::new(&config.backer_path))
                 .await?;
+        control_hub.monitor_wal(Path::new(&config.backer_path));
+        let wal_reader = if config.mem_log_window > 0 {
+            Some(File::open(&config.backer_path).await?)
+        } else {
+            None
+        };
 
         // setup heartbeat management module
         let mut heartbeater = Heartbeater::new_and_setup(
@@ -678,6 +1029,7 @@ impl GenericReplica for MultiPaxosReplica {
             Duration::from_millis(config.hb_hear_timeout_max),
             Duration::from_millis(config.hb_send_interval_ms),
         )?;
+        heartbeater.set_peer_timings(&config.hb_peer_timings)?;
         heartbeater.set_sending(true); // doing all-to-all heartbeating
 
         // setup lease management module
@@ -692,7 +1044,9 @@ impl GenericReplica for MultiPaxosReplica {
         let mut transport_hub = TransportHub::new_and_setup(
             id,
             population,
+            SmrProtocol::MultiPaxos,
             p2p_addr,
+            config.udp_heartbeats,
             HashMap::from([(
                 0, // only one lease purpose exists in MultiPaxos
                 tx_lease_msg,
@@ -709,12 +1063,22 @@ impl GenericReplica for MultiPaxosReplica {
             api_addr,
             p2p_addr,
         })?;
+        let mut ctrl_msg = control_hub.recv_ctrl().await?;
+        while let CtrlMsg::SeedState {
+            offset,
+            chunk,
+            last,
+        } = ctrl_msg
+        {
+            Self::save_seed_chunk(&config, offset, &chunk, last).await?;
+            ctrl_msg = control_hub.recv_ctrl().await?;
+        }
         let to_peers = if let CtrlMsg::ConnectToPeers { to_peers, .. } =
-            control_hub.recv_ctrl().await?
+            ctrl_msg
         {
             to_peers
         } else {
-            return logged_err!("unexpected ctrl msg type received");
+            return logged_err!(Protocol; "unexpected ctrl msg type received");
         };
 
         // proactively connect to some peers, then wait for all population
@@ -728,15 +1092,53 @@ impl GenericReplica for MultiPaxosReplica {
         let snapshot_hub =
             StorageHub::new_and_setup(id, Path::new(&config.snapshot_path))
                 .await?;
+        let install_path = format!("{}.install", config.snapshot_path);
+        let install_hub =
+            StorageHub::new_and_setup(id, Path::new(&install_path)).await?;
+
+        // setup audit log module if enabled
+        let audit_log = if config.audit_path.is_empty() {
+            None
+        } else {
+            Some(
+                AuditLog::new_and_setup(
+                    id,
+                    Path::new(&config.audit_path),
+                    config.audit_rotate_mb * 1024 * 1024,
+                )
+                .await?,
+            )
+        };
+
+        // setup blob store module if enabled
+        let blob_store = if config.blob_threshold_kb > 0 {
+            Some(
+                BlobStore::new_and_setup(id, Path::new(&config.blob_dir))
+                    .await?,
+            )
+        } else {
+            None
+        };
 
         // setup external API module, ready to take in client requests
-        let external_api = ExternalApi::new_and_setup(
+        let mut external_api = ExternalApi::new_and_setup(
             id,
             api_addr,
             Duration::from_millis(config.batch_interval_ms),
             config.max_batch_size,
+            config.idem_cache_size,
+            Duration::from_secs(config.session_idle_s),
         )
         .await?;
+        external_api.stamp_replies(&state_machine);
+        // witnesses hold no values to read; quorum reads could hit them
+        if !witnesses.get(id)? {
+            external_api.enable_local_reads();
+        }
+        if witnesses.count() == 0 {
+            external_api.enable_read_levels();
+        }
+        external_api.enable_commit_acks();
 
         let mut snapshot_interval = time::interval(Duration::from_secs(
             if config.snapshot_interval_s > 0 {
@@ -747,6 +1149,9 @@ impl GenericReplica for MultiPaxosReplica {
         ));
         snapshot_interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
+        let mut disk_check_interval = time::interval(DISK_CHECK_INTERVAL);
+        disk_check_interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
         // [for perf breakdown only]
         let bd_stopwatch = if config.record_breakdown {
             Some(Stopwatch::new())
@@ -760,6 +1165,10 @@ impl GenericReplica for MultiPaxosReplica {
             id,
             population,
             quorum_cnt: (population / 2) + 1,
+            members: Bitmap::new(population, true),
+            prep_members: None,
+            prep_stop_sign: None,
+            witnesses,
             config,
             _api_addr: api_addr,
             _p2p_addr: p2p_addr,
@@ -768,13 +1177,20 @@ impl GenericReplica for MultiPaxosReplica {
             state_machine,
             storage_hub,
             snapshot_hub,
+            install_hub,
+            audit_log,
+            blob_store,
             transport_hub,
             heartbeater,
             lease_manager,
+            lease_held_since: None,
             leader: None,
+            transfer: None,
             insts: vec![],
             start_slot: 0,
             snapshot_interval,
+            disk_check_interval,
+            wal_disk_critical: None,
             bal_prep_sent: 0,
             bal_prepared: 0,
             bal_max_seen: 0,
@@ -783,14 +1199,29 @@ impl GenericReplica for MultiPaxosReplica {
             peer_accept_max: usize::MAX,
             commit_bar: 0,
             exec_bar: 0,
+            replay_bar: 0,
             peer_exec_bar: (0..population)
                 .filter_map(|s| if s == id { None } else { Some((s, 0)) })
                 .collect(),
             snap_bar: 0,
             highest_slot: HashMap::new(),
             quorum_reads: HashMap::new(),
+            rinsing_reads: vec![],
+            exec_pending: VecDeque::new(),
+            blob_fetching: None,
+            accepts_held: vec![],
+            clock: HybridClock::new(),
             wal_offset: 0,
+            wal_reader,
+            page_bar: 0,
             snap_offset: 0,
+            snap_start: 0,
+            snap_members: None,
+            snapshot_mark_due: None,
+            installing: None,
+            install_offset: 0,
+            install_serving: HashMap::new(),
+            divergence: DivergenceTracker::new(),
             startup_time: Instant::now(),
             bd_stopwatch,
             bd_print_interval,
@@ -805,12 +1236,22 @@ impl GenericReplica for MultiPaxosReplica {
         &mut self,
         mut rx_term: watch::Receiver<bool>,
     ) -> Result<bool, SummersetError> {
+        // start from the seed image if the cluster was seeded with one
+        self.restore_seed_image().await?;
+
         // recover state from durable snapshot file
         self.recover_from_snapshot().await?;
 
         // recover the tail-piece memory log & state from durable WAL log
         self.recover_from_wal().await?;
 
+        // pick up an interrupted snapshot install, if any
+        self.recover_install().await?;
+        if self.config.parallel_recovery || self.blob_store.is_some() {
+            self.start_background_replay()?;
+            self.drain_exec_pending().await?;
+        }
+
         // kick off peer heartbeats hearing timer
         if !self.config.disable_hb_timer {
             self.heartbeater.kickoff_hear_timer(None)?;
@@ -879,12 +1320,15 @@ impl GenericReplica for MultiPaxosReplica {
                     }
                     match hb_event.unwrap() {
                         HeartbeatEvent::HearTimeout { peer } => {
-                            if let Err(e) = self.become_a_leader(peer).await {
+                            if let Err(e) = self.become_a_leader(peer, None).await {
                                 pf_error!("error becoming a leader: {}", e);
                             }
                         }
                         HeartbeatEvent::SendTicked => {
-                            if let Err(e) = self.bcast_heartbeats().await {
+                            if let Err(e) = self.check_transfer_timeout().await {
+                                pf_error!("error checking leader transfer: {}", e);
+                            }
+                            if let Err(e) = self.bcast_heartbeats(false).await {
                                 pf_error!("error broadcasting heartbeats: {}", e);
                             }
                         }
@@ -916,6 +1360,14 @@ impl GenericReplica for MultiPaxosReplica {
                     }
                 },
 
+                // WAL disk space checking timeout
+                _ = self.disk_check_interval.tick(), if !paused
+                                                       && self.config.wal_min_free_mb > 0 => {
+                    if let Err(e) = self.guard_wal_disk_space().await {
+                        pf_error!("error guarding WAL disk space: {}", e);
+                    }
+                },
+
                 // [for perf breakdown only]
                 // performance breakdown stats printing
                 _ = self.bd_print_interval.tick(), if !paused && self.config.record_breakdown => {
@@ -990,7 +1442,7 @@ impl GenericReplica for MultiPaxosReplica {
         self.id
     }
 
-    fn population(&self) -> u8 {
+    fn population(&self) -> u16 {
         self.population
     }
 }
@@ -1027,7 +1479,7 @@ pub(crate) struct MultiPaxosClient {
     id: ClientId,
 
     /// Number of servers in the cluster.
-    population: u8,
+    population: u16,
 
     /// Configuration parameters struct.
     config: ClientConfigMultiPaxos,
@@ -1141,7 +1593,7 @@ impl GenericEndpoint for MultiPaxosClient {
                 }
                 Ok(())
             }
-            _ => logged_err!("unexpected reply type received"),
+            _ => logged_err!(Protocol; "unexpected reply type received"),
         }
     }
 
@@ -1179,7 +1631,7 @@ impl GenericEndpoint for MultiPaxosClient {
         &mut self,
         req: Option<&ApiRequest>,
     ) -> Result<bool, SummersetError> {
-        let server_id = match req {
+        let server_id = match req.map(ApiRequest::unfenced) {
             None => {
                 // last send needs retry
                 if let Some(last_server_id) = self.last_server_id {
@@ -1188,6 +1640,13 @@ impl GenericEndpoint for MultiPaxosClient {
                     return logged_err!("last_server_id not set when retrying");
                 }
             }
+            Some(ApiRequest::Read { consistency, .. })
+                if *consistency != ReadConsistency::Leader
+                    && self.near_server_id.is_some() =>
+            {
+                // read that does not have to go to the leader
+                self.near_server_id.unwrap()
+            }
             Some(req)
                 if req.read_only().is_some()
                     && self.near_server_id.is_some()
@@ -1219,6 +1678,32 @@ impl GenericEndpoint for MultiPaxosClient {
         }
     }
 
+    fn send_hedge(&mut self, req: &ApiRequest) -> Result<bool, SummersetError> {
+        // only a read sent to a near server other than the believed leader
+        // can be hedged, by duplicating it to the leader, which can always
+        // serve it safely
+        if req.read_only().is_none()
+            || !self.config.enable_quorum_reads
+            || self.last_server_id.is_some()
+            || self.near_server_id.is_none_or(|s| s == self.curr_server_id)
+        {
+            return Ok(false);
+        }
+
+        match self.api_stubs.get(&self.curr_server_id) {
+            Some(api_stub) => {
+                let mut api_stub = api_stub.borrow_mut();
+                let mut success = api_stub.send_req(Some(req))?;
+                while !success {
+                    success = api_stub.send_req(None)?;
+                }
+                pf_debug!("hedged read to replica {}", self.curr_server_id);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
     async fn recv_reply(&mut self) -> Result<ApiReply, SummersetError> {
         if !self.api_stubs.contains_key(&self.curr_server_id) {
             return Err(SummersetError::msg(format!(
@@ -1268,11 +1753,28 @@ impl GenericEndpoint for MultiPaxosClient {
             }
         };
 
+        if let ApiReply::LeaderChange { leader } = reply {
+            // notified of a new leader; switch over to it, or keep waiting if
+            // already talking to it
+            if leader == self.curr_server_id
+                || !self.servers.contains_key(&leader)
+            {
+                return self.recv_reply().await;
+            }
+            self.curr_server_id = leader;
+            pf_debug!(
+                "notified of new leader {} '{}'",
+                leader,
+                self.servers[&leader]
+            );
+        }
+
         if let ApiReply::Reply {
             id: req_id,
             ref result,
             ref redirect,
             ref mut rq_retry,
+            ..
         } = reply
         {
             // if the current server redirects me to a different server
@@ -1288,14 +1790,14 @@ impl GenericEndpoint for MultiPaxosClient {
             }
 
             // if to retry a failed read-only optimization, just fallback to
-            // sending the request to (believed) current leader and continue
-            // the wait
+            // sending the request to (believed) current leader as a leader
+            // read and continue the wait
             if let Some(read_cmd) = rq_retry.take() {
-                if read_cmd.read_only().is_none() {
+                let Command::Get { key } = read_cmd else {
                     return logged_err!(
                         "non-Get command found in reply rq_retry"
                     );
-                }
+                };
                 while self.last_server_id.is_some() {
                     self.send_req(None)?;
                     if self.last_server_id.is_some() {
@@ -1306,9 +1808,10 @@ impl GenericEndpoint for MultiPaxosClient {
                     .get(&self.curr_server_id)
                     .unwrap()
                     .borrow_mut()
-                    .send_req(Some(&ApiRequest::Req {
+                    .send_req(Some(&ApiRequest::Read {
                         id: req_id,
-                        cmd: read_cmd,
+                        key,
+                        consistency: ReadConsistency::Leader,
                     }))?;
                 return self.recv_reply().await;
             }
@@ -1321,7 +1824,7 @@ impl GenericEndpoint for MultiPaxosClient {
         self.id
     }
 
-    fn population(&self) -> u8 {
+    fn population(&self) -> u16 {
         self.population
     }
 
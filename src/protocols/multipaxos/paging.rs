@@ -0,0 +1,113 @@
+//! MultiPaxos -- out-of-core log window.
+//!
+//! With `mem_log_window` set, only the request batches of the most recent
+//! instances stay in memory. Batches of older instances that are durably
+//! accepted (or already executed) but not yet snapshotted are dropped, and
+//! paged back in from their AcceptData entry in the WAL when the instance
+//! needs them again, e.g., when it gets committed or re-prepared. This
+//! lets replicas with little RAM carry long uncommitted tails.
+
+use super::*;
+
+use crate::server::read_log_entry;
+use crate::utils::SummersetError;
+
+// MultiPaxosReplica out-of-core log window logic
+impl MultiPaxosReplica {
+    /// Drops the request batches of instances that have fallen out of the
+    /// in-memory window, if they can be paged back in later. Instances
+    /// committed but not yet executed and stop-signs are kept in memory.
+    pub(super) fn page_out_insts(&mut self) {
+        if self.config.mem_log_window == 0 {
+            return;
+        }
+        let end_slot = (self.start_slot + self.insts.len())
+            .saturating_sub(self.config.mem_log_window)
+            .min(self.accept_bar);
+        let mut paged_cnt = 0;
+        for slot in self.page_bar.max(self.start_slot)..end_slot {
+            let inst = &mut self.insts[slot - self.start_slot];
+            if inst.paged_out
+                || inst.voted.0 == 0
+                || !matches!(inst.status, Status::Accepting | Status::Executed)
+                || Self::parse_stop_sign(&inst.reqs).is_some()
+            {
+                continue;
+            }
+            inst.reqs = ReqBatch::new();
+            inst.voted.1 = ReqBatch::new();
+            inst.paged_out = true;
+            paged_cnt += 1;
+        }
+        self.page_bar = self.page_bar.max(end_slot);
+        if paged_cnt > 0 {
+            pf_trace!("paged out {} instances before {}", paged_cnt, end_slot);
+        }
+    }
+
+    /// Pages the request batch of the instance at slot back in from the
+    /// WAL, if it has been paged out.
+    pub(super) async fn page_in_inst(
+        &mut self,
+        slot: usize,
+    ) -> Result<(), SummersetError> {
+        if slot < self.start_slot || slot >= self.start_slot + self.insts.len()
+        {
+            return Ok(());
+        }
+        let inst = &self.insts[slot - self.start_slot];
+        if !inst.paged_out {
+            return Ok(());
+        }
+        let (mut offset, voted_bal) = (inst.wal_offset, inst.voted.0);
+        let Some(wal_reader) = self.wal_reader.as_mut() else {
+            return logged_err!("paged out slot {} without WAL reader", slot);
+        };
+
+        // scan forward from the first entry related to this instance for
+        // the AcceptData entry of the ballot I voted
+        let reqs = loop {
+            match read_log_entry(wal_reader, offset).await? {
+                (
+                    Some(WalEntry::AcceptData {
+                        slot: s,
+                        ballot,
+                        reqs,
+                        ..
+                    }),
+                    _,
+                ) if s == slot && ballot == voted_bal => break reqs,
+                (Some(_), end_offset) => offset = end_offset,
+                (None, _) => {
+                    return logged_err!(
+                        "AcceptData of slot {} bal {} not found in WAL",
+                        slot,
+                        voted_bal
+                    );
+                }
+            }
+        };
+
+        let inst = &mut self.insts[slot - self.start_slot];
+        inst.voted.1.clone_from(&reqs);
+        inst.reqs = reqs;
+        inst.paged_out = false;
+        pf_trace!("paged in slot {} bal {}", slot, voted_bal);
+        Ok(())
+    }
+
+    /// Pages back in the instances that are about to be submitted for
+    /// execution, i.e., committed ones starting from commit_bar.
+    pub(super) async fn page_in_committed(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        let mut slot = self.commit_bar;
+        while slot < self.start_slot + self.insts.len()
+            && self.insts[slot - self.start_slot].status >= Status::Committed
+        {
+            self.page_in_inst(slot).await?;
+            slot += 1;
+        }
+        Ok(())
+    }
+}
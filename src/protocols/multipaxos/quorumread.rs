@@ -12,7 +12,7 @@ impl MultiPaxosReplica {
     ) {
         for (_, req) in reqs {
             if let ApiRequest::Req {
-                cmd: Command::Put { key, .. },
+                cmd: Command::Put { key, .. } | Command::Cas { key, .. },
                 ..
             } = req
             {
@@ -42,21 +42,35 @@ impl MultiPaxosReplica {
                 Ok(Some((slot, None)))
             } else {
                 let inst = &self.insts[slot - self.start_slot];
-                if inst.status < Status::Committed {
-                    // instance not committed on me yet
+                if inst.status < Status::Committed || inst.paged_out {
+                    // instance not committed on me yet, or its batch is not
+                    // in memory
                     Ok(Some((slot, None)))
                 } else {
                     // instance committed, return the latest value for the key
                     // in batch
                     for (_, req) in inst.reqs.iter().rev() {
-                        if let ApiRequest::Req {
-                            cmd: Command::Put { key: k, value },
-                            ..
-                        } = req
-                        {
-                            if k == key {
+                        match req {
+                            ApiRequest::Req {
+                                cmd: Command::Put { key: k, value },
+                                ..
+                            } if k == key => {
+                                // values kept in the blob store are served
+                                // by normal reads only
+                                if BlobRef::parse(value).is_some() {
+                                    return Ok(Some((slot, None)));
+                                }
                                 return Ok(Some((slot, Some(value.clone()))));
                             }
+                            ApiRequest::Req {
+                                cmd: Command::Cas { key: k, .. },
+                                ..
+                            } if k == key => {
+                                // outcome of a compare-and-swap is only known
+                                // after execution; play safe
+                                return Ok(Some((slot, None)));
+                            }
+                            _ => {}
                         }
                     }
                     logged_err!(
@@ -72,6 +86,60 @@ impl MultiPaxosReplica {
         }
     }
 
+    /// Starts a near quorum read of the given batch of Gets by broadcasting
+    /// a ReadQuery for them to peers. The read is identified by the client
+    /// and request ID of the first Get. If `rinse` is true, reads whose latest
+    /// write is not yet committed wait for it on me instead of falling back
+    /// to the leader.
+    pub(super) fn start_quorum_read(
+        &mut self,
+        reads: ReqBatch,
+        rinse: bool,
+    ) -> Result<(), SummersetError> {
+        debug_assert!(!reads.is_empty());
+        let mut rq_id = None;
+        let mut rq_bk = ReadQueryBookkeeping {
+            reads: vec![],
+            rq_acks: Bitmap::new(self.population, false),
+            max_replies: vec![],
+            rinse,
+        };
+
+        for (client, req) in reads {
+            if let ApiRequest::Req {
+                id: req_id,
+                cmd: Command::Get { ref key },
+                ..
+            } = req
+            {
+                rq_bk.max_replies.push(self.inspect_highest_slot(key)?);
+                rq_bk.reads.push((client, req.clone()));
+                rq_id.get_or_insert((client, req_id));
+            }
+        }
+        let Some(rq_id) = rq_id else {
+            return logged_err!(Protocol; "non-Get request in quorum read");
+        };
+
+        // broadcast ReadQuery to peers
+        self.transport_hub.bcast_msg(
+            PeerMsg::ReadQuery {
+                reads: rq_bk.reads.clone(),
+            },
+            None,
+        )?;
+        pf_debug!(
+            "broadcast ReadQuery messages for rq_id {}.{}",
+            rq_id.0,
+            rq_id.1
+        );
+
+        // mark myself as replied
+        rq_bk.rq_acks.set(self.id, true)?;
+        self.quorum_reads.insert(rq_id, rq_bk);
+        Ok(())
+    }
+
     /// Handler of ReadQuery message from a peer issuer.
     pub(super) async fn handle_msg_read_query(
         &mut self,
@@ -84,6 +152,7 @@ impl MultiPaxosReplica {
             if let ApiRequest::Req {
                 id: req_id,
                 cmd: Command::Get { .. },
+                ..
             } = reads[0].1
             {
                 req_id
@@ -99,27 +168,22 @@ impl MultiPaxosReplica {
         );
 
         // check if I'm currently a stable majority-leased leader
+        self.observe_lease_held()?;
         if self.is_stable_leader() {
             // yes, can directly reply with my latest committed value for each
             // key and shortcut the read quorum
             let mut replies = Vec::with_capacity(reads.len());
+            let mut served = HashMap::new();
             for (client, req) in reads {
                 if let ApiRequest::Req {
                     id: req_id,
                     cmd: Command::Get { key },
+                    ..
                 } = req
                 {
-                    // has to use the `do_sync_cmd()` API
-                    let (old_results, cmd_result) = self
-                        .state_machine
-                        .do_sync_cmd(
-                            Self::make_ro_command_id(client, req_id),
-                            Command::Get { key: key.clone() },
-                        )
+                    let cmd_result = self
+                        .coalesced_local_read(client, req_id, &key, &mut served)
                         .await?;
-                    for (old_id, old_result) in old_results {
-                        self.handle_cmd_result(old_id, old_result).await?;
-                    }
 
                     if let CommandResult::Get { value } = cmd_result {
                         // dummy
@@ -256,7 +320,9 @@ impl MultiPaxosReplica {
 
                 // if majority number of replies reached, can decide to reply
                 // to clients now
-                if rq_bk.rq_acks.count() >= self.quorum_cnt {
+                if Self::member_cnt(&self.members, &rq_bk.rq_acks)
+                    >= self.quorum_cnt
+                {
                     pf_debug!(
                         "enough ReadQuery replies got for rq_id {}.{}",
                         rq_id.0,
@@ -276,9 +342,22 @@ impl MultiPaxosReplica {
                 if let ApiRequest::Req {
                     id: req_id,
                     cmd: Command::Get { key },
+                    ..
                 } = req
                 {
                     let (api_reply, is_retry) = match reply {
+                        Some((slot, None)) if rq_bk.rinse => {
+                            // highest slot for this key not committed, rinse
+                            // by waiting until I have executed it
+                            self.rinsing_reads
+                                .push((slot, client, req_id, key));
+                            pf_trace!(
+                                "rinsing near-read for client {} at slot {}",
+                                client,
+                                slot
+                            );
+                            continue;
+                        }
                         None => {
                             // no one in quorum knows about this key, safely
                             // reply not found
@@ -339,8 +418,37 @@ impl MultiPaxosReplica {
                     );
                 }
             }
+            self.serve_rinsed_reads()?;
+        }
+
+        Ok(())
+    }
+
+    /// Serves rinsing quorum reads whose awaited slot I have executed, by
+    /// submitting their Gets to my state machine behind that slot. Results
+    /// are replied to clients when they come out of the state machine.
+    //
+    // NOTE: the rinse round of the PQR paper re-queries the quorum until the
+    //       latest write is seen committed; waiting for it to commit at the
+    //       receiving replica is equivalent, and costs no extra messages
+    pub(super) fn serve_rinsed_reads(&mut self) -> Result<(), SummersetError> {
+        if self.rinsing_reads.is_empty() {
+            return Ok(());
         }
 
+        let exec_bar = self.exec_bar;
+        let (ready, waiting): (Vec<_>, Vec<_>) = self
+            .rinsing_reads
+            .drain(..)
+            .partition(|&(slot, ..)| slot < exec_bar);
+        self.rinsing_reads = waiting;
+        for (_, client, req_id, key) in ready {
+            self.state_machine.submit_cmd(
+                Self::make_ro_command_id(client, req_id),
+                Command::Get { key },
+            )?;
+            pf_trace!("submitted rinsed near-read for client {}", client);
+        }
         Ok(())
     }
 }
@@ -35,7 +35,12 @@ impl MultiPaxosReplica {
                 self.bal_prepared = 0;
             }
 
-            WalEntry::AcceptData { slot, ballot, reqs } => {
+            WalEntry::AcceptData {
+                slot,
+                ballot,
+                reqs,
+                hlc,
+            } => {
                 if slot < self.start_slot {
                     return Ok(()); // ignore if slot index outdated
                 }
@@ -48,8 +53,11 @@ impl MultiPaxosReplica {
                 inst.bal = ballot;
                 inst.status = Status::Accepting;
                 inst.reqs.clone_from(&reqs);
+                inst.hlc = hlc;
+                self.clock.observe(hlc);
                 Self::refresh_highest_slot(slot, &reqs, &mut self.highest_slot);
-                inst.voted = (ballot, reqs);
+                Self::note_blob_uses(slot, &reqs, &mut self.blob_store);
+                inst.voted = (ballot, reqs, hlc);
                 // it could be the case that the PrepareBal action for this
                 // ballot has been snapshotted
                 if self.bal_prep_sent < ballot {
@@ -84,7 +92,9 @@ impl MultiPaxosReplica {
                 // update instance status
                 self.insts[slot - self.start_slot].status = Status::Committed;
                 // submit commands in contiguously committed instance to the
-                // state machine
+                // state machine, except those a persistent state machine had
+                // applied already
+                let applied = self.state_machine.durable_applied();
                 if slot == self.commit_bar {
                     while self.commit_bar < self.accept_bar {
                         let inst =
@@ -92,30 +102,118 @@ impl MultiPaxosReplica {
                         if inst.status < Status::Committed {
                             break;
                         }
+                        if self.config.parallel_recovery
+                            || self.blob_store.is_some()
+                        {
+                            // leave execution to `start_background_replay()`
+                            if let Some(members) =
+                                Self::parse_stop_sign(&inst.reqs).cloned()
+                            {
+                                self.set_members(members)?;
+                            }
+                            self.commit_bar += 1;
+                            continue;
+                        }
                         // execute all commands in this instance on state machine
                         // synchronously
-                        for (_, req) in inst.reqs.clone() {
+                        for (cmd_idx, (_, req)) in
+                            inst.reqs.clone().into_iter().enumerate()
+                        {
                             if let ApiRequest::Req { cmd, .. } = req {
+                                let cmd_id = Self::make_command_id(
+                                    self.commit_bar,
+                                    cmd_idx,
+                                );
+                                if applied.is_some_and(|id| cmd_id <= id) {
+                                    continue;
+                                }
                                 self.state_machine
-                                    .do_sync_cmd(
-                                        0, // using 0 as dummy command ID
-                                        cmd,
-                                    )
+                                    .do_sync_cmd(cmd_id, cmd)
                                     .await?;
                             }
                         }
+                        // apply new configuration if this is a stop-sign
+                        let stop_sign =
+                            Self::parse_stop_sign(&inst.reqs).cloned();
                         // update instance status, commit_bar and exec_bar
                         self.commit_bar += 1;
                         self.exec_bar += 1;
                         inst.status = Status::Executed;
+                        if let Some(members) = stop_sign {
+                            self.set_members(members)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Submit commands of recovered committed instances to the state machine
+    /// for asynchronous execution, letting the main event loop start while
+    /// they are being replayed. Their results are handled as usual.
+    pub(super) fn start_background_replay(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        self.replay_bar = self.commit_bar;
+        let applied = self.state_machine.durable_applied();
+        for slot in self.exec_bar..self.commit_bar {
+            let inst = &mut self.insts[slot - self.start_slot];
+            let mut has_cmds = false;
+            for (cmd_idx, (_, req)) in inst.reqs.iter().enumerate() {
+                if let ApiRequest::Req { cmd, checksum, .. } = req {
+                    let cmd_id = Self::make_command_id(slot, cmd_idx);
+                    if applied.is_some_and(|id| cmd_id <= id) {
+                        continue; // applied by persistent state machine
+                    }
+                    if self.blob_store.is_some() {
+                        self.exec_pending.push_back((
+                            cmd_id,
+                            cmd.clone(),
+                            *checksum,
+                            inst.hlc,
+                        ));
+                    } else {
+                        self.state_machine.submit_stamped_cmd(
+                            cmd_id,
+                            cmd.clone(),
+                            *checksum,
+                            inst.hlc,
+                        )?;
                     }
+                    has_cmds = true;
                 }
             }
+            if !has_cmds {
+                inst.status = Status::Executed;
+            }
         }
 
+        // skip over leading instances with nothing to execute
+        while self.exec_bar < self.commit_bar
+            && self.insts[self.exec_bar - self.start_slot].status
+                == Status::Executed
+        {
+            self.exec_bar += 1;
+        }
+
+        if self.replaying() {
+            pf_info!(
+                "replaying wal log in background: exec {} -> {}",
+                self.exec_bar,
+                self.replay_bar
+            );
+        }
         Ok(())
     }
 
+    /// Am I still executing commands recovered from the WAL?
+    #[inline]
+    pub(super) fn replaying(&self) -> bool {
+        self.exec_bar < self.replay_bar
+    }
+
     /// Recover state from durable storage WAL log.
     pub(super) async fn recover_from_wal(
         &mut self,
@@ -146,7 +244,7 @@ impl MultiPaxosReplica {
                     break;
                 }
                 _ => {
-                    return logged_err!("unexpected log result type");
+                    return logged_err!(Protocol; "unexpected log result type");
                 }
             }
         }
@@ -174,7 +272,9 @@ impl MultiPaxosReplica {
             }
             Ok(())
         } else {
-            logged_err!("unexpected log result type or failed truncate")
+            logged_err!(
+                Protocol; "unexpected log result type or failed truncate"
+            )
         }
     }
 }
@@ -0,0 +1,189 @@
+//! MultiPaxos -- offline deterministic replay of durable files.
+//!
+//! Replay re-applies the snapshot and WAL entries in file order to a fresh
+//! local state machine, logging every protocol decision and every executed
+//! command with its result. No network connection is made, so the durable
+//! files left behind by a long run can be replayed any number of times to
+//! reproduce a bug offline.
+
+use super::*;
+
+use crate::server::{ApiRequest, StateMachine};
+use crate::utils::SummersetError;
+
+/// Replay-side view of a log slot.
+#[derive(Debug, Default)]
+struct ReplaySlot {
+    /// Ballot and batch of the latest accepted data, if any.
+    voted: Option<(Ballot, ReqBatch)>,
+
+    /// Whether a commit has been recorded.
+    committed: bool,
+}
+
+// MultiPaxosReplica offline replay
+impl MultiPaxosReplica {
+    /// Executes all commands in the batch of a committed slot on the state
+    /// machine, logging their results.
+    async fn replay_exec_slot(
+        state_machine: &mut StateMachine,
+        slot: usize,
+        reqs: &ReqBatch,
+    ) -> Result<(), SummersetError> {
+        if let Some(members) = Self::parse_stop_sign(reqs) {
+            pf_info!("  exec slot {} stop-sign members {:?}", slot, members);
+            return Ok(());
+        }
+        for (cmd_idx, (client, req)) in reqs.iter().enumerate() {
+            if let ApiRequest::Req { id, cmd, .. } = req {
+                let (_, result) = state_machine
+                    .do_sync_cmd(
+                        Self::make_command_id(slot, cmd_idx),
+                        cmd.clone(),
+                    )
+                    .await?;
+                pf_info!(
+                    "  exec slot {} client {} req {}: {:?} -> {:?}",
+                    slot,
+                    client,
+                    id,
+                    cmd,
+                    result
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Replays the snapshot file and WAL log named in the config on a fresh
+    /// state machine without joining the cluster, stopping after executing
+    /// slot `until` if given.
+    pub(crate) async fn replay_files(
+        config_str: Option<&str>,
+        until: Option<usize>,
+    ) -> Result<(), SummersetError> {
+        let config = Self::parse_config(config_str)?;
+        let mut state_machine =
+            StateMachine::new_and_setup(0, config.ns_quota_kb * 1024).await?;
+
+        // snapshot file
+        let mut start_slot = 0;
+        let snap_entries: Vec<SnapEntry> =
+            Self::dump_read_file(Path::new(&config.snapshot_path)).await?;
+        for entry in snap_entries {
+            match entry {
+                SnapEntry::SlotInfo { start_slot: slot } => {
+                    pf_info!("snapshot covers slots before {}", slot);
+                    start_slot = slot;
+                }
+                SnapEntry::StateImage { image } => {
+                    pf_info!(
+                        "snapshot restores state image of {} bytes",
+                        image.len()
+                    );
+                    state_machine.restore_snapshot(image.into()).await?;
+                }
+                SnapEntry::Members { members } => {
+                    pf_info!("snapshot restores members {:?}", members);
+                }
+            }
+        }
+        if let Some(until) = until.filter(|&until| until < start_slot) {
+            return logged_err!(
+                "replay target slot {} already covered by snapshot",
+                until
+            );
+        }
+
+        // WAL log, in the order entries were appended
+        let wal_entries: Vec<WalEntry> =
+            Self::dump_read_file(Path::new(&config.backer_path)).await?;
+        let mut slots: Vec<ReplaySlot> = vec![];
+        let (mut bal_max_seen, mut exec_bar) = (0, start_slot);
+        for (idx, entry) in wal_entries.into_iter().enumerate() {
+            let slot = match entry {
+                WalEntry::PrepareBal { slot, .. }
+                | WalEntry::AcceptData { slot, .. }
+                | WalEntry::CommitSlot { slot } => slot,
+            };
+            if slot < start_slot {
+                pf_debug!(
+                    "[{}] slot {} covered by snapshot, skipped",
+                    idx,
+                    slot
+                );
+                continue;
+            }
+            while start_slot + slots.len() <= slot {
+                slots.push(ReplaySlot::default());
+            }
+
+            match entry {
+                WalEntry::PrepareBal { ballot, .. } => {
+                    pf_info!("[{}] slot {} prepare bal {}", idx, slot, ballot);
+                    bal_max_seen = bal_max_seen.max(ballot);
+                }
+
+                WalEntry::AcceptData { ballot, reqs, .. } => {
+                    pf_info!(
+                        "[{}] slot {} accept bal {} batch of {}",
+                        idx,
+                        slot,
+                        ballot,
+                        reqs.len()
+                    );
+                    bal_max_seen = bal_max_seen.max(ballot);
+                    slots[slot - start_slot].voted = Some((ballot, reqs));
+                }
+
+                WalEntry::CommitSlot { .. } => {
+                    let Some((ballot, _)) = &slots[slot - start_slot].voted
+                    else {
+                        return logged_err!(
+                            "[{}] slot {} committed without accepted data",
+                            idx,
+                            slot
+                        );
+                    };
+                    pf_info!("[{}] slot {} commit bal {}", idx, slot, ballot);
+                    slots[slot - start_slot].committed = true;
+
+                    // execute the contiguously committed prefix
+                    while let Some(ReplaySlot {
+                        voted: Some((_, reqs)),
+                        committed: true,
+                    }) = slots.get(exec_bar - start_slot)
+                    {
+                        Self::replay_exec_slot(
+                            &mut state_machine,
+                            exec_bar,
+                            reqs,
+                        )
+                        .await?;
+                        exec_bar += 1;
+                        if until.is_some_and(|until| exec_bar > until) {
+                            pf_info!(
+                                "replay stopped after slot {}: bal {} exec {}",
+                                exec_bar - 1,
+                                bal_max_seen,
+                                exec_bar
+                            );
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+        }
+
+        pf_info!(
+            "replay done: bal {} exec {} of {} slots in wal",
+            bal_max_seen,
+            exec_bar,
+            start_slot + slots.len()
+        );
+        if let Some(until) = until {
+            pf_warn!("replay target slot {} never executed", until);
+        }
+        Ok(())
+    }
+}
@@ -7,6 +7,57 @@ use crate::utils::{Bitmap, SummersetError};
 
 // MultiPaxosReplica client requests entrance
 impl MultiPaxosReplica {
+    /// Serves a read of key locally as a majority-leased stable leader. Gets
+    /// to the same key within one batch are coalesced: only the first one
+    /// reads the state machine, and later ones reuse its result kept in
+    /// `served`. This is safe as all reads in the batch are concurrent, so
+    /// the single read is a valid linearization point for all of them.
+    pub(super) async fn coalesced_local_read(
+        &mut self,
+        client: ClientId,
+        req_id: RequestId,
+        key: &str,
+        served: &mut HashMap<String, CommandResult>,
+    ) -> Result<CommandResult, SummersetError> {
+        if let Some(cmd_result) = served.get(key) {
+            return Ok(cmd_result.clone());
+        }
+
+        // has to use the `do_sync_cmd()` API
+        let (old_results, cmd_result) = self
+            .state_machine
+            .do_sync_cmd(
+                Self::make_ro_command_id(client, req_id),
+                Command::Get { key: key.into() },
+            )
+            .await?;
+        for (old_id, old_result) in old_results {
+            self.handle_cmd_result(old_id, old_result).await?;
+        }
+
+        served.insert(key.into(), cmd_result.clone());
+        Ok(cmd_result)
+    }
+
+    /// Executes a scan straight on my state machine; only safe as the
+    /// majority-leased stable leader.
+    async fn local_scan(
+        &mut self,
+        client: ClientId,
+        req_id: RequestId,
+        cmd: Command,
+    ) -> Result<CommandResult, SummersetError> {
+        // has to use the `do_sync_cmd()` API
+        let (old_results, cmd_result) = self
+            .state_machine
+            .do_sync_cmd(Self::make_ro_command_id(client, req_id), cmd)
+            .await?;
+        for (old_id, old_result) in old_results {
+            self.handle_cmd_result(old_id, old_result).await?;
+        }
+        Ok(cmd_result)
+    }
+
     /// Treat read requests in the batch specially if:
     ///   - I'm the majority-leased stable leader
     ///   - simulating read leases
@@ -19,39 +70,55 @@ impl MultiPaxosReplica {
         req_batch: &mut ReqBatch,
     ) -> Result<(), SummersetError> {
         let mut strip_read_only = false;
+        let mut strip_scans = false;
 
+        self.observe_lease_held()?;
         if self.is_stable_leader() {
             // conditions of majority-leased stable leader met, can reply
-            // read-only commands directly back to clients
+            // read-only commands, including scans, directly back to clients
+            let mut served = HashMap::new();
             for (client, req) in req_batch.iter() {
-                if let ApiRequest::Req {
-                    id: req_id,
-                    cmd: Command::Get { key },
-                } = req
-                {
-                    // has to use the `do_sync_cmd()` API
-                    let (old_results, cmd_result) = self
-                        .state_machine
-                        .do_sync_cmd(
-                            Self::make_ro_command_id(*client, *req_id),
-                            Command::Get { key: key.clone() },
+                let (req_id, cmd_result) = match req {
+                    ApiRequest::Req {
+                        id: req_id,
+                        cmd: Command::Get { key },
+                        ..
+                    } => {
+                        strip_read_only = true;
+                        (
+                            *req_id,
+                            self.coalesced_local_read(
+                                *client,
+                                *req_id,
+                                key,
+                                &mut served,
+                            )
+                            .await?,
                         )
-                        .await?;
-                    for (old_id, old_result) in old_results {
-                        self.handle_cmd_result(old_id, old_result).await?;
                     }
-
-                    self.external_api.send_reply(
-                        ApiReply::normal(*req_id, Some(cmd_result)),
-                        *client,
-                    )?;
-                    pf_trace!("replied -> client {} for read-only cmd", client);
-                    // [for access cnt stats only]
-                    if self.config.record_node_cnts {
-                        *self.node_cnts_stats.get_mut(&self.id).unwrap() += 1;
+                    ApiRequest::Req {
+                        id: req_id,
+                        cmd: cmd @ Command::Scan { .. },
+                        ..
+                    } => {
+                        strip_scans = true;
+                        (
+                            *req_id,
+                            self.local_scan(*client, *req_id, cmd.clone())
+                                .await?,
+                        )
                     }
+                    _ => continue,
+                };
 
-                    strip_read_only = true;
+                self.external_api.send_reply(
+                    ApiReply::normal(req_id, Some(cmd_result)),
+                    *client,
+                )?;
+                pf_trace!("replied -> client {} for read-only cmd", client);
+                // [for access cnt stats only]
+                if self.config.record_node_cnts {
+                    *self.node_cnts_stats.get_mut(&self.id).unwrap() += 1;
                 }
             }
         } else if (!self.is_leader() || self.bal_prepared == 0)
@@ -60,55 +127,250 @@ impl MultiPaxosReplica {
             // if near quorum read optimization is on, broadcast ReadQueries
             // for reads. Reference:
             //   https://www.usenix.org/system/files/hotstorage19-paper-charapko.pdf
-            let mut rq_id = (0, 0);
-            let mut rq_bk = ReadQueryBookkeeping {
-                reads: vec![],
-                rq_acks: Bitmap::new(self.population, false),
-                max_replies: vec![],
-            };
+            let reads: ReqBatch = req_batch
+                .iter()
+                .filter(|(_, req)| {
+                    matches!(
+                        req,
+                        ApiRequest::Req {
+                            cmd: Command::Get { .. },
+                            ..
+                        }
+                    )
+                })
+                .cloned()
+                .collect();
+            if !reads.is_empty() {
+                self.start_quorum_read(reads, false)?;
+                strip_read_only = true;
+            }
+        }
 
-            for (client, req) in req_batch.iter() {
-                if let ApiRequest::Req {
+        if strip_read_only {
+            req_batch.retain(|(_, req)| req.read_only().is_none());
+        }
+        if strip_scans {
+            req_batch.retain(|(_, req)| req.scan_range().is_none());
+        }
+        Ok(())
+    }
+
+    /// Reads key straight from my state machine, regardless of my role.
+    async fn read_local(
+        &mut self,
+        client: ClientId,
+        req_id: RequestId,
+        key: String,
+    ) -> Result<Option<String>, SummersetError> {
+        let (old_results, cmd_result) = self
+            .state_machine
+            .do_sync_cmd(
+                Self::make_ro_command_id(client, req_id),
+                Command::Get { key },
+            )
+            .await?;
+        for (old_id, old_result) in old_results {
+            self.handle_cmd_result(old_id, old_result).await?;
+        }
+
+        match cmd_result {
+            CommandResult::Get { value } => Ok(value),
+            _ => logged_err!(
+                Protocol;
+                "unexpected local read result type: {:?}",
+                cmd_result
+            ),
+        }
+    }
+
+    /// Serves all local reads in the batch straight from my state machine,
+    /// regardless of my role, leaving only the other requests in it.
+    async fn serve_local_reads(
+        &mut self,
+        req_batch: &mut ReqBatch,
+    ) -> Result<(), SummersetError> {
+        if !req_batch
+            .iter()
+            .any(|(_, req)| matches!(req, ApiRequest::LocalRead { .. }))
+        {
+            return Ok(());
+        }
+
+        let mut kept = ReqBatch::new();
+        for (client, req) in req_batch.drain(..) {
+            let ApiRequest::LocalRead { id: req_id, key } = req else {
+                kept.push((client, req));
+                continue;
+            };
+            let value = self.read_local(client, req_id, key).await?;
+            self.external_api.send_reply(
+                ApiReply::LocalRead {
                     id: req_id,
-                    cmd: Command::Get { key },
-                } = req
-                {
-                    rq_bk.reads.push((*client, req.clone()));
-                    rq_bk.max_replies.push(self.inspect_highest_slot(key)?);
-
-                    if !strip_read_only {
-                        rq_id = (*client, *req_id);
-                        strip_read_only = true;
+                    value,
+                    exec_bar: self.exec_bar,
+                    hlc: None, // stamped by the external API
+                },
+                client,
+            )?;
+            pf_trace!("replied -> client {} for local read", client);
+        }
+        *req_batch = kept;
+        Ok(())
+    }
+
+    /// Routes all `Read` requests in the batch onto the read paths of their
+    /// asked consistency levels, tagging their replies with the path taken:
+    ///   - `Local`: served right away from my state machine
+    ///   - `Leader`: left in the batch as a plain Get if I'm a prepared
+    ///     leader, to be served by lease or through the log; otherwise
+    ///     redirected
+    ///   - `Quorum`: served by a near quorum read started right away,
+    ///     regardless of `enable_quorum_reads`
+    ///   - `Pqr`: same as `Quorum`, but rinsed on me instead of falling back
+    ///     to the leader
+    async fn route_leveled_reads(
+        &mut self,
+        req_batch: &mut ReqBatch,
+    ) -> Result<(), SummersetError> {
+        if !req_batch
+            .iter()
+            .any(|(_, req)| matches!(req, ApiRequest::Read { .. }))
+        {
+            return Ok(());
+        }
+
+        let mut kept = ReqBatch::new();
+        let mut quorum_reads = ReqBatch::new();
+        let mut pqr_reads = ReqBatch::new();
+        for (client, req) in req_batch.drain(..) {
+            let ApiRequest::Read {
+                id: req_id,
+                key,
+                consistency,
+            } = req
+            else {
+                kept.push((client, req));
+                continue;
+            };
+            match consistency {
+                ReadConsistency::Local => {
+                    let value = self.read_local(client, req_id, key).await?;
+                    self.external_api.tag_read_path(
+                        client,
+                        req_id,
+                        ReadConsistency::Local,
+                    );
+                    self.external_api.send_reply(
+                        ApiReply::normal(
+                            req_id,
+                            Some(CommandResult::Get { value }),
+                        ),
+                        client,
+                    )?;
+                    pf_trace!("replied -> client {} for local read", client);
+                }
+
+                ReadConsistency::Leader => {
+                    if self.is_leader() && self.bal_prepared > 0 {
+                        self.external_api.tag_read_path(
+                            client,
+                            req_id,
+                            ReadConsistency::Leader,
+                        );
+                        kept.push((
+                            client,
+                            ApiRequest::Req {
+                                id: req_id,
+                                cmd: Command::Get { key },
+                                checksum: None,
+                            },
+                        ));
+                    } else {
+                        let target = self.redirect_target();
+                        self.external_api.send_reply(
+                            ApiReply::redirect(req_id, Some(target)),
+                            client,
+                        )?;
+                        pf_trace!(
+                            "redirected client {} to replica {}",
+                            client,
+                            target
+                        );
                     }
                 }
-            }
 
-            if strip_read_only {
-                // broadcast ReadQuery to peers
-                self.transport_hub.bcast_msg(
-                    PeerMsg::ReadQuery {
-                        reads: rq_bk.reads.clone(),
-                    },
-                    None,
-                )?;
-                pf_debug!(
-                    "broadcast ReadQuery messages for rq_id {}.{}",
-                    rq_id.0,
-                    rq_id.1
-                );
-
-                // mark myself as replied
-                rq_bk.rq_acks.set(self.id, true)?;
-                self.quorum_reads.insert(rq_id, rq_bk);
+                ReadConsistency::Quorum | ReadConsistency::Pqr => {
+                    self.external_api.tag_read_path(
+                        client,
+                        req_id,
+                        consistency,
+                    );
+                    let read = (
+                        client,
+                        ApiRequest::Req {
+                            id: req_id,
+                            cmd: Command::Get { key },
+                            checksum: None,
+                        },
+                    );
+                    if consistency == ReadConsistency::Pqr {
+                        pqr_reads.push(read);
+                    } else {
+                        quorum_reads.push(read);
+                    }
+                }
             }
         }
 
-        if strip_read_only {
-            req_batch.retain(|(_, req)| req.read_only().is_none());
+        if !quorum_reads.is_empty() {
+            self.start_quorum_read(quorum_reads, false)?;
+        }
+        if !pqr_reads.is_empty() {
+            self.start_quorum_read(pqr_reads, true)?;
         }
+        *req_batch = kept;
         Ok(())
     }
 
+    /// Replies to all writes in the batch with a `DiskFull` result, leaving
+    /// only the other requests in it.
+    fn reject_writes(
+        &mut self,
+        req_batch: &mut ReqBatch,
+        free_bytes: u64,
+    ) -> Result<(), SummersetError> {
+        let mut kept = ReqBatch::new();
+        for (client, req) in req_batch.drain(..) {
+            match req {
+                ApiRequest::Req {
+                    id: req_id, cmd, ..
+                } if cmd.read_only().is_none() => {
+                    self.external_api.send_reply(
+                        ApiReply::normal(
+                            req_id,
+                            Some(CommandResult::DiskFull { free_bytes }),
+                        ),
+                        client,
+                    )?;
+                    pf_trace!("rejected write from client {}", client);
+                }
+                req => kept.push((client, req)),
+            }
+        }
+        *req_batch = kept;
+        Ok(())
+    }
+
+    /// Replica to redirect clients to when I'm not the one to serve them:
+    /// the known leader, or just the next ID replica.
+    fn redirect_target(&self) -> ReplicaId {
+        if let Some(peer) = self.leader {
+            peer
+        } else {
+            (self.id + 1) % self.population
+        }
+    }
+
     /// Handler of client request batch chan recv.
     pub(super) async fn handle_req_batch(
         &mut self,
@@ -118,6 +380,14 @@ impl MultiPaxosReplica {
         debug_assert!(batch_size > 0);
         pf_debug!("got request batch of size {}", batch_size);
 
+        // local reads are served by whoever receives them, and reads asking
+        // for a consistency level are routed onto the matching paths
+        self.serve_local_reads(&mut req_batch).await?;
+        self.route_leveled_reads(&mut req_batch).await?;
+        if req_batch.is_empty() {
+            return Ok(());
+        }
+
         // if I'm a majority-leased leader or if simulating read leases, extract
         // all the reads and immediately reply to them
         self.treat_read_only_reqs(&mut req_batch).await?;
@@ -125,17 +395,17 @@ impl MultiPaxosReplica {
             return Ok(());
         }
 
-        // if I'm not a prepared leader, ignore client requests
-        if !self.is_leader() || self.bal_prepared == 0 {
+        // if I'm not a prepared leader, or if the current configuration is
+        // frozen by a pending stop-sign, ignore client requests
+        if !self.is_leader()
+            || self.bal_prepared == 0
+            || self.pending_stop_sign()
+        {
             for (client, req) in req_batch {
                 if let ApiRequest::Req { id: req_id, .. } = req {
                     // tell the client to try on known leader or just the
                     // next ID replica
-                    let target = if let Some(peer) = self.leader {
-                        peer
-                    } else {
-                        (self.id + 1) % self.population
-                    };
+                    let target = self.redirect_target();
                     self.external_api.send_reply(
                         ApiReply::redirect(req_id, Some(target)),
                         client,
@@ -154,26 +424,53 @@ impl MultiPaxosReplica {
             return Ok(());
         }
 
+        // if my WAL disk is critically short of space, reject writes rather
+        // than risk failing mid-append
+        if let Some(free_bytes) = self.wal_disk_critical {
+            self.reject_writes(&mut req_batch, free_bytes)?;
+            if req_batch.is_empty() {
+                return Ok(());
+            }
+        }
+
+        // in blob store mode, keep large values out of the log
+        self.stash_large_values(&mut req_batch).await?;
+
         // create a new instance in the first null slot (or append a new one
         // at the end if no holes exist); fill it up with incoming data
         let slot = self.first_null_slot();
+        self.propose_batch(slot, req_batch, true)
+    }
+
+    /// Fills the null instance at slot with the given batch and starts the
+    /// Accept phase for it.
+    pub(super) fn propose_batch(
+        &mut self,
+        slot: usize,
+        req_batch: ReqBatch,
+        external: bool,
+    ) -> Result<(), SummersetError> {
         {
             let inst = &mut self.insts[slot - self.start_slot];
             debug_assert_eq!(inst.status, Status::Null);
             inst.reqs.clone_from(&req_batch);
+            inst.hlc = self.clock.now();
             Self::refresh_highest_slot(
                 slot,
                 &req_batch,
                 &mut self.highest_slot,
             );
+            Self::note_blob_uses(slot, &req_batch, &mut self.blob_store);
             inst.leader_bk = Some(LeaderBookkeeping {
                 trigger_slot: 0,
                 endprep_slot: 0,
                 prepare_acks: Bitmap::new(self.population, false),
                 prepare_max_bal: 0,
+                witness_max_bal: 0,
                 accept_acks: Bitmap::new(self.population, false),
+                commit_members: None,
             });
-            inst.external = true;
+            inst.external = external;
         }
 
         // start the Accept phase for this instance
@@ -188,7 +485,7 @@ impl MultiPaxosReplica {
         }
 
         // record update to largest accepted ballot and corresponding data
-        inst.voted = (inst.bal, req_batch.clone());
+        inst.voted = (inst.bal, req_batch.clone(), inst.hlc);
         self.storage_hub.submit_action(
             Self::make_log_action_id(slot, Status::Accepting),
             LogAction::Append {
@@ -196,6 +493,7 @@ impl MultiPaxosReplica {
                     slot,
                     ballot: inst.bal,
                     reqs: req_batch.clone(),
+                    hlc: inst.hlc,
                 },
                 sync: self.config.logger_sync,
             },
@@ -207,19 +505,9 @@ impl MultiPaxosReplica {
         );
 
         // send Accept messages to all peers
-        self.transport_hub.bcast_msg(
-            PeerMsg::Accept {
-                slot,
-                ballot: inst.bal,
-                reqs: req_batch,
-            },
-            None,
-        )?;
-        pf_trace!(
-            "broadcast Accept messages for slot {} bal {}",
-            slot,
-            inst.bal
-        );
+        let (ballot, hlc) = (inst.bal, inst.hlc);
+        self.bcast_accept(slot, ballot, req_batch, hlc)?;
+        pf_trace!("broadcast Accept messages for slot {} bal {}", slot, ballot);
 
         Ok(())
     }
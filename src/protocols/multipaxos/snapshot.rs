@@ -1,44 +1,104 @@
 //! MultiPaxos -- snapshotting & GC.
 
 use std::cmp;
-use std::collections::HashMap;
+use std::path::PathBuf;
 
 use super::*;
 
 use crate::manager::CtrlMsg;
-use crate::server::{ApiRequest, LogAction, LogResult};
+use crate::server::{disk_free_bytes, LogAction, LogResult};
 use crate::utils::SummersetError;
 
 // MultiPaxosReplica snapshotting & GC logic
 impl MultiPaxosReplica {
-    /// Dump new key-value pairs to snapshot file.
-    async fn snapshot_dump_kv_pairs(
+    /// Dump an image of the state machine to the end of snapshot file.
+    /// Returns the slot it is taken at: the image reflects exactly the
+    /// commands of all slots before it.
+    async fn snapshot_dump_state(&mut self) -> Result<usize, SummersetError> {
+        // handle results of all commands submitted before taking the image,
+        // so that exec_bar then stands exactly where the image is taken
+        let unacked = self.state_machine.num_unacked();
+        let image = self.state_machine.take_snapshot().await?;
+        for _ in 0..unacked {
+            let (cmd_id, cmd_result) = self.state_machine.get_result().await?;
+            self.handle_cmd_result(cmd_id, cmd_result).await?;
+        }
+        let snap_start = self.exec_bar;
+
+        // find the latest stop-sign's members up to there if any
+        for slot in self.snap_start.max(self.start_slot)..snap_start {
+            self.page_in_inst(slot).await?;
+            let inst = &self.insts[slot - self.start_slot];
+            if let Some(stop_sign) = Self::parse_stop_sign(&inst.reqs) {
+                self.snap_members = Some(stop_sign.clone());
+            }
+        }
+
+        // write the image to snapshot file
+        let mut entries = vec![SnapEntry::StateImage {
+            image: image.to_vec(),
+        }];
+        if let Some(members) = self.snap_members.clone() {
+            entries.push(SnapEntry::Members { members });
+        }
+        for entry in entries {
+            if let LogResult::Append { now_size } = self
+                .snapshot_hub
+                .do_sync_action(
+                    0, // using 0 as dummy log action ID
+                    LogAction::Append {
+                        entry,
+                        sync: self.config.logger_sync,
+                    },
+                )
+                .await?
+                .1
+            {
+                self.snap_offset = now_size;
+            } else {
+                return logged_err!(Protocol; "unexpected log result type");
+            }
+        }
+        Ok(snap_start)
+    }
+
+    /// Discard the entries between the slot info head and given offset in
+    /// snapshot file, i.e., those of the previous image.
+    pub(super) async fn snapshot_discard_old(
         &mut self,
-        new_start_slot: usize,
+        offset: usize,
     ) -> Result<(), SummersetError> {
-        // collect all key-value pairs put up to exec_bar
-        let mut pairs = HashMap::new();
-        for slot in self.start_slot..new_start_slot {
-            let inst = &self.insts[slot - self.start_slot];
-            for (_, req) in inst.reqs.clone() {
-                if let ApiRequest::Req {
-                    cmd: Command::Put { key, value },
-                    ..
-                } = req
-                {
-                    pairs.insert(key, value);
-                }
+        let head_len = match self
+            .snapshot_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Read { offset: 0 },
+            )
+            .await?
+            .1
+        {
+            LogResult::Read {
+                entry: Some(SnapEntry::SlotInfo { .. }),
+                end_offset,
+            } => end_offset,
+            _ => {
+                return logged_err!(Protocol; "unexpected log result type");
             }
+        };
+        if offset <= head_len {
+            return Ok(()); // nothing to discard
         }
 
-        // write the collection to snapshot file
-        if let LogResult::Append { now_size } = self
+        if let LogResult::Discard {
+            offset_ok: true,
+            now_size,
+        } = self
             .snapshot_hub
             .do_sync_action(
                 0, // using 0 as dummy log action ID
-                LogAction::Append {
-                    entry: SnapEntry::KVPairSet { pairs },
-                    sync: self.config.logger_sync,
+                LogAction::Discard {
+                    offset,
+                    keep: head_len,
                 },
             )
             .await?
@@ -47,12 +107,16 @@ impl MultiPaxosReplica {
             self.snap_offset = now_size;
             Ok(())
         } else {
-            logged_err!("unexpected log result type")
+            logged_err!(
+                Protocol; "unexpected log result type or failed discard"
+            )
         }
     }
 
     /// Discard everything older than start_slot in durable WAL log.
-    async fn snapshot_discard_log(&mut self) -> Result<(), SummersetError> {
+    pub(super) async fn snapshot_discard_log(
+        &mut self,
+    ) -> Result<(), SummersetError> {
         // do a dummy sync read to force all previously submitted log actions
         // to be processed
         let (old_results, _) = self
@@ -91,7 +155,7 @@ impl MultiPaxosReplica {
                 self.wal_offset = now_size;
             } else {
                 return logged_err!(
-                    "unexpected log result type or failed discard"
+                    Protocol; "unexpected log result type or failed discard"
                 );
             }
         }
@@ -130,25 +194,33 @@ impl MultiPaxosReplica {
         );
         debug_assert!(self.exec_bar >= self.start_slot);
 
-        let new_start_slot = cmp::min(self.snap_bar, self.exec_bar);
-        if new_start_slot == self.start_slot {
+        if cmp::min(self.snap_bar, self.exec_bar) == self.start_slot {
+            return Ok(());
+        }
+
+        // in blob store mode, commands of a slot may be held back partly
+        // behind a missing blob, and an image would cut through that slot
+        if !self.exec_pending.is_empty() {
             return Ok(());
         }
 
         // NOTE: broadcast heartbeats here to appease peers
-        self.bcast_heartbeats().await?;
+        self.bcast_heartbeats(true).await?;
 
-        // collect and dump all Puts in executed instances
-        self.snapshot_dump_kv_pairs(new_start_slot).await?;
+        // dump an image of the state machine after the previous one
+        let old_offset = self.snap_offset;
+        let snap_start = self.snapshot_dump_state().await?;
+        let new_start_slot = cmp::min(self.snap_bar, snap_start);
 
-        // write new slot info entry to the head of snapshot
+        // write new slot info entry to the head of snapshot, then discard the
+        // previous image
         match self
             .snapshot_hub
             .do_sync_action(
                 0, // using 0 as dummy log action ID
                 LogAction::Write {
                     entry: SnapEntry::SlotInfo {
-                        start_slot: new_start_slot,
+                        start_slot: snap_start,
                     },
                     offset: 0,
                     sync: self.config.logger_sync,
@@ -162,17 +234,20 @@ impl MultiPaxosReplica {
             } => {}
             _ => {
                 return logged_err!(
-                    "unexpected log result type or failed write"
+                    Protocol; "unexpected log result type or failed write"
                 );
             }
         }
+        self.snap_start = snap_start;
+        self.snapshot_discard_old(old_offset).await?;
+        self.collect_blob_garbage(snap_start).await?;
 
         // update start_slot and discard all in-memory log instances up to exec_bar
         self.insts.drain(0..(new_start_slot - self.start_slot));
         self.start_slot = new_start_slot;
 
         // NOTE: broadcast heartbeats here to appease peers
-        self.bcast_heartbeats().await?;
+        self.bcast_heartbeats(true).await?;
 
         // discarding everything older than start_slot in WAL log
         self.snapshot_discard_log().await?;
@@ -186,6 +261,52 @@ impl MultiPaxosReplica {
         Ok(())
     }
 
+    /// Checks free space on the WAL's filesystem against `wal_min_free_mb`.
+    /// If below it, forces a snapshot to compact the WAL and checks again;
+    /// if still below, marks the disk critical so that writes get rejected
+    /// (or, on followers, Accepts left unvoted) instead of failing mid-append
+    /// once the disk fills up.
+    pub(super) async fn guard_wal_disk_space(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        let min_free = self.config.wal_min_free_mb * 1024 * 1024;
+        let wal_path = PathBuf::from(&self.config.backer_path);
+        let Some(mut free) = disk_free_bytes(&wal_path) else {
+            return Ok(()); // cannot tell, keep going as usual
+        };
+
+        if free < min_free {
+            pf_warn!(
+                "WAL disk down to {} MiB free, forcing a snapshot",
+                free / (1024 * 1024)
+            );
+            let old_start_slot = self.start_slot;
+            self.take_new_snapshot().await?;
+            if self.start_slot > old_start_slot {
+                self.control_hub.send_ctrl(CtrlMsg::SnapshotUpTo {
+                    new_start: self.start_slot,
+                })?;
+            }
+            free = disk_free_bytes(&wal_path).unwrap_or(free);
+        }
+
+        if free < min_free {
+            if self.wal_disk_critical.is_none() {
+                pf_error!(
+                    "WAL disk still {} MiB free, rejecting writes",
+                    free / (1024 * 1024)
+                );
+            }
+            self.wal_disk_critical = Some(free);
+        } else if self.wal_disk_critical.take().is_some() {
+            pf_info!(
+                "WAL disk back to {} MiB free, accepting writes",
+                free / (1024 * 1024)
+            );
+        }
+        Ok(())
+    }
+
     /// Recover initial state from durable storage snapshot file.
     pub(super) async fn recover_from_snapshot(
         &mut self,
@@ -215,8 +336,16 @@ impl MultiPaxosReplica {
                 self.commit_bar = start_slot;
                 self.exec_bar = start_slot;
                 self.snap_bar = start_slot;
+                self.snap_start = start_slot;
 
-                // repeatedly apply key-value pairs
+                // a persistent state machine that had applied commands at or
+                // beyond start_slot already holds everything snapshotted
+                let have_state =
+                    self.state_machine.durable_applied().is_some_and(|id| {
+                        Self::split_command_id(id).0 >= start_slot
+                    });
+
+                // restore the state image and members recorded
                 loop {
                     match self
                         .snapshot_hub
@@ -230,27 +359,35 @@ impl MultiPaxosReplica {
                         .1
                     {
                         LogResult::Read {
-                            entry: Some(SnapEntry::KVPairSet { pairs }),
+                            entry: Some(SnapEntry::StateImage { image }),
                             end_offset,
                         } => {
-                            // execute Put commands on state machine
-                            for (key, value) in pairs {
+                            if !have_state {
                                 self.state_machine
-                                    .do_sync_cmd(
-                                        0, // using 0 as dummy command ID
-                                        Command::Put { key, value },
-                                    )
+                                    .restore_snapshot(image.into())
                                     .await?;
                             }
                             // update snapshot file offset
                             self.snap_offset = end_offset;
                         }
+                        LogResult::Read {
+                            entry: Some(SnapEntry::Members { members }),
+                            end_offset,
+                        } => {
+                            // restore configuration of voting members
+                            self.set_members(members.clone())?;
+                            self.snap_members = Some(members);
+                            // update snapshot file offset
+                            self.snap_offset = end_offset;
+                        }
                         LogResult::Read { entry: None, .. } => {
                             // end of log reached
                             break;
                         }
                         _ => {
-                            return logged_err!("unexpected log result type");
+                            return logged_err!(
+                                Protocol; "unexpected log result type"
+                            );
                         }
                     }
                 }
@@ -292,12 +429,14 @@ impl MultiPaxosReplica {
                     self.snap_offset = now_size;
                     Ok(())
                 } else {
-                    logged_err!("unexpected log result type or failed write")
+                    logged_err!(
+                        Protocol; "unexpected log result type or failed write"
+                    )
                 }
             }
 
             _ => {
-                logged_err!("unexpected log result type")
+                logged_err!(Protocol; "unexpected log result type")
             }
         }
     }
@@ -0,0 +1,55 @@
+//! MultiPaxos -- Vertical Paxos mode.
+//!
+//! The cluster manager acts as the configuration master: replicas that time
+//! out on the leader request a new ballot from it instead of making one up,
+//! and the master grants a ballot together with a new set of voting members.
+//! The granted leader runs its Prepare phase against a majority of the
+//! previous configuration, then reports the ballot as active. Reference:
+//!   - <https://lamport.azurewebsites.net/pubs/vertical-paxos.pdf>
+
+use super::*;
+
+use crate::server::ReplicaId;
+use crate::utils::{Bitmap, SummersetError};
+
+// MultiPaxosReplica Vertical Paxos mode logic
+impl MultiPaxosReplica {
+    /// Handler of BallotGrant control message.
+    pub(super) async fn handle_ctrl_ballot_grant(
+        &mut self,
+        leader: ReplicaId,
+        epoch: u64,
+        members: Bitmap,
+        prev_members: Bitmap,
+    ) -> Result<(), SummersetError> {
+        let ballot = ((epoch << 16) | ((leader + 1) as u64)) as Ballot;
+        if !self.config.vertical_paxos || ballot < self.bal_max_seen {
+            return Ok(()); // ignore if not in this mode or grant outdated
+        }
+        pf_info!(
+            "manager granted epoch {} to {} with members {:?}",
+            epoch,
+            leader,
+            members
+        );
+
+        // raise my ballot to the granted one first, so that Accepts from the
+        // previous leader get rejected once the new configuration is in place
+        let newer = ballot > self.bal_max_seen;
+        if leader != self.id {
+            self.check_leader(leader, ballot).await?;
+        } else if newer {
+            self.bal_max_seen = ballot;
+        }
+
+        // the new configuration decides all accepts from now on
+        self.set_members(members)?;
+
+        if leader == self.id && newer {
+            self.prep_members = Some(prev_members);
+            self.become_a_leader(self.id, Some(ballot)).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+//! MultiPaxos -- witness replicas.
+//!
+//! Witnesses are acceptors that store only metadata: the leader sends them
+//! Accepts with the request batch stripped off (except for stop-sign entries,
+//! which carry configuration), so their WAL holds ballots but not values.
+//! Their acks count towards commit quorums as usual, saving storage and
+//! bandwidth at the cost of a committed value possibly living on fewer full
+//! replicas. A new leader therefore cannot finish its Prepare phase while
+//! some witness in the quorum reports a higher accepted ballot for a slot
+//! than any full replica does; it keeps waiting for more Prepare replies
+//! until one carrying the value shows up. Witnesses never lead and never
+//! serve reads. Reference:
+//!   - Pâris, "Voting with Witnesses: A Consistency Scheme for Replicated
+//!     Files", ICDCS 1986
+
+use super::*;
+
+use crate::server::ReplicaId;
+use crate::utils::{Bitmap, SummersetError};
+
+// MultiPaxosReplica witness replicas logic
+impl MultiPaxosReplica {
+    /// Parse config string of comma-separated replica IDs into the bitmap of
+    /// witness replicas. Witnesses must be a minority of the population.
+    pub(super) fn parse_witnesses(
+        population: u16,
+        s: &str,
+    ) -> Result<Bitmap, SummersetError> {
+        let mut witnesses = Bitmap::new(population, false);
+        for seg in s.split(',').map(str::trim).filter(|seg| !seg.is_empty()) {
+            let r = seg.parse::<ReplicaId>()?;
+            if r >= population {
+                return logged_err!(Config; "invalid witnesses string {}", s);
+            }
+            witnesses.set(r, true)?;
+        }
+        if witnesses.count() > (population - 1) / 2 {
+            return logged_err!(
+                Config; "witnesses {} are not a minority", s
+            );
+        }
+        Ok(witnesses)
+    }
+
+    /// Is the given replica a witness?
+    #[inline]
+    pub(super) fn is_witness(&self, peer: ReplicaId) -> bool {
+        self.witnesses.get(peer).unwrap_or(false)
+    }
+
+    /// Broadcasts Accept messages for a slot to all peers, with the request
+    /// batch stripped off for witnesses unless it is a stop-sign entry.
+    pub(super) fn bcast_accept(
+        &mut self,
+        slot: usize,
+        ballot: Ballot,
+        reqs: ReqBatch,
+        hlc: HlcStamp,
+    ) -> Result<(), SummersetError> {
+        if self.witnesses.count() == 0 || Self::parse_stop_sign(&reqs).is_some()
+        {
+            return self.transport_hub.bcast_msg(
+                PeerMsg::Accept {
+                    slot,
+                    ballot,
+                    reqs,
+                    hlc,
+                },
+                None,
+            );
+        }
+
+        let mut full = self.witnesses.clone();
+        full.flip();
+        self.transport_hub.bcast_msg(
+            PeerMsg::Accept {
+                slot,
+                ballot,
+                reqs,
+                hlc,
+            },
+            Some(full),
+        )?;
+        self.transport_hub.bcast_msg(
+            PeerMsg::Accept {
+                slot,
+                ballot,
+                reqs: ReqBatch::new(),
+                hlc,
+            },
+            Some(self.witnesses.clone()),
+        )
+    }
+
+    /// Do the Prepare replies received so far carry the value for every
+    /// slot being prepared from `trigger_slot` on, i.e., is no slot voted by
+    /// some witness at a higher ballot than by any full replica?
+    pub(super) fn witness_votes_resolved(&self, trigger_slot: usize) -> bool {
+        self.insts
+            .iter()
+            .skip(trigger_slot - self.start_slot)
+            .filter(|i| i.status == Status::Preparing)
+            .filter_map(|i| i.leader_bk.as_ref())
+            .all(|bk| bk.witness_max_bal <= bk.prepare_max_bal)
+    }
+}
@@ -114,6 +114,11 @@ impl QuorumLeasesReplica {
                 Ok(None)
             }
 
+            CtrlMsg::ExpireClients { clients } => {
+                self.external_api.expire_clients(&clients)?;
+                Ok(None)
+            }
+
             _ => Ok(None), // ignore all other types
         }
     }
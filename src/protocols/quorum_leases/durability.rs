@@ -114,7 +114,7 @@ impl QuorumLeasesReplica {
                 self.qlease_manager.add_notice(
                     self.qlease_num,
                     LeaseNotice::DoRevoke {
-                        peers: Some(self.qlease_grantees().clone()),
+                        peers: Some(self.qlease_grantees()),
                     },
                 )?;
                 // synchronous ensurance not needed here, because in normal case,
@@ -201,13 +201,14 @@ impl QuorumLeasesReplica {
                     for (cmd_idx, (client, req)) in inst.reqs.iter().enumerate()
                     {
                         match req {
-                            ApiRequest::Req { cmd, .. } => {
-                                self.state_machine.submit_cmd(
+                            ApiRequest::Req { cmd, checksum, .. } => {
+                                self.state_machine.submit_checked_cmd(
                                     Self::make_command_id(
                                         self.commit_bar,
                                         cmd_idx,
                                     ),
                                     cmd.clone(),
+                                    *checksum,
                                 )?;
                             }
                             ApiRequest::Conf { id: req_id, delta } => {
@@ -251,7 +252,7 @@ impl QuorumLeasesReplica {
                         self.qlease_manager.add_notice(
                             self.qlease_num,
                             LeaseNotice::NewGrants {
-                                peers: Some(self.qlease_grantees().clone()),
+                                peers: Some(self.qlease_grantees()),
                                 accept_bar: None,
                             },
                         )?;
@@ -307,7 +308,9 @@ impl QuorumLeasesReplica {
             // then update self.wal_offset
             self.wal_offset = now_size;
         } else {
-            return logged_err!("unexpected log result type: {:?}", log_result);
+            return logged_err!(
+                Protocol; "unexpected log result type: {:?}", log_result
+            );
         }
 
         match entry_type {
@@ -315,7 +318,9 @@ impl QuorumLeasesReplica {
             Status::Accepting => self.handle_logged_accept_data(slot).await,
             Status::Committed => self.handle_logged_commit_slot(slot).await,
             _ => {
-                logged_err!("unexpected log entry type: {:?}", entry_type)
+                logged_err!(
+                    Protocol; "unexpected log entry type: {:?}", entry_type
+                )
             }
         }
     }
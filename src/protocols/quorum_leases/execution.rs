@@ -53,7 +53,7 @@ impl QuorumLeasesReplica {
                 }
             }
         } else {
-            return logged_err!("unexpected API request type");
+            return logged_err!(Protocol; "unexpected API request type");
         }
 
         // if all commands in this instance have been executed, set status to
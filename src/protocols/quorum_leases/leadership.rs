@@ -2,8 +2,10 @@
 
 use super::*;
 
-use crate::manager::CtrlMsg;
-use crate::server::{LeaseNotice, LogAction, ReplicaId};
+use crate::manager::{CtrlMsg, ServerStatus};
+use crate::server::{
+    DebugState, LeaseNotice, LogAction, ReplicaId, ResourceUsage,
+};
 use crate::utils::{Bitmap, SummersetError};
 
 // QuorumLeasesReplica heartbeats related logic
@@ -61,6 +63,7 @@ impl QuorumLeasesReplica {
             // set this peer to be the believed leader
             debug_assert_ne!(peer, self.id);
             self.leader = Some(peer);
+            self.external_api.notify_leader_change(peer)?;
             self.bal_max_seen = ballot;
         }
 
@@ -102,6 +105,7 @@ impl QuorumLeasesReplica {
         self.leader = Some(self.id);
         self.control_hub
             .send_ctrl(CtrlMsg::LeaderStatus { step_up: true })?;
+        self.external_api.notify_leader_change(self.id)?;
         pf_info!("becoming a leader...");
 
         // clear peers' heartbeat reply counters, and broadcast a heartbeat now
@@ -230,7 +234,7 @@ impl QuorumLeasesReplica {
                         Some(&Bitmap::from((self.population, vec![leader]))),
                     )?;
                     if to_refresh.count() > 0 {
-                        self.transport_hub.bcast_lease_msg(
+                        self.transport_hub.queue_lease_msg(
                             0, // gid 0 for leader leases
                             self.bal_max_seen,
                             LeaseMsg::Promise,
@@ -247,9 +251,9 @@ impl QuorumLeasesReplica {
         {
             let to_refresh = self
                 .qlease_manager
-                .attempt_refresh(Some(&self.qlease_grantees().clone()))?;
+                .attempt_refresh(Some(&self.qlease_grantees()))?;
             if to_refresh.count() > 0 {
-                self.transport_hub.bcast_lease_msg(
+                self.transport_hub.queue_lease_msg(
                     1, // gid 1 for quorum leases
                     self.qlease_num,
                     LeaseMsg::Promise,
@@ -258,6 +262,9 @@ impl QuorumLeasesReplica {
             }
         }
 
+        // send refreshes of both purposes to each peer in one batch
+        self.transport_hub.flush_lease_msgs()?;
+
         // broadcast heartbeat to all peers
         self.transport_hub.bcast_msg(
             PeerMsg::Heartbeat {
@@ -283,6 +290,36 @@ impl QuorumLeasesReplica {
         )
         .await?;
 
+        // expose my protocol state to debug queries
+        self.external_api.update_debug_state(DebugState {
+            is_leader: self.is_leader(),
+            leader: self.leader,
+            ballot: self.bal_max_seen,
+            commit_bar: self.commit_bar,
+            exec_bar: self.exec_bar,
+            start_slot: self.start_slot,
+            log_len: self.insts.len(),
+            peers_alive: self.heartbeater.peer_alive().clone(),
+        });
+
+        // periodically report my status to the manager
+        self.control_hub.report_status(ServerStatus {
+            ballot: self.bal_max_seen,
+            commit_bar: self.commit_bar,
+            exec_bar: self.exec_bar,
+            peers_alive: self.heartbeater.peer_alive().clone(),
+            perf_models: HashMap::new(),
+            hot_keys: self.state_machine.hot_keys(),
+            queue_depths: vec![
+                self.external_api.queue_depth_report(),
+                self.storage_hub.queue_depth_report(),
+                self.transport_hub.queue_depth_report(),
+                self.state_machine.queue_depth_report(),
+            ],
+            resources: ResourceUsage::default(),
+            divergence_repaired: 0,
+        })?;
+
         // pf_trace!("broadcast heartbeats bal {}", self.bal_prep_sent);
         Ok(())
     }
@@ -350,7 +387,7 @@ impl QuorumLeasesReplica {
                     .peer_exec_bar
                     .values()
                     .filter(|&&e| e >= exec_bar)
-                    .count() as u8;
+                    .count() as u16;
                 if passed_cnt == self.population {
                     // all servers have executed up to exec_bar
                     self.snap_bar = exec_bar;
@@ -404,7 +404,12 @@ impl QuorumLeasesReplica {
             leader_bk.accept_grant_sets.entry(peer).or_insert(grant_set);
 
             // if commit condition is reached, mark this instance as committed
-            if Self::commit_condition(leader_bk, self.quorum_cnt)? {
+            if Self::commit_condition(
+                leader_bk,
+                &inst.reqs,
+                &self.qlease_conf,
+                self.quorum_cnt,
+            )? {
                 inst.status = Status::Committed;
                 pf_debug!(
                     "committed instance at slot {} bal {}",
@@ -464,7 +469,12 @@ impl QuorumLeasesReplica {
                             .or_insert(Bitmap::new(self.population, false));
 
                         // if commit condition is now reached, proceed to commit
-                        if Self::commit_condition(leader_bk, self.quorum_cnt)? {
+                        if Self::commit_condition(
+                            leader_bk,
+                            &inst.reqs,
+                            &self.qlease_conf,
+                            self.quorum_cnt,
+                        )? {
                             inst.status = Status::Committed;
                             pf_debug!(
                                 "committed instance at slot {} bal {}",
@@ -2,11 +2,11 @@
 //!
 //! Built on top of MultiPaxos, allows marking an arbitrary subset of nodes as
 //! read lease holders and letting them serve reads locally during quiescent
-//! periods when no concurrent writes are happending. References:
+//! periods when no concurrent writes are happending. Leaseholders can be set
+//! per range of keys in format `k<number>`; a write then only waits on the
+//! leaseholders of the keys it touches. References:
 //!   - <https://www.cs.cmu.edu/~imoraru/papers/qrl.pdf>
 //!   - <https://www.pdl.cmu.edu/PDL-FTP/associated/CMU-PDL-14-105.pdf>
-//!
-//! TODO: fully support internal key-range partitioned quorum leases
 
 mod control;
 mod durability;
@@ -55,6 +55,19 @@ pub struct ReplicaConfigQuorumLeases {
     /// Client request batching maximum batch size.
     pub max_batch_size: usize,
 
+    /// Number of completed replies cached per client for collapsing retried
+    /// requests by idempotency key; 0 means collapsing disabled.
+    pub idem_cache_size: usize,
+
+    /// Idle timeout in secs after which a client session that sent no request
+    /// is closed and its state reclaimed; 0 means never.
+    pub session_idle_s: u64,
+
+    /// Quota in KiB of approximate key-value bytes stored per namespace (the
+    /// key prefix before the first '/'), beyond which Puts are rejected; 0
+    /// means unlimited.
+    pub ns_quota_kb: usize,
+
     /// Path to backing log file.
     pub backer_path: String,
 
@@ -118,6 +131,9 @@ impl Default for ReplicaConfigQuorumLeases {
         ReplicaConfigQuorumLeases {
             batch_interval_ms: 1,
             max_batch_size: 5000,
+            idem_cache_size: 0,
+            session_idle_s: 0,
+            ns_quota_kb: 0,
             backer_path: "/tmp/summerset.quorum_leases.wal".into(),
             logger_sync: false,
             hb_hear_timeout_min: 1200,
@@ -246,9 +262,9 @@ enum WalEntry {
 
 /// Snapshot file entry type.
 //
-// NOTE: the current implementation simply appends a squashed log at the
-//       end of the snapshot file for simplicity. In production, the snapshot
-//       file should be a bounded-sized backend, e.g., an LSM-tree.
+// NOTE: the current implementation rewrites a whole image of the state
+//       machine into the snapshot file for simplicity. In production, the
+//       snapshot file should be a bounded-sized backend, e.g., an LSM-tree.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, GetSize)]
 enum SnapEntry {
     /// Necessary slot indices to remember.
@@ -258,8 +274,9 @@ enum SnapEntry {
         start_slot: usize,
     },
 
-    /// Set of key-value pairs to apply to the state.
-    KVPairSet { pairs: HashMap<String, String> },
+    /// Image of the state machine taken by `take_snapshot()`, reflecting
+    /// exactly the commands of all slots before start_slot.
+    StateImage { image: Vec<u8> },
 }
 
 /// Peer-peer message type.
@@ -343,10 +360,10 @@ pub(crate) struct QuorumLeasesReplica {
     id: ReplicaId,
 
     /// Total number of replicas in cluster.
-    population: u8,
+    population: u16,
 
     /// Majority quorum size.
-    quorum_cnt: u8,
+    quorum_cnt: u16,
 
     /// Configuration parameters struct.
     config: ReplicaConfigQuorumLeases,
@@ -507,13 +524,13 @@ impl QuorumLeasesReplica {
     /// Compose a unique ballot number from base.
     #[inline]
     fn make_unique_ballot(&self, base: u64) -> Ballot {
-        ((base << 8) | ((self.id + 1) as u64)) as Ballot
+        ((base << 16) | ((self.id + 1) as u64)) as Ballot
     }
 
     /// Compose a unique ballot number greater than the given one.
     #[inline]
     fn make_greater_ballot(&self, bal: Ballot) -> Ballot {
-        self.make_unique_ballot((bal >> 8) + 1)
+        self.make_unique_ballot((bal >> 16) + 1)
     }
 
     /// Compose LogActionId from slot index & entry type.
@@ -579,11 +596,15 @@ impl GenericReplica for QuorumLeasesReplica {
         // connect to the cluster manager and get assigned a server ID
         let mut control_hub = ControlHub::new_and_setup(manager).await?;
         let id = control_hub.me;
+        let (api_addr, p2p_addr) =
+            control_hub.resolve_addrs(api_addr, p2p_addr)?;
         let population = control_hub.population;
 
         // parse protocol-specific configs
         let config = parsed_config!(config_str => ReplicaConfigQuorumLeases;
                                     batch_interval_ms, max_batch_size,
+                                    idem_cache_size, session_idle_s,
+                                    ns_quota_kb,
                                     backer_path, logger_sync,
                                     hb_hear_timeout_min, hb_hear_timeout_max,
                                     hb_send_interval_ms, disable_hb_timer,
@@ -631,12 +652,15 @@ impl GenericReplica for QuorumLeasesReplica {
         }
 
         // setup state machine module
-        let state_machine = StateMachine::new_and_setup(id).await?;
+        let state_machine =
+            StateMachine::new_and_setup(id, config.ns_quota_kb * 1024).await?;
+        control_hub.inspect_keys(&state_machine);
 
         // setup storage hub module
         let storage_hub =
             StorageHub::new_and_setup(id, Path::new(&config.backer_path))
                 .await?;
+        control_hub.monitor_wal(Path::new(&config.backer_path));
 
         // setup heartbeat management module
         let mut heartbeater = Heartbeater::new_and_setup(
@@ -665,16 +689,18 @@ impl GenericReplica for QuorumLeasesReplica {
         )?;
         let mut qlease_conf = RespondersConf::empty(population);
         qlease_conf.set_responders(
-            None, // currently just bluntly maintain a full-range config
+            None, // start with a full-range config with no leaseholders
             Bitmap::new(population, false),
-            Some(()), // ...with dumb custom index ()
+            None,
         )?;
 
         // setup transport hub module
         let mut transport_hub = TransportHub::new_and_setup(
             id,
             population,
+            SmrProtocol::QuorumLeases,
             p2p_addr,
+            false,
             HashMap::from([
                 (
                     0, // gid 0 for leader leases
@@ -702,7 +728,7 @@ impl GenericReplica for QuorumLeasesReplica {
         {
             to_peers
         } else {
-            return logged_err!("unexpected ctrl msg type received");
+            return logged_err!(Protocol; "unexpected ctrl msg type received");
         };
 
         // proactively connect to some peers, then wait for all population
@@ -718,13 +744,16 @@ impl GenericReplica for QuorumLeasesReplica {
                 .await?;
 
         // setup external API module, ready to take in client requests
-        let external_api = ExternalApi::new_and_setup(
+        let mut external_api = ExternalApi::new_and_setup(
             id,
             api_addr,
             Duration::from_millis(config.batch_interval_ms),
             config.max_batch_size,
+            config.idem_cache_size,
+            Duration::from_secs(config.session_idle_s),
         )
         .await?;
+        external_api.stamp_replies(&state_machine);
 
         let mut snapshot_interval = time::interval(Duration::from_secs(
             if config.snapshot_interval_s > 0 {
@@ -958,7 +987,7 @@ impl GenericReplica for QuorumLeasesReplica {
         self.id
     }
 
-    fn population(&self) -> u8 {
+    fn population(&self) -> u16 {
         self.population
     }
 }
@@ -991,7 +1020,7 @@ pub(crate) struct QuorumLeasesClient {
     id: ClientId,
 
     /// Number of servers in the cluster.
-    population: u8,
+    population: u16,
 
     /// Configuration parameters struct.
     config: ClientConfigQuorumLeases,
@@ -1104,7 +1133,7 @@ impl GenericEndpoint for QuorumLeasesClient {
                 }
                 Ok(())
             }
-            _ => logged_err!("unexpected reply type received"),
+            _ => logged_err!(Protocol; "unexpected reply type received"),
         }
     }
 
@@ -1181,6 +1210,31 @@ impl GenericEndpoint for QuorumLeasesClient {
         }
     }
 
+    fn send_hedge(&mut self, req: &ApiRequest) -> Result<bool, SummersetError> {
+        // only a read sent to a near server other than the believed leader
+        // can be hedged, by duplicating it to the leader, which can always
+        // serve it safely
+        if req.read_only().is_none()
+            || self.last_server_id.is_some()
+            || self.near_server_id.is_none_or(|s| s == self.curr_server_id)
+        {
+            return Ok(false);
+        }
+
+        match self.api_stubs.get(&self.curr_server_id) {
+            Some(api_stub) => {
+                let mut api_stub = api_stub.borrow_mut();
+                let mut success = api_stub.send_req(Some(req))?;
+                while !success {
+                    success = api_stub.send_req(None)?;
+                }
+                pf_debug!("hedged read to replica {}", self.curr_server_id);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
     async fn recv_reply(&mut self) -> Result<ApiReply, SummersetError> {
         if !self.api_stubs.contains_key(&self.curr_server_id) {
             return Err(SummersetError::msg(format!(
@@ -1230,11 +1284,28 @@ impl GenericEndpoint for QuorumLeasesClient {
             }
         };
 
+        if let ApiReply::LeaderChange { leader } = reply {
+            // notified of a new leader; switch over to it, or keep waiting if
+            // already talking to it
+            if leader == self.curr_server_id
+                || !self.servers.contains_key(&leader)
+            {
+                return self.recv_reply().await;
+            }
+            self.curr_server_id = leader;
+            pf_debug!(
+                "notified of new leader {} '{}'",
+                leader,
+                self.servers[&leader]
+            );
+        }
+
         if let ApiReply::Reply {
             id: req_id,
             ref result,
             ref redirect,
             ref mut rq_retry,
+            ..
         } = reply
         {
             // if the current server redirects me to a different server
@@ -1271,6 +1342,7 @@ impl GenericEndpoint for QuorumLeasesClient {
                     .send_req(Some(&ApiRequest::Req {
                         id: req_id,
                         cmd: read_cmd,
+                        checksum: None,
                     }))?;
                 return self.recv_reply().await;
             }
@@ -1283,7 +1355,7 @@ impl GenericEndpoint for QuorumLeasesClient {
         self.id
     }
 
-    fn population(&self) -> u8 {
+    fn population(&self) -> u16 {
         self.population
     }
 
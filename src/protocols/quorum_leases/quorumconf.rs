@@ -3,19 +3,79 @@
 use super::*;
 
 use crate::server::LeaseNotice;
+use crate::utils::key_to_inty;
 
 // QuorumLeasesReplica quorum leaseholder roles configuration logic
 impl QuorumLeasesReplica {
-    /// Convenience method for getting the (only) leaseholders bitmap.
+    /// Union of leaseholders of all key ranges in the config.
+    pub(super) fn conf_grantees(
+        qlease_conf: &RespondersConf,
+        population: u16,
+    ) -> Bitmap {
+        let mut grantees = Bitmap::new(population, false);
+        for (_, _, (responders, _)) in qlease_conf.responders.iter() {
+            for (r, flag) in responders.iter() {
+                if flag {
+                    grantees.set(r, true).unwrap();
+                }
+            }
+        }
+        grantees
+    }
+
+    /// Convenience method for getting the leaseholders bitmap of all ranges,
+    /// which is the set of peers quorum leases are granted to.
     #[inline]
-    pub(super) fn qlease_grantees(&self) -> &Bitmap {
-        self.qlease_conf.get_responders_by_idx(&()).unwrap()
+    pub(super) fn qlease_grantees(&self) -> Bitmap {
+        Self::conf_grantees(&self.qlease_conf, self.population)
+    }
+
+    /// Leaseholders responsible for a key: those of its range, or those of
+    /// all ranges if the key cannot be range-partitioned.
+    pub(super) fn key_grantees(
+        qlease_conf: &RespondersConf,
+        population: u16,
+        key: &String,
+    ) -> Bitmap {
+        match qlease_conf.get_responders_by_key(key) {
+            Some((responders, _)) => responders.clone(),
+            None => Self::conf_grantees(qlease_conf, population),
+        }
+    }
+
+    /// Leaseholders whose acks a request batch must gather before commit:
+    /// those responsible for any key it writes, or all of them if it carries
+    /// a config change.
+    pub(super) fn batch_grantees(
+        qlease_conf: &RespondersConf,
+        population: u16,
+        reqs: &ReqBatch,
+    ) -> Result<Bitmap, SummersetError> {
+        let mut grantees = Bitmap::new(population, false);
+        for (_, req) in reqs {
+            match req {
+                ApiRequest::Req {
+                    cmd: Command::Put { key, .. } | Command::Cas { key, .. },
+                    ..
+                } => {
+                    grantees.union(&Self::key_grantees(
+                        qlease_conf,
+                        population,
+                        key,
+                    ))?;
+                }
+                ApiRequest::Conf { .. } => {
+                    return Ok(Self::conf_grantees(qlease_conf, population));
+                }
+                _ => {}
+            }
+        }
+        Ok(grantees)
     }
 
-    /// Checks if a configuration change delta is valid.
-    // NOTE: currently only supports conf change that overwrites the full
-    //       range of keys
-    pub(super) fn is_valid_delta(delta: &ConfChange, population: u8) -> bool {
+    /// Checks if a configuration change delta is valid. A delta may overwrite
+    /// the full range of keys, or a range of keys in format `k<number>`.
+    pub(super) fn is_valid_delta(delta: &ConfChange, population: u16) -> bool {
         if delta.reset {
             pf_warn!("explicit conf reset not supported yet");
             return false;
@@ -28,12 +88,16 @@ impl QuorumLeasesReplica {
         }
 
         if let Some(responders) = &delta.responders {
-            if let Some((_, _)) = &delta.range {
-                pf_warn!("key-ranged conf change not supported yet");
-                false
-            } else {
-                responders.size() == population
+            if let Some((start, end)) = &delta.range {
+                match (key_to_inty(start), key_to_inty(end)) {
+                    (Ok(start), Ok(end)) if start <= end => {}
+                    _ => {
+                        pf_warn!("invalid conf key range {}-{}", start, end);
+                        return false;
+                    }
+                }
             }
+            responders.size() == population
         } else {
             true
         }
@@ -49,9 +113,11 @@ impl QuorumLeasesReplica {
             qlease_conf.set_leader(leader);
         }
         if let Some(responders) = delta.responders {
-            // NOTE: currently only supports conf change that overwrites the
-            //       full range of keys
-            qlease_conf.set_responders(None, responders, Some(()))?;
+            qlease_conf.set_responders(
+                delta.range.as_ref(),
+                responders,
+                None,
+            )?;
         }
         Ok(())
     }
@@ -98,7 +164,7 @@ impl QuorumLeasesReplica {
                 // to apply; first ensure revocation of all the existing
                 // quorum leases
                 debug_assert!(self.commit_bar > self.qlease_ver as usize);
-                let peers = self.qlease_grantees().clone();
+                let peers = self.qlease_grantees();
                 self.qlease_manager.add_notice(
                     self.qlease_num,
                     LeaseNotice::DoRevoke {
@@ -111,10 +177,10 @@ impl QuorumLeasesReplica {
                     }
                 }
 
-                // then apply the new config (currently assuming bluntly
-                // applying to full range)
+                // then apply the new config
                 Self::apply_conf_delta(delta, &mut self.qlease_conf)?;
                 self.qlease_ver = self.commit_bar as ConfNum;
+                self.external_api.set_conf_epoch(self.qlease_ver);
                 self.qlease_num = self.commit_bar as LeaseNum;
 
                 if self.is_leader() {
@@ -164,7 +230,7 @@ impl QuorumLeasesReplica {
     ) {
         for (_, req) in reqs {
             if let ApiRequest::Req {
-                cmd: Command::Put { key, .. },
+                cmd: Command::Put { key, .. } | Command::Cas { key, .. },
                 ..
             } = req
             {
@@ -201,14 +267,22 @@ impl QuorumLeasesReplica {
                     // instance committed, return the latest value for the key
                     // in batch
                     for (_, req) in inst.reqs.iter().rev() {
-                        if let ApiRequest::Req {
-                            cmd: Command::Put { key: k, value },
-                            ..
-                        } = req
-                        {
-                            if k == key {
+                        match req {
+                            ApiRequest::Req {
+                                cmd: Command::Put { key: k, value },
+                                ..
+                            } if k == key => {
                                 return Ok(Some((slot, Some(value.clone()))));
                             }
+                            ApiRequest::Req {
+                                cmd: Command::Cas { key: k, .. },
+                                ..
+                            } if k == key => {
+                                // outcome of a compare-and-swap is only known
+                                // after execution; play safe
+                                return Ok(Some((slot, None)));
+                            }
+                            _ => {}
                         }
                     }
                     logged_err!(
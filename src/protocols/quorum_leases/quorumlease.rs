@@ -6,7 +6,8 @@ use crate::server::{LeaseAction, LeaseNum};
 
 // QuorumLeasesReplica quorum lease-related actions logic
 impl QuorumLeasesReplica {
-    /// Checks if I'm a majority-leased local reader.
+    /// Checks if I'm a majority-leased local reader (for the keys I hold
+    /// leases on).
     #[inline]
     pub(super) fn is_local_reader(&self) -> Result<bool, SummersetError> {
         Ok((self.qlease_grantees().get(self.id)?
@@ -19,27 +20,31 @@ impl QuorumLeasesReplica {
 
     /// The commit condition check. Besides requiring an AcceptReply quorum
     /// size of at least majority, it also requires that replies from all
-    /// possible grantees have been received.
+    /// possible grantees holding leases on keys written by `reqs` have been
+    /// received.
     pub(super) fn commit_condition(
         leader_bk: &LeaderBookkeeping,
-        quorum_cnt: u8,
+        reqs: &ReqBatch,
+        qlease_conf: &RespondersConf,
+        quorum_cnt: u16,
     ) -> Result<bool, SummersetError> {
         if leader_bk.accept_acks.count() < quorum_cnt {
             return Ok(false);
         }
 
+        let affected = Self::batch_grantees(
+            qlease_conf,
+            leader_bk.accept_acks.size(),
+            reqs,
+        )?;
         for grant_set in leader_bk.accept_grant_sets.values() {
-            for grantee in
-                grant_set.iter().filter_map(
-                    |(p, flag)| {
-                        if flag {
-                            Some(p)
-                        } else {
-                            None
-                        }
-                    },
-                )
-            {
+            for grantee in grant_set.iter().filter_map(|(p, flag)| {
+                if flag && affected.get(p).unwrap_or(false) {
+                    Some(p)
+                } else {
+                    None
+                }
+            }) {
                 if !leader_bk.accept_acks.get(grantee)? {
                     return Ok(false);
                 }
@@ -164,13 +164,14 @@ impl QuorumLeasesReplica {
                     break;
                 }
                 _ => {
-                    return logged_err!("unexpected log result type");
+                    return logged_err!(Protocol; "unexpected log result type");
                 }
             }
         }
 
         // tell manager about read lease roles if changed
         if self.qlease_ver > 0 {
+            self.external_api.set_conf_epoch(self.qlease_ver);
             self.control_hub.send_ctrl(CtrlMsg::RespondersConf {
                 conf_num: self.qlease_ver,
                 new_conf: self.qlease_conf.clone(),
@@ -200,7 +201,9 @@ impl QuorumLeasesReplica {
             }
             Ok(())
         } else {
-            logged_err!("unexpected log result type or failed truncate")
+            logged_err!(
+                Protocol; "unexpected log result type or failed truncate"
+            )
         }
     }
 }
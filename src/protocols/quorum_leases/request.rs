@@ -19,15 +19,18 @@ impl QuorumLeasesReplica {
         req_batch: &mut ReqBatch,
     ) -> Result<(), SummersetError> {
         let mut strip_read_only = false;
+        let mut strip_leased = false;
+        let mut strip_scans = false;
 
         if self.is_stable_leader() {
             // conditions of majority-leased stable leader met, can reply
-            // read-only commands directly back to clients by simply using
-            // the last committed value
+            // read-only commands, including scans, directly back to clients
+            // by simply using the last committed value
             for (client, req) in req_batch.iter() {
                 if let ApiRequest::Req {
                     id: req_id,
-                    cmd: Command::Get { key },
+                    cmd: cmd @ (Command::Get { .. } | Command::Scan { .. }),
+                    ..
                 } = req
                 {
                     // has to use the `do_sync_cmd()` API
@@ -35,7 +38,7 @@ impl QuorumLeasesReplica {
                         .state_machine
                         .do_sync_cmd(
                             Self::make_ro_command_id(*client, *req_id),
-                            Command::Get { key: key.clone() },
+                            cmd.clone(),
                         )
                         .await?;
                     for (old_id, old_result) in old_results {
@@ -55,18 +58,33 @@ impl QuorumLeasesReplica {
                         *self.node_cnts_stats.get_mut(&self.id).unwrap() += 1;
                     }
 
-                    strip_read_only = true;
+                    if cmd.scan_range().is_some() {
+                        strip_scans = true;
+                    } else {
+                        strip_read_only = true;
+                    }
                 }
             }
         } else if self.is_local_reader()? {
             // conditions of majority-leased local reader met, can reply
-            // read-only commands directly back to clients
+            // read-only commands on keys I hold leases on directly back to
+            // clients; reads on other keys take the normal path
             for (client, req) in req_batch.iter() {
                 if let ApiRequest::Req {
                     id: req_id,
                     cmd: Command::Get { key },
+                    ..
                 } = req
                 {
+                    if !Self::key_grantees(
+                        &self.qlease_conf,
+                        self.population,
+                        key,
+                    )
+                    .get(self.id)?
+                    {
+                        continue;
+                    }
                     let (api_reply, is_retry) = match self
                         .inspect_highest_slot(key)?
                     {
@@ -117,7 +135,7 @@ impl QuorumLeasesReplica {
                         *self.node_cnts_stats.get_mut(&self.id).unwrap() += 1;
                     }
 
-                    strip_read_only = true;
+                    strip_leased = true;
                 }
             }
         } else if !self.is_leader() || self.bal_prepared == 0 {
@@ -127,6 +145,7 @@ impl QuorumLeasesReplica {
                 if let ApiRequest::Req {
                     id: req_id,
                     cmd: Command::Get { key },
+                    ..
                 } = req
                 {
                     self.external_api.send_reply(
@@ -150,6 +169,21 @@ impl QuorumLeasesReplica {
 
         if strip_read_only {
             req_batch.retain(|(_, req)| req.read_only().is_none());
+        } else if strip_leased {
+            let (qlease_conf, population) =
+                (&self.qlease_conf, self.population);
+            req_batch.retain(|(_, req)| match req {
+                ApiRequest::Req {
+                    cmd: Command::Get { key },
+                    ..
+                } => !Self::key_grantees(qlease_conf, population, key)
+                    .get(self.id)
+                    .unwrap_or(false),
+                _ => true,
+            });
+        }
+        if strip_scans {
+            req_batch.retain(|(_, req)| req.scan_range().is_none());
         }
         Ok(())
     }
@@ -1,53 +1,98 @@
 //! QuorumLeases -- snapshotting & GC.
 
 use std::cmp;
-use std::collections::HashMap;
 
 use super::*;
 
 use crate::manager::CtrlMsg;
-use crate::server::{ApiRequest, LogAction, LogResult};
+use crate::server::{LogAction, LogResult};
 use crate::utils::SummersetError;
 
 // QuorumLeasesReplica snapshotting & GC logic
 impl QuorumLeasesReplica {
-    /// Dump new key-value pairs to snapshot file.
-    async fn snapshot_dump_kv_pairs(
-        &mut self,
-        new_start_slot: usize,
-    ) -> Result<(), SummersetError> {
-        // collect all key-value pairs put up to exec_bar
-        let mut pairs = HashMap::new();
-        for slot in self.start_slot..new_start_slot {
-            let inst = &self.insts[slot - self.start_slot];
-            for (_, req) in inst.reqs.clone() {
-                if let ApiRequest::Req {
-                    cmd: Command::Put { key, value },
-                    ..
-                } = req
-                {
-                    pairs.insert(key, value);
-                }
-            }
+    /// Dump an image of the state machine to the end of snapshot file.
+    /// Returns the slot it is taken at: the image reflects exactly the
+    /// commands of all slots before it.
+    async fn snapshot_dump_state(&mut self) -> Result<usize, SummersetError> {
+        // handle results of all commands submitted before taking the image,
+        // so that exec_bar then stands exactly where the image is taken
+        let unacked = self.state_machine.num_unacked();
+        let image = self.state_machine.take_snapshot().await?;
+        for _ in 0..unacked {
+            let (cmd_id, cmd_result) = self.state_machine.get_result().await?;
+            self.handle_cmd_result(cmd_id, cmd_result).await?;
         }
 
-        // write the collection to snapshot file
+        // write the image to snapshot file
         if let LogResult::Append { now_size } = self
             .snapshot_hub
             .do_sync_action(
                 0, // using 0 as dummy log action ID
                 LogAction::Append {
-                    entry: SnapEntry::KVPairSet { pairs },
+                    entry: SnapEntry::StateImage {
+                        image: image.to_vec(),
+                    },
                     sync: self.config.logger_sync,
                 },
             )
             .await?
             .1
+        {
+            self.snap_offset = now_size;
+            Ok(self.exec_bar)
+        } else {
+            logged_err!(Protocol; "unexpected log result type")
+        }
+    }
+
+    /// Discard the entries between the slot info head and given offset in
+    /// snapshot file, i.e., those of the previous image.
+    async fn snapshot_discard_old(
+        &mut self,
+        offset: usize,
+    ) -> Result<(), SummersetError> {
+        let head_len = match self
+            .snapshot_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Read { offset: 0 },
+            )
+            .await?
+            .1
+        {
+            LogResult::Read {
+                entry: Some(SnapEntry::SlotInfo { .. }),
+                end_offset,
+            } => end_offset,
+            _ => {
+                return logged_err!(Protocol; "unexpected log result type");
+            }
+        };
+        if offset <= head_len {
+            return Ok(()); // nothing to discard
+        }
+
+        if let LogResult::Discard {
+            offset_ok: true,
+            now_size,
+        } = self
+            .snapshot_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Discard {
+                    offset,
+                    keep: head_len,
+                },
+            )
+            .await?
+            .1
         {
             self.snap_offset = now_size;
             Ok(())
         } else {
-            logged_err!("unexpected log result type")
+            logged_err!(
+                Protocol; "unexpected log result type or failed discard"
+            )
         }
     }
 
@@ -91,7 +136,7 @@ impl QuorumLeasesReplica {
                 self.wal_offset = now_size;
             } else {
                 return logged_err!(
-                    "unexpected log result type or failed discard"
+                    Protocol; "unexpected log result type or failed discard"
                 );
             }
         }
@@ -130,25 +175,27 @@ impl QuorumLeasesReplica {
         );
         debug_assert!(self.exec_bar >= self.start_slot);
 
-        let new_start_slot = cmp::min(self.snap_bar, self.exec_bar);
-        if new_start_slot == self.start_slot {
+        if cmp::min(self.snap_bar, self.exec_bar) == self.start_slot {
             return Ok(());
         }
 
         // NOTE: broadcast heartbeats here to appease peers
         self.bcast_heartbeats().await?;
 
-        // collect and dump all Puts in executed instances
-        self.snapshot_dump_kv_pairs(new_start_slot).await?;
+        // dump an image of the state machine after the previous one
+        let old_offset = self.snap_offset;
+        let snap_start = self.snapshot_dump_state().await?;
+        let new_start_slot = cmp::min(self.snap_bar, snap_start);
 
-        // write new slot info entry to the head of snapshot
+        // write new slot info entry to the head of snapshot, then discard the
+        // previous image
         match self
             .snapshot_hub
             .do_sync_action(
                 0, // using 0 as dummy log action ID
                 LogAction::Write {
                     entry: SnapEntry::SlotInfo {
-                        start_slot: new_start_slot,
+                        start_slot: snap_start,
                     },
                     offset: 0,
                     sync: self.config.logger_sync,
@@ -162,10 +209,11 @@ impl QuorumLeasesReplica {
             } => {}
             _ => {
                 return logged_err!(
-                    "unexpected log result type or failed write"
+                    Protocol; "unexpected log result type or failed write"
                 );
             }
         }
+        self.snapshot_discard_old(old_offset).await?;
 
         // update start_slot and discard all in-memory log instances up to exec_bar
         self.insts.drain(0..(new_start_slot - self.start_slot));
@@ -216,7 +264,7 @@ impl QuorumLeasesReplica {
                 self.exec_bar = start_slot;
                 self.snap_bar = start_slot;
 
-                // repeatedly apply key-value pairs
+                // restore the state image recorded
                 loop {
                     match self
                         .snapshot_hub
@@ -230,18 +278,12 @@ impl QuorumLeasesReplica {
                         .1
                     {
                         LogResult::Read {
-                            entry: Some(SnapEntry::KVPairSet { pairs }),
+                            entry: Some(SnapEntry::StateImage { image }),
                             end_offset,
                         } => {
-                            // execute Put commands on state machine
-                            for (key, value) in pairs {
-                                self.state_machine
-                                    .do_sync_cmd(
-                                        0, // using 0 as dummy command ID
-                                        Command::Put { key, value },
-                                    )
-                                    .await?;
-                            }
+                            self.state_machine
+                                .restore_snapshot(image.into())
+                                .await?;
                             // update snapshot file offset
                             self.snap_offset = end_offset;
                         }
@@ -250,7 +292,9 @@ impl QuorumLeasesReplica {
                             break;
                         }
                         _ => {
-                            return logged_err!("unexpected log result type");
+                            return logged_err!(
+                                Protocol; "unexpected log result type"
+                            );
                         }
                     }
                 }
@@ -292,12 +336,14 @@ impl QuorumLeasesReplica {
                     self.snap_offset = now_size;
                     Ok(())
                 } else {
-                    logged_err!("unexpected log result type or failed write")
+                    logged_err!(
+                        Protocol; "unexpected log result type or failed write"
+                    )
                 }
             }
 
             _ => {
-                logged_err!("unexpected log result type")
+                logged_err!(Protocol; "unexpected log result type")
             }
         }
     }
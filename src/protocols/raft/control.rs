@@ -114,6 +114,21 @@ impl RaftReplica {
                 Ok(None)
             }
 
+            CtrlMsg::TransferLeader { target } => {
+                self.handle_ctrl_transfer_leader(target)?;
+                Ok(None)
+            }
+
+            CtrlMsg::Reconfigure { add, remove } => {
+                self.handle_ctrl_reconfigure(add, remove)?;
+                Ok(None)
+            }
+
+            CtrlMsg::ExpireClients { clients } => {
+                self.external_api.expire_clients(&clients)?;
+                Ok(None)
+            }
+
             _ => Ok(None), // ignore all other types
         }
     }
@@ -5,10 +5,32 @@ use std::cmp;
 use super::*;
 
 use crate::server::{LogActionId, LogResult};
-use crate::utils::SummersetError;
+use crate::utils::{Bitmap, SummersetError};
 
 // RaftReplica durable logging
 impl RaftReplica {
+    /// Collects followers whose outgoing queues are congested, if skipping
+    /// them still leaves enough followers to form a quorum with me.
+    /// Otherwise returns an empty set.
+    fn congested_followers(&mut self) -> Result<Bitmap, SummersetError> {
+        let mut congested = Bitmap::new(self.population, false);
+        if self.config.peer_queue_high == 0 {
+            return Ok(congested);
+        }
+        for peer in 0..self.population {
+            if peer != self.id && self.transport_hub.peer_congested(peer)? {
+                congested.set(peer, true)?;
+            }
+        }
+        if !self
+            .conf
+            .is_quorum(|p| p == self.id || !congested.get(p).unwrap_or(true))
+        {
+            return Ok(Bitmap::new(self.population, false));
+        }
+        Ok(congested)
+    }
+
     /// Handler of leader append logging result chan recv.
     async fn handle_logged_leader_append(
         &mut self,
@@ -25,9 +47,14 @@ impl RaftReplica {
         );
         debug_assert_eq!(slot, slot_e);
 
-        // broadcast AppendEntries messages to followers
+        // broadcast AppendEntries messages to followers, holding off the
+        // congested ones; their entries go out on a later trigger
+        let skipped = self.congested_followers()?;
         for peer in 0..self.population {
-            if peer == self.id || self.try_next_slot[&peer] < 1 {
+            if peer == self.id
+                || self.try_next_slot[&peer] < 1
+                || skipped.get(peer)?
+            {
                 continue;
             }
 
@@ -154,7 +181,9 @@ impl RaftReplica {
             debug_assert!(now_size > self.log_offset);
             self.log_offset = now_size;
         } else {
-            return logged_err!("unexpected log result type: {:?}", log_result);
+            return logged_err!(
+                Protocol; "unexpected log result type: {:?}", log_result
+            );
         }
 
         match entry_type {
@@ -163,7 +192,9 @@ impl RaftReplica {
                 self.handle_logged_leader_append(slot, slot_e).await
             }
             _ => {
-                logged_err!("unexpected log entry type: {:?}", entry_type)
+                logged_err!(
+                    Protocol; "unexpected log entry type: {:?}", entry_type
+                )
             }
         }
     }
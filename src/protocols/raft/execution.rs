@@ -39,7 +39,7 @@ impl RaftReplica {
                 );
             }
         } else {
-            return logged_err!("unexpected API request type");
+            return logged_err!(Protocol; "unexpected API request type");
         }
 
         // if all commands in this entry have been executed, update last_exec
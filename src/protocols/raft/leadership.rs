@@ -5,8 +5,10 @@ use std::collections::HashSet;
 
 use super::*;
 
-use crate::manager::CtrlMsg;
-use crate::server::{LogAction, LogResult, ReplicaId};
+use crate::manager::{CtrlMsg, ServerStatus};
+use crate::server::{
+    entry_digest, DebugState, LogAction, LogResult, ReplicaId, ResourceUsage,
+};
 use crate::utils::SummersetError;
 
 // RaftReplica leader election timeout logic
@@ -23,6 +25,8 @@ impl RaftReplica {
             self.curr_term = term;
             self.voted_for = None;
             self.votes_granted.clear();
+            self.pre_votes = None;
+            self.transfer = None;
 
             // refresh heartbeat hearing timer
             self.leader = Some(peer);
@@ -53,7 +57,7 @@ impl RaftReplica {
             {
             } else {
                 return logged_err!(
-                    "unexpected log result type or failed write"
+                    Protocol; "unexpected log result type or failed write"
                 );
             }
 
@@ -73,19 +77,71 @@ impl RaftReplica {
     }
 
     /// If current leader is not me but times out, becomes a candidate and
-    /// starts the election procedure.
+    /// starts the election procedure. With PreVote enabled, a PreVote phase
+    /// goes first, unless `forced` (e.g., on a leadership transfer).
     pub(super) async fn become_a_candidate(
         &mut self,
         timeout_source: ReplicaId,
+        forced: bool,
     ) -> Result<(), SummersetError> {
         if self.role != Role::Follower
             || self.leader.as_ref().is_some_and(|&l| l != timeout_source)
             || self.config.disallow_step_up
+            || !self.conf.contains(self.id)
         {
             return Ok(());
         }
 
+        if self.config.pre_vote && !forced {
+            self.start_pre_vote(timeout_source)
+        } else {
+            self.start_election().await
+        }
+    }
+
+    /// Starts a PreVote phase for my next term, asking peers whether they
+    /// would vote for me. My term is left untouched until a majority would.
+    fn start_pre_vote(
+        &mut self,
+        timeout_source: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        let term = self.curr_term + 1;
+        self.pre_votes = Some((term, HashSet::from([self.id])));
+        pf_info!("starting pre-vote for term {}...", term);
+
+        // re-arm the timer that timed out, so that another round follows if
+        // this one does not gather enough pre-votes
+        if !self.config.disable_hb_timer {
+            self.heartbeater.kickoff_hear_timer(Some(timeout_source))?;
+        }
+
+        // send PreVoteRequest messages to all other peers on the control lane
+        let last_slot = self.start_slot + self.log.len() - 1;
+        debug_assert!(last_slot >= self.start_slot);
+        let last_term = self.log[last_slot - self.start_slot].term;
+        self.transport_hub.bcast_ctrl_msg(
+            PeerMsg::PreVoteRequest {
+                term,
+                last_slot,
+                last_term,
+            },
+            None,
+        )?;
+        pf_trace!(
+            "broadcast PreVoteRequest with term {} last {} term {}",
+            term,
+            last_slot,
+            last_term
+        );
+        Ok(())
+    }
+
+    /// Becomes a candidate of my next term and requests votes from peers.
+    pub(super) async fn start_election(
+        &mut self,
+    ) -> Result<(), SummersetError> {
         self.role = Role::Candidate;
+        self.pre_votes = None;
 
         // increment current term and vote for myself
         self.curr_term += 1;
@@ -96,11 +152,11 @@ impl RaftReplica {
         // reset election timeout timer
         self.heard_heartbeat(self.id, self.curr_term).await?;
 
-        // send RequestVote messages to all other peers
+        // send RequestVote messages to all other peers on the control lane
         let last_slot = self.start_slot + self.log.len() - 1;
         debug_assert!(last_slot >= self.start_slot);
         let last_term = self.log[last_slot - self.start_slot].term;
-        self.transport_hub.bcast_msg(
+        self.transport_hub.bcast_ctrl_msg(
             PeerMsg::RequestVote {
                 term: self.curr_term,
                 last_slot,
@@ -136,7 +192,9 @@ impl RaftReplica {
         } = result
         {
         } else {
-            return logged_err!("unexpected log result type or failed write");
+            return logged_err!(
+                Protocol; "unexpected log result type or failed write"
+            );
         }
 
         Ok(())
@@ -151,6 +209,7 @@ impl RaftReplica {
         self.heartbeater.set_sending(true);
         self.control_hub
             .send_ctrl(CtrlMsg::LeaderStatus { step_up: true })?;
+        self.external_api.notify_leader_change(self.id)?;
 
         // clear peers' heartbeat reply counters, and broadcast a heartbeat now
         self.heartbeater.clear_reply_cnts(None)?;
@@ -176,6 +235,63 @@ impl RaftReplica {
             slot.external = true;
         }
 
+        // finish a membership change whose joint entry got committed by the
+        // previous leader
+        if self.conf.joint.is_some() && self.conf_slot <= self.last_commit {
+            self.advance_conf()?;
+        }
+
+        Ok(())
+    }
+
+    /// Handler of TransferLeader control message. Stops taking in client
+    /// requests, then sends the target a TimeoutNow as soon as its log has
+    /// caught up with mine, so that it wins an election right away. The
+    /// transfer is aborted if it does not complete within an election
+    /// timeout.
+    pub(super) fn handle_ctrl_transfer_leader(
+        &mut self,
+        target: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        pf_warn!("server told to transfer leadership to {}", target);
+
+        if self.role != Role::Leader
+            || self.transfer.is_some()
+            || target == self.id
+            || target >= self.population
+        {
+            pf_warn!("cannot transfer leadership now, rejected");
+            self.control_hub.send_ctrl(CtrlMsg::TransferReject)?;
+            return Ok(());
+        }
+
+        let deadline = Instant::now()
+            + Duration::from_millis(self.config.hb_hear_timeout_max);
+        self.transfer = Some((target, deadline));
+        pf_info!("transferring leadership to {}", target);
+        self.send_timeout_now_if_caught_up()
+    }
+
+    /// Sends TimeoutNow to the target of my ongoing leadership transfer, if
+    /// any, if its log has caught up with mine.
+    pub(super) fn send_timeout_now_if_caught_up(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        let Some((target, _)) = self.transfer else {
+            return Ok(());
+        };
+        let last_slot = self.start_slot + self.log.len() - 1;
+        if self.match_slot[&target] < last_slot {
+            return Ok(());
+        }
+
+        self.transport_hub.send_ctrl_msg(
+            PeerMsg::TimeoutNow {
+                term: self.curr_term,
+            },
+            target,
+        )?;
+        pf_trace!("sent TimeoutNow -> {} term {}", target, self.curr_term);
         Ok(())
     }
 
@@ -183,6 +299,15 @@ impl RaftReplica {
     pub(super) async fn bcast_heartbeats(
         &mut self,
     ) -> Result<(), SummersetError> {
+        // abort my ongoing leadership transfer if taking too long
+        if let Some((target, deadline)) = self.transfer {
+            if Instant::now() >= deadline {
+                pf_warn!("leadership transfer to {} timed out", target);
+                self.transfer = None;
+                self.control_hub.send_ctrl(CtrlMsg::TransferReject)?;
+            }
+        }
+
         for peer in 0..self.population {
             if peer == self.id {
                 continue;
@@ -226,6 +351,12 @@ impl RaftReplica {
         if peer != self.id {
             // update the peer's reply cnt and its liveness status accordingly
             self.heartbeater.update_heard_cnt(peer)?;
+
+            // the leader I know of is alive, no need for my PreVote phase
+            if self.leader == Some(peer) {
+                self.leader_heard = Instant::now();
+                self.pre_votes = None;
+            }
         }
 
         // reset hearing timer
@@ -233,6 +364,43 @@ impl RaftReplica {
             self.heartbeater.kickoff_hear_timer(Some(peer))?;
         }
 
+        // expose my protocol state to debug queries
+        self.external_api.update_debug_state(DebugState {
+            is_leader: self.role == Role::Leader,
+            leader: self.leader,
+            ballot: self.curr_term,
+            commit_bar: self.last_commit,
+            exec_bar: self.last_exec,
+            start_slot: self.start_slot,
+            log_len: self.log.len(),
+            peers_alive: self.heartbeater.peer_alive().clone(),
+        });
+
+        // verify truncated entries whose slots have committed since
+        let (start_slot, log) = (self.start_slot, &self.log);
+        self.divergence.verify(self.last_commit + 1, |slot| {
+            log.get(slot.checked_sub(start_slot)?)
+                .and_then(|entry| entry_digest(&entry.reqs).ok())
+        });
+
+        // periodically report my status to the manager
+        self.control_hub.report_status(ServerStatus {
+            ballot: self.curr_term,
+            commit_bar: self.last_commit,
+            exec_bar: self.last_exec,
+            peers_alive: self.heartbeater.peer_alive().clone(),
+            perf_models: HashMap::new(),
+            hot_keys: self.state_machine.hot_keys(),
+            queue_depths: vec![
+                self.external_api.queue_depth_report(),
+                self.storage_hub.queue_depth_report(),
+                self.transport_hub.queue_depth_report(),
+                self.state_machine.queue_depth_report(),
+            ],
+            resources: ResourceUsage::default(),
+            divergence_repaired: self.divergence.repaired(),
+        })?;
+
         // pf_trace!("heard heartbeat <- {} term {}", peer, term);
         Ok(())
     }
@@ -0,0 +1,244 @@
+//! Raft -- membership changes through joint consensus.
+//!
+//! A change first appends a joint configuration entry holding both the old
+//! and the new voting members, under which elections and commitment need
+//! majorities of both, then a final entry holding only the new members once
+//! the joint one commits. Every replica follows the latest configuration
+//! entry in its log, committed or not. Replicas outside the configuration
+//! keep learning the log, but neither count towards quorums nor start
+//! elections. Members are always drawn from the fixed population; servers
+//! beyond it cannot join, as the transport hub, heartbeater, and clients are
+//! all set up for that population. Reference:
+//!   - <https://web.stanford.edu/~ouster/cgi-bin/papers/OngaroPhD.pdf>
+
+use super::*;
+
+use crate::manager::CtrlMsg;
+use crate::server::{ApiRequest, ConfChange, LogAction};
+use crate::utils::{Bitmap, SummersetError};
+
+/// Client ID used as the source of configuration entries in request batches.
+const CONF_CLIENT: ClientId = ClientId::MAX;
+
+impl VotingConf {
+    /// Configuration with the full population as voting members.
+    pub(super) fn full(population: u16) -> Self {
+        VotingConf {
+            members: Bitmap::new(population, true),
+            joint: None,
+        }
+    }
+
+    /// Is the given replica a voting member, on either side if joint?
+    pub(super) fn contains(&self, peer: ReplicaId) -> bool {
+        self.members.get(peer).unwrap_or(false)
+            || self
+                .joint
+                .as_ref()
+                .is_some_and(|joint| joint.get(peer).unwrap_or(false))
+    }
+
+    /// Do the replicas for which `acked` holds form a quorum, i.e., a
+    /// majority of the members and, if joint, also of the new members?
+    pub(super) fn is_quorum(&self, acked: impl Fn(ReplicaId) -> bool) -> bool {
+        let majority = |members: &Bitmap| {
+            let cnt =
+                members.iter().filter(|&(p, flag)| flag && acked(p)).count()
+                    as u16;
+            cnt > members.count() / 2
+        };
+        majority(&self.members) && self.joint.as_ref().is_none_or(majority)
+    }
+}
+
+// RaftReplica membership changes logic
+impl RaftReplica {
+    /// Compose the request batch value of a configuration entry, carrying
+    /// each side as the responders bitmap of a conf change.
+    fn make_conf_entry(conf: &VotingConf) -> ReqBatch {
+        let side = |members: &Bitmap| {
+            (
+                CONF_CLIENT,
+                ApiRequest::Conf {
+                    id: 0,
+                    delta: ConfChange {
+                        responders: Some(members.clone()),
+                        ..Default::default()
+                    },
+                },
+            )
+        };
+        let mut reqs = vec![side(&conf.members)];
+        if let Some(joint) = &conf.joint {
+            reqs.push(side(joint));
+        }
+        reqs
+    }
+
+    /// If the request batch is a configuration entry, returns its
+    /// configuration.
+    pub(super) fn parse_conf_entry(reqs: &ReqBatch) -> Option<VotingConf> {
+        if reqs.is_empty() || reqs.len() > 2 {
+            return None;
+        }
+        let mut sides = Vec::with_capacity(2);
+        for (client, req) in reqs {
+            match req {
+                ApiRequest::Conf {
+                    delta:
+                        ConfChange {
+                            responders: Some(members),
+                            ..
+                        },
+                    ..
+                } if *client == CONF_CLIENT => sides.push(members.clone()),
+                _ => return None,
+            }
+        }
+        let joint = if sides.len() == 2 { sides.pop() } else { None };
+        Some(VotingConf {
+            members: sides.pop()?,
+            joint,
+        })
+    }
+
+    /// Finds the configuration in effect after my log up to `slot`, along
+    /// with the slot of its entry (0 if it comes from the snapshot).
+    pub(super) fn conf_at(&self, slot: usize) -> (usize, VotingConf) {
+        for s in (self.start_slot..=slot).rev() {
+            let reqs = &self.log[s - self.start_slot].reqs;
+            if let Some(conf) = Self::parse_conf_entry(reqs) {
+                return (s, conf);
+            }
+        }
+        (0, self.base_conf.clone())
+    }
+
+    /// Re-derives the configuration in effect from my log, e.g., after some
+    /// entries got truncated.
+    pub(super) fn refresh_conf(&mut self) {
+        let last_slot = self.start_slot + self.log.len() - 1;
+        (self.conf_slot, self.conf) = self.conf_at(last_slot);
+    }
+
+    /// Switches to the configuration of the entry just appended at slot, if
+    /// it is a configuration entry.
+    pub(super) fn note_appended(&mut self, slot: usize) {
+        let reqs = &self.log[slot - self.start_slot].reqs;
+        if let Some(conf) = Self::parse_conf_entry(reqs) {
+            pf_info!("conf now {:?} at slot {}", conf, slot);
+            self.conf = conf;
+            self.conf_slot = slot;
+        }
+    }
+
+    /// Appends a configuration entry to my log as leader, switching to it
+    /// right away.
+    fn append_conf_entry(
+        &mut self,
+        conf: VotingConf,
+    ) -> Result<(), SummersetError> {
+        let entry = LogEntry {
+            term: self.curr_term,
+            reqs: Self::make_conf_entry(&conf),
+            external: false,
+            log_offset: 0,
+        };
+        let slot = self.start_slot + self.log.len();
+        self.log.push(entry.clone());
+        self.note_appended(slot);
+
+        self.storage_hub.submit_action(
+            Self::make_log_action_id(slot, slot, Role::Leader),
+            LogAction::Append {
+                entry: DurEntry::LogEntry { entry },
+                sync: self.config.logger_sync,
+            },
+        )?;
+        pf_trace!("submitted leader append log action for slot {}", slot);
+        Ok(())
+    }
+
+    /// Moves my latest configuration forward as leader once its entry is
+    /// committed: a joint one is followed by the final one, and a final one
+    /// is reported to the manager, after which I step down if I am no longer
+    /// a voting member.
+    pub(super) fn advance_conf(&mut self) -> Result<(), SummersetError> {
+        if let Some(new_members) = self.conf.joint.clone() {
+            return self.append_conf_entry(VotingConf {
+                members: new_members,
+                joint: None,
+            });
+        }
+
+        self.control_hub.send_ctrl(CtrlMsg::MembersConf {
+            members: self.conf.members.clone(),
+            slot: self.conf_slot,
+        })?;
+        if !self.conf.contains(self.id) {
+            // peers stop hearing from me and elect a leader among the new
+            // members, which I cannot become
+            self.role = Role::Follower;
+            self.leader = None;
+            self.heartbeater.set_sending(false);
+            self.control_hub
+                .send_ctrl(CtrlMsg::LeaderStatus { step_up: false })?;
+            pf_info!("no longer a leader...");
+        }
+        Ok(())
+    }
+
+    /// Checks if my latest configuration entry got committed as leader by
+    /// the commit index moving from `old_commit` to `new_commit`.
+    pub(super) fn check_conf_committed(
+        &mut self,
+        old_commit: usize,
+        new_commit: usize,
+    ) -> Result<(), SummersetError> {
+        if self.role == Role::Leader
+            && self.conf_slot > old_commit
+            && self.conf_slot <= new_commit
+        {
+            self.advance_conf()?;
+        }
+        Ok(())
+    }
+
+    /// Handler of Reconfigure control message. Starts a joint-consensus
+    /// change to the current members plus `add` minus `remove`, unless
+    /// another change is still in progress.
+    pub(super) fn handle_ctrl_reconfigure(
+        &mut self,
+        add: Bitmap,
+        remove: Bitmap,
+    ) -> Result<(), SummersetError> {
+        pf_warn!("server told to reconfigure +{:?} -{:?}", add, remove);
+
+        let mut new_members = self.conf.members.clone();
+        if add.size() == self.population && remove.size() == self.population {
+            for p in 0..self.population {
+                if remove.get(p)? {
+                    new_members.set(p, false)?;
+                } else if add.get(p)? {
+                    new_members.set(p, true)?;
+                }
+            }
+        }
+        if self.role != Role::Leader
+            || self.transfer.is_some()
+            || self.conf.joint.is_some()
+            || self.conf_slot > self.last_commit
+            || new_members.count() == 0
+            || new_members == self.conf.members
+        {
+            pf_warn!("cannot reconfigure now, rejected");
+            self.control_hub.send_ctrl(CtrlMsg::ReconfigureReject)?;
+            return Ok(());
+        }
+
+        self.append_conf_entry(VotingConf {
+            members: self.conf.members.clone(),
+            joint: Some(new_members),
+        })
+    }
+}
@@ -4,7 +4,9 @@ use std::cmp;
 
 use super::*;
 
-use crate::server::{ApiRequest, LogAction, LogResult, ReplicaId};
+use crate::server::{
+    entry_digest, ApiRequest, LogAction, LogResult, ReplicaId,
+};
 use crate::utils::SummersetError;
 
 // RaftReplica peer-peer messages handling
@@ -95,6 +97,7 @@ impl RaftReplica {
         // update my knowledge of who's the current leader, and reset election
         // timeout timer
         self.leader = Some(leader);
+        self.external_api.notify_leader_change(leader)?;
         self.heard_heartbeat(leader, term).await?;
 
         // check if any existing entry conflicts with a new one in `entries`.
@@ -131,11 +134,24 @@ impl RaftReplica {
                     self.log_offset = cut_offset;
                 } else {
                     return logged_err!(
+                        Protocol;
                         "unexpected log result type or failed truncate"
                     );
                 }
-                // truncate in-mem log as well
+                // truncate in-mem log as well, recording what got cut off
+                // for verification once the leader's entries commit
+                for (s, entry) in
+                    self.log.iter().enumerate().skip(slot - self.start_slot)
+                {
+                    self.divergence.record(
+                        s + self.start_slot,
+                        entry_digest(&entry.reqs)?,
+                    );
+                }
                 self.log.truncate(slot - self.start_slot);
+                if slot <= self.conf_slot {
+                    self.refresh_conf();
+                }
                 first_new = slot;
                 break;
             }
@@ -152,6 +168,7 @@ impl RaftReplica {
             entry.log_offset = 0;
 
             self.log.push(entry.clone());
+            self.note_appended(slot);
             self.storage_hub.submit_action(
                 Self::make_log_action_id(
                     slot,
@@ -193,10 +210,11 @@ impl RaftReplica {
             for slot in (self.last_commit + 1)..=new_commit {
                 let entry = &self.log[slot - self.start_slot];
                 for (cmd_idx, (_, req)) in entry.reqs.iter().enumerate() {
-                    if let ApiRequest::Req { cmd, .. } = req {
-                        self.state_machine.submit_cmd(
+                    if let ApiRequest::Req { cmd, checksum, .. } = req {
+                        self.state_machine.submit_checked_cmd(
                             Self::make_command_id(slot, cmd_idx),
                             cmd.clone(),
+                            *checksum,
                         )?;
                     }
                 }
@@ -250,6 +268,9 @@ impl RaftReplica {
                 *self.try_next_slot.get_mut(&peer).unwrap() = end_slot + 1;
             }
             *self.match_slot.get_mut(&peer).unwrap() = end_slot;
+            if self.transfer.is_some_and(|(target, _)| target == peer) {
+                self.send_timeout_now_if_caught_up()?;
+            }
 
             // since we updated some match_slot here, check if any additional
             // entries are now considered committed
@@ -262,12 +283,11 @@ impl RaftReplica {
                     continue; // cannot decide commit using non-latest term
                 }
 
-                let match_cnt = 1 + self
-                    .match_slot
-                    .values()
-                    .filter(|&&s| s >= slot)
-                    .count() as u8;
-                if match_cnt >= self.quorum_cnt {
+                let acked = |p: ReplicaId| {
+                    p == self.id
+                        || self.match_slot.get(&p).is_some_and(|&s| s >= slot)
+                };
+                if self.conf.is_quorum(acked) {
                     // quorum size reached, set new_commit to here
                     new_commit = slot;
                 }
@@ -276,11 +296,19 @@ impl RaftReplica {
             // submit newly committed commands, if any, for execution
             for slot in (self.last_commit + 1)..=new_commit {
                 let entry = &self.log[slot - self.start_slot];
-                for (cmd_idx, (_, req)) in entry.reqs.iter().enumerate() {
-                    if let ApiRequest::Req { cmd, .. } = req {
-                        self.state_machine.submit_cmd(
+                for (cmd_idx, (client, req)) in entry.reqs.iter().enumerate() {
+                    if let ApiRequest::Req {
+                        id, cmd, checksum, ..
+                    } = req
+                    {
+                        if entry.external {
+                            // early acknowledgement if client asked
+                            self.external_api.ack_committed(*client, *id)?;
+                        }
+                        self.state_machine.submit_checked_cmd(
                             Self::make_command_id(slot, cmd_idx),
                             cmd.clone(),
+                            *checksum,
                         )?;
                     }
                 }
@@ -291,7 +319,9 @@ impl RaftReplica {
                 );
             }
 
+            let old_commit = self.last_commit;
             self.last_commit = new_commit;
+            self.check_conf_committed(old_commit, new_commit)?;
 
             // also check if any additional entries are safe to snapshot
             for slot in (self.last_snap + 1)..=end_slot {
@@ -299,7 +329,7 @@ impl RaftReplica {
                     .match_slot
                     .values()
                     .filter(|&&s| s >= slot)
-                    .count() as u8;
+                    .count() as u16;
                 if match_cnt == self.population {
                     // all servers have durably stored this entry
                     self.last_snap = slot;
@@ -404,7 +434,7 @@ impl RaftReplica {
 
         // if the given term is smaller than mine, reply false
         if term < self.curr_term {
-            self.transport_hub.send_msg(
+            self.transport_hub.send_ctrl_msg(
                 PeerMsg::RequestVoteReply {
                     term: self.curr_term,
                     granted: false,
@@ -423,11 +453,8 @@ impl RaftReplica {
         // candidate's log is as up-to-date as mine, grant vote
         #[allow(clippy::collapsible_if)]
         if self.voted_for.is_none() || (self.voted_for.unwrap() == candidate) {
-            if last_term >= self.log.last().unwrap().term
-                || (last_term == self.curr_term
-                    && last_slot + 1 >= self.start_slot + self.log.len())
-            {
-                self.transport_hub.send_msg(
+            if self.log_up_to_date(last_slot, last_term) {
+                self.transport_hub.send_ctrl_msg(
                     PeerMsg::RequestVoteReply {
                         term: self.curr_term,
                         granted: true,
@@ -470,7 +497,7 @@ impl RaftReplica {
                 {
                 } else {
                     return logged_err!(
-                        "unexpected log result type or failed write"
+                        Protocol; "unexpected log result type or failed write"
                     );
                 }
             }
@@ -479,6 +506,14 @@ impl RaftReplica {
         Ok(())
     }
 
+    /// Is a candidate's log with given last slot and term as up-to-date as
+    /// mine?
+    fn log_up_to_date(&self, last_slot: usize, last_term: Term) -> bool {
+        last_term >= self.log.last().unwrap().term
+            || (last_term == self.curr_term
+                && last_slot + 1 >= self.start_slot + self.log.len())
+    }
+
     /// Handler of RequestVote reply from peer.
     async fn handle_msg_request_vote_reply(
         &mut self,
@@ -500,13 +535,112 @@ impl RaftReplica {
         self.votes_granted.insert(peer);
 
         // if a majority of servers have voted for me, become the leader
-        if self.votes_granted.len() as u8 >= self.quorum_cnt {
+        if self.conf.is_quorum(|p| self.votes_granted.contains(&p)) {
             self.become_the_leader().await?;
         }
 
         Ok(())
     }
 
+    /// Handler of PreVoteRequest message from a would-be candidate. Grants
+    /// the pre-vote if I would vote for it in the term it asks for and have
+    /// not heard from a live leader recently. Changes none of my state.
+    fn handle_msg_pre_vote_request(
+        &mut self,
+        candidate: ReplicaId,
+        term: Term,
+        last_slot: usize,
+        last_term: Term,
+    ) -> Result<(), SummersetError> {
+        pf_trace!(
+            "received PreVoteRequest <- {} with term {} last {} term {}",
+            candidate,
+            term,
+            last_slot,
+            last_term
+        );
+
+        let leader_alive = self.role == Role::Leader
+            || (self.leader.is_some()
+                && self.leader_heard.elapsed()
+                    < Duration::from_millis(self.config.hb_hear_timeout_min));
+        let granted = term > self.curr_term
+            && !leader_alive
+            && self.log_up_to_date(last_slot, last_term);
+        let reply_term = if granted { term } else { self.curr_term };
+        self.transport_hub.send_ctrl_msg(
+            PeerMsg::PreVoteReply {
+                term: reply_term,
+                granted,
+            },
+            candidate,
+        )?;
+        pf_trace!(
+            "sent PreVoteReply -> {} term {} {}",
+            candidate,
+            reply_term,
+            if granted { "granted" } else { "false" }
+        );
+        Ok(())
+    }
+
+    /// Handler of PreVoteRequest reply from peer. Starts the actual election
+    /// once a majority granted me pre-votes for the term I asked for.
+    async fn handle_msg_pre_vote_reply(
+        &mut self,
+        peer: ReplicaId,
+        term: Term,
+        granted: bool,
+    ) -> Result<(), SummersetError> {
+        pf_trace!(
+            "received PreVoteReply <- {} with term {} {}",
+            peer,
+            term,
+            if granted { "granted" } else { "false" }
+        );
+        if !granted {
+            // a rejection carries the peer's term, which may be newer
+            self.check_term(peer, term).await?;
+            return Ok(());
+        }
+
+        let Some((pre_term, pre_votes)) = self.pre_votes.as_mut() else {
+            return Ok(());
+        };
+        if term != *pre_term || self.role != Role::Follower {
+            return Ok(());
+        }
+
+        // bookkeep this pre-vote; if a majority granted, start the election
+        pre_votes.insert(peer);
+        if self.conf.is_quorum(|p| pre_votes.contains(&p)) {
+            pf_info!("won pre-vote for term {}", term);
+            self.start_election().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Handler of TimeoutNow message from leader, which is transferring
+    /// leadership to me.
+    async fn handle_msg_timeout_now(
+        &mut self,
+        peer: ReplicaId,
+        term: Term,
+    ) -> Result<(), SummersetError> {
+        pf_trace!("received TimeoutNow <- {} with term {}", peer, term);
+        if self.check_term(peer, term).await?
+            || term < self.curr_term
+            || self.leader != Some(peer)
+        {
+            return Ok(());
+        }
+
+        // start an election right away without waiting for the timeout
+        pf_info!("leadership transfer from {} requested", peer);
+        self.become_a_candidate(peer, true).await
+    }
+
     /// Synthesized handler of receiving message from peer.
     pub(super) async fn handle_msg_recv(
         &mut self,
@@ -555,6 +689,18 @@ impl RaftReplica {
                 self.handle_msg_request_vote_reply(peer, term, granted)
                     .await
             }
+            PeerMsg::PreVoteRequest {
+                term,
+                last_slot,
+                last_term,
+            } => self
+                .handle_msg_pre_vote_request(peer, term, last_slot, last_term),
+            PeerMsg::PreVoteReply { term, granted } => {
+                self.handle_msg_pre_vote_reply(peer, term, granted).await
+            }
+            PeerMsg::TimeoutNow { term } => {
+                self.handle_msg_timeout_now(peer, term).await
+            }
         }
     }
 }
@@ -9,6 +9,7 @@ mod control;
 mod durability;
 mod execution;
 mod leadership;
+mod membership;
 mod messages;
 mod recovery;
 mod request;
@@ -22,11 +23,11 @@ use crate::client::{ClientApiStub, ClientCtrlStub, ClientId, GenericEndpoint};
 use crate::manager::{CtrlMsg, CtrlReply, CtrlRequest};
 use crate::protocols::SmrProtocol;
 use crate::server::{
-    ApiReply, ApiRequest, CommandId, ControlHub, ExternalApi, GenericReplica,
-    HeartbeatEvent, Heartbeater, LogActionId, ReplicaId, StateMachine,
-    StorageHub, TransportHub,
+    ApiReply, ApiRequest, CommandId, ControlHub, DivergenceTracker,
+    ExternalApi, GenericReplica, HeartbeatEvent, Heartbeater, LogActionId,
+    ReplicaId, StateMachine, StorageHub, TransportHub,
 };
-use crate::utils::SummersetError;
+use crate::utils::{Bitmap, SummersetError};
 
 use async_trait::async_trait;
 
@@ -35,7 +36,7 @@ use get_size::GetSize;
 use serde::{Deserialize, Serialize};
 
 use tokio::sync::watch;
-use tokio::time::{self, Duration, Interval, MissedTickBehavior};
+use tokio::time::{self, Duration, Instant, Interval, MissedTickBehavior};
 
 /// Configuration parameters struct.
 #[derive(Debug, Clone, Deserialize)]
@@ -46,6 +47,19 @@ pub struct ReplicaConfigRaft {
     /// Client request batching maximum batch size.
     pub max_batch_size: usize,
 
+    /// Number of completed replies cached per client for collapsing retried
+    /// requests by idempotency key; 0 means collapsing disabled.
+    pub idem_cache_size: usize,
+
+    /// Idle timeout in secs after which a client session that sent no request
+    /// is closed and its state reclaimed; 0 means never.
+    pub session_idle_s: u64,
+
+    /// Quota in KiB of approximate key-value bytes stored per namespace (the
+    /// key prefix before the first '/'), beyond which Puts are rejected; 0
+    /// means unlimited.
+    pub ns_quota_kb: usize,
+
     /// Path to backing log file.
     pub backer_path: String,
 
@@ -66,6 +80,11 @@ pub struct ReplicaConfigRaft {
     /// Disallow me to ever attempt stepping up as leader?
     pub disallow_step_up: bool,
 
+    /// Run a PreVote phase before each election, incrementing my term only
+    /// if a majority would vote for me, so that a replica rejoining from a
+    /// partition does not disrupt a stable leader?
+    pub pre_vote: bool,
+
     /// Path to snapshot file.
     pub snapshot_path: String,
 
@@ -76,6 +95,14 @@ pub struct ReplicaConfigRaft {
     /// Maximum chunk size (in slots) of any bulk messages.
     pub msg_chunk_size: usize,
 
+    /// Outgoing queue depth (in messages) at which a follower is considered
+    /// congested and skipped for new entries, as long as the others can
+    /// still form a quorum. 0 means never skipping.
+    pub peer_queue_high: usize,
+    /// Outgoing queue depth at which a congested follower is considered
+    /// drained again.
+    pub peer_queue_low: usize,
+
     // [for benchmarking purposes only]
     /// Simulate local read lease implementation?
     pub sim_read_lease: bool,
@@ -87,6 +114,9 @@ impl Default for ReplicaConfigRaft {
         ReplicaConfigRaft {
             batch_interval_ms: 1,
             max_batch_size: 5000,
+            idem_cache_size: 0,
+            session_idle_s: 0,
+            ns_quota_kb: 0,
             backer_path: "/tmp/summerset.raft.wal".into(),
             logger_sync: false,
             hb_hear_timeout_min: 1200,
@@ -94,9 +124,12 @@ impl Default for ReplicaConfigRaft {
             hb_send_interval_ms: 20,
             disable_hb_timer: false,
             disallow_step_up: false,
+            pre_vote: false,
             snapshot_path: "/tmp/summerset.raft.snap".into(),
             snapshot_interval_s: 0,
             msg_chunk_size: 10,
+            peer_queue_high: 0,
+            peer_queue_low: 0,
             sim_read_lease: false,
         }
     }
@@ -132,6 +165,16 @@ struct LogEntry {
     log_offset: usize,
 }
 
+/// Voting configuration of the cluster.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, GetSize)]
+struct VotingConf {
+    /// Voting members.
+    members: Bitmap,
+
+    /// New voting members if in the middle of a joint-consensus change.
+    joint: Option<Bitmap>,
+}
+
 /// Stable storage log entry type.
 //
 // NOTE: Raft makes the persistent log exactly mirror the in-memory log, so
@@ -187,8 +230,14 @@ enum SnapEntry {
         start_slot: usize,
     },
 
-    /// Set of key-value pairs to apply to the state.
-    KVPairSet { pairs: HashMap<String, String> },
+    /// Image of the state machine taken by `take_snapshot()`, reflecting
+    /// exactly the commands of all entries up to last_exec, which may be
+    /// beyond the start slot.
+    StateImage { last_exec: usize, image: Vec<u8> },
+
+    /// Voting configuration in effect at the start slot; the latest one in
+    /// file wins.
+    Conf { conf: VotingConf },
 }
 
 /// Peer-peer message type.
@@ -224,6 +273,22 @@ enum PeerMsg {
 
     /// RequestVote reply from follower to leader.
     RequestVoteReply { term: Term, granted: bool },
+
+    /// PreVoteRequest from a would-be candidate to peers, asking whether they
+    /// would vote for it in `term` without anyone changing state yet.
+    PreVoteRequest {
+        term: Term,
+        last_slot: usize,
+        last_term: Term,
+    },
+
+    /// PreVoteRequest reply to the would-be candidate. Carries the term asked
+    /// for if granted, or else the replier's current term.
+    PreVoteReply { term: Term, granted: bool },
+
+    /// TimeoutNow from leader to the target of a leadership transfer, once
+    /// its log is up to date, telling it to start an election right away.
+    TimeoutNow { term: Term },
 }
 
 /// Replica role type.
@@ -242,10 +307,18 @@ pub(crate) struct RaftReplica {
     id: ReplicaId,
 
     /// Total number of replicas in cluster.
-    population: u8,
+    population: u16,
+
+    /// Voting configuration in effect, i.e., that of the latest
+    /// configuration entry in my log.
+    conf: VotingConf,
 
-    /// Majority quorum size.
-    quorum_cnt: u8,
+    /// Slot of the latest configuration entry in my log; 0 if the
+    /// configuration in effect comes from the snapshot.
+    conf_slot: usize,
+
+    /// Voting configuration in effect at the start of my log.
+    base_conf: VotingConf,
 
     /// Configuration parameters struct.
     config: ReplicaConfigRaft,
@@ -292,6 +365,17 @@ pub(crate) struct RaftReplica {
     /// Replica IDs that voted for me in current election.
     votes_granted: HashSet<ReplicaId>,
 
+    /// Term and replica IDs that granted me a pre-vote in my ongoing PreVote
+    /// phase, if any.
+    pre_votes: Option<(Term, HashSet<ReplicaId>)>,
+
+    /// Time I last heard from the leader I know of.
+    leader_heard: Instant,
+
+    /// Target of my ongoing leadership transfer and the deadline to abort it
+    /// at, if any.
+    transfer: Option<(ReplicaId, Instant)>,
+
     /// In-memory log of entries. Slot 0 is a dummy entry to make indexing happy.
     log: Vec<LogEntry>,
 
@@ -333,6 +417,9 @@ pub(crate) struct RaftReplica {
 
     /// Current durable snapshot file offset.
     snap_offset: usize,
+
+    /// Tracker of my log entries truncated in favor of the leader's.
+    divergence: DivergenceTracker,
 }
 
 // RaftReplica common helpers
@@ -395,16 +482,22 @@ impl GenericReplica for RaftReplica {
         // connect to the cluster manager and get assigned a server ID
         let mut control_hub = ControlHub::new_and_setup(manager).await?;
         let id = control_hub.me;
+        let (api_addr, p2p_addr) =
+            control_hub.resolve_addrs(api_addr, p2p_addr)?;
         let population = control_hub.population;
 
         // parse protocol-specific configs
         let config = parsed_config!(config_str => ReplicaConfigRaft;
                                     batch_interval_ms, max_batch_size,
+                                    idem_cache_size, session_idle_s,
+                                    ns_quota_kb,
                                     backer_path, logger_sync,
                                     hb_hear_timeout_min, hb_hear_timeout_max,
                                     hb_send_interval_ms, disable_hb_timer,
-                                    disallow_step_up, snapshot_path,
+                                    disallow_step_up, pre_vote,
+                                    snapshot_path,
                                     snapshot_interval_s, msg_chunk_size,
+                                    peer_queue_high, peer_queue_low,
                                     sim_read_lease)?;
         if config.batch_interval_ms == 0 {
             return logged_err!(
@@ -438,12 +531,15 @@ impl GenericReplica for RaftReplica {
         }
 
         // setup state machine module
-        let state_machine = StateMachine::new_and_setup(id).await?;
+        let state_machine =
+            StateMachine::new_and_setup(id, config.ns_quota_kb * 1024).await?;
+        control_hub.inspect_keys(&state_machine);
 
         // setup storage hub module
         let storage_hub =
             StorageHub::new_and_setup(id, Path::new(&config.backer_path))
                 .await?;
+        control_hub.monitor_wal(Path::new(&config.backer_path));
 
         // setup heartbeat management module
         let heartbeater = Heartbeater::new_and_setup(
@@ -458,10 +554,18 @@ impl GenericReplica for RaftReplica {
         let mut transport_hub = TransportHub::new_and_setup(
             id,
             population,
+            SmrProtocol::Raft,
             p2p_addr,
+            false,
             HashMap::new(),
         )
         .await?;
+        if config.peer_queue_high > 0 {
+            transport_hub.set_queue_watermarks(
+                config.peer_queue_high,
+                config.peer_queue_low,
+            )?;
+        }
 
         // ask for the list of peers to proactively connect to. Do this after
         // transport hub has been set up, so that I will be able to accept
@@ -477,7 +581,7 @@ impl GenericReplica for RaftReplica {
         {
             to_peers
         } else {
-            return logged_err!("unexpected ctrl msg type received");
+            return logged_err!(Protocol; "unexpected ctrl msg type received");
         };
 
         // proactively connect to some peers, then wait for all population
@@ -493,13 +597,17 @@ impl GenericReplica for RaftReplica {
                 .await?;
 
         // setup external API module, ready to take in client requests
-        let external_api = ExternalApi::new_and_setup(
+        let mut external_api = ExternalApi::new_and_setup(
             id,
             api_addr,
             Duration::from_millis(config.batch_interval_ms),
             config.max_batch_size,
+            config.idem_cache_size,
+            Duration::from_secs(config.session_idle_s),
         )
         .await?;
+        external_api.stamp_replies(&state_machine);
+        external_api.enable_commit_acks();
 
         let mut snapshot_interval = time::interval(Duration::from_secs(
             if config.snapshot_interval_s > 0 {
@@ -513,7 +621,9 @@ impl GenericReplica for RaftReplica {
         Ok(RaftReplica {
             id,
             population,
-            quorum_cnt: (population / 2) + 1,
+            conf: VotingConf::full(population),
+            conf_slot: 0,
+            base_conf: VotingConf::full(population),
             config,
             _api_addr: api_addr,
             _p2p_addr: p2p_addr,
@@ -529,6 +639,9 @@ impl GenericReplica for RaftReplica {
             curr_term: 0,
             voted_for: None,
             votes_granted: HashSet::new(),
+            pre_votes: None,
+            leader_heard: Instant::now(),
+            transfer: None,
             log: vec![],
             start_slot: 0,
             snapshot_interval,
@@ -547,6 +660,7 @@ impl GenericReplica for RaftReplica {
             log_offset: 0,
             log_meta_end: 0,
             snap_offset: 0,
+            divergence: DivergenceTracker::new(),
         })
     }
 
@@ -628,7 +742,7 @@ impl GenericReplica for RaftReplica {
                     }
                     match hb_event.unwrap() {
                         HeartbeatEvent::HearTimeout { peer } => {
-                            if let Err(e) = self.become_a_candidate(peer).await {
+                            if let Err(e) = self.become_a_candidate(peer, false).await {
                                 pf_error!("error becoming a candidate: {}", e);
                             }
                         }
@@ -684,7 +798,7 @@ impl GenericReplica for RaftReplica {
         self.id
     }
 
-    fn population(&self) -> u8 {
+    fn population(&self) -> u16 {
         self.population
     }
 }
@@ -709,7 +823,7 @@ pub(crate) struct RaftClient {
     id: ClientId,
 
     /// Number of servers in the cluster.
-    population: u8,
+    population: u16,
 
     /// Configuration parameters struct.
     _config: ClientConfigRaft,
@@ -795,7 +909,7 @@ impl GenericEndpoint for RaftClient {
                 }
                 Ok(())
             }
-            _ => logged_err!("unexpected reply type received"),
+            _ => logged_err!(Protocol; "unexpected reply type received"),
         }
     }
 
@@ -854,6 +968,22 @@ impl GenericEndpoint for RaftClient {
                 .recv_reply()
                 .await?;
 
+            if let ApiReply::LeaderChange { leader } = reply {
+                // notified of a new leader; switch over to it, or keep waiting if
+                // already talking to it
+                if leader == self.server_id
+                    || !self.servers.contains_key(&leader)
+                {
+                    return self.recv_reply().await;
+                }
+                self.server_id = leader;
+                pf_debug!(
+                    "notified of new leader {} '{}'",
+                    leader,
+                    self.servers[&leader]
+                );
+            }
+
             if let ApiReply::Reply {
                 ref result,
                 ref redirect,
@@ -886,7 +1016,7 @@ impl GenericEndpoint for RaftClient {
         self.id
     }
 
-    fn population(&self) -> u8 {
+    fn population(&self) -> u16 {
         self.population
     }
 
@@ -61,7 +61,9 @@ impl RaftReplica {
                             break;
                         }
                         _ => {
-                            return logged_err!("unexpected log result type");
+                            return logged_err!(
+                                Protocol; "unexpected log result type"
+                            );
                         }
                     }
                 }
@@ -89,7 +91,7 @@ impl RaftReplica {
                     self.log_meta_end = now_size;
                 } else {
                     return logged_err!(
-                        "unexpected log result type or failed write"
+                        Protocol; "unexpected log result type or failed write"
                     );
                 }
                 // ... and push a 0-th dummy entry into in-mem log
@@ -121,14 +123,17 @@ impl RaftReplica {
                     self.log_offset = now_size;
                 } else {
                     return logged_err!(
-                        "unexpected log result type or failed write"
+                        Protocol; "unexpected log result type or failed write"
                     );
                 }
             }
 
-            _ => return logged_err!("unexpected log result type"),
+            _ => return logged_err!(Protocol; "unexpected log result type"),
         }
 
+        // follow the latest configuration entry in recovered log, if any
+        self.refresh_conf();
+
         // do an extra Truncate to remove partial entry at the end if any
         debug_assert!(self.log_offset >= self.log_meta_end);
         if let LogResult::Truncate {
@@ -154,7 +159,9 @@ impl RaftReplica {
             }
             Ok(())
         } else {
-            logged_err!("unexpected log result type or failed truncate")
+            logged_err!(
+                Protocol; "unexpected log result type or failed truncate"
+            )
         }
     }
 }
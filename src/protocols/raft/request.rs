@@ -16,13 +16,16 @@ impl RaftReplica {
         debug_assert!(batch_size > 0);
         pf_debug!("got request batch of size {}", batch_size);
 
-        // if I'm not a leader, ignore client requests
-        if self.role != Role::Leader {
+        // if I'm not a leader, or if I'm handing leadership over, ignore
+        // client requests
+        if self.role != Role::Leader || self.transfer.is_some() {
             for (client, req) in req_batch {
                 if let ApiRequest::Req { id: req_id, .. } = req {
-                    // tell the client to try on known leader or just the
-                    // next ID replica
-                    let target = if let Some(peer) = self.leader {
+                    // tell the client to try on the transfer target, known
+                    // leader, or just the next ID replica
+                    let target = if let Some((target, _)) = self.transfer {
+                        target
+                    } else if let Some(peer) = self.leader {
                         peer
                     } else {
                         (self.id + 1) % self.population
@@ -49,6 +52,7 @@ impl RaftReplica {
                 if let ApiRequest::Req {
                     id: req_id,
                     cmd: Command::Get { .. },
+                    ..
                 } = req
                 {
                     self.external_api.send_reply(
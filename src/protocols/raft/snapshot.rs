@@ -1,44 +1,113 @@
 //! Raft -- snapshotting & GC.
 
 use std::cmp;
-use std::collections::HashMap;
 
 use super::*;
 
 use crate::manager::CtrlMsg;
-use crate::server::{ApiRequest, Command, LogAction, LogResult};
+use crate::server::{LogAction, LogResult};
 use crate::utils::SummersetError;
 
 // RaftReplica snapshotting & GC logic
 impl RaftReplica {
-    /// Dump new key-value pairs to snapshot file.
-    async fn snapshot_dump_kv_pairs(
+    /// Dump an image of the state machine to the end of snapshot file.
+    async fn snapshot_dump_state(&mut self) -> Result<(), SummersetError> {
+        // handle results of all commands submitted before taking the image,
+        // so that last_exec then stands exactly where the image is taken
+        let unacked = self.state_machine.num_unacked();
+        let image = self.state_machine.take_snapshot().await?;
+        for _ in 0..unacked {
+            let (cmd_id, cmd_result) = self.state_machine.get_result().await?;
+            self.handle_cmd_result(cmd_id, cmd_result).await?;
+        }
+
+        // write the image to snapshot file
+        if let LogResult::Append { now_size } = self
+            .snapshot_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Append {
+                    entry: SnapEntry::StateImage {
+                        last_exec: self.last_exec,
+                        image: image.to_vec(),
+                    },
+                    sync: self.config.logger_sync,
+                },
+            )
+            .await?
+            .1
+        {
+            self.snap_offset = now_size;
+            Ok(())
+        } else {
+            logged_err!(Protocol; "unexpected log result type")
+        }
+    }
+
+    /// Dump the voting configuration in effect before the new start slot to
+    /// snapshot file, as the entry holding it may get compacted. Returns the
+    /// configuration dumped.
+    async fn snapshot_dump_conf(
         &mut self,
         new_start_slot: usize,
+    ) -> Result<VotingConf, SummersetError> {
+        let (_, conf) = self.conf_at(new_start_slot - 1);
+        if let LogResult::Append { now_size } = self
+            .snapshot_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Append {
+                    entry: SnapEntry::Conf { conf: conf.clone() },
+                    sync: self.config.logger_sync,
+                },
+            )
+            .await?
+            .1
+        {
+            self.snap_offset = now_size;
+            Ok(conf)
+        } else {
+            logged_err!(Protocol; "unexpected log result type")
+        }
+    }
+
+    /// Discard the entries between the slot info head and given offset in
+    /// snapshot file, i.e., those of the previous image.
+    async fn snapshot_discard_old(
+        &mut self,
+        offset: usize,
     ) -> Result<(), SummersetError> {
-        // collect all key-value pairs put up to exec_bar
-        let mut pairs = HashMap::new();
-        for slot in self.start_slot..new_start_slot {
-            let entry = &self.log[slot - self.start_slot];
-            for (_, req) in entry.reqs.clone() {
-                if let ApiRequest::Req {
-                    cmd: Command::Put { key, value },
-                    ..
-                } = req
-                {
-                    pairs.insert(key, value);
-                }
+        let head_len = match self
+            .snapshot_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Read { offset: 0 },
+            )
+            .await?
+            .1
+        {
+            LogResult::Read {
+                entry: Some(SnapEntry::SlotInfo { .. }),
+                end_offset,
+            } => end_offset,
+            _ => {
+                return logged_err!(Protocol; "unexpected log result type");
             }
+        };
+        if offset <= head_len {
+            return Ok(()); // nothing to discard
         }
 
-        // write the collection to snapshot file
-        if let LogResult::Append { now_size } = self
+        if let LogResult::Discard {
+            offset_ok: true,
+            now_size,
+        } = self
             .snapshot_hub
             .do_sync_action(
                 0, // using 0 as dummy log action ID
-                LogAction::Append {
-                    entry: SnapEntry::KVPairSet { pairs },
-                    sync: self.config.logger_sync,
+                LogAction::Discard {
+                    offset,
+                    keep: head_len,
                 },
             )
             .await?
@@ -47,7 +116,9 @@ impl RaftReplica {
             self.snap_offset = now_size;
             Ok(())
         } else {
-            logged_err!("unexpected log result type")
+            logged_err!(
+                Protocol; "unexpected log result type or failed discard"
+            )
         }
     }
 
@@ -94,7 +165,7 @@ impl RaftReplica {
                 self.log_offset = now_size;
             } else {
                 return logged_err!(
-                    "unexpected log result type or failed discard"
+                    Protocol; "unexpected log result type or failed discard"
                 );
             }
         }
@@ -140,14 +211,17 @@ impl RaftReplica {
             return Ok(());
         }
 
-        // collect and dump all Puts in executed entries
+        // dump an image of the state machine after the previous one
         if self.role == Role::Leader {
             // NOTE: broadcast heartbeats here to appease followers
             self.bcast_heartbeats().await?;
         }
-        self.snapshot_dump_kv_pairs(new_start_slot).await?;
+        let old_offset = self.snap_offset;
+        self.snapshot_dump_state().await?;
+        let base_conf = self.snapshot_dump_conf(new_start_slot).await?;
 
-        // write new slot info entry to the head of snapshot
+        // write new slot info entry to the head of snapshot, then discard the
+        // previous image
         match self
             .snapshot_hub
             .do_sync_action(
@@ -168,15 +242,17 @@ impl RaftReplica {
             } => {}
             _ => {
                 return logged_err!(
-                    "unexpected log result type or failed write"
+                    Protocol; "unexpected log result type or failed write"
                 );
             }
         }
+        self.snapshot_discard_old(old_offset).await?;
 
         // update start_slot and discard all in-mem log entries up to
         // new_start_slot
         self.log.drain(0..(new_start_slot - self.start_slot));
         self.start_slot = new_start_slot;
+        self.base_conf = base_conf;
 
         // discarding everything lower than start_slot in durable log
         if self.role == Role::Leader {
@@ -225,7 +301,7 @@ impl RaftReplica {
                     self.last_snap = start_slot - 1;
                 }
 
-                // repeatedly apply key-value pairs
+                // restore the state image and configuration recorded
                 loop {
                     match self
                         .snapshot_hub
@@ -239,27 +315,38 @@ impl RaftReplica {
                         .1
                     {
                         LogResult::Read {
-                            entry: Some(SnapEntry::KVPairSet { pairs }),
+                            entry:
+                                Some(SnapEntry::StateImage { last_exec, image }),
                             end_offset,
                         } => {
-                            // execute Put commands on state machine
-                            for (key, value) in pairs {
-                                self.state_machine
-                                    .do_sync_cmd(
-                                        0, // using 0 as dummy command ID
-                                        Command::Put { key, value },
-                                    )
-                                    .await?;
-                            }
+                            self.state_machine
+                                .restore_snapshot(image.into())
+                                .await?;
+                            // entries up to last_exec must not be executed
+                            // again on top of the image
+                            self.last_commit = last_exec;
+                            self.last_exec = last_exec;
                             // update snapshot file offset
                             self.snap_offset = end_offset;
                         }
+                        LogResult::Read {
+                            entry: Some(SnapEntry::Conf { conf }),
+                            end_offset,
+                        } => {
+                            // conf entries later in my log take precedence
+                            // once recovered from the WAL
+                            self.base_conf = conf.clone();
+                            self.conf = conf;
+                            self.snap_offset = end_offset;
+                        }
                         LogResult::Read { entry: None, .. } => {
                             // end of log reached
                             break;
                         }
                         _ => {
-                            return logged_err!("unexpected log result type");
+                            return logged_err!(
+                                Protocol; "unexpected log result type"
+                            );
                         }
                     }
                 }
@@ -299,12 +386,14 @@ impl RaftReplica {
                     self.snap_offset = now_size;
                     Ok(())
                 } else {
-                    logged_err!("unexpected log result type or failed write")
+                    logged_err!(
+                        Protocol; "unexpected log result type or failed write"
+                    )
                 }
             }
 
             _ => {
-                logged_err!("unexpected log result type")
+                logged_err!(Protocol; "unexpected log result type")
             }
         }
     }
@@ -25,7 +25,7 @@ impl RepNothingReplica {
             }
             _ => {
                 return logged_err!(
-                    "unexpected log result type for {}: {:?}",
+                    Protocol; "unexpected log result type for {}: {:?}",
                     inst_idx,
                     log_result
                 );
@@ -40,10 +40,11 @@ impl RepNothingReplica {
 
         // submit execution commands in order
         for (cmd_idx, (_, req)) in inst.reqs.iter().enumerate() {
-            if let ApiRequest::Req { cmd, .. } = req {
-                self.state_machine.submit_cmd(
+            if let ApiRequest::Req { cmd, checksum, .. } = req {
+                self.state_machine.submit_checked_cmd(
                     Self::make_command_id(inst_idx, cmd_idx),
                     cmd.clone(),
+                    *checksum,
                 )?;
             }
         }
@@ -54,7 +54,7 @@ impl RepNothingReplica {
                     break;
                 }
                 _ => {
-                    return logged_err!("unexpected log result type");
+                    return logged_err!(Protocol; "unexpected log result type");
                 }
             }
         }
@@ -75,7 +75,7 @@ impl RepNothingReplica {
         {
             Ok(())
         } else {
-            logged_err!("unexpected log result type")
+            logged_err!(Protocol; "unexpected log result type")
         }
     }
 }
@@ -166,10 +166,11 @@ impl RSPaxosReplica {
                     inst.status = Status::Executed;
                 } else if inst.status == Status::Committed {
                     for (cmd_idx, (_, req)) in reqs.iter().enumerate() {
-                        if let ApiRequest::Req { cmd, .. } = req {
-                            self.state_machine.submit_cmd(
+                        if let ApiRequest::Req { cmd, checksum, .. } = req {
+                            self.state_machine.submit_checked_cmd(
                                 Self::make_command_id(self.commit_bar, cmd_idx),
                                 cmd.clone(),
+                                *checksum,
                             )?;
                         }
                     }
@@ -210,7 +211,9 @@ impl RSPaxosReplica {
             // then update self.wal_offset
             self.wal_offset = now_size;
         } else {
-            return logged_err!("unexpected log result type: {:?}", log_result);
+            return logged_err!(
+                Protocol; "unexpected log result type: {:?}", log_result
+            );
         }
 
         match entry_type {
@@ -218,7 +221,9 @@ impl RSPaxosReplica {
             Status::Accepting => self.handle_logged_accept_data(slot),
             Status::Committed => self.handle_logged_commit_slot(slot),
             _ => {
-                logged_err!("unexpected log entry type: {:?}", entry_type)
+                logged_err!(
+                    Protocol; "unexpected log entry type: {:?}", entry_type
+                )
             }
         }
     }
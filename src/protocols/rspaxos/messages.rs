@@ -216,6 +216,7 @@ impl RSPaxosReplica {
                     debug_assert!(self.bal_prepared <= ballot);
                     self.bal_prepared = ballot;
 
+                    let mut accepting = vec![];
                     for (this_slot, inst) in self
                         .insts
                         .iter_mut()
@@ -291,26 +292,12 @@ impl RSPaxosReplica {
                             this_slot, ballot
                         );
 
-                        // send Accept messages to all peers
-                        for peer in 0..self.population {
-                            if peer == self.id {
-                                continue;
-                            }
-                            self.transport_hub.send_msg(
-                                PeerMsg::Accept {
-                                    slot: this_slot,
-                                    ballot,
-                                    reqs_cw: inst.reqs_cw.subset_copy(
-                                        &Bitmap::from((
-                                            self.population,
-                                            vec![peer],
-                                        )),
-                                        false,
-                                    )?,
-                                },
-                                peer,
-                            )?;
-                        }
+                        accepting.push(this_slot);
+                    }
+
+                    // send Accept messages to all peers
+                    for this_slot in accepting {
+                        self.send_accepts(this_slot, ballot)?;
                         pf_trace!(
                             "broadcast Accept messages for slot {} bal {}",
                             this_slot,
@@ -324,6 +311,101 @@ impl RSPaxosReplica {
         Ok(())
     }
 
+    /// Sends Accept messages for slot to all peers, each getting one shard
+    /// of data. If shard trains are enabled, a peer's shard gets queued in
+    /// its next train instead, which departs once full.
+    pub(super) fn send_accepts(
+        &mut self,
+        slot: usize,
+        ballot: Ballot,
+    ) -> Result<(), SummersetError> {
+        let inst = &self.insts[slot - self.start_slot];
+        let mut shards = Vec::with_capacity(self.population as usize - 1);
+        for peer in 0..self.population {
+            if peer != self.id {
+                shards.push((
+                    peer,
+                    inst.reqs_cw.subset_copy(
+                        &Bitmap::from((self.population, vec![peer])),
+                        false,
+                    )?,
+                ));
+            }
+        }
+
+        for (peer, reqs_cw) in shards {
+            if self.config.shard_train_len <= 1 {
+                self.transport_hub.send_msg(
+                    PeerMsg::Accept {
+                        slot,
+                        ballot,
+                        reqs_cw,
+                    },
+                    peer,
+                )?;
+                continue;
+            }
+
+            // a train only carries shards under the same ballot
+            if self
+                .shard_trains
+                .get(&peer)
+                .is_some_and(|(b, _)| *b != ballot)
+            {
+                self.depart_shard_train(peer)?;
+            }
+            let (_, train) = self
+                .shard_trains
+                .entry(peer)
+                .or_insert_with(|| (ballot, vec![]));
+            train.push((slot, reqs_cw));
+            if train.len() >= self.config.shard_train_len {
+                self.depart_shard_train(peer)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends out the shards queued in peer's shard train, if any.
+    fn depart_shard_train(
+        &mut self,
+        peer: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        if let Some((ballot, mut shards)) = self.shard_trains.remove(&peer) {
+            if shards.len() == 1 {
+                let (slot, reqs_cw) = shards.pop().unwrap();
+                self.transport_hub.send_msg(
+                    PeerMsg::Accept {
+                        slot,
+                        ballot,
+                        reqs_cw,
+                    },
+                    peer,
+                )?;
+            } else if !shards.is_empty() {
+                pf_trace!(
+                    "sent AcceptTrain -> {} of {} slots bal {}",
+                    peer,
+                    shards.len(),
+                    ballot
+                );
+                self.transport_hub
+                    .send_msg(PeerMsg::AcceptTrain { ballot, shards }, peer)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends out the shards queued in all peers' shard trains.
+    pub(super) fn depart_shard_trains(&mut self) -> Result<(), SummersetError> {
+        let peers: Vec<ReplicaId> = self.shard_trains.keys().copied().collect();
+        for peer in peers {
+            self.depart_shard_train(peer)?;
+        }
+        Ok(())
+    }
+
     /// Handler of Accept message from leader.
     async fn handle_msg_accept(
         &mut self,
@@ -390,6 +472,26 @@ impl RSPaxosReplica {
         Ok(())
     }
 
+    /// Handler of AcceptTrain message from leader, treated as the Accept
+    /// messages it carries in slot order.
+    async fn handle_msg_accept_train(
+        &mut self,
+        peer: ReplicaId,
+        ballot: Ballot,
+        shards: Vec<(usize, RSCodeword<ReqBatch>)>,
+    ) -> Result<(), SummersetError> {
+        pf_trace!(
+            "received AcceptTrain <- {} of {} slots bal {}",
+            peer,
+            shards.len(),
+            ballot
+        );
+        for (slot, reqs_cw) in shards {
+            self.handle_msg_accept(peer, slot, ballot, reqs_cw).await?;
+        }
+        Ok(())
+    }
+
     /// Handler of Accept reply from replica.
     pub(super) fn handle_msg_accept_reply(
         &mut self,
@@ -566,13 +668,17 @@ impl RSPaxosReplica {
                             inst.status = Status::Executed;
                         } else {
                             for (cmd_idx, (_, req)) in reqs.iter().enumerate() {
-                                if let ApiRequest::Req { cmd, .. } = req {
-                                    self.state_machine.submit_cmd(
+                                if let ApiRequest::Req {
+                                    cmd, checksum, ..
+                                } = req
+                                {
+                                    self.state_machine.submit_checked_cmd(
                                         Self::make_command_id(
                                             self.commit_bar,
                                             cmd_idx,
                                         ),
                                         cmd.clone(),
+                                        *checksum,
                                     )?;
                                 }
                             }
@@ -622,6 +728,9 @@ impl RSPaxosReplica {
                 ballot,
                 reqs_cw,
             } => self.handle_msg_accept(peer, slot, ballot, reqs_cw).await,
+            PeerMsg::AcceptTrain { ballot, shards } => {
+                self.handle_msg_accept_train(peer, ballot, shards).await
+            }
             PeerMsg::AcceptReply { slot, ballot } => {
                 self.handle_msg_accept_reply(peer, slot, ballot)
             }
@@ -46,6 +46,19 @@ pub struct ReplicaConfigRSPaxos {
     /// Client request batching maximum batch size.
     pub max_batch_size: usize,
 
+    /// Number of completed replies cached per client for collapsing retried
+    /// requests by idempotency key; 0 means collapsing disabled.
+    pub idem_cache_size: usize,
+
+    /// Idle timeout in secs after which a client session that sent no request
+    /// is closed and its state reclaimed; 0 means never.
+    pub session_idle_s: u64,
+
+    /// Quota in KiB of approximate key-value bytes stored per namespace (the
+    /// key prefix before the first '/'), beyond which Puts are rejected; 0
+    /// means unlimited.
+    pub ns_quota_kb: usize,
+
     /// Path to backing log file.
     pub backer_path: String,
 
@@ -74,11 +87,20 @@ pub struct ReplicaConfigRSPaxos {
     pub snapshot_interval_s: u64,
 
     /// Fault-tolerance level.
-    pub fault_tolerance: u8,
+    pub fault_tolerance: u16,
 
     /// Maximum chunk size (in slots) of any bulk messages.
     pub msg_chunk_size: usize,
 
+    /// Maximum number of slots whose shards destined for the same follower
+    /// are packed into one Accept message, forming a shard train. 1 means
+    /// sending every slot's shards right away.
+    pub shard_train_len: usize,
+
+    /// Maximum time in millisecs a queued shard waits for its train to fill
+    /// up before the train departs anyway.
+    pub shard_train_wait_ms: u64,
+
     // Performance simulation params (all zeros means no perf simulation):
     pub perf_storage_a: u64,
     pub perf_storage_b: u64,
@@ -96,6 +118,9 @@ impl Default for ReplicaConfigRSPaxos {
         ReplicaConfigRSPaxos {
             batch_interval_ms: 1,
             max_batch_size: 5000,
+            idem_cache_size: 0,
+            session_idle_s: 0,
+            ns_quota_kb: 0,
             backer_path: "/tmp/summerset.rs_paxos.wal".into(),
             logger_sync: false,
             hb_hear_timeout_min: 1200,
@@ -107,6 +132,8 @@ impl Default for ReplicaConfigRSPaxos {
             snapshot_interval_s: 0,
             fault_tolerance: 0,
             msg_chunk_size: 10,
+            shard_train_len: 1,
+            shard_train_wait_ms: 1,
             perf_storage_a: 0,
             perf_storage_b: 0,
             perf_network_a: 0,
@@ -134,6 +161,10 @@ enum Status {
 /// Request batch type (i.e., the "value" in Paxos).
 type ReqBatch = Vec<(ClientId, ApiRequest)>;
 
+/// Shard train type: the ballot and the (slot, shards) pairs queued for a
+/// peer to depart together in one message.
+type ShardTrain = (Ballot, Vec<(usize, RSCodeword<ReqBatch>)>);
+
 /// Leader-side bookkeeping info for each instance initiated.
 #[derive(Debug, Clone)]
 struct LeaderBookkeeping {
@@ -216,9 +247,9 @@ enum WalEntry {
 
 /// Snapshot file entry type.
 //
-// NOTE: the current implementation simply appends a squashed log at the
-//       end of the snapshot file for simplicity. In production, the snapshot
-//       file should be a bounded-sized backend, e.g., an LSM-tree.
+// NOTE: the current implementation rewrites a whole image of the state
+//       machine into the snapshot file for simplicity. In production, the
+//       snapshot file should be a bounded-sized backend, e.g., an LSM-tree.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, GetSize)]
 enum SnapEntry {
     /// Necessary slot indices to remember.
@@ -228,8 +259,9 @@ enum SnapEntry {
         start_slot: usize,
     },
 
-    /// Set of key-value pairs to apply to the state.
-    KVPairSet { pairs: HashMap<String, String> },
+    /// Image of the state machine taken by `take_snapshot()`, reflecting
+    /// exactly the commands of all slots before start_slot.
+    StateImage { image: Vec<u8> },
 }
 
 /// Peer-peer message type.
@@ -295,6 +327,13 @@ enum PeerMsg {
         /// For conservative snapshotting purpose.
         snap_bar: usize,
     },
+
+    /// Shard train from leader to a replica, carrying the replica's shards
+    /// of several slots under the same ballot as one Accept message.
+    AcceptTrain {
+        ballot: Ballot,
+        shards: Vec<(usize, RSCodeword<ReqBatch>)>,
+    },
 }
 
 /// RSPaxos server replica module.
@@ -303,10 +342,10 @@ pub(crate) struct RSPaxosReplica {
     id: ReplicaId,
 
     /// Total number of replicas in cluster.
-    population: u8,
+    population: u16,
 
     /// Majority quorum size.
-    majority: u8,
+    majority: u16,
 
     /// Configuration parameters struct.
     config: ReplicaConfigRSPaxos,
@@ -350,6 +389,13 @@ pub(crate) struct RSPaxosReplica {
     /// Timer for taking a new autonomous snapshot.
     snapshot_interval: Interval,
 
+    /// Map from peer ID -> its shards queued in the shard train departing
+    /// next, all under the same ballot.
+    shard_trains: HashMap<ReplicaId, ShardTrain>,
+
+    /// Timer for departing shard trains that have not filled up.
+    train_interval: Interval,
+
     /// Largest ballot number that a leader has sent Prepare messages in.
     bal_prep_sent: Ballot,
 
@@ -432,13 +478,13 @@ impl RSPaxosReplica {
     /// Compose a unique ballot number from base.
     #[inline]
     fn make_unique_ballot(&self, base: u64) -> Ballot {
-        ((base << 8) | ((self.id + 1) as u64)) as Ballot
+        ((base << 16) | ((self.id + 1) as u64)) as Ballot
     }
 
     /// Compose a unique ballot number greater than the given one.
     #[inline]
     fn make_greater_ballot(&self, bal: Ballot) -> Ballot {
-        self.make_unique_ballot((bal >> 8) + 1)
+        self.make_unique_ballot((bal >> 16) + 1)
     }
 
     /// Compose LogActionId from slot index & entry type.
@@ -496,17 +542,22 @@ impl GenericReplica for RSPaxosReplica {
         // connect to the cluster manager and get assigned a server ID
         let mut control_hub = ControlHub::new_and_setup(manager).await?;
         let id = control_hub.me;
+        let (api_addr, p2p_addr) =
+            control_hub.resolve_addrs(api_addr, p2p_addr)?;
         let population = control_hub.population;
 
         // parse protocol-specific configs
         let config = parsed_config!(config_str => ReplicaConfigRSPaxos;
                                     batch_interval_ms, max_batch_size,
+                                    idem_cache_size, session_idle_s,
+                                    ns_quota_kb,
                                     backer_path, logger_sync,
                                     hb_hear_timeout_min, hb_hear_timeout_max,
                                     hb_send_interval_ms, disable_hb_timer,
                                     disallow_step_up, snapshot_path,
                                     snapshot_interval_s, fault_tolerance,
-                                    msg_chunk_size, sim_read_lease,
+                                    msg_chunk_size, shard_train_len,
+                                    shard_train_wait_ms, sim_read_lease,
                                     perf_storage_a, perf_storage_b,
                                     perf_network_a, perf_network_b)?;
         if config.batch_interval_ms == 0 {
@@ -539,14 +590,29 @@ impl GenericReplica for RSPaxosReplica {
                 config.msg_chunk_size
             );
         }
+        if config.shard_train_len == 0 {
+            return logged_err!(
+                "invalid config.shard_train_len '{}'",
+                config.shard_train_len
+            );
+        }
+        if config.shard_train_wait_ms == 0 {
+            return logged_err!(
+                "invalid config.shard_train_wait_ms '{}'",
+                config.shard_train_wait_ms
+            );
+        }
 
         // setup state machine module
-        let state_machine = StateMachine::new_and_setup(id).await?;
+        let state_machine =
+            StateMachine::new_and_setup(id, config.ns_quota_kb * 1024).await?;
+        control_hub.inspect_keys(&state_machine);
 
         // setup storage hub module
         let storage_hub =
             StorageHub::new_and_setup(id, Path::new(&config.backer_path))
                 .await?;
+        control_hub.monitor_wal(Path::new(&config.backer_path));
 
         // setup heartbeat management module
         let heartbeater = Heartbeater::new_and_setup(
@@ -561,7 +627,9 @@ impl GenericReplica for RSPaxosReplica {
         let mut transport_hub = TransportHub::new_and_setup(
             id,
             population,
+            SmrProtocol::RSPaxos,
             p2p_addr,
+            false,
             HashMap::new(),
         )
         .await?;
@@ -580,7 +648,7 @@ impl GenericReplica for RSPaxosReplica {
         {
             to_peers
         } else {
-            return logged_err!("unexpected ctrl msg type received");
+            return logged_err!(Protocol; "unexpected ctrl msg type received");
         };
 
         // create a Reed-Solomon coder with num_data_shards == quorum size and
@@ -610,13 +678,16 @@ impl GenericReplica for RSPaxosReplica {
                 .await?;
 
         // setup external API module, ready to take in client requests
-        let external_api = ExternalApi::new_and_setup(
+        let mut external_api = ExternalApi::new_and_setup(
             id,
             api_addr,
             Duration::from_millis(config.batch_interval_ms),
             config.max_batch_size,
+            config.idem_cache_size,
+            Duration::from_secs(config.session_idle_s),
         )
         .await?;
+        external_api.stamp_replies(&state_machine);
 
         let mut snapshot_interval = time::interval(Duration::from_secs(
             if config.snapshot_interval_s > 0 {
@@ -627,6 +698,10 @@ impl GenericReplica for RSPaxosReplica {
         ));
         snapshot_interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
+        let mut train_interval =
+            time::interval(Duration::from_millis(config.shard_train_wait_ms));
+        train_interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
         Ok(RSPaxosReplica {
             id,
             population,
@@ -645,6 +720,8 @@ impl GenericReplica for RSPaxosReplica {
             insts: vec![],
             start_slot: 0,
             snapshot_interval,
+            shard_trains: HashMap::new(),
+            train_interval,
             bal_prep_sent: 0,
             bal_prepared: 0,
             bal_max_seen: 0,
@@ -762,6 +839,14 @@ impl GenericReplica for RSPaxosReplica {
                     }
                 },
 
+                // shard trains departure timeout
+                _ = self.train_interval.tick(), if !paused
+                                                   && !self.shard_trains.is_empty() => {
+                    if let Err(e) = self.depart_shard_trains() {
+                        pf_error!("error departing shard trains: {}", e);
+                    }
+                },
+
                 // manager control message
                 ctrl_msg = self.control_hub.recv_ctrl() => {
                     if let Err(e) = ctrl_msg {
@@ -794,7 +879,7 @@ impl GenericReplica for RSPaxosReplica {
         self.id
     }
 
-    fn population(&self) -> u8 {
+    fn population(&self) -> u16 {
         self.population
     }
 }
@@ -819,7 +904,7 @@ pub(crate) struct RSPaxosClient {
     id: ClientId,
 
     /// Number of servers in the cluster.
-    population: u8,
+    population: u16,
 
     /// Configuration parameters struct.
     _config: ClientConfigRSPaxos,
@@ -905,7 +990,7 @@ impl GenericEndpoint for RSPaxosClient {
                 }
                 Ok(())
             }
-            _ => logged_err!("unexpected reply type received"),
+            _ => logged_err!(Protocol; "unexpected reply type received"),
         }
     }
 
@@ -964,6 +1049,22 @@ impl GenericEndpoint for RSPaxosClient {
                 .recv_reply()
                 .await?;
 
+            if let ApiReply::LeaderChange { leader } = reply {
+                // notified of a new leader; switch over to it, or keep waiting if
+                // already talking to it
+                if leader == self.server_id
+                    || !self.servers.contains_key(&leader)
+                {
+                    return self.recv_reply().await;
+                }
+                self.server_id = leader;
+                pf_debug!(
+                    "notified of new leader {} '{}'",
+                    leader,
+                    self.servers[&leader]
+                );
+            }
+
             if let ApiReply::Reply {
                 ref result,
                 ref redirect,
@@ -996,7 +1097,7 @@ impl GenericEndpoint for RSPaxosClient {
         self.id
     }
 
-    fn population(&self) -> u8 {
+    fn population(&self) -> u16 {
         self.population
     }
 
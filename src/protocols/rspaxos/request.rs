@@ -49,6 +49,7 @@ impl RSPaxosReplica {
                 if let ApiRequest::Req {
                     id: req_id,
                     cmd: Command::Get { .. },
+                    ..
                 } = req
                 {
                     self.external_api.send_reply(
@@ -124,27 +125,9 @@ impl RSPaxosReplica {
         );
 
         // send Accept messages to all peers, each getting one shard of data
-        for peer in 0..self.population {
-            if peer == self.id {
-                continue;
-            }
-            self.transport_hub.send_msg(
-                PeerMsg::Accept {
-                    slot,
-                    ballot: inst.bal,
-                    reqs_cw: inst.reqs_cw.subset_copy(
-                        &Bitmap::from((self.population, vec![peer])),
-                        false,
-                    )?,
-                },
-                peer,
-            )?;
-        }
-        pf_trace!(
-            "broadcast Accept messages for slot {} bal {}",
-            slot,
-            inst.bal
-        );
+        let ballot = inst.bal;
+        self.send_accepts(slot, ballot)?;
+        pf_trace!("broadcast Accept messages for slot {} bal {}", slot, ballot);
 
         Ok(())
     }
@@ -62,7 +62,7 @@ impl SimplePushReplica {
         }
         if !inst.pending_peers.get(peer)? {
             return logged_err!(
-                "unexpected push reply from peer {} for {}",
+                Protocol; "unexpected push reply from peer {} for {}",
                 peer,
                 inst_idx
             );
@@ -80,10 +80,11 @@ impl SimplePushReplica {
         // completed as well, submit execution commands
         if inst.pending_peers.count() == 0 && inst.durable {
             for (cmd_idx, (_, req)) in inst.reqs.iter().enumerate() {
-                if let ApiRequest::Req { cmd, .. } = req {
-                    self.state_machine.submit_cmd(
+                if let ApiRequest::Req { cmd, checksum, .. } = req {
+                    self.state_machine.submit_checked_cmd(
                         Self::make_command_id(inst_idx, cmd_idx),
                         cmd.clone(),
+                        *checksum,
                     )?
                 }
             }
@@ -42,11 +42,24 @@ pub struct ReplicaConfigSimplePush {
     /// Client request batching maximum batch size.
     pub max_batch_size: usize,
 
+    /// Number of completed replies cached per client for collapsing retried
+    /// requests by idempotency key; 0 means collapsing disabled.
+    pub idem_cache_size: usize,
+
+    /// Idle timeout in secs after which a client session that sent no request
+    /// is closed and its state reclaimed; 0 means never.
+    pub session_idle_s: u64,
+
+    /// Quota in KiB of approximate key-value bytes stored per namespace (the
+    /// key prefix before the first '/'), beyond which Puts are rejected; 0
+    /// means unlimited.
+    pub ns_quota_kb: usize,
+
     /// Path to backing file.
     pub backer_path: String,
 
     /// Number of peer servers to push each command to.
-    pub rep_degree: u8,
+    pub rep_degree: u16,
 }
 
 #[allow(clippy::derivable_impls)]
@@ -55,6 +68,9 @@ impl Default for ReplicaConfigSimplePush {
         ReplicaConfigSimplePush {
             batch_interval_ms: 1,
             max_batch_size: 5000,
+            idem_cache_size: 0,
+            session_idle_s: 0,
+            ns_quota_kb: 0,
             backer_path: "/tmp/summerset.simple_push.wal".into(),
             rep_degree: 2,
         }
@@ -102,7 +118,7 @@ pub(crate) struct SimplePushReplica {
     id: ReplicaId,
 
     /// Total number of replicas in cluster.
-    population: u8,
+    population: u16,
 
     /// Configuration parameters struct.
     config: ReplicaConfigSimplePush,
@@ -165,11 +181,15 @@ impl GenericReplica for SimplePushReplica {
         // connect to the cluster manager and get assigned a server ID
         let mut control_hub = ControlHub::new_and_setup(manager).await?;
         let id = control_hub.me;
+        let (api_addr, p2p_addr) =
+            control_hub.resolve_addrs(api_addr, p2p_addr)?;
         let population = control_hub.population;
 
         // parse protocol-specific configs
         let config = parsed_config!(config_str => ReplicaConfigSimplePush;
                                     batch_interval_ms, max_batch_size,
+                                    idem_cache_size, session_idle_s,
+                                    ns_quota_kb,
                                     backer_path, rep_degree)?;
         if config.batch_interval_ms == 0 {
             return logged_err!(
@@ -179,7 +199,9 @@ impl GenericReplica for SimplePushReplica {
         }
 
         // setup state machine module
-        let state_machine = StateMachine::new_and_setup(id).await?;
+        let state_machine =
+            StateMachine::new_and_setup(id, config.ns_quota_kb * 1024).await?;
+        control_hub.inspect_keys(&state_machine);
 
         // setup storage hub module
         let storage_hub =
@@ -190,7 +212,9 @@ impl GenericReplica for SimplePushReplica {
         let mut transport_hub = TransportHub::new_and_setup(
             id,
             population,
+            SmrProtocol::SimplePush,
             p2p_addr,
+            false,
             HashMap::new(),
         )
         .await?;
@@ -209,7 +233,7 @@ impl GenericReplica for SimplePushReplica {
         {
             to_peers
         } else {
-            return logged_err!("unexpected ctrl msg type received");
+            return logged_err!(Protocol; "unexpected ctrl msg type received");
         };
 
         // proactively connect to some peers, then wait for all population
@@ -220,13 +244,16 @@ impl GenericReplica for SimplePushReplica {
         transport_hub.wait_for_group(population).await?;
 
         // setup external API module, ready to take in client requests
-        let external_api = ExternalApi::new_and_setup(
+        let mut external_api = ExternalApi::new_and_setup(
             id,
             api_addr,
             Duration::from_millis(config.batch_interval_ms),
             config.max_batch_size,
+            config.idem_cache_size,
+            Duration::from_secs(config.session_idle_s),
         )
         .await?;
+        external_api.stamp_replies(&state_machine);
 
         Ok(SimplePushReplica {
             id,
@@ -350,7 +377,7 @@ impl GenericReplica for SimplePushReplica {
         self.id
     }
 
-    fn population(&self) -> u8 {
+    fn population(&self) -> u16 {
         self.population
     }
 }
@@ -375,7 +402,7 @@ pub(crate) struct SimplePushClient {
     id: ClientId,
 
     /// Number of servers in the cluster.
-    population: u8,
+    population: u16,
 
     /// Configuration parameters struct.
     config: ClientConfigSimplePush,
@@ -452,7 +479,7 @@ impl GenericEndpoint for SimplePushClient {
                 self.api_stub = Some(api_stub);
                 Ok(())
             }
-            _ => logged_err!("unexpected reply type received"),
+            _ => logged_err!(Protocol; "unexpected reply type received"),
         }
     }
 
@@ -504,7 +531,7 @@ impl GenericEndpoint for SimplePushClient {
         self.id
     }
 
-    fn population(&self) -> u8 {
+    fn population(&self) -> u16 {
         self.population
     }
 
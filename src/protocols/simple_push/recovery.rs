@@ -64,7 +64,7 @@ impl SimplePushReplica {
                     break;
                 }
                 _ => {
-                    return logged_err!("unexpected log result type");
+                    return logged_err!(Protocol; "unexpected log result type");
                 }
             }
         }
@@ -85,7 +85,7 @@ impl SimplePushReplica {
         {
             Ok(())
         } else {
-            logged_err!("unexpected log result type")
+            logged_err!(Protocol; "unexpected log result type")
         }
     }
 }
@@ -0,0 +1,191 @@
+//! VR -- durable logging.
+
+use super::*;
+
+use crate::server::{ApiRequest, LogAction, LogResult};
+use crate::utils::SummersetError;
+
+// VRReplica durable WAL logging
+impl VRReplica {
+    /// Makes my view number and latest normal view durable, synchronously,
+    /// before I act in the view.
+    pub(super) async fn persist_view_meta(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        let (old_results, result) = self
+            .storage_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Append {
+                    entry: WalEntry::ViewMeta {
+                        view: self.view,
+                        last_normal: self.last_normal,
+                    },
+                    sync: self.config.logger_sync,
+                },
+            )
+            .await?;
+        for (old_id, old_result) in old_results {
+            self.handle_log_result(old_id, old_result).await?;
+        }
+        if let LogResult::Append { now_size } = result {
+            self.wal_offset = now_size;
+            Ok(())
+        } else {
+            logged_err!(Protocol; "unexpected log result type: {:?}", result)
+        }
+    }
+
+    /// Truncates my log at op `from` and extends it with the given ops, both
+    /// in memory and durably.
+    pub(super) fn append_ops(
+        &mut self,
+        from: usize,
+        entries: Vec<ReqBatch>,
+    ) -> Result<(), SummersetError> {
+        debug_assert!(from >= self.commit_bar && from <= self.log.len());
+        self.log.truncate(from);
+        self.log.extend(entries.iter().cloned());
+        let last_op = self.log.len().saturating_sub(1);
+
+        self.storage_hub.submit_action(
+            Self::make_log_action_id(last_op, ActionKind::Append),
+            LogAction::Append {
+                entry: WalEntry::AppendOps { from, entries },
+                sync: self.config.logger_sync,
+            },
+        )?;
+        self.appends_inflight += 1;
+        pf_trace!("submitted AppendOps log action from op {}", from);
+        Ok(())
+    }
+
+    /// Replaces my log with the given one, durably logging only the part
+    /// after the common prefix, which covers at least all committed ops.
+    pub(super) fn adopt_log(
+        &mut self,
+        log: Vec<ReqBatch>,
+    ) -> Result<(), SummersetError> {
+        let common = self
+            .log
+            .iter()
+            .zip(log.iter())
+            .take_while(|(mine, theirs)| mine == theirs)
+            .count();
+        if common == log.len() && common == self.log.len() {
+            return Ok(());
+        }
+        let entries = log.into_iter().skip(common).collect();
+        self.append_ops(common, entries)
+    }
+
+    /// Advances my commit number to `commit`, capped by my log length, and
+    /// records the commit event.
+    pub(super) fn advance_commit(
+        &mut self,
+        commit: usize,
+    ) -> Result<(), SummersetError> {
+        let commit = commit.min(self.log.len());
+        if commit <= self.commit_bar {
+            return Ok(());
+        }
+        self.commit_bar = commit;
+        pf_debug!("committed ops up to {}", commit);
+
+        self.storage_hub.submit_action(
+            Self::make_log_action_id(commit, ActionKind::Commit),
+            LogAction::Append {
+                entry: WalEntry::CommitUpTo { commit },
+                sync: self.config.logger_sync,
+            },
+        )?;
+        pf_trace!("submitted CommitUpTo log action for {}", commit);
+        Ok(())
+    }
+
+    /// Acks my whole log once it is durable: on the primary, as a
+    /// PrepareOk from myself; on a backup, by sending PrepareOk to the
+    /// primary.
+    pub(super) fn ack_durable_log(&mut self) -> Result<(), SummersetError> {
+        if self.status != Status::Normal
+            || self.appends_inflight > 0
+            || self.log.is_empty()
+        {
+            return Ok(());
+        }
+        let op = self.log.len() - 1;
+        if self.is_primary() {
+            self.handle_msg_prepare_ok(self.id, self.view, op)
+        } else {
+            let primary = self.primary_of(self.view);
+            self.transport_hub.send_msg(
+                PeerMsg::PrepareOk {
+                    view: self.view,
+                    op,
+                },
+                primary,
+            )?;
+            pf_trace!("sent PrepareOk -> {} for op {}", primary, op);
+            Ok(())
+        }
+    }
+
+    /// Handler of AppendOps logging result chan recv.
+    fn handle_logged_append(
+        &mut self,
+        op: usize,
+    ) -> Result<(), SummersetError> {
+        pf_trace!("finished AppendOps logging up to op {}", op);
+        self.appends_inflight -= 1;
+        // only ack when nothing submitted later may have changed the log
+        self.ack_durable_log()
+    }
+
+    /// Handler of CommitUpTo logging result chan recv.
+    fn handle_logged_commit(
+        &mut self,
+        commit: usize,
+    ) -> Result<(), SummersetError> {
+        pf_trace!("finished CommitUpTo logging for {}", commit);
+
+        // submit commands of newly committed ops for execution
+        while self.exec_bar < commit {
+            for (cmd_idx, (_, req)) in
+                self.log[self.exec_bar].iter().enumerate()
+            {
+                if let ApiRequest::Req { cmd, checksum, .. } = req {
+                    self.state_machine.submit_checked_cmd(
+                        Self::make_command_id(self.exec_bar, cmd_idx),
+                        cmd.clone(),
+                        *checksum,
+                    )?;
+                }
+            }
+            pf_trace!("submitted commands of op {}", self.exec_bar);
+            self.exec_bar += 1;
+        }
+        Ok(())
+    }
+
+    /// Synthesized handler of durable logging result chan recv.
+    pub(super) async fn handle_log_result(
+        &mut self,
+        action_id: LogActionId,
+        log_result: LogResult<WalEntry>,
+    ) -> Result<(), SummersetError> {
+        let (op, kind) = Self::split_log_action_id(action_id);
+        if let LogResult::Append { now_size } = log_result {
+            debug_assert!(now_size >= self.wal_offset);
+            self.wal_offset = now_size;
+        } else {
+            return logged_err!(
+                Protocol; "unexpected log result type: {:?}", log_result
+            );
+        }
+
+        match kind {
+            ActionKind::Append => self.handle_logged_append(op),
+            ActionKind::Commit => self.handle_logged_commit(op),
+        }
+    }
+}
@@ -0,0 +1,47 @@
+//! VR -- command execution.
+
+use super::*;
+
+use crate::server::{ApiReply, ApiRequest};
+use crate::utils::SummersetError;
+
+// VRReplica state machine execution
+impl VRReplica {
+    /// Handler of state machine exec result chan recv.
+    pub(super) async fn handle_cmd_result(
+        &mut self,
+        cmd_id: CommandId,
+        cmd_result: CommandResult,
+    ) -> Result<(), SummersetError> {
+        let (op, cmd_idx) = Self::split_command_id(cmd_id);
+        debug_assert!(op < self.log.len());
+        pf_trace!("executed cmd in op {} idx {}", op, cmd_idx);
+
+        // only the primary replies to clients
+        if !self.is_primary() {
+            return Ok(());
+        }
+
+        let reqs = &self.log[op];
+        debug_assert!(cmd_idx < reqs.len());
+        let (client, ref req) = reqs[cmd_idx];
+        if let ApiRequest::Req { id: req_id, .. } = req {
+            if self.external_api.has_client(client) {
+                self.external_api.send_reply(
+                    ApiReply::normal(*req_id, Some(cmd_result)),
+                    client,
+                )?;
+                pf_trace!(
+                    "replied -> client {} for op {} idx {}",
+                    client,
+                    op,
+                    cmd_idx
+                );
+            }
+        } else {
+            return logged_err!(Protocol; "unexpected API request type");
+        }
+
+        Ok(())
+    }
+}
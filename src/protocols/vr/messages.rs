@@ -0,0 +1,242 @@
+//! VR -- peer-peer messaging of normal operation & state transfer.
+
+use super::*;
+
+use crate::utils::SummersetError;
+
+// VRReplica peer-peer messages handling
+impl VRReplica {
+    /// Checks the view of a normal-operation message from the primary of
+    /// that view. Returns true if the message should be processed further.
+    /// If the view is newer than mine, catches up to it by state transfer.
+    async fn check_primary_msg(
+        &mut self,
+        peer: ReplicaId,
+        view: View,
+    ) -> Result<bool, SummersetError> {
+        if self.status == Status::Recovering
+            || view < self.view
+            || peer != self.primary_of(view)
+        {
+            return Ok(false);
+        }
+        if view > self.view {
+            self.catch_up_view(view).await?;
+            return Ok(false);
+        }
+        if self.status != Status::Normal || self.is_primary() {
+            return Ok(false);
+        }
+
+        // heard from the primary of my view
+        if !self.config.disable_hb_timer {
+            self.heartbeater.kickoff_hear_timer(Some(peer))?;
+        }
+        Ok(true)
+    }
+
+    /// Sends a GetState to the primary for the ops I am missing, unless one
+    /// is already on its way.
+    pub(super) fn request_state(&mut self) -> Result<(), SummersetError> {
+        if self.awaiting_state {
+            return Ok(());
+        }
+        self.awaiting_state = true;
+        let primary = self.primary_of(self.view);
+        let from = self.log.len();
+        self.transport_hub.send_msg(
+            PeerMsg::GetState {
+                view: self.view,
+                from,
+            },
+            primary,
+        )?;
+        pf_debug!("sent GetState -> {} from op {}", primary, from);
+        Ok(())
+    }
+
+    /// Handler of Prepare message from primary.
+    async fn handle_msg_prepare(
+        &mut self,
+        peer: ReplicaId,
+        view: View,
+        op: usize,
+        reqs: ReqBatch,
+        commit: usize,
+    ) -> Result<(), SummersetError> {
+        if !self.check_primary_msg(peer, view).await? {
+            return Ok(());
+        }
+        pf_trace!(
+            "received Prepare <- {} for op {} of size {}",
+            peer,
+            op,
+            reqs.len()
+        );
+
+        // ops are taken in strictly in order; fetch missing ones first
+        if op > self.log.len() {
+            return self.request_state();
+        }
+        if op == self.log.len() {
+            self.append_ops(op, vec![reqs])?;
+        }
+        self.advance_commit(commit)
+    }
+
+    /// Handler of PrepareOk reply from a backup (or myself).
+    pub(super) fn handle_msg_prepare_ok(
+        &mut self,
+        peer: ReplicaId,
+        view: View,
+        op: usize,
+    ) -> Result<(), SummersetError> {
+        if !self.is_primary() || view != self.view || op >= self.log.len() {
+            return Ok(());
+        }
+        pf_trace!("received PrepareOk <- {} for op {}", peer, op);
+
+        let acked = self.acked.entry(peer).or_insert(0);
+        *acked = (*acked).max(op + 1);
+
+        // ops acked by a quorum are committed
+        let mut counts: Vec<usize> = self.acked.values().copied().collect();
+        if counts.len() < self.quorum_cnt as usize {
+            return Ok(());
+        }
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        let commit = counts[self.quorum_cnt as usize - 1];
+        self.advance_commit(commit)
+    }
+
+    /// Handler of Commit message from primary.
+    async fn handle_msg_commit(
+        &mut self,
+        peer: ReplicaId,
+        view: View,
+        commit: usize,
+    ) -> Result<(), SummersetError> {
+        if !self.check_primary_msg(peer, view).await? {
+            return Ok(());
+        }
+
+        if commit > self.log.len() {
+            self.request_state()?;
+        }
+        self.advance_commit(commit)
+    }
+
+    /// Handler of GetState message from a lagging replica.
+    fn handle_msg_get_state(
+        &mut self,
+        peer: ReplicaId,
+        view: View,
+        from: usize,
+    ) -> Result<(), SummersetError> {
+        if self.status != Status::Normal
+            || view != self.view
+            || from > self.log.len()
+        {
+            return Ok(());
+        }
+        pf_trace!("received GetState <- {} from op {}", peer, from);
+
+        self.transport_hub.send_msg(
+            PeerMsg::NewState {
+                view,
+                from,
+                entries: self.log[from..].to_vec(),
+                commit: self.commit_bar,
+            },
+            peer,
+        )?;
+        pf_trace!("sent NewState -> {} of {} ops", peer, self.log.len() - from);
+        Ok(())
+    }
+
+    /// Handler of NewState reply to my GetState.
+    fn handle_msg_new_state(
+        &mut self,
+        peer: ReplicaId,
+        view: View,
+        from: usize,
+        entries: Vec<ReqBatch>,
+        commit: usize,
+    ) -> Result<(), SummersetError> {
+        if self.status != Status::Normal
+            || view != self.view
+            || from < self.commit_bar
+            || from > self.log.len()
+        {
+            return Ok(());
+        }
+        pf_debug!(
+            "received NewState <- {} from op {} of {} ops",
+            peer,
+            from,
+            entries.len()
+        );
+
+        self.awaiting_state = false;
+        self.append_ops(from, entries)?;
+        self.advance_commit(commit)
+    }
+
+    /// Synthesized handler of receiving message from peer.
+    pub(super) async fn handle_msg_recv(
+        &mut self,
+        peer: ReplicaId,
+        msg: PeerMsg,
+    ) -> Result<(), SummersetError> {
+        match msg {
+            PeerMsg::Prepare {
+                view,
+                op,
+                reqs,
+                commit,
+            } => self.handle_msg_prepare(peer, view, op, reqs, commit).await,
+            PeerMsg::PrepareOk { view, op } => {
+                self.handle_msg_prepare_ok(peer, view, op)
+            }
+            PeerMsg::Commit { view, commit } => {
+                self.handle_msg_commit(peer, view, commit).await
+            }
+            PeerMsg::StartViewChange { view } => {
+                self.handle_msg_start_view_change(peer, view).await
+            }
+            PeerMsg::DoViewChange {
+                view,
+                log,
+                last_normal,
+                commit,
+            } => {
+                self.handle_msg_do_view_change(
+                    peer,
+                    view,
+                    log,
+                    last_normal,
+                    commit,
+                )
+                .await
+            }
+            PeerMsg::StartView { view, log, commit } => {
+                self.handle_msg_start_view(peer, view, log, commit).await
+            }
+            PeerMsg::GetState { view, from } => {
+                self.handle_msg_get_state(peer, view, from)
+            }
+            PeerMsg::NewState {
+                view,
+                from,
+                entries,
+                commit,
+            } => self.handle_msg_new_state(peer, view, from, entries, commit),
+            PeerMsg::Recovery { nonce } => {
+                self.handle_msg_recovery(peer, nonce)
+            }
+            PeerMsg::RecoveryResponse { nonce, state } => {
+                self.handle_msg_recovery_response(peer, nonce, state).await
+            }
+        }
+    }
+}
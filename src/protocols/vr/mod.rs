@@ -0,0 +1,844 @@
+//! Replication protocol: Viewstamped Replication.
+//!
+//! Viewstamped Replication Revisited, with the normal operation, view change,
+//! state transfer, and recovery sub-protocols. The primary of a view is
+//! picked round-robin by view number, and a view change carries whole logs.
+//! References:
+//!   - <https://pmg.csail.mit.edu/papers/vr-revisited.pdf>
+//!
+//! Unlike the paper's diskless design, replicas log ops and view numbers
+//! durably like the other protocols here, so that a restarted replica simply
+//! resumes in its last view. The recovery protocol is run by a replica
+//! starting with empty durable state, e.g., after a non-durable reset; if
+//! all peers turn out to have empty state as well, the cluster starts fresh.
+
+mod control;
+mod durability;
+mod execution;
+mod messages;
+mod recovery;
+mod request;
+mod viewchange;
+
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::path::Path;
+
+use crate::client::{ClientApiStub, ClientCtrlStub, ClientId, GenericEndpoint};
+use crate::manager::{CtrlMsg, CtrlReply, CtrlRequest};
+use crate::protocols::SmrProtocol;
+use crate::server::{
+    ApiReply, ApiRequest, CommandId, CommandResult, ControlHub, ExternalApi,
+    GenericReplica, HeartbeatEvent, Heartbeater, LogActionId, ReplicaId,
+    StateMachine, StorageHub, TransportHub,
+};
+use crate::utils::{new_rng, SummersetError};
+
+use async_trait::async_trait;
+
+use get_size::GetSize;
+
+use rand::rngs::StdRng;
+
+use serde::{Deserialize, Serialize};
+
+use tokio::sync::watch;
+use tokio::time::Duration;
+
+/// Configuration parameters struct.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReplicaConfigVR {
+    /// Client request batching interval in millisecs.
+    pub batch_interval_ms: u64,
+
+    /// Client request batching maximum batch size.
+    pub max_batch_size: usize,
+
+    /// Number of completed replies cached per client for collapsing retried
+    /// requests by idempotency key; 0 means collapsing disabled.
+    pub idem_cache_size: usize,
+
+    /// Idle timeout in secs after which a client session that sent no request
+    /// is closed and its state reclaimed; 0 means never.
+    pub session_idle_s: u64,
+
+    /// Quota in KiB of approximate key-value bytes stored per namespace (the
+    /// key prefix before the first '/'), beyond which Puts are rejected; 0
+    /// means unlimited.
+    pub ns_quota_kb: usize,
+
+    /// Path to backing log file.
+    pub backer_path: String,
+
+    /// Whether to call `fsync()`/`fdatasync()` on logger.
+    pub logger_sync: bool,
+
+    /// Min timeout of not hearing any heartbeat from primary in millisecs.
+    pub hb_hear_timeout_min: u64,
+    /// Max timeout of not hearing any heartbeat from primary in millisecs.
+    pub hb_hear_timeout_max: u64,
+
+    /// Interval of primary sending Commit heartbeats to backups.
+    pub hb_send_interval_ms: u64,
+
+    /// Disable heartbeat timer (to force a deterministic primary during
+    /// tests).
+    pub disable_hb_timer: bool,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for ReplicaConfigVR {
+    fn default() -> Self {
+        ReplicaConfigVR {
+            batch_interval_ms: 1,
+            max_batch_size: 5000,
+            idem_cache_size: 0,
+            session_idle_s: 0,
+            ns_quota_kb: 0,
+            backer_path: "/tmp/summerset.vr.wal".into(),
+            logger_sync: false,
+            hb_hear_timeout_min: 1200,
+            hb_hear_timeout_max: 2000,
+            hb_send_interval_ms: 20,
+            disable_hb_timer: false,
+        }
+    }
+}
+
+/// View number type.
+pub type View = u64;
+
+/// Replica status enum.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Status {
+    Normal,
+    ViewChange,
+    Recovering,
+}
+
+/// Kind of durable logging action.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum ActionKind {
+    Append = 1,
+    Commit = 2,
+}
+
+/// Request batch type (i.e., the "op" in VR).
+type ReqBatch = Vec<(ClientId, ApiRequest)>;
+
+/// Stable storage WAL log entry type.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, GetSize)]
+enum WalEntry {
+    /// Records the view I am in and the latest view I was normal in.
+    ViewMeta { view: View, last_normal: View },
+
+    /// Records that the log got truncated at op `from` and then extended
+    /// with the given ops.
+    AppendOps { from: usize, entries: Vec<ReqBatch> },
+
+    /// Records that ops before `commit` got committed.
+    CommitUpTo { commit: usize },
+}
+
+/// State of a normal replica carried in a RecoveryResponse.
+#[derive(Debug, Clone, Serialize, Deserialize, GetSize)]
+struct RecoveryState {
+    /// Responder's view.
+    view: View,
+
+    /// Number of ops in responder's log.
+    op_cnt: usize,
+
+    /// Responder's commit number.
+    commit: usize,
+
+    /// Responder's whole log, sent only by the primary.
+    log: Option<Vec<ReqBatch>>,
+}
+
+/// Peer-peer message type.
+#[derive(Debug, Clone, Serialize, Deserialize, GetSize)]
+enum PeerMsg {
+    /// Prepare message from primary to backups.
+    Prepare {
+        view: View,
+        op: usize,
+        reqs: ReqBatch,
+        commit: usize,
+    },
+
+    /// Prepare reply from backup to primary, acking all ops up to `op`.
+    PrepareOk { view: View, op: usize },
+
+    /// Commit notice from primary to backups, also serving as heartbeat.
+    Commit { view: View, commit: usize },
+
+    /// View change initiation broadcast to all replicas.
+    StartViewChange { view: View },
+
+    /// View change vote from replica to the new primary.
+    DoViewChange {
+        view: View,
+        log: Vec<ReqBatch>,
+        last_normal: View,
+        commit: usize,
+    },
+
+    /// New view announcement from the new primary to backups.
+    StartView {
+        view: View,
+        log: Vec<ReqBatch>,
+        commit: usize,
+    },
+
+    /// State transfer request for ops starting at `from`.
+    GetState { view: View, from: usize },
+
+    /// State transfer reply carrying ops starting at `from`.
+    NewState {
+        view: View,
+        from: usize,
+        entries: Vec<ReqBatch>,
+        commit: usize,
+    },
+
+    /// Recovery request from a replica with empty state.
+    Recovery { nonce: u64 },
+
+    /// Recovery reply; `state` is `None` if the responder is recovering too.
+    RecoveryResponse {
+        nonce: u64,
+        state: Option<RecoveryState>,
+    },
+}
+
+/// VR server replica module.
+pub(crate) struct VRReplica {
+    /// Replica ID in cluster.
+    id: ReplicaId,
+
+    /// Total number of replicas in cluster.
+    population: u16,
+
+    /// Majority quorum size.
+    quorum_cnt: u16,
+
+    /// Configuration parameters struct.
+    config: ReplicaConfigVR,
+
+    /// Address string for client requests API.
+    _api_addr: SocketAddr,
+
+    /// Address string for internal peer-peer communication.
+    _p2p_addr: SocketAddr,
+
+    /// ControlHub module.
+    control_hub: ControlHub,
+
+    /// ExternalApi module.
+    external_api: ExternalApi,
+
+    /// StateMachine module.
+    state_machine: StateMachine,
+
+    /// StorageHub module.
+    storage_hub: StorageHub<WalEntry>,
+
+    /// TransportHub module.
+    transport_hub: TransportHub<PeerMsg>,
+
+    /// Heartbeater module.
+    heartbeater: Heartbeater,
+
+    /// Current status.
+    status: Status,
+
+    /// Current view number.
+    view: View,
+
+    /// Latest view in which my status was normal.
+    last_normal: View,
+
+    /// In-memory log of ops.
+    log: Vec<ReqBatch>,
+
+    /// Number of committed ops, i.e., index of the first non-committed op.
+    commit_bar: usize,
+
+    /// Number of ops submitted to the state machine.
+    exec_bar: usize,
+
+    /// Number of append actions submitted and not yet finished.
+    appends_inflight: usize,
+
+    /// On the primary: number of ops each replica has acked in my view.
+    acked: HashMap<ReplicaId, usize>,
+
+    /// During a view change: replicas that sent StartViewChange for my view.
+    svc_votes: HashSet<ReplicaId>,
+
+    /// During a view change: whether I have sent my DoViewChange.
+    dvc_sent: bool,
+
+    /// On the new primary: DoViewChange contents received, as tuples of
+    /// (last normal view, log, commit number).
+    dvc_msgs: HashMap<ReplicaId, (View, Vec<ReqBatch>, usize)>,
+
+    /// Whether I am waiting for a NewState reply.
+    awaiting_state: bool,
+
+    /// While recovering: nonce of my latest Recovery and responses to it.
+    recovery: Option<(u64, HashMap<ReplicaId, Option<RecoveryState>>)>,
+
+    /// Random number generator for recovery nonces.
+    nonce_rng: StdRng,
+
+    /// Current durable WAL log file offset.
+    wal_offset: usize,
+}
+
+// VRReplica common helpers
+impl VRReplica {
+    /// Primary of the given view.
+    #[inline]
+    fn primary_of(&self, view: View) -> ReplicaId {
+        (view % self.population as View) as ReplicaId
+    }
+
+    /// Am I the primary of my current view in normal status?
+    #[inline]
+    fn is_primary(&self) -> bool {
+        self.status == Status::Normal && self.primary_of(self.view) == self.id
+    }
+
+    /// Compose LogActionId from op index & action kind.
+    #[inline]
+    fn make_log_action_id(op: usize, kind: ActionKind) -> LogActionId {
+        ((op << 2) | (kind as usize)) as LogActionId
+    }
+
+    /// Decompose LogActionId into op index & action kind.
+    #[inline]
+    fn split_log_action_id(log_action_id: LogActionId) -> (usize, ActionKind) {
+        let op = (log_action_id >> 2) as usize;
+        let kind = match log_action_id & ((1 << 2) - 1) {
+            1 => ActionKind::Append,
+            2 => ActionKind::Commit,
+            k => panic!("unknown log action kind num {}", k),
+        };
+        (op, kind)
+    }
+
+    /// Compose CommandId from op index & command index within.
+    #[inline]
+    fn make_command_id(op: usize, cmd_idx: usize) -> CommandId {
+        debug_assert!(op <= (u32::MAX as usize));
+        debug_assert!(cmd_idx <= (u32::MAX as usize));
+        ((op << 32) | cmd_idx) as CommandId
+    }
+
+    /// Decompose CommandId into op index & command index within.
+    #[inline]
+    fn split_command_id(command_id: CommandId) -> (usize, usize) {
+        let op = (command_id >> 32) as usize;
+        let cmd_idx = (command_id & ((1 << 32) - 1)) as usize;
+        (op, cmd_idx)
+    }
+}
+
+#[async_trait]
+impl GenericReplica for VRReplica {
+    async fn new_and_setup(
+        api_addr: SocketAddr,
+        p2p_addr: SocketAddr,
+        manager: SocketAddr,
+        config_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        // connect to the cluster manager and get assigned a server ID
+        let mut control_hub = ControlHub::new_and_setup(manager).await?;
+        let id = control_hub.me;
+        let (api_addr, p2p_addr) =
+            control_hub.resolve_addrs(api_addr, p2p_addr)?;
+        let population = control_hub.population;
+
+        // parse protocol-specific configs
+        let config = parsed_config!(config_str => ReplicaConfigVR;
+                                    batch_interval_ms, max_batch_size,
+                                    idem_cache_size, session_idle_s,
+                                    ns_quota_kb,
+                                    backer_path, logger_sync,
+                                    hb_hear_timeout_min, hb_hear_timeout_max,
+                                    hb_send_interval_ms, disable_hb_timer)?;
+        if config.batch_interval_ms == 0 {
+            return logged_err!(
+                "invalid config.batch_interval_ms '{}'",
+                config.batch_interval_ms
+            );
+        }
+        if config.hb_hear_timeout_min == 0 {
+            return logged_err!(
+                "invalid config.hb_hear_timeout_min '{}'",
+                config.hb_hear_timeout_min
+            );
+        }
+        if config.hb_hear_timeout_max < config.hb_hear_timeout_min {
+            return logged_err!(
+                "invalid config.hb_hear_timeout_max '{}'",
+                config.hb_hear_timeout_max
+            );
+        }
+        if config.hb_send_interval_ms == 0 {
+            return logged_err!(
+                "invalid config.hb_send_interval_ms '{}'",
+                config.hb_send_interval_ms
+            );
+        }
+
+        // setup state machine module
+        let state_machine =
+            StateMachine::new_and_setup(id, config.ns_quota_kb * 1024).await?;
+        control_hub.inspect_keys(&state_machine);
+
+        // setup storage hub module
+        let storage_hub =
+            StorageHub::new_and_setup(id, Path::new(&config.backer_path))
+                .await?;
+
+        // setup heartbeat management module
+        let heartbeater = Heartbeater::new_and_setup(
+            id,
+            population,
+            Duration::from_millis(config.hb_hear_timeout_min),
+            Duration::from_millis(config.hb_hear_timeout_max),
+            Duration::from_millis(config.hb_send_interval_ms),
+        )?;
+
+        // setup transport hub module
+        let mut transport_hub = TransportHub::new_and_setup(
+            id,
+            population,
+            SmrProtocol::VR,
+            p2p_addr,
+            false,
+            HashMap::new(), // no leases
+        )
+        .await?;
+
+        // ask for the list of peers to proactively connect to. Do this after
+        // transport hub has been set up, so that I will be able to accept
+        // later peer connections
+        control_hub.send_ctrl(CtrlMsg::NewServerJoin {
+            id,
+            protocol: SmrProtocol::VR,
+            api_addr,
+            p2p_addr,
+        })?;
+        let to_peers = if let CtrlMsg::ConnectToPeers { to_peers, .. } =
+            control_hub.recv_ctrl().await?
+        {
+            to_peers
+        } else {
+            return logged_err!(Protocol; "unexpected ctrl msg type received");
+        };
+
+        // proactively connect to some peers, then wait for all population
+        // have been connected with me
+        for (peer, conn_addr) in to_peers {
+            transport_hub.connect_to_peer(peer, conn_addr).await?;
+        }
+        transport_hub.wait_for_group(population).await?;
+
+        // setup external API module, ready to take in client requests
+        let mut external_api = ExternalApi::new_and_setup(
+            id,
+            api_addr,
+            Duration::from_millis(config.batch_interval_ms),
+            config.max_batch_size,
+            config.idem_cache_size,
+            Duration::from_secs(config.session_idle_s),
+        )
+        .await?;
+        external_api.stamp_replies(&state_machine);
+
+        Ok(VRReplica {
+            id,
+            population,
+            quorum_cnt: (population / 2) + 1,
+            config,
+            _api_addr: api_addr,
+            _p2p_addr: p2p_addr,
+            control_hub,
+            external_api,
+            state_machine,
+            storage_hub,
+            transport_hub,
+            heartbeater,
+            status: Status::Recovering,
+            view: 0,
+            last_normal: 0,
+            log: vec![],
+            commit_bar: 0,
+            exec_bar: 0,
+            appends_inflight: 0,
+            acked: HashMap::new(),
+            svc_votes: HashSet::new(),
+            dvc_sent: false,
+            dvc_msgs: HashMap::new(),
+            awaiting_state: false,
+            recovery: None,
+            nonce_rng: new_rng(id, "nonce"),
+            wal_offset: 0,
+        })
+    }
+
+    async fn run(
+        &mut self,
+        mut rx_term: watch::Receiver<bool>,
+    ) -> Result<bool, SummersetError> {
+        // recover the log & view from durable WAL log, or from peers if my
+        // durable state is empty
+        self.recover_from_wal().await?;
+        if self.status == Status::Recovering {
+            self.start_recovery().await?;
+        } else {
+            self.enter_status()?;
+        }
+
+        // kick off primary activity hearing timer
+        if !self.config.disable_hb_timer {
+            self.heartbeater.kickoff_hear_timer(None)?;
+        }
+
+        // main event loop
+        let mut paused = false;
+        loop {
+            tokio::select! {
+                // client request batch
+                req_batch = self.external_api.get_req_batch(), if !paused => {
+                    if let Err(e) = req_batch {
+                        pf_error!("error getting req batch: {}", e);
+                        continue;
+                    }
+                    let req_batch = req_batch.unwrap();
+                    if let Err(e) = self.handle_req_batch(req_batch).await {
+                        pf_error!("error handling req batch: {}", e);
+                    }
+                },
+
+                // durable logging result
+                log_result = self.storage_hub.get_result(), if !paused => {
+                    if let Err(e) = log_result {
+                        pf_error!("error getting log result: {}", e);
+                        continue;
+                    }
+                    let (action_id, log_result) = log_result.unwrap();
+                    if let Err(e) = self.handle_log_result(action_id, log_result).await {
+                        pf_error!("error handling log result {}: {}",
+                                           action_id, e);
+                    }
+                },
+
+                // message from peer
+                msg = self.transport_hub.recv_msg(), if !paused => {
+                    if let Err(_e) = msg {
+                        // NOTE: commented out to prevent console lags
+                        //       during benchmarking
+                        // pf_error!("error receiving peer msg: {}", e);
+                        continue;
+                    }
+                    let (peer, msg) = msg.unwrap();
+                    if let Err(e) = self.handle_msg_recv(peer, msg).await {
+                        pf_error!("error handling msg recv <- {}: {}", peer, e);
+                    }
+                },
+
+                // state machine execution result
+                cmd_result = self.state_machine.get_result(), if !paused => {
+                    if let Err(e) = cmd_result {
+                        pf_error!("error getting cmd result: {}", e);
+                        continue;
+                    }
+                    let (cmd_id, cmd_result) = cmd_result.unwrap();
+                    if let Err(e) = self.handle_cmd_result(cmd_id, cmd_result).await {
+                        pf_error!("error handling cmd result {}: {}", cmd_id, e);
+                    }
+                },
+
+                // heartbeat-related event
+                hb_event = self.heartbeater.get_event(), if !paused => {
+                    if let Err(e) = hb_event {
+                        pf_error!("error getting heartbeat event: {}", e);
+                        continue;
+                    }
+                    match hb_event.unwrap() {
+                        HeartbeatEvent::HearTimeout { peer } => {
+                            if let Err(e) = self.handle_hear_timeout(peer).await {
+                                pf_error!("error handling hear timeout: {}", e);
+                            }
+                        }
+                        HeartbeatEvent::SendTicked => {
+                            if let Err(e) = self.bcast_heartbeats() {
+                                pf_error!("error broadcasting heartbeats: {}", e);
+                            }
+                        }
+                    }
+                },
+
+                // manager control message
+                ctrl_msg = self.control_hub.recv_ctrl() => {
+                    if let Err(e) = ctrl_msg {
+                        pf_error!("error getting ctrl msg: {}", e);
+                        continue;
+                    }
+                    let ctrl_msg = ctrl_msg.unwrap();
+                    match self.handle_ctrl_msg(ctrl_msg, &mut paused).await {
+                        Ok(terminate) => {
+                            if let Some(restart) = terminate {
+                                return Ok(restart);
+                            }
+                        },
+                        Err(e) => {
+                            pf_error!("error handling ctrl msg: {}", e);
+                        }
+                    }
+                },
+
+                // receiving termination signal
+                _ = rx_term.changed() => {
+                    pf_warn!("server caught termination signal");
+                    return Ok(false);
+                }
+            }
+        }
+    }
+
+    fn id(&self) -> ReplicaId {
+        self.id
+    }
+
+    fn population(&self) -> u16 {
+        self.population
+    }
+}
+
+/// Configuration parameters struct.
+#[derive(Debug, Deserialize)]
+pub struct ClientConfigVR {
+    /// Which server to pick initially.
+    pub init_server_id: ReplicaId,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for ClientConfigVR {
+    fn default() -> Self {
+        ClientConfigVR { init_server_id: 0 }
+    }
+}
+
+/// VR client-side module.
+pub(crate) struct VRClient {
+    /// Client ID.
+    id: ClientId,
+
+    /// Number of servers in the cluster.
+    population: u16,
+
+    /// Configuration parameters struct.
+    _config: ClientConfigVR,
+
+    /// List of active servers information.
+    servers: HashMap<ReplicaId, SocketAddr>,
+
+    /// Current server ID to talk to.
+    server_id: ReplicaId,
+
+    /// Control API stub to the cluster manager.
+    ctrl_stub: ClientCtrlStub,
+
+    /// API stubs for communicating with servers.
+    api_stubs: HashMap<ReplicaId, ClientApiStub>,
+}
+
+#[async_trait]
+impl GenericEndpoint for VRClient {
+    async fn new_and_setup(
+        manager: SocketAddr,
+        config_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        // connect to the cluster manager and get assigned a client ID
+        pf_debug!("connecting to manager '{}'...", manager);
+        let ctrl_stub = ClientCtrlStub::new_by_connect(manager).await?;
+        let id = ctrl_stub.id;
+
+        // parse protocol-specific configs
+        let config = parsed_config!(config_str => ClientConfigVR;
+                                    init_server_id)?;
+        let init_server_id = config.init_server_id;
+
+        Ok(VRClient {
+            id,
+            population: 0,
+            _config: config,
+            servers: HashMap::new(),
+            server_id: init_server_id,
+            ctrl_stub,
+            api_stubs: HashMap::new(),
+        })
+    }
+
+    async fn connect(&mut self) -> Result<(), SummersetError> {
+        // disallow reconnection without leaving
+        if !self.api_stubs.is_empty() {
+            return logged_err!("reconnecting without leaving");
+        }
+
+        // ask the manager about the list of active servers
+        let mut sent =
+            self.ctrl_stub.send_req(Some(&CtrlRequest::QueryInfo))?;
+        while !sent {
+            sent = self.ctrl_stub.send_req(None)?;
+        }
+
+        let reply = self.ctrl_stub.recv_reply().await?;
+        match reply {
+            CtrlReply::QueryInfo {
+                population,
+                servers_info,
+            } => {
+                self.population = population;
+
+                // shift to a new server_id if current one not active
+                debug_assert!(!servers_info.is_empty());
+                while !servers_info.contains_key(&self.server_id)
+                    || servers_info[&self.server_id].is_paused
+                {
+                    self.server_id = (self.server_id + 1) % population;
+                }
+                // establish connection to all servers
+                self.servers = servers_info
+                    .into_iter()
+                    .map(|(id, info)| (id, info.api_addr))
+                    .collect();
+                for (&id, &server) in &self.servers {
+                    pf_debug!("connecting to server {} '{}'...", id, server);
+                    let api_stub =
+                        ClientApiStub::new_by_connect(self.id, server).await?;
+                    self.api_stubs.insert(id, api_stub);
+                }
+                Ok(())
+            }
+            _ => logged_err!(Protocol; "unexpected reply type received"),
+        }
+    }
+
+    async fn leave(&mut self, permanent: bool) -> Result<(), SummersetError> {
+        // send leave notification to all servers
+        for (id, mut api_stub) in self.api_stubs.drain() {
+            let mut sent = api_stub.send_req(Some(&ApiRequest::Leave))?;
+            while !sent {
+                sent = api_stub.send_req(None)?;
+            }
+
+            // NOTE: commented out the following wait to avoid accidental
+            //       hanging upon leaving
+            // while api_stub.recv_reply().await? != ApiReply::Leave {}
+            pf_debug!("left server connection {}", id);
+        }
+
+        // if permanently leaving, send leave notification to the manager
+        if permanent {
+            let mut sent =
+                self.ctrl_stub.send_req(Some(&CtrlRequest::Leave))?;
+            while !sent {
+                sent = self.ctrl_stub.send_req(None)?;
+            }
+
+            while self.ctrl_stub.recv_reply().await? != CtrlReply::Leave {}
+            pf_debug!("left manager connection");
+        }
+
+        Ok(())
+    }
+
+    fn send_req(
+        &mut self,
+        req: Option<&ApiRequest>,
+    ) -> Result<bool, SummersetError> {
+        if self.api_stubs.contains_key(&self.server_id) {
+            self.api_stubs
+                .get_mut(&self.server_id)
+                .unwrap()
+                .send_req(req)
+        } else {
+            Err(SummersetError::msg(format!(
+                "server_id {} not in api_stubs",
+                self.server_id
+            )))
+        }
+    }
+
+    async fn recv_reply(&mut self) -> Result<ApiReply, SummersetError> {
+        if self.api_stubs.contains_key(&self.server_id) {
+            let reply = self
+                .api_stubs
+                .get_mut(&self.server_id)
+                .unwrap()
+                .recv_reply()
+                .await?;
+
+            if let ApiReply::LeaderChange { leader } = reply {
+                // notified of a new primary; switch over to it, or keep
+                // waiting if already talking to it
+                if leader == self.server_id
+                    || !self.servers.contains_key(&leader)
+                {
+                    return self.recv_reply().await;
+                }
+                self.server_id = leader;
+                pf_debug!(
+                    "notified of new primary {} '{}'",
+                    leader,
+                    self.servers[&leader]
+                );
+            }
+
+            if let ApiReply::Reply {
+                ref result,
+                ref redirect,
+                ..
+            } = reply
+            {
+                // if the current server redirects me to a different server
+                if result.is_none() && redirect.is_some() {
+                    let redirect_id = redirect.unwrap();
+                    debug_assert!(self.servers.contains_key(&redirect_id));
+                    self.server_id = redirect_id;
+                    pf_debug!(
+                        "redirected to replica {} '{}'",
+                        redirect_id,
+                        self.servers[&redirect_id]
+                    );
+                }
+            }
+
+            Ok(reply)
+        } else {
+            Err(SummersetError::msg(format!(
+                "server_id {} not in api_stubs",
+                self.server_id
+            )))
+        }
+    }
+
+    fn id(&self) -> ClientId {
+        self.id
+    }
+
+    fn population(&self) -> u16 {
+        self.population
+    }
+
+    fn ctrl_stub(&mut self) -> &mut ClientCtrlStub {
+        &mut self.ctrl_stub
+    }
+}
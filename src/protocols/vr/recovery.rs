@@ -0,0 +1,269 @@
+//! VR -- recovery from WAL, and recovery protocol with peers.
+
+use super::*;
+
+use crate::server::{ApiRequest, LogAction, LogResult};
+use crate::utils::SummersetError;
+
+use rand::Rng;
+
+// VRReplica recovery from WAL log & from peers
+impl VRReplica {
+    /// Apply a durable storage log entry for recovery.
+    async fn recover_apply_entry(
+        &mut self,
+        entry: WalEntry,
+    ) -> Result<(), SummersetError> {
+        match entry {
+            WalEntry::ViewMeta { view, last_normal } => {
+                self.view = view;
+                self.last_normal = last_normal;
+            }
+
+            WalEntry::AppendOps { from, entries } => {
+                if from > self.log.len() || from < self.commit_bar {
+                    return logged_err!(
+                        "append from op {} with log of {} ops",
+                        from,
+                        self.log.len()
+                    );
+                }
+                self.log.truncate(from);
+                self.log.extend(entries);
+            }
+
+            WalEntry::CommitUpTo { commit } => {
+                if commit > self.log.len() {
+                    return logged_err!(
+                        "commit up to {} with log of {} ops",
+                        commit,
+                        self.log.len()
+                    );
+                }
+                // execute commands in newly committed ops on the state
+                // machine synchronously
+                while self.commit_bar < commit {
+                    for (_, req) in &self.log[self.commit_bar] {
+                        if let ApiRequest::Req { cmd, .. } = req {
+                            // using 0 as a special command ID
+                            self.state_machine
+                                .do_sync_cmd(0, cmd.clone())
+                                .await?;
+                        }
+                    }
+                    self.commit_bar += 1;
+                }
+                self.exec_bar = self.commit_bar;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Recover state from durable storage WAL log. My status is set to the
+    /// one recorded, or left as recovering if the log is empty.
+    pub(super) async fn recover_from_wal(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        debug_assert_eq!(self.wal_offset, 0);
+        loop {
+            match self
+                .storage_hub
+                .do_sync_action(
+                    0, // using 0 as dummy log action ID
+                    LogAction::Read {
+                        offset: self.wal_offset,
+                    },
+                )
+                .await?
+                .1
+            {
+                LogResult::Read {
+                    entry: Some(entry),
+                    end_offset,
+                } => {
+                    self.recover_apply_entry(entry).await?;
+                    // update log offset
+                    self.wal_offset = end_offset;
+                }
+                LogResult::Read { entry: None, .. } => {
+                    // end of log reached
+                    break;
+                }
+                _ => {
+                    return logged_err!(Protocol; "unexpected log result type");
+                }
+            }
+        }
+
+        // do an extra Truncate to remove partial entry at the end if any
+        if let LogResult::Truncate {
+            offset_ok: true, ..
+        } = self
+            .storage_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Truncate {
+                    offset: self.wal_offset,
+                },
+            )
+            .await?
+            .1
+        {
+            if self.wal_offset > 0 {
+                self.status = if self.view == self.last_normal {
+                    Status::Normal
+                } else {
+                    Status::ViewChange
+                };
+                pf_info!(
+                    "recovered from wal log: view {} ops {} commit {}",
+                    self.view,
+                    self.log.len(),
+                    self.commit_bar
+                );
+            }
+            Ok(())
+        } else {
+            logged_err!(
+                Protocol; "unexpected log result type or failed truncate"
+            )
+        }
+    }
+
+    /// State of a fresh cluster that has never had any op.
+    fn fresh_state() -> RecoveryState {
+        RecoveryState {
+            view: 0,
+            op_cnt: 0,
+            commit: 0,
+            log: Some(vec![]),
+        }
+    }
+
+    /// Broadcasts a Recovery message with a fresh nonce, starting a new round
+    /// of collecting responses.
+    pub(super) async fn start_recovery(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        debug_assert_eq!(self.status, Status::Recovering);
+        let nonce: u64 = self.nonce_rng.gen();
+        self.recovery = Some((nonce, HashMap::new()));
+        self.transport_hub
+            .bcast_msg(PeerMsg::Recovery { nonce }, None)?;
+        pf_info!("recovering from peers with nonce {}...", nonce);
+
+        // a new round follows if this one does not complete in time
+        if !self.config.disable_hb_timer {
+            self.heartbeater.kickoff_hear_timer(None)?;
+        }
+
+        // nobody to wait for if I am alone
+        if self.population == 1 {
+            return self.finish_recovery(Self::fresh_state()).await;
+        }
+        Ok(())
+    }
+
+    /// Handler of Recovery message from a recovering replica.
+    pub(super) fn handle_msg_recovery(
+        &mut self,
+        peer: ReplicaId,
+        nonce: u64,
+    ) -> Result<(), SummersetError> {
+        let state = match self.status {
+            Status::Recovering => None,
+            Status::Normal => Some(RecoveryState {
+                view: self.view,
+                op_cnt: self.log.len(),
+                commit: self.commit_bar,
+                log: if self.is_primary() {
+                    Some(self.log.clone())
+                } else {
+                    None
+                },
+            }),
+            Status::ViewChange => return Ok(()),
+        };
+        pf_trace!("received Recovery <- {} nonce {}", peer, nonce);
+
+        self.transport_hub
+            .send_msg(PeerMsg::RecoveryResponse { nonce, state }, peer)?;
+        Ok(())
+    }
+
+    /// Handler of RecoveryResponse message to my Recovery.
+    pub(super) async fn handle_msg_recovery_response(
+        &mut self,
+        peer: ReplicaId,
+        nonce: u64,
+        state: Option<RecoveryState>,
+    ) -> Result<(), SummersetError> {
+        if self.status != Status::Recovering {
+            return Ok(());
+        }
+        let Some((my_nonce, responses)) = self.recovery.as_mut() else {
+            return Ok(());
+        };
+        if nonce != *my_nonce {
+            return Ok(());
+        }
+        pf_trace!("received RecoveryResponse <- {} nonce {}", peer, nonce);
+        responses.insert(peer, state);
+
+        // normal responses from a quorum including the primary of the latest
+        // view among them: take the primary's state
+        let normal_cnt = responses.values().filter(|s| s.is_some()).count();
+        if normal_cnt >= self.quorum_cnt as usize {
+            let max_view =
+                responses.values().flatten().map(|s| s.view).max().unwrap();
+            // field access only, as `responses` borrows `self.recovery`
+            let primary = (max_view % self.population as View) as ReplicaId;
+            if let Some(Some(RecoveryState {
+                view, log: Some(_), ..
+            })) = responses.get(&primary)
+            {
+                if *view == max_view {
+                    let state = responses.remove(&primary).unwrap().unwrap();
+                    return self.finish_recovery(state).await;
+                }
+            }
+        }
+
+        // every peer has responded, each either recovering or fresh: no
+        // replica has ever had any state, so start fresh
+        if responses.len() == self.population as usize - 1
+            && responses.values().all(|s| {
+                s.as_ref().is_none_or(|s| {
+                    s.view == 0 && s.op_cnt == 0 && s.commit == 0
+                })
+            })
+        {
+            pf_info!("all peers have empty state, starting fresh");
+            return self.finish_recovery(Self::fresh_state()).await;
+        }
+
+        Ok(())
+    }
+
+    /// Takes the given state and becomes normal in its view.
+    async fn finish_recovery(
+        &mut self,
+        state: RecoveryState,
+    ) -> Result<(), SummersetError> {
+        self.recovery = None;
+        self.view = state.view;
+        self.last_normal = state.view;
+        self.adopt_log(state.log.unwrap_or_default())?;
+        self.persist_view_meta().await?;
+        pf_info!(
+            "recovered from peers: view {} ops {} commit {}",
+            self.view,
+            self.log.len(),
+            state.commit
+        );
+
+        self.enter_normal()?;
+        self.advance_commit(state.commit)
+    }
+}
@@ -0,0 +1,57 @@
+//! VR -- client request entrance.
+
+use super::*;
+
+use crate::server::{ApiReply, ApiRequest};
+use crate::utils::SummersetError;
+
+// VRReplica client requests entrance
+impl VRReplica {
+    /// Handler of client request batch chan recv.
+    pub(super) async fn handle_req_batch(
+        &mut self,
+        req_batch: ReqBatch,
+    ) -> Result<(), SummersetError> {
+        let batch_size = req_batch.len();
+        debug_assert!(batch_size > 0);
+        pf_debug!("got request batch of size {}", batch_size);
+
+        // if I'm not the primary in normal status, tell clients to try on
+        // the primary of my view
+        if !self.is_primary() {
+            let target = self.primary_of(self.view);
+            for (client, req) in req_batch {
+                if let ApiRequest::Req { id: req_id, .. } = req {
+                    self.external_api.send_reply(
+                        ApiReply::redirect(req_id, Some(target)),
+                        client,
+                    )?;
+                    pf_trace!(
+                        "redirected client {} to replica {}",
+                        client,
+                        target
+                    );
+                }
+            }
+            return Ok(());
+        }
+
+        // append the batch as a new op to my log durably
+        let op = self.log.len();
+        self.append_ops(op, vec![req_batch.clone()])?;
+
+        // send Prepare messages to all backups
+        self.transport_hub.bcast_msg(
+            PeerMsg::Prepare {
+                view: self.view,
+                op,
+                reqs: req_batch,
+                commit: self.commit_bar,
+            },
+            None,
+        )?;
+        pf_trace!("broadcast Prepare messages for op {}", op);
+
+        Ok(())
+    }
+}
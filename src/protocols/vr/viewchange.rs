@@ -0,0 +1,292 @@
+//! VR -- view change & primary heartbeats.
+
+use super::*;
+
+use crate::manager::CtrlMsg;
+use crate::utils::SummersetError;
+
+// VRReplica view change logic
+impl VRReplica {
+    /// Steps down if I am currently the primary in normal status.
+    fn step_down_if_primary(&mut self) -> Result<(), SummersetError> {
+        if self.is_primary() {
+            self.heartbeater.set_sending(false);
+            self.control_hub
+                .send_ctrl(CtrlMsg::LeaderStatus { step_up: false })?;
+            pf_info!("stepped down as primary of view {}", self.view);
+        }
+        Ok(())
+    }
+
+    /// Sets my status to normal in my current view, doing the duties of
+    /// either the primary or a backup from now on. Called after my view
+    /// metadata has been made durable.
+    pub(super) fn enter_normal(&mut self) -> Result<(), SummersetError> {
+        self.status = Status::Normal;
+        self.last_normal = self.view;
+        self.acked.clear();
+        self.svc_votes.clear();
+        self.dvc_sent = false;
+        self.dvc_msgs.clear();
+        self.awaiting_state = false;
+
+        let primary = self.primary_of(self.view);
+        if primary == self.id {
+            pf_info!("became primary of view {}", self.view);
+            self.heartbeater.set_sending(true);
+            self.control_hub
+                .send_ctrl(CtrlMsg::LeaderStatus { step_up: true })?;
+            self.external_api.notify_leader_change(self.id)?;
+            self.bcast_heartbeats()?;
+        } else {
+            pf_info!("entered view {} as backup of {}", self.view, primary);
+            if !self.config.disable_hb_timer {
+                self.heartbeater.kickoff_hear_timer(Some(primary))?;
+            }
+        }
+
+        // ack the ops I already have durably
+        self.ack_durable_log()
+    }
+
+    /// Finishes my setup after recovering from WAL in the status recorded.
+    pub(super) fn enter_status(&mut self) -> Result<(), SummersetError> {
+        if self.status == Status::Normal {
+            self.enter_normal()
+        } else {
+            pf_info!("resuming view change to view {}", self.view);
+            Ok(())
+        }
+    }
+
+    /// Starts a view change to the given view, announcing it to all peers.
+    pub(super) async fn start_view_change(
+        &mut self,
+        view: View,
+    ) -> Result<(), SummersetError> {
+        debug_assert!(view > self.view);
+        self.step_down_if_primary()?;
+        self.view = view;
+        self.status = Status::ViewChange;
+        self.svc_votes = HashSet::from([self.id]);
+        self.dvc_sent = false;
+        self.dvc_msgs.clear();
+        self.awaiting_state = false;
+        pf_info!("starting view change to view {}...", view);
+
+        // promise durably not to act in older views any more
+        self.persist_view_meta().await?;
+
+        self.transport_hub
+            .bcast_msg(PeerMsg::StartViewChange { view }, None)?;
+        pf_trace!("broadcast StartViewChange for view {}", view);
+
+        // another view change follows if this one does not complete in time
+        if !self.config.disable_hb_timer {
+            self.heartbeater.kickoff_hear_timer(None)?;
+        }
+        self.send_do_view_change_if_ready().await
+    }
+
+    /// Sends DoViewChange to the new primary once a quorum of replicas have
+    /// started the view change.
+    async fn send_do_view_change_if_ready(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        if self.dvc_sent || self.svc_votes.len() < self.quorum_cnt as usize {
+            return Ok(());
+        }
+        self.dvc_sent = true;
+
+        let primary = self.primary_of(self.view);
+        if primary == self.id {
+            self.dvc_msgs.insert(
+                self.id,
+                (self.last_normal, self.log.clone(), self.commit_bar),
+            );
+            self.finish_view_change_if_ready().await
+        } else {
+            self.transport_hub.send_msg(
+                PeerMsg::DoViewChange {
+                    view: self.view,
+                    log: self.log.clone(),
+                    last_normal: self.last_normal,
+                    commit: self.commit_bar,
+                },
+                primary,
+            )?;
+            pf_trace!(
+                "sent DoViewChange -> {} for view {}",
+                primary,
+                self.view
+            );
+            Ok(())
+        }
+    }
+
+    /// Handler of StartViewChange message.
+    pub(super) async fn handle_msg_start_view_change(
+        &mut self,
+        peer: ReplicaId,
+        view: View,
+    ) -> Result<(), SummersetError> {
+        if self.status == Status::Recovering || view < self.view {
+            return Ok(());
+        }
+        pf_trace!("received StartViewChange <- {} for view {}", peer, view);
+
+        if view > self.view {
+            self.start_view_change(view).await?;
+        } else if self.status != Status::ViewChange {
+            return Ok(());
+        }
+        self.svc_votes.insert(peer);
+        self.send_do_view_change_if_ready().await
+    }
+
+    /// Handler of DoViewChange message on the new primary.
+    pub(super) async fn handle_msg_do_view_change(
+        &mut self,
+        peer: ReplicaId,
+        view: View,
+        log: Vec<ReqBatch>,
+        last_normal: View,
+        commit: usize,
+    ) -> Result<(), SummersetError> {
+        if self.status == Status::Recovering
+            || view < self.view
+            || self.primary_of(view) != self.id
+        {
+            return Ok(());
+        }
+        pf_trace!("received DoViewChange <- {} for view {}", peer, view);
+
+        if view > self.view {
+            self.start_view_change(view).await?;
+        } else if self.status != Status::ViewChange {
+            return Ok(());
+        }
+        self.dvc_msgs.insert(peer, (last_normal, log, commit));
+        self.finish_view_change_if_ready().await
+    }
+
+    /// Finishes the view change on the new primary once DoViewChange from a
+    /// quorum including myself have arrived, announcing the new view.
+    async fn finish_view_change_if_ready(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        if self.dvc_msgs.len() < self.quorum_cnt as usize
+            || !self.dvc_msgs.contains_key(&self.id)
+        {
+            return Ok(());
+        }
+
+        // take the log of the latest normal view, the longest among those,
+        // and the largest commit number
+        let commit = self.dvc_msgs.values().map(|m| m.2).max().unwrap_or(0);
+        let (_, (_, log, _)) = self
+            .dvc_msgs
+            .drain()
+            .max_by_key(|(_, (last_normal, log, _))| (*last_normal, log.len()))
+            .unwrap();
+        self.adopt_log(log)?;
+        self.last_normal = self.view;
+        self.persist_view_meta().await?;
+
+        self.transport_hub.bcast_msg(
+            PeerMsg::StartView {
+                view: self.view,
+                log: self.log.clone(),
+                commit,
+            },
+            None,
+        )?;
+        pf_trace!("broadcast StartView for view {}", self.view);
+
+        self.enter_normal()?;
+        self.advance_commit(commit)
+    }
+
+    /// Handler of StartView message from the new primary.
+    pub(super) async fn handle_msg_start_view(
+        &mut self,
+        peer: ReplicaId,
+        view: View,
+        log: Vec<ReqBatch>,
+        commit: usize,
+    ) -> Result<(), SummersetError> {
+        if self.status == Status::Recovering
+            || view < self.view
+            || (view == self.view && self.status == Status::Normal)
+            || peer != self.primary_of(view)
+        {
+            return Ok(());
+        }
+        pf_trace!("received StartView <- {} for view {}", peer, view);
+
+        self.step_down_if_primary()?;
+        self.view = view;
+        self.last_normal = view;
+        self.adopt_log(log)?;
+        self.persist_view_meta().await?;
+        self.enter_normal()?;
+        self.advance_commit(commit)
+    }
+
+    /// Catches up to a newer view I learned of from its primary, keeping
+    /// only my committed ops and fetching the rest by state transfer.
+    pub(super) async fn catch_up_view(
+        &mut self,
+        view: View,
+    ) -> Result<(), SummersetError> {
+        debug_assert!(view > self.view);
+        pf_info!("learned of newer view {}, catching up", view);
+        self.step_down_if_primary()?;
+        self.view = view;
+        self.last_normal = view;
+        if self.log.len() > self.commit_bar {
+            self.append_ops(self.commit_bar, vec![])?;
+        }
+        self.persist_view_meta().await?;
+        self.enter_normal()?;
+        self.request_state()
+    }
+
+    /// Handler of a heartbeat hearing timeout of some peer.
+    pub(super) async fn handle_hear_timeout(
+        &mut self,
+        peer: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        match self.status {
+            Status::Recovering => self.start_recovery().await,
+            Status::ViewChange => {
+                pf_warn!("view change to view {} timed out", self.view);
+                self.start_view_change(self.view + 1).await
+            }
+            Status::Normal => {
+                if peer != self.primary_of(self.view) || self.is_primary() {
+                    return Ok(());
+                }
+                pf_warn!("primary {} timed out", peer);
+                self.start_view_change(self.view + 1).await
+            }
+        }
+    }
+
+    /// Broadcasts Commit messages as heartbeats to all backups.
+    pub(super) fn bcast_heartbeats(&mut self) -> Result<(), SummersetError> {
+        if !self.is_primary() {
+            return Ok(());
+        }
+        self.transport_hub.bcast_msg(
+            PeerMsg::Commit {
+                view: self.view,
+                commit: self.commit_bar,
+            },
+            None,
+        )?;
+
+        // pf_trace!("broadcast heartbeats view {}", self.view);
+        Ok(())
+    }
+}
@@ -0,0 +1,343 @@
+//! ZAB -- discovery & synchronization phases.
+
+use super::*;
+
+use crate::manager::CtrlMsg;
+use crate::utils::SummersetError;
+
+// ZabReplica discovery & synchronization logic
+impl ZabReplica {
+    /// Tells my leader the latest epoch I have accepted, (re-)starting
+    /// discovery with it.
+    pub(super) fn send_follower_info(&mut self) -> Result<(), SummersetError> {
+        let Some(leader) = self.leader else {
+            return Ok(());
+        };
+        self.status = Status::Discovery;
+        self.transport_hub.send_msg(
+            PeerMsg::FollowerInfo {
+                accepted: self.accepted_epoch,
+            },
+            leader,
+        )?;
+        pf_trace!(
+            "sent FollowerInfo -> {} accepted epoch {}",
+            leader,
+            self.accepted_epoch
+        );
+        Ok(())
+    }
+
+    /// Proposes a new epoch, higher than any accepted by a quorum of
+    /// followers including myself, once their FollowerInfo have arrived.
+    pub(super) async fn propose_epoch_if_ready(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        if self.epoch_proposed || self.infos.len() < self.quorum_cnt as usize {
+            return Ok(());
+        }
+        self.epoch_proposed = true;
+        self.accepted_epoch = self.infos.values().max().unwrap() + 1;
+        self.persist_epochs().await?;
+        pf_info!("proposing new epoch {}", self.accepted_epoch);
+
+        let followers: Vec<ReplicaId> = self
+            .infos
+            .keys()
+            .copied()
+            .filter(|&peer| peer != self.id)
+            .collect();
+        for peer in followers {
+            self.transport_hub.send_msg(
+                PeerMsg::NewEpoch {
+                    epoch: self.accepted_epoch,
+                },
+                peer,
+            )?;
+        }
+
+        self.epoch_acks =
+            HashMap::from([(self.id, (self.current_epoch, self.log.clone()))]);
+        self.start_sync_if_ready().await
+    }
+
+    /// Handler of FollowerInfo message on the leader.
+    async fn handle_msg_follower_info(
+        &mut self,
+        peer: ReplicaId,
+        accepted: Epoch,
+    ) -> Result<(), SummersetError> {
+        if !self.is_leader() {
+            return Ok(());
+        }
+        pf_trace!("received FollowerInfo <- {} accepted {}", peer, accepted);
+
+        if !self.epoch_proposed {
+            self.infos.insert(peer, accepted);
+            return self.propose_epoch_if_ready().await;
+        }
+
+        // a follower joining late, or re-syncing: hand it my epoch, unless
+        // it has promised a later one
+        self.up_to_date.remove(&peer);
+        if accepted <= self.accepted_epoch {
+            self.transport_hub.send_msg(
+                PeerMsg::NewEpoch {
+                    epoch: self.accepted_epoch,
+                },
+                peer,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Handler of NewEpoch message on a follower.
+    async fn handle_msg_new_epoch(
+        &mut self,
+        peer: ReplicaId,
+        epoch: Epoch,
+    ) -> Result<(), SummersetError> {
+        if self.leader != Some(peer) || self.status != Status::Discovery {
+            return Ok(());
+        }
+        pf_trace!("received NewEpoch <- {} epoch {}", peer, epoch);
+
+        if epoch < self.accepted_epoch {
+            pf_warn!(
+                "leader {} proposed stale epoch {} < {}",
+                peer,
+                epoch,
+                self.accepted_epoch
+            );
+            return self.start_election().await;
+        }
+
+        // an epoch accepted already must not count towards another leader
+        // establishing it
+        let fresh = epoch > self.accepted_epoch;
+        if fresh {
+            self.accepted_epoch = epoch;
+            self.persist_epochs().await?;
+        }
+        self.transport_hub.send_msg(
+            PeerMsg::AckEpoch {
+                epoch,
+                fresh,
+                current: self.current_epoch,
+                history: self.log.clone(),
+            },
+            peer,
+        )?;
+        pf_trace!("sent AckEpoch -> {} epoch {} fresh {}", peer, epoch, fresh);
+        Ok(())
+    }
+
+    /// Handler of AckEpoch message on the leader.
+    async fn handle_msg_ack_epoch(
+        &mut self,
+        peer: ReplicaId,
+        epoch: Epoch,
+        fresh: bool,
+        current: Epoch,
+        history: Vec<Txn>,
+    ) -> Result<(), SummersetError> {
+        if !self.is_leader() || epoch != self.accepted_epoch {
+            return Ok(());
+        }
+        pf_trace!(
+            "received AckEpoch <- {} epoch {} fresh {}",
+            peer,
+            epoch,
+            fresh
+        );
+
+        if self.status == Status::Discovery {
+            if fresh {
+                self.epoch_acks.insert(peer, (current, history));
+                self.start_sync_if_ready().await?;
+            }
+            return Ok(());
+        }
+
+        // a follower joining late, or re-syncing: hand it my history
+        self.transport_hub.send_msg(
+            PeerMsg::NewLeader {
+                epoch: self.current_epoch,
+                history: self.log.clone(),
+            },
+            peer,
+        )?;
+        pf_trace!("sent NewLeader -> {} of {} txns", peer, self.log.len());
+        Ok(())
+    }
+
+    /// Starts synchronization once a quorum including myself have accepted
+    /// my new epoch: takes the most up-to-date history among them as the
+    /// initial history of the epoch, and hands it to the followers.
+    async fn start_sync_if_ready(&mut self) -> Result<(), SummersetError> {
+        if self.status != Status::Discovery
+            || self.epoch_acks.len() < self.quorum_cnt as usize
+        {
+            return Ok(());
+        }
+
+        // take the history of the latest current epoch, then latest zxid
+        let followers: Vec<ReplicaId> = self
+            .epoch_acks
+            .keys()
+            .copied()
+            .filter(|&peer| peer != self.id)
+            .collect();
+        let (_, (_, history)) = self
+            .epoch_acks
+            .drain()
+            .max_by_key(|(_, (current, history))| {
+                (*current, history.last().map(|(zxid, _)| *zxid))
+            })
+            .unwrap();
+        self.adopt_history(history)?;
+        self.current_epoch = self.accepted_epoch;
+        self.persist_epochs().await?;
+        self.status = Status::Sync;
+        self.sync_len = self.log.len();
+        pf_info!(
+            "synchronizing epoch {} with {} txns",
+            self.current_epoch,
+            self.sync_len
+        );
+
+        for peer in followers {
+            self.transport_hub.send_msg(
+                PeerMsg::NewLeader {
+                    epoch: self.current_epoch,
+                    history: self.log.clone(),
+                },
+                peer,
+            )?;
+        }
+        pf_trace!("sent NewLeader to quorum of {} txns", self.log.len());
+
+        // ack my own history once durable
+        self.ack_durable_log()
+    }
+
+    /// Handler of NewLeader message on a follower.
+    async fn handle_msg_new_leader(
+        &mut self,
+        peer: ReplicaId,
+        epoch: Epoch,
+        history: Vec<Txn>,
+    ) -> Result<(), SummersetError> {
+        if self.leader != Some(peer)
+            || self.status != Status::Discovery
+            || epoch != self.accepted_epoch
+        {
+            return Ok(());
+        }
+        pf_trace!(
+            "received NewLeader <- {} epoch {} of {} txns",
+            peer,
+            epoch,
+            history.len()
+        );
+
+        self.adopt_history(history)?;
+        self.current_epoch = epoch;
+        self.persist_epochs().await?;
+        self.status = Status::Sync;
+        pf_info!("synchronized with leader {} in epoch {}", peer, epoch);
+
+        // ack the adopted history once durable
+        self.ack_durable_log()
+    }
+
+    /// Starts broadcast on the leader once a quorum have synced its initial
+    /// history, telling followers to start delivering.
+    pub(super) fn enter_broadcast(&mut self) -> Result<(), SummersetError> {
+        debug_assert!(self.is_leader() && self.status == Status::Sync);
+        self.status = Status::Broadcast;
+        pf_info!("established as leader of epoch {}", self.current_epoch);
+        self.control_hub
+            .send_ctrl(CtrlMsg::LeaderStatus { step_up: true })?;
+        self.external_api.notify_leader_change(self.id)?;
+
+        let synced: Vec<ReplicaId> = self
+            .acked
+            .keys()
+            .copied()
+            .filter(|&peer| peer != self.id)
+            .collect();
+        for peer in synced {
+            self.send_up_to_date(peer)?;
+        }
+        Ok(())
+    }
+
+    /// Sends UpToDate to a synced follower.
+    pub(super) fn send_up_to_date(
+        &mut self,
+        peer: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        self.transport_hub.send_msg(
+            PeerMsg::UpToDate {
+                epoch: self.current_epoch,
+                commit: self.commit_bar,
+            },
+            peer,
+        )?;
+        self.up_to_date.insert(peer);
+        pf_trace!("sent UpToDate -> {} commit {}", peer, self.commit_bar);
+        Ok(())
+    }
+
+    /// Handler of UpToDate message on a follower.
+    fn handle_msg_up_to_date(
+        &mut self,
+        peer: ReplicaId,
+        epoch: Epoch,
+        commit: usize,
+    ) -> Result<(), SummersetError> {
+        if self.leader != Some(peer)
+            || self.status != Status::Sync
+            || epoch != self.current_epoch
+        {
+            return Ok(());
+        }
+        pf_debug!("received UpToDate <- {} commit {}", peer, commit);
+
+        self.status = Status::Broadcast;
+        self.advance_commit(commit)
+    }
+
+    /// Synthesized handler of discovery & synchronization messages.
+    pub(super) async fn handle_msg_sync(
+        &mut self,
+        peer: ReplicaId,
+        msg: PeerMsg,
+    ) -> Result<(), SummersetError> {
+        match msg {
+            PeerMsg::FollowerInfo { accepted } => {
+                self.handle_msg_follower_info(peer, accepted).await
+            }
+            PeerMsg::NewEpoch { epoch } => {
+                self.handle_msg_new_epoch(peer, epoch).await
+            }
+            PeerMsg::AckEpoch {
+                epoch,
+                fresh,
+                current,
+                history,
+            } => {
+                self.handle_msg_ack_epoch(peer, epoch, fresh, current, history)
+                    .await
+            }
+            PeerMsg::NewLeader { epoch, history } => {
+                self.handle_msg_new_leader(peer, epoch, history).await
+            }
+            PeerMsg::UpToDate { epoch, commit } => {
+                self.handle_msg_up_to_date(peer, epoch, commit)
+            }
+            _ => logged_err!(Protocol; "unexpected peer msg type"),
+        }
+    }
+}
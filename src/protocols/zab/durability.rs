@@ -0,0 +1,192 @@
+//! ZAB -- durable logging.
+
+use super::*;
+
+use crate::server::{ApiRequest, LogAction, LogResult};
+use crate::utils::SummersetError;
+
+// ZabReplica durable WAL logging
+impl ZabReplica {
+    /// Makes my accepted and current epochs durable, synchronously, before I
+    /// act on them.
+    pub(super) async fn persist_epochs(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        let (old_results, result) = self
+            .storage_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Append {
+                    entry: WalEntry::EpochMeta {
+                        accepted: self.accepted_epoch,
+                        current: self.current_epoch,
+                    },
+                    sync: self.config.logger_sync,
+                },
+            )
+            .await?;
+        for (old_id, old_result) in old_results {
+            self.handle_log_result(old_id, old_result).await?;
+        }
+        if let LogResult::Append { now_size } = result {
+            self.wal_offset = now_size;
+            Ok(())
+        } else {
+            logged_err!(Protocol; "unexpected log result type: {:?}", result)
+        }
+    }
+
+    /// Truncates my history at index `from` and extends it with the given
+    /// transactions, both in memory and durably.
+    pub(super) fn append_txns(
+        &mut self,
+        from: usize,
+        txns: Vec<Txn>,
+    ) -> Result<(), SummersetError> {
+        debug_assert!(from >= self.commit_bar && from <= self.log.len());
+        self.log.truncate(from);
+        self.log.extend(txns.iter().cloned());
+        let last = self.log.len().saturating_sub(1);
+
+        self.storage_hub.submit_action(
+            Self::make_log_action_id(last, ActionKind::Append),
+            LogAction::Append {
+                entry: WalEntry::AppendTxns { from, txns },
+                sync: self.config.logger_sync,
+            },
+        )?;
+        self.appends_inflight += 1;
+        pf_trace!("submitted AppendTxns log action from index {}", from);
+        Ok(())
+    }
+
+    /// Replaces my history with the given one, durably logging only the part
+    /// after the common prefix, which covers at least all committed
+    /// transactions.
+    pub(super) fn adopt_history(
+        &mut self,
+        history: Vec<Txn>,
+    ) -> Result<(), SummersetError> {
+        let common = self
+            .log
+            .iter()
+            .zip(history.iter())
+            .take_while(|(mine, theirs)| mine.0 == theirs.0)
+            .count();
+        if common == history.len() && common == self.log.len() {
+            return Ok(());
+        }
+        let txns = history.into_iter().skip(common).collect();
+        self.append_txns(common, txns)
+    }
+
+    /// Advances my commit index to `commit`, capped by my history length,
+    /// and records the commit event.
+    pub(super) fn advance_commit(
+        &mut self,
+        commit: usize,
+    ) -> Result<(), SummersetError> {
+        let commit = commit.min(self.log.len());
+        if commit <= self.commit_bar {
+            return Ok(());
+        }
+        self.commit_bar = commit;
+        pf_debug!("committed txns up to {}", commit);
+
+        self.storage_hub.submit_action(
+            Self::make_log_action_id(commit, ActionKind::Commit),
+            LogAction::Append {
+                entry: WalEntry::CommitUpTo { commit },
+                sync: self.config.logger_sync,
+            },
+        )?;
+        pf_trace!("submitted CommitUpTo log action for {}", commit);
+        Ok(())
+    }
+
+    /// Acks my whole history in my current epoch once it is durable: on the
+    /// leader, as an Ack from myself; on a follower, by sending Ack to the
+    /// leader. Only done after adopting the epoch's NewLeader.
+    pub(super) fn ack_durable_log(&mut self) -> Result<(), SummersetError> {
+        if self.appends_inflight > 0
+            || (self.status != Status::Sync && self.status != Status::Broadcast)
+        {
+            return Ok(());
+        }
+        let upto = self.log.len();
+        if self.is_leader() {
+            self.handle_msg_ack(self.id, self.current_epoch, upto)
+        } else if let Some(leader) = self.leader {
+            self.transport_hub.send_msg(
+                PeerMsg::Ack {
+                    epoch: self.current_epoch,
+                    upto,
+                },
+                leader,
+            )?;
+            pf_trace!("sent Ack -> {} up to {}", leader, upto);
+            Ok(())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Handler of AppendTxns logging result chan recv.
+    fn handle_logged_append(
+        &mut self,
+        index: usize,
+    ) -> Result<(), SummersetError> {
+        pf_trace!("finished AppendTxns logging up to index {}", index);
+        self.appends_inflight -= 1;
+        // only ack when nothing submitted later may have changed the history
+        self.ack_durable_log()
+    }
+
+    /// Handler of CommitUpTo logging result chan recv.
+    fn handle_logged_commit(
+        &mut self,
+        commit: usize,
+    ) -> Result<(), SummersetError> {
+        pf_trace!("finished CommitUpTo logging for {}", commit);
+
+        // submit commands of newly committed transactions for execution
+        while self.exec_bar < commit {
+            for (cmd_idx, (_, req)) in
+                self.log[self.exec_bar].1.iter().enumerate()
+            {
+                if let ApiRequest::Req { cmd, checksum, .. } = req {
+                    self.state_machine.submit_checked_cmd(
+                        Self::make_command_id(self.exec_bar, cmd_idx),
+                        cmd.clone(),
+                        *checksum,
+                    )?;
+                }
+            }
+            pf_trace!("submitted commands of txn {}", self.exec_bar);
+            self.exec_bar += 1;
+        }
+        Ok(())
+    }
+
+    /// Synthesized handler of durable logging result chan recv.
+    pub(super) async fn handle_log_result(
+        &mut self,
+        action_id: LogActionId,
+        log_result: LogResult<WalEntry>,
+    ) -> Result<(), SummersetError> {
+        let (index, kind) = Self::split_log_action_id(action_id);
+        if let LogResult::Append { now_size } = log_result {
+            debug_assert!(now_size >= self.wal_offset);
+            self.wal_offset = now_size;
+        } else {
+            return logged_err!(
+                Protocol; "unexpected log result type: {:?}", log_result
+            );
+        }
+
+        match kind {
+            ActionKind::Append => self.handle_logged_append(index),
+            ActionKind::Commit => self.handle_logged_commit(index),
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! ZAB -- fast leader election & liveness.
+
+use super::*;
+
+use crate::manager::CtrlMsg;
+use crate::utils::SummersetError;
+
+// ZabReplica leader election logic
+impl ZabReplica {
+    /// My own vote, for myself with my current epoch and last zxid.
+    fn my_vote(&self) -> Vote {
+        Vote {
+            leader: self.id,
+            epoch: self.current_epoch,
+            zxid: self.last_zxid(),
+        }
+    }
+
+    /// Restarts the hearing timer of the given peer only, or of all peers if
+    /// `None`, cancelling the others.
+    pub(super) fn reset_hear_timers(
+        &mut self,
+        peer: Option<ReplicaId>,
+    ) -> Result<(), SummersetError> {
+        if self.config.disable_hb_timer {
+            return Ok(());
+        }
+        for timer in self.heartbeater.hear_timers().values() {
+            timer.cancel()?;
+        }
+        self.heartbeater.kickoff_hear_timer(peer)
+    }
+
+    /// Steps down if I am currently the leader.
+    fn step_down_if_leader(&mut self) -> Result<(), SummersetError> {
+        if self.is_leader() {
+            self.heartbeater.set_sending(false);
+            if self.status == Status::Broadcast {
+                self.control_hub
+                    .send_ctrl(CtrlMsg::LeaderStatus { step_up: false })?;
+            }
+            pf_info!("stepped down as leader of epoch {}", self.accepted_epoch);
+        }
+        Ok(())
+    }
+
+    /// Broadcasts a Notification of my current vote.
+    fn bcast_notification(&mut self) -> Result<(), SummersetError> {
+        self.transport_hub.bcast_msg(
+            PeerMsg::Notification {
+                round: self.round,
+                vote: self.vote,
+                looking: true,
+            },
+            None,
+        )?;
+        pf_trace!(
+            "broadcast Notification round {} for {}",
+            self.round,
+            self.vote.leader
+        );
+        Ok(())
+    }
+
+    /// Starts looking for a leader in a new election round, voting for
+    /// myself first.
+    pub(super) async fn start_election(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        self.step_down_if_leader()?;
+        self.status = Status::Election;
+        self.leader = None;
+        self.round += 1;
+        self.vote = self.my_vote();
+        self.votes = HashMap::from([(self.id, self.vote)]);
+        self.settled.clear();
+        pf_info!("looking for leader in round {}...", self.round);
+
+        self.bcast_notification()?;
+
+        // a new round follows if this one does not conclude in time
+        self.reset_hear_timers(Some((self.id + 1) % self.population))?;
+        self.conclude_election_if_ready().await
+    }
+
+    /// Concludes the election once a quorum of looking replicas vote the
+    /// same as me in my round, or a quorum of settled replicas, including
+    /// the leader itself, have elected the same leader.
+    async fn conclude_election_if_ready(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        if self.status != Status::Election {
+            return Ok(());
+        }
+
+        let agreed = self
+            .votes
+            .values()
+            .filter(|v| v.leader == self.vote.leader)
+            .count();
+        if agreed >= self.quorum_cnt as usize {
+            let leader = self.vote.leader;
+            return self.conclude_election(leader).await;
+        }
+
+        let joined = self
+            .settled
+            .iter()
+            .filter(|(&peer, vote)| peer == vote.leader)
+            .map(|(_, &vote)| vote)
+            .find(|vote| {
+                self.settled
+                    .values()
+                    .filter(|v| v.leader == vote.leader)
+                    .count()
+                    >= self.quorum_cnt as usize
+            });
+        if let Some(vote) = joined {
+            self.vote = vote;
+            return self.conclude_election(vote.leader).await;
+        }
+        Ok(())
+    }
+
+    /// Leaves the election with given leader, entering discovery.
+    async fn conclude_election(
+        &mut self,
+        leader: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        self.leader = Some(leader);
+        self.status = Status::Discovery;
+        self.votes.clear();
+        self.settled.clear();
+        self.acked.clear();
+        self.up_to_date.clear();
+
+        if leader == self.id {
+            pf_info!("elected as leader in round {}", self.round);
+            self.heartbeater.set_sending(true);
+            self.alive.clear();
+            self.reset_hear_timers(None)?;
+            self.infos = HashMap::from([(self.id, self.accepted_epoch)]);
+            self.epoch_proposed = false;
+            self.epoch_acks.clear();
+            self.propose_epoch_if_ready().await
+        } else {
+            pf_info!("following leader {} from round {}", leader, self.round);
+            self.reset_hear_timers(Some(leader))?;
+            self.send_follower_info()
+        }
+    }
+
+    /// Handler of Notification message.
+    pub(super) async fn handle_msg_notification(
+        &mut self,
+        peer: ReplicaId,
+        round: u64,
+        vote: Vote,
+        looking: bool,
+    ) -> Result<(), SummersetError> {
+        pf_trace!(
+            "received Notification <- {} round {} for {} looking {}",
+            peer,
+            round,
+            vote.leader,
+            looking
+        );
+
+        // out of election: tell a looking peer whom I have elected
+        if self.status != Status::Election {
+            if looking {
+                self.transport_hub.send_msg(
+                    PeerMsg::Notification {
+                        round: self.round,
+                        vote: self.vote,
+                        looking: false,
+                    },
+                    peer,
+                )?;
+            }
+            return Ok(());
+        }
+
+        if !looking {
+            self.settled.insert(peer, vote);
+            if round > self.round {
+                self.round = round;
+            }
+            return self.conclude_election_if_ready().await;
+        }
+
+        if round > self.round {
+            // catch up to the newer round, reconsidering my vote
+            self.round = round;
+            self.votes.clear();
+            let mine = self.my_vote();
+            self.vote = if vote.beats(&mine) { vote } else { mine };
+            self.votes.insert(self.id, self.vote);
+            self.bcast_notification()?;
+        } else if round < self.round {
+            // help the peer catch up to my round
+            self.transport_hub.send_msg(
+                PeerMsg::Notification {
+                    round: self.round,
+                    vote: self.vote,
+                    looking: true,
+                },
+                peer,
+            )?;
+            return Ok(());
+        } else if vote.beats(&self.vote) {
+            self.vote = vote;
+            self.votes.insert(self.id, vote);
+            self.bcast_notification()?;
+        }
+
+        self.votes.insert(peer, vote);
+        self.conclude_election_if_ready().await
+    }
+
+    /// Handler of a hearing timeout of some peer.
+    pub(super) async fn handle_hear_timeout(
+        &mut self,
+        peer: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        match self.status {
+            Status::Election => {
+                pf_warn!("election round {} timed out", self.round);
+                self.start_election().await
+            }
+            _ if self.is_leader() => {
+                // step down once I no longer hear from a quorum
+                self.alive.remove(&peer);
+                if self.alive.len() + 1 < self.quorum_cnt as usize {
+                    pf_warn!("lost quorum of followers");
+                    return self.start_election().await;
+                }
+                Ok(())
+            }
+            _ => {
+                if Some(peer) != self.leader {
+                    return Ok(());
+                }
+                pf_warn!("leader {} timed out", peer);
+                self.start_election().await
+            }
+        }
+    }
+
+    /// Notes that I have heard from a peer, for liveness.
+    pub(super) fn heard_from(
+        &mut self,
+        peer: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        if self.config.disable_hb_timer || self.status == Status::Election {
+            return Ok(());
+        }
+        if self.is_leader() {
+            self.alive.insert(peer);
+            self.heartbeater.kickoff_hear_timer(Some(peer))
+        } else if Some(peer) == self.leader {
+            self.heartbeater.kickoff_hear_timer(Some(peer))
+        } else {
+            Ok(())
+        }
+    }
+}
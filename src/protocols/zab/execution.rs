@@ -0,0 +1,47 @@
+//! ZAB -- command execution.
+
+use super::*;
+
+use crate::server::{ApiReply, ApiRequest};
+use crate::utils::SummersetError;
+
+// ZabReplica state machine execution
+impl ZabReplica {
+    /// Handler of state machine exec result chan recv.
+    pub(super) async fn handle_cmd_result(
+        &mut self,
+        cmd_id: CommandId,
+        cmd_result: CommandResult,
+    ) -> Result<(), SummersetError> {
+        let (index, cmd_idx) = Self::split_command_id(cmd_id);
+        debug_assert!(index < self.log.len());
+        pf_trace!("executed cmd in txn {} idx {}", index, cmd_idx);
+
+        // only the leader replies to clients
+        if !self.is_leader() {
+            return Ok(());
+        }
+
+        let reqs = &self.log[index].1;
+        debug_assert!(cmd_idx < reqs.len());
+        let (client, ref req) = reqs[cmd_idx];
+        if let ApiRequest::Req { id: req_id, .. } = req {
+            if self.external_api.has_client(client) {
+                self.external_api.send_reply(
+                    ApiReply::normal(*req_id, Some(cmd_result)),
+                    client,
+                )?;
+                pf_trace!(
+                    "replied -> client {} for txn {} idx {}",
+                    client,
+                    index,
+                    cmd_idx
+                );
+            }
+        } else {
+            return logged_err!(Protocol; "unexpected API request type");
+        }
+
+        Ok(())
+    }
+}
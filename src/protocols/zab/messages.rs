@@ -0,0 +1,168 @@
+//! ZAB -- peer-peer messaging of the broadcast phase.
+
+use super::*;
+
+use crate::utils::SummersetError;
+
+// ZabReplica peer-peer messages handling
+impl ZabReplica {
+    /// Checks that a broadcast message is from my leader in my current
+    /// epoch while I am synced with it. Returns true if the message should
+    /// be processed further.
+    fn check_leader_msg(&self, peer: ReplicaId, epoch: Epoch) -> bool {
+        self.leader == Some(peer)
+            && !self.is_leader()
+            && epoch == self.current_epoch
+            && (self.status == Status::Sync || self.status == Status::Broadcast)
+    }
+
+    /// Re-syncs with my leader after missing some of its proposals, going
+    /// through discovery again like a newly joining follower.
+    fn resync_with_leader(&mut self) -> Result<(), SummersetError> {
+        pf_warn!(
+            "missed proposals of epoch {}, re-syncing",
+            self.current_epoch
+        );
+        self.send_follower_info()
+    }
+
+    /// Handler of Propose message from leader.
+    fn handle_msg_propose(
+        &mut self,
+        peer: ReplicaId,
+        epoch: Epoch,
+        index: usize,
+        txn: Txn,
+    ) -> Result<(), SummersetError> {
+        if !self.check_leader_msg(peer, epoch) {
+            return Ok(());
+        }
+        pf_trace!(
+            "received Propose <- {} at index {} of size {}",
+            peer,
+            index,
+            txn.1.len()
+        );
+
+        // transactions are taken strictly in zxid order without gaps
+        if index > self.log.len() {
+            return self.resync_with_leader();
+        }
+        if index == self.log.len() && txn.0 > self.last_zxid() {
+            self.append_txns(index, vec![txn])?;
+        }
+        Ok(())
+    }
+
+    /// Handler of Ack reply from a follower (or myself).
+    pub(super) fn handle_msg_ack(
+        &mut self,
+        peer: ReplicaId,
+        epoch: Epoch,
+        upto: usize,
+    ) -> Result<(), SummersetError> {
+        if !self.is_leader()
+            || epoch != self.current_epoch
+            || upto > self.log.len()
+            || (self.status != Status::Sync && self.status != Status::Broadcast)
+        {
+            return Ok(());
+        }
+        pf_trace!("received Ack <- {} up to {}", peer, upto);
+
+        let acked = self.acked.entry(peer).or_insert(0);
+        *acked = (*acked).max(upto);
+
+        // transactions acked by a quorum are committed
+        let mut counts: Vec<usize> = self.acked.values().copied().collect();
+        if counts.len() < self.quorum_cnt as usize {
+            return Ok(());
+        }
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        let commit = counts[self.quorum_cnt as usize - 1];
+        self.advance_commit(commit)?;
+
+        if self.status == Status::Sync {
+            // established once a quorum has acked the initial history
+            if self.commit_bar >= self.sync_len {
+                self.enter_broadcast()?;
+            }
+        } else if peer != self.id && !self.up_to_date.contains(&peer) {
+            // a follower that synced late
+            self.send_up_to_date(peer)?;
+        }
+        Ok(())
+    }
+
+    /// Handler of Commit message from leader.
+    fn handle_msg_commit(
+        &mut self,
+        peer: ReplicaId,
+        epoch: Epoch,
+        commit: usize,
+    ) -> Result<(), SummersetError> {
+        if !self.check_leader_msg(peer, epoch) {
+            return Ok(());
+        }
+
+        if commit > self.log.len() {
+            return self.resync_with_leader();
+        }
+        // deliver only once told UpToDate
+        if self.status == Status::Broadcast {
+            self.advance_commit(commit)?;
+        }
+
+        // ack my durable history, also serving as heartbeat reply
+        self.ack_durable_log()
+    }
+
+    /// Broadcasts Commit messages as heartbeats to all followers.
+    pub(super) fn bcast_heartbeats(&mut self) -> Result<(), SummersetError> {
+        if !self.is_leader()
+            || (self.status != Status::Sync && self.status != Status::Broadcast)
+        {
+            return Ok(());
+        }
+        self.transport_hub.bcast_msg(
+            PeerMsg::Commit {
+                epoch: self.current_epoch,
+                commit: self.commit_bar,
+            },
+            None,
+        )?;
+
+        // pf_trace!("broadcast heartbeats epoch {}", self.current_epoch);
+        Ok(())
+    }
+
+    /// Synthesized handler of receiving message from peer.
+    pub(super) async fn handle_msg_recv(
+        &mut self,
+        peer: ReplicaId,
+        msg: PeerMsg,
+    ) -> Result<(), SummersetError> {
+        self.heard_from(peer)?;
+
+        match msg {
+            PeerMsg::Notification {
+                round,
+                vote,
+                looking,
+            } => {
+                self.handle_msg_notification(peer, round, vote, looking)
+                    .await
+            }
+            PeerMsg::Propose { epoch, index, txn } => {
+                self.handle_msg_propose(peer, epoch, index, txn)
+            }
+            PeerMsg::Ack { epoch, upto } => {
+                self.handle_msg_ack(peer, epoch, upto)
+            }
+            PeerMsg::Commit { epoch, commit } => {
+                self.handle_msg_commit(peer, epoch, commit)
+            }
+            _ => self.handle_msg_sync(peer, msg).await,
+        }
+    }
+}
@@ -0,0 +1,897 @@
+//! Replication protocol: ZooKeeper Atomic Broadcast (ZAB).
+//!
+//! Primary-order atomic broadcast with its three phases after leader
+//! election: discovery, in which the prospective leader gets a quorum to
+//! accept a new epoch and learns the most up-to-date history among them;
+//! synchronization, in which followers adopt that history; and broadcast,
+//! in which the established leader proposes transactions in zxid order.
+//! Leaders are elected by ZooKeeper's fast leader election, which favors the
+//! candidate with the latest epoch and zxid. References:
+//!   - <https://marcoserafini.github.io/papers/zab.pdf>
+//!   - <https://zookeeper.apache.org/doc/current/zookeeperInternals.html>
+//!
+//! Transactions of one epoch are only accepted in zxid order without gaps,
+//! and a leader proposes nothing before a quorum has synced its history, so
+//! requests of a client session are delivered in the order the leader got
+//! them, after everything delivered in earlier epochs. A follower that misses
+//! a proposal re-syncs with its leader, like a newly joining follower.
+
+mod control;
+mod discovery;
+mod durability;
+mod election;
+mod execution;
+mod messages;
+mod recovery;
+mod request;
+
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::path::Path;
+
+use crate::client::{ClientApiStub, ClientCtrlStub, ClientId, GenericEndpoint};
+use crate::manager::{CtrlMsg, CtrlReply, CtrlRequest};
+use crate::protocols::SmrProtocol;
+use crate::server::{
+    ApiReply, ApiRequest, CommandId, CommandResult, ControlHub, ExternalApi,
+    GenericReplica, HeartbeatEvent, Heartbeater, LogActionId, ReplicaId,
+    StateMachine, StorageHub, TransportHub,
+};
+use crate::utils::SummersetError;
+
+use async_trait::async_trait;
+
+use get_size::GetSize;
+
+use serde::{Deserialize, Serialize};
+
+use tokio::sync::watch;
+use tokio::time::Duration;
+
+/// Configuration parameters struct.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReplicaConfigZab {
+    /// Client request batching interval in millisecs.
+    pub batch_interval_ms: u64,
+
+    /// Client request batching maximum batch size.
+    pub max_batch_size: usize,
+
+    /// Number of completed replies cached per client for collapsing retried
+    /// requests by idempotency key; 0 means collapsing disabled.
+    pub idem_cache_size: usize,
+
+    /// Idle timeout in secs after which a client session that sent no request
+    /// is closed and its state reclaimed; 0 means never.
+    pub session_idle_s: u64,
+
+    /// Quota in KiB of approximate key-value bytes stored per namespace (the
+    /// key prefix before the first '/'), beyond which Puts are rejected; 0
+    /// means unlimited.
+    pub ns_quota_kb: usize,
+
+    /// Path to backing log file.
+    pub backer_path: String,
+
+    /// Whether to call `fsync()`/`fdatasync()` on logger.
+    pub logger_sync: bool,
+
+    /// Min timeout of not hearing from leader (or, on the leader, from a
+    /// follower) in millisecs.
+    pub hb_hear_timeout_min: u64,
+    /// Max timeout of not hearing from leader (or, on the leader, from a
+    /// follower) in millisecs.
+    pub hb_hear_timeout_max: u64,
+
+    /// Interval of leader sending Commit heartbeats to followers.
+    pub hb_send_interval_ms: u64,
+
+    /// Disable heartbeat timer (to force a deterministic leader during
+    /// tests).
+    pub disable_hb_timer: bool,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for ReplicaConfigZab {
+    fn default() -> Self {
+        ReplicaConfigZab {
+            batch_interval_ms: 1,
+            max_batch_size: 5000,
+            idem_cache_size: 0,
+            session_idle_s: 0,
+            ns_quota_kb: 0,
+            backer_path: "/tmp/summerset.zab.wal".into(),
+            logger_sync: false,
+            hb_hear_timeout_min: 1200,
+            hb_hear_timeout_max: 2000,
+            hb_send_interval_ms: 20,
+            disable_hb_timer: false,
+        }
+    }
+}
+
+/// Epoch number type.
+pub type Epoch = u64;
+
+/// Transaction ID: epoch of the leader that proposed it and a counter within
+/// that epoch, ordered lexicographically.
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Clone,
+    Copy,
+    Default,
+    Serialize,
+    Deserialize,
+    GetSize,
+)]
+struct Zxid {
+    epoch: Epoch,
+    counter: u64,
+}
+
+/// Replica status enum, following the phases of the protocol.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Status {
+    Election,
+    Discovery,
+    Sync,
+    Broadcast,
+}
+
+/// Kind of durable logging action.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum ActionKind {
+    Append = 1,
+    Commit = 2,
+}
+
+/// Request batch type.
+type ReqBatch = Vec<(ClientId, ApiRequest)>;
+
+/// Transaction in the history: its zxid and request batch.
+type Txn = (Zxid, ReqBatch);
+
+/// Leader election vote, favoring the candidate with the latest current
+/// epoch, then the latest zxid, then the highest ID.
+#[derive(
+    Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, GetSize,
+)]
+struct Vote {
+    /// Proposed leader.
+    leader: ReplicaId,
+
+    /// Current epoch of the proposed leader.
+    epoch: Epoch,
+
+    /// Last zxid in the history of the proposed leader.
+    zxid: Zxid,
+}
+
+impl Vote {
+    /// Is this vote for a better candidate than `other`?
+    #[inline]
+    fn beats(&self, other: &Vote) -> bool {
+        (self.epoch, self.zxid, self.leader)
+            > (other.epoch, other.zxid, other.leader)
+    }
+}
+
+/// Stable storage WAL log entry type.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, GetSize)]
+enum WalEntry {
+    /// Records the latest epoch I accepted a NewEpoch of, and the epoch of
+    /// the NewLeader whose history I last adopted.
+    EpochMeta { accepted: Epoch, current: Epoch },
+
+    /// Records that the history got truncated at index `from` and then
+    /// extended with the given transactions.
+    AppendTxns { from: usize, txns: Vec<Txn> },
+
+    /// Records that transactions before `commit` got committed.
+    CommitUpTo { commit: usize },
+}
+
+/// Peer-peer message type.
+#[derive(Debug, Clone, Serialize, Deserialize, GetSize)]
+enum PeerMsg {
+    /// Leader election notification of my vote in round `round`, and
+    /// whether I am still looking for a leader.
+    Notification {
+        round: u64,
+        vote: Vote,
+        looking: bool,
+    },
+
+    /// Discovery: follower telling its prospective leader the latest epoch
+    /// it has accepted.
+    FollowerInfo { accepted: Epoch },
+
+    /// Discovery: leader proposing its new epoch.
+    NewEpoch { epoch: Epoch },
+
+    /// Discovery: follower accepting the new epoch with its current epoch
+    /// and history; `fresh` is false if it had accepted that epoch already,
+    /// in which case the ack does not count towards establishing it.
+    AckEpoch {
+        epoch: Epoch,
+        fresh: bool,
+        current: Epoch,
+        history: Vec<Txn>,
+    },
+
+    /// Synchronization: leader handing its initial history to followers.
+    NewLeader { epoch: Epoch, history: Vec<Txn> },
+
+    /// Synchronization: leader telling a follower that a quorum has synced,
+    /// so that it starts delivering.
+    UpToDate { epoch: Epoch, commit: usize },
+
+    /// Broadcast: leader proposing a transaction at history index `index`.
+    Propose {
+        epoch: Epoch,
+        index: usize,
+        txn: Txn,
+    },
+
+    /// Follower acking that its history up to index `upto` (exclusive) in
+    /// epoch `epoch` is durable; the first one in an epoch also acks its
+    /// NewLeader.
+    Ack { epoch: Epoch, upto: usize },
+
+    /// Broadcast: leader telling transactions before `commit` got
+    /// committed, also serving as heartbeat.
+    Commit { epoch: Epoch, commit: usize },
+}
+
+/// ZAB server replica module.
+pub(crate) struct ZabReplica {
+    /// Replica ID in cluster.
+    id: ReplicaId,
+
+    /// Total number of replicas in cluster.
+    population: u16,
+
+    /// Majority quorum size.
+    quorum_cnt: u16,
+
+    /// Configuration parameters struct.
+    config: ReplicaConfigZab,
+
+    /// Address string for client requests API.
+    _api_addr: SocketAddr,
+
+    /// Address string for internal peer-peer communication.
+    _p2p_addr: SocketAddr,
+
+    /// ControlHub module.
+    control_hub: ControlHub,
+
+    /// ExternalApi module.
+    external_api: ExternalApi,
+
+    /// StateMachine module.
+    state_machine: StateMachine,
+
+    /// StorageHub module.
+    storage_hub: StorageHub<WalEntry>,
+
+    /// TransportHub module.
+    transport_hub: TransportHub<PeerMsg>,
+
+    /// Heartbeater module.
+    heartbeater: Heartbeater,
+
+    /// Current status.
+    status: Status,
+
+    /// Leader I have elected, if out of election.
+    leader: Option<ReplicaId>,
+
+    /// Latest epoch I accepted a NewEpoch of (f.p in the paper).
+    accepted_epoch: Epoch,
+
+    /// Epoch of the NewLeader whose history I last adopted (f.a in the
+    /// paper).
+    current_epoch: Epoch,
+
+    /// In-memory history of transactions.
+    log: Vec<Txn>,
+
+    /// Number of committed transactions, i.e., index of the first
+    /// non-committed one.
+    commit_bar: usize,
+
+    /// Number of transactions submitted to the state machine.
+    exec_bar: usize,
+
+    /// Number of append actions submitted and not yet finished.
+    appends_inflight: usize,
+
+    /// Leader election round (the logical clock).
+    round: u64,
+
+    /// My current vote; once out of election, the one elected.
+    vote: Vote,
+
+    /// During election: votes of looking replicas in my round.
+    votes: HashMap<ReplicaId, Vote>,
+
+    /// During election: votes of replicas already out of election.
+    settled: HashMap<ReplicaId, Vote>,
+
+    /// On the leader: followers heard from within the hearing timeout.
+    alive: HashSet<ReplicaId>,
+
+    /// On the leader in discovery: accepted epochs told in FollowerInfo.
+    infos: HashMap<ReplicaId, Epoch>,
+
+    /// On the leader in discovery: whether I have proposed my new epoch.
+    epoch_proposed: bool,
+
+    /// On the leader in discovery: fresh AckEpoch contents received, as
+    /// pairs of (current epoch, history).
+    epoch_acks: HashMap<ReplicaId, (Epoch, Vec<Txn>)>,
+
+    /// On the leader during synchronization: length of the initial history.
+    sync_len: usize,
+
+    /// On the leader: number of transactions each replica has acked in my
+    /// epoch.
+    acked: HashMap<ReplicaId, usize>,
+
+    /// On the leader in broadcast: followers told of UpToDate.
+    up_to_date: HashSet<ReplicaId>,
+
+    /// Current durable WAL log file offset.
+    wal_offset: usize,
+}
+
+// ZabReplica common helpers
+impl ZabReplica {
+    /// Am I the elected leader?
+    #[inline]
+    fn is_leader(&self) -> bool {
+        self.leader == Some(self.id)
+    }
+
+    /// Zxid of the last transaction in my history.
+    #[inline]
+    fn last_zxid(&self) -> Zxid {
+        self.log.last().map(|(zxid, _)| *zxid).unwrap_or_default()
+    }
+
+    /// Compose LogActionId from history index & action kind.
+    #[inline]
+    fn make_log_action_id(index: usize, kind: ActionKind) -> LogActionId {
+        ((index << 2) | (kind as usize)) as LogActionId
+    }
+
+    /// Decompose LogActionId into history index & action kind.
+    #[inline]
+    fn split_log_action_id(log_action_id: LogActionId) -> (usize, ActionKind) {
+        let index = (log_action_id >> 2) as usize;
+        let kind = match log_action_id & ((1 << 2) - 1) {
+            1 => ActionKind::Append,
+            2 => ActionKind::Commit,
+            k => panic!("unknown log action kind num {}", k),
+        };
+        (index, kind)
+    }
+
+    /// Compose CommandId from history index & command index within.
+    #[inline]
+    fn make_command_id(index: usize, cmd_idx: usize) -> CommandId {
+        debug_assert!(index <= (u32::MAX as usize));
+        debug_assert!(cmd_idx <= (u32::MAX as usize));
+        ((index << 32) | cmd_idx) as CommandId
+    }
+
+    /// Decompose CommandId into history index & command index within.
+    #[inline]
+    fn split_command_id(command_id: CommandId) -> (usize, usize) {
+        let index = (command_id >> 32) as usize;
+        let cmd_idx = (command_id & ((1 << 32) - 1)) as usize;
+        (index, cmd_idx)
+    }
+}
+
+#[async_trait]
+impl GenericReplica for ZabReplica {
+    async fn new_and_setup(
+        api_addr: SocketAddr,
+        p2p_addr: SocketAddr,
+        manager: SocketAddr,
+        config_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        // connect to the cluster manager and get assigned a server ID
+        let mut control_hub = ControlHub::new_and_setup(manager).await?;
+        let id = control_hub.me;
+        let (api_addr, p2p_addr) =
+            control_hub.resolve_addrs(api_addr, p2p_addr)?;
+        let population = control_hub.population;
+
+        // parse protocol-specific configs
+        let config = parsed_config!(config_str => ReplicaConfigZab;
+                                    batch_interval_ms, max_batch_size,
+                                    idem_cache_size, session_idle_s,
+                                    ns_quota_kb,
+                                    backer_path, logger_sync,
+                                    hb_hear_timeout_min, hb_hear_timeout_max,
+                                    hb_send_interval_ms, disable_hb_timer)?;
+        if config.batch_interval_ms == 0 {
+            return logged_err!(
+                "invalid config.batch_interval_ms '{}'",
+                config.batch_interval_ms
+            );
+        }
+        if config.hb_hear_timeout_min == 0 {
+            return logged_err!(
+                "invalid config.hb_hear_timeout_min '{}'",
+                config.hb_hear_timeout_min
+            );
+        }
+        if config.hb_hear_timeout_max < config.hb_hear_timeout_min {
+            return logged_err!(
+                "invalid config.hb_hear_timeout_max '{}'",
+                config.hb_hear_timeout_max
+            );
+        }
+        if config.hb_send_interval_ms == 0 {
+            return logged_err!(
+                "invalid config.hb_send_interval_ms '{}'",
+                config.hb_send_interval_ms
+            );
+        }
+
+        // setup state machine module
+        let state_machine =
+            StateMachine::new_and_setup(id, config.ns_quota_kb * 1024).await?;
+        control_hub.inspect_keys(&state_machine);
+
+        // setup storage hub module
+        let storage_hub =
+            StorageHub::new_and_setup(id, Path::new(&config.backer_path))
+                .await?;
+
+        // setup heartbeat management module
+        let heartbeater = Heartbeater::new_and_setup(
+            id,
+            population,
+            Duration::from_millis(config.hb_hear_timeout_min),
+            Duration::from_millis(config.hb_hear_timeout_max),
+            Duration::from_millis(config.hb_send_interval_ms),
+        )?;
+
+        // setup transport hub module
+        let mut transport_hub = TransportHub::new_and_setup(
+            id,
+            population,
+            SmrProtocol::Zab,
+            p2p_addr,
+            false,
+            HashMap::new(), // no leases
+        )
+        .await?;
+
+        // ask for the list of peers to proactively connect to. Do this after
+        // transport hub has been set up, so that I will be able to accept
+        // later peer connections
+        control_hub.send_ctrl(CtrlMsg::NewServerJoin {
+            id,
+            protocol: SmrProtocol::Zab,
+            api_addr,
+            p2p_addr,
+        })?;
+        let to_peers = if let CtrlMsg::ConnectToPeers { to_peers, .. } =
+            control_hub.recv_ctrl().await?
+        {
+            to_peers
+        } else {
+            return logged_err!(Protocol; "unexpected ctrl msg type received");
+        };
+
+        // proactively connect to some peers, then wait for all population
+        // have been connected with me
+        for (peer, conn_addr) in to_peers {
+            transport_hub.connect_to_peer(peer, conn_addr).await?;
+        }
+        transport_hub.wait_for_group(population).await?;
+
+        // setup external API module, ready to take in client requests
+        let mut external_api = ExternalApi::new_and_setup(
+            id,
+            api_addr,
+            Duration::from_millis(config.batch_interval_ms),
+            config.max_batch_size,
+            config.idem_cache_size,
+            Duration::from_secs(config.session_idle_s),
+        )
+        .await?;
+        external_api.stamp_replies(&state_machine);
+
+        Ok(ZabReplica {
+            id,
+            population,
+            quorum_cnt: (population / 2) + 1,
+            config,
+            _api_addr: api_addr,
+            _p2p_addr: p2p_addr,
+            control_hub,
+            external_api,
+            state_machine,
+            storage_hub,
+            transport_hub,
+            heartbeater,
+            status: Status::Election,
+            leader: None,
+            accepted_epoch: 0,
+            current_epoch: 0,
+            log: vec![],
+            commit_bar: 0,
+            exec_bar: 0,
+            appends_inflight: 0,
+            round: 0,
+            vote: Vote {
+                leader: id,
+                epoch: 0,
+                zxid: Zxid::default(),
+            },
+            votes: HashMap::new(),
+            settled: HashMap::new(),
+            alive: HashSet::new(),
+            infos: HashMap::new(),
+            epoch_proposed: false,
+            epoch_acks: HashMap::new(),
+            sync_len: 0,
+            acked: HashMap::new(),
+            up_to_date: HashSet::new(),
+            wal_offset: 0,
+        })
+    }
+
+    async fn run(
+        &mut self,
+        mut rx_term: watch::Receiver<bool>,
+    ) -> Result<bool, SummersetError> {
+        // recover epochs & history from durable WAL log, then look for the
+        // current leader
+        self.recover_from_wal().await?;
+        self.start_election().await?;
+
+        // main event loop
+        let mut paused = false;
+        loop {
+            tokio::select! {
+                // client request batch
+                req_batch = self.external_api.get_req_batch(), if !paused => {
+                    if let Err(e) = req_batch {
+                        pf_error!("error getting req batch: {}", e);
+                        continue;
+                    }
+                    let req_batch = req_batch.unwrap();
+                    if let Err(e) = self.handle_req_batch(req_batch).await {
+                        pf_error!("error handling req batch: {}", e);
+                    }
+                },
+
+                // durable logging result
+                log_result = self.storage_hub.get_result(), if !paused => {
+                    if let Err(e) = log_result {
+                        pf_error!("error getting log result: {}", e);
+                        continue;
+                    }
+                    let (action_id, log_result) = log_result.unwrap();
+                    if let Err(e) = self.handle_log_result(action_id, log_result).await {
+                        pf_error!("error handling log result {}: {}",
+                                           action_id, e);
+                    }
+                },
+
+                // message from peer
+                msg = self.transport_hub.recv_msg(), if !paused => {
+                    if let Err(_e) = msg {
+                        // NOTE: commented out to prevent console lags
+                        //       during benchmarking
+                        // pf_error!("error receiving peer msg: {}", e);
+                        continue;
+                    }
+                    let (peer, msg) = msg.unwrap();
+                    if let Err(e) = self.handle_msg_recv(peer, msg).await {
+                        pf_error!("error handling msg recv <- {}: {}", peer, e);
+                    }
+                },
+
+                // state machine execution result
+                cmd_result = self.state_machine.get_result(), if !paused => {
+                    if let Err(e) = cmd_result {
+                        pf_error!("error getting cmd result: {}", e);
+                        continue;
+                    }
+                    let (cmd_id, cmd_result) = cmd_result.unwrap();
+                    if let Err(e) = self.handle_cmd_result(cmd_id, cmd_result).await {
+                        pf_error!("error handling cmd result {}: {}", cmd_id, e);
+                    }
+                },
+
+                // heartbeat-related event
+                hb_event = self.heartbeater.get_event(), if !paused => {
+                    if let Err(e) = hb_event {
+                        pf_error!("error getting heartbeat event: {}", e);
+                        continue;
+                    }
+                    match hb_event.unwrap() {
+                        HeartbeatEvent::HearTimeout { peer } => {
+                            if let Err(e) = self.handle_hear_timeout(peer).await {
+                                pf_error!("error handling hear timeout: {}", e);
+                            }
+                        }
+                        HeartbeatEvent::SendTicked => {
+                            if let Err(e) = self.bcast_heartbeats() {
+                                pf_error!("error broadcasting heartbeats: {}", e);
+                            }
+                        }
+                    }
+                },
+
+                // manager control message
+                ctrl_msg = self.control_hub.recv_ctrl() => {
+                    if let Err(e) = ctrl_msg {
+                        pf_error!("error getting ctrl msg: {}", e);
+                        continue;
+                    }
+                    let ctrl_msg = ctrl_msg.unwrap();
+                    match self.handle_ctrl_msg(ctrl_msg, &mut paused).await {
+                        Ok(terminate) => {
+                            if let Some(restart) = terminate {
+                                return Ok(restart);
+                            }
+                        },
+                        Err(e) => {
+                            pf_error!("error handling ctrl msg: {}", e);
+                        }
+                    }
+                },
+
+                // receiving termination signal
+                _ = rx_term.changed() => {
+                    pf_warn!("server caught termination signal");
+                    return Ok(false);
+                }
+            }
+        }
+    }
+
+    fn id(&self) -> ReplicaId {
+        self.id
+    }
+
+    fn population(&self) -> u16 {
+        self.population
+    }
+}
+
+/// Configuration parameters struct.
+#[derive(Debug, Deserialize)]
+pub struct ClientConfigZab {
+    /// Which server to pick initially.
+    pub init_server_id: ReplicaId,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for ClientConfigZab {
+    fn default() -> Self {
+        ClientConfigZab { init_server_id: 0 }
+    }
+}
+
+/// ZAB client-side module.
+pub(crate) struct ZabClient {
+    /// Client ID.
+    id: ClientId,
+
+    /// Number of servers in the cluster.
+    population: u16,
+
+    /// Configuration parameters struct.
+    _config: ClientConfigZab,
+
+    /// List of active servers information.
+    servers: HashMap<ReplicaId, SocketAddr>,
+
+    /// Current server ID to talk to.
+    server_id: ReplicaId,
+
+    /// Control API stub to the cluster manager.
+    ctrl_stub: ClientCtrlStub,
+
+    /// API stubs for communicating with servers.
+    api_stubs: HashMap<ReplicaId, ClientApiStub>,
+}
+
+#[async_trait]
+impl GenericEndpoint for ZabClient {
+    async fn new_and_setup(
+        manager: SocketAddr,
+        config_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        // connect to the cluster manager and get assigned a client ID
+        pf_debug!("connecting to manager '{}'...", manager);
+        let ctrl_stub = ClientCtrlStub::new_by_connect(manager).await?;
+        let id = ctrl_stub.id;
+
+        // parse protocol-specific configs
+        let config = parsed_config!(config_str => ClientConfigZab;
+                                    init_server_id)?;
+        let init_server_id = config.init_server_id;
+
+        Ok(ZabClient {
+            id,
+            population: 0,
+            _config: config,
+            servers: HashMap::new(),
+            server_id: init_server_id,
+            ctrl_stub,
+            api_stubs: HashMap::new(),
+        })
+    }
+
+    async fn connect(&mut self) -> Result<(), SummersetError> {
+        // disallow reconnection without leaving
+        if !self.api_stubs.is_empty() {
+            return logged_err!("reconnecting without leaving");
+        }
+
+        // ask the manager about the list of active servers
+        let mut sent =
+            self.ctrl_stub.send_req(Some(&CtrlRequest::QueryInfo))?;
+        while !sent {
+            sent = self.ctrl_stub.send_req(None)?;
+        }
+
+        let reply = self.ctrl_stub.recv_reply().await?;
+        match reply {
+            CtrlReply::QueryInfo {
+                population,
+                servers_info,
+            } => {
+                self.population = population;
+
+                // shift to a new server_id if current one not active
+                debug_assert!(!servers_info.is_empty());
+                while !servers_info.contains_key(&self.server_id)
+                    || servers_info[&self.server_id].is_paused
+                {
+                    self.server_id = (self.server_id + 1) % population;
+                }
+                // establish connection to all servers
+                self.servers = servers_info
+                    .into_iter()
+                    .map(|(id, info)| (id, info.api_addr))
+                    .collect();
+                for (&id, &server) in &self.servers {
+                    pf_debug!("connecting to server {} '{}'...", id, server);
+                    let api_stub =
+                        ClientApiStub::new_by_connect(self.id, server).await?;
+                    self.api_stubs.insert(id, api_stub);
+                }
+                Ok(())
+            }
+            _ => logged_err!(Protocol; "unexpected reply type received"),
+        }
+    }
+
+    async fn leave(&mut self, permanent: bool) -> Result<(), SummersetError> {
+        // send leave notification to all servers
+        for (id, mut api_stub) in self.api_stubs.drain() {
+            let mut sent = api_stub.send_req(Some(&ApiRequest::Leave))?;
+            while !sent {
+                sent = api_stub.send_req(None)?;
+            }
+
+            // NOTE: commented out the following wait to avoid accidental
+            //       hanging upon leaving
+            // while api_stub.recv_reply().await? != ApiReply::Leave {}
+            pf_debug!("left server connection {}", id);
+        }
+
+        // if permanently leaving, send leave notification to the manager
+        if permanent {
+            let mut sent =
+                self.ctrl_stub.send_req(Some(&CtrlRequest::Leave))?;
+            while !sent {
+                sent = self.ctrl_stub.send_req(None)?;
+            }
+
+            while self.ctrl_stub.recv_reply().await? != CtrlReply::Leave {}
+            pf_debug!("left manager connection");
+        }
+
+        Ok(())
+    }
+
+    fn send_req(
+        &mut self,
+        req: Option<&ApiRequest>,
+    ) -> Result<bool, SummersetError> {
+        if self.api_stubs.contains_key(&self.server_id) {
+            self.api_stubs
+                .get_mut(&self.server_id)
+                .unwrap()
+                .send_req(req)
+        } else {
+            Err(SummersetError::msg(format!(
+                "server_id {} not in api_stubs",
+                self.server_id
+            )))
+        }
+    }
+
+    async fn recv_reply(&mut self) -> Result<ApiReply, SummersetError> {
+        if self.api_stubs.contains_key(&self.server_id) {
+            let reply = self
+                .api_stubs
+                .get_mut(&self.server_id)
+                .unwrap()
+                .recv_reply()
+                .await?;
+
+            if let ApiReply::LeaderChange { leader } = reply {
+                // notified of a new leader; switch over to it, or keep
+                // waiting if already talking to it
+                if leader == self.server_id
+                    || !self.servers.contains_key(&leader)
+                {
+                    return self.recv_reply().await;
+                }
+                self.server_id = leader;
+                pf_debug!(
+                    "notified of new leader {} '{}'",
+                    leader,
+                    self.servers[&leader]
+                );
+            }
+
+            if let ApiReply::Reply {
+                ref result,
+                ref redirect,
+                ..
+            } = reply
+            {
+                // if the current server redirects me to a different server
+                if result.is_none() && redirect.is_some() {
+                    let redirect_id = redirect.unwrap();
+                    debug_assert!(self.servers.contains_key(&redirect_id));
+                    self.server_id = redirect_id;
+                    pf_debug!(
+                        "redirected to replica {} '{}'",
+                        redirect_id,
+                        self.servers[&redirect_id]
+                    );
+                }
+            }
+
+            Ok(reply)
+        } else {
+            Err(SummersetError::msg(format!(
+                "server_id {} not in api_stubs",
+                self.server_id
+            )))
+        }
+    }
+
+    fn id(&self) -> ClientId {
+        self.id
+    }
+
+    fn population(&self) -> u16 {
+        self.population
+    }
+
+    fn ctrl_stub(&mut self) -> &mut ClientCtrlStub {
+        &mut self.ctrl_stub
+    }
+}
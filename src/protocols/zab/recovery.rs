@@ -0,0 +1,125 @@
+//! ZAB -- recovery from WAL.
+
+use super::*;
+
+use crate::server::{ApiRequest, LogAction, LogResult};
+use crate::utils::SummersetError;
+
+// ZabReplica recovery from durable WAL log
+impl ZabReplica {
+    /// Apply a durable storage log entry for recovery.
+    async fn recover_apply_entry(
+        &mut self,
+        entry: WalEntry,
+    ) -> Result<(), SummersetError> {
+        match entry {
+            WalEntry::EpochMeta { accepted, current } => {
+                self.accepted_epoch = accepted;
+                self.current_epoch = current;
+            }
+
+            WalEntry::AppendTxns { from, txns } => {
+                if from > self.log.len() || from < self.commit_bar {
+                    return logged_err!(
+                        "append from index {} with history of {} txns",
+                        from,
+                        self.log.len()
+                    );
+                }
+                self.log.truncate(from);
+                self.log.extend(txns);
+            }
+
+            WalEntry::CommitUpTo { commit } => {
+                if commit > self.log.len() {
+                    return logged_err!(
+                        "commit up to {} with history of {} txns",
+                        commit,
+                        self.log.len()
+                    );
+                }
+                // execute commands in newly committed transactions on the
+                // state machine synchronously
+                while self.commit_bar < commit {
+                    for (_, req) in &self.log[self.commit_bar].1 {
+                        if let ApiRequest::Req { cmd, .. } = req {
+                            // using 0 as a special command ID
+                            self.state_machine
+                                .do_sync_cmd(0, cmd.clone())
+                                .await?;
+                        }
+                    }
+                    self.commit_bar += 1;
+                }
+                self.exec_bar = self.commit_bar;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Recover state from durable storage WAL log.
+    pub(super) async fn recover_from_wal(
+        &mut self,
+    ) -> Result<(), SummersetError> {
+        debug_assert_eq!(self.wal_offset, 0);
+        loop {
+            match self
+                .storage_hub
+                .do_sync_action(
+                    0, // using 0 as dummy log action ID
+                    LogAction::Read {
+                        offset: self.wal_offset,
+                    },
+                )
+                .await?
+                .1
+            {
+                LogResult::Read {
+                    entry: Some(entry),
+                    end_offset,
+                } => {
+                    self.recover_apply_entry(entry).await?;
+                    // update log offset
+                    self.wal_offset = end_offset;
+                }
+                LogResult::Read { entry: None, .. } => {
+                    // end of log reached
+                    break;
+                }
+                _ => {
+                    return logged_err!(Protocol; "unexpected log result type");
+                }
+            }
+        }
+
+        // do an extra Truncate to remove partial entry at the end if any
+        if let LogResult::Truncate {
+            offset_ok: true, ..
+        } = self
+            .storage_hub
+            .do_sync_action(
+                0, // using 0 as dummy log action ID
+                LogAction::Truncate {
+                    offset: self.wal_offset,
+                },
+            )
+            .await?
+            .1
+        {
+            if self.wal_offset > 0 {
+                pf_info!(
+                    "recovered from wal log: epoch {} txns {} commit {}",
+                    self.current_epoch,
+                    self.log.len(),
+                    self.commit_bar
+                );
+            }
+            Ok(())
+        } else {
+            logged_err!(
+                Protocol; "unexpected log result type or failed truncate"
+            )
+        }
+    }
+}
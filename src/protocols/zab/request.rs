@@ -0,0 +1,71 @@
+//! ZAB -- client request entrance.
+
+use super::*;
+
+use crate::server::{ApiReply, ApiRequest};
+use crate::utils::SummersetError;
+
+// ZabReplica client requests entrance
+impl ZabReplica {
+    /// Handler of client request batch chan recv.
+    pub(super) async fn handle_req_batch(
+        &mut self,
+        req_batch: ReqBatch,
+    ) -> Result<(), SummersetError> {
+        let batch_size = req_batch.len();
+        debug_assert!(batch_size > 0);
+        pf_debug!("got request batch of size {}", batch_size);
+
+        // if I'm not an established leader, tell clients to try on the
+        // leader I have elected, or on the next replica if none yet
+        if !self.is_leader() || self.status != Status::Broadcast {
+            let target = self
+                .leader
+                .filter(|&leader| leader != self.id)
+                .unwrap_or((self.id + 1) % self.population);
+            for (client, req) in req_batch {
+                if let ApiRequest::Req { id: req_id, .. } = req {
+                    self.external_api.send_reply(
+                        ApiReply::redirect(req_id, Some(target)),
+                        client,
+                    )?;
+                    pf_trace!(
+                        "redirected client {} to replica {}",
+                        client,
+                        target
+                    );
+                }
+            }
+            return Ok(());
+        }
+
+        // assign the next zxid of my epoch
+        let last = self.last_zxid();
+        let zxid = Zxid {
+            epoch: self.current_epoch,
+            counter: if last.epoch == self.current_epoch {
+                last.counter + 1
+            } else {
+                1
+            },
+        };
+
+        // append the batch as a new transaction to my history durably
+        let index = self.log.len();
+        let txn = (zxid, req_batch);
+        self.append_txns(index, vec![txn.clone()])?;
+
+        // send Propose messages to all followers
+        self.transport_hub.bcast_msg(
+            PeerMsg::Propose {
+                epoch: self.current_epoch,
+                index,
+                txn,
+            },
+            None,
+        )?;
+        pf_trace!("broadcast Propose messages for index {}", index);
+
+        Ok(())
+    }
+}
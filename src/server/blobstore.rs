@@ -0,0 +1,318 @@
+//! Summerset server replica-local blob store module implementation.
+//!
+//! The blob store keeps large values out of the consensus log: a value is
+//! saved as a content-addressed file `<dir>/<hash>-<len>` on the replica, and log
+//! entries carry only a short reference string in its place. References are
+//! resolved back into values right before execution; replicas missing a blob
+//! fetch it from peers through protocol-specific messages. Each blob records
+//! the highest log slot referencing it, so that blobs whose references have
+//! all been folded into a snapshot can be garbage collected.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::server::ReplicaId;
+use crate::utils::SummersetError;
+
+use get_size::GetSize;
+
+use serde::{Deserialize, Serialize};
+
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+
+/// Prefix marking a value string as a blob reference. Starts with a NUL
+/// character so that it does not clash with text values.
+const BLOB_REF_PREFIX: &str = "\0blob:";
+
+/// Capacity in bytes of the in-memory cache of recently used blobs.
+const CACHE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Reference to a blob, identified by the hash and length of its content.
+// NOTE: the 64-bit FNV-1a hash is not collision-resistant against crafted
+//       inputs; this is fine for experiments but not for untrusted clients.
+#[derive(
+    Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize, GetSize,
+)]
+pub(crate) struct BlobRef {
+    /// Hash of content.
+    hash: u64,
+
+    /// Length of content in bytes.
+    len: usize,
+}
+
+impl BlobRef {
+    /// Computes the reference of given content.
+    pub(crate) fn of(value: &str) -> Self {
+        // 64-bit FNV-1a
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in value.as_bytes() {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        BlobRef {
+            hash,
+            len: value.len(),
+        }
+    }
+
+    /// Returns whether a value string looks like a blob reference, i.e.,
+    /// whether it would be mistaken for one if carried as is.
+    #[inline]
+    pub(crate) fn looks_like_ref(value: &str) -> bool {
+        value.starts_with(BLOB_REF_PREFIX)
+    }
+
+    /// Parses a value string into a blob reference, or `None` if it is not
+    /// one.
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        let (hash, len) =
+            value.strip_prefix(BLOB_REF_PREFIX)?.split_once(':')?;
+        Some(BlobRef {
+            hash: u64::from_str_radix(hash, 16).ok()?,
+            len: len.parse().ok()?,
+        })
+    }
+
+    /// Encodes the reference as a value string to carry in place of content.
+    pub(crate) fn encode(&self) -> String {
+        format!("{}{:016x}:{}", BLOB_REF_PREFIX, self.hash, self.len)
+    }
+
+    /// Name of the file holding the blob's content.
+    fn file_name(&self) -> String {
+        format!("{:016x}-{}", self.hash, self.len)
+    }
+}
+
+impl fmt::Display for BlobRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}:{}", self.hash, self.len)
+    }
+}
+
+/// The replica-local blob store module.
+pub(crate) struct BlobStore {
+    /// My replica ID.
+    _me: ReplicaId,
+
+    /// Directory holding blob files.
+    dir: PathBuf,
+
+    /// Set of blobs present in the store.
+    present: HashSet<BlobRef>,
+
+    /// Highest log slot known to reference each blob; `usize::MAX` for
+    /// blobs saved but not yet referenced by any slot.
+    uses: HashMap<BlobRef, usize>,
+
+    /// Recently used blobs kept in memory, with total bytes below
+    /// `CACHE_BYTES`.
+    cache: HashMap<BlobRef, String>,
+}
+
+impl BlobStore {
+    /// Creates a new blob store module over directory `dir`, picking up any
+    /// blobs already in it.
+    pub(crate) async fn new_and_setup(
+        me: ReplicaId,
+        dir: &Path,
+    ) -> Result<Self, SummersetError> {
+        fs::create_dir_all(dir).await?;
+        let mut present = HashSet::new();
+        let mut entries = fs::read_dir(dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            let Some((hash, len)) =
+                name.to_str().and_then(|name| name.split_once('-'))
+            else {
+                continue;
+            };
+            if let (Ok(hash), Ok(len)) =
+                (u64::from_str_radix(hash, 16), len.parse())
+            {
+                present.insert(BlobRef { hash, len });
+            }
+        }
+
+        Ok(BlobStore {
+            _me: me,
+            dir: dir.into(),
+            present,
+            uses: HashMap::new(),
+            cache: HashMap::new(),
+        })
+    }
+
+    /// Returns whether the blob is present in the store.
+    #[inline]
+    pub(crate) fn contains(&self, blob: &BlobRef) -> bool {
+        self.present.contains(blob)
+    }
+
+    /// Keeps the blob in the in-memory cache, evicting others if needed.
+    fn cache_insert(&mut self, blob: BlobRef, value: &str) {
+        if value.len() > CACHE_BYTES {
+            return;
+        }
+        let mut cached: usize = self.cache.values().map(|v| v.len()).sum();
+        while cached + value.len() > CACHE_BYTES {
+            let victim = *self.cache.keys().next().unwrap();
+            cached -= self.cache.remove(&victim).unwrap().len();
+        }
+        self.cache.insert(blob, value.into());
+    }
+
+    /// Saves a blob durably, returning its reference. Saving a blob already
+    /// present is a no-op.
+    pub(crate) async fn put(
+        &mut self,
+        value: &str,
+    ) -> Result<BlobRef, SummersetError> {
+        let blob = BlobRef::of(value);
+        if !self.present.contains(&blob) {
+            // write to a temporary file first, so that a crash never leaves
+            // behind a partial blob under its final name; the content and
+            // then the rename are synced before the blob counts as present
+            let path = self.dir.join(blob.file_name());
+            let tmp_path = self.dir.join(format!("{}.tmp", blob.file_name()));
+            let mut file = File::create(&tmp_path).await?;
+            file.write_all(value.as_bytes()).await?;
+            file.sync_all().await?;
+            fs::rename(&tmp_path, &path).await?;
+            File::open(&self.dir).await?.sync_all().await?;
+            self.present.insert(blob);
+        }
+        self.uses.entry(blob).or_insert(usize::MAX);
+        self.cache_insert(blob, value);
+        Ok(blob)
+    }
+
+    /// Saves a blob fetched from elsewhere, after checking that it matches
+    /// the expected reference.
+    pub(crate) async fn put_fetched(
+        &mut self,
+        blob: BlobRef,
+        value: &str,
+    ) -> Result<(), SummersetError> {
+        if BlobRef::of(value) != blob {
+            return logged_err!(
+                Corruption; "fetched content mismatches blob {}", blob
+            );
+        }
+        self.put(value).await?;
+        Ok(())
+    }
+
+    /// Records that the log slot references the blob.
+    pub(crate) fn note_use(&mut self, blob: BlobRef, slot: usize) {
+        let used = self.uses.entry(blob).or_insert(slot);
+        if *used == usize::MAX || *used < slot {
+            *used = slot;
+        }
+    }
+
+    /// Deletes all blobs not referenced by any slot at or after `bar`, e.g.,
+    /// because the state machine image of a snapshot now covers them.
+    /// Blobs saved but not yet referenced are kept. Returns the number of
+    /// blobs deleted.
+    pub(crate) async fn collect_garbage(
+        &mut self,
+        bar: usize,
+    ) -> Result<usize, SummersetError> {
+        let garbage: Vec<BlobRef> = self
+            .present
+            .iter()
+            .filter(|blob| self.uses.get(blob).is_none_or(|&used| used < bar))
+            .copied()
+            .collect();
+        for blob in &garbage {
+            fs::remove_file(self.dir.join(blob.file_name())).await?;
+            self.present.remove(blob);
+            self.uses.remove(blob);
+            self.cache.remove(blob);
+        }
+        self.uses.retain(|_, used| *used >= bar);
+        Ok(garbage.len())
+    }
+
+    /// Reads a blob out, or `None` if not present.
+    pub(crate) async fn get(
+        &mut self,
+        blob: &BlobRef,
+    ) -> Result<Option<String>, SummersetError> {
+        if let Some(value) = self.cache.get(blob) {
+            return Ok(Some(value.clone()));
+        }
+        if !self.present.contains(blob) {
+            return Ok(None);
+        }
+        let value = fs::read_to_string(self.dir.join(blob.file_name())).await?;
+        self.cache_insert(*blob, &value);
+        Ok(Some(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blob_ref_encoding() {
+        let blob = BlobRef::of("Jose");
+        assert_eq!(BlobRef::parse(&blob.encode()), Some(blob));
+        assert_ne!(BlobRef::of("Jose"), BlobRef::of("Jos"));
+        assert_eq!(BlobRef::parse("Jose"), None);
+        assert_eq!(BlobRef::parse("\0blob:xyz:4"), None);
+        assert!(BlobRef::looks_like_ref("\0blob:xyz:4"));
+        assert!(!BlobRef::looks_like_ref("Jose"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn put_get_reload() -> Result<(), SummersetError> {
+        let dir = Path::new("/tmp/test-blobs-0");
+        if fs::try_exists(dir).await? {
+            fs::remove_dir_all(dir).await?;
+        }
+        let mut store = BlobStore::new_and_setup(0, dir).await?;
+        let small = store.put("Jose").await?;
+        let large = store.put("0123456789abcdef").await?;
+        assert_eq!(store.get(&small).await?, Some("Jose".into()));
+        assert_eq!(store.get(&large).await?, Some("0123456789abcdef".into()));
+        assert!(store.put_fetched(small, "Mary").await.is_err());
+
+        let mut store = BlobStore::new_and_setup(0, dir).await?;
+        assert!(store.contains(&small) && store.contains(&large));
+        assert_eq!(store.get(&large).await?, Some("0123456789abcdef".into()));
+        assert_eq!(store.get(&BlobRef::of("Mary")).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn collect_garbage() -> Result<(), SummersetError> {
+        let dir = Path::new("/tmp/test-blobs-1");
+        if fs::try_exists(dir).await? {
+            fs::remove_dir_all(dir).await?;
+        }
+        let mut store = BlobStore::new_and_setup(0, dir).await?;
+        let old = store.put("Jose").await?;
+        let new = store.put("Mary").await?;
+        let unused = store.put("Lily").await?;
+        store.note_use(old, 3);
+        store.note_use(new, 3);
+        store.note_use(new, 7);
+        assert_eq!(store.collect_garbage(5).await?, 1);
+        assert!(!store.contains(&old) && store.get(&old).await?.is_none());
+        assert!(store.contains(&new) && store.contains(&unused));
+
+        // blobs left over from before a restart are garbage unless some
+        // recovered slot references them again
+        let mut store = BlobStore::new_and_setup(0, dir).await?;
+        store.note_use(new, 7);
+        assert_eq!(store.collect_garbage(5).await?, 1);
+        assert!(store.contains(&new) && !store.contains(&unused));
+        Ok(())
+    }
+}
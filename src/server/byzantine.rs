@@ -0,0 +1,95 @@
+//! Summerset server simulated Byzantine message faults (testing only).
+//!
+//! When enabled through the `byzantine-hooks` feature and configured with
+//! nonzero probabilities, every peer messenger may corrupt, reorder, or
+//! duplicate the protocol messages it sends on the bulk lane. Corruption
+//! flips a random bit in a wire piece of a message after the piece's CRC has
+//! been computed, so that it exercises the receiver's CRC check, which drops
+//! the whole message while keeping the connection. Reordering swaps a
+//! message with the one queued right after it, and duplication sends a
+//! message twice.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::utils::SummersetError;
+
+use rand::prelude::*;
+
+/// Probability of corrupting a message, as `f64` bits.
+static CORRUPT_PROB: AtomicU64 = AtomicU64::new(0);
+
+/// Probability of reordering a message, as `f64` bits.
+static REORDER_PROB: AtomicU64 = AtomicU64::new(0);
+
+/// Probability of duplicating a message, as `f64` bits.
+static DUPLICATE_PROB: AtomicU64 = AtomicU64::new(0);
+
+/// Sets the probabilities of corrupting, reordering, and duplicating each
+/// peer message sent in this process. All zeros (the default) disable the
+/// hooks. Should be called once at startup.
+pub fn set_byzantine_hooks(
+    corrupt: f64,
+    reorder: f64,
+    duplicate: f64,
+) -> Result<(), SummersetError> {
+    for prob in [corrupt, reorder, duplicate] {
+        if !(0.0..=1.0).contains(&prob) {
+            return logged_err!(Config; "invalid fault probability {}", prob);
+        }
+    }
+    CORRUPT_PROB.store(corrupt.to_bits(), Ordering::Relaxed);
+    REORDER_PROB.store(reorder.to_bits(), Ordering::Relaxed);
+    DUPLICATE_PROB.store(duplicate.to_bits(), Ordering::Relaxed);
+    if corrupt > 0.0 || reorder > 0.0 || duplicate > 0.0 {
+        pf_warn!(
+            "byzantine hooks on: corrupt {} reorder {} duplicate {}",
+            corrupt,
+            reorder,
+            duplicate
+        );
+    }
+    Ok(())
+}
+
+/// Flips a coin with given probability stored as `f64` bits.
+#[inline]
+fn flip(prob: &AtomicU64) -> bool {
+    let prob = f64::from_bits(prob.load(Ordering::Relaxed));
+    prob > 0.0 && thread_rng().gen_bool(prob)
+}
+
+/// Should the next message be reordered?
+#[inline]
+pub(crate) fn should_reorder() -> bool {
+    flip(&REORDER_PROB)
+}
+
+/// Should the next message be duplicated?
+#[inline]
+pub(crate) fn should_duplicate() -> bool {
+    flip(&DUPLICATE_PROB)
+}
+
+/// Corrupts a wire piece of a message by flipping a random bit, if the coin
+/// says so. Returns true if corrupted.
+pub(crate) fn maybe_corrupt(bytes: &mut [u8]) -> bool {
+    if bytes.is_empty() || !flip(&CORRUPT_PROB) {
+        return false;
+    }
+    let mut rng = thread_rng();
+    let idx = rng.gen_range(0..bytes.len());
+    bytes[idx] ^= 1 << rng.gen_range(0..8);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_probs() {
+        assert!(set_byzantine_hooks(1.5, 0.0, 0.0).is_err());
+        assert!(set_byzantine_hooks(0.0, -0.1, 0.0).is_err());
+        assert!(set_byzantine_hooks(0.0, 0.0, f64::NAN).is_err());
+    }
+}
@@ -1,11 +1,19 @@
 //! Summerset server control messages module implementation.
 
+use std::collections::HashMap;
 use std::net::SocketAddr;
-
-use crate::manager::CtrlMsg;
-use crate::server::ReplicaId;
+use std::path::Path;
+use std::sync::{OnceLock, RwLock};
+
+use crate::manager::{CtrlMsg, ServerStatus};
+use crate::server::netfault;
+use crate::server::{
+    panic_dump_status, KeysInspector, PeerTls, ReplicaId, ResourceMonitor,
+    StateMachine,
+};
 use crate::utils::{
-    safe_tcp_read, safe_tcp_write, tcp_connect_with_retry, SummersetError, ME,
+    exchange_codec, safe_tcp_read, safe_tcp_write, set_log_filters, set_me,
+    spawn_inherit_me, tcp_connect_with_retry, SummersetError,
 };
 
 use bytes::BytesMut;
@@ -13,8 +21,34 @@ use bytes::BytesMut;
 use tokio::io::AsyncReadExt;
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::TcpStream;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+
+/// Minimum interval between two status reports sent to the manager.
+const STATUS_REPORT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Client-facing address of the cluster manager, learned when joining and
+/// handed out to clients that bootstrap through a replica.
+pub(crate) static MANAGER_CLI_ADDR: OnceLock<SocketAddr> = OnceLock::new();
+
+/// Client-facing API addresses of all servers, as last told by the manager,
+/// also handed out to clients that bootstrap through a replica.
+static SERVERS_API_ADDRS: RwLock<Option<HashMap<ReplicaId, SocketAddr>>> =
+    RwLock::new(None);
+
+/// Returns the manager's client-facing address and the map of servers'
+/// client-facing addresses known to this process, for answering discovery
+/// probes from clients.
+pub(crate) fn discovery_info(
+) -> (Option<SocketAddr>, HashMap<ReplicaId, SocketAddr>) {
+    let servers = SERVERS_API_ADDRS
+        .read()
+        .ok()
+        .and_then(|map| map.clone())
+        .unwrap_or_default();
+    (MANAGER_CLI_ADDR.get().copied(), servers)
+}
 
 /// The manager control message handler module.
 pub(crate) struct ControlHub {
@@ -22,7 +56,10 @@ pub(crate) struct ControlHub {
     pub(crate) me: ReplicaId,
 
     /// Number of replicas in cluster.
-    pub(crate) population: u8,
+    pub(crate) population: u16,
+
+    /// API and p2p ports the manager assigned to me; 0 means not assigned.
+    assigned_ports: (u16, u16),
 
     /// Receiver side of the recv channel.
     rx_recv: mpsc::UnboundedReceiver<CtrlMsg>,
@@ -30,6 +67,18 @@ pub(crate) struct ControlHub {
     /// Sender side of the send channel.
     tx_send: mpsc::UnboundedSender<CtrlMsg>,
 
+    /// Time of the last status report sent to the manager.
+    last_status_report: Option<Instant>,
+
+    /// Sampler of my process's resource usage, attached to status reports.
+    resource_monitor: ResourceMonitor,
+
+    /// Handle for answering keys queries from my state machine, if set.
+    keys_inspector: Option<KeysInspector>,
+
+    /// Gate of my state machine's mirror shipper, if set and mirroring.
+    mirror_gate: Option<watch::Sender<bool>>,
+
     /// Control messengener task join handle.
     _control_messenger_handle: JoinHandle<()>,
 }
@@ -47,11 +96,25 @@ impl ControlHub {
         // connect to the cluster manager and receive my assigned server ID
         pf_debug!("connecting to manager '{}'...", manager);
         let mut stream = tcp_connect_with_retry(manager, 15).await?;
-        let id = stream.read_u8().await?; // first receive assigned server ID
-        let population = stream.read_u8().await?; // then receive population
+        let id = stream.read_u16().await?; // first receive assigned server ID
+        let population = stream.read_u16().await?; // then receive population
+        let cli_port = stream.read_u16().await?; // then manager's client port
+        let ca_len = stream.read_u32().await? as usize; // then cluster CA
+        let mut ca_pem = vec![0; ca_len];
+        stream.read_exact(&mut ca_pem).await?;
+        let api_port = stream.read_u16().await?; // then assigned ports, if any
+        let p2p_port = stream.read_u16().await?;
+        exchange_codec(&mut stream).await?; // lastly agree on codec
         pf_debug!("assigned server ID: {} of {}", id, population);
 
-        ME.get_or_init(|| id.to_string());
+        set_me(id.to_string());
+        MANAGER_CLI_ADDR
+            .get_or_init(|| SocketAddr::new(manager.ip(), cli_port));
+        if ca_pem.is_empty() {
+            PeerTls::check_unused()?;
+        } else {
+            PeerTls::setup(id, &ca_pem).await?;
+        }
 
         let (tx_recv, rx_recv) = mpsc::unbounded_channel();
         let (tx_send, rx_send) = mpsc::unbounded_channel();
@@ -59,36 +122,186 @@ impl ControlHub {
         let mut messenger =
             ControlHubMessengerTask::new(stream, tx_recv, rx_send);
         let control_messenger_handle =
-            tokio::spawn(async move { messenger.run().await });
+            spawn_inherit_me(async move { messenger.run().await });
 
         Ok(ControlHub {
             me: id,
             population,
+            assigned_ports: (api_port, p2p_port),
             rx_recv,
             tx_send,
+            last_status_report: None,
+            resource_monitor: ResourceMonitor::new(),
+            keys_inspector: None,
+            mirror_gate: None,
             _control_messenger_handle: control_messenger_handle,
         })
     }
 
-    /// Waits for the next control event message from cluster manager.
+    /// Waits for the next control event message from cluster manager. Log
+    /// filters switches, simulated network faults, keys queries, and servers
+    /// map updates are protocol-agnostic and handled here directly.
     pub(crate) async fn recv_ctrl(
         &mut self,
     ) -> Result<CtrlMsg, SummersetError> {
-        match self.rx_recv.recv().await {
-            Some(msg) => Ok(msg),
-            None => logged_err!("recv channel has been closed"),
+        loop {
+            match self.rx_recv.recv().await {
+                Some(CtrlMsg::SetLogFilters { filters }) => {
+                    self.handle_set_log_filters(&filters)?;
+                }
+                Some(CtrlMsg::NetFaults { blocked, delay_ms }) => {
+                    pf_warn!(
+                        "net faults set: blocked {:?} delay {} ms",
+                        blocked,
+                        delay_ms
+                    );
+                    netfault::set_net_faults(
+                        self.me,
+                        &blocked,
+                        Duration::from_millis(delay_ms),
+                    );
+                }
+                Some(CtrlMsg::ListKeys { cursor, limit }) => {
+                    self.handle_list_keys(cursor, limit);
+                }
+                Some(CtrlMsg::ServersMap { api_addrs }) => {
+                    pf_debug!("servers map updated: {:?}", api_addrs);
+                    if let Ok(mut servers) = SERVERS_API_ADDRS.write() {
+                        *servers = Some(api_addrs);
+                    }
+                }
+                Some(msg) => return Ok(msg),
+                None => return logged_err!("recv channel has been closed"),
+            }
         }
     }
 
-    /// Sends a control message to the cluster manager.
+    /// Handler of SetLogFilters control message.
+    fn handle_set_log_filters(
+        &mut self,
+        filters: &str,
+    ) -> Result<(), SummersetError> {
+        let ok = match set_log_filters(filters) {
+            Ok(level) => {
+                pf_warn!("log filters set to '{}' (max {})", filters, level);
+                true
+            }
+            Err(e) => {
+                pf_error!("error setting log filters: {}", e);
+                false
+            }
+        };
+        self.send_ctrl(CtrlMsg::LogFiltersReply { ok })
+    }
+
+    /// Handler of ListKeys control message. The query is answered by a
+    /// separate task, so that it does not get lost if my caller drops the
+    /// `recv_ctrl()` future in a select.
+    fn handle_list_keys(&self, cursor: String, limit: usize) {
+        let inspector = self.keys_inspector.clone();
+        let tx_send = self.tx_send.clone();
+        spawn_inherit_me(async move {
+            let report = match inspector {
+                Some(inspector) => {
+                    match inspector.list_keys(cursor, limit).await {
+                        Ok(report) => report,
+                        Err(e) => {
+                            pf_error!("error listing keys: {}", e);
+                            None
+                        }
+                    }
+                }
+                None => None,
+            };
+            if let Err(e) = tx_send.send(CtrlMsg::ListKeysReply { report }) {
+                pf_error!("error sending keys reply: {}", e);
+            }
+        });
+    }
+
+    /// Sends a control message to the cluster manager. Leadership changes
+    /// told to the manager also open or close my mirror shipper's gate, so
+    /// that only the leader ships; in protocols without a leader, server 0
+    /// does.
     pub(crate) fn send_ctrl(
         &mut self,
         msg: CtrlMsg,
     ) -> Result<(), SummersetError> {
+        if let Some(gate) = &self.mirror_gate {
+            match &msg {
+                CtrlMsg::NewServerJoin { protocol, .. } => {
+                    gate.send_replace(
+                        !protocol.elects_leader() && self.me == 0,
+                    );
+                }
+                CtrlMsg::LeaderStatus { step_up } => {
+                    gate.send_replace(*step_up);
+                }
+                _ => {}
+            }
+        }
         self.tx_send.send(msg).map_err(SummersetError::msg)?;
         Ok(())
     }
 
+    /// Fills in the ports the manager assigned to me for my API and p2p
+    /// addresses given with port 0.
+    pub(crate) fn resolve_addrs(
+        &self,
+        api_addr: SocketAddr,
+        p2p_addr: SocketAddr,
+    ) -> Result<(SocketAddr, SocketAddr), SummersetError> {
+        let mut addrs = [api_addr, p2p_addr];
+        let assigned = [self.assigned_ports.0, self.assigned_ports.1];
+        for (addr, port) in addrs.iter_mut().zip(assigned) {
+            if addr.port() == 0 {
+                if port == 0 {
+                    return logged_err!(
+                        Config;
+                        "port of {} not given nor assigned by manager",
+                        addr
+                    );
+                }
+                addr.set_port(port);
+            }
+        }
+        Ok((addrs[0], addrs[1]))
+    }
+
+    /// Sets the WAL file whose directory's disk usage gets included in my
+    /// status reports.
+    pub(crate) fn monitor_wal(&mut self, wal_path: &Path) {
+        self.resource_monitor.watch_wal(wal_path);
+    }
+
+    /// Sets the state machine whose keys get reported in reply to keys
+    /// queries from the manager, and whose mirror shipper, if any, gets
+    /// gated by my leadership.
+    pub(crate) fn inspect_keys(&mut self, state_machine: &StateMachine) {
+        self.keys_inspector = Some(state_machine.keys_inspector());
+        self.mirror_gate = state_machine.mirror_gate();
+    }
+
+    /// Reports my latest protocol status to the cluster manager, together
+    /// with a fresh sample of my resource usage. Reports are rate-limited,
+    /// so it is fine to call this on every heartbeat tick.
+    pub(crate) fn report_status(
+        &mut self,
+        mut status: ServerStatus,
+    ) -> Result<(), SummersetError> {
+        panic_dump_status(&status);
+        let now = Instant::now();
+        if self.last_status_report.is_some_and(|last| {
+            now.duration_since(last) < STATUS_REPORT_INTERVAL
+        }) {
+            return Ok(());
+        }
+
+        self.last_status_report = Some(now);
+        status.resources = self.resource_monitor.sample();
+        self.send_ctrl(CtrlMsg::StatusReport { status })
+    }
+
     /// Sends a control message to the cluster manager and waits for an
     /// expected reply blockingly.
     pub(crate) async fn do_sync_ctrl(
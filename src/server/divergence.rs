@@ -0,0 +1,113 @@
+//! Summerset server log divergence tracking, giving visibility into how
+//! conflicting log suffixes get resolved after partitions heal.
+//!
+//! Whenever a replica truncates or overwrites an entry of its log in favor
+//! of the leader's, it records a digest of what it had there. Once that slot
+//! commits, a background check compares the committed entry against the
+//! recorded one: if they differ, the replica had truly diverged, and the
+//! entry counts as repaired. Entries replaced by identical content, e.g.,
+//! the same batch re-proposed under a higher ballot, do not count.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+
+use crate::utils::SummersetError;
+
+use serde::Serialize;
+
+/// Computes a content digest of a log entry's payload.
+pub(crate) fn entry_digest<T: Serialize>(
+    payload: &T,
+) -> Result<u64, SummersetError> {
+    let mut hasher = DefaultHasher::new();
+    bincode::serialize(payload)?.hash(&mut hasher);
+    Ok(hasher.finish())
+}
+
+/// Tracker of log entries replaced by the leader's, awaiting verification.
+#[derive(Debug, Default)]
+pub(crate) struct DivergenceTracker {
+    /// Map from slot -> digest of the local entry replaced there, for slots
+    /// not committed yet.
+    pending: BTreeMap<usize, u64>,
+
+    /// Number of entries verified to have diverged and been repaired.
+    repaired: u64,
+}
+
+impl DivergenceTracker {
+    /// Creates a new divergence tracker.
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that my entry at `slot` with payload digest `digest` got
+    /// truncated or overwritten. If the slot is replaced multiple times
+    /// before committing, the earliest content is kept.
+    pub(crate) fn record(&mut self, slot: usize, digest: u64) {
+        self.pending.entry(slot).or_insert(digest);
+    }
+
+    /// Total number of entries verified to have diverged and been repaired.
+    #[inline]
+    pub(crate) fn repaired(&self) -> u64 {
+        self.repaired
+    }
+
+    /// Verifies recorded slots below `commit_bar` against the committed
+    /// entries, whose payload digests are given by `committed`; slots whose
+    /// entry is no longer available (e.g., compacted by a snapshot) are
+    /// dropped without counting. Returns the number of entries newly found
+    /// repaired.
+    pub(crate) fn verify(
+        &mut self,
+        commit_bar: usize,
+        committed: impl Fn(usize) -> Option<u64>,
+    ) -> u64 {
+        let still_pending = self.pending.split_off(&commit_bar);
+        let verified = std::mem::replace(&mut self.pending, still_pending);
+        let diverged = verified
+            .into_iter()
+            .filter(|&(slot, digest)| {
+                committed(slot).is_some_and(|other| other != digest)
+            })
+            .count() as u64;
+        if diverged > 0 {
+            self.repaired += diverged;
+            pf_info!(
+                "divergence repaired: {} entries ({} total)",
+                diverged,
+                self.repaired
+            );
+        }
+        diverged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_repaired() -> Result<(), SummersetError> {
+        let mut tracker = DivergenceTracker::new();
+        tracker.record(3, entry_digest(&"old3")?);
+        tracker.record(4, entry_digest(&"same4")?);
+        tracker.record(4, entry_digest(&"newer4")?);
+        tracker.record(7, entry_digest(&"old7")?);
+        assert_eq!(tracker.pending.len(), 3);
+        let committed = |slot| match slot {
+            3 => entry_digest(&"new3").ok(),
+            4 => entry_digest(&"same4").ok(),
+            7 => entry_digest(&"new7").ok(),
+            _ => None,
+        };
+        assert_eq!(tracker.verify(5, committed), 1);
+        assert_eq!(tracker.pending.len(), 1);
+        assert_eq!(tracker.verify(8, committed), 1);
+        assert_eq!(tracker.pending.len(), 0);
+        assert_eq!(tracker.repaired(), 2);
+        Ok(())
+    }
+}
@@ -1,13 +1,19 @@
 //! Summerset server external API module implementation.
 
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-use crate::client::ClientId;
-use crate::server::{Command, CommandResult, ReplicaId};
+use crate::client::{ClientId, DiscoveryReply, DISCOVERY_CLIENT_ID};
+use crate::server::{
+    discovery_info, Command, CommandResult, ReplicaId, StateMachine,
+};
 use crate::utils::{
-    safe_tcp_read, safe_tcp_write, tcp_bind_with_retry, Bitmap, SummersetError,
+    encode, exchange_codec, max_msg_bytes, msg_within_limit,
+    safe_tcp_read_bounded, safe_tcp_write, spawn_inherit_me,
+    tcp_bind_with_retry, Bitmap, ConfNum, HlcStamp, QueueGauge, SummersetError,
 };
 
 use get_size::GetSize;
@@ -16,17 +22,35 @@ use bytes::BytesMut;
 
 use serde::{Deserialize, Serialize};
 
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::mpsc::error::TryRecvError;
-use tokio::sync::{mpsc, Notify};
+use tokio::sync::{mpsc, watch, Notify};
 use tokio::task::JoinHandle;
-use tokio::time::{self, Duration, MissedTickBehavior};
+use tokio::time::{self, Duration, Instant, MissedTickBehavior};
 
 /// External API request ID type.
 pub type RequestId = u64;
 
+/// Idempotency key type, unique per logical request of a client.
+pub type IdemKey = u64;
+
+/// Whether `Debug` requests are served in this process.
+static DEBUG_API: AtomicBool = AtomicBool::new(false);
+
+/// Enables serving `Debug` requests in this process. Meant for test
+/// deployments only. Should be called once at startup.
+pub fn set_debug_api(enabled: bool) {
+    DEBUG_API.store(enabled, Ordering::Relaxed);
+}
+
+/// Are `Debug` requests served in this process?
+#[inline]
+fn debug_api() -> bool {
+    DEBUG_API.load(Ordering::Relaxed)
+}
+
 /// Request received from client.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, GetSize)]
 pub enum ApiRequest {
@@ -37,6 +61,10 @@ pub enum ApiRequest {
 
         /// Command to be replicated and executed.
         cmd: Command,
+
+        /// Checksum of `cmd` computed by the client, if any; verified by the
+        /// state machine right before execution.
+        checksum: Option<u64>,
     },
 
     /// Responders configuration change. (only used by relevant protocols)
@@ -48,35 +76,246 @@ pub enum ApiRequest {
         delta: ConfChange,
     },
 
+    /// Regular request tagged with a driver-generated idempotency key, so
+    /// that retried or hedged copies of the same logical request can be
+    /// collapsed. Always unwrapped into a plain `Req` by the external API
+    /// module before reaching the protocol. Collapsing happens at each
+    /// server on its own: copies sent to different servers (e.g., after a
+    /// redirect) are not collapsed with each other.
+    IdemReq {
+        /// Client request ID.
+        id: RequestId,
+
+        /// Command to be replicated and executed.
+        cmd: Command,
+
+        /// Idempotency key shared by all copies of this logical request.
+        idem_key: IdemKey,
+
+        /// Checksum of `cmd` computed by the client, if any.
+        checksum: Option<u64>,
+    },
+
+    /// Regular request for which the client also wants an early `Committed`
+    /// acknowledgement once it is committed, ahead of the reply carrying its
+    /// execution result. Unwrapped into a plain `Req` by the external API
+    /// module before reaching the protocol; protocols that do not report
+    /// commits just reply once as usual.
+    AckedReq {
+        /// Client request ID.
+        id: RequestId,
+
+        /// Command to be replicated and executed.
+        cmd: Command,
+
+        /// Checksum of `cmd` computed by the client, if any.
+        checksum: Option<u64>,
+    },
+
+    /// Read of a key served straight from the receiving replica's own state
+    /// machine, without going through consensus and without any consistency
+    /// guarantee. Only accepted by protocols that enable it; meant for
+    /// observing per-replica staleness.
+    LocalRead {
+        /// Client request ID.
+        id: RequestId,
+
+        /// Key to read.
+        key: String,
+    },
+
+    /// Get request asking to be served at a chosen consistency level. Only
+    /// accepted by protocols that support multiple read paths; the reply
+    /// states the path actually taken.
+    Read {
+        /// Client request ID.
+        id: RequestId,
+
+        /// Key to read.
+        key: String,
+
+        /// Consistency level asked for.
+        consistency: ReadConsistency,
+    },
+
+    /// Query of the receiving replica's internal protocol state, so that
+    /// tests can make precise assertions on it. Only served if the debug
+    /// API is enabled and the protocol reports its state.
+    Debug {
+        /// Client request ID.
+        id: RequestId,
+    },
+
+    /// Request tagged with the config epoch the client routed it by. Refused
+    /// with `ReplyError::StaleEpoch` if the receiving replica has moved on to
+    /// a newer config; otherwise unwrapped by the external API module before
+    /// reaching the protocol.
+    Fenced {
+        /// Config epoch known to the client when sending.
+        epoch: ConfNum,
+
+        /// The request itself.
+        req: Box<ApiRequest>,
+    },
+
+    /// Multiple requests framed together in one message by the client,
+    /// taken into the same batch as a whole even beyond the maximum batch
+    /// size. Unpacked by the external API module before reaching the
+    /// protocol; nested batches are ignored.
+    Batch {
+        /// The requests in order.
+        reqs: Vec<ApiRequest>,
+    },
+
     /// Client leave notification.
     Leave,
 }
 
 impl ApiRequest {
+    /// Returns the request inside any `Fenced` wrapping.
+    #[inline]
+    pub fn unfenced(&self) -> &ApiRequest {
+        let mut req = self;
+        while let ApiRequest::Fenced { req: inner, .. } = req {
+            req = inner;
+        }
+        req
+    }
+
+    /// Client request ID of the request, if it has one.
+    #[inline]
+    pub fn req_id(&self) -> Option<RequestId> {
+        match self.unfenced() {
+            ApiRequest::Req { id, .. }
+            | ApiRequest::Conf { id, .. }
+            | ApiRequest::IdemReq { id, .. }
+            | ApiRequest::AckedReq { id, .. }
+            | ApiRequest::LocalRead { id, .. }
+            | ApiRequest::Read { id, .. }
+            | ApiRequest::Debug { id } => Some(*id),
+            _ => None,
+        }
+    }
+
     /// Is the command contained read-only? If so, returns the key queried.
     #[inline]
     pub fn read_only(&self) -> Option<&String> {
-        if let ApiRequest::Req { cmd, .. } = self {
-            cmd.read_only()
-        } else {
-            None
+        match self.unfenced() {
+            ApiRequest::Req { cmd, .. }
+            | ApiRequest::IdemReq { cmd, .. }
+            | ApiRequest::AckedReq { cmd, .. } => cmd.read_only(),
+            ApiRequest::Read { key, .. } => Some(key),
+            _ => None,
         }
     }
 
     /// Is the command contained non-read-only? If so, returns the key updated.
     #[inline]
     pub fn write_key(&self) -> Option<&String> {
-        if let ApiRequest::Req { cmd, .. } = self {
+        if let ApiRequest::Req { cmd, .. }
+        | ApiRequest::IdemReq { cmd, .. }
+        | ApiRequest::AckedReq { cmd, .. } = self.unfenced()
+        {
             cmd.write_key()
         } else {
             None
         }
     }
 
+    /// Is the command contained a range scan? If so, returns its start and
+    /// end keys.
+    #[inline]
+    pub fn scan_range(&self) -> Option<(&String, &String)> {
+        if let ApiRequest::Req { cmd, .. }
+        | ApiRequest::IdemReq { cmd, .. }
+        | ApiRequest::AckedReq { cmd, .. } = self.unfenced()
+        {
+            cmd.scan_range()
+        } else {
+            None
+        }
+    }
+
     /// Is the request a configuration change request?
     #[inline]
     pub fn conf_change(&self) -> bool {
-        matches!(self, ApiRequest::Conf { .. })
+        matches!(self.unfenced(), ApiRequest::Conf { .. })
+    }
+
+    /// Sets the owner of every lock command contained to the client the
+    /// request arrived from, overriding whatever owner the client put in.
+    fn claim_locks(&mut self, client: ClientId) {
+        match self {
+            ApiRequest::Req { cmd, .. }
+            | ApiRequest::IdemReq { cmd, .. }
+            | ApiRequest::AckedReq { cmd, .. } => {
+                if let Command::Acquire { owner, .. }
+                | Command::Release { owner, .. } = cmd
+                {
+                    *owner = client;
+                }
+            }
+            ApiRequest::Fenced { req, .. } => req.claim_locks(client),
+            ApiRequest::Batch { reqs } => {
+                for req in reqs {
+                    req.claim_locks(client);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Consistency level of a read, naming the path it is served through.
+#[derive(
+    Debug, PartialEq, Eq, Clone, Copy, Hash, Serialize, Deserialize, GetSize,
+)]
+pub enum ReadConsistency {
+    /// Served from the state machine of whichever replica receives it; may
+    /// be arbitrarily stale.
+    Local,
+
+    /// Served by the leader, locally if it holds a read lease or otherwise
+    /// through the log; linearizable.
+    Leader,
+
+    /// Served by querying a quorum of replicas for the latest committed
+    /// value, falling back to the leader if it is not yet known to be
+    /// committed; linearizable.
+    Quorum,
+
+    /// Served like `Quorum`, but if the latest write is not yet known to be
+    /// committed, waits on the receiving replica until it commits that write
+    /// (a rinse) instead of falling back to the leader; linearizable without
+    /// any leader involvement.
+    Pqr,
+}
+
+impl ReadConsistency {
+    /// Parse command line string into ReadConsistency enum.
+    pub fn parse_name(name: &str) -> Option<Self> {
+        match &name.to_lowercase()[..] {
+            "local" => Some(Self::Local),
+            "leader" => Some(Self::Leader),
+            "quorum" => Some(Self::Quorum),
+            "pqr" => Some(Self::Pqr),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ReadConsistency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ReadConsistency::Local => "local",
+                ReadConsistency::Leader => "leader",
+                ReadConsistency::Quorum => "quorum",
+                ReadConsistency::Pqr => "pqr",
+            }
+        )
     }
 }
 
@@ -144,6 +383,25 @@ pub enum ApiReply {
         /// Set if failed read-only attempt when near quorum read (or
         /// some other read-only optimization) enabled and retry indicated.
         rq_retry: Option<Command>,
+
+        /// Commit certificate of the slot holding a write, if the protocol
+        /// is configured to attach one.
+        cert: Option<CommitCert>,
+
+        /// Why the request was not served, if known, when `result` is
+        /// `None`.
+        error: Option<ReplyError>,
+
+        /// Path a `Read` request was served through, if successful.
+        read_path: Option<ReadConsistency>,
+
+        /// Config epoch of the replying replica, if the protocol tracks one.
+        conf_epoch: Option<ConfNum>,
+
+        /// HLC timestamp of the latest command the replying replica had
+        /// executed when replying, which covers the command of a successful
+        /// request.
+        hlc: Option<HlcStamp>,
     },
 
     /// Reply to responders configuration change. (only for relevant protocols)
@@ -155,8 +413,71 @@ pub enum ApiReply {
         success: bool,
     },
 
+    /// Early acknowledgement that a request sent as `AckedReq` has been
+    /// committed, i.e., will take effect; its `Reply` follows once executed.
+    Committed {
+        /// ID of the corresponding client request.
+        id: RequestId,
+    },
+
+    /// Answer of a witness to an update sent to it in parallel with the
+    /// leader, telling if the witness recorded it. (only for relevant
+    /// protocols)
+    WitnessAck {
+        /// ID of the corresponding client request.
+        id: RequestId,
+
+        /// True if recorded, i.e., the update commutes with all updates the
+        /// witness holds; false otherwise.
+        accepted: bool,
+    },
+
+    /// Reply to local read.
+    LocalRead {
+        /// ID of the corresponding client request.
+        id: RequestId,
+
+        /// Value of the key in the replica's state machine, if any.
+        value: Option<String>,
+
+        /// Replica's execution bar at the time of reading, i.e., the number
+        /// of log positions applied to its state machine.
+        exec_bar: usize,
+
+        /// HLC timestamp of the latest command the replica had executed at
+        /// the time of reading.
+        hlc: Option<HlcStamp>,
+    },
+
+    /// Reply to debug state query.
+    Debug {
+        /// ID of the corresponding client request.
+        id: RequestId,
+
+        /// Latest protocol state reported by the replica.
+        state: DebugState,
+    },
+
     /// Reply to client leave notification.
     Leave,
+
+    /// Unsolicited notification that leadership has changed, pushed to all
+    /// connected clients so that they can re-route promptly.
+    LeaderChange {
+        /// ID of the new leader.
+        leader: ReplicaId,
+    },
+
+    /// Rejection of a request, or of the reply to it, for being larger than
+    /// the server's maximum message size.
+    MsgTooLarge {
+        /// ID of the corresponding client request, or `None` if the request
+        /// was discarded unread.
+        id: Option<RequestId>,
+
+        /// Maximum message size in bytes.
+        max_bytes: usize,
+    },
 }
 
 impl ApiReply {
@@ -168,10 +489,36 @@ impl ApiReply {
             result,
             redirect: None,
             rq_retry: None,
+            cert: None,
+            error: None,
+            read_path: None,
+            conf_epoch: None,
+            hlc: None,
         }
     }
 
-    /// Creates a reply with redirect hint.
+    /// Creates a normal reply with given result and commit certificate.
+    #[inline]
+    pub fn certified(
+        id: RequestId,
+        result: Option<CommandResult>,
+        cert: CommitCert,
+    ) -> Self {
+        ApiReply::Reply {
+            id,
+            result,
+            redirect: None,
+            rq_retry: None,
+            cert: Some(cert),
+            error: None,
+            read_path: None,
+            conf_epoch: None,
+            hlc: None,
+        }
+    }
+
+    /// Creates a reply with redirect hint, telling that I am not the one to
+    /// serve the request.
     #[inline]
     pub fn redirect(id: RequestId, redirect: Option<ReplicaId>) -> Self {
         ApiReply::Reply {
@@ -179,9 +526,65 @@ impl ApiReply {
             result: None,
             redirect,
             rq_retry: None,
+            cert: None,
+            error: Some(ReplyError::NotLeader),
+            read_path: None,
+            conf_epoch: None,
+            hlc: None,
+        }
+    }
+
+    /// Creates a reply refusing the request with given error code.
+    #[inline]
+    pub fn error(id: RequestId, error: ReplyError) -> Self {
+        ApiReply::Reply {
+            id,
+            result: None,
+            redirect: None,
+            rq_retry: None,
+            cert: None,
+            error: Some(error),
+            read_path: None,
+            conf_epoch: None,
+            hlc: None,
+        }
+    }
+
+    /// Error code of this reply, if it tells that a request was not served.
+    pub fn error_code(&self) -> Option<ReplyError> {
+        match self {
+            ApiReply::Reply { error, .. } => *error,
+            ApiReply::MsgTooLarge { .. } => Some(ReplyError::TooLarge),
+            _ => None,
         }
     }
 
+    /// ID of the request this reply finally answers, if any. Early
+    /// acknowledgements such as `Committed` do not count.
+    pub fn reply_id(&self) -> Option<RequestId> {
+        match self {
+            ApiReply::Reply { id, .. }
+            | ApiReply::Conf { id, .. }
+            | ApiReply::LocalRead { id, .. }
+            | ApiReply::Debug { id, .. } => Some(*id),
+            ApiReply::MsgTooLarge { id, .. } => *id,
+            _ => None,
+        }
+    }
+
+    /// Returns a copy of this reply relabeled with a different request ID.
+    fn relabeled(&self, new_id: RequestId) -> Self {
+        let mut reply = self.clone();
+        if let ApiReply::Reply { id, .. }
+        | ApiReply::Conf { id, .. }
+        | ApiReply::LocalRead { id, .. }
+        | ApiReply::Debug { id, .. } = &mut reply
+        {
+            *id = new_id;
+        }
+        reply
+    }
+
     /// Creates a reply with rq_retry flag.
     #[inline]
     pub fn rq_retry(
@@ -194,10 +597,160 @@ impl ApiReply {
             result: None,
             redirect,
             rq_retry: Some(read_cmd),
+            cert: None,
+            error: None,
+            read_path: None,
+            conf_epoch: None,
+            hlc: None,
+        }
+    }
+}
+
+/// Machine-readable reason for a request not being served, so that clients
+/// can decide how to retry it.
+#[derive(
+    Debug, PartialEq, Eq, Clone, Copy, Hash, Serialize, Deserialize, GetSize,
+)]
+pub enum ReplyError {
+    /// Replied server is not the leader (or otherwise not the one to serve
+    /// the request); retry at the redirected server, if any, or elsewhere.
+    NotLeader,
+
+    /// Request or its reply is larger than the server's maximum message
+    /// size; retrying it unchanged will not help.
+    TooLarge,
+
+    /// Server does not support this kind of request; retrying will not help.
+    Unsupported,
+
+    /// Request was tagged with a config epoch older than the server's; retry
+    /// after refreshing the config from the manager.
+    StaleEpoch,
+}
+
+impl ReplyError {
+    /// Is it worth retrying the request unchanged?
+    #[inline]
+    pub fn retryable(&self) -> bool {
+        matches!(self, ReplyError::NotLeader)
+    }
+}
+
+impl fmt::Display for ReplyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ReplyError::NotLeader => "not-leader",
+                ReplyError::TooLarge => "too-large",
+                ReplyError::Unsupported => "unsupported",
+                ReplyError::StaleEpoch => "stale-epoch",
+            }
+        )
+    }
+}
+
+/// Certificate attesting that a write's log slot was committed by a quorum,
+/// which clients can check without trusting the replying server's word.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, GetSize)]
+pub struct CommitCert {
+    /// Log slot index of the committed instance.
+    pub slot: usize,
+
+    /// Ballot/term number the slot was committed in, in the protocol's own
+    /// encoding.
+    pub ballot: u64,
+
+    /// Replicas that accepted the slot at that ballot.
+    pub quorum: Bitmap,
+
+    /// Voting members of the configuration in effect at the slot, whose
+    /// majority must be among `quorum`.
+    pub members: Bitmap,
+}
+
+impl CommitCert {
+    /// Checks that the certificate names a majority of the voting members in
+    /// effect at its slot, out of a cluster of `population`.
+    pub fn verify(&self, population: u16) -> bool {
+        if self.quorum.size() != population || self.members.size() != population
+        {
+            return false;
         }
+        let acked = self
+            .quorum
+            .iter()
+            .filter(|&(p, flag)| flag && self.members.get(p).unwrap_or(false))
+            .count();
+        acked > self.members.count() as usize / 2
     }
 }
 
+/// View of a replica's internal protocol state, as served to `Debug`
+/// requests. Bars and ballots follow the same conventions as in
+/// `ServerStatus`.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, GetSize)]
+pub struct DebugState {
+    /// Do I think I am the current leader?
+    pub is_leader: bool,
+
+    /// Who I think the current leader is, if known.
+    pub leader: Option<ReplicaId>,
+
+    /// Highest ballot/term number seen, in the protocol's own encoding.
+    pub ballot: u64,
+
+    /// Commit progress index.
+    pub commit_bar: usize,
+
+    /// Execution progress index.
+    pub exec_bar: usize,
+
+    /// First log slot held in memory, i.e., after the latest snapshot.
+    pub start_slot: usize,
+
+    /// Number of log slots held in memory from `start_slot` on.
+    pub log_len: usize,
+
+    /// Peers (including myself) that my heartbeater considers alive.
+    pub peers_alive: Bitmap,
+}
+
+/// Time after which an in-progress request tagged with an idempotency key
+/// is given up on, so that the next copy of it gets handed to the protocol
+/// anew instead of being collapsed. The protocol may never reply to the
+/// original, e.g., if its log entry got overwritten by a new leader.
+const IDEM_PENDING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Bookkeeping of an in-progress request tagged with an idempotency key.
+#[derive(Debug)]
+struct IdemPending {
+    /// Time the latest copy of it was handed to the protocol.
+    since: Instant,
+
+    /// Request IDs of duplicate copies waiting on its reply.
+    dups: Vec<RequestId>,
+}
+
+/// Per-client bookkeeping for collapsing requests by idempotency key.
+#[derive(Debug, Default)]
+struct IdemTable {
+    /// Map from request ID of a copy handed to the protocol -> its
+    /// idempotency key.
+    req_keys: HashMap<RequestId, IdemKey>,
+
+    /// Map from idempotency key of an in-progress request -> its pending
+    /// bookkeeping.
+    pending: HashMap<IdemKey, IdemPending>,
+
+    /// Cached successful replies of recently completed requests.
+    done: HashMap<IdemKey, ApiReply>,
+
+    /// Completion order of cached replies, oldest first.
+    done_order: VecDeque<IdemKey>,
+}
+
 /// The external client-facing API module.
 pub(crate) struct ExternalApi {
     /// My replica ID.
@@ -217,6 +770,58 @@ pub(crate) struct ExternalApi {
     /// Maximum number of requests to return per batch; 0 means no limit.
     max_batch_size: usize,
 
+    /// Number of requests queued and not yet taken into a batch, sampled at
+    /// every batch tick.
+    queue_gauge: QueueGauge,
+
+    /// Leader ID most recently notified to clients.
+    notified_leader: Option<ReplicaId>,
+
+    /// Number of completed replies cached per client for collapsing
+    /// duplicate requests by idempotency key; 0 means collapsing disabled.
+    idem_cache_size: usize,
+
+    /// Map from client ID -> its idempotency bookkeeping.
+    idem_tables: HashMap<ClientId, IdemTable>,
+
+    /// Time after which an in-progress request is no longer collapsed into.
+    idem_pending_timeout: Duration,
+
+    /// Whether the protocol serves `LocalRead` requests; if not, they are
+    /// refused here without reaching the protocol.
+    local_reads: bool,
+
+    /// Whether the protocol serves `Read` requests at all consistency
+    /// levels; if not, they are refused here without reaching the protocol.
+    read_levels: bool,
+
+    /// Map from (client ID, request ID) -> read path to state in the reply
+    /// to that `Read` request.
+    read_paths: HashMap<(ClientId, RequestId), ReadConsistency>,
+
+    /// Whether the protocol reports commits of requests; if not, `AckedReq`
+    /// requests get no early acknowledgement.
+    commit_acks: bool,
+
+    /// Set of (client ID, request ID) of `AckedReq` requests still owed an
+    /// early acknowledgement.
+    acks_owed: HashSet<(ClientId, RequestId)>,
+
+    /// Latest protocol state reported for `Debug` requests, if any.
+    debug_state: Option<DebugState>,
+
+    /// Current config epoch, if the protocol tracks one; `Fenced` requests
+    /// tagged with an older epoch are refused.
+    conf_epoch: Option<ConfNum>,
+
+    /// Watch of the HLC timestamp of the latest command executed by the
+    /// state machine, if stamping replies with it.
+    applied_stamps: Option<watch::Receiver<HlcStamp>>,
+
+    /// Sender side of the expire channel, for forcing client sessions to be
+    /// closed by the client acceptor task.
+    tx_expire: mpsc::UnboundedSender<ClientId>,
+
     /// Join handle of the client acceptor task.
     _client_acceptor_handle: JoinHandle<()>,
 
@@ -232,12 +837,15 @@ pub(crate) struct ExternalApi {
 impl ExternalApi {
     /// Creates a new external API module. Spawns the client acceptor task
     /// and the batch ticker task. Creates a req channel for buffering
-    /// incoming client requests.
+    /// incoming client requests. Client sessions that send no request for
+    /// `session_idle` are closed; zero means never.
     pub(crate) async fn new_and_setup(
         me: ReplicaId,
         api_addr: SocketAddr,
         batch_interval: Duration,
         max_batch_size: usize,
+        idem_cache_size: usize,
+        session_idle: Duration,
     ) -> Result<Self, SummersetError> {
         if batch_interval < Duration::from_micros(1) {
             return logged_err!(
@@ -254,15 +862,19 @@ impl ExternalApi {
         let (client_servant_handles_write, client_servant_handles_read) =
             flashmap::new::<ClientId, JoinHandle<()>>();
 
+        let (tx_expire, rx_expire) = mpsc::unbounded_channel();
+
         let client_listener = tcp_bind_with_retry(api_addr, 15).await?;
         let mut acceptor = ExternalApiAcceptorTask::new(
             tx_req,
             client_listener,
             tx_replies_write,
             client_servant_handles_write,
+            rx_expire,
+            session_idle,
         );
         let client_acceptor_handle =
-            tokio::spawn(async move { acceptor.run().await });
+            spawn_inherit_me(async move { acceptor.run().await });
 
         let batch_notify = Arc::new(Notify::new());
         let mut batch_ticker = ExternalApiBatchTickerTask::new(
@@ -271,7 +883,7 @@ impl ExternalApi {
             batch_notify.clone(),
         );
         let batch_ticker_handle =
-            tokio::spawn(async move { batch_ticker.run().await });
+            spawn_inherit_me(async move { batch_ticker.run().await });
 
         Ok(ExternalApi {
             _me: me,
@@ -279,6 +891,20 @@ impl ExternalApi {
             tx_replies: tx_replies_read,
             batch_notify,
             max_batch_size,
+            queue_gauge: QueueGauge::new(),
+            notified_leader: None,
+            idem_cache_size,
+            idem_tables: HashMap::new(),
+            idem_pending_timeout: IDEM_PENDING_TIMEOUT,
+            local_reads: false,
+            read_levels: false,
+            read_paths: HashMap::new(),
+            commit_acks: false,
+            acks_owed: HashSet::new(),
+            debug_state: None,
+            conf_epoch: None,
+            applied_stamps: None,
+            tx_expire,
             _client_acceptor_handle: client_acceptor_handle,
             _client_servant_handles: client_servant_handles_read,
             _batch_ticker_handle: batch_ticker_handle,
@@ -291,68 +917,464 @@ impl ExternalApi {
         tx_replies_guard.contains_key(&client)
     }
 
-    /// Waits for the next batch dumping signal and collects all requests
-    /// currently in the req channel. Returns a non-empty `VecDeque` of
-    /// requests on success.
-    pub(crate) async fn get_req_batch(
+    /// Forcefully closes the sessions of given clients, if connected to me,
+    /// and drops their bookkeeping state.
+    pub(crate) fn expire_clients(
         &mut self,
-    ) -> Result<Vec<(ClientId, ApiRequest)>, SummersetError> {
-        let mut batch = Vec::with_capacity(self.max_batch_size);
-
-        // ignore ticks with an empty batch
-        while batch.is_empty() {
-            self.batch_notify.notified().await;
-
-            while self.max_batch_size == 0 || batch.len() < self.max_batch_size
-            {
-                match self.rx_req.try_recv() {
-                    Ok((client, req)) => batch.push((client, req)),
-                    Err(TryRecvError::Empty) => break,
-                    Err(e) => return Err(SummersetError::from(e)),
-                }
+        clients: &[ClientId],
+    ) -> Result<(), SummersetError> {
+        for &client in clients {
+            self.idem_tables.remove(&client);
+            if self.has_client(client) {
+                self.tx_expire.send(client).map_err(SummersetError::msg)?;
             }
         }
+        Ok(())
+    }
 
-        debug_assert!(!batch.is_empty());
-        Ok(batch)
+    /// Lets `LocalRead` requests through to the protocol, which must then
+    /// serve them.
+    #[inline]
+    pub(crate) fn enable_local_reads(&mut self) {
+        self.local_reads = true;
     }
 
-    /// Sends a reply back to client by sending to the reply channel.
-    pub(crate) fn send_reply(
+    /// Lets `Read` requests through to the protocol, which must then serve
+    /// them at their asked consistency levels.
+    #[inline]
+    pub(crate) fn enable_read_levels(&mut self) {
+        self.read_levels = true;
+    }
+
+    /// Records the path a `Read` request is being served through, to be
+    /// stated in its reply if that is a successful one.
+    #[inline]
+    pub(crate) fn tag_read_path(
         &mut self,
-        reply: ApiReply,
         client: ClientId,
-    ) -> Result<(), SummersetError> {
-        let tx_replies_guard = self.tx_replies.guard();
-        match tx_replies_guard.get(&client) {
-            Some(tx_reply) => {
-                tx_reply.send(reply).map_err(SummersetError::msg)?;
-                Ok(())
-            }
-            None => {
-                logged_err!(
-                    "client ID {} not found among active clients",
-                    client
-                )
-            }
-        }
+        req_id: RequestId,
+        path: ReadConsistency,
+    ) {
+        self.read_paths.insert((client, req_id), path);
     }
 
-    /// Broadcasts a reply to all connected clients (mostly used for testing).
-    #[allow(dead_code)]
-    pub(crate) fn bcast_reply(
+    /// Makes `AckedReq` requests owed an early acknowledgement, which the
+    /// protocol must then report through `ack_committed()`.
+    #[inline]
+    pub(crate) fn enable_commit_acks(&mut self) {
+        self.commit_acks = true;
+    }
+
+    /// Sends the early `Committed` acknowledgement of a request if it is
+    /// owed one, i.e., was sent as `AckedReq` and not yet replied to.
+    /// Clients that are no longer connected are skipped.
+    pub(crate) fn ack_committed(
         &mut self,
-        reply: ApiReply,
+        client: ClientId,
+        req_id: RequestId,
     ) -> Result<(), SummersetError> {
+        if !self.acks_owed.remove(&(client, req_id)) {
+            return Ok(());
+        }
         let tx_replies_guard = self.tx_replies.guard();
-        for tx_reply in tx_replies_guard.values() {
-            tx_reply.send(reply.clone()).map_err(SummersetError::msg)?;
+        if let Some(tx_reply) = tx_replies_guard.get(&client) {
+            if !tx_reply.is_closed() {
+                tx_reply
+                    .send(ApiReply::Committed { id: req_id })
+                    .map_err(SummersetError::msg)?;
+            }
         }
         Ok(())
     }
-}
 
-/// ExternalApi client acceptor task.
+    /// Records the latest protocol state to serve `Debug` requests with.
+    /// Ignored unless the debug API is enabled.
+    #[inline]
+    pub(crate) fn update_debug_state(&mut self, state: DebugState) {
+        if debug_api() {
+            self.debug_state = Some(state);
+        }
+    }
+
+    /// Moves on to a newer config epoch, from which on `Fenced` requests
+    /// tagged with older epochs are refused. Older epochs are ignored.
+    #[inline]
+    pub(crate) fn set_conf_epoch(&mut self, epoch: ConfNum) {
+        if self.conf_epoch.is_none_or(|e| e < epoch) {
+            pf_debug!("config epoch now {}", epoch);
+            self.conf_epoch = Some(epoch);
+        }
+    }
+
+    /// Stamps successful replies and local read replies from now on with the
+    /// HLC timestamp of the latest command executed by given state machine.
+    pub(crate) fn stamp_replies(&mut self, state_machine: &StateMachine) {
+        self.applied_stamps = Some(state_machine.applied_stamps());
+    }
+
+    /// Returns the current cap on the number of requests per batch (0 means
+    /// unlimited).
+    #[inline]
+    pub(crate) fn max_batch_size(&self) -> usize {
+        self.max_batch_size
+    }
+
+    /// Changes the cap on the number of requests per batch, taking effect
+    /// from the next batch on. Requests beyond the cap stay queued for later
+    /// batches.
+    #[inline]
+    pub(crate) fn set_max_batch_size(&mut self, max_batch_size: usize) {
+        self.max_batch_size = max_batch_size;
+    }
+
+    /// Returns the number of requests currently queued and not yet taken
+    /// into a batch.
+    #[inline]
+    pub(crate) fn pending_reqs(&self) -> usize {
+        self.rx_req.len()
+    }
+
+    /// Gets the stage name, number of requests queued and not yet taken into
+    /// a batch, and its high watermark since the previous call.
+    pub(crate) fn queue_depth_report(&mut self) -> (String, usize, usize) {
+        self.queue_gauge.observe(self.rx_req.len());
+        let (depth, peak) = self.queue_gauge.report();
+        ("external_api".into(), depth, peak)
+    }
+
+    /// Checks an incoming request against the idempotency bookkeeping.
+    /// Returns the request to be handed to the protocol, unwrapped into a
+    /// plain `Req` if tagged, or `None` if it is a duplicate that has been
+    /// collapsed, a local or leveled read that the protocol does not serve,
+    /// a fenced request tagged with a stale config epoch, a nested batch, or
+    /// a debug query, which is answered here.
+    fn filter_idem_req(
+        &mut self,
+        client: ClientId,
+        mut req: ApiRequest,
+    ) -> Result<Option<ApiRequest>, SummersetError> {
+        while let ApiRequest::Fenced { epoch, req: inner } = req {
+            if self.conf_epoch.is_some_and(|e| epoch < e) {
+                pf_debug!("refused req <- {} of stale epoch {}", client, epoch);
+                if let Some(id) = inner.req_id() {
+                    self.send_reply(
+                        ApiReply::error(id, ReplyError::StaleEpoch),
+                        client,
+                    )?;
+                }
+                return Ok(None);
+            }
+            req = *inner;
+        }
+        match req {
+            ApiRequest::LocalRead { id, .. } if !self.local_reads => {
+                self.send_reply(
+                    ApiReply::error(id, ReplyError::Unsupported),
+                    client,
+                )?;
+                return Ok(None);
+            }
+            ApiRequest::Read { id, .. } if !self.read_levels => {
+                self.send_reply(
+                    ApiReply::error(id, ReplyError::Unsupported),
+                    client,
+                )?;
+                return Ok(None);
+            }
+            ApiRequest::Debug { id } => {
+                let reply = match &self.debug_state {
+                    Some(state) => ApiReply::Debug {
+                        id,
+                        state: state.clone(),
+                    },
+                    None => ApiReply::error(id, ReplyError::Unsupported),
+                };
+                self.send_reply(reply, client)?;
+                return Ok(None);
+            }
+            ApiRequest::Batch { .. } => {
+                pf_warn!("nested req batch <- {} ignored", client);
+                return Ok(None);
+            }
+            ApiRequest::AckedReq { id, cmd, checksum } => {
+                if self.commit_acks {
+                    self.acks_owed.insert((client, id));
+                }
+                return Ok(Some(ApiRequest::Req { id, cmd, checksum }));
+            }
+            _ => {}
+        }
+        let ApiRequest::IdemReq {
+            id,
+            cmd,
+            idem_key,
+            checksum,
+        } = req
+        else {
+            return Ok(Some(req));
+        };
+        if self.idem_cache_size == 0 {
+            return Ok(Some(ApiRequest::Req { id, cmd, checksum }));
+        }
+
+        let table = self.idem_tables.entry(client).or_default();
+        if let Some(reply) = table.done.get(&idem_key) {
+            // already completed, reply with the cached result directly
+            let reply = reply.relabeled(id);
+            pf_debug!(
+                "collapsed done req {} <- {} key {}",
+                id,
+                client,
+                idem_key
+            );
+            self.send_reply(reply, client)?;
+            Ok(None)
+        } else if let Some(pending) = table
+            .pending
+            .get_mut(&idem_key)
+            .filter(|p| p.since.elapsed() < self.idem_pending_timeout)
+        {
+            // still in progress, wait for the original's reply
+            pf_debug!(
+                "collapsed pending req {} <- {} key {}",
+                id,
+                client,
+                idem_key
+            );
+            pending.dups.push(id);
+            Ok(None)
+        } else {
+            // new, or in progress for so long that its reply may never come;
+            // hand this copy to the protocol, keeping earlier duplicates
+            // waiting on whichever copy gets replied first
+            let dups = table
+                .pending
+                .remove(&idem_key)
+                .map(|p| p.dups)
+                .unwrap_or_default();
+            let timeout = self.idem_pending_timeout;
+            table.pending.retain(|_, p| p.since.elapsed() < timeout);
+            let pending = &table.pending;
+            table
+                .req_keys
+                .retain(|_, key| *key == idem_key || pending.contains_key(key));
+            table.req_keys.insert(id, idem_key);
+            table.pending.insert(
+                idem_key,
+                IdemPending {
+                    since: Instant::now(),
+                    dups,
+                },
+            );
+            Ok(Some(ApiRequest::Req { id, cmd, checksum }))
+        }
+    }
+
+    /// Checks an outgoing reply against the idempotency bookkeeping. Returns
+    /// relabeled copies of the reply owed to collapsed duplicates, caching
+    /// the reply if it is a successful one. Any copy of a request handed to
+    /// the protocol settles it, as does any final reply to it, including
+    /// error ones.
+    fn settle_idem_reply(
+        &mut self,
+        reply: &ApiReply,
+        client: ClientId,
+    ) -> Vec<ApiReply> {
+        let Some(id) = reply.reply_id() else {
+            return vec![];
+        };
+        let Some(table) = self.idem_tables.get_mut(&client) else {
+            return vec![];
+        };
+        let Some(idem_key) = table.req_keys.remove(&id) else {
+            return vec![];
+        };
+        let Some(pending) = table.pending.remove(&idem_key) else {
+            // settled already through another copy
+            return vec![];
+        };
+
+        let dups = pending.dups;
+        if let ApiReply::Reply {
+            result: Some(_),
+            rq_retry: None,
+            ..
+        } = reply
+        {
+            table.done.insert(idem_key, reply.clone());
+            table.done_order.push_back(idem_key);
+            while table.done_order.len() > self.idem_cache_size {
+                let old_key = table.done_order.pop_front().unwrap();
+                table.done.remove(&old_key);
+            }
+        }
+        dups.into_iter().map(|dup| reply.relabeled(dup)).collect()
+    }
+
+    /// Waits for the next batch dumping signal and collects all requests
+    /// currently in the req channel. Returns a non-empty `VecDeque` of
+    /// requests on success.
+    pub(crate) async fn get_req_batch(
+        &mut self,
+    ) -> Result<Vec<(ClientId, ApiRequest)>, SummersetError> {
+        let mut batch = Vec::with_capacity(self.max_batch_size);
+
+        // ignore ticks with an empty batch
+        while batch.is_empty() {
+            self.batch_notify.notified().await;
+            self.queue_gauge.observe(self.rx_req.len());
+
+            while self.max_batch_size == 0 || batch.len() < self.max_batch_size
+            {
+                match self.rx_req.try_recv() {
+                    Ok((client, ApiRequest::Batch { reqs })) => {
+                        // pre-formed batch from the client, taken as a whole
+                        for req in reqs {
+                            if let Some(req) =
+                                self.filter_idem_req(client, req)?
+                            {
+                                batch.push((client, req));
+                            }
+                        }
+                    }
+                    Ok((client, req)) => {
+                        if let Some(req) = self.filter_idem_req(client, req)? {
+                            batch.push((client, req));
+                        }
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(e) => return Err(SummersetError::from(e)),
+                }
+            }
+        }
+
+        // NOTE: idempotency bookkeeping of clients that have disconnected is
+        //       kept until the manager expires them, as they may come back
+        //       under the same identity and retry
+        if !self.read_paths.is_empty() {
+            let tx_replies_guard = self.tx_replies.guard();
+            self.read_paths.retain(|(client, _), _| {
+                tx_replies_guard
+                    .get(client)
+                    .is_some_and(|tx_reply| !tx_reply.is_closed())
+            });
+        }
+        if !self.acks_owed.is_empty() {
+            let tx_replies_guard = self.tx_replies.guard();
+            self.acks_owed.retain(|(client, _)| {
+                tx_replies_guard
+                    .get(client)
+                    .is_some_and(|tx_reply| !tx_reply.is_closed())
+            });
+        }
+
+        debug_assert!(!batch.is_empty());
+        Ok(batch)
+    }
+
+    /// Sends a reply back to client by sending to the reply channel.
+    pub(crate) fn send_reply(
+        &mut self,
+        mut reply: ApiReply,
+        client: ClientId,
+    ) -> Result<(), SummersetError> {
+        if let Some(epoch) = self.conf_epoch {
+            if let ApiReply::Reply { conf_epoch, .. } = &mut reply {
+                *conf_epoch = Some(epoch);
+            }
+        }
+        if let Some(stamps) = &self.applied_stamps {
+            match &mut reply {
+                ApiReply::Reply {
+                    result: Some(_),
+                    hlc,
+                    ..
+                }
+                | ApiReply::LocalRead { hlc, .. } => {
+                    *hlc = Some(*stamps.borrow());
+                }
+                _ => {}
+            }
+        }
+        if !self.read_paths.is_empty() {
+            if let ApiReply::Reply {
+                id,
+                result,
+                read_path,
+                ..
+            } = &mut reply
+            {
+                if let Some(path) = self.read_paths.remove(&(client, *id)) {
+                    if result.is_some() {
+                        *read_path = Some(path);
+                    }
+                }
+            }
+        }
+
+        if !self.acks_owed.is_empty() {
+            if let ApiReply::Reply { id, .. } = &reply {
+                // replied before acknowledged, e.g., refused
+                self.acks_owed.remove(&(client, *id));
+            }
+        }
+
+        let dup_replies = if self.idem_cache_size > 0 {
+            self.settle_idem_reply(&reply, client)
+        } else {
+            vec![]
+        };
+
+        let tx_replies_guard = self.tx_replies.guard();
+        match tx_replies_guard.get(&client) {
+            Some(tx_reply) => {
+                tx_reply.send(reply).map_err(SummersetError::msg)?;
+                for dup_reply in dup_replies {
+                    tx_reply.send(dup_reply).map_err(SummersetError::msg)?;
+                }
+                Ok(())
+            }
+            None => {
+                logged_err!(
+                    "client ID {} not found among active clients",
+                    client
+                )
+            }
+        }
+    }
+
+    /// Broadcasts a reply to all connected clients. Clients that have left
+    /// but not yet been garbage collected are skipped.
+    pub(crate) fn bcast_reply(
+        &mut self,
+        reply: ApiReply,
+    ) -> Result<(), SummersetError> {
+        let tx_replies_guard = self.tx_replies.guard();
+        for tx_reply in tx_replies_guard.values() {
+            if tx_reply.is_closed() {
+                continue;
+            }
+            tx_reply.send(reply.clone()).map_err(SummersetError::msg)?;
+        }
+        Ok(())
+    }
+
+    /// Pushes a leader change notification to all connected clients, unless
+    /// the same leader has already been notified.
+    pub(crate) fn notify_leader_change(
+        &mut self,
+        leader: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        if self.notified_leader == Some(leader) {
+            return Ok(());
+        }
+        self.notified_leader = Some(leader);
+        pf_debug!("notifying clients of new leader {}", leader);
+        self.bcast_reply(ApiReply::LeaderChange { leader })
+    }
+}
+
+/// ExternalApi client acceptor task.
 struct ExternalApiAcceptorTask {
     tx_req: mpsc::UnboundedSender<(ClientId, ApiRequest)>,
     tx_replies:
@@ -363,6 +1385,9 @@ struct ExternalApiAcceptorTask {
 
     tx_exit: mpsc::UnboundedSender<ClientId>,
     rx_exit: mpsc::UnboundedReceiver<ClientId>,
+
+    rx_expire: mpsc::UnboundedReceiver<ClientId>,
+    session_idle: Duration,
 }
 
 impl ExternalApiAcceptorTask {
@@ -375,6 +1400,8 @@ impl ExternalApiAcceptorTask {
             mpsc::UnboundedSender<ApiReply>,
         >,
         client_servant_handles: flashmap::WriteHandle<ClientId, JoinHandle<()>>,
+        rx_expire: mpsc::UnboundedReceiver<ClientId>,
+        session_idle: Duration,
     ) -> Self {
         // create an exit mpsc channel for getting notified about termination
         // of client servant tasks
@@ -387,6 +1414,8 @@ impl ExternalApiAcceptorTask {
             client_servant_handles,
             tx_exit,
             rx_exit,
+            rx_expire,
+            session_idle,
         }
     }
 
@@ -403,6 +1432,21 @@ impl ExternalApiAcceptorTask {
             }
         };
 
+        // a discovery probe only asks for the manager's address and the
+        // server map; answer it with what I know and hang up
+        if id == DISCOVERY_CLIENT_ID {
+            exchange_codec(&mut stream).await?;
+            let (manager, servers) = discovery_info();
+            let bytes = encode(&DiscoveryReply { manager, servers })?;
+            stream.write_u32(bytes.len() as u32).await?;
+            stream.write_all(&bytes).await?;
+            pf_debug!("answered discovery probe from '{}'", addr);
+            return Ok(());
+        }
+        if let Err(e) = exchange_codec(&mut stream).await {
+            return logged_err!("error negotiating codec with client: {}", e);
+        }
+
         let mut tx_replies_guard = self.tx_replies.guard();
         if let Some(sender) = tx_replies_guard.get(&id) {
             if sender.is_closed() {
@@ -427,9 +1471,10 @@ impl ExternalApiAcceptorTask {
             self.tx_req.clone(),
             rx_reply,
             self.tx_exit.clone(),
+            self.session_idle,
         );
         let client_servant_handle =
-            tokio::spawn(async move { servant.run().await });
+            spawn_inherit_me(async move { servant.run().await });
         let mut client_servant_handles_guard =
             self.client_servant_handles.guard();
         client_servant_handles_guard.insert(id, client_servant_handle);
@@ -457,6 +1502,22 @@ impl ExternalApiAcceptorTask {
         Ok(())
     }
 
+    /// Forcefully closes a client connection by aborting its servant task,
+    /// and removes its handles.
+    fn expire_client(&mut self, id: ClientId) -> Result<(), SummersetError> {
+        let mut client_servant_handles_guard =
+            self.client_servant_handles.guard();
+        if let Some(handle) = client_servant_handles_guard.get(&id) {
+            handle.abort();
+        }
+        client_servant_handles_guard.remove(id);
+
+        let mut tx_replies_guard = self.tx_replies.guard();
+        tx_replies_guard.remove(id);
+        pf_debug!("expired client {}", id);
+        Ok(())
+    }
+
     /// Starts the client acceptor task loop.
     async fn run(&mut self) {
         pf_debug!("client_acceptor task spawned");
@@ -489,6 +1550,13 @@ impl ExternalApiAcceptorTask {
                     ) {
                         pf_error!("error removing left client {}: {}", id, e);
                     }
+                },
+
+                // a client session to be forcefully closed
+                Some(id) = self.rx_expire.recv() => {
+                    if let Err(e) = self.expire_client(id) {
+                        pf_error!("error expiring client {}: {}", id, e);
+                    }
                 }
             }
         }
@@ -514,6 +1582,9 @@ struct ExternalApiServantTask {
     retrying: bool,
 
     tx_exit: mpsc::UnboundedSender<ClientId>,
+
+    session_idle: Duration,
+    last_active: Instant,
 }
 
 impl ExternalApiServantTask {
@@ -525,6 +1596,7 @@ impl ExternalApiServantTask {
         tx_req: mpsc::UnboundedSender<(ClientId, ApiRequest)>,
         rx_reply: mpsc::UnboundedReceiver<ApiReply>,
         tx_exit: mpsc::UnboundedSender<ClientId>,
+        session_idle: Duration,
     ) -> Self {
         let (conn_read, conn_write) = conn.into_split();
 
@@ -545,6 +1617,8 @@ impl ExternalApiServantTask {
             reply_buf_cursor,
             retrying,
             tx_exit,
+            session_idle,
+            last_active: Instant::now(),
         }
     }
 
@@ -555,8 +1629,8 @@ impl ExternalApiServantTask {
         // request itself
         req_buf: &mut BytesMut,
         conn_read: &mut OwnedReadHalf,
-    ) -> Result<ApiRequest, SummersetError> {
-        safe_tcp_read(req_buf, conn_read).await
+    ) -> Result<Option<ApiRequest>, SummersetError> {
+        safe_tcp_read_bounded(req_buf, conn_read, max_msg_bytes()).await
     }
 
     /// Writes a reply through given TcpStream.
@@ -587,7 +1661,14 @@ impl ExternalApiServantTask {
                 // gets a reply to send back
                 reply = self.rx_reply.recv(), if !self.retrying => {
                     match reply {
-                        Some(reply) => {
+                        Some(mut reply) => {
+                            if !msg_within_limit(&reply).unwrap_or(true) {
+                                pf_warn!("reply -> {} too large, rejecting", self.id);
+                                reply = ApiReply::MsgTooLarge {
+                                    id: reply.reply_id(),
+                                    max_bytes: max_msg_bytes(),
+                                };
+                            }
                             match Self::write_reply(
                                 &mut self.reply_buf,
                                 &mut self.reply_buf_cursor,
@@ -638,7 +1719,7 @@ impl ExternalApiServantTask {
                 // receives client request
                 req = Self::read_req(&mut self.req_buf, &mut self.conn_read) => {
                     match req {
-                        Ok(ApiRequest::Leave) => {
+                        Ok(Some(ApiRequest::Leave)) => {
                             // client leaving, send dummy reply and break
                             let reply = ApiReply::Leave;
                             if let Err(_e) = Self::write_reply(
@@ -654,13 +1735,44 @@ impl ExternalApiServantTask {
                             break;
                         },
 
-                        Ok(req) => {
+                        Ok(Some(mut req)) => {
                             // pf_trace!("request <- {} req {:?}", id, req);
+                            self.last_active = Instant::now();
+                            req.claim_locks(self.id);
                             if let Err(e) = self.tx_req.send((self.id, req)) {
                                 pf_error!("error sending to tx_req for {}: {}", self.id, e);
                             }
                         },
 
+                        Ok(None) => {
+                            // request discarded for being too large; tell the
+                            // client unless busy retrying a previous reply
+                            pf_warn!("request <- {} too large, discarded", self.id);
+                            self.last_active = Instant::now();
+                            if self.retrying {
+                                continue;
+                            }
+                            let reply = ApiReply::MsgTooLarge {
+                                id: None,
+                                max_bytes: max_msg_bytes(),
+                            };
+                            match Self::write_reply(
+                                &mut self.reply_buf,
+                                &mut self.reply_buf_cursor,
+                                &self.conn_write,
+                                Some(&reply),
+                            ) {
+                                Ok(true) => {}
+                                Ok(false) => {
+                                    pf_debug!("should start retrying reply send -> {}", self.id);
+                                    self.retrying = true;
+                                }
+                                Err(e) => {
+                                    pf_error!("error rejecting request <- {}: {}", self.id, e);
+                                }
+                            }
+                        },
+
                         Err(_e) => {
                             // NOTE: commented out to prevent console lags
                             //       during benchmarking
@@ -668,6 +1780,13 @@ impl ExternalApiServantTask {
                             break; // probably the client exited without `leave()`
                         }
                     }
+                },
+
+                // session idle for too long, close it
+                _ = time::sleep_until(self.last_active + self.session_idle),
+                    if !self.session_idle.is_zero() => {
+                    pf_debug!("client {} session idle expired", self.id);
+                    break;
                 }
             }
         }
@@ -719,9 +1838,85 @@ mod tests {
     use super::*;
     use crate::client::{ClientApiStub, ClientId};
     use crate::server::{Command, CommandResult};
-    use tokio::sync::Barrier;
+    use tokio::sync::{oneshot, Barrier};
     use tokio::time::{self, Duration};
 
+    #[test]
+    fn commit_cert_verify() {
+        let cert = CommitCert {
+            slot: 3,
+            ballot: 7,
+            quorum: Bitmap::from((5, vec![0, 2, 4])),
+            members: Bitmap::new(5, true),
+        };
+        assert!(cert.verify(5));
+        assert!(!cert.verify(3));
+        let cert = CommitCert {
+            slot: 3,
+            ballot: 7,
+            quorum: Bitmap::from((5, vec![0, 2])),
+            members: Bitmap::new(5, true),
+        };
+        assert!(!cert.verify(5));
+        // a majority of a shrunk configuration suffices
+        let cert = CommitCert {
+            slot: 3,
+            ballot: 7,
+            quorum: Bitmap::from((5, vec![0, 2])),
+            members: Bitmap::from((5, vec![0, 1, 2])),
+        };
+        assert!(cert.verify(5));
+        // acks from non-members do not count
+        let cert = CommitCert {
+            slot: 3,
+            ballot: 7,
+            quorum: Bitmap::from((5, vec![0, 3, 4])),
+            members: Bitmap::from((5, vec![0, 1, 2])),
+        };
+        assert!(!cert.verify(5));
+    }
+
+    #[test]
+    fn reply_error_codes() {
+        assert_eq!(ApiReply::normal(0, None).error_code(), None);
+        assert_eq!(
+            ApiReply::redirect(0, Some(1)).error_code(),
+            Some(ReplyError::NotLeader)
+        );
+        assert_eq!(
+            ApiReply::error(0, ReplyError::Unsupported).error_code(),
+            Some(ReplyError::Unsupported)
+        );
+        let reply = ApiReply::MsgTooLarge {
+            id: None,
+            max_bytes: 1024,
+        };
+        assert_eq!(reply.error_code(), Some(ReplyError::TooLarge));
+        assert!(ReplyError::NotLeader.retryable());
+        assert!(!ReplyError::Unsupported.retryable());
+        assert!(!ReplyError::StaleEpoch.retryable());
+    }
+
+    #[test]
+    fn read_consistency_names() {
+        for level in [
+            ReadConsistency::Local,
+            ReadConsistency::Leader,
+            ReadConsistency::Quorum,
+            ReadConsistency::Pqr,
+        ] {
+            assert_eq!(
+                ReadConsistency::parse_name(&level.to_string()),
+                Some(level)
+            );
+        }
+        assert_eq!(
+            ReadConsistency::parse_name("Quorum"),
+            Some(ReadConsistency::Quorum)
+        );
+        assert_eq!(ReadConsistency::parse_name("all"), None);
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn api_req_reply() -> Result<(), SummersetError> {
         let barrier = Arc::new(Barrier::new(2));
@@ -733,6 +1928,8 @@ mod tests {
                 "127.0.0.1:30000".parse()?,
                 Duration::from_millis(1),
                 0,
+                0,
+                Duration::ZERO,
             )
             .await?;
             barrier2.wait().await;
@@ -753,6 +1950,7 @@ mod tests {
                         key: "Jose".into(),
                         value: "123".into(),
                     },
+                    checksum: None,
                 }
             );
             assert_eq!(
@@ -760,6 +1958,7 @@ mod tests {
                 ApiRequest::Req {
                     id: 1,
                     cmd: Command::Get { key: "Jose".into() },
+                    checksum: None,
                 }
             );
             assert_eq!(
@@ -767,6 +1966,7 @@ mod tests {
                 ApiRequest::Req {
                     id: 1,
                     cmd: Command::Get { key: "Jose".into() },
+                    checksum: None,
                 }
             );
             // send replies to client
@@ -801,14 +2001,17 @@ mod tests {
                 key: "Jose".into(),
                 value: "123".into(),
             },
+            checksum: None,
         }))?;
         api_stub.send_req(Some(&ApiRequest::Req {
             id: 1,
             cmd: Command::Get { key: "Jose".into() },
+            checksum: None,
         }))?;
         api_stub.send_req(Some(&ApiRequest::Req {
             id: 1,
             cmd: Command::Get { key: "Jose".into() },
+            checksum: None,
         }))?;
         // recv replies from server
         assert_eq!(
@@ -842,6 +2045,8 @@ mod tests {
                 "127.0.0.1:30100".parse()?,
                 Duration::from_millis(1),
                 0,
+                0,
+                Duration::ZERO,
             )
             .await?;
             barrier2.wait().await;
@@ -862,6 +2067,7 @@ mod tests {
                         key: "Jose".into(),
                         value: "123".into(),
                     },
+                    checksum: None,
                 }
             );
             // send reply to client
@@ -890,6 +2096,7 @@ mod tests {
                         key: "Jose".into(),
                         value: "456".into(),
                     },
+                    checksum: None,
                 }
             );
             // send reply to new client
@@ -917,6 +2124,7 @@ mod tests {
                     key: "Jose".into(),
                     value: "123".into(),
                 },
+                checksum: None,
             }))?;
             // recv reply from server
             assert_eq!(
@@ -943,6 +2151,7 @@ mod tests {
                     key: "Jose".into(),
                     value: "456".into(),
                 },
+                checksum: None,
             }))?;
             // recv reply from server
             assert_eq!(
@@ -957,4 +2166,639 @@ mod tests {
         }
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn api_session_expiry() -> Result<(), SummersetError> {
+        let barrier = Arc::new(Barrier::new(2));
+        let barrier2 = barrier.clone();
+        tokio::spawn(async move {
+            // server-side
+            let mut api = ExternalApi::new_and_setup(
+                0,
+                "127.0.0.1:30540".parse()?,
+                Duration::from_millis(1),
+                0,
+                0,
+                Duration::from_millis(300),
+            )
+            .await?;
+            barrier2.wait().await;
+            // recv request from client, then let its session idle out
+            let mut reqs: Vec<(ClientId, ApiRequest)> = vec![];
+            while reqs.is_empty() {
+                let mut req_batch = api.get_req_batch().await?;
+                reqs.append(&mut req_batch);
+            }
+            assert_eq!(reqs[0].0, 2857);
+            api.send_reply(
+                ApiReply::normal(0, Some(CommandResult::Get { value: None })),
+                2857,
+            )?;
+            time::sleep(Duration::from_millis(600)).await;
+            assert!(!api.has_client(2857));
+            // recv request from new client, then expire it forcefully
+            reqs.clear();
+            while reqs.is_empty() {
+                let mut req_batch = api.get_req_batch().await?;
+                reqs.append(&mut req_batch);
+            }
+            assert_eq!(reqs[0].0, 2858);
+            api.expire_clients(&[2858])?;
+            time::sleep(Duration::from_millis(100)).await;
+            assert!(!api.has_client(2858));
+            Ok::<(), SummersetError>(())
+        });
+        // client-side
+        barrier.wait().await;
+        let mut api_stub =
+            ClientApiStub::new_by_connect(2857, "127.0.0.1:30540".parse()?)
+                .await?;
+        api_stub.send_req(Some(&ApiRequest::Req {
+            id: 0,
+            cmd: Command::Get { key: "Jose".into() },
+            checksum: None,
+        }))?;
+        assert_eq!(
+            api_stub.recv_reply().await?,
+            ApiReply::normal(0, Some(CommandResult::Get { value: None }))
+        );
+        // session closed by server after being idle
+        assert!(api_stub.recv_reply().await.is_err());
+        let mut api_stub =
+            ClientApiStub::new_by_connect(2858, "127.0.0.1:30540".parse()?)
+                .await?;
+        api_stub.send_req(Some(&ApiRequest::Req {
+            id: 0,
+            cmd: Command::Get { key: "Jose".into() },
+            checksum: None,
+        }))?;
+        // session closed by server forcefully
+        assert!(api_stub.recv_reply().await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn api_leader_change() -> Result<(), SummersetError> {
+        let barrier = Arc::new(Barrier::new(2));
+        let barrier2 = barrier.clone();
+        tokio::spawn(async move {
+            // server-side
+            let mut api = ExternalApi::new_and_setup(
+                0,
+                "127.0.0.1:30300".parse()?,
+                Duration::from_millis(1),
+                0,
+                0,
+                Duration::ZERO,
+            )
+            .await?;
+            barrier2.wait().await;
+            // wait for client to be connected
+            let mut reqs: Vec<(ClientId, ApiRequest)> = vec![];
+            while reqs.is_empty() {
+                let mut req_batch = api.get_req_batch().await?;
+                reqs.append(&mut req_batch);
+            }
+            // push leader changes, with a duplicate in between
+            api.notify_leader_change(1)?;
+            api.notify_leader_change(1)?;
+            api.notify_leader_change(2)?;
+            Ok::<(), SummersetError>(())
+        });
+        // client-side
+        barrier.wait().await;
+        let mut api_stub =
+            ClientApiStub::new_by_connect(2857, "127.0.0.1:30300".parse()?)
+                .await?;
+        api_stub.send_req(Some(&ApiRequest::Req {
+            id: 0,
+            cmd: Command::Get { key: "Jose".into() },
+            checksum: None,
+        }))?;
+        assert_eq!(
+            api_stub.recv_reply().await?,
+            ApiReply::LeaderChange { leader: 1 }
+        );
+        assert_eq!(
+            api_stub.recv_reply().await?,
+            ApiReply::LeaderChange { leader: 2 }
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn api_idem_collapse() -> Result<(), SummersetError> {
+        let barrier = Arc::new(Barrier::new(2));
+        let barrier2 = barrier.clone();
+        tokio::spawn(async move {
+            // server-side
+            let mut api = ExternalApi::new_and_setup(
+                0,
+                "127.0.0.1:30400".parse()?,
+                Duration::from_millis(1),
+                0,
+                10,
+                Duration::ZERO,
+            )
+            .await?;
+            barrier2.wait().await;
+            // recv request from client; its duplicate gets collapsed
+            let mut reqs: Vec<(ClientId, ApiRequest)> = vec![];
+            while reqs.is_empty() {
+                let mut req_batch = api.get_req_batch().await?;
+                reqs.append(&mut req_batch);
+            }
+            let client = reqs[0].0;
+            assert_eq!(
+                reqs[0].1,
+                ApiRequest::Req {
+                    id: 0,
+                    cmd: Command::Put {
+                        key: "Jose".into(),
+                        value: "123".into(),
+                    },
+                    checksum: None,
+                }
+            );
+            // send reply to client, which also answers the duplicate
+            api.send_reply(
+                ApiReply::normal(
+                    0,
+                    Some(CommandResult::Put { old_value: None }),
+                ),
+                client,
+            )?;
+            // retries are answered without reaching here, so the next
+            // request reaching here is a new one
+            reqs.clear();
+            while reqs.is_empty() {
+                let mut req_batch = api.get_req_batch().await?;
+                reqs.append(&mut req_batch);
+            }
+            assert_eq!(
+                reqs[0].1,
+                ApiRequest::Req {
+                    id: 3,
+                    cmd: Command::Get { key: "Jose".into() },
+                    checksum: None,
+                }
+            );
+            Ok::<(), SummersetError>(())
+        });
+        // client-side
+        barrier.wait().await;
+        let mut api_stub =
+            ClientApiStub::new_by_connect(2857, "127.0.0.1:30400".parse()?)
+                .await?;
+        let put = Command::Put {
+            key: "Jose".into(),
+            value: "123".into(),
+        };
+        api_stub.send_req(Some(&ApiRequest::IdemReq {
+            id: 0,
+            cmd: put.clone(),
+            idem_key: 7,
+            checksum: None,
+        }))?;
+        api_stub.send_req(Some(&ApiRequest::IdemReq {
+            id: 1,
+            cmd: put.clone(),
+            idem_key: 7,
+            checksum: None,
+        }))?;
+        assert_eq!(
+            api_stub.recv_reply().await?,
+            ApiReply::normal(0, Some(CommandResult::Put { old_value: None }))
+        );
+        assert_eq!(
+            api_stub.recv_reply().await?,
+            ApiReply::normal(1, Some(CommandResult::Put { old_value: None }))
+        );
+        api_stub.send_req(Some(&ApiRequest::IdemReq {
+            id: 2,
+            cmd: put,
+            idem_key: 7,
+            checksum: None,
+        }))?;
+        assert_eq!(
+            api_stub.recv_reply().await?,
+            ApiReply::normal(2, Some(CommandResult::Put { old_value: None }))
+        );
+        api_stub.send_req(Some(&ApiRequest::Req {
+            id: 3,
+            cmd: Command::Get { key: "Jose".into() },
+            checksum: None,
+        }))?;
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn api_idem_expire() -> Result<(), SummersetError> {
+        let barrier = Arc::new(Barrier::new(2));
+        let barrier2 = barrier.clone();
+        tokio::spawn(async move {
+            // server-side
+            let mut api = ExternalApi::new_and_setup(
+                0,
+                "127.0.0.1:30440".parse()?,
+                Duration::from_millis(1),
+                0,
+                10,
+                Duration::ZERO,
+            )
+            .await?;
+            api.idem_pending_timeout = Duration::from_millis(100);
+            barrier2.wait().await;
+            // recv request from client but never reply to it, as if lost
+            let mut reqs: Vec<(ClientId, ApiRequest)> = vec![];
+            while reqs.len() < 2 {
+                let mut req_batch = api.get_req_batch().await?;
+                reqs.append(&mut req_batch);
+            }
+            assert_eq!(reqs[0].1.req_id(), Some(0));
+            assert_eq!(reqs[1].1.req_id(), Some(2));
+            let client = reqs[0].0;
+            // the late retry got handed over anew; reply to it
+            api.send_reply(
+                ApiReply::normal(
+                    2,
+                    Some(CommandResult::Put { old_value: None }),
+                ),
+                client,
+            )?;
+            // the lost original settles nothing anymore
+            api.send_reply(ApiReply::error(0, ReplyError::NotLeader), client)?;
+            Ok::<(), SummersetError>(())
+        });
+        // client-side
+        barrier.wait().await;
+        let mut api_stub =
+            ClientApiStub::new_by_connect(2857, "127.0.0.1:30440".parse()?)
+                .await?;
+        let put = Command::Put {
+            key: "Jose".into(),
+            value: "123".into(),
+        };
+        for id in 0..3 {
+            if id == 2 {
+                // sent after the original has been pending for too long
+                time::sleep(Duration::from_millis(200)).await;
+            }
+            api_stub.send_req(Some(&ApiRequest::IdemReq {
+                id,
+                cmd: put.clone(),
+                idem_key: 7,
+                checksum: None,
+            }))?;
+        }
+        assert_eq!(
+            api_stub.recv_reply().await?,
+            ApiReply::normal(2, Some(CommandResult::Put { old_value: None }))
+        );
+        assert_eq!(
+            api_stub.recv_reply().await?,
+            ApiReply::normal(1, Some(CommandResult::Put { old_value: None }))
+        );
+        assert_eq!(
+            api_stub.recv_reply().await?,
+            ApiReply::error(0, ReplyError::NotLeader)
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn api_lock_owner() -> Result<(), SummersetError> {
+        let barrier = Arc::new(Barrier::new(2));
+        let barrier2 = barrier.clone();
+        tokio::spawn(async move {
+            // server-side
+            let mut api = ExternalApi::new_and_setup(
+                0,
+                "127.0.0.1:30450".parse()?,
+                Duration::from_millis(1),
+                0,
+                0,
+                Duration::ZERO,
+            )
+            .await?;
+            barrier2.wait().await;
+            // lock commands arrive owned by the sending client
+            let mut reqs: Vec<(ClientId, ApiRequest)> = vec![];
+            while reqs.len() < 2 {
+                let mut req_batch = api.get_req_batch().await?;
+                reqs.append(&mut req_batch);
+            }
+            assert_eq!(
+                reqs[0].1,
+                ApiRequest::Req {
+                    id: 0,
+                    cmd: Command::Acquire {
+                        key: "lk".into(),
+                        owner: 2857,
+                        ttl_ms: 1000,
+                        now_ms: 0,
+                    },
+                    checksum: None,
+                }
+            );
+            assert_eq!(
+                reqs[1].1,
+                ApiRequest::Req {
+                    id: 1,
+                    cmd: Command::Release {
+                        key: "lk".into(),
+                        owner: 2857,
+                        now_ms: 0,
+                    },
+                    checksum: None,
+                }
+            );
+            api.send_reply(
+                ApiReply::normal(
+                    1,
+                    Some(CommandResult::Release { released: true }),
+                ),
+                reqs[1].0,
+            )?;
+            Ok::<(), SummersetError>(())
+        });
+        // client-side
+        barrier.wait().await;
+        let mut api_stub =
+            ClientApiStub::new_by_connect(2857, "127.0.0.1:30450".parse()?)
+                .await?;
+        // claim to be someone else, plainly and inside a batch
+        api_stub.send_req(Some(&ApiRequest::Req {
+            id: 0,
+            cmd: Command::Acquire {
+                key: "lk".into(),
+                owner: 7,
+                ttl_ms: 1000,
+                now_ms: 0,
+            },
+            checksum: None,
+        }))?;
+        api_stub.send_req(Some(&ApiRequest::Batch {
+            reqs: vec![ApiRequest::Req {
+                id: 1,
+                cmd: Command::Release {
+                    key: "lk".into(),
+                    owner: 7,
+                    now_ms: 0,
+                },
+                checksum: None,
+            }],
+        }))?;
+        assert_eq!(
+            api_stub.recv_reply().await?,
+            ApiReply::normal(
+                1,
+                Some(CommandResult::Release { released: true })
+            )
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn api_epoch_fencing() -> Result<(), SummersetError> {
+        let barrier = Arc::new(Barrier::new(2));
+        let barrier2 = barrier.clone();
+        tokio::spawn(async move {
+            // server-side
+            let mut api = ExternalApi::new_and_setup(
+                0,
+                "127.0.0.1:30410".parse()?,
+                Duration::from_millis(1),
+                0,
+                0,
+                Duration::ZERO,
+            )
+            .await?;
+            api.set_conf_epoch(5);
+            api.set_conf_epoch(3); // older epoch ignored
+            barrier2.wait().await;
+            // the stale request gets refused without reaching here
+            let mut reqs: Vec<(ClientId, ApiRequest)> = vec![];
+            while reqs.is_empty() {
+                let mut req_batch = api.get_req_batch().await?;
+                reqs.append(&mut req_batch);
+            }
+            let client = reqs[0].0;
+            assert_eq!(
+                reqs[0].1,
+                ApiRequest::Req {
+                    id: 1,
+                    cmd: Command::Get { key: "Jose".into() },
+                    checksum: None,
+                }
+            );
+            api.send_reply(
+                ApiReply::normal(1, Some(CommandResult::Get { value: None })),
+                client,
+            )?;
+            Ok::<(), SummersetError>(())
+        });
+        // client-side
+        barrier.wait().await;
+        let mut api_stub =
+            ClientApiStub::new_by_connect(2857, "127.0.0.1:30410".parse()?)
+                .await?;
+        let get = ApiRequest::Req {
+            id: 0,
+            cmd: Command::Get { key: "Jose".into() },
+            checksum: None,
+        };
+        api_stub.send_req(Some(&ApiRequest::Fenced {
+            epoch: 4,
+            req: Box::new(get),
+        }))?;
+        let reply = api_stub.recv_reply().await?;
+        assert_eq!(reply.error_code(), Some(ReplyError::StaleEpoch));
+        assert!(matches!(
+            reply,
+            ApiReply::Reply {
+                id: 0,
+                conf_epoch: Some(5),
+                ..
+            }
+        ));
+        api_stub.send_req(Some(&ApiRequest::Fenced {
+            epoch: 5,
+            req: Box::new(ApiRequest::Req {
+                id: 1,
+                cmd: Command::Get { key: "Jose".into() },
+                checksum: None,
+            }),
+        }))?;
+        let reply = api_stub.recv_reply().await?;
+        assert!(matches!(
+            reply,
+            ApiReply::Reply {
+                id: 1,
+                result: Some(CommandResult::Get { value: None }),
+                conf_epoch: Some(5),
+                ..
+            }
+        ));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn api_reply_stamps() -> Result<(), SummersetError> {
+        let barrier = Arc::new(Barrier::new(2));
+        let barrier2 = barrier.clone();
+        let (tx_stamp, rx_stamp) = oneshot::channel();
+        tokio::spawn(async move {
+            // server-side
+            let mut sm = StateMachine::new_and_setup(0, 0).await?;
+            let mut api = ExternalApi::new_and_setup(
+                0,
+                "127.0.0.1:30430".parse()?,
+                Duration::from_millis(1),
+                0,
+                0,
+                Duration::ZERO,
+            )
+            .await?;
+            api.stamp_replies(&sm);
+            barrier2.wait().await;
+            let mut reqs: Vec<(ClientId, ApiRequest)> = vec![];
+            while reqs.len() < 2 {
+                let mut req_batch = api.get_req_batch().await?;
+                reqs.append(&mut req_batch);
+            }
+            let client = reqs[0].0;
+            let (_, result) = sm
+                .do_sync_cmd(
+                    0,
+                    Command::Put {
+                        key: "Jose".into(),
+                        value: "179".into(),
+                    },
+                )
+                .await?;
+            api.send_reply(ApiReply::normal(0, Some(result)), client)?;
+            api.send_reply(ApiReply::error(1, ReplyError::NotLeader), client)?;
+            tx_stamp.send(*sm.applied_stamps().borrow()).unwrap();
+            Ok::<(), SummersetError>(())
+        });
+        // client-side
+        barrier.wait().await;
+        let mut api_stub =
+            ClientApiStub::new_by_connect(2857, "127.0.0.1:30430".parse()?)
+                .await?;
+        for id in 0..2 {
+            api_stub.send_req(Some(&ApiRequest::Req {
+                id,
+                cmd: Command::Put {
+                    key: "Jose".into(),
+                    value: "179".into(),
+                },
+                checksum: None,
+            }))?;
+        }
+        let stamp = rx_stamp.await.map_err(SummersetError::msg)?;
+        assert!(stamp > HlcStamp::default());
+        let reply = api_stub.recv_reply().await?;
+        assert!(matches!(
+            reply,
+            ApiReply::Reply { id: 0, hlc: Some(s), .. } if s == stamp
+        ));
+        // refused requests are not stamped
+        let reply = api_stub.recv_reply().await?;
+        assert!(matches!(
+            reply,
+            ApiReply::Reply {
+                id: 1,
+                hlc: None,
+                ..
+            }
+        ));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn api_commit_acks() -> Result<(), SummersetError> {
+        let barrier = Arc::new(Barrier::new(2));
+        let barrier2 = barrier.clone();
+        tokio::spawn(async move {
+            // server-side
+            let mut api = ExternalApi::new_and_setup(
+                0,
+                "127.0.0.1:30420".parse()?,
+                Duration::from_millis(1),
+                0,
+                0,
+                Duration::ZERO,
+            )
+            .await?;
+            api.enable_commit_acks();
+            barrier2.wait().await;
+            let mut reqs: Vec<(ClientId, ApiRequest)> = vec![];
+            while reqs.len() < 2 {
+                let mut req_batch = api.get_req_batch().await?;
+                reqs.append(&mut req_batch);
+            }
+            let client = reqs[0].0;
+            assert_eq!(
+                reqs[0].1,
+                ApiRequest::Req {
+                    id: 0,
+                    cmd: Command::Put {
+                        key: "Jose".into(),
+                        value: "123".into(),
+                    },
+                    checksum: None,
+                }
+            );
+            // plain requests are owed no acknowledgement
+            api.ack_committed(client, 1)?;
+            api.ack_committed(client, 0)?;
+            api.ack_committed(client, 0)?;
+            api.send_reply(
+                ApiReply::normal(
+                    0,
+                    Some(CommandResult::Put { old_value: None }),
+                ),
+                client,
+            )?;
+            api.send_reply(
+                ApiReply::normal(1, Some(CommandResult::Get { value: None })),
+                client,
+            )?;
+            Ok::<(), SummersetError>(())
+        });
+        // client-side
+        barrier.wait().await;
+        let mut api_stub =
+            ClientApiStub::new_by_connect(2857, "127.0.0.1:30420".parse()?)
+                .await?;
+        api_stub.send_req(Some(&ApiRequest::AckedReq {
+            id: 0,
+            cmd: Command::Put {
+                key: "Jose".into(),
+                value: "123".into(),
+            },
+            checksum: None,
+        }))?;
+        api_stub.send_req(Some(&ApiRequest::Req {
+            id: 1,
+            cmd: Command::Get { key: "Jose".into() },
+            checksum: None,
+        }))?;
+        assert_eq!(api_stub.recv_reply().await?, ApiReply::Committed { id: 0 });
+        assert!(matches!(
+            api_stub.recv_reply().await?,
+            ApiReply::Reply {
+                id: 0,
+                result: Some(CommandResult::Put { old_value: None }),
+                ..
+            }
+        ));
+        assert!(matches!(
+            api_stub.recv_reply().await?,
+            ApiReply::Reply { id: 1, .. }
+        ));
+        Ok(())
+    }
 }
@@ -3,12 +3,12 @@
 use std::collections::HashMap;
 
 use crate::server::ReplicaId;
-use crate::utils::{Bitmap, SummersetError, Timer};
+use crate::utils::{new_rng, Bitmap, SummersetError, Timer};
 
 use rand::prelude::*;
 
 use tokio::sync::mpsc;
-use tokio::time::{self, Duration, Interval, MissedTickBehavior};
+use tokio::time::{self, Duration, Instant, Interval, MissedTickBehavior};
 
 /// Multiplexed heartbeat timeout events type.
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -20,6 +20,19 @@ pub(crate) enum HeartbeatEvent {
     SendTicked,
 }
 
+/// Heartbeat timing parameters overridden for a specific peer.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+struct PeerHbTiming {
+    /// Interval for sending heartbeat to the peer.
+    send_interval: Duration,
+
+    /// Minimum hearing timeout interval for the peer.
+    hear_timeout_min: Duration,
+
+    /// Maximum hearing timeout interval for the peer.
+    hear_timeout_max: Duration,
+}
+
 /// The heartbeats management module.
 //
 // TODO: make this module channel-oriented like other modules and manage more
@@ -29,7 +42,7 @@ pub(crate) struct Heartbeater {
     me: ReplicaId,
 
     /// Total number of replicas in the cluster.
-    _population: u8,
+    population: u16,
 
     /// Timer for hearing heartbeat from, say, leader.
     hear_timers: HashMap<ReplicaId, Timer>,
@@ -46,23 +59,42 @@ pub(crate) struct Heartbeater {
     /// Interval for sending heartbeat to peers.
     send_interval: Interval,
 
+    /// Default interval for sending heartbeat to peers without overrides.
+    /// `send_interval` ticks at the smallest interval of all peers.
+    default_send_interval: Duration,
+
+    /// Timing parameters overridden for specific peers, e.g., longer ones
+    /// for peers across a WAN link.
+    peer_timings: HashMap<ReplicaId, PeerHbTiming>,
+
+    /// Time of the last heartbeat sent to each peer, used only when some
+    /// peer has overridden timing.
+    last_sent: HashMap<ReplicaId, Instant>,
+
+    /// Peers targeted by the latest sending tick; `None` means all.
+    last_targets: Option<Bitmap>,
+
     /// True if sending ticks are enabled; false otherwise.
     is_sending: bool,
 
     /// Heartbeat reply counters for approximate detection of peer health.
     /// Tuple of (#hb_replied, #hb_replied seen at last send, repetition).
-    reply_cnts: HashMap<ReplicaId, (u64, u64, u8)>,
+    reply_cnts: HashMap<ReplicaId, (u64, u64, u64)>,
 
     /// Approximate health status tracking of peer replicas; this is a more
     /// conservative backup mechanism than tighter timeouts.
     peer_alive: Bitmap,
+
+    /// Random number generator for hearing timeouts, deterministic if a seed
+    /// is set through `set_rng_seed()`.
+    rng: StdRng,
 }
 
 impl Heartbeater {
     /// Creates a new heartbeats manager.
     pub(crate) fn new_and_setup(
         me: ReplicaId,
-        population: u8,
+        population: u16,
         hear_timeout_min: Duration,
         hear_timeout_max: Duration,
         send_interval: Duration,
@@ -88,6 +120,7 @@ impl Heartbeater {
             );
         }
 
+        let default_send_interval = send_interval;
         let mut send_interval = time::interval(send_interval);
         send_interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
@@ -120,18 +153,136 @@ impl Heartbeater {
 
         Ok(Heartbeater {
             me,
-            _population: population,
+            population,
             hear_timers,
             rx_timeout,
             hear_timeout_min,
             hear_timeout_max,
             send_interval,
+            default_send_interval,
+            peer_timings: HashMap::new(),
+            last_sent: HashMap::new(),
+            last_targets: None,
             is_sending: false,
             reply_cnts,
             peer_alive: Bitmap::new(population, true),
+            rng: new_rng(me, "heartbeat"),
         })
     }
 
+    /// Parses a config string of per-peer timing overrides and applies them.
+    /// The string is in format of `peer:send_ms,min_ms,max_ms` entries
+    /// separated by `/`, e.g., `2:100,3000,5000/3:100,3000,5000`; an empty
+    /// string means no overrides. The same sanity rules as for the defaults
+    /// apply to each entry.
+    pub(crate) fn set_peer_timings(
+        &mut self,
+        s: &str,
+    ) -> Result<(), SummersetError> {
+        for seg in s.split('/').map(str::trim).filter(|seg| !seg.is_empty()) {
+            let Some((peer, timing)) = seg.split_once(':') else {
+                return logged_err!(Config; "invalid peer timings string {}", s);
+            };
+            let peer = peer.trim().parse::<ReplicaId>()?;
+            let ms = timing
+                .split(',')
+                .map(|v| v.trim().parse::<u64>().map(Duration::from_millis))
+                .collect::<Result<Vec<_>, _>>()?;
+            if peer == self.me
+                || !self.hear_timers.contains_key(&peer)
+                || ms.len() != 3
+            {
+                return logged_err!(Config; "invalid peer timings string {}", s);
+            }
+            let timing = PeerHbTiming {
+                send_interval: ms[0],
+                hear_timeout_min: ms[1],
+                hear_timeout_max: ms[2],
+            };
+            if timing.hear_timeout_min < Duration::from_millis(100)
+                || timing.hear_timeout_max
+                    < timing.hear_timeout_min + Duration::from_millis(100)
+                || timing.send_interval < Duration::from_millis(1)
+                || timing.send_interval > timing.hear_timeout_max
+            {
+                return logged_err!(
+                    Config;
+                    "invalid heartbeat timing {:?} for peer {}", timing, peer
+                );
+            }
+            self.peer_timings.insert(peer, timing);
+        }
+
+        // tick at the smallest sending interval of all peers
+        let tick = self
+            .peer_timings
+            .values()
+            .map(|timing| timing.send_interval)
+            .fold(self.default_send_interval, Duration::min);
+        if tick != self.send_interval.period() {
+            self.send_interval = time::interval(tick);
+            self.send_interval
+                .set_missed_tick_behavior(MissedTickBehavior::Skip);
+        }
+        if !self.peer_timings.is_empty() {
+            pf_info!("heartbeat timing overrides: {:?}", self.peer_timings);
+        }
+        Ok(())
+    }
+
+    /// Gets the sending interval for a peer.
+    #[inline]
+    fn peer_send_interval(&self, peer: ReplicaId) -> Duration {
+        self.peer_timings
+            .get(&peer)
+            .map_or(self.default_send_interval, |t| t.send_interval)
+    }
+
+    /// Gets the hearing timeout range for a peer.
+    #[inline]
+    fn peer_hear_timeout(&self, peer: ReplicaId) -> (Duration, Duration) {
+        self.peer_timings
+            .get(&peer)
+            .map_or((self.hear_timeout_min, self.hear_timeout_max), |t| {
+                (t.hear_timeout_min, t.hear_timeout_max)
+            })
+    }
+
+    /// Called upon sending heartbeats, decides which peers are due for one.
+    /// If `all` is true, e.g., when a broadcast is forced out of band, all
+    /// peers are. Returns `None` if all peers are, which is always the case
+    /// without per-peer overrides.
+    pub(crate) fn send_targets(
+        &mut self,
+        all: bool,
+    ) -> Result<Option<Bitmap>, SummersetError> {
+        if self.peer_timings.is_empty() {
+            self.last_targets = None;
+            return Ok(None);
+        }
+
+        // allow half a tick of slack so that jitters do not skip a round
+        let now = Instant::now();
+        let slack = self.send_interval.period() / 2;
+        let mut targets = Bitmap::new(self.population, false);
+        for &peer in self.hear_timers.keys() {
+            let due = all
+                || self.last_sent.get(&peer).is_none_or(|&sent| {
+                    sent + self.peer_send_interval(peer) <= now + slack
+                });
+            if due {
+                targets.set(peer, true)?;
+                self.last_sent.insert(peer, now);
+            }
+        }
+        if all {
+            self.last_targets = None;
+            return Ok(None);
+        }
+        self.last_targets = Some(targets.clone());
+        Ok(Some(targets))
+    }
+
     /// Sets the sending flag.
     pub(crate) fn set_sending(&mut self, sending: bool) {
         self.is_sending = sending;
@@ -170,15 +321,19 @@ impl Heartbeater {
         }
     }
 
-    /// Kicks off specified timer.
-    fn kickoff_timer_inner(&self, timer: &Timer) -> Result<(), SummersetError> {
-        timer.cancel()?;
-
-        let timeout_ms = thread_rng().gen_range(
-            self.hear_timeout_min.as_millis()
-                ..=self.hear_timeout_max.as_millis(),
+    /// Kicks off the timer for a peer, which must exist.
+    fn kickoff_timer_inner(
+        &mut self,
+        peer: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        let (hear_timeout_min, hear_timeout_max) = self.peer_hear_timeout(peer);
+        let timeout_ms = self.rng.gen_range(
+            hear_timeout_min.as_millis()..=hear_timeout_max.as_millis(),
         );
         // pf_trace!("kickoff hb_hear_timer @ {} ms", timeout_ms);
+
+        let timer = &self.hear_timers[&peer];
+        timer.cancel()?;
         timer.kickoff(Duration::from_millis(timeout_ms as u64))
     }
 
@@ -190,9 +345,8 @@ impl Heartbeater {
     ) -> Result<(), SummersetError> {
         if let Some(peer) = peer {
             if peer != self.me {
-                let timer = self.hear_timers.get(&peer);
-                if let Some(timer) = timer {
-                    self.kickoff_timer_inner(timer)
+                if self.hear_timers.contains_key(&peer) {
+                    self.kickoff_timer_inner(peer)
                 } else {
                     logged_err!("heartbeat timer for peer {} not found", peer)
                 }
@@ -200,8 +354,12 @@ impl Heartbeater {
                 Ok(())
             }
         } else {
-            for timer in self.hear_timers.values() {
-                self.kickoff_timer_inner(timer)?;
+            // in ID order so that seeded timeouts are reproducible
+            let mut peers: Vec<ReplicaId> =
+                self.hear_timers.keys().copied().collect();
+            peers.sort_unstable();
+            for peer in peers {
+                self.kickoff_timer_inner(peer)?;
             }
             Ok(())
         }
@@ -240,12 +398,20 @@ impl Heartbeater {
 
     /// Called upon each broadcast, updates peers' max heartbeat reply counters
     /// and their repetitions seen, and checks if we should speculate that the
-    /// peer is down. On success, returns true if any peer death got speculated,
+    /// peer is down. Peers not targeted by the latest sending tick are left
+    /// untouched. On success, returns true if any peer death got speculated,
     /// and false otherwise.
     pub(crate) fn update_bcast_cnts(&mut self) -> Result<bool, SummersetError> {
         let mut peer_death = false;
 
         for (&peer, cnts) in self.reply_cnts.iter_mut() {
+            if self
+                .last_targets
+                .as_ref()
+                .is_some_and(|targets| !targets.get(peer).unwrap_or(false))
+            {
+                continue;
+            }
             if cnts.0 > cnts.1 {
                 // more hb replies have been received from this peer; it is
                 // probably alive
@@ -255,9 +421,16 @@ impl Heartbeater {
                 // did not receive hb reply from this peer at least for the
                 // last sent hb from me; increment repetition count
                 cnts.2 += 1;
-                let repeat_threshold = (self.hear_timeout_min.as_millis()
-                    / self.send_interval.period().as_millis())
-                    as u8;
+                let (hear_timeout_min, send_interval) = match self
+                    .peer_timings
+                    .get(&peer)
+                {
+                    Some(t) => (t.hear_timeout_min, t.send_interval),
+                    None => (self.hear_timeout_min, self.default_send_interval),
+                };
+                let repeat_threshold = (hear_timeout_min.as_millis()
+                    / send_interval.as_millis())
+                    as u64;
 
                 if cnts.2 > repeat_threshold {
                     // did not receive hb reply from this peer for too many
@@ -296,4 +469,29 @@ impl Heartbeater {
     }
 }
 
-// TODO: add Heartbeater module unit tests after fleshing it up
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn long_peer_timeout() -> Result<(), SummersetError> {
+        let mut hb = Heartbeater::new_and_setup(
+            0,
+            3,
+            Duration::from_millis(300),
+            Duration::from_millis(500),
+            Duration::from_millis(50),
+        )?;
+        // 30000 / 50 = 600 missed replies tolerated, more than a u8 holds
+        hb.set_peer_timings("1:50,30000,30100")?;
+        // the first tick only consumes the initial reply count
+        for _ in 0..=600 {
+            hb.update_bcast_cnts()?;
+        }
+        assert!(hb.peer_alive().get(1)?);
+        assert!(!hb.peer_alive().get(2)?);
+        assert!(hb.update_bcast_cnts()?);
+        assert!(!hb.peer_alive().get(1)?);
+        Ok(())
+    }
+}
@@ -3,7 +3,7 @@
 use std::collections::{HashMap, HashSet};
 
 use crate::server::ReplicaId;
-use crate::utils::{Bitmap, SummersetError, Timer};
+use crate::utils::{spawn_inherit_me, Bitmap, SummersetError, Timer};
 
 use get_size::GetSize;
 
@@ -43,6 +43,11 @@ pub(crate) enum LeaseMsg {
     /// RevokeReply is received, revocation is guaranteed to have taken effect
     /// whether or not this field is true.
     RevokeReply { held: bool },
+
+    /// Planned handoff from the lease holder: it has already dropped the
+    /// promise held from me, and asks me to start granting to peer `to` under
+    /// the higher lease number `new_num` right away.
+    Handoff { to: ReplicaId, new_num: LeaseNum },
 }
 
 /// Wrapper type for active lease operations the module wants to conduct, e.g.,
@@ -104,6 +109,12 @@ pub(crate) enum LeaseNotice {
     /// Force clear promises held from all peers.
     ClearHeld,
 
+    /// Want to voluntarily give up all promises held, and have their grantors
+    /// grant to peer `to` under the higher lease number `new_num` right away
+    /// instead of waiting on revocation or expiration. Used for planned
+    /// leader transfer, where `new_num` is the target's upcoming lease number.
+    DoHandoff { to: ReplicaId, new_num: LeaseNum },
+
     /// Received a lease-related message from a peer.
     RecvLeaseMsg { peer: ReplicaId, msg: LeaseMsg },
 
@@ -131,7 +142,7 @@ pub(crate) struct LeaseManager {
     me: ReplicaId,
 
     /// Total number of replicas in the cluster.
-    population: u8,
+    population: u16,
 
     /// Expiration timeout used as both T_guard and T_lease.
     expire_timeout: Duration,
@@ -165,7 +176,7 @@ impl LeaseManager {
     /// to feed lease messages directly in.
     pub(crate) fn new_and_setup(
         me: ReplicaId,
-        population: u8,
+        population: u16,
         expire_timeout: Duration, // serves both T_guard and T_lease
         hb_send_interval: Duration,
     ) -> Result<
@@ -209,7 +220,7 @@ impl LeaseManager {
             promises_held_write,
         );
         let lease_manager_handle =
-            tokio::spawn(async move { manager.run().await });
+            spawn_inherit_me(async move { manager.run().await });
 
         let tx_notice_clone = tx_notice.clone();
         Ok((
@@ -250,8 +261,8 @@ impl LeaseManager {
 
     /// Gets the number of promises currently held (implicitly always including
     /// self so always >= 1).
-    pub(crate) fn lease_cnt(&self) -> u8 {
-        1 + self.promises_held.guard().len() as u8
+    pub(crate) fn lease_cnt(&self) -> u16 {
+        1 + self.promises_held.guard().len() as u16
     }
 
     /// Adds a lease notice to be taken care of by the lease manager by sending
@@ -319,7 +330,7 @@ impl LeaseManager {
 /// LeaseManager manager logic task.
 struct LeaseManagerLogicTask {
     me: ReplicaId,
-    population: u8,
+    population: u16,
 
     active_num: LeaseNum,
 
@@ -344,7 +355,7 @@ impl LeaseManagerLogicTask {
     #[allow(clippy::too_many_arguments)]
     fn new(
         me: ReplicaId,
-        population: u8,
+        population: u16,
         guard_timeout: Duration,
         lease_timeout: Duration,
         tx_action: mpsc::UnboundedSender<(LeaseNum, LeaseAction)>,
@@ -500,6 +511,64 @@ impl LeaseManagerLogicTask {
         Ok(())
     }
 
+    /// Hands off all promises held to a peer. Promises are dropped before
+    /// the Handoff messages are sent, so that I never consider myself leased
+    /// once any grantor moves on to the new holder.
+    fn handle_do_handoff(
+        &mut self,
+        lease_num: LeaseNum,
+        to: ReplicaId,
+        new_num: LeaseNum,
+    ) -> Result<(), SummersetError> {
+        if to >= self.population || to == self.me {
+            return logged_err!("invalid handoff target {}", to);
+        }
+        if new_num <= lease_num {
+            return logged_err!(
+                "handoff lease_num {} not higher than {}",
+                new_num,
+                lease_num
+            );
+        }
+
+        let mut grantors = Bitmap::new(self.population, false);
+        for &peer in self.guards_held.keys() {
+            grantors.set(peer, true)?;
+        }
+        self.guards_held.clear();
+        {
+            let mut promises_held = self.promises_held.guard();
+            for peer in (0..self.population).filter(|&p| p != self.me) {
+                if promises_held.remove(peer).is_some() {
+                    grantors.set(peer, true)?;
+                }
+            }
+        }
+
+        pf_debug!("lease all held handed off @ {} => {}", lease_num, to);
+        self.tx_action
+            .send((lease_num, LeaseAction::LeaseCleared))?;
+
+        // broadcast Handoff messages to the grantors
+        if grantors.count() > 0 {
+            pf_debug!(
+                "lease bcast Handoff({}, {}) @ {} -> {:?}",
+                to,
+                new_num,
+                lease_num,
+                grantors
+            );
+            self.tx_action.send((
+                lease_num,
+                LeaseAction::BcastLeaseMsgs {
+                    peers: grantors,
+                    msg: LeaseMsg::Handoff { to, new_num },
+                },
+            ))?;
+        }
+        Ok(())
+    }
+
     /// Received a Guard message.
     fn handle_msg_guard(
         &mut self,
@@ -755,6 +824,48 @@ impl LeaseManagerLogicTask {
         Ok(())
     }
 
+    /// Received a Handoff message.
+    fn handle_msg_handoff(
+        &mut self,
+        lease_num: LeaseNum,
+        peer: ReplicaId,
+        to: ReplicaId,
+        new_num: LeaseNum,
+    ) -> Result<(), SummersetError> {
+        pf_trace!(
+            "lease recv Handoff({}, {}) @ {} <- {}",
+            to,
+            new_num,
+            lease_num,
+            peer
+        );
+
+        // the holder has already dropped what I granted it, so the grant can
+        // be removed without waiting on a Revoke round trip or expiration
+        let guarded = self.guards_sent.remove(&peer).is_some();
+        let held = self.promises_sent.guard().remove(peer).is_some();
+        if !guarded && !held {
+            // not currently granting to this peer, ignore
+            return Ok(());
+        }
+        pf_debug!("lease handoff drop @ {} -> {} => {}", lease_num, peer, to);
+        self.tx_action
+            .send((lease_num, LeaseAction::GrantRemoved { peer, held }))?;
+
+        // start granting to the new holder; goes through the notice channel
+        // so that the higher lease number gets activated the usual way
+        if to < self.population && to != self.me && new_num > lease_num {
+            self.tx_notice.send((
+                new_num,
+                LeaseNotice::NewGrants {
+                    peers: Some(Bitmap::from((self.population, vec![to]))),
+                    accept_bar: None,
+                },
+            ))?;
+        }
+        Ok(())
+    }
+
     /// Timeout reached granting lease to a peer.
     fn handle_grant_timeout(
         &mut self,
@@ -808,6 +919,9 @@ impl LeaseManagerLogicTask {
                 self.handle_do_revoke(lease_num, peers)
             }
             LeaseNotice::ClearHeld => self.handle_clear_held(lease_num),
+            LeaseNotice::DoHandoff { to, new_num } => {
+                self.handle_do_handoff(lease_num, to, new_num)
+            }
             LeaseNotice::RecvLeaseMsg { peer, msg } => {
                 debug_assert_ne!(peer, self.me);
                 match msg {
@@ -827,6 +941,9 @@ impl LeaseManagerLogicTask {
                     LeaseMsg::RevokeReply { held } => {
                         self.handle_msg_revoke_reply(lease_num, peer, held)
                     }
+                    LeaseMsg::Handoff { to, new_num } => {
+                        self.handle_msg_handoff(lease_num, peer, to, new_num)
+                    }
                 }
             }
             LeaseNotice::GrantTimeout { peer } => {
@@ -955,7 +1072,7 @@ mod tests {
     impl TestNode {
         /// Creates a cluster of connected test node structs.
         async fn new_cluster(
-            population: u8,
+            population: u16,
             expire_timeout: Duration,
         ) -> Result<Vec<TestNode>, SummersetError> {
             assert!(population >= 2);
@@ -2288,7 +2405,7 @@ mod tests {
                     // all node should have held leases from all peers at some
                     // point before any timeout happens
                     let abitmap = abitmap_ref.load(Ordering::Acquire);
-                    if (abitmap.count_ones() as u8) == population {
+                    if (abitmap.count_ones() as u16) == population {
                         break;
                     }
                 };
@@ -2301,4 +2418,236 @@ mod tests {
         }
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn handoff_prompt() -> Result<(), SummersetError> {
+        let nodes =
+            TestNode::new_cluster(3, Duration::from_millis(600)).await?;
+        let [mut n0, mut n1, mut n2] = <[TestNode; 3]>::try_from(nodes)
+            .unwrap_or_else(|_| panic!("nodes vec unpack failed"));
+        // replica 0 grants to 1 under lease number 7
+        let peers = Bitmap::from((3, vec![1]));
+        n0.leaseman.add_notice(
+            7,
+            LeaseNotice::NewGrants {
+                peers: Some(peers.clone()),
+                accept_bar: None,
+            },
+        )?;
+        assert_eq!(
+            n0.leaseman.get_action().await?,
+            (7, LeaseAction::HigherNumber)
+        );
+        assert_eq!(
+            n0.leaseman.get_action().await?,
+            (
+                7,
+                LeaseAction::BcastLeaseMsgs {
+                    peers: peers.clone(),
+                    msg: LeaseMsg::Guard { accept_bar: None }
+                }
+            )
+        );
+        n0.transport
+            .send_msg(1, (7, LeaseMsg::Guard { accept_bar: None }))?;
+        assert_eq!(
+            n1.transport.recv_msg().await?,
+            (0, 7, LeaseMsg::Guard { accept_bar: None })
+        );
+        n1.leaseman.add_notice(
+            7,
+            LeaseNotice::RecvLeaseMsg {
+                peer: 0,
+                msg: LeaseMsg::Guard { accept_bar: None },
+            },
+        )?;
+        assert_eq!(
+            n1.leaseman.get_action().await?,
+            (7, LeaseAction::HigherNumber)
+        );
+        assert_eq!(
+            n1.leaseman.get_action().await?,
+            (
+                7,
+                LeaseAction::SendLeaseMsg {
+                    peer: 0,
+                    msg: LeaseMsg::GuardReply
+                }
+            )
+        );
+        n1.transport.send_msg(0, (7, LeaseMsg::GuardReply))?;
+        assert_eq!(
+            n0.transport.recv_msg().await?,
+            (1, 7, LeaseMsg::GuardReply)
+        );
+        n0.leaseman.add_notice(
+            7,
+            LeaseNotice::RecvLeaseMsg {
+                peer: 1,
+                msg: LeaseMsg::GuardReply,
+            },
+        )?;
+        assert_eq!(
+            n0.leaseman.get_action().await?,
+            (
+                7,
+                LeaseAction::SendLeaseMsg {
+                    peer: 1,
+                    msg: LeaseMsg::Promise
+                }
+            )
+        );
+        n0.transport.send_msg(1, (7, LeaseMsg::Promise))?;
+        assert_eq!(n1.transport.recv_msg().await?, (0, 7, LeaseMsg::Promise));
+        n1.leaseman.add_notice(
+            7,
+            LeaseNotice::RecvLeaseMsg {
+                peer: 0,
+                msg: LeaseMsg::Promise,
+            },
+        )?;
+        assert_eq!(
+            n1.leaseman.get_action().await?,
+            (
+                7,
+                LeaseAction::SendLeaseMsg {
+                    peer: 0,
+                    msg: LeaseMsg::PromiseReply { held: true }
+                }
+            )
+        );
+        assert_eq!(n1.leaseman.lease_cnt(), 2);
+        assert_eq!(n0.leaseman.grant_set(), peers);
+        // replica 1 hands off its lease to 2 under lease number 8, dropping
+        // what it holds immediately
+        n1.leaseman
+            .add_notice(7, LeaseNotice::DoHandoff { to: 2, new_num: 8 })?;
+        assert_eq!(
+            n1.leaseman.get_action().await?,
+            (7, LeaseAction::LeaseCleared)
+        );
+        assert_eq!(n1.leaseman.lease_cnt(), 1);
+        assert_eq!(
+            n1.leaseman.get_action().await?,
+            (
+                7,
+                LeaseAction::BcastLeaseMsgs {
+                    peers: Bitmap::from((3, vec![0])),
+                    msg: LeaseMsg::Handoff { to: 2, new_num: 8 }
+                }
+            )
+        );
+        n1.transport
+            .send_msg(0, (7, LeaseMsg::Handoff { to: 2, new_num: 8 }))?;
+        // replica 0 drops its grant to 1 without revoking, and promptly
+        // starts granting to 2 under the new lease number
+        assert_eq!(
+            n0.transport.recv_msg().await?,
+            (1, 7, LeaseMsg::Handoff { to: 2, new_num: 8 })
+        );
+        n0.leaseman.add_notice(
+            7,
+            LeaseNotice::RecvLeaseMsg {
+                peer: 1,
+                msg: LeaseMsg::Handoff { to: 2, new_num: 8 },
+            },
+        )?;
+        assert_eq!(
+            n0.leaseman.get_action().await?,
+            (
+                7,
+                LeaseAction::GrantRemoved {
+                    peer: 1,
+                    held: true
+                }
+            )
+        );
+        assert_eq!(n0.leaseman.grant_set(), Bitmap::new(3, false));
+        let peers = Bitmap::from((3, vec![2]));
+        assert_eq!(
+            n0.leaseman.get_action().await?,
+            (8, LeaseAction::HigherNumber)
+        );
+        assert_eq!(
+            n0.leaseman.get_action().await?,
+            (
+                8,
+                LeaseAction::BcastLeaseMsgs {
+                    peers: peers.clone(),
+                    msg: LeaseMsg::Guard { accept_bar: None }
+                }
+            )
+        );
+        n0.transport
+            .send_msg(2, (8, LeaseMsg::Guard { accept_bar: None }))?;
+        assert_eq!(
+            n2.transport.recv_msg().await?,
+            (0, 8, LeaseMsg::Guard { accept_bar: None })
+        );
+        n2.leaseman.add_notice(
+            8,
+            LeaseNotice::RecvLeaseMsg {
+                peer: 0,
+                msg: LeaseMsg::Guard { accept_bar: None },
+            },
+        )?;
+        assert_eq!(
+            n2.leaseman.get_action().await?,
+            (8, LeaseAction::HigherNumber)
+        );
+        assert_eq!(
+            n2.leaseman.get_action().await?,
+            (
+                8,
+                LeaseAction::SendLeaseMsg {
+                    peer: 0,
+                    msg: LeaseMsg::GuardReply
+                }
+            )
+        );
+        n2.transport.send_msg(0, (8, LeaseMsg::GuardReply))?;
+        assert_eq!(
+            n0.transport.recv_msg().await?,
+            (2, 8, LeaseMsg::GuardReply)
+        );
+        n0.leaseman.add_notice(
+            8,
+            LeaseNotice::RecvLeaseMsg {
+                peer: 2,
+                msg: LeaseMsg::GuardReply,
+            },
+        )?;
+        assert_eq!(
+            n0.leaseman.get_action().await?,
+            (
+                8,
+                LeaseAction::SendLeaseMsg {
+                    peer: 2,
+                    msg: LeaseMsg::Promise
+                }
+            )
+        );
+        n0.transport.send_msg(2, (8, LeaseMsg::Promise))?;
+        assert_eq!(n2.transport.recv_msg().await?, (0, 8, LeaseMsg::Promise));
+        n2.leaseman.add_notice(
+            8,
+            LeaseNotice::RecvLeaseMsg {
+                peer: 0,
+                msg: LeaseMsg::Promise,
+            },
+        )?;
+        assert_eq!(
+            n2.leaseman.get_action().await?,
+            (
+                8,
+                LeaseAction::SendLeaseMsg {
+                    peer: 0,
+                    msg: LeaseMsg::PromiseReply { held: true }
+                }
+            )
+        );
+        assert_eq!(n2.leaseman.lease_cnt(), 2);
+        assert_eq!(n0.leaseman.grant_set(), peers);
+        Ok(())
+    }
 }
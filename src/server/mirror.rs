@@ -0,0 +1,514 @@
+//! Summerset server asynchronous mirroring of executed writes to another
+//! cluster, delivered there in causal order.
+//!
+//! When enabled, the state machine feeds every write it executes, keyed by
+//! its command ID (i.e., its log position) and stamped with its HLC
+//! timestamp, to a shipper task, which replays them as a client of the
+//! target cluster. Writes are shipped one at a time in execution order, each
+//! only after the target has executed the one before, so the target goes
+//! through the same sequence of states as the source, trailing it by the
+//! replication lag: a reader of the target never sees a write without the
+//! writes it causally depends on.
+//!
+//! Every server of the source cluster may run a shipper, but only the one
+//! of the current leader (or server 0, in protocols without a leader) ships
+//! at a time; the others keep a bounded window of recent writes to take over
+//! from. The command ID of the latest write executed by the target is kept
+//! durably at the target itself, under a progress key written after every
+//! shipped write, so that a shipper that takes over or restarts resumes
+//! right after it instead of shipping old writes again. Retried writes carry
+//! their command ID as idempotency key. Mirroring is one-way; writes made
+//! directly to the target may get overwritten by mirrored ones.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::OnceLock;
+
+use crate::client::{ClientApiStub, ClientCtrlStub};
+use crate::manager::{CtrlReply, CtrlRequest};
+use crate::server::statemach::ChangeEvent;
+use crate::server::{
+    ApiReply, ApiRequest, Command, CommandId, CommandResult, IdemKey,
+    ReplicaId, RequestId,
+};
+use crate::utils::{HybridClock, SummersetError};
+
+use tokio::sync::{mpsc, watch};
+use tokio::time::{self, Duration, Instant};
+
+/// Client-facing address of the manager of the cluster to mirror writes to,
+/// if mirroring is enabled.
+static MIRROR_TARGET: OnceLock<SocketAddr> = OnceLock::new();
+
+/// Time to wait for the target to execute a shipped write before refreshing
+/// connections to it and shipping the write again.
+const SHIP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Capacity of the change feed from the state machine executor, which waits
+/// when it is full.
+pub(crate) const MIRROR_FEED_LEN: usize = 1024;
+
+/// Maximum number of writes a shipper keeps for shipping or taking over.
+const MIRROR_WINDOW_LEN: usize = 65536;
+
+/// Key at the target cluster holding the command ID of the latest write
+/// mirrored to it.
+const MIRROR_PROGRESS_KEY: &str = "__summerset_mirror_progress";
+
+/// Sets the client-facing address of the manager of another cluster to
+/// asynchronously mirror the writes executed by this process's state
+/// machine to. Should be called once at startup.
+pub fn set_mirror_target(manager: SocketAddr) {
+    MIRROR_TARGET.get_or_init(|| manager);
+}
+
+/// Gets the manager address of the mirror target cluster, if mirroring.
+pub(crate) fn mirror_target() -> Option<SocketAddr> {
+    MIRROR_TARGET.get().copied()
+}
+
+/// Window of the most recent writes not known to be at the target yet, in
+/// execution order.
+#[derive(Debug, Default)]
+struct MirrorWindow {
+    /// Writes in the window.
+    changes: VecDeque<ChangeEvent>,
+
+    /// Command ID of the latest write evicted for lack of room, if any.
+    evicted: Option<CommandId>,
+}
+
+impl MirrorWindow {
+    /// Appends a newly executed write, evicting the oldest one if full.
+    fn push(&mut self, change: ChangeEvent) {
+        if self.changes.len() >= MIRROR_WINDOW_LEN {
+            let oldest = self.changes.pop_front().unwrap();
+            self.evicted = Some(oldest.id);
+        }
+        self.changes.push_back(change);
+    }
+
+    /// Drops writes up to command ID `shipped` that the target already has.
+    /// Returns false if some writes after it have been evicted, i.e., the
+    /// target can no longer catch up through shipping.
+    fn trim(&mut self, shipped: Option<CommandId>) -> bool {
+        if let Some(shipped) = shipped {
+            while self.changes.front().is_some_and(|c| c.id <= shipped) {
+                self.changes.pop_front();
+            }
+        }
+        match self.evicted {
+            Some(evicted) => shipped.is_some_and(|s| s >= evicted),
+            None => true,
+        }
+    }
+}
+
+/// Mirror shipper task, replaying the writes fed by the state machine at
+/// the target cluster as its client.
+pub(crate) struct MirrorShipperTask {
+    /// Manager address of the target cluster.
+    manager: SocketAddr,
+
+    /// Receiver side of the change feed.
+    rx_changes: mpsc::Receiver<ChangeEvent>,
+
+    /// Receiver side of the gate telling whether I should be shipping.
+    rx_gate: watch::Receiver<bool>,
+
+    /// Writes not known to be at the target yet.
+    window: MirrorWindow,
+
+    /// Command ID of the latest write executed by the target, as last read
+    /// from or written to its progress key; `None` if not read since I last
+    /// started shipping.
+    shipped: Option<Option<CommandId>>,
+
+    /// Control stub to the target cluster's manager, once connected.
+    ctrl_stub: Option<ClientCtrlStub>,
+
+    /// Map from target server ID -> API connection stub to it.
+    api_stubs: HashMap<ReplicaId, ClientApiStub>,
+
+    /// Target server currently shipping writes to, ideally its leader.
+    server: ReplicaId,
+
+    /// Next request ID, monotonically increasing.
+    next_req: RequestId,
+}
+
+impl MirrorShipperTask {
+    /// Creates the mirror shipper task.
+    pub(crate) fn new(
+        manager: SocketAddr,
+        rx_changes: mpsc::Receiver<ChangeEvent>,
+        rx_gate: watch::Receiver<bool>,
+    ) -> Self {
+        MirrorShipperTask {
+            manager,
+            rx_changes,
+            rx_gate,
+            window: MirrorWindow::default(),
+            shipped: None,
+            ctrl_stub: None,
+            api_stubs: HashMap::new(),
+            server: 0,
+            next_req: 0,
+        }
+    }
+
+    /// (Re-)establishes connections to all active servers of the target
+    /// cluster, preferring its leader if known.
+    async fn connect(&mut self) -> Result<(), SummersetError> {
+        self.api_stubs.clear();
+        if self.ctrl_stub.is_none() {
+            self.ctrl_stub =
+                Some(ClientCtrlStub::new_by_connect(self.manager).await?);
+        }
+        let ctrl_stub = self.ctrl_stub.as_mut().unwrap();
+
+        ctrl_stub.send_req_insist(&CtrlRequest::QueryInfo)?;
+        let servers_info = loop {
+            match ctrl_stub.recv_reply().await? {
+                CtrlReply::QueryInfo { servers_info, .. } => {
+                    break servers_info
+                }
+                _ => continue, // ignore unrelated notices
+            }
+        };
+
+        for (server, info) in servers_info {
+            if info.is_paused {
+                continue;
+            }
+            pf_debug!("mirror connecting to server {}...", server);
+            let api_stub =
+                ClientApiStub::new_by_connect(ctrl_stub.id, info.api_addr)
+                    .await?;
+            self.api_stubs.insert(server, api_stub);
+            if info.is_leader || !self.api_stubs.contains_key(&self.server) {
+                self.server = server;
+            }
+        }
+        if self.api_stubs.is_empty() {
+            return logged_err!("no active servers in mirror target");
+        }
+        Ok(())
+    }
+
+    /// Moves on to the next connected server in ID order.
+    fn rotate_server(&mut self) {
+        let mut servers: Vec<ReplicaId> =
+            self.api_stubs.keys().copied().collect();
+        servers.sort_unstable();
+        self.server = servers
+            .iter()
+            .copied()
+            .find(|&s| s > self.server)
+            .unwrap_or(servers[0]);
+    }
+
+    /// Takes a write from the change feed into my window. Returns false if
+    /// the feed has been closed.
+    fn take_change(
+        window: &mut MirrorWindow,
+        change: Option<ChangeEvent>,
+    ) -> bool {
+        match change {
+            Some(change) => {
+                window.push(change);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Waits until `deadline`, meanwhile taking writes from the change feed
+    /// so that the executor does not wait on me.
+    async fn idle_until(&mut self, deadline: Instant) {
+        loop {
+            tokio::select! {
+                _ = time::sleep_until(deadline) => return,
+                change = self.rx_changes.recv() => {
+                    if !Self::take_change(&mut self.window, change) {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sends a command to the current target server and waits for it to get
+    /// executed there, taking writes from the change feed meanwhile. Returns
+    /// `Ok(None)` if it should be sent again, possibly to another server.
+    async fn request(
+        &mut self,
+        cmd: Command,
+        idem_key: Option<IdemKey>,
+    ) -> Result<Option<CommandResult>, SummersetError> {
+        let req_id = self.next_req;
+        self.next_req += 1;
+        let req = match idem_key {
+            Some(idem_key) => ApiRequest::IdemReq {
+                id: req_id,
+                cmd,
+                idem_key,
+                checksum: None,
+            },
+            None => ApiRequest::Req {
+                id: req_id,
+                cmd,
+                checksum: None,
+            },
+        };
+
+        let Some(api_stub) = self.api_stubs.get_mut(&self.server) else {
+            self.api_stubs.clear();
+            return Ok(None);
+        };
+        let mut sent = api_stub.send_req(Some(&req))?;
+        while !sent {
+            sent = api_stub.send_req(None)?;
+        }
+
+        let deadline = Instant::now() + SHIP_TIMEOUT;
+        loop {
+            let reply = tokio::select! {
+                reply = api_stub.recv_reply() => reply?,
+                change = self.rx_changes.recv() => {
+                    Self::take_change(&mut self.window, change);
+                    continue;
+                }
+                _ = time::sleep_until(deadline) => {
+                    pf_warn!("mirror timed-out at server {}", self.server);
+                    self.api_stubs.clear();
+                    return Ok(None);
+                }
+            };
+
+            match reply {
+                ApiReply::Reply {
+                    id,
+                    result: Some(result),
+                    ..
+                } if id == req_id => return Ok(Some(result)),
+                ApiReply::Reply { id, redirect, .. } if id == req_id => {
+                    match redirect {
+                        Some(server)
+                            if self.api_stubs.contains_key(&server) =>
+                        {
+                            self.server = server;
+                        }
+                        _ => self.rotate_server(),
+                    }
+                    return Ok(None);
+                }
+                ApiReply::LeaderChange { leader } => {
+                    // takes effect from the next request
+                    pf_debug!("mirror target leader changed to {}", leader);
+                    self.server = leader;
+                }
+                _ => {} // ignore replies to earlier attempts
+            }
+        }
+    }
+
+    /// Reads the command ID of the latest write executed by the target from
+    /// its progress key. Returns `Ok(None)` if it should be read again.
+    async fn read_progress(
+        &mut self,
+    ) -> Result<Option<Option<CommandId>>, SummersetError> {
+        let cmd = Command::Get {
+            key: MIRROR_PROGRESS_KEY.into(),
+        };
+        match self.request(cmd, None).await? {
+            Some(CommandResult::Get { value: None }) => Ok(Some(None)),
+            Some(CommandResult::Get { value: Some(value) }) => {
+                Ok(Some(Some(value.parse()?)))
+            }
+            Some(result) => {
+                logged_err!("unexpected mirror progress result {:?}", result)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Ships a write to the target and records it as the latest one there.
+    /// Returns `Ok(false)` if it should be shipped again.
+    async fn ship(
+        &mut self,
+        change: &ChangeEvent,
+    ) -> Result<bool, SummersetError> {
+        let cmd = Command::Put {
+            key: change.key.clone(),
+            value: change.value.clone(),
+        };
+        if self.request(cmd, Some(change.id)).await?.is_none() {
+            return Ok(false);
+        }
+        // shipping the latest write again if this fails is harmless
+        let cmd = Command::Put {
+            key: MIRROR_PROGRESS_KEY.into(),
+            value: change.id.to_string(),
+        };
+        Ok(self.request(cmd, None).await?.is_some())
+    }
+
+    /// Starts the mirror shipper task loop.
+    pub(crate) async fn run(&mut self) {
+        pf_info!("mirroring writes to cluster of manager '{}'", self.manager);
+
+        loop {
+            // wait for writes to ship while not the one shipping
+            if !*self.rx_gate.borrow() || self.window.changes.is_empty() {
+                tokio::select! {
+                    change = self.rx_changes.recv() => {
+                        if !Self::take_change(&mut self.window, change) {
+                            break;
+                        }
+                    }
+                    changed = self.rx_gate.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
+                        // another shipper may move the target on meanwhile
+                        self.shipped = None;
+                    }
+                }
+                continue;
+            }
+
+            if self.api_stubs.is_empty() {
+                if let Err(e) = self.connect().await {
+                    pf_error!("error connecting mirror target: {}", e);
+                    self.ctrl_stub = None;
+                    self.idle_until(Instant::now() + SHIP_TIMEOUT).await;
+                    continue;
+                }
+            }
+
+            // resume right after the latest write the target has
+            if self.shipped.is_none() {
+                match self.read_progress().await {
+                    Ok(Some(shipped)) => {
+                        pf_debug!(
+                            "mirror target has writes up to {:?}",
+                            shipped
+                        );
+                        if !self.window.trim(shipped) {
+                            pf_error!(
+                                "mirror target missed writes up to {:?}",
+                                self.window.evicted
+                            );
+                        }
+                        self.shipped = Some(shipped);
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        pf_warn!("error reading mirror progress: {}", e);
+                        self.api_stubs.clear();
+                    }
+                }
+                continue;
+            }
+
+            let Some(change) = self.window.changes.front().cloned() else {
+                continue;
+            };
+            match self.ship(&change).await {
+                Ok(true) => {
+                    self.window.changes.pop_front();
+                    self.shipped = Some(Some(change.id));
+                    // lag measured against the current time at the source
+                    pf_trace!(
+                        "mirrored write {} at {}, lag {} ms",
+                        change.id,
+                        change.hlc,
+                        change.hlc.lag_behind(&HybridClock::new().now())
+                    );
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    pf_warn!("error shipping to mirror target: {}", e);
+                    self.api_stubs.clear();
+                }
+            }
+        }
+
+        // change feed closed
+        pf_debug!("mirror shipper task exited");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::HlcStamp;
+
+    fn change(id: CommandId) -> ChangeEvent {
+        ChangeEvent {
+            id,
+            hlc: HlcStamp::default(),
+            key: "Jose".into(),
+            value: id.to_string(),
+        }
+    }
+
+    #[test]
+    fn window_trim() {
+        let mut window = MirrorWindow::default();
+        for id in [3, 5, 8] {
+            window.push(change(id));
+        }
+        assert!(window.trim(None));
+        assert_eq!(window.changes.len(), 3);
+        assert!(window.trim(Some(5)));
+        assert_eq!(window.changes.front().map(|c| c.id), Some(8));
+        assert!(window.trim(Some(9)));
+        assert!(window.changes.is_empty());
+    }
+
+    #[test]
+    fn window_evict() {
+        let mut window = MirrorWindow::default();
+        for id in 0..(MIRROR_WINDOW_LEN as CommandId + 2) {
+            window.push(change(id));
+        }
+        assert_eq!(window.changes.len(), MIRROR_WINDOW_LEN);
+        assert_eq!(window.evicted, Some(1));
+        // the target has not got the evicted writes yet
+        assert!(!window.trim(None));
+        assert!(!window.trim(Some(0)));
+        assert_eq!(window.changes.front().map(|c| c.id), Some(2));
+        assert!(window.trim(Some(1)));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn shipper_takes_feed() -> Result<(), SummersetError> {
+        let (tx_changes, rx_changes) = mpsc::channel(MIRROR_FEED_LEN);
+        let (tx_gate, rx_gate) = watch::channel(false);
+        let mut shipper = MirrorShipperTask::new(
+            "127.0.0.1:30009".parse()?,
+            rx_changes,
+            rx_gate,
+        );
+        // a gated shipper keeps taking writes, so the feed never fills up
+        let handle = tokio::spawn(async move {
+            shipper.run().await;
+            shipper.window.changes.len()
+        });
+        for id in 0..(2 * MIRROR_FEED_LEN as CommandId) {
+            tx_changes
+                .send(change(id))
+                .await
+                .map_err(SummersetError::msg)?;
+        }
+        drop(tx_changes);
+        assert_eq!(
+            handle.await.map_err(SummersetError::msg)?,
+            2 * MIRROR_FEED_LEN
+        );
+        drop(tx_gate);
+        Ok(())
+    }
+}
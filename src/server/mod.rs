@@ -2,26 +2,64 @@
 
 mod replica;
 
+mod audit;
+mod blobstore;
+#[cfg(feature = "byzantine-hooks")]
+mod byzantine;
 mod control;
+mod divergence;
 mod external;
 mod heartbeat;
 mod leaseman;
+mod mirror;
+mod msgtrace;
+mod netfault;
+mod panicdump;
+mod peertls;
+mod resmon;
+mod skewness;
 mod statemach;
 mod storage;
 mod transport;
+mod wsbridge;
 
-pub use external::{ApiReply, ApiRequest, ConfChange, RequestId};
+pub use audit::{read_audit_segment, AuditEntry};
+#[cfg(feature = "byzantine-hooks")]
+pub use byzantine::set_byzantine_hooks;
+pub use external::{
+    set_debug_api, ApiReply, ApiRequest, CommitCert, ConfChange, DebugState,
+    IdemKey, ReadConsistency, ReplyError, RequestId,
+};
+pub use mirror::set_mirror_target;
+pub use msgtrace::{set_msg_trace_path, MsgTrace};
+pub use panicdump::set_panic_dump_path;
+pub use peertls::set_peer_tls_dir;
 pub use replica::{GenericReplica, ReplicaId};
-pub use statemach::{Command, CommandId, CommandResult};
+pub use resmon::ResourceUsage;
+pub use statemach::{
+    set_app_state_machine, set_conflict_spec, AppStateMachine, Command,
+    CommandId, CommandResult, Footprint, KeysReport,
+};
+pub use storage::{LogAction, LogActionId, LogResult, StorageHub};
+pub use wsbridge::WsBridge;
 
-pub(crate) use control::ControlHub;
+pub(crate) use audit::AuditLog;
+pub(crate) use blobstore::{BlobRef, BlobStore};
+pub(crate) use control::{discovery_info, ControlHub};
+pub(crate) use divergence::{entry_digest, DivergenceTracker};
 pub(crate) use external::ExternalApi;
 pub(crate) use heartbeat::{HeartbeatEvent, Heartbeater};
 pub(crate) use leaseman::{
     LeaseAction, LeaseGid, LeaseManager, LeaseMsg, LeaseNotice, LeaseNum,
 };
-pub(crate) use statemach::StateMachine;
-pub(crate) use storage::{LogAction, LogActionId, LogResult, StorageHub};
+pub(crate) use panicdump::{panic_dump_event, panic_dump_status};
+pub(crate) use peertls::{PeerTls, PEER_TLS};
+pub(crate) use resmon::{disk_free_bytes, ResourceMonitor};
+pub(crate) use skewness::AccessTracker;
+pub(crate) use statemach::{ConflictIndex, KeysInspector, StateMachine};
+pub(crate) use storage::{
+    read_log_entry, scan_log_file, truncate_log_file, write_log_file,
+};
 pub(crate) use transport::TransportHub;
 
 // TODO: turn Heartbeater into a more organized, channel-oriented module like
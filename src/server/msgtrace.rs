@@ -0,0 +1,217 @@
+//! Summerset server peer message tracing, for golden-trace regression tests.
+//!
+//! When enabled, every message a transport hub in this process hands to a
+//! peer is appended to a trace file as one line holding the sender, the
+//! receiver, and the message's `Debug` rendering. A trace recorded while
+//! driving a protocol with some scripted inputs serves as the golden trace;
+//! after a refactor, the same script is run again and the new trace gets
+//! checked against the golden one. Traces are compared per directed peer
+//! link, as the interleaving of messages across links depends on task
+//! scheduling. Messages sent on timers (e.g., heartbeats) are not
+//! reproducible and are usually ignored in the comparison.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{LineWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::server::ReplicaId;
+use crate::utils::SummersetError;
+
+/// True if tracing is enabled, for a cheap check on the common path.
+static TRACING: AtomicBool = AtomicBool::new(false);
+
+/// Trace file being written to, if tracing is enabled.
+static TRACE_FILE: Mutex<Option<LineWriter<File>>> = Mutex::new(None);
+
+/// Starts tracing peer messages sent in this process to a new file at
+/// `path`, replacing the trace in progress if any. `None` stops tracing.
+pub fn set_msg_trace_path(path: Option<&Path>) -> Result<(), SummersetError> {
+    let writer = match path {
+        Some(path) => Some(LineWriter::new(File::create(path)?)),
+        None => None,
+    };
+    let mut file = TRACE_FILE.lock().map_err(SummersetError::msg)?;
+    TRACING.store(writer.is_some(), Ordering::Release);
+    *file = writer;
+    Ok(())
+}
+
+/// Records a message sent from replica `from` to replica `to`. The rendering
+/// of the message is only built if tracing is enabled.
+#[inline]
+pub(crate) fn trace_msg(
+    from: ReplicaId,
+    to: ReplicaId,
+    describe: impl FnOnce() -> String,
+) {
+    if !TRACING.load(Ordering::Acquire) {
+        return;
+    }
+    if let Ok(mut file) = TRACE_FILE.lock() {
+        if let Some(file) = file.as_mut() {
+            // `Debug` renderings escape tabs and newlines in strings
+            let _ = writeln!(file, "{}\t{}\t{}", from, to, describe());
+        }
+    }
+}
+
+/// Peer message trace loaded from a trace file, as the sequence of messages
+/// sent on each directed peer link.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct MsgTrace {
+    /// Map from `(sender, receiver)` -> messages sent on that link in order.
+    links: BTreeMap<(ReplicaId, ReplicaId), Vec<String>>,
+}
+
+impl MsgTrace {
+    /// Parses trace file content.
+    fn parse(text: &str) -> Result<Self, SummersetError> {
+        let mut links: BTreeMap<_, Vec<String>> = BTreeMap::new();
+        for (lineno, line) in text.lines().enumerate() {
+            let mut fields = line.splitn(3, '\t');
+            let (Some(from), Some(to), Some(msg)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                return logged_err!("malformed trace line {}", lineno + 1);
+            };
+            let (Ok(from), Ok(to)) = (from.parse(), to.parse()) else {
+                return logged_err!(
+                    "invalid replica IDs on trace line {}",
+                    lineno + 1
+                );
+            };
+            links.entry((from, to)).or_default().push(msg.into());
+        }
+        Ok(MsgTrace { links })
+    }
+
+    /// Loads a trace recorded to the file at `path`.
+    pub fn load(path: &Path) -> Result<Self, SummersetError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Drops messages whose rendering starts with any of the given prefixes,
+    /// typically names of message variants sent on timers.
+    pub fn ignoring(mut self, prefixes: &[&str]) -> Self {
+        for msgs in self.links.values_mut() {
+            msgs.retain(|msg| !prefixes.iter().any(|p| msg.starts_with(p)));
+        }
+        self.links.retain(|_, msgs| !msgs.is_empty());
+        self
+    }
+
+    /// Messages sent from replica `from` to replica `to`, in order.
+    pub fn link(&self, from: ReplicaId, to: ReplicaId) -> &[String] {
+        self.links
+            .get(&(from, to))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Total number of messages in the trace.
+    pub fn num_msgs(&self) -> usize {
+        self.links.values().map(Vec::len).sum()
+    }
+
+    /// Checks that this trace is equivalent to the `golden` one, i.e., the
+    /// same messages were sent in the same order on every link. Returns an
+    /// error describing the first discrepancy otherwise.
+    pub fn check_against(
+        &self,
+        golden: &MsgTrace,
+    ) -> Result<(), SummersetError> {
+        let links: BTreeSet<_> =
+            self.links.keys().chain(golden.links.keys()).collect();
+        for &(from, to) in links {
+            let (mine, theirs) = (self.link(from, to), golden.link(from, to));
+            for i in 0..mine.len().max(theirs.len()) {
+                if mine.get(i) != theirs.get(i) {
+                    return Err(SummersetError::msg(format!(
+                        "trace differs on link {} -> {} at msg #{}: got {:?}, \
+                         golden has {:?}",
+                        from,
+                        to,
+                        i,
+                        mine.get(i),
+                        theirs.get(i)
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_lines(lines: &[&str]) -> Result<MsgTrace, SummersetError> {
+        MsgTrace::parse(&lines.join("\n"))
+    }
+
+    #[test]
+    fn trace_equivalence() -> Result<(), SummersetError> {
+        let golden = parse_lines(&[
+            "0\t1\tAccept { slot: 0 }",
+            "0\t2\tAccept { slot: 0 }",
+            "1\t0\tHeartbeat",
+            "1\t0\tAcceptReply { slot: 0 }",
+        ])?;
+        assert_eq!(golden.num_msgs(), 4);
+        // interleaving across links does not matter
+        let trace = parse_lines(&[
+            "0\t2\tAccept { slot: 0 }",
+            "1\t0\tHeartbeat",
+            "0\t1\tAccept { slot: 0 }",
+            "1\t0\tAcceptReply { slot: 0 }",
+        ])?;
+        assert!(trace.check_against(&golden).is_ok());
+        // order within a link does
+        let trace = parse_lines(&[
+            "0\t1\tAccept { slot: 0 }",
+            "0\t2\tAccept { slot: 0 }",
+            "1\t0\tAcceptReply { slot: 0 }",
+            "1\t0\tHeartbeat",
+        ])?;
+        assert!(trace.check_against(&golden).is_err());
+        assert!(trace
+            .ignoring(&["Heartbeat"])
+            .check_against(&golden.clone().ignoring(&["Heartbeat"]))
+            .is_ok());
+        // so do missing messages
+        let trace = parse_lines(&[
+            "0\t1\tAccept { slot: 0 }",
+            "1\t0\tHeartbeat",
+            "1\t0\tAcceptReply { slot: 0 }",
+        ])?;
+        assert!(trace.check_against(&golden).is_err());
+        assert!(golden.check_against(&trace).is_err());
+        assert!(parse_lines(&["0\t1"]).is_err());
+        assert!(parse_lines(&["0\tx\tAccept"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn record_and_load() -> Result<(), SummersetError> {
+        // uses IDs no other test's replicas have, as tracing is global
+        let path = Path::new("/tmp/test-msg-trace-0.log");
+        set_msg_trace_path(Some(path))?;
+        trace_msg(250, 251, || "Propose { reqs: [\"a\\tb\"] }".into());
+        trace_msg(251, 250, || "Reply".into());
+        trace_msg(250, 251, || "Commit".into());
+        set_msg_trace_path(None)?;
+        trace_msg(250, 251, || "Ignored".into());
+        let trace = MsgTrace::load(path)?;
+        assert_eq!(
+            trace.link(250, 251),
+            &["Propose { reqs: [\"a\\tb\"] }", "Commit"]
+        );
+        assert_eq!(trace.link(251, 250), &["Reply"]);
+        assert!(trace.link(250, 252).is_empty());
+        Ok(())
+    }
+}
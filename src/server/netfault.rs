@@ -0,0 +1,90 @@
+//! Simulated network faults on peer links, set by the manager in chaos
+//! mode. Faults apply to messages received from peers, so a partition is
+//! symmetric once servers on both of its sides are told. Faults are kept
+//! per receiving replica, as several replicas may share a process.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use crate::server::ReplicaId;
+
+use tokio::time::Duration;
+
+/// True if any fault is in effect, for a cheap check on the common path.
+static FAULTY: AtomicBool = AtomicBool::new(false);
+
+/// Faults in effect at a receiving replica: peers whose messages are
+/// dropped, and extra delay of messages received from peers.
+type NetFaults = (Vec<ReplicaId>, Duration);
+
+/// Faults in effect at each receiving replica that has any.
+static NET_FAULTS: RwLock<Option<HashMap<ReplicaId, NetFaults>>> =
+    RwLock::new(None);
+
+/// Replaces the network faults in effect at replica `me`. An empty `blocked`
+/// set and a zero `delay` heal everything.
+pub(crate) fn set_net_faults(
+    me: ReplicaId,
+    blocked: &HashSet<ReplicaId>,
+    delay: Duration,
+) {
+    if let Ok(mut faults) = NET_FAULTS.write() {
+        let faults = faults.get_or_insert_with(HashMap::new);
+        if blocked.is_empty() && delay.is_zero() {
+            faults.remove(&me);
+        } else {
+            faults.insert(me, (blocked.iter().copied().collect(), delay));
+        }
+        FAULTY.store(!faults.is_empty(), Ordering::Release);
+    }
+}
+
+/// Returns true if messages from the peer should be dropped at replica `me`.
+#[inline]
+pub(crate) fn peer_blocked(me: ReplicaId, peer: ReplicaId) -> bool {
+    FAULTY.load(Ordering::Acquire)
+        && NET_FAULTS.read().is_ok_and(|faults| {
+            faults
+                .as_ref()
+                .and_then(|faults| faults.get(&me))
+                .is_some_and(|(peers, _)| peers.contains(&peer))
+        })
+}
+
+/// Returns the extra delay of messages received from peers at replica `me`.
+#[inline]
+pub(crate) fn inbound_delay(me: ReplicaId) -> Duration {
+    if !FAULTY.load(Ordering::Acquire) {
+        return Duration::ZERO;
+    }
+    NET_FAULTS
+        .read()
+        .ok()
+        .and_then(|faults| {
+            faults
+                .as_ref()
+                .and_then(|faults| faults.get(&me))
+                .map(|f| f.1)
+        })
+        .unwrap_or(Duration::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_and_heal() {
+        // uses IDs no other test's replicas have, as the state is global
+        set_net_faults(250, &HashSet::from([0]), Duration::from_millis(5));
+        assert!(peer_blocked(250, 0));
+        assert!(!peer_blocked(250, 1));
+        assert!(!peer_blocked(251, 0));
+        assert_eq!(inbound_delay(250), Duration::from_millis(5));
+        assert_eq!(inbound_delay(251), Duration::ZERO);
+        set_net_faults(250, &HashSet::new(), Duration::ZERO);
+        assert!(!peer_blocked(250, 0));
+        assert_eq!(inbound_delay(250), Duration::ZERO);
+    }
+}
@@ -0,0 +1,139 @@
+//! Summerset server protocol state dump on panic.
+//!
+//! When enabled, the server keeps its latest reported protocol status and a
+//! bounded ring of recent protocol events (e.g., logged WAL entries and role
+//! changes) in memory. A panic hook writes them out to a file before the
+//! default hook runs, so that a crash in a long unattended run leaves behind
+//! enough state to diagnose it.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::fs;
+use std::panic::{self, PanicHookInfo};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock, TryLockError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::manager::ServerStatus;
+
+/// Number of most recent protocol events kept for the dump.
+const DUMP_EVENTS: usize = 256;
+
+/// Path of the file to dump protocol state to on panic, if enabled.
+static DUMP_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Protocol state kept for the dump.
+static DUMP_STATE: Mutex<DumpState> = Mutex::new(DumpState {
+    status: None,
+    events: VecDeque::new(),
+});
+
+/// Latest protocol state known to the dump.
+struct DumpState {
+    /// Latest status reported by the protocol.
+    status: Option<ServerStatus>,
+
+    /// Recent protocol events as `(unix millisecs, description)`, oldest
+    /// first.
+    events: VecDeque<(u128, String)>,
+}
+
+/// Enables dumping protocol state to `path` on panic, installing the panic
+/// hook. Should be called once at startup.
+pub fn set_panic_dump_path(path: PathBuf) {
+    if DUMP_PATH.set(path).is_err() {
+        return;
+    }
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        write_dump(info);
+        default_hook(info);
+    }));
+}
+
+/// Is dumping on panic enabled?
+#[inline]
+fn enabled() -> bool {
+    DUMP_PATH.get().is_some()
+}
+
+/// Milliseconds since the UNIX epoch.
+fn unix_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis())
+}
+
+/// Records the latest protocol status for the dump.
+pub(crate) fn panic_dump_status(status: &ServerStatus) {
+    if !enabled() {
+        return;
+    }
+    if let Ok(mut state) = DUMP_STATE.lock() {
+        state.status = Some(status.clone());
+    }
+}
+
+/// Records a protocol event for the dump. The description is only built if
+/// dumping is enabled.
+pub(crate) fn panic_dump_event(describe: impl FnOnce() -> String) {
+    if !enabled() {
+        return;
+    }
+    if let Ok(mut state) = DUMP_STATE.lock() {
+        if state.events.len() == DUMP_EVENTS {
+            state.events.pop_front();
+        }
+        state.events.push_back((unix_ms(), describe()));
+    }
+}
+
+/// Renders the dump content.
+fn render_dump(info: &PanicHookInfo, state: Option<&DumpState>) -> String {
+    let mut dump = String::new();
+    let _ = writeln!(dump, "panic at {} ms: {}", unix_ms(), info);
+    match state {
+        None => {
+            let _ = writeln!(dump, "protocol state unavailable (locked)");
+        }
+        Some(state) => {
+            match &state.status {
+                Some(status) => {
+                    let _ = writeln!(dump, "latest status: {:#?}", status);
+                }
+                None => {
+                    let _ = writeln!(dump, "no status reported yet");
+                }
+            }
+            let _ = writeln!(
+                dump,
+                "last {} protocol events, oldest first:",
+                state.events.len()
+            );
+            for (time, event) in &state.events {
+                let _ = writeln!(dump, "  [{}] {}", time, event);
+            }
+        }
+    }
+    dump
+}
+
+/// Writes the dump file. Never blocks: if the state is held by the panicking
+/// thread itself, the dump goes without it.
+fn write_dump(info: &PanicHookInfo) {
+    let Some(path) = DUMP_PATH.get() else {
+        return;
+    };
+    let dump = match DUMP_STATE.try_lock() {
+        Ok(state) => render_dump(info, Some(&*state)),
+        Err(TryLockError::Poisoned(poisoned)) => {
+            render_dump(info, Some(&*poisoned.into_inner()))
+        }
+        Err(TryLockError::WouldBlock) => render_dump(info, None),
+    };
+    if let Err(e) = fs::write(path, dump) {
+        eprintln!("failed to write panic dump '{}': {}", path.display(), e);
+    } else {
+        eprintln!("protocol state dumped to '{}'", path.display());
+    }
+}
@@ -0,0 +1,239 @@
+//! Summerset server peer-peer mutual TLS module implementation.
+//!
+//! When the cluster manager is set up with a cluster CA certificate, it hands
+//! the CA out to every joining server, and all peer-peer connections must be
+//! mutually authenticated: each replica presents its own certificate, which
+//! must chain up to the cluster CA and be issued for the DNS name
+//! `replica<id>.summerset` matching the replica ID it claims.
+//!
+//! The TLS session of each connection is driven by a small bridging task that
+//! relays plaintext through a loopback socket pair, so the peer messenger
+//! tasks keep working on plain `TcpStream`s with non-blocking writes.
+
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use crate::server::ReplicaId;
+use crate::utils::SummersetError;
+
+use tokio::fs;
+use tokio::io::{self, AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore, ServerConfig};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+/// Directory holding this server's replica certificates, set at startup if
+/// the server is allowed to join a cluster with mutual TLS.
+static PEER_TLS_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Mutual TLS setup for peer-peer connections, initialized when joining a
+/// cluster whose manager has a cluster CA configured.
+pub(crate) static PEER_TLS: OnceLock<PeerTls> = OnceLock::new();
+
+/// Sets the directory holding this server's replica certificates, in which
+/// `replica<id>.crt` and `replica<id>.key` are looked up once the manager
+/// assigns me an ID. Should be called once at startup.
+pub fn set_peer_tls_dir(dir: PathBuf) {
+    PEER_TLS_DIR.get_or_init(|| dir);
+}
+
+/// Gets the DNS name that the certificate of replica `id` must be issued for.
+fn replica_name(id: ReplicaId) -> Result<ServerName<'static>, SummersetError> {
+    ServerName::try_from(format!("replica{}.summerset", id))
+        .map_err(SummersetError::msg)
+}
+
+/// Parses all certificates in PEM content.
+fn parse_certs(
+    pem: &[u8],
+) -> Result<Vec<CertificateDer<'static>>, SummersetError> {
+    let certs =
+        rustls_pemfile::certs(&mut &pem[..]).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return logged_err!("no certificate found in PEM content");
+    }
+    Ok(certs)
+}
+
+/// The peer-peer mutual TLS module.
+pub(crate) struct PeerTls {
+    /// TLS acceptor for connections from peers, requiring peer certificates.
+    acceptor: TlsAcceptor,
+
+    /// TLS connector for connections to peers, presenting my certificate.
+    connector: TlsConnector,
+}
+
+impl PeerTls {
+    /// Sets up mutual TLS for replica `me` given the cluster CA certificate
+    /// in PEM, reading my certificate and private key from the directory set
+    /// through `set_peer_tls_dir()`, and installs it for the transport hub.
+    pub(crate) async fn setup(
+        me: ReplicaId,
+        ca_pem: &[u8],
+    ) -> Result<(), SummersetError> {
+        let Some(dir) = PEER_TLS_DIR.get() else {
+            return logged_err!(
+                "manager requires peer mutual TLS but no --tls-dir given"
+            );
+        };
+        let peer_tls = Self::new(me, ca_pem, dir).await?;
+        if PEER_TLS.set(peer_tls).is_err() {
+            return logged_err!("peer mutual TLS already set up");
+        }
+        pf_info!("peer mutual TLS enabled as replica{}.summerset", me);
+        Ok(())
+    }
+
+    /// Returns an error if a directory of replica certificates is given but
+    /// the manager does not use a cluster CA, which is likely a mistake.
+    pub(crate) fn check_unused() -> Result<(), SummersetError> {
+        if let Some(dir) = PEER_TLS_DIR.get() {
+            return logged_err!(
+                "--tls-dir {} given but manager has no cluster CA",
+                dir.display()
+            );
+        }
+        Ok(())
+    }
+
+    /// Creates the mutual TLS configs of replica `me`.
+    async fn new(
+        me: ReplicaId,
+        ca_pem: &[u8],
+        dir: &Path,
+    ) -> Result<Self, SummersetError> {
+        let mut roots = RootCertStore::empty();
+        for ca_cert in parse_certs(ca_pem)? {
+            roots.add(ca_cert)?;
+        }
+        let roots = Arc::new(roots);
+
+        let cert_pem = fs::read(dir.join(format!("replica{}.crt", me))).await?;
+        let key_pem = fs::read(dir.join(format!("replica{}.key", me))).await?;
+        let certs = parse_certs(&cert_pem)?;
+        let Some(key) = rustls_pemfile::private_key(&mut &key_pem[..])? else {
+            return logged_err!("no private key found for replica {}", me);
+        };
+
+        let verifier = WebPkiClientVerifier::builder(roots.clone())
+            .build()
+            .map_err(SummersetError::msg)?;
+        let server_config = ServerConfig::builder()
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(certs.clone(), key.clone_key())?;
+        let client_config = ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_client_auth_cert(certs, key)?;
+
+        Ok(PeerTls {
+            acceptor: TlsAcceptor::from(Arc::new(server_config)),
+            connector: TlsConnector::from(Arc::new(client_config)),
+        })
+    }
+
+    /// Secures a connection I made to replica `peer`, whose certificate must
+    /// be issued for its ID. Returns the plaintext side of the bridge.
+    pub(crate) async fn connect(
+        &self,
+        stream: TcpStream,
+        peer: ReplicaId,
+    ) -> Result<TcpStream, SummersetError> {
+        let tls_stream =
+            self.connector.connect(replica_name(peer)?, stream).await?;
+        Self::bridge(tls_stream).await
+    }
+
+    /// Secures a connection accepted from some peer. Returns the plaintext
+    /// side of the bridge and the peer's certificate, which should be checked
+    /// with `check_identity()` once the peer tells its ID.
+    pub(crate) async fn accept(
+        &self,
+        stream: TcpStream,
+    ) -> Result<(TcpStream, CertificateDer<'static>), SummersetError> {
+        let tls_stream = self.acceptor.accept(stream).await?;
+        let Some(cert) = tls_stream
+            .get_ref()
+            .1
+            .peer_certificates()
+            .and_then(|certs| certs.first())
+            .cloned()
+        else {
+            return logged_err!("peer presented no certificate");
+        };
+        Ok((Self::bridge(tls_stream).await?, cert))
+    }
+
+    /// Checks that a verified peer certificate is issued for replica `id`.
+    pub(crate) fn check_identity(
+        cert: &CertificateDer<'static>,
+        id: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        let cert = webpki::EndEntityCert::try_from(cert)
+            .map_err(SummersetError::msg)?;
+        if cert
+            .verify_is_valid_for_subject_name(&replica_name(id)?)
+            .is_err()
+        {
+            return logged_err!(
+                "peer certificate not issued for replica {}",
+                id
+            );
+        }
+        Ok(())
+    }
+
+    /// Spawns a task relaying between the TLS stream and a fresh loopback
+    /// socket pair, returning the plaintext end of the pair.
+    async fn bridge<S>(tls_stream: S) -> Result<TcpStream, SummersetError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let (plain, accepted) = tokio::join!(
+            TcpStream::connect(listener.local_addr()?),
+            listener.accept()
+        );
+        let plain = plain?;
+        let (mut inner, inner_peer) = accepted?;
+        if inner_peer != plain.local_addr()? {
+            // someone else raced to the ephemeral listener
+            return logged_err!(
+                Network; "unexpected bridge connection from {}",
+                inner_peer
+            );
+        }
+        plain.set_nodelay(true)?;
+        inner.set_nodelay(true)?;
+
+        tokio::spawn(async move {
+            let mut tls_stream = tls_stream;
+            if let Err(_e) =
+                io::copy_bidirectional(&mut tls_stream, &mut inner).await
+            {
+                // NOTE: commented out to prevent console lags
+                // pf_debug!("TLS bridge closed: {}", e);
+            }
+        });
+        Ok(plain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replica_names() -> Result<(), SummersetError> {
+        assert_eq!(
+            replica_name(3)?,
+            ServerName::try_from("replica3.summerset").unwrap()
+        );
+        assert!(parse_certs(b"not a PEM").is_err());
+        Ok(())
+    }
+}
@@ -9,8 +9,11 @@ use async_trait::async_trait;
 
 use tokio::sync::watch;
 
-/// Server replica ID type.
-pub type ReplicaId = u8;
+/// Server replica ID type. Kept a plain alias of the population type rather
+/// than a newtype, as replica IDs are used as bitmap indices, compared
+/// against populations, and stepped through with modular arithmetic all
+/// over the protocols.
+pub type ReplicaId = u16;
 
 /// Replica trait to be implement by all protocol-specific server structs.
 #[async_trait]
@@ -39,5 +42,5 @@ pub trait GenericReplica {
     fn id(&self) -> ReplicaId;
 
     /// Gets current cluster size. 0 means population unknown.
-    fn population(&self) -> u8;
+    fn population(&self) -> u16;
 }
@@ -0,0 +1,166 @@
+//! Summerset server process resource usage sampler implementation.
+//!
+//! Samples are read from Linux `/proc` and `statvfs()`; on other platforms,
+//! or if anything cannot be read, the corresponding fields are left as 0.
+
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use tokio::time::Instant;
+
+/// Resource usage of a server process, sampled at each status report.
+#[derive(
+    Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize,
+)]
+pub struct ResourceUsage {
+    /// CPU usage since the previous sample, in percent of one core.
+    pub cpu_pct: u64,
+
+    /// Resident set size in bytes.
+    pub rss_bytes: u64,
+
+    /// Number of open file descriptors.
+    pub open_fds: u64,
+
+    /// Total size in bytes of files in the WAL directory.
+    pub wal_dir_bytes: u64,
+
+    /// Free space in bytes available on the WAL directory's filesystem.
+    pub disk_free_bytes: u64,
+}
+
+/// Gets the free space in bytes available to unprivileged users on the
+/// filesystem holding `path`.
+pub(crate) fn disk_free_bytes(path: &Path) -> Option<u64> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: `statvfs` is plain old data, for which all zeros is valid;
+    //         `c_path` is a valid NUL-terminated string and `stat` is a
+    //         properly sized buffer owned by us
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// The resource usage sampler.
+#[derive(Debug)]
+pub(crate) struct ResourceMonitor {
+    /// Directory holding the WAL file, if known.
+    wal_dir: Option<PathBuf>,
+
+    /// Time and total CPU ticks of the previous sample.
+    last_cpu: Option<(Instant, u64)>,
+}
+
+impl ResourceMonitor {
+    /// Creates a new sampler, without a WAL directory to watch yet.
+    pub(crate) fn new() -> Self {
+        ResourceMonitor {
+            wal_dir: None,
+            last_cpu: None,
+        }
+    }
+
+    /// Sets the WAL file whose directory gets watched.
+    pub(crate) fn watch_wal(&mut self, wal_path: &Path) {
+        let dir = wal_path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        self.wal_dir = Some(dir.into());
+    }
+
+    /// Reads total user + system CPU ticks of this process.
+    fn cpu_ticks() -> Option<u64> {
+        let stat = fs::read_to_string("/proc/self/stat").ok()?;
+        // skip past the command name, which may contain spaces
+        let fields: Vec<&str> =
+            stat.get(stat.rfind(')')? + 2..)?.split(' ').collect();
+        let utime: u64 = fields.get(11)?.parse().ok()?;
+        let stime: u64 = fields.get(12)?.parse().ok()?;
+        Some(utime + stime)
+    }
+
+    /// Reads resident set size of this process in bytes.
+    fn rss_bytes() -> Option<u64> {
+        let statm = fs::read_to_string("/proc/self/statm").ok()?;
+        let pages: u64 = statm.split(' ').nth(1)?.parse().ok()?;
+        // SAFETY: sysconf is always safe to call
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        Some(pages * page_size.max(0) as u64)
+    }
+
+    /// Counts open file descriptors of this process.
+    fn open_fds() -> Option<u64> {
+        Some(fs::read_dir("/proc/self/fd").ok()?.count() as u64)
+    }
+
+    /// Sums sizes of regular files directly in a directory.
+    fn dir_bytes(dir: &Path) -> Option<u64> {
+        let mut total = 0;
+        for entry in fs::read_dir(dir).ok()?.flatten() {
+            if let Ok(meta) = entry.metadata() {
+                if meta.is_file() {
+                    total += meta.len();
+                }
+            }
+        }
+        Some(total)
+    }
+
+    /// Takes a new sample.
+    pub(crate) fn sample(&mut self) -> ResourceUsage {
+        let now = Instant::now();
+        let mut cpu_pct = 0;
+        if let Some(ticks) = Self::cpu_ticks() {
+            if let Some((last_time, last_ticks)) = self.last_cpu {
+                // SAFETY: sysconf is always safe to call
+                let tck = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1);
+                let secs = now.duration_since(last_time).as_secs_f64();
+                if secs > 0.0 {
+                    let used = ticks.saturating_sub(last_ticks) as f64;
+                    cpu_pct = (used / tck as f64 / secs * 100.0).round() as u64;
+                }
+            }
+            self.last_cpu = Some((now, ticks));
+        }
+
+        ResourceUsage {
+            cpu_pct,
+            rss_bytes: Self::rss_bytes().unwrap_or(0),
+            open_fds: Self::open_fds().unwrap_or(0),
+            wal_dir_bytes: self
+                .wal_dir
+                .as_deref()
+                .and_then(Self::dir_bytes)
+                .unwrap_or(0),
+            disk_free_bytes: self
+                .wal_dir
+                .as_deref()
+                .and_then(disk_free_bytes)
+                .unwrap_or(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_self() {
+        let mut monitor = ResourceMonitor::new();
+        monitor.watch_wal(Path::new("/tmp/summerset.wal"));
+        let usage = monitor.sample();
+        assert_eq!(usage.cpu_pct, 0);
+        assert!(usage.rss_bytes > 0);
+        assert!(usage.open_fds > 0);
+        assert!(usage.disk_free_bytes > 0);
+    }
+}
@@ -0,0 +1,204 @@
+//! Summerset server per-key-range access frequency tracking, for protocols
+//! that reassign read responders toward where the traffic comes from.
+
+use std::collections::HashMap;
+
+use crate::server::ReplicaId;
+use crate::utils::{
+    inty_to_key, key_to_inty, Bitmap, IntyKey, RespondersConf, SummersetError,
+};
+
+/// Proposed responders assignment of a key range, given as the range's
+/// start and end keys.
+type RangeProposal = ((String, String), Bitmap);
+
+/// Per-key-range access frequency tracker. Integer-mappable keys are bucketed
+/// into fixed-width ranges, and for each range it counts how many reads have
+/// arrived at each replica.
+pub(crate) struct AccessTracker {
+    /// Total number of replicas in the cluster.
+    population: u16,
+
+    /// Number of consecutive integer keys per tracked range.
+    range_width: IntyKey,
+
+    /// Map from range index -> per-replica access counts.
+    counts: HashMap<IntyKey, Vec<u64>>,
+}
+
+impl AccessTracker {
+    /// Creates a new access tracker with given key range width.
+    pub(crate) fn new(
+        population: u16,
+        range_width: u64,
+    ) -> Result<Self, SummersetError> {
+        if range_width == 0 {
+            return logged_err!("invalid range_width {}", range_width);
+        }
+        Ok(AccessTracker {
+            population,
+            range_width: range_width as IntyKey,
+            counts: HashMap::new(),
+        })
+    }
+
+    /// Returns the (start, end) keys of the range at given index.
+    fn range_keys(&self, idx: IntyKey) -> (String, String) {
+        let start = idx.saturating_mul(self.range_width);
+        let end = start.saturating_add(self.range_width - 1);
+        (inty_to_key(start), inty_to_key(end))
+    }
+
+    /// Records a read access to key that arrived at replica `origin`.
+    /// Non-integer-mappable keys are ignored.
+    pub(crate) fn record(&mut self, key: &str, origin: ReplicaId) {
+        debug_assert!(origin < self.population);
+        if let Ok(key_int) = key_to_inty(key) {
+            self.counts
+                .entry(key_int / self.range_width)
+                .or_insert_with(|| vec![0; self.population as usize])
+                [origin as usize] += 1;
+        }
+    }
+
+    /// Takes out all counts recorded for replica `origin` as a list of
+    /// (range index, count) pairs, and clears the tracker. Used by
+    /// non-leaders to report their local accesses to the leader.
+    pub(crate) fn drain_report(
+        &mut self,
+        origin: ReplicaId,
+    ) -> Vec<(IntyKey, u64)> {
+        self.counts
+            .drain()
+            .filter_map(|(idx, cnts)| {
+                let cnt = cnts[origin as usize];
+                (cnt > 0).then_some((idx, cnt))
+            })
+            .collect()
+    }
+
+    /// Merges a report of accesses that arrived at replica `origin`.
+    pub(crate) fn merge_report(
+        &mut self,
+        origin: ReplicaId,
+        report: Vec<(IntyKey, u64)>,
+    ) {
+        debug_assert!(origin < self.population);
+        for (idx, cnt) in report {
+            self.counts
+                .entry(idx)
+                .or_insert_with(|| vec![0; self.population as usize])
+                [origin as usize] += cnt;
+        }
+    }
+
+    /// Proposes updated responders for ranges that saw at least `min_reads`
+    /// accesses: a replica becomes a responder of a range if it received at
+    /// least `min_share` of the range's accesses. Only ranges whose proposed
+    /// responders differ from those in `conf` are returned. Counts are halved
+    /// afterwards so that older traffic gradually fades out.
+    pub(crate) fn propose(
+        &mut self,
+        conf: &RespondersConf,
+        min_reads: u64,
+        min_share: f64,
+    ) -> Result<Vec<RangeProposal>, SummersetError> {
+        let mut ranges: Vec<_> = self.counts.keys().copied().collect();
+        ranges.sort_unstable();
+
+        let mut proposals = vec![];
+        for idx in ranges {
+            let cnts = &self.counts[&idx];
+            let total: u64 = cnts.iter().sum();
+            if total == 0 || total < min_reads {
+                continue;
+            }
+
+            let mut responders = Bitmap::new(self.population, false);
+            for (r, &cnt) in cnts.iter().enumerate() {
+                if cnt as f64 >= min_share * total as f64 {
+                    responders.set(r as ReplicaId, true)?;
+                }
+            }
+            if responders.count() == 0 {
+                continue;
+            }
+
+            let (start, end) = self.range_keys(idx);
+            if conf
+                .get_responders_by_key(&start)
+                .is_some_and(|(curr, _)| *curr == responders)
+            {
+                continue;
+            }
+            proposals.push(((start, end), responders));
+        }
+
+        // decay all counts
+        self.counts.retain(|_, cnts| {
+            for cnt in cnts.iter_mut() {
+                *cnt /= 2;
+            }
+            cnts.iter().any(|&cnt| cnt > 0)
+        });
+
+        Ok(proposals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_and_drain() -> Result<(), SummersetError> {
+        let mut tracker = AccessTracker::new(3, 100)?;
+        tracker.record("k5", 1);
+        tracker.record("k99", 1);
+        tracker.record("k150", 1);
+        tracker.record("foo", 1);
+        let mut report = tracker.drain_report(1);
+        report.sort_unstable();
+        assert_eq!(report, vec![(0, 2), (1, 1)]);
+        assert!(tracker.drain_report(1).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn propose_skewed() -> Result<(), SummersetError> {
+        let mut tracker = AccessTracker::new(3, 100)?;
+        let conf = RespondersConf::empty(3);
+        for _ in 0..90 {
+            tracker.record("k10", 2);
+        }
+        for _ in 0..10 {
+            tracker.record("k20", 0);
+        }
+        tracker.merge_report(1, vec![(0, 50), (3, 5)]);
+        let proposals = tracker.propose(&conf, 20, 0.3)?;
+        assert_eq!(
+            proposals,
+            vec![(("k0".into(), "k99".into()), Bitmap::from((3, vec![1, 2])))]
+        );
+        // counts are halved after proposing
+        assert_eq!(tracker.counts[&0], vec![5, 25, 45]);
+        assert_eq!(tracker.counts[&3], vec![0, 2, 0]);
+        Ok(())
+    }
+
+    #[test]
+    fn propose_unchanged() -> Result<(), SummersetError> {
+        let mut tracker = AccessTracker::new(3, 100)?;
+        let mut conf = RespondersConf::empty(3);
+        conf.set_responders(
+            Some(&("k0".into(), "k99".into())),
+            Bitmap::from((3, vec![0])),
+            None,
+        )?;
+        for _ in 0..30 {
+            tracker.record("k42", 0);
+        }
+        assert!(tracker.propose(&conf, 20, 0.5)?.is_empty());
+        Ok(())
+    }
+}
@@ -1,15 +1,28 @@
 //! Summerset server state machine module implementation.
 
-use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::OnceLock;
 
+use crate::client::ClientId;
+use crate::server::mirror::{
+    mirror_target, MirrorShipperTask, MIRROR_FEED_LEN,
+};
 use crate::server::ReplicaId;
-use crate::utils::SummersetError;
+use crate::utils::{
+    spawn_inherit_me, HlcStamp, HybridClock, QueueGauge, SummersetError,
+};
+
+use bytes::Bytes;
 
 use get_size::GetSize;
 
 use serde::{Deserialize, Serialize};
 
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot, watch};
 use tokio::task::JoinHandle;
 
 /// Command ID type.
@@ -21,12 +34,55 @@ pub enum Command {
     /// Get the value of given key.
     Get { key: String },
 
+    /// Get up to `limit` key-value pairs with keys in range [`start_key`,
+    /// `end_key`), in key order. An empty `end_key` means no upper bound,
+    /// and a `limit` of 0 means no limit.
+    Scan {
+        start_key: String,
+        end_key: String,
+        limit: usize,
+    },
+
     /// Put a new value into key.
     Put { key: String, value: String },
+
+    /// Put a new value into key only if its current value equals `expect`,
+    /// where `None` means the key must be absent.
+    Cas {
+        key: String,
+        expect: Option<String>,
+        value: String,
+    },
+
+    /// Acquire the lock named key for owner, holding it for `ttl_ms` millisecs
+    /// unless refreshed by acquiring again. If someone else holds it, owner
+    /// gets queued as a waiter and is granted the lock in FIFO order upon
+    /// release or expiry. `now_ms` is the issuer's wall-clock time, which
+    /// drives the lock table's logical clock. Locks live in a namespace
+    /// separate from key-value pairs. The owner is filled in by the server
+    /// receiving the request with the ID of the client connection it came
+    /// from; whatever the client puts in is ignored.
+    Acquire {
+        key: String,
+        owner: ClientId,
+        ttl_ms: u64,
+        now_ms: u64,
+    },
+
+    /// Release the lock named key held by owner, or leave its wait queue if
+    /// owner is waiting. The owner is filled in by the server, as for
+    /// `Acquire`.
+    Release {
+        key: String,
+        owner: ClientId,
+        now_ms: u64,
+    },
 }
 
 impl Command {
-    /// Is the command type read-only? If so, returns the key queried.
+    /// Is the command a single-key read? If so, returns the key queried.
+    /// Scans are read-only as well but span a range of keys; see
+    /// `scan_range()`.
     #[inline]
     pub fn read_only(&self) -> Option<&String> {
         if let Command::Get { key } = self {
@@ -36,15 +92,239 @@ impl Command {
         }
     }
 
-    /// Is the command non-read-only? If so, returns the key updated.
+    /// Is the command a range scan? If so, returns its start and end keys.
     #[inline]
-    pub fn write_key(&self) -> Option<&String> {
-        if let Command::Put { key, .. } = self {
-            Some(key)
+    pub fn scan_range(&self) -> Option<(&String, &String)> {
+        if let Command::Scan {
+            start_key, end_key, ..
+        } = self
+        {
+            Some((start_key, end_key))
         } else {
             None
         }
     }
+
+    /// Is the command non-read-only? If so, returns the key updated. Lock
+    /// commands do not update any key-value pair and thus return `None`.
+    #[inline]
+    pub fn write_key(&self) -> Option<&String> {
+        match self {
+            Command::Put { key, .. } | Command::Cas { key, .. } => Some(key),
+            _ => None,
+        }
+    }
+
+    /// Is the command a lock-service command? If so, returns the lock name.
+    #[inline]
+    pub fn lock_key(&self) -> Option<&String> {
+        match self {
+            Command::Acquire { key, .. } | Command::Release { key, .. } => {
+                Some(key)
+            }
+            _ => None,
+        }
+    }
+
+    /// Computes the conflict footprint of the command, as declared by the
+    /// specification plugged in by `set_conflict_spec()`, or by the default
+    /// key-value one otherwise.
+    #[inline]
+    pub fn footprint(&self) -> Footprint {
+        match CONFLICT_SPEC.get() {
+            Some(spec) => spec(self),
+            None => Footprint::of_kv(self),
+        }
+    }
+
+    /// Computes the checksum of the command's content, for detecting its
+    /// corruption anywhere between the client and execution.
+    // NOTE: the 64-bit FNV-1a hash of the serialized command catches random
+    //       corruption but not deliberate tampering.
+    pub fn checksum(&self) -> u64 {
+        let bytes = bincode::serialize(self).unwrap_or_default();
+        bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+}
+
+/// Conflict footprint of a command: the keys it reads and writes. Two
+/// commands conflict if one writes a key that the other reads or writes;
+/// otherwise they commute, i.e., applying them in either order leads to the
+/// same state and results. Protocols that order or execute commutative
+/// commands independently, e.g., EPaxos, rely on this relation.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Footprint {
+    /// Keys the command reads.
+    pub reads: Vec<String>,
+
+    /// Key ranges [`start_key`, `end_key`) the command reads, where an empty
+    /// `end_key` means no upper bound.
+    pub read_ranges: Vec<(String, String)>,
+
+    /// Keys the command writes.
+    pub writes: Vec<String>,
+}
+
+impl Footprint {
+    /// Footprint of a command under the default key-value store, where
+    /// commands conflict if they touch the same key and one of them is a
+    /// write. Lock commands write their lock name.
+    pub fn of_kv(cmd: &Command) -> Self {
+        match cmd {
+            Command::Get { key } => Footprint {
+                reads: vec![key.clone()],
+                ..Default::default()
+            },
+            Command::Scan {
+                start_key, end_key, ..
+            } => Footprint {
+                read_ranges: vec![(start_key.clone(), end_key.clone())],
+                ..Default::default()
+            },
+            Command::Put { key, .. }
+            | Command::Cas { key, .. }
+            | Command::Acquire { key, .. }
+            | Command::Release { key, .. } => Footprint {
+                writes: vec![key.clone()],
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Does the footprint read key, either directly or through a range?
+    pub fn reads_key(&self, key: &String) -> bool {
+        self.reads.contains(key)
+            || self
+                .read_ranges
+                .iter()
+                .any(|(start_key, end_key)| in_range(key, start_key, end_key))
+    }
+
+    /// Does a command of this footprint conflict with one of `other`?
+    pub fn conflicts_with(&self, other: &Footprint) -> bool {
+        self.writes
+            .iter()
+            .any(|key| other.writes.contains(key) || other.reads_key(key))
+            || other.writes.iter().any(|key| self.reads_key(key))
+    }
+}
+
+/// Is key in range [`start_key`, `end_key`), where an empty `end_key` means
+/// no upper bound?
+#[inline]
+fn in_range(key: &String, start_key: &String, end_key: &String) -> bool {
+    key >= start_key && (end_key.is_empty() || key < end_key)
+}
+
+/// Per-key index over the footprints of a set of in-flight commands, e.g.,
+/// ones executed speculatively but not yet known to be durable, answering
+/// whether a new command commutes with all of them.
+#[derive(Debug)]
+pub(crate) struct ConflictIndex<Id> {
+    /// Map from indexed command ID -> its footprint.
+    footprints: HashMap<Id, Footprint>,
+
+    /// Map from key -> number of indexed commands reading it directly.
+    reads: HashMap<String, usize>,
+
+    /// Map from key -> number of indexed commands writing it.
+    writes: HashMap<String, usize>,
+
+    /// Key ranges read by indexed commands, tagged with the command's ID.
+    read_ranges: Vec<(Id, String, String)>,
+}
+
+impl<Id> ConflictIndex<Id>
+where
+    Id: Eq + Hash + Clone,
+{
+    /// Creates an empty conflict index.
+    pub(crate) fn new() -> Self {
+        ConflictIndex {
+            footprints: HashMap::new(),
+            reads: HashMap::new(),
+            writes: HashMap::new(),
+            read_ranges: vec![],
+        }
+    }
+
+    /// Number of commands indexed.
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.footprints.len()
+    }
+
+    /// Is the command of given ID indexed?
+    #[inline]
+    pub(crate) fn contains(&self, id: &Id) -> bool {
+        self.footprints.contains_key(id)
+    }
+
+    /// Does a command of given footprint conflict with any indexed one?
+    pub(crate) fn conflicts(&self, footprint: &Footprint) -> bool {
+        footprint.writes.iter().any(|key| {
+            self.writes.contains_key(key)
+                || self.reads.contains_key(key)
+                || self.read_ranges.iter().any(|(_, start_key, end_key)| {
+                    in_range(key, start_key, end_key)
+                })
+        }) || footprint
+            .reads
+            .iter()
+            .any(|key| self.writes.contains_key(key))
+            || footprint.read_ranges.iter().any(|(start_key, end_key)| {
+                self.writes
+                    .keys()
+                    .any(|key| in_range(key, start_key, end_key))
+            })
+    }
+
+    /// Indexes a command of given ID and footprint, replacing the one of the
+    /// same ID if any.
+    pub(crate) fn insert(&mut self, id: Id, footprint: Footprint) {
+        self.remove(&id);
+        for key in &footprint.reads {
+            *self.reads.entry(key.clone()).or_default() += 1;
+        }
+        for key in &footprint.writes {
+            *self.writes.entry(key.clone()).or_default() += 1;
+        }
+        for (start_key, end_key) in &footprint.read_ranges {
+            self.read_ranges.push((
+                id.clone(),
+                start_key.clone(),
+                end_key.clone(),
+            ));
+        }
+        self.footprints.insert(id, footprint);
+    }
+
+    /// Removes the command of given ID from the index. Returns false if it
+    /// was not indexed.
+    pub(crate) fn remove(&mut self, id: &Id) -> bool {
+        let Some(footprint) = self.footprints.remove(id) else {
+            return false;
+        };
+        for (keys, counts) in [
+            (&footprint.reads, &mut self.reads),
+            (&footprint.writes, &mut self.writes),
+        ] {
+            for key in keys {
+                if let Some(cnt) = counts.get_mut(key) {
+                    *cnt -= 1;
+                    if *cnt == 0 {
+                        counts.remove(key);
+                    }
+                }
+            }
+        }
+        if !footprint.read_ranges.is_empty() {
+            self.read_ranges.retain(|(range_id, _, _)| range_id != id);
+        }
+        true
+    }
 }
 
 /// Command execution result returned by the state machine.
@@ -53,35 +333,931 @@ pub enum CommandResult {
     /// `Some(value)` if key is found in state machine, else `None`.
     Get { value: Option<String> },
 
+    /// Key-value pairs found in the scanned range, in key order.
+    Scan { pairs: Vec<(String, String)> },
+
     /// `Some(old_value)` if key was in state machine, else `None`.
     Put { old_value: Option<String> },
+
+    /// Whether the value got swapped in, and the value of key before this
+    /// command (`Some(old_value)` if key was in state machine, else `None`).
+    Cas {
+        success: bool,
+        old_value: Option<String>,
+    },
+
+    /// Whether the lock is now held by the acquiring owner, and its current
+    /// holder (which is someone else if not granted).
+    Acquire { granted: bool, holder: ClientId },
+
+    /// Whether the lock was held by the releasing owner.
+    Release { released: bool },
+
+    /// The Put was rejected without taking effect, because it would grow the
+    /// key's namespace beyond its storage quota.
+    QuotaExceeded { namespace: String },
+
+    /// The write was rejected without taking effect, because the server's
+    /// WAL disk is critically short of free space.
+    DiskFull { free_bytes: u64 },
+
+    /// The command was not executed, because its content no longer matches
+    /// the checksum computed by the client, i.e., it got corrupted on its
+    /// way (e.g., over the network or on disk).
+    ChecksumMismatch,
 }
 
 impl CommandResult {
     /// Is the command type read-only?
     #[inline]
     pub fn read_only(&self) -> bool {
-        matches!(self, CommandResult::Get { .. })
+        matches!(self, CommandResult::Get { .. } | CommandResult::Scan { .. })
+    }
+}
+
+/// Summary of the key-value pairs held by a state machine, together with one
+/// page of its keys, answering a keys enumeration query.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct KeysReport {
+    /// Total number of keys.
+    pub num_keys: usize,
+
+    /// Approximate total bytes of keys and values.
+    pub total_bytes: usize,
+
+    /// Keys right after the queried cursor, in key order.
+    pub keys: Vec<String>,
+
+    /// Cursor to query the next page with, or `None` if no keys follow.
+    pub next_cursor: Option<String>,
+}
+
+/// State is simply an ordered `BTreeMap` from `String` key -> `String` value,
+/// ordered so that range scans are cheap.
+type State = BTreeMap<String, String>;
+
+/// Approximate storage usage per namespace, for enforcing quotas. The
+/// namespace of a key is its prefix before the first '/', or the empty
+/// string if the key contains no '/'.
+#[derive(Debug, Default)]
+struct NsUsage {
+    /// Quota in bytes per namespace; 0 means unlimited.
+    quota: usize,
+
+    /// Map from namespace -> approximate bytes of key-value pairs stored.
+    bytes: HashMap<String, usize>,
+}
+
+impl NsUsage {
+    /// Creates an empty usage tracker with given quota.
+    fn new(quota: usize) -> Self {
+        NsUsage {
+            quota,
+            bytes: HashMap::new(),
+        }
+    }
+
+    /// Gets the namespace of key.
+    #[inline]
+    fn namespace(key: &str) -> &str {
+        key.split_once('/').map_or("", |(ns, _)| ns)
+    }
+
+    /// Attempts to account for replacing the value of key (of `old_len`
+    /// bytes, if present) with a value of `new_len` bytes. Returns false
+    /// without changing anything if that would exceed the quota; shrinking
+    /// writes are always allowed.
+    fn update(
+        &mut self,
+        key: &str,
+        old_len: Option<usize>,
+        new_len: usize,
+    ) -> bool {
+        let ns = Self::namespace(key);
+        let used = self.bytes.get(ns).copied().unwrap_or(0);
+        let freed = old_len.map_or(0, |len| key.len() + len);
+        let added = key.len() + new_len;
+        let new_used = (used + added).saturating_sub(freed);
+        if self.quota > 0 && new_used > self.quota && added > freed {
+            return false;
+        }
+        self.bytes.insert(ns.into(), new_used);
+        true
+    }
+}
+
+/// State of one lock of the lock-service commands.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LockState {
+    /// Current holder and the logical time its hold expires at.
+    holder: Option<(ClientId, u64)>,
+
+    /// Owners waiting for the lock with their requested TTLs, FIFO.
+    waiters: VecDeque<(ClientId, u64)>,
+}
+
+/// Lock table of the lock-service commands. Time here is a logical clock
+/// advanced only by timestamps carried in the lock commands, so that every
+/// replica expires and grants locks at exactly the same points of the
+/// command sequence.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LockTable {
+    /// Logical clock in millisecs, the max `now_ms` seen so far.
+    clock: u64,
+
+    /// Map from lock name -> its state; only non-idle locks are kept.
+    locks: HashMap<String, LockState>,
+}
+
+impl LockTable {
+    /// Creates an empty lock table.
+    fn new() -> Self {
+        LockTable::default()
+    }
+
+    /// Advances the logical clock and returns the state of lock key after
+    /// handing over any expired hold to the next waiters.
+    fn advance(&mut self, key: &str, now_ms: u64) -> &mut LockState {
+        self.clock = self.clock.max(now_ms);
+        let clock = self.clock;
+        let lock = self.locks.entry(key.into()).or_default();
+        loop {
+            match lock.holder {
+                Some((_, expire)) if expire > clock => break,
+                _ => {
+                    lock.holder = lock
+                        .waiters
+                        .pop_front()
+                        .map(|(owner, ttl_ms)| (owner, clock + ttl_ms));
+                    if lock.holder.is_none() {
+                        break;
+                    }
+                }
+            }
+        }
+        lock
+    }
+
+    /// Drops the state of lock key if it is idle.
+    fn cleanup(&mut self, key: &str) {
+        if self
+            .locks
+            .get(key)
+            .is_some_and(|lock| lock.holder.is_none())
+        {
+            self.locks.remove(key);
+        }
+    }
+
+    /// Executes an Acquire command.
+    fn acquire(
+        &mut self,
+        key: &str,
+        owner: ClientId,
+        ttl_ms: u64,
+        now_ms: u64,
+    ) -> CommandResult {
+        let clock = self.clock.max(now_ms);
+        let lock = self.advance(key, now_ms);
+        let holder = match lock.holder {
+            Some((holder, _)) if holder != owner => {
+                // queue up as a waiter, or refresh my requested TTL
+                if let Some(waiter) =
+                    lock.waiters.iter_mut().find(|(o, _)| *o == owner)
+                {
+                    waiter.1 = ttl_ms;
+                } else {
+                    lock.waiters.push_back((owner, ttl_ms));
+                }
+                holder
+            }
+            _ => {
+                // free or already held by me; (re)start my hold
+                lock.waiters.retain(|(o, _)| *o != owner);
+                lock.holder = Some((owner, clock + ttl_ms));
+                owner
+            }
+        };
+        CommandResult::Acquire {
+            granted: holder == owner,
+            holder,
+        }
+    }
+
+    /// Executes a Release command.
+    fn release(
+        &mut self,
+        key: &str,
+        owner: ClientId,
+        now_ms: u64,
+    ) -> CommandResult {
+        let lock = self.advance(key, now_ms);
+        let released = if lock.holder.is_some_and(|(h, _)| h == owner) {
+            lock.holder = None;
+            self.advance(key, now_ms);
+            true
+        } else {
+            lock.waiters.retain(|(o, _)| *o != owner);
+            false
+        };
+        self.cleanup(key);
+        CommandResult::Release { released }
+    }
+}
+
+/// Deterministic application replicated by the protocols, to which the
+/// state machine applies committed commands in the order submitted. Any
+/// implementation must be deterministic, so that replicas applying the same
+/// commands end up with the same state and results. Applications other than
+/// the default key-value store interpret `Command`s in their own way, e.g.,
+/// a counter may take the value of a Put as an increment.
+pub trait AppStateMachine: Send {
+    /// Applies a command to the application state, returning its result.
+    fn apply(&mut self, cmd: &Command) -> CommandResult;
+
+    /// Applies a command knowing its ID, which an application persisting
+    /// its state may record along with the changes. Defaults to `apply()`.
+    fn apply_with_id(
+        &mut self,
+        _id: CommandId,
+        cmd: &Command,
+    ) -> CommandResult {
+        self.apply(cmd)
+    }
+
+    /// Serializes the whole application state. This is what replication
+    /// protocols store in their snapshot files.
+    fn snapshot(&self) -> Result<Bytes, SummersetError>;
+
+    /// Replaces the whole application state with one taken by `snapshot()`,
+    /// e.g., when recovering from or installing a snapshot file.
+    fn restore(&mut self, snap: Bytes) -> Result<(), SummersetError>;
+
+    /// Reports the number and size of key-value pairs held, listing up to
+    /// `limit` keys after `cursor` (or from the first key if `cursor` is
+    /// empty). Applications not holding key-value pairs return `None`, as
+    /// they do by default.
+    fn list_keys(&self, _cursor: &str, _limit: usize) -> Option<KeysReport> {
+        None
+    }
+}
+
+/// Factory of the application plugged in by `set_app_state_machine()`.
+static APP_FACTORY: OnceLock<fn() -> Box<dyn AppStateMachine>> =
+    OnceLock::new();
+
+/// Plugs in an application in place of the default key-value store for all
+/// state machines created in this process afterwards. Should be called once
+/// at startup, before any replica gets set up.
+pub fn set_app_state_machine(factory: fn() -> Box<dyn AppStateMachine>) {
+    APP_FACTORY.get_or_init(|| factory);
+}
+
+/// Conflict specification plugged in by `set_conflict_spec()`.
+static CONFLICT_SPEC: OnceLock<fn(&Command) -> Footprint> = OnceLock::new();
+
+/// Plugs in the conflict specification of the application, mapping each
+/// command to its `Footprint`, in place of the default rule that commands
+/// conflict if they touch the same key. Should be called once at startup,
+/// along with `set_app_state_machine()`, and identically on all servers.
+pub fn set_conflict_spec(spec: fn(&Command) -> Footprint) {
+    CONFLICT_SPEC.get_or_init(|| spec);
+}
+
+/// The default application: an ordered key-value store with per-namespace
+/// storage quotas, plus the lock table of the lock-service commands.
+#[derive(Debug)]
+struct KvStore {
+    /// State is ultimately just an ordered key-value map.
+    state: State,
+
+    /// Lock table of the lock-service commands.
+    locks: LockTable,
+
+    /// Approximate storage usage per namespace.
+    usage: NsUsage,
+}
+
+impl KvStore {
+    /// Creates an empty key-value store with given namespace quota.
+    fn new(ns_quota: usize) -> Self {
+        KvStore {
+            state: State::new(),
+            locks: LockTable::new(),
+            usage: NsUsage::new(ns_quota),
+        }
+    }
+
+    /// Executes given command on the state machine state.
+    /// This is a non-method function to make tests easier to write.
+    fn execute(
+        state: &mut State,
+        locks: &mut LockTable,
+        usage: &mut NsUsage,
+        cmd: &Command,
+    ) -> CommandResult {
+        let result = match cmd {
+            Command::Get { key } => CommandResult::Get {
+                value: state.get(key).cloned(),
+            },
+            Command::Scan {
+                start_key,
+                end_key,
+                limit,
+            } => {
+                let end = if end_key.is_empty() {
+                    Bound::Unbounded
+                } else {
+                    Bound::Excluded(end_key)
+                };
+                let pairs = if end_key.is_empty() || start_key < end_key {
+                    state
+                        .range::<String, _>((Bound::Included(start_key), end))
+                        .take(if *limit == 0 { usize::MAX } else { *limit })
+                        .map(|(k, v)| (k.clone(), v.clone()))
+                        .collect()
+                } else {
+                    vec![]
+                };
+                CommandResult::Scan { pairs }
+            }
+            Command::Put { key, value } => {
+                let old_len = state.get(key).map(|v| v.len());
+                if usage.update(key, old_len, value.len()) {
+                    CommandResult::Put {
+                        old_value: state.insert(key.clone(), value.clone()),
+                    }
+                } else {
+                    CommandResult::QuotaExceeded {
+                        namespace: NsUsage::namespace(key).into(),
+                    }
+                }
+            }
+            Command::Cas { key, expect, value } => {
+                let old_value = state.get(key);
+                if old_value != expect.as_ref() {
+                    CommandResult::Cas {
+                        success: false,
+                        old_value: old_value.cloned(),
+                    }
+                } else if usage.update(
+                    key,
+                    old_value.map(|v| v.len()),
+                    value.len(),
+                ) {
+                    CommandResult::Cas {
+                        success: true,
+                        old_value: state.insert(key.clone(), value.clone()),
+                    }
+                } else {
+                    CommandResult::QuotaExceeded {
+                        namespace: NsUsage::namespace(key).into(),
+                    }
+                }
+            }
+            Command::Acquire {
+                key,
+                owner,
+                ttl_ms,
+                now_ms,
+            } => locks.acquire(key, *owner, *ttl_ms, *now_ms),
+            Command::Release { key, owner, now_ms } => {
+                locks.release(key, *owner, *now_ms)
+            }
+        };
+
+        result
+    }
+
+    /// Replaces the whole state and lock table. Usage is recounted rather
+    /// than checked against the quota, as the given pairs have been admitted
+    /// already.
+    fn replace(&mut self, state: State, locks: LockTable) {
+        let mut usage = NsUsage::new(self.usage.quota);
+        for (key, value) in &state {
+            *usage
+                .bytes
+                .entry(NsUsage::namespace(key).into())
+                .or_default() += key.len() + value.len();
+        }
+        self.state = state;
+        self.locks = locks;
+        self.usage = usage;
+    }
+
+    /// Lists up to `limit` keys after `cursor` along with the number and
+    /// size of all pairs. Like `execute()`, a non-method function for tests.
+    fn list_keys(
+        state: &State,
+        usage: &NsUsage,
+        cursor: &str,
+        limit: usize,
+    ) -> KeysReport {
+        let start = if cursor.is_empty() {
+            Bound::Unbounded
+        } else {
+            Bound::Excluded(cursor)
+        };
+        let mut iter = state.range::<str, _>((start, Bound::Unbounded));
+        let keys: Vec<String> =
+            iter.by_ref().take(limit).map(|(k, _)| k.clone()).collect();
+        let next_cursor = match (keys.last(), iter.next()) {
+            (Some(last), Some(_)) => Some(last.clone()),
+            _ => None,
+        };
+        KeysReport {
+            num_keys: state.len(),
+            total_bytes: usage.bytes.values().sum(),
+            keys,
+            next_cursor,
+        }
+    }
+}
+
+impl AppStateMachine for KvStore {
+    fn apply(&mut self, cmd: &Command) -> CommandResult {
+        Self::execute(&mut self.state, &mut self.locks, &mut self.usage, cmd)
+    }
+
+    fn snapshot(&self) -> Result<Bytes, SummersetError> {
+        let snap = bincode::serialize(&(&self.state, &self.locks))?;
+        Ok(Bytes::from(snap))
+    }
+
+    fn restore(&mut self, snap: Bytes) -> Result<(), SummersetError> {
+        let (state, locks): (State, LockTable) = bincode::deserialize(&snap)?;
+        self.replace(state, locks);
+        Ok(())
+    }
+
+    fn list_keys(&self, cursor: &str, limit: usize) -> Option<KeysReport> {
+        Some(Self::list_keys(&self.state, &self.usage, cursor, limit))
+    }
+}
+
+/// Key prefix of key-value pairs in a durable store's database.
+#[cfg(feature = "persistent-state")]
+const DURABLE_KV_PREFIX: &[u8] = b"kv/";
+
+/// Key of the lock table in a durable store's database.
+#[cfg(feature = "persistent-state")]
+const DURABLE_LOCKS_KEY: &[u8] = b"meta/locks";
+
+/// Key of the ID of the last state-changing command applied in a durable
+/// store's database.
+#[cfg(feature = "persistent-state")]
+const DURABLE_APPLIED_KEY: &[u8] = b"meta/applied";
+
+/// The default key-value store with its state persisted in an embedded sled
+/// database. The effects of every state-changing command are written in one
+/// atomic batch together with its ID, so the store found after a crash holds
+/// exactly the commands up to the recorded ID. Batches are not synced one by
+/// one: the protocol's WAL remains what makes commands durable, and a store
+/// that lost its tail in a crash merely records an earlier ID to replay from.
+#[cfg(feature = "persistent-state")]
+struct DurableKvStore {
+    /// In-memory copy of the state, serving all reads.
+    kv: KvStore,
+
+    /// Database the state is persisted in.
+    db: sled::Db,
+
+    /// ID of the last state-changing command applied, if any.
+    applied: Option<CommandId>,
+
+    /// Set once a write to the database failed; no further changes are
+    /// persisted then, so that the recorded ID stays consistent with them.
+    broken: bool,
+}
+
+#[cfg(feature = "persistent-state")]
+impl DurableKvStore {
+    /// Opens or creates the store's database at given path, loading its
+    /// whole state in memory.
+    fn open(path: &Path, ns_quota: usize) -> Result<Self, SummersetError> {
+        let db = sled::open(path).map_err(SummersetError::msg)?;
+        let mut state = State::new();
+        for pair in db.scan_prefix(DURABLE_KV_PREFIX) {
+            let (key, value) = pair.map_err(SummersetError::msg)?;
+            state.insert(
+                String::from_utf8(key[DURABLE_KV_PREFIX.len()..].to_vec())?,
+                String::from_utf8(value.to_vec())?,
+            );
+        }
+        let locks =
+            match db.get(DURABLE_LOCKS_KEY).map_err(SummersetError::msg)? {
+                Some(bytes) => bincode::deserialize(&bytes)?,
+                None => LockTable::new(),
+            };
+        let applied =
+            match db.get(DURABLE_APPLIED_KEY).map_err(SummersetError::msg)? {
+                Some(bytes) => Some(CommandId::from_le_bytes(
+                    bytes.as_ref().try_into().map_err(SummersetError::msg)?,
+                )),
+                None => None,
+            };
+
+        let mut kv = KvStore::new(ns_quota);
+        kv.replace(state, locks);
+        Ok(DurableKvStore {
+            kv,
+            db,
+            applied,
+            broken: false,
+        })
+    }
+
+    /// Applies a command and persists its effects, if any, along with its ID
+    /// if known.
+    fn apply_durably(
+        &mut self,
+        id: Option<CommandId>,
+        cmd: &Command,
+    ) -> CommandResult {
+        let result = self.kv.apply(cmd);
+        if self.broken
+            || (cmd.write_key().is_none() && cmd.lock_key().is_none())
+        {
+            return result;
+        }
+
+        let mut batch = sled::Batch::default();
+        if let Some(key) = cmd.write_key() {
+            let db_key = [DURABLE_KV_PREFIX, key.as_bytes()].concat();
+            match self.kv.state.get(key) {
+                Some(value) => batch.insert(db_key, value.as_bytes()),
+                None => batch.remove(db_key),
+            }
+        }
+        if cmd.lock_key().is_some() {
+            // the lock table is small, so it is simply rewritten as a whole
+            match bincode::serialize(&self.kv.locks) {
+                Ok(bytes) => batch.insert(DURABLE_LOCKS_KEY, bytes),
+                Err(e) => {
+                    pf_error!("error serializing lock table: {}", e);
+                    self.broken = true;
+                    return result;
+                }
+            }
+        }
+        // IDs are taken as increasing in execution order; commands without
+        // an ID, e.g., ones restoring a protocol snapshot, do not move it
+        self.applied = self.applied.max(id);
+        if let Some(applied) = self.applied {
+            batch.insert(DURABLE_APPLIED_KEY, &applied.to_le_bytes()[..]);
+        }
+
+        if let Err(e) = self.db.apply_batch(batch) {
+            pf_error!("error persisting state: {}", e);
+            self.broken = true;
+        }
+        result
+    }
+}
+
+#[cfg(feature = "persistent-state")]
+impl AppStateMachine for DurableKvStore {
+    fn apply(&mut self, cmd: &Command) -> CommandResult {
+        self.apply_durably(None, cmd)
+    }
+
+    fn apply_with_id(&mut self, id: CommandId, cmd: &Command) -> CommandResult {
+        self.apply_durably(Some(id), cmd)
+    }
+
+    fn snapshot(&self) -> Result<Bytes, SummersetError> {
+        self.kv.snapshot()
+    }
+
+    fn restore(&mut self, snap: Bytes) -> Result<(), SummersetError> {
+        self.kv.restore(snap)?;
+
+        // rewrite the whole database in one batch; the restored state does
+        // not correspond to any command ID, so the recorded one is dropped
+        let mut batch = sled::Batch::default();
+        for pair in self.db.scan_prefix(DURABLE_KV_PREFIX) {
+            let (key, _) = pair.map_err(SummersetError::msg)?;
+            batch.remove(key);
+        }
+        for (key, value) in &self.kv.state {
+            batch.insert(
+                [DURABLE_KV_PREFIX, key.as_bytes()].concat(),
+                value.as_bytes(),
+            );
+        }
+        batch.insert(DURABLE_LOCKS_KEY, bincode::serialize(&self.kv.locks)?);
+        batch.remove(DURABLE_APPLIED_KEY);
+        self.applied = None;
+        self.db.apply_batch(batch).map_err(SummersetError::msg)?;
+        self.broken = false;
+        Ok(())
+    }
+
+    fn list_keys(&self, cursor: &str, limit: usize) -> Option<KeysReport> {
+        AppStateMachine::list_keys(&self.kv, cursor, limit)
+    }
+}
+
+/// Number of rows of the hot-key count-min sketch.
+const SKETCH_DEPTH: usize = 4;
+
+/// Number of counters per row of the hot-key count-min sketch.
+const SKETCH_WIDTH: usize = 2048;
+
+/// Number of hottest key candidates tracked alongside the sketch.
+const HOT_KEYS_TRACKED: usize = 32;
+
+/// Number of hottest keys returned by `StateMachine::hot_keys()`.
+const HOT_KEYS_REPORTED: usize = 10;
+
+/// Number of executed commands between two hot keys publications, when the
+/// executor never gets idle.
+const HOT_KEYS_PUBLISH_EVERY: u64 = 1000;
+
+/// Approximate per-key access counter, using a count-min sketch for the
+/// counts and a small candidates set for the hottest keys.
+struct HotKeys {
+    /// Count-min sketch counters.
+    sketch: Vec<[u64; SKETCH_WIDTH]>,
+
+    /// Map from candidate hot key -> its latest estimated count.
+    candidates: HashMap<String, u64>,
+}
+
+impl HotKeys {
+    /// Creates an empty hot-key counter.
+    fn new() -> Self {
+        HotKeys {
+            sketch: vec![[0; SKETCH_WIDTH]; SKETCH_DEPTH],
+            candidates: HashMap::new(),
+        }
+    }
+
+    /// Records one access to key, returning its new estimated count.
+    fn record(&mut self, key: &str) -> u64 {
+        let mut estimate = u64::MAX;
+        for (row, counters) in self.sketch.iter_mut().enumerate() {
+            let mut hasher = DefaultHasher::new();
+            (row, key).hash(&mut hasher);
+            let counter =
+                &mut counters[(hasher.finish() as usize) % SKETCH_WIDTH];
+            *counter += 1;
+            estimate = estimate.min(*counter);
+        }
+
+        if let Some(cnt) = self.candidates.get_mut(key) {
+            *cnt = estimate;
+        } else if self.candidates.len() < HOT_KEYS_TRACKED {
+            self.candidates.insert(key.into(), estimate);
+        } else {
+            // replace the coldest candidate if this key is now hotter
+            let (coldest, &min_cnt) =
+                self.candidates.iter().min_by_key(|(_, &cnt)| cnt).unwrap();
+            if estimate > min_cnt {
+                let coldest = coldest.clone();
+                self.candidates.remove(&coldest);
+                self.candidates.insert(key.into(), estimate);
+            }
+        }
+        estimate
+    }
+
+    /// Returns the tracked candidates sorted by estimated count, hottest
+    /// first.
+    fn ranked(&self) -> Vec<(String, u64)> {
+        let mut ranked: Vec<_> = self
+            .candidates
+            .iter()
+            .map(|(key, &cnt)| (key.clone(), cnt))
+            .collect();
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranked
+    }
+}
+
+/// Maximum number of Get results kept in the read cache.
+const READ_CACHE_CAPACITY: usize = 1024;
+
+/// Maximum value length in bytes of a Get result kept in the read cache.
+const READ_CACHE_MAX_VALUE: usize = 4096;
+
+/// Cache of recent Get results, kept on the submitting side of the state
+/// machine. Every command passes through it in submission order, which is
+/// also the execution order, so a cached result stays valid until the next
+/// write to its key gets submitted, at which point it is invalidated.
+#[derive(Debug, Default)]
+struct ReadCache {
+    /// Number of commands submitted so far, i.e., the applied index the
+    /// state will have reached once they are all executed.
+    submitted: u64,
+
+    /// Map from key -> (applied index of the Get, its result value).
+    entries: HashMap<String, (u64, Option<String>)>,
+
+    /// Keys in the order they got cached, for evicting the oldest; may
+    /// contain entries already invalidated or refreshed.
+    order: VecDeque<(String, u64)>,
+
+    /// Map from in-flight Get's command ID -> (key, applied index).
+    pending: HashMap<CommandId, (String, u64)>,
+
+    /// Map from key -> applied index of its latest in-flight Get that no
+    /// write has been submitted after.
+    fillable: HashMap<String, u64>,
+}
+
+impl ReadCache {
+    /// Creates an empty read cache.
+    fn new() -> Self {
+        ReadCache::default()
+    }
+
+    /// Looks up the cached result of a Get on key.
+    fn lookup(&self, key: &str) -> Option<CommandResult> {
+        self.entries.get(key).map(|(_, value)| CommandResult::Get {
+            value: value.clone(),
+        })
+    }
+
+    /// Accounts for a command being submitted.
+    fn submit(&mut self, id: CommandId, cmd: &Command) {
+        self.submitted += 1;
+        if let Some(key) = cmd.read_only() {
+            self.pending.insert(id, (key.clone(), self.submitted));
+            self.fillable.insert(key.clone(), self.submitted);
+        } else if let Some(key) = cmd.write_key() {
+            self.entries.remove(key);
+            self.fillable.remove(key);
+        }
+    }
+
+    /// Accounts for an execution result, caching it if it is the result of
+    /// the latest Get on its key and no write to the key came after.
+    fn fill(&mut self, id: CommandId, result: &CommandResult) {
+        let Some((key, index)) = self.pending.remove(&id) else {
+            return;
+        };
+        if self.fillable.get(&key) != Some(&index) {
+            return;
+        }
+        self.fillable.remove(&key);
+        let CommandResult::Get { value } = result else {
+            return;
+        };
+        if value
+            .as_ref()
+            .is_some_and(|v| v.len() > READ_CACHE_MAX_VALUE)
+        {
+            return;
+        }
+
+        while self.entries.len() >= READ_CACHE_CAPACITY {
+            let Some((old_key, old_index)) = self.order.pop_front() else {
+                break;
+            };
+            if self
+                .entries
+                .get(&old_key)
+                .is_some_and(|(i, _)| *i == old_index)
+            {
+                self.entries.remove(&old_key);
+            }
+        }
+        self.entries.insert(key.clone(), (index, value.clone()));
+        self.order.push_back((key, index));
     }
 }
 
-/// State is simply a `HashMap` from `String` key -> `String` value.
-type State = HashMap<String, String>;
+/// Command to execute as sent over the exec channel: its ID, the command,
+/// its client checksum if any, and its assigned timestamp if any.
+type ExecItem = (CommandId, Command, Option<u64>, Option<HlcStamp>);
 
-/// The local volatile state machine, which is simply an in-memory HashMap.
+/// The local volatile state machine, which applies commands to a pluggable
+/// application, by default an in-memory key-value store.
 pub(crate) struct StateMachine {
     /// My replica ID.
     _me: ReplicaId,
 
     /// Sender side of the exec channel.
-    tx_exec: mpsc::UnboundedSender<(CommandId, Command)>,
+    tx_exec: mpsc::UnboundedSender<ExecItem>,
 
     /// Receiver side of the ack channel.
     rx_ack: mpsc::UnboundedReceiver<(CommandId, CommandResult)>,
 
-    /// Join handle of the executor task. The state HashMap is owned by this
+    /// Receiver side of the latest hottest keys published by the executor.
+    rx_hot_keys: watch::Receiver<Vec<(String, u64)>>,
+
+    /// Receiver side of the HLC timestamp of the latest command executed,
+    /// published by the executor.
+    rx_applied: watch::Receiver<HlcStamp>,
+
+    /// Number of submitted commands whose results are not yet taken.
+    queue_gauge: QueueGauge,
+
+    /// Sender side of the snapshot ops channel.
+    tx_snap: mpsc::UnboundedSender<SnapOp>,
+
+    /// Cache of recent Get results, for serving repeated synchronous reads
+    /// of unchanged keys without a trip through the executor. Only kept for
+    /// the default key-value store, whose Gets are known to be pure reads.
+    read_cache: Option<ReadCache>,
+
+    /// ID of the last state-changing command a persistent store had applied
+    /// when opened.
+    durable_applied: Option<CommandId>,
+
+    /// Join handle of the executor task. The application is owned by this
     /// task.
     _executor_handle: JoinHandle<()>,
+
+    /// Sender side of the gate telling the mirror shipper whether it should
+    /// be the one shipping, if mirroring writes to another cluster.
+    mirror_gate: Option<watch::Sender<bool>>,
+
+    /// Join handle of the mirror shipper task, if mirroring writes to
+    /// another cluster.
+    _mirror_handle: Option<JoinHandle<()>>,
+}
+
+/// Effect of an executed write on the key-value state, as published on the
+/// change feed of a state machine.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) struct ChangeEvent {
+    /// ID of the write's command, i.e., its position in the log.
+    pub(crate) id: CommandId,
+
+    /// HLC timestamp of the write's execution.
+    pub(crate) hlc: HlcStamp,
+
+    /// Key written.
+    pub(crate) key: String,
+
+    /// New value of the key.
+    pub(crate) value: String,
+}
+
+impl ChangeEvent {
+    /// Gets the change made by executing command `id` `cmd` with result
+    /// `res` at `hlc`, if it did write a key. Rejected writes and lock
+    /// commands make none.
+    fn of_executed(
+        id: CommandId,
+        cmd: &Command,
+        res: &CommandResult,
+        hlc: HlcStamp,
+    ) -> Option<Self> {
+        match (cmd, res) {
+            (Command::Put { key, value }, CommandResult::Put { .. })
+            | (
+                Command::Cas { key, value, .. },
+                CommandResult::Cas { success: true, .. },
+            ) => Some(ChangeEvent {
+                id,
+                hlc,
+                key: key.clone(),
+                value: value.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Snapshot or inspection operation requested to the executor, with the
+/// sender side of a channel for its reply.
+enum SnapOp {
+    /// Take a snapshot of the application.
+    Take(oneshot::Sender<Result<Bytes, SummersetError>>),
+
+    /// Restore the application from a snapshot.
+    Restore(Bytes, oneshot::Sender<Result<(), SummersetError>>),
+
+    /// List keys of the application after a cursor, up to a limit.
+    ListKeys(String, usize, oneshot::Sender<Option<KeysReport>>),
+}
+
+/// Handle for querying the keys of a state machine from outside of the
+/// protocol owning it, e.g., by the control hub on behalf of the manager.
+#[derive(Clone)]
+pub(crate) struct KeysInspector {
+    /// Sender side of the snapshot ops channel.
+    tx_snap: mpsc::UnboundedSender<SnapOp>,
+}
+
+impl KeysInspector {
+    /// Lists up to `limit` keys after `cursor`, reflecting at least the
+    /// commands executed before this call. Returns `None` if the application
+    /// does not hold key-value pairs.
+    pub(crate) async fn list_keys(
+        &self,
+        cursor: String,
+        limit: usize,
+    ) -> Result<Option<KeysReport>, SummersetError> {
+        let (tx, rx) = oneshot::channel();
+        self.tx_snap
+            .send(SnapOp::ListKeys(cursor, limit, tx))
+            .map_err(SummersetError::msg)?;
+        rx.await.map_err(SummersetError::msg)
+    }
 }
 
 // StateMachine public API implementation
@@ -89,30 +1265,211 @@ impl StateMachine {
     /// Creates a new state machine with one executor task. Spawns the
     /// executor task. Creates an exec channel for submitting commands to the
     /// state machine and an ack channel for getting results.
+    /// The application is the one plugged in by `set_app_state_machine()`
+    /// if any, or else the default key-value store, in which a nonzero
+    /// `ns_quota` limits the approximate bytes stored per key namespace,
+    /// beyond which Puts are rejected.
     pub(crate) async fn new_and_setup(
         me: ReplicaId,
+        ns_quota: usize,
+    ) -> Result<Self, SummersetError> {
+        match APP_FACTORY.get() {
+            Some(factory) => Self::new_with_app(me, factory(), false),
+            None => {
+                Self::new_with_app(me, Box::new(KvStore::new(ns_quota)), true)
+            }
+        }
+    }
+
+    /// Creates a new state machine like `new_and_setup()`, but with the
+    /// default key-value store persisted in a database at given path, which
+    /// requires the `persistent-state` feature. The state found there is
+    /// loaded, and `durable_applied()` then tells up to which command it
+    /// reflects.
+    pub(crate) async fn new_persistent(
+        me: ReplicaId,
+        ns_quota: usize,
+        path: &Path,
+    ) -> Result<Self, SummersetError> {
+        #[cfg(feature = "persistent-state")]
+        {
+            if APP_FACTORY.get().is_some() {
+                return logged_err!(
+                    Config;
+                    "persistent state only supports the key-value store"
+                );
+            }
+            let store = DurableKvStore::open(path, ns_quota)?;
+            let applied = store.applied;
+            let mut state_machine =
+                Self::new_with_app(me, Box::new(store), true)?;
+            state_machine.durable_applied = applied;
+            Ok(state_machine)
+        }
+        #[cfg(not(feature = "persistent-state"))]
+        {
+            let _ = (me, ns_quota);
+            logged_err!(
+                Config;
+                "persistent state at {} needs the persistent-state feature",
+                path.display()
+            )
+        }
+    }
+
+    /// Creates a new state machine applying commands to given application,
+    /// with the read cache if `cache_reads` is set.
+    fn new_with_app(
+        me: ReplicaId,
+        app: Box<dyn AppStateMachine>,
+        cache_reads: bool,
     ) -> Result<Self, SummersetError> {
         let (tx_exec, rx_exec) = mpsc::unbounded_channel();
         let (tx_ack, rx_ack) = mpsc::unbounded_channel();
+        let (tx_hot_keys, rx_hot_keys) = watch::channel(vec![]);
+        let (tx_applied, rx_applied) = watch::channel(HlcStamp::default());
+        let (tx_snap, rx_snap) = mpsc::unbounded_channel();
 
-        let mut executor = StateMachineExecutorTask::new(rx_exec, tx_ack);
-        let executor_handle = tokio::spawn(async move { executor.run().await });
+        // executed writes are fed to the mirror shipper if mirroring
+        let (tx_changes, mirror_gate, mirror_handle) = match mirror_target() {
+            Some(manager) => {
+                let (tx_changes, rx_changes) = mpsc::channel(MIRROR_FEED_LEN);
+                let (mirror_gate, rx_gate) = watch::channel(false);
+                let mut shipper =
+                    MirrorShipperTask::new(manager, rx_changes, rx_gate);
+                let mirror_handle =
+                    spawn_inherit_me(async move { shipper.run().await });
+                (Some(tx_changes), Some(mirror_gate), Some(mirror_handle))
+            }
+            None => (None, None, None),
+        };
+
+        let mut executor = StateMachineExecutorTask::new(
+            rx_exec,
+            tx_ack,
+            tx_hot_keys,
+            tx_applied,
+            tx_changes,
+            rx_snap,
+            app,
+        );
+        let executor_handle =
+            spawn_inherit_me(async move { executor.run().await });
 
         Ok(StateMachine {
             _me: me,
             tx_exec,
             rx_ack,
+            rx_hot_keys,
+            rx_applied,
+            queue_gauge: QueueGauge::new(),
+            tx_snap,
+            read_cache: cache_reads.then(ReadCache::new),
+            durable_applied: None,
             _executor_handle: executor_handle,
+            mirror_gate,
+            _mirror_handle: mirror_handle,
         })
     }
 
+    /// Returns the ID of the last state-changing command the persistent store
+    /// had applied when opened, if any. Commands up to it, in the order of
+    /// their IDs, need not be executed again on recovery. Always `None` for
+    /// in-memory state machines.
+    pub(crate) fn durable_applied(&self) -> Option<CommandId> {
+        self.durable_applied
+    }
+
+    /// Returns the top few most frequently accessed keys so far with their
+    /// approximate access counts, hottest first. Counts may be overestimated,
+    /// and lag slightly behind the executed commands.
+    pub(crate) fn hot_keys(&self) -> Vec<(String, u64)> {
+        self.rx_hot_keys
+            .borrow()
+            .iter()
+            .take(HOT_KEYS_REPORTED)
+            .cloned()
+            .collect()
+    }
+
+    /// Gets a watch of the HLC timestamp of the latest command executed, for
+    /// stamping replies outside of the protocol owning me. Each executed
+    /// command gets a timestamp later than all before it.
+    pub(crate) fn applied_stamps(&self) -> watch::Receiver<HlcStamp> {
+        self.rx_applied.clone()
+    }
+
+    /// Gets a handle for querying my keys, which stays valid as long as I do.
+    pub(crate) fn keys_inspector(&self) -> KeysInspector {
+        KeysInspector {
+            tx_snap: self.tx_snap.clone(),
+        }
+    }
+
+    /// Gets the gate telling my mirror shipper whether it should be the one
+    /// shipping, if mirroring writes to another cluster.
+    pub(crate) fn mirror_gate(&self) -> Option<watch::Sender<bool>> {
+        self.mirror_gate.clone()
+    }
+
+    /// Gets the stage name, number of commands submitted but not yet
+    /// acknowledged, and its high watermark since the previous call.
+    pub(crate) fn queue_depth_report(&mut self) -> (String, usize, usize) {
+        let (depth, peak) = self.queue_gauge.report();
+        ("state_machine".into(), depth, peak)
+    }
+
+    /// Returns the number of submitted commands whose results have not been
+    /// received yet.
+    #[inline]
+    pub(crate) fn num_unacked(&self) -> usize {
+        self.queue_gauge.depth()
+    }
+
     /// Submits a command by sending it to the exec channel.
     pub(crate) fn submit_cmd(
         &mut self,
         id: CommandId,
         cmd: Command,
     ) -> Result<(), SummersetError> {
-        self.tx_exec.send((id, cmd)).map_err(SummersetError::msg)
+        self.submit_checked_cmd(id, cmd, None)
+    }
+
+    /// Submits a command carrying the checksum computed by its client, if
+    /// any. The executor verifies the checksum right before execution and
+    /// answers a `ChecksumMismatch` result instead if it does not match.
+    pub(crate) fn submit_checked_cmd(
+        &mut self,
+        id: CommandId,
+        cmd: Command,
+        checksum: Option<u64>,
+    ) -> Result<(), SummersetError> {
+        self.send_exec((id, cmd, checksum, None))
+    }
+
+    /// Submits a command carrying the checksum computed by its client, if
+    /// any, and the timestamp assigned to it by the protocol, e.g., by the
+    /// leader that proposed it. The command executes at that timestamp, so
+    /// every replica agrees on it; commands submitted without one are
+    /// stamped by the executor's own clock instead.
+    pub(crate) fn submit_stamped_cmd(
+        &mut self,
+        id: CommandId,
+        cmd: Command,
+        checksum: Option<u64>,
+        hlc: HlcStamp,
+    ) -> Result<(), SummersetError> {
+        self.send_exec((id, cmd, checksum, Some(hlc)))
+    }
+
+    /// Sends a command to the exec channel.
+    fn send_exec(&mut self, item: ExecItem) -> Result<(), SummersetError> {
+        if let Some(cache) = self.read_cache.as_mut() {
+            cache.submit(item.0, &item.1);
+        }
+        self.tx_exec.send(item).map_err(SummersetError::msg)?;
+        self.queue_gauge.enter(1);
+        Ok(())
     }
 
     /// Waits for the next execution result by receiving from the ack channel.
@@ -120,7 +1477,13 @@ impl StateMachine {
         &mut self,
     ) -> Result<(CommandId, CommandResult), SummersetError> {
         match self.rx_ack.recv().await {
-            Some((id, result)) => Ok((id, result)),
+            Some((id, result)) => {
+                self.queue_gauge.leave(1);
+                if let Some(cache) = self.read_cache.as_mut() {
+                    cache.fill(id, &result);
+                }
+                Ok((id, result))
+            }
             None => logged_err!("ack channel has been closed"),
         }
     }
@@ -131,7 +1494,13 @@ impl StateMachine {
         &mut self,
     ) -> Result<(CommandId, CommandResult), SummersetError> {
         match self.rx_ack.try_recv() {
-            Ok((id, result)) => Ok((id, result)),
+            Ok((id, result)) => {
+                self.queue_gauge.leave(1);
+                if let Some(cache) = self.read_cache.as_mut() {
+                    cache.fill(id, &result);
+                }
+                Ok((id, result))
+            }
             Err(e) => Err(SummersetError::msg(e)),
         }
     }
@@ -139,13 +1508,21 @@ impl StateMachine {
     /// Submits a command and waits for its execution result blockingly.
     /// Returns a tuple where the first element is a vec containing any old
     /// results of previously submitted commands received in the middle and
-    /// the second element is the result of this sync command.
+    /// the second element is the result of this sync command. A Get on a
+    /// key not written since its last cached result is answered right away
+    /// from the read cache.
     pub(crate) async fn do_sync_cmd(
         &mut self,
         id: CommandId,
         cmd: Command,
     ) -> Result<(Vec<(CommandId, CommandResult)>, CommandResult), SummersetError>
     {
+        if let Some(result) = cmd
+            .read_only()
+            .and_then(|key| self.read_cache.as_ref()?.lookup(key))
+        {
+            return Ok((vec![], result));
+        }
         self.submit_cmd(id, cmd)?;
         let mut old_results = vec![];
         loop {
@@ -157,56 +1534,186 @@ impl StateMachine {
             }
         }
     }
+
+    /// Takes a snapshot of the application state, reflecting exactly the
+    /// commands submitted before this call.
+    pub(crate) async fn take_snapshot(
+        &mut self,
+    ) -> Result<Bytes, SummersetError> {
+        let (tx, rx) = oneshot::channel();
+        self.tx_snap
+            .send(SnapOp::Take(tx))
+            .map_err(SummersetError::msg)?;
+        rx.await.map_err(SummersetError::msg)?
+    }
+
+    /// Replaces the application state with a snapshot taken by
+    /// `take_snapshot()`, after applying the commands submitted before this
+    /// call. Drops the read cache, as its results may no longer hold.
+    pub(crate) async fn restore_snapshot(
+        &mut self,
+        snap: Bytes,
+    ) -> Result<(), SummersetError> {
+        if self.read_cache.is_some() {
+            self.read_cache = Some(ReadCache::new());
+        }
+        let (tx, rx) = oneshot::channel();
+        self.tx_snap
+            .send(SnapOp::Restore(snap, tx))
+            .map_err(SummersetError::msg)?;
+        rx.await.map_err(SummersetError::msg)?
+    }
 }
 
 /// StateMachine command executor task.
 struct StateMachineExecutorTask {
-    rx_exec: mpsc::UnboundedReceiver<(CommandId, Command)>,
+    rx_exec: mpsc::UnboundedReceiver<ExecItem>,
     tx_ack: mpsc::UnboundedSender<(CommandId, CommandResult)>,
+    tx_hot_keys: watch::Sender<Vec<(String, u64)>>,
+    tx_applied: watch::Sender<HlcStamp>,
+    tx_changes: Option<mpsc::Sender<ChangeEvent>>,
+    rx_snap: mpsc::UnboundedReceiver<SnapOp>,
 
-    /// State is ultimately just a key-value HashMap.
-    state: State,
+    /// The application commands are applied to.
+    app: Box<dyn AppStateMachine>,
+
+    /// Approximate per-key access counters.
+    hot_keys: HotKeys,
+
+    /// Number of commands executed since the last hot keys publication.
+    unpublished: u64,
+
+    /// Hybrid logical clock stamping executed commands submitted without a
+    /// timestamp, and observing the timestamps of those submitted with one.
+    clock: HybridClock,
 }
 
 impl StateMachineExecutorTask {
     /// Creates the command executor task.
+    #[allow(clippy::too_many_arguments)]
     fn new(
-        rx_exec: mpsc::UnboundedReceiver<(CommandId, Command)>,
+        rx_exec: mpsc::UnboundedReceiver<ExecItem>,
         tx_ack: mpsc::UnboundedSender<(CommandId, CommandResult)>,
+        tx_hot_keys: watch::Sender<Vec<(String, u64)>>,
+        tx_applied: watch::Sender<HlcStamp>,
+        tx_changes: Option<mpsc::Sender<ChangeEvent>>,
+        rx_snap: mpsc::UnboundedReceiver<SnapOp>,
+        app: Box<dyn AppStateMachine>,
     ) -> Self {
         StateMachineExecutorTask {
             rx_exec,
             tx_ack,
-            state: State::new(),
+            tx_hot_keys,
+            tx_applied,
+            tx_changes,
+            rx_snap,
+            app,
+            hot_keys: HotKeys::new(),
+            unpublished: 0,
+            clock: HybridClock::new(),
         }
     }
 
-    /// Executes given command on the state machine state.
-    /// This is a non-method function to make tests easier to write.
-    fn execute(state: &mut State, cmd: &Command) -> CommandResult {
-        let result = match cmd {
-            Command::Get { key } => CommandResult::Get {
-                value: state.get(key).cloned(),
-            },
-            Command::Put { key, value } => CommandResult::Put {
-                old_value: state.insert(key.clone(), value.clone()),
-            },
+    /// Takes or restores a snapshot of the application, or lists its keys,
+    /// replying to the requester.
+    fn handle_snap_op(&mut self, op: SnapOp) {
+        let sent = match op {
+            SnapOp::Take(tx) => tx.send(self.app.snapshot()).is_ok(),
+            SnapOp::Restore(snap, tx) => {
+                tx.send(self.app.restore(snap)).is_ok()
+            }
+            SnapOp::ListKeys(cursor, limit, tx) => {
+                tx.send(self.app.list_keys(&cursor, limit)).is_ok()
+            }
         };
+        if !sent {
+            pf_error!("error replying to snapshot op");
+        }
+    }
 
-        result
+    /// Publishes the change made by executed command `id`, if any, on the
+    /// change feed. Waits if the feed is full.
+    async fn feed_change(
+        tx_changes: &mpsc::Sender<ChangeEvent>,
+        id: CommandId,
+        cmd: &Command,
+        res: &CommandResult,
+        hlc: HlcStamp,
+    ) {
+        if let Some(change) = ChangeEvent::of_executed(id, cmd, res, hlc) {
+            if let Err(e) = tx_changes.send(change).await {
+                pf_error!("error sending to tx_changes: {}", e);
+            }
+        }
     }
 
     /// Starts the command executor task loop.
     async fn run(&mut self) {
         pf_debug!("executor task spawned");
 
-        while let Some((id, cmd)) = self.rx_exec.recv().await {
-            let res = Self::execute(&mut self.state, &cmd);
+        loop {
+            // commands to execute always go first, so that snapshot ops see
+            // all commands submitted before them
+            let (id, cmd, checksum, hlc) = tokio::select! {
+                biased;
+                msg = self.rx_exec.recv() => match msg {
+                    Some(msg) => msg,
+                    None => break,
+                },
+                Some(op) = self.rx_snap.recv() => {
+                    self.handle_snap_op(op);
+                    continue;
+                },
+            };
+
+            // a corrupted command is answered without executing it
+            let corrupted = checksum.is_some_and(|sum| sum != cmd.checksum());
+            let res = if corrupted {
+                pf_warn!("checksum mismatch of command {}", id);
+                CommandResult::ChecksumMismatch
+            } else {
+                let res = self.app.apply_with_id(id, &cmd);
+                let stamp = match hlc {
+                    Some(hlc) => {
+                        self.clock.observe(hlc);
+                        hlc
+                    }
+                    None => self.clock.now(),
+                };
+                self.tx_applied.send_replace(stamp);
+                if let Some(tx_changes) = &self.tx_changes {
+                    Self::feed_change(tx_changes, id, &cmd, &res, stamp).await;
+                }
+                res
+            };
             // pf_trace!("executed {:?}", cmd);
 
             if let Err(e) = self.tx_ack.send((id, res)) {
                 pf_error!("error sending to tx_ack: {}", e);
             }
+
+            // count the access, and publish the hottest keys whenever idle
+            // or after enough commands; scans do not count as accesses to
+            // any single key
+            let key = match &cmd {
+                _ if corrupted => None,
+                Command::Get { key } => Some(key),
+                Command::Scan { .. } => None,
+                Command::Put { key, .. } => Some(key),
+                Command::Cas { key, .. } => Some(key),
+                Command::Acquire { key, .. } => Some(key),
+                Command::Release { key, .. } => Some(key),
+            };
+            if let Some(key) = key {
+                self.hot_keys.record(key);
+            }
+            self.unpublished += 1;
+            if self.rx_exec.is_empty()
+                || self.unpublished >= HOT_KEYS_PUBLISH_EVERY
+            {
+                self.tx_hot_keys.send_replace(self.hot_keys.ranked());
+                self.unpublished = 0;
+            }
         }
 
         // channel gets closed and no messages remain
@@ -219,12 +1726,80 @@ mod tests {
     use super::*;
     use rand::{seq::SliceRandom, Rng};
 
+    #[test]
+    fn footprint_conflicts() {
+        let get = Footprint::of_kv(&Command::Get { key: "Jose".into() });
+        let put = Footprint::of_kv(&Command::Put {
+            key: "Jose".into(),
+            value: "180".into(),
+        });
+        let put_other = Footprint::of_kv(&Command::Put {
+            key: "Shawn".into(),
+            value: "77".into(),
+        });
+        let scan = Footprint::of_kv(&Command::Scan {
+            start_key: "J".into(),
+            end_key: "K".into(),
+            limit: 0,
+        });
+        assert!(!get.conflicts_with(&get));
+        assert!(get.conflicts_with(&put) && put.conflicts_with(&get));
+        assert!(put.conflicts_with(&put));
+        assert!(!put.conflicts_with(&put_other));
+        assert!(scan.conflicts_with(&put) && put.conflicts_with(&scan));
+        assert!(!scan.conflicts_with(&put_other));
+        assert!(!scan.conflicts_with(&get));
+        assert!(!Footprint::default().conflicts_with(&put));
+    }
+
+    #[test]
+    fn conflict_index_ops() {
+        let get = Footprint::of_kv(&Command::Get { key: "Jose".into() });
+        let put = Footprint::of_kv(&Command::Put {
+            key: "Jose".into(),
+            value: "180".into(),
+        });
+        let put_other = Footprint::of_kv(&Command::Put {
+            key: "Shawn".into(),
+            value: "77".into(),
+        });
+        let scan = Footprint::of_kv(&Command::Scan {
+            start_key: "J".into(),
+            end_key: "K".into(),
+            limit: 0,
+        });
+        let mut index = ConflictIndex::new();
+        assert!(!index.conflicts(&put));
+        index.insert(0, put.clone());
+        index.insert(1, put.clone());
+        assert_eq!(index.len(), 2);
+        assert!(index.conflicts(&get) && index.conflicts(&scan));
+        assert!(!index.conflicts(&put_other));
+        assert!(index.remove(&0));
+        assert!(!index.remove(&0));
+        assert!(index.conflicts(&put));
+        assert!(index.remove(&1));
+        assert!(!index.conflicts(&get) && !index.conflicts(&scan));
+        index.insert(2, scan);
+        assert!(index.contains(&2));
+        assert!(index.conflicts(&put) && !index.conflicts(&put_other));
+        assert!(!index.conflicts(&get));
+        index.insert(2, get);
+        assert!(index.conflicts(&put) && !index.conflicts(&put_other));
+        assert!(index.remove(&2));
+        assert!(!index.conflicts(&put));
+    }
+
     #[test]
     fn get_empty() {
         let mut state = State::new();
+        let mut locks = LockTable::new();
+        let mut usage = NsUsage::new(0);
         assert_eq!(
-            StateMachineExecutorTask::execute(
+            KvStore::execute(
                 &mut state,
+                &mut locks,
+                &mut usage,
                 &Command::Get { key: "Jose".into() }
             ),
             CommandResult::Get { value: None }
@@ -234,9 +1809,13 @@ mod tests {
     #[test]
     fn put_one_get_one() {
         let mut state = State::new();
+        let mut locks = LockTable::new();
+        let mut usage = NsUsage::new(0);
         assert_eq!(
-            StateMachineExecutorTask::execute(
+            KvStore::execute(
                 &mut state,
+                &mut locks,
+                &mut usage,
                 &Command::Put {
                     key: "Jose".into(),
                     value: "180".into(),
@@ -245,8 +1824,10 @@ mod tests {
             CommandResult::Put { old_value: None }
         );
         assert_eq!(
-            StateMachineExecutorTask::execute(
+            KvStore::execute(
                 &mut state,
+                &mut locks,
+                &mut usage,
                 &Command::Get { key: "Jose".into() }
             ),
             CommandResult::Get {
@@ -258,9 +1839,13 @@ mod tests {
     #[test]
     fn put_twice() {
         let mut state = State::new();
+        let mut locks = LockTable::new();
+        let mut usage = NsUsage::new(0);
         assert_eq!(
-            StateMachineExecutorTask::execute(
+            KvStore::execute(
                 &mut state,
+                &mut locks,
+                &mut usage,
                 &Command::Put {
                     key: "Jose".into(),
                     value: "180".into()
@@ -269,8 +1854,10 @@ mod tests {
             CommandResult::Put { old_value: None }
         );
         assert_eq!(
-            StateMachineExecutorTask::execute(
+            KvStore::execute(
                 &mut state,
+                &mut locks,
+                &mut usage,
                 &Command::Put {
                     key: "Jose".into(),
                     value: "185".into()
@@ -293,13 +1880,17 @@ mod tests {
     #[test]
     fn put_rand_get_rand() {
         let mut state = State::new();
+        let mut locks = LockTable::new();
+        let mut usage = NsUsage::new(0);
         let mut ref_state = State::new();
         for _ in 0..100 {
             let key = gen_rand_str(1);
             let value = gen_rand_str(10);
             assert_eq!(
-                StateMachineExecutorTask::execute(
+                KvStore::execute(
                     &mut state,
+                    &mut locks,
+                    &mut usage,
                     &Command::Put {
                         key: key.clone(),
                         value: value.clone()
@@ -318,8 +1909,10 @@ mod tests {
                 "nonexist!".into()
             };
             assert_eq!(
-                StateMachineExecutorTask::execute(
+                KvStore::execute(
                     &mut state,
+                    &mut locks,
+                    &mut usage,
                     &Command::Get { key: key.clone() }
                 ),
                 CommandResult::Get {
@@ -329,9 +1922,319 @@ mod tests {
         }
     }
 
+    #[test]
+    fn scan_ranges() {
+        let mut state = State::new();
+        let mut locks = LockTable::new();
+        let mut usage = NsUsage::new(0);
+        let mut exec = |cmd: Command| {
+            KvStore::execute(&mut state, &mut locks, &mut usage, &cmd)
+        };
+        for key in ["d", "a", "c", "b"] {
+            exec(Command::Put {
+                key: key.into(),
+                value: key.to_uppercase(),
+            });
+        }
+        let scan = |start_key: &str, end_key: &str, limit| Command::Scan {
+            start_key: start_key.into(),
+            end_key: end_key.into(),
+            limit,
+        };
+        let pairs = |keys: &[&str]| CommandResult::Scan {
+            pairs: keys
+                .iter()
+                .map(|k| (k.to_string(), k.to_uppercase()))
+                .collect(),
+        };
+        assert_eq!(exec(scan("b", "d", 0)), pairs(&["b", "c"]));
+        assert_eq!(exec(scan("b", "", 0)), pairs(&["b", "c", "d"]));
+        assert_eq!(exec(scan("", "", 2)), pairs(&["a", "b"]));
+        assert_eq!(exec(scan("d", "b", 0)), pairs(&[]));
+        assert_eq!(exec(scan("bb", "bc", 0)), pairs(&[]));
+    }
+
+    #[test]
+    fn list_keys_paged() {
+        let mut state = State::new();
+        let mut locks = LockTable::new();
+        let mut usage = NsUsage::new(0);
+        for key in ["d", "a/x", "c", "b"] {
+            KvStore::execute(
+                &mut state,
+                &mut locks,
+                &mut usage,
+                &Command::Put {
+                    key: key.into(),
+                    value: "vv".into(),
+                },
+            );
+        }
+        let report = KvStore::list_keys(&state, &usage, "", 2);
+        assert_eq!(report.num_keys, 4);
+        assert_eq!(report.total_bytes, 14);
+        assert_eq!(report.keys, vec!["a/x".to_string(), "b".into()]);
+        assert_eq!(report.next_cursor, Some("b".into()));
+        let report = KvStore::list_keys(&state, &usage, "b", 2);
+        assert_eq!(report.keys, vec!["c".to_string(), "d".into()]);
+        assert_eq!(report.next_cursor, None);
+        let report = KvStore::list_keys(&state, &usage, "bb", 0);
+        assert!(report.keys.is_empty());
+        assert_eq!(report.next_cursor, None);
+    }
+
+    #[test]
+    fn lock_acquire_release() {
+        let mut state = State::new();
+        let mut locks = LockTable::new();
+        let mut usage = NsUsage::new(0);
+        let mut exec = |cmd: Command| {
+            KvStore::execute(&mut state, &mut locks, &mut usage, &cmd)
+        };
+        let acquire = |owner, now_ms| Command::Acquire {
+            key: "Jose".into(),
+            owner,
+            ttl_ms: 100,
+            now_ms,
+        };
+        let release = |owner, now_ms| Command::Release {
+            key: "Jose".into(),
+            owner,
+            now_ms,
+        };
+        assert_eq!(
+            exec(acquire(1, 0)),
+            CommandResult::Acquire {
+                granted: true,
+                holder: 1
+            }
+        );
+        assert_eq!(
+            exec(acquire(2, 10)),
+            CommandResult::Acquire {
+                granted: false,
+                holder: 1
+            }
+        );
+        assert_eq!(
+            exec(acquire(3, 20)),
+            CommandResult::Acquire {
+                granted: false,
+                holder: 1
+            }
+        );
+        // release hands the lock over to the first waiter
+        assert_eq!(
+            exec(release(1, 30)),
+            CommandResult::Release { released: true }
+        );
+        assert_eq!(
+            exec(acquire(3, 40)),
+            CommandResult::Acquire {
+                granted: false,
+                holder: 2
+            }
+        );
+        // expiry hands the lock over to the next waiter
+        assert_eq!(
+            exec(acquire(3, 140)),
+            CommandResult::Acquire {
+                granted: true,
+                holder: 3
+            }
+        );
+        assert_eq!(
+            exec(release(2, 150)),
+            CommandResult::Release { released: false }
+        );
+        // locks do not touch key-value pairs
+        assert_eq!(
+            exec(Command::Get { key: "Jose".into() }),
+            CommandResult::Get { value: None }
+        );
+        assert_eq!(
+            exec(release(3, 160)),
+            CommandResult::Release { released: true }
+        );
+        assert!(locks.locks.is_empty());
+    }
+
+    #[test]
+    fn cas_swap_or_fail() {
+        let mut state = State::new();
+        let mut locks = LockTable::new();
+        let mut usage = NsUsage::new(0);
+        let mut exec = |expect: Option<&str>, value: &str| {
+            KvStore::execute(
+                &mut state,
+                &mut locks,
+                &mut usage,
+                &Command::Cas {
+                    key: "Jose".into(),
+                    expect: expect.map(|e| e.into()),
+                    value: value.into(),
+                },
+            )
+        };
+        assert_eq!(
+            exec(Some("179"), "180"),
+            CommandResult::Cas {
+                success: false,
+                old_value: None
+            }
+        );
+        assert_eq!(
+            exec(None, "180"),
+            CommandResult::Cas {
+                success: true,
+                old_value: None
+            }
+        );
+        assert_eq!(
+            exec(None, "181"),
+            CommandResult::Cas {
+                success: false,
+                old_value: Some("180".into())
+            }
+        );
+        assert_eq!(
+            exec(Some("180"), "185"),
+            CommandResult::Cas {
+                success: true,
+                old_value: Some("180".into())
+            }
+        );
+        assert_eq!(
+            KvStore::execute(
+                &mut state,
+                &mut locks,
+                &mut usage,
+                &Command::Get { key: "Jose".into() }
+            ),
+            CommandResult::Get {
+                value: Some("185".into())
+            }
+        );
+    }
+
+    #[test]
+    fn put_quota_exceeded() {
+        let mut state = State::new();
+        let mut locks = LockTable::new();
+        let mut usage = NsUsage::new(22);
+        let mut exec = |key: &str, value: &str| {
+            KvStore::execute(
+                &mut state,
+                &mut locks,
+                &mut usage,
+                &Command::Put {
+                    key: key.into(),
+                    value: value.into(),
+                },
+            )
+        };
+        assert_eq!(
+            exec("ns/Jose", "1234567890"),
+            CommandResult::Put { old_value: None }
+        );
+        assert_eq!(
+            exec("ns/Mary", "12345"),
+            CommandResult::QuotaExceeded {
+                namespace: "ns".into()
+            }
+        );
+        // other namespaces are accounted separately
+        assert_eq!(
+            exec("Mary", "12345"),
+            CommandResult::Put { old_value: None }
+        );
+        // shrinking writes free up space
+        assert_eq!(
+            exec("ns/Jose", "12"),
+            CommandResult::Put {
+                old_value: Some("1234567890".into())
+            }
+        );
+        assert_eq!(
+            exec("ns/Mary", "12345"),
+            CommandResult::Put { old_value: None }
+        );
+        assert_eq!(usage.bytes.get("ns"), Some(&21));
+    }
+
+    #[test]
+    fn hot_keys_ranked() {
+        let mut hot_keys = HotKeys::new();
+        for _ in 0..50 {
+            hot_keys.record("hot");
+        }
+        for _ in 0..20 {
+            hot_keys.record("warm");
+        }
+        for i in 0..(2 * HOT_KEYS_TRACKED) {
+            hot_keys.record(&format!("cold{}", i));
+        }
+        let ranked = hot_keys.ranked();
+        assert!(ranked.len() <= HOT_KEYS_TRACKED);
+        assert_eq!(ranked[0].0, "hot");
+        assert!(ranked[0].1 >= 50);
+        assert_eq!(ranked[1].0, "warm");
+        assert!(ranked[1].1 >= 20);
+    }
+
+    #[test]
+    fn read_cache_invalidate() {
+        let mut cache = ReadCache::new();
+        let get = Command::Get { key: "Jose".into() };
+        let got = |value: &str| CommandResult::Get {
+            value: Some(value.into()),
+        };
+        cache.submit(0, &get);
+        cache.fill(0, &got("180"));
+        assert_eq!(cache.lookup("Jose"), Some(got("180")));
+        // a write invalidates the cached result
+        cache.submit(
+            1,
+            &Command::Put {
+                key: "Jose".into(),
+                value: "185".into(),
+            },
+        );
+        assert_eq!(cache.lookup("Jose"), None);
+        // a Get submitted before a write does not fill the cache
+        cache.submit(2, &get);
+        cache.submit(
+            3,
+            &Command::Cas {
+                key: "Jose".into(),
+                expect: Some("185".into()),
+                value: "190".into(),
+            },
+        );
+        cache.fill(2, &got("185"));
+        assert_eq!(cache.lookup("Jose"), None);
+        cache.submit(4, &get);
+        cache.fill(4, &got("190"));
+        assert_eq!(cache.lookup("Jose"), Some(got("190")));
+        assert!(cache.pending.is_empty() && cache.fillable.is_empty());
+    }
+
+    #[test]
+    fn read_cache_evict() {
+        let mut cache = ReadCache::new();
+        for i in 0..(READ_CACHE_CAPACITY + 10) {
+            let key = format!("k{}", i);
+            cache.submit(i as CommandId, &Command::Get { key: key.clone() });
+            cache.fill(i as CommandId, &CommandResult::Get { value: None });
+        }
+        assert_eq!(cache.entries.len(), READ_CACHE_CAPACITY);
+        assert_eq!(cache.lookup("k0"), None);
+        assert!(cache.lookup(&format!("k{}", READ_CACHE_CAPACITY)).is_some());
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn api_exec_ack() -> Result<(), SummersetError> {
-        let mut sm = StateMachine::new_and_setup(0).await?;
+        let mut sm = StateMachine::new_and_setup(0, 0).await?;
         sm.submit_cmd(
             0,
             Command::Put {
@@ -364,7 +2267,7 @@ mod tests {
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn api_do_sync() -> Result<(), SummersetError> {
-        let mut sm = StateMachine::new_and_setup(0).await?;
+        let mut sm = StateMachine::new_and_setup(0, 0).await?;
         sm.submit_cmd(
             0,
             Command::Put {
@@ -399,4 +2302,294 @@ mod tests {
         );
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn api_checksum_mismatch() -> Result<(), SummersetError> {
+        let mut sm = StateMachine::new_and_setup(0, 0).await?;
+        let put = Command::Put {
+            key: "Jose".into(),
+            value: "179".into(),
+        };
+        let checksum = put.checksum();
+        sm.submit_checked_cmd(0, put.clone(), Some(checksum))?;
+        let corrupted = Command::Put {
+            key: "Jose".into(),
+            value: "171".into(),
+        };
+        assert_ne!(corrupted.checksum(), checksum);
+        sm.submit_checked_cmd(1, corrupted, Some(checksum))?;
+        assert_eq!(
+            sm.get_result().await?,
+            (0, CommandResult::Put { old_value: None })
+        );
+        assert_eq!(
+            sm.get_result().await?,
+            (1, CommandResult::ChecksumMismatch)
+        );
+        assert_eq!(
+            sm.do_sync_cmd(2, Command::Get { key: "Jose".into() })
+                .await?,
+            (
+                vec![],
+                CommandResult::Get {
+                    value: Some("179".into())
+                }
+            )
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn api_snapshot_restore() -> Result<(), SummersetError> {
+        let mut sm = StateMachine::new_and_setup(0, 0).await?;
+        sm.submit_cmd(
+            0,
+            Command::Put {
+                key: "Jose".into(),
+                value: "179".into(),
+            },
+        )?;
+        assert_eq!(sm.num_unacked(), 1);
+        let snap = sm.take_snapshot().await?;
+        sm.submit_cmd(
+            1,
+            Command::Put {
+                key: "Jose".into(),
+                value: "180".into(),
+            },
+        )?;
+        sm.restore_snapshot(snap.clone()).await?;
+        let (old_results, result) = sm
+            .do_sync_cmd(2, Command::Get { key: "Jose".into() })
+            .await?;
+        assert_eq!(old_results.len(), 2);
+        assert_eq!(sm.num_unacked(), 0);
+        assert_eq!(
+            result,
+            CommandResult::Get {
+                value: Some("179".into())
+            }
+        );
+        let mut sm2 = StateMachine::new_and_setup(1, 0).await?;
+        sm2.restore_snapshot(snap).await?;
+        assert_eq!(
+            sm2.do_sync_cmd(0, Command::Get { key: "Jose".into() })
+                .await?
+                .1,
+            CommandResult::Get {
+                value: Some("179".into())
+            }
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn api_list_keys() -> Result<(), SummersetError> {
+        let mut sm = StateMachine::new_and_setup(0, 0).await?;
+        let inspector = sm.keys_inspector();
+        for (id, key) in ["Jose", "Anna"].into_iter().enumerate() {
+            sm.submit_cmd(
+                id as CommandId,
+                Command::Put {
+                    key: key.into(),
+                    value: "179".into(),
+                },
+            )?;
+        }
+        sm.get_result().await?;
+        sm.get_result().await?;
+        let report = inspector.list_keys("".into(), 1).await?.unwrap();
+        assert_eq!(report.num_keys, 2);
+        assert_eq!(report.total_bytes, 14);
+        assert_eq!(report.keys, vec!["Anna".to_string()]);
+        assert_eq!(report.next_cursor, Some("Anna".into()));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn api_applied_stamps() -> Result<(), SummersetError> {
+        let mut sm = StateMachine::new_and_setup(0, 0).await?;
+        let stamps = sm.applied_stamps();
+        assert_eq!(*stamps.borrow(), HlcStamp::default());
+        let mut last = HlcStamp::default();
+        for id in 0..3 {
+            sm.do_sync_cmd(
+                id,
+                Command::Put {
+                    key: "Jose".into(),
+                    value: id.to_string(),
+                },
+            )
+            .await?;
+            let stamp = *stamps.borrow();
+            assert!(stamp > last);
+            last = stamp;
+        }
+        let hlc = HlcStamp {
+            wall_ms: last.wall_ms + 1000,
+            logical: 5,
+        };
+        sm.submit_stamped_cmd(
+            3,
+            Command::Get { key: "Jose".into() },
+            None,
+            hlc,
+        )?;
+        sm.get_result().await?;
+        assert_eq!(*stamps.borrow(), hlc);
+        sm.do_sync_cmd(
+            4,
+            Command::Put {
+                key: "Jose".into(),
+                value: "4".into(),
+            },
+        )
+        .await?;
+        assert!(*stamps.borrow() > hlc);
+        Ok(())
+    }
+
+    #[test]
+    fn executed_changes() {
+        let hlc = HlcStamp {
+            wall_ms: 7,
+            logical: 1,
+        };
+        let put = Command::Put {
+            key: "Jose".into(),
+            value: "123".into(),
+        };
+        assert_eq!(
+            ChangeEvent::of_executed(
+                3,
+                &put,
+                &CommandResult::Put { old_value: None },
+                hlc
+            ),
+            Some(ChangeEvent {
+                id: 3,
+                hlc,
+                key: "Jose".into(),
+                value: "123".into(),
+            })
+        );
+        let rejected = CommandResult::QuotaExceeded {
+            namespace: "".into(),
+        };
+        assert_eq!(ChangeEvent::of_executed(4, &put, &rejected, hlc), None);
+        let cas = Command::Cas {
+            key: "Jose".into(),
+            expect: None,
+            value: "456".into(),
+        };
+        let swapped = CommandResult::Cas {
+            success: true,
+            old_value: None,
+        };
+        let failed = CommandResult::Cas {
+            success: false,
+            old_value: Some("123".into()),
+        };
+        assert_eq!(
+            ChangeEvent::of_executed(5, &cas, &swapped, hlc).map(|c| c.value),
+            Some("456".into())
+        );
+        assert_eq!(ChangeEvent::of_executed(6, &cas, &failed, hlc), None);
+        let get = Command::Get { key: "Jose".into() };
+        let got = CommandResult::Get { value: None };
+        assert_eq!(ChangeEvent::of_executed(7, &get, &got, hlc), None);
+    }
+
+    /// Counter application adding the values of Puts, for testing.
+    struct Counter(i64);
+
+    impl AppStateMachine for Counter {
+        fn apply(&mut self, cmd: &Command) -> CommandResult {
+            let old_value = Some(self.0.to_string());
+            if let Command::Put { value, .. } = cmd {
+                self.0 += value.parse::<i64>().unwrap_or(0);
+                CommandResult::Put { old_value }
+            } else {
+                CommandResult::Get { value: old_value }
+            }
+        }
+
+        fn snapshot(&self) -> Result<Bytes, SummersetError> {
+            Ok(Bytes::from(self.0.to_be_bytes().to_vec()))
+        }
+
+        fn restore(&mut self, snap: Bytes) -> Result<(), SummersetError> {
+            self.0 = i64::from_be_bytes(
+                snap[..].try_into().map_err(SummersetError::msg)?,
+            );
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn api_custom_app() -> Result<(), SummersetError> {
+        let mut sm =
+            StateMachine::new_with_app(0, Box::new(Counter(0)), false)?;
+        for (id, delta) in [(0, "3"), (1, "4")] {
+            sm.submit_cmd(
+                id,
+                Command::Put {
+                    key: "c".into(),
+                    value: delta.into(),
+                },
+            )?;
+        }
+        let snap = sm.take_snapshot().await?;
+        let (_, result) =
+            sm.do_sync_cmd(2, Command::Get { key: "c".into() }).await?;
+        assert_eq!(
+            result,
+            CommandResult::Get {
+                value: Some("7".into())
+            }
+        );
+        let mut sm2 =
+            StateMachine::new_with_app(1, Box::new(Counter(0)), false)?;
+        sm2.restore_snapshot(snap).await?;
+        assert_eq!(
+            sm2.do_sync_cmd(0, Command::Get { key: "c".into() })
+                .await?
+                .1,
+            CommandResult::Get {
+                value: Some("7".into())
+            }
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "persistent-state")]
+    #[test]
+    fn durable_reopen() -> Result<(), SummersetError> {
+        let path = Path::new("/tmp/test-durable-state-0.db");
+        let _ = std::fs::remove_dir_all(path);
+        {
+            let mut store = DurableKvStore::open(path, 0)?;
+            assert_eq!(store.applied, None);
+            for (id, value) in [(5, "180"), (7, "185")] {
+                store.apply_with_id(
+                    id,
+                    &Command::Put {
+                        key: "Jose".into(),
+                        value: value.into(),
+                    },
+                );
+            }
+            store.apply_with_id(9, &Command::Get { key: "Jose".into() });
+            store.db.flush().map_err(SummersetError::msg)?;
+        }
+        let mut store = DurableKvStore::open(path, 0)?;
+        assert_eq!(store.applied, Some(7));
+        assert_eq!(
+            store.apply(&Command::Get { key: "Jose".into() }),
+            CommandResult::Get {
+                value: Some("185".into())
+            }
+        );
+        Ok(())
+    }
 }
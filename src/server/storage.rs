@@ -1,11 +1,16 @@
 //! Summerset server durable storage logging module implementation.
 
+use std::collections::{HashMap, VecDeque};
 use std::fmt;
-use std::io::SeekFrom;
+use std::fs::File as StdFile;
+use std::io::{self, IoSlice, SeekFrom, Write};
 use std::path::Path;
+use std::sync::Arc;
 
 use crate::server::ReplicaId;
-use crate::utils::SummersetError;
+use crate::utils::{
+    decode, encode, spawn_inherit_me, ErrorKind, QueueGauge, SummersetError,
+};
 
 use get_size::GetSize;
 
@@ -17,12 +22,21 @@ use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 
 /// Log action ID type.
-pub(crate) type LogActionId = u64;
+pub type LogActionId = u64;
+
+/// Minimum number of bytes fetched into the read-ahead buffer on a miss.
+const READ_AHEAD_SIZE: usize = 4 * 1024 * 1024;
+
+/// Maximum number of decoded entries kept in the entry cache.
+const ENTRY_CACHE_CAP: usize = 1024;
+
+/// Maximum number of queued Append actions persisted in one vectored write.
+const APPEND_BATCH_MAX: usize = 256;
 
 /// Action command to the logger. File cursor will be positioned at EOF after
 /// every action.
 #[derive(Debug, Serialize, Deserialize, GetSize)]
-pub(crate) enum LogAction<Ent> {
+pub enum LogAction<Ent> {
     /// Read a log entry out.
     Read { offset: usize },
 
@@ -46,7 +60,7 @@ pub(crate) enum LogAction<Ent> {
 
 /// Action result returned by the logger.
 #[derive(Debug, Serialize, Deserialize, PartialEq, GetSize)]
-pub(crate) enum LogResult<Ent> {
+pub enum LogResult<Ent> {
     /// `Some(entry)` if successful, else `None`.
     Read {
         entry: Option<Ent>,
@@ -70,7 +84,7 @@ pub(crate) enum LogResult<Ent> {
 }
 
 /// Durable storage logging module.
-pub(crate) struct StorageHub<Ent> {
+pub struct StorageHub<Ent> {
     /// My replica ID.
     _me: ReplicaId,
 
@@ -80,6 +94,9 @@ pub(crate) struct StorageHub<Ent> {
     /// Receiver side of the ack channel.
     rx_ack: mpsc::UnboundedReceiver<(LogActionId, LogResult<Ent>)>,
 
+    /// Number of submitted actions whose results are not yet taken.
+    queue_gauge: QueueGauge,
+
     /// Join handle of the logger task.
     _logger_handle: JoinHandle<()>,
 }
@@ -100,7 +117,7 @@ where
     /// Creates a log channel for submitting logging actions to the logger and
     /// an ack channel for getting results. Prepares the given backing file as
     /// durability backend.
-    pub(crate) async fn new_and_setup(
+    pub async fn new_and_setup(
         me: ReplicaId,
         path: &Path,
     ) -> Result<Self, SummersetError> {
@@ -121,51 +138,68 @@ where
 
         let mut logger =
             StorageHubLoggerTask::new(rx_log, tx_ack, backer_file).await?;
-        let logger_handle = tokio::spawn(async move { logger.run().await });
+        let logger_handle = spawn_inherit_me(async move { logger.run().await });
 
         Ok(StorageHub {
             _me: me,
             tx_log,
             rx_ack,
+            queue_gauge: QueueGauge::new(),
             _logger_handle: logger_handle,
         })
     }
 
     /// Submits an action by sending it to the log channel.
-    pub(crate) fn submit_action(
+    pub fn submit_action(
         &mut self,
         id: LogActionId,
         action: LogAction<Ent>,
     ) -> Result<(), SummersetError> {
-        self.tx_log.send((id, action)).map_err(SummersetError::msg)
+        self.tx_log
+            .send((id, action))
+            .map_err(SummersetError::msg)?;
+        self.queue_gauge.enter(1);
+        Ok(())
     }
 
     /// Waits for the next logging result by receiving from the ack channel.
-    pub(crate) async fn get_result(
+    pub async fn get_result(
         &mut self,
     ) -> Result<(LogActionId, LogResult<Ent>), SummersetError> {
         match self.rx_ack.recv().await {
-            Some((id, result)) => Ok((id, result)),
+            Some((id, result)) => {
+                self.queue_gauge.leave(1);
+                Ok((id, result))
+            }
             None => logged_err!("ack channel has been closed"),
         }
     }
 
     /// Try to get the next logging result using `try_recv()`.
-    #[allow(dead_code)]
-    pub(crate) fn try_get_result(
+    pub fn try_get_result(
         &mut self,
     ) -> Result<(LogActionId, LogResult<Ent>), SummersetError> {
         match self.rx_ack.try_recv() {
-            Ok((id, result)) => Ok((id, result)),
+            Ok((id, result)) => {
+                self.queue_gauge.leave(1);
+                Ok((id, result))
+            }
             Err(e) => Err(SummersetError::msg(e)),
         }
     }
 
+    /// Gets the stage name, number of actions submitted but not yet
+    /// acknowledged, and its high watermark since the previous call.
+    pub(crate) fn queue_depth_report(&mut self) -> (String, usize, usize) {
+        let (depth, peak) = self.queue_gauge.report();
+        ("storage_hub".into(), depth, peak)
+    }
+
     /// Submits an action and waits for its result blockingly.
     /// Returns a tuple where the first element is a vec containing any old
     /// results of previously submitted actions received in the middle and
     /// the second element is the result of this sync action.
-    pub(crate) async fn do_sync_action(
+    pub async fn do_sync_action(
         &mut self,
         id: LogActionId,
         action: LogAction<Ent>,
@@ -192,8 +226,66 @@ struct StorageHubLoggerTask<Ent> {
     tx_ack: mpsc::UnboundedSender<(LogActionId, LogResult<Ent>)>,
 
     backer_file: File,
+    /// Blocking handle to the same open backer file, sharing its cursor,
+    /// used for vectored appends.
+    backer_std: Arc<StdFile>,
     /// Backer file size is maintained by the logger.
     file_size: usize,
+
+    /// Read-ahead buffer holding a contiguous range of file content, so that
+    /// sequential reads (e.g., during recovery) do not each hit the file.
+    read_ahead: ReadAheadBuf,
+
+    /// Cache of recently read entries: offset -> (entry, end_offset).
+    entry_cache: HashMap<usize, (Ent, usize)>,
+    /// Insertion order of cached entries for FIFO eviction.
+    cache_order: VecDeque<usize>,
+}
+
+/// Read-ahead buffer of a contiguous range of the backer file.
+#[derive(Debug, Default)]
+struct ReadAheadBuf {
+    /// File offset of the first buffered byte.
+    start: usize,
+
+    /// Buffered file content.
+    buf: Vec<u8>,
+}
+
+impl ReadAheadBuf {
+    /// Returns the buffered bytes in range `[offset, offset + len)`, if all
+    /// of them are buffered.
+    fn get(&self, offset: usize, len: usize) -> Option<&[u8]> {
+        if offset >= self.start && offset + len <= self.start + self.buf.len() {
+            Some(&self.buf[(offset - self.start)..(offset - self.start + len)])
+        } else {
+            None
+        }
+    }
+
+    /// Refills the buffer with at least `len` bytes starting at `offset`,
+    /// reading ahead as much as `READ_AHEAD_SIZE` bytes within file bound.
+    async fn fill(
+        &mut self,
+        backer: &mut File,
+        file_size: usize,
+        offset: usize,
+        len: usize,
+    ) -> Result<(), SummersetError> {
+        let fill_len = len.max(READ_AHEAD_SIZE).min(file_size - offset);
+        self.start = offset;
+        self.buf.resize(fill_len, 0);
+        backer.seek(SeekFrom::Start(offset as u64)).await?;
+        backer.read_exact(&mut self.buf[..]).await?;
+        backer.seek(SeekFrom::End(0)).await?; // recover cursor to EOF
+        Ok(())
+    }
+
+    /// Drops all buffered content.
+    fn clear(&mut self) {
+        self.start = 0;
+        self.buf.clear();
+    }
 }
 
 impl<Ent> StorageHubLoggerTask<Ent>
@@ -221,12 +313,18 @@ where
             );
         }
         let file_size: usize = metadata.unwrap().len() as usize;
+        let backer_std =
+            Arc::new(backer_file.try_clone().await?.into_std().await);
 
         Ok(StorageHubLoggerTask {
             rx_log,
             tx_ack,
             backer_file,
+            backer_std,
             file_size,
+            read_ahead: ReadAheadBuf::default(),
+            entry_cache: HashMap::new(),
+            cache_order: VecDeque::new(),
         })
     }
 
@@ -263,11 +361,92 @@ where
         // read entry content
         let mut entry_buf: Vec<u8> = vec![0; entry_len];
         backer.read_exact(&mut entry_buf[..]).await?;
-        let entry = bincode::deserialize(&entry_buf)?;
+        let entry = decode(&entry_buf)
+            .map_err(|e| e.with_kind(ErrorKind::Corruption))?;
         backer.seek(SeekFrom::End(0)).await?; // recover cursor to EOF
         Ok((Some(entry), offset_e))
     }
 
+    /// Read out entry at given offset through the read-ahead buffer, which
+    /// gets refilled from the file only when the entry is not fully in it.
+    /// This is a non-method function to make tests easier to write.
+    async fn read_entry_buffered(
+        backer: &mut File,
+        file_size: usize,
+        read_ahead: &mut ReadAheadBuf,
+        offset: usize,
+    ) -> Result<(Option<Ent>, usize), SummersetError> {
+        if offset + 8 > file_size {
+            if offset < file_size {
+                pf_warn!(
+                    "read header end offset {} out of file bound {}",
+                    offset + 8,
+                    file_size
+                );
+            }
+            return Ok((None, offset));
+        }
+
+        // read entry length header
+        if read_ahead.get(offset, 8).is_none() {
+            read_ahead.fill(backer, file_size, offset, 8).await?;
+        }
+        let mut header = [0u8; 8];
+        header.copy_from_slice(read_ahead.get(offset, 8).unwrap());
+        let entry_len = u64::from_be_bytes(header) as usize;
+        let offset_e = offset + 8 + entry_len;
+        if offset_e > file_size {
+            pf_warn!("read entry invalid length {}", entry_len);
+            return Ok((None, offset));
+        }
+
+        // read entry content
+        if read_ahead.get(offset, 8 + entry_len).is_none() {
+            read_ahead
+                .fill(backer, file_size, offset, 8 + entry_len)
+                .await?;
+        }
+        let entry = decode(read_ahead.get(offset + 8, entry_len).unwrap())
+            .map_err(|e| e.with_kind(ErrorKind::Corruption))?;
+        Ok((Some(entry), offset_e))
+    }
+
+    /// Read out entry at given offset, serving from the entry cache if
+    /// possible.
+    async fn read_entry_cached(
+        &mut self,
+        offset: usize,
+    ) -> Result<(Option<Ent>, usize), SummersetError> {
+        if let Some((entry, end_offset)) = self.entry_cache.get(&offset) {
+            return Ok((Some(entry.clone()), *end_offset));
+        }
+
+        let (entry, end_offset) = Self::read_entry_buffered(
+            &mut self.backer_file,
+            self.file_size,
+            &mut self.read_ahead,
+            offset,
+        )
+        .await?;
+        if let Some(entry) = entry.as_ref() {
+            if self.cache_order.len() >= ENTRY_CACHE_CAP {
+                let evicted = self.cache_order.pop_front().unwrap();
+                self.entry_cache.remove(&evicted);
+            }
+            self.entry_cache.insert(offset, (entry.clone(), end_offset));
+            self.cache_order.push_back(offset);
+        }
+        Ok((entry, end_offset))
+    }
+
+    /// Invalidates the read-ahead buffer and entry cache, called on every
+    /// action that may overwrite existing file content.
+    fn invalidate_caches(&mut self) {
+        self.read_ahead.clear();
+        self.entry_cache.clear();
+        self.cache_order.clear();
+    }
+
     /// Write given entry to given offset.
     /// This is a non-method function to make tests easier to write.
     async fn write_entry(
@@ -287,7 +466,7 @@ where
             return Ok((false, file_size));
         }
 
-        let entry_bytes = bincode::serialize(entry)?;
+        let entry_bytes = encode(entry)?;
         let entry_len = entry_bytes.len();
 
         // write entry length header first
@@ -311,28 +490,50 @@ where
         Ok((true, now_size))
     }
 
-    /// Append given entry to EOF.
+    /// Append given entries to EOF in one vectored write, where each entry's
+    /// length header and content are separate slices, so nothing gets
+    /// copied into a contiguous buffer first. Returns the file size after
+    /// each entry.
     /// This is a non-method function to make tests easier to write.
-    async fn append_entry(
+    async fn append_entries(
         backer: &mut File,
+        backer_std: &Arc<StdFile>,
         file_size: usize,
-        entry: &Ent,
+        entries: &[Ent],
         sync: bool,
-    ) -> Result<usize, SummersetError> {
-        let entry_bytes = bincode::serialize(entry)?;
-        let entry_len = entry_bytes.len();
-
-        // write entry length header first
-        backer.write_u64(entry_len as u64).await?;
+    ) -> Result<Vec<usize>, SummersetError> {
+        let mut chunks = Vec::with_capacity(entries.len());
+        let mut now_sizes = Vec::with_capacity(entries.len());
+        let mut now_size = file_size;
+        for entry in entries {
+            let entry_bytes = encode(entry)?;
+            now_size += 8 + entry_bytes.len();
+            now_sizes.push(now_size);
+            chunks
+                .push(((entry_bytes.len() as u64).to_be_bytes(), entry_bytes));
+        }
 
-        // then entry content
-        backer.write_all(&entry_bytes[..]).await?;
+        // make sure no write through the async handle is still in flight;
+        // as the cursor is at EOF after every action, the blocking handle
+        // sharing it appends right there
+        backer.flush().await?;
+        let backer_std = backer_std.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut slices = Vec::with_capacity(2 * chunks.len());
+            for (header, entry_bytes) in &chunks {
+                slices.push(IoSlice::new(header));
+                slices.push(IoSlice::new(entry_bytes));
+            }
+            write_all_vectored(&backer_std, &mut slices)
+        })
+        .await
+        .map_err(SummersetError::msg)??;
 
         if sync {
             backer.sync_data().await?;
         }
 
-        Ok(file_size + 8 + entry_len)
+        Ok(now_sizes)
     }
 
     /// Truncate the file at given index, keeping the head part.
@@ -405,14 +606,20 @@ where
         &mut self,
         action: LogAction<Ent>,
     ) -> Result<LogResult<Ent>, SummersetError> {
+        if matches!(
+            action,
+            LogAction::Write { .. }
+                | LogAction::Truncate { .. }
+                | LogAction::Discard { .. }
+        ) {
+            self.invalidate_caches();
+        }
+
         match action {
             LogAction::Read { offset } => {
-                Self::read_entry(&mut self.backer_file, self.file_size, offset)
-                    .await
-                    .map(|(entry, end_offset)| LogResult::Read {
-                        entry,
-                        end_offset,
-                    })
+                self.read_entry_cached(offset).await.map(
+                    |(entry, end_offset)| LogResult::Read { entry, end_offset },
+                )
             }
             LogAction::Write {
                 entry,
@@ -433,17 +640,10 @@ where
                     now_size,
                 }
             }),
-            LogAction::Append { entry, sync } => Self::append_entry(
-                &mut self.backer_file,
-                self.file_size,
-                &entry,
-                sync,
-            )
-            .await
-            .map(|now_size| {
-                self.file_size = now_size;
-                LogResult::Append { now_size }
-            }),
+            LogAction::Append { entry, sync } => self
+                .handle_appends(vec![entry], sync)
+                .await
+                .map(|mut results| results.remove(0)),
             LogAction::Truncate { offset } => Self::truncate_log(
                 &mut self.backer_file,
                 self.file_size,
@@ -474,20 +674,86 @@ where
         }
     }
 
+    /// Handler of a batch of Append actions, persisted in one vectored
+    /// write and synced once if any of them asks for sync. Returns the
+    /// results of the actions in order.
+    async fn handle_appends(
+        &mut self,
+        entries: Vec<Ent>,
+        sync: bool,
+    ) -> Result<Vec<LogResult<Ent>>, SummersetError> {
+        let now_sizes = Self::append_entries(
+            &mut self.backer_file,
+            &self.backer_std,
+            self.file_size,
+            &entries,
+            sync,
+        )
+        .await?;
+        if let Some(&now_size) = now_sizes.last() {
+            self.file_size = now_size;
+        }
+        Ok(now_sizes
+            .into_iter()
+            .map(|now_size| LogResult::Append { now_size })
+            .collect())
+    }
+
+    /// Sends the result of an action to the ack channel.
+    fn send_ack(&mut self, id: LogActionId, result: LogResult<Ent>) {
+        if let Err(e) = self.tx_ack.send((id, result)) {
+            pf_error!("error sending to tx_ack: {}", e);
+        }
+    }
+
     /// Starts the durable logger task loop.
     async fn run(&mut self) {
         pf_debug!("logger task spawned");
 
-        while let Some((id, action)) = self.rx_log.recv().await {
+        let mut lookahead = None;
+        loop {
+            let (id, action) = match lookahead.take() {
+                Some(next) => next,
+                None => match self.rx_log.recv().await {
+                    Some(next) => next,
+                    None => break,
+                },
+            };
             // pf_trace!("log action {:?}", action);
-            let res = self.handle_action(action).await;
-            if let Err(e) = res {
-                pf_error!("error during logging: {}", e);
+
+            // gather consecutive Appends already queued into one batch
+            if let LogAction::Append { entry, sync } = action {
+                let (mut ids, mut entries, mut sync) =
+                    (vec![id], vec![entry], sync);
+                while ids.len() < APPEND_BATCH_MAX {
+                    match self.rx_log.try_recv() {
+                        Ok((id, LogAction::Append { entry, sync: s })) => {
+                            ids.push(id);
+                            entries.push(entry);
+                            sync |= s;
+                        }
+                        Ok(next) => {
+                            lookahead = Some(next);
+                            break;
+                        }
+                        Err(_) => break,
+                    }
+                }
+
+                match self.handle_appends(entries, sync).await {
+                    Ok(results) => {
+                        for (id, result) in ids.into_iter().zip(results) {
+                            self.send_ack(id, result);
+                        }
+                    }
+                    Err(e) => pf_error!("error during logging: {}", e),
+                }
                 continue;
             }
 
-            if let Err(e) = self.tx_ack.send((id, res.unwrap())) {
-                pf_error!("error sending to tx_ack: {}", e);
+            match self.handle_action(action).await {
+                Ok(result) => self.send_ack(id, result),
+                Err(e) => pf_error!("error during logging: {}", e),
             }
         }
 
@@ -496,6 +762,109 @@ where
     }
 }
 
+/// Writes all given slices to file, retrying partial writes. (Like the
+/// unstable `Write::write_all_vectored()`.)
+fn write_all_vectored(
+    mut file: &StdFile,
+    mut slices: &mut [IoSlice<'_>],
+) -> Result<(), SummersetError> {
+    IoSlice::advance_slices(&mut slices, 0);
+    while !slices.is_empty() {
+        match file.write_vectored(slices) {
+            Ok(0) => {
+                return Err(io::Error::from(io::ErrorKind::WriteZero).into());
+            }
+            Ok(n) => IoSlice::advance_slices(&mut slices, n),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Scans a log file directly without going through a logger, decoding
+/// entries from the start until the first invalid one. Returns the decoded
+/// entries with their offsets, the offset at which valid content ends, and
+/// the file size. Meant for offline inspection tools.
+pub(crate) async fn scan_log_file<Ent>(
+    path: &Path,
+) -> Result<(Vec<(usize, Ent)>, usize, usize), SummersetError>
+where
+    Ent: DeserializeOwned,
+{
+    let content = fs::read(path).await?;
+    let mut entries = vec![];
+    let mut offset = 0;
+    while offset + 8 <= content.len() {
+        let mut header = [0u8; 8];
+        header.copy_from_slice(&content[offset..(offset + 8)]);
+        let entry_len = u64::from_be_bytes(header) as usize;
+        if entry_len > content.len() - offset - 8 {
+            break;
+        }
+        let offset_e = offset + 8 + entry_len;
+        match decode(&content[(offset + 8)..offset_e]) {
+            Ok(entry) => entries.push((offset, entry)),
+            Err(_) => break,
+        }
+        offset = offset_e;
+    }
+    Ok((entries, offset, content.len()))
+}
+
+/// Reads the entry at given offset of a log file through a separate file
+/// handle, without going through a logger. Meant for paging entries of a
+/// log in use back in on demand; the entry must already be written.
+pub(crate) async fn read_log_entry<Ent>(
+    file: &mut File,
+    offset: usize,
+) -> Result<(Option<Ent>, usize), SummersetError>
+where
+    Ent: fmt::Debug
+        + Clone
+        + Serialize
+        + DeserializeOwned
+        + Send
+        + Sync
+        + 'static,
+{
+    let file_size = file.metadata().await?.len() as usize;
+    StorageHubLoggerTask::<Ent>::read_entry(file, file_size, offset).await
+}
+
+/// Truncates a log file at given offset without going through a logger.
+/// Meant for offline repair tools.
+pub(crate) async fn truncate_log_file(
+    path: &Path,
+    offset: usize,
+) -> Result<(), SummersetError> {
+    let file = OpenOptions::new().write(true).open(path).await?;
+    file.set_len(offset as u64).await?;
+    file.sync_all().await?;
+    Ok(())
+}
+
+/// Overwrites a log file with the given entries without going through a
+/// logger. Meant for offline import tools.
+pub(crate) async fn write_log_file<Ent>(
+    path: &Path,
+    entries: &[Ent],
+) -> Result<(), SummersetError>
+where
+    Ent: Serialize,
+{
+    let mut content = vec![];
+    for entry in entries {
+        let entry_bytes = encode(entry)?;
+        content.extend_from_slice(&(entry_bytes.len() as u64).to_be_bytes());
+        content.extend_from_slice(&entry_bytes);
+    }
+    let mut file = File::create(path).await?;
+    file.write_all(&content).await?;
+    file.sync_all().await?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -514,6 +883,24 @@ mod tests {
         Ok(file)
     }
 
+    async fn append_entry(
+        backer: &mut File,
+        file_size: usize,
+        entry: &TestEntry,
+        sync: bool,
+    ) -> Result<usize, SummersetError> {
+        let backer_std = Arc::new(backer.try_clone().await?.into_std().await);
+        let now_sizes = StorageHubLoggerTask::append_entries(
+            backer,
+            &backer_std,
+            file_size,
+            std::slice::from_ref(entry),
+            sync,
+        )
+        .await?;
+        Ok(now_sizes[0])
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn write_entries() -> Result<(), SummersetError> {
         let mut backer_file =
@@ -564,22 +951,45 @@ mod tests {
             prepare_test_file("/tmp/test-backer-1.log").await?;
         let entry = TestEntry("test-entry-dummy-string".into());
         let entry_bytes = bincode::serialize(&entry)?;
-        let mid_size = StorageHubLoggerTask::append_entry(
-            &mut backer_file,
-            0,
-            &entry,
-            false,
-        )
-        .await?;
+        let mid_size = append_entry(&mut backer_file, 0, &entry, false).await?;
         debug_assert!(mid_size >= entry_bytes.len());
-        let end_size = StorageHubLoggerTask::append_entry(
+        let end_size =
+            append_entry(&mut backer_file, mid_size, &entry, true).await?;
+        debug_assert!(end_size - mid_size >= entry_bytes.len());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn append_entries_batched() -> Result<(), SummersetError> {
+        let mut backer_file =
+            prepare_test_file("/tmp/test-backer-10.log").await?;
+        let backer_std =
+            Arc::new(backer_file.try_clone().await?.into_std().await);
+        let entries: Vec<_> = (0..3)
+            .map(|i| TestEntry(format!("test-entry-{}", i)))
+            .collect();
+        let now_sizes = StorageHubLoggerTask::append_entries(
             &mut backer_file,
-            mid_size,
-            &entry,
+            &backer_std,
+            0,
+            &entries,
             true,
         )
         .await?;
-        debug_assert!(end_size - mid_size >= entry_bytes.len());
+        assert_eq!(now_sizes.len(), 3);
+        let mut offset = 0;
+        for (entry, now_size) in entries.into_iter().zip(now_sizes) {
+            assert_eq!(
+                StorageHubLoggerTask::read_entry(
+                    &mut backer_file,
+                    now_size,
+                    offset
+                )
+                .await?,
+                (Some(entry), now_size)
+            );
+            offset = now_size;
+        }
         Ok(())
     }
 
@@ -588,20 +998,9 @@ mod tests {
         let mut backer_file =
             prepare_test_file("/tmp/test-backer-2.log").await?;
         let entry = TestEntry("test-entry-dummy-string".into());
-        let mid_size = StorageHubLoggerTask::append_entry(
-            &mut backer_file,
-            0,
-            &entry,
-            false,
-        )
-        .await?;
-        let end_size = StorageHubLoggerTask::append_entry(
-            &mut backer_file,
-            mid_size,
-            &entry,
-            true,
-        )
-        .await?;
+        let mid_size = append_entry(&mut backer_file, 0, &entry, false).await?;
+        let end_size =
+            append_entry(&mut backer_file, mid_size, &entry, true).await?;
         assert_eq!(
             StorageHubLoggerTask::read_entry(
                 &mut backer_file,
@@ -637,25 +1036,87 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn read_entries_buffered() -> Result<(), SummersetError> {
+        let mut backer_file =
+            prepare_test_file("/tmp/test-backer-5.log").await?;
+        let mut read_ahead = ReadAheadBuf::default();
+        let mut file_size = 0;
+        for i in 0..100 {
+            file_size = append_entry(
+                &mut backer_file,
+                file_size,
+                &TestEntry(format!("test-entry-{}", i)),
+                false,
+            )
+            .await?;
+        }
+        let mut offset = 0;
+        for i in 0..100 {
+            let (entry, end_offset) =
+                StorageHubLoggerTask::read_entry_buffered(
+                    &mut backer_file,
+                    file_size,
+                    &mut read_ahead,
+                    offset,
+                )
+                .await?;
+            assert_eq!(entry, Some(TestEntry(format!("test-entry-{}", i))));
+            offset = end_offset;
+        }
+        // whole file got buffered by the first read
+        assert_eq!(read_ahead.start, 0);
+        assert_eq!(read_ahead.buf.len(), file_size);
+        assert_eq!(
+            StorageHubLoggerTask::<TestEntry>::read_entry_buffered(
+                &mut backer_file,
+                file_size,
+                &mut read_ahead,
+                offset,
+            )
+            .await?,
+            (None, file_size)
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn api_read_after_write() -> Result<(), SummersetError> {
+        let path = Path::new("/tmp/test-backer-8.log");
+        let mut hub = StorageHub::new_and_setup(0, path).await?;
+        hub.do_sync_action(0, LogAction::Truncate { offset: 0 })
+            .await?;
+        let entry = TestEntry("abcdefgh".into());
+        hub.do_sync_action(1, LogAction::Append { entry, sync: false })
+            .await?;
+        hub.do_sync_action(2, LogAction::Read { offset: 0 }).await?;
+        // overwriting must not be shadowed by cached content
+        let entry = TestEntry("ijklmnop".into());
+        hub.do_sync_action(
+            3,
+            LogAction::Write {
+                entry,
+                offset: 0,
+                sync: false,
+            },
+        )
+        .await?;
+        assert!(matches!(
+            hub.do_sync_action(4, LogAction::Read { offset: 0 }).await?.1,
+            LogResult::Read { entry: Some(TestEntry(s)), .. } if s == "ijklmnop"
+        ));
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn truncate_log() -> Result<(), SummersetError> {
         let mut backer_file =
             prepare_test_file("/tmp/test-backer-3.log").await?;
         let entry = TestEntry("test-entry-dummy-string".into());
-        let mid_offset = StorageHubLoggerTask::append_entry(
-            &mut backer_file,
-            0,
-            &entry,
-            false,
-        )
-        .await?;
-        let end_offset = StorageHubLoggerTask::append_entry(
-            &mut backer_file,
-            mid_offset,
-            &entry,
-            true,
-        )
-        .await?;
+        let mid_offset =
+            append_entry(&mut backer_file, 0, &entry, false).await?;
+        let end_offset =
+            append_entry(&mut backer_file, mid_offset, &entry, true).await?;
         assert_eq!(
             StorageHubLoggerTask::<TestEntry>::truncate_log(
                 &mut backer_file,
@@ -691,27 +1152,12 @@ mod tests {
         let mut backer_file =
             prepare_test_file("/tmp/test-backer-4.log").await?;
         let entry = TestEntry("test-entry-dummy-string".into());
-        let mid1_offset = StorageHubLoggerTask::append_entry(
-            &mut backer_file,
-            0,
-            &entry,
-            false,
-        )
-        .await?;
-        let mid2_offset = StorageHubLoggerTask::append_entry(
-            &mut backer_file,
-            mid1_offset,
-            &entry,
-            false,
-        )
-        .await?;
-        let end_offset = StorageHubLoggerTask::append_entry(
-            &mut backer_file,
-            mid2_offset,
-            &entry,
-            true,
-        )
-        .await?;
+        let mid1_offset =
+            append_entry(&mut backer_file, 0, &entry, false).await?;
+        let mid2_offset =
+            append_entry(&mut backer_file, mid1_offset, &entry, false).await?;
+        let end_offset =
+            append_entry(&mut backer_file, mid2_offset, &entry, true).await?;
         let tail_size = end_offset - mid2_offset;
         assert_eq!(
             StorageHubLoggerTask::<TestEntry>::discard_log(
@@ -766,6 +1212,38 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn scan_and_truncate() -> Result<(), SummersetError> {
+        let path = "/tmp/test-backer-9.log";
+        let mut backer_file = prepare_test_file(path).await?;
+        let entry = TestEntry("test-entry-dummy-string".into());
+        let mid_size = append_entry(&mut backer_file, 0, &entry, false).await?;
+        backer_file.write_all(&[0xff; 12]).await?; // trailing garbage
+        backer_file.sync_all().await?;
+        let (entries, valid_end, file_size) =
+            scan_log_file::<TestEntry>(Path::new(path)).await?;
+        assert_eq!(entries, vec![(0, entry)]);
+        assert_eq!(valid_end, mid_size);
+        assert_eq!(file_size, mid_size + 12);
+        truncate_log_file(Path::new(path), valid_end).await?;
+        assert_eq!(
+            scan_log_file::<TestEntry>(Path::new(path)).await?.2,
+            mid_size
+        );
+        let entries = vec![TestEntry("a".into()), TestEntry("b".into())];
+        write_log_file(Path::new(path), &entries).await?;
+        assert_eq!(
+            scan_log_file::<TestEntry>(Path::new(path))
+                .await?
+                .0
+                .into_iter()
+                .map(|(_, e)| e)
+                .collect::<Vec<_>>(),
+            entries
+        );
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn api_log_ack() -> Result<(), SummersetError> {
         let path = Path::new("/tmp/test-backer-6.log");
@@ -5,15 +5,35 @@
 //! manner. Here, we use TCP as the communication protocol to get the same
 //! effect of "every message a sender wants to send will be retried until
 //! eventually delivered".
+//!
+//! Each connection carries two logical lanes: a control lane for small,
+//! latency-sensitive messages, and a bulk lane for everything else. Bulk
+//! messages are cut into pieces on the wire, and the writer always sends
+//! pending control messages before the next piece, so that they never wait
+//! behind a large message for long.
+//!
+//! Optionally, a UDP side-channel can be enabled for messages that are fine
+//! to lose, e.g., heartbeats, so that their timing does not get distorted by
+//! head-of-line blocking behind large messages on the TCP connection.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fmt;
-use std::net::SocketAddr;
-
-use crate::server::{LeaseGid, LeaseMsg, LeaseNotice, LeaseNum, ReplicaId};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::protocols::SmrProtocol;
+#[cfg(feature = "byzantine-hooks")]
+use crate::server::byzantine;
+use crate::server::{msgtrace, netfault};
+use crate::server::{
+    LeaseGid, LeaseMsg, LeaseNotice, LeaseNum, PeerTls, ReplicaId, PEER_TLS,
+};
 use crate::utils::{
-    safe_tcp_read, safe_tcp_write, tcp_bind_with_retry, tcp_connect_with_retry,
-    Bitmap, SummersetError,
+    codec, crc32, decode, encode, max_msg_bytes, msg_within_limit,
+    safe_tcp_read_bounded, safe_tcp_write, spawn_inherit_me,
+    tcp_bind_with_retry, tcp_connect_with_retry, Bitmap, Codec, QueueGauge,
+    SummersetError,
 };
 
 use get_size::GetSize;
@@ -24,10 +44,128 @@ use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
-use tokio::net::{TcpListener, TcpStream};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
-use tokio::time::{self, Duration};
+use tokio::time::{self, Duration, Instant};
+
+/// Version of the transport's own wire format: the handshake and the
+/// `PeerMessage` envelope. Peers must agree on it exactly.
+const ENVELOPE_VERSION: u16 = 6;
+
+/// Default per-peer queue depth at which the peer is considered congested.
+const DEFAULT_QUEUE_HIGH_WATERMARK: usize = 1024;
+
+/// Default per-peer queue depth at which a congested peer is considered
+/// drained again.
+const DEFAULT_QUEUE_LOW_WATERMARK: usize = 256;
+
+/// Maximum size of a bulk lane message piece on the wire.
+const BULK_PIECE_BYTES: usize = 64 * 1024;
+
+/// Maximum encoded size of a message sent as UDP datagram; larger ones go
+/// through the TCP connection instead to avoid IP fragmentation.
+const MAX_DATAGRAM_BYTES: usize = 1200;
+
+/// Handshake sent by both sides of a new peer connection, before any message.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+struct PeerHello {
+    /// Sender's replica ID.
+    id: ReplicaId,
+
+    /// Sender's `ENVELOPE_VERSION`.
+    envelope_ver: u16,
+
+    /// Protocol the sender runs.
+    protocol: SmrProtocol,
+
+    /// Latest protocol message format version the sender speaks.
+    proto_ver: u16,
+
+    /// Port of the sender's UDP side-channel socket, if enabled.
+    udp_port: Option<u16>,
+
+    /// Negotiation byte of the sender's serialization codec.
+    codec: u8,
+}
+
+impl PeerHello {
+    /// Composes my handshake.
+    fn mine(
+        me: ReplicaId,
+        protocol: SmrProtocol,
+        udp_port: Option<u16>,
+    ) -> Self {
+        PeerHello {
+            id: me,
+            envelope_ver: ENVELOPE_VERSION,
+            protocol,
+            proto_ver: protocol.wire_version(),
+            udp_port,
+            codec: codec() as u8,
+        }
+    }
+
+    /// Checks a peer's handshake against mine, returning the protocol message
+    /// format version to use on this connection if compatible.
+    fn negotiate(&self, peer: &PeerHello) -> Result<u16, SummersetError> {
+        if peer.envelope_ver != self.envelope_ver {
+            return logged_err!(
+                "peer {} uses transport format v{}, mine is v{}",
+                peer.id,
+                peer.envelope_ver,
+                self.envelope_ver
+            );
+        }
+        if peer.codec != self.codec {
+            return logged_err!(
+                "peer {} uses codec {:?}, mine is {}",
+                peer.id,
+                Codec::from_byte(peer.codec),
+                codec()
+            );
+        }
+        if peer.protocol != self.protocol {
+            return logged_err!(
+                "peer {} runs protocol {}, mine is {}",
+                peer.id,
+                peer.protocol,
+                self.protocol
+            );
+        }
+        let min_ver = self.protocol.min_wire_version();
+        if peer.proto_ver < min_ver {
+            return logged_err!(
+                "peer {} speaks {} format v{}, oldest I support is v{}",
+                peer.id,
+                peer.protocol,
+                peer.proto_ver,
+                min_ver
+            );
+        }
+        // the newer side is responsible for speaking the older format
+        Ok(self.proto_ver.min(peer.proto_ver))
+    }
+
+    /// Sends a handshake through the stream.
+    async fn send(&self, stream: &mut TcpStream) -> Result<(), SummersetError> {
+        let bytes = bincode::serialize(self)?;
+        stream.write_u16(bytes.len() as u16).await?;
+        stream.write_all(&bytes).await?;
+        Ok(())
+    }
+
+    /// Receives a handshake from the stream.
+    async fn recv(stream: &mut TcpStream) -> Result<Self, SummersetError> {
+        let len = stream.read_u16().await? as usize;
+        let mut bytes = vec![0; len];
+        stream.read_exact(&mut bytes).await?;
+        match bincode::deserialize(&bytes) {
+            Ok(hello) => Ok(hello),
+            Err(e) => logged_err!("malformed peer handshake: {}", e),
+        }
+    }
+}
 
 /// Peer-peer message wrapper type that includes leave notification variants.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, GetSize)]
@@ -35,6 +173,10 @@ enum PeerMessage<Msg> {
     /// Normal protocol-specific message.
     Msg { msg: Msg },
 
+    /// Protocol-specific message sent on the control lane; delivered as a
+    /// normal `Msg` on the receiving side.
+    CtrlMsg { msg: Msg },
+
     /// Lease-related message.
     LeaseMsg {
         /// Lease group ID that differentiates between lease groups for
@@ -44,6 +186,18 @@ enum PeerMessage<Msg> {
         lease_msg: LeaseMsg,
     },
 
+    /// Lease-related messages queued for the same peer within one heartbeat
+    /// interval, possibly of different lease groups; delivered one by one on
+    /// the receiving side.
+    LeaseBatch {
+        leases: Vec<(LeaseGid, LeaseNum, LeaseMsg)>,
+    },
+
+    /// Protocol-specific message to be sent as a UDP datagram if the peer
+    /// has the side-channel enabled. Never sent over the TCP connection
+    /// itself; falls back to a normal `Msg` there.
+    Datagram { msg: Msg },
+
     /// Server leave notification.
     Leave,
 
@@ -51,13 +205,43 @@ enum PeerMessage<Msg> {
     LeaveReply,
 }
 
+impl<Msg: fmt::Debug> PeerMessage<Msg> {
+    /// Renders the message for the peer message trace. Protocol messages are
+    /// rendered alike regardless of the lane they travel on.
+    fn trace_repr(&self) -> String {
+        match self {
+            PeerMessage::Msg { msg }
+            | PeerMessage::CtrlMsg { msg }
+            | PeerMessage::Datagram { msg } => format!("{:?}", msg),
+            _ => format!("{:?}", self),
+        }
+    }
+}
+
+/// Frame on the wire of a peer connection. Lease and leave messages as well
+/// as `CtrlMsg`s travel on the control lane; normal `Msg`s on the bulk lane.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+enum PeerFrame<Msg> {
+    /// A whole message on the control lane.
+    Ctrl { msg: PeerMessage<Msg> },
+
+    /// A piece of an encoded message on the bulk lane; `last` is set on its
+    /// final piece. `crc` is the CRC-32 of the piece, so that a corrupted
+    /// piece gets its whole message dropped instead of decoded.
+    Bulk {
+        piece: Vec<u8>,
+        last: bool,
+        crc: u32,
+    },
+}
+
 /// Server internal TCP transport module.
 pub(crate) struct TransportHub<Msg> {
     /// My replica ID.
     me: ReplicaId,
 
     /// Total number of replicas in cluster.
-    population: u8,
+    population: u16,
 
     /// Receiver side of the recv channel.
     rx_recv: mpsc::UnboundedReceiver<(ReplicaId, PeerMessage<Msg>)>,
@@ -72,13 +256,35 @@ pub(crate) struct TransportHub<Msg> {
     /// Join handle of the peer acceptor task.
     _peer_acceptor_handle: JoinHandle<()>,
 
+    /// Join handle of the datagram receiver task, if UDP side-channel is
+    /// enabled.
+    _datagram_receiver_handle: Option<JoinHandle<()>>,
+
+    /// Number of messages to each peer queued at my side, not yet handed to
+    /// the socket, shared with the peer messenger tasks.
+    queue_depths: Arc<Vec<AtomicUsize>>,
+
+    /// High and low watermarks of queue depth for congestion signaling.
+    queue_watermarks: (usize, usize),
+
+    /// Peers currently considered congested.
+    congested: Bitmap,
+
+    /// Total number of messages queued to all peers, sampled on every send.
+    queue_gauge: QueueGauge,
+
+    /// Map from peer ID -> lease-related messages queued for it, to be sent
+    /// together as one batch at the next flush.
+    lease_batches: HashMap<ReplicaId, Vec<(LeaseGid, LeaseNum, LeaseMsg)>>,
+
     /// Sender side of the connect channel, used when proactively connecting
     /// to some peer.
     tx_connect: mpsc::UnboundedSender<(ReplicaId, SocketAddr)>,
 
     /// Receiver side of the connack channel, used when proactively connecting
     /// to some peer.
-    rx_connack: mpsc::UnboundedReceiver<ReplicaId>,
+    rx_connack:
+        mpsc::UnboundedReceiver<(ReplicaId, Result<(), SummersetError>)>,
 
     /// Map from peer ID -> peer messenger task join handles, shared with
     /// the peer acceptor task.
@@ -99,11 +305,15 @@ where
 {
     /// Creates a new server internal TCP transport hub. Spawns the peer
     /// acceptor task. Creates a recv channel for listening on peers'
-    /// messages.
+    /// messages. If `udp_datagrams` is set, also binds a UDP socket at
+    /// `p2p_addr` and spawns the datagram receiver task.
+    #[allow(clippy::too_many_arguments)]
     pub(crate) async fn new_and_setup(
         me: ReplicaId,
-        population: u8,
+        population: u16,
+        protocol: SmrProtocol,
         p2p_addr: SocketAddr,
+        udp_datagrams: bool,
         // if non-null, a shortcut channel to feed lease messages directly in:
         tx_leases: HashMap<
             LeaseGid,
@@ -113,6 +323,10 @@ where
         if population <= me {
             return logged_err!("invalid population {}", population);
         }
+        if udp_datagrams && PEER_TLS.get().is_some() {
+            // datagrams would bypass peer authentication
+            return logged_err!("UDP datagrams cannot be used with peer TLS");
+        }
 
         let (tx_recv, rx_recv) =
             mpsc::unbounded_channel::<(ReplicaId, PeerMessage<Msg>)>();
@@ -130,9 +344,40 @@ where
         let (tx_connect, rx_connect) = mpsc::unbounded_channel();
         let (tx_connack, rx_connack) = mpsc::unbounded_channel();
 
+        // the UDP side-channel socket, if enabled, is shared by the receiver
+        // task and all peer messenger tasks
+        let (udp_socket, datagram_receiver_handle) = if udp_datagrams {
+            let udp_socket = Arc::new(UdpSocket::bind(p2p_addr).await?);
+            let mut receiver = TransportHubDatagramTask::new(
+                me,
+                population,
+                protocol,
+                udp_socket.clone(),
+                tx_recv.clone(),
+            );
+            let datagram_receiver_handle =
+                spawn_inherit_me(async move { receiver.run().await });
+            (Some(udp_socket), Some(datagram_receiver_handle))
+        } else {
+            (None, None)
+        };
+
+        let queue_depths: Arc<Vec<AtomicUsize>> =
+            Arc::new((0..population).map(|_| AtomicUsize::new(0)).collect());
+
         let peer_listener = tcp_bind_with_retry(p2p_addr, 15).await?;
         let mut acceptor = TransportHubAcceptorTask::new(
-            me,
+            PeerHello::mine(
+                me,
+                protocol,
+                udp_socket
+                    .as_ref()
+                    .map(|s| s.local_addr())
+                    .transpose()?
+                    .map(|a| a.port()),
+            ),
+            udp_socket,
+            queue_depths.clone(),
             tx_recv.clone(),
             peer_listener,
             tx_sends_write,
@@ -142,7 +387,7 @@ where
             tx_leases,
         );
         let peer_acceptor_handle =
-            tokio::spawn(async move { acceptor.run().await });
+            spawn_inherit_me(async move { acceptor.run().await });
 
         Ok(TransportHub {
             me,
@@ -150,6 +395,15 @@ where
             rx_recv,
             tx_sends: tx_sends_read,
             _peer_acceptor_handle: peer_acceptor_handle,
+            _datagram_receiver_handle: datagram_receiver_handle,
+            queue_depths,
+            queue_watermarks: (
+                DEFAULT_QUEUE_HIGH_WATERMARK,
+                DEFAULT_QUEUE_LOW_WATERMARK,
+            ),
+            congested: Bitmap::new(population, false),
+            queue_gauge: QueueGauge::new(),
+            lease_batches: HashMap::new(),
             tx_connect,
             rx_connack,
             _peer_messenger_handles: peer_messenger_handles_read,
@@ -165,7 +419,7 @@ where
     ) -> Result<(), SummersetError> {
         self.tx_connect.send((id, peer_addr))?;
         match self.rx_connack.recv().await {
-            Some(ack_id) => {
+            Some((ack_id, result)) => {
                 if ack_id != id {
                     logged_err!(
                         "peer ID mismatch: expected {}, got {}",
@@ -173,7 +427,7 @@ where
                         ack_id
                     )
                 } else {
-                    Ok(())
+                    result
                 }
             }
             None => logged_err!("connack channel closed"),
@@ -184,7 +438,7 @@ where
     /// form a group of specified size.
     pub(crate) async fn wait_for_group(
         &self,
-        group: u8,
+        group: u16,
     ) -> Result<(), SummersetError> {
         if group == 0 {
             logged_err!("invalid group size {}", group)
@@ -208,6 +462,73 @@ where
         Ok(peers)
     }
 
+    /// Gets the number of messages to a peer queued at my side that have not
+    /// been handed to the socket yet.
+    pub(crate) fn peer_queue_depth(&self, peer: ReplicaId) -> usize {
+        self.queue_depths
+            .get(peer as usize)
+            .map_or(0, |d| d.load(Ordering::Relaxed))
+    }
+
+    /// Gets the total number of messages queued at my side to all peers.
+    fn total_queue_depth(&self) -> usize {
+        self.queue_depths
+            .iter()
+            .map(|d| d.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Gets the stage name, total number of messages queued to all peers,
+    /// and its high watermark since the previous call.
+    pub(crate) fn queue_depth_report(&mut self) -> (String, usize, usize) {
+        self.queue_gauge.observe(self.total_queue_depth());
+        let (depth, peak) = self.queue_gauge.report();
+        ("transport_hub".into(), depth, peak)
+    }
+
+    /// Sets the high and low watermarks of per-peer queue depth used by
+    /// `peer_congested()`.
+    pub(crate) fn set_queue_watermarks(
+        &mut self,
+        high: usize,
+        low: usize,
+    ) -> Result<(), SummersetError> {
+        if high == 0 || low >= high {
+            return logged_err!(
+                "invalid queue watermarks high {} low {}",
+                high,
+                low
+            );
+        }
+        self.queue_watermarks = (high, low);
+        Ok(())
+    }
+
+    /// Checks whether a peer is congested, i.e., its queue depth has reached
+    /// the high watermark and has not drained down to the low watermark
+    /// since. Protocols may use this as a backpressure signal to hold off
+    /// sending more to the peer.
+    pub(crate) fn peer_congested(
+        &mut self,
+        peer: ReplicaId,
+    ) -> Result<bool, SummersetError> {
+        if peer >= self.population {
+            return logged_err!("invalid peer ID {}", peer);
+        }
+        let depth = self.peer_queue_depth(peer);
+        let (high, low) = self.queue_watermarks;
+        if self.congested.get(peer)? {
+            if depth <= low {
+                self.congested.set(peer, false)?;
+                pf_debug!("peer {} drained, queue depth {}", peer, depth);
+            }
+        } else if depth >= high {
+            self.congested.set(peer, true)?;
+            pf_debug!("peer {} congested, queue depth {}", peer, depth);
+        }
+        self.congested.get(peer)
+    }
+
     /// Sends a message to a specified peer by sending to the send channel.
     fn send_msg_inner(
         &mut self,
@@ -217,6 +538,9 @@ where
         let tx_sends_guard = self.tx_sends.guard();
         match tx_sends_guard.get(&peer) {
             Some(tx_send) => {
+                msgtrace::trace_msg(self.me, peer, || msg.trace_repr());
+                self.queue_depths[peer as usize]
+                    .fetch_add(1, Ordering::Relaxed);
                 tx_send.send(msg).map_err(SummersetError::msg)?;
             }
             None => {
@@ -228,6 +552,8 @@ where
             }
         }
 
+        let depth = self.total_queue_depth();
+        self.queue_gauge.observe(depth);
         Ok(())
     }
 
@@ -250,6 +576,8 @@ where
             }
 
             // not skipped
+            msgtrace::trace_msg(self.me, peer, || msg.trace_repr());
+            self.queue_depths[peer as usize].fetch_add(1, Ordering::Relaxed);
             tx_sends_guard
                 .get(&peer)
                 .unwrap()
@@ -257,6 +585,8 @@ where
                 .map_err(SummersetError::msg)?;
         }
 
+        let depth = self.total_queue_depth();
+        self.queue_gauge.observe(depth);
         Ok(())
     }
 
@@ -279,6 +609,50 @@ where
         self.bcast_msg_inner(PeerMessage::Msg { msg }, target)
     }
 
+    /// Sends a message to a specified peer on the control lane, so that it
+    /// overtakes bulky messages queued on the connection. It may thus get
+    /// reordered with respect to messages sent through `send_msg()`.
+    pub(crate) fn send_ctrl_msg(
+        &mut self,
+        msg: Msg,
+        peer: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        self.send_msg_inner(PeerMessage::CtrlMsg { msg }, peer)
+    }
+
+    /// Broadcasts message to specified peers on the control lane. If `target`
+    /// is `None`, broadcast to all current peers.
+    pub(crate) fn bcast_ctrl_msg(
+        &mut self,
+        msg: Msg,
+        target: Option<Bitmap>,
+    ) -> Result<(), SummersetError> {
+        self.bcast_msg_inner(PeerMessage::CtrlMsg { msg }, target)
+    }
+
+    /// Sends a message to a specified peer as an unreliable datagram through
+    /// the UDP side-channel if both of us have it enabled, otherwise on the
+    /// control lane of the TCP connection. The message may get lost, or
+    /// reordered with respect to other messages.
+    pub(crate) fn send_msg_unreliable(
+        &mut self,
+        msg: Msg,
+        peer: ReplicaId,
+    ) -> Result<(), SummersetError> {
+        self.send_msg_inner(PeerMessage::Datagram { msg }, peer)
+    }
+
+    /// Broadcasts message to specified peers as unreliable datagrams, with
+    /// the same fallback as `send_msg_unreliable()`. If `target` is `None`,
+    /// broadcast to all current peers.
+    pub(crate) fn bcast_msg_unreliable(
+        &mut self,
+        msg: Msg,
+        target: Option<Bitmap>,
+    ) -> Result<(), SummersetError> {
+        self.bcast_msg_inner(PeerMessage::Datagram { msg }, target)
+    }
+
     /// Sends a lease-related message to a specified peer by sending to the
     /// send channel.
     pub(crate) fn send_lease_msg(
@@ -317,6 +691,55 @@ where
         )
     }
 
+    /// Queues a lease-related message for specified peers, to be sent in one
+    /// batch per peer together with others queued before the next
+    /// `flush_lease_msgs()`. If `target` is `None`, queue for all current
+    /// peers. Meant for periodic renewals that need not go out right away.
+    pub(crate) fn queue_lease_msg(
+        &mut self,
+        lease_gid: LeaseGid,
+        lease_num: LeaseNum,
+        lease_msg: LeaseMsg,
+        target: Option<Bitmap>,
+    ) -> Result<(), SummersetError> {
+        let tx_sends_guard = self.tx_sends.guard();
+        for &peer in tx_sends_guard.keys() {
+            if peer == self.me {
+                continue;
+            }
+            if let Some(ref target) = target {
+                if peer >= target.size() || !target.get(peer)? {
+                    continue;
+                }
+            }
+            self.lease_batches.entry(peer).or_default().push((
+                lease_gid,
+                lease_num,
+                lease_msg.clone(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Sends out all queued lease-related messages, as one message per peer.
+    pub(crate) fn flush_lease_msgs(&mut self) -> Result<(), SummersetError> {
+        let batches: Vec<_> = self.lease_batches.drain().collect();
+        for (peer, mut leases) in batches {
+            let msg = if leases.len() == 1 {
+                let (lease_gid, lease_num, lease_msg) = leases.pop().unwrap();
+                PeerMessage::LeaseMsg {
+                    lease_gid,
+                    lease_num,
+                    lease_msg,
+                }
+            } else {
+                PeerMessage::LeaseBatch { leases }
+            };
+            self.send_msg_inner(msg, peer)?;
+        }
+        Ok(())
+    }
+
     /// Receives a message from some peer by receiving from the recv channel.
     /// Returns a pair of `(peer_id, msg)` on success.
     pub(crate) async fn recv_msg(
@@ -325,7 +748,7 @@ where
         match self.rx_recv.recv().await {
             Some((id, peer_msg)) => match peer_msg {
                 PeerMessage::Msg { msg } => Ok((id, msg)),
-                _ => logged_err!("unexpected peer message type"),
+                _ => logged_err!(Protocol; "unexpected peer message type"),
             },
             None => logged_err!("recv channel has been closed"),
         }
@@ -339,7 +762,7 @@ where
         match self.rx_recv.try_recv() {
             Ok((id, peer_msg)) => match peer_msg {
                 PeerMessage::Msg { msg } => Ok((id, msg)),
-                _ => logged_err!("unexpected peer message type"),
+                _ => logged_err!(Protocol; "unexpected peer message type"),
             },
             Err(e) => Err(SummersetError::msg(e)),
         }
@@ -385,7 +808,9 @@ where
 
 /// TransportHub peer acceptor task.
 struct TransportHubAcceptorTask<Msg> {
-    me: ReplicaId,
+    hello: PeerHello,
+    udp_socket: Option<Arc<UdpSocket>>,
+    queue_depths: Arc<Vec<AtomicUsize>>,
 
     tx_recv: mpsc::UnboundedSender<(ReplicaId, PeerMessage<Msg>)>,
     tx_sends: flashmap::WriteHandle<
@@ -397,7 +822,7 @@ struct TransportHubAcceptorTask<Msg> {
     peer_messenger_handles: flashmap::WriteHandle<ReplicaId, JoinHandle<()>>,
 
     rx_connect: mpsc::UnboundedReceiver<(ReplicaId, SocketAddr)>,
-    tx_connack: mpsc::UnboundedSender<ReplicaId>,
+    tx_connack: mpsc::UnboundedSender<(ReplicaId, Result<(), SummersetError>)>,
 
     tx_leases:
         HashMap<LeaseGid, mpsc::UnboundedSender<(LeaseNum, LeaseNotice)>>,
@@ -419,7 +844,9 @@ where
     /// Creates the peer acceptor task.
     #[allow(clippy::too_many_arguments)]
     fn new(
-        me: ReplicaId,
+        hello: PeerHello,
+        udp_socket: Option<Arc<UdpSocket>>,
+        queue_depths: Arc<Vec<AtomicUsize>>,
         tx_recv: mpsc::UnboundedSender<(ReplicaId, PeerMessage<Msg>)>,
         peer_listener: TcpListener,
         tx_sends: flashmap::WriteHandle<
@@ -431,7 +858,10 @@ where
             JoinHandle<()>,
         >,
         rx_connect: mpsc::UnboundedReceiver<(ReplicaId, SocketAddr)>,
-        tx_connack: mpsc::UnboundedSender<ReplicaId>,
+        tx_connack: mpsc::UnboundedSender<(
+            ReplicaId,
+            Result<(), SummersetError>,
+        )>,
         tx_leases: HashMap<
             LeaseGid,
             mpsc::UnboundedSender<(LeaseNum, LeaseNotice)>,
@@ -442,7 +872,9 @@ where
         let (tx_exit, rx_exit) = mpsc::unbounded_channel();
 
         TransportHubAcceptorTask {
-            me,
+            hello,
+            udp_socket,
+            queue_depths,
             tx_recv,
             peer_listener,
             tx_sends,
@@ -455,6 +887,30 @@ where
         }
     }
 
+    /// Decides where datagrams to a peer go: to its UDP side-channel port at
+    /// the same IP as its connection, if both of us have one enabled. The IP
+    /// is converted to our socket's family, since a dual-stack IPv6 socket
+    /// only reaches IPv4 peers through v4-mapped addresses.
+    fn peer_udp(
+        &self,
+        peer_hello: &PeerHello,
+        conn_addr: SocketAddr,
+    ) -> Option<(Arc<UdpSocket>, SocketAddr)> {
+        match (&self.udp_socket, peer_hello.udp_port) {
+            (Some(socket), Some(port)) => {
+                let ip = match (socket.local_addr(), conn_addr.ip()) {
+                    (Ok(local), IpAddr::V4(ip)) if local.is_ipv6() => {
+                        IpAddr::V6(ip.to_ipv6_mapped())
+                    }
+                    (Ok(local), ip) if local.is_ipv4() => ip.to_canonical(),
+                    (_, ip) => ip,
+                };
+                Some((socket.clone(), SocketAddr::new(ip, port)))
+            }
+            _ => None,
+        }
+    }
+
     /// Connects to a peer proactively.
     async fn connect_new_peer(
         &mut self,
@@ -463,7 +919,20 @@ where
     ) -> Result<(), SummersetError> {
         pf_debug!("connecting to peer {} '{}'...", id, conn_addr);
         let mut stream = tcp_connect_with_retry(conn_addr, 15).await?;
-        stream.write_u8(self.me).await?; // send my ID
+        if let Some(peer_tls) = PEER_TLS.get() {
+            stream = peer_tls.connect(stream, id).await?;
+        }
+        self.hello.send(&mut stream).await?; // send my ID & versions
+        let peer_hello = PeerHello::recv(&mut stream).await?;
+        if peer_hello.id != id {
+            return logged_err!(
+                "peer ID mismatch: expected {}, got {}",
+                id,
+                peer_hello.id
+            );
+        }
+        let wire_ver = self.hello.negotiate(&peer_hello)?;
+        let udp = self.peer_udp(&peer_hello, conn_addr);
 
         let mut peer_messenger_handles_guard =
             self.peer_messenger_handles.guard();
@@ -474,18 +943,23 @@ where
         let mut tx_sends_guard = self.tx_sends.guard();
         let (tx_send, rx_send) = mpsc::unbounded_channel();
         tx_sends_guard.insert(id, tx_send);
+        self.queue_depths[id as usize].store(0, Ordering::Relaxed);
 
         let mut messenger = TransportHubMessengerTask::new(
             id,
             conn_addr,
             stream,
+            wire_ver,
+            self.hello.id,
+            udp,
+            self.queue_depths.clone(),
             rx_send,
             self.tx_recv.clone(),
             self.tx_leases.clone(),
             self.tx_exit.clone(),
         );
         let peer_messenger_handle =
-            tokio::spawn(async move { messenger.run().await });
+            spawn_inherit_me(async move { messenger.run().await });
         peer_messenger_handles_guard.insert(id, peer_messenger_handle);
 
         pf_debug!("connected to peer {}", id);
@@ -498,11 +972,29 @@ where
         mut stream: TcpStream,
         addr: SocketAddr,
     ) -> Result<(), SummersetError> {
-        let id = stream.read_u8().await; // receive peer's ID
-        if let Err(e) = id {
-            return logged_err!("error receiving new peer ID: {}", e);
+        // if using mutual TLS, the peer must present a valid certificate
+        // issued for the ID it claims
+        let mut peer_cert = None;
+        if let Some(peer_tls) = PEER_TLS.get() {
+            let (plain, cert) = peer_tls.accept(stream).await?;
+            stream = plain;
+            peer_cert = Some(cert);
+        }
+
+        // receive peer's ID & versions, and always reply with mine so that
+        // an incompatible peer learns why it gets rejected
+        let peer_hello = PeerHello::recv(&mut stream).await;
+        if let Err(e) = peer_hello {
+            return logged_err!("error receiving new peer handshake: {}", e);
+        }
+        let peer_hello = peer_hello.unwrap();
+        if let Some(cert) = &peer_cert {
+            PeerTls::check_identity(cert, peer_hello.id)?;
         }
-        let id = id.unwrap();
+        self.hello.send(&mut stream).await?;
+        let id = peer_hello.id;
+        let wire_ver = self.hello.negotiate(&peer_hello)?;
+        let udp = self.peer_udp(&peer_hello, addr);
 
         let mut peer_messenger_handles_guard =
             self.peer_messenger_handles.guard();
@@ -513,18 +1005,23 @@ where
         let mut tx_sends_guard = self.tx_sends.guard();
         let (tx_send, rx_send) = mpsc::unbounded_channel();
         tx_sends_guard.insert(id, tx_send);
+        self.queue_depths[id as usize].store(0, Ordering::Relaxed);
 
         let mut messenger = TransportHubMessengerTask::new(
             id,
             addr,
             stream,
+            wire_ver,
+            self.hello.id,
+            udp,
+            self.queue_depths.clone(),
             rx_send,
             self.tx_recv.clone(),
             self.tx_leases.clone(),
             self.tx_exit.clone(),
         );
         let peer_messenger_handle =
-            tokio::spawn(async move { messenger.run().await });
+            spawn_inherit_me(async move { messenger.run().await });
         peer_messenger_handles_guard.insert(id, peer_messenger_handle);
 
         pf_debug!("waited on peer {}", id);
@@ -565,12 +1062,14 @@ where
                         break; // channel gets closed and no messages remain
                     }
                     let (peer, conn_addr) = to_connect.unwrap();
-                    if let Err(e) = self.connect_new_peer(
+                    let result = self.connect_new_peer(
                         peer,
                         conn_addr,
-                    ).await {
+                    ).await;
+                    if let Err(e) = &result {
                         pf_error!("error connecting to new peer: {}", e);
-                    } else if let Err(e) = self.tx_connack.send(peer) {
+                    }
+                    if let Err(e) = self.tx_connack.send((peer, result)) {
                         pf_error!("error sending to tx_connack: {}", e);
                     }
                 },
@@ -612,18 +1111,45 @@ struct TransportHubMessengerTask<Msg> {
     id: ReplicaId,
     /// Corresponding peer's address.
     addr: SocketAddr,
+    /// Protocol message format version negotiated with the peer.
+    wire_ver: u16,
+
+    /// My replica ID, tagged on datagrams.
+    me: ReplicaId,
+    /// UDP side-channel socket and peer's datagram address, if usable.
+    udp: Option<(Arc<UdpSocket>, SocketAddr)>,
+    /// Shared per-peer queue depth counters.
+    queue_depths: Arc<Vec<AtomicUsize>>,
 
     conn_read: OwnedReadHalf,
     conn_write: OwnedWriteHalf,
 
     rx_send: mpsc::UnboundedReceiver<PeerMessage<Msg>>,
     read_buf: BytesMut,
+    /// Pieces received so far of the current incoming bulk lane message.
+    bulk_recv: Vec<u8>,
+    /// True if the current incoming bulk lane message is being dropped for
+    /// being too large.
+    bulk_dropping: bool,
 
     tx_recv: mpsc::UnboundedSender<(ReplicaId, PeerMessage<Msg>)>,
     write_buf: BytesMut,
     write_buf_cursor: usize,
     retrying: bool,
 
+    /// Outgoing messages waiting on the control lane.
+    ctrl_queue: VecDeque<PeerMessage<Msg>>,
+    /// Outgoing messages waiting on the bulk lane.
+    bulk_queue: VecDeque<PeerMessage<Msg>>,
+    /// Encoded bytes of the current outgoing bulk lane message, and the
+    /// cursor at its first unsent byte.
+    bulk_send: Vec<u8>,
+    bulk_send_cursor: usize,
+
+    /// Incoming messages held back by a simulated network delay, with the
+    /// time each is due, in arrival order.
+    delayed: VecDeque<(Instant, PeerMessage<Msg>)>,
+
     tx_leases:
         HashMap<LeaseGid, mpsc::UnboundedSender<(LeaseNum, LeaseNotice)>>,
 
@@ -642,10 +1168,15 @@ where
         + 'static,
 {
     /// Creates a per-peer messenger task.
+    #[allow(clippy::too_many_arguments)]
     fn new(
         id: ReplicaId,
         addr: SocketAddr,
         conn: TcpStream,
+        wire_ver: u16,
+        me: ReplicaId,
+        udp: Option<(Arc<UdpSocket>, SocketAddr)>,
+        queue_depths: Arc<Vec<AtomicUsize>>,
         rx_send: mpsc::UnboundedReceiver<PeerMessage<Msg>>,
         tx_recv: mpsc::UnboundedSender<(ReplicaId, PeerMessage<Msg>)>,
         tx_leases: HashMap<
@@ -664,39 +1195,320 @@ where
         TransportHubMessengerTask {
             id,
             addr,
+            wire_ver,
+            me,
+            udp,
+            queue_depths,
             conn_read,
             conn_write,
             rx_send,
             read_buf,
+            bulk_recv: vec![],
+            bulk_dropping: false,
             tx_recv,
             write_buf,
             write_buf_cursor,
             retrying,
+            ctrl_queue: VecDeque::new(),
+            bulk_queue: VecDeque::new(),
+            bulk_send: vec![],
+            bulk_send_cursor: 0,
+            delayed: VecDeque::new(),
             tx_leases,
             tx_exit,
         }
     }
 
-    /// Writes a message through given TcpStream.
-    /// This is a non-method function to ease `tokio::select!` sharing.
-    fn write_msg(
-        write_buf: &mut BytesMut,
-        write_buf_cursor: &mut usize,
-        conn_write: &OwnedWriteHalf,
-        msg: Option<&PeerMessage<Msg>>,
-    ) -> Result<bool, SummersetError> {
-        safe_tcp_write(write_buf, write_buf_cursor, conn_write, msg)
+    /// Feeds a lease-related message received from the peer to the lease
+    /// manager of its lease group.
+    fn feed_lease_msg(
+        &self,
+        lease_gid: LeaseGid,
+        lease_num: LeaseNum,
+        lease_msg: LeaseMsg,
+    ) {
+        // pf_trace!("recv <- {} lease msg {:?}", self.id, lease_msg);
+        if let Some(tx_lease) = self.tx_leases.get(&lease_gid) {
+            if let Err(e) = tx_lease.send((
+                lease_num,
+                LeaseNotice::RecvLeaseMsg {
+                    peer: self.id,
+                    msg: lease_msg,
+                },
+            )) {
+                pf_error!(
+                    "error sending to tx_lease {} for {}: {}",
+                    lease_gid,
+                    self.id,
+                    e
+                );
+            }
+        } else {
+            pf_error!(
+                "received LeaseMsg <- {} but unknown gid {}",
+                self.id,
+                lease_gid
+            );
+        }
+    }
+
+    /// Hands a message received from the peer over to where it belongs.
+    fn deliver(&self, msg: PeerMessage<Msg>) {
+        match msg {
+            PeerMessage::LeaseMsg {
+                lease_gid,
+                lease_num,
+                lease_msg,
+            } => {
+                self.feed_lease_msg(lease_gid, lease_num, lease_msg);
+            }
+
+            PeerMessage::LeaseBatch { leases } => {
+                for (lease_gid, lease_num, lease_msg) in leases {
+                    self.feed_lease_msg(lease_gid, lease_num, lease_msg);
+                }
+            }
+
+            PeerMessage::CtrlMsg { msg } | PeerMessage::Msg { msg } => {
+                // pf_trace!("recv <- {} msg {:?}", id, msg);
+                if let Err(e) =
+                    self.tx_recv.send((self.id, PeerMessage::Msg { msg }))
+                {
+                    pf_error!(
+                        "error sending to tx_recv for {}: {}",
+                        self.id,
+                        e
+                    );
+                }
+            }
+
+            _ => {
+                pf_error!("unexpected msg type to deliver <- {}", self.id);
+            }
+        }
+    }
+
+    /// Delivers a message received from the peer, subject to simulated
+    /// network faults: dropped if the peer is cut off from me, or held back
+    /// if a delay is in effect. Messages already held back keep the ones
+    /// after them waiting, so that arrival order is preserved.
+    fn deliver_faulty(&mut self, msg: PeerMessage<Msg>) {
+        if netfault::peer_blocked(self.me, self.id) {
+            return;
+        }
+        let delay = netfault::inbound_delay(self.me);
+        if delay.is_zero() && self.delayed.is_empty() {
+            self.deliver(msg);
+        } else {
+            self.delayed.push_back((Instant::now() + delay, msg));
+        }
+    }
+
+    /// Delivers held back messages that are due.
+    fn deliver_delayed(&mut self) {
+        let now = Instant::now();
+        while self.delayed.front().is_some_and(|(due, _)| *due <= now) {
+            let (_, msg) = self.delayed.pop_front().unwrap();
+            self.deliver(msg);
+        }
+    }
+
+    /// Time the first held back message is due.
+    fn next_delayed_due(&self) -> Instant {
+        self.delayed
+            .front()
+            .map_or_else(Instant::now, |(due, _)| *due)
+    }
+
+    /// Counts one message to the peer as no longer queued.
+    fn dequeued(&self) {
+        let _ = self.queue_depths[self.id as usize].fetch_update(
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+            |d| d.checked_sub(1),
+        );
     }
 
-    /// Reads a message from given TcpStream.
+    /// Applies simulated reordering and duplication to a bulk lane message
+    /// about to be sent.
+    #[cfg(feature = "byzantine-hooks")]
+    fn byzantine_hooks(&mut self, msg: &mut PeerMessage<Msg>) {
+        if !self.bulk_queue.is_empty() && byzantine::should_reorder() {
+            pf_debug!("reordered msg -> {}", self.id);
+            std::mem::swap(msg, &mut self.bulk_queue[0]);
+        }
+        if byzantine::should_duplicate() {
+            pf_debug!("duplicated msg -> {}", self.id);
+            self.queue_depths[self.id as usize].fetch_add(1, Ordering::Relaxed);
+            self.bulk_queue.push_front(msg.clone());
+        }
+    }
+
+    /// Writes out queued frames through the TcpStream until either nothing
+    /// is left or the socket is full, always taking a control lane message
+    /// before the next bulk lane piece.
+    fn write_queued(&mut self) -> Result<(), SummersetError> {
+        while !self.retrying {
+            let frame = if let Some(msg) = self.ctrl_queue.pop_front() {
+                if !matches!(msg, PeerMessage::Leave | PeerMessage::LeaveReply)
+                {
+                    self.dequeued();
+                }
+                if !msg_within_limit(&msg)? {
+                    pf_warn!("msg -> {} too large, dropped", self.id);
+                    continue;
+                }
+                PeerFrame::Ctrl { msg }
+            } else if self.bulk_send_cursor < self.bulk_send.len()
+                || !self.bulk_queue.is_empty()
+            {
+                if self.bulk_send_cursor == self.bulk_send.len() {
+                    #[allow(unused_mut)]
+                    let mut msg = self.bulk_queue.pop_front().unwrap();
+                    #[cfg(feature = "byzantine-hooks")]
+                    self.byzantine_hooks(&mut msg);
+                    self.bulk_send = encode(&msg)?;
+                    self.bulk_send_cursor = 0;
+                    let max_bytes = max_msg_bytes();
+                    if max_bytes > 0 && self.bulk_send.len() > max_bytes {
+                        pf_warn!("msg -> {} too large, dropped", self.id);
+                        self.bulk_send.clear();
+                        self.dequeued();
+                        continue;
+                    }
+                }
+                let end = self
+                    .bulk_send
+                    .len()
+                    .min(self.bulk_send_cursor + BULK_PIECE_BYTES);
+                #[allow(unused_mut)]
+                let mut piece =
+                    self.bulk_send[self.bulk_send_cursor..end].to_vec();
+                let crc = crc32(&piece);
+                #[cfg(feature = "byzantine-hooks")]
+                if byzantine::maybe_corrupt(&mut piece) {
+                    pf_debug!("corrupted msg piece -> {}", self.id);
+                }
+                self.bulk_send_cursor = end;
+                if end == self.bulk_send.len() {
+                    self.dequeued();
+                }
+                PeerFrame::Bulk {
+                    piece,
+                    last: end == self.bulk_send.len(),
+                    crc,
+                }
+            } else {
+                break; // nothing left to send
+            };
+
+            if !safe_tcp_write(
+                &mut self.write_buf,
+                &mut self.write_buf_cursor,
+                &self.conn_write,
+                Some(&(self.wire_ver, frame)),
+            )? {
+                pf_debug!("should start retrying msg send -> {}", self.id);
+                self.retrying = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a frame from given TcpStream, checking that it is encoded in
+    /// the expected wire format version. Returns the message if a whole one
+    /// is now received, or `None` if it was a non-final bulk lane piece or
+    /// got dropped for being too large or failing its CRC check.
     /// This is a non-method function to ease `tokio::select!` sharing.
     async fn read_msg(
-        // first 8 bytes being the message length, and the rest bytes being the
-        // message itself
+        // first 8 bytes being the frame length, and the rest bytes being the
+        // frame itself
         read_buf: &mut BytesMut,
         conn_read: &mut OwnedReadHalf,
-    ) -> Result<PeerMessage<Msg>, SummersetError> {
-        safe_tcp_read(read_buf, conn_read).await
+        wire_ver: u16,
+        bulk_recv: &mut Vec<u8>,
+        bulk_dropping: &mut bool,
+    ) -> Result<Option<PeerMessage<Msg>>, SummersetError> {
+        let Some((msg_ver, frame)) = safe_tcp_read_bounded::<
+            (u16, PeerFrame<Msg>),
+            _,
+        >(
+            read_buf, conn_read, max_msg_bytes()
+        )
+        .await?
+        else {
+            pf_warn!("received frame too large, dropped");
+            return Ok(None);
+        };
+        if msg_ver != wire_ver {
+            return logged_err!(
+                "message of wire format v{} on a v{} connection",
+                msg_ver,
+                wire_ver
+            );
+        }
+
+        match frame {
+            PeerFrame::Ctrl { msg } => Ok(Some(msg)),
+            PeerFrame::Bulk { piece, last, crc } if crc32(&piece) != crc => {
+                // drop the whole message this piece belongs to
+                pf_warn!("received corrupted bulk msg piece, dropping");
+                bulk_recv.clear();
+                *bulk_dropping = !last;
+                Ok(None)
+            }
+            PeerFrame::Bulk {
+                piece, last: true, ..
+            } if bulk_recv.is_empty() && !*bulk_dropping => {
+                Ok(Some(decode(&piece)?))
+            }
+            PeerFrame::Bulk {
+                mut piece, last, ..
+            } => {
+                if !*bulk_dropping {
+                    bulk_recv.append(&mut piece);
+                    let max_bytes = max_msg_bytes();
+                    if max_bytes > 0 && bulk_recv.len() > max_bytes {
+                        pf_warn!("received bulk msg too large, dropping");
+                        bulk_recv.clear();
+                        *bulk_dropping = true;
+                    }
+                }
+                if !last {
+                    return Ok(None);
+                } else if *bulk_dropping {
+                    *bulk_dropping = false;
+                    return Ok(None);
+                }
+                let msg = decode(bulk_recv)?;
+                bulk_recv.clear();
+                Ok(Some(msg))
+            }
+        }
+    }
+
+    /// Sends a message as a UDP datagram if possible. Returns the message back
+    /// if it should go through the TCP connection instead. A datagram that
+    /// cannot be sent right away is simply dropped.
+    fn send_datagram(
+        udp: Option<&(Arc<UdpSocket>, SocketAddr)>,
+        me: ReplicaId,
+        wire_ver: u16,
+        msg: Msg,
+    ) -> Result<Option<Msg>, SummersetError> {
+        let Some((socket, addr)) = udp else {
+            return Ok(Some(msg));
+        };
+        let bytes = encode(&(me, wire_ver, &msg))?;
+        if bytes.len() > MAX_DATAGRAM_BYTES {
+            return Ok(Some(msg));
+        }
+        if let Err(_e) = socket.try_send_to(&bytes, *addr) {
+            // NOTE: commented out to prevent console lags
+            //       during benchmarking
+            // pf_warn!("error sending datagram -> {}: {}", addr, e);
+        }
+        Ok(None)
     }
 
     /// Starts a per-peer messenger task loop.
@@ -708,59 +1520,62 @@ where
         );
 
         loop {
+            // push out whatever is queued, control lane first
+            if let Err(_e) = self.write_queued() {
+                // NOTE: commented out to prevent console lags
+                //       during benchmarking
+                // pf_error!("error sending -> {}: {}", id, e);
+            }
+
+            let delayed_due = self.next_delayed_due();
             tokio::select! {
                 // gets a message to send out
-                msg = self.rx_send.recv(), if !self.retrying => {
+                msg = self.rx_send.recv() => {
                     match msg {
                         Some(PeerMessage::Leave) => {
                             // I decide to leave, notify peers
-                            if let Err(_e) = Self::write_msg(
-                                &mut self.write_buf,
-                                &mut self.write_buf_cursor,
-                                &self.conn_write,
-                                Some(&PeerMessage::Leave),
-                            ) {
-                                // NOTE: commented out to prevent console lags
-                                //       during benchmarking
-                                // pf_error!("error sending -> {}: {}", id, e);
-                            } else { // NOTE: skips `WouldBlock` error check here
-                                pf_debug!("sent leave notification -> {}", self.id);
-                            }
+                            self.ctrl_queue.push_back(PeerMessage::Leave);
+                            pf_debug!("queued leave notification -> {}", self.id);
                         },
 
                         Some(PeerMessage::LeaveReply) => {
                             pf_error!("proactively sending LeaveReply msg");
                         },
 
-                        Some(PeerMessage::LeaseMsg { .. }) | Some(PeerMessage::Msg { .. }) => {
-                            match Self::write_msg(
-                                &mut self.write_buf,
-                                &mut self.write_buf_cursor,
-                                &self.conn_write,
-                                Some(msg.as_ref().unwrap()),
+                        Some(PeerMessage::Datagram { msg }) => {
+                            match Self::send_datagram(
+                                self.udp.as_ref(),
+                                self.me,
+                                self.wire_ver,
+                                msg,
                             ) {
-                                Ok(true) => {
-                                    // pf_trace!("sent -> {} msg {:?}", id, msg);
-                                }
-                                Ok(false) => {
-                                    pf_debug!("should start retrying msg send -> {}", self.id);
-                                    self.retrying = true;
+                                Ok(Some(msg)) => {
+                                    // fall back to the control lane
+                                    self.ctrl_queue.push_back(PeerMessage::CtrlMsg { msg });
                                 }
-                                Err(_e) => {
-                                    // NOTE: commented out to prevent console lags
-                                    //       during benchmarking
-                                    // pf_error!("error sending -> {}: {}", id, e);
+                                Ok(None) => self.dequeued(),
+                                Err(e) => {
+                                    pf_error!("error encoding datagram -> {}: {}", self.id, e);
+                                    self.dequeued();
                                 }
                             }
                         },
 
+                        Some(PeerMessage::Msg { .. }) => {
+                            self.bulk_queue.push_back(msg.unwrap());
+                        },
+
+                        Some(PeerMessage::LeaseMsg { .. }) | Some(PeerMessage::LeaseBatch { .. }) | Some(PeerMessage::CtrlMsg { .. }) => {
+                            self.ctrl_queue.push_back(msg.unwrap());
+                        },
+
                         None => break, // channel gets closed and no messages remain
                     }
                 },
 
                 // retrying last unsuccessful send
                 _ = self.conn_write.writable(), if self.retrying => {
-                    match Self::write_msg(
+                    match safe_tcp_write::<(u16, PeerFrame<Msg>), _>(
                         &mut self.write_buf,
                         &mut self.write_buf_cursor,
                         &self.conn_write,
@@ -782,16 +1597,14 @@ where
                 },
 
                 // receives new message from peer
-                msg = Self::read_msg(&mut self.read_buf, &mut self.conn_read) => {
+                msg = Self::read_msg(&mut self.read_buf, &mut self.conn_read, self.wire_ver, &mut self.bulk_recv, &mut self.bulk_dropping) => {
                     match msg {
-                        Ok(PeerMessage::Leave) => {
+                        Ok(None) => {}, // more bulk lane pieces to come
+
+                        Ok(Some(PeerMessage::Leave)) => {
                             // peer leaving, send dummy reply and break
-                            if let Err(_e) = Self::write_msg(
-                                &mut self.write_buf,
-                                &mut self.write_buf_cursor,
-                                &self.conn_write,
-                                Some(&PeerMessage::LeaveReply),
-                            ) {
+                            self.ctrl_queue.push_front(PeerMessage::LeaveReply);
+                            if let Err(_e) = self.write_queued() {
                                 // NOTE: commented out to prevent console lags
                                 //       during benchmarking
                                 // pf_error!("error sending -> {}: {}", id, e);
@@ -801,7 +1614,7 @@ where
                             break;
                         },
 
-                        Ok(PeerMessage::LeaveReply) => {
+                        Ok(Some(PeerMessage::LeaveReply)) => {
                             // my leave notification is acked by peer, break
                             if let Err(e) = self.tx_recv.send((self.id, PeerMessage::LeaveReply)) {
                                 pf_error!("error sending to tx_recv for {}: {}", self.id, e);
@@ -809,32 +1622,11 @@ where
                             break;
                         }
 
-                        Ok(PeerMessage::LeaseMsg { lease_gid, lease_num, lease_msg }) => {
-                            // pf_trace!("recv <- {} msg {:?}", id, msg);
-                            if let Some(tx_lease) = self.tx_leases.get(&lease_gid) {
-                                if let Err(e) = tx_lease.send((
-                                    lease_num,
-                                    LeaseNotice::RecvLeaseMsg { peer: self.id, msg: lease_msg }
-                                )) {
-                                    pf_error!(
-                                        "error sending to tx_lease {} for {}: {}",
-                                        lease_gid, self.id, e
-                                    );
-                                }
-                            } else {
-                                pf_error!(
-                                    "received LeaseMsg <- {} but unknown gid {}",
-                                    self.id, lease_gid
-                                );
-                            }
+                        Ok(Some(PeerMessage::Datagram { .. })) => {
+                            pf_error!("received Datagram msg on TCP <- {}", self.id);
                         },
 
-                        Ok(PeerMessage::Msg { .. }) => {
-                            // pf_trace!("recv <- {} msg {:?}", id, msg);
-                            if let Err(e) = self.tx_recv.send((self.id, msg.unwrap())) {
-                                pf_error!("error sending to tx_recv for {}: {}", self.id, e);
-                            }
-                        },
+                        Ok(Some(msg)) => self.deliver_faulty(msg),
 
                         Err(_e) => {
                             // NOTE: commented out to prevent console lags
@@ -843,6 +1635,11 @@ where
                             break; // probably the peer exited ungracefully
                         }
                     }
+                },
+
+                // held back messages due for delivery
+                _ = time::sleep_until(delayed_due), if !self.delayed.is_empty() => {
+                    self.deliver_delayed();
                 }
             }
         }
@@ -854,6 +1651,99 @@ where
     }
 }
 
+/// TransportHub UDP side-channel datagram receiver task.
+struct TransportHubDatagramTask<Msg> {
+    me: ReplicaId,
+    population: u16,
+    /// Range of protocol message format versions I accept.
+    min_ver: u16,
+    max_ver: u16,
+
+    udp_socket: Arc<UdpSocket>,
+    recv_buf: Vec<u8>,
+
+    tx_recv: mpsc::UnboundedSender<(ReplicaId, PeerMessage<Msg>)>,
+}
+
+impl<Msg> TransportHubDatagramTask<Msg>
+where
+    Msg: fmt::Debug
+        + Clone
+        + Serialize
+        + DeserializeOwned
+        + Send
+        + Sync
+        + 'static,
+{
+    /// Creates the datagram receiver task.
+    fn new(
+        me: ReplicaId,
+        population: u16,
+        protocol: SmrProtocol,
+        udp_socket: Arc<UdpSocket>,
+        tx_recv: mpsc::UnboundedSender<(ReplicaId, PeerMessage<Msg>)>,
+    ) -> Self {
+        TransportHubDatagramTask {
+            me,
+            population,
+            min_ver: protocol.min_wire_version(),
+            max_ver: protocol.wire_version(),
+            udp_socket,
+            recv_buf: vec![0; 65536],
+            tx_recv,
+        }
+    }
+
+    /// Decodes a received datagram into sender ID and message, dropping
+    /// anything malformed or not from a valid peer.
+    fn decode(&self, bytes: &[u8]) -> Option<(ReplicaId, Msg)> {
+        let (id, msg_ver, msg) = decode::<(ReplicaId, u16, Msg)>(bytes).ok()?;
+        if id == self.me
+            || id >= self.population
+            || msg_ver < self.min_ver
+            || msg_ver > self.max_ver
+        {
+            return None;
+        }
+        Some((id, msg))
+    }
+
+    /// Starts the datagram receiver task loop.
+    async fn run(&mut self) {
+        pf_debug!("datagram_receiver task spawned");
+
+        let local_addr = self.udp_socket.local_addr().unwrap();
+        pf_info!("receiving datagrams on '{}'", local_addr);
+
+        loop {
+            match self.udp_socket.recv_from(&mut self.recv_buf).await {
+                Ok((len, addr)) => match self.decode(&self.recv_buf[..len]) {
+                    // datagrams are lossy anyway, so a simulated delay is
+                    // not applied to them, only partitions
+                    Some((id, _)) if netfault::peer_blocked(self.me, id) => {}
+                    Some((id, msg)) => {
+                        if let Err(e) =
+                            self.tx_recv.send((id, PeerMessage::Msg { msg }))
+                        {
+                            pf_error!("error sending to tx_recv: {}", e);
+                            break;
+                        }
+                    }
+                    None => {
+                        pf_warn!("dropped invalid datagram <- '{}'", addr);
+                    }
+                },
+                Err(e) => {
+                    // e.g., ICMP port unreachable reported on some platforms
+                    pf_debug!("error receiving datagram: {}", e);
+                }
+            }
+        }
+
+        // pf_debug!("datagram_receiver task exited");
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -864,6 +1754,33 @@ mod tests {
     #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, GetSize)]
     struct TestMsg(String);
 
+    #[test]
+    fn hello_negotiate() {
+        let mine = PeerHello::mine(0, SmrProtocol::MultiPaxos, None);
+        let peer = PeerHello::mine(1, SmrProtocol::MultiPaxos, None);
+        assert_eq!(
+            mine.negotiate(&peer),
+            Ok(SmrProtocol::MultiPaxos.wire_version())
+        );
+        let peer = PeerHello::mine(1, SmrProtocol::Raft, None);
+        assert!(mine.negotiate(&peer).is_err());
+        let peer = PeerHello {
+            envelope_ver: ENVELOPE_VERSION + 1,
+            ..PeerHello::mine(1, SmrProtocol::MultiPaxos, None)
+        };
+        assert!(mine.negotiate(&peer).is_err());
+        let peer = PeerHello {
+            proto_ver: SmrProtocol::MultiPaxos.min_wire_version() - 1,
+            ..PeerHello::mine(1, SmrProtocol::MultiPaxos, None)
+        };
+        assert!(mine.negotiate(&peer).is_err());
+        let peer = PeerHello {
+            codec: Codec::Postcard as u8,
+            ..PeerHello::mine(1, SmrProtocol::MultiPaxos, None)
+        };
+        assert!(mine.negotiate(&peer).is_err());
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 3)]
     async fn api_send_recv() -> Result<(), SummersetError> {
         let barrier = Arc::new(Barrier::new(3));
@@ -875,7 +1792,9 @@ mod tests {
             let mut hub: TransportHub<TestMsg> = TransportHub::new_and_setup(
                 1,
                 3,
+                SmrProtocol::MultiPaxos,
                 "127.0.0.1:30011".parse()?,
+                false,
                 HashMap::from([(0, tx_lease)]),
             )
             .await?;
@@ -914,7 +1833,9 @@ mod tests {
             let mut hub: TransportHub<TestMsg> = TransportHub::new_and_setup(
                 2,
                 3,
+                SmrProtocol::MultiPaxos,
                 "127.0.0.1:30012".parse()?,
+                false,
                 HashMap::new(),
             )
             .await?;
@@ -935,7 +1856,9 @@ mod tests {
         let mut hub: TransportHub<TestMsg> = TransportHub::new_and_setup(
             0,
             3,
+            SmrProtocol::MultiPaxos,
             "127.0.0.1:30010".parse()?,
+            false,
             HashMap::new(),
         )
         .await?;
@@ -980,7 +1903,9 @@ mod tests {
             let mut hub: TransportHub<TestMsg> = TransportHub::new_and_setup(
                 1,
                 3,
+                SmrProtocol::MultiPaxos,
                 "127.0.0.1:30111".parse()?,
+                false,
                 HashMap::new(),
             )
             .await?;
@@ -996,7 +1921,9 @@ mod tests {
             let mut hub: TransportHub<TestMsg> = TransportHub::new_and_setup(
                 2,
                 3,
+                SmrProtocol::MultiPaxos,
                 "127.0.0.1:30112".parse()?,
+                false,
                 HashMap::new(),
             )
             .await?;
@@ -1009,7 +1936,9 @@ mod tests {
         let mut hub: TransportHub<TestMsg> = TransportHub::new_and_setup(
             0,
             3,
+            SmrProtocol::MultiPaxos,
             "127.0.0.1:30110".parse()?,
+            false,
             HashMap::new(),
         )
         .await?;
@@ -1027,4 +1956,186 @@ mod tests {
         assert!(hub.current_peers()?.get(2)?);
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn api_send_unreliable() -> Result<(), SummersetError> {
+        let barrier = Arc::new(Barrier::new(2));
+        let barrier1 = barrier.clone();
+        tokio::spawn(async move {
+            // replica 1, with UDP side-channel
+            let mut hub: TransportHub<TestMsg> = TransportHub::new_and_setup(
+                1,
+                3,
+                SmrProtocol::MultiPaxos,
+                "127.0.0.1:30511".parse()?,
+                true,
+                HashMap::new(),
+            )
+            .await?;
+            barrier1.wait().await;
+            // recv a datagram from 0
+            let (id, msg) = hub.recv_msg().await?;
+            assert_eq!(id, 0);
+            assert_eq!(msg, TestMsg("ping".into()));
+            // send a too large message, falling back to TCP
+            hub.send_msg_unreliable(TestMsg("x".repeat(4096)), 0)?;
+            Ok::<(), SummersetError>(())
+        });
+        // replica 0, with UDP side-channel
+        let mut hub: TransportHub<TestMsg> = TransportHub::new_and_setup(
+            0,
+            3,
+            SmrProtocol::MultiPaxos,
+            "127.0.0.1:30510".parse()?,
+            true,
+            HashMap::new(),
+        )
+        .await?;
+        barrier.wait().await;
+        hub.connect_to_peer(1, "127.0.0.1:30511".parse()?).await?;
+        // send a datagram to 1
+        hub.bcast_msg_unreliable(TestMsg("ping".into()), None)?;
+        // recv the large message from 1
+        let (id, msg) = hub.recv_msg().await?;
+        assert_eq!(id, 1);
+        assert_eq!(msg, TestMsg("x".repeat(4096)));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn api_lease_batch() -> Result<(), SummersetError> {
+        let barrier = Arc::new(Barrier::new(2));
+        let barrier1 = barrier.clone();
+        tokio::spawn(async move {
+            // replica 1, with two lease groups
+            let (tx_lease0, mut rx_lease0) = mpsc::unbounded_channel();
+            let (tx_lease1, mut rx_lease1) = mpsc::unbounded_channel();
+            let mut hub: TransportHub<TestMsg> = TransportHub::new_and_setup(
+                1,
+                2,
+                SmrProtocol::MultiPaxos,
+                "127.0.0.1:30541".parse()?,
+                false,
+                HashMap::from([(0, tx_lease0), (1, tx_lease1)]),
+            )
+            .await?;
+            barrier1.wait().await;
+            // recv the batched lease messages from 0
+            let (num, msg) = rx_lease0.recv().await.unwrap();
+            assert_eq!(num, 3);
+            assert_eq!(
+                msg,
+                LeaseNotice::RecvLeaseMsg {
+                    peer: 0,
+                    msg: LeaseMsg::Promise
+                }
+            );
+            let (num, msg) = rx_lease1.recv().await.unwrap();
+            assert_eq!(num, 5);
+            assert_eq!(
+                msg,
+                LeaseNotice::RecvLeaseMsg {
+                    peer: 0,
+                    msg: LeaseMsg::Promise
+                }
+            );
+            hub.send_msg(TestMsg("done".into()), 0)?;
+            Ok::<(), SummersetError>(())
+        });
+        // replica 0
+        let mut hub: TransportHub<TestMsg> = TransportHub::new_and_setup(
+            0,
+            2,
+            SmrProtocol::MultiPaxos,
+            "127.0.0.1:30540".parse()?,
+            false,
+            HashMap::new(),
+        )
+        .await?;
+        barrier.wait().await;
+        hub.connect_to_peer(1, "127.0.0.1:30541".parse()?).await?;
+        // queue refreshes of two lease groups, then send them as one batch
+        hub.queue_lease_msg(0, 3, LeaseMsg::Promise, None)?;
+        hub.queue_lease_msg(1, 5, LeaseMsg::Promise, None)?;
+        assert_eq!(hub.lease_batches[&1].len(), 2);
+        hub.flush_lease_msgs()?;
+        assert!(hub.lease_batches.is_empty());
+        let (id, msg) = hub.recv_msg().await?;
+        assert_eq!(id, 1);
+        assert_eq!(msg, TestMsg("done".into()));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn api_send_lanes() -> Result<(), SummersetError> {
+        let barrier = Arc::new(Barrier::new(2));
+        let barrier1 = barrier.clone();
+        let big = "x".repeat(3 * BULK_PIECE_BYTES + 7);
+        let big1 = big.clone();
+        tokio::spawn(async move {
+            // replica 1
+            let mut hub: TransportHub<TestMsg> = TransportHub::new_and_setup(
+                1,
+                2,
+                SmrProtocol::MultiPaxos,
+                "127.0.0.1:30521".parse()?,
+                false,
+                HashMap::new(),
+            )
+            .await?;
+            barrier1.wait().await;
+            // recv both messages from 0, in whichever order
+            let mut msgs = [hub.recv_msg().await?, hub.recv_msg().await?];
+            msgs.sort_by_key(|(_, m)| m.0.len());
+            assert_eq!(msgs[0], (0, TestMsg("vote".into())));
+            assert_eq!(msgs[1], (0, TestMsg(big1)));
+            // reply on the control lane
+            hub.send_ctrl_msg(TestMsg("ack".into()), 0)?;
+            Ok::<(), SummersetError>(())
+        });
+        // replica 0
+        let mut hub: TransportHub<TestMsg> = TransportHub::new_and_setup(
+            0,
+            2,
+            SmrProtocol::MultiPaxos,
+            "127.0.0.1:30520".parse()?,
+            false,
+            HashMap::new(),
+        )
+        .await?;
+        barrier.wait().await;
+        hub.connect_to_peer(1, "127.0.0.1:30521".parse()?).await?;
+        // send a large message on the bulk lane, then one on the control lane
+        hub.send_msg(TestMsg(big), 1)?;
+        hub.bcast_ctrl_msg(TestMsg("vote".into()), None)?;
+        let (id, msg) = hub.recv_msg().await?;
+        assert_eq!(id, 1);
+        assert_eq!(msg, TestMsg("ack".into()));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn api_peer_congested() -> Result<(), SummersetError> {
+        let mut hub: TransportHub<TestMsg> = TransportHub::new_and_setup(
+            0,
+            2,
+            SmrProtocol::MultiPaxos,
+            "127.0.0.1:30530".parse()?,
+            false,
+            HashMap::new(),
+        )
+        .await?;
+        assert!(hub.set_queue_watermarks(4, 4).is_err());
+        hub.set_queue_watermarks(4, 2)?;
+        assert!(!hub.peer_congested(1)?);
+        hub.queue_depths[1].store(4, Ordering::Relaxed);
+        assert!(hub.peer_congested(1)?);
+        hub.queue_depths[1].store(3, Ordering::Relaxed);
+        assert!(hub.peer_congested(1)?);
+        hub.queue_depths[1].store(2, Ordering::Relaxed);
+        assert!(!hub.peer_congested(1)?);
+        assert_eq!(hub.peer_queue_depth(1), 2);
+        assert!(hub.peer_congested(2).is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,327 @@
+//! Summerset server WebSocket bridge module implementation.
+//!
+//! Browser-based clients (JS or wasm32 builds) cannot open raw TCP streams,
+//! so the bridge accepts WebSocket connections and relays each of them as a
+//! regular client connection to the replica's own external API port. Frames
+//! are JSON-encoded `ApiRequest`s / `ApiReply`s in text messages, which any
+//! wasm32 target can produce with plain `serde_json`; `ClientWsStub` is the
+//! client end of this framing only, not a wasm32 client endpoint.
+
+use std::net::SocketAddr;
+
+use crate::client::{ClientApiStub, ClientId};
+use crate::server::{ApiReply, ApiRequest};
+use crate::utils::{tcp_bind_with_retry, SummersetError};
+
+use futures::{SinkExt, StreamExt};
+
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+/// Client IDs assigned to bridged connections have the highest bit set, so
+/// that they never collide with IDs handed out by the manager.
+const WS_CLIENT_ID_FLAG: ClientId = 1 << 63;
+
+/// Decodes a WebSocket text frame payload into an API request.
+fn ws_decode_req(text: &str) -> Result<ApiRequest, SummersetError> {
+    Ok(serde_json::from_str(text)?)
+}
+
+/// Encodes an API reply into a WebSocket text frame payload.
+fn ws_encode_reply(reply: &ApiReply) -> Result<String, SummersetError> {
+    Ok(serde_json::to_string(reply)?)
+}
+
+/// The WebSocket bridge module.
+pub struct WsBridge {
+    /// Join handle of the bridge acceptor task.
+    _acceptor_handle: JoinHandle<()>,
+}
+
+impl WsBridge {
+    /// Creates a new WebSocket bridge module listening on `ws_addr` and
+    /// relaying to the external API at `api_addr`. Spawns the bridge
+    /// acceptor task.
+    pub async fn new_and_setup(
+        ws_addr: SocketAddr,
+        api_addr: SocketAddr,
+    ) -> Result<Self, SummersetError> {
+        let ws_listener = tcp_bind_with_retry(ws_addr, 15).await?;
+        let mut acceptor = WsBridgeAcceptorTask::new(ws_listener, api_addr);
+        let acceptor_handle = tokio::spawn(async move { acceptor.run().await });
+
+        Ok(WsBridge {
+            _acceptor_handle: acceptor_handle,
+        })
+    }
+}
+
+/// WsBridge acceptor task.
+struct WsBridgeAcceptorTask {
+    ws_listener: TcpListener,
+    api_addr: SocketAddr,
+
+    id_nonce: ClientId,
+    next_id: ClientId,
+}
+
+impl WsBridgeAcceptorTask {
+    /// Creates the bridge acceptor task.
+    fn new(ws_listener: TcpListener, api_addr: SocketAddr) -> Self {
+        // random nonce in the middle bits keeps IDs from different replicas'
+        // bridges apart
+        let id_nonce = (rand::random::<u32>() as ClientId & 0x7fff_ffff) << 32;
+
+        WsBridgeAcceptorTask {
+            ws_listener,
+            api_addr,
+            id_nonce,
+            next_id: 0,
+        }
+    }
+
+    /// Starts the bridge acceptor task loop.
+    async fn run(&mut self) {
+        pf_debug!("ws_acceptor task spawned");
+
+        let local_addr = self.ws_listener.local_addr().unwrap();
+        pf_info!("accepting WebSocket clients on '{}'", local_addr);
+
+        loop {
+            match self.ws_listener.accept().await {
+                Ok((stream, addr)) => {
+                    let id = WS_CLIENT_ID_FLAG | self.id_nonce | self.next_id;
+                    self.next_id = (self.next_id + 1) & 0xffff_ffff;
+
+                    let api_addr = self.api_addr;
+                    tokio::spawn(async move {
+                        match WsBridgeRelayTask::new(id, addr, stream, api_addr)
+                            .await
+                        {
+                            Ok(mut relay) => relay.run().await,
+                            Err(e) => pf_warn!(
+                                "error setting up ws relay for '{}': {}",
+                                addr,
+                                e
+                            ),
+                        }
+                    });
+                }
+                Err(e) => {
+                    pf_warn!("error accepting WebSocket connection: {}", e);
+                }
+            }
+        }
+
+        // pf_debug!("ws_acceptor task exited");
+    }
+}
+
+/// WsBridge per-connection relay task.
+struct WsBridgeRelayTask {
+    id: ClientId,
+    addr: SocketAddr,
+
+    ws: WebSocketStream<TcpStream>,
+    api_stub: ClientApiStub,
+
+    leaving: bool,
+}
+
+impl WsBridgeRelayTask {
+    /// Completes the WebSocket handshake and connects to the external API
+    /// on behalf of the browser client.
+    async fn new(
+        id: ClientId,
+        addr: SocketAddr,
+        stream: TcpStream,
+        api_addr: SocketAddr,
+    ) -> Result<Self, SummersetError> {
+        let ws = tokio_tungstenite::accept_async(stream).await?;
+        let api_stub = ClientApiStub::new_by_connect(id, api_addr).await?;
+
+        Ok(WsBridgeRelayTask {
+            id,
+            addr,
+            ws,
+            api_stub,
+            leaving: false,
+        })
+    }
+
+    /// Forwards a request to the external API, retrying immediately on
+    /// `WouldBlock` failure.
+    fn forward_req(&mut self, req: &ApiRequest) -> Result<(), SummersetError> {
+        let mut success = self.api_stub.send_req(Some(req))?;
+        while !success {
+            success = self.api_stub.send_req(None)?;
+        }
+        Ok(())
+    }
+
+    /// Starts a per-connection relay task loop.
+    async fn run(&mut self) {
+        pf_debug!("ws_relay task for {} '{}' spawned", self.id, self.addr);
+
+        loop {
+            tokio::select! {
+                // frame from the browser side
+                msg = self.ws.next(), if !self.leaving => {
+                    let req = match msg {
+                        Some(Ok(Message::Text(text))) => {
+                            match ws_decode_req(&text) {
+                                Ok(req) => req,
+                                Err(e) => {
+                                    pf_warn!("invalid ws frame <- {}: {}", self.id, e);
+                                    continue;
+                                }
+                            }
+                        }
+                        Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                            // browser went away without a `Leave`; leave on
+                            // its behalf so the replica reclaims the client
+                            ApiRequest::Leave
+                        }
+                        Some(Ok(_)) => continue, // ping/pong/binary ignored
+                    };
+
+                    self.leaving = matches!(req, ApiRequest::Leave);
+                    if let Err(e) = self.forward_req(&req) {
+                        pf_error!("error forwarding req <- {}: {}", self.id, e);
+                        break;
+                    }
+                },
+
+                // reply from the replica side
+                reply = self.api_stub.recv_reply() => {
+                    match reply {
+                        Ok(reply) => {
+                            let leaving = matches!(reply, ApiReply::Leave);
+                            let sent = match ws_encode_reply(&reply) {
+                                Ok(text) => self
+                                    .ws
+                                    .send(Message::Text(text))
+                                    .await
+                                    .map_err(SummersetError::from),
+                                Err(e) => Err(e),
+                            };
+                            if leaving {
+                                let _ = self.ws.close(None).await;
+                                break;
+                            }
+                            if let Err(_e) = sent {
+                                // pf_error!("error relaying reply -> {}: {}", id, e);
+                            }
+                        }
+                        Err(_e) => {
+                            // pf_error!("error receiving reply for {}: {}", id, e);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        pf_debug!("ws_relay task for {} '{}' exited", self.id, self.addr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientWsStub;
+    use crate::server::{Command, CommandResult, ExternalApi};
+    use std::sync::Arc;
+    use tokio::sync::Barrier;
+    use tokio::time::Duration;
+
+    #[test]
+    fn ws_frame_format() -> Result<(), SummersetError> {
+        let req =
+            ws_decode_req(r#"{"Req":{"id":7,"cmd":{"Get":{"key":"k"}}}}"#)?;
+        assert_eq!(
+            req,
+            ApiRequest::Req {
+                id: 7,
+                cmd: Command::Get { key: "k".into() },
+                checksum: None,
+            }
+        );
+        assert!(ws_decode_req(r#"{"Req":{"id":7}}"#).is_err());
+        let text = ws_encode_reply(&ApiReply::normal(
+            7,
+            Some(CommandResult::Get { value: None }),
+        ))?;
+        assert!(text.starts_with(r#"{"Reply":{"id":7,"#));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn ws_relay_req_reply() -> Result<(), SummersetError> {
+        let barrier = Arc::new(Barrier::new(2));
+        let barrier2 = barrier.clone();
+        tokio::spawn(async move {
+            // server-side
+            let mut api = ExternalApi::new_and_setup(
+                0,
+                "127.0.0.1:30200".parse()?,
+                Duration::from_millis(1),
+                0,
+                0,
+                Duration::ZERO,
+            )
+            .await?;
+            let _bridge = WsBridge::new_and_setup(
+                "127.0.0.1:30201".parse()?,
+                "127.0.0.1:30200".parse()?,
+            )
+            .await?;
+            barrier2.wait().await;
+            // recv request from browser client
+            let mut reqs = vec![];
+            while reqs.is_empty() {
+                reqs.append(&mut api.get_req_batch().await?);
+            }
+            let (client, req) = reqs.remove(0);
+            assert!(client & WS_CLIENT_ID_FLAG != 0);
+            assert_eq!(
+                req,
+                ApiRequest::Req {
+                    id: 0,
+                    cmd: Command::Get { key: "Jose".into() },
+                    checksum: None,
+                }
+            );
+            api.send_reply(
+                ApiReply::normal(0, Some(CommandResult::Get { value: None })),
+                client,
+            )?;
+            // hold on to the bridge until client finishes
+            barrier2.wait().await;
+            Ok::<(), SummersetError>(())
+        });
+        // browser-side
+        barrier.wait().await;
+        let mut stub = ClientWsStub::new();
+        let (mut ws, _) =
+            tokio_tungstenite::connect_async("ws://127.0.0.1:30201").await?;
+        let (id, text) =
+            stub.encode_cmd(Command::Get { key: "Jose".into() })?;
+        assert_eq!(id, 0);
+        ws.send(Message::Text(text)).await?;
+        let reply = loop {
+            if let Some(Message::Text(text)) = ws.next().await.transpose()? {
+                break ClientWsStub::decode_reply(&text)?;
+            }
+        };
+        assert_eq!(
+            reply,
+            ApiReply::normal(0, Some(CommandResult::Get { value: None }))
+        );
+        barrier.wait().await;
+        Ok(())
+    }
+}
@@ -12,7 +12,7 @@ use get_size::GetSize;
 
 use serde::{Deserialize, Serialize};
 
-/// Compact bitmap for u8 ID -> bool mapping.
+/// Compact bitmap for u16 ID -> bool mapping.
 #[derive(Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Bitmap(FixedBitSet);
 
@@ -27,7 +27,7 @@ impl GetSize for Bitmap {
 impl Bitmap {
     /// Creates a new bitmap of given size. If `ones` is true, all slots are
     /// marked true initially; otherwise, all slots are initially false.
-    pub fn new(size: u8, ones: bool) -> Self {
+    pub fn new(size: u16, ones: bool) -> Self {
         if size == 0 {
             panic!("invalid bitmap size {}", size);
         }
@@ -42,7 +42,7 @@ impl Bitmap {
 
     /// Sets bit at index to given flag.
     #[inline]
-    pub fn set(&mut self, idx: u8, flag: bool) -> Result<(), SummersetError> {
+    pub fn set(&mut self, idx: u16, flag: bool) -> Result<(), SummersetError> {
         if idx as usize >= self.0.len() {
             return Err(SummersetError::msg(format!(
                 "index {} out of bound",
@@ -55,7 +55,7 @@ impl Bitmap {
 
     /// Gets the bit flag at index.
     #[inline]
-    pub fn get(&self, idx: u8) -> Result<bool, SummersetError> {
+    pub fn get(&self, idx: u16) -> Result<bool, SummersetError> {
         if idx as usize >= self.0.len() {
             return Err(SummersetError::msg(format!(
                 "index {} out of bound",
@@ -67,14 +67,14 @@ impl Bitmap {
 
     /// Returns the size of the bitmap.
     #[inline]
-    pub fn size(&self) -> u8 {
-        self.0.len() as u8
+    pub fn size(&self) -> u16 {
+        self.0.len() as u16
     }
 
     /// Returns the number of trues in the bitmap.
     #[inline]
-    pub fn count(&self) -> u8 {
-        self.0.count_ones(..) as u8
+    pub fn count(&self) -> u16 {
+        self.0.count_ones(..) as u16
     }
 
     /// Flips all flags in the bitmap.
@@ -112,8 +112,8 @@ impl Bitmap {
 }
 
 // Convert <- (size, range of contiguous indexes where the flag is true).
-impl From<(u8, Range<u8>)> for Bitmap {
-    fn from(tup: (u8, Range<u8>)) -> Self {
+impl From<(u16, Range<u16>)> for Bitmap {
+    fn from(tup: (u16, Range<u16>)) -> Self {
         let (size, ones) = tup;
         let mut bitmap = Self::new(size, false);
         for idx in ones {
@@ -124,8 +124,8 @@ impl From<(u8, Range<u8>)> for Bitmap {
 }
 
 // Convert <- (size, vec of indexes where the flag is true).
-impl From<(u8, Vec<u8>)> for Bitmap {
-    fn from(tup: (u8, Vec<u8>)) -> Self {
+impl From<(u16, Vec<u16>)> for Bitmap {
+    fn from(tup: (u16, Vec<u16>)) -> Self {
         let (size, ones) = tup;
         let mut bitmap = Self::new(size, false);
         for idx in ones {
@@ -136,8 +136,8 @@ impl From<(u8, Vec<u8>)> for Bitmap {
 }
 
 // Convert <- (size, vec of indexes where the flag is true).
-impl From<(u8, &Vec<u8>)> for Bitmap {
-    fn from(tup: (u8, &Vec<u8>)) -> Self {
+impl From<(u16, &Vec<u16>)> for Bitmap {
+    fn from(tup: (u16, &Vec<u16>)) -> Self {
         let (size, ones) = tup;
         let mut bitmap = Self::new(size, false);
         for &idx in ones {
@@ -148,8 +148,8 @@ impl From<(u8, &Vec<u8>)> for Bitmap {
 }
 
 // Convert <- (size, set of indexes where the flag is true).
-impl From<(u8, HashSet<u8>)> for Bitmap {
-    fn from(tup: (u8, HashSet<u8>)) -> Self {
+impl From<(u16, HashSet<u16>)> for Bitmap {
+    fn from(tup: (u16, HashSet<u16>)) -> Self {
         let (size, ones) = tup;
         let mut bitmap = Self::new(size, false);
         for idx in ones {
@@ -160,8 +160,8 @@ impl From<(u8, HashSet<u8>)> for Bitmap {
 }
 
 // Convert <- (size, set of indexes where the flag is true).
-impl From<(u8, &HashSet<u8>)> for Bitmap {
-    fn from(tup: (u8, &HashSet<u8>)) -> Self {
+impl From<(u16, &HashSet<u16>)> for Bitmap {
+    fn from(tup: (u16, &HashSet<u16>)) -> Self {
         let (size, ones) = tup;
         let mut bitmap = Self::new(size, false);
         for &idx in ones {
@@ -172,7 +172,7 @@ impl From<(u8, &HashSet<u8>)> for Bitmap {
 }
 
 // Convert -> vec of indexes where the flag is true.
-impl From<Bitmap> for Vec<u8> {
+impl From<Bitmap> for Vec<u16> {
     fn from(bitmap: Bitmap) -> Self {
         bitmap
             .iter()
@@ -182,7 +182,7 @@ impl From<Bitmap> for Vec<u8> {
 }
 
 // Convert -> vec of indexes where the flag is true.
-impl From<&Bitmap> for Vec<u8> {
+impl From<&Bitmap> for Vec<u16> {
     fn from(bitmap: &Bitmap) -> Self {
         bitmap
             .iter()
@@ -192,7 +192,7 @@ impl From<&Bitmap> for Vec<u8> {
 }
 
 // Convert -> set of indexes where the flag is true.
-impl From<Bitmap> for HashSet<u8> {
+impl From<Bitmap> for HashSet<u16> {
     fn from(bitmap: Bitmap) -> Self {
         bitmap
             .iter()
@@ -202,7 +202,7 @@ impl From<Bitmap> for HashSet<u8> {
 }
 
 // Convert -> set of indexes where the flag is true.
-impl From<&Bitmap> for HashSet<u8> {
+impl From<&Bitmap> for HashSet<u16> {
     fn from(bitmap: &Bitmap) -> Self {
         bitmap
             .iter()
@@ -219,10 +219,10 @@ pub struct BitmapIter<'m> {
 }
 
 impl Iterator for BitmapIter<'_> {
-    type Item = (u8, bool);
+    type Item = (u16, bool);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let id: u8 = self.idx as u8;
+        let id: u16 = self.idx as u16;
         if id < self.map.size() {
             self.idx += 1;
             Some((id, self.map.get(id).unwrap()))
@@ -288,13 +288,13 @@ mod tests {
         assert_eq!(Bitmap::from((5, &vec![1, 2, 3])), ref_map);
         assert_eq!(Bitmap::from((5, HashSet::from([1, 2, 3]))), ref_map);
         assert_eq!(Bitmap::from((5, &HashSet::from([1, 2, 3]))), ref_map);
-        assert_eq!(Vec::<u8>::from(ref_map.clone()), vec![1, 2, 3]);
-        assert_eq!(Vec::<u8>::from(&ref_map), vec![1, 2, 3]);
+        assert_eq!(Vec::<u16>::from(ref_map.clone()), vec![1, 2, 3]);
+        assert_eq!(Vec::<u16>::from(&ref_map), vec![1, 2, 3]);
         assert_eq!(
-            HashSet::<u8>::from(ref_map.clone()),
+            HashSet::<u16>::from(ref_map.clone()),
             HashSet::from([1, 2, 3])
         );
-        assert_eq!(HashSet::<u8>::from(&ref_map), HashSet::from([1, 2, 3]));
+        assert_eq!(HashSet::<u16>::from(&ref_map), HashSet::from([1, 2, 3]));
     }
 
     #[test]
@@ -345,6 +345,6 @@ mod tests {
         for (id, flag) in map.iter() {
             assert_eq!(ref_map[id as usize], flag);
         }
-        assert_eq!(Vec::<u8>::from(map), [0, 1, 3, 4]);
+        assert_eq!(Vec::<u16>::from(map), [0, 1, 3, 4]);
     }
 }
@@ -33,10 +33,13 @@ macro_rules! parsed_config {
             // if table is not empty at this time, some parsed keys are not
             // expected hence invalid
             if table.len() > 0 {
-                return Err(SummersetError::msg(format!(
-                    "invalid field name '{}' in config",
-                    table.keys().next().unwrap(),
-                )));
+                return Err(SummersetError::new(
+                    $crate::ErrorKind::Config,
+                    format!(
+                        "invalid field name '{}' in config",
+                        table.keys().next().unwrap(),
+                    ),
+                ));
             }
 
             Ok(config)
@@ -6,33 +6,141 @@ use std::io;
 use std::net;
 use std::num;
 use std::string;
+use std::sync::Arc;
 
-/// Customized error type for Summerset.
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub struct SummersetError(String);
+/// Kinds of Summerset errors, for branching on the cause of an error without
+/// matching its message.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Local file or socket I/O failure.
+    Io,
+
+    /// Failure of a network-level library (TLS, WebSocket, coordination
+    /// services).
+    Network,
+
+    /// Malformed message or entry that failed to (de)serialize or decode.
+    Codec,
+
+    /// Invalid configuration or command line argument.
+    Config,
+
+    /// Internal channel or task failure, usually because the other end has
+    /// gone away.
+    Channel,
+
+    /// Violation of the replication protocol's expectations, e.g., an
+    /// unexpected message or log result type.
+    Protocol,
+
+    /// Something did not happen in time.
+    Timeout,
+
+    /// A request or message was refused by the other side, e.g., for being
+    /// too large.
+    Rejected,
+
+    /// Durable data found damaged or inconsistent.
+    Corruption,
+
+    /// Anything else.
+    Other,
+}
+
+/// Customized error type for Summerset. Carries a kind, a message, and the
+/// source error it was converted from, if any.
+#[derive(Debug, Clone)]
+pub struct SummersetError {
+    /// Kind of the error.
+    kind: ErrorKind,
+
+    /// Message of the error, including any added context.
+    msg: String,
+
+    /// Source error this was converted from, if any.
+    source: Option<Arc<dyn error::Error + Send + Sync>>,
+}
 
 impl SummersetError {
+    /// Creates an error of kind `Other` with given message.
     pub fn msg(msg: impl ToString) -> Self {
-        SummersetError(msg.to_string())
+        Self::new(ErrorKind::Other, msg)
+    }
+
+    /// Creates an error of given kind and message.
+    pub fn new(kind: ErrorKind, msg: impl ToString) -> Self {
+        SummersetError {
+            kind,
+            msg: msg.to_string(),
+            source: None,
+        }
+    }
+
+    /// Kind of the error.
+    #[inline]
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the error reclassified as of given kind, keeping its message
+    /// and source.
+    pub fn with_kind(mut self, kind: ErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Returns the error with `context` prepended to its message, keeping
+    /// its kind and source.
+    pub fn context(mut self, context: impl fmt::Display) -> Self {
+        self.msg = format!("{}: {}", context, self.msg);
+        self
+    }
+
+    /// Converts a source error, keeping it for `Error::source()`.
+    fn from_source<E>(kind: ErrorKind, e: E) -> Self
+    where
+        E: error::Error + Send + Sync + 'static,
+    {
+        SummersetError {
+            kind,
+            msg: e.to_string(),
+            source: Some(Arc::new(e)),
+        }
     }
 }
 
+// errors are equal if they are of the same kind and say the same thing,
+// regardless of their sources
+impl PartialEq for SummersetError {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind && self.msg == other.msg
+    }
+}
+
+impl Eq for SummersetError {}
+
 impl fmt::Display for SummersetError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.0) // do not display literal quotes
+        write!(f, "{}", self.msg) // do not display literal quotes
     }
 }
 
-impl error::Error for SummersetError {}
+impl error::Error for SummersetError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|e| e as &(dyn error::Error + 'static))
+    }
+}
 
 // Helper macro for saving boiler-plate `impl From<X>`s for transparent
 // conversion from various common error types to `SummersetError`.
 macro_rules! impl_from_error {
-    ($error:ty) => {
+    ($error:ty => $kind:ident) => {
         impl From<$error> for SummersetError {
             fn from(e: $error) -> Self {
-                // just store the source error's string representation
-                SummersetError(e.to_string())
+                SummersetError::from_source(ErrorKind::$kind, e)
             }
         }
     };
@@ -41,49 +149,86 @@ macro_rules! impl_from_error {
 // Helper macro for saving boiler-plate `impl From<X<T>>`s for transparent
 // conversion from various common generic error types to `SummersetError`.
 macro_rules! impl_from_error_generic {
-    ($error:ty) => {
+    ($error:ty => $kind:ident) => {
         impl<T> From<$error> for SummersetError {
             fn from(e: $error) -> SummersetError {
-                SummersetError::msg(e.to_string())
+                // only the string representation is kept, as the payload
+                // type may not be thread-safe
+                SummersetError::new(ErrorKind::$kind, e.to_string())
             }
         }
     };
 }
 
-impl_from_error!(io::Error);
-impl_from_error!(string::FromUtf8Error);
-impl_from_error!(num::ParseIntError);
-impl_from_error!(num::ParseFloatError);
-impl_from_error!(net::AddrParseError);
-impl_from_error!(bincode::Error);
-impl_from_error!(toml::ser::Error);
-impl_from_error!(toml::de::Error);
-impl_from_error!(reed_solomon_erasure::Error);
-impl_from_error!(ctrlc::Error);
-impl_from_error!(linreg::Error);
-impl_from_error!(tokio::task::JoinError);
-impl_from_error!(tokio::sync::mpsc::error::TryRecvError);
-impl_from_error!(zookeeper_client::Error);
-impl_from_error!(etcd_client::Error);
-
-impl_from_error_generic!(tokio::sync::SetError<T>);
-impl_from_error_generic!(tokio::sync::watch::error::SendError<T>);
-impl_from_error_generic!(tokio::sync::mpsc::error::SendError<T>);
+impl From<io::Error> for SummersetError {
+    fn from(e: io::Error) -> Self {
+        let kind = match e.kind() {
+            io::ErrorKind::TimedOut => ErrorKind::Timeout,
+            _ => ErrorKind::Io,
+        };
+        SummersetError::from_source(kind, e)
+    }
+}
+
+impl_from_error!(string::FromUtf8Error => Codec);
+impl_from_error!(num::ParseIntError => Config);
+impl_from_error!(num::ParseFloatError => Config);
+impl_from_error!(net::AddrParseError => Config);
+impl_from_error!(bincode::Error => Codec);
+impl_from_error!(postcard::Error => Codec);
+impl_from_error!(serde_json::Error => Codec);
+impl_from_error!(toml::ser::Error => Config);
+impl_from_error!(toml::de::Error => Config);
+impl_from_error!(reed_solomon_erasure::Error => Codec);
+impl_from_error!(ctrlc::Error => Other);
+impl_from_error!(tokio::task::JoinError => Channel);
+impl_from_error!(tokio::sync::mpsc::error::TryRecvError => Channel);
+impl_from_error!(tokio_tungstenite::tungstenite::Error => Network);
+impl_from_error!(tokio_rustls::rustls::Error => Network);
+impl_from_error!(zookeeper_client::Error => Network);
+// TMPNOETCD
+
+// not a `std::error::Error` as the crate is `no_std`
+impl From<linreg::Error> for SummersetError {
+    fn from(e: linreg::Error) -> Self {
+        SummersetError::new(ErrorKind::Other, e.to_string())
+    }
+}
+
+impl_from_error_generic!(tokio::sync::SetError<T> => Channel);
+impl_from_error_generic!(tokio::sync::watch::error::SendError<T> => Channel);
+impl_from_error_generic!(tokio::sync::mpsc::error::SendError<T> => Channel);
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::error::Error;
 
     #[test]
     fn error_display() {
-        let e = SummersetError("what the heck?".into());
+        let e = SummersetError::msg("what the heck?");
         assert_eq!(format!("{}", e), String::from("what the heck?"));
+        let e = e.context("while testing");
+        assert_eq!(format!("{}", e), "while testing: what the heck?");
+        assert_eq!(e.kind(), ErrorKind::Other);
     }
 
     #[test]
     fn from_io_error() {
         let io_error = io::Error::new(io::ErrorKind::NotFound, "oh no!");
         let e = SummersetError::from(io_error);
-        assert!(e.0.contains("oh no!"));
+        assert!(e.msg.contains("oh no!"));
+        assert_eq!(e.kind(), ErrorKind::Io);
+        assert!(e.source().is_some());
+        let io_error = io::Error::new(io::ErrorKind::TimedOut, "too slow");
+        assert_eq!(SummersetError::from(io_error).kind(), ErrorKind::Timeout);
+    }
+
+    #[test]
+    fn error_equality() {
+        let e = SummersetError::new(ErrorKind::Config, "bad");
+        assert_eq!(e, SummersetError::new(ErrorKind::Config, "bad"));
+        assert_ne!(e, SummersetError::new(ErrorKind::Rejected, "bad"));
+        assert_ne!(e, SummersetError::msg("bad"));
     }
 }
@@ -52,7 +52,7 @@ where
 {
     /// Creates a new empty responders configuration.
     #[inline]
-    pub(crate) fn empty(population: u8) -> Self {
+    pub(crate) fn empty(population: u16) -> Self {
         RespondersConf {
             leader: None,
             responders: KeyRangeMap::new((
@@ -183,6 +183,90 @@ where
         }
     }
 
+    /// Returns the responders bitmap and index of the previously inserted
+    /// range that is exactly the given keys range, or an error if there is no
+    /// such range.
+    fn exact_range(
+        &self,
+        start: &String,
+        end: &String,
+    ) -> Result<(Bitmap, Option<Idx>), SummersetError> {
+        let (start_int, end_int) = (key_to_inty(start)?, key_to_inty(end)?);
+        let overlaps: Vec<_> =
+            self.responders.get_overlaps(start, end)?.collect();
+        match overlaps[..] {
+            [(ostart, oend, value)]
+                if *ostart == start_int && *oend == end_int =>
+            {
+                Ok(value.clone())
+            }
+            _ => logged_err!("{}-{} is not an existing range", start, end),
+        }
+    }
+
+    /// Splits an existing keys range into two at key `at`, where the part
+    /// from `at` to the end gets the given responders and loses the custom
+    /// index, if any. The responders must differ from the existing ones, as
+    /// adjacent ranges with equal values cannot be told apart.
+    pub(crate) fn split_range(
+        &mut self,
+        range: &(String, String),
+        at: &String,
+        responders: Bitmap,
+    ) -> Result<(), SummersetError> {
+        let (start, end) = range;
+        let (old_responders, _) = self.exact_range(start, end)?;
+        let at_int = key_to_inty(at)?;
+        if at_int <= key_to_inty(start)? || at_int > key_to_inty(end)? {
+            return logged_err!(
+                "split key '{}' not within {}-{} past its start",
+                at,
+                start,
+                end
+            );
+        }
+        if responders == old_responders {
+            return logged_err!(
+                "split of {}-{} at '{}' keeps the same responders",
+                start,
+                end,
+                at
+            );
+        }
+        self.responders.set(at, end, (responders, None))
+    }
+
+    /// Merges two adjacent existing keys ranges into one with the given
+    /// responders, or the left range's responders if `None`. Custom indexes
+    /// of both ranges are dropped.
+    pub(crate) fn merge_ranges(
+        &mut self,
+        left: &(String, String),
+        right: &(String, String),
+        responders: Option<Bitmap>,
+    ) -> Result<(), SummersetError> {
+        let (left_responders, left_idx) = self.exact_range(&left.0, &left.1)?;
+        let (_, right_idx) = self.exact_range(&right.0, &right.1)?;
+        if key_to_inty(&left.1)?.checked_add(1) != Some(key_to_inty(&right.0)?)
+        {
+            return logged_err!(
+                "ranges {}-{} and {}-{} are not adjacent",
+                left.0,
+                left.1,
+                right.0,
+                right.1
+            );
+        }
+        for idx in [left_idx, right_idx].into_iter().flatten() {
+            self.custom_map.remove(&idx);
+        }
+        self.responders.set(
+            &left.0,
+            &right.1,
+            (responders.unwrap_or(left_responders), None),
+        )
+    }
+
     /// Returns a clone of myself where a given node is unmarked from any
     /// special roles in the config. This clone is needed due to the current
     /// lack of support for mutable ref to values in `RangeMap`s.
@@ -287,18 +371,17 @@ where
 
 /// If a string key is of the format 'k<number>' it is allowed to be converted
 /// into an integer '<number>' to support certain operations, e.g., key ranges.
-type IntyKey = u128;
+pub(crate) type IntyKey = u128;
 
 /// Converts a string key to an integer key.
-fn key_to_inty(key: &str) -> Result<IntyKey, SummersetError> {
+pub(crate) fn key_to_inty(key: &str) -> Result<IntyKey, SummersetError> {
     Ok(key.trim_start_matches(['k', 'K']).parse::<IntyKey>()?)
 }
 
 /// Converts an integer key to a string key. Be warned that the converted
 /// string key will not contain leading zeros, so should not be used for being
 /// compared with original string keys.
-#[allow(dead_code)]
-fn inty_to_key(key: IntyKey) -> String {
+pub(crate) fn inty_to_key(key: IntyKey) -> String {
     format!("k{}", key)
 }
 
@@ -550,6 +633,41 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn conf_split_merge() -> Result<(), SummersetError> {
+        let mut conf = RespondersConf::<u32>::empty(5);
+        let range = ("k100".to_string(), "k199".to_string());
+        conf.set_responders(Some(&range), Bitmap::from((5, vec![0, 1])), None)?;
+        assert!(conf
+            .split_range(&range, &"k100".to_string(), Bitmap::new(5, false))
+            .is_err());
+        assert!(conf
+            .split_range(
+                &range,
+                &"k150".to_string(),
+                Bitmap::from((5, vec![0, 1]))
+            )
+            .is_err());
+        conf.split_range(
+            &range,
+            &"k150".to_string(),
+            Bitmap::from((5, vec![2, 3])),
+        )?;
+        assert!(conf.is_responder_by_key(&"k149".to_string(), 1));
+        assert!(conf.is_responder_by_key(&"k150".to_string(), 3));
+        let left = ("k100".to_string(), "k149".to_string());
+        let right = ("k150".to_string(), "k199".to_string());
+        assert!(conf.range_clean(&left.0, &left.1)?);
+        assert!(conf.range_clean(&right.0, &right.1)?);
+        assert!(conf.merge_ranges(&right, &left, None).is_err());
+        assert!(conf.merge_ranges(&left, &range, None).is_err());
+        conf.merge_ranges(&left, &right, None)?;
+        assert!(conf.range_clean(&range.0, &range.1)?);
+        assert!(conf.is_responder_by_key(&"k199".to_string(), 1));
+        assert!(!conf.is_responder_by_key(&"k199".to_string(), 3));
+        Ok(())
+    }
+
     #[test]
     fn conf_into_filtered() -> Result<(), SummersetError> {
         let mut conf = RespondersConf::<u32>::empty(5);
@@ -7,25 +7,43 @@ mod print;
 mod config;
 
 mod bitmap;
+mod checksum;
+mod codec;
 mod error;
+mod hlc;
 mod keyrange;
 mod linreg;
 mod qdisc;
+mod qgauge;
 mod rscoding;
 mod safetcp;
+mod seeding;
 mod stopwatch;
 mod timer;
 
 pub use bitmap::Bitmap;
-pub use error::SummersetError;
+pub use codec::{set_codec, Codec};
+pub use error::{ErrorKind, SummersetError};
+pub use hlc::{HlcStamp, HybridClock};
 pub use keyrange::{ConfNum, RespondersConf};
-pub use print::{logger_init, ME};
+pub use print::{
+    logger_init, logger_max_level, set_log_filters, LogPrefix, ME,
+};
 pub use rscoding::RSCodeword;
+pub use safetcp::set_max_msg_bytes;
+pub use seeding::set_rng_seed;
 pub use stopwatch::Stopwatch;
 pub use timer::Timer;
 
+pub(crate) use checksum::crc32;
+pub(crate) use codec::{codec, decode, encode, encoded_size, exchange_codec};
+pub(crate) use keyrange::{inty_to_key, key_to_inty, IntyKey};
 pub(crate) use linreg::{LinearRegressor, PerfModel};
+pub(crate) use print::{set_me, spawn_inherit_me, with_node_scope};
 pub(crate) use qdisc::QdiscInfo;
+pub(crate) use qgauge::QueueGauge;
 pub(crate) use safetcp::{
-    safe_tcp_read, safe_tcp_write, tcp_bind_with_retry, tcp_connect_with_retry,
+    max_msg_bytes, msg_within_limit, safe_tcp_read, safe_tcp_read_bounded,
+    safe_tcp_write, tcp_bind_with_retry, tcp_connect_with_retry,
 };
+pub(crate) use seeding::new_rng;
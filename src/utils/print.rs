@@ -1,12 +1,72 @@
 //! Helper macros for logging (console printing).
 
-use std::sync::OnceLock;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, OnceLock, RwLock};
 
-use env_logger::Env;
+use crate::utils::SummersetError;
+
+use env_logger::{Builder, Env, Logger};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use tokio::task::JoinHandle;
 
 /// Global variable holding the node identity string to used as logging prefix.
 pub static ME: OnceLock<String> = OnceLock::new();
 
+tokio::task_local! {
+    /// Node identity of the tasks of one node when several nodes share a
+    /// process; takes precedence over `ME`.
+    static NODE_ME: Arc<OnceLock<String>>;
+}
+
+/// Sets my node identity used as logging prefix, if not set yet: that of the
+/// node the current task works for if within `with_node_scope()`, or else
+/// the process-wide `ME`.
+pub(crate) fn set_me(me: String) {
+    match NODE_ME.try_with(Arc::clone) {
+        Ok(cell) => {
+            cell.get_or_init(|| me);
+        }
+        Err(_) => {
+            ME.get_or_init(|| me);
+        }
+    }
+}
+
+/// Runs a future as a node with its own logging identity, inherited by the
+/// tasks it spawns through `spawn_inherit_me()`.
+pub(crate) async fn with_node_scope<F: Future>(fut: F) -> F::Output {
+    NODE_ME.scope(Arc::new(OnceLock::new()), fut).await
+}
+
+/// Spawns a task that works for the same node as the current task.
+pub(crate) fn spawn_inherit_me<F>(fut: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    match NODE_ME.try_with(Arc::clone) {
+        Ok(cell) => tokio::spawn(NODE_ME.scope(cell, fut)),
+        Err(_) => tokio::spawn(fut),
+    }
+}
+
+/// Logging prefix of the node the current task works for. Only resolved
+/// when a message is actually printed.
+#[doc(hidden)]
+pub struct LogPrefix;
+
+impl fmt::Display for LogPrefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match NODE_ME.try_with(|cell| cell.get().map(|me| f.write_str(me))) {
+            Ok(Some(res)) => res,
+            _ => f.write_str(ME.get().map_or("-", |me| me.as_str())),
+        }
+    }
+}
+
 /// Log TRACE message with parenthesized prefix.
 ///
 /// Example:
@@ -18,7 +78,7 @@ macro_rules! pf_trace {
     ($($fmt_args:tt)*) => {
         log::trace!(
             "({}) {}",
-            $crate::ME.get().map_or("-", |me| me.as_str()),
+            $crate::LogPrefix,
             format!($($fmt_args)*)
         )
     };
@@ -35,7 +95,7 @@ macro_rules! pf_debug {
     ($($fmt_args:tt)*) => {
         log::debug!(
             "({}) {}",
-            $crate::ME.get().map_or("-", |me| me.as_str()),
+            $crate::LogPrefix,
             format!($($fmt_args)*)
         )
     };
@@ -52,7 +112,7 @@ macro_rules! pf_info {
     ($($fmt_args:tt)*) => {
         log::info!(
             "({}) {}",
-            $crate::ME.get().map_or("-", |me| me.as_str()),
+            $crate::LogPrefix,
             format!($($fmt_args)*)
         )
     };
@@ -69,7 +129,7 @@ macro_rules! pf_warn {
     ($($fmt_args:tt)*) => {
         log::warn!(
             "({}) {}",
-            $crate::ME.get().map_or("-", |me| me.as_str()),
+            $crate::LogPrefix,
             format!($($fmt_args)*)
         )
     };
@@ -86,31 +146,121 @@ macro_rules! pf_error {
     ($($fmt_args:tt)*) => {
         log::error!(
             "({}) {}",
-            $crate::ME.get().map_or("-", |me| me.as_str()),
+            $crate::LogPrefix,
             format!($($fmt_args)*)
         )
     };
 }
 
+/// Wrapper of env_logger whose filters can be switched at runtime.
+struct SwitchableLogger(RwLock<Logger>);
+
+impl Log for SwitchableLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.0.read().is_ok_and(|logger| logger.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        if let Ok(logger) = self.0.read() {
+            logger.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(logger) = self.0.read() {
+            logger.flush();
+        }
+    }
+}
+
+/// Global logger installed by `logger_init()`.
+static LOGGER: OnceLock<SwitchableLogger> = OnceLock::new();
+
+/// Builds an env_logger with desired formatting and the given builder's
+/// filters.
+fn build_logger(mut builder: Builder) -> Logger {
+    builder
+        .format_timestamp(None)
+        .format_module_path(false)
+        .format_target(false)
+        .build()
+}
+
 /// Initialize env_logger to desired configuration if haven't.
 pub fn logger_init() {
-    let _ =
-        env_logger::Builder::from_env(Env::default().default_filter_or("info"))
-            .format_timestamp(None)
-            .format_module_path(false)
-            .format_target(false)
-            .try_init();
+    let logger = LOGGER.get_or_init(|| {
+        SwitchableLogger(RwLock::new(build_logger(Builder::from_env(
+            Env::default().default_filter_or("info"),
+        ))))
+    });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(logger_max_level());
+    }
+}
+
+/// Maximum log level enabled by the current filters.
+pub fn logger_max_level() -> LevelFilter {
+    LOGGER
+        .get()
+        .and_then(|logger| logger.0.read().ok().map(|l| l.filter()))
+        .unwrap_or_else(log::max_level)
+}
+
+/// Switches the log filters at runtime, given in the same syntax as the
+/// `RUST_LOG` environment variable. For example, with filters
+/// `info,summerset::server::leaseman=trace`, only the lease manager module
+/// logs at trace level. Returns the new maximum level enabled.
+pub fn set_log_filters(filters: &str) -> Result<LevelFilter, SummersetError> {
+    // env_logger silently skips directives it cannot parse, so check the
+    // levels given here to surface typos
+    let directives = filters.split('/').next().unwrap_or_default();
+    for directive in directives.split(',').map(str::trim) {
+        if let Some((_, level)) = directive.split_once('=') {
+            if level.trim().parse::<LevelFilter>().is_err() {
+                return Err(SummersetError::msg(format!(
+                    "invalid log directive '{}'",
+                    directive
+                )));
+            }
+        }
+    }
+
+    let Some(logger) = LOGGER.get() else {
+        return Err(SummersetError::msg("logger not initialized"));
+    };
+    let mut builder = Builder::new();
+    builder.parse_filters(filters);
+    let new_logger = build_logger(builder);
+    let level = new_logger.filter();
+    *logger
+        .0
+        .write()
+        .map_err(|_| SummersetError::msg("logger lock poisoned"))? = new_logger;
+    log::set_max_level(level);
+    Ok(level)
 }
 
 /// Log an error string to logger and then return a `SummersetError`
-/// containing the string.
+/// containing the string, optionally of a given `ErrorKind` (`Other` if not
+/// given).
 ///
 /// Example:
 /// ```no_compile
 /// let e = logged_err!("got {} to print", msg);
+/// let e = logged_err!(Corruption; "entry at {} corrupted", offset);
 /// ```
 #[macro_export]
 macro_rules! logged_err {
+    ($kind:ident; $($fmt_args:tt)*) => {
+        {
+            pf_error!($($fmt_args)*);
+            Err($crate::SummersetError::new(
+                $crate::ErrorKind::$kind,
+                format!($($fmt_args)*),
+            ))
+        }
+    };
+
     ($($fmt_args:tt)*) => {
         {
             pf_error!($($fmt_args)*);
@@ -121,7 +271,8 @@ macro_rules! logged_err {
 
 #[cfg(test)]
 mod tests {
-    use crate::utils::SummersetError;
+    use super::*;
+    use crate::utils::ErrorKind;
 
     #[test]
     fn error_no_args() {
@@ -140,4 +291,44 @@ mod tests {
             Err::<(), SummersetError>(SummersetError::msg("got 777 to print"))
         );
     }
+
+    #[test]
+    fn error_with_kind() {
+        assert_eq!(
+            logged_err!(Corruption; "bad entry at {}", 8),
+            Err::<(), SummersetError>(SummersetError::new(
+                ErrorKind::Corruption,
+                "bad entry at 8"
+            ))
+        );
+    }
+
+    #[test]
+    fn switch_log_filters() -> Result<(), SummersetError> {
+        logger_init();
+        assert!(set_log_filters("info,summerset::server=bogus").is_err());
+        assert_eq!(
+            set_log_filters("warn,summerset::server::leaseman=trace")?,
+            LevelFilter::Trace
+        );
+        assert_eq!(logger_max_level(), LevelFilter::Trace);
+        assert_eq!(set_log_filters("info")?, LevelFilter::Info);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn node_scoped_prefix() -> Result<(), SummersetError> {
+        let node = |me: &'static str| {
+            with_node_scope(async move {
+                set_me(me.into());
+                let inherited =
+                    spawn_inherit_me(async { LogPrefix.to_string() }).await?;
+                Ok::<_, SummersetError>((LogPrefix.to_string(), inherited))
+            })
+        };
+        let (a, b) = tokio::join!(node("7"), node("8"));
+        assert_eq!(a?, ("7".into(), "7".into()));
+        assert_eq!(b?, ("8".into(), "8".into()));
+        Ok(())
+    }
 }
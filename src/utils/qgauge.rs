@@ -0,0 +1,75 @@
+//! Queue depth gauge useful for spotting which pipeline stage is the
+//! bottleneck.
+
+/// Gauge of the number of items currently queued at some pipeline stage,
+/// together with the high watermark seen since the last report.
+#[derive(Debug, Default)]
+pub(crate) struct QueueGauge {
+    /// Current queue depth.
+    depth: usize,
+
+    /// Highest queue depth seen since the last report.
+    peak: usize,
+}
+
+impl QueueGauge {
+    /// Creates a new gauge of an empty queue.
+    pub(crate) fn new() -> Self {
+        QueueGauge::default()
+    }
+
+    /// Records `n` items entering the queue.
+    #[inline]
+    pub(crate) fn enter(&mut self, n: usize) {
+        self.depth += n;
+        self.peak = self.peak.max(self.depth);
+    }
+
+    /// Records `n` items leaving the queue.
+    #[inline]
+    pub(crate) fn leave(&mut self, n: usize) {
+        self.depth = self.depth.saturating_sub(n);
+    }
+
+    /// Returns the current depth.
+    #[inline]
+    pub(crate) fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Sets the current depth to an externally sampled value, for queues
+    /// whose length is cheaper to sample than to track.
+    #[inline]
+    pub(crate) fn observe(&mut self, depth: usize) {
+        self.depth = depth;
+        self.peak = self.peak.max(depth);
+    }
+
+    /// Returns the current depth and the high watermark since the last
+    /// report, then resets the high watermark to the current depth.
+    pub(crate) fn report(&mut self) -> (usize, usize) {
+        let peak = self.peak;
+        self.peak = self.depth;
+        (self.depth, peak)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gauge_report() {
+        let mut gauge = QueueGauge::new();
+        gauge.enter(3);
+        gauge.leave(2);
+        gauge.enter(1);
+        assert_eq!(gauge.depth(), 2);
+        assert_eq!(gauge.report(), (2, 3));
+        assert_eq!(gauge.report(), (2, 2));
+        gauge.leave(5);
+        gauge.observe(4);
+        gauge.observe(1);
+        assert_eq!(gauge.report(), (1, 4));
+    }
+}
@@ -18,10 +18,10 @@ use reed_solomon_erasure::galois_8::ReedSolomon;
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct RSCodeword<T> {
     /// Number of data shards.
-    num_data_shards: u8,
+    num_data_shards: u16,
 
     /// Number of parity shards.
-    num_parity_shards: u8,
+    num_parity_shards: u16,
 
     /// Exact length of original data in bytes.
     data_len: usize,
@@ -66,8 +66,8 @@ where
         data_copy: Option<T>,
         data_bytes: Option<BytesMut>,
         data_len: usize,
-        num_data_shards: u8,
-        num_parity_shards: u8,
+        num_data_shards: u16,
+        num_parity_shards: u16,
     ) -> Result<Self, SummersetError> {
         if num_data_shards == 0 {
             return Err(SummersetError::msg("num_data_shards is zero"));
@@ -122,8 +122,8 @@ where
     /// Creates a new RSCodeword from original data.
     pub fn from_data(
         data: T,
-        num_data_shards: u8,
-        num_parity_shards: u8,
+        num_data_shards: u16,
+        num_parity_shards: u16,
     ) -> Result<Self, SummersetError> {
         // serialize original data into bytes
         let mut data_writer = BytesMut::new().writer();
@@ -140,8 +140,8 @@ where
 
     /// Creates a new RSCodeword from empty bytes.
     pub fn from_null(
-        num_data_shards: u8,
-        num_parity_shards: u8,
+        num_data_shards: u16,
+        num_parity_shards: u16,
     ) -> Result<Self, SummersetError> {
         Self::internal_new(None, None, 0, num_data_shards, num_parity_shards)
     }
@@ -249,48 +249,48 @@ where
 
     /// Gets number of data shards.
     #[inline]
-    pub fn num_data_shards(&self) -> u8 {
+    pub fn num_data_shards(&self) -> u16 {
         self.num_data_shards
     }
 
     /// Gets number of parity shards.
     #[allow(dead_code)]
     #[inline]
-    pub fn num_parity_shards(&self) -> u8 {
+    pub fn num_parity_shards(&self) -> u16 {
         self.num_parity_shards
     }
 
     /// Gets total number of shards.
     #[inline]
-    pub fn num_shards(&self) -> u8 {
-        self.shards.len() as u8
+    pub fn num_shards(&self) -> u16 {
+        self.shards.len() as u16
     }
 
     /// Gets number of currently available data shards.
     #[inline]
-    pub fn avail_data_shards(&self) -> u8 {
+    pub fn avail_data_shards(&self) -> u16 {
         self.shards
             .iter()
             .take(self.num_data_shards as usize)
             .filter(|s| s.is_some())
-            .count() as u8
+            .count() as u16
     }
 
     /// Gets number of currently available parity shards.
     #[allow(dead_code)]
     #[inline]
-    pub fn avail_parity_shards(&self) -> u8 {
+    pub fn avail_parity_shards(&self) -> u16 {
         self.shards
             .iter()
             .skip(self.num_data_shards as usize)
             .filter(|s| s.is_some())
-            .count() as u8
+            .count() as u16
     }
 
     /// Gets total number of currently available shards.
     #[inline]
-    pub fn avail_shards(&self) -> u8 {
-        self.shards.iter().filter(|s| s.is_some()).count() as u8
+    pub fn avail_shards(&self) -> u16 {
+        self.shards.iter().filter(|s| s.is_some()).count() as u16
     }
 
     /// Gets a bitmap of available shard indexes set true.
@@ -299,7 +299,7 @@ where
         let mut map = Bitmap::new(self.num_shards(), false);
         for (i, s) in self.shards.iter().enumerate() {
             if s.is_some() {
-                map.set(i as u8, true).unwrap();
+                map.set(i as u16, true).unwrap();
             }
         }
         map
@@ -510,20 +510,20 @@ struct ShardsReader<'a> {
     shards: &'a Vec<Option<BytesMut>>,
 
     /// Number of data shards in vec.
-    num_data_shards: u8,
+    num_data_shards: u16,
 
     /// Length in bytes of a shard.
     shard_len: usize,
 
     /// Composite cursor: (shard_idx, byte_idx).
-    cursor: (u8, usize),
+    cursor: (u16, usize),
 }
 
 impl<'a> ShardsReader<'a> {
     /// Creates a new temporary reader.
     fn new(
         shards: &'a Vec<Option<BytesMut>>,
-        num_data_shards: u8,
+        num_data_shards: u16,
         shard_len: usize,
     ) -> Result<Self, SummersetError> {
         for shard in shards.iter().take(num_data_shards as usize) {
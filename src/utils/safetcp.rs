@@ -1,11 +1,12 @@
 //! Safe TCP bind/connect/read/write helper functions.
 
-use std::io::ErrorKind;
+use std::io::ErrorKind as IoErrorKind;
 use std::marker::Unpin;
-use std::net::{Ipv4Addr, SocketAddr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use crate::utils::SummersetError;
+use crate::utils::{decode, encode, encoded_size, ErrorKind, SummersetError};
 
 use bytes::{Bytes, BytesMut};
 
@@ -15,9 +16,106 @@ use tokio::io::AsyncReadExt;
 use tokio::net::{TcpListener, TcpSocket, TcpStream};
 use tokio::time::{self, Duration};
 
+/// Maximum size in bytes of any object sent or received through the safe
+/// TCP helpers in this process; 0 means unlimited.
+static MAX_MSG_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// Flag bit set in the length header kept at the front of a read buffer
+/// while the rest of an oversized object is being discarded.
+const DISCARDING_FLAG: u64 = 1 << 63;
+
+/// Sets the maximum size in bytes of any object sent or received through
+/// TCP in this process; 0 means unlimited. Should be called once at startup.
+pub fn set_max_msg_bytes(max_bytes: usize) {
+    MAX_MSG_BYTES.store(max_bytes, Ordering::Relaxed);
+}
+
+/// Gets the maximum size in bytes of any object sent or received through
+/// TCP in this process; 0 means unlimited.
+#[inline]
+pub(crate) fn max_msg_bytes() -> usize {
+    MAX_MSG_BYTES.load(Ordering::Relaxed)
+}
+
+/// Returns whether the encoding of `obj` fits within the maximum message
+/// size, so that callers can substitute it with something smaller.
+pub(crate) fn msg_within_limit<T: Serialize>(
+    obj: &T,
+) -> Result<bool, SummersetError> {
+    let max_bytes = max_msg_bytes();
+    Ok(max_bytes == 0 || encoded_size(obj)? <= max_bytes)
+}
+
+/// Discards the oversized object whose length header is at the front of
+/// `read_buf` as its bytes arrive, without ever buffering the whole object.
+/// The number of bytes left to discard is kept in the header (flagged with
+/// `DISCARDING_FLAG`), so this is cancellation-safe in the same way as
+/// `safe_tcp_read_bounded()`.
+async fn discard_oversized<Conn>(
+    read_buf: &mut BytesMut,
+    conn_read: &mut Conn,
+) -> Result<(), SummersetError>
+where
+    Conn: AsyncReadExt + Unpin,
+{
+    let mut remaining = u64::from_be_bytes(read_buf[..8].try_into().unwrap())
+        & !DISCARDING_FLAG;
+    loop {
+        // drop bytes of the object already in buffer, and record what remains
+        let dropped = (read_buf.len() - 8).min(remaining as usize);
+        let buf_tail = Bytes::copy_from_slice(&read_buf[(8 + dropped)..]);
+        remaining -= dropped as u64;
+        read_buf.truncate(8);
+        if remaining == 0 {
+            read_buf.clear();
+            read_buf.extend_from_slice(&buf_tail);
+            return Ok(());
+        }
+        read_buf[..8]
+            .copy_from_slice(&(remaining | DISCARDING_FLAG).to_be_bytes());
+        read_buf.extend_from_slice(&buf_tail);
+
+        if conn_read.read_buf(read_buf).await? == 0 {
+            return Err(SummersetError::new(
+                ErrorKind::Io,
+                "connection closed while discarding oversized object",
+            ));
+        }
+    }
+}
+
 /// Receives an object of type `T` from TCP readable connection `conn_read`,
 /// using `read_buf` as buffer storage for partial reads. Returns:
 ///   - `Ok(obj)` if successful; upon returning, the read buffer is cleared
+///   - `Err(err)` if any unexpected error occurs, including an object being
+///     larger than the maximum message size (which gets discarded)
+pub(crate) async fn safe_tcp_read<T, Conn>(
+    read_buf: &mut BytesMut,
+    conn_read: &mut Conn,
+) -> Result<T, SummersetError>
+where
+    T: DeserializeOwned,
+    Conn: AsyncReadExt + Unpin,
+{
+    let max_bytes = max_msg_bytes();
+    safe_tcp_read_bounded(read_buf, conn_read, max_bytes)
+        .await?
+        .ok_or_else(|| {
+            SummersetError::new(
+                ErrorKind::Rejected,
+                format!("discarded object larger than max {} bytes", max_bytes),
+            )
+        })
+}
+
+/// Receives an object of type `T` from TCP readable connection `conn_read`,
+/// using `read_buf` as buffer storage for partial reads. Returns:
+///   - `Ok(Some(obj))` if successful; upon returning, the read buffer is
+///     cleared
+///   - `Ok(None)` if the object is larger than `max_bytes` (0 means
+///     unlimited, typically given `max_msg_bytes()`); it gets discarded
+///     without being buffered whole, and the connection stays usable for
+///     following objects
 ///   - `Err(err)` if any unexpected error occurs
 ///
 /// CANCELLATION SAFETY: we cannot use `read_u64()` and `read_exact()` here
@@ -27,10 +125,11 @@ use tokio::time::{self, Duration};
 /// such cancellation can only happen at `.await` points), bytes already read
 /// are stored in the read buffer and will continue to be appended by future
 /// invocations until successful returning.
-pub(crate) async fn safe_tcp_read<T, Conn>(
+pub(crate) async fn safe_tcp_read_bounded<T, Conn>(
     read_buf: &mut BytesMut,
     conn_read: &mut Conn,
-) -> Result<T, SummersetError>
+    max_bytes: usize,
+) -> Result<Option<T>, SummersetError>
 where
     T: DeserializeOwned,
     Conn: AsyncReadExt + Unpin,
@@ -41,10 +140,23 @@ where
     }
     while read_buf.len() < 8 {
         // obj_len not wholesomely read from socket before last cancellation
-        conn_read.read_buf(read_buf).await?;
+        if conn_read.read_buf(read_buf).await? == 0 {
+            return Err(SummersetError::new(
+                ErrorKind::Io,
+                "connection closed while reading object length",
+            ));
+        }
     }
     let obj_len = u64::from_be_bytes(read_buf[..8].try_into().unwrap());
 
+    // discard the obj if too large, before reserving any space for it
+    if obj_len & DISCARDING_FLAG != 0
+        || (max_bytes > 0 && obj_len > max_bytes as u64)
+    {
+        discard_oversized(read_buf, conn_read).await?;
+        return Ok(None);
+    }
+
     // then read the obj itself
     let obj_end = 8 + obj_len as usize;
     if read_buf.capacity() < obj_end {
@@ -52,9 +164,14 @@ where
         read_buf.reserve(obj_end - read_buf.capacity());
     }
     while read_buf.len() < obj_end {
-        conn_read.read_buf(read_buf).await?;
+        if conn_read.read_buf(read_buf).await? == 0 {
+            return Err(SummersetError::new(
+                ErrorKind::Io,
+                "connection closed while reading object",
+            ));
+        }
     }
-    let obj = bincode::deserialize(&read_buf[8..obj_end])?;
+    let obj = decode(&read_buf[8..obj_end])?;
 
     // if reached this point, no further cancellation to this call is
     // possible (because there are no more awaits ahead); discard bytes
@@ -68,7 +185,7 @@ where
         read_buf.clear();
     }
 
-    Ok(obj)
+    Ok(Some(obj))
 }
 
 /// Sends an object of type `T` to TCP writable connection `conn_write`, using
@@ -80,7 +197,9 @@ where
 ///                 indicate retrying (typically after doing a few reads on the
 ///                 same socket to free up some buffer space), until the
 ///                 function returns success
-///   - `Err(err)` if any unexpected error occurs
+///   - `Err(err)` if any unexpected error occurs, including a new object
+///     being larger than the maximum message size; in this case, nothing is
+///     written and the connection stays usable
 ///
 /// DEADLOCK AVOIDANCE: we avoid using `write_u64()` and `write_all()` here
 /// because, in the case of TCP buffers being full, if both ends of the
@@ -108,8 +227,18 @@ where
     } else if obj.is_some() {
         // sending a new object, fill write_buf
         debug_assert_eq!(*write_buf_cursor, 0);
-        let write_bytes = bincode::serialize(obj.unwrap())?;
+        let write_bytes = encode(obj.unwrap())?;
         let write_len = write_bytes.len();
+        let max_bytes = max_msg_bytes();
+        if max_bytes > 0 && write_len > max_bytes {
+            return Err(SummersetError::new(
+                ErrorKind::Rejected,
+                format!(
+                    "object of {} bytes larger than max {} bytes",
+                    write_len, max_bytes
+                ),
+            ));
+        }
         write_buf.extend_from_slice(&write_len.to_be_bytes());
         debug_assert_eq!(write_buf.len(), 8);
         write_buf.extend_from_slice(write_bytes.as_slice());
@@ -127,7 +256,7 @@ where
             Ok(n) => {
                 *write_buf_cursor += n;
             }
-            Err(ref err) if err.kind() == ErrorKind::WouldBlock => {
+            Err(ref err) if err.kind() == IoErrorKind::WouldBlock => {
                 return Ok(false);
             }
             Err(err) => return Err(err.into()),
@@ -141,19 +270,37 @@ where
     Ok(true)
 }
 
+/// Creates a `TcpSocket` of the same address family as `addr`. An IPv6
+/// listener bound to `[::]` is dual-stack (on hosts with the default
+/// `bindv6only=0`) and accepts IPv4 peers as v4-mapped addresses.
+fn new_tcp_socket(addr: &SocketAddr) -> Result<TcpSocket, SummersetError> {
+    let socket = if addr.is_ipv6() {
+        TcpSocket::new_v6()?
+    } else {
+        TcpSocket::new_v4()?
+    };
+    socket.set_linger(None)?;
+    socket.set_reuseaddr(true)?;
+    socket.set_reuseport(true)?;
+    socket.set_nodelay(true)?;
+    Ok(socket)
+}
+
 /// Wrapper over tokio `TcpListener::bind()` that provides a retrying logic.
+/// Binds to the unspecified address of `bind_addr`'s family.
 pub(crate) async fn tcp_bind_with_retry(
     bind_addr: SocketAddr,
     mut retries: u8,
 ) -> Result<TcpListener, SummersetError> {
     loop {
-        let socket = TcpSocket::new_v4()?;
-        socket.set_linger(None)?;
-        socket.set_reuseaddr(true)?;
-        socket.set_reuseport(true)?;
-        socket.set_nodelay(true)?;
+        let socket = new_tcp_socket(&bind_addr)?;
 
-        let bind_addr = (Ipv4Addr::UNSPECIFIED, bind_addr.port()).into();
+        let unspecified = if bind_addr.is_ipv6() {
+            IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+        } else {
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        };
+        let bind_addr = SocketAddr::new(unspecified, bind_addr.port());
         if let Err(e) = socket.bind(bind_addr) {
             eprintln!("Binding {} failed!", bind_addr);
             eprintln!("Output of `ss` command:");
@@ -180,11 +327,7 @@ pub(crate) async fn tcp_connect_with_retry(
     mut retries: u8,
 ) -> Result<TcpStream, SummersetError> {
     loop {
-        let socket = TcpSocket::new_v4()?;
-        socket.set_linger(None)?;
-        socket.set_reuseaddr(true)?;
-        socket.set_reuseport(true)?;
-        socket.set_nodelay(true)?;
+        let socket = new_tcp_socket(&conn_addr)?;
 
         match socket.connect(conn_addr).await {
             Ok(stream) => return Ok(stream),
@@ -216,4 +359,60 @@ mod tests {
         println!("{}", get_ss_cmd_output()?);
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn read_discard_oversized() -> Result<(), SummersetError> {
+        let max_bytes = 1024 * 1024;
+        let mut stream = vec![];
+        for obj in [
+            "x".repeat(2 * 1024 * 1024),
+            "Jose".into(),
+            "y".repeat(1024 * 1024),
+        ] {
+            let bytes = bincode::serialize(&obj)?;
+            stream.extend_from_slice(&bytes.len().to_be_bytes());
+            stream.extend_from_slice(&bytes);
+        }
+        let mut conn_read = stream.as_slice();
+        let mut read_buf = BytesMut::new();
+        for expected in [None, Some("Jose".into()), None] {
+            assert_eq!(
+                safe_tcp_read_bounded::<String, _>(
+                    &mut read_buf,
+                    &mut conn_read,
+                    max_bytes
+                )
+                .await?,
+                expected
+            );
+        }
+        assert!(read_buf.is_empty() && conn_read.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn bind_dual_stack() -> Result<(), SummersetError> {
+        // needs IPv6 with IPv4-mapped addresses allowed (bindv6only=0)
+        if std::fs::read_to_string("/proc/sys/net/ipv6/bindv6only")
+            .is_ok_and(|v| v.trim() == "1")
+        {
+            eprintln!("skipped: net.ipv6.bindv6only is set");
+            return Ok(());
+        }
+        let Ok(listener) = tcp_bind_with_retry("[::1]:0".parse()?, 0).await
+        else {
+            eprintln!("skipped: IPv6 bind failed");
+            return Ok(());
+        };
+        let port = listener.local_addr()?.port();
+        assert!(listener.local_addr()?.is_ipv6());
+        for ip in ["127.0.0.1", "::1"] {
+            let ip: IpAddr = ip.parse()?;
+            let conn_addr = SocketAddr::new(ip, port);
+            let _stream = tcp_connect_with_retry(conn_addr, 0).await?;
+            let (_, peer_addr) = listener.accept().await?;
+            assert_eq!(peer_addr.ip().to_canonical(), ip);
+        }
+        Ok(())
+    }
 }
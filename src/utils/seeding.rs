@@ -0,0 +1,55 @@
+//! Deterministic seeding of random number generators, for reproducible
+//! experiments.
+
+use std::sync::OnceLock;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// Seed that random number generators of server components derive from, if
+/// set.
+static RNG_SEED: OnceLock<u64> = OnceLock::new();
+
+/// Sets the seed that random number generators of server components (e.g.,
+/// for election timeouts) in this process derive from, so that runs with the
+/// same seed and the same fault schedule make the same random choices.
+/// Should be called once at startup.
+pub fn set_rng_seed(seed: u64) {
+    RNG_SEED.get_or_init(|| seed);
+}
+
+/// Creates a random number generator for the component named `stream` of
+/// replica `me`. Derived from the seed set through `set_rng_seed()`, `me`,
+/// and `stream` if a seed is set, so that every component of every replica
+/// draws its own deterministic sequence; otherwise seeded from OS entropy.
+pub(crate) fn new_rng(me: u16, stream: &str) -> StdRng {
+    match RNG_SEED.get() {
+        Some(&seed) => {
+            let mut mixed = seed ^ (u64::from(me) << 48);
+            for byte in stream.bytes() {
+                mixed = (mixed.rotate_left(8) ^ u64::from(byte))
+                    .wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            }
+            StdRng::seed_from_u64(mixed)
+        }
+        None => StdRng::from_entropy(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn seeded_streams() {
+        set_rng_seed(7);
+        let draw = |me, stream| {
+            let mut rng = new_rng(me, stream);
+            (0..4).map(|_| rng.gen::<u64>()).collect::<Vec<_>>()
+        };
+        assert_eq!(draw(1, "heartbeat"), draw(1, "heartbeat"));
+        assert_ne!(draw(1, "heartbeat"), draw(2, "heartbeat"));
+        assert_ne!(draw(1, "heartbeat"), draw(1, "gossip"));
+    }
+}
@@ -0,0 +1,328 @@
+//! Autotune client running short calibration workloads against the service
+//! and recommending protocol timing parameters fit to the measured network.
+
+use std::fs;
+use std::path::Path;
+
+use crate::clients::ClientBench;
+use crate::drivers::{DriverClosedLoop, DriverReply};
+
+use serde::Deserialize;
+
+use tokio::time::Duration;
+
+use summerset::{
+    logged_err, parsed_config, pf_debug, pf_info, pf_warn, GenericEndpoint,
+    ReplyError, SmrProtocol, SummersetError,
+};
+
+/// Fraction of the median write latency spent waiting to fill a batch.
+const BATCH_FRACTION: f64 = 0.1;
+
+/// Number of heartbeat intervals that may pass silently before a peer is
+/// considered gone.
+const HB_MISSES: u64 = 10;
+
+/// Ratio of max over min heartbeat hearing timeout, as in the defaults.
+const HB_HEAR_SPREAD: (u64, u64) = (5, 3);
+
+/// Maximum number of slots packed into one shard train.
+const MAX_SHARD_TRAIN_LEN: u64 = 16;
+
+/// Mode parameters struct.
+#[derive(Debug, Deserialize)]
+pub struct ModeParamsAutotune {
+    /// Key to issue calibration requests on.
+    pub key: String,
+
+    /// Number of warm-up requests whose latencies are not counted.
+    pub warmup: usize,
+
+    /// Number of requests measured in each calibration phase.
+    pub probes: usize,
+
+    /// Value size in bytes of the Puts in the write phase.
+    pub value_size: usize,
+
+    /// If non empty, path to write the recommended `--config` string to, for
+    /// launch scripts to start servers with.
+    pub output_path: String,
+}
+
+impl Default for ModeParamsAutotune {
+    fn default() -> Self {
+        ModeParamsAutotune {
+            key: ClientBench::compose_ith_key(0, false),
+            warmup: 20,
+            probes: 200,
+            value_size: 1024,
+            output_path: "".into(),
+        }
+    }
+}
+
+/// Latency statistics of one calibration phase, in millisecs.
+#[derive(Debug, Default, Clone, Copy)]
+struct PhaseStats {
+    /// Median latency.
+    p50: f64,
+
+    /// 99th-percentile latency.
+    p99: f64,
+
+    /// Number of requests that did not succeed.
+    failed: usize,
+}
+
+/// Timing parameters recommended from the calibration.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Recommendation {
+    batch_interval_ms: u64,
+    hb_send_interval_ms: u64,
+    hb_hear_timeout_min: u64,
+    hb_hear_timeout_max: u64,
+    lease_expire_ms: u64,
+    inv_resend_ms: u64,
+    shard_train_len: u64,
+    shard_train_wait_ms: u64,
+}
+
+/// Autotune client struct.
+pub(crate) struct ClientAutotune {
+    /// Closed-loop request driver.
+    driver: DriverClosedLoop,
+
+    /// Protocol the service runs, deciding which parameters apply.
+    protocol: SmrProtocol,
+
+    /// Mode parameters struct.
+    params: ModeParamsAutotune,
+}
+
+impl ClientAutotune {
+    /// Creates a new autotune client.
+    pub(crate) fn new(
+        endpoint: Box<dyn GenericEndpoint>,
+        timeout: Duration,
+        protocol: SmrProtocol,
+        params_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        let params = parsed_config!(params_str => ModeParamsAutotune;
+                                    key, warmup, probes, value_size,
+                                    output_path)?;
+        if params.probes == 0 {
+            return logged_err!(
+                Config;
+                "invalid params.probes '{}'", params.probes
+            );
+        }
+        if params.value_size == 0 {
+            return logged_err!(
+                Config;
+                "invalid params.value_size '{}'", params.value_size
+            );
+        }
+
+        Ok(ClientAutotune {
+            driver: DriverClosedLoop::new(endpoint, timeout, None, 0),
+            protocol,
+            params,
+        })
+    }
+
+    /// Value at the given percentile of sorted samples.
+    fn percentile(sorted: &[f64], pct: f64) -> f64 {
+        if sorted.is_empty() {
+            return 0.0;
+        }
+        let idx = ((sorted.len() - 1) as f64 * pct / 100.0).round() as usize;
+        sorted[idx]
+    }
+
+    /// Runs one calibration phase of `warmup + probes` sequential requests,
+    /// Puts of `value` if given or Gets otherwise, and returns the latency
+    /// statistics of the measured ones.
+    async fn run_phase(
+        &mut self,
+        value: Option<&str>,
+    ) -> Result<PhaseStats, SummersetError> {
+        let mut lats = Vec::with_capacity(self.params.probes);
+        let mut failed = 0;
+        for i in 0..(self.params.warmup + self.params.probes) {
+            let reply = match value {
+                Some(value) => self.driver.put(&self.params.key, value).await?,
+                None => self.driver.get(&self.params.key).await?,
+            };
+            match reply {
+                DriverReply::Success { latency, .. } => {
+                    if i >= self.params.warmup {
+                        lats.push(latency.as_secs_f64() * 1000.0);
+                    }
+                }
+                DriverReply::Timeout
+                | DriverReply::Failure
+                | DriverReply::Rejected {
+                    code: ReplyError::NotLeader,
+                } => {
+                    pf_debug!("leave and reconnecting...");
+                    failed += 1;
+                    self.driver.leave(false).await?;
+                    self.driver.connect().await?;
+                }
+                _ => failed += 1,
+            }
+        }
+
+        if lats.is_empty() {
+            return logged_err!(
+                Timeout;
+                "no calibration request succeeded ({} failed)", failed
+            );
+        }
+        lats.sort_unstable_by(|a, b| a.total_cmp(b));
+        Ok(PhaseStats {
+            p50: Self::percentile(&lats, 50.0),
+            p99: Self::percentile(&lats, 99.0),
+            failed,
+        })
+    }
+
+    /// Derives timing parameters from the calibration results:
+    ///   - batches wait a small fraction of a typical write's latency, so
+    ///     batching adds little to it;
+    ///   - heartbeats go out every two tail round trips, so a reply to one
+    ///     usually arrives before the next is sent;
+    ///   - peers are suspected after missing a number of heartbeats, with a
+    ///     spread for randomized election timeouts;
+    ///   - leases last as long as the longest hearing timeout, so that they
+    ///     stay valid across the same hiccups heartbeats tolerate;
+    ///   - unacknowledged invalidations are resent once a heartbeat interval
+    ///     has passed on top of a tail round trip;
+    ///   - shard trains wait at most one batch interval, and carry as many
+    ///     slots as get proposed in half a typical write's latency at one
+    ///     batch per interval.
+    fn recommend(reads: &PhaseStats, writes: &PhaseStats) -> Recommendation {
+        let batch_interval_ms =
+            ((writes.p50 * BATCH_FRACTION).ceil() as u64).clamp(1, 50);
+        let tail = reads.p99.max(writes.p99);
+        let hb_send_interval_ms = ((tail * 2.0).ceil() as u64).clamp(5, 500);
+        let hb_hear_timeout_min =
+            (HB_MISSES * hb_send_interval_ms).clamp(100, 6000);
+        let hb_hear_timeout_max =
+            hb_hear_timeout_min * HB_HEAR_SPREAD.0 / HB_HEAR_SPREAD.1;
+        // lease managers require an expiration in [100ms, 10s] longer than
+        // two heartbeat intervals, which the clamps above guarantee
+        let lease_expire_ms = hb_hear_timeout_max.clamp(100, 10000);
+        let inv_resend_ms =
+            hb_send_interval_ms + (tail.ceil() as u64).clamp(1, 500);
+        let shard_train_len = ((writes.p50 / 2.0) as u64 / batch_interval_ms)
+            .clamp(1, MAX_SHARD_TRAIN_LEN);
+        let shard_train_wait_ms = batch_interval_ms;
+
+        Recommendation {
+            batch_interval_ms,
+            hb_send_interval_ms,
+            hb_hear_timeout_min,
+            hb_hear_timeout_max,
+            lease_expire_ms,
+            inv_resend_ms,
+            shard_train_len,
+            shard_train_wait_ms,
+        }
+    }
+
+    /// Recommended config fields as `(name, value)` pairs, keeping only the
+    /// ones the protocol has.
+    fn config_fields(&self, rec: &Recommendation) -> Vec<(&'static str, u64)> {
+        let mut fields = vec![("batch_interval_ms", rec.batch_interval_ms)];
+        if !matches!(
+            self.protocol,
+            SmrProtocol::RepNothing
+                | SmrProtocol::SimplePush
+                | SmrProtocol::ChainRep
+                | SmrProtocol::CRAQ
+                | SmrProtocol::FastPaxos
+                | SmrProtocol::CURP
+        ) {
+            fields.push(("hb_send_interval_ms", rec.hb_send_interval_ms));
+            fields.push(("hb_hear_timeout_min", rec.hb_hear_timeout_min));
+            fields.push(("hb_hear_timeout_max", rec.hb_hear_timeout_max));
+        }
+        if matches!(
+            self.protocol,
+            SmrProtocol::MultiPaxos
+                | SmrProtocol::QuorumLeases
+                | SmrProtocol::Bodega
+                | SmrProtocol::Hermes
+        ) {
+            fields.push(("lease_expire_ms", rec.lease_expire_ms));
+        }
+        if self.protocol == SmrProtocol::Hermes {
+            fields.push(("inv_resend_ms", rec.inv_resend_ms));
+        }
+        if self.protocol == SmrProtocol::RSPaxos {
+            fields.push(("shard_train_len", rec.shard_train_len));
+            fields.push(("shard_train_wait_ms", rec.shard_train_wait_ms));
+        }
+        fields
+    }
+
+    /// Runs the calibration phases and prints the recommended config, also
+    /// writing it to `output_path` if given.
+    pub(crate) async fn run(&mut self) -> Result<(), SummersetError> {
+        self.driver.connect().await?;
+        pf_info!(
+            "calibrating {} replicas of {:?} on key '{}'",
+            self.driver.population(),
+            self.protocol,
+            self.params.key
+        );
+
+        let value = "x".repeat(self.params.value_size);
+        let writes = self.run_phase(Some(&value)).await?;
+        let reads = self.run_phase(None).await?;
+        self.driver.leave(true).await?;
+
+        println!(
+            "{:^7} | {:>10} | {:>10} | {:>8}",
+            "Phase", "p50 (ms)", "p99 (ms)", "Failed"
+        );
+        for (phase, stats) in [("write", &writes), ("read", &reads)] {
+            println!(
+                "{:^7} | {:>10.3} | {:>10.3} | {:>8}",
+                phase, stats.p50, stats.p99, stats.failed
+            );
+        }
+
+        let rec = Self::recommend(&reads, &writes);
+        let fields = self.config_fields(&rec);
+        println!("recommended config:");
+        for (name, value) in &fields {
+            println!("  {} = {}", name, value);
+        }
+        let config_str = fields
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("+");
+        println!("--config {}", config_str);
+
+        if !self.params.output_path.is_empty() {
+            let output_path = Path::new(&self.params.output_path);
+            if fs::exists(output_path)? {
+                pf_warn!(
+                    "overwriting existing output file '{}'",
+                    self.params.output_path
+                );
+            } else if let Some(parent) = output_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(output_path, format!("{}\n", config_str))?;
+            pf_info!(
+                "wrote recommended config to '{}'",
+                self.params.output_path
+            );
+        }
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
 //! Benchmarking client using open-loop driver.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, File, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
 use std::path::Path;
@@ -22,8 +22,9 @@ use serde::Deserialize;
 use tokio::time::{self, Duration, Instant, Interval, MissedTickBehavior};
 
 use summerset::{
-    logged_err, parsed_config, pf_debug, pf_error, pf_info, pf_warn,
-    CommandResult, GenericEndpoint, RequestId, SummersetError,
+    logged_err, parsed_config, pf_debug, pf_error, pf_info, pf_warn, Command,
+    CommandResult, CtrlReply, GenericEndpoint, ReplyError, RequestId,
+    ResetWatcher, SummersetError,
 };
 
 /// Fixed length in bytes of key.
@@ -100,6 +101,15 @@ pub struct ModeParamsBench {
 
     /// Upper bound of uniform distribution to sample value size from.
     pub unif_upper_bound: usize,
+
+    /// Watch for cluster resets by the manager, pausing the benchmark while
+    /// one is in progress instead of erroring out.
+    pub watch_resets: bool,
+
+    /// Number of requests framed together per issue in open-loop style, so
+    /// that they cost one write and land in the same server-side batch;
+    /// `freq_target` still counts single requests.
+    pub client_batch: u64,
 }
 
 #[allow(clippy::derivable_impls)]
@@ -119,6 +129,8 @@ impl Default for ModeParamsBench {
             norm_stdev_ratio: 0.0,
             unif_interval_ms: 0,
             unif_upper_bound: 128 * 1024,
+            watch_resets: false,
+            client_batch: 1,
         }
     }
 }
@@ -170,6 +182,10 @@ pub(crate) struct ClientBench {
     /// Latencies of Get requests in last print interval.
     chunk_rlats: Vec<f64>,
 
+    /// Put requests already counted upon their early commit acknowledgement,
+    /// whose execution results are not to be counted again.
+    committed: HashSet<RequestId>,
+
     /// True if the next issue should be a retry.
     retrying: bool,
 
@@ -190,6 +206,9 @@ pub(crate) struct ClientBench {
 
     /// Current frequency in use.
     curr_freq: u64,
+
+    /// Watcher of cluster resets, if enabled.
+    reset_watcher: Option<ResetWatcher>,
 }
 
 impl ClientBench {
@@ -206,7 +225,8 @@ impl ClientBench {
                                     value_size, num_keys,
                                     use_random_keys, skip_preloading,
                                     norm_stdev_ratio, unif_interval_ms,
-                                    unif_upper_bound)?;
+                                    unif_upper_bound, watch_resets,
+                                    client_batch)?;
         if params.freq_target > 1_000_000 {
             return logged_err!(
                 "invalid params.freq_target '{}'",
@@ -225,6 +245,12 @@ impl ClientBench {
                 params.put_ratio
             );
         }
+        if params.client_batch == 0 || params.client_batch > 1024 {
+            return logged_err!(
+                "invalid params.client_batch '{}'",
+                params.client_batch
+            );
+        }
         if params.num_keys == 0 {
             return logged_err!(
                 "invalid params.num_keys '{}'",
@@ -336,6 +362,7 @@ impl ClientBench {
             chunk_cnt: 0,
             chunk_wlats: vec![],
             chunk_rlats: vec![],
+            committed: HashSet::new(),
             retrying: false,
             slowdown: 0,
             start: Instant::now(),
@@ -343,6 +370,7 @@ impl ClientBench {
             last_unif: Instant::now(),
             ticker: time::interval(Duration::MAX),
             curr_freq: 0,
+            reset_watcher: None,
         })
     }
 
@@ -504,8 +532,8 @@ impl ClientBench {
         Ok(&MOM_VALUE[..size])
     }
 
-    /// Issues a random request.
-    fn issue_rand_cmd(&mut self) -> Result<Option<RequestId>, SummersetError> {
+    /// Generates a random command.
+    fn next_rand_cmd(&mut self) -> Result<Command, SummersetError> {
         debug_assert!(self.keys_pool.is_some());
         let key = self
             .keys_pool
@@ -517,14 +545,17 @@ impl ClientBench {
         if self.rng.gen_range(0..100) < self.params.put_ratio {
             // query the value to use for current timestamp
             let val = self.gen_value_at_now()?;
-            self.driver.issue_put(&key, val)
+            Ok(Command::Put {
+                key,
+                value: val.into(),
+            })
         } else {
-            self.driver.issue_get(&key)
+            Ok(Command::Get { key })
         }
     }
 
-    /// Issues a request following the trace vec.
-    fn issue_trace_cmd(&mut self) -> Result<Option<RequestId>, SummersetError> {
+    /// Generates the next command following the trace vec.
+    fn next_trace_cmd(&mut self) -> Result<Command, SummersetError> {
         debug_assert!(self.trace_vec.is_some());
         let (read, key, vlen) = self
             .trace_vec
@@ -539,46 +570,116 @@ impl ClientBench {
             self.trace_idx = 0;
         }
         if read {
-            self.driver.issue_get(&key)
+            Ok(Command::Get { key })
         } else if vlen == 0 {
             // query the value to use for current timestamp
             let val = self.gen_value_at_now()?;
-            self.driver.issue_put(&key, val)
+            Ok(Command::Put {
+                key,
+                value: val.into(),
+            })
         } else {
             // use vlen in trace
-            let val = &MOM_VALUE[..vlen];
-            self.driver.issue_put(&key, val)
+            Ok(Command::Put {
+                key,
+                value: MOM_VALUE[..vlen].into(),
+            })
         }
     }
 
+    /// Generates the next command, following the trace vec if given.
+    fn next_cmd(&mut self) -> Result<Command, SummersetError> {
+        if self.trace_vec.is_some() {
+            self.next_trace_cmd()
+        } else {
+            self.next_rand_cmd()
+        }
+    }
+
+    /// Issues the next request, or the next `client_batch` requests framed
+    /// together if that is greater than 1, or retries the last issue if it
+    /// failed. Returns the number of requests issued.
+    fn issue_next(&mut self, batch: bool) -> Result<u64, SummersetError> {
+        let req_id = if self.retrying {
+            self.driver.issue_retry()?
+        } else if batch && self.params.client_batch > 1 {
+            let cmds = (0..self.params.client_batch)
+                .map(|_| self.next_cmd())
+                .collect::<Result<Vec<_>, _>>()?;
+            self.driver.issue_batch(cmds)?
+        } else {
+            let cmd = self.next_cmd()?;
+            self.driver.issue_cmd(cmd)?
+        };
+
+        self.retrying = req_id.is_none();
+        Ok(if self.retrying {
+            0
+        } else {
+            self.driver.last_issue_cnt()
+        })
+    }
+
     /// Leaves and reconnects to the service in case the previous server fails.
     async fn leave_reconnect(&mut self) -> Result<(), SummersetError> {
         pf_debug!("leave and reconnecting...");
+        self.committed.clear();
+        self.driver.leave(false).await?;
+        self.driver.connect().await?;
+        Ok(())
+    }
+
+    /// Follows a cluster reset announced by the manager, if any: leaves the
+    /// service, tells the manager so, waits for the reset to finish, and
+    /// reconnects. Returns immediately if no reset is announced.
+    async fn follow_reset(&mut self) -> Result<(), SummersetError> {
+        let Some(watcher) = self.reset_watcher.as_mut() else {
+            return Ok(());
+        };
+        let Ok(notice) =
+            time::timeout(Duration::ZERO, watcher.recv_notice()).await
+        else {
+            return Ok(()); // no notice pending
+        };
+        let CtrlReply::ResetBegin { servers } = notice? else {
+            return Ok(());
+        };
+
+        pf_info!("pausing for reset of servers {:?}...", servers);
         self.driver.leave(false).await?;
+        watcher.quiesced()?;
+        while !matches!(
+            watcher.recv_notice().await?,
+            CtrlReply::ResetDone { .. }
+        ) {}
         self.driver.connect().await?;
+        pf_info!("resuming after reset");
         Ok(())
     }
 
     /// Runs one iteration action of closed-loop style benchmark.
     async fn closed_loop_iter(&mut self) -> Result<(), SummersetError> {
         // send next request
-        let req_id = if self.retrying {
-            self.driver.issue_retry()?
-        } else if self.trace_vec.is_some() {
-            self.issue_trace_cmd()?
-        } else {
-            self.issue_rand_cmd()?
-        };
+        self.total_cnt += self.issue_next(false)?;
 
-        self.retrying = req_id.is_none();
-        if !self.retrying {
-            self.total_cnt += 1;
-        }
-
-        // wait for the next reply
-        if self.total_cnt > self.reply_cnt {
+        // wait for the next reply; a write counts as done upon its early
+        // commit acknowledgement if enabled
+        while self.total_cnt > self.reply_cnt {
             let result = self.driver.wait_reply().await?;
             match result {
+                DriverReply::Committed { req_id, latency } => {
+                    self.committed.insert(req_id);
+                    self.reply_cnt += 1;
+                    self.chunk_cnt += 1;
+                    self.chunk_wlats.push(latency.as_secs_f64() * 1_000_000.0);
+                }
+
+                DriverReply::Success { req_id, .. }
+                    if self.committed.remove(&req_id) =>
+                {
+                    continue; // already counted
+                }
+
                 DriverReply::Success {
                     latency,
                     cmd_result,
@@ -589,21 +690,33 @@ impl ClientBench {
 
                     let lat_us = latency.as_secs_f64() * 1_000_000.0;
                     match cmd_result {
-                        CommandResult::Put { .. } => {
+                        CommandResult::Put { .. }
+                        | CommandResult::Cas { .. }
+                        | CommandResult::Acquire { .. }
+                        | CommandResult::Release { .. }
+                        | CommandResult::QuotaExceeded { .. }
+                        | CommandResult::DiskFull { .. }
+                        | CommandResult::ChecksumMismatch => {
                             self.chunk_wlats.push(lat_us);
                         }
-                        CommandResult::Get { .. } => {
+                        CommandResult::Get { .. }
+                        | CommandResult::Scan { .. } => {
                             self.chunk_rlats.push(lat_us);
                         }
                     }
                 }
 
-                DriverReply::Timeout | DriverReply::Failure => {
+                DriverReply::Timeout
+                | DriverReply::Failure
+                | DriverReply::Rejected {
+                    code: ReplyError::NotLeader,
+                } => {
                     self.leave_reconnect().await?;
                 }
 
                 _ => {}
             }
+            break;
         }
 
         Ok(())
@@ -612,59 +725,72 @@ impl ClientBench {
     /// Runs one iteration action of open-loop style benchmark.
     async fn open_loop_iter(&mut self) -> Result<(), SummersetError> {
         tokio::select! {
-            // prioritize receiving reply
-            biased;
-
-            // receive next reply
-            result = self.driver.wait_reply() => {
-                match result? {
-                    DriverReply::Success { latency, cmd_result, .. } => {
-                        self.reply_cnt += 1;
-                        self.chunk_cnt += 1;
-
-                        let lat_us = latency.as_secs_f64() * 1_000_000.0;
-                        match cmd_result {
-                            CommandResult::Put { .. } => {
-                                self.chunk_wlats.push(lat_us);
+                    // prioritize receiving reply
+                    biased;
+
+                    // receive next reply
+                    result = self.driver.wait_reply() => {
+                        match result? {
+                            DriverReply::Committed { req_id, latency } => {
+                                self.committed.insert(req_id);
+                                self.reply_cnt += 1;
+                                self.chunk_cnt += 1;
+                                self.chunk_wlats.push(latency.as_secs_f64() * 1_000_000.0);
+                                if self.slowdown > 0 {
+                                    self.slowdown -= 1;
+                                }
                             }
-                            CommandResult::Get { .. } => {
-                                self.chunk_rlats.push(lat_us);
+
+                            DriverReply::Success { req_id, .. } if self.committed.remove(&req_id) => {
+                                // already counted
+                            }
+
+                            DriverReply::Success { latency, cmd_result, .. } => {
+                                self.reply_cnt += 1;
+                                self.chunk_cnt += 1;
+
+                                let lat_us = latency.as_secs_f64() * 1_000_000.0;
+                                match cmd_result {
+                                    CommandResult::Put { .. }
+                                | CommandResult::Cas { .. }
+                                | CommandResult::Acquire { .. }
+                                | CommandResult::Release { .. }
+        | CommandResult::QuotaExceeded { .. }
+        | CommandResult::DiskFull { .. }
+        | CommandResult::ChecksumMismatch => {
+                                        self.chunk_wlats.push(lat_us);
+                                    }
+                                    CommandResult::Get { .. }
+                                    | CommandResult::Scan { .. } => {
+                                        self.chunk_rlats.push(lat_us);
+                                    }
+                                }
+
+                                if self.slowdown > 0 {
+                                    self.slowdown -= 1;
+                                }
                             }
-                        }
 
-                        if self.slowdown > 0 {
-                            self.slowdown -= 1;
+                            DriverReply::Timeout
+                            | DriverReply::Failure
+                            | DriverReply::Rejected { code: ReplyError::NotLeader } => {
+                                self.leave_reconnect().await?;
+                            }
+
+                            _ => {}
                         }
                     }
 
-                    DriverReply::Timeout | DriverReply::Failure => {
-                        self.leave_reconnect().await?;
+                    // send next request
+                    _ = self.ticker.tick(), if self.slowdown == 0 => {
+                        let issued = self.issue_next(true)?;
+                        if self.retrying && (self.total_cnt > self.reply_cnt) {
+                            // too many pending requests, pause issuing for a while
+                            self.slowdown = (self.total_cnt - self.reply_cnt) / 2;
+                        }
+                        self.total_cnt += issued;
                     }
-
-                    _ => {}
-                }
-            }
-
-            // send next request
-            _ = self.ticker.tick(), if self.slowdown == 0 => {
-                let req_id = if self.retrying {
-                    self.driver.issue_retry()?
-                } else if self.trace_vec.is_some() {
-                    self.issue_trace_cmd()?
-                } else {
-                    self.issue_rand_cmd()?
-                };
-
-                self.retrying = req_id.is_none();
-                if self.retrying && (self.total_cnt > self.reply_cnt) {
-                    // too many pending requests, pause issuing for a while
-                    self.slowdown = (self.total_cnt - self.reply_cnt) / 2;
                 }
-                if !self.retrying {
-                    self.total_cnt += 1;
-                }
-            }
-        }
 
         Ok(())
     }
@@ -693,7 +819,12 @@ impl ClientBench {
                 loop {
                     while self.driver.issue_put(key, val)?.is_none() {}
 
-                    match self.driver.wait_reply().await? {
+                    // wait past any early commit acknowledgement
+                    let mut reply = self.driver.wait_reply().await?;
+                    while let DriverReply::Committed { .. } = reply {
+                        reply = self.driver.wait_reply().await?;
+                    }
+                    match reply {
                         DriverReply::Success { .. } => {
                             break;
                         }
@@ -721,7 +852,10 @@ impl ClientBench {
             self.curr_freq = 1; // avoid division-by-zero
         }
 
-        let period = Duration::from_nanos(1_000_000_000 / self.curr_freq);
+        // each tick issues a batch of `client_batch` requests
+        let period = Duration::from_nanos(
+            1_000_000_000 * self.params.client_batch / self.curr_freq,
+        );
         self.ticker = time::interval(period);
         self.ticker
             .set_missed_tick_behavior(MissedTickBehavior::Skip);
@@ -730,6 +864,10 @@ impl ClientBench {
     /// Runs the adaptive benchmark for given time length.
     pub(crate) async fn run(&mut self) -> Result<(), SummersetError> {
         self.driver.connect().await?;
+        if self.params.watch_resets {
+            self.reset_watcher =
+                Some(self.driver.ctrl_stub().watch_resets().await?);
+        }
 
         self.start = Instant::now();
         self.now = self.start;
@@ -785,6 +923,7 @@ impl ClientBench {
         // run for specified length
         let mut elapsed = self.now.duration_since(self.start);
         while elapsed < length {
+            self.follow_reset().await?;
             if self.params.freq_target == 0 {
                 self.closed_loop_iter().await?;
             } else {
@@ -0,0 +1,233 @@
+//! Canary client issuing low-rate read/write probes on a key of its own and
+//! checking strict invariants on every reply, flagging violations as they
+//! happen. Meant to run alongside load-generating clients.
+
+use crate::drivers::{DriverClosedLoop, DriverReply};
+
+use serde::Deserialize;
+
+use tokio::time::{self, Duration, Instant, MissedTickBehavior};
+
+use summerset::{
+    logged_err, parsed_config, pf_debug, pf_error, pf_info, CommandResult,
+    GenericEndpoint, ReplyError, SummersetError,
+};
+
+/// Mode parameters struct.
+#[derive(Debug, Deserialize)]
+pub struct ModeParamsCanary {
+    /// Interval between probes in millisecs.
+    pub interval_ms: u64,
+
+    /// Number of Gets following the Put in each probe.
+    pub reads_per_probe: usize,
+
+    /// Duration to run in seconds; 0 means forever.
+    pub length_s: u64,
+}
+
+impl Default for ModeParamsCanary {
+    fn default() -> Self {
+        ModeParamsCanary {
+            interval_ms: 200,
+            reads_per_probe: 2,
+            length_s: 0,
+        }
+    }
+}
+
+/// Canary client struct.
+pub(crate) struct ClientCanary {
+    /// Closed-loop request driver.
+    driver: DriverClosedLoop,
+
+    /// Mode parameters struct.
+    params: ModeParamsCanary,
+
+    /// Key probed, written by no one else.
+    key: String,
+
+    /// Sequence number of the latest write issued, acked or not.
+    issued_seq: u64,
+
+    /// Sequence number of the latest write acked.
+    acked_seq: u64,
+
+    /// Highest sequence number read so far.
+    seen_seq: u64,
+
+    /// Number of probe requests that got no successful reply.
+    failed: u64,
+
+    /// Number of invariant violations flagged.
+    violations: u64,
+
+    /// Start timestamp.
+    start: Instant,
+}
+
+impl ClientCanary {
+    /// Creates a new canary client.
+    pub(crate) fn new(
+        endpoint: Box<dyn GenericEndpoint>,
+        timeout: Duration,
+        hedge_delay: Option<Duration>,
+        params_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        let params = parsed_config!(params_str => ModeParamsCanary;
+                                    interval_ms, reads_per_probe, length_s)?;
+        if params.interval_ms == 0 {
+            return logged_err!(
+                Config;
+                "invalid params.interval_ms '{}'", params.interval_ms
+            );
+        }
+
+        let key = format!("canary{}", endpoint.id());
+        Ok(ClientCanary {
+            driver: DriverClosedLoop::new(endpoint, timeout, hedge_delay, 0),
+            params,
+            key,
+            issued_seq: 0,
+            acked_seq: 0,
+            seen_seq: 0,
+            failed: 0,
+            violations: 0,
+            start: Instant::now(),
+        })
+    }
+
+    /// Flags an invariant violation right away.
+    fn flag(&mut self, what: &str, detail: String) {
+        self.violations += 1;
+        println!(
+            "{:>11.2} | VIOLATION {} on '{}': {}",
+            self.start.elapsed().as_secs_f64(),
+            what,
+            self.key,
+            detail
+        );
+        pf_error!("{} violated on '{}': {}", what, self.key, detail);
+    }
+
+    /// Handles a reply that is not a success, reconnecting if the server
+    /// may have failed.
+    async fn handle_unsuccessful(
+        &mut self,
+        reply: DriverReply,
+    ) -> Result<(), SummersetError> {
+        self.failed += 1;
+        if matches!(
+            reply,
+            DriverReply::Timeout
+                | DriverReply::Failure
+                | DriverReply::Rejected {
+                    code: ReplyError::NotLeader
+                }
+        ) {
+            pf_debug!("leave and reconnecting...");
+            self.driver.leave(false).await?;
+            self.driver.connect().await?;
+        }
+        Ok(())
+    }
+
+    /// Writes the next sequence number to the key.
+    async fn probe_write(&mut self) -> Result<(), SummersetError> {
+        self.issued_seq += 1;
+        let value = self.issued_seq.to_string();
+        match self.driver.put(&self.key, &value).await? {
+            DriverReply::Success {
+                cmd_result: CommandResult::Put { .. },
+                ..
+            } => {
+                self.acked_seq = self.issued_seq;
+                Ok(())
+            }
+            DriverReply::Success { cmd_result, .. } => {
+                // e.g., storage quota or disk space trouble, not a
+                // consistency matter
+                pf_debug!("probe write not applied: {:?}", cmd_result);
+                self.failed += 1;
+                Ok(())
+            }
+            reply => self.handle_unsuccessful(reply).await,
+        }
+    }
+
+    /// Reads the key and checks the invariants on the value read:
+    ///   - read-your-writes: it is no older than the latest acked write
+    ///   - monotonic reads: it is no older than anything read before
+    ///   - it is not newer than any write issued
+    async fn probe_read(&mut self) -> Result<(), SummersetError> {
+        let value = match self.driver.get(&self.key).await? {
+            DriverReply::Success {
+                cmd_result: CommandResult::Get { value },
+                ..
+            } => value,
+            reply => return self.handle_unsuccessful(reply).await,
+        };
+
+        let seq = match value.as_deref().map(str::parse::<u64>) {
+            None => 0,
+            Some(Ok(seq)) => seq,
+            Some(Err(_)) => {
+                self.flag("integrity", format!("read garbage {:?}", value));
+                return Ok(());
+            }
+        };
+        if seq < self.acked_seq {
+            self.flag(
+                "read-your-writes",
+                format!("read {} after write {} acked", seq, self.acked_seq),
+            );
+        }
+        if seq < self.seen_seq {
+            self.flag(
+                "monotonic reads",
+                format!("read {} after reading {}", seq, self.seen_seq),
+            );
+        }
+        if seq > self.issued_seq {
+            self.flag("integrity", format!("read {} never written", seq));
+        }
+        self.seen_seq = self.seen_seq.max(seq);
+        Ok(())
+    }
+
+    /// Runs the canary client until the specified length passes.
+    pub(crate) async fn run(&mut self) -> Result<(), SummersetError> {
+        self.driver.connect().await?;
+        pf_info!(
+            "probing key '{}' every {} ms",
+            self.key,
+            self.params.interval_ms
+        );
+
+        let mut ticker =
+            time::interval(Duration::from_millis(self.params.interval_ms));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let length = Duration::from_secs(self.params.length_s);
+        self.start = Instant::now();
+        while self.params.length_s == 0 || self.start.elapsed() < length {
+            ticker.tick().await;
+            self.probe_write().await?;
+            for _ in 0..self.params.reads_per_probe {
+                self.probe_read().await?;
+            }
+        }
+
+        println!(
+            "canary done: {} probes, {} requests failed, {} violations",
+            self.issued_seq, self.failed, self.violations
+        );
+        self.driver.leave(true).await?;
+        if self.violations > 0 {
+            return logged_err!(
+                Corruption;
+                "{} invariant violation(s) flagged", self.violations
+            );
+        }
+        Ok(())
+    }
+}
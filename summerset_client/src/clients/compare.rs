@@ -0,0 +1,308 @@
+//! Comparison client post-processing the result directories of multiple
+//! manifest runs into comparative statistics, offline.
+
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use summerset::{logged_err, parsed_config, pf_info, pf_warn, SummersetError};
+
+/// Two-sided 95% critical values of Student's t distribution for 1 to 30
+/// degrees of freedom.
+const T_CRIT_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+    2.201, 2.179, 2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086,
+    2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042,
+];
+
+/// Critical value used beyond 30 degrees of freedom.
+const Z_CRIT_95: f64 = 1.960;
+
+/// Names of the per-run metrics compared, in order.
+const METRICS: [&str; 4] =
+    ["tput (ops/s)", "lat (us)", "p50 lat (us)", "p99 lat (us)"];
+
+/// Mode parameters struct.
+#[derive(Debug, Deserialize)]
+pub struct ModeParamsCompare {
+    /// Result directory of the baseline manifest run.
+    pub baseline: String,
+
+    /// Comma-separated result directories of manifest runs to compare
+    /// against the baseline.
+    pub candidates: String,
+
+    /// Name of the bench phase to compare.
+    pub phase: String,
+
+    /// Seconds at the beginning of each run to leave out as warm-up.
+    pub skip_s: f64,
+}
+
+impl Default for ModeParamsCompare {
+    fn default() -> Self {
+        ModeParamsCompare {
+            baseline: "".into(),
+            candidates: "".into(),
+            phase: "".into(),
+            skip_s: 1.0,
+        }
+    }
+}
+
+/// Per-run metric values of one manifest run's repetitions, indexed the
+/// same as `METRICS`.
+struct RunSet {
+    /// Label of the manifest run, its protocol and directory name.
+    label: String,
+
+    /// For each metric, its values across repetitions.
+    values: [Vec<f64>; METRICS.len()],
+}
+
+/// Comparison client struct.
+pub(crate) struct ClientCompare {
+    /// Mode parameters struct.
+    params: ModeParamsCompare,
+}
+
+impl ClientCompare {
+    /// Creates a new comparison client.
+    pub(crate) fn new(
+        params_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        let params = parsed_config!(params_str => ModeParamsCompare;
+                                    baseline, candidates, phase, skip_s)?;
+        if params.baseline.is_empty() || params.candidates.is_empty() {
+            return logged_err!(
+                Config;
+                "params.baseline and params.candidates must be given"
+            );
+        }
+        if params.phase.is_empty() {
+            return logged_err!(Config; "params.phase not given");
+        }
+        if params.skip_s < 0.0 {
+            return logged_err!(
+                Config;
+                "invalid params.skip_s '{}'", params.skip_s
+            );
+        }
+
+        Ok(ClientCompare { params })
+    }
+
+    /// Value at the given percentile of sorted samples.
+    fn percentile(sorted: &[f64], pct: f64) -> f64 {
+        let idx = ((sorted.len() - 1) as f64 * pct / 100.0).round() as usize;
+        sorted[idx]
+    }
+
+    /// Sample mean and variance.
+    fn mean_var(samples: &[f64]) -> (f64, f64) {
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let var = if samples.len() > 1 {
+            samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)
+        } else {
+            0.0
+        };
+        (mean, var)
+    }
+
+    /// Half width of the 95% confidence interval of the difference between
+    /// the means of two sample sets, by Welch's t-test. Returns `None` if
+    /// either set has fewer than two samples.
+    fn welch_half_width(a: &[f64], b: &[f64]) -> Option<f64> {
+        if a.len() < 2 || b.len() < 2 {
+            return None;
+        }
+        let ((_, va), (_, vb)) = (Self::mean_var(a), Self::mean_var(b));
+        let (sa, sb) = (va / a.len() as f64, vb / b.len() as f64);
+        let se = (sa + sb).sqrt();
+        if se == 0.0 {
+            return Some(0.0);
+        }
+        let df = (sa + sb).powi(2)
+            / (sa.powi(2) / (a.len() - 1) as f64
+                + sb.powi(2) / (b.len() - 1) as f64);
+        let t = match df.floor() as usize {
+            0 => T_CRIT_95[0],
+            d if d <= T_CRIT_95.len() => T_CRIT_95[d - 1],
+            _ => Z_CRIT_95,
+        };
+        Some(t * se)
+    }
+
+    /// Parses a bench output file into per-run metric values, leaving out
+    /// rows within the warm-up time. Percentiles are taken over the
+    /// per-interval average latencies the bench prints.
+    fn parse_bench_output(
+        &self,
+        path: &Path,
+    ) -> Result<Option<[f64; METRICS.len()]>, SummersetError> {
+        let mut tputs = vec![];
+        let mut lats = vec![];
+        for line in fs::read_to_string(path)?.lines() {
+            let segs: Vec<&str> =
+                line.split(['|', ':', '~', '/']).map(str::trim).collect();
+            let (Some(Ok(elapsed)), Some(Ok(tput)), Some(Ok(lat))) = (
+                segs.first().map(|s| s.parse::<f64>()),
+                segs.get(1).map(|s| s.parse::<f64>()),
+                segs.get(2).map(|s| s.parse::<f64>()),
+            ) else {
+                continue; // header or malformed line
+            };
+            if elapsed >= self.params.skip_s {
+                tputs.push(tput);
+                if tput > 0.0 {
+                    lats.push(lat);
+                }
+            }
+        }
+        if lats.is_empty() {
+            return Ok(None);
+        }
+
+        let tput_avg = tputs.iter().sum::<f64>() / tputs.len() as f64;
+        let lat_avg = lats.iter().sum::<f64>() / lats.len() as f64;
+        lats.sort_unstable_by(|a, b| a.total_cmp(b));
+        Ok(Some([
+            tput_avg,
+            lat_avg,
+            Self::percentile(&lats, 50.0),
+            Self::percentile(&lats, 99.0),
+        ]))
+    }
+
+    /// Loads the bench outputs of the compared phase from all repetitions in
+    /// a manifest run's result directory.
+    fn load_run_set(&self, dir: &str) -> Result<RunSet, SummersetError> {
+        let dir = Path::new(dir.trim());
+        let manifest: toml::Table =
+            fs::read_to_string(dir.join("manifest.toml"))?.parse()?;
+        let protocol = manifest
+            .get("protocol")
+            .and_then(|v| v.as_str())
+            .unwrap_or("?");
+        let label = format!(
+            "{}@{}",
+            protocol,
+            dir.file_name().unwrap_or_default().to_string_lossy()
+        );
+
+        let suffix = format!("-{}.out", self.params.phase);
+        let mut values: [Vec<f64>; METRICS.len()] = Default::default();
+        let mut rep_dirs: Vec<_> = fs::read_dir(dir)?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_dir()
+                    && path
+                        .file_name()
+                        .is_some_and(|n| n.to_string_lossy().starts_with("rep"))
+            })
+            .collect();
+        rep_dirs.sort();
+        for rep_dir in rep_dirs {
+            for entry in fs::read_dir(&rep_dir)?.filter_map(Result::ok) {
+                let path = entry.path();
+                if !path.to_string_lossy().ends_with(&suffix) {
+                    continue;
+                }
+                match self.parse_bench_output(&path)? {
+                    Some(run) => {
+                        for (m, value) in run.into_iter().enumerate() {
+                            values[m].push(value);
+                        }
+                    }
+                    None => {
+                        pf_warn!(
+                            "no data after warm-up in '{}'",
+                            path.display()
+                        )
+                    }
+                }
+            }
+        }
+
+        if values[0].is_empty() {
+            return logged_err!(
+                Config;
+                "no '{}' phase outputs found in '{}'",
+                self.params.phase, dir.display()
+            );
+        }
+        Ok(RunSet { label, values })
+    }
+
+    /// Prints the statistics of a run set, and if a baseline is given, the
+    /// deltas of its means from the baseline's with 95% confidence intervals.
+    fn print_run_set(set: &RunSet, baseline: Option<&RunSet>) {
+        println!("{} ({} runs)", set.label, set.values[0].len());
+        for (m, metric) in METRICS.iter().enumerate() {
+            let (mean, var) = Self::mean_var(&set.values[m]);
+            let mut row = format!(
+                "  {:<14} | {:>12.2} ± {:>10.2}",
+                metric,
+                mean,
+                var.sqrt()
+            );
+            if let Some(base) = baseline {
+                let (base_mean, _) = Self::mean_var(&base.values[m]);
+                let delta = mean - base_mean;
+                let pct = if base_mean != 0.0 {
+                    format!("{:>+8.2}%", delta / base_mean * 100.0)
+                } else {
+                    format!("{:>9}", "-")
+                };
+                let ci = match Self::welch_half_width(
+                    &set.values[m],
+                    &base.values[m],
+                ) {
+                    Some(hw) => {
+                        let sig = if delta.abs() > hw { "*" } else { "" };
+                        format!(
+                            "[{:+.2}, {:+.2}]{}",
+                            delta - hw,
+                            delta + hw,
+                            sig
+                        )
+                    }
+                    None => "n/a".into(),
+                };
+                row.push_str(&format!(" | {:>+12.2} {} {}", delta, pct, ci));
+            }
+            println!("{}", row);
+        }
+    }
+
+    /// Loads all run sets and prints the comparison report.
+    pub(crate) fn run(&mut self) -> Result<(), SummersetError> {
+        let baseline = self.load_run_set(&self.params.baseline)?;
+        let candidates = self
+            .params
+            .candidates
+            .split(',')
+            .filter(|dir| !dir.trim().is_empty())
+            .map(|dir| self.load_run_set(dir))
+            .collect::<Result<Vec<_>, _>>()?;
+        pf_info!(
+            "comparing phase '{}' of {} run sets against baseline",
+            self.params.phase,
+            candidates.len()
+        );
+
+        println!(
+            "  {:<14} | {:>12}   {:>10} | {:>12} {:>9} 95% CI of delta",
+            "Metric", "Mean", "Stdev", "Delta", "Delta %"
+        );
+        Self::print_run_set(&baseline, None);
+        for set in &candidates {
+            Self::print_run_set(set, Some(&baseline));
+        }
+        println!("(* marks deltas significant at the 95% level)");
+        Ok(())
+    }
+}
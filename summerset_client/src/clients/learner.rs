@@ -0,0 +1,224 @@
+//! Learner client reading the same key from all replicas periodically and
+//! reporting per-replica staleness over time.
+
+use std::collections::HashMap;
+
+use crate::clients::ClientBench;
+use crate::drivers::{DriverLearner, LocalReadResult};
+
+use serde::Deserialize;
+
+use tokio::time::{self, Duration, Instant, MissedTickBehavior};
+
+use summerset::{
+    logged_err, parsed_config, pf_info, GenericEndpoint, ReplicaId,
+    SummersetError,
+};
+
+/// Mode parameters struct.
+#[derive(Debug, Deserialize)]
+pub struct ModeParamsLearner {
+    /// Key to read from all replicas.
+    pub key: String,
+
+    /// Interval between reads in millisecs.
+    pub interval_ms: u64,
+
+    /// Duration to run in seconds; 0 means forever.
+    pub length_s: u64,
+}
+
+impl Default for ModeParamsLearner {
+    fn default() -> Self {
+        ModeParamsLearner {
+            key: ClientBench::compose_ith_key(0, false),
+            interval_ms: 100,
+            length_s: 30,
+        }
+    }
+}
+
+/// Running staleness statistics of one replica.
+#[derive(Debug, Default)]
+struct StalenessStats {
+    /// Number of reads replied.
+    replied: u64,
+
+    /// Number of reads not replied in time.
+    missed: u64,
+
+    /// Sum of execution bar lags behind the freshest replica.
+    lag_sum: u64,
+
+    /// Maximum execution bar lag behind the freshest replica.
+    lag_max: usize,
+
+    /// Number of reads whose lag in time behind the freshest replica is
+    /// known, i.e., both replies carried HLC timestamps.
+    timed: u64,
+
+    /// Sum of time lags in millisecs behind the freshest replica, going by
+    /// the HLC timestamps of their latest executed commands.
+    lag_ms_sum: u64,
+
+    /// Maximum time lag in millisecs behind the freshest replica.
+    lag_ms_max: u64,
+
+    /// Number of reads whose value differed from the freshest replica's.
+    diverged: u64,
+}
+
+/// Learner client struct.
+pub(crate) struct ClientLearner {
+    /// Learner driver.
+    driver: DriverLearner,
+
+    /// Mode parameters struct.
+    params: ModeParamsLearner,
+
+    /// Map from replica ID -> its staleness statistics.
+    stats: HashMap<ReplicaId, StalenessStats>,
+}
+
+impl ClientLearner {
+    /// Creates a new learner client.
+    pub(crate) fn new(
+        endpoint: Box<dyn GenericEndpoint>,
+        timeout: Duration,
+        params_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        let params = parsed_config!(params_str => ModeParamsLearner;
+                                    key, interval_ms, length_s)?;
+        if params.interval_ms == 0 {
+            return logged_err!(
+                Config;
+                "invalid params.interval_ms '{}'", params.interval_ms
+            );
+        }
+
+        Ok(ClientLearner {
+            driver: DriverLearner::new(endpoint, timeout),
+            params,
+            stats: HashMap::new(),
+        })
+    }
+
+    /// Tallies a round of reads into the statistics and formats its row of
+    /// per-replica staleness. Each replica shows its execution bar lag behind the freshest replica,
+    /// marked with `*` if the value it read diverges from the freshest one,
+    /// or `-` if it did not reply in time.
+    fn tally_round(
+        &mut self,
+        replicas: &[ReplicaId],
+        results: &HashMap<ReplicaId, Option<LocalReadResult>>,
+    ) -> String {
+        let freshest = results
+            .values()
+            .flatten()
+            .max_by_key(|result| result.exec_bar);
+
+        let mut row = String::new();
+        for replica in replicas {
+            let stats = self.stats.entry(*replica).or_default();
+            let cell = match (results.get(replica), freshest) {
+                (Some(Some(result)), Some(freshest)) => {
+                    let lag = freshest.exec_bar - result.exec_bar;
+                    let diverged = result.value != freshest.value;
+                    stats.replied += 1;
+                    stats.lag_sum += lag as u64;
+                    stats.lag_max = stats.lag_max.max(lag);
+                    if diverged {
+                        stats.diverged += 1;
+                    }
+                    if let (Some(hlc), Some(freshest_hlc)) =
+                        (result.hlc, freshest.hlc)
+                    {
+                        let lag_ms = hlc.lag_behind(&freshest_hlc);
+                        stats.timed += 1;
+                        stats.lag_ms_sum += lag_ms;
+                        stats.lag_ms_max = stats.lag_ms_max.max(lag_ms);
+                    }
+                    format!("{}{}", lag, if diverged { "*" } else { "" })
+                }
+                _ => {
+                    stats.missed += 1;
+                    "-".into()
+                }
+            };
+            row.push_str(&format!(" | {:>8}", cell));
+        }
+        row
+    }
+
+    /// Prints the summary of staleness statistics per replica.
+    fn print_summary(&self, replicas: &[ReplicaId]) {
+        println!(
+            "{:^7} | {:>8} | {:>8} | {:>10} | {:>8} | {:>10} | {:>10} | {:>8}",
+            "Replica",
+            "Replied",
+            "Missed",
+            "Avg lag",
+            "Max lag",
+            "Avg ms lag",
+            "Max ms lag",
+            "Diverged"
+        );
+        for replica in replicas {
+            let stats = &self.stats[replica];
+            let lag_avg = if stats.replied > 0 {
+                stats.lag_sum as f64 / stats.replied as f64
+            } else {
+                0.0
+            };
+            let lag_ms_avg = if stats.timed > 0 {
+                stats.lag_ms_sum as f64 / stats.timed as f64
+            } else {
+                0.0
+            };
+            println!(
+                "{:^7} | {:>8} | {:>8} | {:>10.2} | {:>8} | {:>10.2} | {:>10} | {:>8}",
+                replica,
+                stats.replied,
+                stats.missed,
+                lag_avg,
+                stats.lag_max,
+                lag_ms_avg,
+                stats.lag_ms_max,
+                stats.diverged
+            );
+        }
+    }
+
+    /// Runs the learner client until the specified length passes.
+    pub(crate) async fn run(&mut self) -> Result<(), SummersetError> {
+        self.driver.connect().await?;
+        let replicas = self.driver.replicas();
+        pf_info!(
+            "reading key '{}' from replicas {:?}",
+            self.params.key,
+            replicas
+        );
+
+        let mut header = format!("{:^11}", "Elapsed (s)");
+        for replica in &replicas {
+            header.push_str(&format!(" | {:>8}", format!("r{}", replica)));
+        }
+        println!("{}", header);
+
+        let mut ticker =
+            time::interval(Duration::from_millis(self.params.interval_ms));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let length = Duration::from_secs(self.params.length_s);
+        let start = Instant::now();
+        while self.params.length_s == 0 || start.elapsed() < length {
+            ticker.tick().await;
+            let results = self.driver.read_all(&self.params.key).await?;
+            let row = self.tally_round(&replicas, &results);
+            println!("{:>11.2}{}", start.elapsed().as_secs_f64(), row);
+        }
+
+        self.print_summary(&replicas);
+        self.driver.leave(true).await?;
+        Ok(())
+    }
+}
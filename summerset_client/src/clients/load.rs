@@ -0,0 +1,340 @@
+//! Data loader client bulk-inserting keys with as many requests in flight as
+//! allowed, as a precondition step before benchmarks.
+
+use std::collections::HashMap;
+
+use crate::clients::ClientBench;
+use crate::drivers::{DriverOpenLoop, DriverReply};
+
+use rand::distributions::Alphanumeric;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use serde::Deserialize;
+
+use tokio::time::{self, Duration, Instant, MissedTickBehavior};
+
+use summerset::{
+    logged_err, parsed_config, pf_debug, pf_info, Command, CommandResult,
+    GenericEndpoint, RequestId, SummersetError,
+};
+
+/// Fixed length in bytes of random keys, same as the `bench` mode's.
+const KEY_LEN: usize = 8;
+
+/// Progress printing interval.
+const PRINT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Length in bytes of the pool string values are taken from.
+const MIN_POOL_LEN: usize = 64 * 1024;
+
+/// Number of consecutive unsuccessful replies tolerated before giving up.
+const MAX_RETRIES: u32 = 10;
+
+/// Mode parameters struct.
+#[derive(Debug, Deserialize)]
+pub struct ModeParamsLoad {
+    /// Number of keys to insert.
+    pub num_keys: usize,
+
+    /// Value size in bytes.
+    pub value_size: usize,
+
+    /// Whether to generate keys randomly or use predetermined sequence from
+    /// 0, same as the `bench` mode's.
+    pub use_random_keys: bool,
+
+    /// Seed of the random keys and values; 0 means a different one per run.
+    pub seed: u64,
+
+    /// Maximum number of Put requests in flight.
+    pub max_inflight: usize,
+
+    /// Number of requests framed together per issue.
+    pub client_batch: usize,
+}
+
+impl Default for ModeParamsLoad {
+    fn default() -> Self {
+        ModeParamsLoad {
+            num_keys: 1000,
+            value_size: 1024,
+            use_random_keys: false,
+            seed: 0,
+            max_inflight: 256,
+            client_batch: 16,
+        }
+    }
+}
+
+/// Data loader client struct.
+pub(crate) struct ClientLoad {
+    /// Open-loop request driver.
+    driver: DriverOpenLoop,
+
+    /// Mode parameters struct.
+    params: ModeParamsLoad,
+
+    /// Random number generator, seeded if asked to.
+    rng: StdRng,
+
+    /// Random string to take values from.
+    value_pool: String,
+
+    /// Index of the next key never issued.
+    next_key: usize,
+
+    /// Puts to issue again, as their previous requests failed.
+    requeued: Vec<Command>,
+
+    /// Puts of the last issue that got a `WouldBlock` failure, to retry.
+    unsent: Vec<Command>,
+
+    /// Map from request ID -> Put in flight.
+    inflight: HashMap<RequestId, Command>,
+
+    /// Number of keys loaded.
+    loaded: usize,
+
+    /// Number of keys loaded in last print interval.
+    chunk_cnt: usize,
+
+    /// Number of unsuccessful replies in a row.
+    failures: u32,
+
+    /// Start timestamp.
+    start: Instant,
+}
+
+impl ClientLoad {
+    /// Creates a new data loader client.
+    pub(crate) fn new(
+        endpoint: Box<dyn GenericEndpoint>,
+        timeout: Duration,
+        params_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        let params = parsed_config!(params_str => ModeParamsLoad;
+                                    num_keys, value_size, use_random_keys,
+                                    seed, max_inflight, client_batch)?;
+        if params.value_size == 0 {
+            return logged_err!(
+                Config;
+                "invalid params.value_size '{}'", params.value_size
+            );
+        }
+        if params.max_inflight == 0 {
+            return logged_err!(
+                Config;
+                "invalid params.max_inflight '{}'", params.max_inflight
+            );
+        }
+        if params.client_batch == 0 || params.client_batch > params.max_inflight
+        {
+            return logged_err!(
+                Config;
+                "invalid params.client_batch '{}'", params.client_batch
+            );
+        }
+
+        let mut rng = if params.seed == 0 {
+            StdRng::from_entropy()
+        } else {
+            StdRng::seed_from_u64(params.seed)
+        };
+        let value_pool = (&mut rng)
+            .sample_iter(&Alphanumeric)
+            .take(MIN_POOL_LEN.max(2 * params.value_size))
+            .map(char::from)
+            .collect();
+
+        Ok(ClientLoad {
+            driver: DriverOpenLoop::new(endpoint, timeout),
+            params,
+            rng,
+            value_pool,
+            next_key: 0,
+            requeued: vec![],
+            unsent: vec![],
+            inflight: HashMap::new(),
+            loaded: 0,
+            chunk_cnt: 0,
+            failures: 0,
+            start: Instant::now(),
+        })
+    }
+
+    /// Composes the Put command of the next key never issued. Keys and values
+    /// are drawn from the generator in key order, so that a run with the same
+    /// seed loads the same data.
+    fn next_put_cmd(&mut self) -> Command {
+        let key = if self.params.use_random_keys {
+            (&mut self.rng)
+                .sample_iter(&Alphanumeric)
+                .take(KEY_LEN)
+                .map(char::from)
+                .collect()
+        } else {
+            ClientBench::compose_ith_key(self.next_key, false)
+        };
+        self.next_key += 1;
+        let offset = self
+            .rng
+            .gen_range(0..=(self.value_pool.len() - self.params.value_size));
+        let value =
+            self.value_pool[offset..(offset + self.params.value_size)].into();
+        Command::Put { key, value }
+    }
+
+    /// Issues Puts of keys not loaded yet until the in-flight window is full
+    /// or the connection would block.
+    fn issue_window(&mut self) -> Result<(), SummersetError> {
+        loop {
+            let (cmds, req_id) = if !self.unsent.is_empty() {
+                (std::mem::take(&mut self.unsent), self.driver.issue_retry()?)
+            } else {
+                let room = self.params.max_inflight - self.inflight.len();
+                let left = self.requeued.len()
+                    + (self.params.num_keys - self.next_key);
+                let cnt = self.params.client_batch.min(room).min(left);
+                if cnt == 0 {
+                    return Ok(());
+                }
+                let mut cmds = Vec::with_capacity(cnt);
+                while cmds.len() < cnt {
+                    let cmd = match self.requeued.pop() {
+                        Some(cmd) => cmd,
+                        None => self.next_put_cmd(),
+                    };
+                    cmds.push(cmd);
+                }
+                let req_id = if cnt == 1 {
+                    self.driver.issue_cmd(cmds[0].clone())?
+                } else {
+                    self.driver.issue_batch(cmds.clone())?
+                };
+                (cmds, req_id)
+            };
+
+            match req_id {
+                Some(first) => {
+                    for (req_id, cmd) in (first..).zip(cmds) {
+                        self.inflight.insert(req_id, cmd);
+                    }
+                }
+                None => {
+                    self.unsent = cmds;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Handles a reply. Upon anything unsuccessful, reconnects and issues
+    /// all keys in flight again, as Puts of the same values are idempotent.
+    async fn handle_reply(
+        &mut self,
+        reply: DriverReply,
+    ) -> Result<(), SummersetError> {
+        match reply {
+            DriverReply::Success {
+                req_id,
+                cmd_result: CommandResult::Put { .. },
+                ..
+            } => {
+                if self.inflight.remove(&req_id).is_some() {
+                    self.loaded += 1;
+                    self.chunk_cnt += 1;
+                    self.failures = 0;
+                }
+                Ok(())
+            }
+
+            DriverReply::Success { cmd_result, .. } => {
+                // e.g., storage quota or disk space trouble, which retrying
+                // won't solve
+                logged_err!("unsuccessful load reply: {:?}", cmd_result)
+            }
+
+            DriverReply::Committed { .. } => Ok(()), // wait for execution
+
+            reply => {
+                self.failures += 1;
+                if self.failures > MAX_RETRIES {
+                    return logged_err!(
+                        "unsuccessful load reply {:?}, no retries left",
+                        reply
+                    );
+                }
+                pf_debug!("got {:?}, leave and reconnecting...", reply);
+                self.requeued.extend(self.inflight.drain().map(|(_, c)| c));
+                self.requeued.append(&mut self.unsent);
+                self.driver.leave(false).await?;
+                self.driver.connect().await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Prints loading progress since last print.
+    fn print_progress(&mut self, since: Instant) {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let rate = self.chunk_cnt as f64 / since.elapsed().as_secs_f64();
+        println!(
+            "{:>11.2} | {:>12.2} | {:>10} / {:<10} ({:>5.1}%)",
+            elapsed,
+            rate,
+            self.loaded,
+            self.params.num_keys,
+            100.0 * self.loaded as f64 / self.params.num_keys.max(1) as f64
+        );
+        self.chunk_cnt = 0;
+    }
+
+    /// Runs the data loader until all keys are loaded.
+    pub(crate) async fn run(&mut self) -> Result<(), SummersetError> {
+        self.driver.connect().await?;
+        pf_info!(
+            "loading {} keys of {} bytes...",
+            self.params.num_keys,
+            self.params.value_size
+        );
+        println!(
+            "{:^11} | {:^12} | {:^10}   {:^10}",
+            "Elapsed (s)", "Rate (keys/s)", "Loaded", "Total"
+        );
+
+        let mut ticker = time::interval(PRINT_INTERVAL);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticker.tick().await;
+        self.start = Instant::now();
+        let mut last_print = self.start;
+        while self.loaded < self.params.num_keys {
+            self.issue_window()?;
+
+            tokio::select! {
+                // prioritize receiving reply
+                biased;
+
+                reply = self.driver.wait_reply() => {
+                    self.handle_reply(reply?).await?;
+                }
+
+                _ = ticker.tick() => {
+                    self.print_progress(last_print);
+                    last_print = Instant::now();
+                }
+            }
+        }
+
+        self.print_progress(last_print);
+        let elapsed = self.start.elapsed().as_secs_f64();
+        println!(
+            "load done: {} keys in {:.2} s, {:.2} keys/s",
+            self.loaded,
+            elapsed,
+            self.loaded as f64 / elapsed
+        );
+        self.driver.leave(true).await?;
+        Ok(())
+    }
+}
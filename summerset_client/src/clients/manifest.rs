@@ -0,0 +1,518 @@
+//! Manifest client running a whole experiment described in a manifest file:
+//! repetitions of workload phases, each driven by a bench or tester client,
+//! with an optional fault script issued through the manager alongside.
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use crate::clients::{ClientBench, ClientTester};
+
+use serde::Deserialize;
+
+use tokio::time::{self, Duration, Instant};
+
+use summerset::{
+    logged_err, parsed_config, pf_error, pf_info, pf_warn, ClientCtrlStub,
+    CtrlReply, CtrlRequest, GenericEndpoint, ReplicaId, SmrProtocol,
+    SummersetError,
+};
+
+/// Mode parameters struct.
+#[derive(Debug, Deserialize)]
+pub struct ModeParamsManifest {
+    /// Path to the manifest TOML file.
+    pub manifest: String,
+
+    /// Directory to write results into.
+    pub output_dir: String,
+}
+
+impl Default for ModeParamsManifest {
+    fn default() -> Self {
+        ModeParamsManifest {
+            manifest: "".into(),
+            output_dir: "/tmp/summerset.manifest".into(),
+        }
+    }
+}
+
+/// Experiment manifest struct, read from the manifest file.
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    /// Name of the protocol the cluster runs.
+    protocol: String,
+
+    /// Number of server replicas the cluster should have.
+    population: u16,
+
+    /// Server configuration string the cluster was launched with, recorded
+    /// along with the results.
+    server_config: String,
+
+    /// Client configuration string, in the same '+'-separated form as the
+    /// `--config` argument.
+    client_config: String,
+
+    /// Number of times to run all the phases.
+    repetitions: usize,
+
+    /// Reset all servers, cleaning their durable state, before every
+    /// repetition but the first?
+    reset_between: bool,
+
+    /// Workload phases, run one after another.
+    phases: Vec<PhaseSpec>,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Manifest {
+            protocol: "".into(),
+            population: 0,
+            server_config: "".into(),
+            client_config: "".into(),
+            repetitions: 1,
+            reset_between: true,
+            phases: vec![],
+        }
+    }
+}
+
+/// Specification of a workload phase.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+struct PhaseSpec {
+    /// Name of the phase, used in output file names.
+    name: String,
+
+    /// Client utility mode driving the workload: bench|tester.
+    utility: String,
+
+    /// Mode-specific parameters string, in the same '+'-separated form as
+    /// the `--params` argument. For bench phases, `output_path` is filled
+    /// in by the runner.
+    params: String,
+
+    /// Fault script to issue while the workload runs.
+    faults: Vec<FaultStep>,
+}
+
+/// A step of a fault script.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+struct FaultStep {
+    /// Time in millisecs since the start of the phase to issue at.
+    at_ms: u64,
+
+    /// Action to issue: pause|resume|reset|snapshot|transfer.
+    action: String,
+
+    /// Comma-separated list of servers to act on. Use special letter 'a'
+    /// for all servers or 'l' for current leader. For `transfer`, the
+    /// single target server.
+    servers: String,
+}
+
+/// Outcome of a workload phase run.
+struct PhaseOutcome {
+    /// Error message if the workload failed.
+    error: Option<String>,
+
+    /// Time the phase took.
+    elapsed: Duration,
+}
+
+/// Manifest runner client struct.
+pub(crate) struct ClientManifest {
+    /// Client endpoint, of which only the control stub is used, for
+    /// querying the cluster and issuing faults.
+    endpoint: Box<dyn GenericEndpoint>,
+
+    /// Protocol the cluster runs.
+    protocol: SmrProtocol,
+
+    /// Cluster manager's client-facing address, for connecting the
+    /// workload clients.
+    manager: SocketAddr,
+
+    /// Reply timeout duration of the workload clients.
+    timeout: Duration,
+
+    /// Experiment manifest.
+    manifest: Manifest,
+
+    /// Raw text of the manifest file, copied into the output directory.
+    manifest_text: String,
+
+    /// Directory to write results into.
+    output_dir: PathBuf,
+}
+
+impl ClientManifest {
+    /// Creates a new manifest runner client.
+    pub(crate) fn new(
+        endpoint: Box<dyn GenericEndpoint>,
+        protocol: SmrProtocol,
+        manager: SocketAddr,
+        timeout: Duration,
+        params_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        let params = parsed_config!(params_str => ModeParamsManifest;
+                                    manifest, output_dir)?;
+        if params.manifest.is_empty() {
+            return logged_err!(Config; "params.manifest not given");
+        }
+        if params.output_dir.is_empty() {
+            return logged_err!(Config; "params.output_dir not given");
+        }
+
+        let manifest_text = fs::read_to_string(&params.manifest)?;
+        let manifest = parsed_config!(Some(&manifest_text[..]) => Manifest;
+                                      protocol, population, server_config,
+                                      client_config, repetitions,
+                                      reset_between, phases)?;
+        Self::validate(&manifest, protocol)?;
+
+        Ok(ClientManifest {
+            endpoint,
+            protocol,
+            manager,
+            timeout,
+            manifest,
+            manifest_text,
+            output_dir: PathBuf::from(params.output_dir),
+        })
+    }
+
+    /// Checks the manifest for errors before running anything.
+    fn validate(
+        manifest: &Manifest,
+        protocol: SmrProtocol,
+    ) -> Result<(), SummersetError> {
+        if SmrProtocol::parse_name(&manifest.protocol) != Some(protocol) {
+            return logged_err!(
+                Config;
+                "manifest protocol '{}' mismatches '{:?}'",
+                manifest.protocol, protocol
+            );
+        }
+        if manifest.repetitions == 0 {
+            return logged_err!(
+                Config;
+                "invalid manifest repetitions '{}'", manifest.repetitions
+            );
+        }
+        if manifest.phases.is_empty() {
+            return logged_err!(Config; "manifest has no phases");
+        }
+
+        for phase in &manifest.phases {
+            if phase.name.is_empty()
+                || phase.name.contains(|c: char| {
+                    !(c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                })
+            {
+                return logged_err!(
+                    Config;
+                    "invalid phase name '{}'", phase.name
+                );
+            }
+            if !matches!(&phase.utility[..], "bench" | "tester") {
+                return logged_err!(
+                    Config;
+                    "invalid utility '{}' of phase '{}'",
+                    phase.utility, phase.name
+                );
+            }
+            for step in &phase.faults {
+                if !matches!(
+                    &step.action[..],
+                    "pause" | "resume" | "reset" | "snapshot" | "transfer"
+                ) {
+                    return logged_err!(
+                        Config;
+                        "invalid fault action '{}' in phase '{}'",
+                        step.action, phase.name
+                    );
+                }
+                if step.action == "transfer"
+                    && step.servers.trim().parse::<ReplicaId>().is_err()
+                {
+                    return logged_err!(
+                        Config;
+                        "invalid transfer target '{}' in phase '{}'",
+                        step.servers, phase.name
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Queries the manager for the population and the current leader(s).
+    async fn query_cluster(
+        ctrl_stub: &mut ClientCtrlStub,
+    ) -> Result<(u16, HashSet<ReplicaId>), SummersetError> {
+        ctrl_stub.send_req_insist(&CtrlRequest::QueryInfo)?;
+        match ctrl_stub.recv_reply().await? {
+            CtrlReply::QueryInfo {
+                population,
+                servers_info,
+            } => Ok((
+                population,
+                servers_info
+                    .into_iter()
+                    .filter(|(_, info)| info.is_leader)
+                    .map(|(id, _)| id)
+                    .collect(),
+            )),
+            _ => logged_err!(Protocol; "unexpected control reply type"),
+        }
+    }
+
+    /// Parse comma-separated string of server IDs, where 'a' means all
+    /// servers (an empty set to the manager) and 'l' means current leader(s).
+    async fn parse_servers(
+        ctrl_stub: &mut ClientCtrlStub,
+        list_str: &str,
+    ) -> Result<HashSet<ReplicaId>, SummersetError> {
+        let mut servers = HashSet::new();
+        for s in list_str.trim().split(',').map(str::trim) {
+            match s {
+                "a" => return Ok(HashSet::new()),
+                "l" => servers.extend(Self::query_cluster(ctrl_stub).await?.1),
+                _ => {
+                    servers.insert(s.parse()?);
+                }
+            }
+        }
+        Ok(servers)
+    }
+
+    /// Issues one fault step through the manager and waits for its reply.
+    async fn issue_fault(
+        ctrl_stub: &mut ClientCtrlStub,
+        step: &FaultStep,
+    ) -> Result<(), SummersetError> {
+        let req = if step.action == "transfer" {
+            CtrlRequest::TransferLeader {
+                target: step.servers.trim().parse()?,
+            }
+        } else {
+            let servers = Self::parse_servers(ctrl_stub, &step.servers).await?;
+            match &step.action[..] {
+                "pause" => CtrlRequest::PauseServers { servers },
+                "resume" => CtrlRequest::ResumeServers { servers },
+                "reset" => CtrlRequest::ResetServers {
+                    servers,
+                    durable: true,
+                },
+                _ => CtrlRequest::TakeSnapshot { servers },
+            }
+        };
+        ctrl_stub.send_req_insist(&req)?;
+
+        match ctrl_stub.recv_reply().await? {
+            CtrlReply::TransferLeader { success: false } => {
+                logged_err!(Rejected; "leadership transfer refused")
+            }
+            CtrlReply::PauseServers { .. }
+            | CtrlReply::ResumeServers { .. }
+            | CtrlReply::ResetServers { .. }
+            | CtrlReply::TakeSnapshot { .. }
+            | CtrlReply::TransferLeader { .. } => Ok(()),
+            _ => logged_err!(Protocol; "unexpected control reply type"),
+        }
+    }
+
+    /// Runs a fault script from `start` on, returning a log line per step.
+    /// A failed step is logged and does not stop the script.
+    async fn run_faults(
+        ctrl_stub: &mut ClientCtrlStub,
+        faults: &[FaultStep],
+        start: Instant,
+    ) -> Vec<String> {
+        let mut steps: Vec<&FaultStep> = faults.iter().collect();
+        steps.sort_by_key(|step| step.at_ms);
+
+        let mut log = Vec::with_capacity(steps.len());
+        for step in steps {
+            time::sleep_until(start + Duration::from_millis(step.at_ms)).await;
+            pf_info!("fault {} on '{}'", step.action, step.servers);
+            let result = match Self::issue_fault(ctrl_stub, step).await {
+                Ok(()) => "ok".into(),
+                Err(e) => format!("error: {}", e),
+            };
+            log.push(format!(
+                "{} {} {} {}",
+                start.elapsed().as_millis(),
+                step.action,
+                step.servers,
+                result
+            ));
+        }
+        log
+    }
+
+    /// Runs one workload phase along with its fault script, writing bench
+    /// output and the fault log into `rep_dir`.
+    async fn run_phase(
+        &mut self,
+        rep_dir: &Path,
+        idx: usize,
+    ) -> Result<PhaseOutcome, SummersetError> {
+        let phase = self.manifest.phases[idx].clone();
+        let stem = format!("{}-{}", idx, phase.name);
+        let config_str = self.manifest.client_config.replace('+', "\n");
+        let config_str = (!config_str.is_empty()).then_some(&config_str[..]);
+        let mut params: toml::Table =
+            phase.params.replace('+', "\n").parse()?;
+
+        let endpoint = self
+            .protocol
+            .new_client_endpoint(self.manager, config_str)
+            .await?;
+        let ctrl_stub = self.endpoint.ctrl_stub();
+        let start = Instant::now();
+        let (result, fault_log) = match &phase.utility[..] {
+            "bench" => {
+                let output_path = rep_dir.join(format!("{}.out", stem));
+                params.insert(
+                    "output_path".into(),
+                    output_path.to_string_lossy().into_owned().into(),
+                );
+                let params_str = toml::to_string(&params)?;
+                let mut bench = ClientBench::new(
+                    endpoint,
+                    self.timeout,
+                    Some(&params_str),
+                )?;
+                tokio::join!(
+                    bench.run(),
+                    Self::run_faults(ctrl_stub, &phase.faults, start)
+                )
+            }
+            _ => {
+                let params_str = toml::to_string(&params)?;
+                let mut tester = ClientTester::new(
+                    endpoint,
+                    self.timeout,
+                    None,
+                    0,
+                    Some(&params_str),
+                )?;
+                tokio::join!(
+                    tester.run(),
+                    Self::run_faults(ctrl_stub, &phase.faults, start)
+                )
+            }
+        };
+        let elapsed = start.elapsed();
+
+        if !fault_log.is_empty() {
+            let mut file =
+                File::create(rep_dir.join(format!("{}.faults", stem)))?;
+            for line in fault_log {
+                writeln!(file, "{}", line)?;
+            }
+        }
+        Ok(PhaseOutcome {
+            error: result.err().map(|e| e.to_string()),
+            elapsed,
+        })
+    }
+
+    /// Runs all repetitions of the experiment, writing results to the output
+    /// directory:
+    ///   - `manifest.toml`: copy of the manifest
+    ///   - `rep<r>/<i>-<phase>.out`: bench output of each bench phase
+    ///   - `rep<r>/<i>-<phase>.faults`: log of each fault script run
+    ///   - `results.csv`: outcome and duration of each phase run
+    pub(crate) async fn run(&mut self) -> Result<(), SummersetError> {
+        let (population, _) =
+            Self::query_cluster(self.endpoint.ctrl_stub()).await?;
+        if population != self.manifest.population {
+            return logged_err!(
+                Config;
+                "cluster population {} mismatches manifest {}",
+                population, self.manifest.population
+            );
+        }
+
+        if fs::exists(&self.output_dir)? {
+            pf_warn!(
+                "writing into existing output dir '{}'",
+                self.output_dir.display()
+            );
+        }
+        fs::create_dir_all(&self.output_dir)?;
+        pf_info!(
+            "running {} on {} servers launched with config '{}'",
+            self.manifest.protocol,
+            population,
+            self.manifest.server_config
+        );
+        fs::write(self.output_dir.join("manifest.toml"), &self.manifest_text)?;
+        let mut results = File::create(self.output_dir.join("results.csv"))?;
+        writeln!(results, "rep,phase,utility,outcome,elapsed_s")?;
+
+        let mut num_failed = 0;
+        for rep in 0..self.manifest.repetitions {
+            if rep > 0 && self.manifest.reset_between {
+                pf_info!("resetting all servers...");
+                let ctrl_stub = self.endpoint.ctrl_stub();
+                ctrl_stub.send_req_insist(&CtrlRequest::ResetServers {
+                    servers: HashSet::new(),
+                    durable: false,
+                })?;
+                if !matches!(
+                    ctrl_stub.recv_reply().await?,
+                    CtrlReply::ResetServers { .. }
+                ) {
+                    return logged_err!(
+                        Protocol;
+                        "unexpected control reply type"
+                    );
+                }
+            }
+
+            let rep_dir = self.output_dir.join(format!("rep{}", rep));
+            fs::create_dir_all(&rep_dir)?;
+            for idx in 0..self.manifest.phases.len() {
+                let (name, utility) = {
+                    let phase = &self.manifest.phases[idx];
+                    (phase.name.clone(), phase.utility.clone())
+                };
+                pf_info!("repetition {} phase {} '{}'...", rep, idx, name);
+                let outcome = self.run_phase(&rep_dir, idx).await?;
+                let outcome_str = match outcome.error {
+                    None => "ok".into(),
+                    Some(e) => {
+                        pf_error!("phase '{}' failed: {}", name, e);
+                        num_failed += 1;
+                        format!("\"failed: {}\"", e.replace('"', "'"))
+                    }
+                };
+                writeln!(
+                    results,
+                    "{},{},{},{},{:.3}",
+                    rep,
+                    name,
+                    utility,
+                    outcome_str,
+                    outcome.elapsed.as_secs_f64()
+                )?;
+            }
+        }
+
+        pf_info!("results written to '{}'", self.output_dir.display());
+        self.endpoint.leave(true).await?;
+        if num_failed > 0 {
+            return logged_err!("{} phase run(s) failed", num_failed);
+        }
+        Ok(())
+    }
+}
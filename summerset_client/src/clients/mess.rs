@@ -6,7 +6,7 @@ use crate::drivers::{DriverClosedLoop, DriverReply};
 
 use serde::Deserialize;
 
-use tokio::time::Duration;
+use tokio::time::{self, Duration};
 
 use summerset::{
     logged_err, parsed_config, pf_error, pf_info, Bitmap, CommandResult,
@@ -76,6 +76,8 @@ impl ClientMess {
     pub(crate) fn new(
         endpoint: Box<dyn GenericEndpoint>,
         timeout: Duration,
+        hedge_delay: Option<Duration>,
+        chunk_size: usize,
         params_str: Option<&str>,
     ) -> Result<Self, SummersetError> {
         let params = parsed_config!(params_str => ModeParamsMess;
@@ -83,7 +85,12 @@ impl ClientMess {
                                       key_range, responder, write)?;
 
         Ok(ClientMess {
-            driver: DriverClosedLoop::new(endpoint, timeout),
+            driver: DriverClosedLoop::new(
+                endpoint,
+                timeout,
+                hedge_delay,
+                chunk_size,
+            ),
             params,
             servers_info: None,
         })
@@ -184,7 +191,7 @@ impl ClientMess {
                     population,
                     servers_info,
                 } => {
-                    debug_assert_eq!(servers_info.len() as u8, population);
+                    debug_assert_eq!(servers_info.len() as u16, population);
                     self.servers_info = Some(servers_info);
                 }
                 _ => return logged_err!("unexpected control reply type"),
@@ -262,10 +269,29 @@ impl ClientMess {
                 DriverReply::Redirect { .. } => {
                     // retry
                 }
+                DriverReply::Rejected { code } if code.retryable() => {
+                    time::sleep(Duration::from_millis(100)).await;
+                    // retry
+                }
                 DriverReply::Success { cmd_result, .. } => match cmd_result {
                     CommandResult::Put { .. } => {
                         return Ok(());
                     }
+                    CommandResult::QuotaExceeded { namespace } => {
+                        return logged_err!(
+                            "namespace '{}' over storage quota",
+                            namespace
+                        );
+                    }
+                    CommandResult::DiskFull { free_bytes } => {
+                        return logged_err!(
+                            "server WAL disk full, {} bytes free",
+                            free_bytes
+                        );
+                    }
+                    CommandResult::ChecksumMismatch => {
+                        return logged_err!("command corrupted on its way");
+                    }
                     _ => {
                         return logged_err!("unexpected command result type");
                     }
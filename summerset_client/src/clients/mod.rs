@@ -12,6 +12,27 @@ pub(crate) use tester::ClientTester;
 mod mess;
 pub(crate) use mess::ClientMess;
 
+mod observe;
+pub(crate) use observe::ClientObserve;
+
+mod learner;
+pub(crate) use learner::ClientLearner;
+
+mod autotune;
+pub(crate) use autotune::ClientAutotune;
+
+mod manifest;
+pub(crate) use manifest::ClientManifest;
+
+mod compare;
+pub(crate) use compare::ClientCompare;
+
+mod canary;
+pub(crate) use canary::ClientCanary;
+
+mod load;
+pub(crate) use load::ClientLoad;
+
 /// Enum of supported client utility modes.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub(crate) enum ClientMode {
@@ -19,6 +40,13 @@ pub(crate) enum ClientMode {
     Bench,
     Tester,
     Mess,
+    Observe,
+    Learner,
+    Autotune,
+    Manifest,
+    Compare,
+    Canary,
+    Load,
 }
 
 impl ClientMode {
@@ -29,6 +57,13 @@ impl ClientMode {
             "bench" => Some(Self::Bench),
             "tester" => Some(Self::Tester),
             "mess" => Some(Self::Mess),
+            "observe" => Some(Self::Observe),
+            "learner" => Some(Self::Learner),
+            "autotune" => Some(Self::Autotune),
+            "manifest" => Some(Self::Manifest),
+            "compare" => Some(Self::Compare),
+            "canary" => Some(Self::Canary),
+            "load" => Some(Self::Load),
             _ => None,
         }
     }
@@ -53,6 +88,13 @@ mod name_tests {
         valid_name_test!(Bench);
         valid_name_test!(Tester);
         valid_name_test!(Mess);
+        valid_name_test!(Observe);
+        valid_name_test!(Learner);
+        valid_name_test!(Autotune);
+        valid_name_test!(Manifest);
+        valid_name_test!(Compare);
+        valid_name_test!(Canary);
+        valid_name_test!(Load);
     }
 
     #[test]
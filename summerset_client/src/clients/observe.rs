@@ -0,0 +1,67 @@
+//! Observer client printing the manager's control-plane event stream.
+
+use serde::Deserialize;
+
+use summerset::{
+    parsed_config, pf_info, CtrlEventRecord, GenericEndpoint, SummersetError,
+};
+
+/// Mod parameters struct.
+#[derive(Debug, Deserialize)]
+pub struct ModeParamsObserve {
+    /// Also print past events with sequence number >= this, if still kept
+    /// in the manager's memory.
+    pub since: u64,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for ModeParamsObserve {
+    fn default() -> Self {
+        ModeParamsObserve { since: 0 }
+    }
+}
+
+/// Observer client struct.
+pub(crate) struct ClientObserve {
+    /// Client endpoint, of which only the control stub is used.
+    endpoint: Box<dyn GenericEndpoint>,
+
+    /// Mode parameters struct.
+    params: ModeParamsObserve,
+}
+
+impl ClientObserve {
+    /// Creates a new observer client.
+    pub(crate) fn new(
+        endpoint: Box<dyn GenericEndpoint>,
+        params_str: Option<&str>,
+    ) -> Result<Self, SummersetError> {
+        let params = parsed_config!(params_str => ModeParamsObserve; since)?;
+
+        Ok(ClientObserve { endpoint, params })
+    }
+
+    /// Prints events as JSON lines to stdout, so that external tools can
+    /// consume them through a pipe.
+    fn print_events(
+        events: Vec<CtrlEventRecord>,
+    ) -> Result<(), SummersetError> {
+        for record in events {
+            println!("{}", serde_json::to_string(&record)?);
+        }
+        Ok(())
+    }
+
+    /// Runs the observer client until the manager connection ends.
+    pub(crate) async fn run(&mut self) -> Result<(), SummersetError> {
+        let ctrl_stub = self.endpoint.ctrl_stub();
+        let past = ctrl_stub.subscribe_events(self.params.since).await?;
+        pf_info!("subscribed to manager events since {}", self.params.since);
+        Self::print_events(past)?;
+
+        loop {
+            let events = ctrl_stub.recv_events().await?;
+            Self::print_events(events)?;
+        }
+    }
+}
@@ -1,6 +1,7 @@
 //! Interactive REPL-style command-line interface client.
 
 use std::collections::HashSet;
+use std::fs;
 use std::io::{self, Write};
 use std::str::SplitWhitespace;
 
@@ -8,11 +9,12 @@ use crate::drivers::{DriverClosedLoop, DriverReply};
 
 use color_print::{cprint, cprintln};
 
-use tokio::time::Duration;
+use tokio::time::{self, Duration};
 
 use summerset::{
-    logged_err, pf_error, Bitmap, Command, ConfChange, CtrlReply, CtrlRequest,
-    GenericEndpoint, ReplicaId, SummersetError,
+    logged_err, parsed_config, pf_error, Bitmap, ChaosParams, ChaosPlan,
+    Command, CommandResult, ConfChange, CtrlReply, CtrlRequest,
+    GenericEndpoint, ReadConsistency, ReplicaId, SummersetError,
 };
 
 /// Prompt string at the start of line.
@@ -23,12 +25,31 @@ enum ReplCommand {
     /// Normal state machine replication command.
     Normal(Command),
 
+    /// Get at a chosen consistency level.
+    Read {
+        key: String,
+        consistency: ReadConsistency,
+    },
+
+    /// Blocking lock acquisition with a hold TTL in millisecs.
+    Acquire { key: String, ttl_ms: u64 },
+
+    /// Lock release.
+    Release { key: String },
+
     /// Responders configuration change request. (only for relevant protocols)
     Conf(ConfChange),
 
     /// Control request to the manager.
     Control(CtrlRequest),
 
+    /// Chaos mode start request, saving the fault schedule to a file if
+    /// given.
+    StartChaos {
+        plan: ChaosPlan,
+        save_to: Option<String>,
+    },
+
     /// Reconnect to the service.
     Reconnect,
 
@@ -59,9 +80,16 @@ impl ClientRepl {
     pub(crate) fn new(
         endpoint: Box<dyn GenericEndpoint>,
         timeout: Duration,
+        hedge_delay: Option<Duration>,
+        chunk_size: usize,
     ) -> Self {
         ClientRepl {
-            driver: DriverClosedLoop::new(endpoint, timeout),
+            driver: DriverClosedLoop::new(
+                endpoint,
+                timeout,
+                hedge_delay,
+                chunk_size,
+            ),
             timeout,
             input_buf: String::new(),
         }
@@ -80,8 +108,12 @@ impl ClientRepl {
             cprintln!("<bright-red>✗</> {}", e);
         }
         println!("HELP: Commands for normal operations:");
-        println!("          get <key>");
+        println!("          get <key> [local|leader|quorum|pqr]");
+        println!("          scan <start_key> <end_key|-> [limit]");
         println!("          put <key> <value>");
+        println!("          cas <key> [expect] <value>");
+        println!("          acquire <lock> <ttl_ms>");
+        println!("          release <lock>");
         println!("          help");
         println!("          exit");
         println!("      Commands for responders conf change:");
@@ -89,16 +121,70 @@ impl ClientRepl {
         println!("          responder <range> [servers]");
         println!("      Commands for control/testing:");
         println!("          reconnect");
+        println!("          status");
+        println!("          events [since]");
         println!("          reset [servers]");
         println!("          pause [servers]");
         println!("          resume [servers]");
         println!("          snapshot [servers]");
+        println!("          backup");
+        println!("          loglevel <filters> [servers]");
+        println!("          keys <server> [cursor|-] [limit]");
+        println!("          members <servers>");
+        println!("          reconfig <+server|-server ...>");
+        println!("          transfer <server>");
+        println!("          chaos start [params] [save_file]");
+        println!("          chaos replay <schedule_file>");
+        println!("          chaos stop");
+        println!("          split <range> <at> [servers]");
+        println!("          merge <range> <range> [servers]");
         println!(
             "      Keys and values currently cannot contain any whitespaces"
         );
         io::stdout().flush().unwrap();
     }
 
+    /// Parses the rest of a chaos command.
+    fn parse_chaos_command(
+        segs: &mut SplitWhitespace,
+    ) -> Result<ReplCommand, SummersetError> {
+        match &Self::expect_next_seg(segs)?.to_lowercase()[..] {
+            "start" => {
+                // params given as TOML fields joined by '+'
+                let params_str = segs.next().map(|s| s.replace('+', "\n"));
+                let params = parsed_config!(
+                    params_str.as_deref() => ChaosParams;
+                    seed, duration_ms, crash_gap_ms, partition_gap_ms,
+                    delay_gap_ms, transfer_gap_ms, net_fault_ms, delay_ms
+                )?;
+                Ok(ReplCommand::StartChaos {
+                    plan: ChaosPlan::Random(params),
+                    save_to: segs.next().map(String::from),
+                })
+            }
+
+            "replay" => {
+                let path = Self::expect_only_seg(segs)?;
+                let steps = serde_json::from_str(&fs::read_to_string(path)?)?;
+                Ok(ReplCommand::StartChaos {
+                    plan: ChaosPlan::Replay(steps),
+                    save_to: None,
+                })
+            }
+
+            "stop" => Ok(ReplCommand::Control(CtrlRequest::StopChaos)),
+
+            sub_cmd => {
+                let err = SummersetError::msg(format!(
+                    "unrecognized chaos subcommand: {}",
+                    sub_cmd
+                ));
+                Self::print_help(Some(&err));
+                Err(err)
+            }
+        }
+    }
+
     /// Expect to get the next segment string from parsed segs.
     #[inline]
     fn expect_next_seg<'s>(
@@ -169,6 +255,17 @@ impl ClientRepl {
         }
     }
 
+    /// Parse a segment that must be a specified keys range `ka-kb`.
+    fn expect_key_range(
+        segs: &mut SplitWhitespace,
+    ) -> Result<(String, String), SummersetError> {
+        let range_str = Self::expect_next_seg(segs)?;
+        match Self::parse_conf_key_range(range_str)? {
+            Some(Some(range)) => Ok(range),
+            _ => logged_err!("expect a specified key_range: {}", range_str),
+        }
+    }
+
     /// Reads in user input and parses into a command.
     async fn read_command(&mut self) -> Result<ReplCommand, SummersetError> {
         self.input_buf.clear();
@@ -193,7 +290,42 @@ impl ClientRepl {
             "get" => {
                 // keys are kept as-is, no case conversions
                 let key = Self::expect_next_seg(&mut segs)?;
-                Ok(ReplCommand::Normal(Command::Get { key: key.into() }))
+                match segs.next() {
+                    None => Ok(ReplCommand::Normal(Command::Get {
+                        key: key.into(),
+                    })),
+                    Some(level) => {
+                        let consistency = ReadConsistency::parse_name(level)
+                            .ok_or(SummersetError::msg(format!(
+                                "invalid consistency level '{}'",
+                                level
+                            )))?;
+                        Ok(ReplCommand::Read {
+                            key: key.into(),
+                            consistency,
+                        })
+                    }
+                }
+            }
+
+            "scan" => {
+                // an end key of '-' means no upper bound
+                let start_key = Self::expect_next_seg(&mut segs)?;
+                let end_key = Self::expect_next_seg(&mut segs)?;
+                let limit = match segs.next() {
+                    None => 0,
+                    Some(_) if segs.clone().next().is_some() => {
+                        let err = SummersetError::msg("too many args");
+                        Self::print_help(Some(&err));
+                        return Err(err);
+                    }
+                    Some(limit) => limit.parse::<usize>()?,
+                };
+                Ok(ReplCommand::Normal(Command::Scan {
+                    start_key: start_key.into(),
+                    end_key: if end_key == "-" { "" } else { end_key }.into(),
+                    limit,
+                }))
             }
 
             "put" => {
@@ -206,6 +338,38 @@ impl ClientRepl {
                 }))
             }
 
+            "cas" => {
+                // without an expected value, the key must be absent
+                let key = Self::expect_next_seg(&mut segs)?;
+                let first = Self::expect_next_seg(&mut segs)?;
+                let (expect, value) = if segs.clone().next().is_none() {
+                    (None, first)
+                } else {
+                    (Some(first.into()), Self::expect_only_seg(&mut segs)?)
+                };
+                Ok(ReplCommand::Normal(Command::Cas {
+                    key: key.into(),
+                    expect,
+                    value: value.into(),
+                }))
+            }
+
+            "acquire" => {
+                // lock names are kept as-is, no case conversions
+                let key = Self::expect_next_seg(&mut segs)?;
+                let ttl_str = Self::expect_only_seg(&mut segs)?;
+                let ttl_ms = ttl_str.parse::<u64>()?;
+                Ok(ReplCommand::Acquire {
+                    key: key.into(),
+                    ttl_ms,
+                })
+            }
+
+            "release" => {
+                let key = Self::expect_only_seg(&mut segs)?;
+                Ok(ReplCommand::Release { key: key.into() })
+            }
+
             "help" => Ok(ReplCommand::PrintHelp),
 
             "reconnect" => Ok(ReplCommand::Reconnect),
@@ -246,6 +410,16 @@ impl ClientRepl {
                 Ok(ReplCommand::Conf(delta))
             }
 
+            "status" => Ok(ReplCommand::Control(CtrlRequest::QueryStatus)),
+
+            "events" => {
+                let since = match segs.next() {
+                    Some(seg) => seg.parse::<u64>()?,
+                    None => 0,
+                };
+                Ok(ReplCommand::Control(CtrlRequest::QueryEvents { since }))
+            }
+
             "reset" => {
                 let servers = Self::drain_server_ids(&mut segs)?;
                 Ok(ReplCommand::Control(CtrlRequest::ResetServers {
@@ -269,6 +443,109 @@ impl ClientRepl {
                 Ok(ReplCommand::Control(CtrlRequest::TakeSnapshot { servers }))
             }
 
+            "backup" => Ok(ReplCommand::Control(CtrlRequest::CoordSnapshot)),
+
+            "loglevel" => {
+                let filters = Self::expect_next_seg(&mut segs)?.to_string();
+                let servers = Self::drain_server_ids(&mut segs)?;
+                Ok(ReplCommand::Control(CtrlRequest::SetLogFilters {
+                    servers,
+                    filters,
+                }))
+            }
+
+            "keys" => {
+                // a cursor of '-' means from the first key, and no limit
+                // means counting keys only
+                let server_str = Self::expect_next_seg(&mut segs)?;
+                let server = server_str.parse::<ReplicaId>()?;
+                let cursor = match segs.next() {
+                    None | Some("-") => String::new(),
+                    Some(cursor) => cursor.into(),
+                };
+                let limit = match segs.next() {
+                    None => 0,
+                    Some(_) if segs.clone().next().is_some() => {
+                        let err = SummersetError::msg("too many args");
+                        Self::print_help(Some(&err));
+                        return Err(err);
+                    }
+                    Some(limit) => limit.parse::<usize>()?,
+                };
+                Ok(ReplCommand::Control(CtrlRequest::QueryKeys {
+                    server,
+                    cursor,
+                    limit,
+                }))
+            }
+
+            "members" => {
+                let members = Self::drain_server_ids(&mut segs)?;
+                if members.is_empty() {
+                    let err = SummersetError::msg("members list is empty");
+                    Self::print_help(Some(&err));
+                    return Err(err);
+                }
+                Ok(ReplCommand::Control(CtrlRequest::ChangeMembers { members }))
+            }
+
+            "reconfig" => {
+                let (mut add, mut remove) = (HashSet::new(), HashSet::new());
+                for seg in segs {
+                    if let Some(id) = seg.strip_prefix('+') {
+                        add.insert(id.parse::<ReplicaId>()?);
+                    } else if let Some(id) = seg.strip_prefix('-') {
+                        remove.insert(id.parse::<ReplicaId>()?);
+                    } else {
+                        let err = SummersetError::msg(format!(
+                            "reconfig item '{}' not +id or -id",
+                            seg
+                        ));
+                        Self::print_help(Some(&err));
+                        return Err(err);
+                    }
+                }
+                Ok(ReplCommand::Control(CtrlRequest::Reconfigure {
+                    add,
+                    remove,
+                }))
+            }
+
+            "transfer" => {
+                let target_str = Self::expect_only_seg(&mut segs)?;
+                let target = target_str.parse::<ReplicaId>()?;
+                Ok(ReplCommand::Control(CtrlRequest::TransferLeader { target }))
+            }
+
+            "chaos" => Self::parse_chaos_command(&mut segs),
+
+            "split" => {
+                let range = Self::expect_key_range(&mut segs)?;
+                let at = Self::expect_next_seg(&mut segs)?.to_string();
+                let servers = Self::drain_server_ids(&mut segs)?;
+                Ok(ReplCommand::Control(CtrlRequest::SplitRange {
+                    range,
+                    at,
+                    responders: Bitmap::from((
+                        self.driver.population(),
+                        servers,
+                    )),
+                }))
+            }
+
+            "merge" => {
+                let left = Self::expect_key_range(&mut segs)?;
+                let right = Self::expect_key_range(&mut segs)?;
+                let servers = Self::drain_server_ids(&mut segs)?;
+                let responders = (!servers.is_empty())
+                    .then(|| Bitmap::from((self.driver.population(), servers)));
+                Ok(ReplCommand::Control(CtrlRequest::MergeRange {
+                    left,
+                    right,
+                    responders,
+                }))
+            }
+
             "exit" => Ok(ReplCommand::Exit),
 
             _ => {
@@ -289,15 +566,96 @@ impl ClientRepl {
     ) -> Result<DriverReply, SummersetError> {
         match cmd {
             Command::Get { key } => Ok(self.driver.get(&key).await?),
+            Command::Scan {
+                start_key,
+                end_key,
+                limit,
+            } => Ok(self.driver.scan(&start_key, &end_key, limit).await?),
             Command::Put { key, value } => {
                 Ok(self.driver.put(&key, &value).await?)
             }
+            Command::Cas { key, expect, value } => {
+                Ok(self.driver.cas(&key, expect.as_deref(), &value).await?)
+            }
+            Command::Acquire { key, ttl_ms, .. } => {
+                Ok(self.driver.acquire(&key, ttl_ms).await?)
+            }
+            Command::Release { key, .. } => {
+                Ok(self.driver.release(&key).await?)
+            }
+        }
+    }
+
+    /// Acquires the lock, blocking until it gets granted. While queued, keeps
+    /// re-acquiring often enough that a hold granted to me on someone else's
+    /// release or expiry does not expire before I learn about it.
+    async fn acquire_blocking(
+        &mut self,
+        key: &str,
+        ttl_ms: u64,
+    ) -> Result<DriverReply, SummersetError> {
+        let poll_interval = Duration::from_millis((ttl_ms / 2).clamp(1, 100));
+        let mut waiting = false;
+        loop {
+            let result = self.driver.acquire(key, ttl_ms).await?;
+            if let DriverReply::Success {
+                cmd_result:
+                    CommandResult::Acquire {
+                        granted: false,
+                        holder,
+                    },
+                ..
+            } = result
+            {
+                if !waiting {
+                    cprintln!("<bright-blue>#</> waiting on holder {}", holder);
+                    waiting = true;
+                }
+                time::sleep(poll_interval).await;
+            } else {
+                return Ok(result);
+            }
         }
     }
 
     /// Prints command execution result.
     fn print_result(&mut self, result: DriverReply) {
         match result {
+            DriverReply::Success {
+                req_id,
+                cmd_result: CommandResult::QuotaExceeded { namespace },
+                ..
+            } => {
+                cprintln!(
+                    "<bright-red>✗</> ({}) namespace '{}' over storage quota",
+                    req_id,
+                    namespace
+                );
+            }
+
+            DriverReply::Success {
+                req_id,
+                cmd_result: CommandResult::DiskFull { free_bytes },
+                ..
+            } => {
+                cprintln!(
+                    "<bright-red>✗</> ({}) WAL disk full, {} bytes free",
+                    req_id,
+                    free_bytes
+                );
+            }
+
+            DriverReply::Success {
+                req_id,
+                cmd_result: CommandResult::ChecksumMismatch,
+                ..
+            } => {
+                cprintln!(
+                    "<bright-red>✗</> ({}) command corrupted on its way",
+                    req_id
+                );
+            }
+
             DriverReply::Success {
                 req_id,
                 cmd_result,
@@ -326,6 +684,13 @@ impl ClientRepl {
                 }
             }
 
+            DriverReply::Rejected { code } => {
+                cprintln!(
+                    "<bright-red>✗</> service rejected request: {}",
+                    code
+                );
+            }
+
             DriverReply::Failure => {
                 cprintln!("<bright-red>✗</> service replied unknown error");
             }
@@ -360,6 +725,77 @@ impl ClientRepl {
     /// Prints control request reply.
     fn print_ctrl_reply(&mut self, reply: CtrlReply) {
         match reply {
+            CtrlReply::QueryStatus { servers_status } => {
+                let mut servers: Vec<_> = servers_status.into_iter().collect();
+                servers.sort_by_key(|(s, _)| *s);
+                for (s, status) in servers {
+                    cprintln!(
+                        "<bright-blue>#</> server {} bal {} commit {} exec {} \
+                         alive {:?}",
+                        s,
+                        status.ballot,
+                        status.commit_bar,
+                        status.exec_bar,
+                        status.peers_alive
+                    );
+                    cprintln!(
+                        "    resources cpu {}% rss {} KiB fds {} wal {} KiB \
+                         free {} MiB",
+                        status.resources.cpu_pct,
+                        status.resources.rss_bytes / 1024,
+                        status.resources.open_fds,
+                        status.resources.wal_dir_bytes / 1024,
+                        status.resources.disk_free_bytes / (1024 * 1024)
+                    );
+                    if !status.hot_keys.is_empty() {
+                        cprintln!(
+                            "    hot keys {}",
+                            status
+                                .hot_keys
+                                .iter()
+                                .map(|(k, c)| format!("{}:{}", k, c))
+                                .collect::<Vec<_>>()
+                                .join(" ")
+                        );
+                    }
+                    if !status.queue_depths.is_empty() {
+                        cprintln!(
+                            "    queues {}",
+                            status
+                                .queue_depths
+                                .iter()
+                                .map(|(q, d, p)| format!("{}:{}/{}", q, d, p))
+                                .collect::<Vec<_>>()
+                                .join(" ")
+                        );
+                    }
+                    if !status.perf_models.is_empty() {
+                        let mut models: Vec<_> =
+                            status.perf_models.into_iter().collect();
+                        models.sort_by_key(|(p, _)| *p);
+                        cprintln!(
+                            "    perf models {}",
+                            models
+                                .iter()
+                                .map(|(p, m)| format!("{}:{}", p, m))
+                                .collect::<Vec<_>>()
+                                .join(" ")
+                        );
+                    }
+                }
+            }
+
+            CtrlReply::QueryEvents { events } => {
+                for record in events {
+                    cprintln!(
+                        "<bright-blue>#</> [{}] @{} {:?}",
+                        record.seq,
+                        record.unix_ms,
+                        record.event
+                    );
+                }
+            }
+
             CtrlReply::ResetServers { servers } => {
                 cprintln!("<bright-blue>#</> reset servers {:?}", servers);
             }
@@ -372,6 +808,27 @@ impl ClientRepl {
                 cprintln!("<bright-blue>#</> resumed servers {:?}", servers);
             }
 
+            CtrlReply::SetLogFilters { servers } => {
+                cprintln!("<bright-blue>#</> log filters set on {:?}", servers);
+            }
+
+            CtrlReply::QueryKeys { report } => match report {
+                Some(report) => {
+                    cprintln!(
+                        "<bright-blue>#</> {} keys {} bytes",
+                        report.num_keys,
+                        report.total_bytes
+                    );
+                    for key in report.keys {
+                        println!("    {}", key);
+                    }
+                    if let Some(cursor) = report.next_cursor {
+                        cprintln!("<bright-blue>#</> more after {}", cursor);
+                    }
+                }
+                None => cprintln!("<bright-red>✗</> keys query unanswered"),
+            },
+
             CtrlReply::TakeSnapshot { snapshot_up_to } => {
                 cprintln!(
                     "<bright-blue>#</> servers snapshot up to {:?}",
@@ -379,6 +836,75 @@ impl ClientRepl {
                 );
             }
 
+            CtrlReply::CoordSnapshot { result } => match result {
+                Some((slot, paths)) => cprintln!(
+                    "<bright-blue>#</> servers snapshot at slot {}: {:?}",
+                    slot,
+                    paths
+                ),
+                None => {
+                    cprintln!("<bright-red>✗</> coordinated snapshot rejected")
+                }
+            },
+
+            CtrlReply::ChangeMembers { result } => match result {
+                Some((members, slot)) => cprintln!(
+                    "<bright-blue>#</> members changed to {:?} at slot {}",
+                    members,
+                    slot
+                ),
+                None => {
+                    cprintln!("<bright-red>✗</> members change rejected")
+                }
+            },
+
+            CtrlReply::Reconfigure { result } => match result {
+                Some((members, slot)) => cprintln!(
+                    "<bright-blue>#</> reconfigured to {:?} at slot {}",
+                    members,
+                    slot
+                ),
+                None => {
+                    cprintln!("<bright-red>✗</> reconfiguration rejected")
+                }
+            },
+
+            CtrlReply::TransferLeader { success } => {
+                if success {
+                    cprintln!("<bright-blue>#</> leadership transferred");
+                } else {
+                    cprintln!("<bright-red>✗</> leadership transfer rejected");
+                }
+            }
+
+            CtrlReply::StartChaos { schedule } => match schedule {
+                Some(schedule) => cprintln!(
+                    "<bright-blue>#</> chaos mode on: {} steps",
+                    schedule.len()
+                ),
+                None => cprintln!("<bright-red>✗</> chaos mode already on"),
+            },
+
+            CtrlReply::StopChaos => {
+                cprintln!("<bright-blue>#</> chaos mode off");
+            }
+
+            CtrlReply::SplitRange { conf_num } => match conf_num {
+                Some(conf_num) => cprintln!(
+                    "<bright-blue>#</> range split in conf {}",
+                    conf_num
+                ),
+                None => cprintln!("<bright-red>✗</> range split rejected"),
+            },
+
+            CtrlReply::MergeRange { conf_num } => match conf_num {
+                Some(conf_num) => cprintln!(
+                    "<bright-blue>#</> ranges merged in conf {}",
+                    conf_num
+                ),
+                None => cprintln!("<bright-red>✗</> range merge rejected"),
+            },
+
             _ => {
                 cprintln!("<bright-red>✗</> unexpected ctrl reply type");
             }
@@ -417,6 +943,42 @@ impl ClientRepl {
                 Ok(true)
             }
 
+            ReplCommand::Read { key, consistency } => {
+                let result =
+                    self.driver.get_at(&key, Some(consistency)).await?;
+                let served = matches!(result, DriverReply::Success { .. });
+                self.print_result(result);
+                if served {
+                    match self.driver.last_read_path() {
+                        Some(path) => {
+                            cprintln!("<bright-blue>#</> served via {}", path)
+                        }
+                        None => {
+                            cprintln!("<bright-blue>#</> read path not stated")
+                        }
+                    }
+                    if let Some(hlc) = self.driver.last_read_hlc() {
+                        cprintln!(
+                            "<bright-blue>#</> replica applied up to hlc {}",
+                            hlc
+                        );
+                    }
+                }
+                Ok(true)
+            }
+
+            ReplCommand::Acquire { key, ttl_ms } => {
+                let result = self.acquire_blocking(&key, ttl_ms).await?;
+                self.print_result(result);
+                Ok(true)
+            }
+
+            ReplCommand::Release { key } => {
+                let result = self.driver.release(&key).await?;
+                self.print_result(result);
+                Ok(true)
+            }
+
             ReplCommand::Conf(delta) => {
                 let result = self.driver.conf(delta).await?;
                 self.print_result(result);
@@ -428,6 +990,27 @@ impl ClientRepl {
                 self.print_ctrl_reply(reply);
                 Ok(true)
             }
+
+            ReplCommand::StartChaos { plan, save_to } => {
+                let reply = self
+                    .make_ctrl_req(CtrlRequest::StartChaos { plan })
+                    .await?;
+                if let (
+                    CtrlReply::StartChaos {
+                        schedule: Some(schedule),
+                    },
+                    Some(path),
+                ) = (&reply, save_to)
+                {
+                    fs::write(&path, serde_json::to_string_pretty(schedule)?)?;
+                    cprintln!(
+                        "<bright-blue>#</> fault schedule saved to {}",
+                        path
+                    );
+                }
+                self.print_ctrl_reply(reply);
+                Ok(true)
+            }
         }
     }
 
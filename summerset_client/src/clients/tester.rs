@@ -15,7 +15,7 @@ use rand::Rng;
 
 use serde::Deserialize;
 
-use tokio::time::{self, Duration};
+use tokio::time::{self, Duration, Instant};
 
 use summerset::{
     logged_err, parsed_config, pf_debug, pf_error, CommandResult, CtrlReply,
@@ -34,9 +34,14 @@ lazy_static! {
         ("non_leader_pause", false),
         ("leader_node_pause", false),
         ("node_pause_resume", false),
+        ("lock_ops", false),
+        ("cas_ops", false),
+        ("large_values", false),
         // NOTE: our current snapshotting implementation does not
         //       guarantee to pass this
         // ("snapshot_reset", false),
+        // NOTE: needs servers run with `--debug-api`, so only run by name
+        // ("follower_catch_up", false),
     ];
 }
 
@@ -82,6 +87,8 @@ impl ClientTester {
     pub(crate) fn new(
         endpoint: Box<dyn GenericEndpoint>,
         timeout: Duration,
+        hedge_delay: Option<Duration>,
+        chunk_size: usize,
         params_str: Option<&str>,
     ) -> Result<Self, SummersetError> {
         let params = parsed_config!(params_str => ModeParamsTester;
@@ -93,7 +100,12 @@ impl ClientTester {
         }
 
         Ok(ClientTester {
-            driver: DriverClosedLoop::new(endpoint, timeout),
+            driver: DriverClosedLoop::new(
+                endpoint,
+                timeout,
+                hedge_delay,
+                chunk_size,
+            ),
             timeout,
             params,
         })
@@ -159,6 +171,17 @@ impl ClientTester {
                     // retry
                 }
 
+                DriverReply::Rejected { code } if code.retryable() => {
+                    time::sleep(Duration::from_millis(500)).await;
+                    // retry
+                }
+
+                DriverReply::Rejected { code } => {
+                    return logged_err!(
+                        Rejected; "service rejected request: {}", code
+                    );
+                }
+
                 DriverReply::Timeout => {
                     timeouts += 1;
                     pf_debug!(
@@ -223,6 +246,158 @@ impl ClientTester {
                     // retry
                 }
 
+                DriverReply::Rejected { code } if code.retryable() => {
+                    time::sleep(Duration::from_millis(500)).await;
+                    // retry
+                }
+
+                DriverReply::Rejected { code } => {
+                    return logged_err!(
+                        Rejected; "service rejected request: {}", code
+                    );
+                }
+
+                DriverReply::Timeout => {
+                    timeouts += 1;
+                    pf_debug!(
+                        "client-side timeout {} ms",
+                        self.timeout.as_millis()
+                    );
+                }
+            }
+        }
+
+        logged_err!(
+            "client-side timeout {} ms {} times",
+            self.timeout.as_millis(),
+            max_timeouts + 1
+        )
+    }
+
+    /// Issues an Acquire request if `ttl_ms` is given, else a Release
+    /// request, for the lock named key, and checks its reply against the
+    /// expected result. Retries in-place upon getting redirection error.
+    /// Retries at most max_timeouts times upon getting timeouts.
+    async fn checked_lock(
+        &mut self,
+        key: &str,
+        ttl_ms: Option<u64>,
+        expect_result: CommandResult,
+        max_timeouts: u8,
+    ) -> Result<(), SummersetError> {
+        let mut timeouts = 0;
+        while timeouts <= max_timeouts {
+            let result = match ttl_ms {
+                Some(ttl_ms) => self.driver.acquire(key, ttl_ms).await?,
+                None => self.driver.release(key).await?,
+            };
+            match result {
+                DriverReply::Success { cmd_result, .. } => {
+                    if cmd_result != expect_result {
+                        return logged_err!(
+                            "lock result mismatch: expect {:?}, got {:?}",
+                            expect_result,
+                            cmd_result
+                        );
+                    }
+                    return Ok(());
+                }
+
+                DriverReply::Conf { .. } => {
+                    return logged_err!(
+                        "DriverReply type mismatch: expect normal command"
+                    );
+                }
+
+                DriverReply::Failure => {
+                    return logged_err!("service replied unknown error");
+                }
+
+                DriverReply::Redirect { .. } => {
+                    time::sleep(Duration::from_millis(500)).await;
+                    // retry
+                }
+
+                DriverReply::Rejected { code } if code.retryable() => {
+                    time::sleep(Duration::from_millis(500)).await;
+                    // retry
+                }
+
+                DriverReply::Rejected { code } => {
+                    return logged_err!(
+                        Rejected; "service rejected request: {}", code
+                    );
+                }
+
+                DriverReply::Timeout => {
+                    timeouts += 1;
+                    pf_debug!(
+                        "client-side timeout {} ms",
+                        self.timeout.as_millis()
+                    );
+                }
+            }
+        }
+
+        logged_err!(
+            "client-side timeout {} ms {} times",
+            self.timeout.as_millis(),
+            max_timeouts + 1
+        )
+    }
+
+    /// Issues a compare-and-swap request and checks its reply against the
+    /// expected result. Retries in-place upon getting redirection error.
+    /// Retries at most max_timeouts times upon getting timeouts.
+    async fn checked_cas(
+        &mut self,
+        key: &str,
+        expect: Option<&str>,
+        value: &str,
+        expect_result: CommandResult,
+        max_timeouts: u8,
+    ) -> Result<(), SummersetError> {
+        let mut timeouts = 0;
+        while timeouts <= max_timeouts {
+            let result = self.driver.cas(key, expect, value).await?;
+            match result {
+                DriverReply::Success { cmd_result, .. } => {
+                    if cmd_result != expect_result {
+                        return logged_err!(
+                            "cas result mismatch: expect {:?}, got {:?}",
+                            expect_result,
+                            cmd_result
+                        );
+                    }
+                    return Ok(());
+                }
+
+                DriverReply::Conf { .. } => {
+                    return logged_err!(
+                        "DriverReply type mismatch: expect normal command"
+                    );
+                }
+
+                DriverReply::Failure => {
+                    return logged_err!("service replied unknown error");
+                }
+
+                DriverReply::Redirect { .. } => {
+                    time::sleep(Duration::from_millis(500)).await;
+                    // retry
+                }
+
+                DriverReply::Rejected { code } if code.retryable() => {
+                    time::sleep(Duration::from_millis(500)).await;
+                    // retry
+                }
+
+                DriverReply::Rejected { code } => {
+                    return logged_err!(
+                        Rejected; "service rejected request: {}", code
+                    );
+                }
+
                 DriverReply::Timeout => {
                     timeouts += 1;
                     pf_debug!(
@@ -258,7 +433,8 @@ impl ClientTester {
         }
     }
 
-    /// Resets some server(s) in the cluster.
+    /// Resets some server(s) in the cluster. Returns once the manager deems
+    /// the cluster ready to serve again.
     async fn reset_servers(
         &mut self,
         servers: HashSet<ReplicaId>,
@@ -327,7 +503,6 @@ impl ClientTester {
     ) -> Result<(), SummersetError> {
         // reset everything to initial state at the start of each test
         self.reset_servers(HashSet::new(), false).await?;
-        time::sleep(Duration::from_secs(1)).await;
         self.driver.connect().await?;
 
         let result = match name {
@@ -341,6 +516,10 @@ impl ClientTester {
             "leader_node_pause" => self.test_leader_node_pause().await,
             "node_pause_resume" => self.test_node_pause_resume().await,
             "snapshot_reset" => self.test_snapshot_reset().await,
+            "lock_ops" => self.test_lock_ops().await,
+            "cas_ops" => self.test_cas_ops().await,
+            "large_values" => self.test_large_values().await,
+            "follower_catch_up" => self.test_follower_catch_up().await,
             _ => {
                 return logged_err!("unrecognized test name '{}'", name);
             }
@@ -415,6 +594,94 @@ impl ClientTester {
         Ok(())
     }
 
+    /// Lock-service operations by a single owner.
+    async fn test_lock_ops(&mut self) -> Result<(), SummersetError> {
+        let me = self.driver.id();
+        let held = CommandResult::Acquire {
+            granted: true,
+            holder: me,
+        };
+        self.checked_lock("Jose", Some(60000), held.clone(), 0)
+            .await?;
+        // re-acquiring refreshes my hold
+        self.checked_lock("Jose", Some(60000), held, 0).await?;
+        self.checked_lock(
+            "Jose",
+            None,
+            CommandResult::Release { released: true },
+            0,
+        )
+        .await?;
+        self.checked_lock(
+            "Jose",
+            None,
+            CommandResult::Release { released: false },
+            0,
+        )
+        .await?;
+        // locks do not touch key-value pairs
+        self.checked_get("Jose", Some(None), 0).await?;
+        Ok(())
+    }
+
+    /// Compare-and-swap operations on a single key.
+    async fn test_cas_ops(&mut self) -> Result<(), SummersetError> {
+        let v0 = Self::gen_rand_string(8);
+        self.checked_put("Jose", &v0, None, 0).await?;
+        let v1 = Self::gen_rand_string(8);
+        self.checked_cas(
+            "Jose",
+            Some(&v1),
+            &v1,
+            CommandResult::Cas {
+                success: false,
+                old_value: Some(v0.clone()),
+            },
+            0,
+        )
+        .await?;
+        self.checked_get("Jose", Some(Some(&v0)), 0).await?;
+        self.checked_cas(
+            "Jose",
+            Some(&v0),
+            &v1,
+            CommandResult::Cas {
+                success: true,
+                old_value: Some(v0.clone()),
+            },
+            0,
+        )
+        .await?;
+        self.checked_get("Jose", Some(Some(&v1)), 0).await?;
+        // expecting absence fails on an existing key
+        self.checked_cas(
+            "Jose",
+            None,
+            &v0,
+            CommandResult::Cas {
+                success: false,
+                old_value: Some(v1.clone()),
+            },
+            0,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Large values, which get chunked if the client is configured so.
+    async fn test_large_values(&mut self) -> Result<(), SummersetError> {
+        let v0 = Self::gen_rand_string(1024 * 1024);
+        self.checked_put("Jose", &v0, Some(None), 0).await?;
+        self.checked_get("Jose", Some(Some(&v0)), 0).await?;
+        let v1 = Self::gen_rand_string(3 * 1024 * 1024 + 7);
+        self.checked_put("Jose", &v1, None, 0).await?;
+        self.checked_get("Jose", Some(Some(&v1)), 0).await?;
+        let v2 = Self::gen_rand_string(8);
+        self.checked_put("Jose", &v2, None, 0).await?;
+        self.checked_get("Jose", Some(Some(&v2)), 0).await?;
+        Ok(())
+    }
+
     /// Client leaves and reconnects.
     async fn test_client_reconnect(&mut self) -> Result<(), SummersetError> {
         let v = Self::gen_rand_string(8);
@@ -434,7 +701,6 @@ impl ClientTester {
                 // picked a non-leader replica
                 self.driver.leave(false).await?;
                 self.reset_servers(HashSet::from([s]), true).await?;
-                time::sleep(Duration::from_secs(1)).await;
                 self.driver.connect().await?;
                 self.checked_get("Jose", Some(Some(&v)), 0).await?;
                 break;
@@ -452,7 +718,6 @@ impl ClientTester {
                 // picked a leader replica
                 self.driver.leave(false).await?;
                 self.reset_servers(HashSet::from([s]), true).await?;
-                time::sleep(Duration::from_secs(3)).await;
                 self.driver.connect().await?;
                 self.checked_get("Jose", Some(Some(&v)), 0).await?;
                 break;
@@ -484,7 +749,6 @@ impl ClientTester {
             // picked two replicas, one leader and one non-leader
             self.driver.leave(false).await?;
             self.reset_servers(resets, true).await?;
-            time::sleep(Duration::from_secs(3)).await;
             self.driver.connect().await?;
             self.checked_get("Jose", Some(Some(&v)), 0).await?;
         }
@@ -497,7 +761,6 @@ impl ClientTester {
         self.checked_put("Jose", &v, Some(None), 0).await?;
         self.driver.leave(false).await?;
         self.reset_servers(HashSet::new(), true).await?;
-        time::sleep(Duration::from_secs(3)).await;
         self.driver.connect().await?;
         self.checked_get("Jose", Some(Some(&v)), 0).await?;
         Ok(())
@@ -599,7 +862,6 @@ impl ClientTester {
         // resetting all nodes and see if things are there
         self.driver.leave(false).await?;
         self.reset_servers(HashSet::new(), true).await?;
-        time::sleep(Duration::from_secs(3)).await;
         self.driver.connect().await?;
         self.checked_get("Shawn", Some(Some(&v1)), 0).await?;
         self.checked_get("Jose", Some(Some(&v1)), 0).await?;
@@ -609,10 +871,54 @@ impl ClientTester {
         // resetting all nodes again and check again
         self.driver.leave(false).await?;
         self.reset_servers(HashSet::new(), true).await?;
-        time::sleep(Duration::from_secs(3)).await;
         self.driver.connect().await?;
         self.checked_get("Shawn", Some(Some(&v1)), 0).await?;
         self.checked_get("Jose", Some(Some(&v1)), 0).await?;
         Ok(())
     }
+
+    /// All replicas execute up to the leader's commit bar within 3 secs
+    /// after a write, checked through debug queries of their state.
+    async fn test_follower_catch_up(&mut self) -> Result<(), SummersetError> {
+        let v0 = Self::gen_rand_string(8);
+        self.checked_put("Jose", &v0, Some(None), 0).await?;
+        let Some(target) = self
+            .driver
+            .debug_states()
+            .await?
+            .values()
+            .flatten()
+            .filter(|state| state.is_leader)
+            .map(|state| state.commit_bar)
+            .max()
+        else {
+            return logged_err!("no leader found through debug queries");
+        };
+
+        let deadline = Instant::now() + Duration::from_secs(3);
+        loop {
+            let mut lagging: Vec<ReplicaId> = self
+                .driver
+                .debug_states()
+                .await?
+                .into_iter()
+                .filter(|(_, state)| {
+                    state.as_ref().is_none_or(|state| state.exec_bar < target)
+                })
+                .map(|(replica, _)| replica)
+                .collect();
+            if lagging.is_empty() {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                lagging.sort_unstable();
+                return logged_err!(
+                    "replicas {:?} not caught up to commit bar {} in time",
+                    lagging,
+                    target
+                );
+            }
+            time::sleep(Duration::from_millis(100)).await;
+        }
+    }
 }
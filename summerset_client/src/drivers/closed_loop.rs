@@ -1,15 +1,23 @@
 //! Closed-loop client-side driver implementation.
 
-use crate::drivers::DriverReply;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use tokio::time::{Duration, Instant};
+use crate::drivers::{checksums, fence_epochs, DriverReply};
+
+use tokio::time::{self, Duration, Instant};
 
 use summerset::{
-    logged_err, pf_debug, pf_error, ApiReply, ApiRequest, ClientCtrlStub,
-    ClientId, Command, CommandResult, ConfChange, GenericEndpoint, RequestId,
-    SummersetError, Timer,
+    logged_err, pf_debug, pf_error, ApiReply, ApiRequest, ClientApiStub,
+    ClientCtrlStub, ClientId, Command, CommandResult, ConfChange, ConfNum,
+    CtrlReply, CtrlRequest, DebugState, GenericEndpoint, HlcStamp, IdemKey,
+    ReadConsistency, ReplicaId, ReplyError, RequestId, SummersetError, Timer,
 };
 
+/// Prefix marking a value as the manifest of a large value stored in chunks.
+/// Starts with a NUL character so that it does not clash with text values.
+const CHUNKED_PREFIX: &str = "\0chunked:";
+
 /// Closed-loop driver struct.
 pub(crate) struct DriverClosedLoop {
     /// Protocol-specific client endpoint.
@@ -18,11 +26,37 @@ pub(crate) struct DriverClosedLoop {
     /// Next request ID, monotonically increasing.
     next_req: RequestId,
 
+    /// Next idempotency key for writes, monotonically increasing. Starts
+    /// from the current time in microsecs, so that keys do not repeat across
+    /// restarts of a client reclaiming the same identity.
+    next_idem: IdemKey,
+
+    /// Last write not yet successfully replied, with its idempotency key.
+    unacked_write: Option<(Command, IdemKey)>,
+
     /// Reply timeout timer.
     timer: Timer,
 
     /// Reply timeout duration.
     timeout: Duration,
+
+    /// If set, a read not replied within this delay gets hedged.
+    hedge_delay: Option<Duration>,
+
+    /// Values longer than this many bytes get written in chunks; 0 means
+    /// never chunk.
+    chunk_size: usize,
+
+    /// Read path stated by the service in the reply to the last successful
+    /// Get, if it asked for a consistency level.
+    last_read_path: Option<ReadConsistency>,
+
+    /// HLC timestamp stated by the service in the reply to the last
+    /// successful Get.
+    last_read_hlc: Option<HlcStamp>,
+
+    /// Config epoch requests are tagged with, if fencing with epochs.
+    fence_epoch: Option<ConfNum>,
 }
 
 impl DriverClosedLoop {
@@ -30,18 +64,46 @@ impl DriverClosedLoop {
     pub(crate) fn new(
         endpoint: Box<dyn GenericEndpoint>,
         timeout: Duration,
+        hedge_delay: Option<Duration>,
+        chunk_size: usize,
     ) -> Self {
         DriverClosedLoop {
             endpoint,
             next_req: 0,
+            next_idem: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_micros() as IdemKey),
+            unacked_write: None,
             timer: Timer::default(),
             timeout,
+            hedge_delay,
+            chunk_size,
+            last_read_path: None,
+            last_read_hlc: None,
+            fence_epoch: fence_epochs().then_some(0),
         }
     }
 
-    /// Establishes connection with the service.
+    /// Establishes connection with the service. If fencing with epochs,
+    /// also refreshes the config epoch from the manager.
     pub(crate) async fn connect(&mut self) -> Result<(), SummersetError> {
-        self.endpoint.connect().await
+        self.endpoint.connect().await?;
+        if let Some(epoch) = self.fence_epoch {
+            let ctrl_stub = self.endpoint.ctrl_stub();
+            ctrl_stub.send_req_insist(&CtrlRequest::QueryConf)?;
+            let conf_num = loop {
+                // skip replies pushed for anything else
+                if let CtrlReply::QueryConf { conf_num, .. } =
+                    ctrl_stub.recv_reply().await?
+                {
+                    break conf_num;
+                }
+            };
+            let epoch = epoch.max(conf_num);
+            pf_debug!("fencing requests with epoch {}", epoch);
+            self.fence_epoch = Some(epoch);
+        }
+        Ok(())
     }
 
     /// Sends leave notification and forgets about the current TCP connections.
@@ -52,14 +114,54 @@ impl DriverClosedLoop {
         self.endpoint.leave(permanent).await
     }
 
+    /// Wraps a request into a `Fenced` one tagged with my config epoch, if
+    /// fencing with epochs.
+    fn fenced(&self, req: &ApiRequest) -> ApiRequest {
+        match self.fence_epoch {
+            Some(epoch) => ApiRequest::Fenced {
+                epoch,
+                req: Box::new(req.clone()),
+            },
+            None => req.clone(),
+        }
+    }
+
+    /// Computes the checksum to attach to a command, if attaching them.
+    #[inline]
+    fn checksum_of(cmd: &Command) -> Option<u64> {
+        checksums().then(|| cmd.checksum())
+    }
+
+    /// Makes the driver reply for a request the service did not serve. If
+    /// refused for a stale config epoch, first refreshes the config by
+    /// reconnecting, having learned at least the replier's epoch.
+    async fn unserved(
+        &mut self,
+        redirect: Option<ReplicaId>,
+        error: Option<ReplyError>,
+        conf_epoch: Option<ConfNum>,
+    ) -> Result<DriverReply, SummersetError> {
+        if error == Some(ReplyError::StaleEpoch) {
+            if let (Some(epoch), Some(seen)) = (self.fence_epoch, conf_epoch) {
+                self.fence_epoch = Some(epoch.max(seen));
+            }
+            pf_debug!("stale epoch, refreshing config...");
+            self.endpoint.leave(false).await?;
+            self.connect().await?;
+        }
+        Ok(DriverReply::unserved(redirect, error))
+    }
+
     /// Attempts to send a request, retrying immediately if receiving
     /// `WouldBlock` failure. This shortcut is used here because TCP write
-    /// blocking is not expected with a closed-loop client.
+    /// blocking is not expected with a closed-loop client. The request gets
+    /// tagged with my config epoch if fencing with epochs.
     fn send_req_insist(
         &mut self,
         req: &ApiRequest,
     ) -> Result<(), SummersetError> {
-        let mut success = self.endpoint.send_req(Some(req))?;
+        let req = self.fenced(req);
+        let mut success = self.endpoint.send_req(Some(&req))?;
         while !success {
             success = self.endpoint.send_req(None)?;
         }
@@ -87,27 +189,252 @@ impl DriverClosedLoop {
         }
     }
 
-    /// Sends a Get request and waits for its reply.
+    /// Waits on a reply from the service with timeout like
+    /// `recv_reply_timed()`, but if nothing arrives by `hedge_at`, also asks
+    /// the endpoint to send a hedged duplicate of `req` and takes whichever
+    /// reply comes first. Hedging does not extend the timeout budget.
+    /// `hedge_at` is cleared once passed.
+    async fn recv_reply_hedged(
+        &mut self,
+        req: &ApiRequest,
+        hedge_at: &mut Option<Instant>,
+    ) -> Result<Option<ApiReply>, SummersetError> {
+        self.timer.kickoff(self.timeout)?;
+
+        loop {
+            tokio::select! {
+                () = self.timer.timeout() => {
+                    pf_debug!("timed-out waiting for reply");
+                    return Ok(None);
+                }
+
+                () = time::sleep_until(hedge_at.unwrap_or_else(Instant::now)),
+                     if hedge_at.is_some() => {
+                    *hedge_at = None;
+                    if self.endpoint.send_hedge(&self.fenced(req))? {
+                        pf_debug!("hedged request {:?}", req);
+                    }
+                }
+
+                reply = self.endpoint.recv_reply() => {
+                    self.timer.cancel()?; // cancel current deadline
+                    return Ok(Some(reply?));
+                }
+            }
+        }
+    }
+
+    /// Composes the key under which chunk `idx` of a chunked value of key is
+    /// stored. Chunk keys extend the original key, so they stay in the same
+    /// namespace.
+    fn chunk_key(key: &str, tag: &str, idx: usize) -> String {
+        format!("{}\0{}.{}", key, tag, idx)
+    }
+
+    /// Splits value into chunks of roughly `chunk_size` bytes each, without
+    /// breaking any UTF-8 character.
+    fn split_chunks(value: &str, chunk_size: usize) -> Vec<&str> {
+        debug_assert!(chunk_size > 0);
+        let mut chunks = vec![];
+        let mut rest = value;
+        while !rest.is_empty() {
+            let mut end = chunk_size.min(rest.len());
+            while !rest.is_char_boundary(end) {
+                end += 1;
+            }
+            let (chunk, tail) = rest.split_at(end);
+            chunks.push(chunk);
+            rest = tail;
+        }
+        chunks
+    }
+
+    /// Sends a Get request and waits for its reply. If the value got stored
+    /// in chunks, fetches all the chunks and reassembles the value, so that
+    /// chunking is transparent to the caller.
     pub(crate) async fn get(
         &mut self,
         key: &str,
+    ) -> Result<DriverReply, SummersetError> {
+        self.get_at(key, None).await
+    }
+
+    /// Like `get()`, but if `consistency` is given, asks the service to
+    /// serve the Get at that consistency level. The path the service took
+    /// can then be checked through `last_read_path()`.
+    pub(crate) async fn get_at(
+        &mut self,
+        key: &str,
+        consistency: Option<ReadConsistency>,
+    ) -> Result<DriverReply, SummersetError> {
+        let issue_ts = Instant::now();
+        let reply = self.get_single(key, consistency).await?;
+        let (req_id, manifest) = match reply {
+            DriverReply::Success {
+                req_id,
+                cmd_result:
+                    CommandResult::Get {
+                        value: Some(ref value),
+                    },
+                ..
+            } => (req_id, value.strip_prefix(CHUNKED_PREFIX)),
+            _ => return Ok(reply),
+        };
+        let Some((tag, num_chunks)) =
+            manifest.and_then(|manifest| manifest.rsplit_once(':'))
+        else {
+            return Ok(reply);
+        };
+        let num_chunks: usize = num_chunks.parse()?;
+
+        let mut value = String::new();
+        for idx in 0..num_chunks {
+            match self
+                .get_single(&Self::chunk_key(key, tag, idx), consistency)
+                .await?
+            {
+                DriverReply::Success {
+                    cmd_result: CommandResult::Get { value: Some(chunk) },
+                    ..
+                } => value.push_str(&chunk),
+                DriverReply::Success { .. } => {
+                    return logged_err!(
+                        "chunk {} of key '{}' missing",
+                        idx,
+                        key
+                    );
+                }
+                reply => return Ok(reply),
+            }
+        }
+
+        Ok(DriverReply::Success {
+            req_id,
+            cmd_result: CommandResult::Get { value: Some(value) },
+            latency: Instant::now().duration_since(issue_ts),
+        })
+    }
+
+    /// Sends a Put request and waits for its reply. Values longer than the
+    /// chunk size get written in chunks, each as a separate Put, followed by
+    /// a small manifest value under key that makes them visible. If writing
+    /// any chunk is unsuccessful, that reply is returned and the caller may
+    /// retry the whole write; chunks already written, as well as the chunks
+    /// of an overwritten chunked value, are left behind unreferenced.
+    pub(crate) async fn put(
+        &mut self,
+        key: &str,
+        value: &str,
+    ) -> Result<DriverReply, SummersetError> {
+        if self.chunk_size == 0 || value.len() <= self.chunk_size {
+            return self.put_single(key, value).await;
+        }
+
+        let issue_ts = Instant::now();
+        let tag = format!("{}.{}", self.id(), self.next_req);
+        let chunks = Self::split_chunks(value, self.chunk_size);
+        for (idx, chunk) in chunks.iter().enumerate() {
+            let reply = self
+                .put_single(&Self::chunk_key(key, &tag, idx), chunk)
+                .await?;
+            if !matches!(
+                reply,
+                DriverReply::Success {
+                    cmd_result: CommandResult::Put { .. },
+                    ..
+                }
+            ) {
+                return Ok(reply);
+            }
+        }
+
+        let manifest = format!("{}{}:{}", CHUNKED_PREFIX, tag, chunks.len());
+        match self.put_single(key, &manifest).await? {
+            DriverReply::Success {
+                req_id, cmd_result, ..
+            } => Ok(DriverReply::Success {
+                req_id,
+                cmd_result,
+                latency: Instant::now().duration_since(issue_ts),
+            }),
+            reply => Ok(reply),
+        }
+    }
+
+    /// Sends a compare-and-swap request and waits for its reply: the value
+    /// of key is set only if it currently equals `expect`, where `None`
+    /// means the key must be absent. Values are never written in chunks, so
+    /// `expect` is compared against the raw stored value.
+    pub(crate) async fn cas(
+        &mut self,
+        key: &str,
+        expect: Option<&str>,
+        value: &str,
+    ) -> Result<DriverReply, SummersetError> {
+        self.write_single(Command::Cas {
+            key: key.into(),
+            expect: expect.map(|e| e.into()),
+            value: value.into(),
+        })
+        .await
+    }
+
+    /// Read path stated by the service in the reply to the last successful
+    /// Get, or `None` if that Get did not ask for a consistency level.
+    #[inline]
+    pub(crate) fn last_read_path(&self) -> Option<ReadConsistency> {
+        self.last_read_path
+    }
+
+    /// HLC timestamp of the latest command the replying replica had executed
+    /// when serving the last successful Get, if stated by the service.
+    /// Comparing it across replicas tells how far behind in time they are.
+    #[inline]
+    pub(crate) fn last_read_hlc(&self) -> Option<HlcStamp> {
+        self.last_read_hlc
+    }
+
+    /// Sends a single Get request, at the given consistency level if any,
+    /// and waits for its reply. The request may get hedged if a hedging
+    /// delay is configured.
+    async fn get_single(
+        &mut self,
+        key: &str,
+        consistency: Option<ReadConsistency>,
     ) -> Result<DriverReply, SummersetError> {
         let req_id = self.next_req;
         self.next_req += 1;
 
-        self.send_req_insist(&ApiRequest::Req {
-            id: req_id,
-            cmd: Command::Get { key: key.into() },
-        })?;
+        let req = match consistency {
+            Some(consistency) => ApiRequest::Read {
+                id: req_id,
+                key: key.into(),
+                consistency,
+            },
+            None => {
+                let cmd = Command::Get { key: key.into() };
+                ApiRequest::Req {
+                    id: req_id,
+                    checksum: Self::checksum_of(&cmd),
+                    cmd,
+                }
+            }
+        };
+        self.send_req_insist(&req)?;
         let issue_ts = Instant::now();
+        let mut hedge_at = self.hedge_delay.map(|d| issue_ts + d);
 
         loop {
-            let reply = self.recv_reply_timed().await?;
+            let reply = self.recv_reply_hedged(&req, &mut hedge_at).await?;
             match reply {
                 Some(ApiReply::Reply {
                     id: reply_id,
                     result: cmd_result,
                     redirect,
+                    error,
+                    read_path,
+                    conf_epoch,
+                    hlc,
                     ..
                 }) => {
                     if reply_id != req_id {
@@ -118,18 +445,16 @@ impl DriverClosedLoop {
                     } else {
                         match cmd_result {
                             None => {
-                                if let Some(server) = redirect {
-                                    return Ok(DriverReply::Redirect {
-                                        server,
-                                    });
-                                } else {
-                                    return Ok(DriverReply::Failure);
-                                }
+                                return self
+                                    .unserved(redirect, error, conf_epoch)
+                                    .await;
                             }
 
                             Some(CommandResult::Get { value }) => {
                                 let latency =
                                     Instant::now().duration_since(issue_ts);
+                                self.last_read_path = read_path;
+                                self.last_read_hlc = hlc;
                                 return Ok(DriverReply::Success {
                                     req_id,
                                     cmd_result: CommandResult::Get { value },
@@ -137,6 +462,16 @@ impl DriverClosedLoop {
                                 });
                             }
 
+                            Some(CommandResult::ChecksumMismatch) => {
+                                let latency =
+                                    Instant::now().duration_since(issue_ts);
+                                return Ok(DriverReply::Success {
+                                    req_id,
+                                    cmd_result: CommandResult::ChecksumMismatch,
+                                    latency,
+                                });
+                            }
+
                             _ => {
                                 return logged_err!(
                                     "command type mismatch: expected Get"
@@ -146,6 +481,23 @@ impl DriverClosedLoop {
                     }
                 }
 
+                Some(ApiReply::MsgTooLarge { id, max_bytes }) => {
+                    if id.is_some_and(|reply_id| reply_id != req_id) {
+                        continue;
+                    }
+                    return logged_err!(
+                        Rejected;
+                        "request rejected by server as larger than max {} bytes",
+                        max_bytes
+                    );
+                }
+
+                Some(ApiReply::LeaderChange { leader }) => {
+                    // endpoint has switched over to the new leader, to which
+                    // the request should be re-issued
+                    return Ok(DriverReply::Redirect { server: leader });
+                }
+
                 None => {
                     return Ok(DriverReply::Timeout);
                 }
@@ -157,22 +509,45 @@ impl DriverClosedLoop {
         }
     }
 
-    /// Sends a Put request and waits for its reply.
-    pub(crate) async fn put(
+    /// Sends a single Put request and waits for its reply.
+    async fn put_single(
         &mut self,
         key: &str,
         value: &str,
+    ) -> Result<DriverReply, SummersetError> {
+        self.write_single(Command::Put {
+            key: key.into(),
+            value: value.into(),
+        })
+        .await
+    }
+
+    /// Sends a single write request and waits for its reply. Writes are
+    /// tagged with an idempotency key, which is reused if the caller retries
+    /// the same write after an unsuccessful attempt, so that the service may
+    /// collapse them.
+    async fn write_single(
+        &mut self,
+        cmd: Command,
     ) -> Result<DriverReply, SummersetError> {
         let req_id = self.next_req;
         self.next_req += 1;
 
-        self.send_req_insist(&ApiRequest::Req {
+        let idem_key = match self.unacked_write.take() {
+            Some((last_cmd, idem_key)) if last_cmd == cmd => idem_key,
+            _ => {
+                self.next_idem += 1;
+                self.next_idem - 1
+            }
+        };
+
+        self.send_req_insist(&ApiRequest::IdemReq {
             id: req_id,
-            cmd: Command::Put {
-                key: key.into(),
-                value: value.into(),
-            },
+            cmd: cmd.clone(),
+            idem_key,
+            checksum: Self::checksum_of(&cmd),
         })?;
+        self.unacked_write = Some((cmd, idem_key));
         let issue_ts = Instant::now();
 
         loop {
@@ -182,6 +557,9 @@ impl DriverClosedLoop {
                     id: reply_id,
                     result: cmd_result,
                     redirect,
+                    cert,
+                    error,
+                    conf_epoch,
                     ..
                 }) => {
                     if reply_id != req_id {
@@ -192,36 +570,216 @@ impl DriverClosedLoop {
                     } else {
                         match cmd_result {
                             None => {
-                                if let Some(server) = redirect {
-                                    return Ok(DriverReply::Redirect {
-                                        server,
-                                    });
-                                } else {
-                                    return Ok(DriverReply::Failure);
+                                return self
+                                    .unserved(redirect, error, conf_epoch)
+                                    .await;
+                            }
+
+                            Some(
+                                cmd_result @ (CommandResult::Put { .. }
+                                | CommandResult::Cas { .. }
+                                | CommandResult::QuotaExceeded {
+                                    ..
                                 }
+                                | CommandResult::DiskFull {
+                                    ..
+                                }
+                                | CommandResult::ChecksumMismatch),
+                            ) => {
+                                // check the commit certificate if attached
+                                if let Some(cert) = cert {
+                                    if !cert.verify(self.population()) {
+                                        return logged_err!(
+                                            "invalid commit certificate {:?}",
+                                            cert
+                                        );
+                                    }
+                                }
+                                self.unacked_write = None;
+                                let latency =
+                                    Instant::now().duration_since(issue_ts);
+                                return Ok(DriverReply::Success {
+                                    req_id,
+                                    cmd_result,
+                                    latency,
+                                });
+                            }
+
+                            _ => {
+                                return logged_err!(
+                                    "command type mismatch: expected write"
+                                );
+                            }
+                        }
+                    }
+                }
+
+                Some(ApiReply::MsgTooLarge { id, max_bytes }) => {
+                    if id.is_some_and(|reply_id| reply_id != req_id) {
+                        continue;
+                    }
+                    return logged_err!(
+                        Rejected;
+                        "request rejected by server as larger than max {} bytes",
+                        max_bytes
+                    );
+                }
+
+                Some(ApiReply::LeaderChange { leader }) => {
+                    // endpoint has switched over to the new leader, to which
+                    // the request should be re-issued
+                    return Ok(DriverReply::Redirect { server: leader });
+                }
+
+                None => {
+                    return Ok(DriverReply::Timeout);
+                }
+
+                _ => {
+                    return logged_err!("unexpected reply type received");
+                }
+            }
+        }
+    }
+
+    /// Sends an Acquire request for the lock named key and waits for its
+    /// reply. Does not block on the lock itself: if someone else holds it, I
+    /// get queued and should re-acquire later to learn if it got granted.
+    pub(crate) async fn acquire(
+        &mut self,
+        key: &str,
+        ttl_ms: u64,
+    ) -> Result<DriverReply, SummersetError> {
+        let cmd = Command::Acquire {
+            key: key.into(),
+            owner: self.id(),
+            ttl_ms,
+            now_ms: Self::now_ms()?,
+        };
+        self.plain_cmd(cmd).await
+    }
+
+    /// Sends a Release request for the lock named key and waits for its
+    /// reply.
+    pub(crate) async fn release(
+        &mut self,
+        key: &str,
+    ) -> Result<DriverReply, SummersetError> {
+        let cmd = Command::Release {
+            key: key.into(),
+            owner: self.id(),
+            now_ms: Self::now_ms()?,
+        };
+        self.plain_cmd(cmd).await
+    }
+
+    /// Gets my wall-clock time in millisecs, used to stamp lock commands.
+    fn now_ms() -> Result<u64, SummersetError> {
+        Ok(SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(SummersetError::msg)?
+            .as_millis() as u64)
+    }
+
+    /// Sends a Scan request for up to `limit` key-value pairs with keys in
+    /// range [`start_key`, `end_key`) and waits for its reply. An empty
+    /// `end_key` means no upper bound, and a `limit` of 0 means no limit.
+    /// Values written in chunks show up as their manifests.
+    pub(crate) async fn scan(
+        &mut self,
+        start_key: &str,
+        end_key: &str,
+        limit: usize,
+    ) -> Result<DriverReply, SummersetError> {
+        let cmd = Command::Scan {
+            start_key: start_key.into(),
+            end_key: end_key.into(),
+            limit,
+        };
+        self.plain_cmd(cmd).await
+    }
+
+    /// Sends a lock-service or Scan request, which needs neither chunking
+    /// nor an idempotency key, and waits for its reply.
+    async fn plain_cmd(
+        &mut self,
+        cmd: Command,
+    ) -> Result<DriverReply, SummersetError> {
+        let req_id = self.next_req;
+        self.next_req += 1;
+
+        self.send_req_insist(&ApiRequest::Req {
+            id: req_id,
+            checksum: Self::checksum_of(&cmd),
+            cmd,
+        })?;
+        let issue_ts = Instant::now();
+
+        loop {
+            let reply = self.recv_reply_timed().await?;
+            match reply {
+                Some(ApiReply::Reply {
+                    id: reply_id,
+                    result: cmd_result,
+                    redirect,
+                    error,
+                    conf_epoch,
+                    ..
+                }) => {
+                    if reply_id != req_id {
+                        continue;
+                    } else {
+                        match cmd_result {
+                            None => {
+                                return self
+                                    .unserved(redirect, error, conf_epoch)
+                                    .await;
                             }
 
-                            Some(CommandResult::Put { old_value }) => {
+                            Some(
+                                cmd_result @ (CommandResult::Acquire { .. }
+                                | CommandResult::Release { .. }
+                                | CommandResult::Scan { .. }
+                                | CommandResult::DiskFull {
+                                    ..
+                                }
+                                | CommandResult::ChecksumMismatch),
+                            ) => {
                                 let latency =
                                     Instant::now().duration_since(issue_ts);
                                 return Ok(DriverReply::Success {
                                     req_id,
-                                    cmd_result: CommandResult::Put {
-                                        old_value,
-                                    },
+                                    cmd_result,
                                     latency,
                                 });
                             }
 
                             _ => {
                                 return logged_err!(
-                                    "command type mismatch: expected Put"
+                                    "command type mismatch: expected lock/scan"
                                 );
                             }
                         }
                     }
                 }
 
+                Some(ApiReply::MsgTooLarge { id, max_bytes }) => {
+                    if id.is_some_and(|reply_id| reply_id != req_id) {
+                        continue;
+                    }
+                    return logged_err!(
+                        Rejected;
+                        "request rejected by server as larger than max {} bytes",
+                        max_bytes
+                    );
+                }
+
+                Some(ApiReply::LeaderChange { leader }) => {
+                    // endpoint has switched over to the new leader, to which
+                    // the request should be re-issued
+                    return Ok(DriverReply::Redirect { server: leader });
+                }
+
                 None => {
                     return Ok(DriverReply::Timeout);
                 }
@@ -249,6 +807,8 @@ impl DriverClosedLoop {
                 Some(ApiReply::Reply {
                     id: reply_id,
                     redirect,
+                    error,
+                    conf_epoch,
                     ..
                 }) => {
                     if reply_id != req_id {
@@ -257,14 +817,9 @@ impl DriverClosedLoop {
                         //             req_id, reply_id)
                         continue;
                     } else {
-                        if let Some(redirect) = redirect {
-                            if reply_id == req_id {
-                                return Ok(DriverReply::Redirect {
-                                    server: redirect,
-                                });
-                            }
-                        }
-                        return Ok(DriverReply::Failure);
+                        return self
+                            .unserved(redirect, error, conf_epoch)
+                            .await;
                     }
                 }
 
@@ -285,6 +840,23 @@ impl DriverClosedLoop {
                     }
                 }
 
+                Some(ApiReply::MsgTooLarge { id, max_bytes }) => {
+                    if id.is_some_and(|reply_id| reply_id != req_id) {
+                        continue;
+                    }
+                    return logged_err!(
+                        Rejected;
+                        "request rejected by server as larger than max {} bytes",
+                        max_bytes
+                    );
+                }
+
+                Some(ApiReply::LeaderChange { leader }) => {
+                    // endpoint has switched over to the new leader, to which
+                    // the request should be re-issued
+                    return Ok(DriverReply::Redirect { server: leader });
+                }
+
                 None => {
                     return Ok(DriverReply::Timeout);
                 }
@@ -296,6 +868,64 @@ impl DriverClosedLoop {
         }
     }
 
+    /// Queries every active replica for its internal protocol state through
+    /// a `Debug` request. The queries go over a sibling client's connections,
+    /// as this client may already be connected to some of the replicas.
+    /// Replicas that did not reply in time map to `None`.
+    pub(crate) async fn debug_states(
+        &mut self,
+    ) -> Result<HashMap<ReplicaId, Option<DebugState>>, SummersetError> {
+        let mut sibling = self.endpoint.ctrl_stub().open_sibling().await?;
+        sibling.send_req_insist(&CtrlRequest::QueryInfo)?;
+        let servers_info = match sibling.recv_reply().await? {
+            CtrlReply::QueryInfo { servers_info, .. } => servers_info,
+            _ => return logged_err!(Protocol; "unexpected reply type received"),
+        };
+
+        let mut states = HashMap::new();
+        for (server, info) in servers_info {
+            if info.is_paused {
+                continue;
+            }
+            let mut api_stub =
+                ClientApiStub::new_by_connect(sibling.id, info.api_addr)
+                    .await?;
+            let mut sent =
+                api_stub.send_req(Some(&ApiRequest::Debug { id: 0 }))?;
+            while !sent {
+                sent = api_stub.send_req(None)?;
+            }
+
+            let state = match time::timeout(self.timeout, api_stub.recv_reply())
+                .await
+            {
+                Ok(reply) => match reply? {
+                    ApiReply::Debug { state, .. } => Some(state),
+                    ApiReply::Reply {
+                        error: Some(code), ..
+                    } => {
+                        return logged_err!(
+                            Rejected;
+                            "server {} refused debug query: {}", server, code
+                        );
+                    }
+                    _ => return logged_err!("unexpected reply type received"),
+                },
+                Err(_) => None,
+            };
+            states.insert(server, state);
+
+            let mut sent = api_stub.send_req(Some(&ApiRequest::Leave))?;
+            while !sent {
+                sent = api_stub.send_req(None)?;
+            }
+        }
+
+        sibling.send_req_insist(&CtrlRequest::Leave)?;
+        while sibling.recv_reply().await? != CtrlReply::Leave {}
+        Ok(states)
+    }
+
     /// Gets my Client ID.
     #[allow(dead_code)]
     pub(crate) fn id(&self) -> ClientId {
@@ -304,7 +934,7 @@ impl DriverClosedLoop {
 
     /// Gets current cluster size.
     #[allow(dead_code)]
-    pub(crate) fn population(&self) -> u8 {
+    pub(crate) fn population(&self) -> u16 {
         self.endpoint.population()
     }
 
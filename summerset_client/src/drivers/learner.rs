@@ -0,0 +1,175 @@
+//! Learner client-side driver implementation, reading from all replicas.
+
+use std::collections::HashMap;
+
+use tokio::time::{self, Duration, Instant};
+
+use summerset::{
+    logged_err, pf_debug, pf_warn, ApiReply, ApiRequest, ClientApiStub,
+    CtrlReply, CtrlRequest, GenericEndpoint, HlcStamp, ReplicaId, RequestId,
+    SummersetError,
+};
+
+/// Result of a local read at one replica.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) struct LocalReadResult {
+    /// Value of the key in the replica's state machine, if any.
+    pub(crate) value: Option<String>,
+
+    /// Replica's execution bar at the time of reading.
+    pub(crate) exec_bar: usize,
+
+    /// HLC timestamp of the latest command the replica had executed at the
+    /// time of reading, if stated.
+    pub(crate) hlc: Option<HlcStamp>,
+}
+
+/// Learner driver struct. Connects to all replicas at once and issues the
+/// same local read to each, bypassing consensus.
+pub(crate) struct DriverLearner {
+    /// Protocol-specific client endpoint, of which only the control stub and
+    /// the client ID are used.
+    endpoint: Box<dyn GenericEndpoint>,
+
+    /// Map from replica ID -> API connection stub to it.
+    api_stubs: HashMap<ReplicaId, ClientApiStub>,
+
+    /// Next request ID, monotonically increasing.
+    next_req: RequestId,
+
+    /// Reply timeout duration.
+    timeout: Duration,
+}
+
+impl DriverLearner {
+    /// Creates a new learner driver.
+    pub(crate) fn new(
+        endpoint: Box<dyn GenericEndpoint>,
+        timeout: Duration,
+    ) -> Self {
+        DriverLearner {
+            endpoint,
+            api_stubs: HashMap::new(),
+            next_req: 0,
+            timeout,
+        }
+    }
+
+    /// Establishes connections to all active replicas.
+    pub(crate) async fn connect(&mut self) -> Result<(), SummersetError> {
+        // disallow reconnection without leaving
+        if !self.api_stubs.is_empty() {
+            return logged_err!("reconnecting without leaving");
+        }
+
+        let id = self.endpoint.id();
+        let ctrl_stub = self.endpoint.ctrl_stub();
+        ctrl_stub.send_req_insist(&CtrlRequest::QueryInfo)?;
+        let servers_info = match ctrl_stub.recv_reply().await? {
+            CtrlReply::QueryInfo { servers_info, .. } => servers_info,
+            _ => return logged_err!(Protocol; "unexpected reply type received"),
+        };
+
+        for (server, info) in servers_info {
+            if info.is_paused {
+                pf_warn!("server {} paused, not reading from it", server);
+                continue;
+            }
+            pf_debug!("connecting to server {} '{}'...", server, info.api_addr);
+            let api_stub =
+                ClientApiStub::new_by_connect(id, info.api_addr).await?;
+            self.api_stubs.insert(server, api_stub);
+        }
+        Ok(())
+    }
+
+    /// Sends leave notification to all replicas and forgets about the current
+    /// TCP connections.
+    pub(crate) async fn leave(
+        &mut self,
+        permanent: bool,
+    ) -> Result<(), SummersetError> {
+        for (server, mut api_stub) in self.api_stubs.drain() {
+            let mut sent = api_stub.send_req(Some(&ApiRequest::Leave))?;
+            while !sent {
+                sent = api_stub.send_req(None)?;
+            }
+            pf_debug!("left server connection {}", server);
+        }
+        self.endpoint.leave(permanent).await
+    }
+
+    /// Replica IDs currently connected to, in ascending order.
+    pub(crate) fn replicas(&self) -> Vec<ReplicaId> {
+        let mut replicas: Vec<ReplicaId> =
+            self.api_stubs.keys().copied().collect();
+        replicas.sort_unstable();
+        replicas
+    }
+
+    /// Issues a local read of key to every connected replica and waits for
+    /// their replies until timeout. Replicas that did not reply in time map
+    /// to `None`.
+    pub(crate) async fn read_all(
+        &mut self,
+        key: &str,
+    ) -> Result<HashMap<ReplicaId, Option<LocalReadResult>>, SummersetError>
+    {
+        let req_id = self.next_req;
+        self.next_req += 1;
+        let req = ApiRequest::LocalRead {
+            id: req_id,
+            key: key.into(),
+        };
+        for api_stub in self.api_stubs.values_mut() {
+            let mut sent = api_stub.send_req(Some(&req))?;
+            while !sent {
+                sent = api_stub.send_req(None)?;
+            }
+        }
+
+        // all reads are in flight, so waiting on the replicas one by one
+        // until a common deadline is as good as waiting on them together
+        let deadline = Instant::now() + self.timeout;
+        let mut results = HashMap::new();
+        for (&server, api_stub) in self.api_stubs.iter_mut() {
+            let result = loop {
+                let Ok(reply) =
+                    time::timeout_at(deadline, api_stub.recv_reply()).await
+                else {
+                    pf_debug!("timed-out waiting for server {}", server);
+                    break None;
+                };
+                match reply? {
+                    ApiReply::LocalRead {
+                        id,
+                        value,
+                        exec_bar,
+                        hlc,
+                    } if id == req_id => {
+                        break Some(LocalReadResult {
+                            value,
+                            exec_bar,
+                            hlc,
+                        });
+                    }
+                    ApiReply::Reply {
+                        id,
+                        error: Some(code),
+                        ..
+                    } if id == req_id => {
+                        return logged_err!(
+                            Rejected;
+                            "server {} refused local read: {}", server, code
+                        );
+                    }
+                    // late replies to earlier reads or unsolicited
+                    // notifications
+                    _ => continue,
+                }
+            };
+            results.insert(server, result);
+        }
+        Ok(results)
+    }
+}
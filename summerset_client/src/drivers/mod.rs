@@ -1,15 +1,66 @@
-//! Closed-loop & Open-loop client-side driver implementations.
+//! Closed-loop, Open-loop & Learner client-side driver implementations.
+
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use tokio::time::Duration;
 
-use summerset::{CommandResult, ReplicaId, RequestId};
+use summerset::{CommandResult, ReplicaId, ReplyError, RequestId};
 
 mod closed_loop;
+mod learner;
 mod open_loop;
 
 pub(crate) use closed_loop::DriverClosedLoop;
+pub(crate) use learner::{DriverLearner, LocalReadResult};
 pub(crate) use open_loop::DriverOpenLoop;
 
+/// Whether closed-loop drivers tag requests with config epochs.
+static FENCE_EPOCHS: AtomicBool = AtomicBool::new(false);
+
+/// Makes closed-loop drivers tag their requests with the config epoch
+/// learned from the manager, so that replicas refuse them once the config
+/// has changed. Should be called once at startup.
+pub(crate) fn set_fence_epochs(enabled: bool) {
+    FENCE_EPOCHS.store(enabled, Ordering::Relaxed);
+}
+
+/// Do closed-loop drivers tag requests with config epochs?
+#[inline]
+fn fence_epochs() -> bool {
+    FENCE_EPOCHS.load(Ordering::Relaxed)
+}
+
+/// Whether closed-loop drivers attach checksums to their commands.
+static CHECKSUMS: AtomicBool = AtomicBool::new(false);
+
+/// Makes closed-loop drivers attach a checksum to every command, which the
+/// replicas verify right before execution. Should be called once at startup.
+pub(crate) fn set_checksums(enabled: bool) {
+    CHECKSUMS.store(enabled, Ordering::Relaxed);
+}
+
+/// Do closed-loop drivers attach checksums to their commands?
+#[inline]
+fn checksums() -> bool {
+    CHECKSUMS.load(Ordering::Relaxed)
+}
+
+/// Whether open-loop drivers ask for early commit acknowledgements.
+static COMMIT_ACKS: AtomicBool = AtomicBool::new(false);
+
+/// Makes open-loop drivers ask for an early acknowledgement of every Put
+/// request once it is committed, ahead of its execution result. Should be
+/// called once at startup.
+pub(crate) fn set_commit_acks(enabled: bool) {
+    COMMIT_ACKS.store(enabled, Ordering::Relaxed);
+}
+
+/// Do open-loop drivers ask for early commit acknowledgements?
+#[inline]
+fn commit_acks() -> bool {
+    COMMIT_ACKS.load(Ordering::Relaxed)
+}
+
 /// Reply result type, common across the two driver styles.
 #[derive(Debug, Clone)]
 pub(crate) enum DriverReply {
@@ -23,6 +74,15 @@ pub(crate) enum DriverReply {
         latency: Duration,
     },
 
+    /// Early acknowledgement that a Put request has been committed; its
+    /// `Success` reply follows once executed. (only if enabled)
+    Committed {
+        /// Request ID.
+        req_id: RequestId,
+        /// Latency duration until committed.
+        latency: Duration,
+    },
+
     /// Responders config change reply. (only for relevant protocols)
     Conf {
         /// Request ID.
@@ -34,9 +94,27 @@ pub(crate) enum DriverReply {
     /// Service indicated redirection.
     Redirect { server: ReplicaId },
 
+    /// Service refused the request with an error code.
+    Rejected { code: ReplyError },
+
     /// Unknown failure.
     Failure,
 
     /// Client-side timer timeout.
     Timeout,
 }
+
+impl DriverReply {
+    /// Makes the driver reply for a request the service did not serve, from
+    /// the redirect hint and error code it replied with.
+    pub(crate) fn unserved(
+        redirect: Option<ReplicaId>,
+        error: Option<ReplyError>,
+    ) -> Self {
+        match (redirect, error) {
+            (Some(server), _) => DriverReply::Redirect { server },
+            (None, Some(code)) => DriverReply::Rejected { code },
+            (None, None) => DriverReply::Failure,
+        }
+    }
+}
@@ -7,7 +7,7 @@
 
 use std::collections::HashMap;
 
-use crate::drivers::DriverReply;
+use crate::drivers::{commit_acks, DriverReply};
 
 use tokio::time::{Duration, Instant};
 
@@ -31,6 +31,10 @@ pub(crate) struct DriverOpenLoop {
     /// Last request reported `WouldBlock` failure.
     should_retry: bool,
 
+    /// Number of requests in the last send attempt, i.e., to be resent by a
+    /// retry if it reported `WouldBlock` failure.
+    retry_cnt: u64,
+
     /// Reply timeout timer.
     timer: Timer,
 
@@ -49,6 +53,7 @@ impl DriverOpenLoop {
             next_req: 0,
             pending_reqs: HashMap::new(),
             should_retry: false,
+            retry_cnt: 1,
             timer: Timer::default(),
             timeout,
         }
@@ -67,84 +72,113 @@ impl DriverOpenLoop {
         self.endpoint.leave(permanent).await
     }
 
-    /// Makes a Get request. Returns request ID for later reference if send
-    /// successful, or `Ok(None)` if got a `WouldBlock` failure. In the latter
-    /// case, caller must do `retry()`s before issuing any new requests,
-    /// typically after doing a few `wait_reply()`s to free up some TCP socket
-    /// buffer space.
-    pub(crate) fn issue_get(
-        &mut self,
-        key: &str,
-    ) -> Result<Option<RequestId>, SummersetError> {
-        let req_id = self.next_req;
-        let req = ApiRequest::Req {
-            id: req_id,
-            cmd: Command::Get { key: key.into() },
-        };
+    /// Composes the request carrying a command, asking for an early commit
+    /// acknowledgement if enabled and the command is a Put.
+    fn make_req(req_id: RequestId, cmd: Command) -> ApiRequest {
+        if commit_acks() && matches!(cmd, Command::Put { .. }) {
+            ApiRequest::AckedReq {
+                id: req_id,
+                cmd,
+                checksum: None,
+            }
+        } else {
+            ApiRequest::Req {
+                id: req_id,
+                cmd,
+                checksum: None,
+            }
+        }
+    }
 
-        if self.endpoint.send_req(Some(&req))? {
+    /// Bookkeeps the outcome of sending `cnt` requests with consecutive IDs
+    /// starting from `next_req`. Returns the first request ID if sent.
+    fn settle_issue(&mut self, sent: bool, cnt: u64) -> Option<RequestId> {
+        self.retry_cnt = cnt;
+        if sent {
             // successful
-            self.pending_reqs.insert(req_id, Instant::now());
-            self.next_req += 1;
+            let req_id = self.next_req;
+            let now = Instant::now();
+            for id in req_id..(req_id + cnt) {
+                self.pending_reqs.insert(id, now);
+            }
+            self.next_req += cnt;
             self.should_retry = false;
-            Ok(Some(req_id))
+            Some(req_id)
         } else {
             // got `WouldBlock` failure
             self.should_retry = true;
-            Ok(None)
+            None
         }
     }
 
-    /// Makes a Put request. Returns request ID for later reference if send
-    /// successful, or `Ok(None)` if got a `WouldBlock` failure. In the latter
-    /// case, caller must do `retry()`s before issuing any new requests,
-    /// typically after doing a few `wait_reply()`s to free up some TCP socket
-    /// buffer space.
+    /// Makes a request of the given command. Returns request ID for later
+    /// reference if send successful, or `Ok(None)` if got a `WouldBlock`
+    /// failure. In the latter case, caller must do `retry()`s before issuing
+    /// any new requests, typically after doing a few `wait_reply()`s to free
+    /// up some TCP socket buffer space. If early commit acknowledgements are
+    /// enabled, a `Committed` reply may come ahead of the `Success` one of a
+    /// Put.
+    pub(crate) fn issue_cmd(
+        &mut self,
+        cmd: Command,
+    ) -> Result<Option<RequestId>, SummersetError> {
+        let req = Self::make_req(self.next_req, cmd);
+        let sent = self.endpoint.send_req(Some(&req))?;
+        Ok(self.settle_issue(sent, 1))
+    }
+
+    /// Makes a Get request. Same as `issue_cmd()` otherwise.
+    #[allow(dead_code)]
+    pub(crate) fn issue_get(
+        &mut self,
+        key: &str,
+    ) -> Result<Option<RequestId>, SummersetError> {
+        self.issue_cmd(Command::Get { key: key.into() })
+    }
+
+    /// Makes a Put request. Same as `issue_cmd()` otherwise.
     pub(crate) fn issue_put(
         &mut self,
         key: &str,
         value: &str,
     ) -> Result<Option<RequestId>, SummersetError> {
-        let req_id = self.next_req;
-        let req = ApiRequest::Req {
-            id: req_id,
-            cmd: Command::Put {
-                key: key.into(),
-                value: value.into(),
-            },
-        };
-
-        if self.endpoint.send_req(Some(&req))? {
-            // successful
-            self.pending_reqs.insert(req_id, Instant::now());
-            self.next_req += 1;
-            self.should_retry = false;
-            Ok(Some(req_id))
-        } else {
-            // got `WouldBlock` failure
-            self.should_retry = true;
-            Ok(None)
-        }
+        self.issue_cmd(Command::Put {
+            key: key.into(),
+            value: value.into(),
+        })
+    }
+
+    /// Makes requests of the given commands framed together in one message,
+    /// taking consecutive request IDs. Returns the first request ID if send
+    /// successful; same as `issue_cmd()` otherwise, with a retry resending
+    /// the whole batch.
+    pub(crate) fn issue_batch(
+        &mut self,
+        cmds: Vec<Command>,
+    ) -> Result<Option<RequestId>, SummersetError> {
+        let cnt = cmds.len() as u64;
+        let reqs: Vec<ApiRequest> = cmds
+            .into_iter()
+            .zip(self.next_req..)
+            .map(|(cmd, req_id)| Self::make_req(req_id, cmd))
+            .collect();
+        let sent = self.endpoint.send_req_batch(&reqs)?;
+        Ok(self.settle_issue(sent, cnt))
     }
 
-    /// Retries the last request that got a `WouldBlock` failure. Returns
-    /// request ID if this retry is successful.
+    /// Retries the last request (or batch of requests) that got a
+    /// `WouldBlock` failure. Returns the first request ID if this retry is
+    /// successful.
     pub(crate) fn issue_retry(
         &mut self,
     ) -> Result<Option<RequestId>, SummersetError> {
-        let req_id = self.next_req;
+        let sent = self.endpoint.send_req(None)?;
+        Ok(self.settle_issue(sent, self.retry_cnt))
+    }
 
-        if self.endpoint.send_req(None)? {
-            // successful
-            self.pending_reqs.insert(req_id, Instant::now());
-            self.next_req += 1;
-            self.should_retry = false;
-            Ok(Some(req_id))
-        } else {
-            // got `WouldBlock` failure
-            self.should_retry = true;
-            Ok(None)
-        }
+    /// Gets the number of requests in the last issue or retry.
+    pub(crate) fn last_issue_cnt(&self) -> u64 {
+        self.retry_cnt
     }
 
     /// Waits on a reply from the service with timeout. Returns `Ok(None)` if
@@ -178,6 +212,7 @@ impl DriverOpenLoop {
                     id: reply_id,
                     result: cmd_result,
                     redirect,
+                    error,
                     ..
                 }) => {
                     if !self.pending_reqs.contains_key(&reply_id) {
@@ -195,12 +230,40 @@ impl DriverOpenLoop {
                                 cmd_result: res,
                                 latency,
                             });
-                        } else if let Some(server) = redirect {
-                            return Ok(DriverReply::Redirect { server });
                         } else {
-                            return Ok(DriverReply::Failure);
+                            return Ok(DriverReply::unserved(redirect, error));
+                        }
+                    }
+                }
+
+                Some(ApiReply::Committed { id: reply_id }) => {
+                    // keep pending until the execution result comes
+                    if let Some(&issue_ts) = self.pending_reqs.get(&reply_id) {
+                        let latency = Instant::now().duration_since(issue_ts);
+                        return Ok(DriverReply::Committed {
+                            req_id: reply_id,
+                            latency,
+                        });
+                    }
+                }
+
+                Some(ApiReply::MsgTooLarge { id, max_bytes }) => {
+                    if let Some(reply_id) = id {
+                        if self.pending_reqs.remove(&reply_id).is_none() {
+                            continue;
                         }
                     }
+                    return logged_err!(
+                        Rejected;
+                        "request rejected by server as larger than max {} bytes",
+                        max_bytes
+                    );
+                }
+
+                Some(ApiReply::LeaderChange { leader }) => {
+                    // endpoint has switched over to the new leader, to which
+                    // the request should be re-issued
+                    return Ok(DriverReply::Redirect { server: leader });
                 }
 
                 None => {
@@ -224,7 +287,7 @@ impl DriverOpenLoop {
 
     /// Gets current cluster size.
     #[allow(dead_code)]
-    pub(crate) fn population(&self) -> u8 {
+    pub(crate) fn population(&self) -> u16 {
         self.endpoint.population()
     }
 
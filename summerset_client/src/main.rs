@@ -1,6 +1,7 @@
 //! Summerset client side executable.
 
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::process::ExitCode;
 
 use clap::Parser;
@@ -8,14 +9,20 @@ use clap::Parser;
 use tokio::runtime::Builder;
 use tokio::time::Duration;
 
-use summerset::{logger_init, pf_error, pf_warn, SmrProtocol, SummersetError};
+use summerset::{
+    logger_init, pf_error, pf_info, pf_warn, set_client_identity_path,
+    set_codec, set_max_msg_bytes, ClientCtrlStub, Codec, ErrorKind,
+    SmrProtocol, SummersetError,
+};
 
 mod clients;
 mod drivers;
 use crate::clients::{
-    ClientBench, ClientMess, ClientMode, ClientRepl, ClientTester,
-    ModeParamsBench,
+    ClientAutotune, ClientBench, ClientCanary, ClientCompare, ClientLearner,
+    ClientLoad, ClientManifest, ClientMess, ClientMode, ClientObserve,
+    ClientRepl, ClientTester, ModeParamsBench,
 };
+use crate::drivers::{set_checksums, set_commit_acks, set_fence_epochs};
 
 mod zookeeper;
 use crate::zookeeper::{ZooKeeperBench, ZooKeeperSession};
@@ -59,7 +66,8 @@ struct CliArgs {
     #[arg(long, default_value_t = String::from(""))]
     config: String,
 
-    /// Client utility mode to run: repl|bench|tester|mess.
+    /// Client utility mode to run: repl|bench|tester|mess|observe|learner|
+    /// autotune|manifest|compare|canary|load.
     #[arg(short, long)]
     utility: String,
 
@@ -70,8 +78,14 @@ struct CliArgs {
 
     /// Cluster manager oracle's client-facing address.
     /// If 'protocol' is an external system, this is the connection address.
+    /// Not needed by the offline compare mode.
+    #[arg(short, long)]
+    manager: Option<SocketAddr>,
+
+    /// Any server replica's client-facing API address, through which to
+    /// discover the manager when its address is not given.
     #[arg(short, long)]
-    manager: SocketAddr,
+    bootstrap: Option<SocketAddr>,
 
     /// Number of tokio worker threads.
     #[arg(long, default_value_t = 4)]
@@ -80,6 +94,48 @@ struct CliArgs {
     /// Reply timeout duration in millisecs.
     #[arg(long, default_value_t = 5000)]
     timeout_ms: u64,
+
+    /// Delay in millisecs after which an outstanding read gets hedged to
+    /// another replica, if the protocol allows; 0 means no hedging.
+    #[arg(long, default_value_t = 0)]
+    hedge_ms: u64,
+
+    /// Values larger than this many KiB get written in chunks, each as a
+    /// separate Put, and reassembled on Get; 0 means no chunking.
+    #[arg(long, default_value_t = 0)]
+    chunk_kb: usize,
+
+    /// Maximum size in bytes of any message to or from servers; larger
+    /// requests fail right away without being sent. 0 means unlimited.
+    #[arg(long, default_value_t = 0)]
+    max_msg_bytes: usize,
+
+    /// Serialization codec for messages: 'bincode' or 'postcard'. Must match
+    /// the one of the manager and servers.
+    #[arg(long, default_value_t = String::from("bincode"))]
+    codec: String,
+
+    /// Tag requests with the config epoch learned from the manager, so that
+    /// replicas refuse them once the config has changed; refused requests
+    /// refresh the config before failing.
+    #[arg(long, default_value_t = false)]
+    fence_epochs: bool,
+
+    /// Attach a checksum to every command, which replicas verify right
+    /// before execution; corrupted commands fail with a checksum mismatch.
+    #[arg(long, default_value_t = false)]
+    checksums: bool,
+
+    /// Ask for an early acknowledgement of every Put request once it is
+    /// committed, ahead of its execution result; writes then count as done
+    /// upon commit. Only protocols that report commits send them.
+    #[arg(long, default_value_t = false)]
+    commit_acks: bool,
+
+    /// Optional file to persist the client identity issued by the manager
+    /// to; a restarted client reclaims the identity found in it.
+    #[arg(long)]
+    identity: Option<PathBuf>,
 }
 
 impl CliArgs {
@@ -89,28 +145,64 @@ impl CliArgs {
         &self,
     ) -> Result<(ClientMode, SmrProtocolOrSystem), SummersetError> {
         if self.threads < 2 {
-            Err(SummersetError::msg(format!(
-                "invalid number of threads {}",
-                self.threads
-            )))
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                format!("invalid number of threads {}", self.threads),
+            ))
         } else if self.timeout_ms == 0 {
-            Err(SummersetError::msg(format!(
-                "invalid timeout duration {} ms",
-                self.timeout_ms
-            )))
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                format!("invalid timeout duration {} ms", self.timeout_ms),
+            ))
+        } else if self.hedge_ms >= self.timeout_ms {
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                format!(
+                    "hedging delay {} ms not smaller than timeout {} ms",
+                    self.hedge_ms, self.timeout_ms
+                ),
+            ))
+        } else if Codec::parse_name(&self.codec).is_none() {
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                format!("codec name '{}' unrecognized", self.codec),
+            ))
         } else {
             let mode = ClientMode::parse_name(&self.utility).ok_or(
-                SummersetError::msg(format!(
-                    "utility mode '{}' unrecognized",
-                    self.utility
-                )),
+                SummersetError::new(
+                    ErrorKind::Config,
+                    format!("utility mode '{}' unrecognized", self.utility),
+                ),
             )?;
             let protocol = SmrProtocolOrSystem::parse_name(&self.protocol)
-                .ok_or(SummersetError::msg(format!(
-                    "protocol name '{}' unrecognized",
-                    self.protocol
-                )))?;
-            Ok((mode, protocol))
+                .ok_or(SummersetError::new(
+                    ErrorKind::Config,
+                    format!("protocol name '{}' unrecognized", self.protocol),
+                ))?;
+            match (self.manager, self.bootstrap) {
+                (None, None) if mode == ClientMode::Compare => {
+                    Ok((mode, protocol))
+                }
+                (Some(_), Some(_)) | (None, None) => Err(SummersetError::new(
+                    ErrorKind::Config,
+                    "exactly one of manager or bootstrap must be given",
+                )),
+                (None, Some(_))
+                    if !matches!(
+                        protocol,
+                        SmrProtocolOrSystem::Protocol(_)
+                    ) =>
+                {
+                    Err(SummersetError::new(
+                        ErrorKind::Config,
+                        format!(
+                            "bootstrap not supported for '{}'",
+                            self.protocol
+                        ),
+                    ))
+                }
+                _ => Ok((mode, protocol)),
+            }
         }
     }
 }
@@ -120,6 +212,14 @@ fn client_main() -> Result<(), SummersetError> {
     // read in and parse command line arguments
     let mut args = CliArgs::parse();
     let (mode, protocol) = args.sanitize()?;
+    set_max_msg_bytes(args.max_msg_bytes);
+    set_codec(Codec::parse_name(&args.codec).unwrap());
+    set_fence_epochs(args.fence_epochs);
+    set_checksums(args.checksums);
+    set_commit_acks(args.commit_acks);
+    if let Some(path) = args.identity.clone() {
+        set_client_identity_path(path);
+    }
 
     // parse optional config string if given
     let config_str = if args.config.is_empty() {
@@ -137,6 +237,37 @@ fn client_main() -> Result<(), SummersetError> {
         Some(&args.params[..])
     };
 
+    // offline post-processing, not connecting to any service
+    if mode == ClientMode::Compare {
+        return ClientCompare::new(params_str)?.run();
+    }
+
+    // parse optional hedging delay
+    let hedge_delay = if args.hedge_ms > 0 {
+        if mode == ClientMode::Bench {
+            pf_warn!("request hedging not supported by open-loop bench yet");
+        }
+        Some(Duration::from_millis(args.hedge_ms))
+    } else {
+        None
+    };
+
+    if args.fence_epochs && mode == ClientMode::Bench {
+        pf_warn!("epoch fencing not supported by open-loop bench yet");
+    }
+    if args.checksums && mode == ClientMode::Bench {
+        pf_warn!("command checksums not supported by open-loop bench yet");
+    }
+    if args.commit_acks && mode != ClientMode::Bench {
+        pf_warn!("early commit acks only supported by open-loop bench yet");
+    }
+
+    // parse optional large-value chunk size
+    let chunk_size = args.chunk_kb * 1024;
+    if chunk_size > 0 && mode == ClientMode::Bench {
+        pf_warn!("value chunking not supported by open-loop bench yet");
+    }
+
     // create tokio multi-threaded runtime
     let runtime = Builder::new_multi_thread()
         .enable_all()
@@ -149,15 +280,32 @@ fn client_main() -> Result<(), SummersetError> {
         match protocol {
             // Summerset-implemented protocol
             SmrProtocolOrSystem::Protocol(protocol) => {
-                let endpoint = protocol
-                    .new_client_endpoint(args.manager, config_str)
-                    .await?;
+                let manager = match args.manager {
+                    Some(manager) => manager,
+                    None => {
+                        // ensured by sanitize
+                        let bootstrap = args.bootstrap.unwrap();
+                        let (manager, servers) =
+                            ClientCtrlStub::discover_cluster(bootstrap).await?;
+                        pf_info!(
+                            "discovered manager '{}' and {} servers via '{}'",
+                            manager,
+                            servers.len(),
+                            bootstrap
+                        );
+                        manager
+                    }
+                };
+                let endpoint =
+                    protocol.new_client_endpoint(manager, config_str).await?;
                 match mode {
                     ClientMode::Repl => {
                         // run interactive REPL loop
                         let mut repl = ClientRepl::new(
                             endpoint,
                             Duration::from_millis(args.timeout_ms),
+                            hedge_delay,
+                            chunk_size,
                         );
                         repl.run().await?;
                     }
@@ -175,6 +323,8 @@ fn client_main() -> Result<(), SummersetError> {
                         let mut tester = ClientTester::new(
                             endpoint,
                             Duration::from_millis(args.timeout_ms),
+                            hedge_delay,
+                            chunk_size,
                             params_str,
                         )?;
                         tester.run().await?;
@@ -184,16 +334,79 @@ fn client_main() -> Result<(), SummersetError> {
                         let mut mess = ClientMess::new(
                             endpoint,
                             Duration::from_millis(args.timeout_ms),
+                            hedge_delay,
+                            chunk_size,
                             params_str,
                         )?;
                         mess.run().await?;
                     }
+                    ClientMode::Observe => {
+                        // run manager event stream observer
+                        let mut observe =
+                            ClientObserve::new(endpoint, params_str)?;
+                        observe.run().await?;
+                    }
+                    ClientMode::Learner => {
+                        // run per-replica staleness learner
+                        let mut learner = ClientLearner::new(
+                            endpoint,
+                            Duration::from_millis(args.timeout_ms),
+                            params_str,
+                        )?;
+                        learner.run().await?;
+                    }
+                    ClientMode::Autotune => {
+                        // run protocol parameter tuner
+                        let mut autotune = ClientAutotune::new(
+                            endpoint,
+                            Duration::from_millis(args.timeout_ms),
+                            protocol,
+                            params_str,
+                        )?;
+                        autotune.run().await?;
+                    }
+                    ClientMode::Canary => {
+                        // run background invariant checker
+                        let mut canary = ClientCanary::new(
+                            endpoint,
+                            Duration::from_millis(args.timeout_ms),
+                            hedge_delay,
+                            params_str,
+                        )?;
+                        canary.run().await?;
+                    }
+                    ClientMode::Load => {
+                        // run bulk data loader
+                        let mut load = ClientLoad::new(
+                            endpoint,
+                            Duration::from_millis(args.timeout_ms),
+                            params_str,
+                        )?;
+                        load.run().await?;
+                    }
+                    ClientMode::Compare => {
+                        unreachable!("compare mode handled offline")
+                    }
+                    ClientMode::Manifest => {
+                        // run experiment described by manifest
+                        let mut runner = ClientManifest::new(
+                            endpoint,
+                            protocol,
+                            manager,
+                            Duration::from_millis(args.timeout_ms),
+                            params_str,
+                        )?;
+                        runner.run().await?;
+                    }
                 }
             }
 
             // ZooKeeper
             SmrProtocolOrSystem::ZooKeeper => {
-                let session = ZooKeeperSession::new(args.manager, config_str)?;
+                let session = ZooKeeperSession::new(
+                    args.manager.unwrap(), // ensured by sanitize
+                    config_str,
+                )?;
                 match mode {
                     ClientMode::Bench => {
                         // run benchmarking client
@@ -213,7 +426,10 @@ fn client_main() -> Result<(), SummersetError> {
 
             // etcd
             SmrProtocolOrSystem::Etcd => {
-                let kv_client = EtcdKvClient::new(args.manager, config_str)?;
+                let kv_client = EtcdKvClient::new(
+                    args.manager.unwrap(), // ensured by sanitize
+                    config_str,
+                )?;
                 match mode {
                     ClientMode::Bench => {
                         // run benchmarking client
@@ -257,9 +473,18 @@ mod arg_tests {
         let args = CliArgs {
             protocol: "RepNothing".into(),
             utility: "repl".into(),
-            manager: "127.0.0.1:40001".parse()?,
+            manager: Some("127.0.0.1:40001".parse()?),
+            bootstrap: None,
             threads: 2,
             timeout_ms: 5000,
+            hedge_ms: 0,
+            chunk_kb: 0,
+            max_msg_bytes: 0,
+            codec: "bincode".into(),
+            fence_epochs: false,
+            checksums: false,
+            commit_acks: false,
+            identity: None,
             config: "".into(),
             params: "".into(),
         };
@@ -278,9 +503,18 @@ mod arg_tests {
         let args = CliArgs {
             protocol: "InvalidProtocol".into(),
             utility: "repl".into(),
-            manager: "127.0.0.1:40001".parse()?,
+            manager: Some("127.0.0.1:40001".parse()?),
+            bootstrap: None,
             threads: 2,
             timeout_ms: 5000,
+            hedge_ms: 0,
+            chunk_kb: 0,
+            max_msg_bytes: 0,
+            codec: "bincode".into(),
+            fence_epochs: false,
+            checksums: false,
+            commit_acks: false,
+            identity: None,
             config: "".into(),
             params: "".into(),
         };
@@ -293,9 +527,18 @@ mod arg_tests {
         let args = CliArgs {
             protocol: "RepNothing".into(),
             utility: "invalid_mode".into(),
-            manager: "127.0.0.1:40001".parse()?,
+            manager: Some("127.0.0.1:40001".parse()?),
+            bootstrap: None,
             threads: 2,
             timeout_ms: 5000,
+            hedge_ms: 0,
+            chunk_kb: 0,
+            max_msg_bytes: 0,
+            codec: "bincode".into(),
+            fence_epochs: false,
+            checksums: false,
+            commit_acks: false,
+            identity: None,
             config: "".into(),
             params: "".into(),
         };
@@ -308,9 +551,18 @@ mod arg_tests {
         let args = CliArgs {
             protocol: "RepNothing".into(),
             utility: "repl".into(),
-            manager: "127.0.0.1:40001".parse()?,
+            manager: Some("127.0.0.1:40001".parse()?),
+            bootstrap: None,
             threads: 1,
             timeout_ms: 5000,
+            hedge_ms: 0,
+            chunk_kb: 0,
+            max_msg_bytes: 0,
+            codec: "bincode".into(),
+            fence_epochs: false,
+            checksums: false,
+            commit_acks: false,
+            identity: None,
             config: "".into(),
             params: "".into(),
         };
@@ -323,13 +575,76 @@ mod arg_tests {
         let args = CliArgs {
             protocol: "RepNothing".into(),
             utility: "repl".into(),
-            manager: "127.0.0.1:40001".parse()?,
+            manager: Some("127.0.0.1:40001".parse()?),
+            bootstrap: None,
             threads: 2,
             timeout_ms: 0,
+            hedge_ms: 0,
+            chunk_kb: 0,
+            max_msg_bytes: 0,
+            codec: "bincode".into(),
+            fence_epochs: false,
+            checksums: false,
+            commit_acks: false,
+            identity: None,
             config: "".into(),
             params: "".into(),
         };
         assert!(args.sanitize().is_err());
         Ok(())
     }
+
+    #[test]
+    fn sanitize_invalid_hedge_ms() -> Result<(), SummersetError> {
+        let args = CliArgs {
+            protocol: "RepNothing".into(),
+            utility: "repl".into(),
+            manager: Some("127.0.0.1:40001".parse()?),
+            bootstrap: None,
+            threads: 2,
+            timeout_ms: 5000,
+            hedge_ms: 5000,
+            chunk_kb: 0,
+            max_msg_bytes: 0,
+            codec: "bincode".into(),
+            fence_epochs: false,
+            checksums: false,
+            commit_acks: false,
+            identity: None,
+            config: "".into(),
+            params: "".into(),
+        };
+        assert!(args.sanitize().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sanitize_invalid_bootstrap() -> Result<(), SummersetError> {
+        let mut args = CliArgs {
+            protocol: "RepNothing".into(),
+            utility: "repl".into(),
+            manager: Some("127.0.0.1:40001".parse()?),
+            bootstrap: Some("127.0.0.1:40002".parse()?),
+            threads: 2,
+            timeout_ms: 5000,
+            hedge_ms: 0,
+            chunk_kb: 0,
+            max_msg_bytes: 0,
+            codec: "bincode".into(),
+            fence_epochs: false,
+            checksums: false,
+            commit_acks: false,
+            identity: None,
+            config: "".into(),
+            params: "".into(),
+        };
+        assert!(args.sanitize().is_err());
+        args.manager = None;
+        assert!(args.sanitize().is_ok());
+        args.bootstrap = None;
+        assert!(args.sanitize().is_err());
+        args.utility = "compare".into();
+        assert!(args.sanitize().is_ok());
+        Ok(())
+    }
 }
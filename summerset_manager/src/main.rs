@@ -1,7 +1,9 @@
 //! Summerset cluster manager oracle.
 
-use std::net::{Ipv4Addr, SocketAddr};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
 use std::process::ExitCode;
+use std::time::Duration;
 
 use clap::Parser;
 
@@ -10,7 +12,10 @@ use log::{self, LevelFilter};
 use tokio::runtime::Builder;
 use tokio::sync::watch;
 
-use summerset::{logger_init, pf_error, SmrProtocol, SummersetError};
+use summerset::{
+    logger_init, pf_error, set_codec, Codec, ErrorKind, SmrProtocol,
+    SummersetError,
+};
 
 /// Command line arguments definition.
 #[derive(Parser, Debug)]
@@ -20,9 +25,10 @@ struct CliArgs {
     #[arg(short, long)]
     protocol: String,
 
-    /// Local IP to use for binding the listening sockets.
-    #[arg(short, long, default_value_t = Ipv4Addr::UNSPECIFIED)]
-    bind_ip: Ipv4Addr,
+    /// Local IP to use for binding the listening sockets. Either IPv4 or
+    /// IPv6; an IPv6 address makes the listeners dual-stack.
+    #[arg(short, long, default_value_t = IpAddr::V4(Ipv4Addr::UNSPECIFIED))]
+    bind_ip: IpAddr,
 
     /// Client-facing API port.
     /// This port must be available at process launch.
@@ -34,9 +40,45 @@ struct CliArgs {
     #[arg(short, long, default_value_t = 52600)]
     srv_port: u16,
 
+    /// Optional HTTP dashboard port; 0 means disabled.
+    #[arg(long, default_value_t = 0)]
+    http_port: u16,
+
+    /// Optional file to persist the control-plane event log to (appended
+    /// as JSON lines).
+    #[arg(long)]
+    event_log: Option<PathBuf>,
+
+    /// Optional cluster CA certificate (PEM) to hand out to servers; if
+    /// given, servers must authenticate each other with mutual TLS using
+    /// replica certificates issued by this CA.
+    #[arg(long)]
+    cluster_ca: Option<PathBuf>,
+
+    /// Optional state machine image, e.g., a file of a coordinated snapshot,
+    /// to hand out to servers; fresh servers load it before serving.
+    #[arg(long)]
+    seed_image: Option<PathBuf>,
+
+    /// Optional inclusive range of ports, in format of `start-end`, to
+    /// assign servers' API and p2p ports from, two per server ID in order.
+    /// Only servers launched with those ports given as 0 take them.
+    #[arg(long, default_value_t = String::from(""))]
+    port_range: String,
+
     /// Total number of server replicas in cluster.
     #[arg(short = 'n', long, default_value_t = 3)]
-    population: u8,
+    population: u16,
+
+    /// How long in millisecs a dropped client's state is kept on servers for
+    /// it to reconnect under the same identity; 0 means expiring right away.
+    #[arg(long, default_value_t = 0)]
+    client_grace_ms: u64,
+
+    /// Serialization codec for messages: 'bincode' or 'postcard'. Servers
+    /// and clients must use the same one to be able to connect.
+    #[arg(long, default_value_t = String::from("bincode"))]
+    codec: String,
 
     /// Number of tokio worker threads.
     #[arg(long, default_value_t = 16)]
@@ -44,38 +86,92 @@ struct CliArgs {
 }
 
 impl CliArgs {
+    /// Parses the port range to assign servers' ports from, if given.
+    fn port_range(&self) -> Result<Option<(u16, u16)>, SummersetError> {
+        if self.port_range.is_empty() {
+            return Ok(None);
+        }
+        let invalid = || {
+            SummersetError::new(
+                ErrorKind::Config,
+                format!("invalid port_range '{}'", self.port_range),
+            )
+        };
+        let (start, end) =
+            self.port_range.split_once('-').ok_or_else(invalid)?;
+        let (start, end): (u16, u16) = (start.parse()?, end.parse()?);
+        // must fit two ports per server and not cover my own ports
+        let needed = 2 * self.population as usize;
+        if start <= 1024
+            || (end as usize) + 1 < start as usize + needed
+            || [self.srv_port, self.cli_port, self.http_port]
+                .iter()
+                .any(|port| (start..=end).contains(port))
+        {
+            return Err(invalid());
+        }
+        Ok(Some((start, end)))
+    }
+
     /// Sanitize command line arguments, return `Ok(protocol)` on success
     /// or `Err(SummersetError)` on any error.
     fn sanitize(&self) -> Result<SmrProtocol, SummersetError> {
         if self.srv_port <= 1024 {
-            Err(SummersetError::msg(format!(
-                "invalid srv_port {}",
-                self.srv_port
-            )))
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                format!("invalid srv_port {}", self.srv_port),
+            ))
         } else if self.cli_port <= 1024 {
-            Err(SummersetError::msg(format!(
-                "invalid cli_port {}",
-                self.cli_port
-            )))
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                format!("invalid cli_port {}", self.cli_port),
+            ))
         } else if self.srv_port == self.cli_port {
-            Err(SummersetError::msg(format!(
-                "srv_port == cli_port {}",
-                self.srv_port
-            )))
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                format!("srv_port == cli_port {}", self.srv_port),
+            ))
+        } else if self.http_port != 0
+            && (self.http_port <= 1024
+                || self.http_port == self.srv_port
+                || self.http_port == self.cli_port)
+        {
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                format!("invalid http_port {}", self.http_port),
+            ))
         } else if self.population == 0 {
-            Err(SummersetError::msg(format!(
-                "invalid population {}",
-                self.population
-            )))
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                format!("invalid population {}", self.population),
+            ))
         } else if self.threads < 2 {
-            Err(SummersetError::msg(format!(
-                "invalid number of threads {}",
-                self.threads
-            )))
-        } else {
-            SmrProtocol::parse_name(&self.protocol).ok_or(SummersetError::msg(
-                format!("protocol name '{}' unrecognized", self.protocol),
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                format!("invalid number of threads {}", self.threads),
             ))
+        } else if let Err(e) = self.port_range() {
+            Err(e)
+        } else if Codec::parse_name(&self.codec).is_none() {
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                format!("codec name '{}' unrecognized", self.codec),
+            ))
+        } else {
+            let protocol = SmrProtocol::parse_name(&self.protocol).ok_or(
+                SummersetError::new(
+                    ErrorKind::Config,
+                    format!("protocol name '{}' unrecognized", self.protocol),
+                ),
+            )?;
+            if self.seed_image.is_some() && protocol != SmrProtocol::MultiPaxos
+            {
+                return Err(SummersetError::new(
+                    ErrorKind::Config,
+                    format!("seed_image not supported by {}", protocol),
+                ));
+            }
+            Ok(protocol)
         }
     }
 }
@@ -85,26 +181,21 @@ fn manager_main() -> Result<(), SummersetError> {
     // read in and parse command line arguments
     let args = CliArgs::parse();
     let protocol = args.sanitize()?;
+    let port_range = args.port_range()?;
+    set_codec(Codec::parse_name(&args.codec).unwrap());
 
     // parse server-facing API port
-    let srv_addr: SocketAddr = format!("{}:{}", args.bind_ip, args.srv_port)
-        .parse()
-        .map_err(|e| {
-            SummersetError::msg(format!(
-                "failed to parse srv_addr: bind_ip {} port {}: {}",
-                args.bind_ip, args.srv_port, e
-            ))
-        })?;
+    let srv_addr = SocketAddr::new(args.bind_ip, args.srv_port);
 
     // parse client-facing API port
-    let cli_addr: SocketAddr = format!("{}:{}", args.bind_ip, args.cli_port)
-        .parse()
-        .map_err(|e| {
-            SummersetError::msg(format!(
-                "failed to parse cli_addr: bind_ip {} port {}: {}",
-                args.bind_ip, args.cli_port, e
-            ))
-        })?;
+    let cli_addr = SocketAddr::new(args.bind_ip, args.cli_port);
+
+    // parse optional dashboard HTTP port
+    let http_addr: Option<SocketAddr> = if args.http_port != 0 {
+        Some(SocketAddr::new(args.bind_ip, args.http_port))
+    } else {
+        None
+    };
 
     // set up termination signals handler
     let (tx_term, rx_term) = watch::channel(false);
@@ -127,7 +218,17 @@ fn manager_main() -> Result<(), SummersetError> {
         // event loop logic
         runtime.block_on(async move {
             let mut manager = protocol
-                .new_cluster_manager_setup(srv_addr, cli_addr, args.population)
+                .new_cluster_manager_setup(
+                    srv_addr,
+                    cli_addr,
+                    http_addr,
+                    args.event_log.as_deref(),
+                    args.cluster_ca.as_deref(),
+                    args.seed_image.as_deref(),
+                    port_range,
+                    args.population,
+                    Duration::from_millis(args.client_grace_ms),
+                )
                 .await?;
 
             manager.run(rx_term).await?;
@@ -168,7 +269,14 @@ mod arg_tests {
             bind_ip: "127.0.0.1".parse()?,
             srv_port: 40000,
             cli_port: 40001,
+            http_port: 0,
+            event_log: None,
+            cluster_ca: None,
+            seed_image: None,
+            port_range: "".into(),
             population: 3,
+            client_grace_ms: 0,
+            codec: "bincode".into(),
             threads: 2,
         };
         assert_eq!(args.sanitize(), Ok(SmrProtocol::RepNothing));
@@ -182,7 +290,14 @@ mod arg_tests {
             bind_ip: "127.0.0.1".parse()?,
             srv_port: 1023,
             cli_port: 40001,
+            http_port: 0,
+            event_log: None,
+            cluster_ca: None,
+            seed_image: None,
+            port_range: "".into(),
             population: 3,
+            client_grace_ms: 0,
+            codec: "bincode".into(),
             threads: 2,
         };
         assert!(args.sanitize().is_err());
@@ -196,7 +311,14 @@ mod arg_tests {
             bind_ip: "127.0.0.1".parse()?,
             srv_port: 40000,
             cli_port: 1023,
+            http_port: 0,
+            event_log: None,
+            cluster_ca: None,
+            seed_image: None,
+            port_range: "".into(),
             population: 3,
+            client_grace_ms: 0,
+            codec: "bincode".into(),
             threads: 2,
         };
         assert!(args.sanitize().is_err());
@@ -210,7 +332,35 @@ mod arg_tests {
             bind_ip: "127.0.0.1".parse()?,
             srv_port: 40000,
             cli_port: 40000,
+            http_port: 0,
+            event_log: None,
+            cluster_ca: None,
+            seed_image: None,
+            port_range: "".into(),
             population: 3,
+            client_grace_ms: 0,
+            codec: "bincode".into(),
+            threads: 2,
+        };
+        assert!(args.sanitize().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sanitize_invalid_http_port() -> Result<(), SummersetError> {
+        let args = CliArgs {
+            protocol: "RepNothing".into(),
+            bind_ip: "127.0.0.1".parse()?,
+            srv_port: 40000,
+            cli_port: 40001,
+            http_port: 40001,
+            event_log: None,
+            cluster_ca: None,
+            seed_image: None,
+            port_range: "".into(),
+            population: 3,
+            client_grace_ms: 0,
+            codec: "bincode".into(),
             threads: 2,
         };
         assert!(args.sanitize().is_err());
@@ -224,7 +374,14 @@ mod arg_tests {
             bind_ip: "127.0.0.1".parse()?,
             srv_port: 40000,
             cli_port: 40001,
+            http_port: 0,
+            event_log: None,
+            cluster_ca: None,
+            seed_image: None,
+            port_range: "".into(),
             population: 3,
+            client_grace_ms: 0,
+            codec: "bincode".into(),
             threads: 2,
         };
         assert!(args.sanitize().is_err());
@@ -238,7 +395,14 @@ mod arg_tests {
             bind_ip: "127.0.0.1".parse()?,
             srv_port: 40000,
             cli_port: 40001,
+            http_port: 0,
+            event_log: None,
+            cluster_ca: None,
+            seed_image: None,
+            port_range: "".into(),
             population: 0,
+            client_grace_ms: 0,
+            codec: "bincode".into(),
             threads: 2,
         };
         assert!(args.sanitize().is_err());
@@ -252,10 +416,66 @@ mod arg_tests {
             bind_ip: "127.0.0.1".parse()?,
             srv_port: 40000,
             cli_port: 40001,
+            http_port: 0,
+            event_log: None,
+            cluster_ca: None,
+            seed_image: None,
+            port_range: "".into(),
             population: 3,
+            client_grace_ms: 0,
+            codec: "bincode".into(),
             threads: 1,
         };
         assert!(args.sanitize().is_err());
         Ok(())
     }
+
+    #[test]
+    fn sanitize_seed_image() -> Result<(), SummersetError> {
+        let mut args = CliArgs {
+            protocol: "MultiPaxos".into(),
+            bind_ip: "127.0.0.1".parse()?,
+            srv_port: 40000,
+            cli_port: 40001,
+            http_port: 0,
+            event_log: None,
+            cluster_ca: None,
+            seed_image: Some("/tmp/summerset.multipaxos.snap.at7".into()),
+            port_range: "".into(),
+            population: 3,
+            client_grace_ms: 0,
+            codec: "bincode".into(),
+            threads: 2,
+        };
+        assert_eq!(args.sanitize(), Ok(SmrProtocol::MultiPaxos));
+        args.protocol = "RepNothing".into();
+        assert!(args.sanitize().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sanitize_port_range() -> Result<(), SummersetError> {
+        let mut args = CliArgs {
+            protocol: "RepNothing".into(),
+            bind_ip: "127.0.0.1".parse()?,
+            srv_port: 40000,
+            cli_port: 40001,
+            http_port: 0,
+            event_log: None,
+            cluster_ca: None,
+            seed_image: None,
+            port_range: "40100-40105".into(),
+            population: 3,
+            client_grace_ms: 0,
+            codec: "bincode".into(),
+            threads: 2,
+        };
+        assert_eq!(args.sanitize(), Ok(SmrProtocol::RepNothing));
+        assert_eq!(args.port_range()?, Some((40100, 40105)));
+        for range in ["40100-40104", "39999-40100", "40100", "1000-1010"] {
+            args.port_range = range.into();
+            assert!(args.sanitize().is_err());
+        }
+        Ok(())
+    }
 }
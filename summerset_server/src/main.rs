@@ -1,6 +1,8 @@
 //! Summerset server replica executable.
 
-use std::net::{Ipv4Addr, SocketAddr};
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
 use std::process::ExitCode;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -12,7 +14,16 @@ use log::{self, LevelFilter};
 use tokio::runtime::Builder;
 use tokio::sync::watch;
 
-use summerset::{logger_init, pf_error, SmrProtocol, SummersetError};
+use summerset::{
+    logger_init, logger_max_level, pf_error, set_codec, set_debug_api,
+    set_max_msg_bytes, set_mirror_target, set_panic_dump_path,
+    set_peer_tls_dir, set_rng_seed, Codec, ErrorKind, SmrProtocol,
+    SummersetError, WsBridge,
+};
+
+/// Smallest nonzero `--max-msg-bytes` allowed, so that pieces of large peer
+/// messages streamed in chunks always fit.
+const MIN_MAX_MSG_BYTES: usize = 1024 * 1024;
 
 /// Command line arguments definition.
 #[derive(Parser, Debug)]
@@ -27,55 +38,252 @@ struct CliArgs {
     #[arg(long, default_value_t = String::from(""))]
     config: String,
 
-    /// Local IP to use for binding the listening sockets.
-    #[arg(short, long, default_value_t = Ipv4Addr::UNSPECIFIED)]
-    bind_ip: Ipv4Addr,
+    /// Local IP to use for binding the listening sockets. Either IPv4 or
+    /// IPv6; an IPv6 address makes the listeners dual-stack.
+    #[arg(short, long, default_value_t = IpAddr::V4(Ipv4Addr::UNSPECIFIED))]
+    bind_ip: IpAddr,
 
-    /// Key-value API port open to clients.
-    /// This port must be available at process launch.
+    /// Key-value API port open to clients; 0 means taking the one assigned
+    /// by the manager. This port must be available at process launch.
     #[arg(short, long, default_value_t = 52700)]
     api_port: u16,
 
-    /// Internal peer-peer communication API port.
-    /// This port must be available at process launch.
+    /// Internal peer-peer communication API port; 0 means taking the one
+    /// assigned by the manager. This port must be available at process
+    /// launch.
     #[arg(short = 'i', long, default_value_t = 52800)]
     p2p_port: u16,
 
+    /// Optional WebSocket bridge port for browser clients; 0 means disabled.
+    #[arg(long, default_value_t = 0)]
+    ws_port: u16,
+
     /// Cluster manager oracle's server-facing address.
     #[arg(short, long)]
-    manager: SocketAddr,
+    manager: Option<SocketAddr>,
 
     /// Number of tokio worker threads.
     #[arg(long, default_value_t = 16)]
     threads: usize,
+
+    /// Number of replicas to run as tasks sharing this one process, for
+    /// low-overhead local experiments. The i-th replica takes `api_port + i`
+    /// and `p2p_port + i` (or ports assigned by the manager if 0), and every
+    /// `{i}` in the config string is replaced by i. If any of them gets
+    /// reset, all of them restart.
+    #[arg(long, default_value_t = 1)]
+    colocate: u16,
+
+    /// Maximum size in bytes of any message from or to clients and peers;
+    /// larger ones get rejected gracefully. 0 means unlimited.
+    #[arg(long, default_value_t = 0)]
+    max_msg_bytes: usize,
+
+    /// Seed for randomized choices such as election timeouts, so that runs
+    /// with the same seed and fault schedule behave the same. Each replica
+    /// derives its own sequence from it; unseeded if not given.
+    #[arg(long)]
+    rng_seed: Option<u64>,
+
+    /// Serialization codec for messages and durable log entries: 'bincode'
+    /// or 'postcard'. Must match across the manager, servers, and clients.
+    #[arg(long, default_value_t = String::from("bincode"))]
+    codec: String,
+
+    /// Directory holding this server's replica certificate and private key
+    /// (PEM) as `replica<id>.crt` and `replica<id>.key`, required if the
+    /// manager is set up with a cluster CA.
+    #[arg(long)]
+    tls_dir: Option<PathBuf>,
+
+    /// File to dump the protocol's latest status and recent events to if the
+    /// server panics.
+    #[arg(long)]
+    panic_dump: Option<PathBuf>,
+
+    /// Client-facing address of another cluster's manager to asynchronously
+    /// mirror the writes executed by this server to, in causal order. Meant
+    /// to be given to every server of the cluster, of which one ships at a
+    /// time.
+    #[arg(long)]
+    mirror_to: Option<SocketAddr>,
+
+    /// Serve `Debug` requests exposing internal protocol state to clients.
+    /// Meant for test deployments only.
+    #[arg(long, default_value_t = false)]
+    debug_api: bool,
+
+    /// Probabilities of corrupting, reordering, and duplicating each peer
+    /// message sent, as `corrupt,reorder,duplicate`. Meant for testing only.
+    #[cfg(feature = "byzantine-hooks")]
+    #[arg(long, default_value_t = String::from("0,0,0"))]
+    byzantine: String,
+
+    /// Only check the integrity of durable files named in config, print a
+    /// report, and exit without joining the cluster.
+    #[arg(long, default_value_t = false)]
+    fsck: bool,
+
+    /// With `--fsck`, also truncate away corrupted trailing content.
+    #[arg(long, default_value_t = false)]
+    fsck_repair: bool,
+
+    /// Only export durable files named in config as JSON to this path, and
+    /// exit without joining the cluster.
+    #[arg(long)]
+    export: Option<PathBuf>,
+
+    /// Only import durable files named in config from a JSON file exported
+    /// earlier, overwriting them, and exit without joining the cluster.
+    #[arg(long)]
+    import: Option<PathBuf>,
+
+    /// Only replay durable files named in config offline, logging every
+    /// protocol decision and executed command, and exit without joining the
+    /// cluster.
+    #[arg(long, default_value_t = false)]
+    replay: bool,
+
+    /// With `--replay`, stop after executing this slot.
+    #[arg(long)]
+    replay_until: Option<usize>,
 }
 
 impl CliArgs {
+    /// Is an offline tool mode that does not join the cluster given?
+    fn offline_mode(&self) -> bool {
+        self.fsck
+            || self.export.is_some()
+            || self.import.is_some()
+            || self.replay
+    }
+
     /// Sanitize command line arguments, return `Ok(protocol)` on success
     /// or `Err(SummersetError)` on any error.
     fn sanitize(&self) -> Result<SmrProtocol, SummersetError> {
-        if self.api_port <= 1024 {
-            Err(SummersetError::msg(format!(
-                "invalid api_port {}",
-                self.api_port
-            )))
-        } else if self.p2p_port <= 1024 {
-            Err(SummersetError::msg(format!(
-                "invalid p2p_port {}",
-                self.p2p_port
-            )))
-        } else if self.api_port == self.p2p_port {
-            Err(SummersetError::msg(format!(
-                "api_port == p2p_port {}",
-                self.api_port
-            )))
+        if self.api_port != 0 && self.api_port <= 1024 {
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                format!("invalid api_port {}", self.api_port),
+            ))
+        } else if self.p2p_port != 0 && self.p2p_port <= 1024 {
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                format!("invalid p2p_port {}", self.p2p_port),
+            ))
+        } else if self.api_port != 0 && self.api_port == self.p2p_port {
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                format!("api_port == p2p_port {}", self.api_port),
+            ))
+        } else if self.colocate == 0 {
+            Err(SummersetError::new(ErrorKind::Config, "invalid colocate 0"))
+        } else if self.colocate > 1
+            && self.api_port != 0
+            && self.p2p_port != 0
+            && self.api_port.abs_diff(self.p2p_port) < self.colocate
+        {
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                format!(
+                    "api_port and p2p_port ranges overlap with colocate {}",
+                    self.colocate
+                ),
+            ))
+        } else if u32::from(self.api_port.max(self.p2p_port))
+            + u32::from(self.colocate)
+            > 65536
+        {
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                format!("ports out of range with colocate {}", self.colocate),
+            ))
+        } else if self.colocate > 1
+            && (self.ws_port != 0
+                || self.tls_dir.is_some()
+                || self.panic_dump.is_some()
+                || self.mirror_to.is_some()
+                || self.offline_mode())
+        {
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                "--colocate cannot be combined with --ws-port, --tls-dir, \
+                 --panic-dump, --mirror-to, or offline modes",
+            ))
+        } else if self.colocate > 1 && !self.config.contains("{i}") {
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                "--colocate requires '{i}' in config to keep the replicas' \
+                 files apart",
+            ))
+        } else if self.ws_port != 0
+            && (self.ws_port <= 1024
+                || self.ws_port == self.api_port
+                || self.ws_port == self.p2p_port)
+        {
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                format!("invalid ws_port {}", self.ws_port),
+            ))
+        } else if self.ws_port != 0 && self.api_port == 0 {
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                "ws_port given without an explicit api_port",
+            ))
+        } else if self.fsck_repair && !self.fsck {
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                "--fsck-repair given without --fsck",
+            ))
+        } else if self.replay_until.is_some() && !self.replay {
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                "--replay-until given without --replay",
+            ))
+        } else if [
+            self.fsck,
+            self.export.is_some(),
+            self.import.is_some(),
+            self.replay,
+        ]
+        .iter()
+        .filter(|&&m| m)
+        .count()
+            > 1
+        {
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                "at most one of --fsck, --export, --import, --replay can be \
+                 given",
+            ))
+        } else if self.manager.is_none() && !self.offline_mode() {
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                "manager address not given",
+            ))
         } else if self.threads < 2 {
-            Err(SummersetError::msg(format!(
-                "invalid number of threads {}",
-                self.threads
-            )))
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                format!("invalid number of threads {}", self.threads),
+            ))
+        } else if self.max_msg_bytes != 0
+            && self.max_msg_bytes < MIN_MAX_MSG_BYTES
+        {
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                format!(
+                    "invalid max_msg_bytes {}, must be 0 or >= {}",
+                    self.max_msg_bytes, MIN_MAX_MSG_BYTES
+                ),
+            ))
+        } else if Codec::parse_name(&self.codec).is_none() {
+            Err(SummersetError::new(
+                ErrorKind::Config,
+                format!("codec name '{}' unrecognized", self.codec),
+            ))
         } else {
-            SmrProtocol::parse_name(&self.protocol).ok_or(SummersetError::msg(
+            SmrProtocol::parse_name(&self.protocol).ok_or(SummersetError::new(
+                ErrorKind::Config,
                 format!("protocol name '{}' unrecognized", self.protocol),
             ))
         }
@@ -87,26 +295,49 @@ fn server_main() -> Result<(), SummersetError> {
     // read in and parse command line arguments
     let mut args = CliArgs::parse();
     let protocol = args.sanitize()?;
+    set_max_msg_bytes(args.max_msg_bytes);
+    set_codec(Codec::parse_name(&args.codec).unwrap());
+    if let Some(rng_seed) = args.rng_seed {
+        set_rng_seed(rng_seed);
+    }
+    if let Some(tls_dir) = &args.tls_dir {
+        set_peer_tls_dir(tls_dir.clone());
+    }
+    if let Some(panic_dump) = &args.panic_dump {
+        set_panic_dump_path(panic_dump.clone());
+    }
+    if let Some(mirror_to) = args.mirror_to {
+        set_mirror_target(mirror_to);
+    }
+    set_debug_api(args.debug_api);
+    #[cfg(feature = "byzantine-hooks")]
+    {
+        let probs = args
+            .byzantine
+            .split(',')
+            .map(|p| p.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()?;
+        if probs.len() != 3 {
+            return Err(SummersetError::new(
+                ErrorKind::Config,
+                format!("invalid byzantine probabilities '{}'", args.byzantine),
+            ));
+        }
+        summerset::set_byzantine_hooks(probs[0], probs[1], probs[2])?;
+    }
 
     // parse key-value API port
-    let api_addr: SocketAddr = format!("{}:{}", args.bind_ip, args.api_port)
-        .parse()
-        .map_err(|e| {
-            SummersetError::msg(format!(
-                "failed to parse api_addr: bind_ip {} port {}: {}",
-                args.bind_ip, args.api_port, e
-            ))
-        })?;
+    let api_addr = SocketAddr::new(args.bind_ip, args.api_port);
 
     // parse internal peer-peer API port
-    let p2p_addr: SocketAddr = format!("{}:{}", args.bind_ip, args.p2p_port)
-        .parse()
-        .map_err(|e| {
-            SummersetError::msg(format!(
-                "failed to parse p2p_addr: bind_ip {} port {}: {}",
-                args.bind_ip, args.p2p_port, e
-            ))
-        })?;
+    let p2p_addr = SocketAddr::new(args.bind_ip, args.p2p_port);
+
+    // parse optional WebSocket bridge port
+    let ws_addr: Option<SocketAddr> = if args.ws_port != 0 {
+        Some(SocketAddr::new(args.bind_ip, args.ws_port))
+    } else {
+        None
+    };
 
     // parse optional config string if given
     let config_str = if args.config.is_empty() {
@@ -116,6 +347,68 @@ fn server_main() -> Result<(), SummersetError> {
         Some(&args.config[..])
     };
 
+    // in fsck mode, check durable files and exit
+    if args.fsck {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        let report = runtime.block_on(
+            protocol.fsck_server_files(config_str, args.fsck_repair),
+        )?;
+        println!("fsck of {} replica files:\n{}", protocol, report);
+        return if report.is_clean() {
+            Ok(())
+        } else {
+            Err(SummersetError::msg("fsck found unrepaired problems"))
+        };
+    }
+
+    // in export/import mode, convert durable files and exit
+    if let Some(path) = args.export.as_ref() {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        let json =
+            runtime.block_on(protocol.export_server_files(config_str))?;
+        fs::write(path, json)?;
+        println!(
+            "exported {} replica files to '{}'",
+            protocol,
+            path.display()
+        );
+        return Ok(());
+    }
+    if let Some(path) = args.import.as_ref() {
+        let json = fs::read_to_string(path)?;
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        runtime.block_on(protocol.import_server_files(config_str, &json))?;
+        println!(
+            "imported {} replica files from '{}'",
+            protocol,
+            path.display()
+        );
+        return Ok(());
+    }
+
+    // in replay mode, replay durable files offline and exit
+    if args.replay {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        runtime.block_on(
+            protocol.replay_server_files(config_str, args.replay_until),
+        )?;
+        return Ok(());
+    }
+    let manager = args.manager.unwrap(); // ensured by sanitize
+
+    // parse per-replica addresses and config strings if colocating several
+    // replicas in this process
+    let colocated: Vec<_> = (0..args.colocate)
+        .map(|i| {
+            let port = |base: u16| if base == 0 { 0 } else { base + i };
+            (
+                SocketAddr::new(args.bind_ip, port(args.api_port)),
+                SocketAddr::new(args.bind_ip, port(args.p2p_port)),
+                config_str.map(|c| c.replace("{i}", &i.to_string())),
+            )
+        })
+        .collect();
+
     // set up termination signals handler
     let (tx_term, rx_term) = watch::channel(false);
     ctrlc::set_handler(move || {
@@ -124,13 +417,15 @@ fn server_main() -> Result<(), SummersetError> {
         }
     })?;
 
-    // using a while loop here to allow software-simulated crash-restart
-    let log_level = log::max_level();
+    // using a while loop here to allow software-simulated crash-restart;
+    // log filters may have been switched at runtime, so the level to
+    // restore is taken from the logger each time
     let shutdown = Arc::new(AtomicBool::new(false));
     while !shutdown.load(Ordering::SeqCst) {
-        log::set_max_level(log_level);
+        log::set_max_level(logger_max_level());
         let shutdown_clone = shutdown.clone();
         let rx_term_clone = rx_term.clone();
+        let colocated_clone = colocated.clone();
 
         // create tokio multi-threaded runtime
         let runtime = Builder::new_multi_thread()
@@ -139,19 +434,34 @@ fn server_main() -> Result<(), SummersetError> {
             .thread_name("tokio-worker-replica")
             .build()?;
 
-        // enter tokio runtime, setup the server replica, and start the main
-        // event loop logic
+        // enter tokio runtime, setup the server replica(s), and start the
+        // main event loop logic
         runtime.block_on(async move {
-            let mut replica = protocol
-                .new_server_replica_setup(
-                    api_addr,
-                    p2p_addr,
-                    args.manager,
-                    config_str,
-                )
-                .await?;
-
-            if replica.run(rx_term_clone).await? {
+            let restart = if colocated_clone.len() > 1 {
+                protocol
+                    .run_colocated_replicas(
+                        colocated_clone,
+                        manager,
+                        rx_term_clone,
+                    )
+                    .await?
+            } else {
+                let _ws_bridge = match ws_addr {
+                    Some(ws_addr) => {
+                        Some(WsBridge::new_and_setup(ws_addr, api_addr).await?)
+                    }
+                    None => None,
+                };
+
+                let mut replica = protocol
+                    .new_server_replica_setup(
+                        api_addr, p2p_addr, manager, config_str,
+                    )
+                    .await?;
+                replica.run(rx_term_clone).await?
+            };
+
+            if restart {
                 // event loop terminated but wants to restart (e.g., when
                 // receiving a reset control message); just drop this runtime
                 // and move to the next iteration of loop
@@ -169,7 +479,7 @@ fn server_main() -> Result<(), SummersetError> {
         })?;
     }
 
-    log::set_max_level(log_level);
+    log::set_max_level(logger_max_level());
     Ok(())
 }
 
@@ -197,9 +507,26 @@ mod arg_tests {
             bind_ip: "127.0.0.1".parse()?,
             api_port: 40103,
             p2p_port: 40203,
-            manager: "127.0.0.1:40000".parse()?,
+            ws_port: 0,
+            manager: Some("127.0.0.1:40000".parse()?),
             threads: 2,
+            colocate: 1,
+            max_msg_bytes: 0,
+            rng_seed: None,
+            codec: "bincode".into(),
+            tls_dir: None,
+            panic_dump: None,
+            mirror_to: None,
+            debug_api: false,
+            #[cfg(feature = "byzantine-hooks")]
+            byzantine: "0,0,0".into(),
             config: "".into(),
+            fsck: false,
+            fsck_repair: false,
+            export: None,
+            import: None,
+            replay: false,
+            replay_until: None,
         };
         assert_eq!(args.sanitize(), Ok(SmrProtocol::RepNothing));
         Ok(())
@@ -212,9 +539,26 @@ mod arg_tests {
             bind_ip: "127.0.0.1".parse()?,
             api_port: 1023,
             p2p_port: 40200,
-            manager: "127.0.0.1:40000".parse()?,
+            ws_port: 0,
+            manager: Some("127.0.0.1:40000".parse()?),
             threads: 2,
+            colocate: 1,
+            max_msg_bytes: 0,
+            rng_seed: None,
+            codec: "bincode".into(),
+            tls_dir: None,
+            panic_dump: None,
+            mirror_to: None,
+            debug_api: false,
+            #[cfg(feature = "byzantine-hooks")]
+            byzantine: "0,0,0".into(),
             config: "".into(),
+            fsck: false,
+            fsck_repair: false,
+            export: None,
+            import: None,
+            replay: false,
+            replay_until: None,
         };
         assert!(args.sanitize().is_err());
         Ok(())
@@ -227,9 +571,26 @@ mod arg_tests {
             bind_ip: "127.0.0.1".parse()?,
             api_port: 40100,
             p2p_port: 1023,
-            manager: "127.0.0.1:40000".parse()?,
+            ws_port: 0,
+            manager: Some("127.0.0.1:40000".parse()?),
             threads: 2,
+            colocate: 1,
+            max_msg_bytes: 0,
+            rng_seed: None,
+            codec: "bincode".into(),
+            tls_dir: None,
+            panic_dump: None,
+            mirror_to: None,
+            debug_api: false,
+            #[cfg(feature = "byzantine-hooks")]
+            byzantine: "0,0,0".into(),
             config: "".into(),
+            fsck: false,
+            fsck_repair: false,
+            export: None,
+            import: None,
+            replay: false,
+            replay_until: None,
         };
         assert!(args.sanitize().is_err());
         Ok(())
@@ -242,9 +603,90 @@ mod arg_tests {
             bind_ip: "127.0.0.1".parse()?,
             api_port: 40100,
             p2p_port: 40100,
-            manager: "127.0.0.1:40000".parse()?,
+            ws_port: 0,
+            manager: Some("127.0.0.1:40000".parse()?),
+            threads: 2,
+            colocate: 1,
+            max_msg_bytes: 0,
+            rng_seed: None,
+            codec: "bincode".into(),
+            tls_dir: None,
+            panic_dump: None,
+            mirror_to: None,
+            debug_api: false,
+            #[cfg(feature = "byzantine-hooks")]
+            byzantine: "0,0,0".into(),
+            config: "".into(),
+            fsck: false,
+            fsck_repair: false,
+            export: None,
+            import: None,
+            replay: false,
+            replay_until: None,
+        };
+        assert!(args.sanitize().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sanitize_assigned_ports() -> Result<(), SummersetError> {
+        let args = CliArgs {
+            protocol: "RepNothing".into(),
+            bind_ip: "127.0.0.1".parse()?,
+            api_port: 0,
+            p2p_port: 0,
+            ws_port: 0,
+            manager: Some("127.0.0.1:40000".parse()?),
+            threads: 2,
+            colocate: 1,
+            max_msg_bytes: 0,
+            rng_seed: None,
+            codec: "bincode".into(),
+            tls_dir: None,
+            panic_dump: None,
+            mirror_to: None,
+            debug_api: false,
+            #[cfg(feature = "byzantine-hooks")]
+            byzantine: "0,0,0".into(),
+            config: "".into(),
+            fsck: false,
+            fsck_repair: false,
+            export: None,
+            import: None,
+            replay: false,
+            replay_until: None,
+        };
+        assert_eq!(args.sanitize(), Ok(SmrProtocol::RepNothing));
+        Ok(())
+    }
+
+    #[test]
+    fn sanitize_invalid_ws_port() -> Result<(), SummersetError> {
+        let args = CliArgs {
+            protocol: "RepNothing".into(),
+            bind_ip: "127.0.0.1".parse()?,
+            api_port: 40100,
+            p2p_port: 40200,
+            ws_port: 40100,
+            manager: Some("127.0.0.1:40000".parse()?),
             threads: 2,
+            colocate: 1,
+            max_msg_bytes: 0,
+            rng_seed: None,
+            codec: "bincode".into(),
+            tls_dir: None,
+            panic_dump: None,
+            mirror_to: None,
+            debug_api: false,
+            #[cfg(feature = "byzantine-hooks")]
+            byzantine: "0,0,0".into(),
             config: "".into(),
+            fsck: false,
+            fsck_repair: false,
+            export: None,
+            import: None,
+            replay: false,
+            replay_until: None,
         };
         assert!(args.sanitize().is_err());
         Ok(())
@@ -257,11 +699,28 @@ mod arg_tests {
             bind_ip: "127.0.0.1".parse()?,
             api_port: 40100,
             p2p_port: 40200,
-            manager: "127.0.0.1:40000".parse()?,
+            ws_port: 0,
+            manager: Some("127.0.0.1:40000".parse()?),
             threads: 2,
+            colocate: 1,
+            max_msg_bytes: 0,
+            rng_seed: None,
+            codec: "bincode".into(),
+            tls_dir: None,
+            panic_dump: None,
+            mirror_to: None,
+            debug_api: false,
+            #[cfg(feature = "byzantine-hooks")]
+            byzantine: "0,0,0".into(),
             config: "".into(),
+            fsck: false,
+            fsck_repair: false,
+            export: None,
+            import: None,
+            replay: false,
+            replay_until: None,
         };
-        assert!(args.sanitize().is_err());
+        assert_eq!(args.sanitize().unwrap_err().kind(), ErrorKind::Config);
         Ok(())
     }
 
@@ -272,11 +731,188 @@ mod arg_tests {
             bind_ip: "127.0.0.1".parse()?,
             api_port: 40100,
             p2p_port: 40200,
-            manager: "127.0.0.1:40000".parse()?,
+            ws_port: 0,
+            manager: Some("127.0.0.1:40000".parse()?),
             threads: 1,
+            colocate: 1,
+            max_msg_bytes: 0,
+            rng_seed: None,
+            codec: "bincode".into(),
+            tls_dir: None,
+            panic_dump: None,
+            mirror_to: None,
+            debug_api: false,
+            #[cfg(feature = "byzantine-hooks")]
+            byzantine: "0,0,0".into(),
+            config: "".into(),
+            fsck: false,
+            fsck_repair: false,
+            export: None,
+            import: None,
+            replay: false,
+            replay_until: None,
+        };
+        assert!(args.sanitize().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sanitize_invalid_max_msg_bytes() -> Result<(), SummersetError> {
+        let args = CliArgs {
+            protocol: "RepNothing".into(),
+            bind_ip: "127.0.0.1".parse()?,
+            api_port: 40100,
+            p2p_port: 40200,
+            ws_port: 0,
+            manager: Some("127.0.0.1:40000".parse()?),
+            threads: 2,
+            colocate: 1,
+            max_msg_bytes: 1024,
+            rng_seed: None,
+            codec: "bincode".into(),
+            tls_dir: None,
+            panic_dump: None,
+            mirror_to: None,
+            debug_api: false,
+            #[cfg(feature = "byzantine-hooks")]
+            byzantine: "0,0,0".into(),
             config: "".into(),
+            fsck: false,
+            fsck_repair: false,
+            export: None,
+            import: None,
+            replay: false,
+            replay_until: None,
         };
         assert!(args.sanitize().is_err());
         Ok(())
     }
+
+    #[test]
+    fn sanitize_invalid_codec() -> Result<(), SummersetError> {
+        let args = CliArgs {
+            protocol: "RepNothing".into(),
+            bind_ip: "127.0.0.1".parse()?,
+            api_port: 40100,
+            p2p_port: 40200,
+            ws_port: 0,
+            manager: Some("127.0.0.1:40000".parse()?),
+            threads: 2,
+            colocate: 1,
+            max_msg_bytes: 0,
+            rng_seed: None,
+            codec: "protobuf".into(),
+            tls_dir: None,
+            panic_dump: None,
+            mirror_to: None,
+            debug_api: false,
+            #[cfg(feature = "byzantine-hooks")]
+            byzantine: "0,0,0".into(),
+            config: "".into(),
+            fsck: false,
+            fsck_repair: false,
+            export: None,
+            import: None,
+            replay: false,
+            replay_until: None,
+        };
+        assert!(args.sanitize().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sanitize_offline_modes() -> Result<(), SummersetError> {
+        let mut args = CliArgs {
+            protocol: "MultiPaxos".into(),
+            bind_ip: "127.0.0.1".parse()?,
+            api_port: 40100,
+            p2p_port: 40200,
+            ws_port: 0,
+            manager: None,
+            threads: 2,
+            colocate: 1,
+            max_msg_bytes: 0,
+            rng_seed: None,
+            codec: "bincode".into(),
+            tls_dir: None,
+            panic_dump: None,
+            mirror_to: None,
+            debug_api: false,
+            #[cfg(feature = "byzantine-hooks")]
+            byzantine: "0,0,0".into(),
+            config: "".into(),
+            fsck: true,
+            fsck_repair: true,
+            export: None,
+            import: None,
+            replay: false,
+            replay_until: None,
+        };
+        assert_eq!(args.sanitize(), Ok(SmrProtocol::MultiPaxos));
+        args.export = Some("/tmp/dump.json".into());
+        assert!(args.sanitize().is_err());
+        args.fsck = false;
+        assert!(args.sanitize().is_err());
+        args.fsck_repair = false;
+        assert_eq!(args.sanitize(), Ok(SmrProtocol::MultiPaxos));
+        args.export = None;
+        assert!(args.sanitize().is_err());
+        args.replay_until = Some(7);
+        assert!(args.sanitize().is_err());
+        args.replay = true;
+        assert_eq!(args.sanitize(), Ok(SmrProtocol::MultiPaxos));
+        args.fsck = true;
+        assert!(args.sanitize().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sanitize_colocate() -> Result<(), SummersetError> {
+        let mut args = CliArgs {
+            protocol: "MultiPaxos".into(),
+            bind_ip: "127.0.0.1".parse()?,
+            api_port: 40100,
+            p2p_port: 40200,
+            ws_port: 0,
+            manager: Some("127.0.0.1:40000".parse()?),
+            threads: 2,
+            colocate: 3,
+            max_msg_bytes: 0,
+            rng_seed: None,
+            codec: "bincode".into(),
+            tls_dir: None,
+            panic_dump: None,
+            mirror_to: None,
+            debug_api: false,
+            #[cfg(feature = "byzantine-hooks")]
+            byzantine: "0,0,0".into(),
+            config: "backer_path='/tmp/summerset.{i}.wal'".into(),
+            fsck: false,
+            fsck_repair: false,
+            export: None,
+            import: None,
+            replay: false,
+            replay_until: None,
+        };
+        assert_eq!(args.sanitize(), Ok(SmrProtocol::MultiPaxos));
+        args.p2p_port = 40102;
+        assert!(args.sanitize().is_err());
+        args.p2p_port = 0;
+        assert_eq!(args.sanitize(), Ok(SmrProtocol::MultiPaxos));
+        args.api_port = 65534;
+        assert!(args.sanitize().is_err());
+        args.api_port = 40100;
+        args.config = "".into();
+        assert!(args.sanitize().is_err());
+        args.config = "backer_path='/tmp/summerset.{i}.wal'".into();
+        args.panic_dump = Some("/tmp/dump.txt".into());
+        assert!(args.sanitize().is_err());
+        args.panic_dump = None;
+        args.mirror_to = Some("127.0.0.1:41000".parse()?);
+        assert!(args.sanitize().is_err());
+        args.mirror_to = None;
+        args.colocate = 0;
+        assert!(args.sanitize().is_err());
+        Ok(())
+    }
 }
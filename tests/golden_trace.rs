@@ -0,0 +1,175 @@
+//! Golden peer message trace regression test of the ChainRep protocol.
+//!
+//! Lives as an integration test so that it gets a process of its own: peer
+//! message tracing is process-global, and the unit tests of the library
+//! send peer messages of their own concurrently.
+//!
+//! Drives three colocated replicas with a fixed sequence of client requests,
+//! one outstanding at a time, and checks the recorded trace against the one
+//! checked in at `tests/golden/chain_rep.trace`. After an intended change in
+//! the protocol's messaging, run the test with `SUMMERSET_BLESS_TRACES=1`
+//! set to record the golden trace anew.
+
+use std::env;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tokio::time;
+
+use summerset::{
+    set_msg_trace_path, ApiReply, ApiRequest, Command, CtrlReply, CtrlRequest,
+    GenericEndpoint, MsgTrace, SmrProtocol, SummersetError,
+};
+
+const POPULATION: u16 = 3;
+const MANAGER_SRV_PORT: u16 = 52600;
+const MANAGER_CLI_PORT: u16 = 52601;
+const REPLICA_API_PORT: u16 = 52610;
+const REPLICA_P2P_PORT: u16 = 52620;
+
+const GOLDEN_PATH: &str = "tests/golden/chain_rep.trace";
+const TRACE_PATH: &str = "/tmp/summerset.golden.chain_rep.trace";
+
+fn local_addr(port: u16) -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], port))
+}
+
+fn backer_path(r: u16) -> String {
+    format!("/tmp/summerset.golden.chain_rep.{}.wal", r)
+}
+
+/// Connects a client endpoint once all replicas have joined.
+async fn connect_client(
+    manager: SocketAddr,
+) -> Result<Box<dyn GenericEndpoint>, SummersetError> {
+    let mut endpoint = SmrProtocol::ChainRep
+        .new_client_endpoint(manager, None)
+        .await?;
+    for _ in 0..100 {
+        endpoint
+            .ctrl_stub()
+            .send_req_insist(&CtrlRequest::QueryInfo)?;
+        if let CtrlReply::QueryInfo { servers_info, .. } =
+            endpoint.ctrl_stub().recv_reply().await?
+        {
+            if servers_info.len() == POPULATION as usize {
+                endpoint.connect().await?;
+                return Ok(endpoint);
+            }
+        }
+        time::sleep(Duration::from_millis(100)).await;
+    }
+    Err(SummersetError::msg("replicas never all joined"))
+}
+
+/// Issues a command and waits for its successful reply.
+async fn issue(
+    endpoint: &mut Box<dyn GenericEndpoint>,
+    id: u64,
+    cmd: Command,
+) -> Result<(), SummersetError> {
+    let req = ApiRequest::Req {
+        id,
+        cmd,
+        checksum: None,
+    };
+    if !endpoint.send_req(Some(&req))? {
+        while !endpoint.send_req(None)? {}
+    }
+    loop {
+        match time::timeout(Duration::from_secs(5), endpoint.recv_reply()).await
+        {
+            Ok(Ok(ApiReply::Reply {
+                id: reply_id,
+                result: Some(_),
+                ..
+            })) if reply_id == id => return Ok(()),
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => return Err(err),
+            Err(_) => {
+                return Err(SummersetError::msg(format!(
+                    "request {} timed out",
+                    id
+                )));
+            }
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn chain_rep_golden_trace() -> Result<(), SummersetError> {
+    let (tx_term, rx_term) = watch::channel(false);
+
+    let mut manager = SmrProtocol::ChainRep
+        .new_cluster_manager_setup(
+            local_addr(MANAGER_SRV_PORT),
+            local_addr(MANAGER_CLI_PORT),
+            None,
+            None,
+            None,
+            None,
+            None,
+            POPULATION,
+            Duration::from_secs(10),
+        )
+        .await?;
+    let manager_task = {
+        let rx_term = rx_term.clone();
+        tokio::spawn(async move { manager.run(rx_term).await })
+    };
+
+    let mut replicas = vec![];
+    for r in 0..POPULATION {
+        let _ = fs::remove_file(backer_path(r));
+        replicas.push((
+            local_addr(REPLICA_API_PORT + r),
+            local_addr(REPLICA_P2P_PORT + r),
+            Some(format!("backer_path='{}'", backer_path(r))),
+        ));
+    }
+    // replicas do not make a `Send` future, so they are polled in place
+    let replicas = SmrProtocol::ChainRep.run_colocated_replicas(
+        replicas,
+        local_addr(MANAGER_SRV_PORT),
+        rx_term,
+    );
+    let script = async {
+        let mut endpoint = connect_client(local_addr(MANAGER_CLI_PORT)).await?;
+        set_msg_trace_path(Some(Path::new(TRACE_PATH)))?;
+        let mut id = 0;
+        for (key, value) in [("x", "1"), ("y", "2"), ("x", "3")] {
+            let put = Command::Put {
+                key: key.into(),
+                value: value.into(),
+            };
+            issue(&mut endpoint, id, put).await?;
+            issue(&mut endpoint, id + 1, Command::Get { key: key.into() })
+                .await?;
+            id += 2;
+        }
+        set_msg_trace_path(None)
+    };
+    tokio::select! {
+        result = replicas => {
+            return Err(SummersetError::msg(format!(
+                "replicas exited early: {:?}",
+                result
+            )));
+        }
+        result = script => result?,
+    }
+
+    tx_term.send(true).map_err(SummersetError::msg)?;
+    manager_task.abort();
+
+    let trace = MsgTrace::load(Path::new(TRACE_PATH))?;
+    if env::var_os("SUMMERSET_BLESS_TRACES").is_some() {
+        fs::copy(TRACE_PATH, GOLDEN_PATH)?;
+    }
+    let golden = MsgTrace::load(Path::new(GOLDEN_PATH))?;
+    assert!(golden.num_msgs() > 0);
+    trace.check_against(&golden)
+}
@@ -0,0 +1,175 @@
+//! Hermes membership reconfiguration test.
+//!
+//! Kills one of three colocated replicas and checks that writes still
+//! complete once the killed replica's membership lease has expired and it
+//! has been dropped from the membership.
+
+use std::fs;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures::future::join_all;
+
+use tokio::sync::watch;
+use tokio::time;
+
+use summerset::{
+    ApiReply, ApiRequest, Command, CommandResult, CtrlReply, CtrlRequest,
+    GenericEndpoint, SmrProtocol, SummersetError,
+};
+
+const POPULATION: u16 = 3;
+const MANAGER_SRV_PORT: u16 = 52700;
+const MANAGER_CLI_PORT: u16 = 52701;
+const REPLICA_API_PORT: u16 = 52710;
+const REPLICA_P2P_PORT: u16 = 52720;
+
+fn local_addr(port: u16) -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], port))
+}
+
+fn backer_path(r: u16) -> String {
+    format!("/tmp/summerset.failover.hermes.{}.wal", r)
+}
+
+/// Connects a client endpoint talking to replica 0 once all replicas have
+/// joined.
+async fn connect_client(
+    manager: SocketAddr,
+) -> Result<Box<dyn GenericEndpoint>, SummersetError> {
+    let mut endpoint = SmrProtocol::Hermes
+        .new_client_endpoint(manager, Some("near_server_id=0"))
+        .await?;
+    for _ in 0..100 {
+        endpoint
+            .ctrl_stub()
+            .send_req_insist(&CtrlRequest::QueryInfo)?;
+        if let CtrlReply::QueryInfo { servers_info, .. } =
+            endpoint.ctrl_stub().recv_reply().await?
+        {
+            if servers_info.len() == POPULATION as usize {
+                endpoint.connect().await?;
+                return Ok(endpoint);
+            }
+        }
+        time::sleep(Duration::from_millis(100)).await;
+    }
+    Err(SummersetError::msg("replicas never all joined"))
+}
+
+/// Issues a command and returns the result of its successful reply.
+async fn issue(
+    endpoint: &mut Box<dyn GenericEndpoint>,
+    id: u64,
+    cmd: Command,
+) -> Result<CommandResult, SummersetError> {
+    let req = ApiRequest::Req {
+        id,
+        cmd,
+        checksum: None,
+    };
+    if !endpoint.send_req(Some(&req))? {
+        while !endpoint.send_req(None)? {}
+    }
+    loop {
+        match time::timeout(Duration::from_secs(10), endpoint.recv_reply())
+            .await
+        {
+            Ok(Ok(ApiReply::Reply {
+                id: reply_id,
+                result: Some(result),
+                ..
+            })) if reply_id == id => return Ok(result),
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => return Err(err),
+            Err(_) => {
+                return Err(SummersetError::msg(format!(
+                    "request {} timed out",
+                    id
+                )));
+            }
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn writes_survive_replica_failure() -> Result<(), SummersetError> {
+    let (tx_term, rx_term) = watch::channel(false);
+
+    let mut manager = SmrProtocol::Hermes
+        .new_cluster_manager_setup(
+            local_addr(MANAGER_SRV_PORT),
+            local_addr(MANAGER_CLI_PORT),
+            None,
+            None,
+            None,
+            None,
+            None,
+            POPULATION,
+            Duration::from_secs(10),
+        )
+        .await?;
+    let manager_task = tokio::spawn(async move { manager.run(rx_term).await });
+
+    // every replica gets a termination signal of its own, so that one can be
+    // killed alone
+    let mut tx_kills = vec![];
+    let mut replicas = vec![];
+    for r in 0..POPULATION {
+        let _ = fs::remove_file(backer_path(r));
+        let (tx_kill, rx_kill) = watch::channel(false);
+        tx_kills.push(tx_kill);
+        let config = format!(
+            "backer_path='{}'\nhb_hear_timeout_min=300\n\
+             hb_hear_timeout_max=400\nlease_expire_ms=500",
+            backer_path(r)
+        );
+        replicas.push(async move {
+            let mut replica = SmrProtocol::Hermes
+                .new_server_replica_setup(
+                    local_addr(REPLICA_API_PORT + r),
+                    local_addr(REPLICA_P2P_PORT + r),
+                    local_addr(MANAGER_SRV_PORT),
+                    Some(&config),
+                )
+                .await?;
+            replica.run(rx_kill).await
+        });
+    }
+    // replicas do not make `Send` futures, so they are polled in place
+    let replicas = join_all(replicas);
+
+    let script = async {
+        let mut endpoint = connect_client(local_addr(MANAGER_CLI_PORT)).await?;
+        let put = |value: &str| Command::Put {
+            key: "x".into(),
+            value: value.into(),
+        };
+        issue(&mut endpoint, 0, put("1")).await?;
+
+        tx_kills[2].send(true).map_err(SummersetError::msg)?;
+        issue(&mut endpoint, 1, put("2")).await?;
+        issue(&mut endpoint, 2, put("3")).await?;
+        issue(&mut endpoint, 3, Command::Get { key: "x".into() }).await
+    };
+    let result = tokio::select! {
+        results = replicas => {
+            return Err(SummersetError::msg(format!(
+                "replicas exited early: {:?}",
+                results
+            )));
+        }
+        result = script => result?,
+    };
+
+    tx_term.send(true).map_err(SummersetError::msg)?;
+    manager_task.abort();
+
+    assert_eq!(
+        result,
+        CommandResult::Get {
+            value: Some("3".into())
+        }
+    );
+    Ok(())
+}